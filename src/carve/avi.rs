@@ -3,10 +3,9 @@
 //! AVI files use the RIFF container format with "AVI " form type.
 //! The file size is embedded in the RIFF header (bytes 4-7).
 
-use std::fs::File;
-
 use crate::carve::{
-    CarveError, CarveHandler, CarveStream, CarvedFile, ExtractionContext, output_path, riff,
+    CarveError, CarveHandler, CarveStream, CarvedFile, ExtractionContext, Rejection, create_output,
+    note_rejection, output_path, riff,
 };
 use crate::scanner::NormalizedHit;
 
@@ -46,7 +45,7 @@ impl CarveHandler for AviCarveHandler {
             &self.extension,
             hit.global_offset,
         )?;
-        let file = File::create(&full_path)?;
+        let file = create_output(&full_path)?;
         let mut stream = CarveStream::new(ctx.evidence, hit.global_offset, self.max_size, file);
 
         let mut validated = false;
@@ -109,6 +108,10 @@ impl CarveHandler for AviCarveHandler {
 
         // Check minimum size
         if size < self.min_size {
+            note_rejection(Rejection::MinSize {
+                size,
+                min_size: self.min_size,
+            });
             let _ = std::fs::remove_file(&full_path);
             return Ok(None);
         }
@@ -130,6 +133,7 @@ impl CarveHandler for AviCarveHandler {
         Ok(Some(CarvedFile {
             run_id: ctx.run_id.to_string(),
             file_type: self.file_type().to_string(),
+            signature_type: self.file_type().to_string(),
             path: rel_path,
             extension: self.extension.clone(),
             global_start: hit.global_offset,
@@ -149,6 +153,7 @@ impl CarveHandler for AviCarveHandler {
 mod tests {
     use super::*;
     use crate::evidence::{EvidenceError, EvidenceSource};
+    use std::fs::File;
     use std::io::Read;
     use tempfile::tempdir;
 
@@ -1,17 +1,28 @@
-use std::fs::File;
+//! Windows bitmaps (BMP).
+//!
+//! The two-byte `BM` magic matches everywhere in memory and pagefiles, so a
+//! hit has to carry a plausible bitmap header: a known DIB header size, one
+//! colour plane, a bit depth that fits the compression, a palette that fits
+//! before the pixel data, and a file size field that agrees with the pixel
+//! data the dimensions call for (or the image size field of compressed
+//! bitmaps, and the colour profile of V5 headers). A size field far past the
+//! image data is not trusted: the carve ends after the image and is kept
+//! unvalidated.
+
 use std::io::Write;
 
 use sha2::{Digest, Sha256};
 
 use crate::carve::{
-    CarveError, CarveHandler, CarvedFile, ExtractionContext, output_path, write_range,
+    CarveError, CarveHandler, CarvedFile, DeclaredSize, ExtractionContext, Rejection,
+    create_output, note_declared_size, note_rejection, output_path, write_range,
 };
 use crate::scanner::NormalizedHit;
 
 /// BMP file header is 14 bytes
-const BMP_HEADER_LEN: usize = 14;
-/// Minimum header to read: BMP header (14) + DIB header size field (4)
-const BMP_MIN_HEADER: usize = 18;
+const BMP_HEADER_LEN: u64 = 14;
+/// Enough for the file header and a BITMAPV5HEADER.
+const HEADER_READ_LEN: usize = 14 + 124;
 const BMP_MAGIC: [u8; 2] = [0x42, 0x4D];
 
 /// Valid DIB header sizes (most common formats)
@@ -22,9 +33,149 @@ const BMP_MAGIC: [u8; 2] = [0x42, 0x4D];
 /// - BITMAPV4HEADER: 108
 /// - BITMAPV5HEADER: 124
 const VALID_DIB_SIZES: [u32; 6] = [12, 40, 52, 56, 108, 124];
+const CORE_HEADER_LEN: u32 = 12;
+const V5_HEADER_LEN: u32 = 124;
 
 /// Maximum reasonable image dimension (32768 pixels)
 const MAX_DIMENSION: u32 = 32768;
+/// How far the size field may fall short of the pixel data; some writers
+/// omit the padding of the last row.
+const SHORT_SIZE_TOLERANCE: u64 = 1024;
+/// How far the size field may run past the image data before it is not
+/// trusted.
+const TRAILING_SLACK: u64 = 4096;
+
+/// Compression methods (`biCompression`).
+const BI_RGB: u32 = 0;
+const BI_RLE8: u32 = 1;
+const BI_RLE4: u32 = 2;
+const BI_BITFIELDS: u32 = 3;
+const BI_JPEG: u32 = 4;
+const BI_PNG: u32 = 5;
+const BI_ALPHABITFIELDS: u32 = 6;
+
+/// The fields of a BMP header that size the file.
+#[derive(Debug)]
+struct BmpHeader {
+    file_size: u64,
+    /// End of the pixel data, and of the colour profile when it follows.
+    image_end: u64,
+}
+
+impl BmpHeader {
+    fn parse(header: &[u8]) -> Result<Self, &'static str> {
+        if header.len() < 18 || header[0..2] != BMP_MAGIC {
+            return Err("BMP header too short");
+        }
+        let file_size = u64::from(le_u32(header, 2));
+        let pixel_offset = u64::from(le_u32(header, 10));
+        if file_size < BMP_HEADER_LEN || pixel_offset < BMP_HEADER_LEN || pixel_offset > file_size {
+            return Err("BMP pixel offset outside the file");
+        }
+
+        // Validate DIB header size - this is critical for reducing false positives
+        let dib_size = le_u32(header, 14);
+        if !VALID_DIB_SIZES.contains(&dib_size) {
+            return Err("unknown BMP DIB header size");
+        }
+        if header.len() < (BMP_HEADER_LEN as usize + dib_size as usize).min(54) {
+            return Err("BMP header too short");
+        }
+        let dib_end = BMP_HEADER_LEN + u64::from(dib_size);
+        if pixel_offset < dib_end {
+            return Err("BMP pixel data inside the DIB header");
+        }
+
+        let (width, height, planes, bpp, compression, size_image, colors_used, palette_entry) =
+            if dib_size == CORE_HEADER_LEN {
+                (
+                    i64::from(le_u16(header, 18)),
+                    i64::from(le_u16(header, 20)),
+                    le_u16(header, 22),
+                    le_u16(header, 24),
+                    BI_RGB,
+                    0,
+                    0,
+                    3,
+                )
+            } else {
+                (
+                    i64::from(le_i32(header, 18)),
+                    i64::from(le_i32(header, 22)),
+                    le_u16(header, 26),
+                    le_u16(header, 28),
+                    le_u32(header, 30),
+                    u64::from(le_u32(header, 34)),
+                    le_u32(header, 46),
+                    4,
+                )
+            };
+
+        // Width must be positive, height can be negative (top-down DIB)
+        let abs_height = height.unsigned_abs();
+        if width <= 0 || width as u64 > u64::from(MAX_DIMENSION) {
+            return Err("BMP width out of range");
+        }
+        if height == 0 || abs_height > u64::from(MAX_DIMENSION) {
+            return Err("BMP height out of range");
+        }
+        if planes != 1 {
+            return Err("BMP plane count is not 1");
+        }
+        let depth_fits = match compression {
+            BI_RGB => matches!(bpp, 1 | 4 | 8 | 16 | 24 | 32),
+            BI_RLE8 => bpp == 8,
+            BI_RLE4 => bpp == 4,
+            BI_BITFIELDS | BI_ALPHABITFIELDS => matches!(bpp, 16 | 32),
+            BI_JPEG | BI_PNG => bpp == 0,
+            _ => return Err("unknown BMP compression"),
+        };
+        if !depth_fits {
+            return Err("BMP bit depth does not fit the compression");
+        }
+
+        if bpp <= 8 && bpp > 0 {
+            let max_colors = 1u32 << bpp;
+            if colors_used > max_colors {
+                return Err("BMP palette larger than the bit depth allows");
+            }
+            let colors = if colors_used == 0 {
+                max_colors
+            } else {
+                colors_used
+            };
+            if pixel_offset < dib_end + u64::from(colors) * palette_entry {
+                return Err("BMP palette overlaps the pixel data");
+            }
+        }
+
+        let data_size = match compression {
+            BI_RGB | BI_BITFIELDS | BI_ALPHABITFIELDS => {
+                // Rows are padded to 4 bytes
+                let row_size = (width as u64 * u64::from(bpp)).div_ceil(32) * 4;
+                row_size * abs_height
+            }
+            _ if size_image == 0 => return Err("compressed BMP without image size"),
+            _ => size_image,
+        };
+        let mut image_end = pixel_offset + data_size;
+        if dib_size == V5_HEADER_LEN && header.len() >= HEADER_READ_LEN {
+            // The profile offset counts from the start of the DIB header.
+            let profile_offset = u64::from(le_u32(header, 14 + 112));
+            let profile_size = u64::from(le_u32(header, 14 + 116));
+            if profile_size > 0 {
+                image_end = image_end.max(BMP_HEADER_LEN + profile_offset + profile_size);
+            }
+        }
+        if file_size + SHORT_SIZE_TOLERANCE < image_end {
+            return Err("BMP size field smaller than its pixel data");
+        }
+        Ok(Self {
+            file_size,
+            image_end,
+        })
+    }
+}
 
 pub struct BmpCarveHandler {
     extension: String,
@@ -56,75 +207,37 @@ impl CarveHandler for BmpCarveHandler {
         hit: &NormalizedHit,
         ctx: &ExtractionContext,
     ) -> Result<Option<CarvedFile>, CarveError> {
-        // Read BMP header + start of DIB header for validation
-        let mut header = [0u8; 58]; // Enough for BMP header + BITMAPINFOHEADER
+        let mut raw = [0u8; HEADER_READ_LEN];
         let n = ctx
             .evidence
-            .read_at(hit.global_offset, &mut header)
+            .read_at(hit.global_offset, &mut raw)
             .map_err(|e| CarveError::Evidence(e.to_string()))?;
-        if n < BMP_MIN_HEADER {
-            return Ok(None);
-        }
-        if header[0..2] != BMP_MAGIC {
-            return Ok(None);
-        }
-
-        let file_size = u32::from_le_bytes([header[2], header[3], header[4], header[5]]) as u64;
-        let pixel_offset =
-            u32::from_le_bytes([header[10], header[11], header[12], header[13]]) as u64;
-
-        // Basic BMP header validation
-        if file_size < BMP_HEADER_LEN as u64
-            || pixel_offset < BMP_HEADER_LEN as u64
-            || pixel_offset > file_size
-        {
-            return Ok(None);
-        }
-
-        // Validate DIB header size - this is critical for reducing false positives
-        let dib_header_size = u32::from_le_bytes([header[14], header[15], header[16], header[17]]);
-        if !VALID_DIB_SIZES.contains(&dib_header_size) {
-            return Ok(None);
-        }
-
-        // pixel_offset must be at least BMP header + DIB header
-        if pixel_offset < (BMP_HEADER_LEN as u64 + dib_header_size as u64) {
-            return Ok(None);
-        }
-
-        // For BITMAPINFOHEADER (40 bytes) and larger, validate dimensions
-        if dib_header_size >= 40 && n >= 26 {
-            let width = i32::from_le_bytes([header[18], header[19], header[20], header[21]]);
-            let height = i32::from_le_bytes([header[22], header[23], header[24], header[25]]);
-
-            // Width must be positive, height can be negative (top-down DIB)
-            let abs_width = width.unsigned_abs();
-            let abs_height = height.unsigned_abs();
-
-            // Reject unreasonable dimensions
-            if width <= 0 || abs_width > MAX_DIMENSION || abs_height > MAX_DIMENSION {
+        let header = match BmpHeader::parse(&raw[..n]) {
+            Ok(header) => header,
+            Err(reason) => {
+                note_rejection(Rejection::Invalid(reason));
                 return Ok(None);
             }
+        };
+        note_declared_size(DeclaredSize {
+            size: header.file_size,
+            source: "header",
+        });
 
-            // If we have enough data, validate bits per pixel
-            if n >= 30 {
-                let bits_per_pixel = u16::from_le_bytes([header[28], header[29]]);
-                // Valid bits per pixel: 1, 4, 8, 16, 24, 32
-                if !matches!(bits_per_pixel, 1 | 4 | 8 | 16 | 24 | 32) {
-                    return Ok(None);
-                }
-
-                // Sanity check: file size should be reasonable for dimensions
-                // Row size is padded to 4 bytes
-                let row_size = ((abs_width * bits_per_pixel as u32 + 31) / 32) * 4;
-                let pixel_data_size = row_size as u64 * abs_height as u64;
-                let min_expected_size = pixel_offset + pixel_data_size;
-
-                // Allow some tolerance for palette and other data, but reject wildly wrong sizes
-                if file_size < min_expected_size.saturating_sub(1024) {
-                    return Ok(None);
-                }
-            }
+        let mut errors = Vec::new();
+        let mut truncated = false;
+        let mut size = header.file_size;
+        if size > header.image_end + TRAILING_SLACK {
+            size = header.image_end;
+            errors.push(format!(
+                "size field {} exceeds image data; carved to {size}",
+                header.file_size
+            ));
+        }
+        if self.max_size > 0 && size > self.max_size {
+            size = self.max_size;
+            truncated = true;
+            errors.push("max_size reached before BMP end".to_string());
         }
 
         let (full_path, rel_path) = output_path(
@@ -133,24 +246,13 @@ impl CarveHandler for BmpCarveHandler {
             &self.extension,
             hit.global_offset,
         )?;
-        let mut file = File::create(&full_path)?;
+        let mut file = create_output(&full_path)?;
         let mut md5 = md5::Context::new();
         let mut sha256 = Sha256::new();
-
-        let mut total_end = hit.global_offset + file_size;
-        let mut truncated = false;
-        let mut errors = Vec::new();
-
-        if self.max_size > 0 && file_size > self.max_size {
-            total_end = hit.global_offset + self.max_size;
-            truncated = true;
-            errors.push("max_size reached before BMP end".to_string());
-        }
-
         let (written, eof_truncated) = write_range(
             ctx,
             hit.global_offset,
-            total_end,
+            hit.global_offset + size,
             &mut file,
             &mut md5,
             &mut sha256,
@@ -162,6 +264,10 @@ impl CarveHandler for BmpCarveHandler {
         file.flush()?;
 
         if written < self.min_size {
+            note_rejection(Rejection::MinSize {
+                size: written,
+                min_size: self.min_size,
+            });
             let _ = std::fs::remove_file(&full_path);
             return Ok(None);
         }
@@ -177,6 +283,7 @@ impl CarveHandler for BmpCarveHandler {
         Ok(Some(CarvedFile {
             run_id: ctx.run_id.to_string(),
             file_type: self.file_type().to_string(),
+            signature_type: self.file_type().to_string(),
             path: rel_path,
             extension: self.extension.clone(),
             global_start: hit.global_offset,
@@ -184,7 +291,7 @@ impl CarveHandler for BmpCarveHandler {
             size: written,
             md5: Some(md5_hex),
             sha256: Some(sha256_hex),
-            validated: !truncated,
+            validated: errors.is_empty(),
             truncated,
             errors,
             pattern_id: Some(hit.pattern_id.clone()),
@@ -192,6 +299,18 @@ impl CarveHandler for BmpCarveHandler {
     }
 }
 
+fn le_u16(data: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([data[at], data[at + 1]])
+}
+
+fn le_u32(data: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(data[at..at + 4].try_into().unwrap_or_default())
+}
+
+fn le_i32(data: &[u8], at: usize) -> i32 {
+    le_u32(data, at) as i32
+}
+
 #[cfg(test)]
 mod tests {
     use super::BmpCarveHandler;
@@ -334,4 +453,66 @@ mod tests {
         let result = handler.process_hit(&hit, &ctx).expect("carve");
         assert!(result.is_none(), "Should reject invalid bits per pixel");
     }
+
+    fn carve(bmp: &[u8]) -> Option<crate::carve::CarvedFile> {
+        let temp_dir = tempfile::tempdir().expect("tempdir");
+        let input_path = temp_dir.path().join("image.bin");
+        std::fs::write(&input_path, bmp).expect("write bmp");
+        let evidence = RawFileSource::open(&input_path).expect("evidence");
+        let ctx = ExtractionContext {
+            run_id: "test",
+            output_root: temp_dir.path(),
+            evidence: &evidence,
+        };
+        let handler = BmpCarveHandler::new("bmp".to_string(), 10, 0);
+        let hit = NormalizedHit {
+            global_offset: 0,
+            file_type_id: "bmp".to_string(),
+            pattern_id: "bmp_header".to_string(),
+        };
+        handler.process_hit(&hit, &ctx).expect("carve")
+    }
+
+    #[test]
+    fn carves_to_image_end_when_size_field_is_implausible() {
+        let mut bmp = create_valid_bmp();
+        let real = bmp.len() as u64;
+        bmp[2..6].copy_from_slice(&0x0100_0000u32.to_le_bytes());
+        bmp.extend_from_slice(&[0x55; 8192]);
+        let carved = carve(&bmp).expect("carved");
+        assert_eq!(carved.size, real);
+        assert!(!carved.validated);
+        assert!(!carved.truncated);
+
+        // Slack after the pixel data within the tolerance is kept.
+        let mut bmp = create_valid_bmp();
+        bmp[2..6].copy_from_slice(&(real as u32 + 2).to_le_bytes());
+        bmp.extend_from_slice(&[0, 0, 0x55, 0x55]);
+        let carved = carve(&bmp).expect("carved");
+        assert_eq!(carved.size, real + 2);
+        assert!(carved.validated);
+    }
+
+    #[test]
+    fn rejects_implausible_headers() {
+        let mut planes = create_valid_bmp();
+        planes[26] = 3;
+        assert!(carve(&planes).is_none());
+
+        // RLE8 needs 8 bits per pixel.
+        let mut rle = create_valid_bmp();
+        rle[30] = 1;
+        assert!(carve(&rle).is_none());
+
+        // A 100x100 24-bit image cannot fit in a 58-byte file.
+        let mut small = create_valid_bmp();
+        small[18..22].copy_from_slice(&100i32.to_le_bytes());
+        small[22..26].copy_from_slice(&100i32.to_le_bytes());
+        assert!(carve(&small).is_none());
+
+        // An 8-bit image with a 256-entry palette needs it before the pixels.
+        let mut palette = create_valid_bmp();
+        palette[28] = 8;
+        assert!(carve(&palette).is_none());
+    }
 }
@@ -0,0 +1,173 @@
+use std::io::Write;
+
+use sha2::{Digest, Sha256};
+
+use crate::carve::{
+    CarveError, CarveHandler, CarvedFile, ExtractionContext, Rejection, create_output,
+    note_rejection, output_path, write_range,
+};
+use crate::parsers::boltdb::{BoltMeta, META_MAGIC_OFFSET, META_PAGE_LEN};
+use crate::scanner::NormalizedHit;
+
+/// bbolt databases (containerd `meta.db`, Docker stores). The hit is the meta
+/// magic inside page 0; the extent is the meta high-water mark times the page
+/// size.
+pub struct BoltDbCarveHandler {
+    extension: String,
+    min_size: u64,
+    max_size: u64,
+}
+
+impl BoltDbCarveHandler {
+    pub fn new(extension: String, min_size: u64, max_size: u64) -> Self {
+        Self {
+            extension,
+            min_size,
+            max_size,
+        }
+    }
+}
+
+impl CarveHandler for BoltDbCarveHandler {
+    fn file_type(&self) -> &str {
+        "boltdb"
+    }
+
+    fn extension(&self) -> &str {
+        &self.extension
+    }
+
+    fn process_hit(
+        &self,
+        hit: &NormalizedHit,
+        ctx: &ExtractionContext,
+    ) -> Result<Option<CarvedFile>, CarveError> {
+        let Some(start_offset) = hit.global_offset.checked_sub(META_MAGIC_OFFSET as u64) else {
+            return Ok(None);
+        };
+        let mut page = [0u8; META_PAGE_LEN];
+        let n = ctx
+            .evidence
+            .read_at(start_offset, &mut page)
+            .map_err(|e| CarveError::Evidence(e.to_string()))?;
+        // Page 1 carries the same magic; only page 0 starts a file.
+        let Some(meta) = BoltMeta::parse(&page[..n]).filter(|meta| meta.page_id == 0) else {
+            note_rejection(Rejection::Invalid("not a valid page 0 meta record"));
+            return Ok(None);
+        };
+        // Two meta pages, a freelist, and a root leaf at minimum.
+        if meta.page_count < 4 {
+            note_rejection(Rejection::Invalid("fewer than 4 pages"));
+            return Ok(None);
+        }
+
+        let total_size = meta.file_size();
+        let target_size = if self.max_size > 0 {
+            total_size.min(self.max_size)
+        } else {
+            total_size
+        };
+        let (full_path, rel_path) = output_path(
+            ctx.output_root,
+            self.file_type(),
+            &self.extension,
+            start_offset,
+        )?;
+        let mut file = create_output(&full_path)?;
+        let mut md5 = md5::Context::new();
+        let mut sha256 = Sha256::new();
+        let (size, short_read) = write_range(
+            ctx,
+            start_offset,
+            start_offset + target_size,
+            &mut file,
+            &mut md5,
+            &mut sha256,
+        )?;
+        file.flush()?;
+        if size < self.min_size {
+            note_rejection(Rejection::MinSize {
+                size,
+                min_size: self.min_size,
+            });
+            let _ = std::fs::remove_file(&full_path);
+            return Ok(None);
+        }
+
+        let mut errors = Vec::new();
+        if short_read {
+            errors.push("evidence ended before database end".to_string());
+        }
+        if target_size < total_size {
+            errors.push("max_size reached".to_string());
+        }
+        let truncated = !errors.is_empty();
+
+        Ok(Some(CarvedFile {
+            run_id: ctx.run_id.to_string(),
+            file_type: self.file_type().to_string(),
+            signature_type: self.file_type().to_string(),
+            path: rel_path,
+            extension: self.extension.clone(),
+            global_start: start_offset,
+            global_end: start_offset + size - 1,
+            size,
+            md5: Some(format!("{:x}", md5.compute())),
+            sha256: Some(hex::encode(sha256.finalize())),
+            validated: !truncated,
+            truncated,
+            errors,
+            pattern_id: Some(hit.pattern_id.clone()),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evidence::RawFileSource;
+    use crate::parsers::boltdb::{BoltValue, bolt_bytes};
+
+    #[test]
+    fn carves_from_page_zero_only() {
+        let db = bolt_bytes(vec![("k", BoltValue::Value(b"v"))]);
+        let mut image = vec![0u8; 4096];
+        image.extend_from_slice(&db);
+        image.extend_from_slice(&[0xAAu8; 4096]);
+
+        let temp_dir = tempfile::tempdir().expect("tempdir");
+        let input_path = temp_dir.path().join("image.bin");
+        std::fs::write(&input_path, &image).expect("write image");
+        let output_root = temp_dir.path().join("out");
+        let evidence = RawFileSource::open(&input_path).expect("evidence");
+        let ctx = ExtractionContext {
+            run_id: "test",
+            output_root: &output_root,
+            evidence: &evidence,
+        };
+        let handler = BoltDbCarveHandler::new("db".to_string(), 0, 0);
+        let hit = NormalizedHit {
+            global_offset: 4096 + 16,
+            file_type_id: "boltdb".to_string(),
+            pattern_id: "boltdb_meta".to_string(),
+        };
+        let carved = handler
+            .process_hit(&hit, &ctx)
+            .expect("carve")
+            .expect("carved");
+        assert_eq!(carved.global_start, 4096);
+        assert_eq!(carved.size, db.len() as u64);
+        assert!(carved.validated);
+
+        let second_meta = NormalizedHit {
+            global_offset: 2 * 4096 + 16,
+            ..hit
+        };
+        assert!(
+            handler
+                .process_hit(&second_meta, &ctx)
+                .expect("carve")
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,165 @@
+//! Binary property list (`bplist00`) carving handler.
+//!
+//! The plist is sized by its trailer, found by searching forward from the
+//! header up to `max_size` (`parsers::bplist`). A plist whose trailer is not
+//! found is rejected, as nothing else gives its length. The `plist_contents`
+//! post-processor decodes the carved plist into JSON.
+
+use sha2::{Digest, Sha256};
+
+use crate::carve::{
+    CarveError, CarveHandler, CarvedFile, DeclaredSize, ExtractionContext, Rejection,
+    create_output, note_declared_size, note_rejection, output_path, read_evidence, write_range,
+};
+use crate::parsers::bplist::{self, BplistError};
+use crate::scanner::NormalizedHit;
+
+pub struct BplistCarveHandler {
+    extension: String,
+    min_size: u64,
+    max_size: u64,
+}
+
+impl BplistCarveHandler {
+    pub fn new(extension: String, min_size: u64, max_size: u64) -> Self {
+        Self {
+            extension,
+            min_size,
+            max_size,
+        }
+    }
+}
+
+impl CarveHandler for BplistCarveHandler {
+    fn file_type(&self) -> &str {
+        "bplist"
+    }
+
+    fn extension(&self) -> &str {
+        &self.extension
+    }
+
+    fn process_hit(
+        &self,
+        hit: &NormalizedHit,
+        ctx: &ExtractionContext,
+    ) -> Result<Option<CarvedFile>, CarveError> {
+        let mut read = |offset: u64, buf: &mut [u8]| {
+            read_evidence(ctx, hit.global_offset.saturating_add(offset), buf)
+        };
+        let limit = if self.max_size > 0 {
+            self.max_size
+        } else {
+            u64::MAX
+        };
+        let trailer = match bplist::find_end(&mut read, limit) {
+            Ok(trailer) => trailer,
+            Err(BplistError::Invalid(reason)) => {
+                note_rejection(Rejection::Invalid(reason));
+                return Ok(None);
+            }
+            Err(BplistError::Incomplete) => {
+                note_rejection(Rejection::Invalid(
+                    "evidence ended before the plist trailer",
+                ));
+                return Ok(None);
+            }
+        };
+
+        let size = trailer.file_len();
+        note_declared_size(DeclaredSize {
+            size,
+            source: "trailer",
+        });
+        let mut errors = Vec::new();
+        let (full_path, rel_path) = output_path(
+            ctx.output_root,
+            self.file_type(),
+            &self.extension,
+            hit.global_offset,
+        )?;
+        let mut file = create_output(&full_path)?;
+        let mut md5 = md5::Context::new();
+        let mut sha256 = Sha256::new();
+        let (written, eof_truncated) = write_range(
+            ctx,
+            hit.global_offset,
+            hit.global_offset + size,
+            &mut file,
+            &mut md5,
+            &mut sha256,
+        )?;
+        if eof_truncated {
+            errors.push("eof before plist end".to_string());
+        }
+
+        if written < self.min_size {
+            note_rejection(Rejection::MinSize {
+                size: written,
+                min_size: self.min_size,
+            });
+            let _ = std::fs::remove_file(&full_path);
+            return Ok(None);
+        }
+
+        Ok(Some(CarvedFile {
+            run_id: ctx.run_id.to_string(),
+            file_type: self.file_type().to_string(),
+            signature_type: self.file_type().to_string(),
+            path: rel_path,
+            extension: self.extension.clone(),
+            global_start: hit.global_offset,
+            global_end: hit.global_offset + written - 1,
+            size: written,
+            md5: Some(format!("{:x}", md5.compute())),
+            sha256: Some(hex::encode(sha256.finalize())),
+            validated: errors.is_empty(),
+            truncated: eof_truncated,
+            errors,
+            pattern_id: Some(hit.pattern_id.clone()),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::carve::support::carve_image;
+    use crate::parsers::bplist::testing::bplist_bytes;
+
+    #[test]
+    fn carves_plist_to_its_trailer() {
+        let plist = bplist_bytes();
+        let mut image = vec![0u8; 0x100];
+        image.extend_from_slice(&plist);
+        image.extend_from_slice(&[0u8; 0x400]);
+        let (carved, dir) = carve_image(
+            &BplistCarveHandler::new("plist".to_string(), 0, 1 << 20),
+            &image,
+            0x100,
+        );
+        let carved = carved.expect("carved");
+        assert_eq!(carved.size, plist.len() as u64);
+        assert!(carved.validated, "{:?}", carved.errors);
+        let written = std::fs::read(dir.path().join("out").join(&carved.path)).expect("read");
+        assert_eq!(written, plist);
+    }
+
+    #[test]
+    fn rejects_plist_without_trailer() {
+        let plist = bplist_bytes();
+        let (carved, _dir) = carve_image(
+            &BplistCarveHandler::new("plist".to_string(), 0, 1 << 20),
+            &plist[..plist.len() - 1],
+            0,
+        );
+        assert!(carved.is_none());
+
+        let (carved, _dir) = carve_image(
+            &BplistCarveHandler::new("plist".to_string(), 0, plist.len() as u64 - 1),
+            &plist,
+            0,
+        );
+        assert!(carved.is_none());
+    }
+}
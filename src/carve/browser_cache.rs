@@ -0,0 +1,348 @@
+use std::io::Write;
+
+use sha2::{Digest, Sha256};
+
+use crate::carve::{
+    CarveError, CarveHandler, CarvedFile, ExtractionContext, create_output, output_path,
+    write_range,
+};
+use crate::parsers::browser_cache::{
+    self, CHROME_SIMPLE_EOF_LEN, CHROME_SIMPLE_FINAL_MAGIC, FIREFOX_MAX_KEY_PREFIX,
+};
+use crate::scanner::NormalizedHit;
+
+/// Bytes read per step while searching for Chromium EOF records.
+const CHROME_READ_STEP: usize = 64 * 1024;
+/// Longest Chromium entry searched for its EOF records, which are buffered
+/// while searching; `max_size` lowers it.
+const MAX_CHROME_ENTRY_LEN: u64 = 64 * 1024 * 1024;
+/// Metadata after a Firefox key anchor (elements + trailing offset).
+const FIREFOX_METADATA_WINDOW: usize = 64 * 1024;
+/// Header fields before the Firefox key.
+const FIREFOX_HEADER_SLACK: usize = 32;
+
+/// Chromium Simple Cache entry files (`<hash>_0`), carved from the initial
+/// magic through the stream 0 EOF record.
+pub struct ChromeCacheCarveHandler {
+    extension: String,
+    min_size: u64,
+    max_size: u64,
+}
+
+impl ChromeCacheCarveHandler {
+    pub fn new(extension: String, min_size: u64, max_size: u64) -> Self {
+        Self {
+            extension,
+            min_size,
+            max_size,
+        }
+    }
+
+    /// Read forward until the second EOF record, returning the entry length.
+    fn find_entry_len(
+        &self,
+        hit: &NormalizedHit,
+        ctx: &ExtractionContext,
+    ) -> Result<Option<u64>, CarveError> {
+        let mut data = Vec::new();
+        let mut search_from = None;
+        let mut eof_ends = Vec::with_capacity(2);
+        let limit = if self.max_size > 0 {
+            self.max_size.min(MAX_CHROME_ENTRY_LEN)
+        } else {
+            MAX_CHROME_ENTRY_LEN
+        };
+        loop {
+            if data.len() as u64 >= limit {
+                return Ok(None);
+            }
+            let mut buf = vec![0u8; CHROME_READ_STEP];
+            let n = ctx
+                .evidence
+                .read_at(hit.global_offset + data.len() as u64, &mut buf)
+                .map_err(|e| CarveError::Evidence(e.to_string()))?;
+            if n == 0 {
+                return Ok(None);
+            }
+            data.extend_from_slice(&buf[..n]);
+            let mut from = match search_from {
+                Some(from) => from,
+                None => match browser_cache::chrome_key_end(&data) {
+                    Some(key_end) => key_end,
+                    None => return Ok(None),
+                },
+            };
+            while eof_ends.len() < 2 {
+                match data
+                    .get(from..)
+                    .and_then(|rest| browser_cache::find(rest, &CHROME_SIMPLE_FINAL_MAGIC))
+                {
+                    Some(pos) => {
+                        from += pos + CHROME_SIMPLE_EOF_LEN;
+                        eof_ends.push(from);
+                    }
+                    None => {
+                        // Keep a magic split across reads findable.
+                        let tail = data.len().saturating_sub(CHROME_SIMPLE_FINAL_MAGIC.len());
+                        from = from.max(tail);
+                        break;
+                    }
+                }
+            }
+            search_from = Some(from);
+            if let Some(&end) = eof_ends.get(1)
+                && end <= data.len()
+            {
+                return Ok(browser_cache::parse_chrome_simple_entry(&data[..end])
+                    .map(|entry| entry.len as u64));
+            }
+            if n < CHROME_READ_STEP {
+                return Ok(None);
+            }
+        }
+    }
+}
+
+impl CarveHandler for ChromeCacheCarveHandler {
+    fn file_type(&self) -> &str {
+        "chrome_cache"
+    }
+
+    fn extension(&self) -> &str {
+        &self.extension
+    }
+
+    fn process_hit(
+        &self,
+        hit: &NormalizedHit,
+        ctx: &ExtractionContext,
+    ) -> Result<Option<CarvedFile>, CarveError> {
+        let Some(len) = self.find_entry_len(hit, ctx)? else {
+            return Ok(None);
+        };
+        write_entry(
+            self.file_type(),
+            &self.extension,
+            self.min_size,
+            hit,
+            hit.global_offset,
+            hit.global_offset + len,
+            ctx,
+        )
+    }
+}
+
+/// Firefox cache2 entries. The signature is the `:http` inside the entry key,
+/// which sits after the body; the entry start is derived from the trailing
+/// metadata offset.
+pub struct FirefoxCacheCarveHandler {
+    extension: String,
+    min_size: u64,
+    max_size: u64,
+}
+
+impl FirefoxCacheCarveHandler {
+    pub fn new(extension: String, min_size: u64, max_size: u64) -> Self {
+        Self {
+            extension,
+            min_size,
+            max_size,
+        }
+    }
+}
+
+impl CarveHandler for FirefoxCacheCarveHandler {
+    fn file_type(&self) -> &str {
+        "firefox_cache"
+    }
+
+    fn extension(&self) -> &str {
+        &self.extension
+    }
+
+    fn process_hit(
+        &self,
+        hit: &NormalizedHit,
+        ctx: &ExtractionContext,
+    ) -> Result<Option<CarvedFile>, CarveError> {
+        let back = (FIREFOX_MAX_KEY_PREFIX + FIREFOX_HEADER_SLACK) as u64;
+        let window_start = hit.global_offset.saturating_sub(back);
+        let anchor = (hit.global_offset - window_start) as usize;
+        let mut window = vec![0u8; anchor + FIREFOX_METADATA_WINDOW];
+        let n = ctx
+            .evidence
+            .read_at(window_start, &mut window)
+            .map_err(|e| CarveError::Evidence(e.to_string()))?;
+        window.truncate(n);
+
+        let max_body = if self.max_size > 0 {
+            self.max_size
+        } else {
+            u64::MAX
+        };
+        let Some(location) =
+            browser_cache::locate_firefox_cache2_entry(&window, anchor, window_start, max_body)
+        else {
+            return Ok(None);
+        };
+        let start = (window_start as i64 + location.start) as u64;
+        let end = window_start + location.end as u64;
+        if self.max_size > 0 && end - start > self.max_size {
+            return Ok(None);
+        }
+        write_entry(
+            self.file_type(),
+            &self.extension,
+            self.min_size,
+            hit,
+            start,
+            end,
+            ctx,
+        )
+    }
+}
+
+fn write_entry(
+    file_type: &str,
+    extension: &str,
+    min_size: u64,
+    hit: &NormalizedHit,
+    start: u64,
+    end: u64,
+    ctx: &ExtractionContext,
+) -> Result<Option<CarvedFile>, CarveError> {
+    let (full_path, rel_path) = output_path(ctx.output_root, file_type, extension, start)?;
+    let mut file = create_output(&full_path)?;
+    let mut md5 = md5::Context::new();
+    let mut sha256 = Sha256::new();
+    let (written, eof_truncated) = write_range(ctx, start, end, &mut file, &mut md5, &mut sha256)?;
+    file.flush()?;
+
+    if eof_truncated || written == 0 || written < min_size {
+        let _ = std::fs::remove_file(&full_path);
+        return Ok(None);
+    }
+
+    Ok(Some(CarvedFile {
+        run_id: ctx.run_id.to_string(),
+        file_type: file_type.to_string(),
+        signature_type: file_type.to_string(),
+        path: rel_path,
+        extension: extension.to_string(),
+        global_start: start,
+        global_end: start + written - 1,
+        size: written,
+        md5: Some(format!("{:x}", md5.compute())),
+        sha256: Some(hex::encode(sha256.finalize())),
+        validated: true,
+        truncated: false,
+        errors: Vec::new(),
+        pattern_id: Some(hit.pattern_id.clone()),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evidence::RawFileSource;
+    use crate::parsers::browser_cache::{chrome_entry_bytes, firefox_entry_bytes};
+
+    const HEADERS: &str = "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\n";
+
+    fn carve(
+        handler: &dyn CarveHandler,
+        image: &[u8],
+        hit_offset: u64,
+    ) -> (tempfile::TempDir, Option<CarvedFile>) {
+        let temp_dir = tempfile::tempdir().expect("tempdir");
+        let input_path = temp_dir.path().join("image.bin");
+        std::fs::write(&input_path, image).expect("write image");
+        let output_root = temp_dir.path().join("out");
+        let evidence = RawFileSource::open(&input_path).expect("evidence");
+        let ctx = ExtractionContext {
+            run_id: "test",
+            output_root: &output_root,
+            evidence: &evidence,
+        };
+        let hit = NormalizedHit {
+            global_offset: hit_offset,
+            file_type_id: handler.file_type().to_string(),
+            pattern_id: "cache".to_string(),
+        };
+        let carved = handler.process_hit(&hit, &ctx).expect("carve");
+        (temp_dir, carved)
+    }
+
+    #[test]
+    fn carves_chrome_entry_across_reads() {
+        let body = vec![b'x'; CHROME_READ_STEP + 100];
+        let entry = chrome_entry_bytes("1/0/https://a.example/big.txt", HEADERS, &body);
+        let mut image = vec![0u8; 512];
+        image.extend_from_slice(&entry);
+        image.extend_from_slice(&[0u8; 512]);
+
+        let handler = ChromeCacheCarveHandler::new("cache".to_string(), 0, 0);
+        let (_dir, carved) = carve(&handler, &image, 512);
+        let carved = carved.expect("carved");
+        assert_eq!(carved.global_start, 512);
+        assert_eq!(carved.size, entry.len() as u64);
+    }
+
+    #[test]
+    fn gives_up_on_a_chrome_entry_without_eof_records() {
+        /// A Chromium entry header followed by endless filler.
+        struct Endless(Vec<u8>);
+
+        impl crate::evidence::EvidenceSource for Endless {
+            fn len(&self) -> u64 {
+                u64::MAX / 2
+            }
+
+            fn read_at(
+                &self,
+                offset: u64,
+                buf: &mut [u8],
+            ) -> Result<usize, crate::evidence::EvidenceError> {
+                for (i, byte) in buf.iter_mut().enumerate() {
+                    let at = offset as usize + i;
+                    *byte = self.0.get(at).copied().unwrap_or(b'x');
+                }
+                Ok(buf.len())
+            }
+        }
+
+        let entry = chrome_entry_bytes("1/0/https://a.example/big.txt", HEADERS, b"body");
+        let key_end = browser_cache::find(&entry, b"big.txt").expect("key") + 7;
+        let evidence = Endless(entry[..key_end].to_vec());
+        let dir = tempfile::tempdir().expect("tempdir");
+        let ctx = ExtractionContext {
+            run_id: "test",
+            output_root: dir.path(),
+            evidence: &evidence,
+        };
+        let hit = NormalizedHit {
+            global_offset: 0,
+            file_type_id: "chrome_cache".to_string(),
+            pattern_id: "cache".to_string(),
+        };
+        let handler = ChromeCacheCarveHandler::new("cache".to_string(), 0, 0);
+        assert_eq!(handler.find_entry_len(&hit, &ctx).expect("search"), None);
+    }
+
+    #[test]
+    fn carves_firefox_entry_from_key_hit() {
+        let entry = firefox_entry_bytes("a,:https://example.com/", HEADERS, b"hello world");
+        let mut image = vec![0u8; 300];
+        let start = image.len() as u64;
+        image.extend_from_slice(&entry);
+        image.extend_from_slice(&[0u8; 300]);
+        let hit = start + browser_cache::find(&entry, b":http").expect("key") as u64;
+
+        let handler = FirefoxCacheCarveHandler::new("cache".to_string(), 0, 0);
+        let (dir, carved) = carve(&handler, &image, hit);
+        let carved = carved.expect("carved");
+        assert_eq!(carved.global_start, start);
+        let bytes = std::fs::read(dir.path().join("out").join(&carved.path)).expect("read");
+        assert_eq!(bytes, entry);
+    }
+}
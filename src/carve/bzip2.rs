@@ -1,18 +1,35 @@
 //! BZIP2 carving handler.
 //!
-//! We scan for the byte-aligned end marker as a best-effort heuristic.
-
-use std::fs::File;
+//! A bzip2 stream is a bit stream: blocks and the end-of-stream marker start
+//! with 48-bit magics that are not byte-aligned. The handler walks the
+//! stream bit by bit from one magic to the next, collecting each block's
+//! CRC, until the end-of-stream marker, whose combined CRC must match the
+//! block CRCs. The file ends at the byte holding the last bit of that CRC.
+//! A gap between magics longer than a block of the stream's level can be
+//! is taken as the end of the data, and the stream is cut there
+//! unvalidated.
 
 use sha2::{Digest, Sha256};
 
 use crate::carve::{
-    CarveError, CarveHandler, CarvedFile, ExtractionContext, output_path, write_range,
+    CarveError, CarveHandler, CarvedFile, ExtractionContext, Rejection, create_output,
+    note_rejection, output_path, write_range,
 };
 use crate::scanner::NormalizedHit;
 
 const BZIP2_MAGIC: [u8; 3] = [0x42, 0x5A, 0x68];
-const BZIP2_END: [u8; 6] = [0x17, 0x72, 0x45, 0x38, 0x50, 0x90];
+const HEADER_LEN: u64 = 4;
+/// Block header magic (BCD pi).
+const BLOCK_MAGIC: u64 = 0x3141_5926_5359;
+/// End-of-stream magic (BCD sqrt(pi)).
+const EOS_MAGIC: u64 = 0x1772_4538_5090;
+const MAGIC_MASK: u64 = (1 << 48) - 1;
+/// Bits of a magic and the CRC after it.
+const MARKER_BITS: u64 = 48 + 32;
+/// Uncompressed block size per level step; a compressed block of
+/// incompressible data is barely larger, so twice this bounds the gap.
+const BLOCK_SIZE_STEP: u64 = 100_000;
+const READ_BUF: usize = 64 * 1024;
 
 pub struct Bzip2CarveHandler {
     extension: String,
@@ -44,7 +61,7 @@ impl CarveHandler for Bzip2CarveHandler {
         hit: &NormalizedHit,
         ctx: &ExtractionContext,
     ) -> Result<Option<CarvedFile>, CarveError> {
-        let header = read_exact_at(ctx, hit.global_offset, 4)
+        let header = read_exact_at(ctx, hit.global_offset, 10)
             .ok_or_else(|| CarveError::Invalid("bzip2 header too short".to_string()))?;
         if header[0..3] != BZIP2_MAGIC {
             return Ok(None);
@@ -52,20 +69,14 @@ impl CarveHandler for Bzip2CarveHandler {
         if !(b'1'..=b'9').contains(&header[3]) {
             return Ok(None);
         }
-
-        let (full_path, rel_path) = output_path(
-            ctx.output_root,
-            self.file_type(),
-            &self.extension,
-            hit.global_offset,
-        )?;
-        let mut file = File::create(&full_path)?;
-        let mut md5 = md5::Context::new();
-        let mut sha256 = Sha256::new();
-
-        let mut validated = false;
-        let mut truncated = false;
-        let mut errors = Vec::new();
+        let first = header[4..10]
+            .iter()
+            .fold(0u64, |acc, &b| (acc << 8) | u64::from(b));
+        if first != BLOCK_MAGIC && first != EOS_MAGIC {
+            note_rejection(Rejection::Invalid("no block or end-of-stream magic"));
+            return Ok(None);
+        }
+        let max_gap = u64::from(header[3] - b'0') * BLOCK_SIZE_STEP * 2;
 
         let max_end = if self.max_size > 0 {
             hit.global_offset.saturating_add(self.max_size)
@@ -73,48 +84,45 @@ impl CarveHandler for Bzip2CarveHandler {
             u64::MAX
         };
 
-        let mut end_offset = None;
-        let mut offset = hit.global_offset + 4;
-        let mut carry: Vec<u8> = Vec::new();
-        let buf_size = 64 * 1024;
-
-        while offset < max_end {
-            let remaining = (max_end - offset).min(buf_size as u64) as usize;
-            let mut buf = vec![0u8; remaining];
-            let n = ctx
-                .evidence
-                .read_at(offset, &mut buf)
-                .map_err(|e| CarveError::Evidence(e.to_string()))?;
-            if n == 0 {
-                end_offset = Some(offset);
-                break;
+        let mut validated = false;
+        let mut truncated = false;
+        let mut errors = Vec::new();
+
+        let end_offset = match walk_stream(ctx, hit.global_offset, max_end, max_gap)? {
+            Walk::End {
+                end,
+                stored_crc,
+                combined_crc,
+            } => {
+                if stored_crc == combined_crc {
+                    validated = true;
+                } else {
+                    errors.push("bzip2 combined crc mismatch".to_string());
+                }
+                end
             }
-            buf.truncate(n);
-
-            let mut search_buf = carry.clone();
-            search_buf.extend_from_slice(&buf);
-            if let Some(pos) = find_pattern(&search_buf, &BZIP2_END) {
-                let absolute = offset
-                    .saturating_sub(carry.len() as u64)
-                    .saturating_add(pos as u64);
-                end_offset = Some(absolute + BZIP2_END.len() as u64);
-                validated = true;
-                break;
+            Walk::Limit => {
+                truncated = true;
+                errors.push("max_size reached before bzip2 end".to_string());
+                max_end
             }
-
-            offset = offset.saturating_add(buf.len() as u64);
-            if buf.len() >= BZIP2_END.len() - 1 {
-                carry = buf[buf.len() - (BZIP2_END.len() - 1)..].to_vec();
-            } else {
-                carry = buf;
+            // Copied up to the end of the evidence, which `write_range` reports.
+            Walk::Eof => max_end,
+            Walk::Gap { end } => {
+                errors.push("no bzip2 block or end-of-stream magic within a block".to_string());
+                end
             }
-        }
+        };
 
-        let end_offset = end_offset.unwrap_or(max_end);
-        if self.max_size > 0 && end_offset >= max_end {
-            truncated = true;
-            errors.push("max_size reached before bzip2 end".to_string());
-        }
+        let (full_path, rel_path) = output_path(
+            ctx.output_root,
+            self.file_type(),
+            &self.extension,
+            hit.global_offset,
+        )?;
+        let mut file = create_output(&full_path)?;
+        let mut md5 = md5::Context::new();
+        let mut sha256 = Sha256::new();
 
         let (written, eof_truncated) = write_range(
             ctx,
@@ -132,6 +140,10 @@ impl CarveHandler for Bzip2CarveHandler {
         }
 
         if written < self.min_size {
+            note_rejection(Rejection::MinSize {
+                size: written,
+                min_size: self.min_size,
+            });
             let _ = std::fs::remove_file(&full_path);
             return Ok(None);
         }
@@ -147,6 +159,7 @@ impl CarveHandler for Bzip2CarveHandler {
         Ok(Some(CarvedFile {
             run_id: ctx.run_id.to_string(),
             file_type: self.file_type().to_string(),
+            signature_type: self.file_type().to_string(),
             path: rel_path,
             extension: self.extension.clone(),
             global_start: hit.global_offset,
@@ -162,19 +175,106 @@ impl CarveHandler for Bzip2CarveHandler {
     }
 }
 
-fn find_pattern(haystack: &[u8], needle: &[u8]) -> Option<usize> {
-    if needle.is_empty() || haystack.len() < needle.len() {
-        return None;
-    }
-    let first = needle[0];
-    let mut i = 0usize;
-    while i + needle.len() <= haystack.len() {
-        if haystack[i] == first && &haystack[i..i + needle.len()] == needle {
-            return Some(i);
+/// Where a stream walk stopped.
+#[derive(Debug, PartialEq, Eq)]
+enum Walk {
+    /// The end-of-stream marker; `end` is past its last byte.
+    End {
+        end: u64,
+        stored_crc: u32,
+        combined_crc: u32,
+    },
+    /// `max_size` was reached.
+    Limit,
+    /// The evidence ended.
+    Eof,
+    /// No magic followed the last one within `max_gap` bytes; `end` is past
+    /// the last byte scanned.
+    Gap { end: u64 },
+}
+
+/// Walk the magics of the stream at `start` from the first one after the
+/// header.
+fn walk_stream(
+    ctx: &ExtractionContext,
+    start: u64,
+    max_end: u64,
+    max_gap: u64,
+) -> Result<Walk, CarveError> {
+    let mut combined_crc = 0u32;
+    // The last eight bytes read, the newest in the low bits.
+    let mut window = 0u64;
+    // Bit offset from `start` before which no magic is searched: the end of
+    // the CRC after the last magic.
+    let mut resume_bit = HEADER_LEN * 8;
+    let mut last_marker = start + HEADER_LEN;
+    let mut offset = start + HEADER_LEN;
+    let mut buf = vec![0u8; READ_BUF];
+    loop {
+        if offset >= max_end {
+            return Ok(Walk::Limit);
+        }
+        let len = (max_end - offset).min(READ_BUF as u64) as usize;
+        let n = ctx
+            .evidence
+            .read_at(offset, &mut buf[..len])
+            .map_err(|e| CarveError::Evidence(e.to_string()))?;
+        if n == 0 {
+            return Ok(Walk::Eof);
         }
-        i += 1;
+        for (i, &byte) in buf[..n].iter().enumerate() {
+            let byte_at = offset + i as u64;
+            window = (window << 8) | u64::from(byte);
+            let bits_read = (byte_at + 1 - start) * 8;
+            // A magic ending `shift` bits before the end of this byte; the
+            // earliest start first.
+            for shift in (0..8).rev() {
+                let Some(magic_bit) = bits_read.checked_sub(shift + 48) else {
+                    continue;
+                };
+                if magic_bit < resume_bit {
+                    continue;
+                }
+                let magic = (window >> shift) & MAGIC_MASK;
+                if magic != BLOCK_MAGIC && magic != EOS_MAGIC {
+                    continue;
+                }
+                let Some(crc) = read_bits_u32(ctx, start, magic_bit + 48) else {
+                    return Ok(Walk::Eof);
+                };
+                if magic == EOS_MAGIC {
+                    let end = start + (magic_bit + MARKER_BITS).div_ceil(8);
+                    if end > max_end {
+                        return Ok(Walk::Limit);
+                    }
+                    return Ok(Walk::End {
+                        end,
+                        stored_crc: crc,
+                        combined_crc,
+                    });
+                }
+                combined_crc = combined_crc.rotate_left(1) ^ crc;
+                resume_bit = magic_bit + MARKER_BITS;
+                last_marker = byte_at;
+            }
+            if byte_at - last_marker > max_gap {
+                return Ok(Walk::Gap { end: byte_at + 1 });
+            }
+        }
+        offset += n as u64;
+    }
+}
+
+/// The 32 bits at bit offset `bit` from `start`, most significant first.
+fn read_bits_u32(ctx: &ExtractionContext, start: u64, bit: u64) -> Option<u32> {
+    let mut bytes = [0u8; 5];
+    let needed = (bit % 8 + 32).div_ceil(8) as usize;
+    let n = ctx.evidence.read_at(start + bit / 8, &mut bytes).ok()?;
+    if n < needed {
+        return None;
     }
-    None
+    let value = bytes.iter().fold(0u64, |acc, &b| (acc << 8) | u64::from(b));
+    Some((value >> (8 - bit % 8)) as u32)
 }
 
 fn read_exact_at(ctx: &ExtractionContext, offset: u64, len: usize) -> Option<Vec<u8>> {
@@ -189,7 +289,7 @@ fn read_exact_at(ctx: &ExtractionContext, offset: u64, len: usize) -> Option<Vec
 #[cfg(test)]
 mod tests {
     use super::Bzip2CarveHandler;
-    use crate::carve::{CarveHandler, ExtractionContext};
+    use crate::carve::{CarveHandler, CarvedFile, ExtractionContext};
     use crate::evidence::{EvidenceError, EvidenceSource};
     use crate::scanner::NormalizedHit;
     use tempfile::tempdir;
@@ -214,15 +314,16 @@ mod tests {
         }
     }
 
-    #[test]
-    fn carves_bzip2_with_end_marker() {
-        let mut data = Vec::new();
-        data.extend_from_slice(b"BZh9");
-        data.extend_from_slice(&[0u8; 10]);
-        data.extend_from_slice(&[0x17, 0x72, 0x45, 0x38, 0x50, 0x90]);
+    /// `bzip2 -9` of "hello bzip2\n" three times: one block, the
+    /// end-of-stream marker 2 bits into its byte.
+    const ONE_BLOCK: &str = "425a68393141592653592ed29d8e000008d9800010400010001264c010200022bfd5\
+                             4034f508069a68c29e69d6d1496447c5dc914e14240bb4a76380";
 
-        let evidence = SliceEvidence { data: data.clone() };
-        let handler = Bzip2CarveHandler::new("bz2".to_string(), 0, 0);
+    fn carve(data: &[u8], max_size: u64) -> Option<CarvedFile> {
+        let evidence = SliceEvidence {
+            data: data.to_vec(),
+        };
+        let handler = Bzip2CarveHandler::new("bz2".to_string(), 0, max_size);
         let hit = NormalizedHit {
             global_offset: 0,
             file_type_id: "bzip2".to_string(),
@@ -234,10 +335,59 @@ mod tests {
             output_root: dir.path(),
             evidence: &evidence,
         };
+        handler.process_hit(&hit, &ctx).expect("process")
+    }
+
+    fn one_block() -> Vec<u8> {
+        hex::decode(ONE_BLOCK).expect("hex")
+    }
+
+    #[test]
+    fn carves_bzip2_with_end_marker() {
+        // An empty stream: the end-of-stream marker right after the header.
+        let mut data = Vec::new();
+        data.extend_from_slice(b"BZh9");
+        data.extend_from_slice(&[0x17, 0x72, 0x45, 0x38, 0x50, 0x90]);
+        data.extend_from_slice(&[0u8; 4]);
 
-        let carved = handler.process_hit(&hit, &ctx).expect("process");
-        let carved = carved.expect("carved");
+        let carved = carve(&data, 0).expect("carved");
         assert!(carved.validated);
         assert_eq!(carved.size, data.len() as u64);
     }
+
+    #[test]
+    fn walks_blocks_to_unaligned_end_marker() {
+        let stream = one_block();
+        let mut data = stream.clone();
+        data.extend_from_slice(&[0xFF; 32]);
+        let carved = carve(&data, 0).expect("carved");
+        assert!(carved.validated, "{:?}", carved.errors);
+        assert_eq!(carved.size, stream.len() as u64);
+    }
+
+    #[test]
+    fn flags_combined_crc_mismatch() {
+        let mut data = one_block();
+        let last = data.len() - 1;
+        data[last] ^= 0x40;
+        let carved = carve(&data, 0).expect("carved");
+        assert!(!carved.validated);
+        assert_eq!(carved.size, data.len() as u64);
+        assert_eq!(carved.errors, ["bzip2 combined crc mismatch"]);
+    }
+
+    #[test]
+    fn rejects_header_without_block_magic() {
+        let mut data = b"BZh9".to_vec();
+        data.extend_from_slice(&[0u8; 10]);
+        data.extend_from_slice(&[0x17, 0x72, 0x45, 0x38, 0x50, 0x90]);
+        assert!(carve(&data, 0).is_none());
+    }
+
+    #[test]
+    fn stops_at_max_size() {
+        let carved = carve(&one_block(), 20).expect("carved");
+        assert!(carved.truncated);
+        assert_eq!(carved.size, 20);
+    }
 }
@@ -0,0 +1,326 @@
+use std::io::Write;
+
+use sha2::{Digest, Sha256};
+
+use crate::carve::{
+    CarveError, CarveHandler, CarvedFile, ExtractionContext, Rejection, build_carved_file,
+    check_min_size, create_output, note_rejection, output_path,
+};
+use crate::parsers::cookies::{
+    self, BINARY_COOKIES_MAGIC, BinaryCookiesLayout, NetscapeLine, classify_netscape_line,
+};
+use crate::scanner::NormalizedHit;
+
+/// Read window for cookie text files.
+const TEXT_WINDOW: usize = 64 * 1024;
+/// Read limit when the type has no `max_size`.
+const UNBOUNDED_LIMIT: u64 = 16 * 1024 * 1024;
+
+/// Safari `Cookies.binarycookies` stores. The extent comes from the page
+/// table, the checksum after the last page, and the trailing plist length;
+/// the file is validated when the checksum over the pages matches.
+pub struct BinaryCookiesCarveHandler {
+    extension: String,
+    min_size: u64,
+    max_size: u64,
+}
+
+impl BinaryCookiesCarveHandler {
+    pub fn new(extension: String, min_size: u64, max_size: u64) -> Self {
+        Self {
+            extension,
+            min_size,
+            max_size,
+        }
+    }
+}
+
+impl CarveHandler for BinaryCookiesCarveHandler {
+    fn file_type(&self) -> &str {
+        "binarycookies"
+    }
+
+    fn extension(&self) -> &str {
+        &self.extension
+    }
+
+    fn process_hit(
+        &self,
+        hit: &NormalizedHit,
+        ctx: &ExtractionContext,
+    ) -> Result<Option<CarvedFile>, CarveError> {
+        let limit = if self.max_size > 0 {
+            self.max_size
+        } else {
+            UNBOUNDED_LIMIT
+        };
+        let mut head = [0u8; 8];
+        let n = read_full(ctx, hit.global_offset, &mut head)?;
+        if n < head.len() || &head[..4] != BINARY_COOKIES_MAGIC {
+            note_rejection(Rejection::Invalid("missing binarycookies header"));
+            return Ok(None);
+        }
+        let pages = u32::from_be_bytes(head[4..8].try_into().expect("4 bytes"));
+        let mut table = vec![0u8; BinaryCookiesLayout::header_len_for(pages).min(limit as usize)];
+        let n = read_full(ctx, hit.global_offset, &mut table)?;
+        let Some(layout) = BinaryCookiesLayout::parse(&table[..n]) else {
+            note_rejection(Rejection::Invalid("implausible page table"));
+            return Ok(None);
+        };
+        let mut trailer = [0u8; 12];
+        let n = read_full(ctx, hit.global_offset + layout.pages_end(), &mut trailer)?;
+        let total = layout.file_len(&trailer[..n]);
+
+        let mut data = vec![0u8; total.min(limit) as usize];
+        let n = read_full(ctx, hit.global_offset, &mut data)?;
+        data.truncate(n);
+        let Some((_, checksum_ok)) = cookies::parse_binary_cookies(&data) else {
+            note_rejection(Rejection::Invalid("implausible page table"));
+            return Ok(None);
+        };
+
+        let (full_path, rel_path) = output_path(
+            ctx.output_root,
+            self.file_type(),
+            &self.extension,
+            hit.global_offset,
+        )?;
+        let mut file = create_output(&full_path)?;
+        file.write_all(&data)?;
+        file.flush()?;
+        let size = data.len() as u64;
+        if !check_min_size(&full_path, size, self.min_size) {
+            return Ok(None);
+        }
+
+        let mut errors = Vec::new();
+        if size < total {
+            errors.push(if size >= limit {
+                "max_size reached".to_string()
+            } else {
+                "evidence ended before store end".to_string()
+            });
+        } else if !checksum_ok {
+            errors.push("page checksum mismatch".to_string());
+        }
+        let truncated = size < total;
+
+        Ok(Some(build_carved_file(
+            ctx.run_id,
+            self.file_type(),
+            &self.extension,
+            rel_path,
+            hit.global_offset,
+            size,
+            format!("{:x}", md5::compute(&data)),
+            hex::encode(Sha256::digest(&data)),
+            errors.is_empty(),
+            truncated,
+            errors,
+            &hit.pattern_id,
+        )))
+    }
+}
+
+/// Netscape `cookies.txt` files (curl, wget, browser exports). The extent
+/// runs from the header through the last line that is a cookie, comment, or
+/// blank; the file is validated when it holds at least one cookie and ends at
+/// a line the format does not allow.
+pub struct NetscapeCookiesCarveHandler {
+    extension: String,
+    min_size: u64,
+    max_size: u64,
+}
+
+impl NetscapeCookiesCarveHandler {
+    pub fn new(extension: String, min_size: u64, max_size: u64) -> Self {
+        Self {
+            extension,
+            min_size,
+            max_size,
+        }
+    }
+}
+
+impl CarveHandler for NetscapeCookiesCarveHandler {
+    fn file_type(&self) -> &str {
+        "netscape_cookies"
+    }
+
+    fn extension(&self) -> &str {
+        &self.extension
+    }
+
+    fn process_hit(
+        &self,
+        hit: &NormalizedHit,
+        ctx: &ExtractionContext,
+    ) -> Result<Option<CarvedFile>, CarveError> {
+        let limit = if self.max_size > 0 {
+            self.max_size
+        } else {
+            UNBOUNDED_LIMIT
+        } as usize;
+        let mut buf = Vec::new();
+        // End of the last accepted line.
+        let mut end = 0usize;
+        let mut cookie_lines = 0u64;
+        let mut closed = false;
+        'read: while buf.len() < limit {
+            let start = buf.len();
+            buf.resize((start + TEXT_WINDOW).min(limit), 0);
+            let want = buf.len() - start;
+            let n = read_full(ctx, hit.global_offset + start as u64, &mut buf[start..])?;
+            buf.truncate(start + n);
+            let eof = n < want;
+            while end < buf.len() {
+                let rest = &buf[end..];
+                let line_len = match rest.iter().position(|&b| b == b'\n') {
+                    Some(pos) => pos + 1,
+                    None if eof => rest.len(),
+                    // A line cut by `max_size` is left out.
+                    None if buf.len() >= limit => break 'read,
+                    None => continue 'read,
+                };
+                let line = &rest[..line_len];
+                if line.contains(&0) {
+                    closed = true;
+                    break 'read;
+                }
+                match classify_netscape_line(line) {
+                    NetscapeLine::Cookie => cookie_lines += 1,
+                    NetscapeLine::Filler => {}
+                    NetscapeLine::Foreign => {
+                        closed = true;
+                        break 'read;
+                    }
+                }
+                end += line_len;
+            }
+            if eof {
+                closed = true;
+                break;
+            }
+        }
+        if cookie_lines == 0 {
+            note_rejection(Rejection::Invalid("no cookie lines after header"));
+            return Ok(None);
+        }
+        let data = &buf[..end];
+
+        let (full_path, rel_path) = output_path(
+            ctx.output_root,
+            self.file_type(),
+            &self.extension,
+            hit.global_offset,
+        )?;
+        let mut file = create_output(&full_path)?;
+        file.write_all(data)?;
+        file.flush()?;
+        let size = data.len() as u64;
+        if !check_min_size(&full_path, size, self.min_size) {
+            return Ok(None);
+        }
+
+        let truncated = !closed;
+        let errors = if truncated {
+            vec!["max_size reached".to_string()]
+        } else {
+            Vec::new()
+        };
+
+        Ok(Some(build_carved_file(
+            ctx.run_id,
+            self.file_type(),
+            &self.extension,
+            rel_path,
+            hit.global_offset,
+            size,
+            format!("{:x}", md5::compute(data)),
+            hex::encode(Sha256::digest(data)),
+            !truncated,
+            truncated,
+            errors,
+            &hit.pattern_id,
+        )))
+    }
+}
+
+fn read_full(ctx: &ExtractionContext, offset: u64, buf: &mut [u8]) -> Result<usize, CarveError> {
+    let lens = ctx
+        .evidence
+        .read_vectored_at(&mut [(offset, buf)])
+        .map_err(|e| CarveError::Evidence(e.to_string()))?;
+    Ok(lens[0])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evidence::RawFileSource;
+    use crate::parsers::cookies::binary_cookies_bytes;
+
+    fn carve(
+        handler: &dyn CarveHandler,
+        image: &[u8],
+        offset: u64,
+    ) -> (Option<CarvedFile>, tempfile::TempDir) {
+        let temp_dir = tempfile::tempdir().expect("tempdir");
+        let input_path = temp_dir.path().join("image.bin");
+        std::fs::write(&input_path, image).expect("write image");
+        let output_root = temp_dir.path().join("out");
+        let evidence = RawFileSource::open(&input_path).expect("evidence");
+        let ctx = ExtractionContext {
+            run_id: "test",
+            output_root: &output_root,
+            evidence: &evidence,
+        };
+        let hit = NormalizedHit {
+            global_offset: offset,
+            file_type_id: handler.file_type().to_string(),
+            pattern_id: handler.file_type().to_string(),
+        };
+        (handler.process_hit(&hit, &ctx).expect("carve"), temp_dir)
+    }
+
+    #[test]
+    fn carves_binarycookies_through_trailer() {
+        let store = binary_cookies_bytes();
+        let mut image = vec![0u8; 512];
+        image.extend_from_slice(&store);
+        image.extend_from_slice(&[0xAAu8; 512]);
+        let handler = BinaryCookiesCarveHandler::new("binarycookies".to_string(), 0, 0);
+
+        let (carved, _dir) = carve(&handler, &image, 512);
+        let carved = carved.expect("carved");
+        assert_eq!(carved.size, store.len() as u64);
+        assert!(carved.validated);
+
+        // Evidence ends inside the second page.
+        let (carved, _dir) = carve(&handler, &image[..512 + store.len() - 40], 512);
+        let carved = carved.expect("carved");
+        assert!(carved.truncated);
+
+        let (carved, _dir) = carve(&handler, b"cookie monster says hi", 0);
+        assert!(carved.is_none());
+    }
+
+    #[test]
+    fn carves_netscape_file_up_to_foreign_line() {
+        let text = "# Netscape HTTP Cookie File\n\n\
+                    .example.com\tTRUE\t/\tTRUE\t1893456000\tsid\tabc\n\
+                    #HttpOnly_example.org\tFALSE\t/\tFALSE\t0\ttoken\txyz\n";
+        let mut image = text.as_bytes().to_vec();
+        image.extend_from_slice(b"<html><body>\n");
+        image.extend_from_slice(&[0u8; 64]);
+        let handler = NetscapeCookiesCarveHandler::new("txt".to_string(), 0, 0);
+
+        let (carved, _dir) = carve(&handler, &image, 0);
+        let carved = carved.expect("carved");
+        assert_eq!(carved.size, text.len() as u64);
+        assert!(carved.validated);
+
+        let (carved, _dir) = carve(&handler, b"# HTTP Cookie File\n\0\0\0", 0);
+        assert!(carved.is_none());
+    }
+}
@@ -1,18 +1,21 @@
 //! ELF carving handler.
 //!
-//! Uses header table offsets to estimate file size.
-
-use std::fs::File;
+//! The file header is checked (class, byte order, version, header and table
+//! entry sizes), then the program and section header tables are read to find
+//! the end of the last segment or section (`parsers::elf`). Shared objects
+//! are written as `.so`; the `executables` post-processor records the kind
+//! and architecture. When the evidence ends inside a table, the carve runs to
+//! the end of the tables and is kept truncated.
 
 use sha2::{Digest, Sha256};
 
 use crate::carve::{
-    CarveError, CarveHandler, CarvedFile, ExtractionContext, output_path, write_range,
+    CarveError, CarveHandler, CarvedFile, DeclaredSize, ExtractionContext, Rejection,
+    create_output, note_declared_size, note_rejection, output_path, read_evidence, write_range,
 };
+use crate::parsers::elf::{self, ElfError};
 use crate::scanner::NormalizedHit;
 
-const ELF_MAGIC: [u8; 4] = [0x7F, 0x45, 0x4C, 0x46];
-
 pub struct ElfCarveHandler {
     extension: String,
     min_size: u64,
@@ -43,151 +46,112 @@ impl CarveHandler for ElfCarveHandler {
         hit: &NormalizedHit,
         ctx: &ExtractionContext,
     ) -> Result<Option<CarvedFile>, CarveError> {
-        let header = read_exact_at(ctx, hit.global_offset, 64)
-            .ok_or_else(|| CarveError::Invalid("elf header too short".to_string()))?;
-        if header[0..4] != ELF_MAGIC {
-            return Ok(None);
-        }
-        let class = header[4];
-        let endian = header[5];
-        if class != 1 && class != 2 {
-            return Ok(None);
-        }
-        if endian != 1 && endian != 2 {
-            return Ok(None);
-        }
-
-        let (e_phoff, e_phentsize, e_phnum, e_shoff, e_shentsize, e_shnum) = if class == 1 {
-            let phoff = read_u32(&header[28..32], endian) as u64;
-            let shoff = read_u32(&header[32..36], endian) as u64;
-            let phentsize = read_u16(&header[42..44], endian) as u64;
-            let phnum = read_u16(&header[44..46], endian) as u64;
-            let shentsize = read_u16(&header[46..48], endian) as u64;
-            let shnum = read_u16(&header[48..50], endian) as u64;
-            (phoff, phentsize, phnum, shoff, shentsize, shnum)
-        } else {
-            let phoff = read_u64(&header[32..40], endian);
-            let shoff = read_u64(&header[40..48], endian);
-            let phentsize = read_u16(&header[54..56], endian) as u64;
-            let phnum = read_u16(&header[56..58], endian) as u64;
-            let shentsize = read_u16(&header[58..60], endian) as u64;
-            let shnum = read_u16(&header[60..62], endian) as u64;
-            (phoff, phentsize, phnum, shoff, shentsize, shnum)
+        let mut header = [0u8; 64];
+        let n = read_evidence(ctx, hit.global_offset, &mut header);
+        let header = match elf::parse_header(&header[..n]) {
+            Ok(header) => header,
+            Err(ElfError::Invalid(reason)) => {
+                note_rejection(Rejection::Invalid(reason));
+                return Ok(None);
+            }
+            Err(ElfError::Incomplete) => {
+                note_rejection(Rejection::Invalid("evidence ended inside the ELF header"));
+                return Ok(None);
+            }
         };
-
-        let mut size = 0u64;
-        if e_phoff > 0 && e_phentsize > 0 && e_phnum > 0 {
-            size = size.max(e_phoff.saturating_add(e_phentsize.saturating_mul(e_phnum)));
-        }
-        if e_shoff > 0 && e_shentsize > 0 && e_shnum > 0 {
-            size = size.max(e_shoff.saturating_add(e_shentsize.saturating_mul(e_shnum)));
-        }
-        if size == 0 {
-            size = header.len() as u64;
-        }
-
-        let mut total_end = hit.global_offset.saturating_add(size);
-        if self.max_size > 0 {
-            let max_end = hit.global_offset.saturating_add(self.max_size);
-            if total_end > max_end {
-                total_end = max_end;
+        let mut read = |offset: u64, buf: &mut [u8]| {
+            read_evidence(ctx, hit.global_offset.saturating_add(offset), buf)
+        };
+        let mut errors = Vec::new();
+        let (declared, layout) = match header.layout(&mut read) {
+            Ok(layout) => (layout.size, Some(layout)),
+            Err(ElfError::Invalid(reason)) => {
+                note_rejection(Rejection::Invalid(reason));
+                return Ok(None);
             }
+            Err(ElfError::Incomplete) => {
+                errors.push("header tables cut off".to_string());
+                (header.table_end(), None)
+            }
+        };
+        note_declared_size(DeclaredSize {
+            size: declared,
+            source: "header_tables",
+        });
+        let mut truncated = false;
+        let mut size = declared;
+        if self.max_size > 0 && size > self.max_size {
+            size = self.max_size;
+            truncated = true;
+            errors.push("max_size reached before ELF end".to_string());
         }
 
+        let extension = match layout.as_ref().map(|layout| header.kind(layout)) {
+            Some("so") => "so",
+            _ => self.extension.as_str(),
+        };
         let (full_path, rel_path) = output_path(
             ctx.output_root,
             self.file_type(),
-            &self.extension,
+            extension,
             hit.global_offset,
         )?;
-        let mut file = File::create(&full_path)?;
+        let mut file = create_output(&full_path)?;
         let mut md5 = md5::Context::new();
         let mut sha256 = Sha256::new();
-
         let (written, eof_truncated) = write_range(
             ctx,
             hit.global_offset,
-            total_end,
+            hit.global_offset + size,
             &mut file,
             &mut md5,
             &mut sha256,
         )?;
+        if eof_truncated {
+            truncated = true;
+            errors.push("eof before ELF end".to_string());
+        }
 
         if written < self.min_size {
+            note_rejection(Rejection::MinSize {
+                size: written,
+                min_size: self.min_size,
+            });
             let _ = std::fs::remove_file(&full_path);
             return Ok(None);
         }
 
-        let md5_hex = format!("{:x}", md5.compute());
-        let sha256_hex = hex::encode(sha256.finalize());
         let global_end = if written == 0 {
             hit.global_offset
         } else {
             hit.global_offset + written - 1
         };
-
         Ok(Some(CarvedFile {
             run_id: ctx.run_id.to_string(),
             file_type: self.file_type().to_string(),
+            signature_type: self.file_type().to_string(),
             path: rel_path,
-            extension: self.extension.clone(),
+            extension: extension.to_string(),
             global_start: hit.global_offset,
             global_end,
             size: written,
-            md5: Some(md5_hex),
-            sha256: Some(sha256_hex),
-            validated: !eof_truncated,
-            truncated: eof_truncated,
-            errors: Vec::new(),
+            md5: Some(format!("{:x}", md5.compute())),
+            sha256: Some(hex::encode(sha256.finalize())),
+            validated: errors.is_empty(),
+            truncated,
+            errors,
             pattern_id: Some(hit.pattern_id.clone()),
         }))
     }
 }
 
-fn read_u16(bytes: &[u8], endian: u8) -> u16 {
-    let mut buf = [0u8; 2];
-    buf.copy_from_slice(&bytes[0..2]);
-    if endian == 1 {
-        u16::from_le_bytes(buf)
-    } else {
-        u16::from_be_bytes(buf)
-    }
-}
-
-fn read_u32(bytes: &[u8], endian: u8) -> u32 {
-    let mut buf = [0u8; 4];
-    buf.copy_from_slice(&bytes[0..4]);
-    if endian == 1 {
-        u32::from_le_bytes(buf)
-    } else {
-        u32::from_be_bytes(buf)
-    }
-}
-
-fn read_u64(bytes: &[u8], endian: u8) -> u64 {
-    let mut buf = [0u8; 8];
-    buf.copy_from_slice(&bytes[0..8]);
-    if endian == 1 {
-        u64::from_le_bytes(buf)
-    } else {
-        u64::from_be_bytes(buf)
-    }
-}
-
-fn read_exact_at(ctx: &ExtractionContext, offset: u64, len: usize) -> Option<Vec<u8>> {
-    let mut buf = vec![0u8; len];
-    let n = ctx.evidence.read_at(offset, &mut buf).ok()?;
-    if n < len {
-        return None;
-    }
-    Some(buf)
-}
-
 #[cfg(test)]
 mod tests {
     use super::ElfCarveHandler;
+    use crate::carve::support::carve_image;
     use crate::carve::{CarveHandler, ExtractionContext};
     use crate::evidence::{EvidenceError, EvidenceSource};
+    use crate::parsers::elf::testing::{ELF_LEN, elf_bytes};
     use crate::scanner::NormalizedHit;
     use tempfile::tempdir;
 
@@ -217,6 +181,9 @@ mod tests {
         data[4] = 2; // 64-bit
         data[5] = 1; // little endian
         data[6] = 1; // version
+        data[0x10..0x12].copy_from_slice(&(2u16).to_le_bytes()); // e_type: executable
+        data[0x14..0x18].copy_from_slice(&(1u32).to_le_bytes()); // e_version
+        data[0x34..0x36].copy_from_slice(&(64u16).to_le_bytes()); // e_ehsize
         data[0x20..0x28].copy_from_slice(&(0x40u64).to_le_bytes()); // e_phoff
         data[0x28..0x30].copy_from_slice(&(0x40u64).to_le_bytes()); // e_shoff
         data[0x36..0x38].copy_from_slice(&(56u16).to_le_bytes()); // e_phentsize
@@ -247,4 +214,52 @@ mod tests {
         let carved = carved.expect("carved");
         assert_eq!(carved.size, data.len() as u64);
     }
+
+    #[test]
+    fn carves_to_the_end_of_the_section_table() {
+        let mut image = vec![0u8; 0x1000];
+        image.extend_from_slice(&elf_bytes());
+        image.extend_from_slice(&[0xAB; 0x800]);
+
+        let (carved, _dir) = carve_image(
+            &ElfCarveHandler::new("elf".to_string(), 0, 0),
+            &image,
+            0x1000,
+        );
+        let carved = carved.expect("elf");
+        assert_eq!(carved.size, ELF_LEN as u64);
+        assert!(carved.validated, "{:?}", carved.errors);
+        assert_eq!(carved.extension, "elf");
+
+        // Cut inside the section header table: kept up to the evidence end.
+        let (carved, _dir) = carve_image(
+            &ElfCarveHandler::new("elf".to_string(), 0, 0),
+            &image[..0x1000 + 0x420],
+            0x1000,
+        );
+        let carved = carved.expect("cut elf");
+        assert!(carved.truncated && !carved.validated);
+        assert_eq!(carved.size, 0x420);
+    }
+
+    #[test]
+    fn writes_shared_objects_as_so() {
+        let mut data = elf_bytes();
+        data[0x40..0x44].copy_from_slice(&4u32.to_le_bytes());
+        let (carved, _dir) = carve_image(&ElfCarveHandler::new("elf".to_string(), 0, 0), &data, 0);
+        let carved = carved.expect("so");
+        assert_eq!(carved.extension, "so");
+        assert!(carved.path.ends_with(".so"));
+    }
+
+    #[test]
+    fn rejects_random_magic_matches() {
+        let mut data = b"\x7FELF".to_vec();
+        data.extend_from_slice(&[0x41; 60]);
+        assert!(
+            carve_image(&ElfCarveHandler::new("elf".to_string(), 0, 0), &data, 0)
+                .0
+                .is_none()
+        );
+    }
 }
@@ -0,0 +1,602 @@
+//! Extensible Storage Engine (ESE) databases: Windows Search (`Windows.edb`),
+//! SRUM (`SRUDB.dat`), the Internet Explorer and Edge `WebCacheV01.dat`, and
+//! others written by the same engine.
+//!
+//! A database starts with a header page and a shadow copy of it; database
+//! page `n` follows at `(n + 1) * page_size`. The header holds the signature
+//! `EF CD AB 89` at offset 4, the format version and revision, the file type,
+//! the shutdown state, and the page size, but no page count. ESE keeps the
+//! pages a database owns in the owned-extent space tree of the database root
+//! (page 2), keyed by the last page of each extent, so the carve walks that
+//! tree to its largest key and ends after that page.
+//!
+//! The tables named in the catalog (page 4) tell which application wrote the
+//! database; the kind is appended to the file name. A hit on the shadow
+//! header, which would read every page one page off, is rejected.
+
+use std::io::Write;
+
+use memchr::memmem;
+use sha2::{Digest, Sha256};
+
+use crate::carve::{
+    CarveError, CarveHandler, CarvedFile, DeclaredSize, ExtractionContext, Rejection,
+    create_output, note_declared_size, note_rejection, tagged_output_path, write_range,
+};
+use crate::scanner::NormalizedHit;
+
+/// The pattern matches the signature, this far into the header.
+const SIGNATURE_OFFSET: u64 = 4;
+const SIGNATURE: u32 = 0x89AB_CDEF;
+const FORMAT_VERSION: u32 = 0x620;
+const FILE_TYPE_DATABASE: u32 = 0;
+/// Header fields read for sizing.
+const HEADER_LEN: usize = 0xF0;
+const STATE_DIRTY_SHUTDOWN: u32 = 2;
+
+/// Fixed pages of every database.
+const PGNO_OWN_EXT: u32 = 2;
+const PGNO_CATALOG: u32 = 4;
+/// Object ids of the database root and the catalog.
+const OBJID_DATABASE: u32 = 1;
+const OBJID_CATALOG: u32 = 2;
+
+const PAGE_ROOT: u32 = 0x01;
+const PAGE_LEAF: u32 = 0x02;
+const PAGE_PARENT: u32 = 0x04;
+const PAGE_SPACE_TREE: u32 = 0x20;
+const TAG_DELETED: u16 = 0x02;
+const TAG_COMMON_KEY: u16 = 0x04;
+
+/// Deepest B-tree walked; real space trees and catalogs have 2 or 3 levels.
+const MAX_DEPTH: usize = 8;
+/// Catalog leaf pages searched for table names.
+const CATALOG_PAGE_LIMIT: usize = 256;
+
+/// Table names that identify the writing application, and the tag each gets.
+const KINDS: [(&[u8], &str); 3] = [
+    (b"SruDbIdMapTable", "srum"),
+    (b"SystemIndex_", "windows_search"),
+    (b"LeakFiles", "webcache"),
+];
+
+/// Fields of a database header page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct DbHeader {
+    state: u32,
+    page_size: u64,
+}
+
+impl DbHeader {
+    fn parse(data: &[u8]) -> Result<Self, &'static str> {
+        if data.len() < HEADER_LEN || le_u32(data, 4) != SIGNATURE {
+            return Err("ESE signature mismatch");
+        }
+        if le_u32(data, 8) != FORMAT_VERSION {
+            return Err("unknown ESE format version");
+        }
+        if le_u32(data, 0x0C) != FILE_TYPE_DATABASE {
+            return Err("ESE streaming file, not a database");
+        }
+        let page_size = le_u32(data, 0xEC);
+        if !matches!(page_size, 2048 | 4096 | 8192 | 16384 | 32768) {
+            return Err("ESE page size invalid");
+        }
+        Ok(Self {
+            state: le_u32(data, 0x34),
+            page_size: u64::from(page_size),
+        })
+    }
+
+    /// Pages above 8 KiB have the 80-byte extended page header and 15-bit
+    /// tag fields, with the tag flags moved into the value.
+    fn large_pages(&self) -> bool {
+        self.page_size > 8192
+    }
+}
+
+/// A database page and its tags.
+struct Page<'a> {
+    data: &'a [u8],
+    large: bool,
+}
+
+/// A tagged value split into its key and data.
+struct Node<'a> {
+    flags: u16,
+    common_key: usize,
+    local_key: &'a [u8],
+    data: &'a [u8],
+}
+
+impl<'a> Page<'a> {
+    fn new(data: &'a [u8], header: &DbHeader) -> Option<Self> {
+        (data.len() as u64 == header.page_size).then_some(Self {
+            data,
+            large: header.large_pages(),
+        })
+    }
+
+    fn next(&self) -> u32 {
+        le_u32(self.data, 20)
+    }
+
+    fn object_id(&self) -> u32 {
+        le_u32(self.data, 24)
+    }
+
+    fn flags(&self) -> u32 {
+        le_u32(self.data, 36)
+    }
+
+    fn header_len(&self) -> usize {
+        if self.large { 80 } else { 40 }
+    }
+
+    fn tag_count(&self) -> usize {
+        usize::from(le_u16(self.data, 34))
+    }
+
+    /// Value of tag `index` and its flags.
+    fn value(&self, index: usize) -> Option<(u16, &'a [u8])> {
+        let at = self.data.len().checked_sub(4 * (index + 1))?;
+        let mask = if self.large { 0x7FFF } else { 0x1FFF };
+        let size = usize::from(le_u16(self.data, at) & mask);
+        let raw_offset = le_u16(self.data, at + 2);
+        let start = self.header_len() + usize::from(raw_offset & mask);
+        let value = self.data.get(start..start + size)?;
+        let flags = if self.large {
+            value.get(1).map_or(0, |b| u16::from(b >> 5))
+        } else {
+            raw_offset >> 13
+        };
+        Some((flags, value))
+    }
+
+    fn node(&self, index: usize) -> Option<Node<'a>> {
+        let (flags, value) = self.value(index)?;
+        // On large pages the flags sit in the top bits of the first field.
+        let mask_first = |v: u16| if self.large { v & 0x1FFF } else { v };
+        let mut at = 0;
+        let common_key = if flags & TAG_COMMON_KEY != 0 {
+            at = 2;
+            usize::from(mask_first(le_u16(value.get(..2)?, 0)))
+        } else {
+            0
+        };
+        let local = le_u16(value.get(at..at + 2)?, 0);
+        let local = usize::from(if at == 0 { mask_first(local) } else { local });
+        let local_key = value.get(at + 2..at + 2 + local)?;
+        Some(Node {
+            flags,
+            common_key,
+            local_key,
+            data: &value[at + 2 + local..],
+        })
+    }
+
+    /// Nodes after tag 0 (the page's external header), without deleted ones.
+    fn nodes(&self) -> impl Iterator<Item = Node<'a>> + '_ {
+        (1..self.tag_count())
+            .filter_map(|index| self.node(index))
+            .filter(|node| node.flags & TAG_DELETED == 0)
+    }
+
+    /// Full key of `node`: its common part comes from the key prefix in tag 0.
+    fn key(&self, node: &Node<'a>) -> Option<Vec<u8>> {
+        let mut key = Vec::with_capacity(node.common_key + node.local_key.len());
+        if node.common_key > 0 {
+            let (_, prefix) = self.value(0)?;
+            key.extend_from_slice(prefix.get(..node.common_key)?);
+        }
+        key.extend_from_slice(node.local_key);
+        Some(key)
+    }
+}
+
+/// Last page the database owns, from the largest key of the owned-extent
+/// space tree; `None` when the tree does not look like one.
+fn last_owned_page(
+    header: &DbHeader,
+    read_page: &mut dyn FnMut(u32) -> Option<Vec<u8>>,
+) -> Option<u32> {
+    let mut pgno = PGNO_OWN_EXT;
+    for depth in 0..MAX_DEPTH {
+        let buf = read_page(pgno)?;
+        let page = Page::new(&buf, header)?;
+        let flags = page.flags();
+        if page.object_id() != OBJID_DATABASE
+            || flags & PAGE_SPACE_TREE == 0
+            || (depth == 0) != (flags & PAGE_ROOT != 0)
+        {
+            return None;
+        }
+        if flags & PAGE_LEAF != 0 {
+            return page
+                .nodes()
+                .filter_map(|node| page.key(&node))
+                .filter(|key| matches!(key.len(), 4 | 5))
+                .map(|key| be_u32(&key[key.len() - 4..]))
+                .max()
+                .filter(|&last| last >= PGNO_CATALOG);
+        }
+        if flags & PAGE_PARENT == 0 {
+            return None;
+        }
+        // The last child holds the largest keys.
+        let node = page.nodes().last()?;
+        pgno = le_u32(node.data.get(..4)?, 0);
+    }
+    None
+}
+
+/// Kind of database from the table names on the catalog's leaf pages.
+fn classify(
+    header: &DbHeader,
+    read_page: &mut dyn FnMut(u32) -> Option<Vec<u8>>,
+) -> Option<&'static str> {
+    let mut pgno = PGNO_CATALOG;
+    let mut buf = read_page(pgno)?;
+    for _ in 0..MAX_DEPTH {
+        let page = Page::new(&buf, header)?;
+        if page.object_id() != OBJID_CATALOG {
+            return None;
+        }
+        if page.flags() & PAGE_LEAF != 0 {
+            break;
+        }
+        let node = page.nodes().next()?;
+        pgno = le_u32(node.data.get(..4)?, 0);
+        buf = read_page(pgno)?;
+    }
+    for _ in 0..CATALOG_PAGE_LIMIT {
+        if let Some((_, kind)) = KINDS
+            .iter()
+            .find(|(table, _)| memmem::find(&buf, table).is_some())
+        {
+            return Some(kind);
+        }
+        let next = Page::new(&buf, header)?.next();
+        if next == 0 {
+            break;
+        }
+        buf = read_page(next)?;
+    }
+    None
+}
+
+pub struct EseCarveHandler {
+    extension: String,
+    min_size: u64,
+    max_size: u64,
+}
+
+impl EseCarveHandler {
+    pub fn new(extension: String, min_size: u64, max_size: u64) -> Self {
+        Self {
+            extension,
+            min_size,
+            max_size,
+        }
+    }
+}
+
+impl CarveHandler for EseCarveHandler {
+    fn file_type(&self) -> &str {
+        "ese"
+    }
+
+    fn extension(&self) -> &str {
+        &self.extension
+    }
+
+    fn process_hit(
+        &self,
+        hit: &NormalizedHit,
+        ctx: &ExtractionContext,
+    ) -> Result<Option<CarvedFile>, CarveError> {
+        let Some(start) = hit.global_offset.checked_sub(SIGNATURE_OFFSET) else {
+            note_rejection(Rejection::Invalid(
+                "ESE signature before the evidence start",
+            ));
+            return Ok(None);
+        };
+        let mut raw = [0u8; HEADER_LEN];
+        let n = read_full(ctx, start, &mut raw)?;
+        let header = match DbHeader::parse(&raw[..n]) {
+            Ok(header) => header,
+            Err(reason) => {
+                note_rejection(Rejection::Invalid(reason));
+                return Ok(None);
+            }
+        };
+        if let Some(before) = start.checked_sub(header.page_size) {
+            let mut previous = [0u8; HEADER_LEN];
+            if read_full(ctx, before, &mut previous)? == HEADER_LEN && previous == raw {
+                note_rejection(Rejection::Invalid("shadow copy of an ESE header"));
+                return Ok(None);
+            }
+        }
+
+        let page_size = header.page_size;
+        let mut read_page = |pgno: u32| -> Option<Vec<u8>> {
+            let mut page = vec![0u8; page_size as usize];
+            let offset = start + (u64::from(pgno) + 1) * page_size;
+            match read_full(ctx, offset, &mut page) {
+                Ok(n) if n == page.len() => Some(page),
+                _ => None,
+            }
+        };
+        let Some(last) = last_owned_page(&header, &mut read_page) else {
+            note_rejection(Rejection::Invalid("ESE space tree unreadable"));
+            return Ok(None);
+        };
+        let kind = classify(&header, &mut read_page);
+        let declared = (u64::from(last) + 2) * page_size;
+        note_declared_size(DeclaredSize {
+            size: declared,
+            source: "space_tree",
+        });
+        let target = if self.max_size > 0 {
+            declared.min(self.max_size)
+        } else {
+            declared
+        };
+
+        let (full_path, rel_path) = tagged_output_path(
+            ctx.output_root,
+            self.file_type(),
+            kind,
+            &self.extension,
+            start,
+        )?;
+        let mut file = create_output(&full_path)?;
+        let mut md5 = md5::Context::new();
+        let mut sha256 = Sha256::new();
+        let (size, short_read) =
+            write_range(ctx, start, start + target, &mut file, &mut md5, &mut sha256)?;
+        file.flush()?;
+        if size < self.min_size {
+            note_rejection(Rejection::MinSize {
+                size,
+                min_size: self.min_size,
+            });
+            let _ = std::fs::remove_file(&full_path);
+            return Ok(None);
+        }
+
+        let mut errors = Vec::new();
+        if short_read {
+            errors.push("evidence ended before database end".to_string());
+        } else if target < declared {
+            errors.push("max_size reached".to_string());
+        }
+        let truncated = !errors.is_empty();
+        if header.state == STATE_DIRTY_SHUTDOWN {
+            errors.push("dirty shutdown: transaction logs not replayed".to_string());
+        }
+
+        Ok(Some(CarvedFile {
+            run_id: ctx.run_id.to_string(),
+            file_type: self.file_type().to_string(),
+            signature_type: self.file_type().to_string(),
+            path: rel_path,
+            extension: self.extension.clone(),
+            global_start: start,
+            global_end: start + size - 1,
+            size,
+            md5: Some(format!("{:x}", md5.compute())),
+            sha256: Some(hex::encode(sha256.finalize())),
+            validated: errors.is_empty(),
+            truncated,
+            errors,
+            pattern_id: Some(hit.pattern_id.clone()),
+        }))
+    }
+}
+
+fn read_full(ctx: &ExtractionContext, offset: u64, buf: &mut [u8]) -> Result<usize, CarveError> {
+    let lens = ctx
+        .evidence
+        .read_vectored_at(&mut [(offset, buf)])
+        .map_err(|e| CarveError::Evidence(e.to_string()))?;
+    Ok(lens[0])
+}
+
+fn le_u16(data: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([data[at], data[at + 1]])
+}
+
+fn le_u32(data: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(data[at..at + 4].try_into().unwrap_or_default())
+}
+
+fn be_u32(data: &[u8]) -> u32 {
+    u32::from_be_bytes(data[..4].try_into().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::carve::support::carve_image;
+
+    const PAGE: usize = 4096;
+
+    /// A 4 KiB page of `object_id` holding `values` (tag 0 first) as
+    /// `(tag flags, bytes)`.
+    fn page(flags: u32, object_id: u32, values: &[(u16, Vec<u8>)]) -> Vec<u8> {
+        let mut page = vec![0u8; PAGE];
+        page[24..28].copy_from_slice(&object_id.to_le_bytes());
+        page[34..36].copy_from_slice(&(values.len() as u16).to_le_bytes());
+        page[36..40].copy_from_slice(&flags.to_le_bytes());
+        let mut offset = 0usize;
+        for (index, (tag_flags, value)) in values.iter().enumerate() {
+            page[40 + offset..40 + offset + value.len()].copy_from_slice(value);
+            let at = PAGE - 4 * (index + 1);
+            page[at..at + 2].copy_from_slice(&(value.len() as u16).to_le_bytes());
+            let raw = offset as u16 | (tag_flags << 13);
+            page[at + 2..at + 4].copy_from_slice(&raw.to_le_bytes());
+            offset += value.len();
+        }
+        page
+    }
+
+    fn node(common: Option<u16>, key: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut node = Vec::new();
+        if let Some(common) = common {
+            node.extend_from_slice(&common.to_le_bytes());
+        }
+        node.extend_from_slice(&(key.len() as u16).to_le_bytes());
+        node.extend_from_slice(key);
+        node.extend_from_slice(data);
+        node
+    }
+
+    /// A database of `last + 2` pages whose catalog names `table`. With
+    /// `two_levels` the space tree root is a parent of a leaf on page 5 that
+    /// stores its keys with a common prefix.
+    fn ese_bytes(last: u32, table: &str, two_levels: bool) -> Vec<u8> {
+        let mut db = vec![0u8; (last as usize + 2) * PAGE];
+        let header = &mut db[..PAGE];
+        header[4..8].copy_from_slice(&SIGNATURE.to_le_bytes());
+        header[8..12].copy_from_slice(&FORMAT_VERSION.to_le_bytes());
+        header[0x34..0x38].copy_from_slice(&3u32.to_le_bytes());
+        header[0xE8..0xEC].copy_from_slice(&0x14u32.to_le_bytes());
+        header[0xEC..0xF0].copy_from_slice(&(PAGE as u32).to_le_bytes());
+        db.copy_within(..PAGE, PAGE);
+        let mut put = |pgno: u32, bytes: Vec<u8>| {
+            let at = (pgno as usize + 1) * PAGE;
+            db[at..at + PAGE].copy_from_slice(&bytes);
+        };
+
+        let space_root = PAGE_ROOT | PAGE_SPACE_TREE;
+        let extent = |key: u32| node(None, &key.to_be_bytes(), &4u32.to_le_bytes());
+        if two_levels {
+            put(
+                2,
+                page(
+                    space_root | PAGE_PARENT,
+                    OBJID_DATABASE,
+                    &[(0, vec![0; 16]), (0, node(None, &[], &5u32.to_le_bytes()))],
+                ),
+            );
+            let key = last.to_be_bytes();
+            put(
+                5,
+                page(
+                    PAGE_LEAF | PAGE_SPACE_TREE,
+                    OBJID_DATABASE,
+                    &[
+                        (0, key[..2].to_vec()),
+                        (0, extent(4)),
+                        (
+                            TAG_COMMON_KEY,
+                            node(Some(2), &key[2..], &4u32.to_le_bytes()),
+                        ),
+                    ],
+                ),
+            );
+        } else {
+            put(
+                2,
+                page(
+                    space_root | PAGE_LEAF,
+                    OBJID_DATABASE,
+                    &[(0, vec![0; 16]), (0, extent(last)), (0, extent(4))],
+                ),
+            );
+        }
+        let table_row = node(None, b"\x00\x01", table.as_bytes());
+        put(
+            PGNO_CATALOG,
+            page(
+                PAGE_ROOT | PAGE_LEAF,
+                OBJID_CATALOG,
+                &[(0, vec![0; 16]), (0, table_row)],
+            ),
+        );
+        db
+    }
+
+    #[test]
+    fn sizes_database_by_space_tree_and_tags_its_kind() {
+        let db = ese_bytes(9, "SruDbIdMapTable", false);
+        let mut image = vec![0u8; 8192];
+        image.extend_from_slice(&db);
+        image.extend_from_slice(&[0xEE; 8192]);
+
+        let (carved, _dir) = carve_image(
+            &EseCarveHandler::new("edb".to_string(), 0, 0),
+            &image,
+            8192 + 4,
+        );
+        let carved = carved.expect("ese");
+        assert_eq!(carved.global_start, 8192);
+        assert_eq!(carved.size, 11 * PAGE as u64);
+        assert!(carved.validated, "{:?}", carved.errors);
+        assert_eq!(carved.path, "ese/ese_000000002000_srum.edb");
+
+        let (carved, _dir) = carve_image(
+            &EseCarveHandler::new("edb".to_string(), 0, 8 * PAGE as u64),
+            &image,
+            8192 + 4,
+        );
+        let carved = carved.expect("cut ese");
+        assert!(carved.truncated);
+        assert_eq!(carved.errors, vec!["max_size reached"]);
+    }
+
+    #[test]
+    fn walks_parent_pages_and_common_key_prefixes() {
+        let db = ese_bytes(300, "SystemIndex_Gthr", true);
+        // The evidence ends long before page 300.
+        let cut = &db[..20 * PAGE];
+        let (carved, _dir) = carve_image(&EseCarveHandler::new("edb".to_string(), 0, 0), cut, 4);
+        let carved = carved.expect("ese");
+        assert!(carved.path.ends_with("_windows_search.edb"));
+        assert_eq!(carved.size, cut.len() as u64);
+        assert!(carved.truncated && !carved.validated);
+        assert_eq!(carved.errors, vec!["evidence ended before database end"]);
+
+        let mut dirty = ese_bytes(9, "MSysObjects", false);
+        dirty[0x34] = STATE_DIRTY_SHUTDOWN as u8;
+        let (carved, _dir) = carve_image(&EseCarveHandler::new("edb".to_string(), 0, 0), &dirty, 4);
+        let carved = carved.expect("dirty ese");
+        assert!(!carved.validated && !carved.truncated);
+        assert_eq!(carved.path, "ese/ese_000000000000.edb");
+    }
+
+    #[test]
+    fn rejects_shadow_headers_and_damaged_space_trees() {
+        let db = ese_bytes(9, "LeakFiles", false);
+        assert!(
+            carve_image(
+                &EseCarveHandler::new("edb".to_string(), 0, 0),
+                &db,
+                PAGE as u64 + 4
+            )
+            .0
+            .is_none()
+        );
+
+        let mut damaged = db.clone();
+        damaged[3 * PAGE + 24] = 7;
+        assert!(
+            carve_image(&EseCarveHandler::new("edb".to_string(), 0, 0), &damaged, 4)
+                .0
+                .is_none()
+        );
+
+        let mut streaming = db;
+        streaming[0x0C] = 1;
+        assert!(
+            carve_image(
+                &EseCarveHandler::new("edb".to_string(), 0, 0),
+                &streaming,
+                4
+            )
+            .0
+            .is_none()
+        );
+    }
+}
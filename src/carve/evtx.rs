@@ -0,0 +1,431 @@
+//! Windows Event Log (EVTX) files.
+//!
+//! A log starts with a 4 KiB file header: the `ElfFile` signature, the
+//! first and last chunk numbers, the next record identifier, a 128-byte
+//! header size, version 3.x, the number of chunks, the file flags (dirty,
+//! full), and a CRC-32 of the first 120 bytes. 64 KiB chunks follow, each
+//! starting with `ElfChnk`, its record numbers, the offset of its free
+//! space, a CRC-32 of its event records, and a CRC-32 of its own header.
+//!
+//! The carve takes the chunks the header counts, ending early at the first
+//! one that is missing. A log Windows did not close (dirty flag) can hold
+//! chunks written after its header was last updated, so for those the walk
+//! continues while chunks parse. Short, dirty, and checksum-failing logs
+//! are kept with `validated` false; the event records are not decoded.
+
+use std::io::Write;
+
+use sha2::{Digest, Sha256};
+
+use crate::carve::{
+    CarveError, CarveHandler, CarvedFile, DeclaredSize, ExtractionContext, Rejection, crc32,
+    create_output, note_declared_size, note_rejection, output_path, write_range,
+};
+use crate::scanner::NormalizedHit;
+
+const FILE_HEADER_LEN: u64 = 4096;
+const CHUNK_LEN: u64 = 65536;
+/// Chunk header and the string and template tables before the records.
+const CHUNK_RECORDS_START: usize = 512;
+const HEADER_SIZE: u32 = 128;
+const MAJOR_VERSION: u16 = 3;
+const FLAG_DIRTY: u32 = 0x1;
+
+/// Fields of an EVTX file header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileHeader {
+    pub first_chunk: u64,
+    pub last_chunk: u64,
+    pub next_record_id: u64,
+    pub minor_version: u16,
+    pub chunk_count: u16,
+    pub flags: u32,
+    pub checksum_ok: bool,
+}
+
+impl FileHeader {
+    /// Parse the file header at the start of `data`; `None` when it is not
+    /// an EVTX 3.x header.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < HEADER_SIZE as usize || &data[..8] != b"ElfFile\0" {
+            return None;
+        }
+        let header_size = le_u32(data, 32);
+        let major = le_u16(data, 38);
+        let block_size = le_u16(data, 40);
+        let chunk_count = le_u16(data, 42);
+        if header_size != HEADER_SIZE
+            || major != MAJOR_VERSION
+            || u64::from(block_size) != FILE_HEADER_LEN
+            || chunk_count == 0
+        {
+            return None;
+        }
+        Some(Self {
+            first_chunk: le_u64(data, 8),
+            last_chunk: le_u64(data, 16),
+            next_record_id: le_u64(data, 24),
+            minor_version: le_u16(data, 36),
+            chunk_count,
+            flags: le_u32(data, 120),
+            checksum_ok: crc32(&data[..120]) == le_u32(data, 124),
+        })
+    }
+
+    pub fn dirty(&self) -> bool {
+        self.flags & FLAG_DIRTY != 0
+    }
+}
+
+/// What a chunk's checks found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Chunk {
+    Valid,
+    /// Signature present, header or records checksum wrong.
+    Damaged,
+    /// No `ElfChnk` signature or a header that cannot be a chunk's.
+    Missing,
+}
+
+fn check_chunk(data: &[u8]) -> Chunk {
+    if &data[..8] != b"ElfChnk\0" || le_u32(data, 40) != HEADER_SIZE {
+        return Chunk::Missing;
+    }
+    let free_offset = le_u32(data, 48) as usize;
+    if !(CHUNK_RECORDS_START..=data.len()).contains(&free_offset) {
+        return Chunk::Missing;
+    }
+    let mut header = data[..120].to_vec();
+    header.extend_from_slice(&data[128..CHUNK_RECORDS_START]);
+    let header_ok = crc32(&header) == le_u32(data, 124);
+    let records_ok = crc32(&data[CHUNK_RECORDS_START..free_offset]) == le_u32(data, 52);
+    if header_ok && records_ok {
+        Chunk::Valid
+    } else {
+        Chunk::Damaged
+    }
+}
+
+pub struct EvtxCarveHandler {
+    extension: String,
+    min_size: u64,
+    max_size: u64,
+}
+
+/// Chunks the walk accepted and why it stopped.
+#[derive(Debug, Default)]
+struct ChunkWalk {
+    chunks: u64,
+    damaged: Vec<u64>,
+    /// Index of the first chunk that was missing or unreadable.
+    missing_at: Option<u64>,
+    short_read: bool,
+}
+
+impl EvtxCarveHandler {
+    pub fn new(extension: String, min_size: u64, max_size: u64) -> Self {
+        Self {
+            extension,
+            min_size,
+            max_size,
+        }
+    }
+
+    /// Walk chunks from `chunks_start`: the `expected` the header counts,
+    /// and past them while they parse when `extend` is set, up to `limit`
+    /// chunks.
+    fn walk_chunks(
+        &self,
+        ctx: &ExtractionContext,
+        chunks_start: u64,
+        expected: u64,
+        extend: bool,
+        limit: u64,
+    ) -> Result<ChunkWalk, CarveError> {
+        let mut walk = ChunkWalk::default();
+        let mut data = vec![0u8; CHUNK_LEN as usize];
+        while walk.chunks < limit {
+            ctx.check_cancelled()?;
+            let index = walk.chunks;
+            let n = ctx
+                .evidence
+                .read_at(chunks_start + index * CHUNK_LEN, &mut data)
+                .map_err(|e| CarveError::Evidence(e.to_string()))?;
+            if n < data.len() {
+                if index < expected {
+                    walk.short_read = true;
+                    walk.missing_at = Some(index);
+                }
+                break;
+            }
+            match check_chunk(&data) {
+                Chunk::Valid => {}
+                Chunk::Damaged if index < expected => walk.damaged.push(index),
+                // Past the counted chunks only intact ones extend the log.
+                Chunk::Damaged | Chunk::Missing => {
+                    if index < expected {
+                        walk.missing_at = Some(index);
+                    }
+                    break;
+                }
+            }
+            walk.chunks += 1;
+            if walk.chunks >= expected && !extend {
+                break;
+            }
+        }
+        Ok(walk)
+    }
+}
+
+impl CarveHandler for EvtxCarveHandler {
+    fn file_type(&self) -> &str {
+        "evtx"
+    }
+
+    fn extension(&self) -> &str {
+        &self.extension
+    }
+
+    fn process_hit(
+        &self,
+        hit: &NormalizedHit,
+        ctx: &ExtractionContext,
+    ) -> Result<Option<CarvedFile>, CarveError> {
+        let start = hit.global_offset;
+        let mut block = [0u8; HEADER_SIZE as usize];
+        let n = ctx
+            .evidence
+            .read_at(start, &mut block)
+            .map_err(|e| CarveError::Evidence(e.to_string()))?;
+        let Some(header) = FileHeader::parse(&block[..n]) else {
+            note_rejection(Rejection::Invalid("not an EVTX 3.x file header"));
+            return Ok(None);
+        };
+        let expected = u64::from(header.chunk_count);
+        let declared = FILE_HEADER_LEN + expected * CHUNK_LEN;
+        note_declared_size(DeclaredSize {
+            size: declared,
+            source: "file_header",
+        });
+        let limit = if self.max_size > 0 {
+            self.max_size.saturating_sub(FILE_HEADER_LEN) / CHUNK_LEN
+        } else {
+            u64::MAX
+        };
+        let walk = self.walk_chunks(
+            ctx,
+            start + FILE_HEADER_LEN,
+            expected,
+            header.dirty(),
+            limit,
+        )?;
+        if walk.chunks == 0 {
+            note_rejection(Rejection::Invalid(
+                "no event log chunk after the file header",
+            ));
+            return Ok(None);
+        }
+        let target = FILE_HEADER_LEN + walk.chunks * CHUNK_LEN;
+
+        let (full_path, rel_path) =
+            output_path(ctx.output_root, self.file_type(), &self.extension, start)?;
+        let mut file = create_output(&full_path)?;
+        let mut md5 = md5::Context::new();
+        let mut sha256 = Sha256::new();
+        let (size, short_read) =
+            write_range(ctx, start, start + target, &mut file, &mut md5, &mut sha256)?;
+        file.flush()?;
+        if size < self.min_size {
+            note_rejection(Rejection::MinSize {
+                size,
+                min_size: self.min_size,
+            });
+            let _ = std::fs::remove_file(&full_path);
+            return Ok(None);
+        }
+
+        let mut errors = Vec::new();
+        if short_read || walk.short_read {
+            errors.push("evidence ended before log end".to_string());
+        } else if let Some(at) = walk.missing_at {
+            errors.push(format!("chunk {at} of {expected} is missing"));
+        } else if walk.chunks < expected {
+            errors.push("max_size reached".to_string());
+        }
+        let truncated = !errors.is_empty();
+        if header.dirty() {
+            errors.push("file header marks the log dirty: it was not closed".to_string());
+        }
+        if !header.checksum_ok {
+            errors.push("file header checksum mismatch".to_string());
+        }
+        if let Some(first) = walk.damaged.first() {
+            errors.push(format!(
+                "{} chunk(s) fail their checksums, first chunk {first}",
+                walk.damaged.len()
+            ));
+        }
+
+        Ok(Some(CarvedFile {
+            run_id: ctx.run_id.to_string(),
+            file_type: self.file_type().to_string(),
+            signature_type: self.file_type().to_string(),
+            path: rel_path,
+            extension: self.extension.clone(),
+            global_start: start,
+            global_end: start + size - 1,
+            size,
+            md5: Some(format!("{:x}", md5.compute())),
+            sha256: Some(hex::encode(sha256.finalize())),
+            validated: errors.is_empty(),
+            truncated,
+            errors,
+            pattern_id: Some(hit.pattern_id.clone()),
+        }))
+    }
+}
+
+fn le_u16(data: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes(data[at..at + 2].try_into().unwrap_or_default())
+}
+
+fn le_u32(data: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(data[at..at + 4].try_into().unwrap_or_default())
+}
+
+fn le_u64(data: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(data[at..at + 8].try_into().unwrap_or_default())
+}
+
+/// Synthetic event logs.
+#[cfg(test)]
+pub(crate) mod testing {
+    use crate::carve::crc32;
+
+    /// A log whose header counts `counted` chunks and carries `flags`,
+    /// followed by `chunks` chunks of 16 records each with valid checksums.
+    pub(crate) fn evtx_bytes(chunks: u64, counted: u16, flags: u32) -> Vec<u8> {
+        let mut log = vec![0u8; 4096];
+        log[..8].copy_from_slice(b"ElfFile\0");
+        log[16..24].copy_from_slice(&chunks.saturating_sub(1).to_le_bytes());
+        log[24..32].copy_from_slice(&(chunks * 16 + 1).to_le_bytes());
+        log[32..36].copy_from_slice(&128u32.to_le_bytes());
+        log[36..38].copy_from_slice(&1u16.to_le_bytes());
+        log[38..40].copy_from_slice(&3u16.to_le_bytes());
+        log[40..42].copy_from_slice(&4096u16.to_le_bytes());
+        log[42..44].copy_from_slice(&counted.to_le_bytes());
+        log[120..124].copy_from_slice(&flags.to_le_bytes());
+        let sum = crc32(&log[..120]);
+        log[124..128].copy_from_slice(&sum.to_le_bytes());
+        for index in 0..chunks {
+            log.extend_from_slice(&chunk_bytes(index * 16 + 1));
+        }
+        log
+    }
+
+    /// A chunk holding records `first..first + 16` as filler bytes.
+    pub(crate) fn chunk_bytes(first: u64) -> Vec<u8> {
+        const FREE: usize = 512 + 16 * 200;
+        let mut chunk = vec![0u8; 65536];
+        chunk[..8].copy_from_slice(b"ElfChnk\0");
+        chunk[8..16].copy_from_slice(&first.to_le_bytes());
+        chunk[16..24].copy_from_slice(&(first + 15).to_le_bytes());
+        chunk[24..32].copy_from_slice(&first.to_le_bytes());
+        chunk[32..40].copy_from_slice(&(first + 15).to_le_bytes());
+        chunk[40..44].copy_from_slice(&128u32.to_le_bytes());
+        chunk[44..48].copy_from_slice(&((FREE - 200) as u32).to_le_bytes());
+        chunk[48..52].copy_from_slice(&(FREE as u32).to_le_bytes());
+        for (i, byte) in chunk[512..FREE].iter_mut().enumerate() {
+            *byte = (i as u64 + first) as u8;
+        }
+        let records = crc32(&chunk[512..FREE]);
+        chunk[52..56].copy_from_slice(&records.to_le_bytes());
+        let mut header = chunk[..120].to_vec();
+        header.extend_from_slice(&chunk[128..512]);
+        let sum = crc32(&header);
+        chunk[124..128].copy_from_slice(&sum.to_le_bytes());
+        chunk
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::{chunk_bytes, evtx_bytes};
+    use super::*;
+    use crate::carve::support::carve_image;
+
+    #[test]
+    fn sizes_log_by_counted_chunks() {
+        let log = evtx_bytes(3, 3, 0);
+        let mut image = vec![0u8; 512];
+        image.extend_from_slice(&log);
+        // A stray chunk of another log right after it is not taken.
+        image.extend_from_slice(&chunk_bytes(900));
+
+        let (carved, _dir) = carve_image(
+            &EvtxCarveHandler::new("evtx".to_string(), 0, 0),
+            &image,
+            512,
+        );
+        let carved = carved.expect("log");
+        assert_eq!(carved.size, log.len() as u64);
+        assert!(carved.validated && !carved.truncated, "{:?}", carved.errors);
+        assert_eq!(carved.path, "evtx/evtx_000000000200.evtx");
+
+        let header = FileHeader::parse(&log).expect("header");
+        assert_eq!((header.chunk_count, header.last_chunk), (3, 2));
+        assert!(header.checksum_ok && !header.dirty());
+    }
+
+    #[test]
+    fn partial_and_dirty_logs_are_kept_unvalidated() {
+        // Cut inside the third chunk.
+        let mut cut = evtx_bytes(3, 3, 0);
+        cut.truncate(4096 + 2 * 65536 + 1000);
+        let (carved, _dir) = carve_image(&EvtxCarveHandler::new("evtx".to_string(), 0, 0), &cut, 0);
+        let carved = carved.expect("partial log");
+        assert_eq!(carved.size, 4096 + 2 * 65536);
+        assert!(!carved.validated && carved.truncated);
+
+        // The second chunk overwritten.
+        let mut gap = evtx_bytes(3, 3, 0);
+        gap[4096 + 65536..4096 + 65536 + 8].copy_from_slice(b"XXXXXXXX");
+        let (carved, _dir) = carve_image(&EvtxCarveHandler::new("evtx".to_string(), 0, 0), &gap, 0);
+        let carved = carved.expect("log with a gap");
+        assert_eq!(carved.size, 4096 + 65536);
+        assert_eq!(carved.errors, vec!["chunk 1 of 3 is missing".to_string()]);
+
+        // A dirty log with a chunk written after the header count.
+        let dirty = evtx_bytes(3, 2, FLAG_DIRTY);
+        let (carved, _dir) =
+            carve_image(&EvtxCarveHandler::new("evtx".to_string(), 0, 0), &dirty, 0);
+        let carved = carved.expect("dirty log");
+        assert_eq!(carved.size, dirty.len() as u64);
+        assert!(!carved.validated && !carved.truncated);
+
+        // A record changed after its chunk was sealed.
+        let mut damaged = evtx_bytes(2, 2, 0);
+        damaged[4096 + 65536 + 600] ^= 0xFF;
+        let (carved, _dir) = carve_image(
+            &EvtxCarveHandler::new("evtx".to_string(), 0, 0),
+            &damaged,
+            0,
+        );
+        let carved = carved.expect("damaged log");
+        assert_eq!(carved.size, damaged.len() as u64);
+        assert!(!carved.validated && !carved.truncated);
+
+        let mut not_evtx = evtx_bytes(1, 1, 0);
+        not_evtx[38] = 2;
+        assert!(
+            carve_image(
+                &EvtxCarveHandler::new("evtx".to_string(), 0, 0),
+                &not_evtx,
+                0
+            )
+            .0
+            .is_none()
+        );
+    }
+}
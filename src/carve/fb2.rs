@@ -3,12 +3,14 @@
 //! Enhanced validation requires FictionBook tag or namespace within first 4KB.
 //! Rejects generic XML files that don't contain FictionBook markers.
 
-use std::fs::File;
-use std::io::{BufWriter, Write};
+use std::io::Write;
 
 use sha2::{Digest, Sha256};
 
-use crate::carve::{CarveError, CarveHandler, CarvedFile, ExtractionContext, output_path};
+use crate::carve::{
+    CarveError, CarveHandler, CarvedFile, ExtractionContext, Rejection, create_output,
+    note_rejection, output_path, output_writer,
+};
 use crate::scanner::NormalizedHit;
 
 const FB2_HEADER: &[u8] = b"<?xml";
@@ -81,8 +83,8 @@ impl CarveHandler for Fb2CarveHandler {
             &self.extension,
             hit.global_offset,
         )?;
-        let file = File::create(&full_path)?;
-        let mut writer = BufWriter::new(file);
+        let file = create_output(&full_path)?;
+        let mut writer = output_writer(file);
         let mut md5 = md5::Context::new();
         let mut sha256 = Sha256::new();
 
@@ -165,6 +167,11 @@ impl CarveHandler for Fb2CarveHandler {
         writer.flush()?;
 
         if bytes_written < self.min_size {
+            note_rejection(Rejection::MinSize {
+                size: bytes_written,
+
+                min_size: self.min_size,
+            });
             let _ = std::fs::remove_file(&full_path);
             return Ok(None);
         }
@@ -180,6 +187,7 @@ impl CarveHandler for Fb2CarveHandler {
         Ok(Some(CarvedFile {
             run_id: ctx.run_id.to_string(),
             file_type: self.file_type().to_string(),
+            signature_type: self.file_type().to_string(),
             path: rel_path,
             extension: self.extension.clone(),
             global_start: hit.global_offset,
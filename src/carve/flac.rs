@@ -0,0 +1,630 @@
+//! FLAC audio carving handler.
+//!
+//! A FLAC file is `fLaC`, a chain of metadata blocks starting with
+//! STREAMINFO, and the audio frames. Frames carry no length: each ends with
+//! a CRC-16 over the frame, so the end of a frame is a position where that
+//! CRC checks out and the next frame header (with its own CRC-8, and the
+//! frame or sample number that follows) starts. The walk ends when the
+//! frames add up to the sample count STREAMINFO declares; a walk that stops
+//! before it keeps the frames found, unvalidated.
+
+use sha2::{Digest, Sha256};
+
+use crate::carve::{
+    CarveError, CarveHandler, CarvedFile, ExtractionContext, Rejection, create_output,
+    note_rejection, output_path, read_evidence, write_range,
+};
+use crate::scanner::NormalizedHit;
+
+const MAGIC: &[u8; 4] = b"fLaC";
+const STREAMINFO_LEN: usize = 34;
+const BLOCK_HEADER_LEN: u64 = 4;
+/// Metadata blocks read before the walk gives up.
+const MAX_BLOCKS: usize = 1024;
+/// Frame length searched when STREAMINFO gives no maximum; the largest
+/// possible frame (65535 samples of 8 channels of 32 bits, verbatim) fits.
+const MAX_FRAME_LEN: u64 = 4 * 1024 * 1024;
+/// Bytes read at a time while searching for the end of a frame.
+const SCAN_CHUNK: usize = 64 * 1024;
+/// Longest frame header: sync and codes, a 7-byte number, block size and
+/// sample rate extensions, CRC-8.
+const MAX_FRAME_HEADER_LEN: usize = 16;
+
+pub struct FlacCarveHandler {
+    extension: String,
+    min_size: u64,
+    max_size: u64,
+}
+
+impl FlacCarveHandler {
+    pub fn new(extension: String, min_size: u64, max_size: u64) -> Self {
+        Self {
+            extension,
+            min_size,
+            max_size,
+        }
+    }
+}
+
+/// The fields of STREAMINFO the frame walk checks against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct StreamInfo {
+    min_frame_len: u32,
+    max_frame_len: u32,
+    sample_rate: u32,
+    channels: u8,
+    bits_per_sample: u8,
+    /// Samples per channel; 0 when the encoder did not know.
+    total_samples: u64,
+}
+
+impl StreamInfo {
+    fn parse(data: &[u8]) -> Option<Self> {
+        let data = data.get(..STREAMINFO_LEN)?;
+        let min_block = u16::from_be_bytes([data[0], data[1]]);
+        let max_block = u16::from_be_bytes([data[2], data[3]]);
+        let min_frame_len = u32::from_be_bytes([0, data[4], data[5], data[6]]);
+        let max_frame_len = u32::from_be_bytes([0, data[7], data[8], data[9]]);
+        let sample_rate =
+            (u32::from(data[10]) << 12) | (u32::from(data[11]) << 4) | u32::from(data[12] >> 4);
+        let channels = ((data[12] >> 1) & 0x07) + 1;
+        let bits_per_sample = (((data[12] & 0x01) << 4) | (data[13] >> 4)) + 1;
+        let total_samples = (u64::from(data[13] & 0x0F) << 32)
+            | u64::from(u32::from_be_bytes([data[14], data[15], data[16], data[17]]));
+        if min_block < 16
+            || max_block < min_block
+            || sample_rate == 0
+            || bits_per_sample < 4
+            || (max_frame_len != 0 && max_frame_len < min_frame_len)
+        {
+            return None;
+        }
+        Some(Self {
+            min_frame_len,
+            max_frame_len,
+            sample_rate,
+            channels,
+            bits_per_sample,
+            total_samples,
+        })
+    }
+}
+
+/// A frame header that checks out against STREAMINFO.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FrameHeader {
+    variable_blocking: bool,
+    /// Frame number (fixed blocking) or first sample number (variable).
+    number: u64,
+    block_size: u32,
+    len: usize,
+}
+
+fn parse_frame_header(bytes: &[u8], info: &StreamInfo) -> Option<FrameHeader> {
+    if bytes.len() < 6 || bytes[0] != 0xFF || bytes[1] & 0xFE != 0xF8 {
+        return None;
+    }
+    let variable_blocking = bytes[1] & 0x01 != 0;
+    let block_code = bytes[2] >> 4;
+    let rate_code = bytes[2] & 0x0F;
+    let channel_code = bytes[3] >> 4;
+    let size_code = (bytes[3] >> 1) & 0x07;
+    if block_code == 0 || rate_code == 0x0F || bytes[3] & 0x01 != 0 || size_code == 3 {
+        return None;
+    }
+    let channels = match channel_code {
+        0..=7 => channel_code + 1,
+        8..=10 => 2,
+        _ => return None,
+    };
+    if channels != info.channels {
+        return None;
+    }
+    let bits = match size_code {
+        0 => info.bits_per_sample,
+        1 => 8,
+        2 => 12,
+        4 => 16,
+        5 => 20,
+        6 => 24,
+        _ => 32,
+    };
+    if bits != info.bits_per_sample {
+        return None;
+    }
+
+    // UTF-8 style coded frame or sample number.
+    let first = bytes[4];
+    let extra = first.leading_ones() as usize;
+    if extra == 1 || extra > 7 {
+        return None;
+    }
+    let extra = extra.saturating_sub(1);
+    let mut number = u64::from(first & (0x7F >> extra.min(6)));
+    if extra == 6 {
+        number = 0;
+    }
+    let mut at = 5;
+    for _ in 0..extra {
+        let byte = *bytes.get(at)?;
+        if byte & 0xC0 != 0x80 {
+            return None;
+        }
+        number = (number << 6) | u64::from(byte & 0x3F);
+        at += 1;
+    }
+
+    let block_size = match block_code {
+        1 => 192,
+        2..=5 => 576 << (block_code - 2),
+        6 => {
+            at += 1;
+            u32::from(*bytes.get(at - 1)?) + 1
+        }
+        7 => {
+            at += 2;
+            u32::from(u16::from_be_bytes([
+                *bytes.get(at - 2)?,
+                *bytes.get(at - 1)?,
+            ])) + 1
+        }
+        _ => 256 << (block_code - 8),
+    };
+    let sample_rate = match rate_code {
+        0 => info.sample_rate,
+        1 => 88_200,
+        2 => 176_400,
+        3 => 192_000,
+        4 => 8_000,
+        5 => 16_000,
+        6 => 22_050,
+        7 => 24_000,
+        8 => 32_000,
+        9 => 44_100,
+        10 => 48_000,
+        11 => 96_000,
+        12 => {
+            at += 1;
+            u32::from(*bytes.get(at - 1)?) * 1000
+        }
+        13 => {
+            at += 2;
+            u32::from(u16::from_be_bytes([
+                *bytes.get(at - 2)?,
+                *bytes.get(at - 1)?,
+            ]))
+        }
+        _ => {
+            at += 2;
+            u32::from(u16::from_be_bytes([
+                *bytes.get(at - 2)?,
+                *bytes.get(at - 1)?,
+            ])) * 10
+        }
+    };
+    if sample_rate != info.sample_rate {
+        return None;
+    }
+    if crc8(&bytes[..at]) != *bytes.get(at)? {
+        return None;
+    }
+    Some(FrameHeader {
+        variable_blocking,
+        number,
+        block_size,
+        len: at + 1,
+    })
+}
+
+/// How a frame walk ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stop {
+    /// The frames add up to the declared sample count, or, without one, no
+    /// frame follows the last.
+    Complete,
+    /// A frame's end could not be found.
+    Damaged,
+    /// The next frame would pass the limit.
+    Limit,
+    /// The evidence ended inside a frame.
+    Eof,
+}
+
+struct Walk {
+    end: u64,
+    frames: u64,
+    samples: u64,
+    total_samples: u64,
+    stop: Stop,
+}
+
+/// Walk the metadata blocks and frames of a FLAC file at 0, up to `limit`
+/// bytes.
+fn walk(read: &mut dyn FnMut(u64, &mut [u8]) -> usize, limit: u64) -> Result<Walk, &'static str> {
+    let mut head = [0u8; 8 + STREAMINFO_LEN];
+    if read(0, &mut head) < head.len() {
+        return Err("evidence ended inside the FLAC STREAMINFO block");
+    }
+    if &head[..4] != MAGIC {
+        return Err("missing fLaC signature");
+    }
+    let block_len = u32::from_be_bytes([0, head[5], head[6], head[7]]);
+    if head[4] & 0x7F != 0 || block_len as usize != STREAMINFO_LEN {
+        return Err("first FLAC metadata block is not STREAMINFO");
+    }
+    let info = StreamInfo::parse(&head[8..]).ok_or("STREAMINFO fields out of range")?;
+
+    let mut offset = 8 + STREAMINFO_LEN as u64;
+    let mut last = head[4] & 0x80 != 0;
+    let mut blocks = 1;
+    while !last {
+        let mut header = [0u8; BLOCK_HEADER_LEN as usize];
+        if read(offset, &mut header) < header.len() {
+            return Err("evidence ended inside the FLAC metadata");
+        }
+        let kind = header[0] & 0x7F;
+        if kind == 0 || kind > 6 {
+            return Err("unknown FLAC metadata block type");
+        }
+        last = header[0] & 0x80 != 0;
+        offset +=
+            BLOCK_HEADER_LEN + u64::from(u32::from_be_bytes([0, header[1], header[2], header[3]]));
+        blocks += 1;
+        if blocks > MAX_BLOCKS {
+            return Err("too many FLAC metadata blocks");
+        }
+    }
+
+    let mut walk = Walk {
+        end: offset,
+        frames: 0,
+        samples: 0,
+        total_samples: info.total_samples,
+        stop: Stop::Complete,
+    };
+    let mut header_buf = [0u8; MAX_FRAME_HEADER_LEN];
+    let filled = read(offset, &mut header_buf);
+    let Some(mut frame) = parse_frame_header(&header_buf[..filled], &info) else {
+        return Err("no FLAC frame after the metadata");
+    };
+    let max_frame = match info.max_frame_len {
+        0 => MAX_FRAME_LEN,
+        len => u64::from(len),
+    };
+    let min_frame = u64::from(info.min_frame_len).max(frame.len as u64 + 3);
+
+    loop {
+        let start = walk.end;
+        let samples_after = walk.samples + u64::from(frame.block_size);
+        let last_frame = info.total_samples != 0 && samples_after >= info.total_samples;
+        let expected_next = if frame.variable_blocking {
+            samples_after
+        } else {
+            frame.number + 1
+        };
+
+        // Search for the frame end: a CRC-16 of zero over the frame so far.
+        let mut crc = 0u16;
+        let mut first_fit = None;
+        let mut found = None;
+        let mut eof = false;
+        let mut buf = vec![0u8; SCAN_CHUNK];
+        let mut pos = start;
+        'scan: while pos - start < max_frame {
+            let want = SCAN_CHUNK.min((max_frame - (pos - start)) as usize);
+            let n = read(pos, &mut buf[..want]);
+            for (i, byte) in buf[..n].iter().enumerate() {
+                crc = crc16_update(crc, *byte);
+                let end = pos + i as u64 + 1;
+                if crc != 0 || end - start < min_frame {
+                    continue;
+                }
+                if last_frame {
+                    found = Some(end);
+                    break 'scan;
+                }
+                first_fit.get_or_insert(end);
+                let filled = read(end, &mut header_buf);
+                if let Some(next) = parse_frame_header(&header_buf[..filled], &info)
+                    && next.variable_blocking == frame.variable_blocking
+                    && next.number == expected_next
+                {
+                    found = Some(end);
+                    frame = next;
+                    break 'scan;
+                }
+            }
+            pos += n as u64;
+            if n < want {
+                eof = true;
+                break;
+            }
+        }
+
+        let end = match found {
+            Some(end) => end,
+            // Without a declared sample count, the last frame is the one
+            // no frame follows.
+            None if info.total_samples == 0 && first_fit.is_some() => first_fit.unwrap_or(start),
+            None => {
+                walk.stop = if eof {
+                    walk.end = pos;
+                    Stop::Eof
+                } else {
+                    Stop::Damaged
+                };
+                break;
+            }
+        };
+        if end > limit {
+            walk.stop = Stop::Limit;
+            break;
+        }
+        walk.end = end;
+        walk.frames += 1;
+        walk.samples = samples_after;
+        if last_frame || found.is_none() {
+            break;
+        }
+    }
+    Ok(walk)
+}
+
+/// CRC-8 of FLAC frame headers: polynomial 0x07, initial value 0.
+fn crc8(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0u8, |mut crc, byte| {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    })
+}
+
+/// CRC-16 of FLAC frames: polynomial 0x8005, initial value 0. Running it over
+/// a whole frame, stored CRC included, gives 0.
+fn crc16_update(crc: u16, byte: u8) -> u16 {
+    (crc << 8) ^ CRC16_TABLE[usize::from((crc >> 8) as u8 ^ byte)]
+}
+
+const CRC16_TABLE: [u16; 256] = {
+    let mut table = [0u16; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = (i as u16) << 8;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x8005
+            } else {
+                crc << 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+impl CarveHandler for FlacCarveHandler {
+    fn file_type(&self) -> &str {
+        "flac"
+    }
+
+    fn extension(&self) -> &str {
+        &self.extension
+    }
+
+    fn process_hit(
+        &self,
+        hit: &NormalizedHit,
+        ctx: &ExtractionContext,
+    ) -> Result<Option<CarvedFile>, CarveError> {
+        let mut read = |offset: u64, buf: &mut [u8]| {
+            read_evidence(ctx, hit.global_offset.saturating_add(offset), buf)
+        };
+        let limit = if self.max_size > 0 {
+            self.max_size
+        } else {
+            u64::MAX
+        };
+        let walk = match walk(&mut read, limit) {
+            Ok(walk) => walk,
+            Err(reason) => {
+                note_rejection(Rejection::Invalid(reason));
+                return Ok(None);
+            }
+        };
+
+        let mut errors = Vec::new();
+        let mut size = walk.end;
+        let mut truncated = false;
+        match walk.stop {
+            Stop::Complete => {}
+            Stop::Damaged => errors.push(format!(
+                "frame walk stopped at sample {} of {} after {} frames",
+                walk.samples, walk.total_samples, walk.frames
+            )),
+            Stop::Limit => {
+                size = limit;
+                truncated = true;
+                errors.push("max_size reached before flac end".to_string());
+            }
+            Stop::Eof => {
+                // Keep the partial frame; decoders skip it.
+                size = size.min(limit);
+                truncated = true;
+                errors.push("eof before flac end".to_string());
+            }
+        }
+
+        let (full_path, rel_path) = output_path(
+            ctx.output_root,
+            self.file_type(),
+            &self.extension,
+            hit.global_offset,
+        )?;
+        let mut file = create_output(&full_path)?;
+        let mut md5 = md5::Context::new();
+        let mut sha256 = Sha256::new();
+        let (written, eof_truncated) = write_range(
+            ctx,
+            hit.global_offset,
+            hit.global_offset + size,
+            &mut file,
+            &mut md5,
+            &mut sha256,
+        )?;
+        if eof_truncated && !truncated {
+            truncated = true;
+            errors.push("eof before flac end".to_string());
+        }
+
+        if written < self.min_size {
+            note_rejection(Rejection::MinSize {
+                size: written,
+                min_size: self.min_size,
+            });
+            let _ = std::fs::remove_file(&full_path);
+            return Ok(None);
+        }
+
+        Ok(Some(CarvedFile {
+            run_id: ctx.run_id.to_string(),
+            file_type: self.file_type().to_string(),
+            signature_type: self.file_type().to_string(),
+            path: rel_path,
+            extension: self.extension.clone(),
+            global_start: hit.global_offset,
+            global_end: hit.global_offset + written.max(1) - 1,
+            size: written,
+            md5: Some(format!("{:x}", md5.compute())),
+            sha256: Some(hex::encode(sha256.finalize())),
+            validated: errors.is_empty(),
+            truncated,
+            errors,
+            pattern_id: Some(hit.pattern_id.clone()),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::carve::support::carve_image;
+
+    /// A 44.1 kHz stereo 16-bit stream of `frames` fixed-blocking frames of
+    /// 4096 samples, with a padding block; `total` is the declared sample
+    /// count.
+    fn flac_bytes(frames: u32, total: u64) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        out.extend_from_slice(&[0x00, 0x00, 0x00, STREAMINFO_LEN as u8]);
+        let mut info = [0u8; STREAMINFO_LEN];
+        info[0..2].copy_from_slice(&4096u16.to_be_bytes());
+        info[2..4].copy_from_slice(&4096u16.to_be_bytes());
+        // Largest frame 1024 bytes.
+        info[7..10].copy_from_slice(&[0x00, 0x04, 0x00]);
+        // 44100 Hz, 2 channels, 16 bits.
+        let rate = 44_100u32;
+        info[10] = (rate >> 12) as u8;
+        info[11] = (rate >> 4) as u8;
+        info[12] = ((rate & 0x0F) as u8) << 4 | (1 << 1);
+        info[13] = (15 << 4) | ((total >> 32) as u8 & 0x0F);
+        info[14..18].copy_from_slice(&(total as u32).to_be_bytes());
+        out.extend_from_slice(&info);
+        // Last block: 16 bytes of padding.
+        out.extend_from_slice(&[0x81, 0x00, 0x00, 0x10]);
+        out.extend_from_slice(&[0u8; 16]);
+
+        for number in 0..frames {
+            // Block size code 12 (4096), rate code 9 (44.1 kHz), stereo,
+            // 16-bit, one-byte frame number.
+            let mut frame = vec![0xFF, 0xF8, 0xC9, 0x18, number as u8];
+            frame.push(crc8(&frame));
+            // Subframe bytes standing in for the audio; they hold sync-like
+            // bytes to exercise the search.
+            for i in 0..700u32 {
+                frame.push(match i % 97 {
+                    0 => 0xFF,
+                    1 => 0xF8,
+                    _ => (i * 31 + number) as u8,
+                });
+            }
+            let crc = frame
+                .iter()
+                .fold(0u16, |crc, byte| crc16_update(crc, *byte));
+            frame.extend_from_slice(&crc.to_be_bytes());
+            out.extend_from_slice(&frame);
+        }
+        out
+    }
+
+    #[test]
+    fn walks_frames_to_the_declared_sample_count() {
+        let flac = flac_bytes(5, 5 * 4096);
+        let mut image = flac.clone();
+        image.extend_from_slice(&[0x5A; 2048]);
+        let (carved, _dir) =
+            carve_image(&FlacCarveHandler::new("flac".to_string(), 0, 0), &image, 0);
+        let carved = carved.expect("carved");
+        assert!(carved.validated, "{:?}", carved.errors);
+        assert_eq!(carved.size, flac.len() as u64);
+
+        // Without a sample count the walk ends at the frame nothing follows.
+        let flac = flac_bytes(5, 0);
+        let mut image = flac.clone();
+        image.extend_from_slice(&[0x5A; 2048]);
+        let (carved, _dir) =
+            carve_image(&FlacCarveHandler::new("flac".to_string(), 0, 0), &image, 0);
+        let carved = carved.expect("carved");
+        assert!(carved.validated, "{:?}", carved.errors);
+        assert_eq!(carved.size, flac.len() as u64);
+    }
+
+    #[test]
+    fn keeps_frames_before_damage_and_cut_files() {
+        let mut flac = flac_bytes(5, 5 * 4096);
+        let frames_start = flac.len() - 5 * 708;
+        flac[frames_start + 2 * 708 + 100] ^= 0x55;
+        let (carved, _dir) =
+            carve_image(&FlacCarveHandler::new("flac".to_string(), 0, 0), &flac, 0);
+        let carved = carved.expect("carved");
+        assert!(!carved.validated && !carved.truncated);
+        assert_eq!(carved.size, (frames_start + 2 * 708) as u64);
+
+        let flac = flac_bytes(5, 5 * 4096);
+        let cut = &flac[..flac.len() - 300];
+        let (carved, _dir) = carve_image(&FlacCarveHandler::new("flac".to_string(), 0, 0), cut, 0);
+        let carved = carved.expect("cut");
+        assert!(carved.truncated);
+        assert_eq!(carved.size, cut.len() as u64);
+    }
+
+    #[test]
+    fn rejects_streams_without_streaminfo_or_frames() {
+        let flac = flac_bytes(2, 2 * 4096);
+        let mut bad = flac.clone();
+        bad[4] = 0x01;
+        assert!(
+            carve_image(&FlacCarveHandler::new("flac".to_string(), 0, 0), &bad, 0)
+                .0
+                .is_none()
+        );
+
+        let frames_start = flac.len() - 2 * 708;
+        assert!(
+            carve_image(
+                &FlacCarveHandler::new("flac".to_string(), 0, 0),
+                &flac[..frames_start],
+                0
+            )
+            .0
+            .is_none()
+        );
+    }
+}
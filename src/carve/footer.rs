@@ -1,11 +1,21 @@
-use std::fs::File;
-use std::io::{BufWriter, Write};
+use std::io::Write;
 
 use sha2::{Digest, Sha256};
 
-use crate::carve::{CarveError, CarveHandler, CarvedFile, ExtractionContext, output_path};
+use crate::carve::{
+    CarveError, CarveHandler, CarvedFile, ExtractionContext, Rejection, build_carved_file,
+    check_min_size, create_output, note_rejection, output_path, output_writer, write_range,
+};
+use crate::config::FooterMode;
 use crate::scanner::NormalizedHit;
 
+const BUF_SIZE: usize = 64 * 1024;
+
+/// Generic header/footer carver for config-defined formats.
+///
+/// With footer patterns, carving ends at a footer according to the configured
+/// [`FooterMode`]. Without footer patterns, `max_size` bytes are carved from the
+/// header (length strategy).
 pub struct FooterCarveHandler {
     file_type: String,
     extension: String,
@@ -14,6 +24,7 @@ pub struct FooterCarveHandler {
     header_patterns: Vec<Vec<u8>>,
     footer_patterns: Vec<Vec<u8>>,
     max_footer_len: usize,
+    footer_mode: FooterMode,
 }
 
 impl FooterCarveHandler {
@@ -34,7 +45,142 @@ impl FooterCarveHandler {
             header_patterns,
             footer_patterns,
             max_footer_len,
+            footer_mode: FooterMode::Inclusive,
+        }
+    }
+
+    pub fn with_footer_mode(mut self, footer_mode: FooterMode) -> Self {
+        self.footer_mode = footer_mode;
+        self
+    }
+
+    /// Carve for `Exclusive`/`Last` footer modes: locate the end offset first,
+    /// then copy the range.
+    fn process_hit_located(
+        &self,
+        hit: &NormalizedHit,
+        ctx: &ExtractionContext,
+    ) -> Result<Option<CarvedFile>, CarveError> {
+        let start = hit.global_offset;
+        let mut offset = start;
+        let mut scanned = 0u64;
+        let mut carry: Vec<u8> = Vec::new();
+        let mut footer_end: Option<u64> = None;
+        let mut hit_eof = false;
+
+        loop {
+            ctx.check_cancelled()?;
+            if self.max_size > 0 && scanned >= self.max_size {
+                break;
+            }
+            let want = if self.max_size > 0 {
+                (self.max_size - scanned).min(BUF_SIZE as u64)
+            } else {
+                BUF_SIZE as u64
+            };
+            let mut buf = vec![0u8; want as usize];
+            let n = ctx
+                .evidence
+                .read_at(offset, &mut buf)
+                .map_err(|e| CarveError::Evidence(e.to_string()))?;
+            if n == 0 {
+                hit_eof = true;
+                break;
+            }
+            buf.truncate(n);
+            if scanned == 0 && !self.header_matches(&buf) {
+                return Ok(None);
+            }
+
+            let search_base = offset - carry.len() as u64;
+            let mut search_buf = Vec::with_capacity(carry.len() + buf.len());
+            search_buf.extend_from_slice(&carry);
+            search_buf.extend_from_slice(&buf);
+
+            match self.footer_mode {
+                FooterMode::Exclusive => {
+                    // Skip the header position so a footer equal to the header
+                    // ends the carve at the next occurrence.
+                    let skip = if search_base == start { 1 } else { 0 };
+                    if let Some((pos, _)) =
+                        find_first_pattern(&search_buf[skip..], &self.footer_patterns)
+                    {
+                        footer_end = Some(search_base + (pos + skip) as u64);
+                        break;
+                    }
+                }
+                FooterMode::Last => {
+                    if let Some(end) = find_last_pattern_end(&search_buf, &self.footer_patterns) {
+                        footer_end = Some(search_base + end as u64);
+                    }
+                }
+                FooterMode::Inclusive => unreachable!("inclusive mode streams directly"),
+            }
+
+            scanned = scanned.saturating_add(n as u64);
+            offset = offset.saturating_add(n as u64);
+            let keep = self.max_footer_len.saturating_sub(1);
+            if keep > 0 {
+                let tail_start = search_buf.len().saturating_sub(keep);
+                carry = search_buf[tail_start..].to_vec();
+            } else {
+                carry.clear();
+            }
+        }
+
+        let mut errors = Vec::new();
+        let (end, validated, mut truncated) = match footer_end {
+            Some(end) => (end, true, false),
+            None => {
+                errors.push(if hit_eof {
+                    "eof before footer".to_string()
+                } else {
+                    "max_size reached before footer".to_string()
+                });
+                (start + scanned, false, true)
+            }
+        };
+        let size = end.saturating_sub(start);
+        if size < self.min_size {
+            note_rejection(Rejection::MinSize {
+                size,
+                min_size: self.min_size,
+            });
+            return Ok(None);
         }
+
+        let (full_path, rel_path) = output_path(
+            ctx.output_root,
+            self.file_type(),
+            &self.extension,
+            hit.global_offset,
+        )?;
+        let mut file = create_output(&full_path)?;
+        let mut md5 = md5::Context::new();
+        let mut sha256 = Sha256::new();
+        let (written, eof) = write_range(ctx, start, end, &mut file, &mut md5, &mut sha256)?;
+        file.flush()?;
+        if eof {
+            truncated = true;
+        }
+        if !check_min_size(&full_path, written, self.min_size) {
+            return Ok(None);
+        }
+
+        Ok(Some(build_carved_file(
+            ctx.run_id,
+            self.file_type(),
+            &self.extension,
+            rel_path,
+            start,
+            written,
+            format!("{:x}", md5.compute()),
+            hex::encode(sha256.finalize()),
+            validated,
+            truncated,
+            errors,
+            &hit.pattern_id,
+        )))
     }
 
     fn header_matches(&self, buf: &[u8]) -> bool {
@@ -61,14 +207,17 @@ impl CarveHandler for FooterCarveHandler {
         hit: &NormalizedHit,
         ctx: &ExtractionContext,
     ) -> Result<Option<CarvedFile>, CarveError> {
+        if !self.footer_patterns.is_empty() && self.footer_mode != FooterMode::Inclusive {
+            return self.process_hit_located(hit, ctx);
+        }
         let (full_path, rel_path) = output_path(
             ctx.output_root,
             self.file_type(),
             &self.extension,
             hit.global_offset,
         )?;
-        let file = File::create(&full_path)?;
-        let mut writer = BufWriter::new(file);
+        let file = create_output(&full_path)?;
+        let mut writer = output_writer(file);
         let mut md5 = md5::Context::new();
         let mut sha256 = Sha256::new();
 
@@ -79,10 +228,15 @@ impl CarveHandler for FooterCarveHandler {
         let mut errors = Vec::new();
 
         let mut carry: Vec<u8> = Vec::new();
-        let buf_size = 64 * 1024;
+        let buf_size = BUF_SIZE;
 
         loop {
+            ctx.check_cancelled()?;
             if self.max_size > 0 && bytes_written >= self.max_size {
+                if self.footer_patterns.is_empty() {
+                    // Length strategy: the carve ends at max_size.
+                    break;
+                }
                 truncated = true;
                 errors.push("max_size reached before footer".to_string());
                 break;
@@ -154,6 +308,11 @@ impl CarveHandler for FooterCarveHandler {
         writer.flush()?;
 
         if bytes_written < self.min_size {
+            note_rejection(Rejection::MinSize {
+                size: bytes_written,
+
+                min_size: self.min_size,
+            });
             let _ = std::fs::remove_file(&full_path);
             return Ok(None);
         }
@@ -169,6 +328,7 @@ impl CarveHandler for FooterCarveHandler {
         Ok(Some(CarvedFile {
             run_id: ctx.run_id.to_string(),
             file_type: self.file_type().to_string(),
+            signature_type: self.file_type().to_string(),
             path: rel_path,
             extension: self.extension.clone(),
             global_start: hit.global_offset,
@@ -201,6 +361,23 @@ fn find_first_pattern(haystack: &[u8], patterns: &[Vec<u8>]) -> Option<(usize, u
     best
 }
 
+/// End offset (exclusive) of the last footer occurrence in `haystack`.
+fn find_last_pattern_end(haystack: &[u8], patterns: &[Vec<u8>]) -> Option<usize> {
+    let mut best: Option<usize> = None;
+    for pat in patterns {
+        if pat.is_empty() || haystack.len() < pat.len() {
+            continue;
+        }
+        let mut from = 0usize;
+        while let Some(pos) = find_pattern(&haystack[from..], pat) {
+            let end = from + pos + pat.len();
+            best = Some(best.map_or(end, |b| b.max(end)));
+            from += pos + 1;
+        }
+    }
+    best
+}
+
 fn find_pattern(haystack: &[u8], needle: &[u8]) -> Option<usize> {
     if needle.is_empty() || haystack.len() < needle.len() {
         return None;
@@ -219,7 +396,8 @@ fn find_pattern(haystack: &[u8], needle: &[u8]) -> Option<usize> {
 #[cfg(test)]
 mod tests {
     use super::FooterCarveHandler;
-    use crate::carve::{CarveHandler, ExtractionContext};
+    use crate::carve::{CarveHandler, CarvedFile, ExtractionContext};
+    use crate::config::FooterMode;
     use crate::evidence::{EvidenceError, EvidenceSource};
     use crate::scanner::NormalizedHit;
     use tempfile::tempdir;
@@ -287,4 +465,95 @@ mod tests {
             (header.len() + "payload".len() + footer.len()) as u64
         );
     }
+
+    fn carve_with_mode(data: Vec<u8>, footer: &[u8], mode: FooterMode) -> Option<CarvedFile> {
+        let evidence = SliceEvidence { data };
+        let dir = tempdir().expect("tempdir");
+        let ctx = ExtractionContext {
+            run_id: "run1",
+            output_root: dir.path(),
+            evidence: &evidence,
+        };
+        let handler = FooterCarveHandler::new(
+            "custom".to_string(),
+            "bin".to_string(),
+            1,
+            1024,
+            vec![b"HEAD".to_vec()],
+            vec![footer.to_vec()],
+        )
+        .with_footer_mode(mode);
+        let hit = NormalizedHit {
+            global_offset: 0,
+            file_type_id: "custom".to_string(),
+            pattern_id: "header".to_string(),
+        };
+        handler.process_hit(&hit, &ctx).expect("process")
+    }
+
+    #[test]
+    fn exclusive_mode_stops_before_footer() {
+        let data = b"HEADpayloadHEADmore".to_vec();
+        let carved = carve_with_mode(data, b"HEAD", FooterMode::Exclusive).expect("carved");
+        assert!(carved.validated);
+        assert_eq!(carved.size, "HEADpayload".len() as u64);
+    }
+
+    #[test]
+    fn last_mode_uses_last_footer() {
+        let data = b"HEADaFOOTbFOOTc".to_vec();
+        let carved = carve_with_mode(data, b"FOOT", FooterMode::Last).expect("carved");
+        assert!(carved.validated);
+        assert_eq!(carved.size, "HEADaFOOTbFOOT".len() as u64);
+    }
+
+    #[test]
+    fn located_modes_report_missing_footer() {
+        let data = b"HEADpayload".to_vec();
+        let carved = carve_with_mode(data, b"FOOT", FooterMode::Last).expect("carved");
+        assert!(!carved.validated);
+        assert!(carved.truncated);
+        assert_eq!(carved.size, "HEADpayload".len() as u64);
+    }
+
+    #[test]
+    fn carves_fixed_length_without_footer() {
+        let header = b"HEAD";
+        let mut data = Vec::new();
+        data.extend_from_slice(header);
+        data.extend_from_slice(&[0xAA; 64]);
+
+        let evidence = SliceEvidence { data };
+        let dir = tempdir().expect("tempdir");
+        let ctx = ExtractionContext {
+            run_id: "run1",
+            output_root: dir.path(),
+            evidence: &evidence,
+        };
+
+        let handler = FooterCarveHandler::new(
+            "custom".to_string(),
+            "bin".to_string(),
+            1,
+            16,
+            vec![header.to_vec()],
+            Vec::new(),
+        );
+
+        let hit = NormalizedHit {
+            global_offset: 0,
+            file_type_id: "custom".to_string(),
+            pattern_id: "header".to_string(),
+        };
+
+        let carved = handler
+            .process_hit(&hit, &ctx)
+            .expect("process")
+            .expect("carved");
+
+        assert_eq!(carved.size, 16);
+        assert!(!carved.truncated);
+        assert!(!carved.validated);
+        assert!(carved.errors.is_empty());
+    }
 }
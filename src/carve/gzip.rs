@@ -1,23 +1,40 @@
 //! GZIP carving handler.
 //!
-//! GZIP streams do not include a reliable end marker without parsing deflate,
-//! so we use a best-effort scan for the next gzip header or EOF.
+//! A member is a header, a raw deflate stream, and an 8-byte trailer with
+//! the CRC-32 and the size (mod 2^32) of the decompressed data. Nothing in
+//! the header gives the compressed length, so the handler inflates the
+//! stream to find where it ends, then checks the trailer against what it
+//! decoded. A member whose trailer does not match, or whose deflate stream
+//! breaks off after decoding some data, is kept with `validated` false.
+//! With `gzip_decompress` the decoded data is also written next to the
+//! carve, without the `.gz` extension, as a `gzip_payload` companion.
 
 use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
 
+use miniz_oxide::inflate::stream::{InflateState, inflate};
+use miniz_oxide::{DataFormat, MZError, MZFlush, MZStatus};
 use sha2::{Digest, Sha256};
 
 use crate::carve::{
-    CarveError, CarveHandler, CarvedFile, ExtractionContext, output_path, write_range,
+    CarveError, CarveHandler, CarvedFile, ExtractionContext, Rejection, crc32_update,
+    create_output, note_companion, note_rejection, output_path, output_writer, write_range,
 };
 use crate::scanner::NormalizedHit;
 
 const GZIP_MAGIC: [u8; 3] = [0x1F, 0x8B, 0x08];
+const TRAILER_LEN: u64 = 8;
+/// Compressed bytes read from the evidence per block.
+const INPUT_BLOCK: usize = 64 * 1024;
+/// Decompressed bytes produced per inflate call.
+const OUTPUT_BLOCK: usize = 64 * 1024;
 
 pub struct GzipCarveHandler {
     extension: String,
     min_size: u64,
     max_size: u64,
+    decompress: bool,
 }
 
 impl GzipCarveHandler {
@@ -26,8 +43,46 @@ impl GzipCarveHandler {
             extension,
             min_size,
             max_size,
+            decompress: false,
         }
     }
+
+    /// Also write the decompressed data of each member (`gzip_decompress`).
+    pub fn with_decompress(mut self, decompress: bool) -> Self {
+        self.decompress = decompress;
+        self
+    }
+}
+
+/// How the deflate stream of a member ended.
+enum StreamEnd {
+    /// The final block was decoded; the trailer starts at this offset.
+    Complete(u64),
+    /// The evidence ended first.
+    Eof(u64),
+    /// `max_size` was reached first.
+    Limit(u64),
+    /// The stream is not valid deflate past this offset.
+    Corrupt(u64),
+}
+
+/// Decompressed data of a member: its CRC-32 and length, and the payload
+/// file when one is written.
+struct Decoded {
+    crc: u32,
+    len: u64,
+    payload: Option<Payload>,
+}
+
+struct Payload {
+    full_path: PathBuf,
+    rel_path: String,
+    writer: BufWriter<File>,
+    written: u64,
+    md5: md5::Context,
+    sha256: Sha256,
+    /// `max_size` stopped the payload before the end of the data.
+    truncated: bool,
 }
 
 impl CarveHandler for GzipCarveHandler {
@@ -56,72 +111,98 @@ impl CarveHandler for GzipCarveHandler {
             &self.extension,
             hit.global_offset,
         )?;
-        let mut file = File::create(&full_path)?;
-        let mut md5 = md5::Context::new();
-        let mut sha256 = Sha256::new();
-
-        let mut validated = false;
-        let mut truncated = false;
-        let mut errors = Vec::new();
+        let payload = if self.decompress {
+            let full_path = full_path.with_extension("");
+            let rel_path = rel_path
+                .strip_suffix(&format!(".{}", self.extension))
+                .unwrap_or(&rel_path)
+                .to_string();
+            Some(Payload {
+                writer: output_writer(create_output(&full_path)?),
+                full_path,
+                rel_path,
+                written: 0,
+                md5: md5::Context::new(),
+                sha256: Sha256::new(),
+                truncated: false,
+            })
+        } else {
+            None
+        };
+        let mut decoded = Decoded {
+            crc: 0,
+            len: 0,
+            payload,
+        };
+        let discard_payload = |decoded: &Decoded| {
+            if let Some(payload) = &decoded.payload {
+                let _ = std::fs::remove_file(&payload.full_path);
+            }
+        };
 
         let max_end = if self.max_size > 0 {
             hit.global_offset.saturating_add(self.max_size)
         } else {
             u64::MAX
         };
+        let stream_start = hit.global_offset.saturating_add(header_len);
+        let end = inflate_member(ctx, stream_start, max_end, self.max_size, &mut decoded)
+            .inspect_err(|_| discard_payload(&decoded))?;
 
-        let mut end_offset = None;
-        let mut offset = hit.global_offset.saturating_add(header_len);
-        let mut carry: Vec<u8> = Vec::new();
-        let buf_size = 64 * 1024;
-
-        while offset < max_end {
-            let remaining = (max_end - offset).min(buf_size as u64) as usize;
-            let mut buf = vec![0u8; remaining];
-            let n = ctx
-                .evidence
-                .read_at(offset, &mut buf)
-                .map_err(|e| CarveError::Evidence(e.to_string()))?;
-            if n == 0 {
-                validated = true;
-                end_offset = Some(offset);
-                break;
+        let mut validated = false;
+        let mut truncated = false;
+        let mut errors = Vec::new();
+        let end_offset = match end {
+            StreamEnd::Complete(trailer) if trailer.saturating_add(TRAILER_LEN) > max_end => {
+                truncated = true;
+                errors.push("max_size reached before gzip trailer".to_string());
+                max_end
             }
-            buf.truncate(n);
-
-            let mut search_buf = carry.clone();
-            search_buf.extend_from_slice(&buf);
-            let mut search_start = 0usize;
-            while let Some(pos) = find_pattern(&search_buf[search_start..], &GZIP_MAGIC) {
-                let absolute = search_start + pos;
-                let gzip_offset = offset
-                    .saturating_sub(carry.len() as u64)
-                    .saturating_add(absolute as u64);
-                if gzip_offset > hit.global_offset {
-                    end_offset = Some(gzip_offset);
+            StreamEnd::Complete(trailer) => match read_exact_at(ctx, trailer, TRAILER_LEN as usize)
+            {
+                Some(bytes) => {
+                    let crc = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                    let isize = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
                     validated = true;
-                    break;
+                    if crc != decoded.crc {
+                        validated = false;
+                        errors.push("gzip crc32 mismatch".to_string());
+                    }
+                    if isize != decoded.len as u32 {
+                        validated = false;
+                        errors.push("gzip isize mismatch".to_string());
+                    }
+                    trailer + TRAILER_LEN
+                }
+                None => {
+                    truncated = true;
+                    errors.push("eof before gzip trailer".to_string());
+                    ctx.evidence.len()
                 }
-                search_start = absolute + 1;
+            },
+            StreamEnd::Eof(offset) => {
+                truncated = true;
+                errors.push("eof before gzip end".to_string());
+                offset
             }
-            if end_offset.is_some() {
-                break;
+            StreamEnd::Limit(offset) => {
+                truncated = true;
+                errors.push("max_size reached before gzip end".to_string());
+                offset
             }
-
-            offset = offset.saturating_add(buf.len() as u64);
-            if buf.len() >= GZIP_MAGIC.len() - 1 {
-                carry = buf[buf.len() - (GZIP_MAGIC.len() - 1)..].to_vec();
-            } else {
-                carry = buf;
+            StreamEnd::Corrupt(offset) => {
+                if decoded.len == 0 {
+                    discard_payload(&decoded);
+                    note_rejection(Rejection::Invalid("deflate stream corrupt"));
+                    return Ok(None);
+                }
+                errors.push("deflate stream corrupt".to_string());
+                offset
             }
-        }
-
-        let end_offset = end_offset.unwrap_or(max_end);
-        if self.max_size > 0 && end_offset >= max_end {
-            truncated = true;
-            errors.push("max_size reached before gzip end".to_string());
-        }
-
+        };
+        let mut file = create_output(&full_path).inspect_err(|_| discard_payload(&decoded))?;
+        let mut md5 = md5::Context::new();
+        let mut sha256 = Sha256::new();
         let (written, eof_truncated) = write_range(
             ctx,
             hit.global_offset,
@@ -129,45 +210,163 @@ impl CarveHandler for GzipCarveHandler {
             &mut file,
             &mut md5,
             &mut sha256,
-        )?;
-        if eof_truncated {
+        )
+        .inspect_err(|_| discard_payload(&decoded))?;
+        if eof_truncated && !truncated {
             truncated = true;
-            if !errors.iter().any(|e| e.contains("eof")) {
-                errors.push("eof before gzip end".to_string());
-            }
+            validated = false;
+            errors.push("eof before gzip end".to_string());
         }
 
         if written < self.min_size {
+            note_rejection(Rejection::MinSize {
+                size: written,
+                min_size: self.min_size,
+            });
             let _ = std::fs::remove_file(&full_path);
+            discard_payload(&decoded);
             return Ok(None);
         }
 
-        let md5_hex = format!("{:x}", md5.compute());
-        let sha256_hex = hex::encode(sha256.finalize());
         let global_end = if written == 0 {
             hit.global_offset
         } else {
             hit.global_offset + written - 1
         };
-
-        Ok(Some(CarvedFile {
+        let carved = CarvedFile {
             run_id: ctx.run_id.to_string(),
             file_type: self.file_type().to_string(),
+            signature_type: self.file_type().to_string(),
             path: rel_path,
             extension: self.extension.clone(),
             global_start: hit.global_offset,
             global_end,
             size: written,
-            md5: Some(md5_hex),
-            sha256: Some(sha256_hex),
+            md5: Some(format!("{:x}", md5.compute())),
+            sha256: Some(hex::encode(sha256.finalize())),
             validated,
             truncated,
             errors,
             pattern_id: Some(hit.pattern_id.clone()),
-        }))
+        };
+
+        if let Some(mut payload) = decoded.payload {
+            if let Err(err) = payload.writer.flush() {
+                let _ = std::fs::remove_file(&payload.full_path);
+                return Err(err.into());
+            }
+            let mut errors = carved.errors.clone();
+            if payload.truncated {
+                errors.push("max_size reached before payload end".to_string());
+            }
+            note_companion(CarvedFile {
+                run_id: carved.run_id.clone(),
+                file_type: "gzip_payload".to_string(),
+                signature_type: self.file_type().to_string(),
+                path: payload.rel_path,
+                extension: String::new(),
+                // The member the payload was decoded from.
+                global_start: carved.global_start,
+                global_end: carved.global_end,
+                size: payload.written,
+                md5: Some(format!("{:x}", payload.md5.compute())),
+                sha256: Some(hex::encode(payload.sha256.finalize())),
+                validated: carved.validated,
+                truncated: carved.truncated || payload.truncated,
+                errors,
+                pattern_id: carved.pattern_id.clone(),
+            });
+        }
+
+        Ok(Some(carved))
     }
 }
 
+/// Inflate the raw deflate stream at `start`, hashing and counting its
+/// output into `decoded` and writing up to `max_size` bytes of it (0 = no
+/// limit) to the payload, if any. Compressed bytes at or past `max_end` are
+/// not read.
+fn inflate_member(
+    ctx: &ExtractionContext,
+    start: u64,
+    max_end: u64,
+    max_size: u64,
+    decoded: &mut Decoded,
+) -> Result<StreamEnd, CarveError> {
+    let mut state = InflateState::new_boxed(DataFormat::Raw);
+    let mut out = vec![0u8; OUTPUT_BLOCK];
+    let mut input = Vec::with_capacity(INPUT_BLOCK);
+    let mut input_pos = 0usize;
+    // Evidence offset of `input[0]`.
+    let mut input_start = start;
+    let mut eof = false;
+
+    loop {
+        ctx.check_cancelled()?;
+        if input_pos == input.len() && !eof {
+            input_start = input_start.saturating_add(input.len() as u64);
+            let want = (max_end.saturating_sub(input_start)).min(INPUT_BLOCK as u64) as usize;
+            input.resize(want, 0);
+            let n = if want == 0 {
+                0
+            } else {
+                ctx.evidence
+                    .read_at(input_start, &mut input)
+                    .map_err(|e| CarveError::Evidence(e.to_string()))?
+            };
+            input.truncate(n);
+            input_pos = 0;
+            eof = n == 0;
+        }
+        let consumed_at = input_start + input_pos as u64;
+
+        let result = inflate(&mut state, &input[input_pos..], &mut out, MZFlush::None);
+        input_pos += result.bytes_consumed;
+        let produced = &out[..result.bytes_written];
+        decoded.crc = crc32_update(decoded.crc, produced);
+        decoded.len = decoded.len.saturating_add(produced.len() as u64);
+        if let Some(payload) = decoded.payload.as_mut() {
+            write_payload(payload, produced, max_size)?;
+        }
+
+        let progressed = result.bytes_consumed > 0 || result.bytes_written > 0;
+        match result.status {
+            Ok(MZStatus::StreamEnd) => {
+                return Ok(StreamEnd::Complete(input_start + input_pos as u64));
+            }
+            Ok(_) | Err(MZError::Buf) if progressed => {}
+            Ok(_) | Err(MZError::Buf) if input_pos == input.len() => {
+                // Out of input.
+                let end = input_start + input_pos as u64;
+                return Ok(if end >= max_end {
+                    StreamEnd::Limit(end)
+                } else {
+                    StreamEnd::Eof(end)
+                });
+            }
+            // No progress with input left: the stream is stuck.
+            Ok(_) | Err(_) => return Ok(StreamEnd::Corrupt(consumed_at)),
+        }
+    }
+}
+
+fn write_payload(payload: &mut Payload, data: &[u8], max_size: u64) -> Result<(), CarveError> {
+    let room = if max_size > 0 {
+        max_size.saturating_sub(payload.written)
+    } else {
+        u64::MAX
+    };
+    let keep = &data[..data.len().min(room.min(usize::MAX as u64) as usize)];
+    if keep.len() < data.len() {
+        payload.truncated = true;
+    }
+    payload.writer.write_all(keep)?;
+    payload.md5.consume(keep);
+    payload.sha256.update(keep);
+    payload.written = payload.written.saturating_add(keep.len() as u64);
+    Ok(())
+}
+
 fn parse_gzip_header(ctx: &ExtractionContext, offset: u64) -> Result<u64, CarveError> {
     let fixed = read_exact_at(ctx, offset, 10)
         .ok_or_else(|| CarveError::Invalid("gzip header too short".to_string()))?;
@@ -216,21 +415,6 @@ fn skip_cstring(ctx: &ExtractionContext, mut offset: u64) -> Result<u64, CarveEr
     Err(CarveError::Invalid("gzip string too long".to_string()))
 }
 
-fn find_pattern(haystack: &[u8], needle: &[u8]) -> Option<usize> {
-    if needle.is_empty() || haystack.len() < needle.len() {
-        return None;
-    }
-    let first = needle[0];
-    let mut i = 0usize;
-    while i + needle.len() <= haystack.len() {
-        if haystack[i] == first && &haystack[i..i + needle.len()] == needle {
-            return Some(i);
-        }
-        i += 1;
-    }
-    None
-}
-
 fn read_exact_at(ctx: &ExtractionContext, offset: u64, len: usize) -> Option<Vec<u8>> {
     let mut buf = vec![0u8; len];
     let n = ctx.evidence.read_at(offset, &mut buf).ok()?;
@@ -243,10 +427,10 @@ fn read_exact_at(ctx: &ExtractionContext, offset: u64, len: usize) -> Option<Vec
 #[cfg(test)]
 mod tests {
     use super::GzipCarveHandler;
-    use crate::carve::{CarveHandler, ExtractionContext};
+    use crate::carve::{CarveHandler, CarvedFile, ExtractionContext, crc32, take_companions};
     use crate::evidence::{EvidenceError, EvidenceSource};
     use crate::scanner::NormalizedHit;
-    use tempfile::tempdir;
+    use tempfile::{TempDir, tempdir};
 
     struct SliceEvidence {
         data: Vec<u8>,
@@ -268,22 +452,27 @@ mod tests {
         }
     }
 
-    fn minimal_gzip_payload() -> Vec<u8> {
-        let mut data = Vec::new();
-        data.extend_from_slice(&[0x1F, 0x8B, 0x08, 0x00]);
-        data.extend_from_slice(&[0x00; 6]);
-        data.extend_from_slice(b"DATA");
-        data
+    fn payload() -> Vec<u8> {
+        (0..200_000u32)
+            .flat_map(|i| format!("line {} of the log\n", i % 977).into_bytes())
+            .collect()
     }
 
-    #[test]
-    fn carves_until_next_gzip_header() {
-        let mut data = minimal_gzip_payload();
-        let second = minimal_gzip_payload();
-        data.extend_from_slice(&second);
+    /// A gzip member named `app.log` holding `data`.
+    fn member(data: &[u8]) -> Vec<u8> {
+        let mut out = vec![0x1F, 0x8B, 0x08, 0x08, 0, 0, 0, 0, 0x00, 0x03];
+        out.extend_from_slice(b"app.log\0");
+        out.extend_from_slice(&miniz_oxide::deflate::compress_to_vec(data, 6));
+        out.extend_from_slice(&crc32(data).to_le_bytes());
+        out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        out
+    }
 
-        let evidence = SliceEvidence { data: data.clone() };
-        let handler = GzipCarveHandler::new("gz".to_string(), 0, 0);
+    fn carve(
+        image: Vec<u8>,
+        handler: GzipCarveHandler,
+    ) -> (Option<CarvedFile>, Vec<CarvedFile>, TempDir) {
+        let evidence = SliceEvidence { data: image };
         let hit = NormalizedHit {
             global_offset: 0,
             file_type_id: "gzip".to_string(),
@@ -295,10 +484,84 @@ mod tests {
             output_root: dir.path(),
             evidence: &evidence,
         };
+        take_companions();
+        let file = handler.process_hit(&hit, &ctx).expect("process");
+        (file, take_companions(), dir)
+    }
+
+    fn handler() -> GzipCarveHandler {
+        GzipCarveHandler::new("gz".to_string(), 18, 0)
+    }
+
+    #[test]
+    fn carves_member_up_to_its_trailer() {
+        let gz = member(&payload());
+        let mut image = gz.clone();
+        // A second member right after must not be swallowed.
+        image.extend_from_slice(&member(b"second"));
+        let (file, companions, dir) = carve(image, handler());
+        let file = file.expect("carved");
+        assert!(file.validated, "{:?}", file.errors);
+        assert!(!file.truncated);
+        assert_eq!(file.size, gz.len() as u64);
+        assert_eq!(std::fs::read(dir.path().join(&file.path)).unwrap(), gz);
+        assert!(companions.is_empty());
+    }
 
-        let carved = handler.process_hit(&hit, &ctx).expect("process");
-        let carved = carved.expect("carved");
-        assert!(carved.validated);
-        assert_eq!(carved.size as usize, minimal_gzip_payload().len());
+    #[test]
+    fn keeps_members_failing_the_trailer_unvalidated() {
+        let mut gz = member(&payload());
+        let crc_at = gz.len() - 8;
+        gz[crc_at] ^= 0xFF;
+        let (file, _, _dir) = carve(gz.clone(), handler());
+        let file = file.expect("carved");
+        assert!(!file.validated);
+        assert_eq!(file.size, gz.len() as u64);
+        assert_eq!(file.errors, vec!["gzip crc32 mismatch".to_string()]);
+    }
+
+    #[test]
+    fn marks_members_cut_by_the_evidence_or_max_size() {
+        let gz = member(&payload());
+        let (file, _, _dir) = carve(gz[..gz.len() / 2].to_vec(), handler());
+        let file = file.expect("carved");
+        assert!(file.truncated && !file.validated);
+        assert_eq!(file.size, (gz.len() / 2) as u64);
+
+        let limited = GzipCarveHandler::new("gz".to_string(), 18, 1000);
+        let (file, _, _dir) = carve(gz, limited);
+        let file = file.expect("carved");
+        assert!(file.truncated && !file.validated);
+        assert_eq!(file.size, 1000);
+    }
+
+    #[test]
+    fn rejects_headers_without_a_deflate_stream() {
+        let mut image = vec![0x1F, 0x8B, 0x08, 0x00, 0, 0, 0, 0, 0x00, 0x03];
+        // Block type 3 is reserved.
+        image.extend_from_slice(&[0xFF; 64]);
+        let (file, _, dir) = carve(image, handler());
+        assert!(file.is_none());
+        assert!(!dir.path().join("gzip/gzip_000000000000.gz").exists());
+    }
+
+    #[test]
+    fn writes_payload_as_companion() {
+        let data = payload();
+        let (file, companions, dir) = carve(member(&data), handler().with_decompress(true));
+        let file = file.expect("carved");
+        assert_eq!(companions.len(), 1);
+        let payload = &companions[0];
+        assert_eq!(payload.file_type, "gzip_payload");
+        assert_eq!(payload.path, "gzip/gzip_000000000000");
+        assert_eq!(payload.size, data.len() as u64);
+        assert_eq!(payload.global_start, file.global_start);
+        assert_eq!(payload.global_end, file.global_end);
+        assert!(payload.validated);
+        assert_eq!(std::fs::read(dir.path().join(&payload.path)).unwrap(), data);
+
+        let (file, companions, dir) = carve(vec![0x1F, 0x8B, 0x08, 0x00], handler());
+        assert!(file.is_none() && companions.is_empty());
+        assert!(!dir.path().join("gzip/gzip_000000000000").exists());
     }
 }
@@ -0,0 +1,518 @@
+//! HEIF images: HEIC photos from phones and cameras, AVIF, and HEIF image
+//! sequences. All are ISO base media files whose `ftyp` names a HEIF brand.
+//!
+//! The pattern matches `ftyp` and the major brand, four bytes into the file.
+//! Top-level boxes are walked from the `ftyp` while their types are ones a
+//! HEIF file uses; the first unknown type or impossible size ends the walk.
+//! Still images keep their items in `meta`: the handler (`hdlr`) must be
+//! `pict`, and the item locations (`iloc`) give where the coded data lies,
+//! usually in an `mdat` after `meta`. An `mdat` of size 0 (to end of file) is
+//! sized by the last item extent, and item data past the walked boxes extends
+//! the carve and is reported.
+//!
+//! AVIF files (an `avif` or `avis` brand) are written as `.avif`; the rest
+//! get the configured extension.
+
+use std::io::Write;
+
+use sha2::{Digest, Sha256};
+
+use crate::carve::{
+    CarveError, CarveHandler, CarvedFile, ExtractionContext, Rejection, create_output,
+    note_rejection, output_path, write_range,
+};
+use crate::scanner::NormalizedHit;
+
+/// The pattern matches `ftyp`, this far into the file.
+const FTYP_TYPE_OFFSET: u64 = 4;
+/// `ftyp` holds a major brand, a minor version and up to 60 compatible brands.
+const FTYP_MIN_LEN: u64 = 16;
+const FTYP_MAX_LEN: u64 = 256;
+/// Largest `meta` box parsed; item tables of large grids are a few KiB.
+const META_LIMIT: u64 = 4 * 1024 * 1024;
+
+/// Brands of HEIF files. `mif1` and `msf1` are the structural brands that
+/// HEIC and AVIF writers both list.
+const HEIF_BRANDS: [&[u8; 4]; 12] = [
+    b"mif1", b"msf1", b"heic", b"heix", b"heim", b"heis", b"hevc", b"hevx", b"hevm", b"hevs",
+    b"avif", b"avis",
+];
+const AVIF_BRANDS: [&[u8; 4]; 2] = [b"avif", b"avis"];
+
+/// Top-level boxes a HEIF file may hold after `ftyp`.
+const TOP_LEVEL: [&[u8; 4]; 10] = [
+    b"meta", b"mdat", b"moov", b"free", b"skip", b"uuid", b"wide", b"moof", b"mfra", b"idat",
+];
+
+/// Whether `brand` is a HEIF major brand; the MP4 handler leaves these files
+/// to this one.
+pub fn is_heif_brand(brand: &[u8]) -> bool {
+    HEIF_BRANDS.iter().any(|b| b.as_slice() == brand)
+}
+
+pub struct HeifCarveHandler {
+    extension: String,
+    min_size: u64,
+    max_size: u64,
+}
+
+impl HeifCarveHandler {
+    pub fn new(extension: String, min_size: u64, max_size: u64) -> Self {
+        Self {
+            extension,
+            min_size,
+            max_size,
+        }
+    }
+}
+
+/// Top-level boxes seen by the walk, as offsets from the file start.
+#[derive(Debug, Default)]
+struct Layout {
+    /// End of the last complete box.
+    end: u64,
+    meta: Option<(u64, u64)>,
+    has_moov: bool,
+    /// Payload start of an `mdat` that runs to the end of the file.
+    open_mdat: Option<u64>,
+    /// The walk reached `max_size` or the end of the evidence.
+    cut: bool,
+}
+
+impl CarveHandler for HeifCarveHandler {
+    fn file_type(&self) -> &str {
+        "heif"
+    }
+
+    fn extension(&self) -> &str {
+        &self.extension
+    }
+
+    fn process_hit(
+        &self,
+        hit: &NormalizedHit,
+        ctx: &ExtractionContext,
+    ) -> Result<Option<CarvedFile>, CarveError> {
+        let Some(start) = hit.global_offset.checked_sub(FTYP_TYPE_OFFSET) else {
+            note_rejection(Rejection::Invalid("ftyp before the evidence start"));
+            return Ok(None);
+        };
+        let mut raw = [0u8; FTYP_MAX_LEN as usize];
+        let n = read_full(ctx, start, &mut raw)?;
+        let brands = match parse_ftyp(&raw[..n]) {
+            Ok(brands) => brands,
+            Err(reason) => {
+                note_rejection(Rejection::Invalid(reason));
+                return Ok(None);
+            }
+        };
+        let avif = brands
+            .iter()
+            .any(|b| AVIF_BRANDS.iter().any(|a| a.as_slice() == b.as_slice()));
+
+        let layout = self.walk(ctx, start, be_u32(&raw) as u64)?;
+        let mut data_end = None;
+        if let Some((offset, size)) = layout.meta {
+            if size > META_LIMIT {
+                note_rejection(Rejection::Invalid("HEIF meta box too large"));
+                return Ok(None);
+            }
+            let mut meta = vec![0u8; size as usize];
+            if read_full(ctx, start + offset, &mut meta)? < meta.len() {
+                note_rejection(Rejection::Invalid("HEIF meta box cut short"));
+                return Ok(None);
+            }
+            match parse_meta(&meta) {
+                Ok(end) => data_end = end,
+                Err(reason) => {
+                    note_rejection(Rejection::Invalid(reason));
+                    return Ok(None);
+                }
+            }
+        } else if !layout.has_moov {
+            note_rejection(Rejection::Invalid("HEIF file without meta or moov"));
+            return Ok(None);
+        }
+
+        let mut errors = Vec::new();
+        let mut end = layout.end;
+        if let Some(payload) = layout.open_mdat {
+            match data_end {
+                Some(data_end) => end = data_end.max(payload),
+                None => {
+                    end = payload;
+                    errors.push("mdat runs to end of file without item locations".to_string());
+                }
+            }
+        } else if let Some(data_end) = data_end.filter(|&e| e > layout.end) {
+            end = data_end;
+            errors.push("item data past the last box".to_string());
+        }
+        if layout.cut && layout.open_mdat.is_none() {
+            errors.push("box walk stopped before HEIF end".to_string());
+        }
+        let capped = self.max_size > 0 && end > self.max_size;
+        let target = if capped { self.max_size } else { end };
+
+        let extension = if avif {
+            "avif"
+        } else {
+            self.extension.as_str()
+        };
+        let (full_path, rel_path) =
+            output_path(ctx.output_root, self.file_type(), extension, start)?;
+        let mut file = create_output(&full_path)?;
+        let mut md5 = md5::Context::new();
+        let mut sha256 = Sha256::new();
+        let (size, short_read) =
+            write_range(ctx, start, start + target, &mut file, &mut md5, &mut sha256)?;
+        file.flush()?;
+        if size < self.min_size {
+            note_rejection(Rejection::MinSize {
+                size,
+                min_size: self.min_size,
+            });
+            let _ = std::fs::remove_file(&full_path);
+            return Ok(None);
+        }
+
+        let mut truncated = layout.cut;
+        if short_read {
+            truncated = true;
+            errors.push("evidence ended before HEIF end".to_string());
+        } else if capped {
+            truncated = true;
+            errors.push("max_size reached".to_string());
+        }
+
+        Ok(Some(CarvedFile {
+            run_id: ctx.run_id.to_string(),
+            file_type: self.file_type().to_string(),
+            signature_type: self.file_type().to_string(),
+            path: rel_path,
+            extension: extension.to_string(),
+            global_start: start,
+            global_end: start + size - 1,
+            size,
+            md5: Some(format!("{:x}", md5.compute())),
+            sha256: Some(hex::encode(sha256.finalize())),
+            validated: errors.is_empty(),
+            truncated,
+            errors,
+            pattern_id: Some(hit.pattern_id.clone()),
+        }))
+    }
+}
+
+impl HeifCarveHandler {
+    /// Walk the top-level boxes after an `ftyp` of `ftyp_len` bytes.
+    fn walk(
+        &self,
+        ctx: &ExtractionContext,
+        start: u64,
+        ftyp_len: u64,
+    ) -> Result<Layout, CarveError> {
+        let mut layout = Layout {
+            end: ftyp_len,
+            ..Layout::default()
+        };
+        loop {
+            ctx.check_cancelled()?;
+            let offset = layout.end;
+            if self.max_size > 0 && offset >= self.max_size {
+                layout.cut = true;
+                break;
+            }
+            let mut header = [0u8; 16];
+            let n = read_full(ctx, start + offset, &mut header)?;
+            if n < 8 {
+                layout.cut = start + offset < ctx.evidence.len();
+                break;
+            }
+            let box_type = &header[4..8];
+            if !TOP_LEVEL.iter().any(|t| t.as_slice() == box_type) {
+                break;
+            }
+            let (size, header_len) = match be_u32(&header) {
+                0 if box_type == b"mdat" => {
+                    layout.open_mdat = Some(offset + 8);
+                    break;
+                }
+                1 if n == 16 => (u64::from_be_bytes(header[8..16].try_into().unwrap()), 16),
+                size if size >= 8 => (u64::from(size), 8),
+                _ => break,
+            };
+            if size < header_len {
+                break;
+            }
+            match box_type {
+                b"meta" => layout.meta = Some((offset, size)),
+                b"moov" => layout.has_moov = true,
+                _ => {}
+            }
+            let Some(next) = offset.checked_add(size) else {
+                break;
+            };
+            if start.saturating_add(next) > ctx.evidence.len() {
+                // The box claims more than the evidence holds; keep it up to the end.
+                layout.end = next;
+                break;
+            }
+            layout.end = next;
+        }
+        Ok(layout)
+    }
+}
+
+/// The major and compatible brands of an `ftyp` box at the start of `data`.
+fn parse_ftyp(data: &[u8]) -> Result<Vec<[u8; 4]>, &'static str> {
+    if data.len() < FTYP_MIN_LEN as usize || &data[4..8] != b"ftyp" {
+        return Err("ftyp box not found");
+    }
+    let len = be_u32(data) as u64;
+    if !(FTYP_MIN_LEN..=FTYP_MAX_LEN).contains(&len)
+        || !len.is_multiple_of(4)
+        || len as usize > data.len()
+    {
+        return Err("ftyp box size out of range");
+    }
+    if !is_heif_brand(&data[8..12]) {
+        return Err("ftyp major brand is not HEIF");
+    }
+    let mut brands = vec![data[8..12].try_into().unwrap()];
+    brands.extend(
+        data[16..len as usize]
+            .chunks_exact(4)
+            .map(|b| <[u8; 4]>::try_from(b).unwrap()),
+    );
+    Ok(brands)
+}
+
+/// Check the handler of a `meta` box and return the end of its last item
+/// extent stored in the file, if any.
+fn parse_meta(meta: &[u8]) -> Result<Option<u64>, &'static str> {
+    // Box header, then the version and flags of the full box.
+    let mut offset = if be_u32(meta) == 1 { 16 } else { 8 } + 4;
+    let mut handler = None;
+    let mut data_end = None;
+    while offset + 8 <= meta.len() {
+        let size = be_u32(&meta[offset..]) as usize;
+        if size < 8 || offset + size > meta.len() {
+            return Err("HEIF meta child box size out of range");
+        }
+        let body = &meta[offset + 8..offset + size];
+        match &meta[offset + 4..offset + 8] {
+            b"hdlr" if body.len() >= 12 => handler = Some(&body[8..12]),
+            b"iloc" => data_end = parse_iloc(body).ok_or("HEIF iloc box malformed")?,
+            _ => {}
+        }
+        offset += size;
+    }
+    match handler {
+        Some(b"pict") => Ok(data_end),
+        Some(_) => Err("HEIF meta handler is not pict"),
+        None => Err("HEIF meta box without handler"),
+    }
+}
+
+/// The end of the last extent an `iloc` box places in the file itself.
+/// Items in `idat`, in other items, or in other files do not count.
+fn parse_iloc(body: &[u8]) -> Option<Option<u64>> {
+    let mut r = Reader { data: body, pos: 0 };
+    let version = r.uint(1)?;
+    r.uint(3)?;
+    if version > 2 {
+        return None;
+    }
+    let sizes = r.uint(1)?;
+    let (offset_size, length_size) = ((sizes >> 4) as usize, (sizes & 0xF) as usize);
+    let sizes = r.uint(1)?;
+    let base_offset_size = (sizes >> 4) as usize;
+    let index_size = if version > 0 {
+        (sizes & 0xF) as usize
+    } else {
+        0
+    };
+    if [offset_size, length_size, base_offset_size, index_size]
+        .iter()
+        .any(|s| ![0, 4, 8].contains(s))
+    {
+        return None;
+    }
+    let id_size = if version < 2 { 2 } else { 4 };
+    let item_count = r.uint(id_size)?;
+    let mut end = None;
+    for _ in 0..item_count {
+        r.uint(id_size)?;
+        let method = if version > 0 { r.uint(2)? & 0xF } else { 0 };
+        let data_reference = r.uint(2)?;
+        let base = r.uint(base_offset_size)?;
+        let extent_count = r.uint(2)?;
+        for _ in 0..extent_count {
+            r.uint(index_size)?;
+            let extent_offset = r.uint(offset_size)?;
+            let extent_length = r.uint(length_size)?;
+            if method == 0 && data_reference == 0 && extent_length > 0 {
+                let extent_end = base
+                    .checked_add(extent_offset)
+                    .and_then(|o| o.checked_add(extent_length))?;
+                end = end.max(Some(extent_end));
+            }
+        }
+    }
+    Some(end)
+}
+
+/// Big-endian fields of 0 to 8 bytes.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn uint(&mut self, len: usize) -> Option<u64> {
+        let bytes = self.data.get(self.pos..self.pos + len)?;
+        self.pos += len;
+        Some(bytes.iter().fold(0, |v, &b| (v << 8) | u64::from(b)))
+    }
+}
+
+fn read_full(ctx: &ExtractionContext, offset: u64, buf: &mut [u8]) -> Result<usize, CarveError> {
+    let lens = ctx
+        .evidence
+        .read_vectored_at(&mut [(offset, buf)])
+        .map_err(|e| CarveError::Evidence(e.to_string()))?;
+    Ok(lens[0])
+}
+
+fn be_u32(data: &[u8]) -> u32 {
+    u32::from_be_bytes(data[..4].try_into().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::carve::support::carve_image;
+
+    fn boxed(box_type: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut out = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+        out.extend_from_slice(box_type);
+        out.extend_from_slice(body);
+        out
+    }
+
+    /// An image with one item of `data` in an `mdat` after `meta`; with
+    /// `open_mdat` the `mdat` declares size 0.
+    fn heif_bytes(brand: &[u8; 4], handler: &[u8; 4], data: &[u8], open_mdat: bool) -> Vec<u8> {
+        let mut ftyp = brand.to_vec();
+        ftyp.extend_from_slice(&0u32.to_be_bytes());
+        ftyp.extend_from_slice(b"mif1");
+        ftyp.extend_from_slice(brand);
+        let ftyp = boxed(b"ftyp", &ftyp);
+
+        let mut hdlr = vec![0u8; 8];
+        hdlr.extend_from_slice(handler);
+        hdlr.extend_from_slice(&[0u8; 13]);
+        let hdlr = boxed(b"hdlr", &hdlr);
+        // Version 0, 4-byte offsets and lengths, no base offset, one item.
+        let iloc_len = 8 + 4 + 2 + 2 + 2 + 2 + 2 + 4 + 4;
+        let meta_len = 12 + hdlr.len() + iloc_len;
+        let data_offset = (ftyp.len() + meta_len + 8) as u32;
+        let mut iloc = vec![0, 0, 0, 0, 0x44, 0x00];
+        iloc.extend_from_slice(&1u16.to_be_bytes());
+        iloc.extend_from_slice(&1u16.to_be_bytes());
+        iloc.extend_from_slice(&0u16.to_be_bytes());
+        iloc.extend_from_slice(&1u16.to_be_bytes());
+        iloc.extend_from_slice(&data_offset.to_be_bytes());
+        iloc.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        let mut meta = vec![0u8; 4];
+        meta.extend_from_slice(&hdlr);
+        meta.extend_from_slice(&boxed(b"iloc", &iloc));
+        let meta = boxed(b"meta", &meta);
+        assert_eq!(meta.len(), meta_len);
+
+        let mut mdat = boxed(b"mdat", data);
+        if open_mdat {
+            mdat[..4].copy_from_slice(&0u32.to_be_bytes());
+        }
+        [ftyp, meta, mdat].concat()
+    }
+
+    #[test]
+    fn carves_heic_and_avif_by_box_walk() {
+        let heic = heif_bytes(b"heic", b"pict", &[0xAB; 300], false);
+        let mut image = vec![0u8; 512];
+        image.extend_from_slice(&heic);
+        image.extend_from_slice(&[0x55; 1024]);
+        let carved = carve_image(
+            &HeifCarveHandler::new("heic".to_string(), 32, 0),
+            &image,
+            512 + FTYP_TYPE_OFFSET,
+        )
+        .0
+        .expect("heic");
+        assert!(carved.validated, "{:?}", carved.errors);
+        assert_eq!(carved.size, heic.len() as u64);
+        assert_eq!(carved.extension, "heic");
+
+        let avif = heif_bytes(b"avif", b"pict", &[0xCD; 100], false);
+        let carved = carve_image(
+            &HeifCarveHandler::new("heic".to_string(), 32, 0),
+            &[avif.clone(), vec![0x55; 64]].concat(),
+            0 + FTYP_TYPE_OFFSET,
+        )
+        .0
+        .expect("avif");
+        assert_eq!(carved.size, avif.len() as u64);
+        assert!(carved.path.ends_with(".avif"));
+    }
+
+    #[test]
+    fn sizes_open_mdat_by_item_locations() {
+        let heic = heif_bytes(b"heic", b"pict", &[0xAB; 300], true);
+        let carved = carve_image(
+            &HeifCarveHandler::new("heic".to_string(), 32, 0),
+            &[heic.clone(), vec![0x55; 4096]].concat(),
+            0 + FTYP_TYPE_OFFSET,
+        )
+        .0
+        .expect("heic");
+        assert!(carved.validated, "{:?}", carved.errors);
+        assert_eq!(carved.size, heic.len() as u64);
+
+        let cut = &heic[..heic.len() - 100];
+        let carved = carve_image(
+            &HeifCarveHandler::new("heic".to_string(), 32, 0),
+            cut,
+            0 + FTYP_TYPE_OFFSET,
+        )
+        .0
+        .expect("cut heic");
+        assert!(carved.truncated);
+        assert_eq!(carved.size, cut.len() as u64);
+    }
+
+    #[test]
+    fn rejects_non_image_meta_and_other_brands() {
+        let video = heif_bytes(b"heic", b"vide", &[0xAB; 300], false);
+        assert!(
+            carve_image(
+                &HeifCarveHandler::new("heic".to_string(), 32, 0),
+                &video,
+                0 + FTYP_TYPE_OFFSET
+            )
+            .0
+            .is_none()
+        );
+        let mp4 = heif_bytes(b"isom", b"pict", &[0xAB; 300], false);
+        assert!(
+            carve_image(
+                &HeifCarveHandler::new("heic".to_string(), 32, 0),
+                &mp4,
+                0 + FTYP_TYPE_OFFSET
+            )
+            .0
+            .is_none()
+        );
+    }
+}
@@ -1,28 +1,68 @@
 //! ICO/CUR carving handler.
 //!
-//! ICO files have a small header with directory entries containing offsets/sizes.
-//! Enhanced validation verifies that at least one entry contains valid BMP or PNG data.
-
-use std::fs::File;
+//! ICO files have a small header with directory entries containing offsets/sizes;
+//! the file ends after the image that ends last. Each image is a PNG or a DIB
+//! (a BMP without its file header) whose dimensions should match its directory
+//! entry, and images may not overlap. A hit needs PNG or DIB data at one entry
+//! at least; images that disagree with their entry, or entries pointing at
+//! other data, keep the file unvalidated.
+//! Cursors are written as `.cur`.
 
 use sha2::{Digest, Sha256};
 
 use crate::carve::{
-    CarveError, CarveHandler, CarvedFile, ExtractionContext, output_path, write_range,
+    CarveError, CarveHandler, CarvedFile, DeclaredSize, ExtractionContext, Rejection,
+    create_output, note_declared_size, note_rejection, output_path, write_range,
 };
 use crate::scanner::NormalizedHit;
 
-/// BMP signature at start of image data within ICO
-const BMP_HEADER_MAGIC: [u8; 2] = [0x28, 0x00]; // BITMAPINFOHEADER size (40) in LE
 /// PNG signature at start of image data within ICO
 const PNG_HEADER_MAGIC: [u8; 8] = [0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A];
+/// BITMAPINFOHEADER size, the first field of an embedded DIB.
+const DIB_HEADER_LEN: u32 = 40;
+
+const ICONDIR_LEN: u64 = 6;
+const ENTRY_LEN: usize = 16;
+const TYPE_ICON: u16 = 1;
+const TYPE_CURSOR: u16 = 2;
 
 /// Maximum reasonable icon entries (Windows typically uses 1-10)
 const MAX_ICON_ENTRIES: usize = 64;
 /// Maximum reasonable single icon image size (256x256 @ 32bpp + overhead)
 const MAX_SINGLE_IMAGE_SIZE: u64 = 512 * 1024; // 512 KB per image
-/// Maximum reasonable total ICO size
-const MAX_REASONABLE_ICO_SIZE: u64 = 4 * 1024 * 1024; // 4 MB total
+
+/// One `ICONDIRENTRY`.
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    /// Width and height in pixels; the directory stores 256 as 0.
+    width: u32,
+    height: u32,
+    size: u64,
+    offset: u64,
+}
+
+impl Entry {
+    fn parse(raw: &[u8]) -> Self {
+        let dimension = |b: u8| if b == 0 { 256 } else { u32::from(b) };
+        Self {
+            width: dimension(raw[0]),
+            height: dimension(raw[1]),
+            size: u64::from(le_u32(raw, 8)),
+            offset: u64::from(le_u32(raw, 12)),
+        }
+    }
+}
+
+/// What the data at an entry's offset turned out to be.
+#[derive(Debug, PartialEq, Eq)]
+enum ImageCheck {
+    /// A PNG or DIB matching the entry.
+    Valid,
+    /// A PNG or DIB whose dimensions or size disagree with the entry.
+    Mismatch(&'static str),
+    /// Neither a PNG nor a DIB.
+    Invalid,
+}
 
 pub struct IcoCarveHandler {
     extension: String,
@@ -39,35 +79,57 @@ impl IcoCarveHandler {
         }
     }
 
-    /// Validate that data at the given offset looks like valid BMP or PNG image data
-    fn validate_image_data(ctx: &ExtractionContext, offset: u64, size: u64) -> bool {
-        if size < 8 {
-            return false;
+    /// Check the image an entry points to, `offset` being its evidence offset.
+    fn check_image(ctx: &ExtractionContext, offset: u64, entry: &Entry) -> ImageCheck {
+        if entry.size < 8 {
+            return ImageCheck::Invalid;
         }
-        let header = match read_exact_at(ctx, offset, 8) {
-            Some(h) => h,
-            None => return false,
+        let Some(header) = read_exact_at(ctx, offset, DIB_HEADER_LEN as usize) else {
+            return ImageCheck::Invalid;
         };
 
-        // Check for PNG signature (embedded PNG in ICO)
+        // Embedded PNG: the IHDR chunk follows the signature.
         if header.starts_with(&PNG_HEADER_MAGIC) {
-            return true;
+            if &header[12..16] != b"IHDR" {
+                return ImageCheck::Invalid;
+            }
+            let width = be_u32(&header, 16);
+            let height = be_u32(&header, 20);
+            // Entries of images larger than 256 pixels hold 0 (256).
+            let fits = |dim: u32, listed: u32| dim == listed || (listed == 256 && dim > 256);
+            if !fits(width, entry.width) || !fits(height, entry.height) {
+                return ImageCheck::Mismatch("PNG dimensions differ from the directory");
+            }
+            return ImageCheck::Valid;
         }
 
-        // Check for BMP DIB header (BITMAPINFOHEADER starts with size=40 as u32 LE)
         // ICO embeds BMP without the BM file header, so we look for BITMAPINFOHEADER
-        if header[0..2] == BMP_HEADER_MAGIC {
-            // Additional validation: check biWidth and biHeight are reasonable
-            if header.len() >= 8 {
-                let width = i32::from_le_bytes([header[4], header[5], header[6], header[7]]);
-                // Width should be positive and <= 256 for ICO
-                if width > 0 && width <= 256 {
-                    return true;
-                }
+        if le_u32(&header, 0) != DIB_HEADER_LEN {
+            return ImageCheck::Invalid;
+        }
+        let width = le_u32(&header, 4);
+        let height = le_u32(&header, 8);
+        let planes = le_u16(&header, 12);
+        let bpp = le_u16(&header, 14);
+        let compression = le_u32(&header, 16);
+        if !(1..=256).contains(&width) || planes != 1 || !matches!(bpp, 1 | 4 | 8 | 16 | 24 | 32) {
+            return ImageCheck::Invalid;
+        }
+        // The DIB height covers the colour (XOR) bitmap and the AND mask.
+        if width != entry.width || height != entry.height * 2 {
+            return ImageCheck::Mismatch("DIB dimensions differ from the directory");
+        }
+        if compression == 0 {
+            let row = |bits: u64| (u64::from(width) * bits).div_ceil(32) * 4;
+            let palette = if bpp <= 8 { 4u64 << bpp } else { 0 };
+            let needed = u64::from(DIB_HEADER_LEN)
+                + palette
+                + (row(u64::from(bpp)) + row(1)) * u64::from(entry.height);
+            if needed > entry.size {
+                return ImageCheck::Mismatch("DIB larger than its directory entry");
             }
         }
-
-        false
+        ImageCheck::Valid
     }
 }
 
@@ -85,96 +147,122 @@ impl CarveHandler for IcoCarveHandler {
         hit: &NormalizedHit,
         ctx: &ExtractionContext,
     ) -> Result<Option<CarvedFile>, CarveError> {
-        let header = read_exact_at(ctx, hit.global_offset, 6)
-            .ok_or_else(|| CarveError::Invalid("ico header too short".to_string()))?;
+        let Some(header) = read_exact_at(ctx, hit.global_offset, ICONDIR_LEN as usize) else {
+            note_rejection(Rejection::Invalid("ICO header too short"));
+            return Ok(None);
+        };
         if header[0] != 0 || header[1] != 0 {
+            note_rejection(Rejection::Invalid("ICO reserved field not zero"));
             return Ok(None);
         }
-        let icon_type = u16::from_le_bytes([header[2], header[3]]);
-        if icon_type != 1 && icon_type != 2 {
+        let icon_type = le_u16(&header, 2);
+        if icon_type != TYPE_ICON && icon_type != TYPE_CURSOR {
+            note_rejection(Rejection::Invalid("ICO type is neither icon nor cursor"));
             return Ok(None);
         }
-        let count = u16::from_le_bytes([header[4], header[5]]) as usize;
+        let count = le_u16(&header, 4) as usize;
         // Stricter limit: most ICO files have 1-10 entries, max 64 for sanity
         if count == 0 || count > MAX_ICON_ENTRIES {
+            note_rejection(Rejection::Invalid("ICO entry count out of range"));
             return Ok(None);
         }
 
-        let dir_len = count * 16;
-        let dir = read_exact_at(ctx, hit.global_offset + 6, dir_len)
-            .ok_or_else(|| CarveError::Invalid("ico directory truncated".to_string()))?;
-        let mut max_end = 0u64;
-        let header_size = 6u64 + dir_len as u64;
-        let mut valid_image_found = false;
-
-        for i in 0..count {
-            let base = i * 16;
-            let size =
-                u32::from_le_bytes([dir[base + 8], dir[base + 9], dir[base + 10], dir[base + 11]])
-                    as u64;
-            let offset = u32::from_le_bytes([
-                dir[base + 12],
-                dir[base + 13],
-                dir[base + 14],
-                dir[base + 15],
-            ]) as u64;
-
-            // Basic sanity checks
-            if size == 0 || offset < header_size {
-                return Ok(None);
-            }
-
-            // Stricter size check per image
-            if size > MAX_SINGLE_IMAGE_SIZE {
-                return Ok(None);
-            }
+        let dir_len = count * ENTRY_LEN;
+        let Some(dir) = read_exact_at(ctx, hit.global_offset + ICONDIR_LEN, dir_len) else {
+            note_rejection(Rejection::Invalid("ICO directory truncated"));
+            return Ok(None);
+        };
+        let header_size = ICONDIR_LEN + dir_len as u64;
+        let mut entries: Vec<Entry> = dir.chunks_exact(ENTRY_LEN).map(Entry::parse).collect();
+        if entries
+            .iter()
+            .any(|e| e.size == 0 || e.offset < header_size || e.size > MAX_SINGLE_IMAGE_SIZE)
+        {
+            note_rejection(Rejection::Invalid(
+                "ICO entry outside the file or too large",
+            ));
+            return Ok(None);
+        }
+        entries.sort_by_key(|e| e.offset);
+        if entries
+            .windows(2)
+            .any(|pair| pair[0].offset + pair[0].size > pair[1].offset)
+        {
+            note_rejection(Rejection::Invalid("ICO images overlap"));
+            return Ok(None);
+        }
 
-            // Validate actual image data at declared offset
-            let image_global_offset = hit.global_offset.saturating_add(offset);
-            if Self::validate_image_data(ctx, image_global_offset, size) {
-                valid_image_found = true;
+        let mut errors = Vec::new();
+        let mut valid_image_found = false;
+        for entry in &entries {
+            match Self::check_image(ctx, hit.global_offset + entry.offset, entry) {
+                ImageCheck::Valid => valid_image_found = true,
+                ImageCheck::Mismatch(reason) => {
+                    valid_image_found = true;
+                    errors.push(format!("image at {}: {reason}", entry.offset))
+                }
+                ImageCheck::Invalid => errors.push(format!(
+                    "image at {}: neither PNG nor DIB data",
+                    entry.offset
+                )),
             }
-
-            max_end = max_end.max(offset.saturating_add(size));
         }
-
         // Reject if no valid image signatures found at any declared offset
         if !valid_image_found {
+            note_rejection(Rejection::Invalid("no PNG or DIB data at any ICO entry"));
             return Ok(None);
         }
 
-        // Apply reasonable total size cap
-        let reasonable_max = MAX_REASONABLE_ICO_SIZE;
-        let mut total_end = hit
-            .global_offset
-            .saturating_add(max_end.min(reasonable_max));
-        if self.max_size > 0 {
-            let max_allowed = hit.global_offset.saturating_add(self.max_size);
-            if total_end > max_allowed {
-                total_end = max_allowed;
-            }
+        let declared = entries
+            .iter()
+            .map(|e| e.offset + e.size)
+            .max()
+            .unwrap_or(header_size);
+        note_declared_size(DeclaredSize {
+            size: declared,
+            source: "directory",
+        });
+        let mut truncated = false;
+        let mut size = declared;
+        if self.max_size > 0 && size > self.max_size {
+            size = self.max_size;
+            truncated = true;
+            errors.push("max_size reached before ICO end".to_string());
         }
 
+        let extension = if icon_type == TYPE_CURSOR {
+            "cur"
+        } else {
+            self.extension.as_str()
+        };
         let (full_path, rel_path) = output_path(
             ctx.output_root,
             self.file_type(),
-            &self.extension,
+            extension,
             hit.global_offset,
         )?;
-        let mut file = File::create(&full_path)?;
+        let mut file = create_output(&full_path)?;
         let mut md5 = md5::Context::new();
         let mut sha256 = Sha256::new();
 
         let (written, eof_truncated) = write_range(
             ctx,
             hit.global_offset,
-            total_end,
+            hit.global_offset + size,
             &mut file,
             &mut md5,
             &mut sha256,
         )?;
+        if eof_truncated {
+            truncated = true;
+            errors.push("eof before ICO end".to_string());
+        }
 
         if written < self.min_size {
+            note_rejection(Rejection::MinSize {
+                size: written,
+                min_size: self.min_size,
+            });
             let _ = std::fs::remove_file(&full_path);
             return Ok(None);
         }
@@ -190,16 +278,17 @@ impl CarveHandler for IcoCarveHandler {
         Ok(Some(CarvedFile {
             run_id: ctx.run_id.to_string(),
             file_type: self.file_type().to_string(),
+            signature_type: self.file_type().to_string(),
             path: rel_path,
-            extension: self.extension.clone(),
+            extension: extension.to_string(),
             global_start: hit.global_offset,
             global_end,
             size: written,
             md5: Some(md5_hex),
             sha256: Some(sha256_hex),
-            validated: !eof_truncated,
-            truncated: eof_truncated,
-            errors: Vec::new(),
+            validated: errors.is_empty(),
+            truncated,
+            errors,
             pattern_id: Some(hit.pattern_id.clone()),
         }))
     }
@@ -214,6 +303,18 @@ fn read_exact_at(ctx: &ExtractionContext, offset: u64, len: usize) -> Option<Vec
     Some(buf)
 }
 
+fn le_u16(data: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([data[at], data[at + 1]])
+}
+
+fn le_u32(data: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(data[at..at + 4].try_into().unwrap_or_default())
+}
+
+fn be_u32(data: &[u8], at: usize) -> u32 {
+    u32::from_be_bytes(data[at..at + 4].try_into().unwrap_or_default())
+}
+
 #[cfg(test)]
 mod tests {
     use super::IcoCarveHandler;
@@ -286,4 +387,94 @@ mod tests {
         let carved = carved.expect("carved");
         assert!(carved.size > 0);
     }
+
+    /// A 1-bit DIB image of `size` x `size` pixels with its AND mask.
+    fn dib(size: u8) -> Vec<u8> {
+        let mut image = Vec::new();
+        image.extend_from_slice(&40u32.to_le_bytes());
+        image.extend_from_slice(&u32::from(size).to_le_bytes());
+        image.extend_from_slice(&(u32::from(size) * 2).to_le_bytes());
+        image.extend_from_slice(&1u16.to_le_bytes());
+        image.extend_from_slice(&1u16.to_le_bytes());
+        image.extend_from_slice(&[0; 24]);
+        image.extend_from_slice(&[0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0]); // palette
+        let row = (usize::from(size)).div_ceil(32) * 4;
+        image.resize(image.len() + 2 * row * usize::from(size), 0xAA);
+        image
+    }
+
+    /// An icon directory of `kind` over `images`, each listed as `(width,
+    /// height)` and stored one after the other.
+    fn icon(kind: u16, images: &[((u8, u8), Vec<u8>)]) -> Vec<u8> {
+        let mut data = vec![0, 0];
+        data.extend_from_slice(&kind.to_le_bytes());
+        data.extend_from_slice(&(images.len() as u16).to_le_bytes());
+        let mut offset = 6 + 16 * images.len() as u32;
+        for ((width, height), image) in images {
+            data.extend_from_slice(&[*width, *height, 0, 0, 1, 0, 1, 0]);
+            data.extend_from_slice(&(image.len() as u32).to_le_bytes());
+            data.extend_from_slice(&offset.to_le_bytes());
+            offset += image.len() as u32;
+        }
+        for (_, image) in images {
+            data.extend_from_slice(image);
+        }
+        data
+    }
+
+    fn carve(data: Vec<u8>) -> Option<crate::carve::CarvedFile> {
+        let evidence = SliceEvidence { data };
+        let handler = IcoCarveHandler::new("ico".to_string(), 0, 0);
+        let hit = NormalizedHit {
+            global_offset: 0,
+            file_type_id: "ico".to_string(),
+            pattern_id: "ico_header".to_string(),
+        };
+        let dir = tempdir().expect("tempdir");
+        let ctx = ExtractionContext {
+            run_id: "test",
+            output_root: dir.path(),
+            evidence: &evidence,
+        };
+        handler.process_hit(&hit, &ctx).expect("process")
+    }
+
+    #[test]
+    fn sizes_icons_and_cursors_by_their_directory() {
+        let ico = icon(1, &[((16, 16), dib(16)), ((32, 32), dib(32))]);
+        let mut data = ico.clone();
+        data.extend_from_slice(&[0x55; 512]);
+        let carved = carve(data).expect("icon");
+        assert_eq!(carved.size, ico.len() as u64);
+        assert!(carved.validated, "{:?}", carved.errors);
+        assert_eq!(carved.extension, "ico");
+
+        let cur = icon(2, &[((16, 16), dib(16))]);
+        let carved = carve(cur).expect("cursor");
+        assert_eq!(carved.extension, "cur");
+        assert!(carved.path.ends_with(".cur"));
+    }
+
+    #[test]
+    fn flags_images_that_disagree_with_their_entry() {
+        let mut png = super::PNG_HEADER_MAGIC.to_vec();
+        png.extend_from_slice(&13u32.to_be_bytes());
+        png.extend_from_slice(b"IHDR");
+        png.extend_from_slice(&64u32.to_be_bytes());
+        png.extend_from_slice(&64u32.to_be_bytes());
+        png.resize(64, 0);
+        let carved = carve(icon(1, &[((16, 16), dib(16)), ((48, 48), png)])).expect("icon");
+        assert!(!carved.validated);
+        assert_eq!(carved.errors.len(), 1, "{:?}", carved.errors);
+    }
+
+    #[test]
+    fn rejects_overlapping_and_imageless_directories() {
+        let mut overlap = icon(1, &[((16, 16), dib(16)), ((16, 16), dib(16))]);
+        overlap[22 + 12..22 + 16].copy_from_slice(&40u32.to_le_bytes());
+        assert!(carve(overlap).is_none());
+
+        let garbage = icon(1, &[((16, 16), vec![0x55; 200])]);
+        assert!(carve(garbage).is_none());
+    }
 }
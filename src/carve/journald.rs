@@ -0,0 +1,172 @@
+use crate::carve::{
+    CarveError, CarveHandler, CarveStream, CarvedFile, ExtractionContext, Rejection, create_output,
+    note_rejection, output_path,
+};
+use crate::parsers::journal::JournalHeader;
+use crate::scanner::NormalizedHit;
+
+/// Bytes of the journal header needed to size the file.
+const HEADER_PREFIX: usize = 104;
+
+/// systemd journal files. The extent is `header_size + arena_size` from the
+/// file header.
+pub struct JournaldCarveHandler {
+    extension: String,
+    min_size: u64,
+    max_size: u64,
+}
+
+impl JournaldCarveHandler {
+    pub fn new(extension: String, min_size: u64, max_size: u64) -> Self {
+        Self {
+            extension,
+            min_size,
+            max_size,
+        }
+    }
+}
+
+impl CarveHandler for JournaldCarveHandler {
+    fn file_type(&self) -> &str {
+        "journald"
+    }
+
+    fn extension(&self) -> &str {
+        &self.extension
+    }
+
+    fn process_hit(
+        &self,
+        hit: &NormalizedHit,
+        ctx: &ExtractionContext,
+    ) -> Result<Option<CarvedFile>, CarveError> {
+        let (full_path, rel_path) = output_path(
+            ctx.output_root,
+            self.file_type(),
+            &self.extension,
+            hit.global_offset,
+        )?;
+        let file = create_output(&full_path)?;
+        let mut stream = CarveStream::new(ctx.evidence, hit.global_offset, self.max_size, file);
+
+        let mut validated = false;
+        let mut truncated = false;
+        let mut errors = Vec::new();
+
+        let result: Result<u64, CarveError> = (|| {
+            let header = stream.read_exact(HEADER_PREFIX)?;
+            let header = JournalHeader::parse(&header)
+                .ok_or_else(|| CarveError::Invalid("journal header invalid".to_string()))?;
+            let total_size = header.file_size();
+            let target_size = if self.max_size > 0 {
+                total_size.min(self.max_size)
+            } else {
+                total_size
+            };
+            let remaining = target_size.saturating_sub(HEADER_PREFIX as u64);
+            if remaining > 0 {
+                stream.read_exact(remaining as usize)?;
+            }
+            validated = true;
+            Ok(target_size)
+        })();
+
+        if let Err(err) = result {
+            match err {
+                CarveError::Truncated | CarveError::Eof => {
+                    truncated = true;
+                    errors.push(err.to_string());
+                }
+                CarveError::Invalid(_msg) => {
+                    let _ = std::fs::remove_file(&full_path);
+                    return Ok(None);
+                }
+                other => return Err(other),
+            }
+        }
+
+        let (size, md5_hex, sha256_hex) = stream.finish()?;
+        if size < self.min_size {
+            note_rejection(Rejection::MinSize {
+                size,
+                min_size: self.min_size,
+            });
+            let _ = std::fs::remove_file(&full_path);
+            return Ok(None);
+        }
+
+        if self.max_size > 0 && size >= self.max_size {
+            truncated = true;
+            if !errors.iter().any(|e| e.contains("max_size")) {
+                errors.push("max_size reached".to_string());
+            }
+        }
+
+        let global_end = if size == 0 {
+            hit.global_offset
+        } else {
+            hit.global_offset + size - 1
+        };
+
+        Ok(Some(CarvedFile {
+            run_id: ctx.run_id.to_string(),
+            file_type: self.file_type().to_string(),
+            signature_type: self.file_type().to_string(),
+            path: rel_path,
+            extension: self.extension.clone(),
+            global_start: hit.global_offset,
+            global_end,
+            size,
+            md5: Some(md5_hex),
+            sha256: Some(sha256_hex),
+            validated,
+            truncated,
+            errors,
+            pattern_id: Some(hit.pattern_id.clone()),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evidence::RawFileSource;
+    use crate::parsers::journal::journal_bytes;
+
+    #[test]
+    fn carves_header_plus_arena() {
+        let journal = journal_bytes(&[(1_700_000_000_000_000, &["MESSAGE=boot"])]);
+        let mut image = vec![0u8; 512];
+        image.extend_from_slice(&journal);
+        image.extend_from_slice(&[0xAAu8; 512]);
+
+        let temp_dir = tempfile::tempdir().expect("tempdir");
+        let input_path = temp_dir.path().join("image.bin");
+        std::fs::write(&input_path, &image).expect("write image");
+        let output_root = temp_dir.path().join("out");
+        let evidence = RawFileSource::open(&input_path).expect("evidence");
+        let ctx = ExtractionContext {
+            run_id: "test",
+            output_root: &output_root,
+            evidence: &evidence,
+        };
+        let handler = JournaldCarveHandler::new("journal".to_string(), 0, 0);
+        let hit = NormalizedHit {
+            global_offset: 512,
+            file_type_id: "journald".to_string(),
+            pattern_id: "journald_header".to_string(),
+        };
+        let carved = handler
+            .process_hit(&hit, &ctx)
+            .expect("carve")
+            .expect("carved");
+        assert_eq!(carved.size, journal.len() as u64);
+        assert!(carved.validated);
+
+        let bogus = NormalizedHit {
+            global_offset: 0,
+            ..hit
+        };
+        assert!(handler.process_hit(&bogus, &ctx).expect("carve").is_none());
+    }
+}
@@ -1,9 +1,11 @@
-use std::fs::File;
-use std::io::{BufWriter, Write};
+use std::io::Write;
 
 use sha2::{Digest, Sha256};
 
-use crate::carve::{CarveError, CarveHandler, CarvedFile, ExtractionContext, output_path};
+use crate::carve::{
+    CarveError, CarveHandler, CarvedFile, ExtractionContext, Rejection, create_output,
+    note_rejection, output_path, output_writer,
+};
 use crate::scanner::NormalizedHit;
 
 pub struct JpegCarveHandler {
@@ -42,8 +44,8 @@ impl CarveHandler for JpegCarveHandler {
             &self.extension,
             hit.global_offset,
         )?;
-        let file = File::create(&full_path)?;
-        let mut writer = BufWriter::new(file);
+        let file = create_output(&full_path)?;
+        let mut writer = output_writer(file);
         let mut md5 = md5::Context::new();
         let mut sha256 = Sha256::new();
 
@@ -57,6 +59,7 @@ impl CarveHandler for JpegCarveHandler {
         let buf_size = 64 * 1024;
 
         loop {
+            ctx.check_cancelled()?;
             if self.max_size > 0 && bytes_written >= self.max_size {
                 truncated = true;
                 errors.push("max_size reached before EOI".to_string());
@@ -111,6 +114,11 @@ impl CarveHandler for JpegCarveHandler {
         writer.flush()?;
 
         if bytes_written < self.min_size {
+            note_rejection(Rejection::MinSize {
+                size: bytes_written,
+
+                min_size: self.min_size,
+            });
             let _ = std::fs::remove_file(&full_path);
             return Ok(None);
         }
@@ -126,6 +134,7 @@ impl CarveHandler for JpegCarveHandler {
         Ok(Some(CarvedFile {
             run_id: ctx.run_id.to_string(),
             file_type: self.file_type().to_string(),
+            signature_type: self.file_type().to_string(),
             path: rel_path,
             extension: self.extension.clone(),
             global_start: hit.global_offset,
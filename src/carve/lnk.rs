@@ -0,0 +1,170 @@
+use std::io::Write;
+
+use sha2::{Digest, Sha256};
+
+use crate::carve::{
+    CarveError, CarveHandler, CarvedFile, ExtractionContext, Rejection, build_carved_file,
+    check_min_size, create_output, note_rejection, output_path,
+};
+use crate::parsers::lnk::{self, LnkError};
+use crate::scanner::NormalizedHit;
+
+/// First read; most shortcuts are under 4 KiB.
+const INITIAL_READ: usize = 16 * 1024;
+/// Read limit when the type has no `max_size`.
+const UNBOUNDED_LIMIT: u64 = 1024 * 1024;
+
+/// Windows shell links (`.lnk`). The extent is found by walking the header,
+/// target ID list, link info, string data, and extra data blocks, read in
+/// doubling windows until the terminal block; the file is validated when
+/// every section is consistent and the terminal block is found. The
+/// `shortcuts` post-processor records the target.
+pub struct LnkCarveHandler {
+    extension: String,
+    min_size: u64,
+    max_size: u64,
+}
+
+impl LnkCarveHandler {
+    pub fn new(extension: String, min_size: u64, max_size: u64) -> Self {
+        Self {
+            extension,
+            min_size,
+            max_size,
+        }
+    }
+}
+
+impl CarveHandler for LnkCarveHandler {
+    fn file_type(&self) -> &str {
+        "lnk"
+    }
+
+    fn extension(&self) -> &str {
+        &self.extension
+    }
+
+    fn process_hit(
+        &self,
+        hit: &NormalizedHit,
+        ctx: &ExtractionContext,
+    ) -> Result<Option<CarvedFile>, CarveError> {
+        let limit = if self.max_size > 0 {
+            self.max_size
+        } else {
+            UNBOUNDED_LIMIT
+        } as usize;
+        let mut buf = Vec::new();
+        let mut want = INITIAL_READ.min(limit);
+        let (len, complete, terminated) = loop {
+            let start = buf.len();
+            buf.resize(want, 0);
+            let mut filled = start;
+            while filled < want {
+                let n = ctx
+                    .evidence
+                    .read_at(hit.global_offset + filled as u64, &mut buf[filled..])
+                    .map_err(|e| CarveError::Evidence(e.to_string()))?;
+                if n == 0 {
+                    break;
+                }
+                filled += n;
+            }
+            buf.truncate(filled);
+            match lnk::parse(&buf) {
+                Ok(link) => break (link.len, true, link.terminated),
+                Err(LnkError::Invalid(reason)) => {
+                    note_rejection(Rejection::Invalid(reason));
+                    return Ok(None);
+                }
+                Err(LnkError::Incomplete) if filled < want || want >= limit => {
+                    break (buf.len(), false, false);
+                }
+                Err(LnkError::Incomplete) => want = want.saturating_mul(2).min(limit),
+            }
+        };
+        let data = &buf[..len];
+
+        let (full_path, rel_path) = output_path(
+            ctx.output_root,
+            self.file_type(),
+            &self.extension,
+            hit.global_offset,
+        )?;
+        let mut file = create_output(&full_path)?;
+        file.write_all(data)?;
+        file.flush()?;
+        let size = data.len() as u64;
+        if !check_min_size(&full_path, size, self.min_size) {
+            return Ok(None);
+        }
+
+        let mut errors = Vec::new();
+        if !complete {
+            errors.push(if size >= limit as u64 {
+                "max_size reached".to_string()
+            } else {
+                "evidence ended before link end".to_string()
+            });
+        } else if !terminated {
+            errors.push("extra data block with unknown signature".to_string());
+        }
+
+        Ok(Some(build_carved_file(
+            ctx.run_id,
+            self.file_type(),
+            &self.extension,
+            rel_path,
+            hit.global_offset,
+            size,
+            format!("{:x}", md5::compute(data)),
+            hex::encode(Sha256::digest(data)),
+            errors.is_empty(),
+            !complete,
+            errors,
+            &hit.pattern_id,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::carve::support::carve_image;
+    use crate::parsers::lnk::testing::lnk_bytes;
+
+    #[test]
+    fn carves_link_to_its_terminal_block() {
+        let link = lnk_bytes();
+        let mut image = vec![0u8; 512];
+        image.extend_from_slice(&link);
+        image.extend_from_slice(&[0xEE; 1024]);
+
+        let (carved, _dir) =
+            carve_image(&LnkCarveHandler::new("lnk".to_string(), 0, 0), &image, 512);
+        let carved = carved.expect("lnk");
+        assert_eq!(carved.size, link.len() as u64);
+        assert!(carved.validated, "{:?}", carved.errors);
+        assert!(!carved.truncated);
+
+        let (carved, _dir) = carve_image(
+            &LnkCarveHandler::new("lnk".to_string(), 0, 0),
+            &image[..512 + 150],
+            512,
+        );
+        let carved = carved.expect("cut lnk");
+        assert!(carved.truncated && !carved.validated);
+        assert_eq!(carved.errors, vec!["evidence ended before link end"]);
+    }
+
+    #[test]
+    fn rejects_damaged_header() {
+        let mut link = lnk_bytes();
+        link[0x10] = 0x99;
+        assert!(
+            carve_image(&LnkCarveHandler::new("lnk".to_string(), 0, 0), &link, 0)
+                .0
+                .is_none()
+        );
+    }
+}
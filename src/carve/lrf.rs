@@ -2,12 +2,11 @@
 //!
 //! Uses a heuristic size field; falls back to max_size when unknown.
 
-use std::fs::File;
-
 use sha2::{Digest, Sha256};
 
 use crate::carve::{
-    CarveError, CarveHandler, CarvedFile, ExtractionContext, output_path, write_range,
+    CarveError, CarveHandler, CarvedFile, ExtractionContext, Rejection, create_output,
+    note_rejection, output_path, write_range,
 };
 use crate::scanner::NormalizedHit;
 
@@ -71,7 +70,7 @@ impl CarveHandler for LrfCarveHandler {
             &self.extension,
             hit.global_offset,
         )?;
-        let mut file = File::create(&full_path)?;
+        let mut file = create_output(&full_path)?;
         let mut md5 = md5::Context::new();
         let mut sha256 = Sha256::new();
 
@@ -85,6 +84,11 @@ impl CarveHandler for LrfCarveHandler {
         )?;
 
         if written < self.min_size {
+            note_rejection(Rejection::MinSize {
+                size: written,
+
+                min_size: self.min_size,
+            });
             let _ = std::fs::remove_file(&full_path);
             return Ok(None);
         }
@@ -100,6 +104,7 @@ impl CarveHandler for LrfCarveHandler {
         Ok(Some(CarvedFile {
             run_id: ctx.run_id.to_string(),
             file_type: self.file_type().to_string(),
+            signature_type: self.file_type().to_string(),
             path: rel_path,
             extension: self.extension.clone(),
             global_start: hit.global_offset,
@@ -0,0 +1,210 @@
+//! Mach-O carving handler for thin and fat (universal) binaries.
+//!
+//! A thin file is sized by the data its load commands point at (segments,
+//! relocations, symbol and string tables, code signature, and the other
+//! `__LINKEDIT` blobs); a fat file by its last slice, after each slice's
+//! header and load commands are checked (`parsers::macho`). Dynamic
+//! libraries and bundles are written with their own extension; the
+//! `executables` post-processor records the kind and architectures.
+
+use sha2::{Digest, Sha256};
+
+use crate::carve::{
+    CarveError, CarveHandler, CarvedFile, DeclaredSize, ExtractionContext, Rejection,
+    create_output, note_declared_size, note_rejection, output_path, read_evidence, write_range,
+};
+use crate::parsers::macho::{self, MachOError, MachOFile};
+use crate::scanner::NormalizedHit;
+
+pub struct MachOCarveHandler {
+    extension: String,
+    min_size: u64,
+    max_size: u64,
+}
+
+impl MachOCarveHandler {
+    pub fn new(extension: String, min_size: u64, max_size: u64) -> Self {
+        Self {
+            extension,
+            min_size,
+            max_size,
+        }
+    }
+}
+
+impl CarveHandler for MachOCarveHandler {
+    fn file_type(&self) -> &str {
+        "macho"
+    }
+
+    fn extension(&self) -> &str {
+        &self.extension
+    }
+
+    fn process_hit(
+        &self,
+        hit: &NormalizedHit,
+        ctx: &ExtractionContext,
+    ) -> Result<Option<CarvedFile>, CarveError> {
+        let mut read = |offset: u64, buf: &mut [u8]| {
+            read_evidence(ctx, hit.global_offset.saturating_add(offset), buf)
+        };
+        let parsed = match macho::parse(&mut read) {
+            Ok(parsed) => parsed,
+            Err(MachOError::Invalid(reason)) => {
+                note_rejection(Rejection::Invalid(reason));
+                return Ok(None);
+            }
+            Err(MachOError::Incomplete) => {
+                note_rejection(Rejection::Invalid(
+                    "evidence ended inside the Mach-O load commands",
+                ));
+                return Ok(None);
+            }
+        };
+
+        let declared = parsed.size();
+        note_declared_size(DeclaredSize {
+            size: declared,
+            source: match parsed {
+                MachOFile::Thin(_) => "load_commands",
+                MachOFile::Fat { .. } => "fat_header",
+            },
+        });
+        let mut errors = Vec::new();
+        let mut truncated = false;
+        let mut size = declared;
+        if self.max_size > 0 && size > self.max_size {
+            size = self.max_size;
+            truncated = true;
+            errors.push("max_size reached before Mach-O end".to_string());
+        }
+
+        let extension = match parsed.primary().kind() {
+            kind @ ("dylib" | "bundle") => kind,
+            _ => self.extension.as_str(),
+        };
+        let (full_path, rel_path) = output_path(
+            ctx.output_root,
+            self.file_type(),
+            extension,
+            hit.global_offset,
+        )?;
+        let mut file = create_output(&full_path)?;
+        let mut md5 = md5::Context::new();
+        let mut sha256 = Sha256::new();
+        let (written, eof_truncated) = write_range(
+            ctx,
+            hit.global_offset,
+            hit.global_offset + size,
+            &mut file,
+            &mut md5,
+            &mut sha256,
+        )?;
+        if eof_truncated {
+            truncated = true;
+            errors.push("eof before Mach-O end".to_string());
+        }
+
+        if written < self.min_size {
+            note_rejection(Rejection::MinSize {
+                size: written,
+                min_size: self.min_size,
+            });
+            let _ = std::fs::remove_file(&full_path);
+            return Ok(None);
+        }
+
+        let global_end = if written == 0 {
+            hit.global_offset
+        } else {
+            hit.global_offset + written - 1
+        };
+        Ok(Some(CarvedFile {
+            run_id: ctx.run_id.to_string(),
+            file_type: self.file_type().to_string(),
+            signature_type: self.file_type().to_string(),
+            path: rel_path,
+            extension: extension.to_string(),
+            global_start: hit.global_offset,
+            global_end,
+            size: written,
+            md5: Some(format!("{:x}", md5.compute())),
+            sha256: Some(hex::encode(sha256.finalize())),
+            validated: errors.is_empty(),
+            truncated,
+            errors,
+            pattern_id: Some(hit.pattern_id.clone()),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::carve::support::carve_image;
+    use crate::parsers::macho::testing::{MACHO_LEN, fat_bytes, macho_bytes};
+
+    #[test]
+    fn carves_thin_and_fat_binaries() {
+        let mut image = vec![0u8; 0x1000];
+        image.extend_from_slice(&macho_bytes(0x0100_000C));
+        image.extend_from_slice(&[0x5A; 0x1000]);
+        let (carved, _dir) = carve_image(
+            &MachOCarveHandler::new("macho".to_string(), 0, 0),
+            &image,
+            0x1000,
+        );
+        let carved = carved.expect("thin");
+        assert_eq!(carved.size, MACHO_LEN as u64);
+        assert!(carved.validated, "{:?}", carved.errors);
+
+        let fat = fat_bytes();
+        let mut image = fat.clone();
+        image.extend_from_slice(&[0x5A; 0x1000]);
+        let (carved, _dir) = carve_image(
+            &MachOCarveHandler::new("macho".to_string(), 0, 0),
+            &image,
+            0,
+        );
+        let carved = carved.expect("fat");
+        assert_eq!(carved.size, fat.len() as u64);
+        assert!(carved.validated, "{:?}", carved.errors);
+
+        // Cut inside the second slice: the fat header still sizes the file.
+        let (carved, _dir) = carve_image(
+            &MachOCarveHandler::new("macho".to_string(), 0, 0),
+            &fat[..0x8010],
+            0,
+        );
+        let carved = carved.expect("cut fat");
+        assert!(carved.truncated && !carved.validated);
+        assert_eq!(carved.errors, vec!["eof before Mach-O end"]);
+    }
+
+    #[test]
+    fn writes_dylibs_with_their_extension() {
+        let mut data = macho_bytes(0x0100_0007);
+        data[12..16].copy_from_slice(&6u32.to_le_bytes());
+        let (carved, _dir) =
+            carve_image(&MachOCarveHandler::new("macho".to_string(), 0, 0), &data, 0);
+        let carved = carved.expect("dylib");
+        assert_eq!(carved.extension, "dylib");
+    }
+
+    #[test]
+    fn rejects_java_class_files() {
+        let mut class = 0xCAFE_BABEu32.to_be_bytes().to_vec();
+        class.extend_from_slice(&[0, 0, 0, 0x41]);
+        class.resize(4096, 0);
+        assert!(
+            carve_image(
+                &MachOCarveHandler::new("macho".to_string(), 0, 0),
+                &class,
+                0
+            )
+            .0
+            .is_none()
+        );
+    }
+}
@@ -0,0 +1,361 @@
+//! Structures found in memory images (the `memory` scan profile).
+//!
+//! These are detectors rather than file carvers: each hit is written out as
+//! the fixed structure it belongs to, so the hit lands in the carved-file
+//! metadata with an offset, size, and hash.
+//! - `crashdump`: Windows crash dump header (`PAGEDUMP` / `PAGEDU64`), 4 or
+//!   8 KiB. Validated when the major version is free (0x0F) or checked (0x0C).
+//! - `hiberfil`: hibernation file header page (`hibr`, `HIBR`, `wake`, `WAKE`),
+//!   page aligned. Validated when `LengthSelf` is plausible.
+//! - `hbin`: registry hive bin, page aligned, sized by its header. Memory-
+//!   resident hives are not contiguous, so bins are recovered one by one.
+//!   Validated when the first cell fits in the bin.
+//! - `pool_process`: x64 pool allocation tagged `Proc` (`EPROCESS`), sized by
+//!   the pool header `BlockSize`. The tag must sit at a 16-byte aligned header.
+//!   Validated when the allocation holds an `.exe` image file name.
+
+use sha2::{Digest, Sha256};
+
+use crate::carve::{
+    CarveError, CarveHandler, CarvedFile, ExtractionContext, Rejection, build_carved_file,
+    create_output, note_rejection, output_path, write_range,
+};
+use crate::evidence::EvidenceSource;
+use crate::scanner::NormalizedHit;
+
+const PAGE_SIZE: u64 = 4096;
+/// x64 pool headers are 16 bytes with the tag at offset 4.
+const POOL_TAG_OFFSET: u64 = 4;
+const POOL_ALIGN: u64 = 16;
+/// `EPROCESS` allocations across Windows versions, in bytes.
+const POOL_PROCESS_SIZE: std::ops::RangeInclusive<u64> = 0x400..=0xFF0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryStructure {
+    CrashDump,
+    Hibernation,
+    HiveBin,
+    PoolProcess,
+}
+
+impl MemoryStructure {
+    pub fn from_validator(name: &str) -> Option<Self> {
+        match name {
+            "crashdump" => Some(Self::CrashDump),
+            "hiberfil" => Some(Self::Hibernation),
+            "hbin" => Some(Self::HiveBin),
+            "pool_process" => Some(Self::PoolProcess),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::CrashDump => "crashdump",
+            Self::Hibernation => "hiberfil",
+            Self::HiveBin => "hbin",
+            Self::PoolProcess => "pool_process",
+        }
+    }
+}
+
+/// Where a structure starts and how long it is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Extent {
+    start: u64,
+    len: u64,
+    validated: bool,
+}
+
+pub struct MemoryCarveHandler {
+    structure: MemoryStructure,
+    extension: String,
+    min_size: u64,
+    max_size: u64,
+}
+
+impl MemoryCarveHandler {
+    pub fn new(
+        structure: MemoryStructure,
+        extension: String,
+        min_size: u64,
+        max_size: u64,
+    ) -> Self {
+        Self {
+            structure,
+            extension,
+            min_size,
+            max_size,
+        }
+    }
+}
+
+impl CarveHandler for MemoryCarveHandler {
+    fn file_type(&self) -> &str {
+        self.structure.name()
+    }
+
+    fn extension(&self) -> &str {
+        &self.extension
+    }
+
+    fn process_hit(
+        &self,
+        hit: &NormalizedHit,
+        ctx: &ExtractionContext,
+    ) -> Result<Option<CarvedFile>, CarveError> {
+        let Some(extent) = measure(self.structure, ctx.evidence, hit.global_offset)? else {
+            return Ok(None);
+        };
+        let len = if self.max_size > 0 {
+            extent.len.min(self.max_size)
+        } else {
+            extent.len
+        };
+
+        let (full_path, rel_path) = output_path(
+            ctx.output_root,
+            self.file_type(),
+            &self.extension,
+            extent.start,
+        )?;
+        let mut file = create_output(&full_path)?;
+        let mut md5 = md5::Context::new();
+        let mut sha256 = Sha256::new();
+        let (written, eof_truncated) = write_range(
+            ctx,
+            extent.start,
+            extent.start.saturating_add(len),
+            &mut file,
+            &mut md5,
+            &mut sha256,
+        )?;
+        if written < self.min_size {
+            note_rejection(Rejection::MinSize {
+                size: written,
+                min_size: self.min_size,
+            });
+            let _ = std::fs::remove_file(&full_path);
+            return Ok(None);
+        }
+
+        let mut errors = Vec::new();
+        if eof_truncated {
+            errors.push("eof before end of structure".to_string());
+        }
+        if len < extent.len {
+            errors.push("max_size reached".to_string());
+        }
+        Ok(Some(build_carved_file(
+            ctx.run_id,
+            self.file_type(),
+            &self.extension,
+            rel_path,
+            extent.start,
+            written,
+            format!("{:x}", md5.compute()),
+            hex::encode(sha256.finalize()),
+            extent.validated,
+            !errors.is_empty(),
+            errors,
+            &hit.pattern_id,
+        )))
+    }
+}
+
+fn measure(
+    structure: MemoryStructure,
+    evidence: &dyn EvidenceSource,
+    offset: u64,
+) -> Result<Option<Extent>, CarveError> {
+    let extent = match structure {
+        MemoryStructure::CrashDump => {
+            let Some(header) = read_at(evidence, offset, 16)? else {
+                return Ok(None);
+            };
+            let len = match &header[4..8] {
+                b"DUMP" => 0x1000,
+                b"DU64" => 0x2000,
+                _ => return reject("unknown dump signature"),
+            };
+            Extent {
+                start: offset,
+                len,
+                validated: matches!(le_u32(&header, 8), 0x0C | 0x0F),
+            }
+        }
+        MemoryStructure::Hibernation => {
+            if !offset.is_multiple_of(PAGE_SIZE) {
+                return reject("hibernation header not page aligned");
+            }
+            let Some(header) = read_at(evidence, offset, 16)? else {
+                return Ok(None);
+            };
+            Extent {
+                start: offset,
+                len: PAGE_SIZE,
+                validated: (0x40..=0x1000).contains(&le_u32(&header, 12)),
+            }
+        }
+        MemoryStructure::HiveBin => {
+            if !offset.is_multiple_of(PAGE_SIZE) {
+                return reject("hive bin not page aligned");
+            }
+            let Some(header) = read_at(evidence, offset, 0x24)? else {
+                return Ok(None);
+            };
+            let bin_offset = le_u32(&header, 4) as u64;
+            let size = le_u32(&header, 8) as u64;
+            if size == 0 || !size.is_multiple_of(PAGE_SIZE) || !bin_offset.is_multiple_of(PAGE_SIZE)
+            {
+                return reject("hive bin offset or size not page aligned");
+            }
+            let cell = (le_u32(&header, 0x20) as i32).unsigned_abs() as u64;
+            Extent {
+                start: offset,
+                len: size,
+                validated: cell >= 8 && cell <= size - 0x20,
+            }
+        }
+        MemoryStructure::PoolProcess => {
+            let Some(start) = offset.checked_sub(POOL_TAG_OFFSET) else {
+                return Ok(None);
+            };
+            if !start.is_multiple_of(POOL_ALIGN) {
+                return reject("pool header not 16-byte aligned");
+            }
+            let Some(header) = read_at(evidence, start, 16)? else {
+                return Ok(None);
+            };
+            let len = header[2] as u64 * POOL_ALIGN;
+            if header[3] == 0 || !POOL_PROCESS_SIZE.contains(&len) {
+                return reject("pool block size or type out of range");
+            }
+            let validated =
+                read_at(evidence, start, len as usize)?.is_some_and(|block| has_image_name(&block));
+            Extent {
+                start,
+                len,
+                validated,
+            }
+        }
+    };
+    Ok(Some(extent))
+}
+
+fn reject(reason: &'static str) -> Result<Option<Extent>, CarveError> {
+    note_rejection(Rejection::Invalid(reason));
+    Ok(None)
+}
+
+/// Whether `block` holds a NUL-terminated `name.exe` as in `ImageFileName`.
+fn has_image_name(block: &[u8]) -> bool {
+    block.windows(5).enumerate().any(|(i, w)| {
+        w[..4].eq_ignore_ascii_case(b".exe")
+            && w[4] == 0
+            && i > 0
+            && block[i - 1].is_ascii_graphic()
+    })
+}
+
+fn read_at(
+    evidence: &dyn EvidenceSource,
+    offset: u64,
+    len: usize,
+) -> Result<Option<Vec<u8>>, CarveError> {
+    let mut buf = vec![0u8; len];
+    let n = evidence
+        .read_at(offset, &mut buf)
+        .map_err(|e| CarveError::Evidence(e.to_string()))?;
+    Ok((n == len).then_some(buf))
+}
+
+fn le_u32(buf: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([buf[at], buf[at + 1], buf[at + 2], buf[at + 3]])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evidence::RawFileSource;
+
+    fn carve(
+        structure: MemoryStructure,
+        image: &[u8],
+        offset: u64,
+    ) -> (Option<CarvedFile>, tempfile::TempDir) {
+        let temp_dir = tempfile::tempdir().expect("tempdir");
+        let input_path = temp_dir.path().join("image.bin");
+        std::fs::write(&input_path, image).expect("write image");
+        let output_root = temp_dir.path().join("out");
+        let evidence = RawFileSource::open(&input_path).expect("evidence");
+        let ctx = ExtractionContext {
+            run_id: "test",
+            output_root: &output_root,
+            evidence: &evidence,
+        };
+        let hit = NormalizedHit {
+            global_offset: offset,
+            file_type_id: structure.name().to_string(),
+            pattern_id: format!("{}_sig", structure.name()),
+        };
+        let handler = MemoryCarveHandler::new(structure, "bin".to_string(), 0, 0);
+        let file = handler.process_hit(&hit, &ctx).expect("carve");
+        (file, temp_dir)
+    }
+
+    #[test]
+    fn carves_crash_dump_header() {
+        let mut image = vec![0u8; 0x3000];
+        image[0x1000..0x1008].copy_from_slice(b"PAGEDU64");
+        image[0x1008..0x100C].copy_from_slice(&0x0Fu32.to_le_bytes());
+        let (file, _dir) = carve(MemoryStructure::CrashDump, &image, 0x1000);
+        let file = file.expect("dump header");
+        assert_eq!(file.size, 0x2000);
+        assert!(file.validated);
+    }
+
+    #[test]
+    fn hive_bins_and_hibernation_headers_need_page_alignment() {
+        let mut image = vec![0u8; 0x4000];
+        image[0x1000..0x1004].copy_from_slice(b"hbin");
+        image[0x1004..0x1008].copy_from_slice(&0x2000u32.to_le_bytes());
+        image[0x1008..0x100C].copy_from_slice(&0x1000u32.to_le_bytes());
+        image[0x1020..0x1024].copy_from_slice(&(-0x60i32).to_le_bytes());
+        let (file, _dir) = carve(MemoryStructure::HiveBin, &image, 0x1000);
+        let file = file.expect("hive bin");
+        assert_eq!((file.global_start, file.size), (0x1000, 0x1000));
+        assert!(file.validated);
+        assert!(carve(MemoryStructure::HiveBin, &image, 0x1010).0.is_none());
+
+        image[0x2000..0x2004].copy_from_slice(b"HIBR");
+        image[0x200C..0x2010].copy_from_slice(&0x1000u32.to_le_bytes());
+        let (file, _dir) = carve(MemoryStructure::Hibernation, &image, 0x2000);
+        assert!(file.expect("hibernation header").validated);
+        assert!(
+            carve(MemoryStructure::Hibernation, &image, 0x2004)
+                .0
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn carves_process_pool_allocation() {
+        let mut image = vec![0u8; 0x2000];
+        // PreviousSize, PoolIndex, BlockSize (0x80 * 16), PoolType, tag.
+        image[0x100..0x108].copy_from_slice(&[0x00, 0x00, 0x80, 0x02, b'P', b'r', b'o', b'c']);
+        image[0x600..0x60C].copy_from_slice(b"notepad.exe\0");
+        let (file, _dir) = carve(MemoryStructure::PoolProcess, &image, 0x104);
+        let file = file.expect("pool allocation");
+        assert_eq!((file.global_start, file.size), (0x100, 0x800));
+        assert!(file.validated);
+
+        // Tags that are not at a pool header are ignored.
+        image[0x208..0x20C].copy_from_slice(b"Proc");
+        assert!(
+            carve(MemoryStructure::PoolProcess, &image, 0x208)
+                .0
+                .is_none()
+        );
+    }
+}
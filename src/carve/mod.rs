@@ -1,30 +1,49 @@
 pub mod avi;
 pub mod bmp;
+pub mod boltdb;
+pub mod bplist;
+pub mod browser_cache;
 pub mod bzip2;
+pub mod cookies;
 pub mod elf;
 pub mod eml;
+pub mod ese;
+pub mod evtx;
 pub mod fb2;
+pub mod flac;
 pub mod footer;
 pub mod gif;
 pub mod gzip;
+pub mod heif;
 pub mod ico;
+pub mod journald;
 pub mod jpeg;
+pub mod lnk;
 pub mod lrf;
+pub mod macho;
+pub mod memory;
 pub mod mobi;
 pub mod mov;
 pub mod mp3;
 pub mod mp4;
 pub mod ogg;
 pub mod ole;
+pub mod pcap;
 pub mod pdf;
+pub mod pe;
 pub mod png;
+pub mod prefetch;
+pub mod pst;
 pub mod rar;
+pub mod regf;
 pub mod riff;
 pub mod rtf;
 pub mod sevenz;
 pub mod sqlite;
 pub mod tar;
+pub mod thumbcache;
 pub mod tiff;
+pub mod torrent;
 pub mod wav;
 pub mod webm;
 pub mod webp;
@@ -32,18 +51,29 @@ pub mod wmv;
 pub mod xz;
 pub mod zip;
 
+mod support;
+
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::{BufWriter, Write};
-use std::path::{Path, PathBuf};
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
 use serde::Serialize;
-use sha2::{Digest, Sha256};
 use thiserror::Error;
+use tracing::warn;
 
+use crate::config::RetentionPolicy;
 use crate::evidence::EvidenceSource;
 use crate::scanner::NormalizedHit;
 
+pub(crate) use support::{
+    CarveStream, build_carved_file, check_min_size, confined_path, crc32, crc32_update,
+    create_output, output_path, output_writer, read_evidence, relocate_classified,
+    sanitize_component, sanitize_extension, tagged_output_path, write_range,
+};
+
 /// Metadata about a carved file.
 ///
 /// # Example
@@ -53,6 +83,7 @@ use crate::scanner::NormalizedHit;
 /// let file = CarvedFile {
 ///     run_id: "example_run".to_string(),
 ///     file_type: "jpeg".to_string(),
+///     signature_type: "jpeg".to_string(),
 ///     path: "jpeg/jpeg_000000001000.jpg".to_string(),
 ///     extension: "jpg".to_string(),
 ///     global_start: 4096,
@@ -70,7 +101,12 @@ use crate::scanner::NormalizedHit;
 #[derive(Debug, Clone, Serialize)]
 pub struct CarvedFile {
     pub run_id: String,
+    /// Final type after content classification; also names the output directory.
     pub file_type: String,
+    /// Type whose signature matched (the handler's `file_type`). Differs from
+    /// `file_type` when classification found a more specific type, e.g. `zip`
+    /// carved as `docx` or `ole` as `xls`.
+    pub signature_type: String,
     pub path: String,
     pub extension: String,
     pub global_start: u64,
@@ -84,8 +120,11 @@ pub struct CarvedFile {
     pub pattern_id: Option<String>,
 }
 
+/// What a handler needs to carve one hit.
 pub struct ExtractionContext<'a> {
     pub run_id: &'a str,
+    /// Root of the carved output tree (`<run>/carved`); handlers write below it
+    /// and report paths relative to it.
     pub output_root: &'a Path,
     pub evidence: &'a dyn EvidenceSource,
 }
@@ -100,7 +139,64 @@ impl<'a> std::fmt::Debug for ExtractionContext<'a> {
     }
 }
 
+impl ExtractionContext<'_> {
+    /// `Err(CarveError::Cancelled)` once the run is cancelled and
+    /// `Err(CarveError::TimedOut)` once the carve runs past its time budget,
+    /// from the [`CancellationToken`] the pipeline installs around
+    /// `process_hit`. Handlers with long loops call it between reads and
+    /// return the error. Always `Ok` when no token is installed.
+    pub fn check_cancelled(&self) -> Result<(), CarveError> {
+        check_cancelled()
+    }
+}
+
+/// Stops a carve early: set when the run is cancelled, or expired when the
+/// carve runs past its time budget (`carve_timeout_secs`).
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    flag: Option<Arc<AtomicBool>>,
+    deadline: Option<Instant>,
+}
+
+impl CancellationToken {
+    /// Token cancelled once `flag` is set.
+    pub fn new(flag: Arc<AtomicBool>) -> Self {
+        Self {
+            flag: Some(flag),
+            deadline: None,
+        }
+    }
+
+    /// The same token, also expiring `timeout` from now.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.deadline = Instant::now().checked_add(timeout);
+        self
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.flag
+            .as_ref()
+            .is_some_and(|flag| flag.load(Ordering::Relaxed))
+    }
+
+    /// `Err(CarveError::Cancelled)` once cancelled, `Err(CarveError::TimedOut)`
+    /// past the deadline.
+    pub fn check(&self) -> Result<(), CarveError> {
+        if self.is_cancelled() {
+            Err(CarveError::Cancelled)
+        } else if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            Err(CarveError::TimedOut)
+        } else {
+            Ok(())
+        }
+    }
+}
+
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum CarveError {
     #[error("io error: {0}")]
     Io(#[from] std::io::Error),
@@ -112,262 +208,371 @@ pub enum CarveError {
     Truncated,
     #[error("unexpected eof")]
     Eof,
+    #[error("cancelled")]
+    Cancelled,
+    #[error("carve timed out")]
+    TimedOut,
 }
 
-pub trait CarveHandler: Send + Sync {
-    fn file_type(&self) -> &str;
-    fn extension(&self) -> &str;
-    fn process_hit(
-        &self,
-        hit: &NormalizedHit,
-        ctx: &ExtractionContext,
-    ) -> Result<Option<CarvedFile>, CarveError>;
+/// Why a handler returned no file for a hit, reported by `--trace-offset`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Rejection {
+    /// The carved size is below the type's `min_size`.
+    MinSize { size: u64, min_size: u64 },
+    /// A structure the handler requires is missing or inconsistent.
+    Invalid(&'static str),
 }
 
-pub struct CarveRegistry {
-    handlers: HashMap<String, Box<dyn CarveHandler>>,
+impl std::fmt::Display for Rejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Rejection::MinSize { size, min_size } => {
+                write!(f, "size {size} below min_size {min_size}")
+            }
+            Rejection::Invalid(reason) => write!(f, "invalid: {reason}"),
+        }
+    }
 }
 
-impl CarveRegistry {
-    pub fn new(handlers: HashMap<String, Box<dyn CarveHandler>>) -> Self {
-        Self { handlers }
+/// Size of the file being carved as its own structures declare it, e.g. the
+/// extent of an OLE FAT or the end of a ZIP EOCD record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeclaredSize {
+    /// Bytes from the hit offset to the declared end of the file.
+    pub size: u64,
+    /// Structure the size was read from (`fat`, `eocd`, `linearization`).
+    pub source: &'static str,
+}
+
+/// Why a carve ended before the end of its file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Truncation {
+    /// A configured limit (`max_size`, `declared_size_factor`) stopped the
+    /// carve; raising it recovers more of the file.
+    Policy,
+    /// The carve ran into the end of the evidence.
+    Evidence,
+}
+
+impl Truncation {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Policy => "policy",
+            Self::Evidence => "evidence",
+        }
     }
 
-    pub fn get(&self, file_type_id: &str) -> Option<&dyn CarveHandler> {
-        self.handlers.get(file_type_id).map(|h| h.as_ref())
+    /// Classify a truncated carve: one that reached the end of an evidence
+    /// source of `evidence_len` bytes was cut by the evidence, any other by
+    /// policy. `None` when `file` is not truncated.
+    pub fn of(file: &CarvedFile, evidence_len: u64) -> Option<Self> {
+        if !file.truncated {
+            None
+        } else if file.global_end.saturating_add(1) >= evidence_len {
+            Some(Self::Evidence)
+        } else {
+            Some(Self::Policy)
+        }
     }
 }
 
-pub fn output_path(
-    output_root: &Path,
-    file_type: &str,
-    extension: &str,
-    global_start: u64,
-) -> Result<(PathBuf, String), CarveError> {
-    let safe_type = sanitize_component(file_type);
-    let safe_ext = sanitize_extension(extension);
-    let dir = output_root.join(&safe_type);
-    std::fs::create_dir_all(&dir)?;
-    let base = format!("{}_{}", safe_type, format!("{:012X}", global_start));
-    let filename = if safe_ext.is_empty() {
-        base
-    } else {
-        format!("{base}.{safe_ext}")
-    };
-    let full_path = dir.join(&filename);
-    let rel_path = full_path
-        .strip_prefix(output_root)
-        .unwrap_or(&full_path)
-        .to_string_lossy()
-        .to_string();
-    Ok((full_path, rel_path))
+thread_local! {
+    static REJECTION: Cell<Option<Rejection>> = const { Cell::new(None) };
+    static DECLARED_SIZE: Cell<Option<DeclaredSize>> = const { Cell::new(None) };
+    static CANCELLATION: RefCell<Option<CancellationToken>> = const { RefCell::new(None) };
+    static COMPANIONS: RefCell<Vec<CarvedFile>> = const { RefCell::new(Vec::new()) };
+    static WRITE_BUFFER: Cell<usize> = const { Cell::new(DEFAULT_WRITE_BUFFER) };
 }
 
-fn sanitize_component(value: &str) -> String {
-    let mut out = String::with_capacity(value.len());
-    for ch in value.chars() {
-        if ch.is_ascii_alphanumeric() || ch == '_' || ch == '-' || ch == '.' {
-            out.push(ch);
-        } else {
-            out.push('_');
+/// Write buffer of carved output when the pipeline sets none.
+pub const DEFAULT_WRITE_BUFFER: usize = 64 * 1024;
+
+/// Run `f` (usually a `process_hit` call) with `token` installed for
+/// [`ExtractionContext::check_cancelled`] on this thread.
+pub fn with_cancellation<R>(token: CancellationToken, f: impl FnOnce() -> R) -> R {
+    /// Puts the previous token back, also when `f` panics.
+    struct Restore(Option<CancellationToken>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let previous = self.0.take();
+            CANCELLATION.with(|cell| *cell.borrow_mut() = previous);
         }
     }
-    while out.contains("..") {
-        out = out.replace("..", "_");
-    }
-    let trimmed = out.trim_matches('.').to_string();
-    if trimmed.is_empty() {
-        "unknown".to_string()
-    } else {
-        trimmed
-    }
+
+    let _restore = Restore(CANCELLATION.with(|cell| cell.replace(Some(token))));
+    f()
 }
 
-pub fn sanitize_extension(ext: &str) -> String {
-    sanitize_component(ext)
-        .trim_start_matches('.')
-        .to_ascii_lowercase()
+/// Check the token installed by [`with_cancellation`] on this thread.
+pub(crate) fn check_cancelled() -> Result<(), CarveError> {
+    CANCELLATION.with(|cell| {
+        cell.borrow()
+            .as_ref()
+            .map_or(Ok(()), CancellationToken::check)
+    })
 }
 
-/// Helper to build a CarvedFile result, reducing boilerplate in handlers
-pub fn build_carved_file(
-    run_id: &str,
-    file_type: &str,
-    extension: &str,
-    rel_path: String,
-    global_start: u64,
-    size: u64,
-    md5_hex: String,
-    sha256_hex: String,
-    validated: bool,
-    truncated: bool,
-    errors: Vec<String>,
-    pattern_id: &str,
-) -> CarvedFile {
-    let global_end = if size == 0 {
-        global_start
-    } else {
-        global_start + size - 1
-    };
-
-    CarvedFile {
-        run_id: run_id.to_string(),
-        file_type: file_type.to_string(),
-        path: rel_path,
-        extension: extension.to_string(),
-        global_start,
-        global_end,
-        size,
-        md5: Some(md5_hex),
-        sha256: Some(sha256_hex),
-        validated,
-        truncated,
-        errors,
-        pattern_id: Some(pattern_id.to_string()),
-    }
+/// Record why the current `process_hit` call returns `Ok(None)`. Handlers call
+/// this on the carve worker thread; the pipeline collects it with
+/// [`take_rejection`].
+pub fn note_rejection(rejection: Rejection) {
+    REJECTION.with(|cell| cell.set(Some(rejection)));
 }
 
-/// Check if carved size meets minimum requirement, delete file if not
-pub fn check_min_size(full_path: &Path, size: u64, min_size: u64) -> bool {
-    if size < min_size {
-        let _ = std::fs::remove_file(full_path);
-        false
-    } else {
-        true
-    }
+/// Take the reason noted by the last rejecting handler on this thread.
+pub(crate) fn take_rejection() -> Option<Rejection> {
+    REJECTION.with(Cell::take)
 }
 
-pub(crate) struct CarveStream<'a> {
-    evidence: &'a dyn EvidenceSource,
-    offset: u64,
-    max_size: u64,
-    written: u64,
-    writer: BufWriter<File>,
-    md5: md5::Context,
-    sha256: Sha256,
+/// Record the size the current hit's format declares. Handlers that read a
+/// size field call this from `process_hit`; the pipeline records it next to
+/// the carve, so a short carve can be told apart from a short file.
+pub fn note_declared_size(declared: DeclaredSize) {
+    DECLARED_SIZE.with(|cell| cell.set(Some(declared)));
 }
 
-impl<'a> CarveStream<'a> {
-    pub(crate) fn new(
-        evidence: &'a dyn EvidenceSource,
-        offset: u64,
-        max_size: u64,
-        writer: File,
-    ) -> Self {
-        Self {
-            evidence,
-            offset,
-            max_size,
-            written: 0,
-            writer: BufWriter::new(writer),
-            md5: md5::Context::new(),
-            sha256: Sha256::new(),
-        }
-    }
+/// Take the size noted by the last handler on this thread.
+pub(crate) fn take_declared_size() -> Option<DeclaredSize> {
+    DECLARED_SIZE.with(Cell::take)
+}
 
-    pub(crate) fn read_exact(&mut self, len: usize) -> Result<Vec<u8>, CarveError> {
-        if self.max_size > 0 && self.written.saturating_add(len as u64) > self.max_size {
-            return Err(CarveError::Truncated);
-        }
+/// Record a file carved together with the current hit's file, e.g. the WAL
+/// stored after a SQLite database. The pipeline records it after the carve it
+/// belongs to, with a `carve_companions` record linking the two, and drops it
+/// when that carve is not kept.
+pub fn note_companion(file: CarvedFile) {
+    COMPANIONS.with(|cell| cell.borrow_mut().push(file));
+}
 
-        let mut buf = vec![0u8; len];
-        let mut read = 0usize;
-        while read < len {
-            let n = self
-                .evidence
-                .read_at(self.offset, &mut buf[read..])
-                .map_err(|e| CarveError::Evidence(e.to_string()))?;
-            if n == 0 {
-                return Err(CarveError::Eof);
-            }
-            self.write_bytes(&buf[read..read + n])?;
-            read += n;
-        }
+/// Take the companions noted by the last handler on this thread.
+pub(crate) fn take_companions() -> Vec<CarvedFile> {
+    COMPANIONS.with(|cell| std::mem::take(&mut *cell.borrow_mut()))
+}
+
+/// Set the write buffer of carved output files on this thread
+/// (`carve_write_buffer`). Larger buffers coalesce writes into fewer, larger
+/// requests, which network file systems handle far better than many small
+/// ones.
+pub fn set_write_buffer(bytes: usize) {
+    WRITE_BUFFER.with(|cell| cell.set(bytes.max(1)));
+}
+
+/// Forget the output directories carves on this thread created, which are
+/// otherwise created only once per thread. The pipeline calls this on each
+/// thread a run carves on; callers that reuse a thread to carve into an
+/// output tree that may have been removed should do the same.
+pub fn forget_output_dirs() {
+    support::forget_created_dirs();
+}
+
+/// Write buffer set by [`set_write_buffer`] on this thread.
+pub(crate) fn write_buffer() -> usize {
+    WRITE_BUFFER.with(Cell::get)
+}
 
-        Ok(buf)
+/// Carve limit for a file that declares `declared` bytes: `factor` times the
+/// declared size when the type sets `declared_size_factor`, else `max_size`
+/// (`0` = unlimited).
+pub(crate) fn declared_limit(max_size: u64, factor: Option<f64>, declared: u64) -> u64 {
+    match factor {
+        Some(factor) => (declared as f64 * factor).ceil() as u64,
+        None => max_size,
     }
+}
+
+/// Validates and extracts one file type at signature hits.
+///
+/// Part of the stable API (see `docs/api.md`). Handlers are registered per
+/// `file_type_id` in a [`CarveRegistry`] and called concurrently from the carve
+/// workers. `Ok(None)` means the hit is not a file of this type (optionally
+/// explained with [`note_rejection`]); `Err` is counted as a carve error.
+///
+/// # Example
+/// ```rust
+/// use swiftbeaver::evidence::RawFileSource;
+/// use swiftbeaver::{
+///     CarveError, CarveHandler, CarvedFile, EvidenceSource, ExtractionContext, NormalizedHit,
+/// };
+///
+/// /// Carves fixed 16-byte records.
+/// struct RecordHandler;
+///
+/// impl CarveHandler for RecordHandler {
+///     fn file_type(&self) -> &str {
+///         "record"
+///     }
+///
+///     fn extension(&self) -> &str {
+///         "bin"
+///     }
+///
+///     fn process_hit(
+///         &self,
+///         hit: &NormalizedHit,
+///         ctx: &ExtractionContext,
+///     ) -> Result<Option<CarvedFile>, CarveError> {
+///         let mut record = [0u8; 16];
+///         let n = ctx
+///             .evidence
+///             .read_at(hit.global_offset, &mut record)
+///             .map_err(|err| CarveError::Evidence(err.to_string()))?;
+///         if n < record.len() {
+///             return Ok(None);
+///         }
+///         let path = format!("record/record_{:012X}.bin", hit.global_offset);
+///         std::fs::create_dir_all(ctx.output_root.join("record"))?;
+///         std::fs::write(ctx.output_root.join(&path), record)?;
+///         Ok(Some(CarvedFile {
+///             run_id: ctx.run_id.to_string(),
+///             file_type: self.file_type().to_string(),
+///             signature_type: self.file_type().to_string(),
+///             path,
+///             extension: self.extension().to_string(),
+///             global_start: hit.global_offset,
+///             global_end: hit.global_offset + 15,
+///             size: 16,
+///             md5: None,
+///             sha256: None,
+///             validated: true,
+///             truncated: false,
+///             errors: Vec::new(),
+///             pattern_id: Some(hit.pattern_id.clone()),
+///         }))
+///     }
+/// }
+///
+/// let dir = std::env::temp_dir().join("SwiftBeaver_carve_example");
+/// std::fs::create_dir_all(&dir).unwrap();
+/// let image = dir.join("image.bin");
+/// std::fs::write(&image, [0xABu8; 32]).unwrap();
+/// let evidence = RawFileSource::open(&image).unwrap();
+/// let ctx = ExtractionContext {
+///     run_id: "example_run",
+///     output_root: &dir,
+///     evidence: &evidence,
+/// };
+/// let hit = NormalizedHit {
+///     global_offset: 8,
+///     file_type_id: "record".to_string(),
+///     pattern_id: "record_magic".to_string(),
+/// };
+/// let file = RecordHandler.process_hit(&hit, &ctx).unwrap().expect("carved");
+/// assert_eq!(file.path, "record/record_000000000008.bin");
+/// ```
+pub trait CarveHandler: Send + Sync {
+    /// The `file_type_id` this handler is registered for.
+    fn file_type(&self) -> &str;
+    /// Extension of carved files, without the dot.
+    fn extension(&self) -> &str;
+    /// Carve the file starting at `hit.global_offset`, writing it below
+    /// `ctx.output_root`.
+    fn process_hit(
+        &self,
+        hit: &NormalizedHit,
+        ctx: &ExtractionContext,
+    ) -> Result<Option<CarvedFile>, CarveError>;
+}
+
+/// Handlers and retention policies by `file_type_id`.
+pub struct CarveRegistry {
+    handlers: HashMap<String, Box<dyn CarveHandler>>,
+    retention: HashMap<String, RetentionPolicy>,
+}
 
-    pub(crate) fn write_bytes(&mut self, buf: &[u8]) -> Result<(), CarveError> {
-        if self.max_size > 0 && self.written.saturating_add(buf.len() as u64) > self.max_size {
-            return Err(CarveError::Truncated);
+impl CarveRegistry {
+    pub fn new(handlers: HashMap<String, Box<dyn CarveHandler>>) -> Self {
+        Self {
+            handlers,
+            retention: HashMap::new(),
         }
-        self.writer.write_all(buf)?;
-        self.md5.consume(buf);
-        self.sha256.update(buf);
-        self.offset = self.offset.saturating_add(buf.len() as u64);
-        self.written = self.written.saturating_add(buf.len() as u64);
-        Ok(())
     }
 
-    pub(crate) fn finish(mut self) -> Result<(u64, String, String), CarveError> {
-        self.writer.flush()?;
-        let md5 = format!("{:x}", self.md5.compute());
-        let sha256 = hex::encode(self.sha256.finalize());
-        Ok((self.written, md5, sha256))
+    /// Set retention policies; types without an entry keep all files.
+    pub fn with_retention(mut self, retention: HashMap<String, RetentionPolicy>) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    pub fn get(&self, file_type_id: &str) -> Option<&dyn CarveHandler> {
+        self.handlers.get(file_type_id).map(|h| h.as_ref())
     }
 
-    /// Get the number of bytes written so far
-    pub(crate) fn bytes_written(&self) -> u64 {
-        self.written
+    /// Whether `file`, carved for `file_type_id`, stays on disk.
+    pub fn keeps(&self, file_type_id: &str, file: &CarvedFile) -> bool {
+        self.retention
+            .get(file_type_id)
+            .is_none_or(|policy| policy.keeps(file.validated))
     }
 }
 
-pub(crate) fn write_range(
-    ctx: &ExtractionContext,
-    start: u64,
-    end: u64,
-    file: &mut File,
-    md5: &mut md5::Context,
-    sha256: &mut Sha256,
-) -> Result<(u64, bool), CarveError> {
-    let mut offset = start;
-    let mut remaining = end.saturating_sub(start);
-    let mut bytes_written = 0u64;
-    let buf_size = 64 * 1024;
-
-    while remaining > 0 {
-        let read_len = remaining.min(buf_size as u64) as usize;
-        let mut buf = vec![0u8; read_len];
-        let n = ctx
-            .evidence
-            .read_at(offset, &mut buf)
-            .map_err(|e| CarveError::Evidence(e.to_string()))?;
-        if n == 0 {
-            return Ok((bytes_written, true));
-        }
-        buf.truncate(n);
-        file.write_all(&buf)?;
-        md5.consume(&buf);
-        sha256.update(&buf);
-        bytes_written = bytes_written.saturating_add(buf.len() as u64);
-        offset = offset.saturating_add(buf.len() as u64);
-        remaining = remaining.saturating_sub(buf.len() as u64);
-        if n < read_len {
-            return Ok((bytes_written, true));
-        }
+/// Turn a carve whose path leaves `output_root` into an error. Built-in
+/// handlers name files through [`output_path`], but handlers registered by
+/// library users report any path they like.
+pub(crate) fn confine(
+    output_root: &Path,
+    result: Result<Option<CarvedFile>, CarveError>,
+) -> Result<Option<CarvedFile>, CarveError> {
+    match result {
+        Ok(Some(file)) => confined_path(output_root, &file.path).map(|_| Some(file)),
+        other => other,
     }
+}
 
-    Ok((bytes_written, false))
+/// Delete a carved file its type's retention policy does not keep.
+pub(crate) fn discard(path: &Path) {
+    if let Err(err) = std::fs::remove_file(path) {
+        warn!("failed to discard {}: {err}", path.display());
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{output_path, sanitize_component, sanitize_extension};
-    use tempfile::tempdir;
+    use super::*;
 
     #[test]
-    fn sanitizes_output_path_components() {
-        let dir = tempdir().expect("tempdir");
-        let (full, rel) =
-            output_path(dir.path(), "../weird", "../JPG", 0x1234).expect("output path");
-        assert!(full.starts_with(dir.path()));
-        assert!(!rel.contains(".."));
-        assert!(sanitize_component("../weird").contains("weird"));
+    fn installed_token_reaches_check_cancelled() {
+        assert!(check_cancelled().is_ok());
+        let flag = Arc::new(AtomicBool::new(false));
+        let token = CancellationToken::new(flag.clone());
+        with_cancellation(token.clone(), || {
+            assert!(check_cancelled().is_ok());
+            flag.store(true, Ordering::Relaxed);
+            assert!(matches!(check_cancelled(), Err(CarveError::Cancelled)));
+        });
+        assert!(check_cancelled().is_ok());
+
+        let expired = CancellationToken::default().with_timeout(Duration::ZERO);
+        let result = with_cancellation(expired, check_cancelled);
+        assert!(matches!(result, Err(CarveError::TimedOut)));
     }
 
     #[test]
-    fn sanitizes_extension() {
-        assert_eq!(sanitize_extension(".JPG"), "jpg");
-        assert_eq!(sanitize_extension("..bad"), "_bad");
+    fn confine_refuses_handler_paths_outside_the_root() {
+        let root = Path::new("/run/carved");
+        let file = |path: &str| {
+            build_carved_file(
+                "r",
+                "x",
+                "bin",
+                path.to_string(),
+                0,
+                1,
+                String::new(),
+                String::new(),
+                true,
+                false,
+                Vec::new(),
+                "p",
+            )
+        };
+        assert!(confine(root, Ok(Some(file("x/x_000000000000.bin")))).is_ok());
+        for hostile in ["../../etc/cron.d/x", "/tmp/x", "x/../../y"] {
+            let result = confine(root, Ok(Some(file(hostile))));
+            assert!(matches!(result, Err(CarveError::Invalid(_))), "{hostile}");
+        }
+        assert!(matches!(confine(root, Ok(None)), Ok(None)));
     }
 }
@@ -3,14 +3,16 @@
 //! QuickTime files use the same atom/box structure as MP4, but typically use
 //! the 'qt  ' brand in the ftyp box.
 
-use std::fs::File;
 use std::io::Write;
 
 use sha2::{Digest, Sha256};
 
+use crate::carve::mp4::open_mdat_payload;
 use crate::carve::{
-    CarveError, CarveHandler, CarvedFile, ExtractionContext, output_path, write_range,
+    CarveError, CarveHandler, CarvedFile, ExtractionContext, Rejection, create_output,
+    note_rejection, output_path, read_evidence, write_range,
 };
+use crate::parsers::{mp4, nal};
 use crate::scanner::NormalizedHit;
 
 const BOX_HEADER_LEN: usize = 8;
@@ -50,6 +52,7 @@ impl CarveHandler for MovCarveHandler {
         let mut truncated = false;
         let mut seen_ftyp = false;
         let mut seen_moov = false;
+        let mut seen_mdat = false;
 
         let mut offset = hit.global_offset;
         let mut last_good = hit.global_offset;
@@ -79,6 +82,31 @@ impl CarveHandler for MovCarveHandler {
 
             let size32 = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as u64;
             let box_type = &header[4..8];
+            if !mp4::is_box_type(box_type) {
+                if seen_ftyp && (seen_moov || seen_mdat) {
+                    break;
+                }
+                return Ok(None);
+            }
+
+            if box_type == b"mdat" && seen_ftyp && !seen_moov {
+                seen_mdat = true;
+                if let Some(payload_start) = open_mdat_payload(ctx, offset, size32) {
+                    let limit = if self.max_size > 0 {
+                        hit.global_offset.saturating_add(self.max_size)
+                    } else {
+                        ctx.evidence.len()
+                    };
+                    let mut read = |at: u64, buf: &mut [u8]| read_evidence(ctx, at, buf);
+                    match nal::media_end(&mut read, payload_start, limit) {
+                        Some(end) => {
+                            last_good = end;
+                            break;
+                        }
+                        None => return Ok(None),
+                    }
+                }
+            }
 
             let (box_size, header_len) = if size32 == 1 {
                 let ext = match read_exact_at(ctx, offset, EXTENDED_HEADER_LEN) {
@@ -100,6 +128,9 @@ impl CarveHandler for MovCarveHandler {
                 if seen_ftyp && seen_moov {
                     break;
                 }
+                if seen_ftyp && seen_mdat {
+                    break;
+                }
                 truncated = true;
                 errors.push("mov box size 0 encountered".to_string());
                 break;
@@ -108,7 +139,7 @@ impl CarveHandler for MovCarveHandler {
             };
 
             if box_size < header_len || box_size == 0 {
-                if seen_ftyp && seen_moov {
+                if seen_ftyp && (seen_moov || seen_mdat) {
                     break;
                 }
                 return Ok(None);
@@ -144,9 +175,12 @@ impl CarveHandler for MovCarveHandler {
             last_good = offset;
         }
 
-        if !seen_ftyp || !seen_moov {
+        if !seen_ftyp || !(seen_moov || seen_mdat) {
             return Ok(None);
         }
+        if !seen_moov {
+            errors.push("no moov box; the recording was not finalized".to_string());
+        }
 
         let (full_path, rel_path) = output_path(
             ctx.output_root,
@@ -154,7 +188,7 @@ impl CarveHandler for MovCarveHandler {
             &self.extension,
             hit.global_offset,
         )?;
-        let mut file = File::create(&full_path)?;
+        let mut file = create_output(&full_path)?;
         let mut md5 = md5::Context::new();
         let mut sha256 = Sha256::new();
 
@@ -178,6 +212,11 @@ impl CarveHandler for MovCarveHandler {
         file.flush()?;
 
         if written < self.min_size {
+            note_rejection(Rejection::MinSize {
+                size: written,
+
+                min_size: self.min_size,
+            });
             let _ = std::fs::remove_file(&full_path);
             return Ok(None);
         }
@@ -193,6 +232,7 @@ impl CarveHandler for MovCarveHandler {
         Ok(Some(CarvedFile {
             run_id: ctx.run_id.to_string(),
             file_type: self.file_type().to_string(),
+            signature_type: self.file_type().to_string(),
             path: rel_path,
             extension: self.extension.clone(),
             global_start: hit.global_offset,
@@ -200,7 +240,7 @@ impl CarveHandler for MovCarveHandler {
             size: written,
             md5: Some(md5_hex),
             sha256: Some(sha256_hex),
-            validated: !truncated,
+            validated: !truncated && seen_moov,
             truncated,
             errors,
             pattern_id: Some(hit.pattern_id.clone()),
@@ -1,11 +1,12 @@
-use std::fs::File;
 use std::io::Write;
 
 use sha2::{Digest, Sha256};
 
 use crate::carve::{
-    CarveError, CarveHandler, CarvedFile, ExtractionContext, output_path, write_range,
+    CarveError, CarveHandler, CarvedFile, ExtractionContext, Rejection, create_output, heif,
+    note_rejection, output_path, read_evidence, write_range,
 };
+use crate::parsers::{mp4, nal};
 use crate::scanner::NormalizedHit;
 
 const BOX_HEADER_LEN: usize = 8;
@@ -47,6 +48,7 @@ impl CarveHandler for Mp4CarveHandler {
         let mut truncated = false;
         let mut seen_ftyp = false;
         let mut seen_moov = false;
+        let mut seen_mdat = false;
 
         let mut offset = hit.global_offset;
         let mut last_good = hit.global_offset;
@@ -76,6 +78,31 @@ impl CarveHandler for Mp4CarveHandler {
 
             let size32 = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as u64;
             let box_type = &header[4..8];
+            if !mp4::is_box_type(box_type) {
+                if seen_ftyp && (seen_moov || seen_mdat) {
+                    break;
+                }
+                return Ok(None);
+            }
+
+            if box_type == b"mdat" && seen_ftyp && !seen_moov {
+                seen_mdat = true;
+                if let Some(payload_start) = open_mdat_payload(ctx, offset, size32) {
+                    let limit = if self.max_size > 0 {
+                        hit.global_offset.saturating_add(self.max_size)
+                    } else {
+                        ctx.evidence.len()
+                    };
+                    let mut read = |at: u64, buf: &mut [u8]| read_evidence(ctx, at, buf);
+                    match nal::media_end(&mut read, payload_start, limit) {
+                        Some(end) => {
+                            last_good = end;
+                            break;
+                        }
+                        None => return Ok(None),
+                    }
+                }
+            }
 
             let (box_size, header_len) = if size32 == 1 {
                 let ext = match read_exact_at(ctx, offset, EXTENDED_HEADER_LEN) {
@@ -97,6 +124,9 @@ impl CarveHandler for Mp4CarveHandler {
                 if seen_ftyp && seen_moov {
                     break;
                 }
+                if seen_ftyp && seen_mdat {
+                    break;
+                }
                 truncated = true;
                 errors.push("mp4 box size 0 encountered".to_string());
                 break;
@@ -105,7 +135,7 @@ impl CarveHandler for Mp4CarveHandler {
             };
 
             if box_size < header_len || box_size == 0 {
-                if seen_ftyp && seen_moov {
+                if seen_ftyp && (seen_moov || seen_mdat) {
                     break;
                 }
                 return Ok(None);
@@ -119,6 +149,10 @@ impl CarveHandler for Mp4CarveHandler {
                     if brand == b"qt  " && !self.allow_quicktime {
                         return Ok(None);
                     }
+                    if heif::is_heif_brand(&brand) {
+                        note_rejection(Rejection::Invalid("HEIF brand, carved as heif"));
+                        return Ok(None);
+                    }
                 }
                 seen_ftyp = true;
             }
@@ -139,9 +173,12 @@ impl CarveHandler for Mp4CarveHandler {
             last_good = offset;
         }
 
-        if !seen_ftyp || !seen_moov {
+        if !seen_ftyp || !(seen_moov || seen_mdat) {
             return Ok(None);
         }
+        if !seen_moov {
+            errors.push("no moov box; the recording was not finalized".to_string());
+        }
 
         let (full_path, rel_path) = output_path(
             ctx.output_root,
@@ -149,7 +186,7 @@ impl CarveHandler for Mp4CarveHandler {
             &self.extension,
             hit.global_offset,
         )?;
-        let mut file = File::create(&full_path)?;
+        let mut file = create_output(&full_path)?;
         let mut md5 = md5::Context::new();
         let mut sha256 = Sha256::new();
 
@@ -173,6 +210,11 @@ impl CarveHandler for Mp4CarveHandler {
         file.flush()?;
 
         if written < self.min_size {
+            note_rejection(Rejection::MinSize {
+                size: written,
+
+                min_size: self.min_size,
+            });
             let _ = std::fs::remove_file(&full_path);
             return Ok(None);
         }
@@ -188,6 +230,7 @@ impl CarveHandler for Mp4CarveHandler {
         Ok(Some(CarvedFile {
             run_id: ctx.run_id.to_string(),
             file_type: self.file_type().to_string(),
+            signature_type: self.file_type().to_string(),
             path: rel_path,
             extension: self.extension.clone(),
             global_start: hit.global_offset,
@@ -195,7 +238,7 @@ impl CarveHandler for Mp4CarveHandler {
             size: written,
             md5: Some(md5_hex),
             sha256: Some(sha256_hex),
-            validated: !truncated,
+            validated: !truncated && seen_moov,
             truncated,
             errors,
             pattern_id: Some(hit.pattern_id.clone()),
@@ -203,6 +246,20 @@ impl CarveHandler for Mp4CarveHandler {
     }
 }
 
+/// Start of the payload of an `mdat` box whose size was never written (0, or
+/// a 64-bit size of 0), as a recorder leaves it when it stops before
+/// finalizing the file. Such an `mdat` runs to the end of the file, and in
+/// evidence its end is found by walking the frames (`parsers::nal`).
+pub(crate) fn open_mdat_payload(ctx: &ExtractionContext, offset: u64, size32: u64) -> Option<u64> {
+    match size32 {
+        0 => Some(offset + BOX_HEADER_LEN as u64),
+        1 => read_exact_at(ctx, offset + BOX_HEADER_LEN as u64, 8)
+            .filter(|size64| size64.iter().all(|byte| *byte == 0))
+            .map(|_| offset + EXTENDED_HEADER_LEN as u64),
+        _ => None,
+    }
+}
+
 fn read_exact_at(ctx: &ExtractionContext, offset: u64, len: usize) -> Option<Vec<u8>> {
     let mut buf = vec![0u8; len];
     let n = ctx.evidence.read_at(offset, &mut buf).ok()?;
@@ -330,4 +387,39 @@ mod tests {
         assert!(carved.validated);
         assert_eq!(carved.size, mov.len() as u64);
     }
+
+    #[test]
+    fn keeps_recording_without_moov_up_to_its_last_frame() {
+        use crate::parsers::mp4::testing::unfinished;
+        use crate::parsers::nal::testing::h264_media;
+
+        let temp_dir = tempfile::tempdir().expect("tempdir");
+        let output_root = temp_dir.path().join("out");
+        std::fs::create_dir_all(&output_root).expect("output root");
+
+        let recording = unfinished(b"isom", &h264_media(12, 4, 5));
+        let mut image = recording.clone();
+        image.extend_from_slice(&[0u8; 8192]);
+        let input_path = temp_dir.path().join("image.bin");
+        std::fs::write(&input_path, &image).expect("write image");
+
+        let evidence = RawFileSource::open(&input_path).expect("evidence");
+        let ctx = ExtractionContext {
+            run_id: "test",
+            output_root: &output_root,
+            evidence: &evidence,
+        };
+        let handler = Mp4CarveHandler::new("mp4".to_string(), 8, 0, false);
+        let hit = NormalizedHit {
+            global_offset: 0,
+            file_type_id: "mp4".to_string(),
+            pattern_id: "mp4_ftyp_18".to_string(),
+        };
+
+        let carved = handler.process_hit(&hit, &ctx).expect("carve");
+        let carved = carved.expect("carved");
+        assert_eq!(carved.size, recording.len() as u64);
+        assert!(!carved.validated && !carved.truncated);
+        assert!(carved.errors[0].contains("no moov"));
+    }
 }
@@ -1,15 +1,27 @@
 //! OGG container carving handler.
 //!
-//! Ogg streams consist of pages with a fixed header and lacing table.
-//! We walk pages until an end-of-stream flag is observed.
+//! Ogg streams consist of pages with a fixed header, a lacing table, and a
+//! CRC over the whole page. The walk starts at a beginning-of-stream page and
+//! follows each logical stream (several when audio and video are
+//! multiplexed) by serial number and page sequence until every stream has
+//! ended. A page that fails its CRC or belongs to no open stream ends the
+//! walk early; the pages before it are kept, unvalidated.
 
-use std::fs::File;
+use std::collections::HashMap;
+
+use sha2::{Digest, Sha256};
 
 use crate::carve::{
-    CarveError, CarveHandler, CarveStream, CarvedFile, ExtractionContext, output_path,
+    CarveError, CarveHandler, CarvedFile, ExtractionContext, Rejection, create_output,
+    note_rejection, output_path, read_evidence, write_range,
 };
 use crate::scanner::NormalizedHit;
 
+const PAGE_HEADER_LEN: usize = 27;
+const FLAG_CONTINUED: u8 = 0x01;
+const FLAG_BOS: u8 = 0x02;
+const FLAG_EOS: u8 = 0x04;
+
 pub struct OggCarveHandler {
     extension: String,
     min_size: u64,
@@ -26,6 +38,166 @@ impl OggCarveHandler {
     }
 }
 
+/// A page read from the evidence.
+struct Page {
+    flags: u8,
+    serial: u32,
+    sequence: u32,
+    len: u64,
+    crc_ok: bool,
+}
+
+enum PageRead {
+    Page(Page),
+    NotAPage,
+    Eof,
+}
+
+fn read_page(read: &mut dyn FnMut(u64, &mut [u8]) -> usize, offset: u64) -> PageRead {
+    let mut header = [0u8; PAGE_HEADER_LEN];
+    let n = read(offset, &mut header);
+    if n < 4 {
+        return if n == 0 || b"OggS".starts_with(&header[..n]) {
+            PageRead::Eof
+        } else {
+            PageRead::NotAPage
+        };
+    }
+    if &header[0..4] != b"OggS" {
+        return PageRead::NotAPage;
+    }
+    if n < PAGE_HEADER_LEN {
+        return PageRead::Eof;
+    }
+    if header[4] != 0 {
+        return PageRead::NotAPage;
+    }
+    let segment_count = usize::from(header[26]);
+    let mut segments = vec![0u8; segment_count];
+    if read(offset + PAGE_HEADER_LEN as u64, &mut segments) < segment_count {
+        return PageRead::Eof;
+    }
+    let body_len: usize = segments.iter().map(|len| usize::from(*len)).sum();
+    let mut body = vec![0u8; body_len];
+    let body_at = offset + (PAGE_HEADER_LEN + segment_count) as u64;
+    if read(body_at, &mut body) < body_len {
+        return PageRead::Eof;
+    }
+
+    let stored_crc = u32::from_le_bytes([header[22], header[23], header[24], header[25]]);
+    header[22..26].fill(0);
+    let crc = ogg_crc(ogg_crc(ogg_crc(0, &header), &segments), &body);
+    PageRead::Page(Page {
+        flags: header[5],
+        serial: u32::from_le_bytes([header[14], header[15], header[16], header[17]]),
+        sequence: u32::from_le_bytes([header[18], header[19], header[20], header[21]]),
+        len: (PAGE_HEADER_LEN + segment_count + body_len) as u64,
+        crc_ok: crc == stored_crc,
+    })
+}
+
+/// How a page walk ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stop {
+    /// Every logical stream reached its end-of-stream page.
+    Complete,
+    /// A page failed its CRC, broke a stream's sequence, or was not a page.
+    Damaged,
+    /// The next page would pass the limit.
+    Limit,
+    /// The evidence ended inside a page.
+    Eof,
+}
+
+struct Walk {
+    end: u64,
+    pages: u64,
+    stop: Stop,
+}
+
+/// Walk the pages of a stream whose first page is at 0, up to `limit` bytes.
+fn walk(read: &mut dyn FnMut(u64, &mut [u8]) -> usize, limit: u64) -> Result<Walk, &'static str> {
+    let first = match read_page(read, 0) {
+        PageRead::Page(page) => page,
+        PageRead::NotAPage => return Err("ogg version unsupported"),
+        PageRead::Eof => return Err("evidence ended inside the first ogg page"),
+    };
+    if first.flags & FLAG_BOS == 0 || first.flags & FLAG_CONTINUED != 0 {
+        return Err("ogg page is not the start of a stream");
+    }
+    if !first.crc_ok {
+        return Err("ogg page CRC mismatch");
+    }
+
+    // Next expected sequence number of each open logical stream.
+    let mut open: HashMap<u32, u32> = HashMap::new();
+    let mut offset = 0u64;
+    let mut pages = 0u64;
+    let mut page = first;
+    let stop = loop {
+        if offset.saturating_add(page.len) > limit {
+            break Stop::Limit;
+        }
+        if page.flags & FLAG_BOS != 0 {
+            // New streams start in the group of BOS pages at the beginning.
+            if open.contains_key(&page.serial) || open.values().any(|next| *next > 1) {
+                break Stop::Damaged;
+            }
+            open.insert(page.serial, page.sequence.wrapping_add(1));
+        } else {
+            match open.get_mut(&page.serial) {
+                Some(next) if *next == page.sequence => *next = next.wrapping_add(1),
+                _ => break Stop::Damaged,
+            }
+        }
+        offset += page.len;
+        pages += 1;
+        if page.flags & FLAG_EOS != 0 {
+            open.remove(&page.serial);
+            if open.is_empty() {
+                break Stop::Complete;
+            }
+        }
+        page = match read_page(read, offset) {
+            PageRead::Page(page) if page.crc_ok => page,
+            PageRead::Page(_) | PageRead::NotAPage => break Stop::Damaged,
+            PageRead::Eof => break Stop::Eof,
+        };
+    };
+    Ok(Walk {
+        end: offset,
+        pages,
+        stop,
+    })
+}
+
+/// CRC-32 of Ogg pages: polynomial 0x04C11DB7, not reflected, no final XOR.
+fn ogg_crc(crc: u32, bytes: &[u8]) -> u32 {
+    bytes.iter().fold(crc, |crc, byte| {
+        (crc << 8) ^ OGG_CRC_TABLE[((crc >> 24) as u8 ^ byte) as usize]
+    })
+}
+
+const OGG_CRC_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = (i as u32) << 24;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x8000_0000 != 0 {
+                (crc << 1) ^ 0x04C1_1DB7
+            } else {
+                crc << 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
 impl CarveHandler for OggCarveHandler {
     fn file_type(&self) -> &str {
         "ogg"
@@ -40,100 +212,97 @@ impl CarveHandler for OggCarveHandler {
         hit: &NormalizedHit,
         ctx: &ExtractionContext,
     ) -> Result<Option<CarvedFile>, CarveError> {
+        let mut read = |offset: u64, buf: &mut [u8]| {
+            read_evidence(ctx, hit.global_offset.saturating_add(offset), buf)
+        };
+        let limit = if self.max_size > 0 {
+            self.max_size
+        } else {
+            u64::MAX
+        };
+        let walk = match walk(&mut read, limit) {
+            Ok(walk) => walk,
+            Err(reason) => {
+                note_rejection(Rejection::Invalid(reason));
+                return Ok(None);
+            }
+        };
+
+        let mut errors = Vec::new();
+        let mut size = walk.end;
+        let mut truncated = false;
+        match walk.stop {
+            Stop::Complete => {}
+            Stop::Damaged => errors.push(format!(
+                "page walk stopped at offset {} after {} pages",
+                walk.end, walk.pages
+            )),
+            Stop::Limit => {
+                size = limit;
+                truncated = true;
+                errors.push("max_size reached before ogg end".to_string());
+            }
+            Stop::Eof => {
+                // Keep the partial page; players skip it.
+                size = ctx
+                    .evidence
+                    .len()
+                    .saturating_sub(hit.global_offset)
+                    .min(limit);
+                truncated = true;
+                errors.push("eof before ogg end".to_string());
+            }
+        }
+
         let (full_path, rel_path) = output_path(
             ctx.output_root,
             self.file_type(),
             &self.extension,
             hit.global_offset,
         )?;
-        let file = File::create(&full_path)?;
-        let mut stream = CarveStream::new(ctx.evidence, hit.global_offset, self.max_size, file);
-
-        let mut validated = false;
-        let mut truncated = false;
-        let mut errors = Vec::new();
-
-        let result: Result<u64, CarveError> = (|| {
-            let mut pages = 0u64;
-            loop {
-                let header = stream.read_exact(27)?;
-                if &header[0..4] != b"OggS" {
-                    return Err(CarveError::Invalid(
-                        "ogg page signature mismatch".to_string(),
-                    ));
-                }
-                if header[4] != 0 {
-                    return Err(CarveError::Invalid("ogg version unsupported".to_string()));
-                }
-                let header_type = header[5];
-                let segment_count = header[26] as usize;
-                let segment_table = stream.read_exact(segment_count)?;
-                let mut data_len = 0u64;
-                for len in &segment_table {
-                    data_len = data_len.saturating_add(*len as u64);
-                }
-                if data_len > 0 {
-                    stream.read_exact(data_len as usize)?;
-                }
-
-                pages += 1;
-                if header_type & 0x04 != 0 {
-                    validated = true;
-                    break;
-                }
-                if pages > 1_000_000 {
-                    return Err(CarveError::Invalid("ogg page limit exceeded".to_string()));
-                }
-            }
-
-            Ok(stream.bytes_written())
-        })();
-
-        if let Err(err) = result {
-            match err {
-                CarveError::Truncated | CarveError::Eof => {
-                    truncated = true;
-                    errors.push(err.to_string());
-                }
-                CarveError::Invalid(_) => {
-                    let _ = std::fs::remove_file(&full_path);
-                    return Ok(None);
-                }
-                other => return Err(other),
-            }
+        let mut file = create_output(&full_path)?;
+        let mut md5 = md5::Context::new();
+        let mut sha256 = Sha256::new();
+        let (written, eof_truncated) = write_range(
+            ctx,
+            hit.global_offset,
+            hit.global_offset + size,
+            &mut file,
+            &mut md5,
+            &mut sha256,
+        )?;
+        if eof_truncated && !truncated {
+            truncated = true;
+            errors.push("eof before ogg end".to_string());
         }
 
-        let (size, md5_hex, sha256_hex) = stream.finish()?;
-
-        if size < self.min_size {
+        if written < self.min_size {
+            note_rejection(Rejection::MinSize {
+                size: written,
+                min_size: self.min_size,
+            });
             let _ = std::fs::remove_file(&full_path);
             return Ok(None);
         }
 
-        if self.max_size > 0 && size >= self.max_size {
-            truncated = true;
-            if !errors.iter().any(|e| e.contains("max_size")) {
-                errors.push("max_size reached".to_string());
-            }
-        }
-
-        let global_end = if size == 0 {
+        let global_end = if written == 0 {
             hit.global_offset
         } else {
-            hit.global_offset + size - 1
+            hit.global_offset + written - 1
         };
 
         Ok(Some(CarvedFile {
             run_id: ctx.run_id.to_string(),
             file_type: self.file_type().to_string(),
+            signature_type: self.file_type().to_string(),
             path: rel_path,
             extension: self.extension.clone(),
             global_start: hit.global_offset,
             global_end,
-            size,
-            md5: Some(md5_hex),
-            sha256: Some(sha256_hex),
-            validated,
+            size: written,
+            md5: Some(format!("{:x}", md5.compute())),
+            sha256: Some(hex::encode(sha256.finalize())),
+            validated: errors.is_empty(),
             truncated,
             errors,
             pattern_id: Some(hit.pattern_id.clone()),
@@ -143,8 +312,8 @@ impl CarveHandler for OggCarveHandler {
 
 #[cfg(test)]
 mod tests {
-    use super::OggCarveHandler;
-    use crate::carve::{CarveHandler, ExtractionContext};
+    use super::{OggCarveHandler, PAGE_HEADER_LEN, ogg_crc};
+    use crate::carve::{CarveHandler, CarvedFile, ExtractionContext};
     use crate::evidence::{EvidenceError, EvidenceSource};
     use crate::scanner::NormalizedHit;
     use tempfile::tempdir;
@@ -169,26 +338,46 @@ mod tests {
         }
     }
 
-    fn minimal_ogg_page() -> Vec<u8> {
+    fn ogg_page(flags: u8, serial: u32, sequence: u32, body: &[u8]) -> Vec<u8> {
         let mut page = Vec::new();
         page.extend_from_slice(b"OggS");
         page.push(0); // version
-        page.push(0x04); // end of stream
+        page.push(flags);
         page.extend_from_slice(&[0u8; 8]); // granule position
-        page.extend_from_slice(&[0u8; 4]); // serial
-        page.extend_from_slice(&[0u8; 4]); // seq
+        page.extend_from_slice(&serial.to_le_bytes());
+        page.extend_from_slice(&sequence.to_le_bytes());
         page.extend_from_slice(&[0u8; 4]); // crc
-        page.push(0); // segment count
+        let mut lacing = vec![255u8; body.len() / 255];
+        lacing.push((body.len() % 255) as u8);
+        page.push(lacing.len() as u8);
+        page.extend_from_slice(&lacing);
+        page.extend_from_slice(body);
+        let crc = ogg_crc(0, &page);
+        page[22..26].copy_from_slice(&crc.to_le_bytes());
         page
     }
 
-    #[test]
-    fn carves_minimal_ogg() {
-        let data = minimal_ogg_page();
-        let evidence = SliceEvidence { data: data.clone() };
-        let handler = OggCarveHandler::new("ogg".to_string(), 0, 0);
+    /// A Vorbis-like stream of `pages` pages of 300-byte packets.
+    fn ogg_stream(serial: u32, pages: u32) -> Vec<u8> {
+        let mut data = Vec::new();
+        for sequence in 0..pages {
+            let flags = match sequence {
+                0 => 0x02,
+                n if n + 1 == pages => 0x04,
+                _ => 0,
+            };
+            data.extend(ogg_page(flags, serial, sequence, &[sequence as u8; 300]));
+        }
+        data
+    }
+
+    fn carve(data: &[u8], offset: u64, max_size: u64) -> Option<CarvedFile> {
+        let evidence = SliceEvidence {
+            data: data.to_vec(),
+        };
+        let handler = OggCarveHandler::new("ogg".to_string(), 0, max_size);
         let hit = NormalizedHit {
-            global_offset: 0,
+            global_offset: offset,
             file_type_id: "ogg".to_string(),
             pattern_id: "ogg_sync".to_string(),
         };
@@ -198,10 +387,63 @@ mod tests {
             output_root: dir.path(),
             evidence: &evidence,
         };
+        handler.process_hit(&hit, &ctx).expect("process")
+    }
 
-        let carved = handler.process_hit(&hit, &ctx).expect("process");
-        let carved = carved.expect("carved");
+    #[test]
+    fn carves_minimal_ogg() {
+        let data = ogg_page(0x06, 1, 0, &[]);
+        let carved = carve(&data, 0, 0).expect("carved");
         assert!(carved.validated);
         assert_eq!(carved.size, data.len() as u64);
     }
+
+    #[test]
+    fn walks_multiplexed_streams_to_the_last_end_of_stream() {
+        let audio = ogg_stream(7, 4);
+        let video = ogg_stream(9, 3);
+        let page_len = audio.len() / 4;
+        // BOS pages first, then the two streams interleaved page by page.
+        let mut data = Vec::new();
+        data.extend_from_slice(&audio[..page_len]);
+        data.extend_from_slice(&video[..page_len]);
+        for i in 1..4 {
+            data.extend_from_slice(&audio[i * page_len..(i + 1) * page_len]);
+            if i < 3 {
+                data.extend_from_slice(&video[i * page_len..(i + 1) * page_len]);
+            }
+        }
+        let file_len = data.len();
+        data.extend(ogg_stream(11, 2));
+
+        let carved = carve(&data, 0, 0).expect("carved");
+        assert!(carved.validated, "{:?}", carved.errors);
+        assert_eq!(carved.size, file_len as u64);
+    }
+
+    #[test]
+    fn keeps_pages_before_a_damaged_one() {
+        let mut data = ogg_stream(3, 5);
+        let page_len = data.len() / 5;
+        data[3 * page_len + PAGE_HEADER_LEN + 10] ^= 0xFF;
+        let carved = carve(&data, 0, 0).expect("carved");
+        assert!(!carved.validated);
+        assert!(!carved.truncated);
+        assert_eq!(carved.size, 3 * page_len as u64);
+
+        let carved = carve(&data[..2 * page_len + 40], 0, 0).expect("cut");
+        assert!(carved.truncated);
+        assert_eq!(carved.size, 2 * page_len as u64 + 40);
+    }
+
+    #[test]
+    fn rejects_hits_inside_a_stream() {
+        let data = ogg_stream(3, 5);
+        let page_len = data.len() / 5;
+        assert!(carve(&data, 2 * page_len as u64, 0).is_none());
+
+        let mut bad = data.clone();
+        bad[PAGE_HEADER_LEN + 5] ^= 0x01;
+        assert!(carve(&bad, 0, 0).is_none());
+    }
 }
@@ -4,13 +4,17 @@
 //! The file structure uses a FAT-based sector allocation scheme.
 //!
 //! Signature: D0 CF 11 E0 A1 B1 1A E1
-
-use std::fs::File;
+//!
+//! Password-protected OOXML documents (ECMA-376 encryption) are compound files
+//! too; they are labelled `encrypted_ooxml` rather than generic OLE.
 
 use crate::carve::{
-    CarveError, CarveHandler, CarveStream, CarvedFile, ExtractionContext, output_path,
+    CarveError, CarveHandler, CarveStream, CarvedFile, DeclaredSize, ExtractionContext, Rejection,
+    create_output, declared_limit, note_declared_size, note_rejection, output_path,
+    relocate_classified,
 };
 use crate::evidence::EvidenceSource;
+use crate::parsers::office_crypto::{ENCRYPTED_PACKAGE_STREAM, ENCRYPTION_INFO_STREAM};
 use crate::scanner::NormalizedHit;
 
 /// OLE/CFB magic signature
@@ -26,10 +30,14 @@ const SECTOR_SIZE_V3: u64 = 512;
 /// Sector size for version 4
 const SECTOR_SIZE_V4: u64 = 4096;
 
+/// File type of compound files holding an encrypted OOXML package.
+pub const ENCRYPTED_OOXML_KIND: &str = "encrypted_ooxml";
+
 pub struct OleCarveHandler {
     extension: String,
     min_size: u64,
     max_size: u64,
+    declared_size_factor: Option<f64>,
     allowed_kinds: Option<Vec<String>>,
 }
 
@@ -46,9 +54,16 @@ impl OleCarveHandler {
             extension,
             min_size,
             max_size,
+            declared_size_factor: None,
             allowed_kinds,
         }
     }
+
+    /// Carve up to `factor` times the size the FAT declares instead of `max_size`.
+    pub fn with_declared_size_factor(mut self, factor: Option<f64>) -> Self {
+        self.declared_size_factor = factor;
+        self
+    }
 }
 
 /// Parse OLE/CFB header and calculate file size.
@@ -189,14 +204,14 @@ fn parse_ole_header(header: &[u8]) -> Result<(u64, u64), CarveError> {
     Ok((estimated_size, sector_size))
 }
 
-/// Try to get a more accurate size by reading FAT entries and finding the highest used sector
+/// Size the FAT declares: header plus sectors up to the highest one in use.
+/// `None` when the header lists no FAT sectors.
 fn refine_ole_size(
     evidence: &dyn EvidenceSource,
     base_offset: u64,
     header: &[u8],
     sector_size: u64,
-    max_size: u64,
-) -> Result<u64, CarveError> {
+) -> Option<u64> {
     let header_size = 512u64; // Always 512 for header
 
     // Read DIFAT entries from header to find FAT sector locations
@@ -221,8 +236,7 @@ fn refine_ole_size(
     }
 
     if fat_sectors.is_empty() {
-        // No FAT sectors found, return minimal size
-        return Ok(header_size + sector_size);
+        return None;
     }
 
     // Read all FAT sectors to find the highest sector that's in use
@@ -241,24 +255,28 @@ fn refine_ole_size(
         highest_used_sector = first_dir_sector;
     }
 
-    // Read each FAT sector and scan for the highest sector ID that is allocated
+    // Read all FAT sectors in one batch; they are scattered through the file
+    let mut fat_bytes = vec![0u8; fat_sectors.len() * sector_size as usize];
+    let mut reads: Vec<(u64, &mut [u8])> = fat_sectors
+        .iter()
+        .zip(fat_bytes.chunks_mut(sector_size as usize))
+        .map(|(&id, buf)| (base_offset + header_size + id as u64 * sector_size, buf))
+        .collect();
+    let read_lens = evidence.read_vectored_at(&mut reads).unwrap_or_default();
+    drop(reads);
+    // Stop at the first FAT sector that could not be read in full
+    let complete = read_lens
+        .iter()
+        .take_while(|&&n| n == sector_size as usize)
+        .count();
+
+    // Scan each FAT sector for the highest sector ID that is allocated
     // A sector is "used" if its FAT entry is not FREESECT (0xFFFFFFFF)
-    for (fat_index, &fat_sector_id) in fat_sectors.iter().enumerate() {
-        let fat_file_offset = header_size + (fat_sector_id as u64 * sector_size);
-
-        if fat_file_offset + sector_size > max_size {
-            break;
-        }
-
-        // Read this FAT sector directly from evidence
-        let mut fat_data = vec![0u8; sector_size as usize];
-        let read_offset = base_offset + fat_file_offset;
-
-        match evidence.read_at(read_offset, &mut fat_data) {
-            Ok(n) if n == sector_size as usize => {}
-            _ => break, // Couldn't read full sector, stop
-        }
-
+    for (fat_index, fat_data) in fat_bytes
+        .chunks(sector_size as usize)
+        .take(complete)
+        .enumerate()
+    {
         // Now parse the FAT entries from this sector
         let entries_per_sector = (sector_size / 4) as usize;
         let base_sector_id = fat_index * entries_per_sector;
@@ -283,8 +301,12 @@ fn refine_ole_size(
                 if sector_index > highest_used_sector && sector_index < 0xFFFFFFFA {
                     highest_used_sector = sector_index;
                 }
-                // Also check where this entry points to (the chain)
-                if fat_entry < 0xFFFFFFFA && fat_entry > highest_used_sector {
+                // Also check where this entry points to (the chain); the FAT
+                // must index every sector, so larger pointers are corrupt
+                if fat_entry < 0xFFFFFFFA
+                    && (fat_entry as usize) < fat_sectors.len() * entries_per_sector
+                    && fat_entry > highest_used_sector
+                {
                     highest_used_sector = fat_entry;
                 }
             }
@@ -292,9 +314,7 @@ fn refine_ole_size(
     }
 
     // File size = header + (highest_sector + 1) * sector_size
-    let total_size = header_size + ((highest_used_sector as u64 + 1) * sector_size);
-
-    Ok(total_size.min(max_size))
+    Some(header_size + ((highest_used_sector as u64 + 1) * sector_size))
 }
 
 fn classify_ole_kind(
@@ -317,6 +337,8 @@ fn classify_ole_kind(
     let mut found_doc = false;
     let mut found_xls = false;
     let mut found_ppt = false;
+    let mut found_encryption_info = false;
+    let mut found_encrypted_package = false;
 
     while current < 0xFFFFFFFA && visited < 1024 {
         let offset = base_offset + 512u64 + (current as u64 * sector_size);
@@ -347,6 +369,10 @@ fn classify_ole_kind(
                 found_xls = true;
             } else if name == "PowerPoint Document" {
                 found_ppt = true;
+            } else if name == ENCRYPTION_INFO_STREAM {
+                found_encryption_info = true;
+            } else if name == ENCRYPTED_PACKAGE_STREAM {
+                found_encrypted_package = true;
             }
         }
 
@@ -358,7 +384,11 @@ fn classify_ole_kind(
         visited += 1;
     }
 
-    if found_doc {
+    // An encrypted OOXML package is a compound file wrapping the zip; the
+    // streams say nothing about which Office application wrote it.
+    if found_encryption_info && found_encrypted_package {
+        Some(ENCRYPTED_OOXML_KIND)
+    } else if found_doc {
         Some("doc")
     } else if found_xls {
         Some("xls")
@@ -459,7 +489,7 @@ impl CarveHandler for OleCarveHandler {
             &self.extension,
             hit.global_offset,
         )?;
-        let file = File::create(&full_path)?;
+        let file = create_output(&full_path)?;
         let effective_max = if self.max_size > 0 {
             self.max_size
         } else {
@@ -472,6 +502,8 @@ impl CarveHandler for OleCarveHandler {
         let mut errors = Vec::new();
 
         let mut classified_kind: Option<&'static str> = None;
+        let mut declared: Option<u64> = None;
+        let mut limit = effective_max;
 
         let result: Result<u64, CarveError> = (|| {
             // Read OLE header (512 bytes minimum)
@@ -487,17 +519,19 @@ impl CarveHandler for OleCarveHandler {
                 effective_max,
             );
 
-            // Try to refine size estimate by reading FAT from evidence directly
-            let target_size = refine_ole_size(
-                ctx.evidence,
-                hit.global_offset,
-                &header,
-                sector_size,
-                effective_max,
-            )?;
-
-            // Apply max_size limit
-            let target_size = target_size.min(effective_max);
+            // Size from the FAT, read from evidence directly
+            declared = refine_ole_size(ctx.evidence, hit.global_offset, &header, sector_size);
+            if let Some(size) = declared {
+                note_declared_size(DeclaredSize {
+                    size,
+                    source: "fat",
+                });
+                limit = declared_limit(effective_max, self.declared_size_factor, size);
+                stream.set_max_size(limit);
+            }
+            let target_size = declared
+                .unwrap_or(header.len() as u64 + sector_size)
+                .min(limit);
 
             // Read remaining data
             let already_read = stream.bytes_written();
@@ -535,6 +569,10 @@ impl CarveHandler for OleCarveHandler {
 
         // Check minimum size
         if size < self.min_size {
+            note_rejection(Rejection::MinSize {
+                size,
+                min_size: self.min_size,
+            });
             let _ = std::fs::remove_file(&full_path);
             return Ok(None);
         }
@@ -544,17 +582,19 @@ impl CarveHandler for OleCarveHandler {
 
         if let Some(kind) = classified_kind {
             file_type = kind.to_string();
-            extension = kind.to_string();
-            if file_type != self.file_type() {
-                if let Ok((new_path, new_rel)) =
-                    output_path(ctx.output_root, &file_type, &extension, hit.global_offset)
-                {
-                    if std::fs::rename(&full_path, &new_path).is_ok() {
-                        full_path = new_path;
-                        rel_path = new_rel;
-                    }
-                }
-            }
+            extension = if kind == ENCRYPTED_OOXML_KIND {
+                "ooxml".to_string()
+            } else {
+                kind.to_string()
+            };
+            relocate_classified(
+                ctx.output_root,
+                &file_type,
+                &extension,
+                hit.global_offset,
+                &mut full_path,
+                &mut rel_path,
+            );
         }
 
         if let Some(allowed) = &self.allowed_kinds {
@@ -564,12 +604,23 @@ impl CarveHandler for OleCarveHandler {
             }
         }
 
-        // Check if we hit max_size
-        if self.max_size > 0 && size >= self.max_size {
-            truncated = true;
-            if !errors.iter().any(|e| e.contains("max_size")) {
-                errors.push("max_size reached".to_string());
+        // Check if we hit the limit or stopped short of the declared size
+        match declared {
+            Some(declared) if size < declared => {
+                truncated = true;
+                if size >= limit {
+                    errors.push("declared size exceeds max_size".to_string());
+                } else if errors.is_empty() {
+                    errors.push("eof before declared end".to_string());
+                }
+            }
+            None if self.max_size > 0 && size >= self.max_size => {
+                truncated = true;
+                if !errors.iter().any(|e| e.contains("max_size")) {
+                    errors.push("max_size reached".to_string());
+                }
             }
+            _ => {}
         }
 
         let global_end = if size == 0 {
@@ -581,6 +632,7 @@ impl CarveHandler for OleCarveHandler {
         Ok(Some(CarvedFile {
             run_id: ctx.run_id.to_string(),
             file_type,
+            signature_type: self.file_type().to_string(),
             path: rel_path,
             extension,
             global_start: hit.global_offset,
@@ -734,6 +786,78 @@ mod tests {
         assert!(carved.size >= 512);
     }
 
+    #[test]
+    fn declared_size_factor_overrides_max_size() {
+        let mut data = create_minimal_ole();
+        // The FAT marks all 128 sectors it indexes as used.
+        let declared = 512 + 128 * 512;
+        data.resize(declared + 4096, 0);
+        let evidence = SliceEvidence { data };
+        let hit = NormalizedHit {
+            global_offset: 0,
+            file_type_id: "ole".to_string(),
+            pattern_id: "ole_cfb".to_string(),
+        };
+        let dir = tempdir().expect("tempdir");
+        let ctx = ExtractionContext {
+            run_id: "test",
+            output_root: dir.path(),
+            evidence: &evidence,
+        };
+
+        let capped = OleCarveHandler::new("doc".to_string(), 0, 4096, None);
+        let carved = capped
+            .process_hit(&hit, &ctx)
+            .expect("process")
+            .expect("carved file");
+        assert_eq!(carved.size, 4096);
+        assert!(carved.truncated);
+        assert_eq!(carved.errors, ["declared size exceeds max_size"]);
+        assert_eq!(
+            crate::carve::take_declared_size(),
+            Some(DeclaredSize {
+                size: declared as u64,
+                source: "fat"
+            })
+        );
+
+        let scaled = OleCarveHandler::new("doc".to_string(), 0, 4096, None)
+            .with_declared_size_factor(Some(1.0));
+        let carved = scaled
+            .process_hit(&hit, &ctx)
+            .expect("process")
+            .expect("carved file");
+        assert_eq!(carved.size, declared as u64);
+        assert!(!carved.truncated);
+    }
+
+    #[test]
+    fn labels_encrypted_ooxml() {
+        let data = crate::parsers::office_crypto::encrypted_ooxml(2048);
+        let evidence = SliceEvidence { data: data.clone() };
+        let handler = OleCarveHandler::new("ole".to_string(), 0, 0, None);
+        let hit = NormalizedHit {
+            global_offset: 0,
+            file_type_id: "ole".to_string(),
+            pattern_id: "ole_cfb".to_string(),
+        };
+        let dir = tempdir().expect("tempdir");
+        let ctx = ExtractionContext {
+            run_id: "test",
+            output_root: dir.path(),
+            evidence: &evidence,
+        };
+
+        let carved = handler
+            .process_hit(&hit, &ctx)
+            .expect("process")
+            .expect("carved file");
+        assert_eq!(carved.file_type, ENCRYPTED_OOXML_KIND);
+        assert_eq!(carved.extension, "ooxml");
+        assert_eq!(carved.size, data.len() as u64);
+        assert!(carved.path.starts_with("encrypted_ooxml/"));
+    }
+
     #[test]
     fn rejects_non_ole_data() {
         let data = vec![0x00; 1024];
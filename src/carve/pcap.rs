@@ -0,0 +1,190 @@
+use std::io::{BufReader, Read, Write};
+
+use sha2::{Digest, Sha256};
+
+use crate::carve::{
+    CarveError, CarveHandler, CarvedFile, ExtractionContext, create_output, output_path,
+    write_range,
+};
+use crate::evidence::EvidenceSource;
+use crate::parsers::pcap::{CaptureFormat, PacketReader};
+use crate::scanner::NormalizedHit;
+
+const READ_BUFFER: usize = 64 * 1024;
+
+/// Packet captures (libpcap or pcapng). The extent is the last record that
+/// parses; trailing bytes that do not form a record end the capture.
+pub struct PcapCarveHandler {
+    format: CaptureFormat,
+    extension: String,
+    min_size: u64,
+    max_size: u64,
+}
+
+impl PcapCarveHandler {
+    pub fn new(format: CaptureFormat, extension: String, min_size: u64, max_size: u64) -> Self {
+        Self {
+            format,
+            extension,
+            min_size,
+            max_size,
+        }
+    }
+}
+
+impl CarveHandler for PcapCarveHandler {
+    fn file_type(&self) -> &str {
+        match self.format {
+            CaptureFormat::Pcap => "pcap",
+            CaptureFormat::PcapNg => "pcapng",
+        }
+    }
+
+    fn extension(&self) -> &str {
+        &self.extension
+    }
+
+    fn process_hit(
+        &self,
+        hit: &NormalizedHit,
+        ctx: &ExtractionContext,
+    ) -> Result<Option<CarvedFile>, CarveError> {
+        let limit = if self.max_size > 0 {
+            self.max_size
+        } else {
+            u64::MAX
+        };
+        let source = EvidenceReader {
+            evidence: ctx.evidence,
+            offset: hit.global_offset,
+            remaining: limit,
+        };
+        let reader = PacketReader::new(BufReader::with_capacity(READ_BUFFER, source))?;
+        let Some(mut reader) = reader.filter(|r| r.format() == self.format) else {
+            return Ok(None);
+        };
+        let mut packets = 0u64;
+        while reader.next_packet()?.is_some() {
+            packets += 1;
+        }
+        let len = reader.position();
+        if packets == 0 || len < self.min_size {
+            return Ok(None);
+        }
+        // Records may continue past `max_size`.
+        let truncated = reader.into_inner().into_inner().remaining == 0;
+
+        let (full_path, rel_path) = output_path(
+            ctx.output_root,
+            self.file_type(),
+            &self.extension,
+            hit.global_offset,
+        )?;
+        let mut file = create_output(&full_path)?;
+        let mut md5 = md5::Context::new();
+        let mut sha256 = Sha256::new();
+        let (written, _) = write_range(
+            ctx,
+            hit.global_offset,
+            hit.global_offset + len,
+            &mut file,
+            &mut md5,
+            &mut sha256,
+        )?;
+        file.flush()?;
+
+        let mut errors = Vec::new();
+        if truncated {
+            errors.push("max_size reached before capture end".to_string());
+        }
+        Ok(Some(CarvedFile {
+            run_id: ctx.run_id.to_string(),
+            file_type: self.file_type().to_string(),
+            signature_type: self.file_type().to_string(),
+            path: rel_path,
+            extension: self.extension.clone(),
+            global_start: hit.global_offset,
+            global_end: hit.global_offset + written - 1,
+            size: written,
+            md5: Some(format!("{:x}", md5.compute())),
+            sha256: Some(hex::encode(sha256.finalize())),
+            validated: !truncated,
+            truncated,
+            errors,
+            pattern_id: Some(hit.pattern_id.clone()),
+        }))
+    }
+}
+
+/// Sequential [`Read`] over evidence bytes starting at `offset`.
+struct EvidenceReader<'a> {
+    evidence: &'a dyn EvidenceSource,
+    offset: u64,
+    remaining: u64,
+}
+
+impl Read for EvidenceReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let want = (buf.len() as u64).min(self.remaining) as usize;
+        if want == 0 {
+            return Ok(0);
+        }
+        let n = self
+            .evidence
+            .read_at(self.offset, &mut buf[..want])
+            .map_err(std::io::Error::other)?;
+        self.offset += n as u64;
+        self.remaining -= n as u64;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evidence::RawFileSource;
+    use crate::parsers::pcap::{ipv4_frame, pcap_bytes, pcapng_bytes};
+
+    fn carve(handler: &PcapCarveHandler, image: &[u8], offset: u64) -> Option<CarvedFile> {
+        let temp_dir = tempfile::tempdir().expect("tempdir");
+        let input_path = temp_dir.path().join("image.bin");
+        std::fs::write(&input_path, image).expect("write image");
+        let output_root = temp_dir.path().join("out");
+        let evidence = RawFileSource::open(&input_path).expect("evidence");
+        let ctx = ExtractionContext {
+            run_id: "test",
+            output_root: &output_root,
+            evidence: &evidence,
+        };
+        let hit = NormalizedHit {
+            global_offset: offset,
+            file_type_id: handler.file_type().to_string(),
+            pattern_id: "pcap_header".to_string(),
+        };
+        handler.process_hit(&hit, &ctx).expect("carve")
+    }
+
+    #[test]
+    fn carves_captures_up_to_last_record() {
+        let frames = vec![ipv4_frame(17, 1000, 53, b"payload"); 3];
+        for (format, capture) in [
+            (CaptureFormat::Pcap, pcap_bytes(&frames)),
+            (CaptureFormat::PcapNg, pcapng_bytes(&frames)),
+        ] {
+            let mut image = vec![0xEEu8; 100];
+            image.extend_from_slice(&capture);
+            image.extend_from_slice(&[0xEEu8; 100]);
+            let handler = PcapCarveHandler::new(format, "pcap".to_string(), 24, 0);
+            let carved = carve(&handler, &image, 100).expect("carved");
+            assert_eq!(carved.size, capture.len() as u64, "{format:?}");
+            assert!(carved.validated);
+        }
+    }
+
+    #[test]
+    fn rejects_header_without_packets() {
+        let capture = pcap_bytes(&[]);
+        let handler = PcapCarveHandler::new(CaptureFormat::Pcap, "pcap".to_string(), 0, 0);
+        assert!(carve(&handler, &capture, 0).is_none());
+    }
+}
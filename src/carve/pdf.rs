@@ -1,18 +1,25 @@
-use std::fs::File;
-use std::io::{BufWriter, Write};
+use std::io::Write;
 
 use sha2::{Digest, Sha256};
 
-use crate::carve::{CarveError, CarveHandler, CarvedFile, ExtractionContext, output_path};
+use crate::carve::{
+    CarveError, CarveHandler, CarvedFile, DeclaredSize, ExtractionContext, Rejection,
+    create_output, declared_limit, note_declared_size, note_rejection, output_path, output_writer,
+};
 use crate::scanner::NormalizedHit;
 
 const PDF_HEADER: &[u8] = b"%PDF-";
 const PDF_EOF: &[u8] = b"%%EOF";
+const LINEARIZED: &[u8] = b"/Linearized";
+/// Bytes a linearized file's `/L` may exceed the end of its final `%%EOF`
+/// (line endings, padding).
+const EOF_SLACK: u64 = 32;
 
 pub struct PdfCarveHandler {
     extension: String,
     min_size: u64,
     max_size: u64,
+    declared_size_factor: Option<f64>,
 }
 
 impl PdfCarveHandler {
@@ -21,8 +28,16 @@ impl PdfCarveHandler {
             extension,
             min_size,
             max_size,
+            declared_size_factor: None,
         }
     }
+
+    /// Carve linearized files up to `factor` times their declared length
+    /// instead of `max_size`.
+    pub fn with_declared_size_factor(mut self, factor: Option<f64>) -> Self {
+        self.declared_size_factor = factor;
+        self
+    }
 }
 
 impl CarveHandler for PdfCarveHandler {
@@ -45,8 +60,8 @@ impl CarveHandler for PdfCarveHandler {
             &self.extension,
             hit.global_offset,
         )?;
-        let file = File::create(&full_path)?;
-        let mut writer = BufWriter::new(file);
+        let file = create_output(&full_path)?;
+        let mut writer = output_writer(file);
         let mut md5 = md5::Context::new();
         let mut sha256 = Sha256::new();
 
@@ -58,16 +73,21 @@ impl CarveHandler for PdfCarveHandler {
 
         let mut carry: Vec<u8> = Vec::new();
         let buf_size = 64 * 1024;
+        let mut limit = self.max_size;
+        // A linearized file has a `%%EOF` after its first-page section; only
+        // the one near the declared length ends the file.
+        let mut min_eof_end = 0u64;
 
         loop {
-            if self.max_size > 0 && bytes_written >= self.max_size {
+            ctx.check_cancelled()?;
+            if limit > 0 && bytes_written >= limit {
                 truncated = true;
                 errors.push("max_size reached before EOF".to_string());
                 break;
             }
 
-            let remaining = if self.max_size > 0 {
-                (self.max_size - bytes_written).min(buf_size as u64)
+            let remaining = if limit > 0 {
+                (limit - bytes_written).min(buf_size as u64)
             } else {
                 buf_size as u64
             };
@@ -89,11 +109,25 @@ impl CarveHandler for PdfCarveHandler {
                     let _ = std::fs::remove_file(&full_path);
                     return Ok(None);
                 }
+                if let Some(size) = linearized_length(&buf) {
+                    note_declared_size(DeclaredSize {
+                        size,
+                        source: "linearization",
+                    });
+                    limit = declared_limit(self.max_size, self.declared_size_factor, size);
+                    min_eof_end = size.saturating_sub(EOF_SLACK);
+                }
             }
 
             let mut search_buf = carry.clone();
             search_buf.extend_from_slice(&buf);
-            if let Some(pos) = find_pattern(&search_buf, PDF_EOF) {
+            // File offset of search_buf[0]; skip markers ending before min_eof_end.
+            let search_base = bytes_written - carry.len() as u64;
+            let skip = min_eof_end
+                .saturating_sub(search_base)
+                .saturating_sub(PDF_EOF.len() as u64)
+                .min(search_buf.len() as u64) as usize;
+            if let Some(pos) = find_pattern(&search_buf[skip..], PDF_EOF).map(|pos| pos + skip) {
                 let write_len = if pos < carry.len() {
                     pos + PDF_EOF.len() - carry.len()
                 } else {
@@ -149,6 +183,11 @@ impl CarveHandler for PdfCarveHandler {
         writer.flush()?;
 
         if bytes_written < self.min_size {
+            note_rejection(Rejection::MinSize {
+                size: bytes_written,
+
+                min_size: self.min_size,
+            });
             let _ = std::fs::remove_file(&full_path);
             return Ok(None);
         }
@@ -164,6 +203,7 @@ impl CarveHandler for PdfCarveHandler {
         Ok(Some(CarvedFile {
             run_id: ctx.run_id.to_string(),
             file_type: self.file_type().to_string(),
+            signature_type: self.file_type().to_string(),
             path: rel_path,
             extension: self.extension.clone(),
             global_start: hit.global_offset,
@@ -194,8 +234,108 @@ fn find_pattern(haystack: &[u8], needle: &[u8]) -> Option<usize> {
     None
 }
 
+/// File length a linearized PDF declares: `/L` in the linearization
+/// dictionary, which must lie in the first 1024 bytes.
+fn linearized_length(head: &[u8]) -> Option<u64> {
+    let head = &head[..head.len().min(1024)];
+    let start = find_pattern(head, LINEARIZED)? + LINEARIZED.len();
+    let end = find_pattern(&head[start..], b">>").map_or(head.len(), |pos| start + pos);
+    let dict = &head[start..end];
+    let mut from = 0usize;
+    while let Some(pos) = find_pattern(&dict[from..], b"/L") {
+        let value = &dict[from + pos + 2..];
+        let digits = value.trim_ascii_start();
+        if digits.len() < value.len() {
+            let len = digits.iter().take_while(|b| b.is_ascii_digit()).count();
+            if let Some(size) = std::str::from_utf8(&digits[..len])
+                .ok()
+                .and_then(|text| text.parse().ok())
+            {
+                return Some(size);
+            }
+        }
+        from += pos + 2;
+    }
+    None
+}
+
 fn read_byte(ctx: &ExtractionContext, offset: u64) -> Option<u8> {
     let mut buf = [0u8; 1];
     let n = ctx.evidence.read_at(offset, &mut buf).ok()?;
     if n == 1 { Some(buf[0]) } else { None }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evidence::{EvidenceError, EvidenceSource};
+    use tempfile::tempdir;
+
+    struct SliceEvidence {
+        data: Vec<u8>,
+    }
+
+    impl EvidenceSource for SliceEvidence {
+        fn len(&self) -> u64 {
+            self.data.len() as u64
+        }
+
+        fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize, EvidenceError> {
+            let start = (offset as usize).min(self.data.len());
+            let n = buf.len().min(self.data.len() - start);
+            buf[..n].copy_from_slice(&self.data[start..start + n]);
+            Ok(n)
+        }
+    }
+
+    /// Linearized layout: first-page section ending in `%%EOF`, then the rest.
+    fn linearized_pdf(len: usize) -> Vec<u8> {
+        let dict = format!(
+            "%PDF-1.7\n1 0 obj\n<< /Linearized 1 /L {len} /H [ 600 140 ] /O 4 /E 900 /N 1 /T 1200 >>\nendobj\n"
+        );
+        let mut pdf = dict.into_bytes();
+        pdf.extend_from_slice(b"trailer\n<< /Size 5 >>\nstartxref\n0\n%%EOF\n");
+        pdf.resize(len - 6, b' ');
+        pdf.extend_from_slice(b"%%EOF\n");
+        pdf
+    }
+
+    #[test]
+    fn reads_linearized_length() {
+        let pdf = linearized_pdf(4000);
+        assert_eq!(linearized_length(&pdf), Some(4000));
+        assert_eq!(
+            linearized_length(b"%PDF-1.4\n1 0 obj\n<< /Length 12 >>"),
+            None
+        );
+    }
+
+    #[test]
+    fn carves_linearized_pdf_to_final_eof() {
+        let mut data = linearized_pdf(4000);
+        data.extend_from_slice(&[0u8; 1024]);
+        let evidence = SliceEvidence { data };
+        let dir = tempdir().expect("tempdir");
+        let ctx = ExtractionContext {
+            run_id: "test",
+            output_root: dir.path(),
+            evidence: &evidence,
+        };
+        let hit = NormalizedHit {
+            global_offset: 0,
+            file_type_id: "pdf".to_string(),
+            pattern_id: "pdf_header".to_string(),
+        };
+
+        let carved = PdfCarveHandler::new("pdf".to_string(), 0, 0)
+            .process_hit(&hit, &ctx)
+            .expect("process")
+            .expect("carved file");
+        assert_eq!(carved.size, 4000);
+        assert!(carved.validated && !carved.truncated);
+        assert_eq!(
+            crate::carve::take_declared_size().map(|d| d.size),
+            Some(4000)
+        );
+    }
+}
@@ -0,0 +1,220 @@
+//! Portable Executable (`.exe`, `.dll`, `.sys`) carving handler.
+//!
+//! The MZ and PE headers are read and checked (PE signature, executable image
+//! flag, optional header magic, section table inside the headers). The file
+//! ends after the section whose raw data ends last, or after the Authenticode
+//! certificate table when that lies further out. DLLs and drivers are written
+//! with their own extension; the `executables` post-processor records the
+//! architecture, compile time, and import hash.
+
+use sha2::{Digest, Sha256};
+
+use crate::carve::{
+    CarveError, CarveHandler, CarvedFile, DeclaredSize, ExtractionContext, Rejection,
+    create_output, note_declared_size, note_rejection, output_path, write_range,
+};
+use crate::parsers::pe::{self, PeError};
+use crate::scanner::NormalizedHit;
+
+/// First read; holds the headers of nearly every image.
+const INITIAL_READ: usize = 4096;
+/// Headers never extend past this: `e_lfanew` limit, a 64 KiB optional header,
+/// and 96 section headers.
+const MAX_HEADER_READ: usize = pe::MAX_PE_OFFSET as usize + 24 + 0xFFFF + 96 * 40;
+
+pub struct PeCarveHandler {
+    extension: String,
+    min_size: u64,
+    max_size: u64,
+}
+
+impl PeCarveHandler {
+    pub fn new(extension: String, min_size: u64, max_size: u64) -> Self {
+        Self {
+            extension,
+            min_size,
+            max_size,
+        }
+    }
+}
+
+impl CarveHandler for PeCarveHandler {
+    fn file_type(&self) -> &str {
+        "pe"
+    }
+
+    fn extension(&self) -> &str {
+        &self.extension
+    }
+
+    fn process_hit(
+        &self,
+        hit: &NormalizedHit,
+        ctx: &ExtractionContext,
+    ) -> Result<Option<CarvedFile>, CarveError> {
+        let mut want = INITIAL_READ;
+        let headers = loop {
+            let buf = read_up_to(ctx, hit.global_offset, want)?;
+            match pe::parse_headers(&buf) {
+                Ok(headers) => break headers,
+                Err(PeError::Invalid(reason)) => {
+                    note_rejection(Rejection::Invalid(reason));
+                    return Ok(None);
+                }
+                Err(PeError::Incomplete(needed)) if buf.len() == want && needed > want => {
+                    if needed > MAX_HEADER_READ {
+                        note_rejection(Rejection::Invalid("PE headers too large"));
+                        return Ok(None);
+                    }
+                    want = needed;
+                }
+                Err(PeError::Incomplete(_)) => {
+                    note_rejection(Rejection::Invalid("evidence ended inside the PE headers"));
+                    return Ok(None);
+                }
+            }
+        };
+
+        let declared = headers.file_size();
+        note_declared_size(DeclaredSize {
+            size: declared,
+            source: "section_table",
+        });
+        let mut errors = Vec::new();
+        let mut truncated = false;
+        let mut size = declared;
+        if self.max_size > 0 && size > self.max_size {
+            size = self.max_size;
+            truncated = true;
+            errors.push("max_size reached before PE end".to_string());
+        }
+
+        let extension = match headers.kind() {
+            "exe" => self.extension.as_str(),
+            kind => kind,
+        };
+        let (full_path, rel_path) = output_path(
+            ctx.output_root,
+            self.file_type(),
+            extension,
+            hit.global_offset,
+        )?;
+        let mut file = create_output(&full_path)?;
+        let mut md5 = md5::Context::new();
+        let mut sha256 = Sha256::new();
+        let (written, eof_truncated) = write_range(
+            ctx,
+            hit.global_offset,
+            hit.global_offset + size,
+            &mut file,
+            &mut md5,
+            &mut sha256,
+        )?;
+        if eof_truncated {
+            truncated = true;
+            errors.push("eof before PE end".to_string());
+        }
+
+        if written < self.min_size {
+            note_rejection(Rejection::MinSize {
+                size: written,
+                min_size: self.min_size,
+            });
+            let _ = std::fs::remove_file(&full_path);
+            return Ok(None);
+        }
+
+        let global_end = if written == 0 {
+            hit.global_offset
+        } else {
+            hit.global_offset + written - 1
+        };
+        Ok(Some(CarvedFile {
+            run_id: ctx.run_id.to_string(),
+            file_type: self.file_type().to_string(),
+            signature_type: self.file_type().to_string(),
+            path: rel_path,
+            extension: extension.to_string(),
+            global_start: hit.global_offset,
+            global_end,
+            size: written,
+            md5: Some(format!("{:x}", md5.compute())),
+            sha256: Some(hex::encode(sha256.finalize())),
+            validated: errors.is_empty(),
+            truncated,
+            errors,
+            pattern_id: Some(hit.pattern_id.clone()),
+        }))
+    }
+}
+
+/// Read up to `len` bytes; shorter only at the end of the evidence.
+fn read_up_to(ctx: &ExtractionContext, offset: u64, len: usize) -> Result<Vec<u8>, CarveError> {
+    let mut buf = vec![0u8; len];
+    let mut filled = 0;
+    while filled < len {
+        let n = ctx
+            .evidence
+            .read_at(offset + filled as u64, &mut buf[filled..])
+            .map_err(|e| CarveError::Evidence(e.to_string()))?;
+        if n == 0 {
+            break;
+        }
+        filled += n;
+    }
+    buf.truncate(filled);
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::carve::support::carve_image;
+    use crate::parsers::pe::testing::{PE_LEN, pe_bytes};
+
+    #[test]
+    fn carves_sections_and_signature() {
+        let mut image = vec![0u8; 4096];
+        image.extend_from_slice(&pe_bytes());
+        image.extend_from_slice(&[0xCC; 2048]);
+
+        let (carved, dir) =
+            carve_image(&PeCarveHandler::new("exe".to_string(), 0, 0), &image, 4096);
+        let carved = carved.expect("pe");
+        assert_eq!(carved.size, PE_LEN as u64);
+        assert!(carved.validated, "{:?}", carved.errors);
+        assert_eq!(carved.extension, "exe");
+        let written = std::fs::read(dir.path().join("out").join(&carved.path)).expect("read");
+        assert_eq!(written, pe_bytes());
+
+        let (carved, _dir) = carve_image(
+            &PeCarveHandler::new("exe".to_string(), 0, 0),
+            &image[..4096 + 0x500],
+            4096,
+        );
+        let carved = carved.expect("cut pe");
+        assert!(carved.truncated && !carved.validated);
+        assert_eq!(carved.errors, vec!["eof before PE end"]);
+    }
+
+    #[test]
+    fn writes_dlls_with_their_extension() {
+        let mut data = pe_bytes();
+        data[0x96..0x98].copy_from_slice(&0x2022u16.to_le_bytes());
+        let (carved, _dir) = carve_image(&PeCarveHandler::new("exe".to_string(), 0, 0), &data, 0);
+        let carved = carved.expect("dll");
+        assert_eq!(carved.extension, "dll");
+        assert!(carved.path.ends_with(".dll"));
+    }
+
+    #[test]
+    fn rejects_bare_mz_headers() {
+        let mut data = b"MZ".to_vec();
+        data.resize(8192, 0);
+        assert!(
+            carve_image(&PeCarveHandler::new("exe".to_string(), 0, 0), &data, 0)
+                .0
+                .is_none()
+        );
+    }
+}
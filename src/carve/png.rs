@@ -1,7 +1,6 @@
-use std::fs::File;
-
 use crate::carve::{
-    CarveError, CarveHandler, CarveStream, CarvedFile, ExtractionContext, output_path,
+    CarveError, CarveHandler, CarveStream, CarvedFile, ExtractionContext, Rejection, create_output,
+    note_rejection, output_path,
 };
 use crate::scanner::NormalizedHit;
 
@@ -43,7 +42,7 @@ impl CarveHandler for PngCarveHandler {
             &self.extension,
             hit.global_offset,
         )?;
-        let file = File::create(&full_path)?;
+        let file = create_output(&full_path)?;
         let mut stream = CarveStream::new(ctx.evidence, hit.global_offset, self.max_size, file);
 
         let mut validated = false;
@@ -97,6 +96,10 @@ impl CarveHandler for PngCarveHandler {
 
         let (size, md5_hex, sha256_hex) = stream.finish()?;
         if size < self.min_size {
+            note_rejection(Rejection::MinSize {
+                size,
+                min_size: self.min_size,
+            });
             let _ = std::fs::remove_file(&full_path);
             return Ok(None);
         }
@@ -110,6 +113,7 @@ impl CarveHandler for PngCarveHandler {
         Ok(Some(CarvedFile {
             run_id: ctx.run_id.to_string(),
             file_type: self.file_type().to_string(),
+            signature_type: self.file_type().to_string(),
             path: rel_path,
             extension: self.extension.clone(),
             global_start: hit.global_offset,
@@ -0,0 +1,307 @@
+//! Windows Prefetch files.
+//!
+//! Uncompressed files (Windows XP to 8) are sized by the file size in their
+//! header. `MAM`-compressed files (Windows 10 and later) do not store their
+//! compressed size; the data is decompressed to the size the `MAM` header
+//! declares and the carve ends after the last input the decompressor read.
+//! Either way the file is validated when the (decompressed) header parses
+//! and its sections lie inside the file. The carve keeps the bytes as found;
+//! the `prefetch_files` post-processor decompresses them again to record the
+//! executable and run count.
+
+use std::io::Write;
+
+use sha2::{Digest, Sha256};
+
+use crate::carve::{
+    CarveError, CarveHandler, CarvedFile, DeclaredSize, ExtractionContext, Rejection,
+    build_carved_file, check_min_size, create_output, note_declared_size, note_rejection,
+    tagged_output_path,
+};
+use crate::parsers::prefetch::{MamHeader, Prefetch, SCCA_PROBE_LEN, SccaHeader};
+use crate::parsers::xpress::{self, XpressError};
+use crate::scanner::NormalizedHit;
+
+/// Read limit when the type has no `max_size`.
+const UNBOUNDED_LIMIT: u64 = 32 * 1024 * 1024;
+
+pub struct PrefetchCarveHandler {
+    extension: String,
+    min_size: u64,
+    max_size: u64,
+}
+
+/// Bytes kept and what is known about them.
+struct Extent {
+    data: Vec<u8>,
+    prefetch: Option<Prefetch>,
+    errors: Vec<String>,
+    truncated: bool,
+}
+
+impl PrefetchCarveHandler {
+    pub fn new(extension: String, min_size: u64, max_size: u64) -> Self {
+        Self {
+            extension,
+            min_size,
+            max_size,
+        }
+    }
+
+    fn limit(&self) -> u64 {
+        if self.max_size > 0 {
+            self.max_size
+        } else {
+            UNBOUNDED_LIMIT
+        }
+    }
+
+    fn plain(
+        &self,
+        header: &SccaHeader,
+        hit: &NormalizedHit,
+        ctx: &ExtractionContext,
+    ) -> Result<Extent, CarveError> {
+        let declared = u64::from(header.file_size);
+        note_declared_size(DeclaredSize {
+            size: declared,
+            source: "scca_header",
+        });
+        let target = declared.min(self.limit());
+        let mut data = vec![0u8; target as usize];
+        let n = read_full(ctx, hit.global_offset, &mut data)?;
+        data.truncate(n);
+        let mut errors = Vec::new();
+        if (n as u64) < target {
+            errors.push("evidence ended before file end".to_string());
+        } else if target < declared {
+            errors.push("max_size reached".to_string());
+        }
+        let truncated = !errors.is_empty();
+        let prefetch = Prefetch::parse(&data);
+        if prefetch.as_ref().is_some_and(|p| !p.sections_ok) {
+            errors.push("section outside the file".to_string());
+        }
+        Ok(Extent {
+            data,
+            prefetch,
+            errors,
+            truncated,
+        })
+    }
+
+    /// `None` when the data does not decompress to a prefetch file.
+    fn compressed(
+        &self,
+        mam: MamHeader,
+        hit: &NormalizedHit,
+        ctx: &ExtractionContext,
+    ) -> Result<Option<Extent>, CarveError> {
+        let size = mam.decompressed_size as u64;
+        // Huffman codes are at most 15 bits; each 64 KiB block adds 256
+        // bytes of code lengths.
+        let bound = mam.data_offset as u64 + size * 2 + size.div_ceil(64 * 1024) * 256 + 8;
+        let target = bound.min(self.limit());
+        let mut data = vec![0u8; target as usize];
+        let n = read_full(ctx, hit.global_offset, &mut data)?;
+        data.truncate(n);
+        match xpress::decompress(&data[mam.data_offset.min(n)..], size as usize) {
+            Ok(out) => {
+                let Some(prefetch) = Prefetch::parse(&out.data) else {
+                    note_rejection(Rejection::Invalid("compressed data is not a prefetch file"));
+                    return Ok(None);
+                };
+                data.truncate(mam.data_offset + out.consumed);
+                let mut errors = Vec::new();
+                if u64::from(prefetch.header.file_size) != size {
+                    errors.push("decompressed size differs from the file size".to_string());
+                }
+                if !prefetch.sections_ok {
+                    errors.push("section outside the file".to_string());
+                }
+                Ok(Some(Extent {
+                    data,
+                    prefetch: Some(prefetch),
+                    errors,
+                    truncated: false,
+                }))
+            }
+            Err(XpressError::Eof) if (n as u64) < bound => {
+                let error = if (n as u64) < target {
+                    "evidence ended before compressed data end"
+                } else {
+                    "max_size reached"
+                };
+                Ok(Some(Extent {
+                    data,
+                    prefetch: None,
+                    errors: vec![error.to_string()],
+                    truncated: true,
+                }))
+            }
+            Err(_) => {
+                note_rejection(Rejection::Invalid("MAM data does not decompress"));
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl CarveHandler for PrefetchCarveHandler {
+    fn file_type(&self) -> &str {
+        "prefetch"
+    }
+
+    fn extension(&self) -> &str {
+        &self.extension
+    }
+
+    fn process_hit(
+        &self,
+        hit: &NormalizedHit,
+        ctx: &ExtractionContext,
+    ) -> Result<Option<CarvedFile>, CarveError> {
+        let mut head = [0u8; SCCA_PROBE_LEN];
+        let n = read_full(ctx, hit.global_offset, &mut head)?;
+        let head = &head[..n];
+        let extent = if let Some(mam) = MamHeader::parse(head) {
+            match self.compressed(mam, hit, ctx)? {
+                Some(extent) => extent,
+                None => return Ok(None),
+            }
+        } else if let Some(header) = SccaHeader::parse(head) {
+            self.plain(&header, hit, ctx)?
+        } else {
+            note_rejection(Rejection::Invalid("not a prefetch or MAM header"));
+            return Ok(None);
+        };
+
+        // Windows names the file `<EXECUTABLE>-<HASH>.pf`.
+        let tag = extent
+            .prefetch
+            .as_ref()
+            .map(|p| format!("{}-{:08X}", p.header.executable, p.header.prefetch_hash));
+        let (full_path, rel_path) = tagged_output_path(
+            ctx.output_root,
+            self.file_type(),
+            tag.as_deref(),
+            &self.extension,
+            hit.global_offset,
+        )?;
+        let mut file = create_output(&full_path)?;
+        file.write_all(&extent.data)?;
+        file.flush()?;
+        let size = extent.data.len() as u64;
+        if !check_min_size(&full_path, size, self.min_size) {
+            return Ok(None);
+        }
+
+        Ok(Some(build_carved_file(
+            ctx.run_id,
+            self.file_type(),
+            &self.extension,
+            rel_path,
+            hit.global_offset,
+            size,
+            format!("{:x}", md5::compute(&extent.data)),
+            hex::encode(Sha256::digest(&extent.data)),
+            extent.errors.is_empty(),
+            extent.truncated,
+            extent.errors,
+            &hit.pattern_id,
+        )))
+    }
+}
+
+fn read_full(ctx: &ExtractionContext, offset: u64, buf: &mut [u8]) -> Result<usize, CarveError> {
+    let lens = ctx
+        .evidence
+        .read_vectored_at(&mut [(offset, buf)])
+        .map_err(|e| CarveError::Evidence(e.to_string()))?;
+    Ok(lens[0])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::carve::support::carve_image;
+    use crate::parsers::prefetch::testing::{RUN_TIME, mam_bytes, prefetch_bytes};
+
+    #[test]
+    fn sizes_plain_files_by_header() {
+        let pf = prefetch_bytes("CMD.EXE", 3, &[RUN_TIME]);
+        let mut image = vec![0u8; 512];
+        image.extend_from_slice(&pf);
+        image.extend_from_slice(&[0xEE; 1024]);
+
+        let (carved, _dir) = carve_image(
+            &PrefetchCarveHandler::new("pf".to_string(), 0, 0),
+            &image,
+            512,
+        );
+        let carved = carved.expect("prefetch");
+        assert_eq!(carved.size, pf.len() as u64);
+        assert!(carved.validated, "{:?}", carved.errors);
+        assert_eq!(
+            carved.path,
+            "prefetch/prefetch_000000000200_CMD.EXE-D7B81A4C.pf"
+        );
+
+        let (carved, _dir) = carve_image(
+            &PrefetchCarveHandler::new("pf".to_string(), 0, 0x140),
+            &image,
+            512,
+        );
+        let carved = carved.expect("cut prefetch");
+        assert!(carved.truncated);
+        assert_eq!(carved.errors, vec!["max_size reached"]);
+    }
+
+    #[test]
+    fn ends_compressed_files_where_the_data_ends() {
+        let mam = mam_bytes(&prefetch_bytes("CHROME.EXE", 7, &[RUN_TIME]));
+        let mut image = mam.clone();
+        image.extend_from_slice(&[0x5A; 4096]);
+
+        let (carved, _dir) = carve_image(
+            &PrefetchCarveHandler::new("pf".to_string(), 0, 0),
+            &image,
+            0,
+        );
+        let carved = carved.expect("mam prefetch");
+        assert_eq!(carved.size, mam.len() as u64);
+        assert!(carved.validated, "{:?}", carved.errors);
+        assert!(carved.path.ends_with("_CHROME.EXE-D7B81A4C.pf"));
+
+        let (carved, _dir) = carve_image(
+            &PrefetchCarveHandler::new("pf".to_string(), 0, 0),
+            &mam[..mam.len() - 10],
+            0,
+        );
+        let carved = carved.expect("cut mam prefetch");
+        assert!(carved.truncated && !carved.validated);
+    }
+
+    #[test]
+    fn rejects_other_data() {
+        let mut junk = b"MAM\x04\x00\x10\x00\x00".to_vec();
+        junk.extend_from_slice(&[0xFF; 4096]);
+        assert!(
+            carve_image(&PrefetchCarveHandler::new("pf".to_string(), 0, 0), &junk, 0)
+                .0
+                .is_none()
+        );
+
+        let mut not_prefetch = mam_bytes(&[0x41; 512]);
+        not_prefetch.extend_from_slice(&[0; 64]);
+        assert!(
+            carve_image(
+                &PrefetchCarveHandler::new("pf".to_string(), 0, 0),
+                &not_prefetch,
+                0
+            )
+            .0
+            .is_none()
+        );
+    }
+}
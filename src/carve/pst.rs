@@ -0,0 +1,379 @@
+//! Outlook personal folders (PST) and offline storage (OST) files.
+//!
+//! A file starts with the `!BDN` signature, a client signature (`SM` for
+//! PST, `SO` for OST), and a format version: 14 or 15 for the ANSI format
+//! (32-bit offsets, 2 GB limit), 23 for Unicode, and 36 for Unicode with
+//! 4 KiB pages. The header's ROOT structure holds `ibFileEof`, the size of
+//! the file, at offset 168 (ANSI, 32-bit) or 184 (Unicode, 64-bit). The
+//! first allocation map page follows the header at 0x4400.
+//!
+//! The carve takes `ibFileEof` bytes, capped at `max_size`. The header
+//! CRCs and the first allocation map page are checked; a mismatch keeps
+//! the file with `validated` false. Folders and messages are not decoded.
+
+use std::io::Write;
+
+use sha2::{Digest, Sha256};
+
+use crate::carve::{
+    CarveError, CarveHandler, CarvedFile, DeclaredSize, ExtractionContext, Rejection,
+    create_output, note_declared_size, note_rejection, output_path, write_range,
+};
+use crate::scanner::NormalizedHit;
+
+/// Header bytes read: the Unicode header ends with `dwCRCFull` at 524.
+const HEADER_LEN: usize = 528;
+/// The CRC-covered header area starts after `dwMagic` and `dwCRCPartial`.
+const CRC_START: usize = 8;
+const CRC_PARTIAL_LEN: usize = 471;
+const CRC_FULL_LEN: usize = 516;
+/// First allocation map (AMap) page.
+const FIRST_AMAP: u64 = 0x4400;
+const PAGE_LEN: u64 = 512;
+const PTYPE_AMAP: u8 = 0x84;
+
+/// On-disk format, from `wVer`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PstFormat {
+    Ansi,
+    Unicode,
+    /// Unicode with 4 KiB pages (Outlook 2013 OST).
+    Unicode4k,
+}
+
+/// Fields of a PST/OST header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PstHeader {
+    pub format: PstFormat,
+    pub version: u16,
+    /// `SO` client signature.
+    pub offline: bool,
+    /// `ibFileEof` from the ROOT structure.
+    pub file_eof: u64,
+    /// `bCryptMethod`: 0 none, 1 permute, 2 cyclic, 0x10 Windows Information Protection.
+    pub crypt_method: u8,
+    pub checksum_ok: bool,
+}
+
+impl PstHeader {
+    /// Parse the header at the start of `data`; `None` when it is not a
+    /// PST/OST header of a known version.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < HEADER_LEN || &data[..4] != b"!BDN" {
+            return None;
+        }
+        let offline = match &data[8..10] {
+            b"SM" => false,
+            b"SO" => true,
+            _ => return None,
+        };
+        let version = le_u16(data, 10);
+        let format = match version {
+            14 | 15 => PstFormat::Ansi,
+            23 => PstFormat::Unicode,
+            36 => PstFormat::Unicode4k,
+            _ => return None,
+        };
+        let partial_ok =
+            weak_crc32(&data[CRC_START..CRC_START + CRC_PARTIAL_LEN]) == le_u32(data, 4);
+        let (file_eof, crypt_method, checksum_ok) = match format {
+            PstFormat::Ansi => (u64::from(le_u32(data, 168)), data[461], partial_ok),
+            PstFormat::Unicode | PstFormat::Unicode4k => {
+                let full_ok =
+                    weak_crc32(&data[CRC_START..CRC_START + CRC_FULL_LEN]) == le_u32(data, 524);
+                (le_u64(data, 184), data[513], partial_ok && full_ok)
+            }
+        };
+        Some(Self {
+            format,
+            version,
+            offline,
+            file_eof,
+            crypt_method,
+            checksum_ok,
+        })
+    }
+
+    /// Offset of the page trailer's `ptype` in a 512-byte page.
+    fn trailer_offset(&self) -> Option<usize> {
+        match self.format {
+            PstFormat::Ansi => Some(500),
+            PstFormat::Unicode => Some(496),
+            // Page layout differs; the AMap page is not checked.
+            PstFormat::Unicode4k => None,
+        }
+    }
+}
+
+/// The CRC-32 of [MS-PST]: the IEEE polynomial without the initial and
+/// final inversion.
+fn weak_crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0u32;
+    for &b in bytes {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320u32 & mask);
+        }
+    }
+    crc
+}
+
+pub struct PstCarveHandler {
+    extension: String,
+    min_size: u64,
+    max_size: u64,
+}
+
+impl PstCarveHandler {
+    pub fn new(extension: String, min_size: u64, max_size: u64) -> Self {
+        Self {
+            extension,
+            min_size,
+            max_size,
+        }
+    }
+
+    /// Whether the first AMap page carries an AMap page trailer; `None`
+    /// when the format is not checked or the evidence ends before it.
+    fn amap_ok(
+        &self,
+        ctx: &ExtractionContext,
+        start: u64,
+        header: &PstHeader,
+    ) -> Result<Option<bool>, CarveError> {
+        let Some(at) = header.trailer_offset() else {
+            return Ok(None);
+        };
+        let mut page = [0u8; PAGE_LEN as usize];
+        let n = ctx
+            .evidence
+            .read_at(start + FIRST_AMAP, &mut page)
+            .map_err(|e| CarveError::Evidence(e.to_string()))?;
+        if n < page.len() {
+            return Ok(None);
+        }
+        Ok(Some(page[at] == PTYPE_AMAP && page[at + 1] == PTYPE_AMAP))
+    }
+}
+
+impl CarveHandler for PstCarveHandler {
+    fn file_type(&self) -> &str {
+        "pst"
+    }
+
+    fn extension(&self) -> &str {
+        &self.extension
+    }
+
+    fn process_hit(
+        &self,
+        hit: &NormalizedHit,
+        ctx: &ExtractionContext,
+    ) -> Result<Option<CarvedFile>, CarveError> {
+        let start = hit.global_offset;
+        let mut block = [0u8; HEADER_LEN];
+        let n = ctx
+            .evidence
+            .read_at(start, &mut block)
+            .map_err(|e| CarveError::Evidence(e.to_string()))?;
+        let Some(header) = PstHeader::parse(&block[..n]) else {
+            note_rejection(Rejection::Invalid("not a PST/OST header"));
+            return Ok(None);
+        };
+        if header.file_eof < FIRST_AMAP + PAGE_LEN {
+            note_rejection(Rejection::Invalid(
+                "file end before the first allocation map page",
+            ));
+            return Ok(None);
+        }
+        note_declared_size(DeclaredSize {
+            size: header.file_eof,
+            source: "root",
+        });
+        let capped = self.max_size > 0 && header.file_eof > self.max_size;
+        let target = if capped {
+            self.max_size
+        } else {
+            header.file_eof
+        };
+        // OST files keep their own extension; both share the `pst` type.
+        let extension = if header.offline {
+            "ost"
+        } else {
+            self.extension.as_str()
+        };
+
+        let (full_path, rel_path) =
+            output_path(ctx.output_root, self.file_type(), extension, start)?;
+        let mut file = create_output(&full_path)?;
+        let mut md5 = md5::Context::new();
+        let mut sha256 = Sha256::new();
+        let (size, short_read) =
+            write_range(ctx, start, start + target, &mut file, &mut md5, &mut sha256)?;
+        file.flush()?;
+        if size < self.min_size {
+            note_rejection(Rejection::MinSize {
+                size,
+                min_size: self.min_size,
+            });
+            let _ = std::fs::remove_file(&full_path);
+            return Ok(None);
+        }
+
+        let mut errors = Vec::new();
+        if short_read {
+            errors.push("evidence ended before file end".to_string());
+        } else if capped {
+            errors.push("max_size reached".to_string());
+        }
+        let truncated = !errors.is_empty();
+        if !header.checksum_ok {
+            errors.push("header checksum mismatch".to_string());
+        }
+        if self.amap_ok(ctx, start, &header)? == Some(false) {
+            errors.push("first allocation map page missing".to_string());
+        }
+
+        Ok(Some(CarvedFile {
+            run_id: ctx.run_id.to_string(),
+            file_type: self.file_type().to_string(),
+            signature_type: self.file_type().to_string(),
+            path: rel_path,
+            extension: extension.to_string(),
+            global_start: start,
+            global_end: start + size - 1,
+            size,
+            md5: Some(format!("{:x}", md5.compute())),
+            sha256: Some(hex::encode(sha256.finalize())),
+            validated: errors.is_empty(),
+            truncated,
+            errors,
+            pattern_id: Some(hit.pattern_id.clone()),
+        }))
+    }
+}
+
+fn le_u16(data: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes(data[at..at + 2].try_into().unwrap_or_default())
+}
+
+fn le_u32(data: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(data[at..at + 4].try_into().unwrap_or_default())
+}
+
+fn le_u64(data: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(data[at..at + 8].try_into().unwrap_or_default())
+}
+
+/// Synthetic PST/OST files.
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::weak_crc32;
+
+    /// A `size`-byte file of format `version` with client signature
+    /// `client`, valid header CRCs, and an AMap page at 0x4400.
+    pub(crate) fn pst_bytes(version: u16, client: &[u8; 2], size: u64) -> Vec<u8> {
+        let mut file = vec![0u8; size as usize];
+        file[..4].copy_from_slice(b"!BDN");
+        file[8..10].copy_from_slice(client);
+        file[10..12].copy_from_slice(&version.to_le_bytes());
+        file[12..14].copy_from_slice(&19u16.to_le_bytes());
+        let unicode = version >= 23;
+        if unicode {
+            file[184..192].copy_from_slice(&size.to_le_bytes());
+        } else {
+            file[168..172].copy_from_slice(&(size as u32).to_le_bytes());
+        }
+        let partial = weak_crc32(&file[8..8 + 471]);
+        file[4..8].copy_from_slice(&partial.to_le_bytes());
+        if unicode {
+            let full = weak_crc32(&file[8..8 + 516]);
+            file[524..528].copy_from_slice(&full.to_le_bytes());
+        }
+        let trailer = 0x4400 + if unicode { 496 } else { 500 };
+        if file.len() > trailer + 1 {
+            file[trailer] = 0x84;
+            file[trailer + 1] = 0x84;
+        }
+        file
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::pst_bytes;
+    use super::*;
+    use crate::carve::support::carve_image;
+
+    #[test]
+    fn sizes_ansi_and_unicode_files_by_file_eof() {
+        for version in [14, 23] {
+            let pst = pst_bytes(version, b"SM", 0x8000);
+            let mut image = vec![0u8; 512];
+            image.extend_from_slice(&pst);
+            image.extend_from_slice(&[0xAB; 4096]);
+
+            let (carved, _dir) =
+                carve_image(&PstCarveHandler::new("pst".to_string(), 0, 0), &image, 512);
+            let carved = carved.expect("pst");
+            assert_eq!(carved.size, pst.len() as u64, "version {version}");
+            assert!(carved.validated && !carved.truncated, "{:?}", carved.errors);
+            assert_eq!(carved.path, "pst/pst_000000000200.pst");
+        }
+
+        let header = PstHeader::parse(&pst_bytes(23, b"SO", 0x8000)).expect("header");
+        assert_eq!(header.format, PstFormat::Unicode);
+        assert!(header.offline && header.checksum_ok);
+        let (carved, _dir) = carve_image(
+            &PstCarveHandler::new("pst".to_string(), 0, 0),
+            &pst_bytes(36, b"SO", 0x8000),
+            0,
+        );
+        let carved = carved.expect("ost");
+        assert_eq!(carved.extension, "ost");
+        assert!(carved.path.ends_with(".ost"));
+    }
+
+    #[test]
+    fn caps_at_max_size_and_flags_damaged_headers() {
+        let pst = pst_bytes(23, b"SM", 0x10000);
+        let (carved, _dir) =
+            carve_image(&PstCarveHandler::new("pst".to_string(), 0, 0x8000), &pst, 0);
+        let carved = carved.expect("capped pst");
+        assert_eq!(carved.size, 0x8000);
+        assert!(carved.truncated && !carved.validated);
+        assert_eq!(carved.errors, vec!["max_size reached".to_string()]);
+
+        let mut cut = pst_bytes(15, b"SM", 0x10000);
+        cut.truncate(0x9000);
+        let (carved, _dir) = carve_image(&PstCarveHandler::new("pst".to_string(), 0, 0), &cut, 0);
+        assert!(carved.expect("partial pst").truncated);
+
+        let mut damaged = pst_bytes(23, b"SM", 0x8000);
+        damaged[300] ^= 0xFF;
+        damaged[0x4400 + 496] = 0;
+        let (carved, _dir) =
+            carve_image(&PstCarveHandler::new("pst".to_string(), 0, 0), &damaged, 0);
+        let carved = carved.expect("damaged pst");
+        assert!(!carved.validated && !carved.truncated);
+        assert_eq!(carved.errors.len(), 2, "{:?}", carved.errors);
+
+        let mut unknown = pst_bytes(23, b"SM", 0x8000);
+        unknown[10] = 20;
+        assert!(
+            carve_image(&PstCarveHandler::new("pst".to_string(), 0, 0), &unknown, 0)
+                .0
+                .is_none()
+        );
+        assert!(
+            carve_image(
+                &PstCarveHandler::new("pst".to_string(), 0, 0),
+                &pst_bytes(23, b"SM", 0x4000),
+                0
+            )
+            .0
+            .is_none()
+        );
+    }
+}
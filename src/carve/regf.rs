@@ -0,0 +1,383 @@
+//! Windows Registry hive files.
+//!
+//! A hive starts with a 4 KiB base block: the `regf` signature, the primary
+//! and secondary sequence numbers, format and file type fields, the size of
+//! the hive bins data, the last 31 characters of the hive's path as
+//! UTF-16LE, and an XOR checksum of the first 508 bytes. The hive bins
+//! follow, each a multiple of 4 KiB that starts with `hbin`, its offset from
+//! the first bin, and its size. The carve walks the bins to the declared
+//! data size and ends after the last bin that fits the chain.
+//!
+//! Sequence numbers that differ mark a hive Windows was writing when it
+//! stopped; the base block then may not match the bins, so such carves are
+//! kept unvalidated. Transaction log files (`.LOG1`/`.LOG2`), which share
+//! the signature but not the bin layout, are rejected.
+
+use std::io::Write;
+
+use sha2::{Digest, Sha256};
+
+use crate::carve::{
+    CarveError, CarveHandler, CarvedFile, DeclaredSize, ExtractionContext, Rejection,
+    create_output, note_declared_size, note_rejection, tagged_output_path, write_range,
+};
+use crate::scanner::NormalizedHit;
+
+const BASE_BLOCK_LEN: u64 = 4096;
+const BIN_ALIGN: u64 = 4096;
+const HBIN_HEADER_LEN: usize = 32;
+const CHECKSUM_OFFSET: usize = 0x1FC;
+const FILE_NAME_OFFSET: usize = 0x30;
+const FILE_NAME_LEN: usize = 64;
+/// `file type` of a primary hive; logs use 1, 2, and 6.
+const FILE_TYPE_PRIMARY: u32 = 0;
+
+/// Hive names recognized in the base block file name and the tag each gets.
+const HIVE_NAMES: [(&str, &str); 10] = [
+    ("SYSTEM", "SYSTEM"),
+    ("SOFTWARE", "SOFTWARE"),
+    ("SAM", "SAM"),
+    ("SECURITY", "SECURITY"),
+    ("DEFAULT", "DEFAULT"),
+    ("COMPONENTS", "COMPONENTS"),
+    ("BCD", "BCD"),
+    ("NTUSER.DAT", "NTUSER"),
+    ("USRCLASS.DAT", "USRCLASS"),
+    ("AMCACHE.HVE", "AMCACHE"),
+];
+
+/// Fields of a hive base block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BaseBlock {
+    pub primary_sequence: u32,
+    pub secondary_sequence: u32,
+    pub minor_version: u32,
+    /// Bytes of hive bins after the base block.
+    pub bins_size: u64,
+    /// Path tail stored in the base block, e.g. `\SystemRoot\System32\Config\SOFTWARE`.
+    pub file_name: String,
+    pub checksum_ok: bool,
+}
+
+impl BaseBlock {
+    /// Parse the base block at the start of `data`; `None` when it is not
+    /// the base block of a primary hive file.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < BASE_BLOCK_LEN as usize || &data[..4] != b"regf" {
+            return None;
+        }
+        let major = le_u32(data, 0x14);
+        let minor_version = le_u32(data, 0x18);
+        let file_type = le_u32(data, 0x1C);
+        let format = le_u32(data, 0x20);
+        let bins_size = u64::from(le_u32(data, 0x28));
+        if major != 1
+            || format != 1
+            || file_type != FILE_TYPE_PRIMARY
+            || bins_size == 0
+            || bins_size % BIN_ALIGN != 0
+        {
+            return None;
+        }
+        let units: Vec<u16> = data[FILE_NAME_OFFSET..FILE_NAME_OFFSET + FILE_NAME_LEN]
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .take_while(|&unit| unit != 0)
+            .collect();
+        Some(Self {
+            primary_sequence: le_u32(data, 0x04),
+            secondary_sequence: le_u32(data, 0x08),
+            minor_version,
+            bins_size,
+            file_name: String::from_utf16_lossy(&units),
+            checksum_ok: checksum(data) == le_u32(data, CHECKSUM_OFFSET),
+        })
+    }
+
+    /// `SYSTEM`, `SOFTWARE`, `NTUSER`, ... when the file name names a
+    /// well-known hive.
+    pub fn hive_kind(&self) -> Option<&'static str> {
+        let name = self
+            .file_name
+            .rsplit(['\\', '/'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_uppercase();
+        HIVE_NAMES
+            .iter()
+            .find(|(file, _)| *file == name)
+            .map(|(_, kind)| *kind)
+    }
+}
+
+/// XOR of the first 127 little-endian words, with 0 and -1 mapped as
+/// Windows maps them.
+fn checksum(data: &[u8]) -> u32 {
+    let sum = data[..CHECKSUM_OFFSET]
+        .chunks_exact(4)
+        .fold(0u32, |acc, word| acc ^ le_u32(word, 0));
+    match sum {
+        0xFFFF_FFFF => 0xFFFF_FFFE,
+        0 => 1,
+        sum => sum,
+    }
+}
+
+pub struct RegfCarveHandler {
+    extension: String,
+    min_size: u64,
+    max_size: u64,
+}
+
+impl RegfCarveHandler {
+    pub fn new(extension: String, min_size: u64, max_size: u64) -> Self {
+        Self {
+            extension,
+            min_size,
+            max_size,
+        }
+    }
+
+    /// Bytes of bins from `bins_start` that form an unbroken chain, up to
+    /// `limit`, and where the chain broke, if it did.
+    fn walk_bins(
+        &self,
+        ctx: &ExtractionContext,
+        bins_start: u64,
+        limit: u64,
+    ) -> Result<(u64, Option<u64>), CarveError> {
+        let mut offset = 0u64;
+        let mut header = [0u8; HBIN_HEADER_LEN];
+        while offset < limit {
+            ctx.check_cancelled()?;
+            let n = ctx
+                .evidence
+                .read_at(bins_start + offset, &mut header)
+                .map_err(|e| CarveError::Evidence(e.to_string()))?;
+            let size = u64::from(le_u32(&header, 0x08));
+            if n < HBIN_HEADER_LEN
+                || &header[..4] != b"hbin"
+                || u64::from(le_u32(&header, 0x04)) != offset
+                || size == 0
+                || size % BIN_ALIGN != 0
+            {
+                return Ok((offset, Some(offset)));
+            }
+            offset += size;
+        }
+        Ok((offset.min(limit), None))
+    }
+}
+
+impl CarveHandler for RegfCarveHandler {
+    fn file_type(&self) -> &str {
+        "regf"
+    }
+
+    fn extension(&self) -> &str {
+        &self.extension
+    }
+
+    fn process_hit(
+        &self,
+        hit: &NormalizedHit,
+        ctx: &ExtractionContext,
+    ) -> Result<Option<CarvedFile>, CarveError> {
+        let start = hit.global_offset;
+        let mut block = vec![0u8; BASE_BLOCK_LEN as usize];
+        let n = ctx
+            .evidence
+            .read_at(start, &mut block)
+            .map_err(|e| CarveError::Evidence(e.to_string()))?;
+        let Some(base) = BaseBlock::parse(&block[..n]) else {
+            note_rejection(Rejection::Invalid("not the base block of a primary hive"));
+            return Ok(None);
+        };
+        let declared = BASE_BLOCK_LEN + base.bins_size;
+        note_declared_size(DeclaredSize {
+            size: declared,
+            source: "base_block",
+        });
+        let limit = if self.max_size > 0 {
+            declared.min(self.max_size)
+        } else {
+            declared
+        };
+        let (bins, broken_at) = self.walk_bins(
+            ctx,
+            start + BASE_BLOCK_LEN,
+            limit.saturating_sub(BASE_BLOCK_LEN),
+        )?;
+        if bins == 0 {
+            note_rejection(Rejection::Invalid("no hive bin after the base block"));
+            return Ok(None);
+        }
+        let target = BASE_BLOCK_LEN + bins;
+
+        let (full_path, rel_path) = tagged_output_path(
+            ctx.output_root,
+            self.file_type(),
+            base.hive_kind(),
+            &self.extension,
+            start,
+        )?;
+        let mut file = create_output(&full_path)?;
+        let mut md5 = md5::Context::new();
+        let mut sha256 = Sha256::new();
+        let (size, short_read) =
+            write_range(ctx, start, start + target, &mut file, &mut md5, &mut sha256)?;
+        file.flush()?;
+        if size < self.min_size {
+            note_rejection(Rejection::MinSize {
+                size,
+                min_size: self.min_size,
+            });
+            let _ = std::fs::remove_file(&full_path);
+            return Ok(None);
+        }
+
+        let mut errors = Vec::new();
+        if short_read {
+            errors.push("evidence ended before hive end".to_string());
+        } else if let Some(at) = broken_at {
+            errors.push(format!("hive bin chain breaks at bin offset {at}"));
+        } else if target < declared {
+            errors.push("max_size reached".to_string());
+        }
+        let truncated = !errors.is_empty();
+        if base.primary_sequence != base.secondary_sequence {
+            errors.push(format!(
+                "sequence numbers differ (primary {}, secondary {}): hive was being written",
+                base.primary_sequence, base.secondary_sequence
+            ));
+        }
+        if !base.checksum_ok {
+            errors.push("base block checksum mismatch".to_string());
+        }
+
+        Ok(Some(CarvedFile {
+            run_id: ctx.run_id.to_string(),
+            file_type: self.file_type().to_string(),
+            signature_type: self.file_type().to_string(),
+            path: rel_path,
+            extension: self.extension.clone(),
+            global_start: start,
+            global_end: start + size - 1,
+            size,
+            md5: Some(format!("{:x}", md5.compute())),
+            sha256: Some(hex::encode(sha256.finalize())),
+            validated: errors.is_empty(),
+            truncated,
+            errors,
+            pattern_id: Some(hit.pattern_id.clone()),
+        }))
+    }
+}
+
+fn le_u32(data: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(data[at..at + 4].try_into().unwrap_or_default())
+}
+
+/// Synthetic hive files.
+#[cfg(test)]
+pub(crate) mod testing {
+    /// A primary hive whose base block names `file_name`, with one 4 KiB
+    /// bin per entry of `bins` (in 4 KiB pages) and the given sequence
+    /// numbers.
+    pub(crate) fn hive_bytes(file_name: &str, bins: &[u32], sequences: (u32, u32)) -> Vec<u8> {
+        let bins_size: u32 = bins.iter().sum::<u32>() * 4096;
+        let mut hive = vec![0u8; 4096];
+        hive[..4].copy_from_slice(b"regf");
+        hive[4..8].copy_from_slice(&sequences.0.to_le_bytes());
+        hive[8..12].copy_from_slice(&sequences.1.to_le_bytes());
+        hive[0x14..0x18].copy_from_slice(&1u32.to_le_bytes());
+        hive[0x18..0x1C].copy_from_slice(&5u32.to_le_bytes());
+        hive[0x20..0x24].copy_from_slice(&1u32.to_le_bytes());
+        hive[0x24..0x28].copy_from_slice(&0x20u32.to_le_bytes());
+        hive[0x28..0x2C].copy_from_slice(&bins_size.to_le_bytes());
+        // Windows keeps the tail of a path longer than the field.
+        let units: Vec<u16> = file_name.encode_utf16().collect();
+        let tail = &units[units.len().saturating_sub(31)..];
+        for (i, unit) in tail.iter().enumerate() {
+            hive[0x30 + i * 2..0x32 + i * 2].copy_from_slice(&unit.to_le_bytes());
+        }
+        let sum = super::checksum(&hive);
+        hive[0x1FC..0x200].copy_from_slice(&sum.to_le_bytes());
+        let mut offset = 0u32;
+        for &pages in bins {
+            let mut bin = vec![0u8; pages as usize * 4096];
+            bin[..4].copy_from_slice(b"hbin");
+            bin[4..8].copy_from_slice(&offset.to_le_bytes());
+            bin[8..12].copy_from_slice(&(pages * 4096).to_le_bytes());
+            hive.extend_from_slice(&bin);
+            offset += pages * 4096;
+        }
+        hive
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::hive_bytes;
+    use super::*;
+    use crate::carve::support::carve_image;
+
+    #[test]
+    fn sizes_hive_by_bins_and_tags_it_by_name() {
+        let hive = hive_bytes(r"\SystemRoot\System32\Config\SOFTWARE", &[1, 2], (7, 7));
+        let mut image = vec![0u8; 4096];
+        image.extend_from_slice(&hive);
+        image.extend_from_slice(&[0xAAu8; 8192]);
+
+        let (carved, _dir) = carve_image(
+            &RegfCarveHandler::new("dat".to_string(), 0, 0),
+            &image,
+            4096,
+        );
+        let carved = carved.expect("hive");
+        assert_eq!(carved.size, hive.len() as u64);
+        assert!(carved.validated, "{:?}", carved.errors);
+        assert_eq!(carved.path, "regf/regf_000000001000_SOFTWARE.dat");
+
+        let user = BaseBlock::parse(&hive_bytes(r"\??\C:\Users\ann\ntuser.dat", &[1], (1, 1)))
+            .expect("base block");
+        assert_eq!(user.hive_kind(), Some("NTUSER"));
+        let other = BaseBlock::parse(&hive_bytes(r"\??\C:\temp\custom.hiv", &[1], (1, 1)))
+            .expect("base block");
+        assert_eq!(other.hive_kind(), None);
+    }
+
+    #[test]
+    fn dirty_and_broken_hives_are_kept_unvalidated() {
+        let dirty = hive_bytes("SYSTEM", &[1], (8, 7));
+        let (carved, _dir) =
+            carve_image(&RegfCarveHandler::new("dat".to_string(), 0, 0), &dirty, 0);
+        let carved = carved.expect("dirty hive");
+        assert!(!carved.validated && !carved.truncated);
+        assert!(carved.errors[0].contains("sequence numbers differ"));
+
+        // The second bin was overwritten.
+        let mut broken = hive_bytes("SYSTEM", &[1, 1, 1], (3, 3));
+        broken[4096 + 4096..4096 + 8192].fill(0);
+        let (carved, _dir) =
+            carve_image(&RegfCarveHandler::new("dat".to_string(), 0, 0), &broken, 0);
+        let carved = carved.expect("broken hive");
+        assert_eq!(carved.size, 8192);
+        assert!(carved.truncated);
+        assert_eq!(
+            carved.errors,
+            vec!["hive bin chain breaks at bin offset 4096"]
+        );
+    }
+
+    #[test]
+    fn rejects_transaction_logs() {
+        let mut log = hive_bytes("SYSTEM.LOG1", &[1], (1, 1));
+        log[0x1C..0x20].copy_from_slice(&6u32.to_le_bytes());
+        assert!(
+            carve_image(&RegfCarveHandler::new("dat".to_string(), 0, 0), &log, 0)
+                .0
+                .is_none()
+        );
+    }
+}
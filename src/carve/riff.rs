@@ -8,6 +8,9 @@ use crate::carve::CarveError;
 /// RIFF header magic bytes
 pub const RIFF_MAGIC: &[u8; 4] = b"RIFF";
 
+/// RF64 header magic bytes (RIFF with 64-bit sizes in a `ds64` chunk)
+pub const RF64_MAGIC: &[u8; 4] = b"RF64";
+
 /// WAV form type
 pub const WAVE_FORM: &[u8; 4] = b"WAVE";
 
@@ -2,10 +2,9 @@
 //!
 //! Uses brace depth tracking with escape and \bin handling to find document end.
 
-use std::fs::File;
-
 use crate::carve::{
-    CarveError, CarveHandler, CarveStream, CarvedFile, ExtractionContext, output_path,
+    CarveError, CarveHandler, CarveStream, CarvedFile, ExtractionContext, Rejection, create_output,
+    note_rejection, output_path,
 };
 use crate::scanner::NormalizedHit;
 
@@ -45,7 +44,7 @@ impl CarveHandler for RtfCarveHandler {
             &self.extension,
             hit.global_offset,
         )?;
-        let file = File::create(&full_path)?;
+        let file = create_output(&full_path)?;
         let mut stream = CarveStream::new(ctx.evidence, hit.global_offset, self.max_size, file);
 
         let mut validated = false;
@@ -143,6 +142,11 @@ impl CarveHandler for RtfCarveHandler {
         let (size, md5_hex, sha256_hex) = stream.finish()?;
 
         if size < self.min_size {
+            note_rejection(Rejection::MinSize {
+                size,
+
+                min_size: self.min_size,
+            });
             let _ = std::fs::remove_file(&full_path);
             return Ok(None);
         }
@@ -163,6 +167,7 @@ impl CarveHandler for RtfCarveHandler {
         Ok(Some(CarvedFile {
             run_id: ctx.run_id.to_string(),
             file_type: self.file_type().to_string(),
+            signature_type: self.file_type().to_string(),
             path: rel_path,
             extension: self.extension.clone(),
             global_start: hit.global_offset,
@@ -1,11 +1,39 @@
-use std::fs::File;
+//! SQLite databases and the rollback journal or WAL stored after them.
+//!
+//! The database size comes from the header page count, which covers freelist
+//! and pointer-map pages as well as pages in use. Writers before SQLite 3.7.0
+//! did not keep that count current (`version-valid-for` differs from the
+//! change counter); for those the size is the last page reached through the
+//! freelist trunk chain or the pointer map.
+//!
+//! A `-journal` or `-wal` starting at the end of the database, or at a sector
+//! boundary shortly after it, is carved next to it as a companion
+//! (`<name>.sqlite-wal`, `<name>.sqlite-journal`), the names SQLite itself
+//! looks for, so the pair opens together.
+
+use std::path::PathBuf;
+
+use sha2::{Digest, Sha256};
 
 use crate::carve::{
-    CarveError, CarveHandler, CarveStream, CarvedFile, ExtractionContext, output_path,
+    CarveError, CarveHandler, CarveStream, CarvedFile, DeclaredSize, ExtractionContext, Rejection,
+    build_carved_file, create_output, note_companion, note_declared_size, note_rejection,
+    output_path, support::write_range,
 };
+use crate::evidence::EvidenceSource;
 use crate::scanner::NormalizedHit;
 
 const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+const WAL_MAGIC: u32 = 0x377f_0682;
+const WAL_HEADER_LEN: u64 = 32;
+const WAL_FRAME_HEADER_LEN: u64 = 24;
+const JOURNAL_MAGIC: [u8; 8] = [0xd9, 0xd5, 0x05, 0xf9, 0x20, 0xa1, 0x63, 0xd7];
+/// Companions are looked for at the end of the database and at the 512-byte
+/// boundaries up to this many bytes after it.
+const COMPANION_WINDOW: u64 = 4096;
+const SECTOR: u64 = 512;
+/// Bytes copied per read while carving the database.
+const COPY_BLOCK: u64 = 1024 * 1024;
 
 pub struct SqliteCarveHandler {
     extension: String,
@@ -23,6 +51,51 @@ impl SqliteCarveHandler {
     }
 }
 
+/// Fields of the 100-byte database header used for sizing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct DbHeader {
+    page_size: u64,
+    /// Page size less the reserved bytes at the end of each page.
+    usable_size: u64,
+    page_count: u64,
+    /// The page count was written by a writer that keeps it current.
+    page_count_valid: bool,
+    freelist_trunk: u64,
+    freelist_pages: u64,
+    /// Non-zero in auto-vacuum databases, which hold pointer-map pages.
+    largest_root: u64,
+}
+
+impl DbHeader {
+    fn parse(header: &[u8]) -> Result<Self, &'static str> {
+        if header.len() < 100 || &header[..SQLITE_HEADER.len()] != SQLITE_HEADER {
+            return Err("sqlite header mismatch");
+        }
+        let page_size = match u16::from_be_bytes([header[16], header[17]]) {
+            1 => 65536,
+            raw => u64::from(raw),
+        };
+        if !is_valid_page_size(page_size as u32) {
+            return Err("sqlite page size invalid");
+        }
+        let reserved = u64::from(header[20]);
+        if page_size - reserved < 480 {
+            return Err("sqlite reserved space too large");
+        }
+        let change_counter = be32(&header[24..28]);
+        let page_count = u64::from(be32(&header[28..32]));
+        Ok(Self {
+            page_size,
+            usable_size: page_size - reserved,
+            page_count,
+            page_count_valid: page_count > 0 && be32(&header[92..96]) == change_counter,
+            freelist_trunk: u64::from(be32(&header[32..36])),
+            freelist_pages: u64::from(be32(&header[36..40])),
+            largest_root: u64::from(be32(&header[52..56])),
+        })
+    }
+}
+
 impl CarveHandler for SqliteCarveHandler {
     fn file_type(&self) -> &str {
         "sqlite"
@@ -37,110 +110,432 @@ impl CarveHandler for SqliteCarveHandler {
         hit: &NormalizedHit,
         ctx: &ExtractionContext,
     ) -> Result<Option<CarvedFile>, CarveError> {
+        let mut raw = [0u8; 100];
+        let n = ctx
+            .evidence
+            .read_at(hit.global_offset, &mut raw)
+            .map_err(|e| CarveError::Evidence(e.to_string()))?;
+        let header = match DbHeader::parse(&raw[..n]) {
+            Ok(header) => header,
+            Err(reason) => {
+                note_rejection(Rejection::Invalid(reason));
+                return Ok(None);
+            }
+        };
+        let limit_pages = match self.max_size {
+            0 => u64::from(u32::MAX),
+            max => max.div_ceil(header.page_size),
+        };
+        let (pages, source) = database_pages(ctx, hit.global_offset, &header, limit_pages)?;
+        let total_size = pages * header.page_size;
+        note_declared_size(DeclaredSize {
+            size: total_size,
+            source,
+        });
+
         let (full_path, rel_path) = output_path(
             ctx.output_root,
             self.file_type(),
             &self.extension,
             hit.global_offset,
         )?;
-        let file = File::create(&full_path)?;
+        let file = create_output(&full_path)?;
         let mut stream = CarveStream::new(ctx.evidence, hit.global_offset, self.max_size, file);
+        let target_size = match self.max_size {
+            0 => total_size,
+            max => total_size.min(max),
+        };
 
-        let mut validated = false;
+        let mut validated = true;
         let mut truncated = false;
         let mut errors = Vec::new();
-
-        let result: Result<u64, CarveError> = (|| {
-            let header = stream.read_exact(100)?;
-            if &header[..SQLITE_HEADER.len()] != SQLITE_HEADER {
-                return Err(CarveError::Invalid("sqlite header mismatch".to_string()));
-            }
-
-            let page_size_raw = u16::from_be_bytes([header[16], header[17]]);
-            let page_size = if page_size_raw == 1 {
-                65536
-            } else {
-                page_size_raw as u32
-            };
-            if !is_valid_page_size(page_size) {
-                return Err(CarveError::Invalid("sqlite page size invalid".to_string()));
-            }
-
-            let page_count = u32::from_be_bytes([header[28], header[29], header[30], header[31]]);
-            let mut total_size = if page_count == 0 {
-                page_size as u64
-            } else {
-                page_size as u64 * page_count as u64
-            };
-            if total_size < 100 {
-                total_size = 100;
-            }
-
-            let max_size = if self.max_size > 0 {
-                self.max_size
-            } else {
-                total_size
-            };
-            let target_size = total_size.min(max_size);
-
-            let remaining = target_size.saturating_sub(100);
-            if remaining > 0 {
-                stream.read_exact(remaining as usize)?;
-            }
-
-            validated = true;
-            Ok(target_size)
-        })();
-
-        if let Err(err) = result {
-            match err {
-                CarveError::Truncated | CarveError::Eof => {
+        let mut copied = 0u64;
+        while copied < target_size {
+            let len = (target_size - copied).min(COPY_BLOCK);
+            match stream.read_exact(len as usize) {
+                Ok(_) => copied += len,
+                Err(err @ (CarveError::Truncated | CarveError::Eof)) => {
+                    validated = false;
                     truncated = true;
                     errors.push(err.to_string());
+                    break;
                 }
-                CarveError::Invalid(_msg) => {
+                Err(other) => {
                     let _ = std::fs::remove_file(&full_path);
-                    return Ok(None);
+                    return Err(other);
                 }
-                other => return Err(other),
             }
         }
 
         let (size, md5_hex, sha256_hex) = stream.finish()?;
         if size < self.min_size {
+            note_rejection(Rejection::MinSize {
+                size,
+                min_size: self.min_size,
+            });
             let _ = std::fs::remove_file(&full_path);
             return Ok(None);
         }
 
-        if self.max_size > 0 && size >= self.max_size {
+        if self.max_size > 0 && size >= self.max_size && size < total_size {
             truncated = true;
             if !errors.iter().any(|e| e.contains("max_size")) {
                 errors.push("max_size reached".to_string());
             }
         }
 
-        let global_end = if size == 0 {
-            hit.global_offset
-        } else {
-            hit.global_offset + size - 1
-        };
+        if !truncated
+            && let Some(companion) = find_companion(ctx, hit.global_offset + size, &header)
+        {
+            match carve_companion(ctx, &companion, &full_path, &rel_path, &self.extension) {
+                Ok(file) => note_companion(file),
+                Err(err) => errors.push(format!(
+                    "{} at offset {} not carved: {err}",
+                    companion.kind.file_type(),
+                    companion.start
+                )),
+            }
+        }
 
-        Ok(Some(CarvedFile {
-            run_id: ctx.run_id.to_string(),
-            file_type: self.file_type().to_string(),
-            path: rel_path,
-            extension: self.extension.clone(),
-            global_start: hit.global_offset,
-            global_end,
+        Ok(Some(build_carved_file(
+            ctx.run_id,
+            self.file_type(),
+            &self.extension,
+            rel_path,
+            hit.global_offset,
             size,
-            md5: Some(md5_hex),
-            sha256: Some(sha256_hex),
+            md5_hex,
+            sha256_hex,
             validated,
             truncated,
             errors,
-            pattern_id: Some(hit.pattern_id.clone()),
-        }))
+            &hit.pattern_id,
+        )))
+    }
+}
+
+/// Pages of the database at `start` and the structure that gave the count.
+fn database_pages(
+    ctx: &ExtractionContext,
+    start: u64,
+    header: &DbHeader,
+    limit_pages: u64,
+) -> Result<(u64, &'static str), CarveError> {
+    if header.page_count_valid {
+        return Ok((header.page_count, "page_count"));
+    }
+    let mut pages = (header.page_count.max(1), "page_count");
+    let freelist = last_freelist_page(ctx, start, header, limit_pages)?;
+    if freelist > pages.0 {
+        pages = (freelist, "freelist");
+    }
+    if header.largest_root > 0 {
+        let ptrmap = last_mapped_page(ctx, start, header, limit_pages)?
+            .max(header.largest_root.min(limit_pages));
+        if ptrmap > pages.0 {
+            pages = (ptrmap, "ptrmap");
+        }
+    }
+    Ok(pages)
+}
+
+/// Highest page number on the freelist trunk chain or its leaves; 0 when
+/// the freelist is empty or unreadable.
+fn last_freelist_page(
+    ctx: &ExtractionContext,
+    start: u64,
+    header: &DbHeader,
+    limit_pages: u64,
+) -> Result<u64, CarveError> {
+    let max_leaves = header.usable_size / 4 - 2;
+    let mut last = 0;
+    let mut trunk = header.freelist_trunk;
+    let mut seen = 0u64;
+    while trunk > 1 && trunk <= limit_pages && seen < header.freelist_pages {
+        ctx.check_cancelled()?;
+        let Some(page) = read_page(ctx.evidence, start, header.page_size, trunk) else {
+            break;
+        };
+        let leaves = u64::from(be32(&page[4..8]));
+        if leaves > max_leaves {
+            break;
+        }
+        last = last.max(trunk);
+        for i in 0..leaves as usize {
+            let leaf = u64::from(be32(&page[8 + 4 * i..12 + 4 * i]));
+            if leaf <= limit_pages {
+                last = last.max(leaf);
+            }
+        }
+        seen += 1 + leaves;
+        trunk = u64::from(be32(&page[..4]));
+    }
+    Ok(last)
+}
+
+/// Highest page described by a pointer-map entry in use; 0 when there is
+/// none. Pointer-map pages start at page 2 and each describes the pages up
+/// to the next one.
+fn last_mapped_page(
+    ctx: &ExtractionContext,
+    start: u64,
+    header: &DbHeader,
+    limit_pages: u64,
+) -> Result<u64, CarveError> {
+    let entries = header.usable_size / 5;
+    let mut last = 0;
+    let mut map = 2u64;
+    while map <= limit_pages {
+        ctx.check_cancelled()?;
+        let Some(page) = read_page(ctx.evidence, start, header.page_size, map) else {
+            break;
+        };
+        let mut used = false;
+        for i in 0..entries {
+            match page[(i * 5) as usize] {
+                0 => {}
+                1..=5 => {
+                    last = map + 1 + i;
+                    used = true;
+                }
+                _ => return Ok(last.min(limit_pages)),
+            }
+        }
+        if !used {
+            break;
+        }
+        last = last.max(map);
+        map += entries + 1;
+    }
+    Ok(last.min(limit_pages))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CompanionKind {
+    Wal,
+    Journal,
+}
+
+impl CompanionKind {
+    fn file_type(self) -> &'static str {
+        match self {
+            CompanionKind::Wal => "sqlite_wal",
+            CompanionKind::Journal => "sqlite_journal",
+        }
+    }
+
+    fn suffix(self) -> &'static str {
+        match self {
+            CompanionKind::Wal => "-wal",
+            CompanionKind::Journal => "-journal",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Companion {
+    kind: CompanionKind,
+    start: u64,
+    size: u64,
+}
+
+/// A WAL or rollback journal of `header`'s page size starting at `db_end` or
+/// at a sector boundary within [`COMPANION_WINDOW`] after it.
+fn find_companion(ctx: &ExtractionContext, db_end: u64, header: &DbHeader) -> Option<Companion> {
+    let first_sector = db_end.div_ceil(SECTOR) * SECTOR;
+    let candidates = std::iter::once(db_end).chain(
+        (first_sector..=db_end + COMPANION_WINDOW)
+            .step_by(SECTOR as usize)
+            .filter(|&offset| offset != db_end),
+    );
+    for offset in candidates {
+        if ctx.check_cancelled().is_err() {
+            return None;
+        }
+        if let Some(size) = wal_size(ctx.evidence, offset, header.page_size) {
+            return Some(Companion {
+                kind: CompanionKind::Wal,
+                start: offset,
+                size,
+            });
+        }
+        if let Some(size) = journal_size(ctx.evidence, offset, header.page_size) {
+            return Some(Companion {
+                kind: CompanionKind::Journal,
+                start: offset,
+                size,
+            });
+        }
+    }
+    None
+}
+
+/// Size of the WAL at `start` up to its last frame with matching salts and
+/// checksum; `None` without a valid header and at least one frame.
+fn wal_size(evidence: &dyn EvidenceSource, start: u64, page_size: u64) -> Option<u64> {
+    let header = read_exact_at(evidence, start, WAL_HEADER_LEN as usize)?;
+    let magic = be32(&header[..4]);
+    if magic & !1 != WAL_MAGIC
+        || be32(&header[4..8]) != 3_007_000
+        || u64::from(be32(&header[8..12])) != page_size
+    {
+        return None;
+    }
+    let big_endian = magic & 1 == 1;
+    let mut sum = wal_checksum(&header[..24], big_endian, (0, 0));
+    if sum != (be32(&header[24..28]), be32(&header[28..32])) {
+        return None;
+    }
+    let frame_len = WAL_FRAME_HEADER_LEN + page_size;
+    let mut frames = 0u64;
+    loop {
+        let offset = start + WAL_HEADER_LEN + frames * frame_len;
+        let Some(frame) = read_exact_at(evidence, offset, frame_len as usize) else {
+            break;
+        };
+        if be32(&frame[..4]) == 0 || frame[8..16] != header[16..24] {
+            break;
+        }
+        let next = wal_checksum(&frame[..8], big_endian, sum);
+        let next = wal_checksum(&frame[24..], big_endian, next);
+        if next != (be32(&frame[16..20]), be32(&frame[20..24])) {
+            break;
+        }
+        sum = next;
+        frames += 1;
+    }
+    (frames > 0).then_some(WAL_HEADER_LEN + frames * frame_len)
+}
+
+/// Running WAL checksum over `data` (a multiple of 8 bytes).
+fn wal_checksum(data: &[u8], big_endian: bool, (mut s0, mut s1): (u32, u32)) -> (u32, u32) {
+    for pair in data.chunks_exact(8) {
+        let word = |b: &[u8]| {
+            let bytes = [b[0], b[1], b[2], b[3]];
+            if big_endian {
+                u32::from_be_bytes(bytes)
+            } else {
+                u32::from_le_bytes(bytes)
+            }
+        };
+        s0 = s0.wrapping_add(word(&pair[..4])).wrapping_add(s1);
+        s1 = s1.wrapping_add(word(&pair[4..])).wrapping_add(s0);
+    }
+    (s0, s1)
+}
+
+/// Size of the rollback journal at `start` up to its last page record with
+/// a matching checksum; `None` without a valid header and one record.
+fn journal_size(evidence: &dyn EvidenceSource, start: u64, page_size: u64) -> Option<u64> {
+    let header = read_exact_at(evidence, start, 28)?;
+    if header[..8] != JOURNAL_MAGIC || u64::from(be32(&header[24..28])) != page_size {
+        return None;
+    }
+    let declared = be32(&header[8..12]);
+    let nonce = be32(&header[12..16]);
+    let sector_size = u64::from(be32(&header[20..24]));
+    if !(SECTOR..=65536).contains(&sector_size) || !sector_size.is_power_of_two() {
+        return None;
+    }
+    // A count of 0 or -1 means the records run to the end of the journal.
+    let declared = match declared {
+        0 | u32::MAX => u64::MAX,
+        n => u64::from(n),
+    };
+    let record_len = page_size + 8;
+    let mut records = 0u64;
+    while records < declared {
+        let offset = start + sector_size + records * record_len;
+        let Some(record) = read_exact_at(evidence, offset, record_len as usize) else {
+            break;
+        };
+        if be32(&record[..4]) == 0 {
+            break;
+        }
+        let page = &record[4..4 + page_size as usize];
+        if journal_checksum(page, nonce) != be32(&record[4 + page_size as usize..]) {
+            break;
+        }
+        records += 1;
+    }
+    (records > 0).then_some(sector_size + records * record_len)
+}
+
+/// Rollback journal page checksum: the nonce plus every 200th byte from the
+/// end of the page.
+fn journal_checksum(page: &[u8], nonce: u32) -> u32 {
+    let mut sum = nonce;
+    let mut i = page.len() as isize - 200;
+    while i > 0 {
+        sum = sum.wrapping_add(u32::from(page[i as usize]));
+        i -= 200;
+    }
+    sum
+}
+
+/// Write `companion` next to the database carved to `db_path`.
+fn carve_companion(
+    ctx: &ExtractionContext,
+    companion: &Companion,
+    db_path: &std::path::Path,
+    db_rel_path: &str,
+    db_extension: &str,
+) -> Result<CarvedFile, CarveError> {
+    let suffix = companion.kind.suffix();
+    let mut name = db_path.as_os_str().to_owned();
+    name.push(suffix);
+    let full_path = PathBuf::from(name);
+    let mut file = create_output(&full_path)?;
+    let mut md5 = md5::Context::new();
+    let mut sha256 = Sha256::new();
+    let end = companion.start + companion.size;
+    let (size, truncated) =
+        write_range(ctx, companion.start, end, &mut file, &mut md5, &mut sha256).inspect_err(
+            |_| {
+                let _ = std::fs::remove_file(&full_path);
+            },
+        )?;
+    let mut carved = build_carved_file(
+        ctx.run_id,
+        companion.kind.file_type(),
+        &format!("{db_extension}{suffix}"),
+        format!("{db_rel_path}{suffix}"),
+        companion.start,
+        size,
+        format!("{:x}", md5.compute()),
+        hex::encode(sha256.finalize()),
+        true,
+        truncated,
+        Vec::new(),
+        "sqlite_header",
+    );
+    carved.signature_type = "sqlite".to_string();
+    Ok(carved)
+}
+
+fn read_page(
+    evidence: &dyn EvidenceSource,
+    start: u64,
+    page_size: u64,
+    page: u64,
+) -> Option<Vec<u8>> {
+    read_exact_at(evidence, start + (page - 1) * page_size, page_size as usize)
+}
+
+fn read_exact_at(evidence: &dyn EvidenceSource, offset: u64, len: usize) -> Option<Vec<u8>> {
+    let mut buf = vec![0u8; len];
+    let mut read = 0;
+    while read < len {
+        match evidence.read_at(offset + read as u64, &mut buf[read..]) {
+            Ok(0) | Err(_) => return None,
+            Ok(n) => read += n,
+        }
     }
+    Some(buf)
+}
+
+fn be32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
 }
 
 fn is_valid_page_size(page_size: u32) -> bool {
@@ -152,7 +547,87 @@ fn is_valid_page_size(page_size: u32) -> bool {
 
 #[cfg(test)]
 mod tests {
-    use super::is_valid_page_size;
+    use super::*;
+    use crate::carve::{take_companions, take_declared_size};
+    use crate::evidence::MemorySource;
+
+    const PAGE: usize = 512;
+
+    fn put32(buf: &mut [u8], at: usize, value: u32) {
+        buf[at..at + 4].copy_from_slice(&value.to_be_bytes());
+    }
+
+    /// A database of `pages` pages whose header claims `header_pages`, valid
+    /// only when `current`.
+    fn database(pages: usize, header_pages: u32, current: bool) -> Vec<u8> {
+        let mut db = vec![0u8; pages * PAGE];
+        db[..16].copy_from_slice(SQLITE_HEADER);
+        db[16..18].copy_from_slice(&(PAGE as u16).to_be_bytes());
+        put32(&mut db, 24, 7);
+        put32(&mut db, 28, header_pages);
+        put32(&mut db, 92, if current { 7 } else { 3 });
+        db
+    }
+
+    fn wal(frames: u32) -> Vec<u8> {
+        let mut wal = vec![0u8; 32];
+        put32(&mut wal, 0, WAL_MAGIC | 1);
+        put32(&mut wal, 4, 3_007_000);
+        put32(&mut wal, 8, PAGE as u32);
+        put32(&mut wal, 16, 0x1111);
+        put32(&mut wal, 20, 0x2222);
+        let mut sum = wal_checksum(&wal[..24], true, (0, 0));
+        put32(&mut wal, 24, sum.0);
+        put32(&mut wal, 28, sum.1);
+        for page in 1..=frames {
+            let mut frame = vec![page as u8; 24 + PAGE];
+            put32(&mut frame, 0, page);
+            put32(&mut frame, 4, if page == frames { page } else { 0 });
+            put32(&mut frame, 8, 0x1111);
+            put32(&mut frame, 12, 0x2222);
+            sum = wal_checksum(&frame[..8], true, sum);
+            sum = wal_checksum(&frame[24..], true, sum);
+            put32(&mut frame, 16, sum.0);
+            put32(&mut frame, 20, sum.1);
+            wal.extend_from_slice(&frame);
+        }
+        wal
+    }
+
+    fn journal(records: u32) -> Vec<u8> {
+        let mut journal = vec![0u8; 512];
+        journal[..8].copy_from_slice(&JOURNAL_MAGIC);
+        put32(&mut journal, 8, records);
+        put32(&mut journal, 12, 0xabcd);
+        put32(&mut journal, 20, 512);
+        put32(&mut journal, 24, PAGE as u32);
+        for page in 1..=records {
+            let data: Vec<u8> = (0..PAGE).map(|i| (i as u32 * page) as u8).collect();
+            journal.extend_from_slice(&page.to_be_bytes());
+            journal.extend_from_slice(&data);
+            journal.extend_from_slice(&journal_checksum(&data, 0xabcd).to_be_bytes());
+        }
+        journal
+    }
+
+    fn carve(image: Vec<u8>) -> (Option<CarvedFile>, Vec<CarvedFile>, tempfile::TempDir) {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let evidence = MemorySource::new(image);
+        let ctx = ExtractionContext {
+            run_id: "run",
+            output_root: dir.path(),
+            evidence: &evidence,
+        };
+        let hit = NormalizedHit {
+            global_offset: 0,
+            file_type_id: "sqlite".to_string(),
+            pattern_id: "sqlite_header".to_string(),
+        };
+        take_companions();
+        let handler = SqliteCarveHandler::new("sqlite".to_string(), 0, 0);
+        let file = handler.process_hit(&hit, &ctx).expect("carve");
+        (file, take_companions(), dir)
+    }
 
     #[test]
     fn sqlite_page_sizes() {
@@ -162,4 +637,71 @@ mod tests {
         assert!(!is_valid_page_size(1000));
         assert!(!is_valid_page_size(128));
     }
+
+    #[test]
+    fn sizes_stale_headers_by_freelist_and_pointer_map() {
+        // The header count is current: freelist pages past it are ignored.
+        let mut db = database(4, 4, true);
+        put32(&mut db, 32, 3);
+        put32(&mut db, 36, 2);
+        put32(&mut db, 2 * PAGE + 4, 1);
+        put32(&mut db, 2 * PAGE + 8, 9);
+        let (file, _, _dir) = carve(db.clone());
+        assert_eq!(file.expect("carved").size, 4 * PAGE as u64);
+
+        // A stale count: the freelist leaf on page 9 ends the database.
+        put32(&mut db, 92, 3);
+        db.resize(12 * PAGE, 0);
+        let (file, _, _dir) = carve(db);
+        assert_eq!(file.expect("carved").size, 9 * PAGE as u64);
+        assert_eq!(take_declared_size().map(|d| d.source), Some("freelist"));
+
+        // Auto-vacuum: the pointer map on page 2 marks pages 3 to 7 in use.
+        let mut db = database(10, 2, false);
+        put32(&mut db, 52, 3);
+        for i in 0..5 {
+            db[PAGE + i * 5] = 1;
+        }
+        let (file, _, _dir) = carve(db);
+        assert_eq!(file.expect("carved").size, 7 * PAGE as u64);
+        assert_eq!(take_declared_size().map(|d| d.source), Some("ptrmap"));
+    }
+
+    #[test]
+    fn carves_wal_and_journal_stored_after_the_database() {
+        let mut image = database(3, 3, true);
+        image.extend_from_slice(&wal(2));
+        let mut corrupt = wal(1);
+        corrupt[40] ^= 0xff;
+        image.extend_from_slice(&corrupt);
+        let (file, companions, dir) = carve(image);
+        let file = file.expect("carved");
+        assert_eq!(companions.len(), 1);
+        let wal_file = &companions[0];
+        assert_eq!(wal_file.file_type, "sqlite_wal");
+        assert_eq!(wal_file.path, format!("{}-wal", file.path));
+        assert_eq!(wal_file.global_start, 3 * PAGE as u64);
+        assert_eq!(wal_file.size, (32 + 2 * (24 + PAGE)) as u64);
+        let carved = std::fs::read(dir.path().join(&wal_file.path)).expect("wal");
+        assert_eq!(carved, wal(2));
+
+        // A journal in the next sector after slack, with one bad record.
+        let mut image = database(2, 2, true);
+        image.extend_from_slice(&[0xee; 512]);
+        let mut records = journal(3);
+        let last = records.len() - 1;
+        records[last] ^= 1;
+        image.extend_from_slice(&records);
+        let (_, companions, _dir) = carve(image);
+        assert_eq!(companions.len(), 1);
+        assert_eq!(companions[0].file_type, "sqlite_journal");
+        assert_eq!(companions[0].global_start, 3 * PAGE as u64);
+        assert_eq!(companions[0].size, (512 + 2 * (PAGE + 8)) as u64);
+
+        // Nothing valid follows.
+        let mut image = database(2, 2, true);
+        image.extend_from_slice(&[0u8; 8192]);
+        let (_, companions, _dir) = carve(image);
+        assert!(companions.is_empty());
+    }
 }
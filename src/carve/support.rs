@@ -0,0 +1,557 @@
+//! Helpers shared by the built-in carve handlers: output naming, streaming
+//! copies with hashing, and the `CarvedFile` builder. Crate-private so they can
+//! change without touching the public [`CarveHandler`](super::CarveHandler)
+//! contract.
+//!
+//! Names derived from evidence (hive names, executable names, archive members)
+//! only reach the file system through [`sanitize_component`], and every carved
+//! path stays under the output root: [`confined_path`] refuses absolute paths
+//! and `..`, and [`create_output`] refuses to write through a symbolic link
+//! or into a special file planted in the output tree.
+
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Component, Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use super::{
+    CarveError, CarvedFile, ExtractionContext, Rejection, check_cancelled, note_rejection,
+    write_buffer,
+};
+use crate::evidence::EvidenceSource;
+
+pub(crate) fn output_path(
+    output_root: &Path,
+    file_type: &str,
+    extension: &str,
+    global_start: u64,
+) -> Result<(PathBuf, String), CarveError> {
+    tagged_output_path(output_root, file_type, None, extension, global_start)
+}
+
+/// [`output_path`] with `tag` (e.g. the hive a registry file belongs to)
+/// appended to the file name.
+pub(crate) fn tagged_output_path(
+    output_root: &Path,
+    file_type: &str,
+    tag: Option<&str>,
+    extension: &str,
+    global_start: u64,
+) -> Result<(PathBuf, String), CarveError> {
+    let safe_type = sanitize_component(file_type);
+    let safe_ext = sanitize_extension(extension);
+    let dir = output_root.join(&safe_type);
+    create_output_dir(&dir)?;
+    let mut base = format!("{}_{}", safe_type, format!("{:012X}", global_start));
+    if let Some(tag) = tag {
+        base = format!("{base}_{}", sanitize_component(tag));
+    }
+    let filename = if safe_ext.is_empty() {
+        base
+    } else {
+        format!("{base}.{safe_ext}")
+    };
+    let full_path = dir.join(&filename);
+    let rel_path = full_path
+        .strip_prefix(output_root)
+        .unwrap_or(&full_path)
+        .to_string_lossy()
+        .to_string();
+    Ok((full_path, rel_path))
+}
+
+/// `rel`, a carved path relative to `root` as handlers and metadata records
+/// hold it, joined to `root`. Absolute paths, drive prefixes, and `..` are
+/// refused so a hostile name cannot point outside the output tree.
+pub(crate) fn confined_path(root: &Path, rel: &str) -> Result<PathBuf, CarveError> {
+    let path = Path::new(rel);
+    let confined = !rel.is_empty()
+        && path
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+    if !confined {
+        return Err(CarveError::Invalid(format!(
+            "carved path escapes the output directory: {rel:?}"
+        )));
+    }
+    Ok(root.join(path))
+}
+
+/// Create the carved output `path`. The open itself is the check, so nothing
+/// planted between naming the file and writing it is written through: a
+/// symbolic link is never followed, and an existing file (a later
+/// `--append-to` pass rewrites the carved paths of earlier passes) is only
+/// truncated once its open handle shows a regular file, not a FIFO or device.
+pub(crate) fn create_output(path: &Path) -> std::io::Result<File> {
+    let refuse = |err: std::io::Error| {
+        std::io::Error::new(
+            err.kind(),
+            format!("refusing to write through {}: {err}", path.display()),
+        )
+    };
+    match open_output(path, true) {
+        Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {
+            let file = open_output(path, false).map_err(refuse)?;
+            if !file.metadata()?.is_file() {
+                return Err(refuse(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "not a regular file",
+                )));
+            }
+            file.set_len(0)?;
+            Ok(file)
+        }
+        other => other.map_err(refuse),
+    }
+}
+
+fn open_output(path: &Path, create_new: bool) -> std::io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(create_new);
+    // Opening a FIFO without a reader fails instead of blocking.
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.custom_flags(libc::O_NOFOLLOW | libc::O_NONBLOCK);
+    }
+    options.open(path)
+}
+
+thread_local! {
+    /// Type directories already created on this thread. Every carve asks for
+    /// its directory; on network file systems each `create_dir_all` is a
+    /// round trip, so each directory is created, and checked to be a real
+    /// directory rather than a symbolic link, once per thread and run.
+    static CREATED_DIRS: RefCell<HashSet<PathBuf>> = RefCell::new(HashSet::new());
+}
+
+/// Forget the directories created on this thread, so the next carve into
+/// each creates it again.
+pub(crate) fn forget_created_dirs() {
+    CREATED_DIRS.with(|created| created.borrow_mut().clear());
+}
+
+fn create_output_dir(dir: &Path) -> std::io::Result<()> {
+    CREATED_DIRS.with(|created| {
+        if created.borrow().contains(dir) {
+            return Ok(());
+        }
+        std::fs::create_dir_all(dir)?;
+        if !std::fs::symlink_metadata(dir)?.is_dir() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "refusing to write through {}: not a directory",
+                    dir.display()
+                ),
+            ));
+        }
+        created.borrow_mut().insert(dir.to_path_buf());
+        Ok(())
+    })
+}
+
+/// Buffered writer for a carved output file, sized by
+/// [`set_write_buffer`](super::set_write_buffer).
+pub(crate) fn output_writer(file: File) -> BufWriter<File> {
+    BufWriter::with_capacity(write_buffer(), file)
+}
+
+/// Move a carved file to the directory and extension of the type content
+/// classification settled on (`zip` to `docx`, `ole` to `xls`). The paths are
+/// left unchanged when they already match or the rename fails.
+pub(crate) fn relocate_classified(
+    output_root: &Path,
+    file_type: &str,
+    extension: &str,
+    global_start: u64,
+    full_path: &mut PathBuf,
+    rel_path: &mut String,
+) {
+    let Ok((new_path, new_rel)) = output_path(output_root, file_type, extension, global_start)
+    else {
+        return;
+    };
+    if new_path != *full_path && std::fs::rename(&*full_path, &new_path).is_ok() {
+        *full_path = new_path;
+        *rel_path = new_rel;
+    }
+}
+
+pub(crate) fn sanitize_component(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for ch in value.chars() {
+        if ch.is_ascii_alphanumeric() || ch == '_' || ch == '-' || ch == '.' {
+            out.push(ch);
+        } else {
+            out.push('_');
+        }
+    }
+    while out.contains("..") {
+        out = out.replace("..", "_");
+    }
+    let trimmed = out.trim_matches('.').to_string();
+    if trimmed.is_empty() {
+        "unknown".to_string()
+    } else {
+        trimmed
+    }
+}
+
+pub(crate) fn sanitize_extension(ext: &str) -> String {
+    sanitize_component(ext)
+        .trim_start_matches('.')
+        .to_ascii_lowercase()
+}
+
+/// Helper to build a CarvedFile result, reducing boilerplate in handlers
+pub(crate) fn build_carved_file(
+    run_id: &str,
+    file_type: &str,
+    extension: &str,
+    rel_path: String,
+    global_start: u64,
+    size: u64,
+    md5_hex: String,
+    sha256_hex: String,
+    validated: bool,
+    truncated: bool,
+    errors: Vec<String>,
+    pattern_id: &str,
+) -> CarvedFile {
+    let global_end = if size == 0 {
+        global_start
+    } else {
+        global_start + size - 1
+    };
+
+    CarvedFile {
+        run_id: run_id.to_string(),
+        file_type: file_type.to_string(),
+        signature_type: file_type.to_string(),
+        path: rel_path,
+        extension: extension.to_string(),
+        global_start,
+        global_end,
+        size,
+        md5: Some(md5_hex),
+        sha256: Some(sha256_hex),
+        validated,
+        truncated,
+        errors,
+        pattern_id: Some(pattern_id.to_string()),
+    }
+}
+
+/// Check if carved size meets minimum requirement, delete file if not
+pub(crate) fn check_min_size(full_path: &Path, size: u64, min_size: u64) -> bool {
+    if size < min_size {
+        note_rejection(Rejection::MinSize { size, min_size });
+        let _ = std::fs::remove_file(full_path);
+        false
+    } else {
+        true
+    }
+}
+
+pub(crate) struct CarveStream<'a> {
+    evidence: &'a dyn EvidenceSource,
+    offset: u64,
+    max_size: u64,
+    written: u64,
+    writer: BufWriter<File>,
+    md5: md5::Context,
+    sha256: Sha256,
+}
+
+impl<'a> CarveStream<'a> {
+    pub(crate) fn new(
+        evidence: &'a dyn EvidenceSource,
+        offset: u64,
+        max_size: u64,
+        writer: File,
+    ) -> Self {
+        Self {
+            evidence,
+            offset,
+            max_size,
+            written: 0,
+            writer: output_writer(writer),
+            md5: md5::Context::new(),
+            sha256: Sha256::new(),
+        }
+    }
+
+    pub(crate) fn read_exact(&mut self, len: usize) -> Result<Vec<u8>, CarveError> {
+        if self.max_size > 0 && self.written.saturating_add(len as u64) > self.max_size {
+            return Err(CarveError::Truncated);
+        }
+
+        let mut buf = vec![0u8; len];
+        let mut read = 0usize;
+        while read < len {
+            let n = self
+                .evidence
+                .read_at(self.offset, &mut buf[read..])
+                .map_err(|e| CarveError::Evidence(e.to_string()))?;
+            if n == 0 {
+                return Err(CarveError::Eof);
+            }
+            self.write_bytes(&buf[read..read + n])?;
+            read += n;
+        }
+
+        Ok(buf)
+    }
+
+    pub(crate) fn write_bytes(&mut self, buf: &[u8]) -> Result<(), CarveError> {
+        check_cancelled()?;
+        if self.max_size > 0 && self.written.saturating_add(buf.len() as u64) > self.max_size {
+            return Err(CarveError::Truncated);
+        }
+        self.writer.write_all(buf)?;
+        self.md5.consume(buf);
+        self.sha256.update(buf);
+        self.offset = self.offset.saturating_add(buf.len() as u64);
+        self.written = self.written.saturating_add(buf.len() as u64);
+        Ok(())
+    }
+
+    pub(crate) fn finish(mut self) -> Result<(u64, String, String), CarveError> {
+        self.writer.flush()?;
+        let md5 = format!("{:x}", self.md5.compute());
+        let sha256 = hex::encode(self.sha256.finalize());
+        Ok((self.written, md5, sha256))
+    }
+
+    /// Change the size limit, e.g. once the format's declared size is known.
+    pub(crate) fn set_max_size(&mut self, max_size: u64) {
+        self.max_size = max_size;
+    }
+
+    /// Get the number of bytes written so far
+    pub(crate) fn bytes_written(&self) -> u64 {
+        self.written
+    }
+}
+
+pub(crate) fn write_range(
+    ctx: &ExtractionContext,
+    start: u64,
+    end: u64,
+    file: &mut File,
+    md5: &mut md5::Context,
+    sha256: &mut Sha256,
+) -> Result<(u64, bool), CarveError> {
+    let mut offset = start;
+    let mut remaining = end.saturating_sub(start);
+    let mut bytes_written = 0u64;
+    let buf_size = 64 * 1024;
+    if remaining > buf_size as u64 {
+        ctx.evidence.prefetch(start, remaining);
+    }
+
+    while remaining > 0 {
+        ctx.check_cancelled()?;
+        let read_len = remaining.min(buf_size as u64) as usize;
+        let mut buf = vec![0u8; read_len];
+        let n = ctx
+            .evidence
+            .read_at(offset, &mut buf)
+            .map_err(|e| CarveError::Evidence(e.to_string()))?;
+        if n == 0 {
+            return Ok((bytes_written, true));
+        }
+        buf.truncate(n);
+        file.write_all(&buf)?;
+        md5.consume(&buf);
+        sha256.update(&buf);
+        bytes_written = bytes_written.saturating_add(buf.len() as u64);
+        offset = offset.saturating_add(buf.len() as u64);
+        remaining = remaining.saturating_sub(buf.len() as u64);
+        if n < read_len {
+            return Ok((bytes_written, true));
+        }
+    }
+
+    Ok((bytes_written, false))
+}
+
+/// Fill `buf` from the evidence at `offset`, returning the bytes read; fewer
+/// only at the end of the evidence or on a read error. The reader callback
+/// of parsers that walk tables spread over a file (`parsers::elf`,
+/// `parsers::macho`).
+pub(crate) fn read_evidence(ctx: &ExtractionContext, offset: u64, buf: &mut [u8]) -> usize {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match ctx
+            .evidence
+            .read_at(offset.saturating_add(filled as u64), &mut buf[filled..])
+        {
+            Ok(0) | Err(_) => break,
+            Ok(n) => filled += n,
+        }
+    }
+    filled
+}
+
+/// CRC-32 (IEEE 802.3), as xz, gzip, and EVTX use it.
+pub(crate) fn crc32(bytes: &[u8]) -> u32 {
+    crc32_update(0, bytes)
+}
+
+/// CRC-32 of the bytes hashed into `crc` followed by `bytes`; start from 0.
+pub(crate) fn crc32_update(crc: u32, bytes: &[u8]) -> u32 {
+    let mut crc = !crc;
+    for &b in bytes {
+        crc = CRC32_TABLE[((crc ^ b as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320u32 & mask);
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Carve `image` with `handler` from a hit at `offset`. The image and the
+/// `out/` output root live in the returned directory.
+#[cfg(test)]
+pub(crate) fn carve_image(
+    handler: &dyn super::CarveHandler,
+    image: &[u8],
+    offset: u64,
+) -> (Option<CarvedFile>, tempfile::TempDir) {
+    let temp_dir = tempfile::tempdir().expect("tempdir");
+    let input_path = temp_dir.path().join("image.bin");
+    std::fs::write(&input_path, image).expect("write image");
+    let output_root = temp_dir.path().join("out");
+    let evidence = crate::evidence::RawFileSource::open(&input_path).expect("evidence");
+    let ctx = ExtractionContext {
+        run_id: "test",
+        output_root: &output_root,
+        evidence: &evidence,
+    };
+    let hit = crate::scanner::NormalizedHit {
+        global_offset: offset,
+        file_type_id: handler.file_type().to_string(),
+        pattern_id: format!("{}_header", handler.file_type()),
+    };
+    let carved = handler.process_hit(&hit, &ctx).expect("carve");
+    (carved, temp_dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        confined_path, create_output, output_path, sanitize_component, sanitize_extension,
+        tagged_output_path,
+    };
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    #[test]
+    fn creates_a_removed_output_root_again_once_forgotten() {
+        let dir = tempdir().expect("tempdir");
+        let root = dir.path().join("out");
+        let (full, _) = output_path(&root, "jpeg", "jpg", 0).expect("output path");
+        std::fs::write(&full, b"x").expect("write");
+        std::fs::remove_dir_all(&root).expect("remove root");
+        super::forget_created_dirs();
+        let (full, _) = output_path(&root, "jpeg", "jpg", 0).expect("output path");
+        std::fs::write(&full, b"x").expect("write after removal");
+    }
+
+    #[test]
+    fn sanitizes_output_path_components() {
+        let dir = tempdir().expect("tempdir");
+        let (full, rel) =
+            output_path(dir.path(), "../weird", "../JPG", 0x1234).expect("output path");
+        assert!(full.starts_with(dir.path()));
+        assert!(!rel.contains(".."));
+        assert!(sanitize_component("../weird").contains("weird"));
+    }
+
+    #[test]
+    fn confines_hostile_names_to_the_output_root() {
+        let dir = tempdir().expect("tempdir");
+        for tag in [
+            "../../etc/passwd",
+            "/etc/passwd",
+            "a/../../b",
+            "..\\..\\win",
+            "\0x",
+            "..",
+        ] {
+            let (full, rel) =
+                tagged_output_path(dir.path(), "regf", Some(tag), "dat", 0).expect("output path");
+            assert_eq!(full.parent(), Some(dir.path().join("regf").as_path()));
+            assert!(confined_path(dir.path(), &rel).is_ok(), "{rel}");
+        }
+        for rel in ["", "../x.jpg", "jpeg/../../x.jpg", "/etc/passwd", "jpeg/.."] {
+            assert!(confined_path(dir.path(), rel).is_err(), "{rel}");
+        }
+        assert_eq!(
+            confined_path(dir.path(), "jpeg/./a.jpg").expect("confined"),
+            dir.path().join("jpeg/./a.jpg")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn refuses_symlinks_planted_in_the_output_tree() {
+        let dir = tempdir().expect("tempdir");
+        let outside = tempdir().expect("outside");
+        let root = dir.path().join("carved");
+        std::fs::create_dir_all(&root).expect("root");
+        std::os::unix::fs::symlink(outside.path(), root.join("png")).expect("dir link");
+        assert!(output_path(&root, "png", "png", 0).is_err());
+
+        // Planted after the path is named and before the carve opens it.
+        let (full, _) = output_path(&root, "gif", "gif", 0).expect("output path");
+        let victim = outside.path().join("victim");
+        std::fs::write(&victim, b"keep").expect("victim");
+        std::os::unix::fs::symlink(&victim, &full).expect("file link");
+        assert!(create_output(&full).is_err());
+        assert_eq!(std::fs::read(&victim).expect("victim"), b"keep");
+
+        let (fifo, _) = output_path(&root, "gif", "gif", 1).expect("output path");
+        let fifo_c = std::ffi::CString::new(fifo.as_os_str().as_encoded_bytes()).expect("path");
+        // SAFETY: `fifo_c` is a NUL-terminated path that outlives the call.
+        assert_eq!(unsafe { libc::mkfifo(fifo_c.as_ptr(), 0o600) }, 0);
+        assert!(create_output(&fifo).is_err());
+    }
+
+    #[test]
+    fn rewrites_regular_files_of_earlier_passes() {
+        let dir = tempdir().expect("tempdir");
+        let (full, _) = output_path(dir.path(), "gif", "gif", 0).expect("output path");
+        std::fs::write(&full, b"earlier pass").expect("earlier");
+        create_output(&full)
+            .expect("rewrite")
+            .write_all(b"new")
+            .expect("write");
+        assert_eq!(std::fs::read(&full).expect("read"), b"new");
+    }
+
+    #[test]
+    fn sanitizes_extension() {
+        assert_eq!(sanitize_extension(".JPG"), "jpg");
+        assert_eq!(sanitize_extension("..bad"), "_bad");
+    }
+}
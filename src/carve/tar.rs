@@ -3,10 +3,9 @@
 //! TAR archives consist of 512-byte headers followed by file data.
 //! The archive ends with two consecutive zero blocks.
 
-use std::fs::File;
-
 use crate::carve::{
-    CarveError, CarveHandler, CarveStream, CarvedFile, ExtractionContext, output_path,
+    CarveError, CarveHandler, CarveStream, CarvedFile, ExtractionContext, Rejection, create_output,
+    note_rejection, output_path,
 };
 use crate::scanner::NormalizedHit;
 
@@ -59,7 +58,7 @@ impl CarveHandler for TarCarveHandler {
             &self.extension,
             start_offset,
         )?;
-        let file = File::create(&full_path)?;
+        let file = create_output(&full_path)?;
         let mut stream = CarveStream::new(ctx.evidence, start_offset, self.max_size, file);
 
         let mut validated = false;
@@ -119,6 +118,11 @@ impl CarveHandler for TarCarveHandler {
         let (size, md5_hex, sha256_hex) = stream.finish()?;
 
         if size < self.min_size {
+            note_rejection(Rejection::MinSize {
+                size,
+
+                min_size: self.min_size,
+            });
             let _ = std::fs::remove_file(&full_path);
             return Ok(None);
         }
@@ -139,6 +143,7 @@ impl CarveHandler for TarCarveHandler {
         Ok(Some(CarvedFile {
             run_id: ctx.run_id.to_string(),
             file_type: self.file_type().to_string(),
+            signature_type: self.file_type().to_string(),
             path: rel_path,
             extension: self.extension.clone(),
             global_start: start_offset,
@@ -0,0 +1,189 @@
+//! Windows thumbnail cache (`thumbcache_*.db`) carving handler.
+//!
+//! The `CMMM` file header is checked and the entries are walked from the
+//! first entry (`parsers::thumbcache`). The cache ends at the first free byte
+//! the header records; a walk that stops before it leaves the carve
+//! unvalidated. The `thumbcache_images` post-processor writes each entry's
+//! image as its own file.
+
+use sha2::{Digest, Sha256};
+
+use crate::carve::{
+    CarveError, CarveHandler, CarvedFile, DeclaredSize, ExtractionContext, Rejection,
+    create_output, note_declared_size, note_rejection, output_path, read_evidence, write_range,
+};
+use crate::parsers::thumbcache::{self, ThumbcacheError};
+use crate::scanner::NormalizedHit;
+
+pub struct ThumbcacheCarveHandler {
+    extension: String,
+    min_size: u64,
+    max_size: u64,
+}
+
+impl ThumbcacheCarveHandler {
+    pub fn new(extension: String, min_size: u64, max_size: u64) -> Self {
+        Self {
+            extension,
+            min_size,
+            max_size,
+        }
+    }
+}
+
+impl CarveHandler for ThumbcacheCarveHandler {
+    fn file_type(&self) -> &str {
+        "thumbcache"
+    }
+
+    fn extension(&self) -> &str {
+        &self.extension
+    }
+
+    fn process_hit(
+        &self,
+        hit: &NormalizedHit,
+        ctx: &ExtractionContext,
+    ) -> Result<Option<CarvedFile>, CarveError> {
+        let mut read = |offset: u64, buf: &mut [u8]| {
+            read_evidence(ctx, hit.global_offset.saturating_add(offset), buf)
+        };
+        let cache = match thumbcache::walk(&mut read) {
+            Ok(cache) => cache,
+            Err(ThumbcacheError::Invalid(reason)) => {
+                note_rejection(Rejection::Invalid(reason));
+                return Ok(None);
+            }
+            Err(ThumbcacheError::Incomplete) => {
+                note_rejection(Rejection::Invalid(
+                    "evidence ended inside the thumbcache header",
+                ));
+                return Ok(None);
+            }
+        };
+        if cache.entries.is_empty() {
+            note_rejection(Rejection::Invalid("no thumbcache entry after the header"));
+            return Ok(None);
+        }
+
+        let declared = cache.size();
+        note_declared_size(DeclaredSize {
+            size: declared,
+            source: "cache_header",
+        });
+        let mut errors = Vec::new();
+        if !cache.complete() {
+            errors.push(format!(
+                "entry walk stopped at {} before the first free byte",
+                cache.end
+            ));
+        }
+        let mut truncated = false;
+        let mut size = declared;
+        if self.max_size > 0 && size > self.max_size {
+            size = self.max_size;
+            truncated = true;
+            errors.push("max_size reached before thumbcache end".to_string());
+        }
+
+        let (full_path, rel_path) = output_path(
+            ctx.output_root,
+            self.file_type(),
+            &self.extension,
+            hit.global_offset,
+        )?;
+        let mut file = create_output(&full_path)?;
+        let mut md5 = md5::Context::new();
+        let mut sha256 = Sha256::new();
+        let (written, eof_truncated) = write_range(
+            ctx,
+            hit.global_offset,
+            hit.global_offset + size,
+            &mut file,
+            &mut md5,
+            &mut sha256,
+        )?;
+        if eof_truncated {
+            truncated = true;
+            errors.push("eof before thumbcache end".to_string());
+        }
+
+        if written < self.min_size {
+            note_rejection(Rejection::MinSize {
+                size: written,
+                min_size: self.min_size,
+            });
+            let _ = std::fs::remove_file(&full_path);
+            return Ok(None);
+        }
+
+        Ok(Some(CarvedFile {
+            run_id: ctx.run_id.to_string(),
+            file_type: self.file_type().to_string(),
+            signature_type: self.file_type().to_string(),
+            path: rel_path,
+            extension: self.extension.clone(),
+            global_start: hit.global_offset,
+            global_end: hit.global_offset + written - 1,
+            size: written,
+            md5: Some(format!("{:x}", md5.compute())),
+            sha256: Some(hex::encode(sha256.finalize())),
+            validated: errors.is_empty(),
+            truncated,
+            errors,
+            pattern_id: Some(hit.pattern_id.clone()),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::carve::support::carve_image;
+    use crate::parsers::thumbcache::testing::thumbcache_bytes;
+
+    #[test]
+    fn carves_cache_to_the_first_free_byte() {
+        let cache = thumbcache_bytes(32);
+        let mut image = vec![0u8; 0x200];
+        image.extend_from_slice(&cache);
+        image.extend_from_slice(&[0x5A; 0x400]);
+        let (carved, _dir) = carve_image(
+            &ThumbcacheCarveHandler::new("db".to_string(), 0, 0),
+            &image,
+            0x200,
+        );
+        let carved = carved.expect("carved");
+        assert_eq!(carved.size, cache.len() as u64);
+        assert!(carved.validated, "{:?}", carved.errors);
+
+        let (carved, _dir) = carve_image(
+            &ThumbcacheCarveHandler::new("db".to_string(), 0, 0),
+            &cache[..cache.len() - 8],
+            0,
+        );
+        let carved = carved.expect("cut cache");
+        assert!(carved.truncated && !carved.validated);
+    }
+
+    #[test]
+    fn rejects_entry_signatures_and_empty_caches() {
+        let cache = thumbcache_bytes(21);
+        // An entry signature is followed by its size, not a known version.
+        let (carved, _dir) = carve_image(
+            &ThumbcacheCarveHandler::new("db".to_string(), 0, 0),
+            &cache,
+            24,
+        );
+        assert!(carved.is_none());
+
+        let mut empty = cache[..24].to_vec();
+        empty[16..20].copy_from_slice(&24u32.to_le_bytes());
+        let (carved, _dir) = carve_image(
+            &ThumbcacheCarveHandler::new("db".to_string(), 0, 0),
+            &empty,
+            0,
+        );
+        assert!(carved.is_none());
+    }
+}
@@ -0,0 +1,181 @@
+use std::io::Write;
+
+use sha2::{Digest, Sha256};
+
+use crate::carve::{
+    CarveError, CarveHandler, CarvedFile, ExtractionContext, Rejection, build_carved_file,
+    check_min_size, create_output, note_rejection, output_path,
+};
+use crate::parsers::p2p::{self, BencodeError, Torrent};
+use crate::scanner::NormalizedHit;
+
+/// First read; most metainfo files are a few KiB.
+const INITIAL_READ: usize = 64 * 1024;
+/// Read limit when the type has no `max_size`.
+const UNBOUNDED_LIMIT: u64 = 64 * 1024 * 1024;
+
+/// BitTorrent metainfo (`.torrent`) files. The extent is the top-level
+/// bencoded dictionary, read in doubling windows until it closes; the file is
+/// validated when the dictionary holds a usable `info` dictionary.
+pub struct TorrentCarveHandler {
+    extension: String,
+    min_size: u64,
+    max_size: u64,
+}
+
+impl TorrentCarveHandler {
+    pub fn new(extension: String, min_size: u64, max_size: u64) -> Self {
+        Self {
+            extension,
+            min_size,
+            max_size,
+        }
+    }
+}
+
+impl CarveHandler for TorrentCarveHandler {
+    fn file_type(&self) -> &str {
+        "torrent"
+    }
+
+    fn extension(&self) -> &str {
+        &self.extension
+    }
+
+    fn process_hit(
+        &self,
+        hit: &NormalizedHit,
+        ctx: &ExtractionContext,
+    ) -> Result<Option<CarvedFile>, CarveError> {
+        let limit = if self.max_size > 0 {
+            self.max_size
+        } else {
+            UNBOUNDED_LIMIT
+        } as usize;
+        let mut buf = Vec::new();
+        let mut want = INITIAL_READ.min(limit);
+        let (len, complete) = loop {
+            let start = buf.len();
+            buf.resize(want, 0);
+            let mut filled = start;
+            while filled < want {
+                let n = ctx
+                    .evidence
+                    .read_at(hit.global_offset + filled as u64, &mut buf[filled..])
+                    .map_err(|e| CarveError::Evidence(e.to_string()))?;
+                if n == 0 {
+                    break;
+                }
+                filled += n;
+            }
+            buf.truncate(filled);
+            match p2p::decode(&buf) {
+                Ok(root) => break (root.span.end, true),
+                Err(BencodeError::Invalid) => {
+                    note_rejection(Rejection::Invalid("malformed bencode"));
+                    return Ok(None);
+                }
+                Err(BencodeError::Incomplete) if filled < want || want >= limit => {
+                    break (buf.len(), false);
+                }
+                Err(BencodeError::Incomplete) => want = want.saturating_mul(2).min(limit),
+            }
+        };
+        if complete && Torrent::parse(&buf[..len]).is_none() {
+            note_rejection(Rejection::Invalid("no usable info dictionary"));
+            return Ok(None);
+        }
+        let data = &buf[..len];
+
+        let (full_path, rel_path) = output_path(
+            ctx.output_root,
+            self.file_type(),
+            &self.extension,
+            hit.global_offset,
+        )?;
+        let mut file = create_output(&full_path)?;
+        file.write_all(data)?;
+        file.flush()?;
+        let size = data.len() as u64;
+        if !check_min_size(&full_path, size, self.min_size) {
+            return Ok(None);
+        }
+
+        let mut errors = Vec::new();
+        if !complete {
+            errors.push(if size >= limit as u64 {
+                "max_size reached".to_string()
+            } else {
+                "evidence ended before dictionary end".to_string()
+            });
+        }
+
+        Ok(Some(build_carved_file(
+            ctx.run_id,
+            self.file_type(),
+            &self.extension,
+            rel_path,
+            hit.global_offset,
+            size,
+            format!("{:x}", md5::compute(data)),
+            hex::encode(Sha256::digest(data)),
+            complete,
+            !complete,
+            errors,
+            &hit.pattern_id,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evidence::RawFileSource;
+    use crate::parsers::p2p::torrent_bytes;
+
+    #[test]
+    fn carves_metainfo_dictionary() {
+        let torrent = torrent_bytes();
+        let mut image = vec![0u8; 512];
+        image.extend_from_slice(&torrent);
+        image.extend_from_slice(b"d8:announce3:abce");
+        image.extend_from_slice(&[0xAAu8; 512]);
+
+        let temp_dir = tempfile::tempdir().expect("tempdir");
+        let input_path = temp_dir.path().join("image.bin");
+        std::fs::write(&input_path, &image).expect("write image");
+        let output_root = temp_dir.path().join("out");
+        let evidence = RawFileSource::open(&input_path).expect("evidence");
+        let ctx = ExtractionContext {
+            run_id: "test",
+            output_root: &output_root,
+            evidence: &evidence,
+        };
+        let handler = TorrentCarveHandler::new("torrent".to_string(), 0, 4096);
+        let hit = NormalizedHit {
+            global_offset: 512,
+            file_type_id: "torrent".to_string(),
+            pattern_id: "torrent_announce".to_string(),
+        };
+        let carved = handler
+            .process_hit(&hit, &ctx)
+            .expect("carve")
+            .expect("carved");
+        assert_eq!(carved.size, torrent.len() as u64);
+        assert!(carved.validated);
+        let written = std::fs::read(output_root.join(&carved.path)).expect("read carved");
+        assert_eq!(written, torrent);
+
+        // A dictionary without `info` is not a torrent.
+        let no_info = NormalizedHit {
+            global_offset: 512 + torrent.len() as u64,
+            ..hit
+        };
+        assert!(
+            handler
+                .process_hit(&no_info, &ctx)
+                .expect("carve")
+                .is_none()
+        );
+    }
+}
@@ -1,15 +1,27 @@
 //! WAV (Waveform Audio) file carving handler.
 //!
-//! WAV files use the RIFF container format with "WAVE" form type.
-//! The file size is embedded in the RIFF header (bytes 4-7).
+//! WAV files use the RIFF container format with "WAVE" form type. The file
+//! size is embedded in the RIFF header (bytes 4-7); RF64 files, for
+//! recordings past 4 GiB, give it in the `ds64` chunk instead. The chunks
+//! are walked to the declared end to check for `fmt ` and `data`. A recorder
+//! that stopped before writing the sizes leaves the RIFF size at 0 or
+//! 0xFFFFFFFF; such files are sized by the chunk walk when the `data` chunk
+//! size was written.
 
-use std::fs::File;
+use sha2::{Digest, Sha256};
 
 use crate::carve::{
-    CarveError, CarveHandler, CarveStream, CarvedFile, ExtractionContext, output_path, riff,
+    CarveError, CarveHandler, CarvedFile, DeclaredSize, ExtractionContext, Rejection,
+    create_output, note_declared_size, note_rejection, output_path, read_evidence, riff,
+    write_range,
 };
 use crate::scanner::NormalizedHit;
 
+const RIFF_HEADER_LEN: u64 = 12;
+const CHUNK_HEADER_LEN: u64 = 8;
+/// RIFF and chunk sizes a streaming recorder leaves unset.
+const PLACEHOLDER_SIZES: [u32; 2] = [0, u32::MAX];
+
 pub struct WavCarveHandler {
     extension: String,
     min_size: u64,
@@ -26,6 +38,82 @@ impl WavCarveHandler {
     }
 }
 
+/// What the chunk walk found.
+#[derive(Debug, Default)]
+struct Chunks {
+    /// End of the last whole chunk.
+    end: u64,
+    fmt_ok: bool,
+    fmt_seen: bool,
+    data_seen: bool,
+    /// A `data` chunk whose size was never written.
+    data_unsized: bool,
+}
+
+/// Walk the chunks after the RIFF header up to `end` (or, without a
+/// declared size, as far as they check out). `data_size` replaces a `data`
+/// size of 0xFFFFFFFF in RF64 files.
+fn walk_chunks(
+    read: &mut dyn FnMut(u64, &mut [u8]) -> usize,
+    end: Option<u64>,
+    data_size: Option<u64>,
+) -> Chunks {
+    let mut chunks = Chunks {
+        end: RIFF_HEADER_LEN,
+        ..Chunks::default()
+    };
+    let mut offset = RIFF_HEADER_LEN;
+    while end.is_none_or(|end| offset + CHUNK_HEADER_LEN <= end) {
+        let mut header = [0u8; CHUNK_HEADER_LEN as usize];
+        if read(offset, &mut header) < header.len()
+            || !header[..4]
+                .iter()
+                .all(|byte| byte.is_ascii_graphic() || *byte == b' ')
+        {
+            break;
+        }
+        let id = &header[..4];
+        let raw_size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        let mut size = u64::from(raw_size);
+        if id == b"data" {
+            chunks.data_seen = true;
+            match data_size {
+                Some(ds64) if raw_size == u32::MAX => size = ds64,
+                None if end.is_none() && PLACEHOLDER_SIZES.contains(&raw_size) => {
+                    chunks.data_unsized = true;
+                    break;
+                }
+                _ => {}
+            }
+        } else if id == b"fmt " {
+            chunks.fmt_seen = true;
+            let mut fmt = [0u8; 16];
+            if size >= 16 && read(offset + CHUNK_HEADER_LEN, &mut fmt) == fmt.len() {
+                let channels = u16::from_le_bytes([fmt[2], fmt[3]]);
+                let sample_rate = u32::from_le_bytes([fmt[4], fmt[5], fmt[6], fmt[7]]);
+                let block_align = u16::from_le_bytes([fmt[12], fmt[13]]);
+                chunks.fmt_ok = channels > 0 && sample_rate > 0 && block_align > 0;
+            }
+        }
+        // Chunks are padded to an even length.
+        let next = offset
+            .saturating_add(CHUNK_HEADER_LEN)
+            .saturating_add(size)
+            .saturating_add(size & 1);
+        if end.is_some_and(|end| next > end + (size & 1)) {
+            break;
+        }
+        offset = next;
+        chunks.end = offset;
+        // Without a declared end, bytes after the samples are not trusted
+        // to be chunks; recorders write `data` last.
+        if end.is_none() && id == b"data" {
+            break;
+        }
+    }
+    chunks
+}
+
 impl CarveHandler for WavCarveHandler {
     fn file_type(&self) -> &str {
         "wav"
@@ -40,104 +128,142 @@ impl CarveHandler for WavCarveHandler {
         hit: &NormalizedHit,
         ctx: &ExtractionContext,
     ) -> Result<Option<CarvedFile>, CarveError> {
-        let (full_path, rel_path) = output_path(
-            ctx.output_root,
-            self.file_type(),
-            &self.extension,
-            hit.global_offset,
-        )?;
-        let file = File::create(&full_path)?;
-        let mut stream = CarveStream::new(ctx.evidence, hit.global_offset, self.max_size, file);
-
-        let mut validated = false;
-        let mut truncated = false;
-        let mut errors = Vec::new();
-
-        let result: Result<u64, CarveError> = (|| {
-            // Read RIFF header (12 bytes)
-            let header = stream.read_exact(12)?;
-
-            // Parse and validate RIFF structure
-            let (form_type, total_size) = riff::parse_riff_header(&header)?;
-
-            // Verify this is a WAVE file
-            if &form_type != riff::WAVE_FORM {
-                return Err(CarveError::Invalid(format!(
-                    "wav form type mismatch: expected WAVE, got {:?}",
-                    String::from_utf8_lossy(&form_type)
-                )));
-            }
-
-            // Sanity check on size
-            if total_size < 12 {
-                return Err(CarveError::Invalid("wav size too small".to_string()));
+        let mut read = |offset: u64, buf: &mut [u8]| {
+            read_evidence(ctx, hit.global_offset.saturating_add(offset), buf)
+        };
+        let mut header = [0u8; RIFF_HEADER_LEN as usize];
+        if read(0, &mut header) < header.len() {
+            note_rejection(Rejection::Invalid("evidence ended inside the RIFF header"));
+            return Ok(None);
+        }
+        let rf64 = &header[0..4] == riff::RF64_MAGIC;
+        if rf64 {
+            header[0..4].copy_from_slice(riff::RIFF_MAGIC);
+        }
+        let (form_type, riff_size) = match riff::parse_riff_header(&header) {
+            Ok(parsed) => parsed,
+            Err(_) => {
+                note_rejection(Rejection::Invalid("riff magic mismatch"));
+                return Ok(None);
             }
+        };
+        // Verify this is a WAVE file
+        if &form_type != riff::WAVE_FORM {
+            note_rejection(Rejection::Invalid("wav form type mismatch"));
+            return Ok(None);
+        }
 
-            // Apply max_size limit
-            let max_size = if self.max_size > 0 {
-                self.max_size
-            } else {
-                total_size
-            };
-            let target_size = total_size.min(max_size);
-
-            // Read remaining data
-            let remaining = target_size.saturating_sub(12);
-            if remaining > 0 {
-                stream.read_exact(remaining as usize)?;
+        // Declared end and the `data` size RF64 files keep in `ds64`.
+        let raw_size = (riff_size - 8) as u32;
+        let (declared, data_size) = if rf64 {
+            let mut ds64 = [0u8; 24];
+            if read(RIFF_HEADER_LEN, &mut ds64) < ds64.len() || &ds64[..4] != b"ds64" {
+                note_rejection(Rejection::Invalid("RF64 file without a ds64 chunk"));
+                return Ok(None);
             }
+            let riff_size = u64::from_le_bytes(ds64[8..16].try_into().unwrap_or_default());
+            let data_size = u64::from_le_bytes(ds64[16..24].try_into().unwrap_or_default());
+            (Some((riff_size.saturating_add(8), "ds64")), Some(data_size))
+        } else if PLACEHOLDER_SIZES.contains(&raw_size) {
+            (None, None)
+        } else {
+            (Some((riff_size, "riff_header")), None)
+        };
+        let chunks = walk_chunks(&mut read, declared.map(|(end, _)| end), data_size);
 
-            validated = true;
-            Ok(target_size)
-        })();
-
-        if let Err(err) = result {
-            match err {
-                CarveError::Truncated | CarveError::Eof => {
-                    truncated = true;
-                    errors.push(err.to_string());
-                }
-                CarveError::Invalid(_) => {
-                    let _ = std::fs::remove_file(&full_path);
+        let mut errors = Vec::new();
+        let total_size = match declared {
+            Some((end, source)) => {
+                if end < RIFF_HEADER_LEN {
+                    note_rejection(Rejection::Invalid("wav size too small"));
                     return Ok(None);
                 }
-                other => return Err(other),
+                note_declared_size(DeclaredSize { size: end, source });
+                if chunks.end < end {
+                    errors.push(format!(
+                        "chunk walk stopped at {} before the RIFF end",
+                        chunks.end
+                    ));
+                }
+                end
+            }
+            None if chunks.data_unsized || !chunks.data_seen => {
+                note_rejection(Rejection::Invalid("wav sizes were never written"));
+                return Ok(None);
             }
+            None => {
+                errors.push("RIFF size was never written; sized by the chunk walk".to_string());
+                chunks.end
+            }
+        };
+        if !chunks.fmt_seen {
+            errors.push("no fmt chunk".to_string());
+        } else if !chunks.fmt_ok {
+            errors.push("fmt chunk fields out of range".to_string());
+        }
+        if !chunks.data_seen {
+            errors.push("no data chunk".to_string());
+        }
+
+        // Apply max_size limit
+        let mut truncated = false;
+        let mut size = total_size;
+        if self.max_size > 0 && size > self.max_size {
+            size = self.max_size;
+            truncated = true;
+            errors.push("max_size reached before wav end".to_string());
         }
 
-        let (size, md5_hex, sha256_hex) = stream.finish()?;
+        let (full_path, rel_path) = output_path(
+            ctx.output_root,
+            self.file_type(),
+            &self.extension,
+            hit.global_offset,
+        )?;
+        let mut file = create_output(&full_path)?;
+        let mut md5 = md5::Context::new();
+        let mut sha256 = Sha256::new();
+        let (written, eof_truncated) = write_range(
+            ctx,
+            hit.global_offset,
+            hit.global_offset + size,
+            &mut file,
+            &mut md5,
+            &mut sha256,
+        )?;
+        if eof_truncated {
+            truncated = true;
+            errors.push("eof before wav end".to_string());
+        }
 
         // Check minimum size
-        if size < self.min_size {
+        if written < self.min_size {
+            note_rejection(Rejection::MinSize {
+                size: written,
+                min_size: self.min_size,
+            });
             let _ = std::fs::remove_file(&full_path);
             return Ok(None);
         }
 
-        // Check if we hit max_size
-        if self.max_size > 0 && size >= self.max_size {
-            truncated = true;
-            if !errors.iter().any(|e| e.contains("max_size")) {
-                errors.push("max_size reached".to_string());
-            }
-        }
-
-        let global_end = if size == 0 {
+        let global_end = if written == 0 {
             hit.global_offset
         } else {
-            hit.global_offset + size - 1
+            hit.global_offset + written - 1
         };
 
         Ok(Some(CarvedFile {
             run_id: ctx.run_id.to_string(),
             file_type: self.file_type().to_string(),
+            signature_type: self.file_type().to_string(),
             path: rel_path,
             extension: self.extension.clone(),
             global_start: hit.global_offset,
             global_end,
-            size,
-            md5: Some(md5_hex),
-            sha256: Some(sha256_hex),
-            validated,
+            size: written,
+            md5: Some(format!("{:x}", md5.compute())),
+            sha256: Some(hex::encode(sha256.finalize())),
+            validated: errors.is_empty(),
             truncated,
             errors,
             pattern_id: Some(hit.pattern_id.clone()),
@@ -149,6 +275,7 @@ impl CarveHandler for WavCarveHandler {
 mod tests {
     use super::*;
     use crate::evidence::{EvidenceError, EvidenceSource};
+    use std::fs::File;
     use std::io::Read;
     use tempfile::tempdir;
 
@@ -307,4 +434,81 @@ mod tests {
         let result = handler.process_hit(&hit, &ctx).expect("process");
         assert!(result.is_none(), "should reject file below min_size");
     }
+
+    fn carve(data: Vec<u8>) -> Option<CarvedFile> {
+        let evidence = SliceEvidence { data };
+        let handler = WavCarveHandler::new("wav".to_string(), 0, 0);
+        let hit = NormalizedHit {
+            global_offset: 0,
+            file_type_id: "wav".to_string(),
+            pattern_id: "wav_riff".to_string(),
+        };
+        let dir = tempdir().expect("tempdir");
+        let ctx = ExtractionContext {
+            run_id: "test",
+            output_root: dir.path(),
+            evidence: &evidence,
+        };
+        handler.process_hit(&hit, &ctx).expect("process")
+    }
+
+    /// A WAV with 1000 bytes of samples, followed by other data.
+    fn wav_with_samples() -> (Vec<u8>, usize) {
+        let mut wav = create_minimal_wav();
+        wav.truncate(wav.len() - 8);
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&1000u32.to_le_bytes());
+        wav.extend_from_slice(&[0x11; 1000]);
+        let riff_size = (wav.len() - 8) as u32;
+        wav[4..8].copy_from_slice(&riff_size.to_le_bytes());
+        let len = wav.len();
+        wav.extend_from_slice(&[0xC3; 512]);
+        (wav, len)
+    }
+
+    #[test]
+    fn sizes_unfinished_and_rf64_files() {
+        // Recorder stopped before writing the RIFF size.
+        let (mut wav, len) = wav_with_samples();
+        wav[4..8].copy_from_slice(&0u32.to_le_bytes());
+        let carved = carve(wav.clone()).expect("carved");
+        assert_eq!(carved.size, len as u64);
+        assert!(!carved.validated);
+
+        // Neither size written: nothing gives the end.
+        let data_size_at = len - 1000 - 4;
+        wav[data_size_at..data_size_at + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(carve(wav).is_none());
+
+        // RF64: sizes in the ds64 chunk.
+        let (wav, len) = wav_with_samples();
+        let mut rf64 = b"RF64".to_vec();
+        rf64.extend_from_slice(&u32::MAX.to_le_bytes());
+        rf64.extend_from_slice(b"WAVE");
+        rf64.extend_from_slice(b"ds64");
+        rf64.extend_from_slice(&28u32.to_le_bytes());
+        let total = (len + 36 - 8) as u64;
+        rf64.extend_from_slice(&total.to_le_bytes());
+        rf64.extend_from_slice(&1000u64.to_le_bytes());
+        rf64.extend_from_slice(&0u64.to_le_bytes());
+        rf64.extend_from_slice(&0u32.to_le_bytes());
+        let mut body = wav[12..].to_vec();
+        let data_size_at = len - 1000 - 4 - 12;
+        body[data_size_at..data_size_at + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        rf64.extend_from_slice(&body);
+        let carved = carve(rf64).expect("rf64");
+        assert_eq!(carved.size, len as u64 + 36);
+        assert!(carved.validated, "{:?}", carved.errors);
+    }
+
+    #[test]
+    fn flags_files_whose_chunks_stop_early() {
+        let (mut wav, len) = wav_with_samples();
+        // The data chunk claims more than the RIFF size leaves.
+        let data_size_at = len - 1000 - 4;
+        wav[data_size_at..data_size_at + 4].copy_from_slice(&4000u32.to_le_bytes());
+        let carved = carve(wav).expect("carved");
+        assert_eq!(carved.size, len as u64);
+        assert!(!carved.validated);
+    }
 }
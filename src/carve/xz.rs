@@ -1,18 +1,30 @@
 //! XZ stream carving handler.
 //!
-//! We validate the header magic and scan for a footer with a valid CRC32.
-
-use std::fs::File;
+//! The stream is walked as a decoder would, without decompressing: each
+//! block header (CRC32-checked), the LZMA2 chunk headers of its data up to
+//! the end marker, the block padding and check, then the index, whose
+//! records must match the walked blocks, and the stream footer, whose
+//! backward size must name the index. A stream whose blocks do not walk is
+//! cut at the first footer whose backward size points to an index with a
+//! valid CRC32, or where the walk stopped, and kept unvalidated.
 
 use sha2::{Digest, Sha256};
 
 use crate::carve::{
-    CarveError, CarveHandler, CarvedFile, ExtractionContext, output_path, write_range,
+    CarveError, CarveHandler, CarvedFile, ExtractionContext, Rejection, crc32, create_output,
+    note_rejection, output_path, write_range,
 };
+use crate::evidence::EvidenceSource;
 use crate::scanner::NormalizedHit;
 
 const XZ_MAGIC: [u8; 6] = [0xFD, 0x37, 0x7A, 0x58, 0x5A, 0x00];
 const XZ_FOOTER_MAGIC: [u8; 2] = [0x59, 0x5A];
+const STREAM_HEADER_LEN: u64 = 12;
+const STREAM_FOOTER_LEN: u64 = 12;
+const FILTER_LZMA2: u64 = 0x21;
+/// Largest valid LZMA properties byte (`(pb * 5 + lp) * 9 + lc`).
+const MAX_LZMA_PROPS: u8 = 224;
+const READ_BUF: usize = 64 * 1024;
 
 pub struct XzCarveHandler {
     extension: String,
@@ -44,7 +56,7 @@ impl CarveHandler for XzCarveHandler {
         hit: &NormalizedHit,
         ctx: &ExtractionContext,
     ) -> Result<Option<CarvedFile>, CarveError> {
-        let header = read_exact_at(ctx, hit.global_offset, 12)
+        let header = read_exact_at(ctx, hit.global_offset, STREAM_HEADER_LEN as usize)
             .ok_or_else(|| CarveError::Invalid("xz header too short".to_string()))?;
         if header[0..6] != XZ_MAGIC {
             return Ok(None);
@@ -54,20 +66,7 @@ impl CarveHandler for XzCarveHandler {
         if header_crc != computed {
             return Ok(None);
         }
-
-        let (full_path, rel_path) = output_path(
-            ctx.output_root,
-            self.file_type(),
-            &self.extension,
-            hit.global_offset,
-        )?;
-        let mut file = File::create(&full_path)?;
-        let mut md5 = md5::Context::new();
-        let mut sha256 = Sha256::new();
-
-        let mut validated = false;
-        let mut truncated = false;
-        let mut errors = Vec::new();
+        let flags = [header[6], header[7]];
 
         let max_end = if self.max_size > 0 {
             hit.global_offset.saturating_add(self.max_size)
@@ -75,72 +74,48 @@ impl CarveHandler for XzCarveHandler {
             u64::MAX
         };
 
-        let mut end_offset = None;
-        let mut offset = hit.global_offset + 12;
-        let mut carry: Vec<u8> = Vec::new();
-        let buf_size = 64 * 1024;
+        let mut validated = false;
+        let mut truncated = false;
+        let mut errors = Vec::new();
 
-        while offset < max_end {
-            let remaining = (max_end - offset).min(buf_size as u64) as usize;
-            let mut buf = vec![0u8; remaining];
-            let n = ctx
-                .evidence
-                .read_at(offset, &mut buf)
-                .map_err(|e| CarveError::Evidence(e.to_string()))?;
-            if n == 0 {
-                break;
+        let mut cursor = Cursor::new(ctx.evidence, hit.global_offset + STREAM_HEADER_LEN, max_end);
+        let end_offset = match walk_stream(&mut cursor, flags) {
+            Ok(end) => {
+                validated = true;
+                end
             }
-            buf.truncate(n);
-
-            let mut search_buf = carry.clone();
-            search_buf.extend_from_slice(&buf);
-            let mut search_start = 0usize;
-            while let Some(pos) = find_pattern(&search_buf[search_start..], &XZ_FOOTER_MAGIC) {
-                let absolute = search_start + pos;
-                let footer_end = offset
-                    .saturating_sub(carry.len() as u64)
-                    .saturating_add(absolute as u64)
-                    .saturating_add(2);
-                if footer_end < hit.global_offset + 12 {
-                    search_start = absolute + 1;
-                    continue;
-                }
-                let footer_start = footer_end.saturating_sub(12);
-                if footer_start <= hit.global_offset {
-                    search_start = absolute + 1;
-                    continue;
-                }
-                if let Some(footer) = read_exact_at(ctx, footer_start, 12) {
-                    if footer[10..12] == XZ_FOOTER_MAGIC {
-                        let footer_crc =
-                            u32::from_le_bytes([footer[0], footer[1], footer[2], footer[3]]);
-                        let computed = crc32(&footer[4..10]);
-                        if footer_crc == computed {
-                            end_offset = Some(footer_end);
-                            validated = true;
-                            break;
-                        }
+            Err(WalkError::Limit) => {
+                truncated = true;
+                errors.push("max_size reached before xz end".to_string());
+                max_end
+            }
+            // Copied up to the end of the evidence, which `write_range` reports.
+            Err(WalkError::Eof) => max_end,
+            Err(WalkError::Evidence(err)) => return Err(CarveError::Evidence(err)),
+            Err(WalkError::Corrupt(reason)) => {
+                let stopped_at = cursor.pos;
+                match find_footer(ctx, hit.global_offset, max_end, flags)? {
+                    Some(end) if end > stopped_at => {
+                        errors.push(format!("{reason}; cut at stream footer"));
+                        end
+                    }
+                    _ => {
+                        errors.push(reason);
+                        stopped_at
                     }
                 }
-                search_start = absolute + 1;
-            }
-            if end_offset.is_some() {
-                break;
             }
+        };
 
-            offset = offset.saturating_add(buf.len() as u64);
-            if buf.len() >= XZ_FOOTER_MAGIC.len() - 1 {
-                carry = buf[buf.len() - (XZ_FOOTER_MAGIC.len() - 1)..].to_vec();
-            } else {
-                carry = buf;
-            }
-        }
-
-        let end_offset = end_offset.unwrap_or(max_end);
-        if self.max_size > 0 && end_offset >= max_end {
-            truncated = true;
-            errors.push("max_size reached before xz end".to_string());
-        }
+        let (full_path, rel_path) = output_path(
+            ctx.output_root,
+            self.file_type(),
+            &self.extension,
+            hit.global_offset,
+        )?;
+        let mut file = create_output(&full_path)?;
+        let mut md5 = md5::Context::new();
+        let mut sha256 = Sha256::new();
 
         let (written, eof_truncated) = write_range(
             ctx,
@@ -158,6 +133,10 @@ impl CarveHandler for XzCarveHandler {
         }
 
         if written < self.min_size {
+            note_rejection(Rejection::MinSize {
+                size: written,
+                min_size: self.min_size,
+            });
             let _ = std::fs::remove_file(&full_path);
             return Ok(None);
         }
@@ -173,6 +152,7 @@ impl CarveHandler for XzCarveHandler {
         Ok(Some(CarvedFile {
             run_id: ctx.run_id.to_string(),
             file_type: self.file_type().to_string(),
+            signature_type: self.file_type().to_string(),
             path: rel_path,
             extension: self.extension.clone(),
             global_start: hit.global_offset,
@@ -188,19 +168,356 @@ impl CarveHandler for XzCarveHandler {
     }
 }
 
-fn find_pattern(haystack: &[u8], needle: &[u8]) -> Option<usize> {
-    if needle.is_empty() || haystack.len() < needle.len() {
-        return None;
+/// Why a stream walk stopped.
+#[derive(Debug)]
+enum WalkError {
+    /// The evidence ended.
+    Eof,
+    /// `max_size` was reached.
+    Limit,
+    Evidence(String),
+    Corrupt(String),
+}
+
+fn corrupt(reason: &str) -> WalkError {
+    WalkError::Corrupt(reason.to_string())
+}
+
+/// Buffered sequential reader over the evidence, up to `limit`.
+struct Cursor<'a> {
+    evidence: &'a dyn EvidenceSource,
+    pos: u64,
+    limit: u64,
+    buf: Vec<u8>,
+    buf_start: u64,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(evidence: &'a dyn EvidenceSource, pos: u64, limit: u64) -> Self {
+        Self {
+            evidence,
+            pos,
+            limit,
+            buf: Vec::new(),
+            buf_start: pos,
+        }
     }
-    let first = needle[0];
-    let mut i = 0usize;
-    while i + needle.len() <= haystack.len() {
-        if haystack[i] == first && &haystack[i..i + needle.len()] == needle {
-            return Some(i);
+
+    fn byte(&mut self) -> Result<u8, WalkError> {
+        if self.pos >= self.limit {
+            return Err(WalkError::Limit);
         }
-        i += 1;
+        let buffered = self.buf_start..self.buf_start + self.buf.len() as u64;
+        if !buffered.contains(&self.pos) {
+            let len = (self.limit - self.pos).min(READ_BUF as u64) as usize;
+            self.buf.resize(len, 0);
+            let n = self
+                .evidence
+                .read_at(self.pos, &mut self.buf)
+                .map_err(|e| WalkError::Evidence(e.to_string()))?;
+            self.buf.truncate(n);
+            self.buf_start = self.pos;
+            if n == 0 {
+                return Err(WalkError::Eof);
+            }
+        }
+        let b = self.buf[(self.pos - self.buf_start) as usize];
+        self.pos += 1;
+        Ok(b)
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<Vec<u8>, WalkError> {
+        (0..len).map(|_| self.byte()).collect()
+    }
+
+    fn be_u16(&mut self) -> Result<u64, WalkError> {
+        Ok(u64::from(self.byte()?) << 8 | u64::from(self.byte()?))
+    }
+
+    fn le_u32(&mut self) -> Result<u32, WalkError> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    /// Move past `len` bytes without reading them; a short evidence shows
+    /// at the next read.
+    fn skip(&mut self, len: u64) -> Result<(), WalkError> {
+        let target = self.pos.saturating_add(len);
+        if target > self.limit {
+            return Err(WalkError::Limit);
+        }
+        self.pos = target;
+        Ok(())
+    }
+
+    fn varint(&mut self) -> Result<u64, WalkError> {
+        varint(|| self.byte())
+    }
+
+    /// Read zero padding up to a multiple of 4 bytes from `from`.
+    fn padding(&mut self, from: u64, what: &str) -> Result<(), WalkError> {
+        while !(self.pos - from).is_multiple_of(4) {
+            if self.byte()? != 0 {
+                return Err(WalkError::Corrupt(format!("nonzero xz {what} padding")));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// xz multibyte integer: 7 bits per byte, least significant first, at most
+/// 9 bytes.
+fn varint(mut next: impl FnMut() -> Result<u8, WalkError>) -> Result<u64, WalkError> {
+    let mut value = 0u64;
+    for i in 0..9 {
+        let b = next()?;
+        value |= u64::from(b & 0x7F) << (7 * i);
+        if b & 0x80 == 0 {
+            if i > 0 && b == 0 {
+                return Err(corrupt("xz integer not minimally encoded"));
+            }
+            return Ok(value);
+        }
+    }
+    Err(corrupt("xz integer too long"))
+}
+
+/// Size of the check field for the check type in the stream flags.
+fn check_len(flags: [u8; 2]) -> u64 {
+    match flags[1] & 0x0F {
+        0 => 0,
+        1..=3 => 4,
+        4..=6 => 8,
+        7..=9 => 16,
+        10..=12 => 32,
+        _ => 64,
     }
-    None
+}
+
+/// Sizes of one block, as its index record stores them.
+#[derive(Debug, PartialEq, Eq)]
+struct BlockRecord {
+    unpadded: u64,
+    uncompressed: u64,
+}
+
+/// Walk the blocks, index, and footer after the stream header; the end
+/// offset of the stream footer.
+fn walk_stream(cursor: &mut Cursor, flags: [u8; 2]) -> Result<u64, WalkError> {
+    let check = check_len(flags);
+    let mut blocks = Vec::new();
+    let index_start = loop {
+        let block_start = cursor.pos;
+        let size_byte = cursor.byte()?;
+        if size_byte == 0 {
+            break block_start;
+        }
+        let header_len = (usize::from(size_byte) + 1) * 4;
+        let mut header = vec![size_byte];
+        header.extend(cursor.bytes(header_len - 1)?);
+        let (body, stored) = header.split_at(header_len - 4);
+        if crc32(body) != u32::from_le_bytes([stored[0], stored[1], stored[2], stored[3]]) {
+            return Err(corrupt("xz block header crc32 mismatch"));
+        }
+        let (declared_compressed, declared_uncompressed) = parse_block_header(body)?;
+
+        let data_start = cursor.pos;
+        let uncompressed = walk_lzma2(cursor)?;
+        let compressed = cursor.pos - data_start;
+        if declared_compressed.is_some_and(|size| size != compressed) {
+            return Err(corrupt("xz block compressed size mismatch"));
+        }
+        if declared_uncompressed.is_some_and(|size| size != uncompressed) {
+            return Err(corrupt("xz block uncompressed size mismatch"));
+        }
+        cursor.padding(block_start, "block")?;
+        cursor.skip(check)?;
+        blocks.push(BlockRecord {
+            unpadded: header_len as u64 + compressed + check,
+            uncompressed,
+        });
+    };
+
+    let count = cursor.varint()?;
+    if count != blocks.len() as u64 {
+        return Err(corrupt("xz index record count mismatch"));
+    }
+    for block in &blocks {
+        let record = BlockRecord {
+            unpadded: cursor.varint()?,
+            uncompressed: cursor.varint()?,
+        };
+        if record != *block {
+            return Err(corrupt("xz index record mismatch"));
+        }
+    }
+    cursor.padding(index_start, "index")?;
+    let crc_at = cursor.pos;
+    let stored = cursor.le_u32()?;
+    let mut index = vec![0u8; (crc_at - index_start) as usize];
+    let n = cursor
+        .evidence
+        .read_at(index_start, &mut index)
+        .map_err(|e| WalkError::Evidence(e.to_string()))?;
+    if n < index.len() || crc32(&index) != stored {
+        return Err(corrupt("xz index crc32 mismatch"));
+    }
+
+    let footer = cursor.bytes(STREAM_FOOTER_LEN as usize)?;
+    if !footer_matches(&footer, flags, cursor.pos - STREAM_FOOTER_LEN - index_start) {
+        return Err(corrupt("xz stream footer mismatch"));
+    }
+    Ok(cursor.pos)
+}
+
+/// Declared compressed and uncompressed sizes of a block header (without
+/// its CRC32); the filter chain must end in LZMA2.
+fn parse_block_header(body: &[u8]) -> Result<(Option<u64>, Option<u64>), WalkError> {
+    let flags = body[1];
+    if flags & 0x3C != 0 {
+        return Err(corrupt("xz block flags reserved bits set"));
+    }
+    let mut at = 2;
+    let mut next = || {
+        let b = body
+            .get(at)
+            .copied()
+            .ok_or_else(|| corrupt("xz block header overrun"));
+        at += 1;
+        b
+    };
+    let compressed = if flags & 0x40 != 0 {
+        Some(varint(&mut next)?)
+    } else {
+        None
+    };
+    let uncompressed = if flags & 0x80 != 0 {
+        Some(varint(&mut next)?)
+    } else {
+        None
+    };
+    let mut last_filter = 0;
+    for _ in 0..=(flags & 0x03) {
+        last_filter = varint(&mut next)?;
+        let props = varint(&mut next)?;
+        for _ in 0..props {
+            next()?;
+        }
+    }
+    if last_filter != FILTER_LZMA2 {
+        return Err(corrupt("xz block filter chain does not end in lzma2"));
+    }
+    while let Ok(b) = next() {
+        if b != 0 {
+            return Err(corrupt("nonzero xz block header padding"));
+        }
+    }
+    Ok((compressed, uncompressed))
+}
+
+/// Walk LZMA2 chunk headers up to the end marker; the uncompressed size.
+fn walk_lzma2(cursor: &mut Cursor) -> Result<u64, WalkError> {
+    let mut uncompressed = 0u64;
+    loop {
+        let control = cursor.byte()?;
+        match control {
+            0x00 => return Ok(uncompressed),
+            // Uncompressed chunk, with or without a dictionary reset.
+            0x01 | 0x02 => {
+                let size = cursor.be_u16()? + 1;
+                cursor.skip(size)?;
+                uncompressed += size;
+            }
+            0x03..=0x7F => return Err(corrupt("invalid lzma2 control byte")),
+            _ => {
+                let unpacked = (u64::from(control & 0x1F) << 16) + cursor.be_u16()? + 1;
+                let packed = cursor.be_u16()? + 1;
+                // States 2 and 3 reset the properties, which follow.
+                if control >= 0xC0 && cursor.byte()? > MAX_LZMA_PROPS {
+                    return Err(corrupt("invalid lzma2 properties"));
+                }
+                cursor.skip(packed)?;
+                uncompressed += unpacked;
+            }
+        }
+    }
+}
+
+/// Whether `footer` is a stream footer with a valid CRC32, the stream
+/// header's flags, and a backward size of `index_len`.
+fn footer_matches(footer: &[u8], flags: [u8; 2], index_len: u64) -> bool {
+    let stored = u32::from_le_bytes([footer[0], footer[1], footer[2], footer[3]]);
+    let backward = u32::from_le_bytes([footer[4], footer[5], footer[6], footer[7]]);
+    stored == crc32(&footer[4..10])
+        && footer[8..10] == flags
+        && footer[10..12] == XZ_FOOTER_MAGIC
+        && (u64::from(backward) + 1) * 4 == index_len
+}
+
+/// End of the first stream footer after `start` whose backward size points
+/// to an index with a valid CRC32.
+fn find_footer(
+    ctx: &ExtractionContext,
+    start: u64,
+    max_end: u64,
+    flags: [u8; 2],
+) -> Result<Option<u64>, CarveError> {
+    let mut offset = start + STREAM_HEADER_LEN;
+    let mut carry: Vec<u8> = Vec::new();
+    while offset < max_end {
+        let remaining = (max_end - offset).min(READ_BUF as u64) as usize;
+        let mut buf = vec![0u8; remaining];
+        let n = ctx
+            .evidence
+            .read_at(offset, &mut buf)
+            .map_err(|e| CarveError::Evidence(e.to_string()))?;
+        if n == 0 {
+            break;
+        }
+        buf.truncate(n);
+
+        let mut search_buf = carry.clone();
+        search_buf.extend_from_slice(&buf);
+        let base = offset - carry.len() as u64;
+        for pos in memchr::memmem::find_iter(&search_buf, &XZ_FOOTER_MAGIC) {
+            let footer_end = base + pos as u64 + 2;
+            let Some(footer_start) = footer_end
+                .checked_sub(STREAM_FOOTER_LEN)
+                .filter(|&at| at >= start + STREAM_HEADER_LEN)
+            else {
+                continue;
+            };
+            if footer_verifies(ctx, start, footer_start, flags) {
+                return Ok(Some(footer_end));
+            }
+        }
+
+        offset += buf.len() as u64;
+        carry = buf[buf.len().saturating_sub(XZ_FOOTER_MAGIC.len() - 1)..].to_vec();
+    }
+    Ok(None)
+}
+
+fn footer_verifies(ctx: &ExtractionContext, start: u64, footer_start: u64, flags: [u8; 2]) -> bool {
+    let Some(footer) = read_exact_at(ctx, footer_start, STREAM_FOOTER_LEN as usize) else {
+        return false;
+    };
+    let backward = u32::from_le_bytes([footer[4], footer[5], footer[6], footer[7]]);
+    let index_len = (u64::from(backward) + 1) * 4;
+    let Some(index_start) = footer_start
+        .checked_sub(index_len)
+        .filter(|&at| at >= start + STREAM_HEADER_LEN)
+    else {
+        return false;
+    };
+    if !footer_matches(&footer, flags, index_len) {
+        return false;
+    }
+    let Some(index) = read_exact_at(ctx, index_start, index_len as usize) else {
+        return false;
+    };
+    let (body, stored) = index.split_at(index.len() - 4);
+    body[0] == 0 && crc32(body) == u32::from_le_bytes([stored[0], stored[1], stored[2], stored[3]])
 }
 
 fn read_exact_at(ctx: &ExtractionContext, offset: u64, len: usize) -> Option<Vec<u8>> {
@@ -212,18 +529,6 @@ fn read_exact_at(ctx: &ExtractionContext, offset: u64, len: usize) -> Option<Vec
     Some(buf)
 }
 
-fn crc32(bytes: &[u8]) -> u32 {
-    let mut crc = 0xFFFF_FFFFu32;
-    for &b in bytes {
-        crc ^= b as u32;
-        for _ in 0..8 {
-            let mask = (crc & 1).wrapping_neg();
-            crc = (crc >> 1) ^ (0xEDB8_8320u32 & mask);
-        }
-    }
-    !crc
-}
-
 #[cfg(test)]
 mod tests {
     use super::{XzCarveHandler, crc32};
@@ -252,33 +557,90 @@ mod tests {
         }
     }
 
-    fn minimal_xz() -> Vec<u8> {
-        let mut out = Vec::new();
+    fn stream_header(out: &mut Vec<u8>, flags: [u8; 2]) {
         out.extend_from_slice(&[0xFD, 0x37, 0x7A, 0x58, 0x5A, 0x00]);
-        let flags = [0x00, 0x00];
         out.extend_from_slice(&flags);
         out.extend_from_slice(&crc32(&flags).to_le_bytes());
-        out.extend_from_slice(&[0u8; 4]); // dummy index bytes
-
-        let backward_size = [0x00, 0x00, 0x00, 0x00];
-        let stream_flags = [0x00, 0x00];
-        let mut footer = Vec::new();
-        let footer_crc = crc32(&[
-            backward_size[0],
-            backward_size[1],
-            backward_size[2],
-            backward_size[3],
-            stream_flags[0],
-            stream_flags[1],
-        ]);
-        footer.extend_from_slice(&footer_crc.to_le_bytes());
-        footer.extend_from_slice(&backward_size);
-        footer.extend_from_slice(&stream_flags);
-        footer.extend_from_slice(&[0x59, 0x5A]);
-        out.extend_from_slice(&footer);
+    }
+
+    /// Index of `records` (unpadded, uncompressed) and the stream footer.
+    fn index_and_footer(out: &mut Vec<u8>, flags: [u8; 2], records: &[(u8, u8)]) {
+        let mut index = vec![0x00, records.len() as u8];
+        for &(unpadded, uncompressed) in records {
+            index.extend_from_slice(&[unpadded, uncompressed]);
+        }
+        while !index.len().is_multiple_of(4) {
+            index.push(0);
+        }
+        let index_crc = crc32(&index);
+        index.extend_from_slice(&index_crc.to_le_bytes());
+        let backward_size = (index.len() as u32 / 4 - 1).to_le_bytes();
+        out.extend_from_slice(&index);
+
+        let mut footer_fields = backward_size.to_vec();
+        footer_fields.extend_from_slice(&flags);
+        out.extend_from_slice(&crc32(&footer_fields).to_le_bytes());
+        out.extend_from_slice(&footer_fields);
+        out.extend_from_slice(&[0x59, 0x5A]);
+    }
+
+    fn minimal_xz() -> Vec<u8> {
+        let mut out = Vec::new();
+        stream_header(&mut out, [0x00, 0x00]);
+        index_and_footer(&mut out, [0x00, 0x00], &[]);
         out
     }
 
+    /// A stream with one block holding `payload` as an uncompressed LZMA2
+    /// chunk, checked with CRC32.
+    fn one_block_xz(payload: &[u8]) -> Vec<u8> {
+        let flags = [0x00, 0x01];
+        let mut out = Vec::new();
+        stream_header(&mut out, flags);
+        let block_start = out.len();
+
+        // Header size 2 (12 bytes), one filter: LZMA2 with a 1-byte
+        // dictionary size property.
+        let mut block_header = vec![0x02, 0x00, 0x21, 0x01, 0x00, 0x00, 0x00, 0x00];
+        let header_crc = crc32(&block_header);
+        block_header.extend_from_slice(&header_crc.to_le_bytes());
+        out.extend_from_slice(&block_header);
+
+        let mut data = vec![0x01];
+        data.extend_from_slice(&(payload.len() as u16 - 1).to_be_bytes());
+        data.extend_from_slice(payload);
+        data.push(0x00);
+        let compressed = data.len();
+        out.extend_from_slice(&data);
+        while !(out.len() - block_start).is_multiple_of(4) {
+            out.push(0);
+        }
+        out.extend_from_slice(&crc32(payload).to_le_bytes());
+
+        let unpadded = (block_header.len() + compressed + 4) as u8;
+        index_and_footer(&mut out, flags, &[(unpadded, payload.len() as u8)]);
+        out
+    }
+
+    fn carve(data: &[u8], max_size: u64) -> Option<crate::carve::CarvedFile> {
+        let evidence = SliceEvidence {
+            data: data.to_vec(),
+        };
+        let handler = XzCarveHandler::new("xz".to_string(), 0, max_size);
+        let hit = NormalizedHit {
+            global_offset: 0,
+            file_type_id: "xz".to_string(),
+            pattern_id: "xz_header".to_string(),
+        };
+        let dir = tempdir().expect("tempdir");
+        let ctx = ExtractionContext {
+            run_id: "test",
+            output_root: dir.path(),
+            evidence: &evidence,
+        };
+        handler.process_hit(&hit, &ctx).expect("process")
+    }
+
     #[test]
     fn carves_minimal_xz_with_footer() {
         let data = minimal_xz();
@@ -301,4 +663,53 @@ mod tests {
         assert!(carved.validated);
         assert_eq!(carved.size, data.len() as u64);
     }
+
+    #[test]
+    fn walks_blocks_and_index_to_the_footer() {
+        let stream = one_block_xz(b"hello xz");
+        let mut data = stream.clone();
+        data.extend_from_slice(&[0xAB; 64]);
+        let carved = carve(&data, 0).expect("carved");
+        assert!(carved.validated, "{:?}", carved.errors);
+        assert_eq!(carved.size, stream.len() as u64);
+    }
+
+    #[test]
+    fn cuts_corrupt_stream_at_verified_footer() {
+        let stream = one_block_xz(b"hello xz");
+        let mut data = stream.clone();
+        // LZMA2 control byte of the chunk: now invalid.
+        data[24] = 0x05;
+        data.extend_from_slice(&[0xAB; 64]);
+        let carved = carve(&data, 0).expect("carved");
+        assert!(!carved.validated);
+        assert_eq!(carved.size, stream.len() as u64);
+        assert!(carved.errors[0].starts_with("invalid lzma2 control byte"));
+    }
+
+    #[test]
+    fn rejects_index_that_does_not_match_blocks() {
+        let mut data = Vec::new();
+        stream_header(&mut data, [0x00, 0x01]);
+        let good = one_block_xz(b"hello xz");
+        // The block of a valid stream, then an index claiming no blocks.
+        let index_at = good.len() - 12 - 8;
+        data.extend_from_slice(&good[12..index_at]);
+        index_and_footer(&mut data, [0x00, 0x01], &[]);
+        let carved = carve(&data, 0).expect("carved");
+        assert!(!carved.validated);
+        assert_eq!(
+            carved.errors[0],
+            "xz index record count mismatch; cut at stream footer"
+        );
+        assert_eq!(carved.size, data.len() as u64);
+    }
+
+    #[test]
+    fn stops_at_max_size() {
+        let data = one_block_xz(&[0x42; 200]);
+        let carved = carve(&data, 100).expect("carved");
+        assert!(carved.truncated);
+        assert_eq!(carved.size, 100);
+    }
 }
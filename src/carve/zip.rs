@@ -6,7 +6,9 @@ use std::path::Path;
 use sha2::{Digest, Sha256};
 
 use crate::carve::{
-    CarveError, CarveHandler, CarvedFile, ExtractionContext, output_path, write_range,
+    CarveError, CarveHandler, CarvedFile, DeclaredSize, ExtractionContext, Rejection,
+    create_output, declared_limit, note_declared_size, note_rejection, output_path,
+    relocate_classified, write_range,
 };
 use crate::scanner::NormalizedHit;
 
@@ -17,6 +19,7 @@ pub struct ZipCarveHandler {
     extension: String,
     min_size: u64,
     max_size: u64,
+    declared_size_factor: Option<f64>,
     require_eocd: bool,
     allowed_kinds: Option<HashSet<String>>,
 }
@@ -39,10 +42,41 @@ impl ZipCarveHandler {
             extension,
             min_size,
             max_size,
+            declared_size_factor: None,
             require_eocd,
             allowed_kinds,
         }
     }
+
+    /// Once an EOCD consistent with the hit is found, allow up to `factor`
+    /// times the archive size it declares instead of `max_size`.
+    pub fn with_declared_size_factor(mut self, factor: Option<f64>) -> Self {
+        self.declared_size_factor = factor;
+        self
+    }
+
+    /// Note the archive size an EOCD at `eocd_offset` declares for a hit at
+    /// `start` and return the carve limit for it. `None` when the central
+    /// directory does not end at the EOCD, so the EOCD is not this archive's.
+    fn declared_limit(&self, start: u64, eocd_offset: u64, eocd: &ZipEocd) -> Option<u64> {
+        if start
+            .saturating_add(eocd.cd_offset)
+            .saturating_add(eocd.cd_size)
+            != eocd_offset
+        {
+            return None;
+        }
+        let size = eocd_offset + 22 + eocd.comment_len as u64 - start;
+        note_declared_size(DeclaredSize {
+            size,
+            source: "eocd",
+        });
+        Some(declared_limit(
+            self.max_size,
+            self.declared_size_factor,
+            size,
+        ))
+    }
 }
 
 impl CarveHandler for ZipCarveHandler {
@@ -65,18 +99,21 @@ impl CarveHandler for ZipCarveHandler {
         let mut eocd: Option<ZipEocd> = None;
         let mut bytes_written = 0u64;
 
-        let (full_path, mut rel_path) = if self.require_eocd {
+        let (mut full_path, mut rel_path) = if self.require_eocd {
             let Some((eocd_offset, parsed)) = find_eocd(ctx, hit.global_offset, self.max_size)?
             else {
                 return Ok(None);
             };
             let comment_len = parsed.comment_len;
+            let limit = self
+                .declared_limit(hit.global_offset, eocd_offset, &parsed)
+                .unwrap_or(self.max_size);
             eocd = Some(parsed);
             validated = true;
 
             let mut total_end = eocd_offset + 22 + comment_len as u64;
-            if self.max_size > 0 {
-                let max_end = hit.global_offset + self.max_size;
+            if limit > 0 {
+                let max_end = hit.global_offset + limit;
                 if total_end > max_end {
                     total_end = max_end;
                     truncated = true;
@@ -90,7 +127,7 @@ impl CarveHandler for ZipCarveHandler {
                 &self.extension,
                 hit.global_offset,
             )?;
-            let mut file = File::create(&full_path)?;
+            let mut file = create_output(&full_path)?;
             let mut md5 = md5::Context::new();
             let mut sha256 = Sha256::new();
 
@@ -110,6 +147,11 @@ impl CarveHandler for ZipCarveHandler {
             file.flush()?;
 
             if bytes_written < self.min_size {
+                note_rejection(Rejection::MinSize {
+                    size: bytes_written,
+
+                    min_size: self.min_size,
+                });
                 let _ = std::fs::remove_file(&full_path);
                 return Ok(None);
             }
@@ -129,16 +171,14 @@ impl CarveHandler for ZipCarveHandler {
                 if let Some(kind) = classify_zip(&full_path, parsed.cd_offset, parsed.cd_size) {
                     file_type = kind.file_type().to_string();
                     extension = kind.extension().to_string();
-                    if file_type != self.file_type() {
-                        if let Ok((new_path, new_rel)) =
-                            output_path(ctx.output_root, &file_type, &extension, hit.global_offset)
-                        {
-                            if std::fs::rename(&full_path, &new_path).is_ok() {
-                                rel_path = new_rel;
-                                full_path = new_path;
-                            }
-                        }
-                    }
+                    relocate_classified(
+                        ctx.output_root,
+                        &file_type,
+                        &extension,
+                        hit.global_offset,
+                        &mut full_path,
+                        &mut rel_path,
+                    );
                 }
             }
 
@@ -152,6 +192,7 @@ impl CarveHandler for ZipCarveHandler {
             return Ok(Some(CarvedFile {
                 run_id: ctx.run_id.to_string(),
                 file_type,
+                signature_type: self.file_type().to_string(),
                 path: rel_path,
                 extension,
                 global_start: hit.global_offset,
@@ -173,7 +214,7 @@ impl CarveHandler for ZipCarveHandler {
             )?
         };
 
-        let mut file = File::create(&full_path)?;
+        let mut file = create_output(&full_path)?;
         let mut md5 = md5::Context::new();
         let mut sha256 = Sha256::new();
 
@@ -182,6 +223,7 @@ impl CarveHandler for ZipCarveHandler {
         let buf_size = 64 * 1024;
 
         loop {
+            ctx.check_cancelled()?;
             if self.max_size > 0 && bytes_written >= self.max_size {
                 truncated = true;
                 errors.push("max_size reached before EOCD".to_string());
@@ -217,7 +259,13 @@ impl CarveHandler for ZipCarveHandler {
             search_buf.extend_from_slice(&buf);
             if let Some(pos) = find_pattern(&search_buf, ZIP_EOCD) {
                 let eocd_offset = offset.saturating_sub(carry.len() as u64) + pos as u64;
+                let mut limit = self.max_size;
                 if let Ok(parsed) = read_eocd(ctx, eocd_offset) {
+                    if let Some(declared) =
+                        self.declared_limit(hit.global_offset, eocd_offset, &parsed)
+                    {
+                        limit = declared;
+                    }
                     eocd = Some(parsed);
                 }
 
@@ -227,8 +275,8 @@ impl CarveHandler for ZipCarveHandler {
                     eocd_offset + 22
                 };
 
-                if self.max_size > 0 {
-                    let max_end = hit.global_offset + self.max_size;
+                if limit > 0 {
+                    let max_end = hit.global_offset + limit;
                     if total_end > max_end {
                         total_end = max_end;
                         truncated = true;
@@ -296,6 +344,11 @@ impl CarveHandler for ZipCarveHandler {
         file.flush()?;
 
         if bytes_written < self.min_size {
+            note_rejection(Rejection::MinSize {
+                size: bytes_written,
+
+                min_size: self.min_size,
+            });
             let _ = std::fs::remove_file(&full_path);
             return Ok(None);
         }
@@ -316,15 +369,14 @@ impl CarveHandler for ZipCarveHandler {
                 if let Some(kind) = classify_zip(&full_path, parsed.cd_offset, parsed.cd_size) {
                     file_type = kind.file_type().to_string();
                     extension = kind.extension().to_string();
-                    if file_type != self.file_type() {
-                        if let Ok((new_path, new_rel)) =
-                            output_path(ctx.output_root, &file_type, &extension, hit.global_offset)
-                        {
-                            if std::fs::rename(&full_path, &new_path).is_ok() {
-                                rel_path = new_rel;
-                            }
-                        }
-                    }
+                    relocate_classified(
+                        ctx.output_root,
+                        &file_type,
+                        &extension,
+                        hit.global_offset,
+                        &mut full_path,
+                        &mut rel_path,
+                    );
                 }
             }
         }
@@ -332,6 +384,7 @@ impl CarveHandler for ZipCarveHandler {
         Ok(Some(CarvedFile {
             run_id: ctx.run_id.to_string(),
             file_type,
+            signature_type: self.file_type().to_string(),
             path: rel_path,
             extension,
             global_start: hit.global_offset,
@@ -359,6 +412,7 @@ fn find_eocd(
     let mut last_valid: Option<(u64, ZipEocd)> = None;
 
     loop {
+        ctx.check_cancelled()?;
         if max_size > 0 && bytes_scanned >= max_size {
             return Ok(last_valid);
         }
@@ -828,7 +882,11 @@ mod tests {
         let result = handler.process_hit(&hit, &ctx).expect("process");
         let carved = result.expect("carved");
         assert_eq!(carved.file_type, "docx");
-        assert!(dir.path().join("docx").exists());
+        assert_eq!(carved.signature_type, "zip");
+        assert_eq!(carved.extension, "docx");
+        assert_eq!(carved.path, "docx/docx_000000000000.docx");
+        assert!(dir.path().join(&carved.path).exists());
+        assert!(!dir.path().join("zip").join("zip_000000000000.zip").exists());
 
         let dir = tempdir().expect("tempdir");
         let evidence_path = dir.path().join("evidence.bin");
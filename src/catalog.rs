@@ -0,0 +1,271 @@
+//! Signature catalog for `list-types`: what the loaded config can find and how
+//! each type is carved, described from the carve registry built for a run.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::util;
+
+/// GPU scan backends compiled into this binary.
+pub const GPU_BACKENDS: &[&str] = &[
+    #[cfg(feature = "gpu-opencl")]
+    "opencl",
+    #[cfg(feature = "gpu-cuda")]
+    "cuda",
+];
+
+/// Every file type of a config, in config order.
+#[derive(Debug, Serialize)]
+pub struct Catalog {
+    pub gpu_backends: Vec<String>,
+    pub types: Vec<TypeEntry>,
+    pub type_groups: BTreeMap<String, Vec<String>>,
+}
+
+/// One configured file type.
+#[derive(Debug, Serialize)]
+pub struct TypeEntry {
+    pub id: String,
+    pub validator: String,
+    /// Extension of carved files, as the handler writes it.
+    pub extension: String,
+    pub extensions: Vec<String>,
+    pub header_patterns: Vec<PatternEntry>,
+    pub footer_patterns: Vec<PatternEntry>,
+    pub min_size: u64,
+    pub max_size: u64,
+    /// How the handler finds the end of a file and decides it is valid.
+    pub strategy: String,
+    /// Header patterns are searched by the GPU backends of this binary.
+    pub gpu: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PatternEntry {
+    pub id: String,
+    /// Upper-case hex, as in `header_patterns`.
+    pub hex: String,
+}
+
+impl Catalog {
+    /// Describe the file types of `cfg`, building the carve registry so the
+    /// reported extensions are the ones a run would write.
+    pub fn from_config(cfg: &Config) -> Result<Self> {
+        let registry = util::build_carve_registry(cfg, true)?;
+        let types = cfg
+            .file_types
+            .iter()
+            .map(|file_type| {
+                let validator = if file_type.validator.trim().is_empty() {
+                    file_type.id.clone()
+                } else {
+                    file_type.validator.clone()
+                };
+                let extension = match registry.get(&file_type.id) {
+                    Some(handler) => handler.extension().to_string(),
+                    None => file_type.extensions.first().cloned().unwrap_or_default(),
+                };
+                let patterns = |patterns: &[crate::config::PatternConfig]| {
+                    patterns
+                        .iter()
+                        .map(|pattern| PatternEntry {
+                            id: pattern.id.clone(),
+                            hex: pattern.hex.trim().to_ascii_uppercase(),
+                        })
+                        .collect::<Vec<_>>()
+                };
+                let header_patterns = patterns(&file_type.header_patterns);
+                TypeEntry {
+                    id: file_type.id.clone(),
+                    strategy: strategy(&validator).to_string(),
+                    validator,
+                    extension,
+                    extensions: file_type.extensions.clone(),
+                    gpu: !GPU_BACKENDS.is_empty()
+                        && header_patterns.iter().any(|p| !p.hex.is_empty()),
+                    header_patterns,
+                    footer_patterns: patterns(&file_type.footer_patterns),
+                    min_size: file_type.min_size,
+                    max_size: file_type.max_size,
+                }
+            })
+            .collect();
+        Ok(Self {
+            gpu_backends: GPU_BACKENDS.iter().map(|b| b.to_string()).collect(),
+            types,
+            type_groups: cfg.type_groups.clone(),
+        })
+    }
+
+    /// One line per type, or a block per type with `detailed`.
+    pub fn render_text(&self, detailed: bool) -> String {
+        let mut out = String::new();
+        let width = self.types.iter().map(|t| t.id.len()).max().unwrap_or(0);
+        for entry in &self.types {
+            if !detailed {
+                let _ = writeln!(
+                    out,
+                    "{:width$}  .{:8} {}",
+                    entry.id, entry.extension, entry.strategy
+                );
+                continue;
+            }
+            let _ = writeln!(out, "{}", entry.id);
+            let _ = writeln!(out, "  validator:  {}", entry.validator);
+            let _ = writeln!(
+                out,
+                "  extension:  {} (config: {})",
+                entry.extension,
+                entry.extensions.join(", ")
+            );
+            for pattern in &entry.header_patterns {
+                let _ = writeln!(out, "  header:     {} {}", pattern.id, pattern.hex);
+            }
+            for pattern in &entry.footer_patterns {
+                let _ = writeln!(out, "  footer:     {} {}", pattern.id, pattern.hex);
+            }
+            let _ = writeln!(
+                out,
+                "  size:       {} to {}",
+                size_text(entry.min_size),
+                size_text(entry.max_size)
+            );
+            let _ = writeln!(out, "  strategy:   {}", entry.strategy);
+            let _ = writeln!(
+                out,
+                "  gpu:        {}",
+                if entry.gpu { "yes" } else { "no" }
+            );
+            out.push('\n');
+        }
+        if detailed {
+            let backends = if self.gpu_backends.is_empty() {
+                "none (CPU only)".to_string()
+            } else {
+                self.gpu_backends.join(", ")
+            };
+            let _ = writeln!(out, "GPU backends: {backends}");
+        }
+        for (group, members) in &self.type_groups {
+            let _ = writeln!(out, "group {group}: {}", members.join(", "));
+        }
+        out
+    }
+}
+
+/// Binary-unit size as accepted by the config, e.g. `100MiB`.
+fn size_text(bytes: u64) -> String {
+    for (unit, factor) in [("GiB", 1u64 << 30), ("MiB", 1 << 20), ("KiB", 1 << 10)] {
+        if bytes >= factor && bytes.is_multiple_of(factor) {
+            return format!("{}{unit}", bytes / factor);
+        }
+    }
+    bytes.to_string()
+}
+
+/// How the handler for `validator` finds the end of a file and validates it.
+pub fn strategy(validator: &str) -> &'static str {
+    match validator {
+        "jpeg" => "byte scan to the FF D9 EOI marker",
+        "png" => "chunk walk to IEND",
+        "gif" => "block walk (extensions, image data) to the 0x3B trailer",
+        "bmp" => {
+            "file size field, capped at the image data; DIB header, depth, compression, and palette checked"
+        }
+        "tiff" => "IFD chain walk; ends after the furthest referenced data",
+        "webp" => "RIFF size field + 8, WEBP form type checked",
+        "ico" => {
+            "end of the furthest directory entry; entries may not overlap, an entry must hold BMP or PNG data"
+        }
+        "pdf" => "to the first %%EOF; for linearized files, the one near /L",
+        "zip" => "end of central directory; kind (docx, xlsx, epub, ...) from entries",
+        "ole" => "FAT sector extent; kind (doc, xls, ppt, encrypted_ooxml) from streams",
+        "rtf" => "brace depth tracking (escapes, \\bin) to the closing brace",
+        "rar" => "block header walk (RAR 4 and 5)",
+        "sevenz" => "start header: next-header offset and size give the length",
+        "tar" => "512-byte header checksums, to the two zero blocks",
+        "gzip" => "best effort: up to the next gzip header, EOF, or max_size",
+        "bzip2" => "best effort: scan for the byte-aligned end-of-stream marker",
+        "xz" => "header magic; ends at a stream footer with a valid CRC32",
+        "mp4" => "box walk (ftyp, moov, mdat); requires ftyp and moov",
+        "mov" => "QuickTime box walk; requires ftyp and moov",
+        "wav" => "RIFF size field + 8 (RF64: ds64 chunk), chunk walk for fmt and data",
+        "avi" => "RIFF size field + 8, form type checked",
+        "webm" => "EBML header; Segment element size when known",
+        "wmv" => "ASF header; file size from the file properties object",
+        "mp3" => "ID3 tag and MPEG frame walk; 3 frames needed without ID3",
+        "ogg" => "page walk from the BOS page to the last EOS page; page CRCs checked",
+        "flac" => {
+            "metadata blocks, then frames found by CRC-8 headers and CRC-16 footers to the STREAMINFO sample count"
+        }
+        "elf" => "end of the last segment, section, or header table; file header checked",
+        "macho" => "end of the data the load commands point at; fat files to the last slice",
+        "eml" => "at least 2 RFC 822 headers; ends at an mbox boundary or max_size",
+        "mobi" => "best effort: PalmDB record offsets",
+        "fb2" => "FictionBook tag or namespace in the first 4 KiB",
+        "lrf" => "best effort: header size field, else max_size",
+        "sqlite" => "page size x page count from the database header",
+        "chrome_cache" => "Simple Cache entry, through the stream 0 EOF record",
+        "firefox_cache" => "cache2 entry; start derived from the trailing offset",
+        "pcap" => "record walk; ends at the last record that parses",
+        "pcapng" => "block walk; ends at the last block that parses",
+        "journald" => "header_size + arena_size from the file header",
+        "boltdb" => "meta high-water mark x page size",
+        "regf" => "hive bin chain up to the base block's bins size; sequence numbers checked",
+        "evtx" => "header chunk count x 64 KiB chunks; chunk checksums checked",
+        "prefetch" => "file size from the SCCA header; MAM data decompressed to its declared size",
+        "lnk" => "header, ID list, link info, string data, and extra blocks to the terminal block",
+        "thumbcache" => "first free byte from the CMMM header; entries walked from the first entry",
+        "bplist" => "trailer found after the objects; offset table entries checked",
+        "pe" => "end of the last section's raw data or the certificate table; PE headers checked",
+        "heif" => {
+            "box walk from ftyp (meta, mdat); meta handler pict; end from iloc item extents when later"
+        }
+        "ese" => "last page of the owned-extent space tree x page size; shadow headers rejected",
+        "pst" => "ibFileEof from the header ROOT; header CRCs and first AMap page checked",
+        "torrent" => "top-level bencoded dictionary; needs a usable info dictionary",
+        "binarycookies" => "page table, checksum over the pages, trailing plist",
+        "netscape_cookies" => "header through the last cookie, comment, or blank line",
+        "crashdump" => "fixed 4 KiB or 8 KiB header; major version checked",
+        "hiberfil" => "page-aligned header page, LengthSelf checked",
+        "hbin" => "bin size from its header, first cell checked",
+        "pool_process" => "pool header BlockSize; image name checked",
+        "footer" => "header to footer (footer_mode); max_size bytes without footers",
+        _ => "no built-in handler",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{self, KNOWN_VALIDATORS};
+
+    #[test]
+    fn every_validator_has_a_strategy() {
+        for validator in KNOWN_VALIDATORS {
+            assert_ne!(strategy(validator), "no built-in handler", "{validator}");
+        }
+    }
+
+    #[test]
+    fn describes_default_types() {
+        let cfg = config::load_config(None).expect("config").config;
+        let catalog = Catalog::from_config(&cfg).expect("catalog");
+        assert_eq!(catalog.types.len(), cfg.file_types.len());
+        let jpeg = &catalog.types[0];
+        assert_eq!(jpeg.id, "jpeg");
+        assert_eq!(jpeg.extension, "jpg");
+        assert!(jpeg.header_patterns[0].hex.starts_with("FFD8"));
+        assert_eq!(jpeg.gpu, !GPU_BACKENDS.is_empty());
+
+        let text = catalog.render_text(true);
+        assert!(text.contains("  size:       500 to 100MiB\n"), "{text}");
+        assert!(text.contains("group images: jpeg"));
+        let json = serde_json::to_value(&catalog).expect("json");
+        assert_eq!(json["types"][0]["validator"], "jpeg");
+    }
+}
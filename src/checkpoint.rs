@@ -3,8 +3,16 @@ use std::path::Path;
 
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use thiserror::Error;
 
+use crate::evidence::EvidenceSource;
+use crate::inputs::InputFile;
+
+/// Checkpoints written before input files were hashed have version 1, those
+/// written before evidence regions were sampled version 2.
+pub const CHECKPOINT_VERSION: u32 = 3;
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct CheckpointState {
     pub version: u32,
@@ -14,6 +22,22 @@ pub struct CheckpointState {
     pub next_offset: u64,
     pub evidence_len: u64,
     pub created_at: String,
+    /// Config, signature, and hash list files of the run; empty in version 1.
+    #[serde(default)]
+    pub input_files: Vec<InputFile>,
+    /// Evidence regions hashed when the checkpoint was saved; empty before
+    /// version 3.
+    #[serde(default)]
+    pub evidence_samples: Vec<EvidenceSample>,
+}
+
+/// A region of the scanned evidence and its SHA-256, read again on resume to
+/// tell whether the evidence is still the same.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct EvidenceSample {
+    pub offset: u64,
+    pub len: u64,
+    pub sha256: String,
 }
 
 impl CheckpointState {
@@ -23,17 +47,25 @@ impl CheckpointState {
         overlap: u64,
         next_offset: u64,
         evidence_len: u64,
+        input_files: Vec<InputFile>,
     ) -> Self {
         Self {
-            version: 1,
+            version: CHECKPOINT_VERSION,
             run_id: run_id.to_string(),
             chunk_size,
             overlap,
             next_offset,
             evidence_len,
             created_at: Utc::now().to_rfc3339(),
+            input_files,
+            evidence_samples: Vec::new(),
         }
     }
+
+    pub fn with_evidence_samples(mut self, samples: Vec<EvidenceSample>) -> Self {
+        self.evidence_samples = samples;
+        self
+    }
 }
 
 #[derive(Debug, Error)]
@@ -42,6 +74,15 @@ pub enum CheckpointError {
     Io(#[from] std::io::Error),
     #[error("json error: {0}")]
     Json(#[from] serde_json::Error),
+    #[error(
+        "evidence changed since the checkpoint: {changed} of {sampled} sampled regions differ, first at offset {first_offset} ({first_len} bytes)"
+    )]
+    EvidenceChanged {
+        changed: usize,
+        sampled: usize,
+        first_offset: u64,
+        first_len: u64,
+    },
 }
 
 pub fn load_checkpoint(path: &Path) -> Result<CheckpointState, CheckpointError> {
@@ -55,20 +96,138 @@ pub fn save_checkpoint(path: &Path, state: &CheckpointState) -> Result<(), Check
     Ok(())
 }
 
+/// Hash `count` regions of `len` bytes from the first `scanned` bytes of the
+/// evidence: the first region, which holds the partition table or file
+/// system header, and the rest spread at random. Unreadable regions are left
+/// out.
+pub fn sample_evidence(
+    evidence: &dyn EvidenceSource,
+    scanned: u64,
+    count: usize,
+    len: u64,
+) -> Vec<EvidenceSample> {
+    let len = len.min(scanned);
+    if count == 0 || len == 0 {
+        return Vec::new();
+    }
+    let span = scanned - len + 1;
+    let seed = Utc::now().timestamp_nanos_opt().unwrap_or_default() as u64;
+    let mut offsets: Vec<u64> = std::iter::once(0)
+        .chain((1..count as u64).map(|i| mix(seed.wrapping_add(i)) % span))
+        .collect();
+    offsets.sort_unstable();
+    offsets.dedup();
+    offsets
+        .into_iter()
+        .filter_map(|offset| {
+            Some(EvidenceSample {
+                offset,
+                len,
+                sha256: hash_region(evidence, offset, len)?,
+            })
+        })
+        .collect()
+}
+
+/// Hash the sampled regions again. Fails when any of them differs or can no
+/// longer be read; returns the number checked otherwise.
+pub fn verify_evidence(
+    evidence: &dyn EvidenceSource,
+    samples: &[EvidenceSample],
+) -> Result<usize, CheckpointError> {
+    let changed: Vec<&EvidenceSample> = samples
+        .iter()
+        .filter(|sample| {
+            hash_region(evidence, sample.offset, sample.len).as_ref() != Some(&sample.sha256)
+        })
+        .collect();
+    match changed.first() {
+        Some(first) => Err(CheckpointError::EvidenceChanged {
+            changed: changed.len(),
+            sampled: samples.len(),
+            first_offset: first.offset,
+            first_len: first.len,
+        }),
+        None => Ok(samples.len()),
+    }
+}
+
+fn hash_region(evidence: &dyn EvidenceSource, offset: u64, len: u64) -> Option<String> {
+    let mut buf = vec![0u8; usize::try_from(len).ok()?];
+    let read = evidence.read_at(offset, &mut buf).ok()?;
+    (read == buf.len()).then(|| hex::encode(Sha256::digest(&buf)))
+}
+
+/// SplitMix64 finalizer, to spread the sample offsets.
+fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^ (x >> 31)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::evidence::RawFileSource;
     use tempfile::tempdir;
 
     #[test]
     fn roundtrip_checkpoint() {
         let dir = tempdir().expect("tempdir");
         let path = dir.path().join("checkpoint.json");
-        let state = CheckpointState::new("run", 1024, 64, 2048, 4096);
+        let state = CheckpointState::new("run", 1024, 64, 2048, 4096, Vec::new());
         save_checkpoint(&path, &state).expect("save");
         let loaded = load_checkpoint(&path).expect("load");
         assert_eq!(loaded.run_id, "run");
         assert_eq!(loaded.next_offset, 2048);
         assert_eq!(loaded.evidence_len, 4096);
     }
+
+    #[test]
+    fn loads_version_1_checkpoint_without_input_files() {
+        let dir = tempdir().expect("tempdir");
+        let path = dir.path().join("checkpoint.json");
+        fs::write(
+            &path,
+            r#"{"version":1,"run_id":"run","chunk_size":1024,"overlap":64,"next_offset":2048,"evidence_len":4096,"created_at":"2024-01-01T00:00:00Z"}"#,
+        )
+        .expect("write");
+        let loaded = load_checkpoint(&path).expect("load");
+        assert_eq!(loaded.version, 1);
+        assert!(loaded.input_files.is_empty());
+        assert!(loaded.evidence_samples.is_empty());
+    }
+
+    #[test]
+    fn refuses_evidence_that_changed_in_a_sampled_region() {
+        let dir = tempdir().expect("tempdir");
+        let path = dir.path().join("image.dd");
+        let mut bytes: Vec<u8> = (0..256 * 1024u32).map(|i| (i % 251) as u8).collect();
+        fs::write(&path, &bytes).expect("write");
+        let source = RawFileSource::open(&path).expect("open");
+        let samples = sample_evidence(&source, 128 * 1024, 8, 4096);
+        assert!(!samples.is_empty());
+        assert_eq!(samples[0].offset, 0);
+        assert!(samples.iter().all(|s| s.offset + s.len <= 128 * 1024));
+        assert_eq!(
+            verify_evidence(&source, &samples).expect("same"),
+            samples.len()
+        );
+
+        bytes[100] ^= 0xFF;
+        fs::write(&path, &bytes).expect("write");
+        let source = RawFileSource::open(&path).expect("open");
+        match verify_evidence(&source, &samples) {
+            Err(CheckpointError::EvidenceChanged {
+                changed,
+                first_offset,
+                ..
+            }) => {
+                assert!(changed >= 1);
+                assert_eq!(first_offset, 0);
+            }
+            other => panic!("expected a changed region, got {other:?}"),
+        }
+    }
 }
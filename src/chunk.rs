@@ -7,37 +7,54 @@ pub struct ScanChunk {
 }
 
 pub fn build_chunks(total_len: u64, chunk_size: u64, overlap: u64) -> Vec<ScanChunk> {
-    if chunk_size == 0 {
-        return Vec::new();
-    }
-
-    let mut chunks = Vec::new();
-    let mut start = 0u64;
-    let mut id = 0u64;
+    chunk_iter(total_len, chunk_size, overlap).collect()
+}
 
-    while start < total_len {
+/// The chunks of [`build_chunks`], made as they are taken, so a stream of
+/// unknown length (`evidence::UNKNOWN_LEN`) is not listed up front.
+pub fn chunk_iter(
+    total_len: u64,
+    chunk_size: u64,
+    overlap: u64,
+) -> impl Iterator<Item = ScanChunk> {
+    let count = if chunk_size == 0 {
+        0
+    } else {
+        total_len.div_ceil(chunk_size)
+    };
+    (0..count).map(move |id| {
+        let start = id * chunk_size;
         let remaining = total_len - start;
-        let length = remaining.min(chunk_size.saturating_add(overlap));
-        let valid_length = remaining.min(chunk_size);
-
-        chunks.push(ScanChunk {
+        ScanChunk {
             id,
             start,
-            length,
-            valid_length,
-        });
+            length: remaining.min(chunk_size.saturating_add(overlap)),
+            valid_length: remaining.min(chunk_size),
+        }
+    })
+}
 
-        start = start.saturating_add(chunk_size);
-        id += 1;
+/// Round `chunk_size` up to a multiple of the evidence `block_size`, so scan
+/// chunks start on block boundaries and no block is split between chunks.
+pub fn align_chunk_size(chunk_size: u64, block_size: Option<u64>) -> u64 {
+    match block_size {
+        Some(block) if block > 1 && chunk_size > 0 => chunk_size.div_ceil(block) * block,
+        _ => chunk_size,
     }
-
-    chunks
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn aligns_chunk_size_to_blocks() {
+        assert_eq!(align_chunk_size(1 << 20, Some(32 * 1024)), 1 << 20);
+        assert_eq!(align_chunk_size(1 << 20, Some(24 * 1024 * 1024)), 24 << 20);
+        assert_eq!(align_chunk_size(1000, Some(384)), 1152);
+        assert_eq!(align_chunk_size(1000, None), 1000);
+    }
+
     #[test]
     fn builds_chunks_with_overlap() {
         let chunks = build_chunks(100, 40, 10);
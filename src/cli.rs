@@ -1,6 +1,9 @@
 use std::path::PathBuf;
 
-use clap::{Parser, ValueEnum};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+use crate::config::{CarveFsync, EvidenceTransform, PipelineMode, ScanProfile};
+use crate::export::ExportFormat;
 
 #[derive(ValueEnum, Debug, Clone, Copy)]
 pub enum MetadataBackend {
@@ -15,12 +18,147 @@ pub enum LogFormat {
     Json,
 }
 
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureFormat {
+    /// PhotoRec `photorec.sig` signature file
+    Photorec,
+    /// Scalpel / Foremost `scalpel.conf` rules
+    Scalpel,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Convert third-party signature definitions into config `file_types` entries
+    ImportSignatures(ImportSignaturesArgs),
+    /// Inspect configuration files
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    /// Write a decrypted copy of a run (or one file) encrypted with --encrypt-to
+    Decrypt(DecryptArgs),
+    /// Export a run's locations or timeline for case visualization tools
+    Export(ExportArgs),
+    /// List the file types the config carves and how each is found
+    ListTypes(ListTypesArgs),
+    /// Recover the metadata records of an aborted run into well-formed files
+    Salvage(SalvageArgs),
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum ConfigCommand {
+    /// Validate a config file and report every problem with its line number
+    Check(ConfigCheckArgs),
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ConfigCheckArgs {
+    /// Config file to check (defaults to the built-in config)
+    pub path: Option<PathBuf>,
+
+    /// Chunk size, in MiB, to validate overlap and entropy window against
+    #[arg(long, default_value_t = 512)]
+    pub chunk_size_mib: u64,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ListTypesArgs {
+    /// Config file to describe (defaults to the built-in config)
+    #[arg(long)]
+    pub config_path: Option<PathBuf>,
+
+    /// Apply a scan profile first
+    #[arg(long, value_enum)]
+    pub profile: Option<ScanProfile>,
+
+    /// Show patterns, sizes, and GPU support for every type
+    #[arg(long)]
+    pub detailed: bool,
+
+    /// Print the full catalog as JSON
+    #[arg(long, conflicts_with = "detailed")]
+    pub json: bool,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct SalvageArgs {
+    /// Run directory (`<output>/<run_id>`) of the aborted run
+    pub run_dir: PathBuf,
+
+    /// Directory to write the recovered files to (defaults to `<run_dir>/salvaged`)
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ExportArgs {
+    /// Run directory (`<output>/<run_id>`) to export
+    pub run_dir: PathBuf,
+
+    /// Export format
+    #[arg(long, value_enum)]
+    pub format: ExportFormat,
+
+    /// File to write (defaults to `<run_dir>/exports/<locations.kml|locations.geojson|timeline.jsonl>`)
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct DecryptArgs {
+    /// Encrypted `.age` file or run directory
+    pub path: PathBuf,
+
+    /// age identity file with the private key (`age-keygen` output)
+    #[arg(short, long)]
+    pub identity: PathBuf,
+
+    /// Directory to write the decrypted copy to
+    #[arg(short, long)]
+    pub output: PathBuf,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ImportSignaturesArgs {
+    /// Signature file to convert
+    pub path: PathBuf,
+
+    /// Source signature format
+    #[arg(long, value_enum, default_value_t = SignatureFormat::Photorec)]
+    pub format: SignatureFormat,
+
+    /// Write YAML to this file instead of stdout
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// Prefix for generated file type ids (defaults to `<format>_`)
+    #[arg(long)]
+    pub id_prefix: Option<String>,
+
+    /// Bytes to carve per hit for signatures without a footer or size (PhotoRec)
+    #[arg(long, default_value_t = 10 * 1024 * 1024)]
+    pub max_size: u64,
+
+    /// Minimum carve size in bytes
+    #[arg(long, default_value_t = 0)]
+    pub min_size: u64,
+}
+
 #[derive(Parser, Debug)]
-#[command(author, version, about)]
+#[command(
+    author,
+    version,
+    about,
+    subcommand_negates_reqs = true,
+    args_conflicts_with_subcommands = true
+)]
 pub struct CliOptions {
-    /// Input image (raw, E01, or device)
-    #[arg(short, long)]
-    pub input: PathBuf,
+    /// Optional subcommand (runs a carve when omitted)
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Input image (raw, E01, or device), or `-` to read a stream from
+    /// standard input
+    #[arg(short, long, required = true)]
+    pub input: Option<PathBuf>,
 
     /// Output directory for carved files and metadata
     #[arg(short, long, default_value = "./output")]
@@ -38,10 +176,28 @@ pub struct CliOptions {
     #[arg(long, default_value_t = num_cpus::get())]
     pub workers: usize,
 
+    /// Run the stages on worker threads or all on one thread: threaded or
+    /// single-threaded (overrides pipeline_mode; single-threaded ignores
+    /// --workers)
+    #[arg(long, value_enum)]
+    pub pipeline_mode: Option<PipelineMode>,
+
+    /// Undo an acquisition fault before scanning: none, auto (detect from
+    /// signature hit rates), byte-swap16, or invert-bits (overrides
+    /// evidence_transform)
+    #[arg(long, value_enum)]
+    pub evidence_transform: Option<EvidenceTransform>,
+
     /// Chunk size, in MiB
     #[arg(long, default_value_t = 512)]
     pub chunk_size_mib: u64,
 
+    /// Bytes of a `--input -` stream held in memory, in MiB. Files running
+    /// past the window are carved truncated. Must be at least twice the
+    /// chunk size plus overlap
+    #[arg(long, default_value_t = 2048)]
+    pub stream_window_mib: u64,
+
     /// Chunk overlap, in KiB (overrides config when set)
     #[arg(long)]
     pub overlap_kib: Option<u64>,
@@ -58,6 +214,10 @@ pub struct CliOptions {
     #[arg(long, default_value_t = 5)]
     pub progress_interval_secs: u64,
 
+    /// Built-in scan profile for the kind of evidence (`memory` for RAM images)
+    #[arg(long, value_enum)]
+    pub profile: Option<ScanProfile>,
+
     /// Enable printable string scanning
     #[arg(long)]
     pub scan_strings: bool,
@@ -90,10 +250,54 @@ pub struct CliOptions {
     #[arg(long, conflicts_with = "scan_phones")]
     pub no_scan_phones: bool,
 
+    /// Enable syslog line extraction from string spans
+    #[arg(long, conflicts_with = "no_scan_logs")]
+    pub scan_logs: bool,
+
+    /// Disable syslog line extraction from string spans
+    #[arg(long, conflicts_with = "scan_logs")]
+    pub no_scan_logs: bool,
+
+    /// Enable SSH key, known_hosts, and shell history extraction from string spans
+    #[arg(long, conflicts_with = "no_scan_system")]
+    pub scan_system: bool,
+
+    /// Disable SSH key, known_hosts, and shell history extraction from string spans
+    #[arg(long, conflicts_with = "scan_system")]
+    pub no_scan_system: bool,
+
+    /// Enable extraction of clear-text credentials (auth headers, password pairs, hash dumps)
+    #[arg(long, conflicts_with = "no_scan_credentials")]
+    pub scan_credentials: bool,
+
+    /// Disable extraction of clear-text credentials
+    #[arg(long, conflicts_with = "scan_credentials")]
+    pub no_scan_credentials: bool,
+
+    /// Enable magnet URI and eD2k link extraction from string spans
+    #[arg(long, conflicts_with = "no_scan_p2p")]
+    pub scan_p2p: bool,
+
+    /// Disable magnet URI and eD2k link extraction from string spans
+    #[arg(long, conflicts_with = "scan_p2p")]
+    pub no_scan_p2p: bool,
+
     /// Override minimum string length when scanning
     #[arg(long)]
     pub string_min_len: Option<usize>,
 
+    /// Override minimum UTF-8 string length (defaults to the string minimum)
+    #[arg(long)]
+    pub string_min_len_utf8: Option<usize>,
+
+    /// Override minimum UTF-16 string length in code units (defaults to the string minimum)
+    #[arg(long)]
+    pub string_min_len_utf16: Option<usize>,
+
+    /// Extract string artefacts only outside carved files (implies --scan-strings)
+    #[arg(long)]
+    pub strings_gaps_only: bool,
+
     /// Enable entropy-based region detection
     #[arg(long)]
     pub scan_entropy: bool,
@@ -106,10 +310,38 @@ pub struct CliOptions {
     #[arg(long)]
     pub entropy_threshold: Option<f64>,
 
+    /// Drop PNG, 7z, EVTX, registry, and PST hits whose first checksum fails
+    #[arg(long)]
+    pub hit_prefilter: bool,
+
+    /// Write the decompressed data of each carved gzip member next to it
+    #[arg(long)]
+    pub gzip_decompress: bool,
+
     /// Enable SQLite page-level URL recovery when DB parsing fails
     #[arg(long)]
     pub scan_sqlite_pages: bool,
 
+    /// YAML catalog of SQLite application databases to add to the built-in one
+    #[arg(long)]
+    pub sqlite_app_catalog: Option<PathBuf>,
+
+    /// Also scan the changed blocks of NTFS Volume Shadow Copies
+    #[arg(long)]
+    pub scan_shadow_copies: bool,
+
+    /// Read the MFT of NTFS volumes and attribute carved files to their paths
+    #[arg(long)]
+    pub ntfs: bool,
+
+    /// Only scan space the NTFS, FAT, or ext file systems in the evidence mark as free
+    #[arg(long)]
+    pub unallocated_only: bool,
+
+    /// Record the partition, relative sector, and cluster of each carved file and artefact
+    #[arg(long)]
+    pub volume_locations: bool,
+
     /// Stop after scanning this many bytes (approximate limit)
     #[arg(long)]
     pub max_bytes: Option<u64>,
@@ -122,6 +354,43 @@ pub struct CliOptions {
     #[arg(long)]
     pub max_files: Option<u64>,
 
+    /// Stop a carve that runs longer than this many seconds (0 = no limit)
+    #[arg(long, value_name = "SECS")]
+    pub carve_timeout_secs: Option<u64>,
+
+    /// When kept carves are flushed to stable storage: none, periodic, or
+    /// per-file (overrides carve_fsync)
+    #[arg(long, value_enum)]
+    pub carve_fsync: Option<CarveFsync>,
+
+    /// Copy this share of carved files per type (0.01 = 1%) to qa_sample/
+    #[arg(long, value_name = "RATE")]
+    pub qa_sample: Option<f64>,
+
+    /// Seed for --qa-sample; the same seed picks the same files
+    #[arg(long, value_name = "SEED")]
+    pub qa_sample_seed: Option<u64>,
+
+    /// List uncovered extents of at least this many bytes in summaries/slack_extents.jsonl
+    #[arg(long, value_name = "BYTES", value_parser = crate::config::units::parse_byte_size)]
+    pub export_slack: Option<u64>,
+
+    /// Export this many bytes around each carve attempt to hit_context/
+    #[arg(long, value_name = "BYTES", value_parser = crate::config::units::parse_byte_size)]
+    pub hit_context_window: Option<u64>,
+
+    /// Encrypt carved files and metadata to this age X25519 recipient (age1...)
+    #[arg(long, value_name = "RECIPIENT")]
+    pub encrypt_to: Option<String>,
+
+    /// Directory for scratch files of derived processing (default: the run directory)
+    #[arg(long, value_name = "DIR")]
+    pub temp_dir: Option<PathBuf>,
+
+    /// Most bytes of scratch files derived processing may hold at once
+    #[arg(long, value_name = "BYTES", value_parser = crate::config::units::parse_byte_size)]
+    pub temp_space_limit: Option<u64>,
+
     /// Limit address space usage in MiB (Unix only)
     #[arg(long)]
     pub max_memory_mib: Option<u64>,
@@ -138,29 +407,66 @@ pub struct CliOptions {
     #[arg(long)]
     pub resume_from: Option<PathBuf>,
 
+    /// Resume with a warning even when sampled evidence regions differ from the checkpoint
+    #[arg(long, requires = "resume_from")]
+    pub allow_evidence_change: bool,
+
     /// Provide evidence SHA-256 (hex) for metadata output
     #[arg(long)]
     pub evidence_sha256: Option<String>,
 
-    /// Compute evidence SHA-256 before scanning (extra full pass)
+    /// Compute evidence SHA-256 from the chunks the scan reads (no extra pass)
     #[arg(long)]
     pub compute_evidence_sha256: bool,
 
+    /// Check an EWF image against its stored MD5 before scanning (extra full pass)
+    #[arg(long)]
+    pub verify_evidence_hash: bool,
+
+    /// Memory-map a raw image instead of reading it with pread (falls back to
+    /// pread when mapping fails)
+    #[arg(long)]
+    pub mmap: bool,
+
     /// Disable ZIP carving (skips zip/docx/xlsx/pptx)
     #[arg(long)]
     pub disable_zip: bool,
 
-    /// Limit carving to these file types (comma-separated list)
-    #[arg(long, value_delimiter = ',')]
+    /// Limit carving to these file types: comma-separated types or config
+    /// `type_groups`, `-name` removes one (e.g. `images,-gif,documents,+sqlite`;
+    /// a leading `-name` starts from all types)
+    #[arg(long, value_delimiter = ',', allow_hyphen_values = true)]
     pub types: Option<Vec<String>>,
 
     /// Enable only these file types (alias for --types)
-    #[arg(long, value_delimiter = ',', conflicts_with = "types")]
+    #[arg(
+        long,
+        value_delimiter = ',',
+        allow_hyphen_values = true,
+        conflicts_with = "types"
+    )]
     pub enable_types: Option<Vec<String>>,
 
+    /// Carve using Scalpel/Foremost rules from this file instead of config file types
+    #[arg(long)]
+    pub scalpel_conf: Option<PathBuf>,
+
     /// Dry run mode: scan and count but don't write files
     #[arg(long)]
     pub dry_run: bool,
+    /// Take over another run's lock on the run output directory before starting
+    #[arg(long)]
+    pub force_unlock: bool,
+
+    /// Run another pass into an existing run directory, appending metadata
+    /// under the next pass_id (reuses the run id and evidence hash)
+    #[arg(long, value_name = "RUN_DIR", conflicts_with_all = ["dry_run", "resume_from"])]
+    pub append_to: Option<PathBuf>,
+
+    /// Also write the run's result.json (status, exit code, stop reason) to
+    /// this path; written even when the run fails before its directory exists
+    #[arg(long, value_name = "PATH")]
+    pub result_path: Option<PathBuf>,
 
     /// Validate carved files after extraction (runs file magic check)
     #[arg(long)]
@@ -169,6 +475,22 @@ pub struct CliOptions {
     /// Remove files that fail post-carving validation (requires --validate-carved)
     #[arg(long, requires = "validate_carved")]
     pub remove_invalid: bool,
+
+    /// Log every pipeline decision affecting this evidence offset (hex with 0x, or decimal)
+    #[arg(long, value_parser = crate::pipeline::trace::parse_offset)]
+    pub trace_offset: Option<u64>,
+
+    /// Bytes on either side of --trace-offset to trace (default 4096)
+    #[arg(long, value_parser = crate::config::units::parse_byte_size, requires = "trace_offset")]
+    pub trace_window: Option<u64>,
+
+    /// Time chunks, carves, and queue waits into profile.parquet and summaries/profile.json
+    #[arg(long)]
+    pub profile_throughput: bool,
+
+    /// Snapshot the metadata into snapshots/ every SECS seconds while the run continues
+    #[arg(long, value_name = "SECS")]
+    pub metadata_snapshot_interval: Option<u64>,
 }
 
 pub fn parse() -> CliOptions {
@@ -183,6 +505,8 @@ pub fn get_types_filter(opts: &CliOptions) -> Option<&Vec<String>> {
 #[cfg(test)]
 mod tests {
     use super::CliOptions;
+    use crate::config::{CarveFsync, EvidenceTransform, PipelineMode, ScanProfile};
+    use crate::export::ExportFormat;
     use clap::Parser;
     use std::path::PathBuf;
 
@@ -194,6 +518,20 @@ mod tests {
         assert!(opts.disable_zip);
     }
 
+    #[test]
+    fn parses_stdin_input_and_stream_window() {
+        let opts = CliOptions::try_parse_from([
+            "SwiftBeaver",
+            "--input",
+            "-",
+            "--stream-window-mib",
+            "256",
+        ])
+        .expect("parse");
+        assert_eq!(opts.input, Some(PathBuf::from("-")));
+        assert_eq!(opts.stream_window_mib, 256);
+    }
+
     #[test]
     fn parses_utf16_flag() {
         let opts =
@@ -216,6 +554,19 @@ mod tests {
         assert_eq!(types, vec!["jpeg", "png", "sqlite"]);
     }
 
+    #[test]
+    fn parses_types_expression_with_exclusions() {
+        let opts = CliOptions::try_parse_from([
+            "SwiftBeaver",
+            "--input",
+            "image.dd",
+            "--types",
+            "-gif,+sqlite",
+        ])
+        .expect("parse");
+        assert_eq!(opts.types.expect("types"), vec!["-gif", "+sqlite"]);
+    }
+
     #[test]
     fn parses_scan_url_flags() {
         let opts =
@@ -347,6 +698,109 @@ mod tests {
         assert!(opts.dry_run);
     }
 
+    #[test]
+    fn parses_force_unlock_flag() {
+        let opts =
+            CliOptions::try_parse_from(["SwiftBeaver", "--input", "image.dd", "--force-unlock"])
+                .expect("parse");
+        assert!(opts.force_unlock);
+    }
+
+    #[test]
+    fn append_to_conflicts_with_resume() {
+        let opts = CliOptions::try_parse_from([
+            "SwiftBeaver",
+            "--input",
+            "image.dd",
+            "--append-to",
+            "out/run1",
+        ])
+        .expect("parse");
+        assert_eq!(opts.append_to, Some(PathBuf::from("out/run1")));
+        assert!(
+            CliOptions::try_parse_from([
+                "SwiftBeaver",
+                "--input",
+                "image.dd",
+                "--append-to",
+                "out/run1",
+                "--resume-from",
+                "resume.json",
+            ])
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn parses_scan_shadow_copies_flag() {
+        let opts = CliOptions::try_parse_from([
+            "SwiftBeaver",
+            "--input",
+            "image.dd",
+            "--scan-shadow-copies",
+        ])
+        .expect("parse");
+        assert!(opts.scan_shadow_copies);
+    }
+
+    #[test]
+    fn parses_ntfs_flag() {
+        let opts = CliOptions::try_parse_from(["SwiftBeaver", "--input", "image.dd", "--ntfs"])
+            .expect("parse");
+        assert!(opts.ntfs);
+    }
+
+    #[test]
+    fn parses_unallocated_only_flag() {
+        let opts = CliOptions::try_parse_from([
+            "SwiftBeaver",
+            "--input",
+            "image.dd",
+            "--unallocated-only",
+        ])
+        .expect("parse");
+        assert!(opts.unallocated_only);
+    }
+
+    #[test]
+    fn parses_carve_fsync_policy() {
+        let opts = CliOptions::try_parse_from([
+            "SwiftBeaver",
+            "--input",
+            "image.dd",
+            "--carve-fsync",
+            "per-file",
+        ])
+        .expect("parse");
+        assert_eq!(opts.carve_fsync, Some(CarveFsync::PerFile));
+    }
+
+    #[test]
+    fn parses_pipeline_mode() {
+        let opts = CliOptions::try_parse_from([
+            "SwiftBeaver",
+            "--input",
+            "image.dd",
+            "--pipeline-mode",
+            "single-threaded",
+        ])
+        .expect("parse");
+        assert_eq!(opts.pipeline_mode, Some(PipelineMode::SingleThreaded));
+    }
+
+    #[test]
+    fn parses_evidence_transform() {
+        let opts = CliOptions::try_parse_from([
+            "SwiftBeaver",
+            "--input",
+            "image.dd",
+            "--evidence-transform",
+            "byte-swap16",
+        ])
+        .expect("parse");
+        assert_eq!(opts.evidence_transform, Some(EvidenceTransform::ByteSwap16));
+    }
+
     #[test]
     fn parses_validate_carved_flag() {
         let opts =
@@ -390,6 +844,146 @@ mod tests {
         assert_eq!(types, vec!["jpeg", "png", "gif"]);
     }
 
+    #[test]
+    fn parses_import_signatures_without_input() {
+        let opts = CliOptions::try_parse_from([
+            "SwiftBeaver",
+            "import-signatures",
+            "photorec.sig",
+            "--max-size",
+            "4096",
+        ])
+        .expect("parse");
+        assert!(opts.input.is_none());
+        match opts.command {
+            Some(super::Command::ImportSignatures(args)) => {
+                assert_eq!(args.path, PathBuf::from("photorec.sig"));
+                assert_eq!(args.format, super::SignatureFormat::Photorec);
+                assert_eq!(args.max_size, 4096);
+            }
+            other => panic!("unexpected command: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn parses_config_check() {
+        let opts = CliOptions::try_parse_from([
+            "SwiftBeaver",
+            "config",
+            "check",
+            "custom.yml",
+            "--chunk-size-mib",
+            "64",
+        ])
+        .expect("parse");
+        match opts.command {
+            Some(super::Command::Config(super::ConfigCommand::Check(args))) => {
+                assert_eq!(args.path, Some(PathBuf::from("custom.yml")));
+                assert_eq!(args.chunk_size_mib, 64);
+            }
+            other => panic!("unexpected command: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn parses_decrypt() {
+        let opts = CliOptions::try_parse_from([
+            "SwiftBeaver",
+            "decrypt",
+            "out/run",
+            "--identity",
+            "key.txt",
+            "-o",
+            "plain",
+        ])
+        .expect("parse");
+        match opts.command {
+            Some(super::Command::Decrypt(args)) => {
+                assert_eq!(args.path, PathBuf::from("out/run"));
+                assert_eq!(args.identity, PathBuf::from("key.txt"));
+                assert_eq!(args.output, PathBuf::from("plain"));
+            }
+            other => panic!("unexpected command: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn parses_list_types() {
+        let opts = CliOptions::try_parse_from([
+            "SwiftBeaver",
+            "list-types",
+            "--profile",
+            "memory",
+            "--json",
+        ])
+        .expect("parse");
+        match opts.command {
+            Some(super::Command::ListTypes(args)) => {
+                assert!(args.json && !args.detailed);
+                assert_eq!(args.profile, Some(ScanProfile::Memory));
+            }
+            other => panic!("unexpected command: {other:?}"),
+        }
+        assert!(
+            CliOptions::try_parse_from(["SwiftBeaver", "list-types", "--json", "--detailed"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn parses_salvage() {
+        let opts =
+            CliOptions::try_parse_from(["SwiftBeaver", "salvage", "out/run", "-o", "rescued"])
+                .expect("parse");
+        match opts.command {
+            Some(super::Command::Salvage(args)) => {
+                assert_eq!(args.run_dir, PathBuf::from("out/run"));
+                assert_eq!(args.output, Some(PathBuf::from("rescued")));
+            }
+            other => panic!("unexpected command: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn parses_export() {
+        let opts = CliOptions::try_parse_from([
+            "SwiftBeaver",
+            "export",
+            "out/run",
+            "--format",
+            "timesketch",
+        ])
+        .expect("parse");
+        match opts.command {
+            Some(super::Command::Export(args)) => {
+                assert_eq!(args.run_dir, PathBuf::from("out/run"));
+                assert_eq!(args.format, ExportFormat::Timesketch);
+                assert_eq!(args.output, None);
+            }
+            other => panic!("unexpected command: {other:?}"),
+        }
+        assert!(CliOptions::try_parse_from(["SwiftBeaver", "export", "out/run"]).is_err());
+    }
+
+    #[test]
+    fn parses_scalpel_conf() {
+        let opts = CliOptions::try_parse_from([
+            "SwiftBeaver",
+            "--input",
+            "image.dd",
+            "--scalpel-conf",
+            "scalpel.conf",
+        ])
+        .expect("parse");
+        assert_eq!(opts.scalpel_conf, Some(PathBuf::from("scalpel.conf")));
+    }
+
+    #[test]
+    fn input_required_without_subcommand() {
+        let result = CliOptions::try_parse_from(["SwiftBeaver", "--dry-run"]);
+        assert!(result.is_err(), "input should be required for carve runs");
+    }
+
     #[test]
     fn types_and_enable_types_conflict() {
         let result = CliOptions::try_parse_from([
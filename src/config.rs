@@ -1,23 +1,275 @@
-use std::path::Path;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 
-use anyhow::Result;
-use serde::Deserialize;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 
-#[derive(Debug, Deserialize, Clone)]
+pub mod units;
+mod validate;
+
+pub use validate::{
+    ConfigIssue, IssueSeverity, KNOWN_VALIDATORS, check_config, validate_config,
+    validate_selected_types,
+};
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct FileTypeConfig {
     pub id: String,
     pub extensions: Vec<String>,
     pub header_patterns: Vec<PatternConfig>,
     pub footer_patterns: Vec<PatternConfig>,
+    #[serde(deserialize_with = "units::bytes")]
     pub max_size: u64,
+    #[serde(deserialize_with = "units::bytes")]
     pub min_size: u64,
     pub validator: String,
     #[serde(default)]
     pub require_eocd: bool,
+    #[serde(default)]
+    pub footer_mode: FooterMode,
+    /// Which carved files of this type stay on disk.
+    #[serde(default)]
+    pub retention: RetentionPolicy,
+    /// Overrides `Config::hit_cluster_window` for this type.
+    #[serde(
+        default,
+        deserialize_with = "units::opt_bytes",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub hit_cluster_window: Option<u64>,
+    /// When set, handlers that read the file's size from its own structures
+    /// (OLE FAT, ZIP EOCD, PDF linearization) carve up to this multiple of
+    /// that size instead of `max_size`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub declared_size_factor: Option<f64>,
+    /// Post-carve processors for this type. `None` uses the built-in defaults
+    /// (`sqlite_browser`, `apple_databases`, and `sqlite_apps` for `sqlite`);
+    /// an empty list disables them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_processors: Option<Vec<PostProcessorConfig>>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+/// A post-carve processor attached to a file type.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PostProcessorConfig {
+    /// Extract browser history, cookies, and downloads from carved SQLite databases.
+    SqliteBrowser,
+    /// Reconstruct cached HTTP responses from Chromium and Firefox cache entries.
+    BrowserCache,
+    /// Report connection tuples, DNS queries, and HTTP hosts from packet captures.
+    PcapArtefacts,
+    /// Report systemd journal entries as log events.
+    JournalEvents,
+    /// Tag container image layers and tarballs and read containerd metadata.
+    ContainerArtefacts,
+    /// Read Apple Notes, iMessage, and KnowledgeC databases.
+    AppleDatabases,
+    /// Record image dimensions and how much of a JPEG decodes cleanly.
+    ImageInfo,
+    /// Record camera make, model, serial, lens, and capture time of photos
+    /// and group them by device and session.
+    PhotoExif,
+    /// Report info hashes, names, trackers, and DHT nodes from `.torrent` files.
+    P2pArtefacts,
+    /// Record encryption metadata of password-protected OOXML documents.
+    EncryptedDocuments,
+    /// Classify SQLite databases by the application catalog and read the
+    /// rows named by the extraction plans of each matching application.
+    SqliteApps,
+    /// Read cookies from Safari binarycookies and Netscape `cookies.txt` files.
+    BrowserCookies,
+    /// Record executable names, run counts, and run times of Windows
+    /// Prefetch files.
+    PrefetchFiles,
+    /// Record target paths, arguments, and target times of Windows shortcuts.
+    Shortcuts,
+    /// Record the kind and architecture of executables, and the compile time
+    /// and import hash of PE images.
+    Executables,
+    /// Write each thumbnail of a Windows thumbnail cache as its own file and
+    /// record its cache ID.
+    ThumbcacheImages,
+    /// Decode binary plists and record their contents as JSON.
+    PlistContents,
+    /// Rebuild the `moov` index of MP4 and QuickTime recordings that were
+    /// never finalized and write a playable copy.
+    VideoRepair {
+        /// Intact file from the same device to take the decoder setup and
+        /// frame duration from; without one, H.264 parameter sets in the
+        /// media data are used.
+        #[serde(default)]
+        donor: Option<PathBuf>,
+        /// Frames per second when there is no donor.
+        #[serde(default = "default_video_frame_rate")]
+        frame_rate: f64,
+    },
+    /// Run an external program on each carved file.
+    Command {
+        /// Name used in logs and for the output directory; defaults to the program name.
+        #[serde(default)]
+        name: Option<String>,
+        program: String,
+        /// Arguments; `{path}`, `{type}`, and `{offset}` are substituted per file.
+        #[serde(default)]
+        args: Vec<String>,
+        #[serde(default = "default_command_timeout_secs")]
+        timeout_secs: u64,
+    },
+    /// Score each carved file with a user-supplied model server and record
+    /// its labels in `classifications`.
+    Classifier {
+        /// Name recorded in `classifier` and used for the output directory;
+        /// defaults to the program name. Types naming the same classifier
+        /// share one server process.
+        #[serde(default)]
+        name: Option<String>,
+        program: String,
+        #[serde(default)]
+        args: Vec<String>,
+        /// Seconds to wait for the answer for one file.
+        #[serde(default = "default_command_timeout_secs")]
+        timeout_secs: u64,
+        /// Labels scoring below this are not recorded.
+        #[serde(default)]
+        min_score: f64,
+        /// Highest-scoring labels recorded per file.
+        #[serde(default = "default_classifier_top_k")]
+        top_k: u32,
+    },
+}
+
+/// Condition under which a carve is moved to the quarantine area instead of
+/// `carved/`. Every condition a rule sets must hold.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Default)]
+pub struct QuarantineRule {
+    /// Recorded in the quarantine index.
+    pub name: String,
+    /// File of MD5 or SHA-256 digests, one per line.
+    #[serde(default)]
+    pub hash_list: Option<PathBuf>,
+    /// File type ids the rule applies to (empty = any).
+    #[serde(default)]
+    pub file_types: Vec<String>,
+    /// Classifier labels, any one of which matches.
+    #[serde(default)]
+    pub labels: Vec<String>,
+    /// Classifier that must give the label (any when unset).
+    #[serde(default)]
+    pub classifier: Option<String>,
+    /// Lowest label score that matches.
+    #[serde(default)]
+    pub min_score: f64,
+}
+
+/// A hook run when a run completes, fails, or saves a checkpoint.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum NotificationConfig {
+    /// POST the notification JSON to a plain `http://` URL.
+    Webhook {
+        url: String,
+        #[serde(default = "default_notify_events")]
+        on: Vec<NotifyEvent>,
+        #[serde(default = "default_notify_timeout_secs")]
+        timeout_secs: u64,
+    },
+    /// Mail the notification through an SMTP relay (no TLS or authentication).
+    Email {
+        /// Relay as `host` or `host:port` (port 25 by default).
+        smtp_server: String,
+        from: String,
+        to: Vec<String>,
+        #[serde(default = "default_notify_events")]
+        on: Vec<NotifyEvent>,
+        #[serde(default = "default_notify_timeout_secs")]
+        timeout_secs: u64,
+    },
+    /// Run a local program with the notification JSON on stdin.
+    Command {
+        program: String,
+        /// Arguments; `{run_dir}`, `{run_id}`, and `{event}` are substituted.
+        /// Defaults to the run directory alone.
+        #[serde(default)]
+        args: Vec<String>,
+        #[serde(default = "default_notify_events")]
+        on: Vec<NotifyEvent>,
+        #[serde(default = "default_notify_timeout_secs")]
+        timeout_secs: u64,
+    },
+}
+
+impl NotificationConfig {
+    pub fn events(&self) -> &[NotifyEvent] {
+        match self {
+            Self::Webhook { on, .. } | Self::Email { on, .. } | Self::Command { on, .. } => on,
+        }
+    }
+}
+
+/// When a notification hook fires.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NotifyEvent {
+    /// The pipeline finished, including runs stopped early by Ctrl+C or a limit.
+    Completed,
+    /// The pipeline returned an error.
+    Failed,
+    /// A resume checkpoint was written.
+    Checkpoint,
+}
+
+impl NotifyEvent {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Completed => "completed",
+            Self::Failed => "failed",
+            Self::Checkpoint => "checkpoint",
+        }
+    }
+}
+
+/// Where the `footer` validator ends a carve relative to the footer match.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum FooterMode {
+    /// End after the first footer (footer included).
+    #[default]
+    Inclusive,
+    /// End before the first footer after the header (footer excluded).
+    Exclusive,
+    /// End after the last footer within `max_size` (footer included).
+    Last,
+}
+
+/// Which carved files of a type are kept on disk. Metadata is recorded for
+/// every carve either way.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RetentionPolicy {
+    /// Keep every carved file.
+    #[default]
+    KeepAll,
+    /// Delete carves that failed validation.
+    KeepValidated,
+    /// Delete every carve once post-processors have read it.
+    MetadataOnly,
+}
+
+impl RetentionPolicy {
+    /// Whether a carve that did (`validated`) or did not pass validation
+    /// stays on disk under this policy.
+    pub fn keeps(self, validated: bool) -> bool {
+        match self {
+            RetentionPolicy::KeepAll => true,
+            RetentionPolicy::KeepValidated => validated,
+            RetentionPolicy::MetadataOnly => false,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct PatternConfig {
     pub id: String,
     pub hex: String,
@@ -30,13 +282,83 @@ pub enum QuicktimeMode {
     Mp4,
 }
 
+/// Built-in adjustments for a kind of evidence (`--profile`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum ScanProfile {
+    /// Disk images; the config is used as loaded.
+    #[default]
+    Disk,
+    /// Memory images, crash dumps, and hibernation files.
+    Memory,
+}
+
+/// Digest of the whole evidence computed while scanning (`evidence_hashes`).
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum EvidenceDigest {
+    Md5,
+    Sha1,
+    Sha256,
+}
+
+/// When carved files are flushed to stable storage (`--carve-fsync`).
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum CarveFsync {
+    /// Left to the operating system.
+    #[default]
+    None,
+    /// Every `carve_fsync_interval` kept files, and at the end of the run.
+    Periodic,
+    /// Each kept file before the next carve (forensic strict mode).
+    PerFile,
+}
+
+/// How the pipeline stages run (`--pipeline-mode`).
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum PipelineMode {
+    /// Scan, carve, string, and metadata stages on worker threads.
+    #[default]
+    Threaded,
+    /// Every stage on the calling thread, one chunk at a time.
+    SingleThreaded,
+}
+
+/// Byte-level fix-up applied to the evidence before scanning
+/// (`--evidence-transform`).
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum EvidenceTransform {
+    /// Bytes as acquired.
+    #[default]
+    None,
+    /// Sample the evidence and apply the transform under which the
+    /// signatures match best, if any matches clearly better.
+    Auto,
+    /// Swap the two bytes of every 16-bit word.
+    ByteSwap16,
+    /// Invert every bit.
+    InvertBits,
+}
+
+/// Type changes a profile makes, from `config/profiles/<name>.yml`.
+#[derive(Debug, Deserialize)]
+struct ProfileOverlay {
+    #[serde(default)]
+    disable_types: Vec<String>,
+    #[serde(default)]
+    file_types: Vec<FileTypeConfig>,
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct Config {
     pub run_id: String,
+    #[serde(deserialize_with = "units::bytes")]
     pub overlap_bytes: u64,
     #[serde(default)]
     pub max_files: Option<u64>,
-    #[serde(default)]
+    #[serde(default, deserialize_with = "units::opt_mib")]
     pub max_memory_mib: Option<u64>,
     #[serde(default)]
     pub max_open_files: Option<u64>,
@@ -47,26 +369,60 @@ pub struct Config {
     pub enable_email_scan: bool,
     #[serde(default = "default_true")]
     pub enable_phone_scan: bool,
+    #[serde(default = "default_true")]
+    pub enable_log_scan: bool,
+    #[serde(default = "default_true")]
+    pub enable_system_scan: bool,
+    #[serde(default)]
+    pub enable_credential_scan: bool,
+    #[serde(default = "default_true")]
+    pub enable_p2p_scan: bool,
     #[serde(default)]
     pub string_scan_utf16: bool,
     #[serde(default = "default_string_min_len")]
     pub string_min_len: usize,
+    /// Minimum UTF-8 span length in characters (unset = `string_min_len`).
+    #[serde(default)]
+    pub string_min_len_utf8: Option<usize>,
+    /// Minimum UTF-16 span length in code units (unset = `string_min_len`).
+    #[serde(default)]
+    pub string_min_len_utf16: Option<usize>,
     #[serde(default = "default_string_max_len")]
     pub string_max_len: usize,
+    #[serde(default)]
+    pub string_scan_gaps_only: bool,
+    #[serde(default = "default_artefact_summary_top_n")]
+    pub artefact_summary_top_n: usize,
     #[serde(default = "default_gpu_max_hits")]
     pub gpu_max_hits_per_chunk: usize,
     #[serde(default = "default_gpu_max_string_spans")]
     pub gpu_max_string_spans_per_chunk: usize,
+    /// Chunks smaller than this are scanned on the CPU under `--gpu` (0 = no minimum).
+    #[serde(
+        default = "default_gpu_min_chunk_size",
+        deserialize_with = "units::bytes"
+    )]
+    pub gpu_min_chunk_size: u64,
+    /// Chunks queued on a GPU before further chunks go to the CPU (0 = unlimited).
+    #[serde(default = "default_gpu_max_inflight")]
+    pub gpu_max_inflight: usize,
     #[serde(default = "default_parquet_row_group_size")]
     pub parquet_row_group_size: usize,
     #[serde(default)]
     pub enable_entropy_detection: bool,
-    #[serde(default = "default_entropy_window_size")]
+    #[serde(
+        default = "default_entropy_window_size",
+        deserialize_with = "units::bytes_usize"
+    )]
     pub entropy_window_size: usize,
     #[serde(default = "default_entropy_threshold")]
     pub entropy_threshold: f64,
     #[serde(default)]
     pub enable_sqlite_page_recovery: bool,
+    /// YAML catalog of SQLite application databases merged over the
+    /// built-in one; entries with a built-in id replace it.
+    #[serde(default)]
+    pub sqlite_app_catalog: Option<PathBuf>,
     pub opencl_platform_index: Option<usize>,
     pub opencl_device_index: Option<usize>,
     #[serde(default)]
@@ -75,6 +431,189 @@ pub struct Config {
     pub ole_allowed_kinds: Option<Vec<String>>,
     #[serde(default = "default_quicktime_mode")]
     pub quicktime_mode: QuicktimeMode,
+    /// Write the decompressed data of each carved gzip member next to it.
+    #[serde(default)]
+    pub gzip_decompress: bool,
+    /// Minimum distance between carve attempts of one type after a failed attempt (0 = off).
+    #[serde(default, deserialize_with = "units::bytes")]
+    pub hit_cluster_window: u64,
+    /// Drop hits whose first structure fails its checksum in the scan
+    /// workers, before they are queued for carving.
+    #[serde(default)]
+    pub hit_prefilter: bool,
+    /// Carve attempts after which a pattern that rarely carves is disabled
+    /// (0 = never disable).
+    #[serde(default)]
+    pub pattern_disable_min_attempts: u64,
+    /// Highest carved/attempted ratio at which a pattern is disabled.
+    #[serde(default = "default_pattern_disable_max_success")]
+    pub pattern_disable_max_success: f64,
+    /// Share of the larger of two overlapping carves from different handlers
+    /// at which only the more confident one is kept (0 = off).
+    #[serde(default)]
+    pub arbitration_overlap: f64,
+    /// Longest gap between two photos of one device in the same session.
+    #[serde(default = "default_photo_session_gap_secs")]
+    pub photo_session_gap_secs: u64,
+    /// Largest gap, in seconds, at which two clocks dating one carved file agree.
+    #[serde(default = "default_clock_tolerance_secs")]
+    pub clock_tolerance_secs: u64,
+    /// Region size of the per-region rows in `summaries/coverage.json` (0 = none).
+    #[serde(
+        default = "default_coverage_region_size",
+        deserialize_with = "units::bytes"
+    )]
+    pub coverage_region_size: u64,
+    /// Uncovered extents at least this long are listed in
+    /// `summaries/slack_extents.jsonl` (0 = off).
+    #[serde(default, deserialize_with = "units::bytes")]
+    pub slack_export_min_size: u64,
+    /// Look for NTFS LZNT1-compressed units and carve from their decompressed data.
+    #[serde(default)]
+    pub enable_lznt1_scan: bool,
+    /// NTFS compression unit size (16 clusters).
+    #[serde(default = "default_lznt1_unit_size", deserialize_with = "units::bytes")]
+    pub lznt1_unit_size: u64,
+    /// Scan the changed blocks of Volume Shadow Copies after the evidence.
+    #[serde(default)]
+    pub enable_vss_scan: bool,
+    /// Read the MFT of NTFS volumes and record allocated and deleted files.
+    #[serde(default)]
+    pub enable_ntfs_scan: bool,
+    /// Skip scan chunks lying wholly in clusters NTFS marks in use.
+    #[serde(default)]
+    pub ntfs_unallocated_only: bool,
+    /// Skip scan chunks lying wholly in space an NTFS, FAT, or ext file
+    /// system marks in use.
+    #[serde(default)]
+    pub unallocated_only: bool,
+    /// Record the partition, relative sector, and cluster of each carved
+    /// file and artefact.
+    #[serde(default)]
+    pub enable_volume_locations: bool,
+    /// Evidence offset whose pipeline decisions are logged (`--trace-offset`).
+    #[serde(default)]
+    pub trace_offset: Option<u64>,
+    /// Bytes on either side of `trace_offset` that are traced.
+    #[serde(default = "default_trace_window", deserialize_with = "units::bytes")]
+    pub trace_window: u64,
+    /// Time chunks, carves, and queue waits into `profile.parquet` (`--profile-throughput`).
+    #[serde(default)]
+    pub enable_profiling: bool,
+    /// Region size of the per-region rows in `summaries/profile.json` (0 = one region).
+    #[serde(
+        default = "default_profile_region_size",
+        deserialize_with = "units::bytes"
+    )]
+    pub profile_region_size: u64,
+    /// Slowest chunks and carves listed in `summaries/profile.json`.
+    #[serde(default = "default_profile_top_n")]
+    pub profile_top_n: usize,
+    /// Seconds between snapshots of the metadata under `snapshots/`
+    /// (`--metadata-snapshot-interval`, 0 = off).
+    #[serde(default)]
+    pub metadata_snapshot_interval_secs: u64,
+    /// Snapshots kept; older ones are removed (0 = keep all).
+    #[serde(default = "default_metadata_snapshot_keep")]
+    pub metadata_snapshot_keep: usize,
+    /// Memory for decoded blocks of compressed evidence such as EWF (0 = off).
+    #[serde(
+        default = "default_evidence_cache_size",
+        deserialize_with = "units::bytes"
+    )]
+    pub evidence_cache_size: u64,
+    /// Byte swap or bit inversion undone before scanning, or `auto`.
+    #[serde(default)]
+    pub evidence_transform: EvidenceTransform,
+    /// Seconds one carve may take before it stops with a carve error (0 = no limit).
+    #[serde(default)]
+    pub carve_timeout_secs: u64,
+    /// Threaded workers or everything on the calling thread.
+    #[serde(default)]
+    pub pipeline_mode: PipelineMode,
+    /// When kept carves are flushed to stable storage.
+    #[serde(default)]
+    pub carve_fsync: CarveFsync,
+    /// Kept carves per flush with `carve_fsync: periodic`.
+    #[serde(default = "default_carve_fsync_interval")]
+    pub carve_fsync_interval: u64,
+    /// Write buffer per carved output file; larger buffers mean fewer,
+    /// larger writes.
+    #[serde(
+        default = "default_carve_write_buffer",
+        deserialize_with = "units::bytes"
+    )]
+    pub carve_write_buffer: u64,
+    /// Parent of the run's scratch directory; the run directory when unset.
+    #[serde(default)]
+    pub temp_dir: Option<PathBuf>,
+    /// Most bytes of scratch files held at once (0 = no scratch files).
+    #[serde(
+        default = "default_temp_space_limit",
+        deserialize_with = "units::bytes"
+    )]
+    pub temp_space_limit: u64,
+    /// Share of carved files per type copied to `qa_sample/` (0 = off).
+    #[serde(default)]
+    pub qa_sample_rate: f64,
+    /// Seed of the QA sample selection.
+    #[serde(default)]
+    pub qa_sample_seed: u64,
+    /// Bytes per window exported to `hit_context/` (0 = off).
+    #[serde(default, deserialize_with = "units::bytes")]
+    pub hit_context_window: u64,
+    /// Bytes of each window before the hit offset.
+    #[serde(default, deserialize_with = "units::bytes")]
+    pub hit_context_before: u64,
+    /// File types whose carve attempts are exported (empty = all).
+    #[serde(default)]
+    pub hit_context_types: Vec<String>,
+    /// Also export a window at the start of each high-entropy region.
+    #[serde(default)]
+    pub hit_context_entropy: bool,
+    /// Windows exported at most (0 = no limit).
+    #[serde(default)]
+    pub hit_context_max_windows: u64,
+    /// Digests of the evidence computed from the chunks the scan reads.
+    #[serde(default)]
+    pub evidence_hashes: Vec<EvidenceDigest>,
+    /// Scanned evidence regions hashed into a checkpoint and read again on
+    /// resume (0 = none).
+    #[serde(default = "default_resume_verify_samples")]
+    pub resume_verify_samples: usize,
+    /// Size of each region hashed for `resume_verify_samples`.
+    #[serde(
+        default = "default_resume_verify_sample_size",
+        deserialize_with = "units::bytes"
+    )]
+    pub resume_verify_sample_size: u64,
+    /// Resume with a warning instead of refusing when a sampled region
+    /// differs (`--allow-evidence-change`).
+    #[serde(default)]
+    pub resume_allow_evidence_change: bool,
+    /// Rules moving matching carves to the quarantine area.
+    #[serde(default)]
+    pub quarantine_rules: Vec<QuarantineRule>,
+    /// Quarantine area; `<run dir>/quarantine` when unset.
+    #[serde(default)]
+    pub quarantine_dir: Option<PathBuf>,
+    /// age X25519 recipient (`age1...`) quarantined files are encrypted to;
+    /// `encryption_recipient` when unset.
+    #[serde(default)]
+    pub quarantine_recipient: Option<String>,
+    /// age X25519 recipient (`age1...`) all run outputs are encrypted to.
+    #[serde(default)]
+    pub encryption_recipient: Option<String>,
+    /// Memory filesystem holding plaintext outputs of an encrypted run until
+    /// they are sealed; `/dev/shm` when unset.
+    #[serde(default)]
+    pub encryption_staging_dir: Option<PathBuf>,
+    /// Hooks notified when the run completes, fails, or checkpoints.
+    #[serde(default)]
+    pub notifications: Vec<NotificationConfig>,
+    /// Named sets of file types usable in `--types` expressions.
+    #[serde(default)]
+    pub type_groups: BTreeMap<String, Vec<String>>,
     pub file_types: Vec<FileTypeConfig>,
 }
 
@@ -86,12 +625,15 @@ pub struct LoadedConfig {
 
 pub fn load_config(path: Option<&Path>) -> Result<LoadedConfig> {
     let bytes: Vec<u8> = if let Some(p) = path {
-        std::fs::read(p)?
+        std::fs::read(p).with_context(|| format!("read config {}", p.display()))?
     } else {
         include_bytes!("../config/default.yml").to_vec()
     };
 
-    let mut config: Config = serde_yaml::from_slice(&bytes)?;
+    let mut config: Config = serde_yaml::from_slice(&bytes).with_context(|| match path {
+        Some(p) => format!("invalid config {}", p.display()),
+        None => "invalid built-in config".to_string(),
+    })?;
     if config.run_id.trim().is_empty() {
         config.run_id = generate_run_id();
     }
@@ -125,6 +667,38 @@ fn rand_suffix() -> String {
     format!("{:08x}", nanos)
 }
 
+fn default_command_timeout_secs() -> u64 {
+    30
+}
+
+fn default_classifier_top_k() -> u32 {
+    3
+}
+
+fn default_video_frame_rate() -> f64 {
+    30.0
+}
+
+fn default_notify_events() -> Vec<NotifyEvent> {
+    vec![NotifyEvent::Completed, NotifyEvent::Failed]
+}
+
+fn default_notify_timeout_secs() -> u64 {
+    10
+}
+
+fn default_carve_fsync_interval() -> u64 {
+    256
+}
+
+fn default_carve_write_buffer() -> u64 {
+    crate::carve::DEFAULT_WRITE_BUFFER as u64
+}
+
+fn default_temp_space_limit() -> u64 {
+    2 * 1024 * 1024 * 1024
+}
+
 fn default_string_min_len() -> usize {
     6
 }
@@ -133,6 +707,14 @@ fn default_string_max_len() -> usize {
     1024
 }
 
+fn default_artefact_summary_top_n() -> usize {
+    100
+}
+
+fn default_evidence_cache_size() -> u64 {
+    256 * 1024 * 1024
+}
+
 fn default_gpu_max_hits() -> usize {
     1_000_000
 }
@@ -141,10 +723,62 @@ fn default_gpu_max_string_spans() -> usize {
     250_000
 }
 
+fn default_gpu_min_chunk_size() -> u64 {
+    4 * 1024 * 1024
+}
+
+fn default_gpu_max_inflight() -> usize {
+    2
+}
+
 fn default_parquet_row_group_size() -> usize {
     10_000
 }
 
+fn default_pattern_disable_max_success() -> f64 {
+    0.001
+}
+
+fn default_photo_session_gap_secs() -> u64 {
+    3600
+}
+
+fn default_clock_tolerance_secs() -> u64 {
+    120
+}
+
+fn default_coverage_region_size() -> u64 {
+    1024 * 1024 * 1024
+}
+
+fn default_lznt1_unit_size() -> u64 {
+    64 * 1024
+}
+
+fn default_trace_window() -> u64 {
+    4096
+}
+
+fn default_profile_region_size() -> u64 {
+    1024 * 1024 * 1024
+}
+
+fn default_profile_top_n() -> usize {
+    20
+}
+
+fn default_metadata_snapshot_keep() -> usize {
+    4
+}
+
+fn default_resume_verify_samples() -> usize {
+    16
+}
+
+fn default_resume_verify_sample_size() -> u64 {
+    64 * 1024
+}
+
 fn default_quicktime_mode() -> QuicktimeMode {
     QuicktimeMode::Mov
 }
@@ -162,11 +796,47 @@ fn default_true() -> bool {
 }
 
 impl Config {
+    /// Apply a built-in scan profile. Call before [`Config::merge_cli`] so
+    /// explicit flags still override the profile.
+    ///
+    /// `memory` disables carvers whose short signatures match constantly in
+    /// RAM, adds crash dump, hibernation, hive bin, and process pool detectors,
+    /// turns on UTF-16 string scanning and credential extraction, and turns off
+    /// phone extraction.
+    pub fn apply_profile(&mut self, profile: ScanProfile) -> Result<()> {
+        let bytes: &[u8] = match profile {
+            ScanProfile::Disk => return Ok(()),
+            ScanProfile::Memory => include_bytes!("../config/profiles/memory.yml"),
+        };
+        let overlay: ProfileOverlay =
+            serde_yaml::from_slice(bytes).context("invalid built-in profile")?;
+        self.file_types
+            .retain(|ft| !overlay.disable_types.contains(&ft.id));
+        for file_type in overlay.file_types {
+            if !self.file_types.iter().any(|ft| ft.id == file_type.id) {
+                self.file_types.push(file_type);
+            }
+        }
+        self.enable_string_scan = true;
+        self.string_scan_utf16 = true;
+        self.enable_credential_scan = true;
+        self.enable_phone_scan = false;
+        Ok(())
+    }
+
     /// Merge CLI options into the config.
     /// CLI flags override config file values.
     pub fn merge_cli(&mut self, cli: &crate::cli::CliOptions) {
         // String scanning
-        if cli.scan_strings || cli.scan_utf16 || cli.scan_urls || cli.scan_emails || cli.scan_phones
+        if cli.scan_strings
+            || cli.scan_utf16
+            || cli.scan_urls
+            || cli.scan_emails
+            || cli.scan_phones
+            || cli.scan_logs
+            || cli.scan_system
+            || cli.scan_credentials
+            || cli.scan_p2p
         {
             self.enable_string_scan = true;
         }
@@ -198,10 +868,57 @@ impl Config {
             self.enable_phone_scan = false;
         }
 
+        // Syslog line scanning
+        if cli.scan_logs {
+            self.enable_log_scan = true;
+        }
+        if cli.no_scan_logs {
+            self.enable_log_scan = false;
+        }
+
+        // SSH key and shell history scanning
+        if cli.scan_system {
+            self.enable_system_scan = true;
+        }
+        if cli.no_scan_system {
+            self.enable_system_scan = false;
+        }
+
+        // Credential scanning
+        if cli.scan_credentials {
+            self.enable_credential_scan = true;
+        }
+        if cli.no_scan_credentials {
+            self.enable_credential_scan = false;
+        }
+
+        // Magnet URI and eD2k link scanning
+        if cli.scan_p2p {
+            self.enable_p2p_scan = true;
+        }
+        if cli.no_scan_p2p {
+            self.enable_p2p_scan = false;
+        }
+
         // String length
         if let Some(min_len) = cli.string_min_len {
             self.string_min_len = min_len;
         }
+        if let Some(min_len) = cli.string_min_len_utf8 {
+            self.string_min_len_utf8 = Some(min_len);
+        }
+        if let Some(min_len) = cli.string_min_len_utf16 {
+            self.string_min_len_utf16 = Some(min_len);
+        }
+        if cli.strings_gaps_only {
+            self.enable_string_scan = true;
+            self.string_scan_gaps_only = true;
+        }
+
+        // Chunk overlap
+        if let Some(overlap_kib) = cli.overlap_kib {
+            self.overlap_bytes = overlap_kib.saturating_mul(1024);
+        }
 
         // Output limits
         if let Some(max_files) = cli.max_files {
@@ -226,9 +943,118 @@ impl Config {
             self.entropy_threshold = threshold;
         }
 
+        // Hit prefilter
+        if cli.hit_prefilter {
+            self.hit_prefilter = true;
+        }
+
+        // Gzip payloads
+        if cli.gzip_decompress {
+            self.gzip_decompress = true;
+        }
+
         // SQLite page recovery
         if cli.scan_sqlite_pages {
             self.enable_sqlite_page_recovery = true;
         }
+
+        // SQLite application catalog
+        if let Some(path) = &cli.sqlite_app_catalog {
+            self.sqlite_app_catalog = Some(path.clone());
+        }
+
+        // Shadow copies
+        if cli.scan_shadow_copies {
+            self.enable_vss_scan = true;
+        }
+
+        // NTFS file records
+        if cli.ntfs {
+            self.enable_ntfs_scan = true;
+        }
+        if cli.unallocated_only {
+            self.unallocated_only = true;
+        }
+        if cli.volume_locations {
+            self.enable_volume_locations = true;
+        }
+
+        // Offset trace
+        if let Some(offset) = cli.trace_offset {
+            self.trace_offset = Some(offset);
+        }
+        if let Some(window) = cli.trace_window {
+            self.trace_window = window;
+        }
+
+        // Throughput profiling
+        if cli.profile_throughput {
+            self.enable_profiling = true;
+        }
+
+        // Metadata snapshots
+        if let Some(secs) = cli.metadata_snapshot_interval {
+            self.metadata_snapshot_interval_secs = secs;
+        }
+
+        // Carve time budget
+        if let Some(secs) = cli.carve_timeout_secs {
+            self.carve_timeout_secs = secs;
+        }
+
+        // Acquisition fix-up
+        if let Some(transform) = cli.evidence_transform {
+            self.evidence_transform = transform;
+        }
+
+        // Pipeline execution
+        if let Some(mode) = cli.pipeline_mode {
+            self.pipeline_mode = mode;
+        }
+
+        // Carved output durability
+        if let Some(policy) = cli.carve_fsync {
+            self.carve_fsync = policy;
+        }
+
+        // QA sampling
+        if let Some(rate) = cli.qa_sample {
+            self.qa_sample_rate = rate;
+        }
+        if let Some(seed) = cli.qa_sample_seed {
+            self.qa_sample_seed = seed;
+        }
+
+        if let Some(min_size) = cli.export_slack {
+            self.slack_export_min_size = min_size;
+        }
+
+        // Hit context export
+        if let Some(window) = cli.hit_context_window {
+            self.hit_context_window = window;
+        }
+
+        // Scratch space
+        if let Some(dir) = &cli.temp_dir {
+            self.temp_dir = Some(dir.clone());
+        }
+        if let Some(limit) = cli.temp_space_limit {
+            self.temp_space_limit = limit;
+        }
+
+        // Output encryption
+        if let Some(recipient) = &cli.encrypt_to {
+            self.encryption_recipient = Some(recipient.clone());
+        }
+
+        // Evidence hashing
+        if cli.compute_evidence_sha256 && !self.evidence_hashes.contains(&EvidenceDigest::Sha256) {
+            self.evidence_hashes.push(EvidenceDigest::Sha256);
+        }
+
+        // Resume against changed evidence
+        if cli.allow_evidence_change {
+            self.resume_allow_evidence_change = true;
+        }
     }
 }
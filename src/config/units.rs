@@ -0,0 +1,221 @@
+//! Human-friendly byte size parsing for config values.
+//!
+//! Sizes may be plain integers (bytes) or strings with a unit suffix, following
+//! coreutils conventions: `K`/`M`/`G`/`T` (any case) and `KiB`/`MiB`/`GiB`/`TiB` are
+//! powers of 1024, `KB`/`MB`/`GB`/`TB` are powers of 1000. Fractions are allowed
+//! (`1.5GiB`) as long as the result is a whole number of bytes.
+
+use std::fmt;
+
+use serde::Deserializer;
+use serde::de::{self, Visitor};
+
+const KIB: u64 = 1024;
+
+/// Parse a byte size such as `4096`, `64KiB`, `512MiB`, `10m`, or `1.5GB`.
+pub fn parse_byte_size(value: &str) -> Result<u64, String> {
+    let trimmed = value.trim();
+    if trimmed.is_empty() {
+        return Err("empty size".to_string());
+    }
+    let split = trimmed
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '_'))
+        .unwrap_or(trimmed.len());
+    let (number, unit) = trimmed.split_at(split);
+    let number = number.replace('_', "");
+    if number.is_empty() {
+        return Err(format!("`{value}` does not start with a number"));
+    }
+    let multiplier = unit_multiplier(unit.trim())
+        .ok_or_else(|| format!("unknown size unit `{}` in `{value}`", unit.trim()))?;
+
+    if let Ok(whole) = number.parse::<u64>() {
+        return whole
+            .checked_mul(multiplier)
+            .ok_or_else(|| format!("size `{value}` overflows u64"));
+    }
+    let fractional: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid number in `{value}`"))?;
+    let bytes = fractional * multiplier as f64;
+    if !bytes.is_finite() || bytes < 0.0 || bytes > u64::MAX as f64 {
+        return Err(format!("size `{value}` is out of range"));
+    }
+    if bytes.fract() != 0.0 {
+        return Err(format!("size `{value}` is not a whole number of bytes"));
+    }
+    Ok(bytes as u64)
+}
+
+fn unit_multiplier(unit: &str) -> Option<u64> {
+    let multiplier = match unit {
+        "" | "b" | "B" => 1,
+        "k" | "K" | "KiB" | "kiB" => KIB,
+        "m" | "M" | "MiB" => KIB.pow(2),
+        "g" | "G" | "GiB" => KIB.pow(3),
+        "t" | "T" | "TiB" => KIB.pow(4),
+        "kB" | "KB" => 1000,
+        "MB" => 1000u64.pow(2),
+        "GB" => 1000u64.pow(3),
+        "TB" => 1000u64.pow(4),
+        _ => return None,
+    };
+    Some(multiplier)
+}
+
+struct ByteSizeVisitor;
+
+impl<'de> Visitor<'de> for ByteSizeVisitor {
+    type Value = u64;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a byte count or a size string such as \"512MiB\"")
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<u64, E> {
+        Ok(v)
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<u64, E> {
+        u64::try_from(v).map_err(|_| E::custom(format!("size must not be negative (got {v})")))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<u64, E> {
+        parse_byte_size(v).map_err(E::custom)
+    }
+}
+
+struct OptionalByteSizeVisitor;
+
+impl<'de> Visitor<'de> for OptionalByteSizeVisitor {
+    type Value = Option<u64>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an optional byte count or size string")
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, d: D) -> Result<Self::Value, D::Error> {
+        bytes(d).map(Some)
+    }
+}
+
+/// Deserialize a `u64` byte size from an integer or unit string.
+pub fn bytes<'de, D: Deserializer<'de>>(d: D) -> Result<u64, D::Error> {
+    d.deserialize_any(ByteSizeVisitor)
+}
+
+/// Deserialize a `usize` byte size from an integer or unit string.
+pub fn bytes_usize<'de, D: Deserializer<'de>>(d: D) -> Result<usize, D::Error> {
+    let value = bytes(d)?;
+    usize::try_from(value).map_err(|_| de::Error::custom(format!("size {value} is too large")))
+}
+
+/// Deserialize an optional size expressed in MiB. Plain integers are MiB;
+/// strings with units are converted (and must be a whole number of MiB).
+pub fn opt_mib<'de, D: Deserializer<'de>>(d: D) -> Result<Option<u64>, D::Error> {
+    struct MibVisitor;
+
+    impl<'de> Visitor<'de> for MibVisitor {
+        type Value = Option<u64>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("an optional MiB count or size string such as \"4GiB\"")
+        }
+
+        fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_some<D: Deserializer<'de>>(self, d: D) -> Result<Self::Value, D::Error> {
+            d.deserialize_any(self)
+        }
+
+        fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+            Ok(Some(v))
+        }
+
+        fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+            u64::try_from(v)
+                .map(Some)
+                .map_err(|_| E::custom(format!("size must not be negative (got {v})")))
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+            let bytes = parse_byte_size(v).map_err(E::custom)?;
+            let mib = KIB.pow(2);
+            if bytes % mib != 0 {
+                return Err(E::custom(format!("`{v}` is not a whole number of MiB")));
+            }
+            Ok(Some(bytes / mib))
+        }
+    }
+
+    d.deserialize_option(MibVisitor)
+}
+
+/// Deserialize an optional `u64` byte size.
+pub fn opt_bytes<'de, D: Deserializer<'de>>(d: D) -> Result<Option<u64>, D::Error> {
+    d.deserialize_option(OptionalByteSizeVisitor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_plain_and_suffixed_sizes() {
+        assert_eq!(parse_byte_size("4096"), Ok(4096));
+        assert_eq!(parse_byte_size("64KiB"), Ok(64 * 1024));
+        assert_eq!(parse_byte_size("512MiB"), Ok(512 * 1024 * 1024));
+        assert_eq!(parse_byte_size("10m"), Ok(10 * 1024 * 1024));
+        assert_eq!(parse_byte_size("2G"), Ok(2 * 1024 * 1024 * 1024));
+        assert_eq!(parse_byte_size("1MB"), Ok(1_000_000));
+        assert_eq!(parse_byte_size("1.5KiB"), Ok(1536));
+        assert_eq!(parse_byte_size("1_000"), Ok(1000));
+        assert_eq!(parse_byte_size(" 8 KiB "), Ok(8192));
+    }
+
+    #[test]
+    fn rejects_invalid_sizes() {
+        assert!(parse_byte_size("").is_err());
+        assert!(parse_byte_size("MiB").is_err());
+        assert!(parse_byte_size("10 parsecs").is_err());
+        assert!(parse_byte_size("0.3B").is_err());
+        assert!(parse_byte_size("99999999999T").is_err());
+    }
+
+    #[derive(serde::Deserialize)]
+    struct Sample {
+        #[serde(deserialize_with = "bytes")]
+        size: u64,
+        #[serde(default, deserialize_with = "opt_mib")]
+        mem: Option<u64>,
+        #[serde(default, deserialize_with = "opt_bytes")]
+        limit: Option<u64>,
+    }
+
+    #[test]
+    fn deserializes_from_yaml() {
+        let s: Sample = serde_yaml::from_str("size: 64KiB\nmem: 4GiB\nlimit:\n").expect("yaml");
+        assert_eq!(s.size, 65536);
+        assert_eq!(s.mem, Some(4096));
+        assert_eq!(s.limit, None);
+        let s: Sample = serde_yaml::from_str("size: 100\nmem: 256\nlimit: 1k\n").expect("yaml");
+        assert_eq!(s.size, 100);
+        assert_eq!(s.mem, Some(256));
+        assert_eq!(s.limit, Some(1024));
+        assert!(serde_yaml::from_str::<Sample>("size: -1\n").is_err());
+    }
+}
@@ -0,0 +1,1407 @@
+//! Cross-field config validation.
+//!
+//! Checks that individual fields cannot enforce on their own (overlap vs. chunk
+//! size, `min_size` vs. `max_size`, entropy window vs. threshold, pattern hex) and
+//! reports every problem at once. When the YAML source is available, issues carry
+//! the line of the offending key so `config check` output can point at it.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::Path;
+
+use crate::util::known_type_names;
+
+use super::{CarveFsync, Config, FileTypeConfig, NotificationConfig, PostProcessorConfig};
+
+/// Validators with a carve handler in `util::build_carve_registry`.
+pub const KNOWN_VALIDATORS: &[&str] = &[
+    "jpeg",
+    "png",
+    "gif",
+    "sqlite",
+    "pdf",
+    "zip",
+    "gzip",
+    "bzip2",
+    "xz",
+    "tar",
+    "webp",
+    "bmp",
+    "tiff",
+    "mp4",
+    "rar",
+    "sevenz",
+    "wav",
+    "avi",
+    "mov",
+    "webm",
+    "wmv",
+    "mp3",
+    "ole",
+    "ogg",
+    "flac",
+    "rtf",
+    "ico",
+    "elf",
+    "macho",
+    "eml",
+    "mobi",
+    "fb2",
+    "lrf",
+    "chrome_cache",
+    "firefox_cache",
+    "pcap",
+    "pcapng",
+    "journald",
+    "boltdb",
+    "regf",
+    "evtx",
+    "prefetch",
+    "lnk",
+    "thumbcache",
+    "bplist",
+    "pe",
+    "ese",
+    "heif",
+    "pst",
+    "torrent",
+    "binarycookies",
+    "netscape_cookies",
+    "crashdump",
+    "hiberfil",
+    "hbin",
+    "pool_process",
+    "footer",
+];
+
+/// Smallest entropy window that can reach the 8 bits/byte maximum.
+const FULL_ENTROPY_WINDOW: usize = 256;
+
+/// Largest `hit_context_window`; each window is one row held in memory.
+const MAX_HIT_CONTEXT_WINDOW: u64 = 1024 * 1024;
+
+/// Largest `carve_write_buffer`; every carve worker holds one per open carve.
+const MAX_CARVE_WRITE_BUFFER: u64 = 64 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum IssueSeverity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for IssueSeverity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IssueSeverity::Warning => f.write_str("warning"),
+            IssueSeverity::Error => f.write_str("error"),
+        }
+    }
+}
+
+/// A single configuration problem.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigIssue {
+    pub severity: IssueSeverity,
+    /// Dotted path of the field, e.g. `file_types.jpeg.min_size` (empty for parse errors).
+    pub field: String,
+    /// 1-based line in the YAML source, when known.
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl ConfigIssue {
+    pub fn is_error(&self) -> bool {
+        self.severity == IssueSeverity::Error
+    }
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(line) = self.line {
+            write!(f, "line {line}: ")?;
+        }
+        write!(f, "{}: ", self.severity)?;
+        if !self.field.is_empty() {
+            write!(f, "{}: ", self.field)?;
+        }
+        f.write_str(&self.message)
+    }
+}
+
+/// Validate a config. `chunk_size` enables checks against the scan chunk size;
+/// `source` (the YAML text the config was parsed from) enables line numbers.
+pub fn validate_config(
+    cfg: &Config,
+    chunk_size: Option<u64>,
+    source: Option<&str>,
+) -> Vec<ConfigIssue> {
+    validate(cfg, chunk_size, source, None)
+}
+
+/// Validate a config for a run limited to the file types in `selected` (those
+/// left after `--types`): the other types are not checked, so a broken entry
+/// for a type the run does not carve does not stop it. References to file
+/// types elsewhere in the config are still resolved against every type.
+pub fn validate_selected_types(
+    cfg: &Config,
+    selected: &[String],
+    chunk_size: Option<u64>,
+) -> Vec<ConfigIssue> {
+    let selected: HashSet<&str> = selected.iter().map(String::as_str).collect();
+    validate(cfg, chunk_size, None, Some(&selected))
+}
+
+fn validate(
+    cfg: &Config,
+    chunk_size: Option<u64>,
+    source: Option<&str>,
+    selected: Option<&HashSet<&str>>,
+) -> Vec<ConfigIssue> {
+    let mut v = Validator {
+        lines: source.map(LineIndex::new),
+        issues: Vec::new(),
+    };
+
+    if let Some(chunk_size) = chunk_size {
+        if chunk_size == 0 {
+            v.top(IssueSeverity::Error, "chunk_size", "chunk size must be > 0");
+        } else if cfg.overlap_bytes >= chunk_size {
+            v.top(
+                IssueSeverity::Error,
+                "overlap_bytes",
+                format!(
+                    "overlap ({}) must be smaller than the chunk size ({chunk_size})",
+                    cfg.overlap_bytes
+                ),
+            );
+        }
+    }
+
+    for (field, min_len) in [
+        ("string_min_len", Some(cfg.string_min_len)),
+        ("string_min_len_utf8", cfg.string_min_len_utf8),
+        ("string_min_len_utf16", cfg.string_min_len_utf16),
+    ] {
+        let Some(min_len) = min_len else {
+            continue;
+        };
+        if min_len == 0 {
+            v.top(IssueSeverity::Error, field, format!("{field} must be > 0"));
+        }
+        if min_len > cfg.string_max_len {
+            v.top(
+                IssueSeverity::Error,
+                "string_max_len",
+                format!(
+                    "string_max_len ({}) is smaller than {field} ({min_len})",
+                    cfg.string_max_len
+                ),
+            );
+        }
+    }
+    if cfg.enable_string_scan
+        && !cfg.enable_url_scan
+        && !cfg.enable_email_scan
+        && !cfg.enable_phone_scan
+        && !cfg.enable_log_scan
+        && !cfg.enable_system_scan
+        && !cfg.enable_credential_scan
+        && !cfg.enable_p2p_scan
+    {
+        v.top(
+            IssueSeverity::Warning,
+            "enable_string_scan",
+            "string scanning enabled but all artefact types are disabled",
+        );
+    }
+    if cfg.parquet_row_group_size == 0 {
+        v.top(
+            IssueSeverity::Error,
+            "parquet_row_group_size",
+            "parquet_row_group_size must be > 0",
+        );
+    }
+
+    let max_success = cfg.pattern_disable_max_success;
+    if !(0.0..=1.0).contains(&max_success) {
+        v.top(
+            IssueSeverity::Error,
+            "pattern_disable_max_success",
+            format!("pattern_disable_max_success ({max_success}) must be in [0, 1]"),
+        );
+    }
+
+    if cfg.photo_session_gap_secs == 0 {
+        v.top(
+            IssueSeverity::Error,
+            "photo_session_gap_secs",
+            "photo_session_gap_secs must be greater than 0".to_string(),
+        );
+    }
+
+    if cfg.clock_tolerance_secs == 0 {
+        v.top(
+            IssueSeverity::Error,
+            "clock_tolerance_secs",
+            "clock_tolerance_secs must be greater than 0".to_string(),
+        );
+    }
+
+    if cfg.ntfs_unallocated_only && !cfg.enable_ntfs_scan {
+        v.top(
+            IssueSeverity::Warning,
+            "ntfs_unallocated_only",
+            "ntfs_unallocated_only has no effect without enable_ntfs_scan",
+        );
+    }
+
+    let overlap = cfg.arbitration_overlap;
+    if !(0.0..=1.0).contains(&overlap) {
+        v.top(
+            IssueSeverity::Error,
+            "arbitration_overlap",
+            format!("arbitration_overlap ({overlap}) must be in [0, 1]"),
+        );
+    }
+
+    if cfg.carve_fsync == CarveFsync::Periodic && cfg.carve_fsync_interval == 0 {
+        v.top(
+            IssueSeverity::Error,
+            "carve_fsync_interval",
+            "carve_fsync_interval must be > 0 with carve_fsync: periodic",
+        );
+    }
+    if cfg.carve_write_buffer == 0 || cfg.carve_write_buffer > MAX_CARVE_WRITE_BUFFER {
+        v.top(
+            IssueSeverity::Error,
+            "carve_write_buffer",
+            format!(
+                "carve_write_buffer ({}) must be between 1 byte and {MAX_CARVE_WRITE_BUFFER}",
+                cfg.carve_write_buffer
+            ),
+        );
+    }
+
+    let qa_rate = cfg.qa_sample_rate;
+    if !(0.0..=1.0).contains(&qa_rate) {
+        v.top(
+            IssueSeverity::Error,
+            "qa_sample_rate",
+            format!("qa_sample_rate ({qa_rate}) must be in [0, 1]"),
+        );
+    }
+
+    if cfg.hit_context_window > MAX_HIT_CONTEXT_WINDOW {
+        v.top(
+            IssueSeverity::Error,
+            "hit_context_window",
+            format!(
+                "hit_context_window ({}) must be at most {MAX_HIT_CONTEXT_WINDOW}",
+                cfg.hit_context_window
+            ),
+        );
+    }
+    if cfg.hit_context_window > 0 && cfg.hit_context_before >= cfg.hit_context_window {
+        v.top(
+            IssueSeverity::Error,
+            "hit_context_before",
+            format!(
+                "hit_context_before ({}) must be smaller than hit_context_window ({})",
+                cfg.hit_context_before, cfg.hit_context_window
+            ),
+        );
+    }
+    for id in &cfg.hit_context_types {
+        if !cfg.file_types.iter().any(|ft| &ft.id == id) {
+            v.top(
+                IssueSeverity::Warning,
+                "hit_context_types",
+                format!("hit_context_types names unknown file type `{id}`"),
+            );
+        }
+    }
+
+    let known_types = known_type_names(cfg);
+    for (group, members) in &cfg.type_groups {
+        if known_types.contains(&group.to_ascii_lowercase()) {
+            v.top(
+                IssueSeverity::Warning,
+                "type_groups",
+                format!("type group `{group}` shadows the file type of the same name in --types"),
+            );
+        }
+        for member in members {
+            // Built-in handler names stay quiet: profiles disable some of them.
+            let name = member.trim().to_ascii_lowercase();
+            if !known_types.contains(&name) && !KNOWN_VALIDATORS.contains(&name.as_str()) {
+                v.top(
+                    IssueSeverity::Warning,
+                    "type_groups",
+                    format!("type group `{group}` names unknown file type `{member}`"),
+                );
+            }
+        }
+    }
+
+    let mut rule_names = HashSet::new();
+    for rule in &cfg.quarantine_rules {
+        let name = rule.name.trim();
+        if name.is_empty() {
+            v.top(
+                IssueSeverity::Error,
+                "quarantine_rules",
+                "quarantine rule has an empty `name`",
+            );
+        } else if !rule_names.insert(name) {
+            v.top(
+                IssueSeverity::Error,
+                "quarantine_rules",
+                format!("quarantine rule `{name}` is defined twice"),
+            );
+        }
+        if rule.hash_list.is_none() && rule.file_types.is_empty() && rule.labels.is_empty() {
+            v.top(
+                IssueSeverity::Error,
+                "quarantine_rules",
+                format!("quarantine rule `{name}` needs hash_list, file_types, or labels"),
+            );
+        }
+        if !(0.0..=1.0).contains(&rule.min_score) {
+            v.top(
+                IssueSeverity::Error,
+                "quarantine_rules",
+                format!("quarantine rule `{name}` needs min_score between 0 and 1"),
+            );
+        }
+        for id in &rule.file_types {
+            if !cfg.file_types.iter().any(|ft| &ft.id == id) {
+                v.top(
+                    IssueSeverity::Warning,
+                    "quarantine_rules",
+                    format!("quarantine rule `{name}` names unknown file type `{id}`"),
+                );
+            }
+        }
+    }
+    for (key, recipient) in [
+        ("quarantine_recipient", &cfg.quarantine_recipient),
+        ("encryption_recipient", &cfg.encryption_recipient),
+    ] {
+        if let Some(recipient) = recipient
+            && let Err(err) = crate::encryption::parse_recipient(recipient)
+        {
+            v.top(
+                IssueSeverity::Error,
+                key,
+                format!("{key} is not an age X25519 recipient: {err}"),
+            );
+        }
+    }
+
+    if let Some(path) = &cfg.sqlite_app_catalog
+        && let Err(err) = crate::parsers::sqlite_apps::AppCatalog::load(Some(path))
+    {
+        v.top(
+            IssueSeverity::Error,
+            "sqlite_app_catalog",
+            format!("{err:#}"),
+        );
+    }
+
+    if let Some(dir) = &cfg.temp_dir
+        && dir.exists()
+        && !dir.is_dir()
+    {
+        v.top(
+            IssueSeverity::Error,
+            "temp_dir",
+            format!("temp_dir {} is not a directory", dir.display()),
+        );
+    }
+
+    if cfg.enable_lznt1_scan
+        && (cfg.lznt1_unit_size == 0 || !cfg.lznt1_unit_size.is_multiple_of(4096))
+    {
+        v.top(
+            IssueSeverity::Error,
+            "lznt1_unit_size",
+            format!(
+                "lznt1_unit_size ({}) must be a non-zero multiple of 4096",
+                cfg.lznt1_unit_size
+            ),
+        );
+    }
+
+    if cfg.enable_entropy_detection {
+        validate_entropy(&mut v, cfg, chunk_size);
+    }
+
+    for hook in &cfg.notifications {
+        validate_notification(&mut v, hook);
+    }
+
+    let mut seen_ids = HashSet::new();
+    for (index, file_type) in cfg.file_types.iter().enumerate() {
+        let first = seen_ids.insert(file_type.id.as_str());
+        if selected.is_some_and(|ids| !ids.contains(file_type.id.as_str())) {
+            continue;
+        }
+        if !first {
+            v.file_type(
+                index,
+                file_type,
+                "id",
+                IssueSeverity::Error,
+                format!("duplicate file type id `{}`", file_type.id),
+            );
+        }
+        validate_file_type(&mut v, index, file_type);
+    }
+
+    v.issues
+}
+
+/// Parse and validate a config file (or the built-in default), collecting every
+/// problem instead of stopping at the first. Keys the config does not know,
+/// usually misspellings, are reported as warnings since serde ignores them.
+pub fn check_config(
+    path: Option<&Path>,
+    chunk_size: Option<u64>,
+) -> anyhow::Result<Vec<ConfigIssue>> {
+    let text = match path {
+        Some(p) => std::fs::read_to_string(p)?,
+        None => DEFAULT_CONFIG.to_string(),
+    };
+    let lines = LineIndex::new(&text);
+    let mut unknown = Vec::new();
+    let parsed: Result<Config, _> =
+        serde_ignored::deserialize(serde_yaml::Deserializer::from_str(&text), |path| {
+            unknown.push(path.to_string())
+        });
+    let mut issues = match parsed {
+        Ok(cfg) => validate_config(&cfg, chunk_size, Some(&text)),
+        Err(err) => {
+            unknown.clear();
+            parse_errors(&text, &lines, err, &mut unknown)
+        }
+    };
+    let ids = file_type_ids(&text);
+    issues.extend(unknown.iter().map(|path| unknown_key(&lines, &ids, path)));
+    issues.sort_by_key(|issue| issue.line);
+    Ok(issues)
+}
+
+const DEFAULT_CONFIG: &str = include_str!("../../config/default.yml");
+
+/// Every parse error in `text`, not just the first (`first`): each top-level
+/// key is parsed on its own over the built-in defaults, and each `file_types`
+/// entry on its own, so one bad value does not hide the next. Unknown keys met
+/// along the way go to `unknown`.
+fn parse_errors(
+    text: &str,
+    lines: &LineIndex,
+    first: serde_yaml::Error,
+    unknown: &mut Vec<String>,
+) -> Vec<ConfigIssue> {
+    let syntax_error = |err: serde_yaml::Error| ConfigIssue {
+        severity: IssueSeverity::Error,
+        field: String::new(),
+        line: err.location().map(|loc| loc.line()),
+        message: err.to_string(),
+    };
+    let user = match serde_yaml::from_str::<serde_yaml::Value>(text) {
+        Ok(serde_yaml::Value::Mapping(user)) => user,
+        Ok(_) => return vec![syntax_error(first)],
+        Err(err) => return vec![syntax_error(err)],
+    };
+    let Ok(serde_yaml::Value::Mapping(defaults)) = serde_yaml::from_str(DEFAULT_CONFIG) else {
+        return vec![syntax_error(first)];
+    };
+
+    let mut issues = Vec::new();
+    // The config with every broken part set back to its default, to find
+    // errors no single key shows, such as missing fields.
+    let mut repaired = user.clone();
+    for (key, value) in &user {
+        let Some(name) = key.as_str() else {
+            continue;
+        };
+        if let (Some(items), "file_types") = (value.as_sequence(), name) {
+            let mut kept = Vec::new();
+            for (index, item) in items.iter().enumerate() {
+                let parsed: Result<FileTypeConfig, _> =
+                    serde_ignored::deserialize(item.clone(), |path| {
+                        unknown.push(format!("file_types.{index}.{path}"))
+                    });
+                match parsed {
+                    Ok(_) => kept.push(item.clone()),
+                    Err(err) => issues.push(ConfigIssue {
+                        severity: IssueSeverity::Error,
+                        field: format!("file_types.{}", item_id(item, index)),
+                        line: lines.file_types.get(index).map(|item| item.start),
+                        message: err.to_string(),
+                    }),
+                }
+            }
+            repaired.insert(key.clone(), serde_yaml::Value::Sequence(kept));
+            continue;
+        }
+        let mut alone = defaults.clone();
+        alone.insert(key.clone(), value.clone());
+        let mut alone_unknown = Vec::new();
+        let parsed: Result<Config, _> =
+            serde_ignored::deserialize(serde_yaml::Value::Mapping(alone), |path| {
+                alone_unknown.push(path.to_string())
+            });
+        match parsed {
+            Ok(_) => unknown.extend(alone_unknown),
+            Err(err) => {
+                issues.push(ConfigIssue {
+                    severity: IssueSeverity::Error,
+                    field: name.to_string(),
+                    line: lines.top_level.get(name).copied(),
+                    message: err.to_string(),
+                });
+                match defaults.get(key) {
+                    Some(default) => repaired.insert(key.clone(), default.clone()),
+                    None => repaired.remove(key),
+                };
+            }
+        }
+    }
+    if let Err(err) = serde_yaml::from_value::<Config>(serde_yaml::Value::Mapping(repaired)) {
+        issues.push(syntax_error(err));
+    }
+    if issues.is_empty() {
+        issues.push(syntax_error(first));
+    }
+    unknown.sort();
+    unknown.dedup();
+    issues
+}
+
+/// Ids of the `file_types` entries in `text`, by position.
+fn file_type_ids(text: &str) -> Vec<String> {
+    serde_yaml::from_str::<serde_yaml::Value>(text)
+        .ok()
+        .and_then(|value| value.get("file_types")?.as_sequence().cloned())
+        .unwrap_or_default()
+        .iter()
+        .enumerate()
+        .map(|(index, item)| item_id(item, index))
+        .collect()
+}
+
+fn item_id(item: &serde_yaml::Value, index: usize) -> String {
+    item.get("id")
+        .and_then(serde_yaml::Value::as_str)
+        .map(str::to_string)
+        .unwrap_or_else(|| index.to_string())
+}
+
+/// Warning for a key serde ignored; `path` as `serde_ignored` prints it, e.g.
+/// `file_types.3.min_sise`.
+fn unknown_key(lines: &LineIndex, ids: &[String], path: &str) -> ConfigIssue {
+    let segments: Vec<&str> = path.split('.').collect();
+    let (field, line) = match segments.as_slice() {
+        ["file_types", index, key, ..] if index.parse::<usize>().is_ok() => {
+            let index: usize = index.parse().unwrap_or_default();
+            let item = lines.file_types.get(index);
+            let id = ids.get(index).cloned().unwrap_or_else(|| index.to_string());
+            (
+                format!("file_types.{id}.{}", segments[2..].join(".")),
+                item.map(|item| item.keys.get(*key).copied().unwrap_or(item.start)),
+            )
+        }
+        [key, ..] => (path.to_string(), lines.top_level.get(*key).copied()),
+        [] => (path.to_string(), None),
+    };
+    ConfigIssue {
+        severity: IssueSeverity::Warning,
+        field,
+        line,
+        message: "unknown key is ignored; check the spelling".to_string(),
+    }
+}
+
+fn validate_notification(v: &mut Validator, hook: &NotificationConfig) {
+    let (timeout_secs, problem) = match hook {
+        NotificationConfig::Webhook {
+            url, timeout_secs, ..
+        } => {
+            let problem = if url.starts_with("https://") {
+                Some(format!(
+                    "webhook `{url}`: https is not supported; use a `command` hook such as curl"
+                ))
+            } else if !url.starts_with("http://") {
+                Some(format!("webhook `{url}` must start with http://"))
+            } else {
+                None
+            };
+            (*timeout_secs, problem)
+        }
+        NotificationConfig::Email {
+            smtp_server,
+            from,
+            to,
+            timeout_secs,
+            ..
+        } => {
+            let problem = if smtp_server.trim().is_empty() || from.trim().is_empty() {
+                Some("email notification needs `smtp_server` and `from`".to_string())
+            } else if to.is_empty() {
+                Some("email notification has no `to` recipients".to_string())
+            } else {
+                None
+            };
+            (*timeout_secs, problem)
+        }
+        NotificationConfig::Command {
+            program,
+            timeout_secs,
+            ..
+        } => {
+            let problem = program
+                .trim()
+                .is_empty()
+                .then(|| "command notification has an empty `program`".to_string());
+            (*timeout_secs, problem)
+        }
+    };
+    if let Some(problem) = problem {
+        v.top(IssueSeverity::Error, "notifications", problem);
+    }
+    if timeout_secs == 0 {
+        v.top(
+            IssueSeverity::Error,
+            "notifications",
+            "notification hooks need timeout_secs > 0",
+        );
+    }
+    if hook.events().is_empty() {
+        v.top(
+            IssueSeverity::Warning,
+            "notifications",
+            "notification hook has an empty `on` list and never fires",
+        );
+    }
+}
+
+fn validate_entropy(v: &mut Validator, cfg: &Config, chunk_size: Option<u64>) {
+    let window = cfg.entropy_window_size;
+    if window == 0 {
+        v.top(
+            IssueSeverity::Error,
+            "entropy_window_size",
+            "entropy_window_size must be > 0",
+        );
+        return;
+    }
+    if let Some(chunk_size) = chunk_size
+        && chunk_size > 0
+        && window as u64 > chunk_size
+    {
+        v.top(
+            IssueSeverity::Error,
+            "entropy_window_size",
+            format!("entropy window ({window}) is larger than the chunk size ({chunk_size})"),
+        );
+    }
+    let threshold = cfg.entropy_threshold;
+    if !(threshold > 0.0 && threshold <= 8.0) {
+        v.top(
+            IssueSeverity::Error,
+            "entropy_threshold",
+            format!("entropy_threshold ({threshold}) must be in (0, 8] bits per byte"),
+        );
+        return;
+    }
+    // A window of n bytes holds at most log2(min(n, 256)) bits per byte.
+    let max_entropy = (window.min(FULL_ENTROPY_WINDOW) as f64).log2();
+    if threshold > max_entropy {
+        v.top(
+            IssueSeverity::Error,
+            "entropy_window_size",
+            format!(
+                "a {window}-byte window cannot exceed {max_entropy:.2} bits per byte, \
+                 so entropy_threshold ({threshold}) never triggers"
+            ),
+        );
+    } else if window < FULL_ENTROPY_WINDOW {
+        v.top(
+            IssueSeverity::Warning,
+            "entropy_window_size",
+            format!(
+                "entropy window ({window}) is smaller than {FULL_ENTROPY_WINDOW} bytes; \
+                 estimates will be noisy"
+            ),
+        );
+    }
+}
+
+fn validate_file_type(v: &mut Validator, index: usize, file_type: &FileTypeConfig) {
+    let validator = if file_type.validator.trim().is_empty() {
+        file_type.id.as_str()
+    } else {
+        file_type.validator.as_str()
+    };
+    if !KNOWN_VALIDATORS.contains(&validator) {
+        v.file_type(
+            index,
+            file_type,
+            "validator",
+            IssueSeverity::Warning,
+            format!("unknown validator `{validator}`; hits will not be carved"),
+        );
+    }
+
+    if file_type.max_size > 0 && file_type.min_size > file_type.max_size {
+        v.file_type(
+            index,
+            file_type,
+            "min_size",
+            IssueSeverity::Error,
+            format!(
+                "min_size ({}) is larger than max_size ({})",
+                file_type.min_size, file_type.max_size
+            ),
+        );
+    }
+
+    if let Some(factor) = file_type.declared_size_factor
+        && !(factor.is_finite() && factor >= 1.0)
+    {
+        v.file_type(
+            index,
+            file_type,
+            "declared_size_factor",
+            IssueSeverity::Error,
+            format!("declared_size_factor ({factor}) must be a number of at least 1"),
+        );
+    }
+
+    if file_type.header_patterns.is_empty() {
+        v.file_type(
+            index,
+            file_type,
+            "header_patterns",
+            IssueSeverity::Warning,
+            "no header patterns; this type can never match",
+        );
+    }
+    for (key, patterns) in [
+        ("header_patterns", &file_type.header_patterns),
+        ("footer_patterns", &file_type.footer_patterns),
+    ] {
+        for pattern in patterns {
+            let message = if pattern.hex.trim().is_empty() {
+                Some(format!("pattern `{}` is empty", pattern.id))
+            } else {
+                hex::decode(pattern.hex.trim())
+                    .err()
+                    .map(|err| format!("pattern `{}` has invalid hex: {err}", pattern.id))
+            };
+            if let Some(message) = message {
+                v.file_type(index, file_type, key, IssueSeverity::Error, message);
+            }
+        }
+    }
+
+    for processor in file_type.post_processors.iter().flatten() {
+        if let PostProcessorConfig::Command {
+            program,
+            timeout_secs,
+            ..
+        } = processor
+        {
+            if program.trim().is_empty() {
+                v.file_type(
+                    index,
+                    file_type,
+                    "post_processors",
+                    IssueSeverity::Error,
+                    "command post-processor has an empty `program`",
+                );
+            }
+            if *timeout_secs == 0 {
+                v.file_type(
+                    index,
+                    file_type,
+                    "post_processors",
+                    IssueSeverity::Error,
+                    format!("command post-processor `{program}` needs timeout_secs > 0"),
+                );
+            }
+        }
+        if let PostProcessorConfig::VideoRepair { donor, frame_rate } = processor {
+            let message = if !(*frame_rate > 0.0 && *frame_rate <= 1000.0) {
+                Some("video_repair post-processor needs frame_rate between 0 and 1000".to_string())
+            } else if let Some(donor) = donor {
+                crate::parsers::mp4::VideoTemplate::from_donor(donor)
+                    .err()
+                    .map(|err| format!("{err:#}"))
+            } else {
+                None
+            };
+            if let Some(message) = message {
+                v.file_type(
+                    index,
+                    file_type,
+                    "post_processors",
+                    IssueSeverity::Error,
+                    message,
+                );
+            }
+        }
+        if let PostProcessorConfig::Classifier {
+            program,
+            timeout_secs,
+            min_score,
+            top_k,
+            ..
+        } = processor
+        {
+            let message = if program.trim().is_empty() {
+                Some("classifier post-processor has an empty `program`".to_string())
+            } else if *timeout_secs == 0 {
+                Some(format!(
+                    "classifier post-processor `{program}` needs timeout_secs > 0"
+                ))
+            } else if !(0.0..=1.0).contains(min_score) {
+                Some(format!(
+                    "classifier post-processor `{program}` needs min_score between 0 and 1"
+                ))
+            } else if *top_k == 0 {
+                Some(format!(
+                    "classifier post-processor `{program}` needs top_k > 0"
+                ))
+            } else {
+                None
+            };
+            if let Some(message) = message {
+                v.file_type(
+                    index,
+                    file_type,
+                    "post_processors",
+                    IssueSeverity::Error,
+                    message,
+                );
+            }
+        }
+    }
+
+    if validator == "footer" && file_type.footer_patterns.is_empty() && file_type.max_size == 0 {
+        v.file_type(
+            index,
+            file_type,
+            "max_size",
+            IssueSeverity::Warning,
+            "footer validator needs footer patterns or max_size > 0; this type is skipped",
+        );
+    }
+}
+
+struct Validator {
+    lines: Option<LineIndex>,
+    issues: Vec<ConfigIssue>,
+}
+
+impl Validator {
+    fn top(&mut self, severity: IssueSeverity, key: &str, message: impl Into<String>) {
+        let line = self
+            .lines
+            .as_ref()
+            .and_then(|l| l.top_level.get(key).copied());
+        self.issues.push(ConfigIssue {
+            severity,
+            field: key.to_string(),
+            line,
+            message: message.into(),
+        });
+    }
+
+    fn file_type(
+        &mut self,
+        index: usize,
+        file_type: &FileTypeConfig,
+        key: &str,
+        severity: IssueSeverity,
+        message: impl Into<String>,
+    ) {
+        let line = self
+            .lines
+            .as_ref()
+            .and_then(|l| l.file_types.get(index))
+            .map(|item| item.keys.get(key).copied().unwrap_or(item.start));
+        self.issues.push(ConfigIssue {
+            severity,
+            field: format!("file_types.{}.{key}", file_type.id),
+            line,
+            message: message.into(),
+        });
+    }
+}
+
+/// Key positions in block-style YAML, enough to point issues at the right line.
+/// Keys that are not present in the source (defaults) have no entry.
+struct LineIndex {
+    top_level: HashMap<String, usize>,
+    file_types: Vec<ItemLines>,
+}
+
+struct ItemLines {
+    start: usize,
+    keys: HashMap<String, usize>,
+}
+
+impl LineIndex {
+    fn new(text: &str) -> Self {
+        let mut top_level = HashMap::new();
+        let mut file_types: Vec<ItemLines> = Vec::new();
+        let mut in_file_types = false;
+        let mut item_indent: Option<usize> = None;
+
+        for (idx, raw) in text.lines().enumerate() {
+            let line_no = idx + 1;
+            let content = raw.trim_start();
+            if content.is_empty() || content.starts_with('#') {
+                continue;
+            }
+            let indent = raw.len() - content.len();
+            if indent == 0 && !content.starts_with('-') {
+                let key = yaml_key(content);
+                if let Some(key) = key {
+                    top_level.entry(key.to_string()).or_insert(line_no);
+                    in_file_types = key == "file_types";
+                } else {
+                    in_file_types = false;
+                }
+                continue;
+            }
+            if !in_file_types {
+                continue;
+            }
+            let (is_item, body) = match content.strip_prefix('-') {
+                Some(rest) if rest.is_empty() || rest.starts_with(' ') => (true, rest.trim_start()),
+                _ => (false, content),
+            };
+            if is_item && item_indent.is_none_or(|i| i == indent) {
+                item_indent = Some(indent);
+                file_types.push(ItemLines {
+                    start: line_no,
+                    keys: HashMap::new(),
+                });
+            }
+            let Some(item) = file_types.last_mut() else {
+                continue;
+            };
+            // Only keys directly inside the item (not nested pattern entries).
+            let key_indent = if is_item && item_indent == Some(indent) {
+                indent + 2
+            } else {
+                indent
+            };
+            if item_indent.is_some_and(|i| key_indent == i + 2)
+                && let Some(key) = yaml_key(body)
+            {
+                item.keys.entry(key.to_string()).or_insert(line_no);
+            }
+        }
+
+        Self {
+            top_level,
+            file_types,
+        }
+    }
+}
+
+fn yaml_key(content: &str) -> Option<&str> {
+    let (key, _) = content.split_once(':')?;
+    let key = key.trim().trim_matches(['"', '\'']);
+    if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return None;
+    }
+    Some(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::QuarantineRule;
+
+    const SAMPLE: &str = "\
+run_id: \"\"
+overlap_bytes: 1MiB
+enable_string_scan: false
+string_min_len: 10
+string_max_len: 8
+enable_entropy_detection: true
+entropy_window_size: 16
+entropy_threshold: 7.5
+opencl_platform_index: null
+opencl_device_index: null
+file_types:
+  - id: \"jpeg\"
+    extensions: [\"jpg\"]
+    header_patterns:
+      - id: \"jpeg_soi\"
+        hex: \"FFD8FF\"
+    footer_patterns: []
+    max_size: 10KiB
+    min_size: 20KiB
+    validator: \"jpeg\"
+  - id: \"custom\"
+    extensions: [\"bin\"]
+    header_patterns:
+      - id: \"custom_hdr\"
+        hex: \"ZZ\"
+    footer_patterns: []
+    max_size: 0
+    min_size: 0
+    validator: \"magic\"
+";
+
+    fn find<'a>(issues: &'a [ConfigIssue], field: &str) -> &'a ConfigIssue {
+        issues
+            .iter()
+            .find(|i| i.field == field)
+            .unwrap_or_else(|| panic!("no issue for {field}: {issues:#?}"))
+    }
+
+    #[test]
+    fn reports_all_problems_with_lines() {
+        let cfg: Config = serde_yaml::from_str(SAMPLE).expect("parse");
+        assert_eq!(cfg.overlap_bytes, 1024 * 1024);
+        let issues = validate_config(&cfg, Some(512 * 1024), Some(SAMPLE));
+
+        let overlap = find(&issues, "overlap_bytes");
+        assert!(overlap.is_error());
+        assert_eq!(overlap.line, Some(2));
+        assert_eq!(find(&issues, "string_max_len").line, Some(5));
+        let entropy = find(&issues, "entropy_window_size");
+        assert!(entropy.is_error());
+        assert!(entropy.message.contains("never triggers"));
+        assert_eq!(entropy.line, Some(7));
+
+        let min_size = find(&issues, "file_types.jpeg.min_size");
+        assert!(min_size.is_error());
+        assert_eq!(min_size.line, Some(19));
+        let hex = find(&issues, "file_types.custom.header_patterns");
+        assert!(hex.is_error());
+        assert_eq!(hex.line, Some(23));
+        let validator = find(&issues, "file_types.custom.validator");
+        assert_eq!(validator.severity, IssueSeverity::Warning);
+        assert_eq!(validator.line, Some(29));
+    }
+
+    #[test]
+    fn checks_each_encoding_minimum() {
+        let mut cfg = crate::config::load_config(None).expect("config").config;
+        cfg.string_min_len_utf8 = Some(cfg.string_max_len + 1);
+        cfg.string_min_len_utf16 = Some(0);
+        let issues = validate_config(&cfg, Some(512 * 1024 * 1024), None);
+        assert!(find(&issues, "string_min_len_utf16").is_error());
+        assert!(
+            find(&issues, "string_max_len")
+                .message
+                .contains("string_min_len_utf8")
+        );
+    }
+
+    #[test]
+    fn default_config_is_clean() {
+        let issues = check_config(None, Some(512 * 1024 * 1024)).expect("check");
+        assert!(issues.is_empty(), "{issues:#?}");
+    }
+
+    #[test]
+    fn memory_profile_is_clean() {
+        let mut cfg = crate::config::load_config(None).expect("config").config;
+        cfg.apply_profile(crate::config::ScanProfile::Memory)
+            .expect("profile");
+        assert!(!cfg.file_types.iter().any(|ft| ft.id == "bmp"));
+        assert!(cfg.file_types.iter().any(|ft| ft.id == "pool_process"));
+        assert!(cfg.enable_credential_scan && !cfg.enable_phone_scan);
+        let issues = validate_config(&cfg, Some(512 * 1024 * 1024), None);
+        assert!(issues.is_empty(), "{issues:#?}");
+    }
+
+    #[test]
+    fn parse_errors_carry_line() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("bad.yml");
+        std::fs::write(&path, "run_id: \"\"\noverlap_bytes: 12 parsecs\n").expect("write");
+        let issues = check_config(Some(&path), None).expect("check");
+        let unit = find(&issues, "overlap_bytes");
+        assert_eq!(unit.line, Some(2));
+        assert!(unit.message.contains("parsecs"), "{}", unit.message);
+        // Fixing the unit would not be enough: the file lacks required keys.
+        assert!(
+            find(&issues, "").message.contains("missing field"),
+            "{issues:?}"
+        );
+        assert_eq!(issues.len(), 2);
+    }
+
+    #[test]
+    fn misspelled_keys_are_warnings_with_lines() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("typo.yml");
+        let text = SAMPLE
+            .replace(
+                "overlap_bytes: 1MiB",
+                "overlap_bytes: 1MiB\noverlap_byte: 2MiB",
+            )
+            .replace(
+                "    max_size: 10KiB",
+                "    max_sise: 10KiB\n    max_size: 10KiB",
+            )
+            + "bogus_key: 1\n";
+        std::fs::write(&path, text).expect("write");
+        let issues = check_config(Some(&path), None).expect("check");
+
+        let top = find(&issues, "overlap_byte");
+        assert_eq!(top.severity, IssueSeverity::Warning);
+        assert_eq!(top.line, Some(3));
+        let nested = find(&issues, "file_types.jpeg.max_sise");
+        assert_eq!(nested.severity, IssueSeverity::Warning);
+        assert_eq!(nested.line, Some(19));
+        assert_eq!(find(&issues, "bogus_key").line, Some(32));
+        // The rest of the file is still validated.
+        assert_eq!(
+            find(&issues, "string_max_len").severity,
+            IssueSeverity::Error
+        );
+    }
+
+    #[test]
+    fn reports_every_parse_error() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("bad.yml");
+        let text = SAMPLE
+            .replace("overlap_bytes: 1MiB", "overlap_bytes: 12 parsecs")
+            .replace("string_min_len: 10", "string_min_len: ten")
+            .replace("    max_size: 0", "    max_size: lots")
+            .replace("entropy_window_size: 16", "entropy_window_sise: 16");
+        std::fs::write(&path, text).expect("write");
+        let issues = check_config(Some(&path), None).expect("check");
+
+        let errors: Vec<_> = issues
+            .iter()
+            .filter(|issue| issue.severity == IssueSeverity::Error)
+            .map(|issue| (issue.field.as_str(), issue.line))
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                ("overlap_bytes", Some(2)),
+                ("string_min_len", Some(4)),
+                ("file_types.custom", Some(21)),
+            ]
+        );
+        assert!(find(&issues, "file_types.custom").message.contains("lots"));
+        let typo = find(&issues, "entropy_window_sise");
+        assert_eq!(typo.severity, IssueSeverity::Warning);
+        assert_eq!(typo.line, Some(7));
+    }
+
+    #[test]
+    fn unreadable_sqlite_app_catalog_is_an_error() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("apps.yml");
+        std::fs::write(
+            &path,
+            "apps:\n  - id: chat\n    tables: [messages]\n    plans: []\n",
+        )
+        .expect("write");
+        let mut cfg = crate::config::load_config(None).expect("config").config;
+        cfg.sqlite_app_catalog = Some(path);
+        let issues = validate_config(&cfg, None, None);
+        let issue = find(&issues, "sqlite_app_catalog");
+        assert!(issue.is_error());
+        assert!(issue.message.contains("no plans"), "{}", issue.message);
+    }
+
+    #[test]
+    fn temp_dir_must_be_a_directory() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let file = dir.path().join("scratch");
+        std::fs::write(&file, b"").expect("write");
+        let mut cfg = crate::config::load_config(None).expect("config").config;
+        cfg.temp_dir = Some(dir.path().to_path_buf());
+        assert!(
+            !validate_config(&cfg, None, None)
+                .iter()
+                .any(|issue| issue.field == "temp_dir")
+        );
+        cfg.temp_dir = Some(file);
+        let issues = validate_config(&cfg, None, None);
+        assert!(find(&issues, "temp_dir").is_error());
+    }
+
+    #[test]
+    fn declared_size_factor_below_one_is_an_error() {
+        let mut cfg: Config = serde_yaml::from_str(SAMPLE).expect("parse");
+        cfg.file_types[1].declared_size_factor = Some(0.5);
+        let issues = validate_config(&cfg, None, None);
+        assert!(find(&issues, "file_types.custom.declared_size_factor").is_error());
+    }
+
+    #[test]
+    fn only_selected_file_types_are_checked() {
+        let mut cfg: Config = serde_yaml::from_str(SAMPLE).expect("parse");
+        cfg.hit_context_types = vec!["jpeg".to_string()];
+        let all = validate_config(&cfg, None, None);
+        assert!(find(&all, "file_types.jpeg.min_size").is_error());
+
+        let selected = validate_selected_types(&cfg, &["custom".to_string()], None);
+        assert!(
+            !selected
+                .iter()
+                .any(|i| i.field.starts_with("file_types.jpeg"))
+        );
+        assert!(find(&selected, "file_types.custom.header_patterns").is_error());
+        // Still a known type, though not carved in this run.
+        assert!(!selected.iter().any(|i| i.field == "hit_context_types"));
+        // Config-wide checks still apply.
+        assert!(find(&selected, "string_max_len").is_error());
+    }
+
+    #[test]
+    fn duplicate_ids_are_errors() {
+        let mut cfg: Config = serde_yaml::from_str(SAMPLE).expect("parse");
+        cfg.file_types[1].id = "jpeg".to_string();
+        let issues = validate_config(&cfg, None, None);
+        assert!(
+            issues
+                .iter()
+                .any(|i| i.field == "file_types.jpeg.id" && i.is_error())
+        );
+        assert!(issues.iter().all(|i| i.line.is_none()));
+    }
+
+    #[test]
+    fn command_post_processors_need_program_and_timeout() {
+        let text = SAMPLE.replace(
+            "    validator: \"jpeg\"\n",
+            "    validator: \"jpeg\"\n    post_processors:\n      - kind: sqlite_browser\n      - kind: command\n        program: \"\"\n        timeout_secs: 0\n",
+        );
+        let cfg: Config = serde_yaml::from_str(&text).expect("parse");
+        assert_eq!(
+            cfg.file_types[0].post_processors.as_ref().map(Vec::len),
+            Some(2)
+        );
+        let issues = validate_config(&cfg, None, Some(&text));
+        let processors: Vec<_> = issues
+            .iter()
+            .filter(|i| i.field == "file_types.jpeg.post_processors")
+            .collect();
+        assert_eq!(processors.len(), 2, "{issues:#?}");
+        assert!(
+            processors
+                .iter()
+                .all(|i| i.is_error() && i.line == Some(21))
+        );
+    }
+
+    #[test]
+    fn classifier_scores_are_checked() {
+        let text = SAMPLE.replace(
+            "    validator: \"jpeg\"\n",
+            "    validator: \"jpeg\"\n    post_processors:\n      - kind: classifier\n        program: nsfw-server\n      - kind: classifier\n        program: doc-server\n        min_score: 1.5\n",
+        );
+        let cfg: Config = serde_yaml::from_str(&text).expect("parse");
+        let issues = validate_config(&cfg, None, None);
+        let processors: Vec<_> = issues
+            .iter()
+            .filter(|i| i.field == "file_types.jpeg.post_processors")
+            .collect();
+        assert_eq!(processors.len(), 1, "{issues:#?}");
+        assert!(processors[0].message.contains("doc-server"));
+    }
+
+    #[test]
+    fn video_repair_donor_and_frame_rate_are_checked() {
+        let text = SAMPLE.replace(
+            "    validator: \"jpeg\"\n",
+            "    validator: \"jpeg\"\n    post_processors:\n      - kind: video_repair\n      - kind: video_repair\n        frame_rate: 0\n      - kind: video_repair\n        donor: /nonexistent/donor.mp4\n",
+        );
+        let cfg: Config = serde_yaml::from_str(&text).expect("parse");
+        let issues = validate_config(&cfg, None, None);
+        let processors: Vec<_> = issues
+            .iter()
+            .filter(|i| i.field == "file_types.jpeg.post_processors")
+            .collect();
+        assert_eq!(processors.len(), 2, "{issues:#?}");
+        assert!(processors[0].message.contains("frame_rate"));
+        assert!(processors[1].message.contains("donor.mp4"));
+    }
+
+    #[test]
+    fn quarantine_rules_are_checked() {
+        let mut cfg: Config = serde_yaml::from_str(SAMPLE).expect("parse");
+        cfg.quarantine_rules = vec![
+            QuarantineRule {
+                name: "known".to_string(),
+                hash_list: Some("hashes.txt".into()),
+                ..Default::default()
+            },
+            QuarantineRule {
+                name: "known".to_string(),
+                labels: vec!["nudity".to_string()],
+                min_score: 2.0,
+                ..Default::default()
+            },
+        ];
+        cfg.quarantine_recipient = Some("age1notakey".to_string());
+        cfg.encryption_recipient = Some("not a key".to_string());
+        let issues = validate_config(&cfg, None, None);
+        let rules: Vec<_> = issues
+            .iter()
+            .filter(|i| i.field == "quarantine_rules")
+            .collect();
+        assert_eq!(rules.len(), 2, "{issues:#?}");
+        assert!(find(&issues, "quarantine_recipient").is_error());
+        assert!(find(&issues, "encryption_recipient").is_error());
+    }
+
+    #[test]
+    fn type_groups_are_checked() {
+        let mut cfg: Config = serde_yaml::from_str(SAMPLE).expect("parse");
+        cfg.type_groups.insert(
+            "pictures".to_string(),
+            vec!["jpeg".to_string(), "jpg".to_string()],
+        );
+        cfg.type_groups.insert(
+            "jpeg".to_string(),
+            vec!["jpeg".to_string(), "gif".to_string()],
+        );
+        let issues = validate_config(&cfg, None, None);
+        let groups: Vec<_> = issues.iter().filter(|i| i.field == "type_groups").collect();
+        assert_eq!(groups.len(), 2, "{issues:#?}");
+        assert!(groups.iter().all(|i| !i.is_error()));
+        assert!(groups[0].message.contains("`jpeg` shadows"));
+        assert!(groups[1].message.contains("`jpg`"));
+    }
+
+    #[test]
+    fn notification_hooks_are_checked() {
+        let text = SAMPLE.replace(
+            "file_types:\n",
+            "notifications:\n  - kind: webhook\n    url: https://hooks.example/run\n  - kind: email\n    smtp_server: relay.lab\n    from: carver@lab\n    to: []\n    on: [failed, checkpoint]\n  - kind: command\n    program: notify-send\n    timeout_secs: 0\n    on: []\nfile_types:\n",
+        );
+        let cfg: Config = serde_yaml::from_str(&text).expect("parse");
+        assert_eq!(cfg.notifications[0].events().len(), 2);
+        let issues = validate_config(&cfg, None, Some(&text));
+        let hooks: Vec<_> = issues
+            .iter()
+            .filter(|i| i.field == "notifications")
+            .collect();
+        assert_eq!(hooks.len(), 4, "{issues:#?}");
+        assert!(hooks[0].message.contains("https is not supported"));
+        assert!(hooks[1].message.contains("no `to` recipients"));
+        assert!(hooks[2].message.contains("timeout_secs"));
+        assert_eq!(hooks[3].severity, IssueSeverity::Warning);
+        assert!(hooks.iter().all(|i| i.line == Some(11)));
+    }
+}
@@ -0,0 +1,569 @@
+//! # Encrypted output
+//!
+//! With `encryption_recipient` (`--encrypt-to`) set, everything a run writes
+//! from the evidence is encrypted to one age X25519 public key before it
+//! reaches the run directory, so the processing node never stores a readable
+//! copy on disk. The key pair comes from `age-keygen`; only the public half
+//! is given to the carver.
+//!
+//! Files use the age format: a header wrapping a random file key for the
+//! recipient, then the payload in 64 KiB chunks sealed with
+//! ChaCha20-Poly1305 (the STREAM construction). Encrypted files keep their
+//! name with `.age` appended and can be read with `age -d -i key.txt` or the
+//! `decrypt` subcommand.
+//!
+//! - Metadata sinks write through [`OutputFile`], which encrypts each stream
+//!   chunk by chunk as records arrive.
+//! - Everything else that has to be read back by path while the run goes on
+//!   (carved files, which post-processors open, post-processor output, QA
+//!   samples, summaries) is written below a [`StagedOutput`] directory on a
+//!   memory filesystem (`encryption_staging_dir`, `/dev/shm` by default)
+//!   instead of the run directory. Carve workers move each carve into the
+//!   run directory as an age file as soon as the post-processors and the QA
+//!   sampler are done with it; [`StagedOutput::seal_remaining`] moves the
+//!   rest when the run ends. Any file that cannot be sealed fails the run.
+//! - The run bookkeeping is kept in the clear: `result.json`, `passes.json`,
+//!   the run lock, and a checkpoint.
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use age::stream::StreamWriter;
+use anyhow::{Context, Result, anyhow};
+use tracing::{info, warn};
+
+use crate::config::Config;
+use crate::runlock::pid_alive;
+
+/// Extension appended to encrypted files.
+pub const ENCRYPTED_EXT: &str = "age";
+/// Name prefix of the per-run staging directories under
+/// `encryption_staging_dir`, followed by the process ID.
+pub const STAGING_PREFIX: &str = ".swiftbeaver-plain-";
+/// Staging parent used when `encryption_staging_dir` is unset.
+pub const DEFAULT_STAGING_DIR: &str = "/dev/shm";
+
+/// Parse an age X25519 recipient (`age1...`).
+pub fn parse_recipient(key: &str) -> Result<age::x25519::Recipient, String> {
+    key.trim()
+        .parse::<age::x25519::Recipient>()
+        .map_err(|err| err.to_string())
+}
+
+/// `path` with `.age` appended.
+pub fn sealed_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(ENCRYPTED_EXT);
+    PathBuf::from(name)
+}
+
+fn is_sealed(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == ENCRYPTED_EXT)
+}
+
+/// Whether the run in `run_output_dir` wrote encrypted metadata streams.
+pub fn holds_sealed_metadata(run_output_dir: &Path) -> bool {
+    ["metadata", "parquet"].iter().any(|dir| {
+        fs::read_dir(run_output_dir.join(dir))
+            .is_ok_and(|entries| entries.flatten().any(|entry| is_sealed(&entry.path())))
+    })
+}
+
+/// Encrypts output files to one recipient.
+#[derive(Clone)]
+pub struct OutputCipher {
+    recipient: age::x25519::Recipient,
+}
+
+impl OutputCipher {
+    pub fn new(recipient: age::x25519::Recipient) -> Self {
+        Self { recipient }
+    }
+
+    /// `None` unless `encryption_recipient` is set.
+    pub fn from_config(cfg: &Config) -> Result<Option<Self>> {
+        cfg.encryption_recipient
+            .as_deref()
+            .map(|key| {
+                parse_recipient(key)
+                    .map(Self::new)
+                    .map_err(|err| anyhow!("invalid encryption_recipient: {err}"))
+            })
+            .transpose()
+    }
+
+    /// Start an age stream on `output`; the header is written right away.
+    /// The stream must be finished to write its last chunk.
+    pub fn wrap<W: Write>(&self, output: W) -> io::Result<StreamWriter<W>> {
+        let recipient = &self.recipient as &dyn age::Recipient;
+        age::Encryptor::with_recipients(std::iter::once(recipient))
+            .map_err(io::Error::other)?
+            .wrap_output(output)
+    }
+
+    /// Encrypt `src` into the new file `dest`.
+    pub fn copy_sealed(&self, src: &Path, dest: File) -> io::Result<()> {
+        let mut input = File::open(src)?;
+        let mut sealed = self.wrap(BufWriter::new(dest))?;
+        io::copy(&mut input, &mut sealed)?;
+        sealed.finish()?.flush()
+    }
+
+    /// Encrypt `src` into `<dest>.age`, keeping its permissions, and remove
+    /// `src`. Returns the new path.
+    fn seal_into(&self, src: &Path, dest: &Path) -> io::Result<PathBuf> {
+        let dest = sealed_path(dest);
+        let permissions = fs::metadata(src)?.permissions();
+        let output = File::create(&dest)?;
+        output.set_permissions(permissions)?;
+        if let Err(err) = self.copy_sealed(src, output) {
+            let _ = fs::remove_file(&dest);
+            return Err(err);
+        }
+        fs::remove_file(src)?;
+        Ok(dest)
+    }
+}
+
+#[cfg(target_os = "linux")]
+const TMPFS_MAGIC: u32 = 0x0102_1994;
+#[cfg(target_os = "linux")]
+const RAMFS_MAGIC: u32 = 0x8584_58f6;
+
+/// Whether `path` is on a memory filesystem (tmpfs or ramfs), so files
+/// written there never reach a disk. Only Linux can tell; elsewhere the
+/// answer is `false`.
+pub fn is_memory_backed(path: &Path) -> io::Result<bool> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        let c_path = std::ffi::CString::new(path.as_os_str().as_bytes())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        let mut stats = std::mem::MaybeUninit::<libc::statfs>::uninit();
+        // SAFETY: `c_path` is NUL-terminated and `stats` points to writable
+        // memory of the size `statfs` fills.
+        if unsafe { libc::statfs(c_path.as_ptr(), stats.as_mut_ptr()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `statfs` returned 0, so it initialised `stats`.
+        // `f_type` is signed and of varying width; the magic numbers are
+        // 32-bit.
+        let kind = unsafe { stats.assume_init() }.f_type as u32;
+        Ok(kind == TMPFS_MAGIC || kind == RAMFS_MAGIC)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = path;
+        Ok(false)
+    }
+}
+
+/// Plaintext outputs of an encrypted run, kept on a memory filesystem until
+/// they are sealed into the run directory. The staging directory mirrors
+/// the run directory's layout and is removed, with whatever is left in it,
+/// when this is dropped.
+pub struct StagedOutput {
+    cipher: OutputCipher,
+    root: PathBuf,
+    run_dir: PathBuf,
+    sealed: AtomicU64,
+    failed: AtomicU64,
+}
+
+impl StagedOutput {
+    /// Staging for `run_dir` under `parent`, which has to be a memory
+    /// filesystem. Staging directories of processes that are gone are
+    /// removed first.
+    pub fn new(cipher: OutputCipher, parent: &Path, run_dir: &Path) -> Result<Self> {
+        let memory = is_memory_backed(parent)
+            .with_context(|| format!("check encryption staging dir {}", parent.display()))?;
+        if !memory {
+            return Err(anyhow!(
+                "encryption staging dir {} is not a memory filesystem (tmpfs or ramfs); \
+                 plaintext written there could reach a disk",
+                parent.display()
+            ));
+        }
+        let root = parent.join(format!("{STAGING_PREFIX}{}", std::process::id()));
+        let removed = sweep_staging(parent, &root);
+        if removed > 0 {
+            info!(
+                "removed {removed} staging directories left by crashed runs in {}",
+                parent.display()
+            );
+        }
+        create_private_dir(&root)
+            .with_context(|| format!("create encryption staging dir {}", root.display()))?;
+        Ok(Self::with_root(cipher, root, run_dir))
+    }
+
+    fn with_root(cipher: OutputCipher, root: PathBuf, run_dir: &Path) -> Self {
+        Self {
+            cipher,
+            root,
+            run_dir: run_dir.to_path_buf(),
+            sealed: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+        }
+    }
+
+    /// Directory plaintext outputs are written to, in place of the run
+    /// directory.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Move the staged file `path` into the run directory as `<path>.age`;
+    /// files already encrypted (quarantined carves) are moved unchanged.
+    /// The plaintext is removed even when sealing fails, and the failure is
+    /// counted. Returns the path in the run directory.
+    pub fn seal(&self, path: &Path) -> io::Result<PathBuf> {
+        let sealed = self.seal_staged(path);
+        match &sealed {
+            Ok(_) => self.sealed.fetch_add(1, Ordering::Relaxed),
+            Err(_) => {
+                let _ = fs::remove_file(path);
+                self.failed.fetch_add(1, Ordering::Relaxed)
+            }
+        };
+        sealed
+    }
+
+    fn seal_staged(&self, path: &Path) -> io::Result<PathBuf> {
+        let rel = path.strip_prefix(&self.root).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not in the staging directory", path.display()),
+            )
+        })?;
+        let dest = self.run_dir.join(rel);
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if is_sealed(path) {
+            fs::copy(path, &dest)?;
+            fs::remove_file(path)?;
+            return Ok(dest);
+        }
+        self.cipher.seal_into(path, &dest)
+    }
+
+    /// Seal everything still staged. Returns the number of files sealed
+    /// over the whole run.
+    pub fn seal_remaining(&self) -> u64 {
+        let mut pending = vec![self.root.clone()];
+        while let Some(dir) = pending.pop() {
+            let entries = match fs::read_dir(&dir) {
+                Ok(entries) => entries,
+                Err(err) => {
+                    warn!("failed to list {} for encryption: {err}", dir.display());
+                    self.failed.fetch_add(1, Ordering::Relaxed);
+                    continue;
+                }
+            };
+            for entry in entries.flatten() {
+                let path = entry.path();
+                let Ok(kind) = entry.file_type() else {
+                    continue;
+                };
+                if kind.is_dir() {
+                    pending.push(path);
+                } else if let Err(err) = self.seal(&path) {
+                    warn!("failed to encrypt {}: {err}", path.display());
+                }
+            }
+        }
+        self.sealed.load(Ordering::Relaxed)
+    }
+
+    /// Files that could not be sealed and were dropped.
+    pub fn failed(&self) -> u64 {
+        self.failed.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for StagedOutput {
+    fn drop(&mut self) {
+        if let Err(err) = fs::remove_dir_all(&self.root)
+            && err.kind() != io::ErrorKind::NotFound
+        {
+            warn!(
+                "failed to remove encryption staging dir {}: {err}",
+                self.root.display()
+            );
+        }
+    }
+}
+
+/// Remove the staging directories under `parent` whose process is gone,
+/// and `own` if an earlier process with this PID left it. Memory
+/// filesystems are local, so the PID in the name is enough.
+fn sweep_staging(parent: &Path, own: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(parent) else {
+        return 0;
+    };
+    let mut removed = 0;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let pid = entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.strip_prefix(STAGING_PREFIX))
+            .and_then(|pid| pid.parse::<u32>().ok());
+        let Some(pid) = pid else {
+            continue;
+        };
+        if (path == own || !pid_alive(pid)) && fs::remove_dir_all(&path).is_ok() {
+            removed += 1;
+        }
+    }
+    removed
+}
+
+fn create_private_dir(dir: &Path) -> io::Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    builder.create(dir)
+}
+
+/// A file written in the clear or, with a cipher, as an age stream. The
+/// stream's last chunk is written by [`OutputFile::finish`], or when the
+/// file is dropped.
+pub enum OutputFile {
+    Plain(File),
+    Sealed(Option<StreamWriter<File>>),
+}
+
+impl OutputFile {
+    /// Create `path`, or `<path>.age` when `cipher` is set.
+    pub fn create(path: &Path, cipher: Option<&OutputCipher>) -> io::Result<Self> {
+        match cipher {
+            Some(_) => Self::wrap(File::create(sealed_path(path))?, cipher),
+            None => File::create(path).map(Self::Plain),
+        }
+    }
+
+    /// Write to `file`, which the caller created (as `<path>.age` when
+    /// `cipher` is set), encrypting when `cipher` is set.
+    pub fn wrap(file: File, cipher: Option<&OutputCipher>) -> io::Result<Self> {
+        match cipher {
+            Some(cipher) => Ok(Self::Sealed(Some(cipher.wrap(file)?))),
+            None => Ok(Self::Plain(file)),
+        }
+    }
+
+    /// Whether nothing has been written to the file yet. A new encrypted
+    /// file counts as empty even though its header is written.
+    pub fn is_empty(&self) -> io::Result<bool> {
+        match self {
+            Self::Plain(file) => Ok(file.metadata()?.len() == 0),
+            Self::Sealed(_) => Ok(true),
+        }
+    }
+
+    /// Write the last chunk of an encrypted stream; later writes fail.
+    pub fn finish(&mut self) -> io::Result<()> {
+        match self {
+            Self::Plain(file) => file.flush(),
+            Self::Sealed(stream) => match stream.take() {
+                Some(stream) => stream.finish()?.flush(),
+                None => Ok(()),
+            },
+        }
+    }
+}
+
+impl Write for OutputFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Plain(file) => file.write(buf),
+            Self::Sealed(Some(stream)) => stream.write(buf),
+            Self::Sealed(None) => Err(io::Error::other("encrypted stream already finished")),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Plain(file) => file.flush(),
+            Self::Sealed(Some(stream)) => stream.flush(),
+            Self::Sealed(None) => Ok(()),
+        }
+    }
+}
+
+impl Drop for OutputFile {
+    fn drop(&mut self) {
+        if let Err(err) = self.finish() {
+            warn!("failed to finish encrypted output: {err}");
+        }
+    }
+}
+
+/// Private keys read from an age identity file (`age-keygen` output).
+pub struct Identities(Vec<Box<dyn age::Identity>>);
+
+impl Identities {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let identities = age::IdentityFile::from_file(path.to_string_lossy().to_string())
+            .with_context(|| format!("read identity file {}", path.display()))?
+            .into_identities()
+            .map_err(|err| anyhow!("identity file {}: {err}", path.display()))?;
+        if identities.is_empty() {
+            return Err(anyhow!("identity file {} holds no keys", path.display()));
+        }
+        Ok(Self(identities))
+    }
+
+    /// Decrypt the age file `src` into `dest`.
+    pub fn decrypt_file(&self, src: &Path, dest: &Path) -> Result<()> {
+        let input =
+            BufReader::new(File::open(src).with_context(|| format!("open {}", src.display()))?);
+        let mut reader = age::Decryptor::new_buffered(input)
+            .and_then(|decryptor| decryptor.decrypt(self.0.iter().map(|id| id.as_ref())))
+            .map_err(|err| anyhow!("decrypt {}: {err}", src.display()))?;
+        let mut output = BufWriter::new(
+            File::create(dest).with_context(|| format!("create {}", dest.display()))?,
+        );
+        let copied = io::copy(&mut reader, &mut output).and_then(|_| output.flush());
+        if let Err(err) = copied {
+            drop(output);
+            let _ = fs::remove_file(dest);
+            return Err(anyhow!("decrypt {}: {err}", src.display()));
+        }
+        Ok(())
+    }
+
+    /// Write a readable copy of `src` (a file or a run directory) to the
+    /// directory `dest`: `.age` files are decrypted without the suffix,
+    /// other files are copied. Returns the number of files decrypted and
+    /// copied.
+    pub fn decrypt_tree(&self, src: &Path, dest: &Path) -> Result<(u64, u64)> {
+        let mut counts = (0, 0);
+        if src.is_file() {
+            fs::create_dir_all(dest).with_context(|| format!("create {}", dest.display()))?;
+            let name = src.file_name().unwrap_or(src.as_os_str());
+            self.restore(src, &dest.join(name), &mut counts)?;
+            return Ok(counts);
+        }
+        let mut pending = vec![PathBuf::new()];
+        while let Some(rel) = pending.pop() {
+            let dir = dest.join(&rel);
+            fs::create_dir_all(&dir).with_context(|| format!("create {}", dir.display()))?;
+            let entries =
+                fs::read_dir(src.join(&rel)).with_context(|| format!("list {}", src.display()))?;
+            for entry in entries {
+                let entry = entry?;
+                let rel = rel.join(entry.file_name());
+                if entry.file_type()?.is_dir() {
+                    pending.push(rel);
+                } else {
+                    self.restore(&entry.path(), &dest.join(&rel), &mut counts)?;
+                }
+            }
+        }
+        Ok(counts)
+    }
+
+    fn restore(&self, src: &Path, dest: &Path, counts: &mut (u64, u64)) -> Result<()> {
+        if is_sealed(src) {
+            self.decrypt_file(src, &dest.with_extension(""))?;
+            counts.0 += 1;
+        } else {
+            fs::copy(src, dest)
+                .with_context(|| format!("copy {} to {}", src.display(), dest.display()))?;
+            counts.1 += 1;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use age::secrecy::ExposeSecret;
+
+    #[test]
+    fn sealed_streams_and_trees_decrypt() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let key = age::x25519::Identity::generate();
+        let cipher = OutputCipher::new(key.to_public());
+        let run = dir.path().join("run");
+        let stage = dir.path().join("stage");
+        fs::create_dir_all(&run).unwrap();
+        fs::create_dir_all(stage.join("carved/jpeg")).unwrap();
+        fs::create_dir_all(stage.join("summaries")).unwrap();
+        fs::write(stage.join("carved/jpeg/a.jpg"), b"jpeg bytes").unwrap();
+        fs::write(stage.join("summaries/s.json"), b"[]").unwrap();
+        fs::write(run.join("result.json"), b"{}").unwrap();
+
+        // Larger than one 64 KiB chunk, written in small records.
+        let line = b"{\"record\":1}\n";
+        let mut stream = OutputFile::create(&run.join("files.jsonl"), Some(&cipher)).unwrap();
+        assert!(stream.is_empty().unwrap());
+        for _ in 0..10_000 {
+            stream.write_all(line).unwrap();
+        }
+        drop(stream);
+
+        let staged = StagedOutput::with_root(cipher, stage.clone(), &run);
+        let sealed = staged.seal(&stage.join("carved/jpeg/a.jpg")).unwrap();
+        assert_eq!(sealed, run.join("carved/jpeg/a.jpg.age"));
+        assert!(!stage.join("carved/jpeg/a.jpg").exists());
+        assert_eq!(staged.seal_remaining(), 2);
+        assert_eq!(staged.failed(), 0);
+        assert!(staged.seal(&dir.path().join("elsewhere")).is_err());
+        assert_eq!(staged.failed(), 1);
+        assert!(run.join("summaries/s.json.age").exists());
+        assert!(run.join("result.json").exists());
+        drop(staged);
+        assert!(!stage.exists());
+
+        let key_path = dir.path().join("key.txt");
+        fs::write(&key_path, format!("{}\n", key.to_string().expose_secret())).unwrap();
+        let identities = Identities::from_file(&key_path).unwrap();
+        let plain = dir.path().join("plain");
+        assert_eq!(identities.decrypt_tree(&run, &plain).unwrap(), (3, 1));
+        assert_eq!(
+            fs::read(plain.join("carved/jpeg/a.jpg")).unwrap(),
+            b"jpeg bytes"
+        );
+        assert_eq!(
+            fs::read(plain.join("files.jsonl")).unwrap(),
+            line.repeat(10_000)
+        );
+        assert_eq!(fs::read(plain.join("result.json")).unwrap(), b"{}");
+    }
+
+    #[test]
+    fn wrong_key_and_truncated_streams_fail() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let cipher = OutputCipher::new(age::x25519::Identity::generate().to_public());
+        let path = dir.path().join("a.bin");
+        fs::write(&path, vec![7u8; 100_000]).unwrap();
+        let sealed = cipher.seal_into(&path, &path).unwrap();
+
+        let other = dir.path().join("other.txt");
+        let key = age::x25519::Identity::generate();
+        fs::write(&other, key.to_string().expose_secret()).unwrap();
+        let identities = Identities::from_file(&other).unwrap();
+        let dest = dir.path().join("out.bin");
+        assert!(identities.decrypt_file(&sealed, &dest).is_err());
+        assert!(!dest.exists());
+
+        // A stream cut short lacks its last chunk.
+        let mine = OutputCipher::new(key.to_public());
+        fs::write(&path, vec![7u8; 100_000]).unwrap();
+        let sealed = mine.seal_into(&path, &path).unwrap();
+        let bytes = fs::read(&sealed).unwrap();
+        fs::write(&sealed, &bytes[..bytes.len() - 100]).unwrap();
+        assert!(identities.decrypt_file(&sealed, &dest).is_err());
+    }
+}
@@ -2,7 +2,21 @@ use std::fs::{File, OpenOptions};
 
 use thiserror::Error;
 
+use crate::parsers::vss;
+
+mod gzip;
+mod stream;
+mod transform;
+
+pub use gzip::GzipSource;
+pub use stream::StreamSource;
+pub use transform::{ByteTransform, TransformedSource};
+
+/// [`EvidenceSource::len`] of a stream that has not ended yet.
+pub const UNKNOWN_LEN: u64 = u64::MAX;
+
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum EvidenceError {
     #[error("io error: {0}")]
     Io(#[from] std::io::Error),
@@ -14,6 +28,9 @@ pub enum EvidenceError {
 
 /// A read-only evidence source backed by a linear byte space.
 ///
+/// Part of the stable API (see `docs/api.md`). Implementations are shared
+/// across worker threads and must support concurrent positional reads.
+///
 /// # Example
 /// ```rust
 /// use swiftbeaver::evidence::{EvidenceSource, RawFileSource};
@@ -26,8 +43,361 @@ pub enum EvidenceError {
 /// assert_eq!(&buf, b"hello");
 /// ```
 pub trait EvidenceSource: Send + Sync {
+    /// Total size of the byte space in bytes; [`UNKNOWN_LEN`] for a stream
+    /// still being read.
     fn len(&self) -> u64;
+
+    /// Read up to `buf.len()` bytes at `offset` and return the number read.
+    /// Short reads are allowed; `Ok(0)` means `offset` is at or past the end.
     fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize, EvidenceError>;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Unit the source decodes data in, such as the compressed chunk of an
+    /// EWF image. Reads inside one block cost as much as reading all of it;
+    /// `None` when any offset is as cheap as another.
+    fn block_size(&self) -> Option<u64> {
+        None
+    }
+
+    /// Read several ranges in one call: each `(offset, buf)` is filled like
+    /// repeated `read_at` calls until it is full or the evidence ends, and
+    /// the bytes read per range are returned in order. Sources that can
+    /// batch scattered reads (decoding shared blocks once, queueing them to
+    /// the device together) override the default, which reads one by one.
+    fn read_vectored_at(
+        &self,
+        reads: &mut [(u64, &mut [u8])],
+    ) -> Result<Vec<usize>, EvidenceError> {
+        reads
+            .iter_mut()
+            .map(|(offset, buf)| read_full(self, *offset, buf))
+            .collect()
+    }
+
+    /// Hint that `len` bytes at `offset` will be read soon, so the source
+    /// can start loading them. Purely advisory: the default does nothing,
+    /// and a source may ignore any hint.
+    fn prefetch(&self, _offset: u64, _len: u64) {}
+
+    /// Bytes held by a source read once from a stream ([`StreamSource`]),
+    /// which can only serve reads inside that window and whose length is
+    /// unknown until the stream ends; `None` for random access sources.
+    fn stream_window(&self) -> Option<u64> {
+        None
+    }
+
+    /// Nothing before `offset` will be read again. Streaming sources drop
+    /// those bytes to make room; the default does nothing.
+    fn release_before(&self, _offset: u64) {}
+}
+
+/// Read into `buf` at `offset` until it is full or the evidence ends.
+fn read_full<S: EvidenceSource + ?Sized>(
+    source: &S,
+    offset: u64,
+    buf: &mut [u8],
+) -> Result<usize, EvidenceError> {
+    let mut read = 0usize;
+    while read < buf.len() {
+        let n = source.read_at(offset + read as u64, &mut buf[read..])?;
+        if n == 0 {
+            break;
+        }
+        read += n;
+    }
+    Ok(read)
+}
+
+/// Ask the kernel to read `len` bytes at `offset` of `file` ahead of use.
+#[cfg(target_os = "linux")]
+fn advise_willneed(file: &File, offset: u64, len: u64) {
+    use std::os::unix::io::AsRawFd;
+
+    let (Ok(offset), Ok(len)) = (libc::off_t::try_from(offset), libc::off_t::try_from(len)) else {
+        return;
+    };
+    // Advisory only; a failure just means no read-ahead.
+    unsafe {
+        libc::posix_fadvise(file.as_raw_fd(), offset, len, libc::POSIX_FADV_WILLNEED);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn advise_willneed(_file: &File, _offset: u64, _len: u64) {}
+
+/// Keeps recently decoded blocks of a source with a [`EvidenceSource::block_size`],
+/// so the reader thread's sequential pass decodes each block once and carve
+/// workers reading hits in recently scanned chunks are served from memory.
+/// The oldest blocks are dropped first once `capacity` bytes are held.
+pub struct BlockCacheSource {
+    inner: std::sync::Arc<dyn EvidenceSource>,
+    block: u64,
+    max_blocks: usize,
+    cache: std::sync::Mutex<BlockCache>,
+    hits: std::sync::atomic::AtomicU64,
+    misses: std::sync::atomic::AtomicU64,
+}
+
+#[derive(Default)]
+struct BlockCache {
+    blocks: std::collections::HashMap<u64, std::sync::Arc<[u8]>>,
+    order: std::collections::VecDeque<u64>,
+}
+
+impl BlockCacheSource {
+    pub fn new(inner: std::sync::Arc<dyn EvidenceSource>, block: u64, capacity: u64) -> Self {
+        let block = block.max(1);
+        Self {
+            inner,
+            block,
+            max_blocks: (capacity / block).max(1) as usize,
+            cache: std::sync::Mutex::new(BlockCache::default()),
+            hits: std::sync::atomic::AtomicU64::new(0),
+            misses: std::sync::atomic::AtomicU64::new(0),
+        }
+    }
+
+    /// Block reads served from the cache and from the inner source.
+    pub fn stats(&self) -> (u64, u64) {
+        use std::sync::atomic::Ordering;
+        (
+            self.hits.load(Ordering::Relaxed),
+            self.misses.load(Ordering::Relaxed),
+        )
+    }
+
+    fn block_at(&self, index: u64) -> Result<std::sync::Arc<[u8]>, EvidenceError> {
+        use std::sync::atomic::Ordering;
+        let lock_err = || EvidenceError::Unsupported("block cache lock poisoned".to_string());
+        if let Some(block) = self
+            .cache
+            .lock()
+            .map_err(|_| lock_err())?
+            .blocks
+            .get(&index)
+        {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(block.clone());
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let start = index * self.block;
+        let len = self.block.min(self.inner.len().saturating_sub(start)) as usize;
+        let mut data = vec![0u8; len];
+        let mut read = 0;
+        while read < len {
+            let n = self.inner.read_at(start + read as u64, &mut data[read..])?;
+            if n == 0 {
+                break;
+            }
+            read += n;
+        }
+        data.truncate(read);
+        let block: std::sync::Arc<[u8]> = data.into();
+        let mut cache = self.cache.lock().map_err(|_| lock_err())?;
+        if cache.blocks.insert(index, block.clone()).is_none() {
+            cache.order.push_back(index);
+        }
+        while cache.order.len() > self.max_blocks {
+            if let Some(old) = cache.order.pop_front() {
+                cache.blocks.remove(&old);
+            }
+        }
+        Ok(block)
+    }
+}
+
+impl EvidenceSource for BlockCacheSource {
+    fn len(&self) -> u64 {
+        self.inner.len()
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize, EvidenceError> {
+        let mut copied = 0usize;
+        while copied < buf.len() {
+            let pos = offset + copied as u64;
+            if pos >= self.len() {
+                break;
+            }
+            let block = self.block_at(pos / self.block)?;
+            let within = (pos % self.block) as usize;
+            let Some(available) = block.get(within..) else {
+                break;
+            };
+            if available.is_empty() {
+                break;
+            }
+            let n = available.len().min(buf.len() - copied);
+            buf[copied..copied + n].copy_from_slice(&available[..n]);
+            copied += n;
+        }
+        Ok(copied)
+    }
+
+    fn block_size(&self) -> Option<u64> {
+        Some(self.block)
+    }
+
+    /// Blocks already cached are skipped; the rest is passed on as whole
+    /// blocks.
+    fn prefetch(&self, offset: u64, len: u64) {
+        let end = offset.saturating_add(len).min(self.len());
+        if offset >= end {
+            return;
+        }
+        let first = offset / self.block;
+        let last = (end - 1) / self.block;
+        let Ok(cache) = self.cache.lock() else {
+            return;
+        };
+        let missing = (first..=last).find(|index| !cache.blocks.contains_key(index));
+        drop(cache);
+        if let Some(index) = missing {
+            let start = index * self.block;
+            self.inner.prefetch(start, (last + 1) * self.block - start);
+        }
+    }
+}
+
+/// One Volume Shadow Copy snapshot of an NTFS volume in `inner`: byte `n` is
+/// byte `n` of the volume as it was when the snapshot was taken.
+///
+/// A 16 KiB block comes from the oldest store, from this snapshot's onwards,
+/// that copied it, and from the current volume if none did. Forwarder
+/// descriptors redirect the lookup to another block of the next store;
+/// overlay descriptors replace single sectors, older stores winning.
+pub struct ShadowCopySource {
+    inner: std::sync::Arc<dyn EvidenceSource>,
+    volume_offset: u64,
+    len: u64,
+    stores: std::sync::Arc<Vec<vss::ShadowStore>>,
+    index: usize,
+}
+
+impl ShadowCopySource {
+    /// Snapshot `index` of `stores`, which must be ordered oldest first as
+    /// [`vss::read_stores`] returns them, of the volume at `volume_offset`.
+    pub fn new(
+        inner: std::sync::Arc<dyn EvidenceSource>,
+        volume_offset: u64,
+        stores: std::sync::Arc<Vec<vss::ShadowStore>>,
+        index: usize,
+    ) -> Self {
+        let len = stores.get(index).map_or(0, |store| store.volume_size);
+        Self {
+            inner,
+            volume_offset,
+            len,
+            stores,
+            index,
+        }
+    }
+
+    pub fn store(&self) -> &vss::ShadowStore {
+        &self.stores[self.index]
+    }
+
+    /// Fill `out` from volume block `block`, starting `within` bytes in.
+    fn read_block(&self, block: u64, within: u64, out: &mut [u8]) -> Result<(), EvidenceError> {
+        let mut original = block;
+        let mut overlays = Vec::new();
+        let mut copied_at = None;
+        for store in self.stores.iter().skip(self.index) {
+            let Some(descriptor) = store.blocks.get(&original) else {
+                continue;
+            };
+            if descriptor.flags & vss::FLAG_NOT_USED != 0 {
+                continue;
+            }
+            if descriptor.flags & vss::FLAG_FORWARDER != 0 {
+                original = descriptor.relative_offset;
+            } else if descriptor.flags & vss::FLAG_OVERLAY != 0 {
+                overlays.push(*descriptor);
+            } else {
+                copied_at = Some(descriptor.store_offset);
+                break;
+            }
+        }
+        self.read_volume(copied_at.unwrap_or(original) + within, out)?;
+        let end = within + out.len() as u64;
+        for overlay in overlays.iter().rev() {
+            for sector in within / 512..end.div_ceil(512) {
+                if overlay.bitmap & (1 << sector) == 0 {
+                    continue;
+                }
+                let start = (sector * 512).max(within);
+                let stop = ((sector + 1) * 512).min(end);
+                self.read_volume(
+                    overlay.store_offset + start,
+                    &mut out[(start - within) as usize..(stop - within) as usize],
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Read volume bytes at `offset`, zero-filling past the evidence end.
+    fn read_volume(&self, offset: u64, buf: &mut [u8]) -> Result<(), EvidenceError> {
+        let mut done = 0usize;
+        while done < buf.len() {
+            let n = self
+                .inner
+                .read_at(self.volume_offset + offset + done as u64, &mut buf[done..])?;
+            if n == 0 {
+                buf[done..].fill(0);
+                break;
+            }
+            done += n;
+        }
+        Ok(())
+    }
+}
+
+impl EvidenceSource for ShadowCopySource {
+    fn len(&self) -> u64 {
+        self.len
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize, EvidenceError> {
+        let total = buf.len().min(self.len.saturating_sub(offset) as usize);
+        let mut copied = 0usize;
+        while copied < total {
+            let pos = offset + copied as u64;
+            let within = pos % vss::BLOCK_SIZE;
+            let n = ((vss::BLOCK_SIZE - within) as usize).min(total - copied);
+            self.read_block(pos - within, within, &mut buf[copied..copied + n])?;
+            copied += n;
+        }
+        Ok(copied)
+    }
+}
+
+/// Evidence held in memory, such as an image handed over by an embedding
+/// application that has no file to open.
+pub struct MemorySource {
+    data: Vec<u8>,
+}
+
+impl MemorySource {
+    pub fn new(data: Vec<u8>) -> Self {
+        Self { data }
+    }
+}
+
+impl EvidenceSource for MemorySource {
+    fn len(&self) -> u64 {
+        self.data.len() as u64
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize, EvidenceError> {
+        let start = usize::try_from(offset).unwrap_or(usize::MAX);
+        let data = self.data.get(start..).unwrap_or_default();
+        let n = data.len().min(buf.len());
+        buf[..n].copy_from_slice(&data[..n]);
+        Ok(n)
+    }
 }
 
 pub struct RawFileSource {
@@ -73,6 +443,260 @@ impl EvidenceSource for RawFileSource {
             Ok(f.read(buf)?)
         }
     }
+
+    fn prefetch(&self, offset: u64, len: u64) {
+        advise_willneed(&self.file, offset, len);
+    }
+}
+
+/// A raw image split into numbered segments (`image.000`, `image.001`, ... or
+/// `image.001`, `image.002`, ...) read as one byte space, each segment
+/// following the previous one without a gap.
+pub struct SegmentedRawSource {
+    segments: Vec<RawFileSource>,
+    /// Logical offset of each segment's first byte.
+    starts: Vec<u64>,
+    len: u64,
+}
+
+impl SegmentedRawSource {
+    pub fn open(paths: &[std::path::PathBuf]) -> Result<Self, EvidenceError> {
+        if paths.is_empty() {
+            return Err(EvidenceError::Unsupported(
+                "no raw image segments".to_string(),
+            ));
+        }
+        let mut segments = Vec::with_capacity(paths.len());
+        let mut starts = Vec::with_capacity(paths.len());
+        let mut len = 0u64;
+        for path in paths {
+            let segment = RawFileSource::open(path)?;
+            starts.push(len);
+            len += segment.len();
+            segments.push(segment);
+        }
+        Ok(Self {
+            segments,
+            starts,
+            len,
+        })
+    }
+
+    pub fn segment_count(&self) -> usize {
+        self.segments.len()
+    }
+
+    /// Index of the segment holding `offset` (`offset < len`). An empty
+    /// segment shares its start with the next one and is never chosen.
+    fn segment_at(&self, offset: u64) -> usize {
+        self.starts.partition_point(|start| *start <= offset) - 1
+    }
+}
+
+impl EvidenceSource for SegmentedRawSource {
+    fn len(&self) -> u64 {
+        self.len
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize, EvidenceError> {
+        let mut read = 0usize;
+        while read < buf.len() {
+            let pos = offset + read as u64;
+            if pos >= self.len {
+                break;
+            }
+            let index = self.segment_at(pos);
+            let n = self.segments[index].read_at(pos - self.starts[index], &mut buf[read..])?;
+            if n == 0 {
+                // The segment shrank after it was opened.
+                break;
+            }
+            read += n;
+        }
+        Ok(read)
+    }
+
+    fn prefetch(&self, offset: u64, len: u64) {
+        let end = offset.saturating_add(len).min(self.len);
+        let mut pos = offset;
+        while pos < end {
+            let index = self.segment_at(pos);
+            let segment_end = self.starts[index] + self.segments[index].len();
+            let n = segment_end.min(end) - pos;
+            self.segments[index].prefetch(pos - self.starts[index], n);
+            pos += n;
+        }
+    }
+}
+
+/// Segments of a split raw image given its first segment: a numeric
+/// extension of two or more digits, `0` or `1`, followed by consecutive
+/// numbers of the same width. `None` when `first` is not named like a first
+/// segment or no second segment exists, so it is read as a plain raw file.
+pub fn raw_segment_paths(
+    first: &std::path::Path,
+) -> Result<Option<Vec<std::path::PathBuf>>, EvidenceError> {
+    let Some(ext) = first.extension().and_then(|ext| ext.to_str()) else {
+        return Ok(None);
+    };
+    if ext.len() < 2 || !ext.bytes().all(|b| b.is_ascii_digit()) {
+        return Ok(None);
+    }
+    let width = ext.len();
+    let Ok(number) = ext.parse::<u64>() else {
+        return Ok(None);
+    };
+    let segment = |n: u64| first.with_extension(format!("{n:0width$}"));
+    if number > 1 || !segment(number + 1).is_file() {
+        return Ok(None);
+    }
+    if number == 1 && segment(0).is_file() {
+        return Err(EvidenceError::Unsupported(format!(
+            "{} is not the first segment; pass {}",
+            first.display(),
+            segment(0).display()
+        )));
+    }
+    let mut paths = Vec::new();
+    let mut next = number;
+    while segment(next).is_file() {
+        paths.push(segment(next));
+        next += 1;
+    }
+    // A gap leaves later segments behind the first missing one.
+    for skip in 1..=2 {
+        if segment(next + skip).is_file() {
+            return Err(EvidenceError::Unsupported(format!(
+                "raw image segment {} is missing",
+                segment(next).display()
+            )));
+        }
+    }
+    Ok(Some(paths))
+}
+
+/// A raw image mapped read-only into memory (`--mmap`). Reads copy from the
+/// mapping, with no system call once the pages are resident.
+///
+/// An I/O error while a page is faulted in raises `SIGBUS` instead of
+/// returning an error, so images on failing media should be read with
+/// [`RawFileSource`].
+#[cfg(unix)]
+pub struct MappedFileSource {
+    ptr: std::ptr::NonNull<u8>,
+    len: usize,
+    _file: File,
+}
+
+// SAFETY: the mapping is read-only (`PROT_READ`) and is only unmapped in
+// `drop`, which needs the source by value, so sharing the pointer between
+// threads gives every thread the same immutable bytes for as long as any of
+// them can reach the source.
+#[cfg(unix)]
+unsafe impl Send for MappedFileSource {}
+#[cfg(unix)]
+unsafe impl Sync for MappedFileSource {}
+
+#[cfg(unix)]
+impl MappedFileSource {
+    pub fn open(path: &std::path::Path) -> Result<Self, EvidenceError> {
+        use std::os::unix::io::AsRawFd;
+
+        let file = File::open(path)?;
+        let len = usize::try_from(file.metadata()?.len())
+            .map_err(|_| EvidenceError::Unsupported("file too large to map".to_string()))?;
+        if len == 0 {
+            return Err(EvidenceError::Unsupported(
+                "cannot map an empty file".to_string(),
+            ));
+        }
+        // SAFETY: a fresh read-only private mapping of `len` bytes (checked
+        // non-zero above) of a file we hold open; the kernel picks the
+        // address, so no existing memory is replaced. The result is checked
+        // against `MAP_FAILED` before use.
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        let ptr = std::ptr::NonNull::new(ptr.cast::<u8>())
+            .ok_or_else(|| EvidenceError::Unsupported("mmap returned null".to_string()))?;
+        Ok(Self {
+            ptr,
+            len,
+            _file: file,
+        })
+    }
+
+    fn bytes(&self) -> &[u8] {
+        // SAFETY: `ptr` is the start of a successful mapping of exactly `len`
+        // bytes, fixed at `open` and never changed. The slice borrows `self`,
+        // and the mapping is only unmapped in `drop`, so it cannot outlive the
+        // mapping. The pages are mapped `PROT_READ`, so nothing in this
+        // process writes to them; the evidence file itself is assumed not to
+        // change during the run (see the type's docs on failing media).
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+#[cfg(unix)]
+impl EvidenceSource for MappedFileSource {
+    fn len(&self) -> u64 {
+        self.len as u64
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize, EvidenceError> {
+        let Ok(start) = usize::try_from(offset) else {
+            return Ok(0);
+        };
+        let Some(available) = self.bytes().get(start..) else {
+            return Ok(0);
+        };
+        let n = buf.len().min(available.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        Ok(n)
+    }
+
+    fn prefetch(&self, offset: u64, len: u64) {
+        let page = 4096u64;
+        let start = offset - offset % page;
+        let end = offset.saturating_add(len).min(self.len as u64);
+        if start >= end {
+            return;
+        }
+        // Advisory only, like `posix_fadvise` for pread; an error (such as a
+        // start not aligned to a larger page size) is ignored.
+        // SAFETY: `start < end <= len`, so the range lies inside the mapping,
+        // which `&self` keeps alive. `MADV_WILLNEED` only schedules reads and
+        // does not change the mapping's contents.
+        unsafe {
+            libc::madvise(
+                self.ptr.as_ptr().add(start as usize).cast(),
+                (end - start) as usize,
+                libc::MADV_WILLNEED,
+            );
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for MappedFileSource {
+    fn drop(&mut self) {
+        // SAFETY: `ptr` and `len` describe the mapping made in `open`, which
+        // is unmapped only here, once. No slice from `bytes` can still exist,
+        // since they all borrow the source being dropped.
+        unsafe {
+            libc::munmap(self.ptr.as_ptr().cast(), self.len);
+        }
+    }
 }
 
 pub struct DeviceSource {
@@ -137,6 +761,10 @@ impl EvidenceSource for DeviceSource {
             Ok(f.read(buf)?)
         }
     }
+
+    fn prefetch(&self, offset: u64, len: u64) {
+        advise_willneed(&self.file, offset, len);
+    }
 }
 
 #[cfg(target_os = "linux")]
@@ -165,7 +793,7 @@ fn device_len(_file: &File, fallback_len: u64) -> Result<u64, EvidenceError> {
 #[cfg(feature = "ewf")]
 mod ewf {
     use std::ffi::{CStr, CString};
-    use std::path::Path;
+    use std::path::PathBuf;
     use std::ptr;
     use std::sync::Mutex;
 
@@ -176,8 +804,6 @@ mod ewf {
     type LibEwfHandle = libc::intptr_t;
     type LibEwfError = libc::intptr_t;
 
-    const LIBEWF_FORMAT_UNKNOWN: u8 = 0x00;
-
     #[link(name = "ewf")]
     unsafe extern "C" {
         fn libewf_get_access_flags_read() -> c_int;
@@ -186,20 +812,6 @@ mod ewf {
             filename: *const c_char,
             error: *mut *mut LibEwfError,
         ) -> c_int;
-        fn libewf_glob(
-            filename: *const c_char,
-            filename_length: size_t,
-            format: u8,
-            filenames: *mut *mut *mut c_char,
-            number_of_filenames: *mut c_int,
-            error: *mut *mut LibEwfError,
-        ) -> c_int;
-        fn libewf_glob_free(
-            filenames: *mut *mut c_char,
-            number_of_filenames: c_int,
-            error: *mut *mut LibEwfError,
-        ) -> c_int;
-
         fn libewf_handle_initialize(
             handle: *mut *mut LibEwfHandle,
             error: *mut *mut LibEwfError,
@@ -221,6 +833,16 @@ mod ewf {
             media_size: *mut u64,
             error: *mut *mut LibEwfError,
         ) -> c_int;
+        fn libewf_handle_get_sectors_per_chunk(
+            handle: *mut LibEwfHandle,
+            sectors_per_chunk: *mut u32,
+            error: *mut *mut LibEwfError,
+        ) -> c_int;
+        fn libewf_handle_get_bytes_per_sector(
+            handle: *mut LibEwfHandle,
+            bytes_per_sector: *mut u32,
+            error: *mut *mut LibEwfError,
+        ) -> c_int;
         fn libewf_handle_read_random(
             handle: *mut LibEwfHandle,
             buffer: *mut c_void,
@@ -241,6 +863,7 @@ mod ewf {
     pub struct EwfSource {
         handle: Mutex<HandleInner>,
         len: u64,
+        chunk_size: Option<u64>,
     }
 
     // SAFETY: libewf handle access is serialized via the mutex.
@@ -248,9 +871,17 @@ mod ewf {
     unsafe impl Sync for EwfSource {}
 
     impl EwfSource {
-        pub fn open(path: &Path) -> Result<Self, EvidenceError> {
-            let c_path = CString::new(path.to_string_lossy().as_bytes())
+        /// Open a segment set already listed and verified by
+        /// [`crate::parsers::ewf::segment_paths`].
+        pub fn open(segments: &[PathBuf]) -> Result<Self, EvidenceError> {
+            let c_paths = segments
+                .iter()
+                .map(|path| CString::new(path.to_string_lossy().as_bytes()))
+                .collect::<Result<Vec<_>, _>>()
                 .map_err(|_| EvidenceError::Unsupported("path contains null byte".to_string()))?;
+            let c_path = c_paths
+                .first()
+                .ok_or_else(|| EvidenceError::Unsupported("no EWF segments".to_string()))?;
 
             unsafe {
                 let mut error: *mut LibEwfError = ptr::null_mut();
@@ -272,30 +903,18 @@ mod ewf {
                     return Err(EvidenceError::Unsupported(error_to_string(error)));
                 }
 
-                let mut filenames: *mut *mut c_char = ptr::null_mut();
-                let mut number_of_filenames: c_int = 0;
-                let rc = libewf_glob(
-                    c_path.as_ptr(),
-                    c_path.as_bytes().len(),
-                    LIBEWF_FORMAT_UNKNOWN,
-                    &mut filenames,
-                    &mut number_of_filenames,
-                    &mut error,
-                );
-                if rc != 1 {
-                    let _ = libewf_handle_free(&mut handle, &mut error);
-                    return Err(EvidenceError::Unsupported(error_to_string(error)));
-                }
-
+                let mut filenames: Vec<*mut c_char> = c_paths
+                    .iter()
+                    .map(|path| path.as_ptr() as *mut c_char)
+                    .collect();
                 let access_flags = libewf_get_access_flags_read();
                 let rc = libewf_handle_open(
                     handle,
-                    filenames,
-                    number_of_filenames,
+                    filenames.as_mut_ptr(),
+                    filenames.len() as c_int,
                     access_flags,
                     &mut error,
                 );
-                let _ = libewf_glob_free(filenames, number_of_filenames, &mut error);
                 if rc != 1 {
                     let _ = libewf_handle_free(&mut handle, &mut error);
                     return Err(EvidenceError::Unsupported(error_to_string(error)));
@@ -308,9 +927,31 @@ mod ewf {
                     return Err(EvidenceError::Unsupported(error_to_string(error)));
                 }
 
+                // Sectors per chunk times bytes per sector is the unit libewf
+                // decompresses; unknown values leave reads unaligned.
+                let mut sectors_per_chunk: u32 = 0;
+                let mut bytes_per_sector: u32 = 0;
+                let chunk_size = if libewf_handle_get_sectors_per_chunk(
+                    handle,
+                    &mut sectors_per_chunk,
+                    &mut error,
+                ) == 1
+                    && libewf_handle_get_bytes_per_sector(handle, &mut bytes_per_sector, &mut error)
+                        == 1
+                {
+                    Some(u64::from(sectors_per_chunk) * u64::from(bytes_per_sector))
+                        .filter(|&size| size > 0)
+                } else {
+                    if !error.is_null() {
+                        libewf_error_free(&mut error);
+                    }
+                    None
+                };
+
                 Ok(Self {
                     handle: Mutex::new(HandleInner { handle }),
                     len: media_size,
+                    chunk_size,
                 })
             }
         }
@@ -352,6 +993,10 @@ mod ewf {
                 Ok(read as usize)
             }
         }
+
+        fn block_size(&self) -> Option<u64> {
+            self.chunk_size
+        }
     }
 
     impl Drop for EwfSource {
@@ -400,36 +1045,100 @@ mod ewf {
 }
 
 use crate::cli::CliOptions;
+use crate::constants::MIB;
+use crate::parsers::ewf::{AcquisitionInfo, EwfFormat};
 
 pub fn open_source(opts: &CliOptions) -> Result<Box<dyn EvidenceSource>, EvidenceError> {
-    if is_ewf_path(&opts.input) {
+    open_source_with_info(opts).map(|(source, _)| source)
+}
+
+/// Like [`open_source`], also returning the acquisition metadata of EWF
+/// inputs. Every segment of an EWF set is checked before libewf opens it.
+pub fn open_source_with_info(
+    opts: &CliOptions,
+) -> Result<(Box<dyn EvidenceSource>, Option<AcquisitionInfo>), EvidenceError> {
+    let input = opts
+        .input
+        .as_deref()
+        .ok_or_else(|| EvidenceError::Unsupported("no input path provided".to_string()))?;
+    if input == std::path::Path::new("-") {
+        let src = StreamSource::stdin(opts.stream_window_mib.saturating_mul(MIB));
+        tracing::info!(
+            "reading evidence from standard input through a {} MiB window",
+            opts.stream_window_mib
+        );
+        return Ok((Box::new(src), None));
+    }
+    if is_ewf_path(input) {
         #[cfg(feature = "ewf")]
         {
-            let src = ewf::EwfSource::open(&opts.input)?;
-            return Ok(Box::new(src));
+            use crate::parsers::ewf::{acquisition_info, segment_paths};
+
+            let (format, segments) =
+                segment_paths(input).map_err(|err| EvidenceError::Unsupported(err.to_string()))?;
+            let src = ewf::EwfSource::open(&segments)?;
+            let info = acquisition_info(format, &segments);
+            return Ok((Box::new(src), Some(info)));
         }
         #[cfg(not(feature = "ewf"))]
         {
             return Err(EvidenceError::Unsupported(
-                "E01 support requires the `ewf` feature and libewf".to_string(),
+                "EWF support requires the `ewf` feature and libewf".to_string(),
             ));
         }
     }
 
-    if is_block_device(&opts.input)? {
-        let src = DeviceSource::open(&opts.input)?;
-        return Ok(Box::new(src));
+    if is_block_device(input)? {
+        let src = DeviceSource::open(input)?;
+        return Ok((Box::new(src), None));
+    }
+
+    if let Some(segments) = raw_segment_paths(input)? {
+        let src = SegmentedRawSource::open(&segments)?;
+        tracing::info!(
+            "split raw image: {} segments, {} bytes",
+            src.segment_count(),
+            src.len()
+        );
+        return Ok((Box::new(src), None));
+    }
+
+    if is_gzip_path(input) {
+        let src = GzipSource::open(input)?;
+        tracing::info!("gzip-compressed image: {} bytes decompressed", src.len());
+        return Ok((Box::new(src), None));
+    }
+
+    if opts.mmap {
+        #[cfg(unix)]
+        match MappedFileSource::open(input) {
+            Ok(src) => return Ok((Box::new(src), None)),
+            Err(err) => {
+                tracing::warn!("cannot map {} ({err}); reading with pread", input.display())
+            }
+        }
+        #[cfg(not(unix))]
+        tracing::warn!("--mmap is only supported on unix; reading with pread");
     }
 
-    let src = RawFileSource::open(&opts.input)?;
-    Ok(Box::new(src))
+    let src = RawFileSource::open(input)?;
+    Ok((Box::new(src), None))
 }
 
+/// First segment of an EWF set: `.E01`, `.Ex01`, `.L01`, or `.Lx01`.
 fn is_ewf_path(path: &std::path::Path) -> bool {
     path.extension()
         .and_then(|ext| ext.to_str())
-        .map(|ext| ext.eq_ignore_ascii_case("e01"))
-        .unwrap_or(false)
+        .and_then(EwfFormat::from_extension)
+        .is_some()
+}
+
+/// A `.gz` file that starts with the gzip magic.
+fn is_gzip_path(path: &std::path::Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("gz"))
+        && gzip::is_gzip_file(path)
 }
 
 fn is_block_device(path: &std::path::Path) -> Result<bool, EvidenceError> {
@@ -447,6 +1156,53 @@ fn is_block_device(path: &std::path::Path) -> Result<bool, EvidenceError> {
     }
 }
 
+/// File name of the EWF acquisition metadata in `summaries/`.
+pub const ACQUISITION_SUMMARY_FILE: &str = "evidence.json";
+
+/// Write `summaries/evidence.json` with the acquisition metadata of an EWF
+/// input, as `evidence.json.age` with a cipher.
+pub fn write_acquisition_info(
+    run_output_dir: &std::path::Path,
+    info: &AcquisitionInfo,
+    cipher: Option<&crate::encryption::OutputCipher>,
+) -> std::io::Result<std::path::PathBuf> {
+    let dir = run_output_dir.join(crate::report::SUMMARIES_DIR);
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(ACQUISITION_SUMMARY_FILE);
+    let mut output = crate::encryption::OutputFile::create(&path, cipher)?;
+    serde_json::to_writer_pretty(std::io::BufWriter::new(&mut output), info)?;
+    output.finish()?;
+    Ok(match cipher {
+        Some(_) => crate::encryption::sealed_path(&path),
+        None => path,
+    })
+}
+
+/// MD5 of the whole evidence, for comparison with the hash stored in an
+/// EWF set.
+pub fn compute_md5(
+    evidence: &dyn EvidenceSource,
+    chunk_size: usize,
+) -> Result<String, EvidenceError> {
+    let mut context = md5::Context::new();
+    let total_len = evidence.len();
+    let mut offset = 0u64;
+    let mut buf = vec![0u8; chunk_size.max(1)];
+
+    while offset < total_len {
+        let remaining = total_len - offset;
+        let read_len = remaining.min(buf.len() as u64) as usize;
+        let n = evidence.read_at(offset, &mut buf[..read_len])?;
+        if n == 0 {
+            break;
+        }
+        context.consume(&buf[..n]);
+        offset = offset.saturating_add(n as u64);
+    }
+
+    Ok(format!("{:x}", context.compute()))
+}
+
 pub fn compute_sha256(
     evidence: &dyn EvidenceSource,
     chunk_size: usize,
@@ -474,15 +1230,176 @@ pub fn compute_sha256(
 
 #[cfg(test)]
 mod tests {
-    use super::{RawFileSource, compute_sha256, is_ewf_path};
+    #[cfg(unix)]
+    use super::MappedFileSource;
+    use super::{
+        BlockCacheSource, EvidenceError, EvidenceSource, RawFileSource, SegmentedRawSource,
+        compute_md5, compute_sha256, is_ewf_path, raw_segment_paths,
+    };
+
+    /// Counts reads; decodes in 4-byte blocks.
+    struct CountingSource {
+        data: Vec<u8>,
+        reads: std::sync::atomic::AtomicU64,
+    }
+
+    impl EvidenceSource for CountingSource {
+        fn len(&self) -> u64 {
+            self.data.len() as u64
+        }
+
+        fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize, EvidenceError> {
+            self.reads
+                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            let data = self.data.get(offset as usize..).unwrap_or_default();
+            let n = data.len().min(buf.len());
+            buf[..n].copy_from_slice(&data[..n]);
+            Ok(n)
+        }
+
+        fn block_size(&self) -> Option<u64> {
+            Some(4)
+        }
+    }
+
+    #[test]
+    fn block_cache_decodes_each_block_once() {
+        let inner = std::sync::Arc::new(CountingSource {
+            data: (0u8..10).collect(),
+            reads: Default::default(),
+        });
+        let cache = BlockCacheSource::new(inner.clone(), 4, 64);
+        let mut buf = [0u8; 6];
+        assert_eq!(cache.read_at(3, &mut buf).unwrap(), 6);
+        assert_eq!(buf, [3, 4, 5, 6, 7, 8]);
+        // Overlapping and tail reads reuse the decoded blocks.
+        let mut tail = [0u8; 4];
+        assert_eq!(cache.read_at(7, &mut tail).unwrap(), 3);
+        assert_eq!(&tail[..3], &[7, 8, 9]);
+        assert_eq!(cache.read_at(10, &mut tail).unwrap(), 0);
+        assert_eq!(inner.reads.load(std::sync::atomic::Ordering::Relaxed), 3);
+        assert_eq!(cache.stats(), (2, 3));
+
+        // A one-block cache evicts the oldest block.
+        let small = BlockCacheSource::new(inner.clone(), 4, 4);
+        small.read_at(0, &mut tail).unwrap();
+        small.read_at(4, &mut tail).unwrap();
+        small.read_at(0, &mut tail).unwrap();
+        assert_eq!(small.stats(), (0, 3));
+    }
+
+    /// Records prefetch hints.
+    struct HintSource {
+        hints: std::sync::Mutex<Vec<(u64, u64)>>,
+    }
+
+    impl EvidenceSource for HintSource {
+        fn len(&self) -> u64 {
+            16
+        }
+
+        fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize, EvidenceError> {
+            let n = buf.len().min(16usize.saturating_sub(offset as usize));
+            buf[..n].fill(offset as u8);
+            Ok(n)
+        }
+
+        fn prefetch(&self, offset: u64, len: u64) {
+            self.hints.lock().unwrap().push((offset, len));
+        }
+    }
+
+    #[test]
+    fn reads_vectored_ranges_and_forwards_prefetch_hints() {
+        let source = CountingSource {
+            data: (0u8..10).collect(),
+            reads: Default::default(),
+        };
+        let (mut head, mut tail) = ([0u8; 3], [0u8; 4]);
+        let lens = source
+            .read_vectored_at(&mut [(1, &mut head[..]), (8, &mut tail[..])])
+            .unwrap();
+        assert_eq!(lens, vec![3, 2]);
+        assert_eq!(head, [1, 2, 3]);
+        assert_eq!(&tail[..2], &[8, 9]);
+
+        let inner = std::sync::Arc::new(HintSource {
+            hints: Default::default(),
+        });
+        let cache = BlockCacheSource::new(inner.clone(), 4, 64);
+        cache.read_at(0, &mut [0u8; 4]).unwrap();
+        // Block 0 is cached; the hint starts at block 1 and covers block 2.
+        cache.prefetch(2, 8);
+        cache.prefetch(0, 4);
+        cache.prefetch(16, 4);
+        assert_eq!(*inner.hints.lock().unwrap(), vec![(4, 8)]);
+    }
 
     #[test]
     fn ewf_extension_detection() {
         assert!(is_ewf_path(std::path::Path::new("case.E01")));
         assert!(is_ewf_path(std::path::Path::new("case.e01")));
+        assert!(is_ewf_path(std::path::Path::new("case.Ex01")));
+        assert!(is_ewf_path(std::path::Path::new("case.L01")));
+        assert!(is_ewf_path(std::path::Path::new("case.lx01")));
+        assert!(!is_ewf_path(std::path::Path::new("case.E02")));
         assert!(!is_ewf_path(std::path::Path::new("case.dd")));
     }
 
+    #[test]
+    fn stitches_split_raw_segments() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let data: Vec<u8> = (0..250u32).map(|i| i as u8).collect();
+        for (index, part) in [&data[..100], &data[100..100], &data[100..]]
+            .iter()
+            .enumerate()
+        {
+            std::fs::write(tmp.path().join(format!("image.{index:03}")), part).expect("segment");
+        }
+        let first = tmp.path().join("image.000");
+        let segments = raw_segment_paths(&first).expect("paths").expect("split");
+        assert_eq!(segments.len(), 3);
+        let source = SegmentedRawSource::open(&segments).expect("open");
+        assert_eq!(source.len(), 250);
+        let mut buf = [0u8; 20];
+        assert_eq!(source.read_at(90, &mut buf).expect("read"), 20);
+        assert_eq!(&buf[..], &data[90..110]);
+        assert_eq!(source.read_at(240, &mut buf).expect("read"), 10);
+        assert_eq!(source.read_at(250, &mut buf).expect("read"), 0);
+
+        assert!(raw_segment_paths(&tmp.path().join("image.001")).is_err());
+        std::fs::write(tmp.path().join("image.004"), b"x").expect("segment");
+        assert!(raw_segment_paths(&first).is_err());
+        assert!(
+            raw_segment_paths(&tmp.path().join("image.dd"))
+                .expect("dd")
+                .is_none()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn mapped_source_reads_like_pread() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let path = tmp.path().join("image.dd");
+        let data: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&path, &data).expect("write");
+        let mapped = MappedFileSource::open(&path).expect("map");
+        let raw = RawFileSource::open(&path).expect("open");
+        assert_eq!(mapped.len(), raw.len());
+        for offset in [0u64, 4095, 9990, 10_000, 20_000] {
+            let (mut a, mut b) = ([0u8; 64], [0u8; 64]);
+            let n = mapped.read_at(offset, &mut a).expect("mapped read");
+            assert_eq!(n, raw.read_at(offset, &mut b).expect("raw read"));
+            assert_eq!(a[..n], b[..n]);
+        }
+        mapped.prefetch(9000, 5000);
+
+        let empty = tmp.path().join("empty.dd");
+        std::fs::write(&empty, b"").expect("write");
+        assert!(MappedFileSource::open(&empty).is_err());
+    }
+
     #[test]
     fn computes_sha256_for_raw_file() {
         use std::fs;
@@ -497,6 +1414,8 @@ mod tests {
             hash,
             "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
         );
+        let md5 = compute_md5(&src, 2).expect("md5");
+        assert_eq!(md5, "900150983cd24fb0d6963f7d28e17f72");
     }
 
     #[cfg(not(feature = "ewf"))]
@@ -510,16 +1429,19 @@ mod tests {
         fs::write(&path, b"not ewf").expect("write");
 
         let opts = CliOptions {
-            input: path,
+            command: None,
+            input: Some(path),
             output: tmp.path().to_path_buf(),
             config_path: None,
             gpu: false,
             workers: 1,
             chunk_size_mib: 1,
+            stream_window_mib: 2048,
             overlap_kib: None,
             metadata_backend: MetadataBackend::Jsonl,
             log_format: crate::cli::LogFormat::Text,
             progress_interval_secs: 0,
+            profile: None,
             scan_strings: false,
             scan_utf16: false,
             scan_urls: false,
@@ -528,11 +1450,32 @@ mod tests {
             no_scan_emails: false,
             scan_phones: false,
             no_scan_phones: false,
+            scan_logs: false,
+            no_scan_logs: false,
+            scan_system: false,
+            no_scan_system: false,
+            scan_credentials: false,
+            no_scan_credentials: false,
+            scan_p2p: false,
+            no_scan_p2p: false,
             string_min_len: None,
+            string_min_len_utf8: None,
+            string_min_len_utf16: None,
+            strings_gaps_only: false,
             scan_entropy: false,
             entropy_window_bytes: None,
             entropy_threshold: None,
+            hit_prefilter: false,
+            gzip_decompress: false,
             scan_sqlite_pages: false,
+            sqlite_app_catalog: None,
+            scan_shadow_copies: false,
+            ntfs: false,
+            unallocated_only: false,
+            volume_locations: false,
+            carve_fsync: None,
+            pipeline_mode: None,
+            evidence_transform: None,
             max_bytes: None,
             max_chunks: None,
             max_files: None,
@@ -540,14 +1483,33 @@ mod tests {
             max_open_files: None,
             checkpoint_path: None,
             resume_from: None,
+            allow_evidence_change: false,
             evidence_sha256: None,
             compute_evidence_sha256: false,
+            verify_evidence_hash: false,
+            mmap: false,
             disable_zip: false,
             types: None,
             enable_types: None,
+            scalpel_conf: None,
             dry_run: false,
+            force_unlock: false,
+            append_to: None,
+            result_path: None,
+            carve_timeout_secs: None,
+            qa_sample: None,
+            qa_sample_seed: None,
+            export_slack: None,
+            temp_dir: None,
+            temp_space_limit: None,
+            hit_context_window: None,
+            encrypt_to: None,
             validate_carved: false,
             remove_invalid: false,
+            trace_offset: None,
+            trace_window: None,
+            profile_throughput: false,
+            metadata_snapshot_interval: None,
         };
 
         let result = super::open_source(&opts);
@@ -0,0 +1,352 @@
+//! Gzip-compressed raw images (`image.dd.gz`) read in place.
+//!
+//! Opening the image decompresses it once to learn its size and to snapshot
+//! the inflater every [`INDEX_SPACING`] bytes of output. A read restarts from
+//! the snapshot before its offset, so carve workers seek without decoding
+//! from the start. Concatenated members (`cat a.gz b.gz`, `pigz`) are read
+//! as one stream; a truncated or corrupt tail ends the image where the last
+//! good byte was decoded.
+
+use std::fs::File;
+use std::path::Path;
+
+use miniz_oxide::inflate::stream::{InflateState, inflate};
+use miniz_oxide::{DataFormat, MZError, MZFlush, MZStatus};
+
+use super::{EvidenceError, EvidenceSource};
+
+/// Decompressed bytes between inflater snapshots; also the block size the
+/// pipeline caches. Each snapshot holds about 45 KiB.
+pub const INDEX_SPACING: u64 = 32 * 1024 * 1024;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+/// Compressed bytes fed to the inflater per call.
+const INPUT_BLOCK: usize = 64 * 1024;
+/// Longest member header read (file name and comment included).
+const MAX_HEADER: usize = 64 * 1024;
+
+/// Where a decoder stands: the next compressed byte, the decompressed bytes
+/// before it, and the inflater (`None` between members).
+#[derive(Clone)]
+struct Position {
+    input: u64,
+    output: u64,
+    state: Option<Box<InflateState>>,
+}
+
+pub struct GzipSource {
+    file: File,
+    file_len: u64,
+    len: u64,
+    spacing: u64,
+    /// Positions at multiples of `spacing` bytes of output.
+    index: Vec<Position>,
+}
+
+impl GzipSource {
+    pub fn open(path: &Path) -> Result<Self, EvidenceError> {
+        Self::open_with_spacing(path, INDEX_SPACING)
+    }
+
+    fn open_with_spacing(path: &Path, spacing: u64) -> Result<Self, EvidenceError> {
+        let file = File::open(path)?;
+        let file_len = file.metadata()?.len();
+        let mut source = Self {
+            file,
+            file_len,
+            len: 0,
+            spacing,
+            index: Vec::new(),
+        };
+        source.build_index()?;
+        Ok(source)
+    }
+
+    /// Decompress the whole image once, keeping a position every `spacing`
+    /// bytes of output.
+    fn build_index(&mut self) -> Result<(), EvidenceError> {
+        let mut pos = Position {
+            input: 0,
+            output: 0,
+            state: None,
+        };
+        if member_header_len(&self.read_input(0, MAX_HEADER)?).is_none() {
+            return Err(EvidenceError::Unsupported("not a gzip file".to_string()));
+        }
+        self.index.push(pos.clone());
+        let mut scratch = vec![0u8; 1024 * 1024];
+        let mut next = self.spacing;
+        loop {
+            let room = (next - pos.output).min(scratch.len() as u64) as usize;
+            match self.step(&mut pos, &mut scratch[..room]) {
+                Ok(0) => break,
+                Ok(_) => {}
+                Err(err) => {
+                    tracing::warn!(
+                        "gzip image ends early at {} decompressed bytes: {err}",
+                        pos.output
+                    );
+                    break;
+                }
+            }
+            if pos.output == next {
+                self.index.push(pos.clone());
+                next += self.spacing;
+            }
+        }
+        self.len = pos.output;
+        Ok(())
+    }
+
+    fn read_input(&self, offset: u64, len: usize) -> Result<Vec<u8>, EvidenceError> {
+        let len = len.min(self.file_len.saturating_sub(offset) as usize);
+        let mut buf = vec![0u8; len];
+        let mut read = 0;
+        while read < len {
+            let n = pread(&self.file, offset + read as u64, &mut buf[read..])?;
+            if n == 0 {
+                break;
+            }
+            read += n;
+        }
+        buf.truncate(read);
+        Ok(buf)
+    }
+
+    /// Decompress into `out` from `pos`; `Ok(0)` at the end of the image.
+    fn step(&self, pos: &mut Position, out: &mut [u8]) -> Result<usize, EvidenceError> {
+        if out.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let Some(state) = pos.state.as_mut() else {
+                if pos.input >= self.file_len {
+                    return Ok(0);
+                }
+                // Between members: anything but another header is trailing data.
+                match member_header_len(&self.read_input(pos.input, MAX_HEADER)?) {
+                    Some(len) => {
+                        pos.input += len as u64;
+                        pos.state = Some(InflateState::new_boxed(DataFormat::Raw));
+                        continue;
+                    }
+                    None => {
+                        pos.input = self.file_len;
+                        return Ok(0);
+                    }
+                }
+            };
+            // With the input used up the inflater may still hold output.
+            let input = self.read_input(pos.input, INPUT_BLOCK)?;
+            let result = inflate(state, &input, out, MZFlush::None);
+            pos.input += result.bytes_consumed as u64;
+            pos.output += result.bytes_written as u64;
+            match result.status {
+                Ok(MZStatus::StreamEnd) => {
+                    // CRC-32 and size trailer.
+                    pos.input += 8;
+                    pos.state = None;
+                }
+                Ok(_) => {
+                    if result.bytes_written == 0 && result.bytes_consumed == 0 {
+                        return Err(truncated(pos));
+                    }
+                }
+                Err(MZError::Buf) if result.bytes_written == 0 => return Err(truncated(pos)),
+                Err(MZError::Buf) => {}
+                Err(err) => {
+                    return Err(EvidenceError::Unsupported(format!(
+                        "corrupt gzip data near compressed offset {}: {err:?}",
+                        pos.input
+                    )));
+                }
+            }
+            if result.bytes_written > 0 {
+                return Ok(result.bytes_written);
+            }
+        }
+    }
+}
+
+fn truncated(pos: &Position) -> EvidenceError {
+    EvidenceError::Unsupported(format!(
+        "gzip stream truncated at compressed offset {}",
+        pos.input
+    ))
+}
+
+impl EvidenceSource for GzipSource {
+    fn len(&self) -> u64 {
+        self.len
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize, EvidenceError> {
+        if offset >= self.len {
+            return Ok(0);
+        }
+        let want = buf.len().min((self.len - offset) as usize);
+        let slot = self.index.partition_point(|p| p.output <= offset) - 1;
+        let mut pos = self.index[slot].clone();
+        let mut scratch = vec![0u8; (offset - pos.output).min(1024 * 1024) as usize];
+        while pos.output < offset {
+            let room = (offset - pos.output).min(scratch.len() as u64) as usize;
+            if self.step(&mut pos, &mut scratch[..room])? == 0 {
+                return Ok(0);
+            }
+        }
+        let mut filled = 0;
+        while filled < want {
+            let n = self.step(&mut pos, &mut buf[filled..want])?;
+            if n == 0 {
+                break;
+            }
+            filled += n;
+        }
+        Ok(filled)
+    }
+
+    fn block_size(&self) -> Option<u64> {
+        Some(self.spacing)
+    }
+}
+
+/// Length of the gzip member header at the start of `bytes`, or `None` when
+/// `bytes` does not start with a complete deflate member header.
+fn member_header_len(bytes: &[u8]) -> Option<usize> {
+    const FHCRC: u8 = 0x02;
+    const FEXTRA: u8 = 0x04;
+    const FNAME: u8 = 0x08;
+    const FCOMMENT: u8 = 0x10;
+
+    if bytes.len() < 10 || bytes[..2] != GZIP_MAGIC || bytes[2] != 8 {
+        return None;
+    }
+    let flags = bytes[3];
+    let mut len = 10usize;
+    if flags & FEXTRA != 0 {
+        let extra = u16::from_le_bytes([*bytes.get(len)?, *bytes.get(len + 1)?]);
+        len += 2 + extra as usize;
+    }
+    for flag in [FNAME, FCOMMENT] {
+        if flags & flag != 0 {
+            len += bytes.get(len..)?.iter().position(|b| *b == 0)? + 1;
+        }
+    }
+    if flags & FHCRC != 0 {
+        len += 2;
+    }
+    (len <= bytes.len()).then_some(len)
+}
+
+/// Whether `path` holds gzip data (checked by magic, not by extension).
+pub fn is_gzip_file(path: &Path) -> bool {
+    let mut magic = [0u8; 2];
+    File::open(path)
+        .and_then(|file| pread(&file, 0, &mut magic))
+        .is_ok_and(|n| n == 2 && magic == GZIP_MAGIC)
+}
+
+fn pread(file: &File, offset: u64, buf: &mut [u8]) -> std::io::Result<usize> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileExt;
+        file.read_at(buf, offset)
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::FileExt;
+        file.seek_read(buf, offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use miniz_oxide::deflate::compress_to_vec;
+
+    fn member(data: &[u8], name: Option<&str>) -> Vec<u8> {
+        let mut out = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 3];
+        if let Some(name) = name {
+            out[3] = 0x08;
+            out.extend_from_slice(name.as_bytes());
+            out.push(0);
+        }
+        out.extend_from_slice(&compress_to_vec(data, 6));
+        // The CRC is not checked; the size is the low 32 bits.
+        out.extend_from_slice(&0u32.to_le_bytes());
+        out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        out
+    }
+
+    fn pattern(len: usize, seed: u32) -> Vec<u8> {
+        let mut x = seed;
+        (0..len)
+            .map(|i| {
+                x = x.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                if i % 7 == 0 {
+                    (x >> 16) as u8
+                } else {
+                    b'a' + (i % 13) as u8
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reads_across_members_and_index_points() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let (first, second) = (pattern(300_000, 1), pattern(123_457, 2));
+        let mut gz = member(&first, Some("image.dd"));
+        gz.extend_from_slice(&member(&second, None));
+        let path = tmp.path().join("image.dd.gz");
+        std::fs::write(&path, &gz).expect("write");
+        assert!(is_gzip_file(&path));
+
+        let source = GzipSource::open_with_spacing(&path, 64 * 1024).expect("open");
+        let expected = [first, second].concat();
+        assert_eq!(source.len(), expected.len() as u64);
+        assert!(source.index.len() > 5);
+        for (offset, len) in [
+            (0u64, 100usize),
+            (65_530, 20),
+            (299_990, 40),
+            (423_400, 100),
+        ] {
+            let mut buf = vec![0u8; len];
+            let n = source.read_at(offset, &mut buf).expect("read");
+            let end = (offset as usize + len).min(expected.len());
+            assert_eq!(
+                &buf[..n],
+                &expected[offset as usize..end],
+                "offset {offset}"
+            );
+        }
+        assert_eq!(
+            source
+                .read_at(expected.len() as u64, &mut [0u8; 4])
+                .expect("end"),
+            0
+        );
+    }
+
+    #[test]
+    fn truncated_image_ends_at_the_last_decoded_byte() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let data = pattern(200_000, 3);
+        let gz = member(&data, None);
+        let path = tmp.path().join("cut.raw.gz");
+        std::fs::write(&path, &gz[..gz.len() / 2]).expect("write");
+
+        let source = GzipSource::open_with_spacing(&path, 64 * 1024).expect("open");
+        assert!(source.len() > 0 && source.len() < data.len() as u64);
+        let mut buf = vec![0u8; 1000];
+        let n = source.read_at(source.len() - 500, &mut buf).expect("read");
+        assert_eq!(n, 500);
+        let start = source.len() as usize - 500;
+        assert_eq!(&buf[..n], &data[start..start + 500]);
+
+        std::fs::write(&path, b"plain data").expect("write");
+        assert!(!is_gzip_file(&path));
+        assert!(GzipSource::open(&path).is_err());
+    }
+}
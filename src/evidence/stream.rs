@@ -0,0 +1,233 @@
+//! Evidence read once from a byte stream (`--input -`).
+//!
+//! A thread reads the stream into a window of at most `window` bytes while
+//! the scan runs, so carving starts before an acquisition pipeline
+//! (`dcfldd ... | swiftbeaver --input -`) has finished imaging. The pipeline
+//! moves the window on with [`EvidenceSource::release_before`] once the
+//! chunks before that offset are scanned and their hits carved. Reads before
+//! the window fail, and reads past the data a full window holds come back
+//! short, so files running beyond it are carved truncated. The length is
+//! [`UNKNOWN_LEN`] until the stream ends.
+
+use std::collections::VecDeque;
+use std::io::{ErrorKind, Read};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+
+use super::{EvidenceError, EvidenceSource, UNKNOWN_LEN};
+
+/// Bytes read from the stream per call.
+const READ_BLOCK: usize = 1024 * 1024;
+
+#[derive(Default)]
+struct Window {
+    /// Stream offset of `data[0]`.
+    base: u64,
+    data: VecDeque<u8>,
+    /// The stream ended, failed, or the source was dropped.
+    done: bool,
+}
+
+impl Window {
+    fn end(&self) -> u64 {
+        self.base + self.data.len() as u64
+    }
+}
+
+struct Shared {
+    window: Mutex<Window>,
+    /// Signalled when bytes arrive, are dropped, or the stream ends.
+    changed: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> Result<MutexGuard<'_, Window>, EvidenceError> {
+        self.window
+            .lock()
+            .map_err(|_| EvidenceError::Unsupported("stream window lock poisoned".to_string()))
+    }
+}
+
+pub struct StreamSource {
+    shared: Arc<Shared>,
+    capacity: u64,
+}
+
+impl StreamSource {
+    /// Read standard input through a window of `window` bytes.
+    pub fn stdin(window: u64) -> Self {
+        Self::new(Box::new(std::io::stdin()), window)
+    }
+
+    /// Start reading `reader` on its own thread into a window of `window`
+    /// bytes.
+    pub fn new(reader: Box<dyn Read + Send>, window: u64) -> Self {
+        let shared = Arc::new(Shared {
+            window: Mutex::new(Window::default()),
+            changed: Condvar::new(),
+        });
+        let capacity = window.max(1);
+        let filler = shared.clone();
+        std::thread::Builder::new()
+            .name("evidence-stream".to_string())
+            .spawn(move || fill(&filler, reader, capacity))
+            .expect("spawn evidence stream reader");
+        Self { shared, capacity }
+    }
+}
+
+/// Append `reader` to the window whenever it has room, until the stream ends.
+fn fill(shared: &Shared, mut reader: Box<dyn Read + Send>, capacity: u64) {
+    let mut block = vec![0u8; READ_BLOCK];
+    loop {
+        let room = {
+            let Ok(mut window) = shared.window.lock() else {
+                return;
+            };
+            while !window.done && window.data.len() as u64 >= capacity {
+                window = match shared.changed.wait(window) {
+                    Ok(window) => window,
+                    Err(_) => return,
+                };
+            }
+            if window.done {
+                return;
+            }
+            (capacity - window.data.len() as u64).min(READ_BLOCK as u64) as usize
+        };
+        let read = reader.read(&mut block[..room]);
+        let Ok(mut window) = shared.window.lock() else {
+            return;
+        };
+        match read {
+            Ok(0) => window.done = true,
+            Ok(n) => window.data.extend(&block[..n]),
+            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(err) => {
+                tracing::warn!(
+                    "input stream failed after {} bytes: {err}; treating it as ended",
+                    window.end()
+                );
+                window.done = true;
+            }
+        }
+        shared.changed.notify_all();
+    }
+}
+
+impl EvidenceSource for StreamSource {
+    fn len(&self) -> u64 {
+        match self.shared.lock() {
+            Ok(window) if window.done => window.end(),
+            _ => UNKNOWN_LEN,
+        }
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize, EvidenceError> {
+        let mut window = self.shared.lock()?;
+        if offset < window.base {
+            return Err(EvidenceError::InvalidOffset(format!(
+                "offset {offset} has left the stream window, which starts at {}",
+                window.base
+            )));
+        }
+        let want = offset.saturating_add(buf.len() as u64);
+        // Wait for the stream unless the window is full: only the scan
+        // moving on makes room for more.
+        while window.end() < want && !window.done && (window.data.len() as u64) < self.capacity {
+            window = self.shared.changed.wait(window).map_err(|_| {
+                EvidenceError::Unsupported("stream window lock poisoned".to_string())
+            })?;
+        }
+        if offset >= window.end() {
+            return Ok(0);
+        }
+        let start = (offset - window.base) as usize;
+        let n = buf.len().min(window.data.len() - start);
+        let (front, back) = window.data.as_slices();
+        let mut copied = 0;
+        if start < front.len() {
+            copied = n.min(front.len() - start);
+            buf[..copied].copy_from_slice(&front[start..start + copied]);
+        }
+        if copied < n {
+            let from = start + copied - front.len();
+            buf[copied..n].copy_from_slice(&back[from..from + n - copied]);
+        }
+        Ok(n)
+    }
+
+    fn stream_window(&self) -> Option<u64> {
+        Some(self.capacity)
+    }
+
+    fn release_before(&self, offset: u64) {
+        let Ok(mut window) = self.shared.lock() else {
+            return;
+        };
+        // Bytes not read from the stream yet cannot be skipped.
+        if offset > window.base {
+            let drop = ((offset - window.base) as usize).min(window.data.len());
+            window.data.drain(..drop);
+            window.base += drop as u64;
+            self.shared.changed.notify_all();
+        }
+    }
+}
+
+impl Drop for StreamSource {
+    fn drop(&mut self) {
+        // Stops the reader thread once its current read returns.
+        if let Ok(mut window) = self.shared.window.lock() {
+            window.done = true;
+        }
+        self.shared.changed.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[test]
+    fn reads_a_stream_shorter_than_the_window() {
+        let data = pattern(10_000);
+        let source = StreamSource::new(Box::new(std::io::Cursor::new(data.clone())), 1 << 20);
+        let mut buf = vec![0u8; 20_000];
+        let n = source.read_at(100, &mut buf).expect("read");
+        assert_eq!(&buf[..n], &data[100..]);
+        assert_eq!(source.len(), 10_000);
+        assert_eq!(source.read_at(10_000, &mut buf).expect("read at end"), 0);
+    }
+
+    #[test]
+    fn window_follows_the_scan() {
+        let data = pattern(100_000);
+        let source = StreamSource::new(Box::new(std::io::Cursor::new(data.clone())), 10_000);
+        let mut buf = vec![0u8; 4_000];
+        assert_eq!(source.read_at(0, &mut buf).expect("read"), 4_000);
+        assert_eq!(source.len(), UNKNOWN_LEN);
+        // A full window answers reads beyond it short.
+        assert_eq!(source.read_at(8_000, &mut buf).expect("read"), 2_000);
+        assert_eq!(source.read_at(12_000, &mut buf).expect("read"), 0);
+
+        source.release_before(4_000);
+        assert!(source.read_at(3_999, &mut buf[..1]).is_err());
+        let n = source.read_at(10_000, &mut buf).expect("read");
+        assert_eq!(n, 4_000);
+        assert_eq!(&buf[..n], &data[10_000..14_000]);
+
+        let mut offset = 14_000;
+        while offset < 100_000 {
+            let n = source.read_at(offset, &mut buf).expect("sequential read");
+            assert_eq!(&buf[..n], &data[offset as usize..offset as usize + n]);
+            offset += n as u64;
+            source.release_before(offset);
+        }
+        assert_eq!(source.read_at(offset, &mut buf).expect("read at end"), 0);
+        assert_eq!(source.len(), 100_000);
+    }
+}
@@ -0,0 +1,159 @@
+//! Evidence read through a byte-level fix-up.
+//!
+//! Some damaged or misconfigured acquisitions store every 16-bit word with
+//! its bytes swapped (imagers reading an ATA device in the wrong byte order)
+//! or every bit inverted. Nothing in such an image matches a signature.
+//! [`TransformedSource`] undoes the fault on each read, so scanning and
+//! carving see the bytes as they were on the device. Both transforms are
+//! their own inverse.
+
+use std::sync::Arc;
+
+use super::{EvidenceError, EvidenceSource, read_full};
+
+/// A fault undone by [`TransformedSource`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteTransform {
+    /// The two bytes of every 16-bit word are swapped.
+    ByteSwap16,
+    /// Every bit is inverted.
+    InvertBits,
+}
+
+impl ByteTransform {
+    pub const ALL: [ByteTransform; 2] = [ByteTransform::ByteSwap16, ByteTransform::InvertBits];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ByteTransform::ByteSwap16 => "byte_swap16",
+            ByteTransform::InvertBits => "invert_bits",
+        }
+    }
+
+    /// Apply the transform to `buf`, which must start at an even evidence
+    /// offset. A trailing odd byte has no partner to swap with and is kept.
+    pub fn apply(self, buf: &mut [u8]) {
+        match self {
+            ByteTransform::ByteSwap16 => {
+                for word in buf.chunks_exact_mut(2) {
+                    word.swap(0, 1);
+                }
+            }
+            ByteTransform::InvertBits => {
+                for byte in buf {
+                    *byte = !*byte;
+                }
+            }
+        }
+    }
+}
+
+/// `inner` with `transform` applied to every byte read.
+pub struct TransformedSource {
+    inner: Arc<dyn EvidenceSource>,
+    transform: ByteTransform,
+}
+
+impl TransformedSource {
+    pub fn new(inner: Arc<dyn EvidenceSource>, transform: ByteTransform) -> Self {
+        Self { inner, transform }
+    }
+}
+
+impl EvidenceSource for TransformedSource {
+    fn len(&self) -> u64 {
+        self.inner.len()
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize, EvidenceError> {
+        if self.transform == ByteTransform::InvertBits
+            || (offset.is_multiple_of(2) && buf.len().is_multiple_of(2))
+        {
+            // Whole words: read in place. A short read ends the evidence,
+            // so an odd count only ever leaves its last byte unswapped.
+            let n = read_full(self.inner.as_ref(), offset, buf)?;
+            self.transform.apply(&mut buf[..n]);
+            return Ok(n);
+        }
+        // Widen the read to word boundaries so every byte has its partner.
+        let start = offset & !1;
+        let end = (offset + buf.len() as u64).next_multiple_of(2);
+        let mut words = vec![0u8; (end - start) as usize];
+        let n = read_full(self.inner.as_ref(), start, &mut words)?;
+        self.transform.apply(&mut words[..n]);
+        let skip = (offset - start) as usize;
+        let Some(read) = words.get(skip..n) else {
+            return Ok(0);
+        };
+        let n = read.len().min(buf.len());
+        buf[..n].copy_from_slice(&read[..n]);
+        Ok(n)
+    }
+
+    fn block_size(&self) -> Option<u64> {
+        self.inner.block_size()
+    }
+
+    fn prefetch(&self, offset: u64, len: u64) {
+        self.inner.prefetch(offset, len);
+    }
+
+    fn stream_window(&self) -> Option<u64> {
+        self.inner.stream_window()
+    }
+
+    fn release_before(&self, offset: u64) {
+        self.inner.release_before(offset);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Image(Vec<u8>);
+
+    impl EvidenceSource for Image {
+        fn len(&self) -> u64 {
+            self.0.len() as u64
+        }
+
+        fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize, EvidenceError> {
+            let Some(rest) = self.0.get(offset as usize..) else {
+                return Ok(0);
+            };
+            let n = rest.len().min(buf.len());
+            buf[..n].copy_from_slice(&rest[..n]);
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn swaps_words_at_any_offset_and_length() {
+        let device: Vec<u8> = (0u8..=100).collect();
+        let mut stored = device.clone();
+        ByteTransform::ByteSwap16.apply(&mut stored);
+        let source = TransformedSource::new(Arc::new(Image(stored)), ByteTransform::ByteSwap16);
+        for offset in [0u64, 1, 2, 7, 99, 100] {
+            for len in [1usize, 2, 3, 10, 11] {
+                let mut buf = vec![0u8; len];
+                let n = source.read_at(offset, &mut buf).unwrap();
+                let want = &device[(offset as usize).min(101)..][..len.min(101 - offset as usize)];
+                assert_eq!(&buf[..n], want, "offset {offset} len {len}");
+            }
+        }
+        let mut buf = [0u8; 4];
+        assert_eq!(source.read_at(101, &mut buf).unwrap(), 0);
+    }
+
+    #[test]
+    fn inverts_bits() {
+        let source = TransformedSource::new(
+            Arc::new(Image(vec![0xff, 0x00, 0xa5])),
+            ByteTransform::InvertBits,
+        );
+        let mut buf = [0u8; 3];
+        assert_eq!(source.read_at(1, &mut buf).unwrap(), 2);
+        assert_eq!(&buf[..2], &[0xff, 0x5a]);
+    }
+}
@@ -0,0 +1,195 @@
+//! Photo GPS positions as KML placemarks or GeoJSON points.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use chrono::NaiveDateTime;
+use serde_json::{Value, json};
+
+use super::{ExportError, Record, iso_time, number, read_records, text, time};
+
+/// Metadata output the positions are read from.
+const SOURCE: &str = "photo_devices";
+
+/// A located record.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeoPoint {
+    pub latitude: f64,
+    pub longitude: f64,
+    /// Placemark name: the carved file name.
+    pub name: String,
+    /// Capture time by the camera clock.
+    pub time: Option<NaiveDateTime>,
+    /// The other fields of the record.
+    pub properties: Record,
+}
+
+/// Photos of the run with a GPS position, in evidence order.
+pub fn photo_points(run_dir: &Path) -> Result<Vec<GeoPoint>, ExportError> {
+    let mut points: Vec<GeoPoint> = read_records(run_dir, SOURCE)?
+        .into_iter()
+        .filter_map(|mut record| {
+            let latitude = number(&record, "latitude")?;
+            let longitude = number(&record, "longitude")?;
+            let name = text(&record, "source_file")
+                .map(|path| path.rsplit('/').next().unwrap_or(&path).to_string())
+                .unwrap_or_default();
+            let time = time(&record, "taken_at");
+            record.remove("latitude");
+            record.remove("longitude");
+            Some(GeoPoint {
+                latitude,
+                longitude,
+                name,
+                time,
+                properties: record,
+            })
+        })
+        .collect();
+    points.sort_by_key(|p| number(&p.properties, "global_start").map(|n| n as u64));
+    Ok(points)
+}
+
+pub fn write_kml(path: &Path, document: &str, points: &[GeoPoint]) -> Result<(), ExportError> {
+    let mut out = BufWriter::new(File::create(path)?);
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(out, r#"<kml xmlns="http://www.opengis.net/kml/2.2">"#)?;
+    writeln!(out, "<Document>")?;
+    writeln!(out, "<name>{}</name>", escape_xml(document))?;
+    for point in points {
+        writeln!(out, "<Placemark>")?;
+        writeln!(out, "<name>{}</name>", escape_xml(&point.name))?;
+        let camera = ["make", "model"]
+            .iter()
+            .filter_map(|key| text(&point.properties, key))
+            .collect::<Vec<_>>()
+            .join(" ");
+        if !camera.is_empty() {
+            writeln!(out, "<description>{}</description>", escape_xml(&camera))?;
+        }
+        if let Some(time) = point.time {
+            writeln!(
+                out,
+                "<TimeStamp><when>{}</when></TimeStamp>",
+                iso_time(time)
+            )?;
+        }
+        writeln!(
+            out,
+            "<Point><coordinates>{},{}</coordinates></Point>",
+            point.longitude, point.latitude
+        )?;
+        writeln!(out, "<ExtendedData>")?;
+        for (key, value) in &point.properties {
+            let value = match value {
+                Value::Null => continue,
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            writeln!(
+                out,
+                r#"<Data name="{}"><value>{}</value></Data>"#,
+                escape_xml(key),
+                escape_xml(&value)
+            )?;
+        }
+        writeln!(out, "</ExtendedData>")?;
+        writeln!(out, "</Placemark>")?;
+    }
+    writeln!(out, "</Document>")?;
+    writeln!(out, "</kml>")?;
+    out.flush()?;
+    Ok(())
+}
+
+pub fn write_geojson(path: &Path, points: &[GeoPoint]) -> Result<(), ExportError> {
+    let features: Vec<Value> = points
+        .iter()
+        .map(|point| {
+            let mut properties = point.properties.clone();
+            properties.insert("source".to_string(), Value::from(SOURCE));
+            json!({
+                "type": "Feature",
+                "geometry": {
+                    "type": "Point",
+                    "coordinates": [point.longitude, point.latitude],
+                },
+                "properties": properties,
+            })
+        })
+        .collect();
+    let collection = json!({ "type": "FeatureCollection", "features": features });
+    let mut out = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(&mut out, &collection)?;
+    out.flush()?;
+    Ok(())
+}
+
+fn escape_xml(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c if c.is_control() && !matches!(c, '\t' | '\n' | '\r') => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_with_photos() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().expect("tempdir");
+        std::fs::create_dir_all(dir.path().join("metadata")).expect("dir");
+        std::fs::write(
+            dir.path().join("metadata/photo_devices.jsonl"),
+            concat!(
+                r#"{"source_file":"jpeg/b.jpg","global_start":9000,"make":"Canon","model":"EOS <R5>","taken_at":"2024-05-01T10:15:30","latitude":-33.865,"longitude":151.21}"#,
+                "\n",
+                r#"{"source_file":"jpeg/c.jpg","global_start":100,"make":"Canon","latitude":null,"longitude":null}"#,
+                "\n",
+                r#"{"source_file":"jpeg/a.jpg","global_start":512,"latitude":48.5,"longitude":2.25}"#,
+                "\n",
+            ),
+        )
+        .expect("write");
+        dir
+    }
+
+    #[test]
+    fn exports_located_photos_as_kml_and_geojson() {
+        let run = run_with_photos();
+        let points = photo_points(run.path()).expect("points");
+        assert_eq!(
+            points.iter().map(|p| p.name.as_str()).collect::<Vec<_>>(),
+            ["a.jpg", "b.jpg"]
+        );
+
+        let kml_path = run.path().join("out.kml");
+        write_kml(&kml_path, "run", &points).expect("kml");
+        let kml = std::fs::read_to_string(&kml_path).expect("read");
+        assert!(kml.contains("<Point><coordinates>151.21,-33.865</coordinates></Point>"));
+        assert!(kml.contains("<description>Canon EOS &lt;R5&gt;</description>"));
+        assert!(kml.contains("<TimeStamp><when>2024-05-01T10:15:30</when></TimeStamp>"));
+        assert!(!kml.contains(r#"<Data name="latitude">"#));
+
+        let geojson_path = run.path().join("out.geojson");
+        write_geojson(&geojson_path, &points).expect("geojson");
+        let geojson: Value =
+            serde_json::from_str(&std::fs::read_to_string(&geojson_path).expect("read"))
+                .expect("json");
+        assert_eq!(geojson["type"], "FeatureCollection");
+        let feature = &geojson["features"][1];
+        assert_eq!(feature["geometry"]["coordinates"], json!([151.21, -33.865]));
+        assert_eq!(feature["properties"]["source_file"], "jpeg/b.jpg");
+        assert_eq!(feature["properties"]["source"], "photo_devices");
+    }
+}
@@ -0,0 +1,326 @@
+//! # Case Exports
+//!
+//! Converters from the metadata outputs of a finished run into formats that
+//! case visualization tools load directly: photo GPS positions as KML or
+//! GeoJSON for map viewers, and every timestamped record as a Timesketch
+//! JSONL timeline. Runs written with any metadata backend can be exported;
+//! encrypted runs must be decrypted first.
+
+pub mod geo;
+pub mod timeline;
+
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use arrow_array::Array;
+use arrow_array::cast::AsArray;
+use arrow_array::types::{Float64Type, Int32Type, Int64Type, TimestampMicrosecondType};
+use arrow_schema::{DataType, TimeUnit};
+use chrono::{DateTime, NaiveDateTime};
+use clap::ValueEnum;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use serde_json::{Map, Value};
+use thiserror::Error;
+use tracing::warn;
+
+use crate::encryption::sealed_path;
+
+/// Default output directory, inside the run directory.
+pub const EXPORT_DIR: &str = "exports";
+
+/// One metadata record, keyed by column name.
+pub type Record = Map<String, Value>;
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// KML placemarks of photo GPS positions (Google Earth, QGIS)
+    Kml,
+    /// GeoJSON points of photo GPS positions
+    Geojson,
+    /// Timesketch JSONL timeline of every timestamped record
+    Timesketch,
+}
+
+impl ExportFormat {
+    /// File name written under `exports/` when no output path is given.
+    pub fn default_file_name(self) -> &'static str {
+        match self {
+            Self::Kml => "locations.kml",
+            Self::Geojson => "locations.geojson",
+            Self::Timesketch => "timeline.jsonl",
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ExportError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("json error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("csv error: {0}")]
+    Csv(#[from] csv::Error),
+    #[error("parquet error: {0}")]
+    Parquet(#[from] parquet::errors::ParquetError),
+    #[error("arrow error: {0}")]
+    Arrow(#[from] arrow_schema::ArrowError),
+    #[error("{0} is encrypted; decrypt the run first")]
+    Encrypted(PathBuf),
+}
+
+/// What an export wrote.
+#[derive(Debug)]
+pub struct ExportReport {
+    pub path: PathBuf,
+    /// Placemarks, features, or timeline events written.
+    pub records: usize,
+}
+
+/// Export the run at `run_dir` as `format` to `output` (defaults to
+/// `<run_dir>/exports/<format file name>`).
+pub fn export_run(
+    run_dir: &Path,
+    format: ExportFormat,
+    output: Option<&Path>,
+) -> Result<ExportReport, ExportError> {
+    let path = match output {
+        Some(path) => path.to_path_buf(),
+        None => run_dir.join(EXPORT_DIR).join(format.default_file_name()),
+    };
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+    let records = match format {
+        ExportFormat::Kml | ExportFormat::Geojson => {
+            let points = geo::photo_points(run_dir)?;
+            let name = run_dir
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            if format == ExportFormat::Kml {
+                geo::write_kml(&path, &name, &points)?;
+            } else {
+                geo::write_geojson(&path, &points)?;
+            }
+            points.len()
+        }
+        ExportFormat::Timesketch => {
+            let events = timeline::events(run_dir)?;
+            timeline::write_jsonl(&path, &events)?;
+            events.len()
+        }
+    };
+    Ok(ExportReport { path, records })
+}
+
+/// Records of the metadata output `name` (e.g. `photo_devices`) of a run,
+/// from `metadata/<name>.jsonl`, `metadata/<name>.csv`, or
+/// `parquet/<name>.parquet`; empty when the run wrote none.
+pub fn read_records(run_dir: &Path, name: &str) -> Result<Vec<Record>, ExportError> {
+    let jsonl = run_dir.join("metadata").join(format!("{name}.jsonl"));
+    let csv = run_dir.join("metadata").join(format!("{name}.csv"));
+    let parquet = run_dir.join("parquet").join(format!("{name}.parquet"));
+    for path in [&jsonl, &csv, &parquet] {
+        let sealed = sealed_path(path);
+        if sealed.exists() {
+            return Err(ExportError::Encrypted(sealed));
+        }
+    }
+    if jsonl.exists() {
+        read_jsonl(&jsonl)
+    } else if csv.exists() {
+        read_csv(&csv)
+    } else if parquet.exists() {
+        read_parquet(&parquet)
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+/// Records of a JSONL stream; lines that do not parse (a record cut short
+/// by an aborted run) are skipped.
+fn read_jsonl(path: &Path) -> Result<Vec<Record>, ExportError> {
+    let mut records = Vec::new();
+    let mut skipped = 0u64;
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(record) => records.push(record),
+            Err(_) => skipped += 1,
+        }
+    }
+    if skipped > 0 {
+        warn!("skipped {skipped} malformed lines in {}", path.display());
+    }
+    Ok(records)
+}
+
+/// Records of a CSV stream; empty cells are null.
+fn read_csv(path: &Path) -> Result<Vec<Record>, ExportError> {
+    let mut reader = csv::Reader::from_path(path)?;
+    let headers = reader.headers()?.clone();
+    let mut records = Vec::new();
+    for row in reader.records() {
+        let row = row?;
+        let record = headers
+            .iter()
+            .zip(row.iter())
+            .map(|(key, value)| {
+                let value = if value.is_empty() {
+                    Value::Null
+                } else {
+                    Value::from(value)
+                };
+                (key.to_string(), value)
+            })
+            .collect();
+        records.push(record);
+    }
+    Ok(records)
+}
+
+fn read_parquet(path: &Path) -> Result<Vec<Record>, ExportError> {
+    let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(path)?)?.build()?;
+    let mut records = Vec::new();
+    for batch in reader {
+        let batch = batch?;
+        let schema = batch.schema();
+        for row in 0..batch.num_rows() {
+            let record = schema
+                .fields()
+                .iter()
+                .zip(batch.columns())
+                .map(|(field, column)| (field.name().clone(), arrow_value(column, row)))
+                .collect();
+            records.push(record);
+        }
+    }
+    Ok(records)
+}
+
+/// JSON value of one cell; timestamps become ISO 8601 text as in JSONL.
+fn arrow_value(array: &dyn Array, row: usize) -> Value {
+    if array.is_null(row) {
+        return Value::Null;
+    }
+    match array.data_type() {
+        DataType::Utf8 => Value::from(array.as_string::<i32>().value(row)),
+        DataType::Int32 => Value::from(array.as_primitive::<Int32Type>().value(row)),
+        DataType::Int64 => Value::from(array.as_primitive::<Int64Type>().value(row)),
+        DataType::Float64 => Value::from(array.as_primitive::<Float64Type>().value(row)),
+        DataType::Boolean => Value::from(array.as_boolean().value(row)),
+        DataType::Timestamp(TimeUnit::Microsecond, _) => DateTime::from_timestamp_micros(
+            array.as_primitive::<TimestampMicrosecondType>().value(row),
+        )
+        .map_or(Value::Null, |t| Value::from(iso_time(t.naive_utc()))),
+        _ => Value::Null,
+    }
+}
+
+/// Text of a field; numbers and booleans are formatted, empty text is `None`.
+pub fn text(record: &Record, key: &str) -> Option<String> {
+    match record.get(key)? {
+        Value::String(s) if !s.trim().is_empty() => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// Number of a field, whether stored as a number (JSONL, Parquet) or as
+/// text (CSV).
+pub fn number(record: &Record, key: &str) -> Option<f64> {
+    match record.get(key)? {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// Timestamp of a field, as written by any backend: `2024-05-01T10:15:30`
+/// (JSONL), `2024-05-01 10:15:30` (CSV), with fractional seconds, or with
+/// a UTC offset.
+pub fn time(record: &Record, key: &str) -> Option<NaiveDateTime> {
+    let value = text(record, key)?;
+    let value = value.trim();
+    if let Ok(t) = DateTime::parse_from_rfc3339(value) {
+        return Some(t.naive_utc());
+    }
+    if let Ok(t) = DateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S%.f %:z") {
+        return Some(t.naive_utc());
+    }
+    ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+}
+
+/// ISO 8601 text of a time, without a zone.
+pub fn iso_time(time: NaiveDateTime) -> String {
+    time.format("%Y-%m-%dT%H:%M:%S%.f").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_same_records_from_every_backend() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let jsonl_run = dir.path().join("jsonl");
+        std::fs::create_dir_all(jsonl_run.join("metadata")).expect("dir");
+        std::fs::write(
+            jsonl_run.join("metadata/photo_devices.jsonl"),
+            "{\"source_file\":\"a.jpg\",\"taken_at\":\"2024-05-01T10:15:30\",\"latitude\":48.5}\n\
+             {\"source_file\":\"b.jp",
+        )
+        .expect("write");
+        let csv_run = dir.path().join("csv");
+        std::fs::create_dir_all(csv_run.join("metadata")).expect("dir");
+        std::fs::write(
+            csv_run.join("metadata/photo_devices.csv"),
+            "source_file,taken_at,latitude,make\na.jpg,2024-05-01 10:15:30,48.5,\n",
+        )
+        .expect("write");
+
+        for run in [&jsonl_run, &csv_run] {
+            let records = read_records(run, "photo_devices").expect("records");
+            assert_eq!(records.len(), 1, "{}", run.display());
+            let record = &records[0];
+            assert_eq!(text(record, "source_file").as_deref(), Some("a.jpg"));
+            assert_eq!(number(record, "latitude"), Some(48.5));
+            assert_eq!(text(record, "make"), None);
+            assert_eq!(
+                time(record, "taken_at").map(iso_time).as_deref(),
+                Some("2024-05-01T10:15:30")
+            );
+        }
+        assert!(read_records(&csv_run, "messages").expect("none").is_empty());
+
+        std::fs::write(csv_run.join("metadata/messages.csv.age"), b"sealed").expect("write");
+        assert!(matches!(
+            read_records(&csv_run, "messages"),
+            Err(ExportError::Encrypted(_))
+        ));
+    }
+
+    #[test]
+    fn parses_times_with_offsets() {
+        let record: Record = serde_json::from_str(
+            r#"{"a":"2024-05-01 12:15:30 +02:00","b":"2024-05-01T10:15:30.250Z","c":"soon"}"#,
+        )
+        .expect("json");
+        assert_eq!(
+            time(&record, "a").map(iso_time).as_deref(),
+            Some("2024-05-01T10:15:30")
+        );
+        assert_eq!(
+            time(&record, "b").map(iso_time).as_deref(),
+            Some("2024-05-01T10:15:30.250")
+        );
+        assert_eq!(time(&record, "c"), None);
+    }
+}
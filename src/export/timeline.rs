@@ -0,0 +1,364 @@
+//! Timestamped records as a Timesketch JSONL timeline.
+//!
+//! Each timestamp of a record becomes one event with the fields Timesketch
+//! requires (`message`, `datetime`, `timestamp`, `timestamp_desc`), a
+//! `data_type` naming the metadata output, and the other fields of the
+//! record. Times are written as UTC; photo capture times are by the camera
+//! clock, whose zone is unknown. Events of a carved file that other clocks
+//! date too (`summaries/file_clocks.json`) carry every candidate and the
+//! consensus in `clock_*` fields.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+
+use chrono::NaiveDateTime;
+use serde_json::Value;
+
+use super::{ExportError, Record, iso_time, number, read_records, text, time};
+use crate::encryption::sealed_path;
+use crate::report::SUMMARIES_DIR;
+use crate::report::clocks::{ClockSource, FILE_CLOCKS_SUMMARY_FILE, FileClock, FileClockSummary};
+
+/// Fields every event sets; record fields of the same name are dropped.
+const RESERVED: &[&str] = &[
+    "message",
+    "datetime",
+    "timestamp",
+    "timestamp_desc",
+    "data_type",
+];
+
+/// A metadata output with timestamps.
+struct Source {
+    name: &'static str,
+    /// `(field, timestamp_desc)` of each timestamp.
+    times: &'static [(&'static str, &'static str)],
+    /// Fields joined with spaces into the event message.
+    message: &'static [&'static str],
+}
+
+const SOURCES: &[Source] = &[
+    Source {
+        name: "browser_history",
+        times: &[("visit_time", "Visited")],
+        message: &["browser", "url", "title"],
+    },
+    Source {
+        name: "browser_cookies",
+        times: &[
+            ("creation_utc", "Cookie created"),
+            ("last_access_utc", "Cookie last accessed"),
+        ],
+        message: &["browser", "host", "name"],
+    },
+    Source {
+        name: "browser_downloads",
+        times: &[
+            ("start_time", "Download started"),
+            ("end_time", "Download finished"),
+        ],
+        message: &["browser", "url", "target_path"],
+    },
+    Source {
+        name: "log_events",
+        times: &[("timestamp", "Logged")],
+        message: &["host", "program", "message"],
+    },
+    Source {
+        name: "messages",
+        times: &[
+            ("timestamp_utc", "Sent or created"),
+            ("modified_utc", "Modified"),
+        ],
+        message: &["app", "thread", "handle", "text"],
+    },
+    Source {
+        name: "usage_events",
+        times: &[("start_utc", "Usage started"), ("end_utc", "Usage ended")],
+        message: &["source", "stream", "value"],
+    },
+    Source {
+        name: "photo_devices",
+        times: &[("taken_at", "Photo taken")],
+        message: &["make", "model", "source_file"],
+    },
+];
+
+/// One timeline event.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimelineEvent {
+    pub time: NaiveDateTime,
+    pub timestamp_desc: &'static str,
+    /// `swiftbeaver:<metadata output>`.
+    pub data_type: String,
+    pub message: String,
+    /// The other fields of the record.
+    pub fields: Record,
+}
+
+impl TimelineEvent {
+    /// The Timesketch JSON object of the event.
+    pub fn to_json(&self) -> Value {
+        let mut object = Record::new();
+        object.insert("message".into(), Value::from(self.message.clone()));
+        object.insert(
+            "datetime".into(),
+            Value::from(self.time.format("%Y-%m-%dT%H:%M:%S%.6f+00:00").to_string()),
+        );
+        object.insert(
+            "timestamp".into(),
+            Value::from(self.time.and_utc().timestamp_micros()),
+        );
+        object.insert("timestamp_desc".into(), Value::from(self.timestamp_desc));
+        object.insert("data_type".into(), Value::from(self.data_type.clone()));
+        for (key, value) in &self.fields {
+            object.insert(key.clone(), value.clone());
+        }
+        Value::Object(object)
+    }
+}
+
+/// Events of every timestamped record of the run, oldest first.
+pub fn events(run_dir: &Path) -> Result<Vec<TimelineEvent>, ExportError> {
+    let clocks = file_clocks(run_dir)?;
+    let mut events = Vec::new();
+    for source in SOURCES {
+        for record in read_records(run_dir, source.name)? {
+            events.extend(source_events(source, &record, &clocks));
+        }
+    }
+    events.sort_by(|a, b| {
+        (a.time, &a.data_type, &a.message).cmp(&(b.time, &b.data_type, &b.message))
+    });
+    Ok(events)
+}
+
+/// Cross-dated files of `summaries/file_clocks.json` by evidence offset;
+/// empty when the run wrote none.
+fn file_clocks(run_dir: &Path) -> Result<HashMap<u64, FileClock>, ExportError> {
+    let path = run_dir.join(SUMMARIES_DIR).join(FILE_CLOCKS_SUMMARY_FILE);
+    let sealed = sealed_path(&path);
+    if sealed.exists() {
+        return Err(ExportError::Encrypted(sealed));
+    }
+    if !path.exists() {
+        return Ok(HashMap::new());
+    }
+    let summary: FileClockSummary = serde_json::from_reader(BufReader::new(File::open(&path)?))?;
+    Ok(summary
+        .files
+        .into_iter()
+        .map(|file| (file.global_start, file))
+        .collect())
+}
+
+/// Comma-separated source names.
+fn source_list(sources: &[ClockSource]) -> String {
+    sources
+        .iter()
+        .map(|s| s.as_str())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// `clock_*` fields of a file dated by several clocks.
+fn clock_fields(file: &FileClock) -> Record {
+    let candidates = file
+        .candidates
+        .iter()
+        .map(|c| {
+            let zone = if c.source.is_utc() { "Z" } else { "" };
+            format!(
+                "{}:{}={}{zone}",
+                c.source.as_str(),
+                c.field,
+                iso_time(c.time)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("; ");
+    let consensus = &file.consensus;
+    let zone = if consensus.utc { "Z" } else { "" };
+    let mut fields = Record::new();
+    fields.insert("clock_candidates".into(), Value::from(candidates));
+    fields.insert(
+        "clock_consensus".into(),
+        Value::from(format!("{}{zone}", iso_time(consensus.time))),
+    );
+    fields.insert("clock_confidence".into(), Value::from(consensus.confidence));
+    fields.insert(
+        "clock_agreeing".into(),
+        Value::from(source_list(&consensus.agreeing)),
+    );
+    if !consensus.disagreeing.is_empty() {
+        fields.insert(
+            "clock_disagreeing".into(),
+            Value::from(source_list(&consensus.disagreeing)),
+        );
+    }
+    if let Some(minutes) = consensus.camera_offset_minutes {
+        fields.insert("clock_camera_offset_minutes".into(), Value::from(minutes));
+    }
+    fields
+}
+
+fn source_events(
+    source: &Source,
+    record: &Record,
+    clocks: &HashMap<u64, FileClock>,
+) -> Vec<TimelineEvent> {
+    let message = source
+        .message
+        .iter()
+        .filter_map(|key| text(record, key))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let mut fields: Record = record
+        .iter()
+        .filter(|(key, value)| !value.is_null() && !RESERVED.contains(&key.as_str()))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    if let Some(file) = number(record, "global_start").and_then(|start| clocks.get(&(start as u64)))
+    {
+        fields.extend(clock_fields(file));
+    }
+    source
+        .times
+        .iter()
+        .filter_map(|&(field, desc)| {
+            Some(TimelineEvent {
+                time: time(record, field)?,
+                timestamp_desc: desc,
+                data_type: format!("swiftbeaver:{}", source.name),
+                message: message.clone(),
+                fields: fields.clone(),
+            })
+        })
+        .collect()
+}
+
+pub fn write_jsonl(path: &Path, events: &[TimelineEvent]) -> Result<(), ExportError> {
+    let mut out = BufWriter::new(File::create(path)?);
+    for event in events {
+        serde_json::to_writer(&mut out, &event.to_json())?;
+        out.write_all(b"\n")?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_sorted_events_with_timesketch_fields() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let meta = dir.path().join("metadata");
+        std::fs::create_dir_all(&meta).expect("dir");
+        std::fs::write(
+            meta.join("browser_downloads.jsonl"),
+            concat!(
+                r#"{"browser":"chrome","url":"https://example.com/a.zip","target_path":"C:\\a.zip","#,
+                r#""start_time":"2024-05-01T10:00:00","end_time":"2024-05-01T10:02:00","total_bytes":42}"#,
+                "\n"
+            ),
+        )
+        .expect("write");
+        std::fs::write(
+            meta.join("log_events.csv"),
+            "source,timestamp,host,program,message\n\
+             syslog,2024-05-01 10:01:00,box,sshd,Accepted password\n\
+             syslog,,box,cron,no date\n",
+        )
+        .expect("write");
+
+        let events = events(dir.path()).expect("events");
+        assert_eq!(
+            events.iter().map(|e| e.timestamp_desc).collect::<Vec<_>>(),
+            ["Download started", "Logged", "Download finished"]
+        );
+        let log = events[1].to_json();
+        assert_eq!(log["message"], "box sshd Accepted password");
+        assert_eq!(log["datetime"], "2024-05-01T10:01:00.000000+00:00");
+        assert_eq!(log["timestamp"], 1_714_557_660_000_000i64);
+        assert_eq!(log["data_type"], "swiftbeaver:log_events");
+        assert_eq!(log["source"], "syslog");
+
+        let download = events[0].to_json();
+        assert_eq!(
+            download["message"],
+            "chrome https://example.com/a.zip C:\\a.zip"
+        );
+        assert_eq!(download["total_bytes"], 42);
+        assert_eq!(download["end_time"], "2024-05-01T10:02:00");
+    }
+
+    #[test]
+    fn photo_events_carry_every_clock_of_the_file() {
+        use crate::report::clocks::{ClockConsensus, TimeCandidate, write_summary};
+
+        let dir = tempfile::tempdir().expect("tempdir");
+        let meta = dir.path().join("metadata");
+        std::fs::create_dir_all(&meta).expect("dir");
+        std::fs::write(
+            meta.join("photo_devices.csv"),
+            "source_file,global_start,make,model,taken_at\n\
+             jpeg/a.jpg,4096,Canon,EOS R5,2024-05-01 12:00:00\n\
+             jpeg/b.jpg,8192,Canon,EOS R5,2024-05-01 13:00:00\n",
+        )
+        .expect("write");
+        let at =
+            |text: &str| NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S").expect("time");
+        let candidate = |source, field: &str, time| TimeCandidate {
+            source,
+            field: field.to_string(),
+            time: at(time),
+        };
+        write_summary(
+            dir.path(),
+            &FileClockSummary {
+                run_id: "run".to_string(),
+                tolerance_secs: 120,
+                files_dated: 2,
+                files_cross_dated: 1,
+                files_in_conflict: 1,
+                files: vec![FileClock {
+                    global_start: 4096,
+                    paths: vec!["jpeg/a.jpg".to_string()],
+                    candidates: vec![
+                        candidate(ClockSource::Ntfs, "modified", "2024-05-01 10:00:00"),
+                        candidate(ClockSource::Exif, "taken_at", "2024-05-01 12:00:00"),
+                        candidate(ClockSource::Http, "date", "2024-05-03 09:00:00"),
+                    ],
+                    consensus: ClockConsensus {
+                        time: at("2024-05-01 10:00:00"),
+                        utc: true,
+                        agreeing: vec![ClockSource::Exif, ClockSource::Ntfs],
+                        disagreeing: vec![ClockSource::Http],
+                        confidence: 0.67,
+                        camera_offset_minutes: Some(120),
+                    },
+                }],
+            },
+        )
+        .expect("summary");
+
+        let events = events(dir.path()).expect("events");
+        let photo = events[0].to_json();
+        assert_eq!(photo["datetime"], "2024-05-01T12:00:00.000000+00:00");
+        assert_eq!(
+            photo["clock_candidates"],
+            "ntfs:modified=2024-05-01T10:00:00Z; exif:taken_at=2024-05-01T12:00:00; \
+             http:date=2024-05-03T09:00:00Z"
+        );
+        assert_eq!(photo["clock_consensus"], "2024-05-01T10:00:00Z");
+        assert_eq!(photo["clock_confidence"], 0.67);
+        assert_eq!(photo["clock_agreeing"], "exif,ntfs");
+        assert_eq!(photo["clock_disagreeing"], "http");
+        assert_eq!(photo["clock_camera_offset_minutes"], 120);
+        assert!(events[1].to_json().get("clock_consensus").is_none());
+    }
+}
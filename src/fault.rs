@@ -0,0 +1,440 @@
+//! # Fault Injection
+//!
+//! Wrappers that inject failures into pipeline components for robustness tests.
+//! Only compiled for unit tests or with the `fault-injection` feature; production
+//! builds never contain these types.
+//!
+//! - [`FlakyEvidence`] fails evidence reads.
+//! - [`FlakySink`] fails metadata writes.
+//! - [`PanickingScanner`] and [`PanickingCarveHandler`] panic inside worker threads.
+//!
+//! Failure timing comes from a [`FaultSchedule`], which is deterministic so test
+//! failures are reproducible.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::carve::{CarveError, CarveHandler, CarvedFile, ExtractionContext};
+use crate::chunk::ScanChunk;
+use crate::evidence::{EvidenceError, EvidenceSource};
+use crate::inputs::InputFileRecord;
+use crate::metadata::{
+    CarveCompanionRecord, CarveSizeRecord, ClassificationRecord, EntropyRegion, MetadataError,
+    MetadataSink, ResourceUsage, RunSummary, VolumeLocationRecord,
+};
+use crate::parsers::apple::MessageRecord;
+use crate::parsers::apple::UsageEventRecord;
+use crate::parsers::bplist::PlistRecord;
+use crate::parsers::browser::{
+    BrowserCacheRecord, BrowserCookieRecord, BrowserDownloadRecord, BrowserHistoryRecord,
+};
+use crate::parsers::container::ContainerArtefactRecord;
+use crate::parsers::executable::ExecutableRecord;
+use crate::parsers::exif::PhotoDeviceRecord;
+use crate::parsers::image::ImageInfoRecord;
+use crate::parsers::lnk::ShortcutRecord;
+use crate::parsers::logs::LogEventRecord;
+use crate::parsers::mp4::VideoRepairRecord;
+use crate::parsers::ntfs::NtfsFileRecord;
+use crate::parsers::office_crypto::EncryptedDocumentRecord;
+use crate::parsers::prefetch::PrefetchRecord;
+use crate::parsers::sqlite_apps::AppRecord;
+use crate::parsers::thumbcache::ThumbnailRecord;
+use crate::scanner::{Hit, NormalizedHit, SignatureScanner};
+use crate::strings::artifacts::StringArtefact;
+
+/// Decides which calls fail.
+#[derive(Debug)]
+pub struct FaultSchedule {
+    kind: ScheduleKind,
+    calls: AtomicU64,
+    injected: AtomicU64,
+}
+
+#[derive(Debug)]
+enum ScheduleKind {
+    Never,
+    /// Fail every nth call (1-based: `every(3)` fails calls 3, 6, 9, ...).
+    Every(u64),
+    /// Fail only the nth call (1-based).
+    Nth(u64),
+    /// Fail roughly `percent`% of calls, from a seeded generator.
+    Percent {
+        percent: u64,
+        seed: u64,
+    },
+}
+
+impl FaultSchedule {
+    pub fn never() -> Self {
+        Self::with_kind(ScheduleKind::Never)
+    }
+
+    pub fn every(n: u64) -> Self {
+        Self::with_kind(ScheduleKind::Every(n.max(1)))
+    }
+
+    pub fn nth(n: u64) -> Self {
+        Self::with_kind(ScheduleKind::Nth(n))
+    }
+
+    pub fn percent(percent: u64, seed: u64) -> Self {
+        Self::with_kind(ScheduleKind::Percent {
+            percent: percent.min(100),
+            seed,
+        })
+    }
+
+    fn with_kind(kind: ScheduleKind) -> Self {
+        Self {
+            kind,
+            calls: AtomicU64::new(0),
+            injected: AtomicU64::new(0),
+        }
+    }
+
+    /// Advance the schedule; returns true when this call should fail.
+    pub fn trip(&self) -> bool {
+        let call = self.calls.fetch_add(1, Ordering::Relaxed) + 1;
+        let fail = match self.kind {
+            ScheduleKind::Never => false,
+            ScheduleKind::Every(n) => call.is_multiple_of(n),
+            ScheduleKind::Nth(n) => call == n,
+            ScheduleKind::Percent { percent, seed } => mix(seed ^ call) % 100 < percent,
+        };
+        if fail {
+            self.injected.fetch_add(1, Ordering::Relaxed);
+        }
+        fail
+    }
+
+    /// Number of calls seen so far.
+    pub fn calls(&self) -> u64 {
+        self.calls.load(Ordering::Relaxed)
+    }
+
+    /// Number of faults injected so far.
+    pub fn injected(&self) -> u64 {
+        self.injected.load(Ordering::Relaxed)
+    }
+}
+
+/// SplitMix64 finalizer; good enough to spread sequential call numbers.
+fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^ (x >> 31)
+}
+
+/// Evidence source whose reads fail according to a schedule.
+pub struct FlakyEvidence {
+    inner: Arc<dyn EvidenceSource>,
+    schedule: Arc<FaultSchedule>,
+}
+
+impl FlakyEvidence {
+    pub fn new(inner: Arc<dyn EvidenceSource>, schedule: Arc<FaultSchedule>) -> Self {
+        Self { inner, schedule }
+    }
+}
+
+impl EvidenceSource for FlakyEvidence {
+    fn len(&self) -> u64 {
+        self.inner.len()
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize, EvidenceError> {
+        if self.schedule.trip() {
+            return Err(EvidenceError::Io(std::io::Error::other(format!(
+                "injected read fault at offset {offset}"
+            ))));
+        }
+        self.inner.read_at(offset, buf)
+    }
+
+    fn prefetch(&self, offset: u64, len: u64) {
+        self.inner.prefetch(offset, len);
+    }
+}
+
+/// Metadata sink whose writes fail according to a schedule. Flushes always
+/// reach the inner sink so output written before a fault is preserved.
+pub struct FlakySink {
+    inner: Box<dyn MetadataSink>,
+    schedule: Arc<FaultSchedule>,
+}
+
+impl FlakySink {
+    pub fn new(inner: Box<dyn MetadataSink>, schedule: Arc<FaultSchedule>) -> Self {
+        Self { inner, schedule }
+    }
+
+    fn check(&self) -> Result<(), MetadataError> {
+        if self.schedule.trip() {
+            return Err(MetadataError::Other("injected sink fault".to_string()));
+        }
+        Ok(())
+    }
+}
+
+impl MetadataSink for FlakySink {
+    fn record_file(&self, file: &CarvedFile) -> Result<(), MetadataError> {
+        self.check()?;
+        self.inner.record_file(file)
+    }
+
+    fn record_string(&self, artefact: &StringArtefact) -> Result<(), MetadataError> {
+        self.check()?;
+        self.inner.record_string(artefact)
+    }
+
+    fn record_history(&self, record: &BrowserHistoryRecord) -> Result<(), MetadataError> {
+        self.check()?;
+        self.inner.record_history(record)
+    }
+
+    fn record_cookie(&self, record: &BrowserCookieRecord) -> Result<(), MetadataError> {
+        self.check()?;
+        self.inner.record_cookie(record)
+    }
+
+    fn record_download(&self, record: &BrowserDownloadRecord) -> Result<(), MetadataError> {
+        self.check()?;
+        self.inner.record_download(record)
+    }
+
+    fn record_cache(&self, record: &BrowserCacheRecord) -> Result<(), MetadataError> {
+        self.check()?;
+        self.inner.record_cache(record)
+    }
+
+    fn record_log_event(&self, record: &LogEventRecord) -> Result<(), MetadataError> {
+        self.check()?;
+        self.inner.record_log_event(record)
+    }
+
+    fn record_container(&self, record: &ContainerArtefactRecord) -> Result<(), MetadataError> {
+        self.check()?;
+        self.inner.record_container(record)
+    }
+
+    fn record_run_summary(&self, summary: &RunSummary) -> Result<(), MetadataError> {
+        self.check()?;
+        self.inner.record_run_summary(summary)
+    }
+
+    fn record_message(&self, record: &MessageRecord) -> Result<(), MetadataError> {
+        self.check()?;
+        self.inner.record_message(record)
+    }
+
+    fn record_usage_event(&self, record: &UsageEventRecord) -> Result<(), MetadataError> {
+        self.check()?;
+        self.inner.record_usage_event(record)
+    }
+
+    fn record_app_record(&self, record: &AppRecord) -> Result<(), MetadataError> {
+        self.check()?;
+        self.inner.record_app_record(record)
+    }
+
+    fn record_prefetch(&self, record: &PrefetchRecord) -> Result<(), MetadataError> {
+        self.check()?;
+        self.inner.record_prefetch(record)
+    }
+
+    fn record_shortcut(&self, record: &ShortcutRecord) -> Result<(), MetadataError> {
+        self.check()?;
+        self.inner.record_shortcut(record)
+    }
+
+    fn record_executable(&self, record: &ExecutableRecord) -> Result<(), MetadataError> {
+        self.check()?;
+        self.inner.record_executable(record)
+    }
+
+    fn record_thumbnail(&self, record: &ThumbnailRecord) -> Result<(), MetadataError> {
+        self.check()?;
+        self.inner.record_thumbnail(record)
+    }
+
+    fn record_plist(&self, record: &PlistRecord) -> Result<(), MetadataError> {
+        self.check()?;
+        self.inner.record_plist(record)
+    }
+
+    fn record_video_repair(&self, record: &VideoRepairRecord) -> Result<(), MetadataError> {
+        self.check()?;
+        self.inner.record_video_repair(record)
+    }
+
+    fn record_image_info(&self, record: &ImageInfoRecord) -> Result<(), MetadataError> {
+        self.check()?;
+        self.inner.record_image_info(record)
+    }
+
+    fn record_photo_device(&self, record: &PhotoDeviceRecord) -> Result<(), MetadataError> {
+        self.check()?;
+        self.inner.record_photo_device(record)
+    }
+
+    fn record_ntfs_file(&self, record: &NtfsFileRecord) -> Result<(), MetadataError> {
+        self.check()?;
+        self.inner.record_ntfs_file(record)
+    }
+
+    fn record_input_file(&self, record: &InputFileRecord) -> Result<(), MetadataError> {
+        self.check()?;
+        self.inner.record_input_file(record)
+    }
+
+    fn record_encrypted_document(
+        &self,
+        record: &EncryptedDocumentRecord,
+    ) -> Result<(), MetadataError> {
+        self.check()?;
+        self.inner.record_encrypted_document(record)
+    }
+
+    fn record_carve_size(&self, record: &CarveSizeRecord) -> Result<(), MetadataError> {
+        self.check()?;
+        self.inner.record_carve_size(record)
+    }
+
+    fn record_carve_companion(&self, record: &CarveCompanionRecord) -> Result<(), MetadataError> {
+        self.check()?;
+        self.inner.record_carve_companion(record)
+    }
+
+    fn record_volume_location(&self, record: &VolumeLocationRecord) -> Result<(), MetadataError> {
+        self.check()?;
+        self.inner.record_volume_location(record)
+    }
+
+    fn record_classification(&self, record: &ClassificationRecord) -> Result<(), MetadataError> {
+        self.check()?;
+        self.inner.record_classification(record)
+    }
+
+    fn record_entropy(&self, region: &EntropyRegion) -> Result<(), MetadataError> {
+        self.check()?;
+        self.inner.record_entropy(region)
+    }
+
+    fn record_resource_usage(&self, usage: &ResourceUsage) -> Result<(), MetadataError> {
+        self.check()?;
+        self.inner.record_resource_usage(usage)
+    }
+
+    fn flush(&self) -> Result<(), MetadataError> {
+        self.inner.flush()
+    }
+}
+
+/// Signature scanner that panics when scanning the chunks a schedule selects.
+pub struct PanickingScanner {
+    inner: Arc<dyn SignatureScanner>,
+    schedule: Arc<FaultSchedule>,
+}
+
+impl PanickingScanner {
+    pub fn new(inner: Arc<dyn SignatureScanner>, schedule: Arc<FaultSchedule>) -> Self {
+        Self { inner, schedule }
+    }
+}
+
+impl SignatureScanner for PanickingScanner {
+    fn scan_chunk(&self, chunk: &ScanChunk, data: &[u8]) -> Vec<Hit> {
+        if self.schedule.trip() {
+            panic!("injected scan panic in chunk {}", chunk.id);
+        }
+        self.inner.scan_chunk(chunk, data)
+    }
+}
+
+/// Carve handler that panics on hits a schedule selects and otherwise
+/// delegates to the wrapped handler.
+pub struct PanickingCarveHandler {
+    inner: Box<dyn CarveHandler>,
+    schedule: Arc<FaultSchedule>,
+}
+
+impl PanickingCarveHandler {
+    pub fn new(inner: Box<dyn CarveHandler>, schedule: Arc<FaultSchedule>) -> Self {
+        Self { inner, schedule }
+    }
+}
+
+impl CarveHandler for PanickingCarveHandler {
+    fn file_type(&self) -> &str {
+        self.inner.file_type()
+    }
+
+    fn extension(&self) -> &str {
+        self.inner.extension()
+    }
+
+    fn process_hit(
+        &self,
+        hit: &NormalizedHit,
+        ctx: &ExtractionContext,
+    ) -> Result<Option<CarvedFile>, CarveError> {
+        if self.schedule.trip() {
+            panic!("injected carve panic at offset {}", hit.global_offset);
+        }
+        self.inner.process_hit(hit, ctx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Zeros(u64);
+
+    impl EvidenceSource for Zeros {
+        fn len(&self) -> u64 {
+            self.0
+        }
+
+        fn read_at(&self, _offset: u64, buf: &mut [u8]) -> Result<usize, EvidenceError> {
+            buf.fill(0);
+            Ok(buf.len())
+        }
+    }
+
+    #[test]
+    fn every_schedule_fails_each_nth_call() {
+        let schedule = FaultSchedule::every(3);
+        let fails: Vec<bool> = (0..7).map(|_| schedule.trip()).collect();
+        assert_eq!(fails, vec![false, false, true, false, false, true, false]);
+        assert_eq!(schedule.injected(), 2);
+        assert_eq!(schedule.calls(), 7);
+
+        let once = FaultSchedule::nth(2);
+        let fails: Vec<bool> = (0..4).map(|_| once.trip()).collect();
+        assert_eq!(fails, vec![false, true, false, false]);
+    }
+
+    #[test]
+    fn percent_schedule_is_deterministic_and_roughly_calibrated() {
+        let a = FaultSchedule::percent(25, 7);
+        let b = FaultSchedule::percent(25, 7);
+        let run_a: Vec<bool> = (0..1000).map(|_| a.trip()).collect();
+        let run_b: Vec<bool> = (0..1000).map(|_| b.trip()).collect();
+        assert_eq!(run_a, run_b);
+        assert!((150..350).contains(&a.injected()), "{}", a.injected());
+        assert!(!FaultSchedule::never().trip());
+        assert!(FaultSchedule::percent(100, 1).trip());
+    }
+
+    #[test]
+    fn flaky_evidence_fails_scheduled_reads() {
+        let evidence = FlakyEvidence::new(Arc::new(Zeros(16)), Arc::new(FaultSchedule::every(2)));
+        let mut buf = [1u8; 4];
+        assert_eq!(evidence.read_at(0, &mut buf).expect("first read"), 4);
+        assert!(evidence.read_at(4, &mut buf).is_err());
+        assert_eq!(evidence.len(), 16);
+    }
+}
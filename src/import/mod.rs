@@ -0,0 +1,160 @@
+//! # Signature Import
+//!
+//! Converters from third-party carver signature definitions into `file_types`
+//! entries for the generic `footer` validator. Entries that cannot be represented
+//! faithfully are reported instead of being silently dropped.
+
+pub mod photorec;
+pub mod scalpel;
+
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::config::FileTypeConfig;
+
+/// Signatures shorter than this flood the scanner with false positives.
+pub const MIN_SIGNATURE_LEN: usize = 2;
+
+#[derive(Debug, Error)]
+pub enum ImportError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("yaml error: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+}
+
+/// A source line that could not be converted, or was converted only approximately.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportIssue {
+    /// 1-based line number in the source file.
+    pub line: usize,
+    pub text: String,
+    pub reason: String,
+}
+
+/// Result of converting a signature file.
+#[derive(Debug, Default, Clone)]
+pub struct ImportResult {
+    pub file_types: Vec<FileTypeConfig>,
+    /// Entries that could not be represented and were dropped.
+    pub skipped: Vec<ImportIssue>,
+    /// Entries converted with weaker semantics than the source format.
+    pub approximated: Vec<ImportIssue>,
+}
+
+#[derive(Serialize)]
+struct FileTypesDocument<'a> {
+    file_types: &'a [FileTypeConfig],
+}
+
+impl ImportResult {
+    /// Render the converted entries as a YAML `file_types:` block, with skipped
+    /// and approximated entries listed as leading comments.
+    pub fn to_yaml(&self, source: &str) -> Result<String, ImportError> {
+        let mut out = String::new();
+        out.push_str(&format!("# Imported from {source}\n"));
+        out.push_str(&format!(
+            "# {} file types converted, {} entries skipped, {} approximated\n",
+            self.file_types.len(),
+            self.skipped.len(),
+            self.approximated.len()
+        ));
+        for (label, issues) in [
+            ("skipped", &self.skipped),
+            ("approximated", &self.approximated),
+        ] {
+            for entry in issues {
+                out.push_str(&format!(
+                    "#   {label} line {}: {} ({})\n",
+                    entry.line, entry.reason, entry.text
+                ));
+            }
+        }
+        out.push_str(&serde_yaml::to_string(&FileTypesDocument {
+            file_types: &self.file_types,
+        })?);
+        Ok(out)
+    }
+}
+
+/// Encode pattern bytes the way `config/default.yml` does (uppercase hex).
+pub(crate) fn pattern_hex(bytes: &[u8]) -> String {
+    hex::encode_upper(bytes)
+}
+
+/// Build a file type id from a prefix and a source extension.
+pub(crate) fn file_type_id(prefix: &str, ext: &str) -> String {
+    let ext: String = ext
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{prefix}{ext}")
+}
+
+/// Decode a C-style escape sequence character (after the backslash).
+pub(crate) fn unescape_char(c: char) -> Option<u8> {
+    match c {
+        'n' => Some(b'\n'),
+        'r' => Some(b'\r'),
+        't' => Some(b'\t'),
+        'b' => Some(0x08),
+        'a' => Some(0x07),
+        'f' => Some(0x0C),
+        'v' => Some(0x0B),
+        '0' => Some(0),
+        '\\' => Some(b'\\'),
+        '"' => Some(b'"'),
+        '\'' => Some(b'\''),
+        ' ' => Some(b' '),
+        '?' => Some(b'?'),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{FooterMode, PatternConfig, RetentionPolicy};
+
+    #[test]
+    fn renders_yaml_with_skipped_comments() {
+        let result = ImportResult {
+            file_types: vec![FileTypeConfig {
+                id: "photorec_abc".to_string(),
+                extensions: vec!["abc".to_string()],
+                header_patterns: vec![PatternConfig {
+                    id: "photorec_abc_1".to_string(),
+                    hex: pattern_hex(b"ABC"),
+                }],
+                footer_patterns: Vec::new(),
+                max_size: 1024,
+                min_size: 0,
+                validator: "footer".to_string(),
+                require_eocd: false,
+                footer_mode: FooterMode::Inclusive,
+                retention: RetentionPolicy::KeepAll,
+                hit_cluster_window: None,
+                declared_size_factor: None,
+                post_processors: None,
+            }],
+            skipped: vec![ImportIssue {
+                line: 3,
+                text: "xyz 4 0x01".to_string(),
+                reason: "offset 4 not supported".to_string(),
+            }],
+            approximated: Vec::new(),
+        };
+        let yaml = result.to_yaml("test.sig").expect("yaml");
+        assert!(yaml.contains("#   skipped line 3: offset 4 not supported (xyz 4 0x01)"));
+        assert!(yaml.contains("hex: '414243'") || yaml.contains("hex: \"414243\""));
+
+        let body: serde_yaml::Value = serde_yaml::from_str(&yaml).expect("parse");
+        assert_eq!(body["file_types"][0]["validator"], "footer");
+    }
+}
@@ -0,0 +1,277 @@
+//! PhotoRec signature file (`photorec.sig`) importer.
+//!
+//! Each line has the form `extension offset signature`, where the signature is a
+//! sequence of `0x` hex runs, `"quoted strings"`, and `'c'` characters. Lines
+//! sharing an extension are merged into one file type with several header
+//! patterns. PhotoRec signatures carry no footer or size, so entries use the
+//! `footer` validator's length strategy with a configurable `max_size`.
+
+use std::collections::BTreeMap;
+
+use crate::config::{FileTypeConfig, FooterMode, PatternConfig, RetentionPolicy};
+
+use super::{
+    ImportIssue, ImportResult, MIN_SIGNATURE_LEN, file_type_id, pattern_hex, unescape_char,
+};
+
+/// Options controlling how PhotoRec signatures are converted.
+#[derive(Debug, Clone)]
+pub struct PhotorecImportOptions {
+    /// Prefix for generated file type ids (avoids clashing with built-in ids).
+    pub id_prefix: String,
+    /// Bytes carved per hit (PhotoRec signatures do not define a size).
+    pub max_size: u64,
+    pub min_size: u64,
+}
+
+impl Default for PhotorecImportOptions {
+    fn default() -> Self {
+        Self {
+            id_prefix: "photorec_".to_string(),
+            max_size: 10 * 1024 * 1024,
+            min_size: 0,
+        }
+    }
+}
+
+/// Convert the contents of a PhotoRec signature file.
+pub fn parse_signatures(text: &str, opts: &PhotorecImportOptions) -> ImportResult {
+    let mut grouped: BTreeMap<String, Vec<Vec<u8>>> = BTreeMap::new();
+    let mut order: Vec<String> = Vec::new();
+    let mut skipped = Vec::new();
+
+    for (idx, raw) in text.lines().enumerate() {
+        let line = raw.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let skip = |reason: String| ImportIssue {
+            line: idx + 1,
+            text: line.to_string(),
+            reason,
+        };
+
+        let (ext, rest) = split_token(line);
+        let (offset_str, signature_str) = split_token(rest);
+        if ext.is_empty() || offset_str.is_empty() || signature_str.is_empty() {
+            skipped.push(skip("expected `extension offset signature`".to_string()));
+            continue;
+        }
+        let offset = match parse_offset(offset_str) {
+            Some(offset) => offset,
+            None => {
+                skipped.push(skip(format!("invalid offset `{offset_str}`")));
+                continue;
+            }
+        };
+        if offset != 0 {
+            skipped.push(skip(format!(
+                "signature at offset {offset} (only offset 0 is supported)"
+            )));
+            continue;
+        }
+        let signature = match parse_signature(signature_str) {
+            Ok(bytes) => bytes,
+            Err(reason) => {
+                skipped.push(skip(reason));
+                continue;
+            }
+        };
+        if signature.len() < MIN_SIGNATURE_LEN {
+            skipped.push(skip(format!(
+                "signature shorter than {MIN_SIGNATURE_LEN} bytes"
+            )));
+            continue;
+        }
+
+        let ext = ext.to_ascii_lowercase();
+        let patterns = grouped.entry(ext.clone()).or_insert_with(|| {
+            order.push(ext.clone());
+            Vec::new()
+        });
+        if !patterns.contains(&signature) {
+            patterns.push(signature);
+        }
+    }
+
+    let file_types = order
+        .iter()
+        .map(|ext| {
+            let id = file_type_id(&opts.id_prefix, ext);
+            let header_patterns = grouped[ext]
+                .iter()
+                .enumerate()
+                .map(|(n, bytes)| PatternConfig {
+                    id: format!("{id}_{}", n + 1),
+                    hex: pattern_hex(bytes),
+                })
+                .collect();
+            FileTypeConfig {
+                id,
+                extensions: vec![ext.clone()],
+                header_patterns,
+                footer_patterns: Vec::new(),
+                max_size: opts.max_size,
+                min_size: opts.min_size,
+                validator: "footer".to_string(),
+                require_eocd: false,
+                footer_mode: FooterMode::Inclusive,
+                retention: RetentionPolicy::KeepAll,
+                hit_cluster_window: None,
+                declared_size_factor: None,
+                post_processors: None,
+            }
+        })
+        .collect();
+
+    ImportResult {
+        file_types,
+        skipped,
+        approximated: Vec::new(),
+    }
+}
+
+fn split_token(value: &str) -> (&str, &str) {
+    let value = value.trim_start();
+    match value.find(char::is_whitespace) {
+        Some(pos) => (&value[..pos], value[pos..].trim_start()),
+        None => (value, ""),
+    }
+}
+
+fn parse_offset(value: &str) -> Option<u64> {
+    if let Some(hex) = value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        u64::from_str_radix(hex, 16).ok()
+    } else {
+        value.parse().ok()
+    }
+}
+
+fn parse_signature(value: &str) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '#' => break,
+            '"' | '\'' => {
+                let quote = c;
+                let mut closed = false;
+                while let Some(c) = chars.next() {
+                    if c == quote {
+                        closed = true;
+                        break;
+                    }
+                    if c == '\\' {
+                        let escaped = chars
+                            .next()
+                            .ok_or_else(|| "unterminated escape sequence".to_string())?;
+                        let byte = unescape_char(escaped)
+                            .ok_or_else(|| format!("unsupported escape `\\{escaped}`"))?;
+                        out.push(byte);
+                    } else if c.is_ascii() {
+                        out.push(c as u8);
+                    } else {
+                        let mut buf = [0u8; 4];
+                        out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                    }
+                }
+                if !closed {
+                    return Err("unterminated quoted string".to_string());
+                }
+            }
+            '0' if matches!(chars.peek(), Some('x') | Some('X')) => {
+                chars.next();
+                let mut digits = String::new();
+                while let Some(&d) = chars.peek() {
+                    if d.is_ascii_hexdigit() {
+                        digits.push(d);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                if digits.is_empty() || !digits.len().is_multiple_of(2) {
+                    return Err(format!("invalid hex run `0x{digits}`"));
+                }
+                out.extend(hex::decode(&digits).map_err(|e| e.to_string())?);
+            }
+            other => return Err(format!("unexpected character `{other}` in signature")),
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_hex_and_string_signatures() {
+        let text = "\
+# comment
+doc 0 0xd0cf11e0a1b11ae1
+mng 0 0x8a4d4e470d0a1a0a
+txt 0 \"BEGIN:\" 0x0d0a 'X'
+";
+        let result = parse_signatures(text, &PhotorecImportOptions::default());
+        assert!(result.skipped.is_empty(), "{:?}", result.skipped);
+        assert_eq!(result.file_types.len(), 3);
+        let doc = &result.file_types[0];
+        assert_eq!(doc.id, "photorec_doc");
+        assert_eq!(doc.validator, "footer");
+        assert_eq!(doc.header_patterns[0].hex, "D0CF11E0A1B11AE1");
+        let txt = &result.file_types[2];
+        assert_eq!(txt.header_patterns[0].hex, pattern_hex(b"BEGIN:\r\nX"));
+    }
+
+    #[test]
+    fn merges_patterns_by_extension() {
+        let text = "\
+abc 0 \"ABC1\"
+abc 0 \"ABC2\"
+abc 0 \"ABC1\"
+";
+        let result = parse_signatures(text, &PhotorecImportOptions::default());
+        assert_eq!(result.file_types.len(), 1);
+        let ids: Vec<&str> = result.file_types[0]
+            .header_patterns
+            .iter()
+            .map(|p| p.id.as_str())
+            .collect();
+        assert_eq!(ids, vec!["photorec_abc_1", "photorec_abc_2"]);
+    }
+
+    #[test]
+    fn reports_unrepresentable_entries() {
+        let text = "\
+iso 0x8001 \"CD001\"
+bad 0 0xabc
+one 0 'A'
+open 0 \"unterminated
+short
+";
+        let result = parse_signatures(text, &PhotorecImportOptions::default());
+        assert!(result.file_types.is_empty());
+        let lines: Vec<usize> = result.skipped.iter().map(|s| s.line).collect();
+        assert_eq!(lines, vec![1, 2, 3, 4, 5]);
+        assert!(result.skipped[0].reason.contains("offset 32769"));
+    }
+
+    #[test]
+    fn applies_size_options() {
+        let opts = PhotorecImportOptions {
+            id_prefix: "pr_".to_string(),
+            max_size: 4096,
+            min_size: 16,
+        };
+        let result = parse_signatures("dat 0 0x0102", &opts);
+        let ft = &result.file_types[0];
+        assert_eq!(ft.id, "pr_dat");
+        assert_eq!(ft.max_size, 4096);
+        assert_eq!(ft.min_size, 16);
+    }
+}
@@ -0,0 +1,338 @@
+//! Scalpel / Foremost configuration (`scalpel.conf`) importer.
+//!
+//! Each rule has the form `extension case_sensitive size header [footer] [REVERSE|NEXT]`.
+//! Rules map onto the `footer` validator: the size becomes `max_size` (or
+//! `min:max`), `REVERSE` maps to [`FooterMode::Last`], and `NEXT` maps to
+//! [`FooterMode::Exclusive`]. Wildcards have no scanner equivalent, so rules using
+//! them are skipped; case-insensitive rules are matched as written and reported as
+//! approximations.
+
+use std::collections::HashMap;
+
+use crate::config::{FileTypeConfig, FooterMode, PatternConfig, RetentionPolicy};
+
+use super::{
+    ImportIssue, ImportResult, MIN_SIGNATURE_LEN, file_type_id, pattern_hex, unescape_char,
+};
+
+/// Options controlling how Scalpel rules are converted.
+#[derive(Debug, Clone)]
+pub struct ScalpelImportOptions {
+    /// Prefix for generated file type ids (avoids clashing with built-in ids).
+    pub id_prefix: String,
+}
+
+impl Default for ScalpelImportOptions {
+    fn default() -> Self {
+        Self {
+            id_prefix: "scalpel_".to_string(),
+        }
+    }
+}
+
+/// Convert the contents of a Scalpel or Foremost configuration file.
+pub fn parse_config(text: &str, opts: &ScalpelImportOptions) -> ImportResult {
+    let mut result = ImportResult::default();
+    let mut wildcard = '?';
+    let mut id_counts: HashMap<String, usize> = HashMap::new();
+
+    for (idx, raw) in text.lines().enumerate() {
+        let line = strip_comment(raw).trim();
+        if line.is_empty() {
+            continue;
+        }
+        let issue = |reason: String| ImportIssue {
+            line: idx + 1,
+            text: line.to_string(),
+            reason,
+        };
+
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if tokens[0].eq_ignore_ascii_case("wildcard") {
+            match tokens.get(1).and_then(|t| t.chars().next()) {
+                Some(c) if tokens[1].chars().count() == 1 => wildcard = c,
+                _ => result
+                    .skipped
+                    .push(issue("wildcard directive needs one character".to_string())),
+            }
+            continue;
+        }
+        if tokens.len() < 4 {
+            result.skipped.push(issue(
+                "expected `extension case_sensitive size header [footer] [REVERSE|NEXT]`"
+                    .to_string(),
+            ));
+            continue;
+        }
+
+        let ext = tokens[0];
+        let case_sensitive = match tokens[1].to_ascii_lowercase().as_str() {
+            "y" | "yes" => true,
+            "n" | "no" => false,
+            other => {
+                result
+                    .skipped
+                    .push(issue(format!("invalid case_sensitive flag `{other}`")));
+                continue;
+            }
+        };
+        let (min_size, max_size) = match parse_size(tokens[2]) {
+            Some(sizes) => sizes,
+            None => {
+                result
+                    .skipped
+                    .push(issue(format!("invalid size `{}`", tokens[2])));
+                continue;
+            }
+        };
+
+        let mut rest = &tokens[4..];
+        let footer_mode = match rest.last().map(|t| t.to_ascii_uppercase()) {
+            Some(flag) if flag == "REVERSE" => {
+                rest = &rest[..rest.len() - 1];
+                FooterMode::Last
+            }
+            Some(flag) if flag == "NEXT" => {
+                rest = &rest[..rest.len() - 1];
+                FooterMode::Exclusive
+            }
+            _ => FooterMode::Inclusive,
+        };
+        if rest.len() > 1 {
+            result
+                .skipped
+                .push(issue("unexpected fields after footer".to_string()));
+            continue;
+        }
+
+        let header = match parse_pattern(tokens[3], wildcard) {
+            Ok(bytes) => bytes,
+            Err(reason) => {
+                result.skipped.push(issue(format!("header: {reason}")));
+                continue;
+            }
+        };
+        if header.len() < MIN_SIGNATURE_LEN {
+            result.skipped.push(issue(format!(
+                "header shorter than {MIN_SIGNATURE_LEN} bytes"
+            )));
+            continue;
+        }
+        let footer = match rest.first() {
+            Some(token) => match parse_pattern(token, wildcard) {
+                Ok(bytes) if !bytes.is_empty() => Some(bytes),
+                Ok(_) => None,
+                Err(reason) => {
+                    result.skipped.push(issue(format!("footer: {reason}")));
+                    continue;
+                }
+            },
+            None => None,
+        };
+        if footer.is_none() && footer_mode != FooterMode::Inclusive {
+            result
+                .skipped
+                .push(issue("REVERSE/NEXT requires a footer".to_string()));
+            continue;
+        }
+        if max_size == 0 {
+            result
+                .skipped
+                .push(issue("size must be greater than zero".to_string()));
+            continue;
+        }
+
+        let has_letters = header.iter().any(|b| b.is_ascii_alphabetic())
+            || footer
+                .as_ref()
+                .is_some_and(|f| f.iter().any(|b| b.is_ascii_alphabetic()));
+        if !case_sensitive && has_letters {
+            result.approximated.push(issue(
+                "case-insensitive rule is matched case-sensitively".to_string(),
+            ));
+        }
+
+        let ext = if ext.eq_ignore_ascii_case("NONE") {
+            "bin".to_string()
+        } else {
+            ext.to_ascii_lowercase()
+        };
+        let base_id = file_type_id(&opts.id_prefix, &ext);
+        let count = id_counts.entry(base_id.clone()).or_insert(0);
+        *count += 1;
+        let id = if *count == 1 {
+            base_id
+        } else {
+            format!("{base_id}_{count}")
+        };
+
+        result.file_types.push(FileTypeConfig {
+            header_patterns: vec![PatternConfig {
+                id: format!("{id}_header"),
+                hex: pattern_hex(&header),
+            }],
+            footer_patterns: footer
+                .map(|bytes| {
+                    vec![PatternConfig {
+                        id: format!("{id}_footer"),
+                        hex: pattern_hex(&bytes),
+                    }]
+                })
+                .unwrap_or_default(),
+            id,
+            extensions: vec![ext],
+            max_size,
+            min_size,
+            validator: "footer".to_string(),
+            require_eocd: false,
+            footer_mode,
+            retention: RetentionPolicy::KeepAll,
+            hit_cluster_window: None,
+            declared_size_factor: None,
+            post_processors: None,
+        });
+    }
+
+    result
+}
+
+fn strip_comment(line: &str) -> &str {
+    // `#` only starts a comment at the beginning of a token; escaped `\#` is data.
+    let bytes = line.as_bytes();
+    for (i, b) in bytes.iter().enumerate() {
+        if *b == b'#' && (i == 0 || bytes[i - 1].is_ascii_whitespace()) {
+            return &line[..i];
+        }
+    }
+    line
+}
+
+/// Parse `max` or `min:max`.
+fn parse_size(value: &str) -> Option<(u64, u64)> {
+    match value.split_once(':') {
+        Some((min, max)) => {
+            let min = min.parse().ok()?;
+            let max = max.parse().ok()?;
+            if min > max {
+                return None;
+            }
+            Some((min, max))
+        }
+        None => Some((0, value.parse().ok()?)),
+    }
+}
+
+fn parse_pattern(token: &str, wildcard: char) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    let mut chars = token.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            let escaped = chars
+                .next()
+                .ok_or_else(|| "unterminated escape sequence".to_string())?;
+            match escaped {
+                'x' | 'X' => {
+                    let hi = chars.next().and_then(|c| c.to_digit(16));
+                    let lo = chars.next().and_then(|c| c.to_digit(16));
+                    match (hi, lo) {
+                        (Some(hi), Some(lo)) => out.push((hi * 16 + lo) as u8),
+                        _ => return Err("invalid \\x escape".to_string()),
+                    }
+                }
+                's' => out.push(b' '),
+                other => {
+                    if other == wildcard {
+                        out.push(other as u8);
+                    } else {
+                        let byte = unescape_char(other)
+                            .ok_or_else(|| format!("unsupported escape `\\{other}`"))?;
+                        out.push(byte);
+                    }
+                }
+            }
+        } else if c == wildcard {
+            return Err(format!("wildcard `{wildcard}` is not supported"));
+        } else if c.is_ascii() {
+            out.push(c as u8);
+        } else {
+            let mut buf = [0u8; 4];
+            out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_header_footer_and_size() {
+        let text = "\
+# image rules
+gif  y 5000000  \\x47\\x49\\x46\\x38\\x37\\x61  \\x00\\x3b
+jpg  y 200000000 \\xff\\xd8\\xff\\xe0\\x00\\x10 \\xff\\xd9
+";
+        let result = parse_config(text, &ScalpelImportOptions::default());
+        assert!(result.skipped.is_empty(), "{:?}", result.skipped);
+        assert_eq!(result.file_types.len(), 2);
+        let gif = &result.file_types[0];
+        assert_eq!(gif.id, "scalpel_gif");
+        assert_eq!(gif.max_size, 5_000_000);
+        assert_eq!(gif.header_patterns[0].hex, "474946383761");
+        assert_eq!(gif.footer_patterns[0].hex, "003B");
+        assert_eq!(gif.footer_mode, FooterMode::Inclusive);
+    }
+
+    #[test]
+    fn maps_reverse_next_and_min_max() {
+        let text = "\
+pdf y 100:5000000 %PDF %EOF\\x0d REVERSE
+htm n 50000 <html <html NEXT
+";
+        let result = parse_config(text, &ScalpelImportOptions::default());
+        assert_eq!(result.file_types[0].footer_mode, FooterMode::Last);
+        assert_eq!(result.file_types[0].min_size, 100);
+        assert_eq!(result.file_types[1].footer_mode, FooterMode::Exclusive);
+        assert_eq!(result.approximated.len(), 1);
+        assert_eq!(result.approximated[0].line, 2);
+    }
+
+    #[test]
+    fn deduplicates_ids_and_handles_none_extension() {
+        let text = "\
+doc y 1000 \\xd0\\xcf\\x11\\xe0
+doc y 1000 \\xdb\\xa5\\x2d\\x00
+NONE y 1000 \\x01\\x02\\x03
+";
+        let result = parse_config(text, &ScalpelImportOptions::default());
+        let ids: Vec<&str> = result.file_types.iter().map(|f| f.id.as_str()).collect();
+        assert_eq!(ids, vec!["scalpel_doc", "scalpel_doc_2", "scalpel_bin"]);
+    }
+
+    #[test]
+    fn reports_unrepresentable_rules() {
+        let text = "\
+mov y 10000000 ????moov
+wildcard *
+mpg y 1000 \\x00\\x00\\x01\\xba*
+bad x 1000 abc
+txt y 1000 abc NEXT
+neg y abc \\x01\\x02
+";
+        let result = parse_config(text, &ScalpelImportOptions::default());
+        assert!(result.file_types.is_empty());
+        let lines: Vec<usize> = result.skipped.iter().map(|s| s.line).collect();
+        assert_eq!(lines, vec![1, 3, 4, 5, 6]);
+        assert!(result.skipped[0].reason.contains("wildcard"));
+    }
+
+    #[test]
+    fn escaped_wildcard_is_literal() {
+        let result = parse_config("qm y 100 ab\\?", &ScalpelImportOptions::default());
+        assert_eq!(
+            result.file_types[0].header_patterns[0].hex,
+            pattern_hex(b"ab?")
+        );
+    }
+}
@@ -0,0 +1,215 @@
+//! Files a run takes its setup from.
+//!
+//! The YAML config, a `--scalpel-conf` signature file, and the hash lists of
+//! quarantine rules are opened read-only and their SHA-256 recorded in the
+//! pass log (`summaries/passes.json`), the `input_files` metadata stream,
+//! and the checkpoint. A resume refuses to start when any of them differs
+//! from the checkpoint, since carving the rest of the evidence with other
+//! signatures or lists would leave one run with two sets of rules.
+
+use std::fs::OpenOptions;
+use std::io::{self, Read};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::config::Config;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InputKind {
+    /// YAML config given with `--config`.
+    Config,
+    /// Signature file imported with `--scalpel-conf`.
+    Signatures,
+    /// `hash_list` of a quarantine rule.
+    HashList,
+}
+
+impl InputKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            InputKind::Config => "config",
+            InputKind::Signatures => "signatures",
+            InputKind::HashList => "hash_list",
+        }
+    }
+}
+
+/// One input file and the SHA-256 of its contents when the run started.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InputFile {
+    pub kind: InputKind,
+    pub path: String,
+    pub sha256: String,
+}
+
+/// Row of the `input_files` metadata stream.
+#[derive(Debug, Clone, Serialize)]
+pub struct InputFileRecord {
+    pub run_id: String,
+    pub kind: InputKind,
+    pub path: String,
+    pub sha256: String,
+}
+
+impl InputFile {
+    /// An input whose contents were already read.
+    pub fn from_bytes(kind: InputKind, path: &Path, bytes: &[u8]) -> Self {
+        Self {
+            kind,
+            path: path.to_string_lossy().to_string(),
+            sha256: hex::encode(Sha256::digest(bytes)),
+        }
+    }
+
+    /// Hash `path`, opened read-only.
+    pub fn hash(kind: InputKind, path: &Path) -> io::Result<Self> {
+        let mut file = OpenOptions::new().read(true).open(path)?;
+        let mut sha256 = Sha256::new();
+        let mut buf = vec![0u8; 64 * 1024];
+        loop {
+            let n = file.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            sha256.update(&buf[..n]);
+        }
+        Ok(Self {
+            kind,
+            path: path.to_string_lossy().to_string(),
+            sha256: hex::encode(sha256.finalize()),
+        })
+    }
+
+    pub fn record(&self, run_id: &str) -> InputFileRecord {
+        InputFileRecord {
+            run_id: run_id.to_string(),
+            kind: self.kind,
+            path: self.path.clone(),
+            sha256: self.sha256.clone(),
+        }
+    }
+}
+
+/// Hash the quarantine hash lists of `cfg`; a list named by several rules is
+/// listed once.
+pub fn hash_lists(cfg: &Config) -> io::Result<Vec<InputFile>> {
+    let mut files: Vec<InputFile> = Vec::new();
+    for path in cfg
+        .quarantine_rules
+        .iter()
+        .filter_map(|r| r.hash_list.as_deref())
+    {
+        if files.iter().any(|f| Path::new(&f.path) == path) {
+            continue;
+        }
+        let file = InputFile::hash(InputKind::HashList, path).map_err(|err| {
+            io::Error::new(err.kind(), format!("hash list {}: {err}", path.display()))
+        })?;
+        files.push(file);
+    }
+    Ok(files)
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum InputError {
+    #[error("{kind} {path} changed since the checkpoint (sha256 {recorded}, now {current})")]
+    Changed {
+        kind: &'static str,
+        path: String,
+        recorded: String,
+        current: String,
+    },
+    #[error("{kind} {path} was used before the checkpoint and is not used now")]
+    Dropped { kind: &'static str, path: String },
+    #[error("{kind} {path} was not used before the checkpoint")]
+    Added { kind: &'static str, path: String },
+}
+
+/// Check that a resumed run reads the same inputs, with the same contents,
+/// as the run that wrote the checkpoint.
+pub fn check_unchanged(recorded: &[InputFile], current: &[InputFile]) -> Result<(), InputError> {
+    let find = |files: &[InputFile], file: &InputFile| {
+        files
+            .iter()
+            .position(|f| f.kind == file.kind && f.path == file.path)
+    };
+    for file in recorded {
+        let Some(at) = find(current, file) else {
+            return Err(InputError::Dropped {
+                kind: file.kind.as_str(),
+                path: file.path.clone(),
+            });
+        };
+        if current[at].sha256 != file.sha256 {
+            return Err(InputError::Changed {
+                kind: file.kind.as_str(),
+                path: file.path.clone(),
+                recorded: file.sha256.clone(),
+                current: current[at].sha256.clone(),
+            });
+        }
+    }
+    match current.iter().find(|file| find(recorded, file).is_none()) {
+        Some(file) => Err(InputError::Added {
+            kind: file.kind.as_str(),
+            path: file.path.clone(),
+        }),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn refuses_inputs_that_changed_since_the_checkpoint() {
+        let dir = tempfile::tempdir().unwrap();
+        let list = dir.path().join("known.txt");
+        fs::write(&list, "d41d8cd98f00b204e9800998ecf8427e\n").unwrap();
+        let mut cfg = crate::config::load_config(None).unwrap().config;
+        cfg.quarantine_rules = vec![crate::config::QuarantineRule {
+            name: "known".to_string(),
+            hash_list: Some(list.clone()),
+            ..Default::default()
+        }];
+        let config = InputFile::from_bytes(InputKind::Config, Path::new("carve.yml"), b"a: 1");
+        let mut recorded = vec![config.clone()];
+        recorded.extend(hash_lists(&cfg).unwrap());
+        assert_eq!(
+            recorded[1].sha256,
+            hex::encode(Sha256::digest(b"d41d8cd98f00b204e9800998ecf8427e\n"))
+        );
+        assert_eq!(check_unchanged(&recorded, &recorded), Ok(()));
+
+        fs::write(&list, "0cc175b9c0f1b6a831c399e269772661\n").unwrap();
+        let mut current = vec![config.clone()];
+        current.extend(hash_lists(&cfg).unwrap());
+        assert!(matches!(
+            check_unchanged(&recorded, &current),
+            Err(InputError::Changed {
+                kind: "hash_list",
+                ..
+            })
+        ));
+        assert!(matches!(
+            check_unchanged(&recorded, &[config.clone()]),
+            Err(InputError::Dropped {
+                kind: "hash_list",
+                ..
+            })
+        ));
+        assert!(matches!(
+            check_unchanged(&[config], &recorded),
+            Err(InputError::Added {
+                kind: "hash_list",
+                ..
+            })
+        ));
+    }
+}
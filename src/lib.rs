@@ -4,20 +4,112 @@
 //!
 //! This crate provides tools for extracting files and forensic artefacts from
 //! disk images and raw evidence sources.
+//!
+//! ## Stable API
+//!
+//! The extension traits below, and the types in their signatures, are the
+//! crate's public API and follow the semver policy in `docs/api.md`. They are
+//! re-exported at the crate root:
+//!
+//! - [`EvidenceSource`]: random-access byte space to scan (raw files, devices, E01).
+//! - [`SignatureScanner`]: finds header signatures in a chunk ([`Hit`]).
+//! - [`StringScanner`]: finds printable string runs in a chunk ([`StringSpan`]).
+//! - [`CarveHandler`]: validates and extracts one file type ([`CarvedFile`]).
+//! - [`MetadataSink`]: records carved files, artefacts, and the run summary.
+//!
+//! The remaining public modules (`config`, `pipeline`, `util`, `cli`, parsers,
+//! post-processors, and the built-in handlers) serve the `swiftbeaver` binary.
+//! They are usable but may change in any minor release.
+//!
+//! ## Example
+//!
+//! A custom evidence source and signature scanner:
+//!
+//! ```rust
+//! use swiftbeaver::{EvidenceError, EvidenceSource, Hit, ScanChunk, SignatureScanner};
+//!
+//! struct MemorySource(Vec<u8>);
+//!
+//! impl EvidenceSource for MemorySource {
+//!     fn len(&self) -> u64 {
+//!         self.0.len() as u64
+//!     }
+//!
+//!     fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize, EvidenceError> {
+//!         let data = self.0.get(offset as usize..).unwrap_or_default();
+//!         let n = data.len().min(buf.len());
+//!         buf[..n].copy_from_slice(&data[..n]);
+//!         Ok(n)
+//!     }
+//! }
+//!
+//! struct MagicScanner;
+//!
+//! impl SignatureScanner for MagicScanner {
+//!     fn scan_chunk(&self, chunk: &ScanChunk, data: &[u8]) -> Vec<Hit> {
+//!         data.windows(4)
+//!             .enumerate()
+//!             .filter(|(_, window)| *window == b"MAGI")
+//!             .map(|(i, _)| Hit {
+//!                 chunk_id: chunk.id,
+//!                 local_offset: i as u64,
+//!                 pattern_id: "magic".to_string(),
+//!                 file_type_id: "magic".to_string(),
+//!             })
+//!             .collect()
+//!     }
+//! }
+//!
+//! let source = MemorySource(b"....MAGIC....".to_vec());
+//! let mut data = vec![0u8; source.len() as usize];
+//! source.read_at(0, &mut data).unwrap();
+//! let chunk = ScanChunk {
+//!     id: 0,
+//!     start: 0,
+//!     length: source.len(),
+//!     valid_length: source.len(),
+//! };
+//! let hits = MagicScanner.scan_chunk(&chunk, &data);
+//! assert_eq!(hits[0].local_offset, 4);
+//! ```
 
 pub mod carve;
+pub mod catalog;
 pub mod checkpoint;
 pub mod chunk;
 pub mod cli;
 pub mod config;
 pub mod constants;
+pub mod encryption;
 pub mod entropy;
 pub mod error;
 pub mod evidence;
+pub mod export;
+#[cfg(any(test, feature = "fault-injection"))]
+pub mod fault;
+pub mod import;
+pub mod inputs;
 pub mod logging;
 pub mod metadata;
+pub mod notify;
+pub mod outcome;
 pub mod parsers;
+pub mod passes;
 pub mod pipeline;
+pub mod postprocess;
+pub mod report;
+pub mod runlock;
 pub mod scanner;
 pub mod strings;
+pub mod tempspace;
 pub mod util;
+
+pub use carve::{
+    CancellationToken, CarveError, CarveHandler, CarveRegistry, CarvedFile, DeclaredSize,
+    ExtractionContext, Rejection, note_declared_size, note_rejection, with_cancellation,
+};
+pub use chunk::ScanChunk;
+pub use evidence::{EvidenceError, EvidenceSource};
+pub use metadata::{MetadataError, MetadataSink, RunSummary};
+pub use scanner::{Hit, NormalizedHit, ScanRoute, SignatureScanner};
+pub use strings::{StringScanner, StringSpan};
@@ -1,12 +1,15 @@
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
 
-use anyhow::{Context, Result, bail};
+use anyhow::{Context, Result, anyhow};
 use tracing::{info, warn};
 
 use swiftbeaver::{
-    checkpoint, cli, config, constants::MIB, evidence, logging, metadata, pipeline, scanner,
+    catalog, checkpoint, cli, config, constants::MIB, encryption, evidence, export, import, inputs,
+    logging, metadata, notify, outcome, outcome::RunStatus, passes, pipeline, runlock, scanner,
     strings, util,
 };
 
@@ -14,12 +17,17 @@ struct LoggingProgressReporter;
 
 impl pipeline::ProgressReporter for LoggingProgressReporter {
     fn on_progress(&self, snapshot: &pipeline::ProgressSnapshot) {
-        let eta_str = snapshot
-            .eta_seconds
-            .map(|s| format!("{:.0}s", s))
-            .unwrap_or_else(|| "N/A".to_string());
+        let eta_str = match (
+            snapshot.eta_seconds,
+            snapshot.eta_low_seconds,
+            snapshot.eta_high_seconds,
+        ) {
+            (Some(eta), Some(low), Some(high)) => format!("{eta}s[{low}-{high}s]"),
+            (Some(eta), _, _) => format!("{eta}s"),
+            _ => "N/A".to_string(),
+        };
         info!(
-            "progress {:.1}% scanned={}/{} hits={} files={} rate={:.2}MiB/s eta={} errs=[carve:{} meta:{} sql:{}]",
+            "progress {:.1}% scanned={}/{} hits={} files={} rate={:.2}MiB/s eta={} errs=[read:{} carve:{} meta:{} sql:{} panic:{}]",
             snapshot.completion_pct,
             snapshot.bytes_scanned,
             snapshot.total_bytes,
@@ -27,37 +35,156 @@ impl pipeline::ProgressReporter for LoggingProgressReporter {
             snapshot.files_carved,
             snapshot.throughput_mib,
             eta_str,
+            snapshot.read_errors,
             snapshot.carve_errors,
             snapshot.metadata_errors,
-            snapshot.sqlite_errors
+            snapshot.sqlite_errors,
+            snapshot.worker_panics
         );
     }
 }
 
-fn main() -> Result<()> {
+/// Where a run writes, known once the config is loaded.
+#[derive(Default)]
+struct RunTarget {
+    run_id: Option<String>,
+    run_dir: Option<PathBuf>,
+}
+
+fn main() -> ExitCode {
     let cli_opts = cli::parse();
     logging::init_logging_with_format(cli_opts.log_format);
-    let loaded = config::load_config(cli_opts.config_path.as_deref())?;
+    if let Some(command) = &cli_opts.command {
+        return match run_command(command) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(err) => {
+                eprintln!("Error: {err:?}");
+                ExitCode::from(RunStatus::of_error(&err).exit_code())
+            }
+        };
+    }
+
+    let mut target = RunTarget::default();
+    let result = run(&cli_opts, &mut target);
+    let run_id = target.run_id.as_deref();
+    let run_dir = target.run_dir.as_deref().filter(|dir| dir.is_dir());
+    let outcome = match &result {
+        Ok(stats) => outcome::RunResult::completed(run_id, run_dir, stats),
+        Err(err) => {
+            eprintln!("Error: {err:?}");
+            outcome::RunResult::failed(run_id, run_dir, err)
+        }
+    };
+    let mut paths: Vec<PathBuf> = run_dir
+        .filter(|_| !cli_opts.dry_run)
+        .map(|dir| dir.join(outcome::RESULT_FILE))
+        .into_iter()
+        .collect();
+    paths.extend(cli_opts.result_path.clone());
+    for path in paths {
+        match outcome::write_result(&path, &outcome) {
+            Ok(path) => info!("result written to {}", path.display()),
+            Err(err) => warn!("failed to write result {}: {err}", path.display()),
+        }
+    }
+    if outcome.status != RunStatus::Success {
+        warn!(
+            "run ended with status {} (exit code {})",
+            outcome.status, outcome.exit_code
+        );
+    }
+    ExitCode::from(outcome.exit_code)
+}
+
+fn run(cli_opts: &cli::CliOptions, target: &mut RunTarget) -> Result<pipeline::PipelineStats> {
+    let input_path = cli_opts
+        .input
+        .clone()
+        .context("--input is required")
+        .context(RunStatus::ConfigError)?;
+    let loaded =
+        config::load_config(cli_opts.config_path.as_deref()).context(RunStatus::ConfigError)?;
     let mut cfg = loaded.config;
+    // The config hash is the SHA-256 of the file's bytes as parsed.
+    let mut input_files: Vec<inputs::InputFile> = cli_opts
+        .config_path
+        .iter()
+        .map(|path| inputs::InputFile {
+            kind: inputs::InputKind::Config,
+            path: path.to_string_lossy().to_string(),
+            sha256: loaded.config_hash.clone(),
+        })
+        .collect();
+
+    if let Some(profile) = cli_opts.profile {
+        cfg.apply_profile(profile).context(RunStatus::ConfigError)?;
+        info!("scan profile: {profile:?}");
+    }
 
     // Apply CLI overrides to config
     cfg.merge_cli(&cli_opts);
 
+    if let Some(path) = &cli_opts.scalpel_conf {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("read scalpel config {}", path.display()))
+            .context(RunStatus::ConfigError)?;
+        input_files.push(inputs::InputFile::from_bytes(
+            inputs::InputKind::Signatures,
+            path,
+            text.as_bytes(),
+        ));
+        let result =
+            import::scalpel::parse_config(&text, &import::scalpel::ScalpelImportOptions::default());
+        log_import_issues(&result);
+        info!(
+            "using {} file types from scalpel config {}",
+            result.file_types.len(),
+            path.display()
+        );
+        cfg.file_types = result.file_types;
+    }
+
     // Apply file type filters (support both --types and --enable-types)
-    let types_filter = cli::get_types_filter(&cli_opts);
-    let unknown_types = util::filter_file_types(
-        &mut cfg,
-        types_filter.map(|v| v.as_slice()),
-        cli_opts.disable_zip,
-    );
-    for unknown in unknown_types {
-        warn!("unknown file type in --types/--enable-types: {unknown}");
+    let types_filter = cli::get_types_filter(cli_opts)
+        .map(|terms| util::resolve_type_expression(&cfg, terms))
+        .transpose()
+        .map_err(|err| err.context(RunStatus::ConfigError))?;
+    let mut filtered = cfg.clone();
+    // Unknown names were rejected while resolving the expression.
+    let _ = util::filter_file_types(&mut filtered, types_filter.as_deref(), cli_opts.disable_zip);
+    let selected: Vec<String> = filtered.file_types.iter().map(|ft| ft.id.clone()).collect();
+
+    // Only the types this run carves are checked; references elsewhere in the
+    // config are still resolved against the full list.
+    let chunk_size = cli_opts.chunk_size_mib.saturating_mul(MIB);
+    let issues = config::validate_selected_types(&cfg, &selected, Some(chunk_size));
+    for issue in issues.iter().filter(|i| !i.is_error()) {
+        warn!("config {issue}");
     }
+    let errors: Vec<String> = issues
+        .iter()
+        .filter(|i| i.is_error())
+        .map(|i| i.to_string())
+        .collect();
+    if !errors.is_empty() {
+        return Err(anyhow!(
+            "invalid configuration ({} error(s); run `config check` for line numbers):\n  {}",
+            errors.len(),
+            errors.join("\n  ")
+        )
+        .context(RunStatus::ConfigError));
+    }
+    cfg = filtered;
+
     if cli_opts.disable_zip {
         info!("zip carving disabled by CLI");
     }
-    if types_filter.is_some() && cfg.file_types.is_empty() {
-        warn!("no file types enabled after applying type filter");
+    if types_filter.is_some() {
+        if cfg.file_types.is_empty() {
+            warn!("no file types enabled after applying type filter");
+        } else {
+            info!("file types: {}", util::describe_file_types(&cfg));
+        }
     }
     if cli_opts.dry_run {
         info!("dry-run mode enabled: no files will be written");
@@ -65,70 +192,186 @@ fn main() -> Result<()> {
     if cli_opts.validate_carved {
         info!("post-carving validation enabled");
     }
-    if cfg.enable_string_scan
-        && !cfg.enable_url_scan
-        && !cfg.enable_email_scan
-        && !cfg.enable_phone_scan
-    {
-        warn!("string scanning enabled but all artefact types are disabled");
-    }
+
+    input_files.extend(inputs::hash_lists(&cfg).context(RunStatus::ConfigError)?);
 
     util::apply_resource_limits(cfg.max_memory_mib, cfg.max_open_files)?;
 
-    // In dry-run mode, skip output directory creation
-    if !cli_opts.dry_run {
-        util::ensure_output_dir(&cli_opts.output)?;
-    }
-    let run_output_dir = cli_opts.output.join(&cfg.run_id);
-    if !cli_opts.dry_run {
-        std::fs::create_dir_all(&run_output_dir)?;
+    let cipher = encryption::OutputCipher::from_config(&cfg).context(RunStatus::ConfigError)?;
+    if cipher.is_some() && !cli_opts.dry_run {
+        info!(
+            "encrypting run outputs to {}",
+            cfg.encryption_recipient.as_deref().unwrap_or("-")
+        );
     }
 
+    // A later pass keeps the run id and directory of the first one.
+    let mut pass_log = match &cli_opts.append_to {
+        Some(dir) => {
+            if cipher.is_some() || encryption::holds_sealed_metadata(dir) {
+                return Err(anyhow!(
+                    "--append-to cannot add a pass to an encrypted run; encrypted metadata streams cannot be appended to"
+                )
+                .context(RunStatus::ConfigError));
+            }
+            let log = passes::PassLog::load(dir).context(RunStatus::ConfigError)?;
+            cfg.run_id = log.run_id.clone();
+            info!(
+                "appending pass {} to run {}",
+                log.next_pass_id(),
+                dir.display()
+            );
+            Some(log)
+        }
+        None => None,
+    };
+    let run_output_dir = match &cli_opts.append_to {
+        Some(dir) => dir.clone(),
+        None => {
+            // In dry-run mode, skip output directory creation
+            if !cli_opts.dry_run {
+                util::ensure_output_dir(&cli_opts.output)?;
+            }
+            let dir = cli_opts.output.join(&cfg.run_id);
+            if !cli_opts.dry_run {
+                std::fs::create_dir_all(&dir)?;
+            }
+            dir
+        }
+    };
+    target.run_id = Some(cfg.run_id.clone());
+    target.run_dir = Some(run_output_dir.clone());
+    // Held until main returns; a second run on the same directory fails here.
+    let run_lock = if cli_opts.dry_run {
+        None
+    } else {
+        Some(runlock::RunLock::acquire(
+            &run_output_dir,
+            &cfg.run_id,
+            cli_opts.force_unlock,
+        )?)
+    };
+
     let tool_version = env!("CARGO_PKG_VERSION");
-    let evidence_path = cli_opts.input.clone();
+    let evidence_path = input_path.clone();
 
     info!(
         "starting run_id={} input={} output={} workers={} chunk_mib={}",
         cfg.run_id,
-        cli_opts.input.display(),
+        input_path.display(),
         run_output_dir.display(),
         cli_opts.workers,
         cli_opts.chunk_size_mib
     );
 
-    let evidence_source = evidence::open_source(&cli_opts)?;
+    if input_path == Path::new("-") {
+        // With room for a single chunk the scan would wait for carving to
+        // catch up before every chunk.
+        let needed = chunk_size
+            .saturating_add(cfg.overlap_bytes)
+            .saturating_mul(2);
+        if cli_opts.stream_window_mib.saturating_mul(MIB) < needed {
+            return Err(anyhow!(
+                "--stream-window-mib {} is smaller than twice the chunk size plus overlap ({} MiB)",
+                cli_opts.stream_window_mib,
+                needed.div_ceil(MIB)
+            )
+            .context(RunStatus::ConfigError));
+        }
+    }
+
+    let (evidence_source, mut acquisition) =
+        evidence::open_source_with_info(cli_opts).context(RunStatus::EvidenceReadFailure)?;
     let evidence_source: Arc<dyn evidence::EvidenceSource> = Arc::from(evidence_source);
+    if let Some(info) = &acquisition {
+        info!(
+            "EWF {} set with {} segment(s) case={} evidence={} examiner={} stored_md5={}",
+            info.format,
+            info.segments.len(),
+            info.case_number.as_deref().unwrap_or("-"),
+            info.evidence_number.as_deref().unwrap_or("-"),
+            info.examiner.as_deref().unwrap_or("-"),
+            info.stored_md5.as_deref().unwrap_or("-")
+        );
+    }
 
-    if cli_opts.evidence_sha256.is_some() && cli_opts.compute_evidence_sha256 {
-        bail!("set either --evidence-sha256 or --compute-evidence-sha256, not both");
+    if cli_opts.verify_evidence_hash {
+        let Some(info) = acquisition.as_mut() else {
+            return Err(anyhow!(
+                "--verify-evidence-hash needs an EWF input (.E01, .Ex01, .L01, .Lx01)"
+            )
+            .context(RunStatus::ConfigError));
+        };
+        let Some(stored) = info.stored_md5.clone() else {
+            return Err(anyhow!("EWF image has no stored MD5 to verify against")
+                .context(RunStatus::EvidenceReadFailure));
+        };
+        info!("verifying evidence md5 against stored hash (full pass)");
+        let computed = evidence::compute_md5(evidence_source.as_ref(), 8 * MIB as usize)
+            .context(RunStatus::EvidenceReadFailure)?;
+        let verified = computed.eq_ignore_ascii_case(&stored);
+        info.computed_md5 = Some(computed.clone());
+        info.md5_verified = Some(verified);
+        if !verified {
+            return Err(
+                anyhow!("evidence md5 {computed} does not match stored md5 {stored}")
+                    .context(RunStatus::EvidenceReadFailure),
+            );
+        }
+        info!("evidence md5 matches stored hash");
     }
 
+    // A SHA-256 computed while scanning (`--compute-evidence-sha256`) is
+    // known only once the scan is done; it goes to the pass log and result.
     let evidence_sha256 = if let Some(hash) = cli_opts.evidence_sha256.as_ref() {
         hash.trim().to_string()
-    } else if cli_opts.compute_evidence_sha256 {
-        info!("computing evidence sha256 (full pass)");
-        let hash = evidence::compute_sha256(evidence_source.as_ref(), 8 * MIB as usize)?;
-        info!("evidence sha256={hash}");
-        hash
     } else {
-        String::new()
+        pass_log
+            .as_ref()
+            .and_then(|log| log.evidence_sha256())
+            .unwrap_or_default()
+            .to_string()
     };
 
     let meta_backend = util::backend_from_cli(cli_opts.metadata_backend);
+    let pass_id = pass_log.as_ref().map_or(1, passes::PassLog::next_pass_id);
+    if let Some(log) = &pass_log {
+        log.check_append(&evidence_path, &evidence_sha256, meta_backend.name())
+            .context(RunStatus::ConfigError)?;
+        passes::archive_summaries(&run_output_dir, pass_id - 1)
+            .context("archive summaries of the previous pass")?;
+    } else if !cli_opts.dry_run {
+        pass_log = Some(passes::PassLog::new(&cfg.run_id));
+    }
+    let identity = metadata::SinkIdentity {
+        run_id: &cfg.run_id,
+        tool_version,
+        config_hash: &loaded.config_hash,
+        evidence_path: &evidence_path,
+        evidence_sha256: &evidence_sha256,
+        pass_id,
+    };
+    if let Some(log) = pass_log.as_mut() {
+        log.begin(passes::PassRecord::started(
+            &identity,
+            meta_backend.name(),
+            cfg.file_types.iter().map(|ft| ft.id.clone()).collect(),
+            cfg.enable_string_scan,
+            input_files.clone(),
+        ));
+        log.save(&run_output_dir)?;
+    }
     let meta_sink: Box<dyn metadata::MetadataSink> = if cli_opts.dry_run {
         metadata::build_dry_run_sink()
     } else {
-        metadata::build_sink(
-            meta_backend,
-            &cfg,
-            &cfg.run_id,
-            tool_version,
-            &loaded.config_hash,
-            &evidence_path,
-            &evidence_sha256,
-            &run_output_dir,
-        )?
+        metadata::build_pass_sink(meta_backend, &cfg, &identity, &run_output_dir)
+            .context(RunStatus::SinkFailure)?
     };
+    for file in &input_files {
+        meta_sink
+            .record_input_file(&file.record(&cfg.run_id))
+            .context(RunStatus::SinkFailure)?;
+    }
 
     let sig_scanner = scanner::build_signature_scanner(&cfg, cli_opts.gpu)?;
     let sig_scanner = Arc::from(sig_scanner);
@@ -142,16 +385,18 @@ fn main() -> Result<()> {
         None
     };
 
-    let carve_registry = Arc::new(util::build_carve_registry(&cfg, cli_opts.dry_run)?);
+    let carve_registry = Arc::new(
+        util::build_carve_registry(&cfg, cli_opts.dry_run).context(RunStatus::ConfigError)?,
+    );
 
-    let chunk_size = cli_opts.chunk_size_mib.saturating_mul(MIB);
-    let overlap = cli_opts
-        .overlap_kib
-        .map(|kib| kib.saturating_mul(1024))
-        .unwrap_or(cfg.overlap_bytes);
+    let overlap = cfg.overlap_bytes;
 
     let resume_state = match cli_opts.resume_from.as_ref() {
-        Some(path) => Some(checkpoint::load_checkpoint(path).context("load checkpoint")?),
+        Some(path) => Some(
+            checkpoint::load_checkpoint(path)
+                .context("load checkpoint")
+                .context(RunStatus::ConfigError)?,
+        ),
         None => None,
     };
     let checkpoint_path = cli_opts
@@ -161,6 +406,7 @@ fn main() -> Result<()> {
     let checkpoint_cfg = checkpoint_path.map(|path| pipeline::CheckpointConfig {
         path,
         resume: resume_state,
+        input_files,
     });
 
     let cancel_flag = Arc::new(AtomicBool::new(false));
@@ -171,6 +417,9 @@ fn main() -> Result<()> {
         })
         .context("failed to install Ctrl+C handler")?;
     }
+    if let Some(lock) = &run_lock {
+        lock.cancel_on_loss(Arc::clone(&cancel_flag));
+    }
 
     let progress = if cli_opts.progress_interval_secs == 0 {
         None
@@ -181,7 +430,7 @@ fn main() -> Result<()> {
         })
     };
 
-    pipeline::run_pipeline_with_cancel(
+    let result = pipeline::run_pipeline_with_cancel(
         &cfg,
         evidence_source,
         sig_scanner,
@@ -197,8 +446,230 @@ fn main() -> Result<()> {
         cancel_flag,
         progress,
         checkpoint_cfg,
-    )?;
+    );
+    let note = match &result {
+        Ok(stats) => notify::Notification::completed(&cfg.run_id, &run_output_dir, stats),
+        Err(err) => notify::Notification::failed(&cfg.run_id, &run_output_dir, format!("{err:#}")),
+    };
+    notify::send(&cfg.notifications, &note);
+    if let Some(log) = pass_log.as_mut() {
+        let outcome = match &result {
+            Ok(stats) => Ok(passes::PassCounts::from_stats(stats)),
+            Err(err) => Err(format!("{err:#}")),
+        };
+        let hashed = result
+            .as_ref()
+            .ok()
+            .and_then(|stats| stats.evidence_hashes.as_ref()?.sha256.as_deref());
+        if let Some(sha256) = hashed
+            && let Some(given) = log.record_evidence_sha256(pass_id, sha256)
+        {
+            warn!("evidence sha256 {sha256} hashed while scanning does not match {given}");
+        }
+        log.finish(pass_id, outcome);
+        match log.save(&run_output_dir) {
+            Ok(path) => info!("pass {pass_id} recorded in {}", path.display()),
+            Err(err) => warn!("failed to write pass log: {err}"),
+        }
+    }
+    let stats = result?;
+
+    if let (Some(info), Some(hashes)) = (acquisition.as_mut(), &stats.evidence_hashes) {
+        info.verify_hashes(hashes.md5.as_deref(), hashes.sha1.as_deref());
+        for (name, verified) in [("md5", info.md5_verified), ("sha1", info.sha1_verified)] {
+            match verified {
+                Some(true) => info!("evidence {name} matches stored hash"),
+                Some(false) => warn!("evidence {name} does not match the stored hash"),
+                None => {}
+            }
+        }
+    }
+
+    if let Some(info) = &acquisition
+        && run_output_dir.is_dir()
+    {
+        match evidence::write_acquisition_info(&run_output_dir, info, cipher.as_ref()) {
+            Ok(path) => info!("acquisition metadata written to {}", path.display()),
+            Err(err) => warn!("failed to write acquisition metadata: {err}"),
+        }
+    }
 
     info!("SwiftBeaver run finished");
+    Ok(stats)
+}
+
+fn run_command(command: &cli::Command) -> Result<()> {
+    match command {
+        cli::Command::ImportSignatures(args) => import_signatures(args),
+        cli::Command::Config(cli::ConfigCommand::Check(args)) => check_config(args),
+        cli::Command::Decrypt(args) => decrypt_outputs(args),
+        cli::Command::Export(args) => export_run(args),
+        cli::Command::ListTypes(args) => list_types(args),
+        cli::Command::Salvage(args) => salvage_run(args),
+    }
+}
+
+fn salvage_run(args: &cli::SalvageArgs) -> Result<()> {
+    let output = args
+        .output
+        .clone()
+        .unwrap_or_else(|| args.run_dir.join(metadata::salvage::SALVAGE_DIR));
+    let report = metadata::salvage::salvage_run(&args.run_dir, &output)
+        .with_context(|| format!("salvage {}", args.run_dir.display()))?;
+    for file in &report.files {
+        let note = file.note.as_deref().unwrap_or("");
+        info!(
+            "{}: {:?} recovered={} lost={} discarded_bytes={} {note}",
+            file.path, file.status, file.records_recovered, file.records_lost, file.bytes_discarded
+        );
+    }
+    if report.files.is_empty() {
+        warn!(
+            "no metadata outputs under {}/metadata or {}/parquet",
+            args.run_dir.display(),
+            args.run_dir.display()
+        );
+    }
+    let path = report.write()?;
+    info!("salvage report written to {}", path.display());
+    Ok(())
+}
+
+fn export_run(args: &cli::ExportArgs) -> Result<()> {
+    let report = export::export_run(&args.run_dir, args.format, args.output.as_deref())
+        .with_context(|| format!("export {}", args.run_dir.display()))?;
+    if report.records == 0 {
+        warn!(
+            "no {} to export in {}",
+            match args.format {
+                export::ExportFormat::Timesketch => "timestamped records",
+                _ => "photo GPS positions",
+            },
+            args.run_dir.display()
+        );
+    }
+    info!(
+        "exported {} records to {}",
+        report.records,
+        report.path.display()
+    );
     Ok(())
 }
+
+fn list_types(args: &cli::ListTypesArgs) -> Result<()> {
+    let mut cfg = config::load_config(args.config_path.as_deref())
+        .context(RunStatus::ConfigError)?
+        .config;
+    if let Some(profile) = args.profile {
+        cfg.apply_profile(profile).context(RunStatus::ConfigError)?;
+    }
+    let catalog = catalog::Catalog::from_config(&cfg)?;
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&catalog)?);
+    } else {
+        print!("{}", catalog.render_text(args.detailed));
+    }
+    Ok(())
+}
+
+fn decrypt_outputs(args: &cli::DecryptArgs) -> Result<()> {
+    let identities = encryption::Identities::from_file(&args.identity)?;
+    let (decrypted, copied) = identities.decrypt_tree(&args.path, &args.output)?;
+    info!(
+        "decrypted {decrypted} files ({copied} copied unchanged) to {}",
+        args.output.display()
+    );
+    Ok(())
+}
+
+fn check_config(args: &cli::ConfigCheckArgs) -> Result<()> {
+    let source = args
+        .path
+        .as_ref()
+        .map(|p| p.display().to_string())
+        .unwrap_or_else(|| "<built-in>".to_string());
+    let issues = config::check_config(
+        args.path.as_deref(),
+        Some(args.chunk_size_mib.saturating_mul(MIB)),
+    )
+    .with_context(|| format!("read config {source}"))?;
+    for issue in &issues {
+        match issue.line {
+            Some(line) => println!("{source}:{line}: {}", issue_text(issue)),
+            None => println!("{source}: {}", issue_text(issue)),
+        }
+    }
+    let errors = issues.iter().filter(|i| i.is_error()).count();
+    let warnings = issues.len() - errors;
+    if errors > 0 {
+        return Err(
+            anyhow!("{source}: {errors} error(s), {warnings} warning(s)")
+                .context(RunStatus::ConfigError),
+        );
+    }
+    println!("{source}: ok ({warnings} warning(s))");
+    Ok(())
+}
+
+fn issue_text(issue: &config::ConfigIssue) -> String {
+    if issue.field.is_empty() {
+        format!("{}: {}", issue.severity, issue.message)
+    } else {
+        format!("{}: {}: {}", issue.severity, issue.field, issue.message)
+    }
+}
+
+fn import_signatures(args: &cli::ImportSignaturesArgs) -> Result<()> {
+    let text = std::fs::read_to_string(&args.path)
+        .with_context(|| format!("read signature file {}", args.path.display()))?;
+    let result = match args.format {
+        cli::SignatureFormat::Photorec => {
+            let mut opts = import::photorec::PhotorecImportOptions {
+                max_size: args.max_size,
+                min_size: args.min_size,
+                ..Default::default()
+            };
+            if let Some(prefix) = &args.id_prefix {
+                opts.id_prefix = prefix.clone();
+            }
+            import::photorec::parse_signatures(&text, &opts)
+        }
+        cli::SignatureFormat::Scalpel => {
+            let mut opts = import::scalpel::ScalpelImportOptions::default();
+            if let Some(prefix) = &args.id_prefix {
+                opts.id_prefix = prefix.clone();
+            }
+            import::scalpel::parse_config(&text, &opts)
+        }
+    };
+    log_import_issues(&result);
+    let yaml = result.to_yaml(&args.path.display().to_string())?;
+    match &args.output {
+        Some(path) => {
+            std::fs::write(path, yaml).with_context(|| format!("write {}", path.display()))?;
+            info!(
+                "imported {} file types ({} skipped) to {}",
+                result.file_types.len(),
+                result.skipped.len(),
+                path.display()
+            );
+        }
+        None => print!("{yaml}"),
+    }
+    Ok(())
+}
+
+fn log_import_issues(result: &import::ImportResult) {
+    for entry in &result.skipped {
+        warn!(
+            "skipped line {}: {} ({})",
+            entry.line, entry.reason, entry.text
+        );
+    }
+    for entry in &result.approximated {
+        warn!(
+            "approximated line {}: {} ({})",
+            entry.line, entry.reason, entry.text
+        );
+    }
+}
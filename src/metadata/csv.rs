@@ -1,32 +1,76 @@
-use std::fs::File;
 use std::path::Path;
 use std::sync::Mutex;
 
 use serde::Serialize;
 
-use crate::carve::CarvedFile;
-use crate::metadata::{EntropyRegion, MetadataError, MetadataSink, RunSummary};
-use crate::parsers::browser::{BrowserCookieRecord, BrowserDownloadRecord};
+use crate::carve::{CarvedFile, Truncation};
+use crate::encryption::{OutputCipher, OutputFile};
+use crate::inputs::InputFileRecord;
+use crate::metadata::{
+    CarveCompanionRecord, CarveSizeRecord, ClassificationRecord, EntropyRegion, MetadataError,
+    MetadataSink, ResourceUsage, RunSummary, SinkIdentity, VolumeLocationRecord, open_stream,
+};
+use crate::parsers::apple::MessageRecord;
+use crate::parsers::apple::UsageEventRecord;
+use crate::parsers::bplist::PlistRecord;
+use crate::parsers::browser::{BrowserCacheRecord, BrowserCookieRecord, BrowserDownloadRecord};
+use crate::parsers::container::ContainerArtefactRecord;
+use crate::parsers::executable::ExecutableRecord;
+use crate::parsers::exif::PhotoDeviceRecord;
+use crate::parsers::image::ImageInfoRecord;
+use crate::parsers::lnk::ShortcutRecord;
+use crate::parsers::logs::LogEventRecord;
+use crate::parsers::mp4::VideoRepairRecord;
+use crate::parsers::ntfs::NtfsFileRecord;
+use crate::parsers::office_crypto::EncryptedDocumentRecord;
+use crate::parsers::prefetch::PrefetchRecord;
+use crate::parsers::sqlite_apps::AppRecord;
+use crate::parsers::thumbcache::ThumbnailRecord;
 use crate::strings::artifacts::{ArtefactKind, StringArtefact};
+use crate::strings::url::decode_url;
 
 pub struct CsvSink {
     tool_version: String,
     config_hash: String,
     evidence_path: String,
     evidence_sha256: String,
-    files_writer: Mutex<csv::Writer<File>>,
-    strings_writer: Mutex<csv::Writer<File>>,
-    history_writer: Mutex<csv::Writer<File>>,
-    cookies_writer: Mutex<csv::Writer<File>>,
-    downloads_writer: Mutex<csv::Writer<File>>,
-    run_writer: Mutex<csv::Writer<File>>,
-    entropy_writer: Mutex<csv::Writer<File>>,
+    pass_id: u32,
+    files_writer: Mutex<csv::Writer<OutputFile>>,
+    strings_writer: Mutex<csv::Writer<OutputFile>>,
+    history_writer: Mutex<csv::Writer<OutputFile>>,
+    cookies_writer: Mutex<csv::Writer<OutputFile>>,
+    downloads_writer: Mutex<csv::Writer<OutputFile>>,
+    cache_writer: Mutex<csv::Writer<OutputFile>>,
+    log_events_writer: Mutex<csv::Writer<OutputFile>>,
+    container_writer: Mutex<csv::Writer<OutputFile>>,
+    run_writer: Mutex<csv::Writer<OutputFile>>,
+    resource_writer: Mutex<csv::Writer<OutputFile>>,
+    message_writer: Mutex<csv::Writer<OutputFile>>,
+    usage_event_writer: Mutex<csv::Writer<OutputFile>>,
+    app_record_writer: Mutex<csv::Writer<OutputFile>>,
+    prefetch_writer: Mutex<csv::Writer<OutputFile>>,
+    shortcut_writer: Mutex<csv::Writer<OutputFile>>,
+    executable_writer: Mutex<csv::Writer<OutputFile>>,
+    thumbnail_writer: Mutex<csv::Writer<OutputFile>>,
+    plist_writer: Mutex<csv::Writer<OutputFile>>,
+    video_repair_writer: Mutex<csv::Writer<OutputFile>>,
+    image_info_writer: Mutex<csv::Writer<OutputFile>>,
+    photo_device_writer: Mutex<csv::Writer<OutputFile>>,
+    ntfs_file_writer: Mutex<csv::Writer<OutputFile>>,
+    input_file_writer: Mutex<csv::Writer<OutputFile>>,
+    encrypted_document_writer: Mutex<csv::Writer<OutputFile>>,
+    carve_size_writer: Mutex<csv::Writer<OutputFile>>,
+    carve_companion_writer: Mutex<csv::Writer<OutputFile>>,
+    volume_location_writer: Mutex<csv::Writer<OutputFile>>,
+    classification_writer: Mutex<csv::Writer<OutputFile>>,
+    entropy_writer: Mutex<csv::Writer<OutputFile>>,
 }
 
 #[derive(Serialize)]
 struct CarvedFileCsv<'a> {
     run_id: &'a str,
     file_type: &'a str,
+    signature_type: &'a str,
     path: &'a str,
     extension: &'a str,
     global_start: u64,
@@ -42,6 +86,7 @@ struct CarvedFileCsv<'a> {
     config_hash: &'a str,
     evidence_path: &'a str,
     evidence_sha256: &'a str,
+    pass_id: u32,
 }
 
 #[derive(Serialize)]
@@ -52,10 +97,15 @@ struct StringArtefactCsv<'a> {
     encoding: &'a str,
     global_start: u64,
     global_end: u64,
+    source_kind: &'a str,
+    source_detail: &'a str,
+    host_unicode: Option<String>,
+    path_decoded: Option<String>,
     tool_version: &'a str,
     config_hash: &'a str,
     evidence_path: &'a str,
     evidence_sha256: &'a str,
+    pass_id: u32,
 }
 
 #[derive(Serialize)]
@@ -72,6 +122,7 @@ struct BrowserHistoryCsv<'a> {
     config_hash: &'a str,
     evidence_path: &'a str,
     evidence_sha256: &'a str,
+    pass_id: u32,
 }
 
 #[derive(Serialize)]
@@ -93,6 +144,7 @@ struct BrowserCookieCsv<'a> {
     config_hash: &'a str,
     evidence_path: &'a str,
     evidence_sha256: &'a str,
+    pass_id: u32,
 }
 
 #[derive(Serialize)]
@@ -111,6 +163,63 @@ struct BrowserDownloadCsv<'a> {
     config_hash: &'a str,
     evidence_path: &'a str,
     evidence_sha256: &'a str,
+    pass_id: u32,
+}
+
+#[derive(Serialize)]
+struct BrowserCacheCsv<'a> {
+    run_id: &'a str,
+    browser: &'a str,
+    url: &'a str,
+    http_status: Option<u16>,
+    content_type: Option<&'a str>,
+    content_encoding: Option<&'a str>,
+    response_headers: Option<&'a str>,
+    body_size: u64,
+    body_path: Option<&'a str>,
+    source_file: String,
+    tool_version: &'a str,
+    config_hash: &'a str,
+    evidence_path: &'a str,
+    evidence_sha256: &'a str,
+    pass_id: u32,
+}
+
+#[derive(Serialize)]
+struct LogEventCsv<'a> {
+    run_id: &'a str,
+    source: &'a str,
+    timestamp: Option<String>,
+    timestamp_raw: Option<&'a str>,
+    host: Option<&'a str>,
+    program: Option<&'a str>,
+    pid: Option<u32>,
+    severity: Option<u8>,
+    message: &'a str,
+    global_offset: u64,
+    source_file: Option<&'a str>,
+    tool_version: &'a str,
+    config_hash: &'a str,
+    evidence_path: &'a str,
+    evidence_sha256: &'a str,
+    pass_id: u32,
+}
+
+#[derive(Serialize)]
+struct ContainerArtefactCsv<'a> {
+    run_id: &'a str,
+    kind: &'a str,
+    name: Option<&'a str>,
+    digest: Option<&'a str>,
+    namespace: Option<&'a str>,
+    detail: Option<&'a str>,
+    global_offset: u64,
+    source_file: &'a str,
+    tool_version: &'a str,
+    config_hash: &'a str,
+    evidence_path: &'a str,
+    evidence_sha256: &'a str,
+    pass_id: u32,
 }
 
 #[derive(Serialize)]
@@ -126,337 +235,2035 @@ struct RunSummaryCsv<'a> {
     config_hash: &'a str,
     evidence_path: &'a str,
     evidence_sha256: &'a str,
+    pass_id: u32,
+}
+
+#[derive(Serialize)]
+struct ResourceUsageCsv<'a> {
+    run_id: &'a str,
+    stage: &'a str,
+    threads: u64,
+    busy_secs: f64,
+    user_cpu_secs: Option<f64>,
+    sys_cpu_secs: Option<f64>,
+    bytes_read: Option<u64>,
+    bytes_written: Option<u64>,
+    gpu_secs: Option<f64>,
+    peak_rss_bytes: Option<u64>,
+    tool_version: &'a str,
+    config_hash: &'a str,
+    evidence_path: &'a str,
+    evidence_sha256: &'a str,
+    pass_id: u32,
+}
+
+#[derive(Serialize)]
+struct MessageCsv<'a> {
+    run_id: &'a str,
+    app: &'a str,
+    message_id: Option<&'a str>,
+    thread: Option<&'a str>,
+    handle: Option<&'a str>,
+    is_from_me: Option<bool>,
+    service: Option<&'a str>,
+    text: Option<&'a str>,
+    timestamp_utc: Option<String>,
+    modified_utc: Option<String>,
+    source_file: &'a str,
+    tool_version: &'a str,
+    config_hash: &'a str,
+    evidence_path: &'a str,
+    evidence_sha256: &'a str,
+    pass_id: u32,
+}
+
+#[derive(Serialize)]
+struct UsageEventCsv<'a> {
+    run_id: &'a str,
+    source: &'a str,
+    stream: &'a str,
+    value: Option<&'a str>,
+    bundle_id: Option<&'a str>,
+    start_utc: Option<String>,
+    end_utc: Option<String>,
+    source_file: &'a str,
+    tool_version: &'a str,
+    config_hash: &'a str,
+    evidence_path: &'a str,
+    evidence_sha256: &'a str,
+    pass_id: u32,
+}
+
+#[derive(Serialize)]
+struct AppCsv<'a> {
+    run_id: &'a str,
+    app: &'a str,
+    plan: &'a str,
+    row_id: Option<i64>,
+    timestamp_utc: Option<String>,
+    timestamp_desc: Option<&'a str>,
+    fields: &'a str,
+    source_file: &'a str,
+    tool_version: &'a str,
+    config_hash: &'a str,
+    evidence_path: &'a str,
+    evidence_sha256: &'a str,
+    pass_id: u32,
+}
+
+#[derive(Serialize)]
+struct PrefetchCsv<'a> {
+    run_id: &'a str,
+    executable: &'a str,
+    prefetch_hash: &'a str,
+    format_version: u32,
+    compressed: bool,
+    run_count: u32,
+    last_run_utc: Option<String>,
+    earlier_runs_utc: Option<&'a str>,
+    source_file: &'a str,
+    tool_version: &'a str,
+    config_hash: &'a str,
+    evidence_path: &'a str,
+    evidence_sha256: &'a str,
+    pass_id: u32,
+}
+
+#[derive(Serialize)]
+struct ShortcutCsv<'a> {
+    run_id: &'a str,
+    target_path: Option<&'a str>,
+    arguments: Option<&'a str>,
+    working_dir: Option<&'a str>,
+    relative_path: Option<&'a str>,
+    description: Option<&'a str>,
+    icon_location: Option<&'a str>,
+    target_size: u32,
+    file_attributes: u32,
+    target_created_utc: Option<String>,
+    target_accessed_utc: Option<String>,
+    target_modified_utc: Option<String>,
+    volume_serial: Option<&'a str>,
+    machine_id: Option<&'a str>,
+    source_file: &'a str,
+    tool_version: &'a str,
+    config_hash: &'a str,
+    evidence_path: &'a str,
+    evidence_sha256: &'a str,
+    pass_id: u32,
+}
+
+#[derive(Serialize)]
+struct ExecutableCsv<'a> {
+    run_id: &'a str,
+    format: &'a str,
+    kind: &'a str,
+    architecture: &'a str,
+    bits: u8,
+    compile_time_utc: Option<String>,
+    subsystem: Option<&'a str>,
+    section_count: u32,
+    import_count: Option<u32>,
+    import_hash: Option<&'a str>,
+    signed: bool,
+    source_file: &'a str,
+    tool_version: &'a str,
+    config_hash: &'a str,
+    evidence_path: &'a str,
+    evidence_sha256: &'a str,
+    pass_id: u32,
+}
+
+#[derive(Serialize)]
+struct ThumbnailCsv<'a> {
+    run_id: &'a str,
+    cache_id: &'a str,
+    cache_version: &'a str,
+    cache_type: u32,
+    entry_offset: u64,
+    width: Option<u32>,
+    height: Option<u32>,
+    image_format: &'a str,
+    data_size: u64,
+    image_path: &'a str,
+    source_file: &'a str,
+    tool_version: &'a str,
+    config_hash: &'a str,
+    evidence_path: &'a str,
+    evidence_sha256: &'a str,
+    pass_id: u32,
+}
+
+#[derive(Serialize)]
+struct PlistCsv<'a> {
+    run_id: &'a str,
+    object_count: u64,
+    top_type: &'a str,
+    top_keys: Option<&'a str>,
+    archiver: Option<&'a str>,
+    json_len: u64,
+    json: Option<&'a str>,
+    source_file: &'a str,
+    tool_version: &'a str,
+    config_hash: &'a str,
+    evidence_path: &'a str,
+    evidence_sha256: &'a str,
+    pass_id: u32,
+}
+
+#[derive(Serialize)]
+struct VideoRepairCsv<'a> {
+    run_id: &'a str,
+    method: &'a str,
+    codec: &'a str,
+    width: u32,
+    height: u32,
+    frame_count: u64,
+    keyframe_count: u64,
+    duration_secs: f64,
+    media_bytes: u64,
+    skipped_bytes: u64,
+    donor_file: Option<&'a str>,
+    repaired_path: &'a str,
+    source_file: &'a str,
+    tool_version: &'a str,
+    config_hash: &'a str,
+    evidence_path: &'a str,
+    evidence_sha256: &'a str,
+    pass_id: u32,
+}
+
+#[derive(Serialize)]
+struct ImageInfoCsv<'a> {
+    run_id: &'a str,
+    format: &'a str,
+    width: u32,
+    height: u32,
+    bits_per_pixel: Option<u32>,
+    decoded_pct: Option<f64>,
+    corrupt_offset: Option<u64>,
+    source_file: &'a str,
+    tool_version: &'a str,
+    config_hash: &'a str,
+    evidence_path: &'a str,
+    evidence_sha256: &'a str,
+    pass_id: u32,
+}
+
+#[derive(Serialize)]
+struct PhotoDeviceCsv<'a> {
+    run_id: &'a str,
+    source_file: &'a str,
+    global_start: u64,
+    make: Option<&'a str>,
+    model: Option<&'a str>,
+    serial: Option<&'a str>,
+    lens: Option<&'a str>,
+    lens_serial: Option<&'a str>,
+    taken_at: Option<String>,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    device_id: Option<&'a str>,
+    session_id: Option<&'a str>,
+    tool_version: &'a str,
+    config_hash: &'a str,
+    evidence_path: &'a str,
+    evidence_sha256: &'a str,
+    pass_id: u32,
+}
+
+#[derive(Serialize)]
+struct NtfsFileCsv<'a> {
+    run_id: &'a str,
+    volume_offset: u64,
+    mft_record: u64,
+    sequence: u16,
+    path: &'a str,
+    name: &'a str,
+    in_use: bool,
+    directory: bool,
+    size: u64,
+    resident: bool,
+    data_runs: &'a str,
+    created: Option<String>,
+    modified: Option<String>,
+    mft_modified: Option<String>,
+    accessed: Option<String>,
+    carved_path: Option<&'a str>,
+    tool_version: &'a str,
+    config_hash: &'a str,
+    evidence_path: &'a str,
+    evidence_sha256: &'a str,
+    pass_id: u32,
+}
+
+#[derive(Serialize)]
+struct InputFileCsv<'a> {
+    run_id: &'a str,
+    kind: &'a str,
+    path: &'a str,
+    sha256: &'a str,
+    tool_version: &'a str,
+    config_hash: &'a str,
+    evidence_path: &'a str,
+    evidence_sha256: &'a str,
+    pass_id: u32,
+}
+
+#[derive(Serialize)]
+struct EncryptedDocumentCsv<'a> {
+    run_id: &'a str,
+    encryption_type: &'a str,
+    version: Option<&'a str>,
+    cipher_algorithm: Option<&'a str>,
+    key_bits: Option<u32>,
+    hash_algorithm: Option<&'a str>,
+    spin_count: Option<u32>,
+    csp_name: Option<&'a str>,
+    original_size: Option<u64>,
+    source_file: &'a str,
+    tool_version: &'a str,
+    config_hash: &'a str,
+    evidence_path: &'a str,
+    evidence_sha256: &'a str,
+    pass_id: u32,
+}
+
+#[derive(Serialize)]
+struct CarveSizeCsv<'a> {
+    run_id: &'a str,
+    file_type: &'a str,
+    path: &'a str,
+    global_start: u64,
+    size: u64,
+    declared_size: Option<u64>,
+    declared_by: Option<&'a str>,
+    truncation: Option<Truncation>,
+    tool_version: &'a str,
+    config_hash: &'a str,
+    evidence_path: &'a str,
+    evidence_sha256: &'a str,
+    pass_id: u32,
+}
+
+#[derive(Serialize)]
+struct CarveCompanionCsv<'a> {
+    run_id: &'a str,
+    file_type: &'a str,
+    path: &'a str,
+    global_start: u64,
+    companion_type: &'a str,
+    companion_path: &'a str,
+    companion_start: u64,
+    companion_size: u64,
+    tool_version: &'a str,
+    config_hash: &'a str,
+    evidence_path: &'a str,
+    evidence_sha256: &'a str,
+    pass_id: u32,
+}
+
+#[derive(Serialize)]
+struct VolumeLocationCsv<'a> {
+    run_id: &'a str,
+    subject: &'a str,
+    reference: &'a str,
+    global_start: u64,
+    partition: u32,
+    partition_offset: u64,
+    relative_offset: u64,
+    relative_sector: u64,
+    file_system: Option<&'a str>,
+    cluster: Option<u64>,
+    cluster_chain: Option<&'a str>,
+    tool_version: &'a str,
+    config_hash: &'a str,
+    evidence_path: &'a str,
+    evidence_sha256: &'a str,
+    pass_id: u32,
+}
+
+#[derive(Serialize)]
+struct ClassificationCsv<'a> {
+    run_id: &'a str,
+    file_type: &'a str,
+    path: &'a str,
+    global_start: u64,
+    classifier: &'a str,
+    label: &'a str,
+    score: f64,
+    rank: u32,
+    tool_version: &'a str,
+    config_hash: &'a str,
+    evidence_path: &'a str,
+    evidence_sha256: &'a str,
+    pass_id: u32,
+}
+
+#[derive(Serialize)]
+struct EntropyRegionCsv<'a> {
+    run_id: &'a str,
+    global_start: u64,
+    global_end: u64,
+    entropy: f64,
+    window_size: u64,
+    tool_version: &'a str,
+    config_hash: &'a str,
+    evidence_path: &'a str,
+    evidence_sha256: &'a str,
+    pass_id: u32,
+}
+
+/// Write the header row of a stream that is still empty; streams appended to
+/// by a later pass already have one.
+fn write_header(
+    writer: &mut csv::Writer<OutputFile>,
+    empty: bool,
+    columns: &[&str],
+) -> Result<(), MetadataError> {
+    if empty {
+        writer.write_record(columns)?;
+    }
+    Ok(())
 }
 
-#[derive(Serialize)]
-struct EntropyRegionCsv<'a> {
-    run_id: &'a str,
-    global_start: u64,
-    global_end: u64,
-    entropy: f64,
-    window_size: u64,
-    tool_version: &'a str,
-    config_hash: &'a str,
-    evidence_path: &'a str,
-    evidence_sha256: &'a str,
-}
+impl CsvSink {
+    pub fn new(
+        identity: &SinkIdentity,
+        run_output_dir: &Path,
+        cipher: Option<&OutputCipher>,
+    ) -> Result<Self, MetadataError> {
+        let meta_dir = run_output_dir.join("metadata");
+        std::fs::create_dir_all(&meta_dir)?;
+
+        let files_file = open_stream(&meta_dir.join("carved_files.csv"), identity.pass_id, cipher)?;
+        let files_empty = files_file.is_empty()?;
+        let strings_file = open_stream(
+            &meta_dir.join("string_artefacts.csv"),
+            identity.pass_id,
+            cipher,
+        )?;
+        let strings_empty = strings_file.is_empty()?;
+        let history_file = open_stream(
+            &meta_dir.join("browser_history.csv"),
+            identity.pass_id,
+            cipher,
+        )?;
+        let history_empty = history_file.is_empty()?;
+        let cookies_file = open_stream(
+            &meta_dir.join("browser_cookies.csv"),
+            identity.pass_id,
+            cipher,
+        )?;
+        let cookies_empty = cookies_file.is_empty()?;
+        let downloads_file = open_stream(
+            &meta_dir.join("browser_downloads.csv"),
+            identity.pass_id,
+            cipher,
+        )?;
+        let downloads_empty = downloads_file.is_empty()?;
+        let cache_file = open_stream(
+            &meta_dir.join("browser_cache.csv"),
+            identity.pass_id,
+            cipher,
+        )?;
+        let cache_empty = cache_file.is_empty()?;
+        let log_events_file =
+            open_stream(&meta_dir.join("log_events.csv"), identity.pass_id, cipher)?;
+        let log_events_empty = log_events_file.is_empty()?;
+        let container_file = open_stream(
+            &meta_dir.join("container_artefacts.csv"),
+            identity.pass_id,
+            cipher,
+        )?;
+        let container_empty = container_file.is_empty()?;
+        let run_file = open_stream(&meta_dir.join("run_summary.csv"), identity.pass_id, cipher)?;
+        let run_empty = run_file.is_empty()?;
+        let resource_file = open_stream(
+            &meta_dir.join("resource_usage.csv"),
+            identity.pass_id,
+            cipher,
+        )?;
+        let resource_empty = resource_file.is_empty()?;
+        let message_file = open_stream(&meta_dir.join("messages.csv"), identity.pass_id, cipher)?;
+        let message_empty = message_file.is_empty()?;
+        let usage_event_file =
+            open_stream(&meta_dir.join("usage_events.csv"), identity.pass_id, cipher)?;
+        let usage_event_empty = usage_event_file.is_empty()?;
+        let app_record_file =
+            open_stream(&meta_dir.join("app_records.csv"), identity.pass_id, cipher)?;
+        let app_record_empty = app_record_file.is_empty()?;
+        let prefetch_file = open_stream(
+            &meta_dir.join("prefetch_files.csv"),
+            identity.pass_id,
+            cipher,
+        )?;
+        let prefetch_empty = prefetch_file.is_empty()?;
+        let shortcut_file = open_stream(&meta_dir.join("shortcuts.csv"), identity.pass_id, cipher)?;
+        let shortcut_empty = shortcut_file.is_empty()?;
+        let executable_file =
+            open_stream(&meta_dir.join("executables.csv"), identity.pass_id, cipher)?;
+        let executable_empty = executable_file.is_empty()?;
+        let thumbnail_file =
+            open_stream(&meta_dir.join("thumbnails.csv"), identity.pass_id, cipher)?;
+        let thumbnail_empty = thumbnail_file.is_empty()?;
+        let plist_file = open_stream(&meta_dir.join("plists.csv"), identity.pass_id, cipher)?;
+        let plist_empty = plist_file.is_empty()?;
+        let video_repair_file = open_stream(
+            &meta_dir.join("video_repairs.csv"),
+            identity.pass_id,
+            cipher,
+        )?;
+        let video_repair_empty = video_repair_file.is_empty()?;
+        let image_info_file =
+            open_stream(&meta_dir.join("image_info.csv"), identity.pass_id, cipher)?;
+        let image_info_empty = image_info_file.is_empty()?;
+        let photo_device_file = open_stream(
+            &meta_dir.join("photo_devices.csv"),
+            identity.pass_id,
+            cipher,
+        )?;
+        let photo_device_empty = photo_device_file.is_empty()?;
+        let ntfs_file_file =
+            open_stream(&meta_dir.join("ntfs_files.csv"), identity.pass_id, cipher)?;
+        let ntfs_file_empty = ntfs_file_file.is_empty()?;
+        let input_file_file =
+            open_stream(&meta_dir.join("input_files.csv"), identity.pass_id, cipher)?;
+        let input_file_empty = input_file_file.is_empty()?;
+        let encrypted_document_file = open_stream(
+            &meta_dir.join("encrypted_documents.csv"),
+            identity.pass_id,
+            cipher,
+        )?;
+        let encrypted_document_empty = encrypted_document_file.is_empty()?;
+        let carve_size_file =
+            open_stream(&meta_dir.join("carve_sizes.csv"), identity.pass_id, cipher)?;
+        let carve_size_empty = carve_size_file.is_empty()?;
+        let carve_companion_file = open_stream(
+            &meta_dir.join("carve_companions.csv"),
+            identity.pass_id,
+            cipher,
+        )?;
+        let carve_companion_empty = carve_companion_file.is_empty()?;
+        let volume_location_file = open_stream(
+            &meta_dir.join("volume_locations.csv"),
+            identity.pass_id,
+            cipher,
+        )?;
+        let volume_location_empty = volume_location_file.is_empty()?;
+        let classification_file = open_stream(
+            &meta_dir.join("classifications.csv"),
+            identity.pass_id,
+            cipher,
+        )?;
+        let classification_empty = classification_file.is_empty()?;
+        let entropy_file = open_stream(
+            &meta_dir.join("entropy_regions.csv"),
+            identity.pass_id,
+            cipher,
+        )?;
+        let entropy_empty = entropy_file.is_empty()?;
+
+        let mut files_writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(files_file);
+        let mut strings_writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(strings_file);
+        let mut history_writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(history_file);
+        let mut cookies_writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(cookies_file);
+        let mut downloads_writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(downloads_file);
+        let mut cache_writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(cache_file);
+        let mut log_events_writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(log_events_file);
+        let mut container_writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(container_file);
+        let mut run_writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(run_file);
+        let mut resource_writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(resource_file);
+        let mut message_writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(message_file);
+        let mut usage_event_writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(usage_event_file);
+        let mut app_record_writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(app_record_file);
+        let mut prefetch_writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(prefetch_file);
+        let mut shortcut_writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(shortcut_file);
+        let mut executable_writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(executable_file);
+        let mut thumbnail_writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(thumbnail_file);
+        let mut plist_writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(plist_file);
+        let mut video_repair_writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(video_repair_file);
+        let mut image_info_writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(image_info_file);
+        let mut photo_device_writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(photo_device_file);
+        let mut ntfs_file_writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(ntfs_file_file);
+        let mut input_file_writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(input_file_file);
+        let mut encrypted_document_writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(encrypted_document_file);
+        let mut carve_size_writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(carve_size_file);
+        let mut carve_companion_writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(carve_companion_file);
+        let mut volume_location_writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(volume_location_file);
+        let mut classification_writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(classification_file);
+        let mut entropy_writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(entropy_file);
+
+        write_header(
+            &mut files_writer,
+            files_empty,
+            &[
+                "run_id",
+                "file_type",
+                "signature_type",
+                "path",
+                "extension",
+                "global_start",
+                "global_end",
+                "size",
+                "md5",
+                "sha256",
+                "validated",
+                "truncated",
+                "errors",
+                "pattern_id",
+                "tool_version",
+                "config_hash",
+                "evidence_path",
+                "evidence_sha256",
+                "pass_id",
+            ],
+        )?;
+
+        write_header(
+            &mut strings_writer,
+            strings_empty,
+            &[
+                "run_id",
+                "artefact_kind",
+                "content",
+                "encoding",
+                "global_start",
+                "global_end",
+                "source_kind",
+                "source_detail",
+                "host_unicode",
+                "path_decoded",
+                "tool_version",
+                "config_hash",
+                "evidence_path",
+                "evidence_sha256",
+                "pass_id",
+            ],
+        )?;
+
+        write_header(
+            &mut history_writer,
+            history_empty,
+            &[
+                "run_id",
+                "browser",
+                "profile",
+                "url",
+                "title",
+                "visit_time",
+                "visit_source",
+                "source_file",
+                "tool_version",
+                "config_hash",
+                "evidence_path",
+                "evidence_sha256",
+                "pass_id",
+            ],
+        )?;
+
+        write_header(
+            &mut cookies_writer,
+            cookies_empty,
+            &[
+                "run_id",
+                "browser",
+                "profile",
+                "host",
+                "name",
+                "value",
+                "path",
+                "expires_utc",
+                "last_access_utc",
+                "creation_utc",
+                "is_secure",
+                "is_http_only",
+                "source_file",
+                "tool_version",
+                "config_hash",
+                "evidence_path",
+                "evidence_sha256",
+                "pass_id",
+            ],
+        )?;
+
+        write_header(
+            &mut downloads_writer,
+            downloads_empty,
+            &[
+                "run_id",
+                "browser",
+                "profile",
+                "url",
+                "target_path",
+                "start_time",
+                "end_time",
+                "total_bytes",
+                "state",
+                "source_file",
+                "tool_version",
+                "config_hash",
+                "evidence_path",
+                "evidence_sha256",
+                "pass_id",
+            ],
+        )?;
+
+        write_header(
+            &mut cache_writer,
+            cache_empty,
+            &[
+                "run_id",
+                "browser",
+                "url",
+                "http_status",
+                "content_type",
+                "content_encoding",
+                "response_headers",
+                "body_size",
+                "body_path",
+                "source_file",
+                "tool_version",
+                "config_hash",
+                "evidence_path",
+                "evidence_sha256",
+                "pass_id",
+            ],
+        )?;
+
+        write_header(
+            &mut log_events_writer,
+            log_events_empty,
+            &[
+                "run_id",
+                "source",
+                "timestamp",
+                "timestamp_raw",
+                "host",
+                "program",
+                "pid",
+                "severity",
+                "message",
+                "global_offset",
+                "source_file",
+                "tool_version",
+                "config_hash",
+                "evidence_path",
+                "evidence_sha256",
+                "pass_id",
+            ],
+        )?;
+
+        write_header(
+            &mut container_writer,
+            container_empty,
+            &[
+                "run_id",
+                "kind",
+                "name",
+                "digest",
+                "namespace",
+                "detail",
+                "global_offset",
+                "source_file",
+                "tool_version",
+                "config_hash",
+                "evidence_path",
+                "evidence_sha256",
+                "pass_id",
+            ],
+        )?;
+
+        write_header(
+            &mut run_writer,
+            run_empty,
+            &[
+                "run_id",
+                "bytes_scanned",
+                "chunks_processed",
+                "hits_found",
+                "files_carved",
+                "string_spans",
+                "artefacts_extracted",
+                "tool_version",
+                "config_hash",
+                "evidence_path",
+                "evidence_sha256",
+                "pass_id",
+            ],
+        )?;
+
+        write_header(
+            &mut resource_writer,
+            resource_empty,
+            &[
+                "run_id",
+                "stage",
+                "threads",
+                "busy_secs",
+                "user_cpu_secs",
+                "sys_cpu_secs",
+                "bytes_read",
+                "bytes_written",
+                "gpu_secs",
+                "peak_rss_bytes",
+                "tool_version",
+                "config_hash",
+                "evidence_path",
+                "evidence_sha256",
+                "pass_id",
+            ],
+        )?;
+
+        write_header(
+            &mut message_writer,
+            message_empty,
+            &[
+                "run_id",
+                "app",
+                "message_id",
+                "thread",
+                "handle",
+                "is_from_me",
+                "service",
+                "text",
+                "timestamp_utc",
+                "modified_utc",
+                "source_file",
+                "tool_version",
+                "config_hash",
+                "evidence_path",
+                "evidence_sha256",
+                "pass_id",
+            ],
+        )?;
+
+        write_header(
+            &mut usage_event_writer,
+            usage_event_empty,
+            &[
+                "run_id",
+                "source",
+                "stream",
+                "value",
+                "bundle_id",
+                "start_utc",
+                "end_utc",
+                "source_file",
+                "tool_version",
+                "config_hash",
+                "evidence_path",
+                "evidence_sha256",
+                "pass_id",
+            ],
+        )?;
+
+        write_header(
+            &mut prefetch_writer,
+            prefetch_empty,
+            &[
+                "run_id",
+                "executable",
+                "prefetch_hash",
+                "format_version",
+                "compressed",
+                "run_count",
+                "last_run_utc",
+                "earlier_runs_utc",
+                "source_file",
+                "tool_version",
+                "config_hash",
+                "evidence_path",
+                "evidence_sha256",
+                "pass_id",
+            ],
+        )?;
+
+        write_header(
+            &mut shortcut_writer,
+            shortcut_empty,
+            &[
+                "run_id",
+                "target_path",
+                "arguments",
+                "working_dir",
+                "relative_path",
+                "description",
+                "icon_location",
+                "target_size",
+                "file_attributes",
+                "target_created_utc",
+                "target_accessed_utc",
+                "target_modified_utc",
+                "volume_serial",
+                "machine_id",
+                "source_file",
+                "tool_version",
+                "config_hash",
+                "evidence_path",
+                "evidence_sha256",
+                "pass_id",
+            ],
+        )?;
+
+        write_header(
+            &mut executable_writer,
+            executable_empty,
+            &[
+                "run_id",
+                "format",
+                "kind",
+                "architecture",
+                "bits",
+                "compile_time_utc",
+                "subsystem",
+                "section_count",
+                "import_count",
+                "import_hash",
+                "signed",
+                "source_file",
+                "tool_version",
+                "config_hash",
+                "evidence_path",
+                "evidence_sha256",
+                "pass_id",
+            ],
+        )?;
+
+        write_header(
+            &mut thumbnail_writer,
+            thumbnail_empty,
+            &[
+                "run_id",
+                "cache_id",
+                "cache_version",
+                "cache_type",
+                "entry_offset",
+                "width",
+                "height",
+                "image_format",
+                "data_size",
+                "image_path",
+                "source_file",
+                "tool_version",
+                "config_hash",
+                "evidence_path",
+                "evidence_sha256",
+                "pass_id",
+            ],
+        )?;
+
+        write_header(
+            &mut plist_writer,
+            plist_empty,
+            &[
+                "run_id",
+                "object_count",
+                "top_type",
+                "top_keys",
+                "archiver",
+                "json_len",
+                "json",
+                "source_file",
+                "tool_version",
+                "config_hash",
+                "evidence_path",
+                "evidence_sha256",
+                "pass_id",
+            ],
+        )?;
+
+        write_header(
+            &mut video_repair_writer,
+            video_repair_empty,
+            &[
+                "run_id",
+                "method",
+                "codec",
+                "width",
+                "height",
+                "frame_count",
+                "keyframe_count",
+                "duration_secs",
+                "media_bytes",
+                "skipped_bytes",
+                "donor_file",
+                "repaired_path",
+                "source_file",
+                "tool_version",
+                "config_hash",
+                "evidence_path",
+                "evidence_sha256",
+                "pass_id",
+            ],
+        )?;
+
+        write_header(
+            &mut app_record_writer,
+            app_record_empty,
+            &[
+                "run_id",
+                "app",
+                "plan",
+                "row_id",
+                "timestamp_utc",
+                "timestamp_desc",
+                "fields",
+                "source_file",
+                "tool_version",
+                "config_hash",
+                "evidence_path",
+                "evidence_sha256",
+                "pass_id",
+            ],
+        )?;
+
+        write_header(
+            &mut image_info_writer,
+            image_info_empty,
+            &[
+                "run_id",
+                "format",
+                "width",
+                "height",
+                "bits_per_pixel",
+                "decoded_pct",
+                "corrupt_offset",
+                "source_file",
+                "tool_version",
+                "config_hash",
+                "evidence_path",
+                "evidence_sha256",
+                "pass_id",
+            ],
+        )?;
+
+        write_header(
+            &mut photo_device_writer,
+            photo_device_empty,
+            &[
+                "run_id",
+                "source_file",
+                "global_start",
+                "make",
+                "model",
+                "serial",
+                "lens",
+                "lens_serial",
+                "taken_at",
+                "latitude",
+                "longitude",
+                "device_id",
+                "session_id",
+                "tool_version",
+                "config_hash",
+                "evidence_path",
+                "evidence_sha256",
+                "pass_id",
+            ],
+        )?;
+
+        write_header(
+            &mut ntfs_file_writer,
+            ntfs_file_empty,
+            &[
+                "run_id",
+                "volume_offset",
+                "mft_record",
+                "sequence",
+                "path",
+                "name",
+                "in_use",
+                "directory",
+                "size",
+                "resident",
+                "data_runs",
+                "created",
+                "modified",
+                "mft_modified",
+                "accessed",
+                "carved_path",
+                "tool_version",
+                "config_hash",
+                "evidence_path",
+                "evidence_sha256",
+                "pass_id",
+            ],
+        )?;
+
+        write_header(
+            &mut input_file_writer,
+            input_file_empty,
+            &[
+                "run_id",
+                "kind",
+                "path",
+                "sha256",
+                "tool_version",
+                "config_hash",
+                "evidence_path",
+                "evidence_sha256",
+                "pass_id",
+            ],
+        )?;
+
+        write_header(
+            &mut encrypted_document_writer,
+            encrypted_document_empty,
+            &[
+                "run_id",
+                "encryption_type",
+                "version",
+                "cipher_algorithm",
+                "key_bits",
+                "hash_algorithm",
+                "spin_count",
+                "csp_name",
+                "original_size",
+                "source_file",
+                "tool_version",
+                "config_hash",
+                "evidence_path",
+                "evidence_sha256",
+                "pass_id",
+            ],
+        )?;
+
+        write_header(
+            &mut carve_size_writer,
+            carve_size_empty,
+            &[
+                "run_id",
+                "file_type",
+                "path",
+                "global_start",
+                "size",
+                "declared_size",
+                "declared_by",
+                "truncation",
+                "tool_version",
+                "config_hash",
+                "evidence_path",
+                "evidence_sha256",
+                "pass_id",
+            ],
+        )?;
+
+        write_header(
+            &mut carve_companion_writer,
+            carve_companion_empty,
+            &[
+                "run_id",
+                "file_type",
+                "path",
+                "global_start",
+                "companion_type",
+                "companion_path",
+                "companion_start",
+                "companion_size",
+                "tool_version",
+                "config_hash",
+                "evidence_path",
+                "evidence_sha256",
+                "pass_id",
+            ],
+        )?;
+
+        write_header(
+            &mut volume_location_writer,
+            volume_location_empty,
+            &[
+                "run_id",
+                "subject",
+                "reference",
+                "global_start",
+                "partition",
+                "partition_offset",
+                "relative_offset",
+                "relative_sector",
+                "file_system",
+                "cluster",
+                "cluster_chain",
+                "tool_version",
+                "config_hash",
+                "evidence_path",
+                "evidence_sha256",
+                "pass_id",
+            ],
+        )?;
+
+        write_header(
+            &mut classification_writer,
+            classification_empty,
+            &[
+                "run_id",
+                "file_type",
+                "path",
+                "global_start",
+                "classifier",
+                "label",
+                "score",
+                "rank",
+                "tool_version",
+                "config_hash",
+                "evidence_path",
+                "evidence_sha256",
+                "pass_id",
+            ],
+        )?;
+
+        write_header(
+            &mut entropy_writer,
+            entropy_empty,
+            &[
+                "run_id",
+                "global_start",
+                "global_end",
+                "entropy",
+                "window_size",
+                "tool_version",
+                "config_hash",
+                "evidence_path",
+                "evidence_sha256",
+                "pass_id",
+            ],
+        )?;
+
+        Ok(Self {
+            tool_version: identity.tool_version.to_string(),
+            config_hash: identity.config_hash.to_string(),
+            evidence_path: identity.evidence_path.to_string_lossy().to_string(),
+            evidence_sha256: identity.evidence_sha256.to_string(),
+            pass_id: identity.pass_id,
+            files_writer: Mutex::new(files_writer),
+            strings_writer: Mutex::new(strings_writer),
+            history_writer: Mutex::new(history_writer),
+            cookies_writer: Mutex::new(cookies_writer),
+            downloads_writer: Mutex::new(downloads_writer),
+            cache_writer: Mutex::new(cache_writer),
+            log_events_writer: Mutex::new(log_events_writer),
+            container_writer: Mutex::new(container_writer),
+            run_writer: Mutex::new(run_writer),
+            resource_writer: Mutex::new(resource_writer),
+            message_writer: Mutex::new(message_writer),
+            usage_event_writer: Mutex::new(usage_event_writer),
+            app_record_writer: Mutex::new(app_record_writer),
+            prefetch_writer: Mutex::new(prefetch_writer),
+            shortcut_writer: Mutex::new(shortcut_writer),
+            executable_writer: Mutex::new(executable_writer),
+            thumbnail_writer: Mutex::new(thumbnail_writer),
+            plist_writer: Mutex::new(plist_writer),
+            video_repair_writer: Mutex::new(video_repair_writer),
+            image_info_writer: Mutex::new(image_info_writer),
+            photo_device_writer: Mutex::new(photo_device_writer),
+            ntfs_file_writer: Mutex::new(ntfs_file_writer),
+            input_file_writer: Mutex::new(input_file_writer),
+            encrypted_document_writer: Mutex::new(encrypted_document_writer),
+            carve_size_writer: Mutex::new(carve_size_writer),
+            carve_companion_writer: Mutex::new(carve_companion_writer),
+            volume_location_writer: Mutex::new(volume_location_writer),
+            classification_writer: Mutex::new(classification_writer),
+            entropy_writer: Mutex::new(entropy_writer),
+        })
+    }
+}
+
+impl MetadataSink for CsvSink {
+    fn record_file(&self, file: &CarvedFile) -> Result<(), MetadataError> {
+        let record = CarvedFileCsv {
+            run_id: &file.run_id,
+            file_type: &file.file_type,
+            signature_type: &file.signature_type,
+            path: &file.path,
+            extension: &file.extension,
+            global_start: file.global_start,
+            global_end: file.global_end,
+            size: file.size,
+            md5: file.md5.as_deref(),
+            sha256: file.sha256.as_deref(),
+            validated: file.validated,
+            truncated: file.truncated,
+            errors: file.errors.join("; "),
+            pattern_id: file.pattern_id.as_deref(),
+            tool_version: &self.tool_version,
+            config_hash: &self.config_hash,
+            evidence_path: &self.evidence_path,
+            evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
+        };
+        let mut guard = self
+            .files_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("files writer lock poisoned".into()))?;
+        guard.serialize(record)?;
+        Ok(())
+    }
+
+    fn record_string(&self, artefact: &StringArtefact) -> Result<(), MetadataError> {
+        let decoded = match artefact.artefact_kind {
+            ArtefactKind::Url => decode_url(&artefact.content),
+            _ => Default::default(),
+        };
+        let record = StringArtefactCsv {
+            run_id: &artefact.run_id,
+            artefact_kind: artefact_kind_label(&artefact.artefact_kind),
+            content: &artefact.content,
+            encoding: &artefact.encoding,
+            global_start: artefact.global_start,
+            global_end: artefact.global_end,
+            source_kind: &artefact.source_kind,
+            source_detail: &artefact.source_detail,
+            host_unicode: decoded.host_unicode,
+            path_decoded: decoded.path_decoded,
+            tool_version: &self.tool_version,
+            config_hash: &self.config_hash,
+            evidence_path: &self.evidence_path,
+            evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
+        };
+        let mut guard = self
+            .strings_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("strings writer lock poisoned".into()))?;
+        guard.serialize(record)?;
+        Ok(())
+    }
+
+    fn record_history(
+        &self,
+        record: &crate::parsers::browser::BrowserHistoryRecord,
+    ) -> Result<(), MetadataError> {
+        let record = BrowserHistoryCsv {
+            run_id: &record.run_id,
+            browser: &record.browser,
+            profile: &record.profile,
+            url: &record.url,
+            title: record.title.as_deref(),
+            visit_time: record.visit_time.map(|t| t.to_string()),
+            visit_source: record.visit_source.as_deref(),
+            source_file: record.source_file.to_string_lossy().to_string(),
+            tool_version: &self.tool_version,
+            config_hash: &self.config_hash,
+            evidence_path: &self.evidence_path,
+            evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
+        };
+        let mut guard = self
+            .history_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("history writer lock poisoned".into()))?;
+        guard.serialize(record)?;
+        Ok(())
+    }
+
+    fn record_cookie(&self, record: &BrowserCookieRecord) -> Result<(), MetadataError> {
+        let record = BrowserCookieCsv {
+            run_id: &record.run_id,
+            browser: &record.browser,
+            profile: &record.profile,
+            host: &record.host,
+            name: &record.name,
+            value: record.value.as_deref(),
+            path: record.path.as_deref(),
+            expires_utc: record.expires_utc.map(|dt| dt.to_string()),
+            last_access_utc: record.last_access_utc.map(|dt| dt.to_string()),
+            creation_utc: record.creation_utc.map(|dt| dt.to_string()),
+            is_secure: record.is_secure,
+            is_http_only: record.is_http_only,
+            source_file: record.source_file.to_string_lossy().to_string(),
+            tool_version: &self.tool_version,
+            config_hash: &self.config_hash,
+            evidence_path: &self.evidence_path,
+            evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
+        };
+        let mut guard = self
+            .cookies_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("cookies writer lock poisoned".into()))?;
+        guard.serialize(record)?;
+        Ok(())
+    }
+
+    fn record_download(&self, record: &BrowserDownloadRecord) -> Result<(), MetadataError> {
+        let record = BrowserDownloadCsv {
+            run_id: &record.run_id,
+            browser: &record.browser,
+            profile: &record.profile,
+            url: record.url.as_deref(),
+            target_path: record.target_path.as_deref(),
+            start_time: record.start_time.map(|dt| dt.to_string()),
+            end_time: record.end_time.map(|dt| dt.to_string()),
+            total_bytes: record.total_bytes,
+            state: record.state.as_deref(),
+            source_file: record.source_file.to_string_lossy().to_string(),
+            tool_version: &self.tool_version,
+            config_hash: &self.config_hash,
+            evidence_path: &self.evidence_path,
+            evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
+        };
+        let mut guard = self
+            .downloads_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("downloads writer lock poisoned".into()))?;
+        guard.serialize(record)?;
+        Ok(())
+    }
+
+    fn record_cache(&self, record: &BrowserCacheRecord) -> Result<(), MetadataError> {
+        let record = BrowserCacheCsv {
+            run_id: &record.run_id,
+            browser: &record.browser,
+            url: &record.url,
+            http_status: record.http_status,
+            content_type: record.content_type.as_deref(),
+            content_encoding: record.content_encoding.as_deref(),
+            response_headers: record.response_headers.as_deref(),
+            body_size: record.body_size,
+            body_path: record.body_path.as_deref(),
+            source_file: record.source_file.to_string_lossy().to_string(),
+            tool_version: &self.tool_version,
+            config_hash: &self.config_hash,
+            evidence_path: &self.evidence_path,
+            evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
+        };
+        let mut guard = self
+            .cache_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("cache writer lock poisoned".into()))?;
+        guard.serialize(record)?;
+        Ok(())
+    }
+
+    fn record_log_event(&self, record: &LogEventRecord) -> Result<(), MetadataError> {
+        let record = LogEventCsv {
+            run_id: &record.run_id,
+            source: &record.source,
+            timestamp: record.timestamp.map(|t| t.to_string()),
+            timestamp_raw: record.timestamp_raw.as_deref(),
+            host: record.host.as_deref(),
+            program: record.program.as_deref(),
+            pid: record.pid,
+            severity: record.severity,
+            message: &record.message,
+            global_offset: record.global_offset,
+            source_file: record.source_file.as_deref(),
+            tool_version: &self.tool_version,
+            config_hash: &self.config_hash,
+            evidence_path: &self.evidence_path,
+            evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
+        };
+        let mut guard = self
+            .log_events_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("log events writer lock poisoned".into()))?;
+        guard.serialize(record)?;
+        Ok(())
+    }
+
+    fn record_container(&self, record: &ContainerArtefactRecord) -> Result<(), MetadataError> {
+        let record = ContainerArtefactCsv {
+            run_id: &record.run_id,
+            kind: &record.kind,
+            name: record.name.as_deref(),
+            digest: record.digest.as_deref(),
+            namespace: record.namespace.as_deref(),
+            detail: record.detail.as_deref(),
+            global_offset: record.global_offset,
+            source_file: &record.source_file,
+            tool_version: &self.tool_version,
+            config_hash: &self.config_hash,
+            evidence_path: &self.evidence_path,
+            evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
+        };
+        let mut guard = self
+            .container_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("container writer lock poisoned".into()))?;
+        guard.serialize(record)?;
+        Ok(())
+    }
+
+    fn record_run_summary(&self, summary: &RunSummary) -> Result<(), MetadataError> {
+        let record = RunSummaryCsv {
+            run_id: &summary.run_id,
+            bytes_scanned: summary.bytes_scanned,
+            chunks_processed: summary.chunks_processed,
+            hits_found: summary.hits_found,
+            files_carved: summary.files_carved,
+            string_spans: summary.string_spans,
+            artefacts_extracted: summary.artefacts_extracted,
+            tool_version: &self.tool_version,
+            config_hash: &self.config_hash,
+            evidence_path: &self.evidence_path,
+            evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
+        };
+        let mut guard = self
+            .run_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("run writer lock poisoned".into()))?;
+        guard.serialize(record)?;
+        Ok(())
+    }
+
+    fn record_resource_usage(&self, usage: &ResourceUsage) -> Result<(), MetadataError> {
+        let record = ResourceUsageCsv {
+            run_id: &usage.run_id,
+            stage: &usage.stage,
+            threads: usage.threads,
+            busy_secs: usage.busy_secs,
+            user_cpu_secs: usage.user_cpu_secs,
+            sys_cpu_secs: usage.sys_cpu_secs,
+            bytes_read: usage.bytes_read,
+            bytes_written: usage.bytes_written,
+            gpu_secs: usage.gpu_secs,
+            peak_rss_bytes: usage.peak_rss_bytes,
+            tool_version: &self.tool_version,
+            config_hash: &self.config_hash,
+            evidence_path: &self.evidence_path,
+            evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
+        };
+        let mut guard = self
+            .resource_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("resource writer lock poisoned".into()))?;
+        guard.serialize(record)?;
+        Ok(())
+    }
+
+    fn record_message(&self, record: &MessageRecord) -> Result<(), MetadataError> {
+        let record = MessageCsv {
+            run_id: &record.run_id,
+            app: &record.app,
+            message_id: record.message_id.as_deref(),
+            thread: record.thread.as_deref(),
+            handle: record.handle.as_deref(),
+            is_from_me: record.is_from_me,
+            service: record.service.as_deref(),
+            text: record.text.as_deref(),
+            timestamp_utc: record.timestamp_utc.map(|t| t.to_string()),
+            modified_utc: record.modified_utc.map(|t| t.to_string()),
+            source_file: &record.source_file,
+            tool_version: &self.tool_version,
+            config_hash: &self.config_hash,
+            evidence_path: &self.evidence_path,
+            evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
+        };
+        let mut guard = self
+            .message_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("message writer lock poisoned".into()))?;
+        guard.serialize(record)?;
+        Ok(())
+    }
+
+    fn record_usage_event(&self, record: &UsageEventRecord) -> Result<(), MetadataError> {
+        let record = UsageEventCsv {
+            run_id: &record.run_id,
+            source: &record.source,
+            stream: &record.stream,
+            value: record.value.as_deref(),
+            bundle_id: record.bundle_id.as_deref(),
+            start_utc: record.start_utc.map(|t| t.to_string()),
+            end_utc: record.end_utc.map(|t| t.to_string()),
+            source_file: &record.source_file,
+            tool_version: &self.tool_version,
+            config_hash: &self.config_hash,
+            evidence_path: &self.evidence_path,
+            evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
+        };
+        let mut guard = self
+            .usage_event_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("usage event writer lock poisoned".into()))?;
+        guard.serialize(record)?;
+        Ok(())
+    }
+
+    fn record_app_record(&self, record: &AppRecord) -> Result<(), MetadataError> {
+        let record = AppCsv {
+            run_id: &record.run_id,
+            app: &record.app,
+            plan: &record.plan,
+            row_id: record.row_id,
+            timestamp_utc: record.timestamp_utc.map(|t| t.to_string()),
+            timestamp_desc: record.timestamp_desc.as_deref(),
+            fields: &record.fields,
+            source_file: &record.source_file,
+            tool_version: &self.tool_version,
+            config_hash: &self.config_hash,
+            evidence_path: &self.evidence_path,
+            evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
+        };
+        let mut guard = self
+            .app_record_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("app record writer lock poisoned".into()))?;
+        guard.serialize(record)?;
+        Ok(())
+    }
+
+    fn record_prefetch(&self, record: &PrefetchRecord) -> Result<(), MetadataError> {
+        let record = PrefetchCsv {
+            run_id: &record.run_id,
+            executable: &record.executable,
+            prefetch_hash: &record.prefetch_hash,
+            format_version: record.format_version,
+            compressed: record.compressed,
+            run_count: record.run_count,
+            last_run_utc: record.last_run_utc.map(|t| t.to_string()),
+            earlier_runs_utc: record.earlier_runs_utc.as_deref(),
+            source_file: &record.source_file,
+            tool_version: &self.tool_version,
+            config_hash: &self.config_hash,
+            evidence_path: &self.evidence_path,
+            evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
+        };
+        let mut guard = self
+            .prefetch_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("prefetch writer lock poisoned".into()))?;
+        guard.serialize(record)?;
+        Ok(())
+    }
+
+    fn record_shortcut(&self, record: &ShortcutRecord) -> Result<(), MetadataError> {
+        let record = ShortcutCsv {
+            run_id: &record.run_id,
+            target_path: record.target_path.as_deref(),
+            arguments: record.arguments.as_deref(),
+            working_dir: record.working_dir.as_deref(),
+            relative_path: record.relative_path.as_deref(),
+            description: record.description.as_deref(),
+            icon_location: record.icon_location.as_deref(),
+            target_size: record.target_size,
+            file_attributes: record.file_attributes,
+            target_created_utc: record.target_created_utc.map(|t| t.to_string()),
+            target_accessed_utc: record.target_accessed_utc.map(|t| t.to_string()),
+            target_modified_utc: record.target_modified_utc.map(|t| t.to_string()),
+            volume_serial: record.volume_serial.as_deref(),
+            machine_id: record.machine_id.as_deref(),
+            source_file: &record.source_file,
+            tool_version: &self.tool_version,
+            config_hash: &self.config_hash,
+            evidence_path: &self.evidence_path,
+            evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
+        };
+        let mut guard = self
+            .shortcut_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("shortcut writer lock poisoned".into()))?;
+        guard.serialize(record)?;
+        Ok(())
+    }
+
+    fn record_executable(&self, record: &ExecutableRecord) -> Result<(), MetadataError> {
+        let record = ExecutableCsv {
+            run_id: &record.run_id,
+            format: &record.format,
+            kind: &record.kind,
+            architecture: &record.architecture,
+            bits: record.bits,
+            compile_time_utc: record.compile_time_utc.map(|t| t.to_string()),
+            subsystem: record.subsystem.as_deref(),
+            section_count: record.section_count,
+            import_count: record.import_count,
+            import_hash: record.import_hash.as_deref(),
+            signed: record.signed,
+            source_file: &record.source_file,
+            tool_version: &self.tool_version,
+            config_hash: &self.config_hash,
+            evidence_path: &self.evidence_path,
+            evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
+        };
+        let mut guard = self
+            .executable_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("executable writer lock poisoned".into()))?;
+        guard.serialize(record)?;
+        Ok(())
+    }
 
-impl CsvSink {
-    pub fn new(
-        _run_id: &str,
-        tool_version: &str,
-        config_hash: &str,
-        evidence_path: &Path,
-        evidence_sha256: &str,
-        run_output_dir: &Path,
-    ) -> Result<Self, MetadataError> {
-        let meta_dir = run_output_dir.join("metadata");
-        std::fs::create_dir_all(&meta_dir)?;
+    fn record_thumbnail(&self, record: &ThumbnailRecord) -> Result<(), MetadataError> {
+        let record = ThumbnailCsv {
+            run_id: &record.run_id,
+            cache_id: &record.cache_id,
+            cache_version: &record.cache_version,
+            cache_type: record.cache_type,
+            entry_offset: record.entry_offset,
+            width: record.width,
+            height: record.height,
+            image_format: &record.image_format,
+            data_size: record.data_size,
+            image_path: &record.image_path,
+            source_file: &record.source_file,
+            tool_version: &self.tool_version,
+            config_hash: &self.config_hash,
+            evidence_path: &self.evidence_path,
+            evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
+        };
+        let mut guard = self
+            .thumbnail_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("thumbnail writer lock poisoned".into()))?;
+        guard.serialize(record)?;
+        Ok(())
+    }
 
-        let files_file = File::create(meta_dir.join("carved_files.csv"))?;
-        let strings_file = File::create(meta_dir.join("string_artefacts.csv"))?;
-        let history_file = File::create(meta_dir.join("browser_history.csv"))?;
-        let cookies_file = File::create(meta_dir.join("browser_cookies.csv"))?;
-        let downloads_file = File::create(meta_dir.join("browser_downloads.csv"))?;
-        let run_file = File::create(meta_dir.join("run_summary.csv"))?;
-        let entropy_file = File::create(meta_dir.join("entropy_regions.csv"))?;
+    fn record_plist(&self, record: &PlistRecord) -> Result<(), MetadataError> {
+        let record = PlistCsv {
+            run_id: &record.run_id,
+            object_count: record.object_count,
+            top_type: &record.top_type,
+            top_keys: record.top_keys.as_deref(),
+            archiver: record.archiver.as_deref(),
+            json_len: record.json_len,
+            json: record.json.as_deref(),
+            source_file: &record.source_file,
+            tool_version: &self.tool_version,
+            config_hash: &self.config_hash,
+            evidence_path: &self.evidence_path,
+            evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
+        };
+        let mut guard = self
+            .plist_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("plist writer lock poisoned".into()))?;
+        guard.serialize(record)?;
+        Ok(())
+    }
 
-        let mut files_writer = csv::WriterBuilder::new()
-            .has_headers(false)
-            .from_writer(files_file);
-        let mut strings_writer = csv::WriterBuilder::new()
-            .has_headers(false)
-            .from_writer(strings_file);
-        let mut history_writer = csv::WriterBuilder::new()
-            .has_headers(false)
-            .from_writer(history_file);
-        let mut cookies_writer = csv::WriterBuilder::new()
-            .has_headers(false)
-            .from_writer(cookies_file);
-        let mut downloads_writer = csv::WriterBuilder::new()
-            .has_headers(false)
-            .from_writer(downloads_file);
-        let mut run_writer = csv::WriterBuilder::new()
-            .has_headers(false)
-            .from_writer(run_file);
-        let mut entropy_writer = csv::WriterBuilder::new()
-            .has_headers(false)
-            .from_writer(entropy_file);
+    fn record_video_repair(&self, record: &VideoRepairRecord) -> Result<(), MetadataError> {
+        let record = VideoRepairCsv {
+            run_id: &record.run_id,
+            method: &record.method,
+            codec: &record.codec,
+            width: record.width,
+            height: record.height,
+            frame_count: record.frame_count,
+            keyframe_count: record.keyframe_count,
+            duration_secs: record.duration_secs,
+            media_bytes: record.media_bytes,
+            skipped_bytes: record.skipped_bytes,
+            donor_file: record.donor_file.as_deref(),
+            repaired_path: &record.repaired_path,
+            source_file: &record.source_file,
+            tool_version: &self.tool_version,
+            config_hash: &self.config_hash,
+            evidence_path: &self.evidence_path,
+            evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
+        };
+        let mut guard = self
+            .video_repair_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("video repair writer lock poisoned".into()))?;
+        guard.serialize(record)?;
+        Ok(())
+    }
 
-        files_writer.write_record(&[
-            "run_id",
-            "file_type",
-            "path",
-            "extension",
-            "global_start",
-            "global_end",
-            "size",
-            "md5",
-            "sha256",
-            "validated",
-            "truncated",
-            "errors",
-            "pattern_id",
-            "tool_version",
-            "config_hash",
-            "evidence_path",
-            "evidence_sha256",
-        ])?;
-
-        strings_writer.write_record(&[
-            "run_id",
-            "artefact_kind",
-            "content",
-            "encoding",
-            "global_start",
-            "global_end",
-            "tool_version",
-            "config_hash",
-            "evidence_path",
-            "evidence_sha256",
-        ])?;
-
-        history_writer.write_record(&[
-            "run_id",
-            "browser",
-            "profile",
-            "url",
-            "title",
-            "visit_time",
-            "visit_source",
-            "source_file",
-            "tool_version",
-            "config_hash",
-            "evidence_path",
-            "evidence_sha256",
-        ])?;
-
-        cookies_writer.write_record(&[
-            "run_id",
-            "browser",
-            "profile",
-            "host",
-            "name",
-            "value",
-            "path",
-            "expires_utc",
-            "last_access_utc",
-            "creation_utc",
-            "is_secure",
-            "is_http_only",
-            "source_file",
-            "tool_version",
-            "config_hash",
-            "evidence_path",
-            "evidence_sha256",
-        ])?;
-
-        downloads_writer.write_record(&[
-            "run_id",
-            "browser",
-            "profile",
-            "url",
-            "target_path",
-            "start_time",
-            "end_time",
-            "total_bytes",
-            "state",
-            "source_file",
-            "tool_version",
-            "config_hash",
-            "evidence_path",
-            "evidence_sha256",
-        ])?;
-
-        run_writer.write_record(&[
-            "run_id",
-            "bytes_scanned",
-            "chunks_processed",
-            "hits_found",
-            "files_carved",
-            "string_spans",
-            "artefacts_extracted",
-            "tool_version",
-            "config_hash",
-            "evidence_path",
-            "evidence_sha256",
-        ])?;
-
-        entropy_writer.write_record(&[
-            "run_id",
-            "global_start",
-            "global_end",
-            "entropy",
-            "window_size",
-            "tool_version",
-            "config_hash",
-            "evidence_path",
-            "evidence_sha256",
-        ])?;
+    fn record_image_info(&self, record: &ImageInfoRecord) -> Result<(), MetadataError> {
+        let record = ImageInfoCsv {
+            run_id: &record.run_id,
+            format: &record.format,
+            width: record.width,
+            height: record.height,
+            bits_per_pixel: record.bits_per_pixel,
+            decoded_pct: record.decoded_pct,
+            corrupt_offset: record.corrupt_offset,
+            source_file: &record.source_file,
+            tool_version: &self.tool_version,
+            config_hash: &self.config_hash,
+            evidence_path: &self.evidence_path,
+            evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
+        };
+        let mut guard = self
+            .image_info_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("image info writer lock poisoned".into()))?;
+        guard.serialize(record)?;
+        Ok(())
+    }
 
-        Ok(Self {
-            tool_version: tool_version.to_string(),
-            config_hash: config_hash.to_string(),
-            evidence_path: evidence_path.to_string_lossy().to_string(),
-            evidence_sha256: evidence_sha256.to_string(),
-            files_writer: Mutex::new(files_writer),
-            strings_writer: Mutex::new(strings_writer),
-            history_writer: Mutex::new(history_writer),
-            cookies_writer: Mutex::new(cookies_writer),
-            downloads_writer: Mutex::new(downloads_writer),
-            run_writer: Mutex::new(run_writer),
-            entropy_writer: Mutex::new(entropy_writer),
-        })
+    fn record_photo_device(&self, record: &PhotoDeviceRecord) -> Result<(), MetadataError> {
+        let record = PhotoDeviceCsv {
+            run_id: &record.run_id,
+            source_file: &record.source_file,
+            global_start: record.global_start,
+            make: record.make.as_deref(),
+            model: record.model.as_deref(),
+            serial: record.serial.as_deref(),
+            lens: record.lens.as_deref(),
+            lens_serial: record.lens_serial.as_deref(),
+            taken_at: record.taken_at.map(|t| t.to_string()),
+            latitude: record.latitude,
+            longitude: record.longitude,
+            device_id: record.device_id.as_deref(),
+            session_id: record.session_id.as_deref(),
+            tool_version: &self.tool_version,
+            config_hash: &self.config_hash,
+            evidence_path: &self.evidence_path,
+            evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
+        };
+        let mut guard = self
+            .photo_device_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("photo device writer lock poisoned".into()))?;
+        guard.serialize(record)?;
+        Ok(())
     }
-}
 
-impl MetadataSink for CsvSink {
-    fn record_file(&self, file: &CarvedFile) -> Result<(), MetadataError> {
-        let record = CarvedFileCsv {
-            run_id: &file.run_id,
-            file_type: &file.file_type,
-            path: &file.path,
-            extension: &file.extension,
-            global_start: file.global_start,
-            global_end: file.global_end,
-            size: file.size,
-            md5: file.md5.as_deref(),
-            sha256: file.sha256.as_deref(),
-            validated: file.validated,
-            truncated: file.truncated,
-            errors: file.errors.join("; "),
-            pattern_id: file.pattern_id.as_deref(),
+    fn record_ntfs_file(&self, record: &NtfsFileRecord) -> Result<(), MetadataError> {
+        let time = |t: Option<chrono::NaiveDateTime>| t.map(|t| t.to_string());
+        let record = NtfsFileCsv {
+            run_id: &record.run_id,
+            volume_offset: record.volume_offset,
+            mft_record: record.mft_record,
+            sequence: record.sequence,
+            path: &record.path,
+            name: &record.name,
+            in_use: record.in_use,
+            directory: record.directory,
+            size: record.size,
+            resident: record.resident,
+            data_runs: &record.data_runs,
+            created: time(record.created),
+            modified: time(record.modified),
+            mft_modified: time(record.mft_modified),
+            accessed: time(record.accessed),
+            carved_path: record.carved_path.as_deref(),
             tool_version: &self.tool_version,
             config_hash: &self.config_hash,
             evidence_path: &self.evidence_path,
             evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
         };
         let mut guard = self
-            .files_writer
+            .ntfs_file_writer
             .lock()
-            .map_err(|_| MetadataError::Other("files writer lock poisoned".into()))?;
+            .map_err(|_| MetadataError::Other("ntfs file writer lock poisoned".into()))?;
         guard.serialize(record)?;
         Ok(())
     }
 
-    fn record_string(&self, artefact: &StringArtefact) -> Result<(), MetadataError> {
-        let record = StringArtefactCsv {
-            run_id: &artefact.run_id,
-            artefact_kind: artefact_kind_label(&artefact.artefact_kind),
-            content: &artefact.content,
-            encoding: &artefact.encoding,
-            global_start: artefact.global_start,
-            global_end: artefact.global_end,
+    fn record_input_file(&self, record: &InputFileRecord) -> Result<(), MetadataError> {
+        let record = InputFileCsv {
+            run_id: &record.run_id,
+            kind: record.kind.as_str(),
+            path: &record.path,
+            sha256: &record.sha256,
             tool_version: &self.tool_version,
             config_hash: &self.config_hash,
             evidence_path: &self.evidence_path,
             evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
         };
         let mut guard = self
-            .strings_writer
+            .input_file_writer
             .lock()
-            .map_err(|_| MetadataError::Other("strings writer lock poisoned".into()))?;
+            .map_err(|_| MetadataError::Other("input file writer lock poisoned".into()))?;
         guard.serialize(record)?;
         Ok(())
     }
 
-    fn record_history(
+    fn record_encrypted_document(
         &self,
-        record: &crate::parsers::browser::BrowserHistoryRecord,
+        record: &EncryptedDocumentRecord,
     ) -> Result<(), MetadataError> {
-        let record = BrowserHistoryCsv {
+        let record = EncryptedDocumentCsv {
             run_id: &record.run_id,
-            browser: &record.browser,
-            profile: &record.profile,
-            url: &record.url,
-            title: record.title.as_deref(),
-            visit_time: record.visit_time.map(|t| t.to_string()),
-            visit_source: record.visit_source.as_deref(),
-            source_file: record.source_file.to_string_lossy().to_string(),
+            encryption_type: &record.encryption_type,
+            version: record.version.as_deref(),
+            cipher_algorithm: record.cipher_algorithm.as_deref(),
+            key_bits: record.key_bits,
+            hash_algorithm: record.hash_algorithm.as_deref(),
+            spin_count: record.spin_count,
+            csp_name: record.csp_name.as_deref(),
+            original_size: record.original_size,
+            source_file: &record.source_file,
             tool_version: &self.tool_version,
             config_hash: &self.config_hash,
             evidence_path: &self.evidence_path,
             evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
         };
         let mut guard = self
-            .history_writer
+            .encrypted_document_writer
             .lock()
-            .map_err(|_| MetadataError::Other("history writer lock poisoned".into()))?;
+            .map_err(|_| MetadataError::Other("encrypted document writer lock poisoned".into()))?;
         guard.serialize(record)?;
         Ok(())
     }
 
-    fn record_cookie(&self, record: &BrowserCookieRecord) -> Result<(), MetadataError> {
-        let record = BrowserCookieCsv {
+    fn record_carve_size(&self, record: &CarveSizeRecord) -> Result<(), MetadataError> {
+        let record = CarveSizeCsv {
             run_id: &record.run_id,
-            browser: &record.browser,
-            profile: &record.profile,
-            host: &record.host,
-            name: &record.name,
-            value: record.value.as_deref(),
-            path: record.path.as_deref(),
-            expires_utc: record.expires_utc.map(|dt| dt.to_string()),
-            last_access_utc: record.last_access_utc.map(|dt| dt.to_string()),
-            creation_utc: record.creation_utc.map(|dt| dt.to_string()),
-            is_secure: record.is_secure,
-            is_http_only: record.is_http_only,
-            source_file: record.source_file.to_string_lossy().to_string(),
+            file_type: &record.file_type,
+            path: &record.path,
+            global_start: record.global_start,
+            size: record.size,
+            declared_size: record.declared_size,
+            declared_by: record.declared_by.as_deref(),
+            truncation: record.truncation,
             tool_version: &self.tool_version,
             config_hash: &self.config_hash,
             evidence_path: &self.evidence_path,
             evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
         };
         let mut guard = self
-            .cookies_writer
+            .carve_size_writer
             .lock()
-            .map_err(|_| MetadataError::Other("cookies writer lock poisoned".into()))?;
+            .map_err(|_| MetadataError::Other("carve size writer lock poisoned".into()))?;
         guard.serialize(record)?;
         Ok(())
     }
 
-    fn record_download(&self, record: &BrowserDownloadRecord) -> Result<(), MetadataError> {
-        let record = BrowserDownloadCsv {
+    fn record_carve_companion(&self, record: &CarveCompanionRecord) -> Result<(), MetadataError> {
+        let record = CarveCompanionCsv {
             run_id: &record.run_id,
-            browser: &record.browser,
-            profile: &record.profile,
-            url: record.url.as_deref(),
-            target_path: record.target_path.as_deref(),
-            start_time: record.start_time.map(|dt| dt.to_string()),
-            end_time: record.end_time.map(|dt| dt.to_string()),
-            total_bytes: record.total_bytes,
-            state: record.state.as_deref(),
-            source_file: record.source_file.to_string_lossy().to_string(),
+            file_type: &record.file_type,
+            path: &record.path,
+            global_start: record.global_start,
+            companion_type: &record.companion_type,
+            companion_path: &record.companion_path,
+            companion_start: record.companion_start,
+            companion_size: record.companion_size,
             tool_version: &self.tool_version,
             config_hash: &self.config_hash,
             evidence_path: &self.evidence_path,
             evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
         };
         let mut guard = self
-            .downloads_writer
+            .carve_companion_writer
             .lock()
-            .map_err(|_| MetadataError::Other("downloads writer lock poisoned".into()))?;
+            .map_err(|_| MetadataError::Other("carve companion writer lock poisoned".into()))?;
         guard.serialize(record)?;
         Ok(())
     }
 
-    fn record_run_summary(&self, summary: &RunSummary) -> Result<(), MetadataError> {
-        let record = RunSummaryCsv {
-            run_id: &summary.run_id,
-            bytes_scanned: summary.bytes_scanned,
-            chunks_processed: summary.chunks_processed,
-            hits_found: summary.hits_found,
-            files_carved: summary.files_carved,
-            string_spans: summary.string_spans,
-            artefacts_extracted: summary.artefacts_extracted,
+    fn record_volume_location(&self, record: &VolumeLocationRecord) -> Result<(), MetadataError> {
+        let record = VolumeLocationCsv {
+            run_id: &record.run_id,
+            subject: &record.subject,
+            reference: &record.reference,
+            global_start: record.global_start,
+            partition: record.partition,
+            partition_offset: record.partition_offset,
+            relative_offset: record.relative_offset,
+            relative_sector: record.relative_sector,
+            file_system: record.file_system.as_deref(),
+            cluster: record.cluster,
+            cluster_chain: record.cluster_chain.as_deref(),
             tool_version: &self.tool_version,
             config_hash: &self.config_hash,
             evidence_path: &self.evidence_path,
             evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
         };
         let mut guard = self
-            .run_writer
+            .volume_location_writer
             .lock()
-            .map_err(|_| MetadataError::Other("run writer lock poisoned".into()))?;
+            .map_err(|_| MetadataError::Other("volume location writer lock poisoned".into()))?;
+        guard.serialize(record)?;
+        Ok(())
+    }
+
+    fn record_classification(&self, record: &ClassificationRecord) -> Result<(), MetadataError> {
+        let record = ClassificationCsv {
+            run_id: &record.run_id,
+            file_type: &record.file_type,
+            path: &record.path,
+            global_start: record.global_start,
+            classifier: &record.classifier,
+            label: &record.label,
+            score: record.score,
+            rank: record.rank,
+            tool_version: &self.tool_version,
+            config_hash: &self.config_hash,
+            evidence_path: &self.evidence_path,
+            evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
+        };
+        let mut guard = self
+            .classification_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("classification writer lock poisoned".into()))?;
         guard.serialize(record)?;
         Ok(())
     }
@@ -472,6 +2279,7 @@ impl MetadataSink for CsvSink {
             config_hash: &self.config_hash,
             evidence_path: &self.evidence_path,
             evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
         };
         let mut guard = self
             .entropy_writer
@@ -502,10 +2310,98 @@ impl MetadataSink for CsvSink {
             .downloads_writer
             .lock()
             .map_err(|_| MetadataError::Other("downloads writer lock poisoned".into()))?;
+        let mut cache = self
+            .cache_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("cache writer lock poisoned".into()))?;
+        let mut log_events = self
+            .log_events_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("log events writer lock poisoned".into()))?;
+        let mut container = self
+            .container_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("container writer lock poisoned".into()))?;
         let mut run = self
             .run_writer
             .lock()
             .map_err(|_| MetadataError::Other("run writer lock poisoned".into()))?;
+        let mut resources = self
+            .resource_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("resource writer lock poisoned".into()))?;
+        let mut messages = self
+            .message_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("message writer lock poisoned".into()))?;
+        let mut usage_events = self
+            .usage_event_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("usage event writer lock poisoned".into()))?;
+        let mut app_records = self
+            .app_record_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("app record writer lock poisoned".into()))?;
+        let mut prefetch_files = self
+            .prefetch_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("prefetch writer lock poisoned".into()))?;
+        let mut shortcuts = self
+            .shortcut_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("shortcut writer lock poisoned".into()))?;
+        let mut executables = self
+            .executable_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("executable writer lock poisoned".into()))?;
+        let mut thumbnails = self
+            .thumbnail_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("thumbnail writer lock poisoned".into()))?;
+        let mut plists = self
+            .plist_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("plist writer lock poisoned".into()))?;
+        let mut video_repairs = self
+            .video_repair_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("video repair writer lock poisoned".into()))?;
+        let mut image_infos = self
+            .image_info_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("image info writer lock poisoned".into()))?;
+        let mut photo_devices = self
+            .photo_device_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("photo device writer lock poisoned".into()))?;
+        let mut ntfs_files = self
+            .ntfs_file_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("ntfs file writer lock poisoned".into()))?;
+        let mut input_files = self
+            .input_file_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("input file writer lock poisoned".into()))?;
+        let mut encrypted_documents = self
+            .encrypted_document_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("encrypted document writer lock poisoned".into()))?;
+        let mut carve_sizes = self
+            .carve_size_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("carve size writer lock poisoned".into()))?;
+        let mut carve_companions = self
+            .carve_companion_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("carve companion writer lock poisoned".into()))?;
+        let mut volume_locations = self
+            .volume_location_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("volume location writer lock poisoned".into()))?;
+        let mut classifications = self
+            .classification_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("classification writer lock poisoned".into()))?;
         let mut entropy = self
             .entropy_writer
             .lock()
@@ -515,17 +2411,53 @@ impl MetadataSink for CsvSink {
         history.flush()?;
         cookies.flush()?;
         downloads.flush()?;
+        cache.flush()?;
+        log_events.flush()?;
+        container.flush()?;
         run.flush()?;
+        resources.flush()?;
+        messages.flush()?;
+        usage_events.flush()?;
+        app_records.flush()?;
+        prefetch_files.flush()?;
+        shortcuts.flush()?;
+        executables.flush()?;
+        thumbnails.flush()?;
+        plists.flush()?;
+        video_repairs.flush()?;
+        image_infos.flush()?;
+        photo_devices.flush()?;
+        ntfs_files.flush()?;
+        input_files.flush()?;
+        encrypted_documents.flush()?;
+        carve_sizes.flush()?;
+        carve_companions.flush()?;
+        volume_locations.flush()?;
+        classifications.flush()?;
         entropy.flush()?;
         Ok(())
     }
 }
 
-fn artefact_kind_label(kind: &ArtefactKind) -> &'static str {
+pub(crate) fn artefact_kind_label(kind: &ArtefactKind) -> &'static str {
     match kind {
         ArtefactKind::Url => "url",
         ArtefactKind::Email => "email",
         ArtefactKind::Phone => "phone",
+        ArtefactKind::HttpHost => "http_host",
+        ArtefactKind::DnsQuery => "dns_query",
+        ArtefactKind::Connection => "connection",
+        ArtefactKind::SshPublicKey => "ssh_public_key",
+        ArtefactKind::SshPrivateKey => "ssh_private_key",
+        ArtefactKind::SshKnownHost => "ssh_known_host",
+        ArtefactKind::ShellHistory => "shell_history",
+        ArtefactKind::Credential => "credential",
+        ArtefactKind::MagnetUri => "magnet_uri",
+        ArtefactKind::Ed2kLink => "ed2k_link",
+        ArtefactKind::TorrentInfoHash => "torrent_info_hash",
+        ArtefactKind::TorrentName => "torrent_name",
+        ArtefactKind::TorrentTracker => "torrent_tracker",
+        ArtefactKind::DhtNode => "dht_node",
         ArtefactKind::GenericString => "string",
     }
 }
@@ -539,19 +2471,20 @@ mod tests {
     #[test]
     fn writes_csv_files() {
         let dir = tempdir().expect("tempdir");
-        let sink = CsvSink::new(
-            "run1",
-            "0.1.0",
-            "hash",
-            Path::new("/evidence.dd"),
-            "",
-            dir.path(),
-        )
-        .expect("csv sink");
+        let identity = SinkIdentity {
+            run_id: "run1",
+            tool_version: "0.1.0",
+            config_hash: "hash",
+            evidence_path: Path::new("/evidence.dd"),
+            evidence_sha256: "",
+            pass_id: 1,
+        };
+        let sink = CsvSink::new(&identity, dir.path(), None).expect("csv sink");
 
         let file = CarvedFile {
             run_id: "run1".to_string(),
             file_type: "jpeg".to_string(),
+            signature_type: "jpeg".to_string(),
             path: "jpeg/file.jpg".to_string(),
             extension: "jpg".to_string(),
             global_start: 0,
@@ -573,6 +2506,8 @@ mod tests {
             encoding: "ascii".to_string(),
             global_start: 100,
             global_end: 120,
+            source_kind: "string_span".to_string(),
+            source_detail: "chunk=0 span=100-120".to_string(),
         };
         sink.record_string(&artefact).expect("record string");
 
@@ -677,4 +2612,77 @@ mod tests {
                 .exists()
         );
     }
+
+    #[test]
+    fn later_pass_appends_rows_without_header() {
+        let dir = tempdir().expect("tempdir");
+        for pass_id in [1, 2] {
+            let identity = SinkIdentity {
+                run_id: "run1",
+                tool_version: "0.1.0",
+                config_hash: "hash",
+                evidence_path: Path::new("/evidence.dd"),
+                evidence_sha256: "",
+                pass_id,
+            };
+            let sink = CsvSink::new(&identity, dir.path(), None).expect("csv sink");
+            let summary = RunSummary {
+                run_id: "run1".to_string(),
+                bytes_scanned: 10,
+                chunks_processed: 1,
+                hits_found: 2,
+                files_carved: 1,
+                string_spans: 0,
+                artefacts_extracted: 0,
+            };
+            sink.record_run_summary(&summary).expect("record summary");
+            sink.flush().expect("flush");
+        }
+
+        let text = std::fs::read_to_string(dir.path().join("metadata").join("run_summary.csv"))
+            .expect("read");
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].contains("pass_id"));
+        assert!(lines[1].contains(",1,") && lines[2].contains(",2,"));
+    }
+
+    #[test]
+    fn encrypted_streams_decrypt_to_the_same_rows() {
+        let dir = tempdir().expect("tempdir");
+        let key = age::x25519::Identity::generate();
+        let cipher = OutputCipher::new(key.to_public());
+        let mut identity = SinkIdentity {
+            run_id: "run1",
+            tool_version: "0.1.0",
+            config_hash: "hash",
+            evidence_path: Path::new("/evidence.dd"),
+            evidence_sha256: "",
+            pass_id: 1,
+        };
+        let sink = CsvSink::new(&identity, dir.path(), Some(&cipher)).expect("csv sink");
+        let summary = RunSummary {
+            run_id: "run1".to_string(),
+            bytes_scanned: 10,
+            chunks_processed: 1,
+            hits_found: 2,
+            files_carved: 1,
+            string_spans: 0,
+            artefacts_extracted: 0,
+        };
+        sink.record_run_summary(&summary).expect("record summary");
+        sink.flush().expect("flush");
+        drop(sink);
+
+        let meta = dir.path().join("metadata");
+        assert!(!meta.join("run_summary.csv").exists());
+        let sealed = std::fs::read(meta.join("run_summary.csv.age")).expect("read");
+        let text = String::from_utf8(age::decrypt(&key, &sealed).expect("decrypt")).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains("pass_id") && lines[1].contains("run1"));
+
+        identity.pass_id = 2;
+        assert!(CsvSink::new(&identity, dir.path(), Some(&cipher)).is_err());
+    }
 }
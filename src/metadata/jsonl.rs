@@ -1,4 +1,3 @@
-use std::fs::File;
 use std::io::{BufWriter, Write};
 use std::path::Path;
 use std::sync::Mutex;
@@ -6,24 +5,72 @@ use std::sync::Mutex;
 use serde::Serialize;
 
 use crate::carve::CarvedFile;
-use crate::metadata::{EntropyRegion, MetadataError, MetadataSink, RunSummary};
+use crate::encryption::{OutputCipher, OutputFile};
+use crate::inputs::InputFileRecord;
+use crate::metadata::memory::{MemoryStream, MemoryStreams};
+use crate::metadata::{
+    CarveCompanionRecord, CarveSizeRecord, ClassificationRecord, EntropyRegion, MetadataError,
+    MetadataSink, ResourceUsage, RunSummary, SinkIdentity, VolumeLocationRecord, open_stream,
+};
+use crate::parsers::apple::MessageRecord;
+use crate::parsers::apple::UsageEventRecord;
+use crate::parsers::bplist::PlistRecord;
 use crate::parsers::browser::{
-    BrowserCookieRecord as CookieRecord, BrowserDownloadRecord as DownloadRecord,
+    BrowserCacheRecord as CacheRecord, BrowserCookieRecord as CookieRecord,
+    BrowserDownloadRecord as DownloadRecord,
 };
-use crate::strings::artifacts::StringArtefact;
+use crate::parsers::container::ContainerArtefactRecord;
+use crate::parsers::executable::ExecutableRecord;
+use crate::parsers::exif::PhotoDeviceRecord;
+use crate::parsers::image::ImageInfoRecord;
+use crate::parsers::lnk::ShortcutRecord;
+use crate::parsers::logs::LogEventRecord;
+use crate::parsers::mp4::VideoRepairRecord;
+use crate::parsers::ntfs::NtfsFileRecord;
+use crate::parsers::office_crypto::EncryptedDocumentRecord;
+use crate::parsers::prefetch::PrefetchRecord;
+use crate::parsers::sqlite_apps::AppRecord;
+use crate::parsers::thumbcache::ThumbnailRecord;
+use crate::strings::artifacts::{ArtefactKind, StringArtefact};
+use crate::strings::url::decode_url;
 
-pub struct JsonlSink {
+/// Writes each metadata stream as JSON lines; `W` is a file per stream for
+/// runs with an output directory and a memory buffer for in-memory runs.
+pub struct JsonlSink<W: Write = OutputFile> {
     tool_version: String,
     config_hash: String,
     evidence_path: String,
     evidence_sha256: String,
-    files_writer: Mutex<BufWriter<File>>,
-    strings_writer: Mutex<BufWriter<File>>,
-    history_writer: Mutex<BufWriter<File>>,
-    cookies_writer: Mutex<BufWriter<File>>,
-    downloads_writer: Mutex<BufWriter<File>>,
-    run_writer: Mutex<BufWriter<File>>,
-    entropy_writer: Mutex<BufWriter<File>>,
+    pass_id: u32,
+    files_writer: Mutex<BufWriter<W>>,
+    strings_writer: Mutex<BufWriter<W>>,
+    history_writer: Mutex<BufWriter<W>>,
+    cookies_writer: Mutex<BufWriter<W>>,
+    downloads_writer: Mutex<BufWriter<W>>,
+    cache_writer: Mutex<BufWriter<W>>,
+    log_events_writer: Mutex<BufWriter<W>>,
+    container_writer: Mutex<BufWriter<W>>,
+    run_writer: Mutex<BufWriter<W>>,
+    resource_writer: Mutex<BufWriter<W>>,
+    message_writer: Mutex<BufWriter<W>>,
+    usage_event_writer: Mutex<BufWriter<W>>,
+    app_record_writer: Mutex<BufWriter<W>>,
+    prefetch_writer: Mutex<BufWriter<W>>,
+    shortcut_writer: Mutex<BufWriter<W>>,
+    executable_writer: Mutex<BufWriter<W>>,
+    thumbnail_writer: Mutex<BufWriter<W>>,
+    plist_writer: Mutex<BufWriter<W>>,
+    video_repair_writer: Mutex<BufWriter<W>>,
+    image_info_writer: Mutex<BufWriter<W>>,
+    photo_device_writer: Mutex<BufWriter<W>>,
+    ntfs_file_writer: Mutex<BufWriter<W>>,
+    input_file_writer: Mutex<BufWriter<W>>,
+    encrypted_document_writer: Mutex<BufWriter<W>>,
+    carve_size_writer: Mutex<BufWriter<W>>,
+    carve_companion_writer: Mutex<BufWriter<W>>,
+    volume_location_writer: Mutex<BufWriter<W>>,
+    classification_writer: Mutex<BufWriter<W>>,
+    entropy_writer: Mutex<BufWriter<W>>,
 }
 
 #[derive(Serialize)]
@@ -34,16 +81,23 @@ struct CarvedFileRecord<'a> {
     config_hash: &'a str,
     evidence_path: &'a str,
     evidence_sha256: &'a str,
+    pass_id: u32,
 }
 
 #[derive(Serialize)]
 struct StringArtefactRecord<'a> {
     #[serde(flatten)]
     artefact: &'a StringArtefact,
+    /// URLs only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    host_unicode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path_decoded: Option<String>,
     tool_version: &'a str,
     config_hash: &'a str,
     evidence_path: &'a str,
     evidence_sha256: &'a str,
+    pass_id: u32,
 }
 
 #[derive(Serialize)]
@@ -54,6 +108,7 @@ struct BrowserHistoryRecord<'a> {
     config_hash: &'a str,
     evidence_path: &'a str,
     evidence_sha256: &'a str,
+    pass_id: u32,
 }
 
 #[derive(Serialize)]
@@ -64,6 +119,7 @@ struct BrowserCookieRecord<'a> {
     config_hash: &'a str,
     evidence_path: &'a str,
     evidence_sha256: &'a str,
+    pass_id: u32,
 }
 
 #[derive(Serialize)]
@@ -74,6 +130,18 @@ struct BrowserDownloadRecord<'a> {
     config_hash: &'a str,
     evidence_path: &'a str,
     evidence_sha256: &'a str,
+    pass_id: u32,
+}
+
+#[derive(Serialize)]
+struct BrowserCacheRecord<'a> {
+    #[serde(flatten)]
+    record: &'a CacheRecord,
+    tool_version: &'a str,
+    config_hash: &'a str,
+    evidence_path: &'a str,
+    evidence_sha256: &'a str,
+    pass_id: u32,
 }
 
 #[derive(Serialize)]
@@ -84,6 +152,238 @@ struct RunSummaryRecord<'a> {
     config_hash: &'a str,
     evidence_path: &'a str,
     evidence_sha256: &'a str,
+    pass_id: u32,
+}
+
+#[derive(Serialize)]
+struct LogEventJsonRecord<'a> {
+    #[serde(flatten)]
+    record: &'a LogEventRecord,
+    tool_version: &'a str,
+    config_hash: &'a str,
+    evidence_path: &'a str,
+    evidence_sha256: &'a str,
+    pass_id: u32,
+}
+
+#[derive(Serialize)]
+struct ContainerArtefactJsonRecord<'a> {
+    #[serde(flatten)]
+    record: &'a ContainerArtefactRecord,
+    tool_version: &'a str,
+    config_hash: &'a str,
+    evidence_path: &'a str,
+    evidence_sha256: &'a str,
+    pass_id: u32,
+}
+
+#[derive(Serialize)]
+struct ResourceUsageRecord<'a> {
+    #[serde(flatten)]
+    usage: &'a ResourceUsage,
+    tool_version: &'a str,
+    config_hash: &'a str,
+    evidence_path: &'a str,
+    evidence_sha256: &'a str,
+    pass_id: u32,
+}
+
+#[derive(Serialize)]
+struct MessageJsonRecord<'a> {
+    #[serde(flatten)]
+    record: &'a MessageRecord,
+    tool_version: &'a str,
+    config_hash: &'a str,
+    evidence_path: &'a str,
+    evidence_sha256: &'a str,
+    pass_id: u32,
+}
+
+#[derive(Serialize)]
+struct UsageEventJsonRecord<'a> {
+    #[serde(flatten)]
+    record: &'a UsageEventRecord,
+    tool_version: &'a str,
+    config_hash: &'a str,
+    evidence_path: &'a str,
+    evidence_sha256: &'a str,
+    pass_id: u32,
+}
+
+#[derive(Serialize)]
+struct AppJsonRecord<'a> {
+    #[serde(flatten)]
+    record: &'a AppRecord,
+    tool_version: &'a str,
+    config_hash: &'a str,
+    evidence_path: &'a str,
+    evidence_sha256: &'a str,
+    pass_id: u32,
+}
+
+#[derive(Serialize)]
+struct PrefetchJsonRecord<'a> {
+    #[serde(flatten)]
+    record: &'a PrefetchRecord,
+    tool_version: &'a str,
+    config_hash: &'a str,
+    evidence_path: &'a str,
+    evidence_sha256: &'a str,
+    pass_id: u32,
+}
+
+#[derive(Serialize)]
+struct ShortcutJsonRecord<'a> {
+    #[serde(flatten)]
+    record: &'a ShortcutRecord,
+    tool_version: &'a str,
+    config_hash: &'a str,
+    evidence_path: &'a str,
+    evidence_sha256: &'a str,
+    pass_id: u32,
+}
+
+#[derive(Serialize)]
+struct ExecutableJsonRecord<'a> {
+    #[serde(flatten)]
+    record: &'a ExecutableRecord,
+    tool_version: &'a str,
+    config_hash: &'a str,
+    evidence_path: &'a str,
+    evidence_sha256: &'a str,
+    pass_id: u32,
+}
+
+#[derive(Serialize)]
+struct ThumbnailJsonRecord<'a> {
+    #[serde(flatten)]
+    record: &'a ThumbnailRecord,
+    tool_version: &'a str,
+    config_hash: &'a str,
+    evidence_path: &'a str,
+    evidence_sha256: &'a str,
+    pass_id: u32,
+}
+
+#[derive(Serialize)]
+struct PlistJsonRecord<'a> {
+    #[serde(flatten)]
+    record: &'a PlistRecord,
+    tool_version: &'a str,
+    config_hash: &'a str,
+    evidence_path: &'a str,
+    evidence_sha256: &'a str,
+    pass_id: u32,
+}
+
+#[derive(Serialize)]
+struct VideoRepairJsonRecord<'a> {
+    #[serde(flatten)]
+    record: &'a VideoRepairRecord,
+    tool_version: &'a str,
+    config_hash: &'a str,
+    evidence_path: &'a str,
+    evidence_sha256: &'a str,
+    pass_id: u32,
+}
+
+#[derive(Serialize)]
+struct ImageInfoJsonRecord<'a> {
+    #[serde(flatten)]
+    record: &'a ImageInfoRecord,
+    tool_version: &'a str,
+    config_hash: &'a str,
+    evidence_path: &'a str,
+    evidence_sha256: &'a str,
+    pass_id: u32,
+}
+
+#[derive(Serialize)]
+struct PhotoDeviceJsonRecord<'a> {
+    #[serde(flatten)]
+    record: &'a PhotoDeviceRecord,
+    tool_version: &'a str,
+    config_hash: &'a str,
+    evidence_path: &'a str,
+    evidence_sha256: &'a str,
+    pass_id: u32,
+}
+
+#[derive(Serialize)]
+struct NtfsFileJsonRecord<'a> {
+    #[serde(flatten)]
+    record: &'a NtfsFileRecord,
+    tool_version: &'a str,
+    config_hash: &'a str,
+    evidence_path: &'a str,
+    evidence_sha256: &'a str,
+    pass_id: u32,
+}
+
+#[derive(Serialize)]
+struct InputFileJsonRecord<'a> {
+    #[serde(flatten)]
+    record: &'a InputFileRecord,
+    tool_version: &'a str,
+    config_hash: &'a str,
+    evidence_path: &'a str,
+    evidence_sha256: &'a str,
+    pass_id: u32,
+}
+
+#[derive(Serialize)]
+struct CarveCompanionJsonRecord<'a> {
+    #[serde(flatten)]
+    record: &'a CarveCompanionRecord,
+    tool_version: &'a str,
+    config_hash: &'a str,
+    evidence_path: &'a str,
+    evidence_sha256: &'a str,
+    pass_id: u32,
+}
+
+#[derive(Serialize)]
+struct VolumeLocationJsonRecord<'a> {
+    #[serde(flatten)]
+    record: &'a VolumeLocationRecord,
+    tool_version: &'a str,
+    config_hash: &'a str,
+    evidence_path: &'a str,
+    evidence_sha256: &'a str,
+    pass_id: u32,
+}
+
+#[derive(Serialize)]
+struct CarveSizeJsonRecord<'a> {
+    #[serde(flatten)]
+    record: &'a CarveSizeRecord,
+    tool_version: &'a str,
+    config_hash: &'a str,
+    evidence_path: &'a str,
+    evidence_sha256: &'a str,
+    pass_id: u32,
+}
+
+#[derive(Serialize)]
+struct ClassificationJsonRecord<'a> {
+    #[serde(flatten)]
+    record: &'a ClassificationRecord,
+    tool_version: &'a str,
+    config_hash: &'a str,
+    evidence_path: &'a str,
+    evidence_sha256: &'a str,
+    pass_id: u32,
+}
+
+#[derive(Serialize)]
+struct EncryptedDocumentJsonRecord<'a> {
+    #[serde(flatten)]
+    record: &'a EncryptedDocumentRecord,
+    tool_version: &'a str,
+    config_hash: &'a str,
+    evidence_path: &'a str,
+    evidence_sha256: &'a str,
+    pass_id: u32,
 }
 
 #[derive(Serialize)]
@@ -94,50 +394,81 @@ struct EntropyRegionRecord<'a> {
     config_hash: &'a str,
     evidence_path: &'a str,
     evidence_sha256: &'a str,
+    pass_id: u32,
 }
 
 impl JsonlSink {
     pub fn new(
-        _run_id: &str,
-        tool_version: &str,
-        config_hash: &str,
-        evidence_path: &Path,
-        evidence_sha256: &str,
+        identity: &SinkIdentity,
         run_output_dir: &Path,
+        cipher: Option<&OutputCipher>,
     ) -> Result<Self, MetadataError> {
         let meta_dir = run_output_dir.join("metadata");
         std::fs::create_dir_all(&meta_dir)?;
-        let files_path = meta_dir.join("carved_files.jsonl");
-        let strings_path = meta_dir.join("string_artefacts.jsonl");
-        let history_path = meta_dir.join("browser_history.jsonl");
-        let cookies_path = meta_dir.join("browser_cookies.jsonl");
-        let downloads_path = meta_dir.join("browser_downloads.jsonl");
-        let run_path = meta_dir.join("run_summary.jsonl");
-        let entropy_path = meta_dir.join("entropy_regions.jsonl");
-        let files_file = File::create(files_path)?;
-        let strings_file = File::create(strings_path)?;
-        let history_file = File::create(history_path)?;
-        let cookies_file = File::create(cookies_path)?;
-        let downloads_file = File::create(downloads_path)?;
-        let run_file = File::create(run_path)?;
-        let entropy_file = File::create(entropy_path)?;
+        Self::with_streams(identity, |stream| {
+            open_stream(
+                &meta_dir.join(format!("{stream}.jsonl")),
+                identity.pass_id,
+                cipher,
+            )
+        })
+    }
+}
+
+impl JsonlSink<MemoryStream> {
+    /// Keep every stream in `streams` instead of writing files.
+    pub fn in_memory(identity: &SinkIdentity, streams: &MemoryStreams) -> Self {
+        Self::with_streams(identity, |stream| Ok(streams.stream(stream)))
+            .expect("memory streams open infallibly")
+    }
+}
+
+impl<W: Write> JsonlSink<W> {
+    /// Build a sink writing stream `<name>` to `open(name)`.
+    fn with_streams(
+        identity: &SinkIdentity,
+        mut open: impl FnMut(&'static str) -> std::io::Result<W>,
+    ) -> Result<Self, MetadataError> {
         Ok(Self {
-            tool_version: tool_version.to_string(),
-            config_hash: config_hash.to_string(),
-            evidence_path: evidence_path.to_string_lossy().to_string(),
-            evidence_sha256: evidence_sha256.to_string(),
-            files_writer: Mutex::new(BufWriter::new(files_file)),
-            strings_writer: Mutex::new(BufWriter::new(strings_file)),
-            history_writer: Mutex::new(BufWriter::new(history_file)),
-            cookies_writer: Mutex::new(BufWriter::new(cookies_file)),
-            downloads_writer: Mutex::new(BufWriter::new(downloads_file)),
-            run_writer: Mutex::new(BufWriter::new(run_file)),
-            entropy_writer: Mutex::new(BufWriter::new(entropy_file)),
+            tool_version: identity.tool_version.to_string(),
+            config_hash: identity.config_hash.to_string(),
+            evidence_path: identity.evidence_path.to_string_lossy().to_string(),
+            evidence_sha256: identity.evidence_sha256.to_string(),
+            pass_id: identity.pass_id,
+            files_writer: Mutex::new(BufWriter::new(open("carved_files")?)),
+            strings_writer: Mutex::new(BufWriter::new(open("string_artefacts")?)),
+            history_writer: Mutex::new(BufWriter::new(open("browser_history")?)),
+            cookies_writer: Mutex::new(BufWriter::new(open("browser_cookies")?)),
+            downloads_writer: Mutex::new(BufWriter::new(open("browser_downloads")?)),
+            cache_writer: Mutex::new(BufWriter::new(open("browser_cache")?)),
+            log_events_writer: Mutex::new(BufWriter::new(open("log_events")?)),
+            container_writer: Mutex::new(BufWriter::new(open("container_artefacts")?)),
+            run_writer: Mutex::new(BufWriter::new(open("run_summary")?)),
+            resource_writer: Mutex::new(BufWriter::new(open("resource_usage")?)),
+            message_writer: Mutex::new(BufWriter::new(open("messages")?)),
+            usage_event_writer: Mutex::new(BufWriter::new(open("usage_events")?)),
+            app_record_writer: Mutex::new(BufWriter::new(open("app_records")?)),
+            prefetch_writer: Mutex::new(BufWriter::new(open("prefetch_files")?)),
+            shortcut_writer: Mutex::new(BufWriter::new(open("shortcuts")?)),
+            executable_writer: Mutex::new(BufWriter::new(open("executables")?)),
+            thumbnail_writer: Mutex::new(BufWriter::new(open("thumbnails")?)),
+            plist_writer: Mutex::new(BufWriter::new(open("plists")?)),
+            video_repair_writer: Mutex::new(BufWriter::new(open("video_repairs")?)),
+            image_info_writer: Mutex::new(BufWriter::new(open("image_info")?)),
+            photo_device_writer: Mutex::new(BufWriter::new(open("photo_devices")?)),
+            ntfs_file_writer: Mutex::new(BufWriter::new(open("ntfs_files")?)),
+            input_file_writer: Mutex::new(BufWriter::new(open("input_files")?)),
+            encrypted_document_writer: Mutex::new(BufWriter::new(open("encrypted_documents")?)),
+            carve_size_writer: Mutex::new(BufWriter::new(open("carve_sizes")?)),
+            carve_companion_writer: Mutex::new(BufWriter::new(open("carve_companions")?)),
+            volume_location_writer: Mutex::new(BufWriter::new(open("volume_locations")?)),
+            classification_writer: Mutex::new(BufWriter::new(open("classifications")?)),
+            entropy_writer: Mutex::new(BufWriter::new(open("entropy_regions")?)),
         })
     }
 }
 
-impl MetadataSink for JsonlSink {
+impl<W: Write + Send> MetadataSink for JsonlSink<W> {
     fn record_file(&self, file: &CarvedFile) -> Result<(), MetadataError> {
         let record = CarvedFileRecord {
             file,
@@ -145,6 +476,7 @@ impl MetadataSink for JsonlSink {
             config_hash: &self.config_hash,
             evidence_path: &self.evidence_path,
             evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
         };
         let mut guard = self
             .files_writer
@@ -156,12 +488,19 @@ impl MetadataSink for JsonlSink {
     }
 
     fn record_string(&self, artefact: &StringArtefact) -> Result<(), MetadataError> {
+        let decoded = match artefact.artefact_kind {
+            ArtefactKind::Url => decode_url(&artefact.content),
+            _ => Default::default(),
+        };
         let record = StringArtefactRecord {
             artefact,
+            host_unicode: decoded.host_unicode,
+            path_decoded: decoded.path_decoded,
             tool_version: &self.tool_version,
             config_hash: &self.config_hash,
             evidence_path: &self.evidence_path,
             evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
         };
         let mut guard = self
             .strings_writer
@@ -182,6 +521,7 @@ impl MetadataSink for JsonlSink {
             config_hash: &self.config_hash,
             evidence_path: &self.evidence_path,
             evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
         };
         let mut guard = self
             .history_writer
@@ -199,6 +539,7 @@ impl MetadataSink for JsonlSink {
             config_hash: &self.config_hash,
             evidence_path: &self.evidence_path,
             evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
         };
         let mut guard = self
             .cookies_writer
@@ -216,6 +557,7 @@ impl MetadataSink for JsonlSink {
             config_hash: &self.config_hash,
             evidence_path: &self.evidence_path,
             evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
         };
         let mut guard = self
             .downloads_writer
@@ -226,6 +568,60 @@ impl MetadataSink for JsonlSink {
         Ok(())
     }
 
+    fn record_cache(&self, record: &CacheRecord) -> Result<(), MetadataError> {
+        let record = BrowserCacheRecord {
+            record,
+            tool_version: &self.tool_version,
+            config_hash: &self.config_hash,
+            evidence_path: &self.evidence_path,
+            evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
+        };
+        let mut guard = self
+            .cache_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("cache writer lock poisoned".into()))?;
+        serde_json::to_writer(&mut *guard, &record)?;
+        guard.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn record_log_event(&self, record: &LogEventRecord) -> Result<(), MetadataError> {
+        let record = LogEventJsonRecord {
+            record,
+            tool_version: &self.tool_version,
+            config_hash: &self.config_hash,
+            evidence_path: &self.evidence_path,
+            evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
+        };
+        let mut guard = self
+            .log_events_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("log events writer lock poisoned".into()))?;
+        serde_json::to_writer(&mut *guard, &record)?;
+        guard.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn record_container(&self, record: &ContainerArtefactRecord) -> Result<(), MetadataError> {
+        let record = ContainerArtefactJsonRecord {
+            record,
+            tool_version: &self.tool_version,
+            config_hash: &self.config_hash,
+            evidence_path: &self.evidence_path,
+            evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
+        };
+        let mut guard = self
+            .container_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("container writer lock poisoned".into()))?;
+        serde_json::to_writer(&mut *guard, &record)?;
+        guard.write_all(b"\n")?;
+        Ok(())
+    }
+
     fn record_run_summary(&self, summary: &RunSummary) -> Result<(), MetadataError> {
         let record = RunSummaryRecord {
             summary,
@@ -233,6 +629,7 @@ impl MetadataSink for JsonlSink {
             config_hash: &self.config_hash,
             evidence_path: &self.evidence_path,
             evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
         };
         let mut guard = self
             .run_writer
@@ -243,6 +640,351 @@ impl MetadataSink for JsonlSink {
         Ok(())
     }
 
+    fn record_resource_usage(&self, usage: &ResourceUsage) -> Result<(), MetadataError> {
+        let record = ResourceUsageRecord {
+            usage,
+            tool_version: &self.tool_version,
+            config_hash: &self.config_hash,
+            evidence_path: &self.evidence_path,
+            evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
+        };
+        let mut guard = self
+            .resource_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("resource writer lock poisoned".into()))?;
+        serde_json::to_writer(&mut *guard, &record)?;
+        guard.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn record_message(&self, record: &MessageRecord) -> Result<(), MetadataError> {
+        let record = MessageJsonRecord {
+            record,
+            tool_version: &self.tool_version,
+            config_hash: &self.config_hash,
+            evidence_path: &self.evidence_path,
+            evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
+        };
+        let mut guard = self
+            .message_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("message writer lock poisoned".into()))?;
+        serde_json::to_writer(&mut *guard, &record)?;
+        guard.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn record_usage_event(&self, record: &UsageEventRecord) -> Result<(), MetadataError> {
+        let record = UsageEventJsonRecord {
+            record,
+            tool_version: &self.tool_version,
+            config_hash: &self.config_hash,
+            evidence_path: &self.evidence_path,
+            evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
+        };
+        let mut guard = self
+            .usage_event_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("usage event writer lock poisoned".into()))?;
+        serde_json::to_writer(&mut *guard, &record)?;
+        guard.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn record_app_record(&self, record: &AppRecord) -> Result<(), MetadataError> {
+        let record = AppJsonRecord {
+            record,
+            tool_version: &self.tool_version,
+            config_hash: &self.config_hash,
+            evidence_path: &self.evidence_path,
+            evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
+        };
+        let mut guard = self
+            .app_record_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("app record writer lock poisoned".into()))?;
+        serde_json::to_writer(&mut *guard, &record)?;
+        guard.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn record_prefetch(&self, record: &PrefetchRecord) -> Result<(), MetadataError> {
+        let record = PrefetchJsonRecord {
+            record,
+            tool_version: &self.tool_version,
+            config_hash: &self.config_hash,
+            evidence_path: &self.evidence_path,
+            evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
+        };
+        let mut guard = self
+            .prefetch_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("prefetch writer lock poisoned".into()))?;
+        serde_json::to_writer(&mut *guard, &record)?;
+        guard.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn record_shortcut(&self, record: &ShortcutRecord) -> Result<(), MetadataError> {
+        let record = ShortcutJsonRecord {
+            record,
+            tool_version: &self.tool_version,
+            config_hash: &self.config_hash,
+            evidence_path: &self.evidence_path,
+            evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
+        };
+        let mut guard = self
+            .shortcut_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("shortcut writer lock poisoned".into()))?;
+        serde_json::to_writer(&mut *guard, &record)?;
+        guard.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn record_executable(&self, record: &ExecutableRecord) -> Result<(), MetadataError> {
+        let record = ExecutableJsonRecord {
+            record,
+            tool_version: &self.tool_version,
+            config_hash: &self.config_hash,
+            evidence_path: &self.evidence_path,
+            evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
+        };
+        let mut guard = self
+            .executable_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("executable writer lock poisoned".into()))?;
+        serde_json::to_writer(&mut *guard, &record)?;
+        guard.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn record_thumbnail(&self, record: &ThumbnailRecord) -> Result<(), MetadataError> {
+        let record = ThumbnailJsonRecord {
+            record,
+            tool_version: &self.tool_version,
+            config_hash: &self.config_hash,
+            evidence_path: &self.evidence_path,
+            evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
+        };
+        let mut guard = self
+            .thumbnail_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("thumbnail writer lock poisoned".into()))?;
+        serde_json::to_writer(&mut *guard, &record)?;
+        guard.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn record_plist(&self, record: &PlistRecord) -> Result<(), MetadataError> {
+        let record = PlistJsonRecord {
+            record,
+            tool_version: &self.tool_version,
+            config_hash: &self.config_hash,
+            evidence_path: &self.evidence_path,
+            evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
+        };
+        let mut guard = self
+            .plist_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("plist writer lock poisoned".into()))?;
+        serde_json::to_writer(&mut *guard, &record)?;
+        guard.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn record_video_repair(&self, record: &VideoRepairRecord) -> Result<(), MetadataError> {
+        let record = VideoRepairJsonRecord {
+            record,
+            tool_version: &self.tool_version,
+            config_hash: &self.config_hash,
+            evidence_path: &self.evidence_path,
+            evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
+        };
+        let mut guard = self
+            .video_repair_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("video repair writer lock poisoned".into()))?;
+        serde_json::to_writer(&mut *guard, &record)?;
+        guard.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn record_image_info(&self, record: &ImageInfoRecord) -> Result<(), MetadataError> {
+        let record = ImageInfoJsonRecord {
+            record,
+            tool_version: &self.tool_version,
+            config_hash: &self.config_hash,
+            evidence_path: &self.evidence_path,
+            evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
+        };
+        let mut guard = self
+            .image_info_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("image info writer lock poisoned".into()))?;
+        serde_json::to_writer(&mut *guard, &record)?;
+        guard.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn record_photo_device(&self, record: &PhotoDeviceRecord) -> Result<(), MetadataError> {
+        let record = PhotoDeviceJsonRecord {
+            record,
+            tool_version: &self.tool_version,
+            config_hash: &self.config_hash,
+            evidence_path: &self.evidence_path,
+            evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
+        };
+        let mut guard = self
+            .photo_device_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("photo device writer lock poisoned".into()))?;
+        serde_json::to_writer(&mut *guard, &record)?;
+        guard.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn record_ntfs_file(&self, record: &NtfsFileRecord) -> Result<(), MetadataError> {
+        let record = NtfsFileJsonRecord {
+            record,
+            tool_version: &self.tool_version,
+            config_hash: &self.config_hash,
+            evidence_path: &self.evidence_path,
+            evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
+        };
+        let mut guard = self
+            .ntfs_file_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("ntfs file writer lock poisoned".into()))?;
+        serde_json::to_writer(&mut *guard, &record)?;
+        guard.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn record_input_file(&self, record: &InputFileRecord) -> Result<(), MetadataError> {
+        let record = InputFileJsonRecord {
+            record,
+            tool_version: &self.tool_version,
+            config_hash: &self.config_hash,
+            evidence_path: &self.evidence_path,
+            evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
+        };
+        let mut guard = self
+            .input_file_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("input file writer lock poisoned".into()))?;
+        serde_json::to_writer(&mut *guard, &record)?;
+        guard.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn record_encrypted_document(
+        &self,
+        record: &EncryptedDocumentRecord,
+    ) -> Result<(), MetadataError> {
+        let record = EncryptedDocumentJsonRecord {
+            record,
+            tool_version: &self.tool_version,
+            config_hash: &self.config_hash,
+            evidence_path: &self.evidence_path,
+            evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
+        };
+        let mut guard = self
+            .encrypted_document_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("encrypted document writer lock poisoned".into()))?;
+        serde_json::to_writer(&mut *guard, &record)?;
+        guard.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn record_carve_size(&self, record: &CarveSizeRecord) -> Result<(), MetadataError> {
+        let record = CarveSizeJsonRecord {
+            record,
+            tool_version: &self.tool_version,
+            config_hash: &self.config_hash,
+            evidence_path: &self.evidence_path,
+            evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
+        };
+        let mut guard = self
+            .carve_size_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("carve size writer lock poisoned".into()))?;
+        serde_json::to_writer(&mut *guard, &record)?;
+        guard.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn record_carve_companion(&self, record: &CarveCompanionRecord) -> Result<(), MetadataError> {
+        let record = CarveCompanionJsonRecord {
+            record,
+            tool_version: &self.tool_version,
+            config_hash: &self.config_hash,
+            evidence_path: &self.evidence_path,
+            evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
+        };
+        let mut guard = self
+            .carve_companion_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("carve companion writer lock poisoned".into()))?;
+        serde_json::to_writer(&mut *guard, &record)?;
+        guard.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn record_volume_location(&self, record: &VolumeLocationRecord) -> Result<(), MetadataError> {
+        let record = VolumeLocationJsonRecord {
+            record,
+            tool_version: &self.tool_version,
+            config_hash: &self.config_hash,
+            evidence_path: &self.evidence_path,
+            evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
+        };
+        let mut guard = self
+            .volume_location_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("volume location writer lock poisoned".into()))?;
+        serde_json::to_writer(&mut *guard, &record)?;
+        guard.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn record_classification(&self, record: &ClassificationRecord) -> Result<(), MetadataError> {
+        let record = ClassificationJsonRecord {
+            record,
+            tool_version: &self.tool_version,
+            config_hash: &self.config_hash,
+            evidence_path: &self.evidence_path,
+            evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
+        };
+        let mut guard = self
+            .classification_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("classification writer lock poisoned".into()))?;
+        serde_json::to_writer(&mut *guard, &record)?;
+        guard.write_all(b"\n")?;
+        Ok(())
+    }
+
     fn record_entropy(&self, region: &EntropyRegion) -> Result<(), MetadataError> {
         let record = EntropyRegionRecord {
             region,
@@ -250,6 +992,7 @@ impl MetadataSink for JsonlSink {
             config_hash: &self.config_hash,
             evidence_path: &self.evidence_path,
             evidence_sha256: &self.evidence_sha256,
+            pass_id: self.pass_id,
         };
         let mut guard = self
             .entropy_writer
@@ -281,10 +1024,98 @@ impl MetadataSink for JsonlSink {
             .downloads_writer
             .lock()
             .map_err(|_| MetadataError::Other("downloads writer lock poisoned".into()))?;
+        let mut cache = self
+            .cache_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("cache writer lock poisoned".into()))?;
+        let mut log_events = self
+            .log_events_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("log events writer lock poisoned".into()))?;
+        let mut container = self
+            .container_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("container writer lock poisoned".into()))?;
         let mut run = self
             .run_writer
             .lock()
             .map_err(|_| MetadataError::Other("run writer lock poisoned".into()))?;
+        let mut resources = self
+            .resource_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("resource writer lock poisoned".into()))?;
+        let mut messages = self
+            .message_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("message writer lock poisoned".into()))?;
+        let mut usage_events = self
+            .usage_event_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("usage event writer lock poisoned".into()))?;
+        let mut app_records = self
+            .app_record_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("app record writer lock poisoned".into()))?;
+        let mut prefetch_files = self
+            .prefetch_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("prefetch writer lock poisoned".into()))?;
+        let mut shortcuts = self
+            .shortcut_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("shortcut writer lock poisoned".into()))?;
+        let mut executables = self
+            .executable_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("executable writer lock poisoned".into()))?;
+        let mut thumbnails = self
+            .thumbnail_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("thumbnail writer lock poisoned".into()))?;
+        let mut plists = self
+            .plist_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("plist writer lock poisoned".into()))?;
+        let mut video_repairs = self
+            .video_repair_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("video repair writer lock poisoned".into()))?;
+        let mut image_infos = self
+            .image_info_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("image info writer lock poisoned".into()))?;
+        let mut photo_devices = self
+            .photo_device_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("photo device writer lock poisoned".into()))?;
+        let mut ntfs_files = self
+            .ntfs_file_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("ntfs file writer lock poisoned".into()))?;
+        let mut input_files = self
+            .input_file_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("input file writer lock poisoned".into()))?;
+        let mut encrypted_documents = self
+            .encrypted_document_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("encrypted document writer lock poisoned".into()))?;
+        let mut carve_sizes = self
+            .carve_size_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("carve size writer lock poisoned".into()))?;
+        let mut carve_companions = self
+            .carve_companion_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("carve companion writer lock poisoned".into()))?;
+        let mut volume_locations = self
+            .volume_location_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("volume location writer lock poisoned".into()))?;
+        let mut classifications = self
+            .classification_writer
+            .lock()
+            .map_err(|_| MetadataError::Other("classification writer lock poisoned".into()))?;
         let mut entropy = self
             .entropy_writer
             .lock()
@@ -294,7 +1125,29 @@ impl MetadataSink for JsonlSink {
         history.flush()?;
         cookies.flush()?;
         downloads.flush()?;
+        cache.flush()?;
+        log_events.flush()?;
+        container.flush()?;
         run.flush()?;
+        resources.flush()?;
+        messages.flush()?;
+        usage_events.flush()?;
+        app_records.flush()?;
+        prefetch_files.flush()?;
+        shortcuts.flush()?;
+        executables.flush()?;
+        thumbnails.flush()?;
+        plists.flush()?;
+        video_repairs.flush()?;
+        image_infos.flush()?;
+        photo_devices.flush()?;
+        ntfs_files.flush()?;
+        input_files.flush()?;
+        encrypted_documents.flush()?;
+        carve_sizes.flush()?;
+        carve_companions.flush()?;
+        volume_locations.flush()?;
+        classifications.flush()?;
         entropy.flush()?;
         Ok(())
     }
@@ -0,0 +1,154 @@
+//! In-memory metadata streams for runs without an output directory, such as
+//! a triage build carving an image handed over as a byte buffer.
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+use crate::metadata::MetadataError;
+
+/// JSON lines of every metadata stream, keyed by stream name (the JSONL file
+/// stem, e.g. `carved_files`). Cloning shares the buffers, so a handle kept
+/// by the caller sees what the sink wrote after the pipeline flushed it.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStreams {
+    streams: Arc<Mutex<BTreeMap<&'static str, Vec<u8>>>>,
+}
+
+impl MemoryStreams {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Writer appending to stream `name`.
+    pub(crate) fn stream(&self, name: &'static str) -> MemoryStream {
+        MemoryStream {
+            streams: self.clone(),
+            name,
+        }
+    }
+
+    /// Raw JSON lines written to stream `name` so far.
+    pub fn bytes(&self, name: &str) -> Vec<u8> {
+        self.streams
+            .lock()
+            .map(|streams| streams.get(name).cloned().unwrap_or_default())
+            .unwrap_or_default()
+    }
+
+    /// Records written to stream `name` so far.
+    pub fn records(&self, name: &str) -> Result<Vec<serde_json::Value>, MetadataError> {
+        let bytes = self.bytes(name);
+        serde_json::Deserializer::from_slice(&bytes)
+            .into_iter()
+            .map(|record| record.map_err(MetadataError::from))
+            .collect()
+    }
+}
+
+/// One stream of [`MemoryStreams`].
+pub struct MemoryStream {
+    streams: MemoryStreams,
+    name: &'static str,
+}
+
+impl Write for MemoryStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut streams = self
+            .streams
+            .streams
+            .lock()
+            .map_err(|_| io::Error::other("memory streams lock poisoned"))?;
+        streams.entry(self.name).or_default().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::carve::CarvedFile;
+    use crate::config;
+    use crate::metadata::{
+        MetadataBackendKind, MetadataSink, RunSummary, SinkIdentity, build_memory_sink, build_sink,
+    };
+    use std::path::Path;
+
+    fn carved(start: u64) -> CarvedFile {
+        CarvedFile {
+            run_id: "run".to_string(),
+            file_type: "jpeg".to_string(),
+            signature_type: "jpeg".to_string(),
+            path: format!("jpeg/{start}.jpg"),
+            extension: "jpg".to_string(),
+            global_start: start,
+            global_end: start + 99,
+            size: 100,
+            md5: None,
+            sha256: None,
+            validated: true,
+            truncated: false,
+            errors: Vec::new(),
+            pattern_id: None,
+        }
+    }
+
+    fn record(sink: &dyn MetadataSink) {
+        sink.record_file(&carved(0)).expect("file");
+        sink.record_file(&carved(4096)).expect("file");
+        sink.record_run_summary(&RunSummary {
+            run_id: "run".to_string(),
+            bytes_scanned: 8192,
+            chunks_processed: 1,
+            hits_found: 2,
+            files_carved: 2,
+            string_spans: 0,
+            artefacts_extracted: 0,
+        })
+        .expect("summary");
+        sink.flush().expect("flush");
+    }
+
+    #[test]
+    fn collects_the_same_records_as_the_jsonl_sink() {
+        let identity = SinkIdentity {
+            run_id: "run",
+            tool_version: "0.0.0",
+            config_hash: "cfg",
+            evidence_path: Path::new("image.dd"),
+            evidence_sha256: "",
+            pass_id: 1,
+        };
+        let streams = MemoryStreams::new();
+        record(build_memory_sink(&identity, &streams).as_ref());
+
+        let dir = tempfile::tempdir().expect("tempdir");
+        let cfg = config::load_config(None).expect("config").config;
+        let jsonl = build_sink(
+            MetadataBackendKind::Jsonl,
+            &cfg,
+            identity.run_id,
+            identity.tool_version,
+            identity.config_hash,
+            identity.evidence_path,
+            identity.evidence_sha256,
+            dir.path(),
+        )
+        .expect("sink");
+        record(jsonl.as_ref());
+
+        for stream in ["carved_files", "run_summary", "string_artefacts"] {
+            let on_disk =
+                std::fs::read(dir.path().join("metadata").join(format!("{stream}.jsonl")))
+                    .expect("read");
+            assert_eq!(streams.bytes(stream), on_disk, "{stream}");
+        }
+        let files = streams.records("carved_files").expect("records");
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[1]["global_start"], 4096);
+    }
+}
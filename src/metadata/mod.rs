@@ -1,13 +1,37 @@
-pub mod csv;
-pub mod jsonl;
-pub mod parquet;
+mod csv;
+mod jsonl;
+pub mod memory;
+mod parquet;
+pub mod salvage;
+pub mod snapshot;
+
+pub(crate) use csv::artefact_kind_label;
 
 use std::path::Path;
 
 use thiserror::Error;
 
-use crate::carve::CarvedFile;
-use crate::parsers::browser::{BrowserCookieRecord, BrowserDownloadRecord, BrowserHistoryRecord};
+use crate::carve::{CarvedFile, Truncation};
+use crate::encryption::{OutputCipher, OutputFile};
+use crate::inputs::InputFileRecord;
+use crate::parsers::apple::MessageRecord;
+use crate::parsers::apple::UsageEventRecord;
+use crate::parsers::bplist::PlistRecord;
+use crate::parsers::browser::{
+    BrowserCacheRecord, BrowserCookieRecord, BrowserDownloadRecord, BrowserHistoryRecord,
+};
+use crate::parsers::container::ContainerArtefactRecord;
+use crate::parsers::executable::ExecutableRecord;
+use crate::parsers::exif::PhotoDeviceRecord;
+use crate::parsers::image::ImageInfoRecord;
+use crate::parsers::lnk::ShortcutRecord;
+use crate::parsers::logs::LogEventRecord;
+use crate::parsers::mp4::VideoRepairRecord;
+use crate::parsers::ntfs::NtfsFileRecord;
+use crate::parsers::office_crypto::EncryptedDocumentRecord;
+use crate::parsers::prefetch::PrefetchRecord;
+use crate::parsers::sqlite_apps::AppRecord;
+use crate::parsers::thumbcache::ThumbnailRecord;
 use crate::strings::artifacts::StringArtefact;
 
 #[derive(Debug, Clone, serde::Serialize)]
@@ -21,6 +45,107 @@ pub struct RunSummary {
     pub artefacts_extracted: u64,
 }
 
+/// Resource usage of one pipeline stage, or of the whole run (`stage` =
+/// `process`). CPU and I/O fields are `None` where the platform has no
+/// per-thread accounting; `gpu_secs` is `None` without a GPU scanner.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ResourceUsage {
+    pub run_id: String,
+    pub stage: String,
+    /// Threads accounted to the stage.
+    pub threads: u64,
+    /// Time spent on jobs, summed over threads; run wall time for `process`.
+    pub busy_secs: f64,
+    pub user_cpu_secs: Option<f64>,
+    pub sys_cpu_secs: Option<f64>,
+    pub bytes_read: Option<u64>,
+    pub bytes_written: Option<u64>,
+    pub gpu_secs: Option<f64>,
+    /// Peak resident memory; set on the `process` row only.
+    pub peak_rss_bytes: Option<u64>,
+}
+
+/// Declared size and truncation cause of a carve. Recorded for carves whose
+/// handler read a declared size and for every truncated carve.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct CarveSizeRecord {
+    pub run_id: String,
+    pub file_type: String,
+    /// Carved path, as in `carved_files`.
+    pub path: String,
+    pub global_start: u64,
+    /// Bytes carved.
+    pub size: u64,
+    /// Size the file's own structures declare.
+    pub declared_size: Option<u64>,
+    /// Structure `declared_size` was read from (`fat`, `eocd`, `linearization`,
+    /// `page_count`, `freelist`, `ptrmap`, `base_block`, `file_header`, `space_tree`, `root`,
+    /// `section_table`, `header_tables`, `load_commands`, `fat_header`, `cache_header`, `trailer`,
+    /// `riff_header`, `ds64`).
+    pub declared_by: Option<String>,
+    /// Why the carve is short; `None` when it is complete.
+    pub truncation: Option<Truncation>,
+}
+
+/// A file carved together with another, e.g. the WAL or rollback journal
+/// stored after a SQLite database.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct CarveCompanionRecord {
+    pub run_id: String,
+    pub file_type: String,
+    /// Carved path of the file the companion belongs to.
+    pub path: String,
+    pub global_start: u64,
+    /// `sqlite_wal` or `sqlite_journal`.
+    pub companion_type: String,
+    pub companion_path: String,
+    pub companion_start: u64,
+    pub companion_size: u64,
+}
+
+/// Where in a partition and file system a carved file or artefact starts.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct VolumeLocationRecord {
+    pub run_id: String,
+    /// `file` or `artefact`.
+    pub subject: String,
+    /// Carved path for a file, artefact kind for an artefact.
+    pub reference: String,
+    pub global_start: u64,
+    /// Partition table slot or entry, from 1; 0 for a file system that
+    /// starts at offset 0 with no partition table.
+    pub partition: u32,
+    pub partition_offset: u64,
+    /// Bytes from the start of the partition.
+    pub relative_offset: u64,
+    /// 512-byte sectors from the start of the partition.
+    pub relative_sector: u64,
+    /// `ntfs`, `fat12`, `fat16`, `fat32`, or `ext`; `None` when no known
+    /// file system is found in the partition.
+    pub file_system: Option<String>,
+    /// NTFS cluster, FAT data cluster, or ext block holding `global_start`.
+    pub cluster: Option<u64>,
+    /// For a carved file whose FAT cluster is in use, the cluster chain from
+    /// it as runs (`5-8,12`).
+    pub cluster_chain: Option<String>,
+}
+
+/// One label a content classifier gave a carved file.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ClassificationRecord {
+    pub run_id: String,
+    pub file_type: String,
+    /// Carved path, as in `carved_files`.
+    pub path: String,
+    pub global_start: u64,
+    /// Name of the classifier post-processor.
+    pub classifier: String,
+    pub label: String,
+    pub score: f64,
+    /// 1 for the highest-scoring label of the file.
+    pub rank: u32,
+}
+
 #[derive(Debug, Clone, serde::Serialize)]
 pub struct EntropyRegion {
     pub run_id: String,
@@ -37,7 +162,18 @@ pub enum MetadataBackendKind {
     Parquet,
 }
 
+impl MetadataBackendKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Jsonl => "jsonl",
+            Self::Csv => "csv",
+            Self::Parquet => "parquet",
+        }
+    }
+}
+
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum MetadataError {
     #[error("io error: {0}")]
     Io(#[from] std::io::Error),
@@ -51,6 +187,11 @@ pub enum MetadataError {
 
 /// Metadata output sink for carved files and artefacts.
 ///
+/// Part of the stable API (see `docs/api.md`). Only `record_file`,
+/// `record_run_summary`, and `flush` are required; every artefact stream has a
+/// no-op default. All methods are called from the single metadata thread, but
+/// sinks must be `Send + Sync` because they are built on the caller's thread.
+///
 /// # Example
 /// ```rust
 /// use swiftbeaver::config;
@@ -86,23 +227,24 @@ pub enum MetadataError {
 /// sink.flush().unwrap();
 /// ```
 pub trait MetadataSink: Send + Sync {
+    /// Record one `carved_files` row.
     fn record_file(&self, file: &CarvedFile) -> Result<(), MetadataError>;
-    fn record_string(&self, artefact: &StringArtefact) -> Result<(), MetadataError>;
-    fn record_history(&self, record: &BrowserHistoryRecord) -> Result<(), MetadataError>;
-    fn record_cookie(&self, record: &BrowserCookieRecord) -> Result<(), MetadataError>;
-    fn record_download(&self, record: &BrowserDownloadRecord) -> Result<(), MetadataError>;
+
+    /// Record the run summary; called once, after all workers finish.
     fn record_run_summary(&self, summary: &RunSummary) -> Result<(), MetadataError>;
-    fn record_entropy(&self, region: &EntropyRegion) -> Result<(), MetadataError>;
-    fn flush(&self) -> Result<(), MetadataError>;
-}
 
-/// A no-op sink for dry-run mode that doesn't write any files
-pub struct DryRunSink;
+    /// Flush buffered rows. Called once at the end of a run.
+    fn flush(&self) -> Result<(), MetadataError>;
 
-impl MetadataSink for DryRunSink {
-    fn record_file(&self, _file: &CarvedFile) -> Result<(), MetadataError> {
-        Ok(())
+    /// Bring the files on disk up to date for a metadata snapshot: every row
+    /// recorded so far ends a complete record or row group.
+    fn flush_for_snapshot(&self) -> Result<(), MetadataError> {
+        self.flush()
     }
+
+    // Artefact streams. Sinks that do not store a stream keep the no-op
+    // default, so new streams can be added without breaking implementations.
+
     fn record_string(&self, _artefact: &StringArtefact) -> Result<(), MetadataError> {
         Ok(())
     }
@@ -115,17 +257,109 @@ impl MetadataSink for DryRunSink {
     fn record_download(&self, _record: &BrowserDownloadRecord) -> Result<(), MetadataError> {
         Ok(())
     }
-    fn record_run_summary(&self, _summary: &RunSummary) -> Result<(), MetadataError> {
+    fn record_cache(&self, _record: &BrowserCacheRecord) -> Result<(), MetadataError> {
+        Ok(())
+    }
+    fn record_log_event(&self, _record: &LogEventRecord) -> Result<(), MetadataError> {
+        Ok(())
+    }
+    fn record_container(&self, _record: &ContainerArtefactRecord) -> Result<(), MetadataError> {
+        Ok(())
+    }
+    fn record_message(&self, _record: &MessageRecord) -> Result<(), MetadataError> {
+        Ok(())
+    }
+    fn record_usage_event(&self, _record: &UsageEventRecord) -> Result<(), MetadataError> {
+        Ok(())
+    }
+    fn record_app_record(&self, _record: &AppRecord) -> Result<(), MetadataError> {
+        Ok(())
+    }
+    fn record_prefetch(&self, _record: &PrefetchRecord) -> Result<(), MetadataError> {
+        Ok(())
+    }
+    fn record_shortcut(&self, _record: &ShortcutRecord) -> Result<(), MetadataError> {
+        Ok(())
+    }
+    fn record_executable(&self, _record: &ExecutableRecord) -> Result<(), MetadataError> {
+        Ok(())
+    }
+    fn record_thumbnail(&self, _record: &ThumbnailRecord) -> Result<(), MetadataError> {
+        Ok(())
+    }
+    fn record_plist(&self, _record: &PlistRecord) -> Result<(), MetadataError> {
+        Ok(())
+    }
+    fn record_video_repair(&self, _record: &VideoRepairRecord) -> Result<(), MetadataError> {
+        Ok(())
+    }
+    fn record_image_info(&self, _record: &ImageInfoRecord) -> Result<(), MetadataError> {
+        Ok(())
+    }
+    fn record_photo_device(&self, _record: &PhotoDeviceRecord) -> Result<(), MetadataError> {
+        Ok(())
+    }
+    fn record_ntfs_file(&self, _record: &NtfsFileRecord) -> Result<(), MetadataError> {
+        Ok(())
+    }
+    fn record_input_file(&self, _record: &InputFileRecord) -> Result<(), MetadataError> {
+        Ok(())
+    }
+    fn record_encrypted_document(
+        &self,
+        _record: &EncryptedDocumentRecord,
+    ) -> Result<(), MetadataError> {
+        Ok(())
+    }
+    fn record_carve_size(&self, _record: &CarveSizeRecord) -> Result<(), MetadataError> {
+        Ok(())
+    }
+    fn record_carve_companion(&self, _record: &CarveCompanionRecord) -> Result<(), MetadataError> {
+        Ok(())
+    }
+    fn record_volume_location(&self, _record: &VolumeLocationRecord) -> Result<(), MetadataError> {
+        Ok(())
+    }
+    fn record_classification(&self, _record: &ClassificationRecord) -> Result<(), MetadataError> {
         Ok(())
     }
     fn record_entropy(&self, _region: &EntropyRegion) -> Result<(), MetadataError> {
         Ok(())
     }
+    fn record_resource_usage(&self, _usage: &ResourceUsage) -> Result<(), MetadataError> {
+        Ok(())
+    }
+}
+
+/// A no-op sink for dry-run mode that doesn't write any files
+pub struct DryRunSink;
+
+impl MetadataSink for DryRunSink {
+    fn record_file(&self, _file: &CarvedFile) -> Result<(), MetadataError> {
+        Ok(())
+    }
+    fn record_run_summary(&self, _summary: &RunSummary) -> Result<(), MetadataError> {
+        Ok(())
+    }
     fn flush(&self) -> Result<(), MetadataError> {
         Ok(())
     }
 }
 
+/// Run and pass identity stamped on every metadata record.
+#[derive(Debug, Clone, Copy)]
+pub struct SinkIdentity<'a> {
+    pub run_id: &'a str,
+    pub tool_version: &'a str,
+    pub config_hash: &'a str,
+    pub evidence_path: &'a Path,
+    pub evidence_sha256: &'a str,
+    /// Pass over the run directory, from 1. Later passes append to the
+    /// streams of earlier ones instead of replacing them.
+    pub pass_id: u32,
+}
+
+/// Build a sink for the first (or only) pass over `run_output_dir`.
 pub fn build_sink(
     backend: MetadataBackendKind,
     cfg: &crate::config::Config,
@@ -136,35 +370,78 @@ pub fn build_sink(
     evidence_sha256: &str,
     run_output_dir: &Path,
 ) -> Result<Box<dyn MetadataSink>, MetadataError> {
+    let identity = SinkIdentity {
+        run_id,
+        tool_version,
+        config_hash,
+        evidence_path,
+        evidence_sha256,
+        pass_id: 1,
+    };
+    build_pass_sink(backend, cfg, &identity, run_output_dir)
+}
+
+/// Build a sink for pass `identity.pass_id`. JSONL and CSV streams of later
+/// passes are appended to; Parquet files get a `.pass<N>` suffix.
+pub fn build_pass_sink(
+    backend: MetadataBackendKind,
+    cfg: &crate::config::Config,
+    identity: &SinkIdentity,
+    run_output_dir: &Path,
+) -> Result<Box<dyn MetadataSink>, MetadataError> {
+    let cipher =
+        OutputCipher::from_config(cfg).map_err(|err| MetadataError::Other(err.to_string()))?;
+    let cipher = cipher.as_ref();
     match backend {
         MetadataBackendKind::Jsonl => Ok(Box::new(jsonl::JsonlSink::new(
-            run_id,
-            tool_version,
-            config_hash,
-            evidence_path,
-            evidence_sha256,
+            identity,
             run_output_dir,
+            cipher,
         )?)),
         MetadataBackendKind::Csv => Ok(Box::new(csv::CsvSink::new(
-            run_id,
-            tool_version,
-            config_hash,
-            evidence_path,
-            evidence_sha256,
+            identity,
             run_output_dir,
+            cipher,
         )?)),
-        MetadataBackendKind::Parquet => parquet::build_parquet_sink(
-            cfg,
-            run_id,
-            tool_version,
-            config_hash,
-            evidence_path,
-            evidence_sha256,
-            run_output_dir,
-        ),
+        MetadataBackendKind::Parquet => {
+            parquet::build_parquet_sink(cfg, identity, run_output_dir, cipher)
+        }
+    }
+}
+
+/// Open a metadata stream, truncating it on the first pass and appending to
+/// it on later ones. With a cipher the stream is written to `<path>.age`;
+/// encrypted streams cannot be appended to.
+fn open_stream(
+    path: &Path,
+    pass_id: u32,
+    cipher: Option<&OutputCipher>,
+) -> std::io::Result<OutputFile> {
+    if pass_id > 1 {
+        if cipher.is_some() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "encrypted metadata streams cannot be appended to",
+            ));
+        }
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map(OutputFile::Plain)
+    } else {
+        OutputFile::create(path, cipher)
     }
 }
 
+/// Build a JSONL sink keeping its streams in `streams` instead of files.
+pub fn build_memory_sink(
+    identity: &SinkIdentity,
+    streams: &memory::MemoryStreams,
+) -> Box<dyn MetadataSink> {
+    Box::new(jsonl::JsonlSink::in_memory(identity, streams))
+}
+
 /// Build a dry-run sink that doesn't write any files
 pub fn build_dry_run_sink() -> Box<dyn MetadataSink> {
     Box::new(DryRunSink)
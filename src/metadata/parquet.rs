@@ -1,4 +1,3 @@
-use std::fs::File;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 
@@ -13,9 +12,32 @@ use parquet::file::properties::WriterProperties;
 
 use crate::carve::CarvedFile;
 use crate::config::Config;
-use crate::metadata::{MetadataError, MetadataSink, RunSummary};
-use crate::parsers::browser::{BrowserCookieRecord, BrowserDownloadRecord, BrowserHistoryRecord};
+use crate::encryption::{OutputCipher, OutputFile};
+use crate::inputs::InputFileRecord;
+use crate::metadata::{
+    CarveCompanionRecord, CarveSizeRecord, ClassificationRecord, MetadataError, MetadataSink,
+    RunSummary, SinkIdentity, VolumeLocationRecord,
+};
+use crate::parsers::apple::MessageRecord;
+use crate::parsers::apple::UsageEventRecord;
+use crate::parsers::bplist::PlistRecord;
+use crate::parsers::browser::{
+    BrowserCacheRecord, BrowserCookieRecord, BrowserDownloadRecord, BrowserHistoryRecord,
+};
+use crate::parsers::container::ContainerArtefactRecord;
+use crate::parsers::executable::ExecutableRecord;
+use crate::parsers::exif::PhotoDeviceRecord;
+use crate::parsers::image::ImageInfoRecord;
+use crate::parsers::lnk::ShortcutRecord;
+use crate::parsers::logs::LogEventRecord;
+use crate::parsers::mp4::VideoRepairRecord;
+use crate::parsers::ntfs::NtfsFileRecord;
+use crate::parsers::office_crypto::EncryptedDocumentRecord;
+use crate::parsers::prefetch::PrefetchRecord;
+use crate::parsers::sqlite_apps::AppRecord;
+use crate::parsers::thumbcache::ThumbnailRecord;
 use crate::strings::artifacts::{ArtefactKind, StringArtefact};
+use crate::strings::url::{decode_url, parse_url_parts};
 
 #[derive(Clone)]
 struct ParquetContext {
@@ -24,6 +46,8 @@ struct ParquetContext {
     config_hash: String,
     evidence_path: String,
     evidence_sha256: String,
+    pass_id: u32,
+    cipher: Option<OutputCipher>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -39,14 +63,83 @@ enum ParquetCategory {
     ArtefactsUrls,
     ArtefactsEmails,
     ArtefactsPhones,
+    ArtefactsNetwork,
+    ArtefactsSystem,
+    ArtefactsP2p,
     BrowserHistory,
     BrowserCookies,
     BrowserDownloads,
+    BrowserCache,
+    LogEvents,
+    ContainerArtefacts,
+    Messages,
+    UsageEvents,
+    AppRecords,
+    PrefetchFiles,
+    Shortcuts,
+    Executables,
+    Thumbnails,
+    Plists,
+    VideoRepairs,
+    ImageInfo,
+    PhotoDevices,
+    NtfsFiles,
+    InputFiles,
+    EncryptedDocuments,
+    CarveSizes,
+    CarveCompanions,
+    VolumeLocations,
+    Classifications,
     EntropyRegions,
     RunSummary,
+    ResourceUsage,
 }
 
 impl ParquetCategory {
+    const ALL: [Self; 41] = [
+        Self::FilesJpeg,
+        Self::FilesPng,
+        Self::FilesGif,
+        Self::FilesSqlite,
+        Self::FilesPdf,
+        Self::FilesZip,
+        Self::FilesWebp,
+        Self::FilesOther,
+        Self::ArtefactsUrls,
+        Self::ArtefactsEmails,
+        Self::ArtefactsPhones,
+        Self::ArtefactsNetwork,
+        Self::ArtefactsSystem,
+        Self::ArtefactsP2p,
+        Self::BrowserHistory,
+        Self::BrowserCookies,
+        Self::BrowserDownloads,
+        Self::BrowserCache,
+        Self::LogEvents,
+        Self::ContainerArtefacts,
+        Self::Messages,
+        Self::UsageEvents,
+        Self::AppRecords,
+        Self::PrefetchFiles,
+        Self::Shortcuts,
+        Self::Executables,
+        Self::Thumbnails,
+        Self::Plists,
+        Self::VideoRepairs,
+        Self::ImageInfo,
+        Self::PhotoDevices,
+        Self::NtfsFiles,
+        Self::InputFiles,
+        Self::EncryptedDocuments,
+        Self::CarveSizes,
+        Self::CarveCompanions,
+        Self::VolumeLocations,
+        Self::Classifications,
+        Self::EntropyRegions,
+        Self::RunSummary,
+        Self::ResourceUsage,
+    ];
+
     fn filename(self) -> &'static str {
         match self {
             ParquetCategory::FilesJpeg => "files_jpeg.parquet",
@@ -60,11 +153,36 @@ impl ParquetCategory {
             ParquetCategory::ArtefactsUrls => "artefacts_urls.parquet",
             ParquetCategory::ArtefactsEmails => "artefacts_emails.parquet",
             ParquetCategory::ArtefactsPhones => "artefacts_phones.parquet",
+            ParquetCategory::ArtefactsNetwork => "artefacts_network.parquet",
+            ParquetCategory::ArtefactsSystem => "system_artifacts.parquet",
+            ParquetCategory::ArtefactsP2p => "p2p_artifacts.parquet",
             ParquetCategory::BrowserHistory => "browser_history.parquet",
             ParquetCategory::BrowserCookies => "browser_cookies.parquet",
             ParquetCategory::BrowserDownloads => "browser_downloads.parquet",
+            ParquetCategory::BrowserCache => "browser_cache.parquet",
+            ParquetCategory::LogEvents => "log_events.parquet",
+            ParquetCategory::ContainerArtefacts => "container_artefacts.parquet",
+            ParquetCategory::Messages => "messages.parquet",
+            ParquetCategory::UsageEvents => "usage_events.parquet",
+            ParquetCategory::AppRecords => "app_records.parquet",
+            ParquetCategory::PrefetchFiles => "prefetch_files.parquet",
+            ParquetCategory::Shortcuts => "shortcuts.parquet",
+            ParquetCategory::Executables => "executables.parquet",
+            ParquetCategory::Thumbnails => "thumbnails.parquet",
+            ParquetCategory::Plists => "plists.parquet",
+            ParquetCategory::VideoRepairs => "video_repairs.parquet",
+            ParquetCategory::ImageInfo => "image_info.parquet",
+            ParquetCategory::PhotoDevices => "photo_devices.parquet",
+            ParquetCategory::NtfsFiles => "ntfs_files.parquet",
+            ParquetCategory::InputFiles => "input_files.parquet",
+            ParquetCategory::EncryptedDocuments => "encrypted_documents.parquet",
+            ParquetCategory::CarveSizes => "carve_sizes.parquet",
+            ParquetCategory::CarveCompanions => "carve_companions.parquet",
+            ParquetCategory::VolumeLocations => "volume_locations.parquet",
+            ParquetCategory::Classifications => "classifications.parquet",
             ParquetCategory::EntropyRegions => "entropy_regions.parquet",
             ParquetCategory::RunSummary => "run_summary.parquet",
+            ParquetCategory::ResourceUsage => "resource_usage.parquet",
         }
     }
 
@@ -111,6 +229,8 @@ struct UrlArtefactRow {
     path: Option<String>,
     query: Option<String>,
     fragment: Option<String>,
+    host_unicode: Option<String>,
+    path_decoded: Option<String>,
     source_kind: String,
     source_detail: String,
     certainty: f64,
@@ -140,6 +260,16 @@ struct PhoneArtefactRow {
     certainty: f64,
 }
 
+#[derive(Debug, Clone)]
+struct NetworkArtefactRow {
+    global_start: i64,
+    global_end: i64,
+    artefact_kind: String,
+    value: String,
+    source_kind: String,
+    source_detail: String,
+}
+
 #[derive(Debug, Clone)]
 struct BrowserHistoryRow {
     source_file: String,
@@ -182,6 +312,279 @@ struct BrowserDownloadRow {
     state: Option<String>,
 }
 
+#[derive(Debug, Clone)]
+struct BrowserCacheRow {
+    source_file: String,
+    browser: String,
+    url: String,
+    http_status: Option<i32>,
+    content_type: Option<String>,
+    content_encoding: Option<String>,
+    response_headers: Option<String>,
+    body_size: i64,
+    body_path: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct LogEventRow {
+    source: String,
+    timestamp_utc: Option<i64>,
+    timestamp_raw: Option<String>,
+    host: Option<String>,
+    program: Option<String>,
+    pid: Option<i64>,
+    severity: Option<i32>,
+    message: String,
+    global_offset: i64,
+    source_file: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct ContainerArtefactRow {
+    kind: String,
+    name: Option<String>,
+    digest: Option<String>,
+    namespace: Option<String>,
+    detail: Option<String>,
+    global_offset: i64,
+    source_file: String,
+}
+
+#[derive(Debug, Clone)]
+struct MessageRow {
+    app: String,
+    message_id: Option<String>,
+    thread: Option<String>,
+    handle: Option<String>,
+    is_from_me: Option<bool>,
+    service: Option<String>,
+    text: Option<String>,
+    timestamp_utc: Option<i64>,
+    modified_utc: Option<i64>,
+    source_file: String,
+}
+
+#[derive(Debug, Clone)]
+struct UsageEventRow {
+    source: String,
+    stream: String,
+    value: Option<String>,
+    bundle_id: Option<String>,
+    start_utc: Option<i64>,
+    end_utc: Option<i64>,
+    source_file: String,
+}
+
+#[derive(Debug, Clone)]
+struct AppRecordRow {
+    app: String,
+    plan: String,
+    row_id: Option<i64>,
+    timestamp_utc: Option<i64>,
+    timestamp_desc: Option<String>,
+    fields: String,
+    source_file: String,
+}
+
+#[derive(Debug, Clone)]
+struct PrefetchRow {
+    executable: String,
+    prefetch_hash: String,
+    format_version: u32,
+    compressed: bool,
+    run_count: u32,
+    last_run_utc: Option<i64>,
+    earlier_runs_utc: Option<String>,
+    source_file: String,
+}
+
+#[derive(Debug, Clone)]
+struct ShortcutRow {
+    target_path: Option<String>,
+    arguments: Option<String>,
+    working_dir: Option<String>,
+    relative_path: Option<String>,
+    description: Option<String>,
+    icon_location: Option<String>,
+    target_size: u32,
+    file_attributes: u32,
+    target_created_utc: Option<i64>,
+    target_accessed_utc: Option<i64>,
+    target_modified_utc: Option<i64>,
+    volume_serial: Option<String>,
+    machine_id: Option<String>,
+    source_file: String,
+}
+
+#[derive(Debug, Clone)]
+struct ExecutableRow {
+    format: String,
+    kind: String,
+    architecture: String,
+    bits: u8,
+    compile_time_utc: Option<i64>,
+    subsystem: Option<String>,
+    section_count: u32,
+    import_count: Option<u32>,
+    import_hash: Option<String>,
+    signed: bool,
+    source_file: String,
+}
+
+#[derive(Debug, Clone)]
+struct PlistRow {
+    object_count: u64,
+    top_type: String,
+    top_keys: Option<String>,
+    archiver: Option<String>,
+    json_len: u64,
+    json: Option<String>,
+    source_file: String,
+}
+
+#[derive(Debug, Clone)]
+struct VideoRepairRow {
+    method: String,
+    codec: String,
+    width: u32,
+    height: u32,
+    frame_count: u64,
+    keyframe_count: u64,
+    duration_secs: f64,
+    media_bytes: u64,
+    skipped_bytes: u64,
+    donor_file: Option<String>,
+    repaired_path: String,
+    source_file: String,
+}
+
+#[derive(Debug, Clone)]
+struct ThumbnailRow {
+    cache_id: String,
+    cache_version: String,
+    cache_type: u32,
+    entry_offset: u64,
+    width: Option<u32>,
+    height: Option<u32>,
+    image_format: String,
+    data_size: u64,
+    image_path: String,
+    source_file: String,
+}
+
+#[derive(Debug, Clone)]
+struct ImageInfoRow {
+    format: String,
+    width: i64,
+    height: i64,
+    bits_per_pixel: Option<i64>,
+    decoded_pct: Option<f64>,
+    corrupt_offset: Option<i64>,
+    source_file: String,
+}
+
+#[derive(Debug, Clone)]
+struct PhotoDeviceRow {
+    source_file: String,
+    global_start: i64,
+    make: Option<String>,
+    model: Option<String>,
+    serial: Option<String>,
+    lens: Option<String>,
+    lens_serial: Option<String>,
+    taken_at: Option<i64>,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    device_id: Option<String>,
+    session_id: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct NtfsFileRow {
+    volume_offset: i64,
+    mft_record: i64,
+    sequence: i64,
+    path: String,
+    name: String,
+    in_use: bool,
+    directory: bool,
+    size: i64,
+    resident: bool,
+    data_runs: String,
+    created: Option<i64>,
+    modified: Option<i64>,
+    mft_modified: Option<i64>,
+    accessed: Option<i64>,
+    carved_path: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct InputFileRow {
+    kind: String,
+    path: String,
+    sha256: String,
+}
+
+#[derive(Debug, Clone)]
+struct CarveSizeRow {
+    file_type: String,
+    path: String,
+    global_start: i64,
+    size: i64,
+    declared_size: Option<i64>,
+    declared_by: Option<String>,
+    truncation: Option<&'static str>,
+}
+
+#[derive(Debug, Clone)]
+struct CarveCompanionRow {
+    file_type: String,
+    path: String,
+    global_start: i64,
+    companion_type: String,
+    companion_path: String,
+    companion_start: i64,
+    companion_size: i64,
+}
+
+#[derive(Debug, Clone)]
+struct VolumeLocationRow {
+    subject: String,
+    reference: String,
+    global_start: i64,
+    partition: i64,
+    partition_offset: i64,
+    relative_offset: i64,
+    relative_sector: i64,
+    file_system: Option<String>,
+    cluster: Option<i64>,
+    cluster_chain: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct ClassificationRow {
+    file_type: String,
+    path: String,
+    global_start: i64,
+    classifier: String,
+    label: String,
+    score: f64,
+    rank: i64,
+}
+
+#[derive(Debug, Clone)]
+struct EncryptedDocumentRow {
+    encryption_type: String,
+    version: Option<String>,
+    cipher_algorithm: Option<String>,
+    key_bits: Option<i64>,
+    hash_algorithm: Option<String>,
+    spin_count: Option<i64>,
+    csp_name: Option<String>,
+    original_size: Option<i64>,
+    source_file: String,
+}
+
 #[derive(Debug, Clone)]
 struct EntropyRegionRow {
     global_start: i64,
@@ -200,21 +603,57 @@ struct RunSummaryRow {
     artefacts_extracted: i64,
 }
 
+#[derive(Debug, Clone)]
+struct ResourceUsageRow {
+    stage: String,
+    threads: i64,
+    busy_secs: f64,
+    user_cpu_secs: Option<f64>,
+    sys_cpu_secs: Option<f64>,
+    bytes_read: Option<i64>,
+    bytes_written: Option<i64>,
+    gpu_secs: Option<f64>,
+    peak_rss_bytes: Option<i64>,
+}
+
 enum CategoryBuffer {
     Files(Vec<FileRow>),
     Urls(Vec<UrlArtefactRow>),
     Emails(Vec<EmailArtefactRow>),
     Phones(Vec<PhoneArtefactRow>),
+    Network(Vec<NetworkArtefactRow>),
     History(Vec<BrowserHistoryRow>),
     Cookies(Vec<BrowserCookieRow>),
     Downloads(Vec<BrowserDownloadRow>),
+    Cache(Vec<BrowserCacheRow>),
+    LogEvents(Vec<LogEventRow>),
+    Containers(Vec<ContainerArtefactRow>),
+    Messages(Vec<MessageRow>),
+    UsageEvents(Vec<UsageEventRow>),
+    AppRecords(Vec<AppRecordRow>),
+    PrefetchFiles(Vec<PrefetchRow>),
+    Shortcuts(Vec<ShortcutRow>),
+    Executables(Vec<ExecutableRow>),
+    Thumbnails(Vec<ThumbnailRow>),
+    Plists(Vec<PlistRow>),
+    VideoRepairs(Vec<VideoRepairRow>),
+    ImageInfo(Vec<ImageInfoRow>),
+    PhotoDevices(Vec<PhotoDeviceRow>),
+    NtfsFiles(Vec<NtfsFileRow>),
+    InputFiles(Vec<InputFileRow>),
+    EncryptedDocuments(Vec<EncryptedDocumentRow>),
+    CarveSizes(Vec<CarveSizeRow>),
+    CarveCompanions(Vec<CarveCompanionRow>),
+    VolumeLocations(Vec<VolumeLocationRow>),
+    Classifications(Vec<ClassificationRow>),
     Entropy(Vec<EntropyRegionRow>),
     Summary(Vec<RunSummaryRow>),
+    Resources(Vec<ResourceUsageRow>),
 }
 
 struct CategoryWriter {
     schema: SchemaRef,
-    writer: ArrowWriter<File>,
+    writer: ArrowWriter<OutputFile>,
     buffer: CategoryBuffer,
     row_group_size: usize,
     context: Arc<ParquetContext>,
@@ -232,18 +671,43 @@ impl CategoryWriter {
         let props = WriterProperties::builder()
             .set_max_row_group_size(row_group_size)
             .build();
-        let file = File::create(path)?;
+        let file = OutputFile::create(&path, context.cipher.as_ref())?;
         let writer = ArrowWriter::try_new(file, schema.clone(), Some(props))
             .map_err(|err| MetadataError::Other(format!("parquet writer error: {err}")))?;
         let buffer = match category {
             ParquetCategory::ArtefactsUrls => CategoryBuffer::Urls(Vec::new()),
             ParquetCategory::ArtefactsEmails => CategoryBuffer::Emails(Vec::new()),
             ParquetCategory::ArtefactsPhones => CategoryBuffer::Phones(Vec::new()),
+            ParquetCategory::ArtefactsNetwork
+            | ParquetCategory::ArtefactsSystem
+            | ParquetCategory::ArtefactsP2p => CategoryBuffer::Network(Vec::new()),
             ParquetCategory::BrowserHistory => CategoryBuffer::History(Vec::new()),
             ParquetCategory::BrowserCookies => CategoryBuffer::Cookies(Vec::new()),
             ParquetCategory::BrowserDownloads => CategoryBuffer::Downloads(Vec::new()),
+            ParquetCategory::BrowserCache => CategoryBuffer::Cache(Vec::new()),
+            ParquetCategory::LogEvents => CategoryBuffer::LogEvents(Vec::new()),
+            ParquetCategory::ContainerArtefacts => CategoryBuffer::Containers(Vec::new()),
+            ParquetCategory::Messages => CategoryBuffer::Messages(Vec::new()),
+            ParquetCategory::UsageEvents => CategoryBuffer::UsageEvents(Vec::new()),
+            ParquetCategory::AppRecords => CategoryBuffer::AppRecords(Vec::new()),
+            ParquetCategory::PrefetchFiles => CategoryBuffer::PrefetchFiles(Vec::new()),
+            ParquetCategory::Shortcuts => CategoryBuffer::Shortcuts(Vec::new()),
+            ParquetCategory::Executables => CategoryBuffer::Executables(Vec::new()),
+            ParquetCategory::Thumbnails => CategoryBuffer::Thumbnails(Vec::new()),
+            ParquetCategory::Plists => CategoryBuffer::Plists(Vec::new()),
+            ParquetCategory::VideoRepairs => CategoryBuffer::VideoRepairs(Vec::new()),
+            ParquetCategory::ImageInfo => CategoryBuffer::ImageInfo(Vec::new()),
+            ParquetCategory::PhotoDevices => CategoryBuffer::PhotoDevices(Vec::new()),
+            ParquetCategory::NtfsFiles => CategoryBuffer::NtfsFiles(Vec::new()),
+            ParquetCategory::InputFiles => CategoryBuffer::InputFiles(Vec::new()),
+            ParquetCategory::EncryptedDocuments => CategoryBuffer::EncryptedDocuments(Vec::new()),
+            ParquetCategory::CarveSizes => CategoryBuffer::CarveSizes(Vec::new()),
+            ParquetCategory::CarveCompanions => CategoryBuffer::CarveCompanions(Vec::new()),
+            ParquetCategory::VolumeLocations => CategoryBuffer::VolumeLocations(Vec::new()),
+            ParquetCategory::Classifications => CategoryBuffer::Classifications(Vec::new()),
             ParquetCategory::EntropyRegions => CategoryBuffer::Entropy(Vec::new()),
             ParquetCategory::RunSummary => CategoryBuffer::Summary(Vec::new()),
+            ParquetCategory::ResourceUsage => CategoryBuffer::Resources(Vec::new()),
             _ => CategoryBuffer::Files(Vec::new()),
         };
         Ok(Self {
@@ -316,6 +780,21 @@ impl CategoryWriter {
         }
     }
 
+    fn append_network(&mut self, row: NetworkArtefactRow) -> Result<(), MetadataError> {
+        match &mut self.buffer {
+            CategoryBuffer::Network(rows) => {
+                rows.push(row);
+                if rows.len() >= self.row_group_size {
+                    self.flush_buffer()?;
+                }
+                Ok(())
+            }
+            _ => Err(MetadataError::Other(
+                "network row on non-network category".to_string(),
+            )),
+        }
+    }
+
     fn append_history(&mut self, row: BrowserHistoryRow) -> Result<(), MetadataError> {
         match &mut self.buffer {
             CategoryBuffer::History(rows) => {
@@ -361,9 +840,9 @@ impl CategoryWriter {
         }
     }
 
-    fn append_entropy(&mut self, row: EntropyRegionRow) -> Result<(), MetadataError> {
+    fn append_cache(&mut self, row: BrowserCacheRow) -> Result<(), MetadataError> {
         match &mut self.buffer {
-            CategoryBuffer::Entropy(rows) => {
+            CategoryBuffer::Cache(rows) => {
                 rows.push(row);
                 if rows.len() >= self.row_group_size {
                     self.flush_buffer()?;
@@ -371,14 +850,14 @@ impl CategoryWriter {
                 Ok(())
             }
             _ => Err(MetadataError::Other(
-                "entropy row on non-entropy category".to_string(),
+                "browser cache row on non-cache category".to_string(),
             )),
         }
     }
 
-    fn append_summary(&mut self, row: RunSummaryRow) -> Result<(), MetadataError> {
+    fn append_log_event(&mut self, row: LogEventRow) -> Result<(), MetadataError> {
         match &mut self.buffer {
-            CategoryBuffer::Summary(rows) => {
+            CategoryBuffer::LogEvents(rows) => {
                 rows.push(row);
                 if rows.len() >= self.row_group_size {
                     self.flush_buffer()?;
@@ -386,688 +865,3677 @@ impl CategoryWriter {
                 Ok(())
             }
             _ => Err(MetadataError::Other(
-                "run summary row on non-summary category".to_string(),
+                "log event row on non-log category".to_string(),
             )),
         }
     }
 
-    fn flush_buffer(&mut self) -> Result<(), MetadataError> {
-        if self.buffer_len() == 0 {
-            return Ok(());
-        }
-        let batch = match &mut self.buffer {
-            CategoryBuffer::Files(rows) => {
-                let batch = build_files_batch(&self.context, rows, &self.schema)?;
-                rows.clear();
-                batch
-            }
-            CategoryBuffer::Urls(rows) => {
-                let batch = build_urls_batch(&self.context, rows, &self.schema)?;
-                rows.clear();
-                batch
-            }
-            CategoryBuffer::Emails(rows) => {
-                let batch = build_emails_batch(&self.context, rows, &self.schema)?;
-                rows.clear();
-                batch
-            }
-            CategoryBuffer::Phones(rows) => {
-                let batch = build_phones_batch(&self.context, rows, &self.schema)?;
-                rows.clear();
-                batch
-            }
-            CategoryBuffer::History(rows) => {
-                let batch = build_history_batch(&self.context, rows, &self.schema)?;
-                rows.clear();
-                batch
-            }
-            CategoryBuffer::Cookies(rows) => {
-                let batch = build_cookies_batch(&self.context, rows, &self.schema)?;
-                rows.clear();
-                batch
-            }
-            CategoryBuffer::Downloads(rows) => {
-                let batch = build_downloads_batch(&self.context, rows, &self.schema)?;
-                rows.clear();
-                batch
+    fn append_container(&mut self, row: ContainerArtefactRow) -> Result<(), MetadataError> {
+        match &mut self.buffer {
+            CategoryBuffer::Containers(rows) => {
+                rows.push(row);
+                if rows.len() >= self.row_group_size {
+                    self.flush_buffer()?;
+                }
+                Ok(())
             }
-            CategoryBuffer::Entropy(rows) => {
-                let batch = build_entropy_batch(&self.context, rows, &self.schema)?;
-                rows.clear();
-                batch
+            _ => Err(MetadataError::Other(
+                "container row on non-container category".to_string(),
+            )),
+        }
+    }
+
+    fn append_message(&mut self, row: MessageRow) -> Result<(), MetadataError> {
+        match &mut self.buffer {
+            CategoryBuffer::Messages(rows) => {
+                rows.push(row);
+                if rows.len() >= self.row_group_size {
+                    self.flush_buffer()?;
+                }
+                Ok(())
             }
-            CategoryBuffer::Summary(rows) => {
-                let batch = build_summary_batch(&self.context, rows, &self.schema)?;
-                rows.clear();
-                batch
+            _ => Err(MetadataError::Other(
+                "message row on non-message category".to_string(),
+            )),
+        }
+    }
+
+    fn append_usage_event(&mut self, row: UsageEventRow) -> Result<(), MetadataError> {
+        match &mut self.buffer {
+            CategoryBuffer::UsageEvents(rows) => {
+                rows.push(row);
+                if rows.len() >= self.row_group_size {
+                    self.flush_buffer()?;
+                }
+                Ok(())
             }
-        };
-        self.writer
-            .write(&batch)
-            .map_err(|err| MetadataError::Other(format!("parquet write error: {err}")))?;
-        Ok(())
+            _ => Err(MetadataError::Other(
+                "usage event row on non-usage event category".to_string(),
+            )),
+        }
     }
 
-    fn finish(&mut self) -> Result<(), MetadataError> {
-        if self.finished {
-            return Ok(());
+    fn append_app_record(&mut self, row: AppRecordRow) -> Result<(), MetadataError> {
+        match &mut self.buffer {
+            CategoryBuffer::AppRecords(rows) => {
+                rows.push(row);
+                if rows.len() >= self.row_group_size {
+                    self.flush_buffer()?;
+                }
+                Ok(())
+            }
+            _ => Err(MetadataError::Other(
+                "app record row on non-app record category".to_string(),
+            )),
         }
-        self.flush_buffer()?;
-        self.writer
-            .finish()
-            .map_err(|err| MetadataError::Other(format!("parquet finish error: {err}")))?;
-        self.finished = true;
-        Ok(())
     }
 
-    fn buffer_len(&self) -> usize {
-        match &self.buffer {
-            CategoryBuffer::Files(rows) => rows.len(),
-            CategoryBuffer::Urls(rows) => rows.len(),
-            CategoryBuffer::Emails(rows) => rows.len(),
-            CategoryBuffer::Phones(rows) => rows.len(),
-            CategoryBuffer::History(rows) => rows.len(),
-            CategoryBuffer::Cookies(rows) => rows.len(),
-            CategoryBuffer::Downloads(rows) => rows.len(),
-            CategoryBuffer::Entropy(rows) => rows.len(),
-            CategoryBuffer::Summary(rows) => rows.len(),
+    fn append_prefetch(&mut self, row: PrefetchRow) -> Result<(), MetadataError> {
+        match &mut self.buffer {
+            CategoryBuffer::PrefetchFiles(rows) => {
+                rows.push(row);
+                if rows.len() >= self.row_group_size {
+                    self.flush_buffer()?;
+                }
+                Ok(())
+            }
+            _ => Err(MetadataError::Other(
+                "prefetch row on non-prefetch category".to_string(),
+            )),
         }
     }
-}
 
-struct ParquetSinkInner {
-    context: Arc<ParquetContext>,
-    parquet_dir: PathBuf,
-    row_group_size: usize,
-    files_jpeg: Option<CategoryWriter>,
-    files_png: Option<CategoryWriter>,
-    files_gif: Option<CategoryWriter>,
-    files_sqlite: Option<CategoryWriter>,
-    files_pdf: Option<CategoryWriter>,
-    files_zip: Option<CategoryWriter>,
-    files_webp: Option<CategoryWriter>,
-    files_other: Option<CategoryWriter>,
-    artefacts_urls: Option<CategoryWriter>,
-    artefacts_emails: Option<CategoryWriter>,
-    artefacts_phones: Option<CategoryWriter>,
-    browser_history: Option<CategoryWriter>,
-    browser_cookies: Option<CategoryWriter>,
-    browser_downloads: Option<CategoryWriter>,
-    entropy_regions: Option<CategoryWriter>,
-    run_summary: Option<CategoryWriter>,
-}
-
-impl ParquetSinkInner {
-    fn get_or_create_writer(
-        &mut self,
-        category: ParquetCategory,
-    ) -> Result<&mut CategoryWriter, MetadataError> {
-        let slot = match category {
-            ParquetCategory::FilesJpeg => &mut self.files_jpeg,
-            ParquetCategory::FilesPng => &mut self.files_png,
-            ParquetCategory::FilesGif => &mut self.files_gif,
-            ParquetCategory::FilesSqlite => &mut self.files_sqlite,
-            ParquetCategory::FilesPdf => &mut self.files_pdf,
-            ParquetCategory::FilesZip => &mut self.files_zip,
-            ParquetCategory::FilesWebp => &mut self.files_webp,
-            ParquetCategory::FilesOther => &mut self.files_other,
-            ParquetCategory::ArtefactsUrls => &mut self.artefacts_urls,
-            ParquetCategory::ArtefactsEmails => &mut self.artefacts_emails,
-            ParquetCategory::ArtefactsPhones => &mut self.artefacts_phones,
-            ParquetCategory::BrowserHistory => &mut self.browser_history,
-            ParquetCategory::BrowserCookies => &mut self.browser_cookies,
-            ParquetCategory::BrowserDownloads => &mut self.browser_downloads,
-            ParquetCategory::EntropyRegions => &mut self.entropy_regions,
-            ParquetCategory::RunSummary => &mut self.run_summary,
-        };
-
-        if slot.is_none() {
-            let path = self.parquet_dir.join(category.filename());
-            let writer = CategoryWriter::new(
-                path,
-                category,
-                self.row_group_size,
-                Arc::clone(&self.context),
-            )?;
-            *slot = Some(writer);
+    fn append_shortcut(&mut self, row: ShortcutRow) -> Result<(), MetadataError> {
+        match &mut self.buffer {
+            CategoryBuffer::Shortcuts(rows) => {
+                rows.push(row);
+                if rows.len() >= self.row_group_size {
+                    self.flush_buffer()?;
+                }
+                Ok(())
+            }
+            _ => Err(MetadataError::Other(
+                "shortcut row on non-shortcut category".to_string(),
+            )),
         }
-
-        slot.as_mut().ok_or_else(|| {
-            MetadataError::Other("parquet writer slot missing after init".to_string())
-        })
     }
 
-    fn finish_all(&mut self) -> Result<(), MetadataError> {
-        if let Some(writer) = &mut self.files_jpeg {
-            writer.finish()?;
-        }
-        if let Some(writer) = &mut self.files_png {
-            writer.finish()?;
-        }
-        if let Some(writer) = &mut self.files_gif {
-            writer.finish()?;
-        }
-        if let Some(writer) = &mut self.files_sqlite {
-            writer.finish()?;
-        }
-        if let Some(writer) = &mut self.files_pdf {
-            writer.finish()?;
-        }
-        if let Some(writer) = &mut self.files_zip {
-            writer.finish()?;
-        }
-        if let Some(writer) = &mut self.files_webp {
-            writer.finish()?;
+    fn append_executable(&mut self, row: ExecutableRow) -> Result<(), MetadataError> {
+        match &mut self.buffer {
+            CategoryBuffer::Executables(rows) => {
+                rows.push(row);
+                if rows.len() >= self.row_group_size {
+                    self.flush_buffer()?;
+                }
+                Ok(())
+            }
+            _ => Err(MetadataError::Other(
+                "executable row on non-executable category".to_string(),
+            )),
         }
-        if let Some(writer) = &mut self.files_other {
-            writer.finish()?;
+    }
+
+    fn append_thumbnail(&mut self, row: ThumbnailRow) -> Result<(), MetadataError> {
+        match &mut self.buffer {
+            CategoryBuffer::Thumbnails(rows) => {
+                rows.push(row);
+                if rows.len() >= self.row_group_size {
+                    self.flush_buffer()?;
+                }
+                Ok(())
+            }
+            _ => Err(MetadataError::Other(
+                "thumbnail row on non-thumbnail category".to_string(),
+            )),
         }
-        if let Some(writer) = &mut self.artefacts_urls {
-            writer.finish()?;
+    }
+
+    fn append_plist(&mut self, row: PlistRow) -> Result<(), MetadataError> {
+        match &mut self.buffer {
+            CategoryBuffer::Plists(rows) => {
+                rows.push(row);
+                if rows.len() >= self.row_group_size {
+                    self.flush_buffer()?;
+                }
+                Ok(())
+            }
+            _ => Err(MetadataError::Other(
+                "plist row on non-plist category".to_string(),
+            )),
         }
-        if let Some(writer) = &mut self.artefacts_emails {
-            writer.finish()?;
+    }
+
+    fn append_video_repair(&mut self, row: VideoRepairRow) -> Result<(), MetadataError> {
+        match &mut self.buffer {
+            CategoryBuffer::VideoRepairs(rows) => {
+                rows.push(row);
+                if rows.len() >= self.row_group_size {
+                    self.flush_buffer()?;
+                }
+                Ok(())
+            }
+            _ => Err(MetadataError::Other(
+                "video repair row on non-video-repair category".to_string(),
+            )),
         }
-        if let Some(writer) = &mut self.artefacts_phones {
-            writer.finish()?;
+    }
+
+    fn append_image_info(&mut self, row: ImageInfoRow) -> Result<(), MetadataError> {
+        match &mut self.buffer {
+            CategoryBuffer::ImageInfo(rows) => {
+                rows.push(row);
+                if rows.len() >= self.row_group_size {
+                    self.flush_buffer()?;
+                }
+                Ok(())
+            }
+            _ => Err(MetadataError::Other(
+                "image info row on non-image info category".to_string(),
+            )),
         }
-        if let Some(writer) = &mut self.browser_history {
-            writer.finish()?;
+    }
+
+    fn append_photo_device(&mut self, row: PhotoDeviceRow) -> Result<(), MetadataError> {
+        match &mut self.buffer {
+            CategoryBuffer::PhotoDevices(rows) => {
+                rows.push(row);
+                if rows.len() >= self.row_group_size {
+                    self.flush_buffer()?;
+                }
+                Ok(())
+            }
+            _ => Err(MetadataError::Other(
+                "photo device row on non-photo device category".to_string(),
+            )),
         }
-        if let Some(writer) = &mut self.browser_cookies {
-            writer.finish()?;
+    }
+
+    fn append_ntfs_file(&mut self, row: NtfsFileRow) -> Result<(), MetadataError> {
+        match &mut self.buffer {
+            CategoryBuffer::NtfsFiles(rows) => {
+                rows.push(row);
+                if rows.len() >= self.row_group_size {
+                    self.flush_buffer()?;
+                }
+                Ok(())
+            }
+            _ => Err(MetadataError::Other(
+                "ntfs file row on non-ntfs file category".to_string(),
+            )),
         }
-        if let Some(writer) = &mut self.browser_downloads {
-            writer.finish()?;
+    }
+
+    fn append_input_file(&mut self, row: InputFileRow) -> Result<(), MetadataError> {
+        match &mut self.buffer {
+            CategoryBuffer::InputFiles(rows) => {
+                rows.push(row);
+                if rows.len() >= self.row_group_size {
+                    self.flush_buffer()?;
+                }
+                Ok(())
+            }
+            _ => Err(MetadataError::Other(
+                "input file row on non-input file category".to_string(),
+            )),
         }
-        if let Some(writer) = &mut self.entropy_regions {
-            writer.finish()?;
+    }
+
+    fn append_encrypted_document(
+        &mut self,
+        row: EncryptedDocumentRow,
+    ) -> Result<(), MetadataError> {
+        match &mut self.buffer {
+            CategoryBuffer::EncryptedDocuments(rows) => {
+                rows.push(row);
+                if rows.len() >= self.row_group_size {
+                    self.flush_buffer()?;
+                }
+                Ok(())
+            }
+            _ => Err(MetadataError::Other(
+                "encrypted document row on non-encrypted document category".to_string(),
+            )),
         }
-        if let Some(writer) = &mut self.run_summary {
-            writer.finish()?;
+    }
+
+    fn append_carve_size(&mut self, row: CarveSizeRow) -> Result<(), MetadataError> {
+        match &mut self.buffer {
+            CategoryBuffer::CarveSizes(rows) => {
+                rows.push(row);
+                if rows.len() >= self.row_group_size {
+                    self.flush_buffer()?;
+                }
+                Ok(())
+            }
+            _ => Err(MetadataError::Other(
+                "carve size row on non-carve size category".to_string(),
+            )),
         }
-        Ok(())
     }
 
-    /// Flush all writer buffers without finishing (allows continued writes)
-    fn flush_all_buffers(&mut self) -> Result<(), MetadataError> {
-        if let Some(writer) = &mut self.files_jpeg {
-            writer.flush_buffer()?;
+    fn append_carve_companion(&mut self, row: CarveCompanionRow) -> Result<(), MetadataError> {
+        match &mut self.buffer {
+            CategoryBuffer::CarveCompanions(rows) => {
+                rows.push(row);
+                if rows.len() >= self.row_group_size {
+                    self.flush_buffer()?;
+                }
+                Ok(())
+            }
+            _ => Err(MetadataError::Other(
+                "carve companion row on non-carve companion category".to_string(),
+            )),
         }
-        if let Some(writer) = &mut self.files_png {
-            writer.flush_buffer()?;
+    }
+
+    fn append_volume_location(&mut self, row: VolumeLocationRow) -> Result<(), MetadataError> {
+        match &mut self.buffer {
+            CategoryBuffer::VolumeLocations(rows) => {
+                rows.push(row);
+                if rows.len() >= self.row_group_size {
+                    self.flush_buffer()?;
+                }
+                Ok(())
+            }
+            _ => Err(MetadataError::Other(
+                "volume location row on non-volume location category".to_string(),
+            )),
         }
-        if let Some(writer) = &mut self.files_gif {
-            writer.flush_buffer()?;
+    }
+
+    fn append_classification(&mut self, row: ClassificationRow) -> Result<(), MetadataError> {
+        match &mut self.buffer {
+            CategoryBuffer::Classifications(rows) => {
+                rows.push(row);
+                if rows.len() >= self.row_group_size {
+                    self.flush_buffer()?;
+                }
+                Ok(())
+            }
+            _ => Err(MetadataError::Other(
+                "classification row on non-classification category".to_string(),
+            )),
         }
-        if let Some(writer) = &mut self.files_sqlite {
-            writer.flush_buffer()?;
+    }
+
+    fn append_entropy(&mut self, row: EntropyRegionRow) -> Result<(), MetadataError> {
+        match &mut self.buffer {
+            CategoryBuffer::Entropy(rows) => {
+                rows.push(row);
+                if rows.len() >= self.row_group_size {
+                    self.flush_buffer()?;
+                }
+                Ok(())
+            }
+            _ => Err(MetadataError::Other(
+                "entropy row on non-entropy category".to_string(),
+            )),
         }
-        if let Some(writer) = &mut self.files_pdf {
-            writer.flush_buffer()?;
+    }
+
+    fn append_summary(&mut self, row: RunSummaryRow) -> Result<(), MetadataError> {
+        match &mut self.buffer {
+            CategoryBuffer::Summary(rows) => {
+                rows.push(row);
+                if rows.len() >= self.row_group_size {
+                    self.flush_buffer()?;
+                }
+                Ok(())
+            }
+            _ => Err(MetadataError::Other(
+                "run summary row on non-summary category".to_string(),
+            )),
         }
-        if let Some(writer) = &mut self.files_zip {
-            writer.flush_buffer()?;
+    }
+
+    fn append_resources(&mut self, row: ResourceUsageRow) -> Result<(), MetadataError> {
+        match &mut self.buffer {
+            CategoryBuffer::Resources(rows) => {
+                rows.push(row);
+                if rows.len() >= self.row_group_size {
+                    self.flush_buffer()?;
+                }
+                Ok(())
+            }
+            _ => Err(MetadataError::Other(
+                "resource usage row on non-resource category".to_string(),
+            )),
         }
-        if let Some(writer) = &mut self.files_webp {
-            writer.flush_buffer()?;
+    }
+
+    fn flush_buffer(&mut self) -> Result<(), MetadataError> {
+        if self.buffer_len() == 0 {
+            return Ok(());
         }
-        if let Some(writer) = &mut self.files_other {
-            writer.flush_buffer()?;
+        let batch = match &mut self.buffer {
+            CategoryBuffer::Files(rows) => {
+                let batch = build_files_batch(&self.context, rows, &self.schema)?;
+                rows.clear();
+                batch
+            }
+            CategoryBuffer::Urls(rows) => {
+                let batch = build_urls_batch(&self.context, rows, &self.schema)?;
+                rows.clear();
+                batch
+            }
+            CategoryBuffer::Emails(rows) => {
+                let batch = build_emails_batch(&self.context, rows, &self.schema)?;
+                rows.clear();
+                batch
+            }
+            CategoryBuffer::Phones(rows) => {
+                let batch = build_phones_batch(&self.context, rows, &self.schema)?;
+                rows.clear();
+                batch
+            }
+            CategoryBuffer::Network(rows) => {
+                let batch = build_network_batch(&self.context, rows, &self.schema)?;
+                rows.clear();
+                batch
+            }
+            CategoryBuffer::History(rows) => {
+                let batch = build_history_batch(&self.context, rows, &self.schema)?;
+                rows.clear();
+                batch
+            }
+            CategoryBuffer::Cookies(rows) => {
+                let batch = build_cookies_batch(&self.context, rows, &self.schema)?;
+                rows.clear();
+                batch
+            }
+            CategoryBuffer::Downloads(rows) => {
+                let batch = build_downloads_batch(&self.context, rows, &self.schema)?;
+                rows.clear();
+                batch
+            }
+            CategoryBuffer::Cache(rows) => {
+                let batch = build_cache_batch(&self.context, rows, &self.schema)?;
+                rows.clear();
+                batch
+            }
+            CategoryBuffer::LogEvents(rows) => {
+                let batch = build_log_events_batch(&self.context, rows, &self.schema)?;
+                rows.clear();
+                batch
+            }
+            CategoryBuffer::Containers(rows) => {
+                let batch = build_containers_batch(&self.context, rows, &self.schema)?;
+                rows.clear();
+                batch
+            }
+            CategoryBuffer::Messages(rows) => {
+                let batch = build_messages_batch(&self.context, rows, &self.schema)?;
+                rows.clear();
+                batch
+            }
+            CategoryBuffer::UsageEvents(rows) => {
+                let batch = build_usage_events_batch(&self.context, rows, &self.schema)?;
+                rows.clear();
+                batch
+            }
+            CategoryBuffer::AppRecords(rows) => {
+                let batch = build_app_records_batch(&self.context, rows, &self.schema)?;
+                rows.clear();
+                batch
+            }
+            CategoryBuffer::PrefetchFiles(rows) => {
+                let batch = build_prefetch_files_batch(&self.context, rows, &self.schema)?;
+                rows.clear();
+                batch
+            }
+            CategoryBuffer::Shortcuts(rows) => {
+                let batch = build_shortcuts_batch(&self.context, rows, &self.schema)?;
+                rows.clear();
+                batch
+            }
+            CategoryBuffer::Executables(rows) => {
+                let batch = build_executables_batch(&self.context, rows, &self.schema)?;
+                rows.clear();
+                batch
+            }
+            CategoryBuffer::Thumbnails(rows) => {
+                let batch = build_thumbnails_batch(&self.context, rows, &self.schema)?;
+                rows.clear();
+                batch
+            }
+            CategoryBuffer::Plists(rows) => {
+                let batch = build_plists_batch(&self.context, rows, &self.schema)?;
+                rows.clear();
+                batch
+            }
+            CategoryBuffer::VideoRepairs(rows) => {
+                let batch = build_video_repairs_batch(&self.context, rows, &self.schema)?;
+                rows.clear();
+                batch
+            }
+            CategoryBuffer::ImageInfo(rows) => {
+                let batch = build_image_info_batch(&self.context, rows, &self.schema)?;
+                rows.clear();
+                batch
+            }
+            CategoryBuffer::PhotoDevices(rows) => {
+                let batch = build_photo_devices_batch(&self.context, rows, &self.schema)?;
+                rows.clear();
+                batch
+            }
+            CategoryBuffer::NtfsFiles(rows) => {
+                let batch = build_ntfs_files_batch(&self.context, rows, &self.schema)?;
+                rows.clear();
+                batch
+            }
+            CategoryBuffer::InputFiles(rows) => {
+                let batch = build_input_files_batch(&self.context, rows, &self.schema)?;
+                rows.clear();
+                batch
+            }
+            CategoryBuffer::EncryptedDocuments(rows) => {
+                let batch = build_encrypted_documents_batch(&self.context, rows, &self.schema)?;
+                rows.clear();
+                batch
+            }
+            CategoryBuffer::CarveSizes(rows) => {
+                let batch = build_carve_sizes_batch(&self.context, rows, &self.schema)?;
+                rows.clear();
+                batch
+            }
+            CategoryBuffer::CarveCompanions(rows) => {
+                let batch = build_carve_companions_batch(&self.context, rows, &self.schema)?;
+                rows.clear();
+                batch
+            }
+            CategoryBuffer::VolumeLocations(rows) => {
+                let batch = build_volume_locations_batch(&self.context, rows, &self.schema)?;
+                rows.clear();
+                batch
+            }
+            CategoryBuffer::Classifications(rows) => {
+                let batch = build_classifications_batch(&self.context, rows, &self.schema)?;
+                rows.clear();
+                batch
+            }
+            CategoryBuffer::Entropy(rows) => {
+                let batch = build_entropy_batch(&self.context, rows, &self.schema)?;
+                rows.clear();
+                batch
+            }
+            CategoryBuffer::Summary(rows) => {
+                let batch = build_summary_batch(&self.context, rows, &self.schema)?;
+                rows.clear();
+                batch
+            }
+            CategoryBuffer::Resources(rows) => {
+                let batch = build_resources_batch(&self.context, rows, &self.schema)?;
+                rows.clear();
+                batch
+            }
+        };
+        self.writer
+            .write(&batch)
+            .map_err(|err| MetadataError::Other(format!("parquet write error: {err}")))?;
+        Ok(())
+    }
+
+    /// Write the buffered rows; with `seal`, also close the open row group,
+    /// so every row recorded so far is in a complete row group.
+    fn flush_rows(&mut self, seal: bool) -> Result<(), MetadataError> {
+        self.flush_buffer()?;
+        if seal {
+            self.writer
+                .flush()
+                .map_err(|err| MetadataError::Other(format!("parquet write error: {err}")))?;
         }
-        if let Some(writer) = &mut self.artefacts_urls {
-            writer.flush_buffer()?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), MetadataError> {
+        if self.finished {
+            return Ok(());
         }
-        if let Some(writer) = &mut self.artefacts_emails {
-            writer.flush_buffer()?;
+        self.flush_buffer()?;
+        self.writer
+            .finish()
+            .map_err(|err| MetadataError::Other(format!("parquet finish error: {err}")))?;
+        self.finished = true;
+        Ok(())
+    }
+
+    fn buffer_len(&self) -> usize {
+        match &self.buffer {
+            CategoryBuffer::Files(rows) => rows.len(),
+            CategoryBuffer::Urls(rows) => rows.len(),
+            CategoryBuffer::Emails(rows) => rows.len(),
+            CategoryBuffer::Phones(rows) => rows.len(),
+            CategoryBuffer::Network(rows) => rows.len(),
+            CategoryBuffer::History(rows) => rows.len(),
+            CategoryBuffer::Cookies(rows) => rows.len(),
+            CategoryBuffer::Downloads(rows) => rows.len(),
+            CategoryBuffer::Cache(rows) => rows.len(),
+            CategoryBuffer::LogEvents(rows) => rows.len(),
+            CategoryBuffer::Containers(rows) => rows.len(),
+            CategoryBuffer::Messages(rows) => rows.len(),
+            CategoryBuffer::UsageEvents(rows) => rows.len(),
+            CategoryBuffer::AppRecords(rows) => rows.len(),
+            CategoryBuffer::PrefetchFiles(rows) => rows.len(),
+            CategoryBuffer::Shortcuts(rows) => rows.len(),
+            CategoryBuffer::Executables(rows) => rows.len(),
+            CategoryBuffer::Thumbnails(rows) => rows.len(),
+            CategoryBuffer::Plists(rows) => rows.len(),
+            CategoryBuffer::VideoRepairs(rows) => rows.len(),
+            CategoryBuffer::ImageInfo(rows) => rows.len(),
+            CategoryBuffer::PhotoDevices(rows) => rows.len(),
+            CategoryBuffer::NtfsFiles(rows) => rows.len(),
+            CategoryBuffer::InputFiles(rows) => rows.len(),
+            CategoryBuffer::EncryptedDocuments(rows) => rows.len(),
+            CategoryBuffer::CarveSizes(rows) => rows.len(),
+            CategoryBuffer::CarveCompanions(rows) => rows.len(),
+            CategoryBuffer::VolumeLocations(rows) => rows.len(),
+            CategoryBuffer::Classifications(rows) => rows.len(),
+            CategoryBuffer::Entropy(rows) => rows.len(),
+            CategoryBuffer::Summary(rows) => rows.len(),
+            CategoryBuffer::Resources(rows) => rows.len(),
         }
-        if let Some(writer) = &mut self.artefacts_phones {
-            writer.flush_buffer()?;
+    }
+}
+
+struct ParquetSinkInner {
+    context: Arc<ParquetContext>,
+    parquet_dir: PathBuf,
+    row_group_size: usize,
+    files_jpeg: Option<CategoryWriter>,
+    files_png: Option<CategoryWriter>,
+    files_gif: Option<CategoryWriter>,
+    files_sqlite: Option<CategoryWriter>,
+    files_pdf: Option<CategoryWriter>,
+    files_zip: Option<CategoryWriter>,
+    files_webp: Option<CategoryWriter>,
+    files_other: Option<CategoryWriter>,
+    artefacts_urls: Option<CategoryWriter>,
+    artefacts_emails: Option<CategoryWriter>,
+    artefacts_phones: Option<CategoryWriter>,
+    artefacts_network: Option<CategoryWriter>,
+    system_artifacts: Option<CategoryWriter>,
+    p2p_artifacts: Option<CategoryWriter>,
+    browser_history: Option<CategoryWriter>,
+    browser_cookies: Option<CategoryWriter>,
+    browser_downloads: Option<CategoryWriter>,
+    browser_cache: Option<CategoryWriter>,
+    log_events: Option<CategoryWriter>,
+    container_artefacts: Option<CategoryWriter>,
+    messages: Option<CategoryWriter>,
+    usage_events: Option<CategoryWriter>,
+    app_records: Option<CategoryWriter>,
+    prefetch_files: Option<CategoryWriter>,
+    shortcuts: Option<CategoryWriter>,
+    executables: Option<CategoryWriter>,
+    thumbnails: Option<CategoryWriter>,
+    plists: Option<CategoryWriter>,
+    video_repairs: Option<CategoryWriter>,
+    image_info: Option<CategoryWriter>,
+    photo_devices: Option<CategoryWriter>,
+    ntfs_files: Option<CategoryWriter>,
+    input_files: Option<CategoryWriter>,
+    encrypted_documents: Option<CategoryWriter>,
+    carve_sizes: Option<CategoryWriter>,
+    carve_companions: Option<CategoryWriter>,
+    volume_locations: Option<CategoryWriter>,
+    classifications: Option<CategoryWriter>,
+    entropy_regions: Option<CategoryWriter>,
+    run_summary: Option<CategoryWriter>,
+    resource_usage: Option<CategoryWriter>,
+}
+
+impl ParquetSinkInner {
+    fn get_or_create_writer(
+        &mut self,
+        category: ParquetCategory,
+    ) -> Result<&mut CategoryWriter, MetadataError> {
+        let slot = match category {
+            ParquetCategory::FilesJpeg => &mut self.files_jpeg,
+            ParquetCategory::FilesPng => &mut self.files_png,
+            ParquetCategory::FilesGif => &mut self.files_gif,
+            ParquetCategory::FilesSqlite => &mut self.files_sqlite,
+            ParquetCategory::FilesPdf => &mut self.files_pdf,
+            ParquetCategory::FilesZip => &mut self.files_zip,
+            ParquetCategory::FilesWebp => &mut self.files_webp,
+            ParquetCategory::FilesOther => &mut self.files_other,
+            ParquetCategory::ArtefactsUrls => &mut self.artefacts_urls,
+            ParquetCategory::ArtefactsEmails => &mut self.artefacts_emails,
+            ParquetCategory::ArtefactsPhones => &mut self.artefacts_phones,
+            ParquetCategory::ArtefactsNetwork => &mut self.artefacts_network,
+            ParquetCategory::ArtefactsSystem => &mut self.system_artifacts,
+            ParquetCategory::ArtefactsP2p => &mut self.p2p_artifacts,
+            ParquetCategory::BrowserHistory => &mut self.browser_history,
+            ParquetCategory::BrowserCookies => &mut self.browser_cookies,
+            ParquetCategory::BrowserDownloads => &mut self.browser_downloads,
+            ParquetCategory::BrowserCache => &mut self.browser_cache,
+            ParquetCategory::LogEvents => &mut self.log_events,
+            ParquetCategory::ContainerArtefacts => &mut self.container_artefacts,
+            ParquetCategory::Messages => &mut self.messages,
+            ParquetCategory::UsageEvents => &mut self.usage_events,
+            ParquetCategory::AppRecords => &mut self.app_records,
+            ParquetCategory::PrefetchFiles => &mut self.prefetch_files,
+            ParquetCategory::Shortcuts => &mut self.shortcuts,
+            ParquetCategory::Executables => &mut self.executables,
+            ParquetCategory::Thumbnails => &mut self.thumbnails,
+            ParquetCategory::Plists => &mut self.plists,
+            ParquetCategory::VideoRepairs => &mut self.video_repairs,
+            ParquetCategory::ImageInfo => &mut self.image_info,
+            ParquetCategory::PhotoDevices => &mut self.photo_devices,
+            ParquetCategory::NtfsFiles => &mut self.ntfs_files,
+            ParquetCategory::InputFiles => &mut self.input_files,
+            ParquetCategory::EncryptedDocuments => &mut self.encrypted_documents,
+            ParquetCategory::CarveSizes => &mut self.carve_sizes,
+            ParquetCategory::CarveCompanions => &mut self.carve_companions,
+            ParquetCategory::VolumeLocations => &mut self.volume_locations,
+            ParquetCategory::Classifications => &mut self.classifications,
+            ParquetCategory::EntropyRegions => &mut self.entropy_regions,
+            ParquetCategory::RunSummary => &mut self.run_summary,
+            ParquetCategory::ResourceUsage => &mut self.resource_usage,
+        };
+
+        if slot.is_none() {
+            let path = self
+                .parquet_dir
+                .join(pass_filename(category.filename(), self.context.pass_id));
+            let writer = CategoryWriter::new(
+                path,
+                category,
+                self.row_group_size,
+                Arc::clone(&self.context),
+            )?;
+            *slot = Some(writer);
         }
-        if let Some(writer) = &mut self.browser_history {
-            writer.flush_buffer()?;
+
+        slot.as_mut().ok_or_else(|| {
+            MetadataError::Other("parquet writer slot missing after init".to_string())
+        })
+    }
+
+    fn finish_all(&mut self) -> Result<(), MetadataError> {
+        if let Some(writer) = &mut self.files_jpeg {
+            writer.finish()?;
         }
-        if let Some(writer) = &mut self.browser_cookies {
-            writer.flush_buffer()?;
+        if let Some(writer) = &mut self.files_png {
+            writer.finish()?;
         }
-        if let Some(writer) = &mut self.browser_downloads {
-            writer.flush_buffer()?;
+        if let Some(writer) = &mut self.files_gif {
+            writer.finish()?;
+        }
+        if let Some(writer) = &mut self.files_sqlite {
+            writer.finish()?;
+        }
+        if let Some(writer) = &mut self.files_pdf {
+            writer.finish()?;
+        }
+        if let Some(writer) = &mut self.files_zip {
+            writer.finish()?;
+        }
+        if let Some(writer) = &mut self.files_webp {
+            writer.finish()?;
+        }
+        if let Some(writer) = &mut self.files_other {
+            writer.finish()?;
+        }
+        if let Some(writer) = &mut self.artefacts_urls {
+            writer.finish()?;
+        }
+        if let Some(writer) = &mut self.artefacts_emails {
+            writer.finish()?;
+        }
+        if let Some(writer) = &mut self.artefacts_phones {
+            writer.finish()?;
+        }
+        if let Some(writer) = &mut self.artefacts_network {
+            writer.finish()?;
+        }
+        if let Some(writer) = &mut self.system_artifacts {
+            writer.finish()?;
+        }
+        if let Some(writer) = &mut self.p2p_artifacts {
+            writer.finish()?;
+        }
+        if let Some(writer) = &mut self.browser_history {
+            writer.finish()?;
+        }
+        if let Some(writer) = &mut self.browser_cookies {
+            writer.finish()?;
+        }
+        if let Some(writer) = &mut self.browser_downloads {
+            writer.finish()?;
+        }
+        if let Some(writer) = &mut self.browser_cache {
+            writer.finish()?;
+        }
+        if let Some(writer) = &mut self.log_events {
+            writer.finish()?;
+        }
+        if let Some(writer) = &mut self.container_artefacts {
+            writer.finish()?;
+        }
+        if let Some(writer) = &mut self.messages {
+            writer.finish()?;
+        }
+        if let Some(writer) = &mut self.usage_events {
+            writer.finish()?;
+        }
+        if let Some(writer) = &mut self.app_records {
+            writer.finish()?;
+        }
+        if let Some(writer) = &mut self.prefetch_files {
+            writer.finish()?;
+        }
+        if let Some(writer) = &mut self.shortcuts {
+            writer.finish()?;
+        }
+        if let Some(writer) = &mut self.executables {
+            writer.finish()?;
+        }
+        if let Some(writer) = &mut self.thumbnails {
+            writer.finish()?;
+        }
+        if let Some(writer) = &mut self.plists {
+            writer.finish()?;
+        }
+        if let Some(writer) = &mut self.video_repairs {
+            writer.finish()?;
+        }
+        if let Some(writer) = &mut self.image_info {
+            writer.finish()?;
+        }
+        if let Some(writer) = &mut self.photo_devices {
+            writer.finish()?;
+        }
+        if let Some(writer) = &mut self.ntfs_files {
+            writer.finish()?;
+        }
+        if let Some(writer) = &mut self.input_files {
+            writer.finish()?;
+        }
+        if let Some(writer) = &mut self.encrypted_documents {
+            writer.finish()?;
+        }
+        if let Some(writer) = &mut self.carve_sizes {
+            writer.finish()?;
+        }
+        if let Some(writer) = &mut self.carve_companions {
+            writer.finish()?;
+        }
+        if let Some(writer) = &mut self.volume_locations {
+            writer.finish()?;
+        }
+        if let Some(writer) = &mut self.classifications {
+            writer.finish()?;
+        }
+        if let Some(writer) = &mut self.entropy_regions {
+            writer.finish()?;
+        }
+        if let Some(writer) = &mut self.run_summary {
+            writer.finish()?;
+        }
+        if let Some(writer) = &mut self.resource_usage {
+            writer.finish()?;
+        }
+        Ok(())
+    }
+
+    /// Flush all writer buffers without finishing (allows continued writes);
+    /// with `seal`, also close the open row groups.
+    fn flush_all_buffers(&mut self, seal: bool) -> Result<(), MetadataError> {
+        if let Some(writer) = &mut self.files_jpeg {
+            writer.flush_rows(seal)?;
+        }
+        if let Some(writer) = &mut self.files_png {
+            writer.flush_rows(seal)?;
+        }
+        if let Some(writer) = &mut self.files_gif {
+            writer.flush_rows(seal)?;
+        }
+        if let Some(writer) = &mut self.files_sqlite {
+            writer.flush_rows(seal)?;
+        }
+        if let Some(writer) = &mut self.files_pdf {
+            writer.flush_rows(seal)?;
+        }
+        if let Some(writer) = &mut self.files_zip {
+            writer.flush_rows(seal)?;
+        }
+        if let Some(writer) = &mut self.files_webp {
+            writer.flush_rows(seal)?;
+        }
+        if let Some(writer) = &mut self.files_other {
+            writer.flush_rows(seal)?;
+        }
+        if let Some(writer) = &mut self.artefacts_urls {
+            writer.flush_rows(seal)?;
+        }
+        if let Some(writer) = &mut self.artefacts_emails {
+            writer.flush_rows(seal)?;
+        }
+        if let Some(writer) = &mut self.artefacts_phones {
+            writer.flush_rows(seal)?;
+        }
+        if let Some(writer) = &mut self.artefacts_network {
+            writer.flush_rows(seal)?;
+        }
+        if let Some(writer) = &mut self.system_artifacts {
+            writer.flush_rows(seal)?;
+        }
+        if let Some(writer) = &mut self.p2p_artifacts {
+            writer.flush_rows(seal)?;
+        }
+        if let Some(writer) = &mut self.browser_history {
+            writer.flush_rows(seal)?;
+        }
+        if let Some(writer) = &mut self.browser_cookies {
+            writer.flush_rows(seal)?;
+        }
+        if let Some(writer) = &mut self.browser_downloads {
+            writer.flush_rows(seal)?;
+        }
+        if let Some(writer) = &mut self.browser_cache {
+            writer.flush_rows(seal)?;
+        }
+        if let Some(writer) = &mut self.log_events {
+            writer.flush_rows(seal)?;
+        }
+        if let Some(writer) = &mut self.container_artefacts {
+            writer.flush_rows(seal)?;
+        }
+        if let Some(writer) = &mut self.messages {
+            writer.flush_rows(seal)?;
+        }
+        if let Some(writer) = &mut self.usage_events {
+            writer.flush_rows(seal)?;
+        }
+        if let Some(writer) = &mut self.app_records {
+            writer.flush_rows(seal)?;
+        }
+        if let Some(writer) = &mut self.prefetch_files {
+            writer.flush_rows(seal)?;
+        }
+        if let Some(writer) = &mut self.shortcuts {
+            writer.flush_rows(seal)?;
+        }
+        if let Some(writer) = &mut self.executables {
+            writer.flush_rows(seal)?;
+        }
+        if let Some(writer) = &mut self.thumbnails {
+            writer.flush_rows(seal)?;
+        }
+        if let Some(writer) = &mut self.plists {
+            writer.flush_rows(seal)?;
+        }
+        if let Some(writer) = &mut self.video_repairs {
+            writer.flush_rows(seal)?;
+        }
+        if let Some(writer) = &mut self.image_info {
+            writer.flush_rows(seal)?;
+        }
+        if let Some(writer) = &mut self.photo_devices {
+            writer.flush_rows(seal)?;
+        }
+        if let Some(writer) = &mut self.ntfs_files {
+            writer.flush_rows(seal)?;
+        }
+        if let Some(writer) = &mut self.input_files {
+            writer.flush_rows(seal)?;
+        }
+        if let Some(writer) = &mut self.encrypted_documents {
+            writer.flush_rows(seal)?;
+        }
+        if let Some(writer) = &mut self.carve_sizes {
+            writer.flush_rows(seal)?;
+        }
+        if let Some(writer) = &mut self.carve_companions {
+            writer.flush_rows(seal)?;
+        }
+        if let Some(writer) = &mut self.volume_locations {
+            writer.flush_rows(seal)?;
+        }
+        if let Some(writer) = &mut self.classifications {
+            writer.flush_rows(seal)?;
         }
         if let Some(writer) = &mut self.entropy_regions {
-            writer.flush_buffer()?;
+            writer.flush_rows(seal)?;
         }
         if let Some(writer) = &mut self.run_summary {
-            writer.flush_buffer()?;
+            writer.flush_rows(seal)?;
+        }
+        if let Some(writer) = &mut self.resource_usage {
+            writer.flush_rows(seal)?;
         }
         Ok(())
     }
 }
 
-pub struct ParquetSink {
-    inner: Mutex<ParquetSinkInner>,
+pub struct ParquetSink {
+    inner: Mutex<ParquetSinkInner>,
+}
+
+impl ParquetSink {
+    pub fn new(
+        cfg: &Config,
+        identity: &SinkIdentity,
+        run_output_dir: &Path,
+        cipher: Option<&OutputCipher>,
+    ) -> Result<Self, MetadataError> {
+        let parquet_dir = run_output_dir.join("parquet");
+        std::fs::create_dir_all(&parquet_dir)?;
+        let context = Arc::new(ParquetContext {
+            run_id: identity.run_id.to_string(),
+            tool_version: identity.tool_version.to_string(),
+            config_hash: identity.config_hash.to_string(),
+            evidence_path: identity.evidence_path.to_string_lossy().to_string(),
+            evidence_sha256: identity.evidence_sha256.to_string(),
+            pass_id: identity.pass_id,
+            cipher: cipher.cloned(),
+        });
+
+        Ok(Self {
+            inner: Mutex::new(ParquetSinkInner {
+                context,
+                parquet_dir,
+                row_group_size: cfg.parquet_row_group_size.max(1),
+                files_jpeg: None,
+                files_png: None,
+                files_gif: None,
+                files_sqlite: None,
+                files_pdf: None,
+                files_zip: None,
+                files_webp: None,
+                files_other: None,
+                artefacts_urls: None,
+                artefacts_emails: None,
+                artefacts_phones: None,
+                artefacts_network: None,
+                system_artifacts: None,
+                p2p_artifacts: None,
+                browser_history: None,
+                browser_cookies: None,
+                browser_downloads: None,
+                browser_cache: None,
+                log_events: None,
+                container_artefacts: None,
+                messages: None,
+                usage_events: None,
+                app_records: None,
+                prefetch_files: None,
+                shortcuts: None,
+                executables: None,
+                thumbnails: None,
+                plists: None,
+                video_repairs: None,
+                image_info: None,
+                photo_devices: None,
+                ntfs_files: None,
+                input_files: None,
+                encrypted_documents: None,
+                carve_sizes: None,
+                carve_companions: None,
+                volume_locations: None,
+                classifications: None,
+                entropy_regions: None,
+                run_summary: None,
+                resource_usage: None,
+            }),
+        })
+    }
+
+    fn lock_inner(&self) -> Result<std::sync::MutexGuard<'_, ParquetSinkInner>, MetadataError> {
+        self.inner
+            .lock()
+            .map_err(|_| MetadataError::Other("parquet sink lock poisoned".to_string()))
+    }
+}
+
+impl MetadataSink for ParquetSink {
+    fn record_file(&self, file: &CarvedFile) -> Result<(), MetadataError> {
+        let category = category_for_file_type(&file.file_type);
+        let row = FileRow {
+            handler_id: file.signature_type.clone(),
+            file_type: file.file_type.clone(),
+            carved_path: file.path.clone(),
+            global_start: to_i64(file.global_start)?,
+            global_end: to_i64(file.global_end)?,
+            size: to_i64(file.size)?,
+            md5: file.md5.clone(),
+            sha256: file.sha256.clone(),
+            pattern_id: file.pattern_id.clone(),
+            magic_bytes: None,
+            validated: file.validated,
+            truncated: file.truncated,
+            error: join_errors(&file.errors),
+        };
+
+        let mut inner = self.lock_inner()?;
+        let writer = inner.get_or_create_writer(category)?;
+        writer.append_file(row)
+    }
+
+    fn record_string(&self, artefact: &StringArtefact) -> Result<(), MetadataError> {
+        let mut inner = self.lock_inner()?;
+        match artefact.artefact_kind {
+            ArtefactKind::Url => {
+                let row = map_url_artefact(artefact)?;
+                let writer = inner.get_or_create_writer(ParquetCategory::ArtefactsUrls)?;
+                writer.append_url(row)
+            }
+            ArtefactKind::Email => {
+                let row = map_email_artefact(artefact)?;
+                let writer = inner.get_or_create_writer(ParquetCategory::ArtefactsEmails)?;
+                writer.append_email(row)
+            }
+            ArtefactKind::Phone => {
+                let row = map_phone_artefact(artefact)?;
+                let writer = inner.get_or_create_writer(ParquetCategory::ArtefactsPhones)?;
+                writer.append_phone(row)
+            }
+            ArtefactKind::HttpHost | ArtefactKind::DnsQuery | ArtefactKind::Connection => {
+                let row = map_network_artefact(artefact)?;
+                let writer = inner.get_or_create_writer(ParquetCategory::ArtefactsNetwork)?;
+                writer.append_network(row)
+            }
+            ArtefactKind::SshPublicKey
+            | ArtefactKind::SshPrivateKey
+            | ArtefactKind::SshKnownHost
+            | ArtefactKind::ShellHistory
+            | ArtefactKind::Credential => {
+                let row = map_system_artefact(artefact)?;
+                let writer = inner.get_or_create_writer(ParquetCategory::ArtefactsSystem)?;
+                writer.append_network(row)
+            }
+            ArtefactKind::MagnetUri
+            | ArtefactKind::Ed2kLink
+            | ArtefactKind::TorrentInfoHash
+            | ArtefactKind::TorrentName
+            | ArtefactKind::TorrentTracker
+            | ArtefactKind::DhtNode => {
+                let row = map_p2p_artefact(artefact)?;
+                let writer = inner.get_or_create_writer(ParquetCategory::ArtefactsP2p)?;
+                writer.append_network(row)
+            }
+            ArtefactKind::GenericString => Ok(()),
+        }
+    }
+
+    fn record_history(&self, record: &BrowserHistoryRecord) -> Result<(), MetadataError> {
+        let row = BrowserHistoryRow {
+            source_file: record.source_file.to_string_lossy().to_string(),
+            browser: record.browser.clone(),
+            profile: record.profile.clone(),
+            url: record.url.clone(),
+            title: record.title.clone(),
+            visit_time_utc: record.visit_time.map(to_micros),
+            visit_source: record.visit_source.clone(),
+            row_id: None,
+            table_name: None,
+        };
+
+        let mut inner = self.lock_inner()?;
+        let writer = inner.get_or_create_writer(ParquetCategory::BrowserHistory)?;
+        writer.append_history(row)
+    }
+
+    fn record_cookie(&self, record: &BrowserCookieRecord) -> Result<(), MetadataError> {
+        let row = BrowserCookieRow {
+            source_file: record.source_file.to_string_lossy().to_string(),
+            browser: record.browser.clone(),
+            profile: record.profile.clone(),
+            host: record.host.clone(),
+            name: record.name.clone(),
+            value: record.value.clone(),
+            path: record.path.clone(),
+            expires_utc: record.expires_utc.map(to_micros),
+            last_access_utc: record.last_access_utc.map(to_micros),
+            creation_utc: record.creation_utc.map(to_micros),
+            is_secure: record.is_secure,
+            is_http_only: record.is_http_only,
+        };
+
+        let mut inner = self.lock_inner()?;
+        let writer = inner.get_or_create_writer(ParquetCategory::BrowserCookies)?;
+        writer.append_cookie(row)
+    }
+
+    fn record_download(&self, record: &BrowserDownloadRecord) -> Result<(), MetadataError> {
+        let row = BrowserDownloadRow {
+            source_file: record.source_file.to_string_lossy().to_string(),
+            browser: record.browser.clone(),
+            profile: record.profile.clone(),
+            url: record.url.clone(),
+            target_path: record.target_path.clone(),
+            start_time_utc: record.start_time.map(to_micros),
+            end_time_utc: record.end_time.map(to_micros),
+            total_bytes: record.total_bytes,
+            state: record.state.clone(),
+        };
+
+        let mut inner = self.lock_inner()?;
+        let writer = inner.get_or_create_writer(ParquetCategory::BrowserDownloads)?;
+        writer.append_download(row)
+    }
+
+    fn record_cache(&self, record: &BrowserCacheRecord) -> Result<(), MetadataError> {
+        let row = BrowserCacheRow {
+            source_file: record.source_file.to_string_lossy().to_string(),
+            browser: record.browser.clone(),
+            url: record.url.clone(),
+            http_status: record.http_status.map(i32::from),
+            content_type: record.content_type.clone(),
+            content_encoding: record.content_encoding.clone(),
+            response_headers: record.response_headers.clone(),
+            body_size: to_i64(record.body_size)?,
+            body_path: record.body_path.clone(),
+        };
+
+        let mut inner = self.lock_inner()?;
+        let writer = inner.get_or_create_writer(ParquetCategory::BrowserCache)?;
+        writer.append_cache(row)
+    }
+
+    fn record_log_event(&self, record: &LogEventRecord) -> Result<(), MetadataError> {
+        let row = LogEventRow {
+            source: record.source.clone(),
+            timestamp_utc: record.timestamp.map(to_micros),
+            timestamp_raw: record.timestamp_raw.clone(),
+            host: record.host.clone(),
+            program: record.program.clone(),
+            pid: record.pid.map(i64::from),
+            severity: record.severity.map(i32::from),
+            message: record.message.clone(),
+            global_offset: to_i64(record.global_offset)?,
+            source_file: record.source_file.clone(),
+        };
+
+        let mut inner = self.lock_inner()?;
+        let writer = inner.get_or_create_writer(ParquetCategory::LogEvents)?;
+        writer.append_log_event(row)
+    }
+
+    fn record_container(&self, record: &ContainerArtefactRecord) -> Result<(), MetadataError> {
+        let row = ContainerArtefactRow {
+            kind: record.kind.clone(),
+            name: record.name.clone(),
+            digest: record.digest.clone(),
+            namespace: record.namespace.clone(),
+            detail: record.detail.clone(),
+            global_offset: to_i64(record.global_offset)?,
+            source_file: record.source_file.clone(),
+        };
+
+        let mut inner = self.lock_inner()?;
+        let writer = inner.get_or_create_writer(ParquetCategory::ContainerArtefacts)?;
+        writer.append_container(row)
+    }
+
+    fn record_run_summary(&self, summary: &RunSummary) -> Result<(), MetadataError> {
+        let row = RunSummaryRow {
+            bytes_scanned: to_i64(summary.bytes_scanned)?,
+            chunks_processed: to_i64(summary.chunks_processed)?,
+            hits_found: to_i64(summary.hits_found)?,
+            files_carved: to_i64(summary.files_carved)?,
+            string_spans: to_i64(summary.string_spans)?,
+            artefacts_extracted: to_i64(summary.artefacts_extracted)?,
+        };
+        let mut inner = self.lock_inner()?;
+        let writer = inner.get_or_create_writer(ParquetCategory::RunSummary)?;
+        writer.append_summary(row)
+    }
+
+    fn record_resource_usage(
+        &self,
+        usage: &crate::metadata::ResourceUsage,
+    ) -> Result<(), MetadataError> {
+        let row = ResourceUsageRow {
+            stage: usage.stage.clone(),
+            threads: to_i64(usage.threads)?,
+            busy_secs: usage.busy_secs,
+            user_cpu_secs: usage.user_cpu_secs,
+            sys_cpu_secs: usage.sys_cpu_secs,
+            bytes_read: usage.bytes_read.map(to_i64).transpose()?,
+            bytes_written: usage.bytes_written.map(to_i64).transpose()?,
+            gpu_secs: usage.gpu_secs,
+            peak_rss_bytes: usage.peak_rss_bytes.map(to_i64).transpose()?,
+        };
+        let mut inner = self.lock_inner()?;
+        let writer = inner.get_or_create_writer(ParquetCategory::ResourceUsage)?;
+        writer.append_resources(row)
+    }
+
+    fn record_message(&self, record: &MessageRecord) -> Result<(), MetadataError> {
+        let row = MessageRow {
+            app: record.app.clone(),
+            message_id: record.message_id.clone(),
+            thread: record.thread.clone(),
+            handle: record.handle.clone(),
+            is_from_me: record.is_from_me,
+            service: record.service.clone(),
+            text: record.text.clone(),
+            timestamp_utc: record.timestamp_utc.map(to_micros),
+            modified_utc: record.modified_utc.map(to_micros),
+            source_file: record.source_file.clone(),
+        };
+
+        let mut inner = self.lock_inner()?;
+        let writer = inner.get_or_create_writer(ParquetCategory::Messages)?;
+        writer.append_message(row)
+    }
+
+    fn record_usage_event(&self, record: &UsageEventRecord) -> Result<(), MetadataError> {
+        let row = UsageEventRow {
+            source: record.source.clone(),
+            stream: record.stream.clone(),
+            value: record.value.clone(),
+            bundle_id: record.bundle_id.clone(),
+            start_utc: record.start_utc.map(to_micros),
+            end_utc: record.end_utc.map(to_micros),
+            source_file: record.source_file.clone(),
+        };
+
+        let mut inner = self.lock_inner()?;
+        let writer = inner.get_or_create_writer(ParquetCategory::UsageEvents)?;
+        writer.append_usage_event(row)
+    }
+
+    fn record_app_record(&self, record: &AppRecord) -> Result<(), MetadataError> {
+        let row = AppRecordRow {
+            app: record.app.clone(),
+            plan: record.plan.clone(),
+            row_id: record.row_id,
+            timestamp_utc: record.timestamp_utc.map(to_micros),
+            timestamp_desc: record.timestamp_desc.clone(),
+            fields: record.fields.clone(),
+            source_file: record.source_file.clone(),
+        };
+
+        let mut inner = self.lock_inner()?;
+        let writer = inner.get_or_create_writer(ParquetCategory::AppRecords)?;
+        writer.append_app_record(row)
+    }
+
+    fn record_prefetch(&self, record: &PrefetchRecord) -> Result<(), MetadataError> {
+        let row = PrefetchRow {
+            executable: record.executable.clone(),
+            prefetch_hash: record.prefetch_hash.clone(),
+            format_version: record.format_version,
+            compressed: record.compressed,
+            run_count: record.run_count,
+            last_run_utc: record.last_run_utc.map(to_micros),
+            earlier_runs_utc: record.earlier_runs_utc.clone(),
+            source_file: record.source_file.clone(),
+        };
+
+        let mut inner = self.lock_inner()?;
+        let writer = inner.get_or_create_writer(ParquetCategory::PrefetchFiles)?;
+        writer.append_prefetch(row)
+    }
+
+    fn record_shortcut(&self, record: &ShortcutRecord) -> Result<(), MetadataError> {
+        let row = ShortcutRow {
+            target_path: record.target_path.clone(),
+            arguments: record.arguments.clone(),
+            working_dir: record.working_dir.clone(),
+            relative_path: record.relative_path.clone(),
+            description: record.description.clone(),
+            icon_location: record.icon_location.clone(),
+            target_size: record.target_size,
+            file_attributes: record.file_attributes,
+            target_created_utc: record.target_created_utc.map(to_micros),
+            target_accessed_utc: record.target_accessed_utc.map(to_micros),
+            target_modified_utc: record.target_modified_utc.map(to_micros),
+            volume_serial: record.volume_serial.clone(),
+            machine_id: record.machine_id.clone(),
+            source_file: record.source_file.clone(),
+        };
+
+        let mut inner = self.lock_inner()?;
+        let writer = inner.get_or_create_writer(ParquetCategory::Shortcuts)?;
+        writer.append_shortcut(row)
+    }
+
+    fn record_executable(&self, record: &ExecutableRecord) -> Result<(), MetadataError> {
+        let row = ExecutableRow {
+            format: record.format.clone(),
+            kind: record.kind.clone(),
+            architecture: record.architecture.clone(),
+            bits: record.bits,
+            compile_time_utc: record.compile_time_utc.map(to_micros),
+            subsystem: record.subsystem.clone(),
+            section_count: record.section_count,
+            import_count: record.import_count,
+            import_hash: record.import_hash.clone(),
+            signed: record.signed,
+            source_file: record.source_file.clone(),
+        };
+
+        let mut inner = self.lock_inner()?;
+        let writer = inner.get_or_create_writer(ParquetCategory::Executables)?;
+        writer.append_executable(row)
+    }
+
+    fn record_plist(&self, record: &PlistRecord) -> Result<(), MetadataError> {
+        let row = PlistRow {
+            object_count: record.object_count,
+            top_type: record.top_type.clone(),
+            top_keys: record.top_keys.clone(),
+            archiver: record.archiver.clone(),
+            json_len: record.json_len,
+            json: record.json.clone(),
+            source_file: record.source_file.clone(),
+        };
+
+        let mut inner = self.lock_inner()?;
+        let writer = inner.get_or_create_writer(ParquetCategory::Plists)?;
+        writer.append_plist(row)
+    }
+
+    fn record_video_repair(&self, record: &VideoRepairRecord) -> Result<(), MetadataError> {
+        let row = VideoRepairRow {
+            method: record.method.clone(),
+            codec: record.codec.clone(),
+            width: record.width,
+            height: record.height,
+            frame_count: record.frame_count,
+            keyframe_count: record.keyframe_count,
+            duration_secs: record.duration_secs,
+            media_bytes: record.media_bytes,
+            skipped_bytes: record.skipped_bytes,
+            donor_file: record.donor_file.clone(),
+            repaired_path: record.repaired_path.clone(),
+            source_file: record.source_file.clone(),
+        };
+
+        let mut inner = self.lock_inner()?;
+        let writer = inner.get_or_create_writer(ParquetCategory::VideoRepairs)?;
+        writer.append_video_repair(row)
+    }
+
+    fn record_thumbnail(&self, record: &ThumbnailRecord) -> Result<(), MetadataError> {
+        let row = ThumbnailRow {
+            cache_id: record.cache_id.clone(),
+            cache_version: record.cache_version.clone(),
+            cache_type: record.cache_type,
+            entry_offset: record.entry_offset,
+            width: record.width,
+            height: record.height,
+            image_format: record.image_format.clone(),
+            data_size: record.data_size,
+            image_path: record.image_path.clone(),
+            source_file: record.source_file.clone(),
+        };
+
+        let mut inner = self.lock_inner()?;
+        let writer = inner.get_or_create_writer(ParquetCategory::Thumbnails)?;
+        writer.append_thumbnail(row)
+    }
+
+    fn record_image_info(&self, record: &ImageInfoRecord) -> Result<(), MetadataError> {
+        let row = ImageInfoRow {
+            format: record.format.clone(),
+            width: i64::from(record.width),
+            height: i64::from(record.height),
+            bits_per_pixel: record.bits_per_pixel.map(i64::from),
+            decoded_pct: record.decoded_pct,
+            corrupt_offset: record.corrupt_offset.map(to_i64).transpose()?,
+            source_file: record.source_file.clone(),
+        };
+
+        let mut inner = self.lock_inner()?;
+        let writer = inner.get_or_create_writer(ParquetCategory::ImageInfo)?;
+        writer.append_image_info(row)
+    }
+
+    fn record_photo_device(&self, record: &PhotoDeviceRecord) -> Result<(), MetadataError> {
+        let row = PhotoDeviceRow {
+            source_file: record.source_file.clone(),
+            global_start: to_i64(record.global_start)?,
+            make: record.make.clone(),
+            model: record.model.clone(),
+            serial: record.serial.clone(),
+            lens: record.lens.clone(),
+            lens_serial: record.lens_serial.clone(),
+            taken_at: record.taken_at.map(to_micros),
+            latitude: record.latitude,
+            longitude: record.longitude,
+            device_id: record.device_id.clone(),
+            session_id: record.session_id.clone(),
+        };
+
+        let mut inner = self.lock_inner()?;
+        let writer = inner.get_or_create_writer(ParquetCategory::PhotoDevices)?;
+        writer.append_photo_device(row)
+    }
+
+    fn record_ntfs_file(&self, record: &NtfsFileRecord) -> Result<(), MetadataError> {
+        let row = NtfsFileRow {
+            volume_offset: to_i64(record.volume_offset)?,
+            mft_record: to_i64(record.mft_record)?,
+            sequence: i64::from(record.sequence),
+            path: record.path.clone(),
+            name: record.name.clone(),
+            in_use: record.in_use,
+            directory: record.directory,
+            size: to_i64(record.size)?,
+            resident: record.resident,
+            data_runs: record.data_runs.clone(),
+            created: record.created.map(to_micros),
+            modified: record.modified.map(to_micros),
+            mft_modified: record.mft_modified.map(to_micros),
+            accessed: record.accessed.map(to_micros),
+            carved_path: record.carved_path.clone(),
+        };
+
+        let mut inner = self.lock_inner()?;
+        let writer = inner.get_or_create_writer(ParquetCategory::NtfsFiles)?;
+        writer.append_ntfs_file(row)
+    }
+
+    fn record_input_file(&self, record: &InputFileRecord) -> Result<(), MetadataError> {
+        let row = InputFileRow {
+            kind: record.kind.as_str().to_string(),
+            path: record.path.clone(),
+            sha256: record.sha256.clone(),
+        };
+
+        let mut inner = self.lock_inner()?;
+        let writer = inner.get_or_create_writer(ParquetCategory::InputFiles)?;
+        writer.append_input_file(row)
+    }
+
+    fn record_encrypted_document(
+        &self,
+        record: &EncryptedDocumentRecord,
+    ) -> Result<(), MetadataError> {
+        let row = EncryptedDocumentRow {
+            encryption_type: record.encryption_type.clone(),
+            version: record.version.clone(),
+            cipher_algorithm: record.cipher_algorithm.clone(),
+            key_bits: record.key_bits.map(i64::from),
+            hash_algorithm: record.hash_algorithm.clone(),
+            spin_count: record.spin_count.map(i64::from),
+            csp_name: record.csp_name.clone(),
+            original_size: record.original_size.map(to_i64).transpose()?,
+            source_file: record.source_file.clone(),
+        };
+
+        let mut inner = self.lock_inner()?;
+        let writer = inner.get_or_create_writer(ParquetCategory::EncryptedDocuments)?;
+        writer.append_encrypted_document(row)
+    }
+
+    fn record_carve_size(&self, record: &CarveSizeRecord) -> Result<(), MetadataError> {
+        let row = CarveSizeRow {
+            file_type: record.file_type.clone(),
+            path: record.path.clone(),
+            global_start: to_i64(record.global_start)?,
+            size: to_i64(record.size)?,
+            declared_size: record.declared_size.map(to_i64).transpose()?,
+            declared_by: record.declared_by.clone(),
+            truncation: record.truncation.map(|truncation| truncation.as_str()),
+        };
+        let mut inner = self.lock_inner()?;
+        let writer = inner.get_or_create_writer(ParquetCategory::CarveSizes)?;
+        writer.append_carve_size(row)
+    }
+
+    fn record_carve_companion(&self, record: &CarveCompanionRecord) -> Result<(), MetadataError> {
+        let row = CarveCompanionRow {
+            file_type: record.file_type.clone(),
+            path: record.path.clone(),
+            global_start: to_i64(record.global_start)?,
+            companion_type: record.companion_type.clone(),
+            companion_path: record.companion_path.clone(),
+            companion_start: to_i64(record.companion_start)?,
+            companion_size: to_i64(record.companion_size)?,
+        };
+        let mut inner = self.lock_inner()?;
+        let writer = inner.get_or_create_writer(ParquetCategory::CarveCompanions)?;
+        writer.append_carve_companion(row)
+    }
+
+    fn record_volume_location(&self, record: &VolumeLocationRecord) -> Result<(), MetadataError> {
+        let row = VolumeLocationRow {
+            subject: record.subject.clone(),
+            reference: record.reference.clone(),
+            global_start: to_i64(record.global_start)?,
+            partition: i64::from(record.partition),
+            partition_offset: to_i64(record.partition_offset)?,
+            relative_offset: to_i64(record.relative_offset)?,
+            relative_sector: to_i64(record.relative_sector)?,
+            file_system: record.file_system.clone(),
+            cluster: record.cluster.map(to_i64).transpose()?,
+            cluster_chain: record.cluster_chain.clone(),
+        };
+        let mut inner = self.lock_inner()?;
+        let writer = inner.get_or_create_writer(ParquetCategory::VolumeLocations)?;
+        writer.append_volume_location(row)
+    }
+
+    fn record_classification(&self, record: &ClassificationRecord) -> Result<(), MetadataError> {
+        let row = ClassificationRow {
+            file_type: record.file_type.clone(),
+            path: record.path.clone(),
+            global_start: to_i64(record.global_start)?,
+            classifier: record.classifier.clone(),
+            label: record.label.clone(),
+            score: record.score,
+            rank: i64::from(record.rank),
+        };
+        let mut inner = self.lock_inner()?;
+        let writer = inner.get_or_create_writer(ParquetCategory::Classifications)?;
+        writer.append_classification(row)
+    }
+
+    fn record_entropy(&self, region: &crate::metadata::EntropyRegion) -> Result<(), MetadataError> {
+        let row = EntropyRegionRow {
+            global_start: to_i64(region.global_start)?,
+            global_end: to_i64(region.global_end)?,
+            entropy: region.entropy,
+            window_size: to_i64(region.window_size)?,
+        };
+        let mut inner = self.lock_inner()?;
+        let writer = inner.get_or_create_writer(ParquetCategory::EntropyRegions)?;
+        writer.append_entropy(row)
+    }
+
+    fn flush(&self) -> Result<(), MetadataError> {
+        // Flush all buffers to ensure data is written to disk
+        // This allows recovery of data if the process is interrupted
+        let mut inner = self.lock_inner()?;
+        inner.flush_all_buffers(false)?;
+        // Note: We don't call finish_all() here because that would close writers
+        // and prevent further writes. Finish is called in Drop.
+        Ok(())
+    }
+
+    fn flush_for_snapshot(&self) -> Result<(), MetadataError> {
+        self.lock_inner()?.flush_all_buffers(true)
+    }
+}
+
+impl Drop for ParquetSink {
+    fn drop(&mut self) {
+        // Ensure all writers are properly finished when the sink is dropped
+        if let Ok(mut inner) = self.inner.lock() {
+            let _ = inner.finish_all();
+        }
+    }
+}
+
+pub fn build_parquet_sink(
+    cfg: &Config,
+    identity: &SinkIdentity,
+    run_output_dir: &Path,
+    cipher: Option<&OutputCipher>,
+) -> Result<Box<dyn MetadataSink>, MetadataError> {
+    Ok(Box::new(ParquetSink::new(
+        cfg,
+        identity,
+        run_output_dir,
+        cipher,
+    )?))
+}
+
+/// Parquet files cannot be appended to, so passes after the first write
+/// `<name>.pass<N>.parquet` next to the first pass' files.
+fn pass_filename(filename: &str, pass_id: u32) -> String {
+    if pass_id > 1 {
+        let stem = filename.trim_end_matches(".parquet");
+        format!("{stem}.pass{pass_id}.parquet")
+    } else {
+        filename.to_string()
+    }
+}
+
+/// Schema of a Parquet output written by this sink, by file name (any pass).
+pub(crate) fn schema_for_filename(filename: &str) -> Option<SchemaRef> {
+    let stem = filename.strip_suffix(".parquet")?;
+    let stem = match stem.rsplit_once(".pass") {
+        Some((base, pass)) if pass.parse::<u32>().is_ok() => base,
+        _ => stem,
+    };
+    ParquetCategory::ALL
+        .into_iter()
+        .find(|category| category.filename().strip_suffix(".parquet") == Some(stem))
+        .map(schema_for_category)
+}
+
+fn category_for_file_type(file_type: &str) -> ParquetCategory {
+    match file_type {
+        "jpeg" | "jpg" => ParquetCategory::FilesJpeg,
+        "png" => ParquetCategory::FilesPng,
+        "gif" => ParquetCategory::FilesGif,
+        "sqlite" | "sqlite_db" => ParquetCategory::FilesSqlite,
+        "pdf" => ParquetCategory::FilesPdf,
+        "zip" | "docx" | "xlsx" | "pptx" => ParquetCategory::FilesZip,
+        "webp" => ParquetCategory::FilesWebp,
+        _ => ParquetCategory::FilesOther,
+    }
+}
+
+fn schema_for_category(category: ParquetCategory) -> SchemaRef {
+    if category.is_files() {
+        return Arc::new(Schema::new(vec![
+            Field::new("run_id", DataType::Utf8, false),
+            Field::new("tool_version", DataType::Utf8, false),
+            Field::new("config_hash", DataType::Utf8, false),
+            Field::new("evidence_path", DataType::Utf8, false),
+            Field::new("evidence_sha256", DataType::Utf8, false),
+            Field::new("pass_id", DataType::Int64, false),
+            Field::new("handler_id", DataType::Utf8, false),
+            Field::new("file_type", DataType::Utf8, false),
+            Field::new("carved_path", DataType::Utf8, false),
+            Field::new("global_start", DataType::Int64, false),
+            Field::new("global_end", DataType::Int64, false),
+            Field::new("size", DataType::Int64, false),
+            Field::new("md5", DataType::Utf8, true),
+            Field::new("sha256", DataType::Utf8, true),
+            Field::new("pattern_id", DataType::Utf8, true),
+            Field::new("magic_bytes", DataType::Binary, true),
+            Field::new("validated", DataType::Boolean, false),
+            Field::new("truncated", DataType::Boolean, false),
+            Field::new("error", DataType::Utf8, true),
+        ]));
+    }
+
+    match category {
+        ParquetCategory::ArtefactsUrls => Arc::new(Schema::new(vec![
+            Field::new("run_id", DataType::Utf8, false),
+            Field::new("tool_version", DataType::Utf8, false),
+            Field::new("config_hash", DataType::Utf8, false),
+            Field::new("evidence_path", DataType::Utf8, false),
+            Field::new("evidence_sha256", DataType::Utf8, false),
+            Field::new("pass_id", DataType::Int64, false),
+            Field::new("global_start", DataType::Int64, false),
+            Field::new("global_end", DataType::Int64, false),
+            Field::new("url", DataType::Utf8, false),
+            Field::new("scheme", DataType::Utf8, false),
+            Field::new("host", DataType::Utf8, false),
+            Field::new("port", DataType::Int32, true),
+            Field::new("path", DataType::Utf8, true),
+            Field::new("query", DataType::Utf8, true),
+            Field::new("fragment", DataType::Utf8, true),
+            Field::new("host_unicode", DataType::Utf8, true),
+            Field::new("path_decoded", DataType::Utf8, true),
+            Field::new("source_kind", DataType::Utf8, false),
+            Field::new("source_detail", DataType::Utf8, false),
+            Field::new("certainty", DataType::Float64, false),
+        ])),
+        ParquetCategory::ArtefactsEmails => Arc::new(Schema::new(vec![
+            Field::new("run_id", DataType::Utf8, false),
+            Field::new("tool_version", DataType::Utf8, false),
+            Field::new("config_hash", DataType::Utf8, false),
+            Field::new("evidence_path", DataType::Utf8, false),
+            Field::new("evidence_sha256", DataType::Utf8, false),
+            Field::new("pass_id", DataType::Int64, false),
+            Field::new("global_start", DataType::Int64, false),
+            Field::new("global_end", DataType::Int64, false),
+            Field::new("email", DataType::Utf8, false),
+            Field::new("local_part", DataType::Utf8, false),
+            Field::new("domain", DataType::Utf8, false),
+            Field::new("source_kind", DataType::Utf8, false),
+            Field::new("source_detail", DataType::Utf8, false),
+            Field::new("certainty", DataType::Float64, false),
+        ])),
+        ParquetCategory::ArtefactsPhones => Arc::new(Schema::new(vec![
+            Field::new("run_id", DataType::Utf8, false),
+            Field::new("tool_version", DataType::Utf8, false),
+            Field::new("config_hash", DataType::Utf8, false),
+            Field::new("evidence_path", DataType::Utf8, false),
+            Field::new("evidence_sha256", DataType::Utf8, false),
+            Field::new("pass_id", DataType::Int64, false),
+            Field::new("global_start", DataType::Int64, false),
+            Field::new("global_end", DataType::Int64, false),
+            Field::new("phone_raw", DataType::Utf8, false),
+            Field::new("phone_e164", DataType::Utf8, true),
+            Field::new("country", DataType::Utf8, true),
+            Field::new("source_kind", DataType::Utf8, false),
+            Field::new("source_detail", DataType::Utf8, false),
+            Field::new("certainty", DataType::Float64, false),
+        ])),
+        ParquetCategory::ArtefactsNetwork
+        | ParquetCategory::ArtefactsSystem
+        | ParquetCategory::ArtefactsP2p => Arc::new(Schema::new(vec![
+            Field::new("run_id", DataType::Utf8, false),
+            Field::new("tool_version", DataType::Utf8, false),
+            Field::new("config_hash", DataType::Utf8, false),
+            Field::new("evidence_path", DataType::Utf8, false),
+            Field::new("evidence_sha256", DataType::Utf8, false),
+            Field::new("pass_id", DataType::Int64, false),
+            Field::new("global_start", DataType::Int64, false),
+            Field::new("global_end", DataType::Int64, false),
+            Field::new("artefact_kind", DataType::Utf8, false),
+            Field::new("value", DataType::Utf8, false),
+            Field::new("source_kind", DataType::Utf8, false),
+            Field::new("source_detail", DataType::Utf8, false),
+        ])),
+        ParquetCategory::BrowserHistory => Arc::new(Schema::new(vec![
+            Field::new("run_id", DataType::Utf8, false),
+            Field::new("tool_version", DataType::Utf8, false),
+            Field::new("config_hash", DataType::Utf8, false),
+            Field::new("evidence_path", DataType::Utf8, false),
+            Field::new("evidence_sha256", DataType::Utf8, false),
+            Field::new("pass_id", DataType::Int64, false),
+            Field::new("source_file", DataType::Utf8, false),
+            Field::new("browser", DataType::Utf8, false),
+            Field::new("profile", DataType::Utf8, false),
+            Field::new("url", DataType::Utf8, false),
+            Field::new("title", DataType::Utf8, true),
+            Field::new(
+                "visit_time_utc",
+                DataType::Timestamp(TimeUnit::Microsecond, None),
+                true,
+            ),
+            Field::new("visit_source", DataType::Utf8, true),
+            Field::new("row_id", DataType::Int64, true),
+            Field::new("table_name", DataType::Utf8, true),
+        ])),
+        ParquetCategory::BrowserCookies => Arc::new(Schema::new(vec![
+            Field::new("run_id", DataType::Utf8, false),
+            Field::new("tool_version", DataType::Utf8, false),
+            Field::new("config_hash", DataType::Utf8, false),
+            Field::new("evidence_path", DataType::Utf8, false),
+            Field::new("evidence_sha256", DataType::Utf8, false),
+            Field::new("pass_id", DataType::Int64, false),
+            Field::new("source_file", DataType::Utf8, false),
+            Field::new("browser", DataType::Utf8, false),
+            Field::new("profile", DataType::Utf8, false),
+            Field::new("host", DataType::Utf8, false),
+            Field::new("name", DataType::Utf8, false),
+            Field::new("value", DataType::Utf8, true),
+            Field::new("path", DataType::Utf8, true),
+            Field::new(
+                "expires_utc",
+                DataType::Timestamp(TimeUnit::Microsecond, None),
+                true,
+            ),
+            Field::new(
+                "last_access_utc",
+                DataType::Timestamp(TimeUnit::Microsecond, None),
+                true,
+            ),
+            Field::new(
+                "creation_utc",
+                DataType::Timestamp(TimeUnit::Microsecond, None),
+                true,
+            ),
+            Field::new("is_secure", DataType::Boolean, true),
+            Field::new("is_http_only", DataType::Boolean, true),
+        ])),
+        ParquetCategory::BrowserDownloads => Arc::new(Schema::new(vec![
+            Field::new("run_id", DataType::Utf8, false),
+            Field::new("tool_version", DataType::Utf8, false),
+            Field::new("config_hash", DataType::Utf8, false),
+            Field::new("evidence_path", DataType::Utf8, false),
+            Field::new("evidence_sha256", DataType::Utf8, false),
+            Field::new("pass_id", DataType::Int64, false),
+            Field::new("source_file", DataType::Utf8, false),
+            Field::new("browser", DataType::Utf8, false),
+            Field::new("profile", DataType::Utf8, false),
+            Field::new("url", DataType::Utf8, true),
+            Field::new("target_path", DataType::Utf8, true),
+            Field::new(
+                "start_time_utc",
+                DataType::Timestamp(TimeUnit::Microsecond, None),
+                true,
+            ),
+            Field::new(
+                "end_time_utc",
+                DataType::Timestamp(TimeUnit::Microsecond, None),
+                true,
+            ),
+            Field::new("total_bytes", DataType::Int64, true),
+            Field::new("state", DataType::Utf8, true),
+        ])),
+        ParquetCategory::BrowserCache => Arc::new(Schema::new(vec![
+            Field::new("run_id", DataType::Utf8, false),
+            Field::new("tool_version", DataType::Utf8, false),
+            Field::new("config_hash", DataType::Utf8, false),
+            Field::new("evidence_path", DataType::Utf8, false),
+            Field::new("evidence_sha256", DataType::Utf8, false),
+            Field::new("pass_id", DataType::Int64, false),
+            Field::new("source_file", DataType::Utf8, false),
+            Field::new("browser", DataType::Utf8, false),
+            Field::new("url", DataType::Utf8, false),
+            Field::new("http_status", DataType::Int32, true),
+            Field::new("content_type", DataType::Utf8, true),
+            Field::new("content_encoding", DataType::Utf8, true),
+            Field::new("response_headers", DataType::Utf8, true),
+            Field::new("body_size", DataType::Int64, false),
+            Field::new("body_path", DataType::Utf8, true),
+        ])),
+        ParquetCategory::LogEvents => Arc::new(Schema::new(vec![
+            Field::new("run_id", DataType::Utf8, false),
+            Field::new("tool_version", DataType::Utf8, false),
+            Field::new("config_hash", DataType::Utf8, false),
+            Field::new("evidence_path", DataType::Utf8, false),
+            Field::new("evidence_sha256", DataType::Utf8, false),
+            Field::new("pass_id", DataType::Int64, false),
+            Field::new("source", DataType::Utf8, false),
+            Field::new(
+                "timestamp_utc",
+                DataType::Timestamp(TimeUnit::Microsecond, None),
+                true,
+            ),
+            Field::new("timestamp_raw", DataType::Utf8, true),
+            Field::new("host", DataType::Utf8, true),
+            Field::new("program", DataType::Utf8, true),
+            Field::new("pid", DataType::Int64, true),
+            Field::new("severity", DataType::Int32, true),
+            Field::new("message", DataType::Utf8, false),
+            Field::new("global_offset", DataType::Int64, false),
+            Field::new("source_file", DataType::Utf8, true),
+        ])),
+        ParquetCategory::ContainerArtefacts => Arc::new(Schema::new(vec![
+            Field::new("run_id", DataType::Utf8, false),
+            Field::new("tool_version", DataType::Utf8, false),
+            Field::new("config_hash", DataType::Utf8, false),
+            Field::new("evidence_path", DataType::Utf8, false),
+            Field::new("evidence_sha256", DataType::Utf8, false),
+            Field::new("pass_id", DataType::Int64, false),
+            Field::new("kind", DataType::Utf8, false),
+            Field::new("name", DataType::Utf8, true),
+            Field::new("digest", DataType::Utf8, true),
+            Field::new("namespace", DataType::Utf8, true),
+            Field::new("detail", DataType::Utf8, true),
+            Field::new("global_offset", DataType::Int64, false),
+            Field::new("source_file", DataType::Utf8, false),
+        ])),
+        ParquetCategory::Messages => Arc::new(Schema::new(vec![
+            Field::new("run_id", DataType::Utf8, false),
+            Field::new("tool_version", DataType::Utf8, false),
+            Field::new("config_hash", DataType::Utf8, false),
+            Field::new("evidence_path", DataType::Utf8, false),
+            Field::new("evidence_sha256", DataType::Utf8, false),
+            Field::new("pass_id", DataType::Int64, false),
+            Field::new("app", DataType::Utf8, false),
+            Field::new("message_id", DataType::Utf8, true),
+            Field::new("thread", DataType::Utf8, true),
+            Field::new("handle", DataType::Utf8, true),
+            Field::new("is_from_me", DataType::Boolean, true),
+            Field::new("service", DataType::Utf8, true),
+            Field::new("text", DataType::Utf8, true),
+            Field::new(
+                "timestamp_utc",
+                DataType::Timestamp(TimeUnit::Microsecond, None),
+                true,
+            ),
+            Field::new(
+                "modified_utc",
+                DataType::Timestamp(TimeUnit::Microsecond, None),
+                true,
+            ),
+            Field::new("source_file", DataType::Utf8, false),
+        ])),
+        ParquetCategory::UsageEvents => Arc::new(Schema::new(vec![
+            Field::new("run_id", DataType::Utf8, false),
+            Field::new("tool_version", DataType::Utf8, false),
+            Field::new("config_hash", DataType::Utf8, false),
+            Field::new("evidence_path", DataType::Utf8, false),
+            Field::new("evidence_sha256", DataType::Utf8, false),
+            Field::new("pass_id", DataType::Int64, false),
+            Field::new("source", DataType::Utf8, false),
+            Field::new("stream", DataType::Utf8, false),
+            Field::new("value", DataType::Utf8, true),
+            Field::new("bundle_id", DataType::Utf8, true),
+            Field::new(
+                "start_utc",
+                DataType::Timestamp(TimeUnit::Microsecond, None),
+                true,
+            ),
+            Field::new(
+                "end_utc",
+                DataType::Timestamp(TimeUnit::Microsecond, None),
+                true,
+            ),
+            Field::new("source_file", DataType::Utf8, false),
+        ])),
+        ParquetCategory::AppRecords => Arc::new(Schema::new(vec![
+            Field::new("run_id", DataType::Utf8, false),
+            Field::new("tool_version", DataType::Utf8, false),
+            Field::new("config_hash", DataType::Utf8, false),
+            Field::new("evidence_path", DataType::Utf8, false),
+            Field::new("evidence_sha256", DataType::Utf8, false),
+            Field::new("pass_id", DataType::Int64, false),
+            Field::new("app", DataType::Utf8, false),
+            Field::new("plan", DataType::Utf8, false),
+            Field::new("row_id", DataType::Int64, true),
+            Field::new(
+                "timestamp_utc",
+                DataType::Timestamp(TimeUnit::Microsecond, None),
+                true,
+            ),
+            Field::new("timestamp_desc", DataType::Utf8, true),
+            Field::new("fields", DataType::Utf8, false),
+            Field::new("source_file", DataType::Utf8, false),
+        ])),
+        ParquetCategory::PrefetchFiles => Arc::new(Schema::new(vec![
+            Field::new("run_id", DataType::Utf8, false),
+            Field::new("tool_version", DataType::Utf8, false),
+            Field::new("config_hash", DataType::Utf8, false),
+            Field::new("evidence_path", DataType::Utf8, false),
+            Field::new("evidence_sha256", DataType::Utf8, false),
+            Field::new("pass_id", DataType::Int64, false),
+            Field::new("executable", DataType::Utf8, false),
+            Field::new("prefetch_hash", DataType::Utf8, false),
+            Field::new("format_version", DataType::Int64, false),
+            Field::new("compressed", DataType::Boolean, false),
+            Field::new("run_count", DataType::Int64, false),
+            Field::new(
+                "last_run_utc",
+                DataType::Timestamp(TimeUnit::Microsecond, None),
+                true,
+            ),
+            Field::new("earlier_runs_utc", DataType::Utf8, true),
+            Field::new("source_file", DataType::Utf8, false),
+        ])),
+        ParquetCategory::Shortcuts => Arc::new(Schema::new(vec![
+            Field::new("run_id", DataType::Utf8, false),
+            Field::new("tool_version", DataType::Utf8, false),
+            Field::new("config_hash", DataType::Utf8, false),
+            Field::new("evidence_path", DataType::Utf8, false),
+            Field::new("evidence_sha256", DataType::Utf8, false),
+            Field::new("pass_id", DataType::Int64, false),
+            Field::new("target_path", DataType::Utf8, true),
+            Field::new("arguments", DataType::Utf8, true),
+            Field::new("working_dir", DataType::Utf8, true),
+            Field::new("relative_path", DataType::Utf8, true),
+            Field::new("description", DataType::Utf8, true),
+            Field::new("icon_location", DataType::Utf8, true),
+            Field::new("target_size", DataType::Int64, false),
+            Field::new("file_attributes", DataType::Int64, false),
+            Field::new(
+                "target_created_utc",
+                DataType::Timestamp(TimeUnit::Microsecond, None),
+                true,
+            ),
+            Field::new(
+                "target_accessed_utc",
+                DataType::Timestamp(TimeUnit::Microsecond, None),
+                true,
+            ),
+            Field::new(
+                "target_modified_utc",
+                DataType::Timestamp(TimeUnit::Microsecond, None),
+                true,
+            ),
+            Field::new("volume_serial", DataType::Utf8, true),
+            Field::new("machine_id", DataType::Utf8, true),
+            Field::new("source_file", DataType::Utf8, false),
+        ])),
+        ParquetCategory::Executables => Arc::new(Schema::new(vec![
+            Field::new("run_id", DataType::Utf8, false),
+            Field::new("tool_version", DataType::Utf8, false),
+            Field::new("config_hash", DataType::Utf8, false),
+            Field::new("evidence_path", DataType::Utf8, false),
+            Field::new("evidence_sha256", DataType::Utf8, false),
+            Field::new("pass_id", DataType::Int64, false),
+            Field::new("format", DataType::Utf8, false),
+            Field::new("kind", DataType::Utf8, false),
+            Field::new("architecture", DataType::Utf8, false),
+            Field::new("bits", DataType::Int32, false),
+            Field::new(
+                "compile_time_utc",
+                DataType::Timestamp(TimeUnit::Microsecond, None),
+                true,
+            ),
+            Field::new("subsystem", DataType::Utf8, true),
+            Field::new("section_count", DataType::Int64, false),
+            Field::new("import_count", DataType::Int64, true),
+            Field::new("import_hash", DataType::Utf8, true),
+            Field::new("signed", DataType::Boolean, false),
+            Field::new("source_file", DataType::Utf8, false),
+        ])),
+        ParquetCategory::Plists => Arc::new(Schema::new(vec![
+            Field::new("run_id", DataType::Utf8, false),
+            Field::new("tool_version", DataType::Utf8, false),
+            Field::new("config_hash", DataType::Utf8, false),
+            Field::new("evidence_path", DataType::Utf8, false),
+            Field::new("evidence_sha256", DataType::Utf8, false),
+            Field::new("pass_id", DataType::Int64, false),
+            Field::new("object_count", DataType::Int64, false),
+            Field::new("top_type", DataType::Utf8, false),
+            Field::new("top_keys", DataType::Utf8, true),
+            Field::new("archiver", DataType::Utf8, true),
+            Field::new("json_len", DataType::Int64, false),
+            Field::new("json", DataType::Utf8, true),
+            Field::new("source_file", DataType::Utf8, false),
+        ])),
+        ParquetCategory::VideoRepairs => Arc::new(Schema::new(vec![
+            Field::new("run_id", DataType::Utf8, false),
+            Field::new("tool_version", DataType::Utf8, false),
+            Field::new("config_hash", DataType::Utf8, false),
+            Field::new("evidence_path", DataType::Utf8, false),
+            Field::new("evidence_sha256", DataType::Utf8, false),
+            Field::new("pass_id", DataType::Int64, false),
+            Field::new("method", DataType::Utf8, false),
+            Field::new("codec", DataType::Utf8, false),
+            Field::new("width", DataType::Int64, false),
+            Field::new("height", DataType::Int64, false),
+            Field::new("frame_count", DataType::Int64, false),
+            Field::new("keyframe_count", DataType::Int64, false),
+            Field::new("duration_secs", DataType::Float64, false),
+            Field::new("media_bytes", DataType::Int64, false),
+            Field::new("skipped_bytes", DataType::Int64, false),
+            Field::new("donor_file", DataType::Utf8, true),
+            Field::new("repaired_path", DataType::Utf8, false),
+            Field::new("source_file", DataType::Utf8, false),
+        ])),
+        ParquetCategory::Thumbnails => Arc::new(Schema::new(vec![
+            Field::new("run_id", DataType::Utf8, false),
+            Field::new("tool_version", DataType::Utf8, false),
+            Field::new("config_hash", DataType::Utf8, false),
+            Field::new("evidence_path", DataType::Utf8, false),
+            Field::new("evidence_sha256", DataType::Utf8, false),
+            Field::new("pass_id", DataType::Int64, false),
+            Field::new("cache_id", DataType::Utf8, false),
+            Field::new("cache_version", DataType::Utf8, false),
+            Field::new("cache_type", DataType::Int64, false),
+            Field::new("entry_offset", DataType::Int64, false),
+            Field::new("width", DataType::Int64, true),
+            Field::new("height", DataType::Int64, true),
+            Field::new("image_format", DataType::Utf8, false),
+            Field::new("data_size", DataType::Int64, false),
+            Field::new("image_path", DataType::Utf8, false),
+            Field::new("source_file", DataType::Utf8, false),
+        ])),
+        ParquetCategory::ImageInfo => Arc::new(Schema::new(vec![
+            Field::new("run_id", DataType::Utf8, false),
+            Field::new("tool_version", DataType::Utf8, false),
+            Field::new("config_hash", DataType::Utf8, false),
+            Field::new("evidence_path", DataType::Utf8, false),
+            Field::new("evidence_sha256", DataType::Utf8, false),
+            Field::new("pass_id", DataType::Int64, false),
+            Field::new("format", DataType::Utf8, false),
+            Field::new("width", DataType::Int64, false),
+            Field::new("height", DataType::Int64, false),
+            Field::new("bits_per_pixel", DataType::Int64, true),
+            Field::new("decoded_pct", DataType::Float64, true),
+            Field::new("corrupt_offset", DataType::Int64, true),
+            Field::new("source_file", DataType::Utf8, false),
+        ])),
+        ParquetCategory::PhotoDevices => Arc::new(Schema::new(vec![
+            Field::new("run_id", DataType::Utf8, false),
+            Field::new("tool_version", DataType::Utf8, false),
+            Field::new("config_hash", DataType::Utf8, false),
+            Field::new("evidence_path", DataType::Utf8, false),
+            Field::new("evidence_sha256", DataType::Utf8, false),
+            Field::new("pass_id", DataType::Int64, false),
+            Field::new("source_file", DataType::Utf8, false),
+            Field::new("global_start", DataType::Int64, false),
+            Field::new("make", DataType::Utf8, true),
+            Field::new("model", DataType::Utf8, true),
+            Field::new("serial", DataType::Utf8, true),
+            Field::new("lens", DataType::Utf8, true),
+            Field::new("lens_serial", DataType::Utf8, true),
+            Field::new(
+                "taken_at",
+                DataType::Timestamp(TimeUnit::Microsecond, None),
+                true,
+            ),
+            Field::new("latitude", DataType::Float64, true),
+            Field::new("longitude", DataType::Float64, true),
+            Field::new("device_id", DataType::Utf8, true),
+            Field::new("session_id", DataType::Utf8, true),
+        ])),
+        ParquetCategory::NtfsFiles => Arc::new(Schema::new(vec![
+            Field::new("run_id", DataType::Utf8, false),
+            Field::new("tool_version", DataType::Utf8, false),
+            Field::new("config_hash", DataType::Utf8, false),
+            Field::new("evidence_path", DataType::Utf8, false),
+            Field::new("evidence_sha256", DataType::Utf8, false),
+            Field::new("pass_id", DataType::Int64, false),
+            Field::new("volume_offset", DataType::Int64, false),
+            Field::new("mft_record", DataType::Int64, false),
+            Field::new("sequence", DataType::Int64, false),
+            Field::new("path", DataType::Utf8, false),
+            Field::new("name", DataType::Utf8, false),
+            Field::new("in_use", DataType::Boolean, false),
+            Field::new("directory", DataType::Boolean, false),
+            Field::new("size", DataType::Int64, false),
+            Field::new("resident", DataType::Boolean, false),
+            Field::new("data_runs", DataType::Utf8, false),
+            Field::new(
+                "created",
+                DataType::Timestamp(TimeUnit::Microsecond, None),
+                true,
+            ),
+            Field::new(
+                "modified",
+                DataType::Timestamp(TimeUnit::Microsecond, None),
+                true,
+            ),
+            Field::new(
+                "mft_modified",
+                DataType::Timestamp(TimeUnit::Microsecond, None),
+                true,
+            ),
+            Field::new(
+                "accessed",
+                DataType::Timestamp(TimeUnit::Microsecond, None),
+                true,
+            ),
+            Field::new("carved_path", DataType::Utf8, true),
+        ])),
+        ParquetCategory::InputFiles => Arc::new(Schema::new(vec![
+            Field::new("run_id", DataType::Utf8, false),
+            Field::new("tool_version", DataType::Utf8, false),
+            Field::new("config_hash", DataType::Utf8, false),
+            Field::new("evidence_path", DataType::Utf8, false),
+            Field::new("evidence_sha256", DataType::Utf8, false),
+            Field::new("pass_id", DataType::Int64, false),
+            Field::new("kind", DataType::Utf8, false),
+            Field::new("path", DataType::Utf8, false),
+            Field::new("sha256", DataType::Utf8, false),
+        ])),
+        ParquetCategory::EncryptedDocuments => Arc::new(Schema::new(vec![
+            Field::new("run_id", DataType::Utf8, false),
+            Field::new("tool_version", DataType::Utf8, false),
+            Field::new("config_hash", DataType::Utf8, false),
+            Field::new("evidence_path", DataType::Utf8, false),
+            Field::new("evidence_sha256", DataType::Utf8, false),
+            Field::new("pass_id", DataType::Int64, false),
+            Field::new("encryption_type", DataType::Utf8, false),
+            Field::new("version", DataType::Utf8, true),
+            Field::new("cipher_algorithm", DataType::Utf8, true),
+            Field::new("key_bits", DataType::Int64, true),
+            Field::new("hash_algorithm", DataType::Utf8, true),
+            Field::new("spin_count", DataType::Int64, true),
+            Field::new("csp_name", DataType::Utf8, true),
+            Field::new("original_size", DataType::Int64, true),
+            Field::new("source_file", DataType::Utf8, false),
+        ])),
+        ParquetCategory::CarveSizes => Arc::new(Schema::new(vec![
+            Field::new("run_id", DataType::Utf8, false),
+            Field::new("tool_version", DataType::Utf8, false),
+            Field::new("config_hash", DataType::Utf8, false),
+            Field::new("evidence_path", DataType::Utf8, false),
+            Field::new("evidence_sha256", DataType::Utf8, false),
+            Field::new("pass_id", DataType::Int64, false),
+            Field::new("file_type", DataType::Utf8, false),
+            Field::new("path", DataType::Utf8, false),
+            Field::new("global_start", DataType::Int64, false),
+            Field::new("size", DataType::Int64, false),
+            Field::new("declared_size", DataType::Int64, true),
+            Field::new("declared_by", DataType::Utf8, true),
+            Field::new("truncation", DataType::Utf8, true),
+        ])),
+        ParquetCategory::CarveCompanions => Arc::new(Schema::new(vec![
+            Field::new("run_id", DataType::Utf8, false),
+            Field::new("tool_version", DataType::Utf8, false),
+            Field::new("config_hash", DataType::Utf8, false),
+            Field::new("evidence_path", DataType::Utf8, false),
+            Field::new("evidence_sha256", DataType::Utf8, false),
+            Field::new("pass_id", DataType::Int64, false),
+            Field::new("file_type", DataType::Utf8, false),
+            Field::new("path", DataType::Utf8, false),
+            Field::new("global_start", DataType::Int64, false),
+            Field::new("companion_type", DataType::Utf8, false),
+            Field::new("companion_path", DataType::Utf8, false),
+            Field::new("companion_start", DataType::Int64, false),
+            Field::new("companion_size", DataType::Int64, false),
+        ])),
+        ParquetCategory::VolumeLocations => Arc::new(Schema::new(vec![
+            Field::new("run_id", DataType::Utf8, false),
+            Field::new("tool_version", DataType::Utf8, false),
+            Field::new("config_hash", DataType::Utf8, false),
+            Field::new("evidence_path", DataType::Utf8, false),
+            Field::new("evidence_sha256", DataType::Utf8, false),
+            Field::new("pass_id", DataType::Int64, false),
+            Field::new("subject", DataType::Utf8, false),
+            Field::new("reference", DataType::Utf8, false),
+            Field::new("global_start", DataType::Int64, false),
+            Field::new("partition", DataType::Int64, false),
+            Field::new("partition_offset", DataType::Int64, false),
+            Field::new("relative_offset", DataType::Int64, false),
+            Field::new("relative_sector", DataType::Int64, false),
+            Field::new("file_system", DataType::Utf8, true),
+            Field::new("cluster", DataType::Int64, true),
+            Field::new("cluster_chain", DataType::Utf8, true),
+        ])),
+        ParquetCategory::Classifications => Arc::new(Schema::new(vec![
+            Field::new("run_id", DataType::Utf8, false),
+            Field::new("tool_version", DataType::Utf8, false),
+            Field::new("config_hash", DataType::Utf8, false),
+            Field::new("evidence_path", DataType::Utf8, false),
+            Field::new("evidence_sha256", DataType::Utf8, false),
+            Field::new("pass_id", DataType::Int64, false),
+            Field::new("file_type", DataType::Utf8, false),
+            Field::new("path", DataType::Utf8, false),
+            Field::new("global_start", DataType::Int64, false),
+            Field::new("classifier", DataType::Utf8, false),
+            Field::new("label", DataType::Utf8, false),
+            Field::new("score", DataType::Float64, false),
+            Field::new("rank", DataType::Int64, false),
+        ])),
+        ParquetCategory::EntropyRegions => Arc::new(Schema::new(vec![
+            Field::new("run_id", DataType::Utf8, false),
+            Field::new("tool_version", DataType::Utf8, false),
+            Field::new("config_hash", DataType::Utf8, false),
+            Field::new("evidence_path", DataType::Utf8, false),
+            Field::new("evidence_sha256", DataType::Utf8, false),
+            Field::new("pass_id", DataType::Int64, false),
+            Field::new("global_start", DataType::Int64, false),
+            Field::new("global_end", DataType::Int64, false),
+            Field::new("entropy", DataType::Float64, false),
+            Field::new("window_size", DataType::Int64, false),
+        ])),
+        ParquetCategory::RunSummary => Arc::new(Schema::new(vec![
+            Field::new("run_id", DataType::Utf8, false),
+            Field::new("tool_version", DataType::Utf8, false),
+            Field::new("config_hash", DataType::Utf8, false),
+            Field::new("evidence_path", DataType::Utf8, false),
+            Field::new("evidence_sha256", DataType::Utf8, false),
+            Field::new("pass_id", DataType::Int64, false),
+            Field::new("bytes_scanned", DataType::Int64, false),
+            Field::new("chunks_processed", DataType::Int64, false),
+            Field::new("hits_found", DataType::Int64, false),
+            Field::new("files_carved", DataType::Int64, false),
+            Field::new("string_spans", DataType::Int64, false),
+            Field::new("artefacts_extracted", DataType::Int64, false),
+        ])),
+        ParquetCategory::ResourceUsage => Arc::new(Schema::new(vec![
+            Field::new("run_id", DataType::Utf8, false),
+            Field::new("tool_version", DataType::Utf8, false),
+            Field::new("config_hash", DataType::Utf8, false),
+            Field::new("evidence_path", DataType::Utf8, false),
+            Field::new("evidence_sha256", DataType::Utf8, false),
+            Field::new("pass_id", DataType::Int64, false),
+            Field::new("stage", DataType::Utf8, false),
+            Field::new("threads", DataType::Int64, false),
+            Field::new("busy_secs", DataType::Float64, false),
+            Field::new("user_cpu_secs", DataType::Float64, true),
+            Field::new("sys_cpu_secs", DataType::Float64, true),
+            Field::new("bytes_read", DataType::Int64, true),
+            Field::new("bytes_written", DataType::Int64, true),
+            Field::new("gpu_secs", DataType::Float64, true),
+            Field::new("peak_rss_bytes", DataType::Int64, true),
+        ])),
+        _ => Arc::new(Schema::empty()),
+    }
+}
+
+fn build_files_batch(
+    ctx: &ParquetContext,
+    rows: &[FileRow],
+    schema: &SchemaRef,
+) -> Result<RecordBatch, MetadataError> {
+    let mut run_id = StringBuilder::new();
+    let mut tool_version = StringBuilder::new();
+    let mut config_hash = StringBuilder::new();
+    let mut evidence_path = StringBuilder::new();
+    let mut evidence_sha256 = StringBuilder::new();
+    let mut pass_id = Int64Builder::new();
+    let mut handler_id = StringBuilder::new();
+    let mut file_type = StringBuilder::new();
+    let mut carved_path = StringBuilder::new();
+    let mut global_start = Int64Builder::new();
+    let mut global_end = Int64Builder::new();
+    let mut size = Int64Builder::new();
+    let mut md5 = StringBuilder::new();
+    let mut sha256 = StringBuilder::new();
+    let mut pattern_id = StringBuilder::new();
+    let mut magic_bytes = BinaryBuilder::new();
+    let mut validated = BooleanBuilder::new();
+    let mut truncated = BooleanBuilder::new();
+    let mut error = StringBuilder::new();
+
+    for row in rows {
+        run_id.append_value(&ctx.run_id);
+        tool_version.append_value(&ctx.tool_version);
+        config_hash.append_value(&ctx.config_hash);
+        evidence_path.append_value(&ctx.evidence_path);
+        evidence_sha256.append_value(&ctx.evidence_sha256);
+        pass_id.append_value(i64::from(ctx.pass_id));
+        handler_id.append_value(&row.handler_id);
+        file_type.append_value(&row.file_type);
+        carved_path.append_value(&row.carved_path);
+        global_start.append_value(row.global_start);
+        global_end.append_value(row.global_end);
+        size.append_value(row.size);
+        md5.append_option(row.md5.as_deref());
+        sha256.append_option(row.sha256.as_deref());
+        pattern_id.append_option(row.pattern_id.as_deref());
+        magic_bytes.append_option(row.magic_bytes.as_deref());
+        validated.append_value(row.validated);
+        truncated.append_value(row.truncated);
+        error.append_option(row.error.as_deref());
+    }
+
+    let arrays: Vec<ArrayRef> = vec![
+        Arc::new(run_id.finish()),
+        Arc::new(tool_version.finish()),
+        Arc::new(config_hash.finish()),
+        Arc::new(evidence_path.finish()),
+        Arc::new(evidence_sha256.finish()),
+        Arc::new(pass_id.finish()),
+        Arc::new(handler_id.finish()),
+        Arc::new(file_type.finish()),
+        Arc::new(carved_path.finish()),
+        Arc::new(global_start.finish()),
+        Arc::new(global_end.finish()),
+        Arc::new(size.finish()),
+        Arc::new(md5.finish()),
+        Arc::new(sha256.finish()),
+        Arc::new(pattern_id.finish()),
+        Arc::new(magic_bytes.finish()),
+        Arc::new(validated.finish()),
+        Arc::new(truncated.finish()),
+        Arc::new(error.finish()),
+    ];
+
+    RecordBatch::try_new(Arc::clone(schema), arrays)
+        .map_err(|err| MetadataError::Other(format!("parquet batch error: {err}")))
+}
+
+fn build_urls_batch(
+    ctx: &ParquetContext,
+    rows: &[UrlArtefactRow],
+    schema: &SchemaRef,
+) -> Result<RecordBatch, MetadataError> {
+    let mut run_id = StringBuilder::new();
+    let mut tool_version = StringBuilder::new();
+    let mut config_hash = StringBuilder::new();
+    let mut evidence_path = StringBuilder::new();
+    let mut evidence_sha256 = StringBuilder::new();
+    let mut pass_id = Int64Builder::new();
+    let mut global_start = Int64Builder::new();
+    let mut global_end = Int64Builder::new();
+    let mut url = StringBuilder::new();
+    let mut scheme = StringBuilder::new();
+    let mut host = StringBuilder::new();
+    let mut port = Int32Builder::new();
+    let mut path = StringBuilder::new();
+    let mut query = StringBuilder::new();
+    let mut fragment = StringBuilder::new();
+    let mut host_unicode = StringBuilder::new();
+    let mut path_decoded = StringBuilder::new();
+    let mut source_kind = StringBuilder::new();
+    let mut source_detail = StringBuilder::new();
+    let mut certainty = arrow_array::builder::Float64Builder::new();
+
+    for row in rows {
+        run_id.append_value(&ctx.run_id);
+        tool_version.append_value(&ctx.tool_version);
+        config_hash.append_value(&ctx.config_hash);
+        evidence_path.append_value(&ctx.evidence_path);
+        evidence_sha256.append_value(&ctx.evidence_sha256);
+        pass_id.append_value(i64::from(ctx.pass_id));
+        global_start.append_value(row.global_start);
+        global_end.append_value(row.global_end);
+        url.append_value(&row.url);
+        scheme.append_value(&row.scheme);
+        host.append_value(&row.host);
+        port.append_option(row.port);
+        path.append_option(row.path.as_deref());
+        query.append_option(row.query.as_deref());
+        fragment.append_option(row.fragment.as_deref());
+        host_unicode.append_option(row.host_unicode.as_deref());
+        path_decoded.append_option(row.path_decoded.as_deref());
+        source_kind.append_value(&row.source_kind);
+        source_detail.append_value(&row.source_detail);
+        certainty.append_value(row.certainty);
+    }
+
+    let arrays: Vec<ArrayRef> = vec![
+        Arc::new(run_id.finish()),
+        Arc::new(tool_version.finish()),
+        Arc::new(config_hash.finish()),
+        Arc::new(evidence_path.finish()),
+        Arc::new(evidence_sha256.finish()),
+        Arc::new(pass_id.finish()),
+        Arc::new(global_start.finish()),
+        Arc::new(global_end.finish()),
+        Arc::new(url.finish()),
+        Arc::new(scheme.finish()),
+        Arc::new(host.finish()),
+        Arc::new(port.finish()),
+        Arc::new(path.finish()),
+        Arc::new(query.finish()),
+        Arc::new(fragment.finish()),
+        Arc::new(host_unicode.finish()),
+        Arc::new(path_decoded.finish()),
+        Arc::new(source_kind.finish()),
+        Arc::new(source_detail.finish()),
+        Arc::new(certainty.finish()),
+    ];
+
+    RecordBatch::try_new(Arc::clone(schema), arrays)
+        .map_err(|err| MetadataError::Other(format!("parquet batch error: {err}")))
+}
+
+fn build_emails_batch(
+    ctx: &ParquetContext,
+    rows: &[EmailArtefactRow],
+    schema: &SchemaRef,
+) -> Result<RecordBatch, MetadataError> {
+    let mut run_id = StringBuilder::new();
+    let mut tool_version = StringBuilder::new();
+    let mut config_hash = StringBuilder::new();
+    let mut evidence_path = StringBuilder::new();
+    let mut evidence_sha256 = StringBuilder::new();
+    let mut pass_id = Int64Builder::new();
+    let mut global_start = Int64Builder::new();
+    let mut global_end = Int64Builder::new();
+    let mut email = StringBuilder::new();
+    let mut local_part = StringBuilder::new();
+    let mut domain = StringBuilder::new();
+    let mut source_kind = StringBuilder::new();
+    let mut source_detail = StringBuilder::new();
+    let mut certainty = arrow_array::builder::Float64Builder::new();
+
+    for row in rows {
+        run_id.append_value(&ctx.run_id);
+        tool_version.append_value(&ctx.tool_version);
+        config_hash.append_value(&ctx.config_hash);
+        evidence_path.append_value(&ctx.evidence_path);
+        evidence_sha256.append_value(&ctx.evidence_sha256);
+        pass_id.append_value(i64::from(ctx.pass_id));
+        global_start.append_value(row.global_start);
+        global_end.append_value(row.global_end);
+        email.append_value(&row.email);
+        local_part.append_value(&row.local_part);
+        domain.append_value(&row.domain);
+        source_kind.append_value(&row.source_kind);
+        source_detail.append_value(&row.source_detail);
+        certainty.append_value(row.certainty);
+    }
+
+    let arrays: Vec<ArrayRef> = vec![
+        Arc::new(run_id.finish()),
+        Arc::new(tool_version.finish()),
+        Arc::new(config_hash.finish()),
+        Arc::new(evidence_path.finish()),
+        Arc::new(evidence_sha256.finish()),
+        Arc::new(pass_id.finish()),
+        Arc::new(global_start.finish()),
+        Arc::new(global_end.finish()),
+        Arc::new(email.finish()),
+        Arc::new(local_part.finish()),
+        Arc::new(domain.finish()),
+        Arc::new(source_kind.finish()),
+        Arc::new(source_detail.finish()),
+        Arc::new(certainty.finish()),
+    ];
+
+    RecordBatch::try_new(Arc::clone(schema), arrays)
+        .map_err(|err| MetadataError::Other(format!("parquet batch error: {err}")))
+}
+
+fn build_phones_batch(
+    ctx: &ParquetContext,
+    rows: &[PhoneArtefactRow],
+    schema: &SchemaRef,
+) -> Result<RecordBatch, MetadataError> {
+    let mut run_id = StringBuilder::new();
+    let mut tool_version = StringBuilder::new();
+    let mut config_hash = StringBuilder::new();
+    let mut evidence_path = StringBuilder::new();
+    let mut evidence_sha256 = StringBuilder::new();
+    let mut pass_id = Int64Builder::new();
+    let mut global_start = Int64Builder::new();
+    let mut global_end = Int64Builder::new();
+    let mut phone_raw = StringBuilder::new();
+    let mut phone_e164 = StringBuilder::new();
+    let mut country = StringBuilder::new();
+    let mut source_kind = StringBuilder::new();
+    let mut source_detail = StringBuilder::new();
+    let mut certainty = arrow_array::builder::Float64Builder::new();
+
+    for row in rows {
+        run_id.append_value(&ctx.run_id);
+        tool_version.append_value(&ctx.tool_version);
+        config_hash.append_value(&ctx.config_hash);
+        evidence_path.append_value(&ctx.evidence_path);
+        evidence_sha256.append_value(&ctx.evidence_sha256);
+        pass_id.append_value(i64::from(ctx.pass_id));
+        global_start.append_value(row.global_start);
+        global_end.append_value(row.global_end);
+        phone_raw.append_value(&row.phone_raw);
+        phone_e164.append_option(row.phone_e164.as_deref());
+        country.append_option(row.country.as_deref());
+        source_kind.append_value(&row.source_kind);
+        source_detail.append_value(&row.source_detail);
+        certainty.append_value(row.certainty);
+    }
+
+    let arrays: Vec<ArrayRef> = vec![
+        Arc::new(run_id.finish()),
+        Arc::new(tool_version.finish()),
+        Arc::new(config_hash.finish()),
+        Arc::new(evidence_path.finish()),
+        Arc::new(evidence_sha256.finish()),
+        Arc::new(pass_id.finish()),
+        Arc::new(global_start.finish()),
+        Arc::new(global_end.finish()),
+        Arc::new(phone_raw.finish()),
+        Arc::new(phone_e164.finish()),
+        Arc::new(country.finish()),
+        Arc::new(source_kind.finish()),
+        Arc::new(source_detail.finish()),
+        Arc::new(certainty.finish()),
+    ];
+
+    RecordBatch::try_new(Arc::clone(schema), arrays)
+        .map_err(|err| MetadataError::Other(format!("parquet batch error: {err}")))
+}
+
+fn build_network_batch(
+    ctx: &ParquetContext,
+    rows: &[NetworkArtefactRow],
+    schema: &SchemaRef,
+) -> Result<RecordBatch, MetadataError> {
+    let mut run_id = StringBuilder::new();
+    let mut tool_version = StringBuilder::new();
+    let mut config_hash = StringBuilder::new();
+    let mut evidence_path = StringBuilder::new();
+    let mut evidence_sha256 = StringBuilder::new();
+    let mut pass_id = Int64Builder::new();
+    let mut global_start = Int64Builder::new();
+    let mut global_end = Int64Builder::new();
+    let mut artefact_kind = StringBuilder::new();
+    let mut value = StringBuilder::new();
+    let mut source_kind = StringBuilder::new();
+    let mut source_detail = StringBuilder::new();
+
+    for row in rows {
+        run_id.append_value(&ctx.run_id);
+        tool_version.append_value(&ctx.tool_version);
+        config_hash.append_value(&ctx.config_hash);
+        evidence_path.append_value(&ctx.evidence_path);
+        evidence_sha256.append_value(&ctx.evidence_sha256);
+        pass_id.append_value(i64::from(ctx.pass_id));
+        global_start.append_value(row.global_start);
+        global_end.append_value(row.global_end);
+        artefact_kind.append_value(&row.artefact_kind);
+        value.append_value(&row.value);
+        source_kind.append_value(&row.source_kind);
+        source_detail.append_value(&row.source_detail);
+    }
+
+    let arrays: Vec<ArrayRef> = vec![
+        Arc::new(run_id.finish()),
+        Arc::new(tool_version.finish()),
+        Arc::new(config_hash.finish()),
+        Arc::new(evidence_path.finish()),
+        Arc::new(evidence_sha256.finish()),
+        Arc::new(pass_id.finish()),
+        Arc::new(global_start.finish()),
+        Arc::new(global_end.finish()),
+        Arc::new(artefact_kind.finish()),
+        Arc::new(value.finish()),
+        Arc::new(source_kind.finish()),
+        Arc::new(source_detail.finish()),
+    ];
+
+    RecordBatch::try_new(Arc::clone(schema), arrays)
+        .map_err(|err| MetadataError::Other(format!("parquet batch error: {err}")))
+}
+
+fn build_history_batch(
+    ctx: &ParquetContext,
+    rows: &[BrowserHistoryRow],
+    schema: &SchemaRef,
+) -> Result<RecordBatch, MetadataError> {
+    let mut run_id = StringBuilder::new();
+    let mut tool_version = StringBuilder::new();
+    let mut config_hash = StringBuilder::new();
+    let mut evidence_path = StringBuilder::new();
+    let mut evidence_sha256 = StringBuilder::new();
+    let mut pass_id = Int64Builder::new();
+    let mut source_file = StringBuilder::new();
+    let mut browser = StringBuilder::new();
+    let mut profile = StringBuilder::new();
+    let mut url = StringBuilder::new();
+    let mut title = StringBuilder::new();
+    let mut visit_time = TimestampMicrosecondBuilder::new();
+    let mut visit_source = StringBuilder::new();
+    let mut row_id = Int64Builder::new();
+    let mut table_name = StringBuilder::new();
+
+    for row in rows {
+        run_id.append_value(&ctx.run_id);
+        tool_version.append_value(&ctx.tool_version);
+        config_hash.append_value(&ctx.config_hash);
+        evidence_path.append_value(&ctx.evidence_path);
+        evidence_sha256.append_value(&ctx.evidence_sha256);
+        pass_id.append_value(i64::from(ctx.pass_id));
+        source_file.append_value(&row.source_file);
+        browser.append_value(&row.browser);
+        profile.append_value(&row.profile);
+        url.append_value(&row.url);
+        title.append_option(row.title.as_deref());
+        visit_time.append_option(row.visit_time_utc);
+        visit_source.append_option(row.visit_source.as_deref());
+        row_id.append_option(row.row_id);
+        table_name.append_option(row.table_name.as_deref());
+    }
+
+    let arrays: Vec<ArrayRef> = vec![
+        Arc::new(run_id.finish()),
+        Arc::new(tool_version.finish()),
+        Arc::new(config_hash.finish()),
+        Arc::new(evidence_path.finish()),
+        Arc::new(evidence_sha256.finish()),
+        Arc::new(pass_id.finish()),
+        Arc::new(source_file.finish()),
+        Arc::new(browser.finish()),
+        Arc::new(profile.finish()),
+        Arc::new(url.finish()),
+        Arc::new(title.finish()),
+        Arc::new(visit_time.finish()),
+        Arc::new(visit_source.finish()),
+        Arc::new(row_id.finish()),
+        Arc::new(table_name.finish()),
+    ];
+
+    RecordBatch::try_new(Arc::clone(schema), arrays)
+        .map_err(|err| MetadataError::Other(format!("parquet batch error: {err}")))
+}
+
+fn build_cookies_batch(
+    ctx: &ParquetContext,
+    rows: &[BrowserCookieRow],
+    schema: &SchemaRef,
+) -> Result<RecordBatch, MetadataError> {
+    let mut run_id = StringBuilder::new();
+    let mut tool_version = StringBuilder::new();
+    let mut config_hash = StringBuilder::new();
+    let mut evidence_path = StringBuilder::new();
+    let mut evidence_sha256 = StringBuilder::new();
+    let mut pass_id = Int64Builder::new();
+    let mut source_file = StringBuilder::new();
+    let mut browser = StringBuilder::new();
+    let mut profile = StringBuilder::new();
+    let mut host = StringBuilder::new();
+    let mut name = StringBuilder::new();
+    let mut value = StringBuilder::new();
+    let mut path = StringBuilder::new();
+    let mut expires = TimestampMicrosecondBuilder::new();
+    let mut last_access = TimestampMicrosecondBuilder::new();
+    let mut creation = TimestampMicrosecondBuilder::new();
+    let mut is_secure = BooleanBuilder::new();
+    let mut is_http_only = BooleanBuilder::new();
+
+    for row in rows {
+        run_id.append_value(&ctx.run_id);
+        tool_version.append_value(&ctx.tool_version);
+        config_hash.append_value(&ctx.config_hash);
+        evidence_path.append_value(&ctx.evidence_path);
+        evidence_sha256.append_value(&ctx.evidence_sha256);
+        pass_id.append_value(i64::from(ctx.pass_id));
+        source_file.append_value(&row.source_file);
+        browser.append_value(&row.browser);
+        profile.append_value(&row.profile);
+        host.append_value(&row.host);
+        name.append_value(&row.name);
+        value.append_option(row.value.as_deref());
+        path.append_option(row.path.as_deref());
+        expires.append_option(row.expires_utc);
+        last_access.append_option(row.last_access_utc);
+        creation.append_option(row.creation_utc);
+        is_secure.append_option(row.is_secure);
+        is_http_only.append_option(row.is_http_only);
+    }
+
+    let arrays: Vec<ArrayRef> = vec![
+        Arc::new(run_id.finish()),
+        Arc::new(tool_version.finish()),
+        Arc::new(config_hash.finish()),
+        Arc::new(evidence_path.finish()),
+        Arc::new(evidence_sha256.finish()),
+        Arc::new(pass_id.finish()),
+        Arc::new(source_file.finish()),
+        Arc::new(browser.finish()),
+        Arc::new(profile.finish()),
+        Arc::new(host.finish()),
+        Arc::new(name.finish()),
+        Arc::new(value.finish()),
+        Arc::new(path.finish()),
+        Arc::new(expires.finish()),
+        Arc::new(last_access.finish()),
+        Arc::new(creation.finish()),
+        Arc::new(is_secure.finish()),
+        Arc::new(is_http_only.finish()),
+    ];
+
+    RecordBatch::try_new(Arc::clone(schema), arrays)
+        .map_err(|err| MetadataError::Other(format!("parquet batch error: {err}")))
+}
+
+fn build_downloads_batch(
+    ctx: &ParquetContext,
+    rows: &[BrowserDownloadRow],
+    schema: &SchemaRef,
+) -> Result<RecordBatch, MetadataError> {
+    let mut run_id = StringBuilder::new();
+    let mut tool_version = StringBuilder::new();
+    let mut config_hash = StringBuilder::new();
+    let mut evidence_path = StringBuilder::new();
+    let mut evidence_sha256 = StringBuilder::new();
+    let mut pass_id = Int64Builder::new();
+    let mut source_file = StringBuilder::new();
+    let mut browser = StringBuilder::new();
+    let mut profile = StringBuilder::new();
+    let mut url = StringBuilder::new();
+    let mut target_path = StringBuilder::new();
+    let mut start_time = TimestampMicrosecondBuilder::new();
+    let mut end_time = TimestampMicrosecondBuilder::new();
+    let mut total_bytes = Int64Builder::new();
+    let mut state = StringBuilder::new();
+
+    for row in rows {
+        run_id.append_value(&ctx.run_id);
+        tool_version.append_value(&ctx.tool_version);
+        config_hash.append_value(&ctx.config_hash);
+        evidence_path.append_value(&ctx.evidence_path);
+        evidence_sha256.append_value(&ctx.evidence_sha256);
+        pass_id.append_value(i64::from(ctx.pass_id));
+        source_file.append_value(&row.source_file);
+        browser.append_value(&row.browser);
+        profile.append_value(&row.profile);
+        url.append_option(row.url.as_deref());
+        target_path.append_option(row.target_path.as_deref());
+        start_time.append_option(row.start_time_utc);
+        end_time.append_option(row.end_time_utc);
+        total_bytes.append_option(row.total_bytes);
+        state.append_option(row.state.as_deref());
+    }
+
+    let arrays: Vec<ArrayRef> = vec![
+        Arc::new(run_id.finish()),
+        Arc::new(tool_version.finish()),
+        Arc::new(config_hash.finish()),
+        Arc::new(evidence_path.finish()),
+        Arc::new(evidence_sha256.finish()),
+        Arc::new(pass_id.finish()),
+        Arc::new(source_file.finish()),
+        Arc::new(browser.finish()),
+        Arc::new(profile.finish()),
+        Arc::new(url.finish()),
+        Arc::new(target_path.finish()),
+        Arc::new(start_time.finish()),
+        Arc::new(end_time.finish()),
+        Arc::new(total_bytes.finish()),
+        Arc::new(state.finish()),
+    ];
+
+    RecordBatch::try_new(Arc::clone(schema), arrays)
+        .map_err(|err| MetadataError::Other(format!("parquet batch error: {err}")))
+}
+
+fn build_cache_batch(
+    ctx: &ParquetContext,
+    rows: &[BrowserCacheRow],
+    schema: &SchemaRef,
+) -> Result<RecordBatch, MetadataError> {
+    let mut run_id = StringBuilder::new();
+    let mut tool_version = StringBuilder::new();
+    let mut config_hash = StringBuilder::new();
+    let mut evidence_path = StringBuilder::new();
+    let mut evidence_sha256 = StringBuilder::new();
+    let mut pass_id = Int64Builder::new();
+    let mut source_file = StringBuilder::new();
+    let mut browser = StringBuilder::new();
+    let mut url = StringBuilder::new();
+    let mut http_status = Int32Builder::new();
+    let mut content_type = StringBuilder::new();
+    let mut content_encoding = StringBuilder::new();
+    let mut response_headers = StringBuilder::new();
+    let mut body_size = Int64Builder::new();
+    let mut body_path = StringBuilder::new();
+
+    for row in rows {
+        run_id.append_value(&ctx.run_id);
+        tool_version.append_value(&ctx.tool_version);
+        config_hash.append_value(&ctx.config_hash);
+        evidence_path.append_value(&ctx.evidence_path);
+        evidence_sha256.append_value(&ctx.evidence_sha256);
+        pass_id.append_value(i64::from(ctx.pass_id));
+        source_file.append_value(&row.source_file);
+        browser.append_value(&row.browser);
+        url.append_value(&row.url);
+        http_status.append_option(row.http_status);
+        content_type.append_option(row.content_type.as_deref());
+        content_encoding.append_option(row.content_encoding.as_deref());
+        response_headers.append_option(row.response_headers.as_deref());
+        body_size.append_value(row.body_size);
+        body_path.append_option(row.body_path.as_deref());
+    }
+
+    let arrays: Vec<ArrayRef> = vec![
+        Arc::new(run_id.finish()),
+        Arc::new(tool_version.finish()),
+        Arc::new(config_hash.finish()),
+        Arc::new(evidence_path.finish()),
+        Arc::new(evidence_sha256.finish()),
+        Arc::new(pass_id.finish()),
+        Arc::new(source_file.finish()),
+        Arc::new(browser.finish()),
+        Arc::new(url.finish()),
+        Arc::new(http_status.finish()),
+        Arc::new(content_type.finish()),
+        Arc::new(content_encoding.finish()),
+        Arc::new(response_headers.finish()),
+        Arc::new(body_size.finish()),
+        Arc::new(body_path.finish()),
+    ];
+
+    RecordBatch::try_new(Arc::clone(schema), arrays)
+        .map_err(|err| MetadataError::Other(format!("parquet batch error: {err}")))
+}
+
+fn build_log_events_batch(
+    ctx: &ParquetContext,
+    rows: &[LogEventRow],
+    schema: &SchemaRef,
+) -> Result<RecordBatch, MetadataError> {
+    let mut run_id = StringBuilder::new();
+    let mut tool_version = StringBuilder::new();
+    let mut config_hash = StringBuilder::new();
+    let mut evidence_path = StringBuilder::new();
+    let mut evidence_sha256 = StringBuilder::new();
+    let mut pass_id = Int64Builder::new();
+    let mut source = StringBuilder::new();
+    let mut timestamp = TimestampMicrosecondBuilder::new();
+    let mut timestamp_raw = StringBuilder::new();
+    let mut host = StringBuilder::new();
+    let mut program = StringBuilder::new();
+    let mut pid = Int64Builder::new();
+    let mut severity = Int32Builder::new();
+    let mut message = StringBuilder::new();
+    let mut global_offset = Int64Builder::new();
+    let mut source_file = StringBuilder::new();
+
+    for row in rows {
+        run_id.append_value(&ctx.run_id);
+        tool_version.append_value(&ctx.tool_version);
+        config_hash.append_value(&ctx.config_hash);
+        evidence_path.append_value(&ctx.evidence_path);
+        evidence_sha256.append_value(&ctx.evidence_sha256);
+        pass_id.append_value(i64::from(ctx.pass_id));
+        source.append_value(&row.source);
+        timestamp.append_option(row.timestamp_utc);
+        timestamp_raw.append_option(row.timestamp_raw.as_deref());
+        host.append_option(row.host.as_deref());
+        program.append_option(row.program.as_deref());
+        pid.append_option(row.pid);
+        severity.append_option(row.severity);
+        message.append_value(&row.message);
+        global_offset.append_value(row.global_offset);
+        source_file.append_option(row.source_file.as_deref());
+    }
+
+    let arrays: Vec<ArrayRef> = vec![
+        Arc::new(run_id.finish()),
+        Arc::new(tool_version.finish()),
+        Arc::new(config_hash.finish()),
+        Arc::new(evidence_path.finish()),
+        Arc::new(evidence_sha256.finish()),
+        Arc::new(pass_id.finish()),
+        Arc::new(source.finish()),
+        Arc::new(timestamp.finish()),
+        Arc::new(timestamp_raw.finish()),
+        Arc::new(host.finish()),
+        Arc::new(program.finish()),
+        Arc::new(pid.finish()),
+        Arc::new(severity.finish()),
+        Arc::new(message.finish()),
+        Arc::new(global_offset.finish()),
+        Arc::new(source_file.finish()),
+    ];
+
+    RecordBatch::try_new(Arc::clone(schema), arrays)
+        .map_err(|err| MetadataError::Other(format!("parquet batch error: {err}")))
+}
+
+fn build_containers_batch(
+    ctx: &ParquetContext,
+    rows: &[ContainerArtefactRow],
+    schema: &SchemaRef,
+) -> Result<RecordBatch, MetadataError> {
+    let mut run_id = StringBuilder::new();
+    let mut tool_version = StringBuilder::new();
+    let mut config_hash = StringBuilder::new();
+    let mut evidence_path = StringBuilder::new();
+    let mut evidence_sha256 = StringBuilder::new();
+    let mut pass_id = Int64Builder::new();
+    let mut kind = StringBuilder::new();
+    let mut name = StringBuilder::new();
+    let mut digest = StringBuilder::new();
+    let mut namespace = StringBuilder::new();
+    let mut detail = StringBuilder::new();
+    let mut global_offset = Int64Builder::new();
+    let mut source_file = StringBuilder::new();
+
+    for row in rows {
+        run_id.append_value(&ctx.run_id);
+        tool_version.append_value(&ctx.tool_version);
+        config_hash.append_value(&ctx.config_hash);
+        evidence_path.append_value(&ctx.evidence_path);
+        evidence_sha256.append_value(&ctx.evidence_sha256);
+        pass_id.append_value(i64::from(ctx.pass_id));
+        kind.append_value(&row.kind);
+        name.append_option(row.name.as_deref());
+        digest.append_option(row.digest.as_deref());
+        namespace.append_option(row.namespace.as_deref());
+        detail.append_option(row.detail.as_deref());
+        global_offset.append_value(row.global_offset);
+        source_file.append_value(&row.source_file);
+    }
+
+    let arrays: Vec<ArrayRef> = vec![
+        Arc::new(run_id.finish()),
+        Arc::new(tool_version.finish()),
+        Arc::new(config_hash.finish()),
+        Arc::new(evidence_path.finish()),
+        Arc::new(evidence_sha256.finish()),
+        Arc::new(pass_id.finish()),
+        Arc::new(kind.finish()),
+        Arc::new(name.finish()),
+        Arc::new(digest.finish()),
+        Arc::new(namespace.finish()),
+        Arc::new(detail.finish()),
+        Arc::new(global_offset.finish()),
+        Arc::new(source_file.finish()),
+    ];
+
+    RecordBatch::try_new(Arc::clone(schema), arrays)
+        .map_err(|err| MetadataError::Other(format!("parquet batch error: {err}")))
+}
+
+fn build_messages_batch(
+    ctx: &ParquetContext,
+    rows: &[MessageRow],
+    schema: &SchemaRef,
+) -> Result<RecordBatch, MetadataError> {
+    let mut run_id = StringBuilder::new();
+    let mut tool_version = StringBuilder::new();
+    let mut config_hash = StringBuilder::new();
+    let mut evidence_path = StringBuilder::new();
+    let mut evidence_sha256 = StringBuilder::new();
+    let mut pass_id = Int64Builder::new();
+    let mut app = StringBuilder::new();
+    let mut message_id = StringBuilder::new();
+    let mut thread = StringBuilder::new();
+    let mut handle = StringBuilder::new();
+    let mut is_from_me = BooleanBuilder::new();
+    let mut service = StringBuilder::new();
+    let mut text = StringBuilder::new();
+    let mut timestamp_utc = TimestampMicrosecondBuilder::new();
+    let mut modified_utc = TimestampMicrosecondBuilder::new();
+    let mut source_file = StringBuilder::new();
+
+    for row in rows {
+        run_id.append_value(&ctx.run_id);
+        tool_version.append_value(&ctx.tool_version);
+        config_hash.append_value(&ctx.config_hash);
+        evidence_path.append_value(&ctx.evidence_path);
+        evidence_sha256.append_value(&ctx.evidence_sha256);
+        pass_id.append_value(i64::from(ctx.pass_id));
+        app.append_value(&row.app);
+        message_id.append_option(row.message_id.as_deref());
+        thread.append_option(row.thread.as_deref());
+        handle.append_option(row.handle.as_deref());
+        is_from_me.append_option(row.is_from_me);
+        service.append_option(row.service.as_deref());
+        text.append_option(row.text.as_deref());
+        timestamp_utc.append_option(row.timestamp_utc);
+        modified_utc.append_option(row.modified_utc);
+        source_file.append_value(&row.source_file);
+    }
+
+    let arrays: Vec<ArrayRef> = vec![
+        Arc::new(run_id.finish()),
+        Arc::new(tool_version.finish()),
+        Arc::new(config_hash.finish()),
+        Arc::new(evidence_path.finish()),
+        Arc::new(evidence_sha256.finish()),
+        Arc::new(pass_id.finish()),
+        Arc::new(app.finish()),
+        Arc::new(message_id.finish()),
+        Arc::new(thread.finish()),
+        Arc::new(handle.finish()),
+        Arc::new(is_from_me.finish()),
+        Arc::new(service.finish()),
+        Arc::new(text.finish()),
+        Arc::new(timestamp_utc.finish()),
+        Arc::new(modified_utc.finish()),
+        Arc::new(source_file.finish()),
+    ];
+
+    RecordBatch::try_new(Arc::clone(schema), arrays)
+        .map_err(|err| MetadataError::Other(format!("parquet batch error: {err}")))
 }
 
-impl ParquetSink {
-    pub fn new(
-        cfg: &Config,
-        run_id: &str,
-        tool_version: &str,
-        config_hash: &str,
-        evidence_path: &Path,
-        evidence_sha256: &str,
-        run_output_dir: &Path,
-    ) -> Result<Self, MetadataError> {
-        let parquet_dir = run_output_dir.join("parquet");
-        std::fs::create_dir_all(&parquet_dir)?;
-        let context = Arc::new(ParquetContext {
-            run_id: run_id.to_string(),
-            tool_version: tool_version.to_string(),
-            config_hash: config_hash.to_string(),
-            evidence_path: evidence_path.to_string_lossy().to_string(),
-            evidence_sha256: evidence_sha256.to_string(),
-        });
+fn build_usage_events_batch(
+    ctx: &ParquetContext,
+    rows: &[UsageEventRow],
+    schema: &SchemaRef,
+) -> Result<RecordBatch, MetadataError> {
+    let mut run_id = StringBuilder::new();
+    let mut tool_version = StringBuilder::new();
+    let mut config_hash = StringBuilder::new();
+    let mut evidence_path = StringBuilder::new();
+    let mut evidence_sha256 = StringBuilder::new();
+    let mut pass_id = Int64Builder::new();
+    let mut source = StringBuilder::new();
+    let mut stream = StringBuilder::new();
+    let mut value = StringBuilder::new();
+    let mut bundle_id = StringBuilder::new();
+    let mut start_utc = TimestampMicrosecondBuilder::new();
+    let mut end_utc = TimestampMicrosecondBuilder::new();
+    let mut source_file = StringBuilder::new();
 
-        Ok(Self {
-            inner: Mutex::new(ParquetSinkInner {
-                context,
-                parquet_dir,
-                row_group_size: cfg.parquet_row_group_size.max(1),
-                files_jpeg: None,
-                files_png: None,
-                files_gif: None,
-                files_sqlite: None,
-                files_pdf: None,
-                files_zip: None,
-                files_webp: None,
-                files_other: None,
-                artefacts_urls: None,
-                artefacts_emails: None,
-                artefacts_phones: None,
-                browser_history: None,
-                browser_cookies: None,
-                browser_downloads: None,
-                entropy_regions: None,
-                run_summary: None,
-            }),
-        })
+    for row in rows {
+        run_id.append_value(&ctx.run_id);
+        tool_version.append_value(&ctx.tool_version);
+        config_hash.append_value(&ctx.config_hash);
+        evidence_path.append_value(&ctx.evidence_path);
+        evidence_sha256.append_value(&ctx.evidence_sha256);
+        pass_id.append_value(i64::from(ctx.pass_id));
+        source.append_value(&row.source);
+        stream.append_value(&row.stream);
+        value.append_option(row.value.as_deref());
+        bundle_id.append_option(row.bundle_id.as_deref());
+        start_utc.append_option(row.start_utc);
+        end_utc.append_option(row.end_utc);
+        source_file.append_value(&row.source_file);
     }
 
-    fn lock_inner(&self) -> Result<std::sync::MutexGuard<'_, ParquetSinkInner>, MetadataError> {
-        self.inner
-            .lock()
-            .map_err(|_| MetadataError::Other("parquet sink lock poisoned".to_string()))
+    let arrays: Vec<ArrayRef> = vec![
+        Arc::new(run_id.finish()),
+        Arc::new(tool_version.finish()),
+        Arc::new(config_hash.finish()),
+        Arc::new(evidence_path.finish()),
+        Arc::new(evidence_sha256.finish()),
+        Arc::new(pass_id.finish()),
+        Arc::new(source.finish()),
+        Arc::new(stream.finish()),
+        Arc::new(value.finish()),
+        Arc::new(bundle_id.finish()),
+        Arc::new(start_utc.finish()),
+        Arc::new(end_utc.finish()),
+        Arc::new(source_file.finish()),
+    ];
+
+    RecordBatch::try_new(Arc::clone(schema), arrays)
+        .map_err(|err| MetadataError::Other(format!("parquet batch error: {err}")))
+}
+
+fn build_app_records_batch(
+    ctx: &ParquetContext,
+    rows: &[AppRecordRow],
+    schema: &SchemaRef,
+) -> Result<RecordBatch, MetadataError> {
+    let mut run_id = StringBuilder::new();
+    let mut tool_version = StringBuilder::new();
+    let mut config_hash = StringBuilder::new();
+    let mut evidence_path = StringBuilder::new();
+    let mut evidence_sha256 = StringBuilder::new();
+    let mut pass_id = Int64Builder::new();
+    let mut app = StringBuilder::new();
+    let mut plan = StringBuilder::new();
+    let mut row_id = Int64Builder::new();
+    let mut timestamp_utc = TimestampMicrosecondBuilder::new();
+    let mut timestamp_desc = StringBuilder::new();
+    let mut fields = StringBuilder::new();
+    let mut source_file = StringBuilder::new();
+
+    for row in rows {
+        run_id.append_value(&ctx.run_id);
+        tool_version.append_value(&ctx.tool_version);
+        config_hash.append_value(&ctx.config_hash);
+        evidence_path.append_value(&ctx.evidence_path);
+        evidence_sha256.append_value(&ctx.evidence_sha256);
+        pass_id.append_value(i64::from(ctx.pass_id));
+        app.append_value(&row.app);
+        plan.append_value(&row.plan);
+        row_id.append_option(row.row_id);
+        timestamp_utc.append_option(row.timestamp_utc);
+        timestamp_desc.append_option(row.timestamp_desc.as_deref());
+        fields.append_value(&row.fields);
+        source_file.append_value(&row.source_file);
     }
+
+    let arrays: Vec<ArrayRef> = vec![
+        Arc::new(run_id.finish()),
+        Arc::new(tool_version.finish()),
+        Arc::new(config_hash.finish()),
+        Arc::new(evidence_path.finish()),
+        Arc::new(evidence_sha256.finish()),
+        Arc::new(pass_id.finish()),
+        Arc::new(app.finish()),
+        Arc::new(plan.finish()),
+        Arc::new(row_id.finish()),
+        Arc::new(timestamp_utc.finish()),
+        Arc::new(timestamp_desc.finish()),
+        Arc::new(fields.finish()),
+        Arc::new(source_file.finish()),
+    ];
+
+    RecordBatch::try_new(Arc::clone(schema), arrays)
+        .map_err(|err| MetadataError::Other(format!("parquet batch error: {err}")))
 }
 
-impl MetadataSink for ParquetSink {
-    fn record_file(&self, file: &CarvedFile) -> Result<(), MetadataError> {
-        let category = category_for_file_type(&file.file_type);
-        let row = FileRow {
-            handler_id: handler_id_for_file_type(&file.file_type).to_string(),
-            file_type: file.file_type.clone(),
-            carved_path: file.path.clone(),
-            global_start: to_i64(file.global_start)?,
-            global_end: to_i64(file.global_end)?,
-            size: to_i64(file.size)?,
-            md5: file.md5.clone(),
-            sha256: file.sha256.clone(),
-            pattern_id: file.pattern_id.clone(),
-            magic_bytes: None,
-            validated: file.validated,
-            truncated: file.truncated,
-            error: join_errors(&file.errors),
-        };
+fn build_prefetch_files_batch(
+    ctx: &ParquetContext,
+    rows: &[PrefetchRow],
+    schema: &SchemaRef,
+) -> Result<RecordBatch, MetadataError> {
+    let mut run_id = StringBuilder::new();
+    let mut tool_version = StringBuilder::new();
+    let mut config_hash = StringBuilder::new();
+    let mut evidence_path = StringBuilder::new();
+    let mut evidence_sha256 = StringBuilder::new();
+    let mut pass_id = Int64Builder::new();
+    let mut executable = StringBuilder::new();
+    let mut prefetch_hash = StringBuilder::new();
+    let mut format_version = Int64Builder::new();
+    let mut compressed = BooleanBuilder::new();
+    let mut run_count = Int64Builder::new();
+    let mut last_run_utc = TimestampMicrosecondBuilder::new();
+    let mut earlier_runs_utc = StringBuilder::new();
+    let mut source_file = StringBuilder::new();
 
-        let mut inner = self.lock_inner()?;
-        let writer = inner.get_or_create_writer(category)?;
-        writer.append_file(row)
+    for row in rows {
+        run_id.append_value(&ctx.run_id);
+        tool_version.append_value(&ctx.tool_version);
+        config_hash.append_value(&ctx.config_hash);
+        evidence_path.append_value(&ctx.evidence_path);
+        evidence_sha256.append_value(&ctx.evidence_sha256);
+        pass_id.append_value(i64::from(ctx.pass_id));
+        executable.append_value(&row.executable);
+        prefetch_hash.append_value(&row.prefetch_hash);
+        format_version.append_value(i64::from(row.format_version));
+        compressed.append_value(row.compressed);
+        run_count.append_value(i64::from(row.run_count));
+        last_run_utc.append_option(row.last_run_utc);
+        earlier_runs_utc.append_option(row.earlier_runs_utc.as_deref());
+        source_file.append_value(&row.source_file);
     }
 
-    fn record_string(&self, artefact: &StringArtefact) -> Result<(), MetadataError> {
-        let mut inner = self.lock_inner()?;
-        match artefact.artefact_kind {
-            ArtefactKind::Url => {
-                let row = map_url_artefact(artefact)?;
-                let writer = inner.get_or_create_writer(ParquetCategory::ArtefactsUrls)?;
-                writer.append_url(row)
-            }
-            ArtefactKind::Email => {
-                let row = map_email_artefact(artefact)?;
-                let writer = inner.get_or_create_writer(ParquetCategory::ArtefactsEmails)?;
-                writer.append_email(row)
-            }
-            ArtefactKind::Phone => {
-                let row = map_phone_artefact(artefact)?;
-                let writer = inner.get_or_create_writer(ParquetCategory::ArtefactsPhones)?;
-                writer.append_phone(row)
-            }
-            ArtefactKind::GenericString => Ok(()),
-        }
+    let arrays: Vec<ArrayRef> = vec![
+        Arc::new(run_id.finish()),
+        Arc::new(tool_version.finish()),
+        Arc::new(config_hash.finish()),
+        Arc::new(evidence_path.finish()),
+        Arc::new(evidence_sha256.finish()),
+        Arc::new(pass_id.finish()),
+        Arc::new(executable.finish()),
+        Arc::new(prefetch_hash.finish()),
+        Arc::new(format_version.finish()),
+        Arc::new(compressed.finish()),
+        Arc::new(run_count.finish()),
+        Arc::new(last_run_utc.finish()),
+        Arc::new(earlier_runs_utc.finish()),
+        Arc::new(source_file.finish()),
+    ];
+
+    RecordBatch::try_new(Arc::clone(schema), arrays)
+        .map_err(|err| MetadataError::Other(format!("parquet batch error: {err}")))
+}
+
+fn build_shortcuts_batch(
+    ctx: &ParquetContext,
+    rows: &[ShortcutRow],
+    schema: &SchemaRef,
+) -> Result<RecordBatch, MetadataError> {
+    let mut run_id = StringBuilder::new();
+    let mut tool_version = StringBuilder::new();
+    let mut config_hash = StringBuilder::new();
+    let mut evidence_path = StringBuilder::new();
+    let mut evidence_sha256 = StringBuilder::new();
+    let mut pass_id = Int64Builder::new();
+    let mut target_path = StringBuilder::new();
+    let mut arguments = StringBuilder::new();
+    let mut working_dir = StringBuilder::new();
+    let mut relative_path = StringBuilder::new();
+    let mut description = StringBuilder::new();
+    let mut icon_location = StringBuilder::new();
+    let mut target_size = Int64Builder::new();
+    let mut file_attributes = Int64Builder::new();
+    let mut target_created_utc = TimestampMicrosecondBuilder::new();
+    let mut target_accessed_utc = TimestampMicrosecondBuilder::new();
+    let mut target_modified_utc = TimestampMicrosecondBuilder::new();
+    let mut volume_serial = StringBuilder::new();
+    let mut machine_id = StringBuilder::new();
+    let mut source_file = StringBuilder::new();
+
+    for row in rows {
+        run_id.append_value(&ctx.run_id);
+        tool_version.append_value(&ctx.tool_version);
+        config_hash.append_value(&ctx.config_hash);
+        evidence_path.append_value(&ctx.evidence_path);
+        evidence_sha256.append_value(&ctx.evidence_sha256);
+        pass_id.append_value(i64::from(ctx.pass_id));
+        target_path.append_option(row.target_path.as_deref());
+        arguments.append_option(row.arguments.as_deref());
+        working_dir.append_option(row.working_dir.as_deref());
+        relative_path.append_option(row.relative_path.as_deref());
+        description.append_option(row.description.as_deref());
+        icon_location.append_option(row.icon_location.as_deref());
+        target_size.append_value(i64::from(row.target_size));
+        file_attributes.append_value(i64::from(row.file_attributes));
+        target_created_utc.append_option(row.target_created_utc);
+        target_accessed_utc.append_option(row.target_accessed_utc);
+        target_modified_utc.append_option(row.target_modified_utc);
+        volume_serial.append_option(row.volume_serial.as_deref());
+        machine_id.append_option(row.machine_id.as_deref());
+        source_file.append_value(&row.source_file);
     }
 
-    fn record_history(&self, record: &BrowserHistoryRecord) -> Result<(), MetadataError> {
-        let row = BrowserHistoryRow {
-            source_file: record.source_file.to_string_lossy().to_string(),
-            browser: record.browser.clone(),
-            profile: record.profile.clone(),
-            url: record.url.clone(),
-            title: record.title.clone(),
-            visit_time_utc: record.visit_time.map(to_micros),
-            visit_source: record.visit_source.clone(),
-            row_id: None,
-            table_name: None,
-        };
+    let arrays: Vec<ArrayRef> = vec![
+        Arc::new(run_id.finish()),
+        Arc::new(tool_version.finish()),
+        Arc::new(config_hash.finish()),
+        Arc::new(evidence_path.finish()),
+        Arc::new(evidence_sha256.finish()),
+        Arc::new(pass_id.finish()),
+        Arc::new(target_path.finish()),
+        Arc::new(arguments.finish()),
+        Arc::new(working_dir.finish()),
+        Arc::new(relative_path.finish()),
+        Arc::new(description.finish()),
+        Arc::new(icon_location.finish()),
+        Arc::new(target_size.finish()),
+        Arc::new(file_attributes.finish()),
+        Arc::new(target_created_utc.finish()),
+        Arc::new(target_accessed_utc.finish()),
+        Arc::new(target_modified_utc.finish()),
+        Arc::new(volume_serial.finish()),
+        Arc::new(machine_id.finish()),
+        Arc::new(source_file.finish()),
+    ];
 
-        let mut inner = self.lock_inner()?;
-        let writer = inner.get_or_create_writer(ParquetCategory::BrowserHistory)?;
-        writer.append_history(row)
-    }
+    RecordBatch::try_new(Arc::clone(schema), arrays)
+        .map_err(|err| MetadataError::Other(format!("parquet batch error: {err}")))
+}
 
-    fn record_cookie(&self, record: &BrowserCookieRecord) -> Result<(), MetadataError> {
-        let row = BrowserCookieRow {
-            source_file: record.source_file.to_string_lossy().to_string(),
-            browser: record.browser.clone(),
-            profile: record.profile.clone(),
-            host: record.host.clone(),
-            name: record.name.clone(),
-            value: record.value.clone(),
-            path: record.path.clone(),
-            expires_utc: record.expires_utc.map(to_micros),
-            last_access_utc: record.last_access_utc.map(to_micros),
-            creation_utc: record.creation_utc.map(to_micros),
-            is_secure: record.is_secure,
-            is_http_only: record.is_http_only,
-        };
+fn build_executables_batch(
+    ctx: &ParquetContext,
+    rows: &[ExecutableRow],
+    schema: &SchemaRef,
+) -> Result<RecordBatch, MetadataError> {
+    let mut run_id = StringBuilder::new();
+    let mut tool_version = StringBuilder::new();
+    let mut config_hash = StringBuilder::new();
+    let mut evidence_path = StringBuilder::new();
+    let mut evidence_sha256 = StringBuilder::new();
+    let mut pass_id = Int64Builder::new();
+    let mut format = StringBuilder::new();
+    let mut kind = StringBuilder::new();
+    let mut architecture = StringBuilder::new();
+    let mut bits = Int32Builder::new();
+    let mut compile_time_utc = TimestampMicrosecondBuilder::new();
+    let mut subsystem = StringBuilder::new();
+    let mut section_count = Int64Builder::new();
+    let mut import_count = Int64Builder::new();
+    let mut import_hash = StringBuilder::new();
+    let mut signed = BooleanBuilder::new();
+    let mut source_file = StringBuilder::new();
 
-        let mut inner = self.lock_inner()?;
-        let writer = inner.get_or_create_writer(ParquetCategory::BrowserCookies)?;
-        writer.append_cookie(row)
+    for row in rows {
+        run_id.append_value(&ctx.run_id);
+        tool_version.append_value(&ctx.tool_version);
+        config_hash.append_value(&ctx.config_hash);
+        evidence_path.append_value(&ctx.evidence_path);
+        evidence_sha256.append_value(&ctx.evidence_sha256);
+        pass_id.append_value(i64::from(ctx.pass_id));
+        format.append_value(&row.format);
+        kind.append_value(&row.kind);
+        architecture.append_value(&row.architecture);
+        bits.append_value(i32::from(row.bits));
+        compile_time_utc.append_option(row.compile_time_utc);
+        subsystem.append_option(row.subsystem.as_deref());
+        section_count.append_value(i64::from(row.section_count));
+        import_count.append_option(row.import_count.map(i64::from));
+        import_hash.append_option(row.import_hash.as_deref());
+        signed.append_value(row.signed);
+        source_file.append_value(&row.source_file);
     }
 
-    fn record_download(&self, record: &BrowserDownloadRecord) -> Result<(), MetadataError> {
-        let row = BrowserDownloadRow {
-            source_file: record.source_file.to_string_lossy().to_string(),
-            browser: record.browser.clone(),
-            profile: record.profile.clone(),
-            url: record.url.clone(),
-            target_path: record.target_path.clone(),
-            start_time_utc: record.start_time.map(to_micros),
-            end_time_utc: record.end_time.map(to_micros),
-            total_bytes: record.total_bytes,
-            state: record.state.clone(),
-        };
+    let arrays: Vec<ArrayRef> = vec![
+        Arc::new(run_id.finish()),
+        Arc::new(tool_version.finish()),
+        Arc::new(config_hash.finish()),
+        Arc::new(evidence_path.finish()),
+        Arc::new(evidence_sha256.finish()),
+        Arc::new(pass_id.finish()),
+        Arc::new(format.finish()),
+        Arc::new(kind.finish()),
+        Arc::new(architecture.finish()),
+        Arc::new(bits.finish()),
+        Arc::new(compile_time_utc.finish()),
+        Arc::new(subsystem.finish()),
+        Arc::new(section_count.finish()),
+        Arc::new(import_count.finish()),
+        Arc::new(import_hash.finish()),
+        Arc::new(signed.finish()),
+        Arc::new(source_file.finish()),
+    ];
 
-        let mut inner = self.lock_inner()?;
-        let writer = inner.get_or_create_writer(ParquetCategory::BrowserDownloads)?;
-        writer.append_download(row)
-    }
+    RecordBatch::try_new(Arc::clone(schema), arrays)
+        .map_err(|err| MetadataError::Other(format!("parquet batch error: {err}")))
+}
 
-    fn record_run_summary(&self, summary: &RunSummary) -> Result<(), MetadataError> {
-        let row = RunSummaryRow {
-            bytes_scanned: to_i64(summary.bytes_scanned)?,
-            chunks_processed: to_i64(summary.chunks_processed)?,
-            hits_found: to_i64(summary.hits_found)?,
-            files_carved: to_i64(summary.files_carved)?,
-            string_spans: to_i64(summary.string_spans)?,
-            artefacts_extracted: to_i64(summary.artefacts_extracted)?,
-        };
-        let mut inner = self.lock_inner()?;
-        let writer = inner.get_or_create_writer(ParquetCategory::RunSummary)?;
-        writer.append_summary(row)
-    }
+fn build_plists_batch(
+    ctx: &ParquetContext,
+    rows: &[PlistRow],
+    schema: &SchemaRef,
+) -> Result<RecordBatch, MetadataError> {
+    let mut run_id = StringBuilder::new();
+    let mut tool_version = StringBuilder::new();
+    let mut config_hash = StringBuilder::new();
+    let mut evidence_path = StringBuilder::new();
+    let mut evidence_sha256 = StringBuilder::new();
+    let mut pass_id = Int64Builder::new();
+    let mut object_count = Int64Builder::new();
+    let mut top_type = StringBuilder::new();
+    let mut top_keys = StringBuilder::new();
+    let mut archiver = StringBuilder::new();
+    let mut json_len = Int64Builder::new();
+    let mut json = StringBuilder::new();
+    let mut source_file = StringBuilder::new();
 
-    fn record_entropy(&self, region: &crate::metadata::EntropyRegion) -> Result<(), MetadataError> {
-        let row = EntropyRegionRow {
-            global_start: to_i64(region.global_start)?,
-            global_end: to_i64(region.global_end)?,
-            entropy: region.entropy,
-            window_size: to_i64(region.window_size)?,
-        };
-        let mut inner = self.lock_inner()?;
-        let writer = inner.get_or_create_writer(ParquetCategory::EntropyRegions)?;
-        writer.append_entropy(row)
+    for row in rows {
+        run_id.append_value(&ctx.run_id);
+        tool_version.append_value(&ctx.tool_version);
+        config_hash.append_value(&ctx.config_hash);
+        evidence_path.append_value(&ctx.evidence_path);
+        evidence_sha256.append_value(&ctx.evidence_sha256);
+        pass_id.append_value(i64::from(ctx.pass_id));
+        object_count.append_value(to_i64(row.object_count)?);
+        top_type.append_value(&row.top_type);
+        top_keys.append_option(row.top_keys.as_deref());
+        archiver.append_option(row.archiver.as_deref());
+        json_len.append_value(to_i64(row.json_len)?);
+        json.append_option(row.json.as_deref());
+        source_file.append_value(&row.source_file);
     }
 
-    fn flush(&self) -> Result<(), MetadataError> {
-        // Flush all buffers to ensure data is written to disk
-        // This allows recovery of data if the process is interrupted
-        let mut inner = self.lock_inner()?;
-        inner.flush_all_buffers()?;
-        // Note: We don't call finish_all() here because that would close writers
-        // and prevent further writes. Finish is called in Drop.
-        Ok(())
-    }
+    let arrays: Vec<ArrayRef> = vec![
+        Arc::new(run_id.finish()),
+        Arc::new(tool_version.finish()),
+        Arc::new(config_hash.finish()),
+        Arc::new(evidence_path.finish()),
+        Arc::new(evidence_sha256.finish()),
+        Arc::new(pass_id.finish()),
+        Arc::new(object_count.finish()),
+        Arc::new(top_type.finish()),
+        Arc::new(top_keys.finish()),
+        Arc::new(archiver.finish()),
+        Arc::new(json_len.finish()),
+        Arc::new(json.finish()),
+        Arc::new(source_file.finish()),
+    ];
+
+    RecordBatch::try_new(Arc::clone(schema), arrays)
+        .map_err(|err| MetadataError::Other(format!("parquet batch error: {err}")))
 }
 
-impl Drop for ParquetSink {
-    fn drop(&mut self) {
-        // Ensure all writers are properly finished when the sink is dropped
-        if let Ok(mut inner) = self.inner.lock() {
-            let _ = inner.finish_all();
-        }
+fn build_video_repairs_batch(
+    ctx: &ParquetContext,
+    rows: &[VideoRepairRow],
+    schema: &SchemaRef,
+) -> Result<RecordBatch, MetadataError> {
+    let mut run_id = StringBuilder::new();
+    let mut tool_version = StringBuilder::new();
+    let mut config_hash = StringBuilder::new();
+    let mut evidence_path = StringBuilder::new();
+    let mut evidence_sha256 = StringBuilder::new();
+    let mut pass_id = Int64Builder::new();
+    let mut method = StringBuilder::new();
+    let mut codec = StringBuilder::new();
+    let mut width = Int64Builder::new();
+    let mut height = Int64Builder::new();
+    let mut frame_count = Int64Builder::new();
+    let mut keyframe_count = Int64Builder::new();
+    let mut duration_secs = arrow_array::builder::Float64Builder::new();
+    let mut media_bytes = Int64Builder::new();
+    let mut skipped_bytes = Int64Builder::new();
+    let mut donor_file = StringBuilder::new();
+    let mut repaired_path = StringBuilder::new();
+    let mut source_file = StringBuilder::new();
+
+    for row in rows {
+        run_id.append_value(&ctx.run_id);
+        tool_version.append_value(&ctx.tool_version);
+        config_hash.append_value(&ctx.config_hash);
+        evidence_path.append_value(&ctx.evidence_path);
+        evidence_sha256.append_value(&ctx.evidence_sha256);
+        pass_id.append_value(i64::from(ctx.pass_id));
+        method.append_value(&row.method);
+        codec.append_value(&row.codec);
+        width.append_value(i64::from(row.width));
+        height.append_value(i64::from(row.height));
+        frame_count.append_value(to_i64(row.frame_count)?);
+        keyframe_count.append_value(to_i64(row.keyframe_count)?);
+        duration_secs.append_value(row.duration_secs);
+        media_bytes.append_value(to_i64(row.media_bytes)?);
+        skipped_bytes.append_value(to_i64(row.skipped_bytes)?);
+        donor_file.append_option(row.donor_file.as_deref());
+        repaired_path.append_value(&row.repaired_path);
+        source_file.append_value(&row.source_file);
     }
-}
 
-pub fn build_parquet_sink(
-    cfg: &Config,
-    run_id: &str,
-    tool_version: &str,
-    config_hash: &str,
-    evidence_path: &Path,
-    evidence_sha256: &str,
-    run_output_dir: &Path,
-) -> Result<Box<dyn MetadataSink>, MetadataError> {
-    Ok(Box::new(ParquetSink::new(
-        cfg,
-        run_id,
-        tool_version,
-        config_hash,
-        evidence_path,
-        evidence_sha256,
-        run_output_dir,
-    )?))
+    let arrays: Vec<ArrayRef> = vec![
+        Arc::new(run_id.finish()),
+        Arc::new(tool_version.finish()),
+        Arc::new(config_hash.finish()),
+        Arc::new(evidence_path.finish()),
+        Arc::new(evidence_sha256.finish()),
+        Arc::new(pass_id.finish()),
+        Arc::new(method.finish()),
+        Arc::new(codec.finish()),
+        Arc::new(width.finish()),
+        Arc::new(height.finish()),
+        Arc::new(frame_count.finish()),
+        Arc::new(keyframe_count.finish()),
+        Arc::new(duration_secs.finish()),
+        Arc::new(media_bytes.finish()),
+        Arc::new(skipped_bytes.finish()),
+        Arc::new(donor_file.finish()),
+        Arc::new(repaired_path.finish()),
+        Arc::new(source_file.finish()),
+    ];
+
+    RecordBatch::try_new(Arc::clone(schema), arrays)
+        .map_err(|err| MetadataError::Other(format!("parquet batch error: {err}")))
 }
 
-fn handler_id_for_file_type(file_type: &str) -> &str {
-    match file_type {
-        "docx" | "xlsx" | "pptx" | "zip" => "zip",
-        other => other,
+fn build_thumbnails_batch(
+    ctx: &ParquetContext,
+    rows: &[ThumbnailRow],
+    schema: &SchemaRef,
+) -> Result<RecordBatch, MetadataError> {
+    let mut run_id = StringBuilder::new();
+    let mut tool_version = StringBuilder::new();
+    let mut config_hash = StringBuilder::new();
+    let mut evidence_path = StringBuilder::new();
+    let mut evidence_sha256 = StringBuilder::new();
+    let mut pass_id = Int64Builder::new();
+    let mut cache_id = StringBuilder::new();
+    let mut cache_version = StringBuilder::new();
+    let mut cache_type = Int64Builder::new();
+    let mut entry_offset = Int64Builder::new();
+    let mut width = Int64Builder::new();
+    let mut height = Int64Builder::new();
+    let mut image_format = StringBuilder::new();
+    let mut data_size = Int64Builder::new();
+    let mut image_path = StringBuilder::new();
+    let mut source_file = StringBuilder::new();
+
+    for row in rows {
+        run_id.append_value(&ctx.run_id);
+        tool_version.append_value(&ctx.tool_version);
+        config_hash.append_value(&ctx.config_hash);
+        evidence_path.append_value(&ctx.evidence_path);
+        evidence_sha256.append_value(&ctx.evidence_sha256);
+        pass_id.append_value(i64::from(ctx.pass_id));
+        cache_id.append_value(&row.cache_id);
+        cache_version.append_value(&row.cache_version);
+        cache_type.append_value(i64::from(row.cache_type));
+        entry_offset.append_value(to_i64(row.entry_offset)?);
+        width.append_option(row.width.map(i64::from));
+        height.append_option(row.height.map(i64::from));
+        image_format.append_value(&row.image_format);
+        data_size.append_value(to_i64(row.data_size)?);
+        image_path.append_value(&row.image_path);
+        source_file.append_value(&row.source_file);
     }
+
+    let arrays: Vec<ArrayRef> = vec![
+        Arc::new(run_id.finish()),
+        Arc::new(tool_version.finish()),
+        Arc::new(config_hash.finish()),
+        Arc::new(evidence_path.finish()),
+        Arc::new(evidence_sha256.finish()),
+        Arc::new(pass_id.finish()),
+        Arc::new(cache_id.finish()),
+        Arc::new(cache_version.finish()),
+        Arc::new(cache_type.finish()),
+        Arc::new(entry_offset.finish()),
+        Arc::new(width.finish()),
+        Arc::new(height.finish()),
+        Arc::new(image_format.finish()),
+        Arc::new(data_size.finish()),
+        Arc::new(image_path.finish()),
+        Arc::new(source_file.finish()),
+    ];
+
+    RecordBatch::try_new(Arc::clone(schema), arrays)
+        .map_err(|err| MetadataError::Other(format!("parquet batch error: {err}")))
 }
 
-fn category_for_file_type(file_type: &str) -> ParquetCategory {
-    match file_type {
-        "jpeg" | "jpg" => ParquetCategory::FilesJpeg,
-        "png" => ParquetCategory::FilesPng,
-        "gif" => ParquetCategory::FilesGif,
-        "sqlite" | "sqlite_db" => ParquetCategory::FilesSqlite,
-        "pdf" => ParquetCategory::FilesPdf,
-        "zip" | "docx" | "xlsx" | "pptx" => ParquetCategory::FilesZip,
-        "webp" => ParquetCategory::FilesWebp,
-        _ => ParquetCategory::FilesOther,
+fn build_image_info_batch(
+    ctx: &ParquetContext,
+    rows: &[ImageInfoRow],
+    schema: &SchemaRef,
+) -> Result<RecordBatch, MetadataError> {
+    let mut run_id = StringBuilder::new();
+    let mut tool_version = StringBuilder::new();
+    let mut config_hash = StringBuilder::new();
+    let mut evidence_path = StringBuilder::new();
+    let mut evidence_sha256 = StringBuilder::new();
+    let mut pass_id = Int64Builder::new();
+    let mut format = StringBuilder::new();
+    let mut width = Int64Builder::new();
+    let mut height = Int64Builder::new();
+    let mut bits_per_pixel = Int64Builder::new();
+    let mut decoded_pct = arrow_array::builder::Float64Builder::new();
+    let mut corrupt_offset = Int64Builder::new();
+    let mut source_file = StringBuilder::new();
+
+    for row in rows {
+        run_id.append_value(&ctx.run_id);
+        tool_version.append_value(&ctx.tool_version);
+        config_hash.append_value(&ctx.config_hash);
+        evidence_path.append_value(&ctx.evidence_path);
+        evidence_sha256.append_value(&ctx.evidence_sha256);
+        pass_id.append_value(i64::from(ctx.pass_id));
+        format.append_value(&row.format);
+        width.append_value(row.width);
+        height.append_value(row.height);
+        bits_per_pixel.append_option(row.bits_per_pixel);
+        decoded_pct.append_option(row.decoded_pct);
+        corrupt_offset.append_option(row.corrupt_offset);
+        source_file.append_value(&row.source_file);
     }
+
+    let arrays: Vec<ArrayRef> = vec![
+        Arc::new(run_id.finish()),
+        Arc::new(tool_version.finish()),
+        Arc::new(config_hash.finish()),
+        Arc::new(evidence_path.finish()),
+        Arc::new(evidence_sha256.finish()),
+        Arc::new(pass_id.finish()),
+        Arc::new(format.finish()),
+        Arc::new(width.finish()),
+        Arc::new(height.finish()),
+        Arc::new(bits_per_pixel.finish()),
+        Arc::new(decoded_pct.finish()),
+        Arc::new(corrupt_offset.finish()),
+        Arc::new(source_file.finish()),
+    ];
+
+    RecordBatch::try_new(Arc::clone(schema), arrays)
+        .map_err(|err| MetadataError::Other(format!("parquet batch error: {err}")))
 }
 
-fn schema_for_category(category: ParquetCategory) -> SchemaRef {
-    if category.is_files() {
-        return Arc::new(Schema::new(vec![
-            Field::new("run_id", DataType::Utf8, false),
-            Field::new("tool_version", DataType::Utf8, false),
-            Field::new("config_hash", DataType::Utf8, false),
-            Field::new("evidence_path", DataType::Utf8, false),
-            Field::new("evidence_sha256", DataType::Utf8, false),
-            Field::new("handler_id", DataType::Utf8, false),
-            Field::new("file_type", DataType::Utf8, false),
-            Field::new("carved_path", DataType::Utf8, false),
-            Field::new("global_start", DataType::Int64, false),
-            Field::new("global_end", DataType::Int64, false),
-            Field::new("size", DataType::Int64, false),
-            Field::new("md5", DataType::Utf8, true),
-            Field::new("sha256", DataType::Utf8, true),
-            Field::new("pattern_id", DataType::Utf8, true),
-            Field::new("magic_bytes", DataType::Binary, true),
-            Field::new("validated", DataType::Boolean, false),
-            Field::new("truncated", DataType::Boolean, false),
-            Field::new("error", DataType::Utf8, true),
-        ]));
-    }
+fn build_photo_devices_batch(
+    ctx: &ParquetContext,
+    rows: &[PhotoDeviceRow],
+    schema: &SchemaRef,
+) -> Result<RecordBatch, MetadataError> {
+    let mut run_id = StringBuilder::new();
+    let mut tool_version = StringBuilder::new();
+    let mut config_hash = StringBuilder::new();
+    let mut evidence_path = StringBuilder::new();
+    let mut evidence_sha256 = StringBuilder::new();
+    let mut pass_id = Int64Builder::new();
+    let mut source_file = StringBuilder::new();
+    let mut global_start = Int64Builder::new();
+    let mut make = StringBuilder::new();
+    let mut model = StringBuilder::new();
+    let mut serial = StringBuilder::new();
+    let mut lens = StringBuilder::new();
+    let mut lens_serial = StringBuilder::new();
+    let mut taken_at = TimestampMicrosecondBuilder::new();
+    let mut latitude = arrow_array::builder::Float64Builder::new();
+    let mut longitude = arrow_array::builder::Float64Builder::new();
+    let mut device_id = StringBuilder::new();
+    let mut session_id = StringBuilder::new();
 
-    match category {
-        ParquetCategory::ArtefactsUrls => Arc::new(Schema::new(vec![
-            Field::new("run_id", DataType::Utf8, false),
-            Field::new("tool_version", DataType::Utf8, false),
-            Field::new("config_hash", DataType::Utf8, false),
-            Field::new("evidence_path", DataType::Utf8, false),
-            Field::new("evidence_sha256", DataType::Utf8, false),
-            Field::new("global_start", DataType::Int64, false),
-            Field::new("global_end", DataType::Int64, false),
-            Field::new("url", DataType::Utf8, false),
-            Field::new("scheme", DataType::Utf8, false),
-            Field::new("host", DataType::Utf8, false),
-            Field::new("port", DataType::Int32, true),
-            Field::new("path", DataType::Utf8, true),
-            Field::new("query", DataType::Utf8, true),
-            Field::new("fragment", DataType::Utf8, true),
-            Field::new("source_kind", DataType::Utf8, false),
-            Field::new("source_detail", DataType::Utf8, false),
-            Field::new("certainty", DataType::Float64, false),
-        ])),
-        ParquetCategory::ArtefactsEmails => Arc::new(Schema::new(vec![
-            Field::new("run_id", DataType::Utf8, false),
-            Field::new("tool_version", DataType::Utf8, false),
-            Field::new("config_hash", DataType::Utf8, false),
-            Field::new("evidence_path", DataType::Utf8, false),
-            Field::new("evidence_sha256", DataType::Utf8, false),
-            Field::new("global_start", DataType::Int64, false),
-            Field::new("global_end", DataType::Int64, false),
-            Field::new("email", DataType::Utf8, false),
-            Field::new("local_part", DataType::Utf8, false),
-            Field::new("domain", DataType::Utf8, false),
-            Field::new("source_kind", DataType::Utf8, false),
-            Field::new("source_detail", DataType::Utf8, false),
-            Field::new("certainty", DataType::Float64, false),
-        ])),
-        ParquetCategory::ArtefactsPhones => Arc::new(Schema::new(vec![
-            Field::new("run_id", DataType::Utf8, false),
-            Field::new("tool_version", DataType::Utf8, false),
-            Field::new("config_hash", DataType::Utf8, false),
-            Field::new("evidence_path", DataType::Utf8, false),
-            Field::new("evidence_sha256", DataType::Utf8, false),
-            Field::new("global_start", DataType::Int64, false),
-            Field::new("global_end", DataType::Int64, false),
-            Field::new("phone_raw", DataType::Utf8, false),
-            Field::new("phone_e164", DataType::Utf8, true),
-            Field::new("country", DataType::Utf8, true),
-            Field::new("source_kind", DataType::Utf8, false),
-            Field::new("source_detail", DataType::Utf8, false),
-            Field::new("certainty", DataType::Float64, false),
-        ])),
-        ParquetCategory::BrowserHistory => Arc::new(Schema::new(vec![
-            Field::new("run_id", DataType::Utf8, false),
-            Field::new("tool_version", DataType::Utf8, false),
-            Field::new("config_hash", DataType::Utf8, false),
-            Field::new("evidence_path", DataType::Utf8, false),
-            Field::new("evidence_sha256", DataType::Utf8, false),
-            Field::new("source_file", DataType::Utf8, false),
-            Field::new("browser", DataType::Utf8, false),
-            Field::new("profile", DataType::Utf8, false),
-            Field::new("url", DataType::Utf8, false),
-            Field::new("title", DataType::Utf8, true),
-            Field::new(
-                "visit_time_utc",
-                DataType::Timestamp(TimeUnit::Microsecond, None),
-                true,
-            ),
-            Field::new("visit_source", DataType::Utf8, true),
-            Field::new("row_id", DataType::Int64, true),
-            Field::new("table_name", DataType::Utf8, true),
-        ])),
-        ParquetCategory::BrowserCookies => Arc::new(Schema::new(vec![
-            Field::new("run_id", DataType::Utf8, false),
-            Field::new("tool_version", DataType::Utf8, false),
-            Field::new("config_hash", DataType::Utf8, false),
-            Field::new("evidence_path", DataType::Utf8, false),
-            Field::new("evidence_sha256", DataType::Utf8, false),
-            Field::new("source_file", DataType::Utf8, false),
-            Field::new("browser", DataType::Utf8, false),
-            Field::new("profile", DataType::Utf8, false),
-            Field::new("host", DataType::Utf8, false),
-            Field::new("name", DataType::Utf8, false),
-            Field::new("value", DataType::Utf8, true),
-            Field::new("path", DataType::Utf8, true),
-            Field::new(
-                "expires_utc",
-                DataType::Timestamp(TimeUnit::Microsecond, None),
-                true,
-            ),
-            Field::new(
-                "last_access_utc",
-                DataType::Timestamp(TimeUnit::Microsecond, None),
-                true,
-            ),
-            Field::new(
-                "creation_utc",
-                DataType::Timestamp(TimeUnit::Microsecond, None),
-                true,
-            ),
-            Field::new("is_secure", DataType::Boolean, true),
-            Field::new("is_http_only", DataType::Boolean, true),
-        ])),
-        ParquetCategory::BrowserDownloads => Arc::new(Schema::new(vec![
-            Field::new("run_id", DataType::Utf8, false),
-            Field::new("tool_version", DataType::Utf8, false),
-            Field::new("config_hash", DataType::Utf8, false),
-            Field::new("evidence_path", DataType::Utf8, false),
-            Field::new("evidence_sha256", DataType::Utf8, false),
-            Field::new("source_file", DataType::Utf8, false),
-            Field::new("browser", DataType::Utf8, false),
-            Field::new("profile", DataType::Utf8, false),
-            Field::new("url", DataType::Utf8, true),
-            Field::new("target_path", DataType::Utf8, true),
-            Field::new(
-                "start_time_utc",
-                DataType::Timestamp(TimeUnit::Microsecond, None),
-                true,
-            ),
-            Field::new(
-                "end_time_utc",
-                DataType::Timestamp(TimeUnit::Microsecond, None),
-                true,
-            ),
-            Field::new("total_bytes", DataType::Int64, true),
-            Field::new("state", DataType::Utf8, true),
-        ])),
-        ParquetCategory::EntropyRegions => Arc::new(Schema::new(vec![
-            Field::new("run_id", DataType::Utf8, false),
-            Field::new("tool_version", DataType::Utf8, false),
-            Field::new("config_hash", DataType::Utf8, false),
-            Field::new("evidence_path", DataType::Utf8, false),
-            Field::new("evidence_sha256", DataType::Utf8, false),
-            Field::new("global_start", DataType::Int64, false),
-            Field::new("global_end", DataType::Int64, false),
-            Field::new("entropy", DataType::Float64, false),
-            Field::new("window_size", DataType::Int64, false),
-        ])),
-        ParquetCategory::RunSummary => Arc::new(Schema::new(vec![
-            Field::new("run_id", DataType::Utf8, false),
-            Field::new("tool_version", DataType::Utf8, false),
-            Field::new("config_hash", DataType::Utf8, false),
-            Field::new("evidence_path", DataType::Utf8, false),
-            Field::new("evidence_sha256", DataType::Utf8, false),
-            Field::new("bytes_scanned", DataType::Int64, false),
-            Field::new("chunks_processed", DataType::Int64, false),
-            Field::new("hits_found", DataType::Int64, false),
-            Field::new("files_carved", DataType::Int64, false),
-            Field::new("string_spans", DataType::Int64, false),
-            Field::new("artefacts_extracted", DataType::Int64, false),
-        ])),
-        _ => Arc::new(Schema::empty()),
+    for row in rows {
+        run_id.append_value(&ctx.run_id);
+        tool_version.append_value(&ctx.tool_version);
+        config_hash.append_value(&ctx.config_hash);
+        evidence_path.append_value(&ctx.evidence_path);
+        evidence_sha256.append_value(&ctx.evidence_sha256);
+        pass_id.append_value(i64::from(ctx.pass_id));
+        source_file.append_value(&row.source_file);
+        global_start.append_value(row.global_start);
+        make.append_option(row.make.as_deref());
+        model.append_option(row.model.as_deref());
+        serial.append_option(row.serial.as_deref());
+        lens.append_option(row.lens.as_deref());
+        lens_serial.append_option(row.lens_serial.as_deref());
+        taken_at.append_option(row.taken_at);
+        latitude.append_option(row.latitude);
+        longitude.append_option(row.longitude);
+        device_id.append_option(row.device_id.as_deref());
+        session_id.append_option(row.session_id.as_deref());
     }
+
+    let arrays: Vec<ArrayRef> = vec![
+        Arc::new(run_id.finish()),
+        Arc::new(tool_version.finish()),
+        Arc::new(config_hash.finish()),
+        Arc::new(evidence_path.finish()),
+        Arc::new(evidence_sha256.finish()),
+        Arc::new(pass_id.finish()),
+        Arc::new(source_file.finish()),
+        Arc::new(global_start.finish()),
+        Arc::new(make.finish()),
+        Arc::new(model.finish()),
+        Arc::new(serial.finish()),
+        Arc::new(lens.finish()),
+        Arc::new(lens_serial.finish()),
+        Arc::new(taken_at.finish()),
+        Arc::new(latitude.finish()),
+        Arc::new(longitude.finish()),
+        Arc::new(device_id.finish()),
+        Arc::new(session_id.finish()),
+    ];
+
+    RecordBatch::try_new(Arc::clone(schema), arrays)
+        .map_err(|err| MetadataError::Other(format!("parquet batch error: {err}")))
 }
 
-fn build_files_batch(
+fn build_ntfs_files_batch(
     ctx: &ParquetContext,
-    rows: &[FileRow],
+    rows: &[NtfsFileRow],
     schema: &SchemaRef,
 ) -> Result<RecordBatch, MetadataError> {
     let mut run_id = StringBuilder::new();
@@ -1075,19 +4543,22 @@ fn build_files_batch(
     let mut config_hash = StringBuilder::new();
     let mut evidence_path = StringBuilder::new();
     let mut evidence_sha256 = StringBuilder::new();
-    let mut handler_id = StringBuilder::new();
-    let mut file_type = StringBuilder::new();
-    let mut carved_path = StringBuilder::new();
-    let mut global_start = Int64Builder::new();
-    let mut global_end = Int64Builder::new();
+    let mut pass_id = Int64Builder::new();
+    let mut volume_offset = Int64Builder::new();
+    let mut mft_record = Int64Builder::new();
+    let mut sequence = Int64Builder::new();
+    let mut path = StringBuilder::new();
+    let mut name = StringBuilder::new();
+    let mut in_use = BooleanBuilder::new();
+    let mut directory = BooleanBuilder::new();
     let mut size = Int64Builder::new();
-    let mut md5 = StringBuilder::new();
-    let mut sha256 = StringBuilder::new();
-    let mut pattern_id = StringBuilder::new();
-    let mut magic_bytes = BinaryBuilder::new();
-    let mut validated = BooleanBuilder::new();
-    let mut truncated = BooleanBuilder::new();
-    let mut error = StringBuilder::new();
+    let mut resident = BooleanBuilder::new();
+    let mut data_runs = StringBuilder::new();
+    let mut created = TimestampMicrosecondBuilder::new();
+    let mut modified = TimestampMicrosecondBuilder::new();
+    let mut mft_modified = TimestampMicrosecondBuilder::new();
+    let mut accessed = TimestampMicrosecondBuilder::new();
+    let mut carved_path = StringBuilder::new();
 
     for row in rows {
         run_id.append_value(&ctx.run_id);
@@ -1095,19 +4566,22 @@ fn build_files_batch(
         config_hash.append_value(&ctx.config_hash);
         evidence_path.append_value(&ctx.evidence_path);
         evidence_sha256.append_value(&ctx.evidence_sha256);
-        handler_id.append_value(&row.handler_id);
-        file_type.append_value(&row.file_type);
-        carved_path.append_value(&row.carved_path);
-        global_start.append_value(row.global_start);
-        global_end.append_value(row.global_end);
+        pass_id.append_value(i64::from(ctx.pass_id));
+        volume_offset.append_value(row.volume_offset);
+        mft_record.append_value(row.mft_record);
+        sequence.append_value(row.sequence);
+        path.append_value(&row.path);
+        name.append_value(&row.name);
+        in_use.append_value(row.in_use);
+        directory.append_value(row.directory);
         size.append_value(row.size);
-        md5.append_option(row.md5.as_deref());
-        sha256.append_option(row.sha256.as_deref());
-        pattern_id.append_option(row.pattern_id.as_deref());
-        magic_bytes.append_option(row.magic_bytes.as_deref());
-        validated.append_value(row.validated);
-        truncated.append_value(row.truncated);
-        error.append_option(row.error.as_deref());
+        resident.append_value(row.resident);
+        data_runs.append_value(&row.data_runs);
+        created.append_option(row.created);
+        modified.append_option(row.modified);
+        mft_modified.append_option(row.mft_modified);
+        accessed.append_option(row.accessed);
+        carved_path.append_option(row.carved_path.as_deref());
     }
 
     let arrays: Vec<ArrayRef> = vec![
@@ -1116,28 +4590,31 @@ fn build_files_batch(
         Arc::new(config_hash.finish()),
         Arc::new(evidence_path.finish()),
         Arc::new(evidence_sha256.finish()),
-        Arc::new(handler_id.finish()),
-        Arc::new(file_type.finish()),
-        Arc::new(carved_path.finish()),
-        Arc::new(global_start.finish()),
-        Arc::new(global_end.finish()),
+        Arc::new(pass_id.finish()),
+        Arc::new(volume_offset.finish()),
+        Arc::new(mft_record.finish()),
+        Arc::new(sequence.finish()),
+        Arc::new(path.finish()),
+        Arc::new(name.finish()),
+        Arc::new(in_use.finish()),
+        Arc::new(directory.finish()),
         Arc::new(size.finish()),
-        Arc::new(md5.finish()),
-        Arc::new(sha256.finish()),
-        Arc::new(pattern_id.finish()),
-        Arc::new(magic_bytes.finish()),
-        Arc::new(validated.finish()),
-        Arc::new(truncated.finish()),
-        Arc::new(error.finish()),
+        Arc::new(resident.finish()),
+        Arc::new(data_runs.finish()),
+        Arc::new(created.finish()),
+        Arc::new(modified.finish()),
+        Arc::new(mft_modified.finish()),
+        Arc::new(accessed.finish()),
+        Arc::new(carved_path.finish()),
     ];
 
     RecordBatch::try_new(Arc::clone(schema), arrays)
         .map_err(|err| MetadataError::Other(format!("parquet batch error: {err}")))
 }
 
-fn build_urls_batch(
+fn build_input_files_batch(
     ctx: &ParquetContext,
-    rows: &[UrlArtefactRow],
+    rows: &[InputFileRow],
     schema: &SchemaRef,
 ) -> Result<RecordBatch, MetadataError> {
     let mut run_id = StringBuilder::new();
@@ -1145,18 +4622,10 @@ fn build_urls_batch(
     let mut config_hash = StringBuilder::new();
     let mut evidence_path = StringBuilder::new();
     let mut evidence_sha256 = StringBuilder::new();
-    let mut global_start = Int64Builder::new();
-    let mut global_end = Int64Builder::new();
-    let mut url = StringBuilder::new();
-    let mut scheme = StringBuilder::new();
-    let mut host = StringBuilder::new();
-    let mut port = Int32Builder::new();
+    let mut pass_id = Int64Builder::new();
+    let mut kind = StringBuilder::new();
     let mut path = StringBuilder::new();
-    let mut query = StringBuilder::new();
-    let mut fragment = StringBuilder::new();
-    let mut source_kind = StringBuilder::new();
-    let mut source_detail = StringBuilder::new();
-    let mut certainty = arrow_array::builder::Float64Builder::new();
+    let mut sha256 = StringBuilder::new();
 
     for row in rows {
         run_id.append_value(&ctx.run_id);
@@ -1164,18 +4633,10 @@ fn build_urls_batch(
         config_hash.append_value(&ctx.config_hash);
         evidence_path.append_value(&ctx.evidence_path);
         evidence_sha256.append_value(&ctx.evidence_sha256);
-        global_start.append_value(row.global_start);
-        global_end.append_value(row.global_end);
-        url.append_value(&row.url);
-        scheme.append_value(&row.scheme);
-        host.append_value(&row.host);
-        port.append_option(row.port);
-        path.append_option(row.path.as_deref());
-        query.append_option(row.query.as_deref());
-        fragment.append_option(row.fragment.as_deref());
-        source_kind.append_value(&row.source_kind);
-        source_detail.append_value(&row.source_detail);
-        certainty.append_value(row.certainty);
+        pass_id.append_value(i64::from(ctx.pass_id));
+        kind.append_value(&row.kind);
+        path.append_value(&row.path);
+        sha256.append_value(&row.sha256);
     }
 
     let arrays: Vec<ArrayRef> = vec![
@@ -1184,27 +4645,19 @@ fn build_urls_batch(
         Arc::new(config_hash.finish()),
         Arc::new(evidence_path.finish()),
         Arc::new(evidence_sha256.finish()),
-        Arc::new(global_start.finish()),
-        Arc::new(global_end.finish()),
-        Arc::new(url.finish()),
-        Arc::new(scheme.finish()),
-        Arc::new(host.finish()),
-        Arc::new(port.finish()),
+        Arc::new(pass_id.finish()),
+        Arc::new(kind.finish()),
         Arc::new(path.finish()),
-        Arc::new(query.finish()),
-        Arc::new(fragment.finish()),
-        Arc::new(source_kind.finish()),
-        Arc::new(source_detail.finish()),
-        Arc::new(certainty.finish()),
+        Arc::new(sha256.finish()),
     ];
 
     RecordBatch::try_new(Arc::clone(schema), arrays)
         .map_err(|err| MetadataError::Other(format!("parquet batch error: {err}")))
 }
 
-fn build_emails_batch(
+fn build_encrypted_documents_batch(
     ctx: &ParquetContext,
-    rows: &[EmailArtefactRow],
+    rows: &[EncryptedDocumentRow],
     schema: &SchemaRef,
 ) -> Result<RecordBatch, MetadataError> {
     let mut run_id = StringBuilder::new();
@@ -1212,14 +4665,16 @@ fn build_emails_batch(
     let mut config_hash = StringBuilder::new();
     let mut evidence_path = StringBuilder::new();
     let mut evidence_sha256 = StringBuilder::new();
-    let mut global_start = Int64Builder::new();
-    let mut global_end = Int64Builder::new();
-    let mut email = StringBuilder::new();
-    let mut local_part = StringBuilder::new();
-    let mut domain = StringBuilder::new();
-    let mut source_kind = StringBuilder::new();
-    let mut source_detail = StringBuilder::new();
-    let mut certainty = arrow_array::builder::Float64Builder::new();
+    let mut pass_id = Int64Builder::new();
+    let mut encryption_type = StringBuilder::new();
+    let mut version = StringBuilder::new();
+    let mut cipher_algorithm = StringBuilder::new();
+    let mut key_bits = Int64Builder::new();
+    let mut hash_algorithm = StringBuilder::new();
+    let mut spin_count = Int64Builder::new();
+    let mut csp_name = StringBuilder::new();
+    let mut original_size = Int64Builder::new();
+    let mut source_file = StringBuilder::new();
 
     for row in rows {
         run_id.append_value(&ctx.run_id);
@@ -1227,14 +4682,16 @@ fn build_emails_batch(
         config_hash.append_value(&ctx.config_hash);
         evidence_path.append_value(&ctx.evidence_path);
         evidence_sha256.append_value(&ctx.evidence_sha256);
-        global_start.append_value(row.global_start);
-        global_end.append_value(row.global_end);
-        email.append_value(&row.email);
-        local_part.append_value(&row.local_part);
-        domain.append_value(&row.domain);
-        source_kind.append_value(&row.source_kind);
-        source_detail.append_value(&row.source_detail);
-        certainty.append_value(row.certainty);
+        pass_id.append_value(i64::from(ctx.pass_id));
+        encryption_type.append_value(&row.encryption_type);
+        version.append_option(row.version.as_deref());
+        cipher_algorithm.append_option(row.cipher_algorithm.as_deref());
+        key_bits.append_option(row.key_bits);
+        hash_algorithm.append_option(row.hash_algorithm.as_deref());
+        spin_count.append_option(row.spin_count);
+        csp_name.append_option(row.csp_name.as_deref());
+        original_size.append_option(row.original_size);
+        source_file.append_value(&row.source_file);
     }
 
     let arrays: Vec<ArrayRef> = vec![
@@ -1243,23 +4700,25 @@ fn build_emails_batch(
         Arc::new(config_hash.finish()),
         Arc::new(evidence_path.finish()),
         Arc::new(evidence_sha256.finish()),
-        Arc::new(global_start.finish()),
-        Arc::new(global_end.finish()),
-        Arc::new(email.finish()),
-        Arc::new(local_part.finish()),
-        Arc::new(domain.finish()),
-        Arc::new(source_kind.finish()),
-        Arc::new(source_detail.finish()),
-        Arc::new(certainty.finish()),
+        Arc::new(pass_id.finish()),
+        Arc::new(encryption_type.finish()),
+        Arc::new(version.finish()),
+        Arc::new(cipher_algorithm.finish()),
+        Arc::new(key_bits.finish()),
+        Arc::new(hash_algorithm.finish()),
+        Arc::new(spin_count.finish()),
+        Arc::new(csp_name.finish()),
+        Arc::new(original_size.finish()),
+        Arc::new(source_file.finish()),
     ];
 
     RecordBatch::try_new(Arc::clone(schema), arrays)
         .map_err(|err| MetadataError::Other(format!("parquet batch error: {err}")))
 }
 
-fn build_phones_batch(
+fn build_carve_sizes_batch(
     ctx: &ParquetContext,
-    rows: &[PhoneArtefactRow],
+    rows: &[CarveSizeRow],
     schema: &SchemaRef,
 ) -> Result<RecordBatch, MetadataError> {
     let mut run_id = StringBuilder::new();
@@ -1267,14 +4726,14 @@ fn build_phones_batch(
     let mut config_hash = StringBuilder::new();
     let mut evidence_path = StringBuilder::new();
     let mut evidence_sha256 = StringBuilder::new();
+    let mut pass_id = Int64Builder::new();
+    let mut file_type = StringBuilder::new();
+    let mut path = StringBuilder::new();
     let mut global_start = Int64Builder::new();
-    let mut global_end = Int64Builder::new();
-    let mut phone_raw = StringBuilder::new();
-    let mut phone_e164 = StringBuilder::new();
-    let mut country = StringBuilder::new();
-    let mut source_kind = StringBuilder::new();
-    let mut source_detail = StringBuilder::new();
-    let mut certainty = arrow_array::builder::Float64Builder::new();
+    let mut size = Int64Builder::new();
+    let mut declared_size = Int64Builder::new();
+    let mut declared_by = StringBuilder::new();
+    let mut truncation = StringBuilder::new();
 
     for row in rows {
         run_id.append_value(&ctx.run_id);
@@ -1282,14 +4741,14 @@ fn build_phones_batch(
         config_hash.append_value(&ctx.config_hash);
         evidence_path.append_value(&ctx.evidence_path);
         evidence_sha256.append_value(&ctx.evidence_sha256);
+        pass_id.append_value(i64::from(ctx.pass_id));
+        file_type.append_value(&row.file_type);
+        path.append_value(&row.path);
         global_start.append_value(row.global_start);
-        global_end.append_value(row.global_end);
-        phone_raw.append_value(&row.phone_raw);
-        phone_e164.append_option(row.phone_e164.as_deref());
-        country.append_option(row.country.as_deref());
-        source_kind.append_value(&row.source_kind);
-        source_detail.append_value(&row.source_detail);
-        certainty.append_value(row.certainty);
+        size.append_value(row.size);
+        declared_size.append_option(row.declared_size);
+        declared_by.append_option(row.declared_by.as_deref());
+        truncation.append_option(row.truncation);
     }
 
     let arrays: Vec<ArrayRef> = vec![
@@ -1298,23 +4757,23 @@ fn build_phones_batch(
         Arc::new(config_hash.finish()),
         Arc::new(evidence_path.finish()),
         Arc::new(evidence_sha256.finish()),
+        Arc::new(pass_id.finish()),
+        Arc::new(file_type.finish()),
+        Arc::new(path.finish()),
         Arc::new(global_start.finish()),
-        Arc::new(global_end.finish()),
-        Arc::new(phone_raw.finish()),
-        Arc::new(phone_e164.finish()),
-        Arc::new(country.finish()),
-        Arc::new(source_kind.finish()),
-        Arc::new(source_detail.finish()),
-        Arc::new(certainty.finish()),
+        Arc::new(size.finish()),
+        Arc::new(declared_size.finish()),
+        Arc::new(declared_by.finish()),
+        Arc::new(truncation.finish()),
     ];
 
     RecordBatch::try_new(Arc::clone(schema), arrays)
         .map_err(|err| MetadataError::Other(format!("parquet batch error: {err}")))
 }
 
-fn build_history_batch(
+fn build_carve_companions_batch(
     ctx: &ParquetContext,
-    rows: &[BrowserHistoryRow],
+    rows: &[CarveCompanionRow],
     schema: &SchemaRef,
 ) -> Result<RecordBatch, MetadataError> {
     let mut run_id = StringBuilder::new();
@@ -1322,15 +4781,14 @@ fn build_history_batch(
     let mut config_hash = StringBuilder::new();
     let mut evidence_path = StringBuilder::new();
     let mut evidence_sha256 = StringBuilder::new();
-    let mut source_file = StringBuilder::new();
-    let mut browser = StringBuilder::new();
-    let mut profile = StringBuilder::new();
-    let mut url = StringBuilder::new();
-    let mut title = StringBuilder::new();
-    let mut visit_time = TimestampMicrosecondBuilder::new();
-    let mut visit_source = StringBuilder::new();
-    let mut row_id = Int64Builder::new();
-    let mut table_name = StringBuilder::new();
+    let mut pass_id = Int64Builder::new();
+    let mut file_type = StringBuilder::new();
+    let mut path = StringBuilder::new();
+    let mut global_start = Int64Builder::new();
+    let mut companion_type = StringBuilder::new();
+    let mut companion_path = StringBuilder::new();
+    let mut companion_start = Int64Builder::new();
+    let mut companion_size = Int64Builder::new();
 
     for row in rows {
         run_id.append_value(&ctx.run_id);
@@ -1338,15 +4796,14 @@ fn build_history_batch(
         config_hash.append_value(&ctx.config_hash);
         evidence_path.append_value(&ctx.evidence_path);
         evidence_sha256.append_value(&ctx.evidence_sha256);
-        source_file.append_value(&row.source_file);
-        browser.append_value(&row.browser);
-        profile.append_value(&row.profile);
-        url.append_value(&row.url);
-        title.append_option(row.title.as_deref());
-        visit_time.append_option(row.visit_time_utc);
-        visit_source.append_option(row.visit_source.as_deref());
-        row_id.append_option(row.row_id);
-        table_name.append_option(row.table_name.as_deref());
+        pass_id.append_value(i64::from(ctx.pass_id));
+        file_type.append_value(&row.file_type);
+        path.append_value(&row.path);
+        global_start.append_value(row.global_start);
+        companion_type.append_value(&row.companion_type);
+        companion_path.append_value(&row.companion_path);
+        companion_start.append_value(row.companion_start);
+        companion_size.append_value(row.companion_size);
     }
 
     let arrays: Vec<ArrayRef> = vec![
@@ -1355,24 +4812,23 @@ fn build_history_batch(
         Arc::new(config_hash.finish()),
         Arc::new(evidence_path.finish()),
         Arc::new(evidence_sha256.finish()),
-        Arc::new(source_file.finish()),
-        Arc::new(browser.finish()),
-        Arc::new(profile.finish()),
-        Arc::new(url.finish()),
-        Arc::new(title.finish()),
-        Arc::new(visit_time.finish()),
-        Arc::new(visit_source.finish()),
-        Arc::new(row_id.finish()),
-        Arc::new(table_name.finish()),
+        Arc::new(pass_id.finish()),
+        Arc::new(file_type.finish()),
+        Arc::new(path.finish()),
+        Arc::new(global_start.finish()),
+        Arc::new(companion_type.finish()),
+        Arc::new(companion_path.finish()),
+        Arc::new(companion_start.finish()),
+        Arc::new(companion_size.finish()),
     ];
 
     RecordBatch::try_new(Arc::clone(schema), arrays)
         .map_err(|err| MetadataError::Other(format!("parquet batch error: {err}")))
 }
 
-fn build_cookies_batch(
+fn build_volume_locations_batch(
     ctx: &ParquetContext,
-    rows: &[BrowserCookieRow],
+    rows: &[VolumeLocationRow],
     schema: &SchemaRef,
 ) -> Result<RecordBatch, MetadataError> {
     let mut run_id = StringBuilder::new();
@@ -1380,18 +4836,17 @@ fn build_cookies_batch(
     let mut config_hash = StringBuilder::new();
     let mut evidence_path = StringBuilder::new();
     let mut evidence_sha256 = StringBuilder::new();
-    let mut source_file = StringBuilder::new();
-    let mut browser = StringBuilder::new();
-    let mut profile = StringBuilder::new();
-    let mut host = StringBuilder::new();
-    let mut name = StringBuilder::new();
-    let mut value = StringBuilder::new();
-    let mut path = StringBuilder::new();
-    let mut expires = TimestampMicrosecondBuilder::new();
-    let mut last_access = TimestampMicrosecondBuilder::new();
-    let mut creation = TimestampMicrosecondBuilder::new();
-    let mut is_secure = BooleanBuilder::new();
-    let mut is_http_only = BooleanBuilder::new();
+    let mut pass_id = Int64Builder::new();
+    let mut subject = StringBuilder::new();
+    let mut reference = StringBuilder::new();
+    let mut global_start = Int64Builder::new();
+    let mut partition = Int64Builder::new();
+    let mut partition_offset = Int64Builder::new();
+    let mut relative_offset = Int64Builder::new();
+    let mut relative_sector = Int64Builder::new();
+    let mut file_system = StringBuilder::new();
+    let mut cluster = Int64Builder::new();
+    let mut cluster_chain = StringBuilder::new();
 
     for row in rows {
         run_id.append_value(&ctx.run_id);
@@ -1399,18 +4854,17 @@ fn build_cookies_batch(
         config_hash.append_value(&ctx.config_hash);
         evidence_path.append_value(&ctx.evidence_path);
         evidence_sha256.append_value(&ctx.evidence_sha256);
-        source_file.append_value(&row.source_file);
-        browser.append_value(&row.browser);
-        profile.append_value(&row.profile);
-        host.append_value(&row.host);
-        name.append_value(&row.name);
-        value.append_option(row.value.as_deref());
-        path.append_option(row.path.as_deref());
-        expires.append_option(row.expires_utc);
-        last_access.append_option(row.last_access_utc);
-        creation.append_option(row.creation_utc);
-        is_secure.append_option(row.is_secure);
-        is_http_only.append_option(row.is_http_only);
+        pass_id.append_value(i64::from(ctx.pass_id));
+        subject.append_value(&row.subject);
+        reference.append_value(&row.reference);
+        global_start.append_value(row.global_start);
+        partition.append_value(row.partition);
+        partition_offset.append_value(row.partition_offset);
+        relative_offset.append_value(row.relative_offset);
+        relative_sector.append_value(row.relative_sector);
+        file_system.append_option(row.file_system.as_deref());
+        cluster.append_option(row.cluster);
+        cluster_chain.append_option(row.cluster_chain.as_deref());
     }
 
     let arrays: Vec<ArrayRef> = vec![
@@ -1419,27 +4873,26 @@ fn build_cookies_batch(
         Arc::new(config_hash.finish()),
         Arc::new(evidence_path.finish()),
         Arc::new(evidence_sha256.finish()),
-        Arc::new(source_file.finish()),
-        Arc::new(browser.finish()),
-        Arc::new(profile.finish()),
-        Arc::new(host.finish()),
-        Arc::new(name.finish()),
-        Arc::new(value.finish()),
-        Arc::new(path.finish()),
-        Arc::new(expires.finish()),
-        Arc::new(last_access.finish()),
-        Arc::new(creation.finish()),
-        Arc::new(is_secure.finish()),
-        Arc::new(is_http_only.finish()),
+        Arc::new(pass_id.finish()),
+        Arc::new(subject.finish()),
+        Arc::new(reference.finish()),
+        Arc::new(global_start.finish()),
+        Arc::new(partition.finish()),
+        Arc::new(partition_offset.finish()),
+        Arc::new(relative_offset.finish()),
+        Arc::new(relative_sector.finish()),
+        Arc::new(file_system.finish()),
+        Arc::new(cluster.finish()),
+        Arc::new(cluster_chain.finish()),
     ];
 
     RecordBatch::try_new(Arc::clone(schema), arrays)
         .map_err(|err| MetadataError::Other(format!("parquet batch error: {err}")))
 }
 
-fn build_downloads_batch(
+fn build_classifications_batch(
     ctx: &ParquetContext,
-    rows: &[BrowserDownloadRow],
+    rows: &[ClassificationRow],
     schema: &SchemaRef,
 ) -> Result<RecordBatch, MetadataError> {
     let mut run_id = StringBuilder::new();
@@ -1447,15 +4900,14 @@ fn build_downloads_batch(
     let mut config_hash = StringBuilder::new();
     let mut evidence_path = StringBuilder::new();
     let mut evidence_sha256 = StringBuilder::new();
-    let mut source_file = StringBuilder::new();
-    let mut browser = StringBuilder::new();
-    let mut profile = StringBuilder::new();
-    let mut url = StringBuilder::new();
-    let mut target_path = StringBuilder::new();
-    let mut start_time = TimestampMicrosecondBuilder::new();
-    let mut end_time = TimestampMicrosecondBuilder::new();
-    let mut total_bytes = Int64Builder::new();
-    let mut state = StringBuilder::new();
+    let mut pass_id = Int64Builder::new();
+    let mut file_type = StringBuilder::new();
+    let mut path = StringBuilder::new();
+    let mut global_start = Int64Builder::new();
+    let mut classifier = StringBuilder::new();
+    let mut label = StringBuilder::new();
+    let mut score = arrow_array::builder::Float64Builder::new();
+    let mut rank = Int64Builder::new();
 
     for row in rows {
         run_id.append_value(&ctx.run_id);
@@ -1463,15 +4915,14 @@ fn build_downloads_batch(
         config_hash.append_value(&ctx.config_hash);
         evidence_path.append_value(&ctx.evidence_path);
         evidence_sha256.append_value(&ctx.evidence_sha256);
-        source_file.append_value(&row.source_file);
-        browser.append_value(&row.browser);
-        profile.append_value(&row.profile);
-        url.append_option(row.url.as_deref());
-        target_path.append_option(row.target_path.as_deref());
-        start_time.append_option(row.start_time_utc);
-        end_time.append_option(row.end_time_utc);
-        total_bytes.append_option(row.total_bytes);
-        state.append_option(row.state.as_deref());
+        pass_id.append_value(i64::from(ctx.pass_id));
+        file_type.append_value(&row.file_type);
+        path.append_value(&row.path);
+        global_start.append_value(row.global_start);
+        classifier.append_value(&row.classifier);
+        label.append_value(&row.label);
+        score.append_value(row.score);
+        rank.append_value(row.rank);
     }
 
     let arrays: Vec<ArrayRef> = vec![
@@ -1480,15 +4931,14 @@ fn build_downloads_batch(
         Arc::new(config_hash.finish()),
         Arc::new(evidence_path.finish()),
         Arc::new(evidence_sha256.finish()),
-        Arc::new(source_file.finish()),
-        Arc::new(browser.finish()),
-        Arc::new(profile.finish()),
-        Arc::new(url.finish()),
-        Arc::new(target_path.finish()),
-        Arc::new(start_time.finish()),
-        Arc::new(end_time.finish()),
-        Arc::new(total_bytes.finish()),
-        Arc::new(state.finish()),
+        Arc::new(pass_id.finish()),
+        Arc::new(file_type.finish()),
+        Arc::new(path.finish()),
+        Arc::new(global_start.finish()),
+        Arc::new(classifier.finish()),
+        Arc::new(label.finish()),
+        Arc::new(score.finish()),
+        Arc::new(rank.finish()),
     ];
 
     RecordBatch::try_new(Arc::clone(schema), arrays)
@@ -1505,6 +4955,7 @@ fn build_entropy_batch(
     let mut config_hash = StringBuilder::new();
     let mut evidence_path = StringBuilder::new();
     let mut evidence_sha256 = StringBuilder::new();
+    let mut pass_id = Int64Builder::new();
     let mut global_start = Int64Builder::new();
     let mut global_end = Int64Builder::new();
     let mut entropy = arrow_array::builder::Float64Builder::new();
@@ -1516,6 +4967,7 @@ fn build_entropy_batch(
         config_hash.append_value(&ctx.config_hash);
         evidence_path.append_value(&ctx.evidence_path);
         evidence_sha256.append_value(&ctx.evidence_sha256);
+        pass_id.append_value(i64::from(ctx.pass_id));
         global_start.append_value(row.global_start);
         global_end.append_value(row.global_end);
         entropy.append_value(row.entropy);
@@ -1528,6 +4980,7 @@ fn build_entropy_batch(
         Arc::new(config_hash.finish()),
         Arc::new(evidence_path.finish()),
         Arc::new(evidence_sha256.finish()),
+        Arc::new(pass_id.finish()),
         Arc::new(global_start.finish()),
         Arc::new(global_end.finish()),
         Arc::new(entropy.finish()),
@@ -1548,6 +5001,7 @@ fn build_summary_batch(
     let mut config_hash = StringBuilder::new();
     let mut evidence_path = StringBuilder::new();
     let mut evidence_sha256 = StringBuilder::new();
+    let mut pass_id = Int64Builder::new();
     let mut bytes_scanned = Int64Builder::new();
     let mut chunks_processed = Int64Builder::new();
     let mut hits_found = Int64Builder::new();
@@ -1561,6 +5015,7 @@ fn build_summary_batch(
         config_hash.append_value(&ctx.config_hash);
         evidence_path.append_value(&ctx.evidence_path);
         evidence_sha256.append_value(&ctx.evidence_sha256);
+        pass_id.append_value(i64::from(ctx.pass_id));
         bytes_scanned.append_value(row.bytes_scanned);
         chunks_processed.append_value(row.chunks_processed);
         hits_found.append_value(row.hits_found);
@@ -1575,6 +5030,7 @@ fn build_summary_batch(
         Arc::new(config_hash.finish()),
         Arc::new(evidence_path.finish()),
         Arc::new(evidence_sha256.finish()),
+        Arc::new(pass_id.finish()),
         Arc::new(bytes_scanned.finish()),
         Arc::new(chunks_processed.finish()),
         Arc::new(hits_found.finish()),
@@ -1587,8 +5043,70 @@ fn build_summary_batch(
         .map_err(|err| MetadataError::Other(format!("parquet batch error: {err}")))
 }
 
+fn build_resources_batch(
+    ctx: &ParquetContext,
+    rows: &[ResourceUsageRow],
+    schema: &SchemaRef,
+) -> Result<RecordBatch, MetadataError> {
+    let mut run_id = StringBuilder::new();
+    let mut tool_version = StringBuilder::new();
+    let mut config_hash = StringBuilder::new();
+    let mut evidence_path = StringBuilder::new();
+    let mut evidence_sha256 = StringBuilder::new();
+    let mut pass_id = Int64Builder::new();
+    let mut stage = StringBuilder::new();
+    let mut threads = Int64Builder::new();
+    let mut busy_secs = arrow_array::builder::Float64Builder::new();
+    let mut user_cpu_secs = arrow_array::builder::Float64Builder::new();
+    let mut sys_cpu_secs = arrow_array::builder::Float64Builder::new();
+    let mut bytes_read = Int64Builder::new();
+    let mut bytes_written = Int64Builder::new();
+    let mut gpu_secs = arrow_array::builder::Float64Builder::new();
+    let mut peak_rss_bytes = Int64Builder::new();
+
+    for row in rows {
+        run_id.append_value(&ctx.run_id);
+        tool_version.append_value(&ctx.tool_version);
+        config_hash.append_value(&ctx.config_hash);
+        evidence_path.append_value(&ctx.evidence_path);
+        evidence_sha256.append_value(&ctx.evidence_sha256);
+        pass_id.append_value(i64::from(ctx.pass_id));
+        stage.append_value(&row.stage);
+        threads.append_value(row.threads);
+        busy_secs.append_value(row.busy_secs);
+        user_cpu_secs.append_option(row.user_cpu_secs);
+        sys_cpu_secs.append_option(row.sys_cpu_secs);
+        bytes_read.append_option(row.bytes_read);
+        bytes_written.append_option(row.bytes_written);
+        gpu_secs.append_option(row.gpu_secs);
+        peak_rss_bytes.append_option(row.peak_rss_bytes);
+    }
+
+    let arrays: Vec<ArrayRef> = vec![
+        Arc::new(run_id.finish()),
+        Arc::new(tool_version.finish()),
+        Arc::new(config_hash.finish()),
+        Arc::new(evidence_path.finish()),
+        Arc::new(evidence_sha256.finish()),
+        Arc::new(pass_id.finish()),
+        Arc::new(stage.finish()),
+        Arc::new(threads.finish()),
+        Arc::new(busy_secs.finish()),
+        Arc::new(user_cpu_secs.finish()),
+        Arc::new(sys_cpu_secs.finish()),
+        Arc::new(bytes_read.finish()),
+        Arc::new(bytes_written.finish()),
+        Arc::new(gpu_secs.finish()),
+        Arc::new(peak_rss_bytes.finish()),
+    ];
+
+    RecordBatch::try_new(Arc::clone(schema), arrays)
+        .map_err(|err| MetadataError::Other(format!("parquet batch error: {err}")))
+}
+
 fn map_url_artefact(artefact: &StringArtefact) -> Result<UrlArtefactRow, MetadataError> {
     let (scheme, host, port, path, query, fragment) = parse_url_parts(&artefact.content);
+    let decoded = decode_url(&artefact.content);
     Ok(UrlArtefactRow {
         global_start: to_i64(artefact.global_start)?,
         global_end: to_i64(artefact.global_end)?,
@@ -1599,8 +5117,10 @@ fn map_url_artefact(artefact: &StringArtefact) -> Result<UrlArtefactRow, Metadat
         path,
         query,
         fragment,
-        source_kind: "string_span".to_string(),
-        source_detail: "strings_artefacts".to_string(),
+        host_unicode: decoded.host_unicode,
+        path_decoded: decoded.path_decoded,
+        source_kind: artefact.source_kind.clone(),
+        source_detail: artefact.source_detail.clone(),
         certainty: 1.0,
     })
 }
@@ -1613,8 +5133,8 @@ fn map_email_artefact(artefact: &StringArtefact) -> Result<EmailArtefactRow, Met
         email: artefact.content.clone(),
         local_part,
         domain,
-        source_kind: "string_span".to_string(),
-        source_detail: "strings_artefacts".to_string(),
+        source_kind: artefact.source_kind.clone(),
+        source_detail: artefact.source_detail.clone(),
         certainty: 1.0,
     })
 }
@@ -1626,66 +5146,63 @@ fn map_phone_artefact(artefact: &StringArtefact) -> Result<PhoneArtefactRow, Met
         phone_raw: artefact.content.clone(),
         phone_e164: None,
         country: None,
-        source_kind: "string_span".to_string(),
-        source_detail: "strings_artefacts".to_string(),
+        source_kind: artefact.source_kind.clone(),
+        source_detail: artefact.source_detail.clone(),
         certainty: 1.0,
     })
 }
 
-fn parse_url_parts(
-    url: &str,
-) -> (
-    String,
-    String,
-    Option<i32>,
-    Option<String>,
-    Option<String>,
-    Option<String>,
-) {
-    let mut scheme = String::new();
-    let mut rest = url;
-    if let Some(stripped) = url.strip_prefix("http://") {
-        scheme = "http".to_string();
-        rest = stripped;
-    } else if let Some(stripped) = url.strip_prefix("https://") {
-        scheme = "https".to_string();
-        rest = stripped;
-    } else if url.starts_with("www.") {
-        scheme = "http".to_string();
-        rest = url;
-    }
-
-    let mut fragment = None;
-    let mut query = None;
-    let mut path = None;
-
-    let mut base = rest;
-    if let Some(pos) = base.find('#') {
-        fragment = Some(base[pos + 1..].to_string());
-        base = &base[..pos];
-    }
-    if let Some(pos) = base.find('?') {
-        query = Some(base[pos + 1..].to_string());
-        base = &base[..pos];
-    }
-    if let Some(pos) = base.find('/') {
-        path = Some(base[pos..].to_string());
-        base = &base[..pos];
-    }
-
-    let mut host = base.to_string();
-    let mut port = None;
-    if let Some(pos) = base.rfind(':') {
-        let candidate = &base[pos + 1..];
-        if !candidate.is_empty() && candidate.chars().all(|c| c.is_ascii_digit()) {
-            if let Ok(parsed) = candidate.parse::<i32>() {
-                port = Some(parsed);
-                host = base[..pos].to_string();
-            }
-        }
-    }
-
-    (scheme, host, port, path, query, fragment)
+fn map_network_artefact(artefact: &StringArtefact) -> Result<NetworkArtefactRow, MetadataError> {
+    Ok(NetworkArtefactRow {
+        global_start: to_i64(artefact.global_start)?,
+        global_end: to_i64(artefact.global_end)?,
+        artefact_kind: match artefact.artefact_kind {
+            ArtefactKind::HttpHost => "http_host",
+            ArtefactKind::DnsQuery => "dns_query",
+            _ => "connection",
+        }
+        .to_string(),
+        value: artefact.content.clone(),
+        source_kind: artefact.source_kind.clone(),
+        source_detail: artefact.source_detail.clone(),
+    })
+}
+
+fn map_system_artefact(artefact: &StringArtefact) -> Result<NetworkArtefactRow, MetadataError> {
+    Ok(NetworkArtefactRow {
+        global_start: to_i64(artefact.global_start)?,
+        global_end: to_i64(artefact.global_end)?,
+        artefact_kind: match artefact.artefact_kind {
+            ArtefactKind::SshPublicKey => "ssh_public_key",
+            ArtefactKind::SshPrivateKey => "ssh_private_key",
+            ArtefactKind::SshKnownHost => "ssh_known_host",
+            ArtefactKind::Credential => "credential",
+            _ => "shell_history",
+        }
+        .to_string(),
+        value: artefact.content.clone(),
+        source_kind: artefact.source_kind.clone(),
+        source_detail: artefact.source_detail.clone(),
+    })
+}
+
+fn map_p2p_artefact(artefact: &StringArtefact) -> Result<NetworkArtefactRow, MetadataError> {
+    Ok(NetworkArtefactRow {
+        global_start: to_i64(artefact.global_start)?,
+        global_end: to_i64(artefact.global_end)?,
+        artefact_kind: match artefact.artefact_kind {
+            ArtefactKind::MagnetUri => "magnet_uri",
+            ArtefactKind::Ed2kLink => "ed2k_link",
+            ArtefactKind::TorrentInfoHash => "torrent_info_hash",
+            ArtefactKind::TorrentName => "torrent_name",
+            ArtefactKind::TorrentTracker => "torrent_tracker",
+            _ => "dht_node",
+        }
+        .to_string(),
+        value: artefact.content.clone(),
+        source_kind: artefact.source_kind.clone(),
+        source_detail: artefact.source_detail.clone(),
+    })
 }
 
 fn split_email(value: &str) -> (String, String) {
@@ -0,0 +1,675 @@
+//! Recovery of metadata outputs an aborted run left damaged (`salvage`).
+//!
+//! JSONL streams keep every complete, parseable line. CSV streams are read
+//! record by record, so quoted fields may span lines, and keep the records
+//! before the first malformed one or the partial record at the end. Parquet files
+//! written without a footer are repaired by walking their pages and the
+//! column chunk metadata written after each chunk, and appending a footer for
+//! the complete row groups; each row group is then decoded and copied to
+//! a fresh file, so a damaged group costs only its own rows.
+
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Cursor, Read};
+use std::path::{Path, PathBuf};
+
+use arrow_array::RecordBatch;
+use arrow_schema::SchemaRef;
+use parquet::arrow::ArrowWriter;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::arrow_to_parquet_schema;
+use parquet::format::{ColumnChunk, ColumnMetaData, FileMetaData, PageHeader, RowGroup};
+use parquet::schema::types::SchemaDescriptor;
+use parquet::thrift::TSerializable;
+use serde::Serialize;
+use thrift::protocol::{TCompactInputProtocol, TCompactOutputProtocol, TOutputProtocol};
+
+use crate::encryption::ENCRYPTED_EXT;
+use crate::metadata::MetadataError;
+use crate::metadata::parquet::schema_for_filename;
+
+/// Default output directory, inside the run directory.
+pub const SALVAGE_DIR: &str = "salvaged";
+/// Per-file results, written to the output directory.
+pub const REPORT_FILE: &str = "salvage_report.json";
+
+const PARQUET_MAGIC: &[u8; 4] = b"PAR1";
+
+/// What `salvage` recovered from a run directory.
+#[derive(Debug, Serialize)]
+pub struct SalvageReport {
+    pub run_dir: PathBuf,
+    pub output_dir: PathBuf,
+    pub files: Vec<SalvagedFile>,
+}
+
+/// One metadata output and what was recovered from it.
+#[derive(Debug, Serialize)]
+pub struct SalvagedFile {
+    /// Path relative to the run directory.
+    pub path: String,
+    pub status: SalvageStatus,
+    pub records_recovered: u64,
+    /// Records known to be lost: malformed lines, rows of row groups that
+    /// failed to decode. Rows of a Parquet row group cut short are not
+    /// counted; see `bytes_discarded`.
+    pub records_lost: u64,
+    pub bytes_discarded: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SalvageStatus {
+    /// Read in full; copied unchanged.
+    Intact,
+    /// Damaged; the recovered records were written to a fresh file.
+    Repaired,
+    /// Nothing could be recovered.
+    Unrecoverable,
+    /// Not read (encrypted, or a Parquet file this tool did not write).
+    Skipped,
+}
+
+impl SalvageReport {
+    pub fn write(&self) -> Result<PathBuf, MetadataError> {
+        let path = self.output_dir.join(REPORT_FILE);
+        let writer = BufWriter::new(File::create(&path)?);
+        serde_json::to_writer_pretty(writer, self)?;
+        Ok(path)
+    }
+}
+
+/// Recover the JSONL, CSV, and Parquet metadata of `run_dir` into
+/// `output_dir` (`metadata/` and `parquet/` keep their names).
+pub fn salvage_run(run_dir: &Path, output_dir: &Path) -> Result<SalvageReport, MetadataError> {
+    if !run_dir.is_dir() {
+        return Err(MetadataError::Other(format!(
+            "{} is not a run directory",
+            run_dir.display()
+        )));
+    }
+    fs::create_dir_all(output_dir)?;
+    let mut files = Vec::new();
+    for dir in ["metadata", "parquet"] {
+        let Ok(entries) = fs::read_dir(run_dir.join(dir)) else {
+            continue;
+        };
+        let mut paths: Vec<PathBuf> = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file())
+            .collect();
+        paths.sort();
+        for path in paths {
+            let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+            let relative = format!("{dir}/{name}");
+            let dest = output_dir.join(dir).join(name);
+            let result = match path.extension().and_then(|e| e.to_str()) {
+                Some("jsonl") => salvage_jsonl(&path, &dest),
+                Some("csv") => salvage_csv(&path, &dest),
+                Some("parquet") => salvage_parquet(&path, &dest),
+                Some(ENCRYPTED_EXT) => Ok(Outcome::skipped("encrypted; run `decrypt` first")),
+                _ => continue,
+            };
+            let outcome = result.unwrap_or_else(|err| Outcome {
+                status: SalvageStatus::Unrecoverable,
+                note: Some(err.to_string()),
+                ..Outcome::default()
+            });
+            files.push(outcome.into_file(relative));
+        }
+    }
+    Ok(SalvageReport {
+        run_dir: run_dir.to_path_buf(),
+        output_dir: output_dir.to_path_buf(),
+        files,
+    })
+}
+
+#[derive(Debug)]
+struct Outcome {
+    status: SalvageStatus,
+    recovered: u64,
+    lost: u64,
+    discarded: u64,
+    note: Option<String>,
+}
+
+impl Default for Outcome {
+    fn default() -> Self {
+        Self {
+            status: SalvageStatus::Intact,
+            recovered: 0,
+            lost: 0,
+            discarded: 0,
+            note: None,
+        }
+    }
+}
+
+impl Outcome {
+    fn skipped(note: &str) -> Self {
+        Self {
+            status: SalvageStatus::Skipped,
+            note: Some(note.to_string()),
+            ..Self::default()
+        }
+    }
+
+    fn into_file(self, path: String) -> SalvagedFile {
+        SalvagedFile {
+            path,
+            status: self.status,
+            records_recovered: self.recovered,
+            records_lost: self.lost,
+            bytes_discarded: self.discarded,
+            note: self.note,
+        }
+    }
+}
+
+/// Keeps the lines that are complete JSON objects.
+fn salvage_jsonl(src: &Path, dest: &Path) -> Result<Outcome, MetadataError> {
+    let mut reader = BufReader::new(File::open(src)?);
+    let mut out = Vec::new();
+    let mut outcome = Outcome::default();
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        let read = reader.read_until(b'\n', &mut line)?;
+        if read == 0 {
+            break;
+        }
+        let record = trim_line_end(&line);
+        if record.is_empty() && line.ends_with(b"\n") {
+            continue;
+        }
+        if serde_json::from_slice::<serde_json::Map<String, serde_json::Value>>(record).is_ok() {
+            outcome.recovered += 1;
+            out.extend_from_slice(record);
+            out.push(b'\n');
+        } else {
+            outcome.lost += 1;
+            outcome.discarded += read as u64;
+        }
+    }
+    if outcome.lost > 0 {
+        outcome.status = SalvageStatus::Repaired;
+    }
+    write_output(dest, &out)?;
+    Ok(outcome)
+}
+
+fn trim_line_end(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+/// Keeps the header and the records after it up to the first one that does
+/// not parse or has another field count, or the last one when it was cut off
+/// mid-write (no line end after it, or an open quote). A stream is written
+/// front to back, so nothing after a broken record is trusted. The kept
+/// records are copied byte for byte.
+fn salvage_csv(src: &Path, dest: &Path) -> Result<Outcome, MetadataError> {
+    let bytes = fs::read(src)?;
+    let mut reader = ::csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(bytes.as_slice());
+    let mut outcome = Outcome::default();
+    let mut header_fields = None;
+    let mut kept = 0usize;
+    let mut broken = false;
+    let mut record = ::csv::ByteRecord::new();
+    loop {
+        let start = reader.position().byte() as usize;
+        match reader.read_byte_record(&mut record) {
+            Ok(false) => break,
+            Ok(true) => {}
+            Err(_) => {
+                outcome.lost += 1;
+                break;
+            }
+        }
+        let end = reader.position().byte() as usize;
+        let complete = !broken
+            && csv_record_complete(&bytes[start..end.min(bytes.len())])
+            && header_fields.is_none_or(|count| count == record.len());
+        if !complete {
+            broken = true;
+            outcome.lost += 1;
+            continue;
+        }
+        if header_fields.is_none() {
+            header_fields = Some(record.len());
+        } else {
+            outcome.recovered += 1;
+        }
+        kept = end;
+    }
+    outcome.discarded = (bytes.len() - kept) as u64;
+    if outcome.lost > 0 {
+        outcome.status = SalvageStatus::Repaired;
+    }
+    if header_fields.is_none() {
+        outcome.status = SalvageStatus::Unrecoverable;
+        outcome.note = Some("no header row".to_string());
+        return Ok(outcome);
+    }
+    write_output(dest, &bytes[..kept])?;
+    Ok(outcome)
+}
+
+/// Whether the raw bytes of one CSV record end with its line end, outside
+/// quotes. The csv reader closes an open quote and the last record at end
+/// of input, which is how a cut-off write looks.
+fn csv_record_complete(raw: &[u8]) -> bool {
+    let quotes = raw.iter().filter(|b| **b == b'"').count();
+    raw.ends_with(b"\n") && quotes % 2 == 0
+}
+
+fn write_output(dest: &Path, bytes: &[u8]) -> Result<(), MetadataError> {
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(dest, bytes)?;
+    Ok(())
+}
+
+fn salvage_parquet(src: &Path, dest: &Path) -> Result<Outcome, MetadataError> {
+    let name = src.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+    let Some(schema) = schema_for_filename(name) else {
+        return Ok(Outcome::skipped("not a SwiftBeaver Parquet output"));
+    };
+    if let Ok(file) = File::open(src)
+        && ParquetRecordBatchReaderBuilder::try_new(file).is_ok()
+    {
+        let mut outcome = copy_row_groups(src, dest, &schema)?;
+        if outcome.lost > 0 {
+            outcome.status = SalvageStatus::Repaired;
+        }
+        return Ok(outcome);
+    }
+
+    // No readable footer: rebuild one from the column chunks on disk.
+    let mut bytes = Vec::new();
+    File::open(src)?.read_to_end(&mut bytes)?;
+    if !bytes.starts_with(PARQUET_MAGIC) {
+        return Ok(Outcome {
+            status: SalvageStatus::Unrecoverable,
+            discarded: bytes.len() as u64,
+            note: Some("no Parquet header".to_string()),
+            ..Outcome::default()
+        });
+    }
+    let descr = arrow_to_parquet_schema(&schema).map_err(parquet_error)?;
+    let columns: Vec<Vec<String>> = descr
+        .columns()
+        .iter()
+        .map(|column| column.path().parts().to_vec())
+        .collect();
+    let groups = complete_row_groups(walk_chunks(&bytes), &columns);
+    let end = groups
+        .last()
+        .and_then(|group| group.last())
+        .map_or(PARQUET_MAGIC.len() as u64, |chunk| chunk.end);
+    let discarded = bytes.len() as u64 - end;
+    if groups.is_empty() {
+        return Ok(Outcome {
+            status: SalvageStatus::Unrecoverable,
+            discarded,
+            note: Some("no complete row group".to_string()),
+            ..Outcome::default()
+        });
+    }
+
+    bytes.truncate(end as usize);
+    let footer = build_footer(&groups, &descr)?;
+    bytes.extend_from_slice(&footer);
+    bytes.extend_from_slice(&(footer.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(PARQUET_MAGIC);
+    let rebuilt = dest.with_extension("rebuilt");
+    write_output(&rebuilt, &bytes)?;
+    let result = copy_row_groups(&rebuilt, dest, &schema);
+    let _ = fs::remove_file(&rebuilt);
+    let mut outcome = result?;
+    outcome.status = SalvageStatus::Repaired;
+    outcome.discarded += discarded;
+    outcome.note = Some(format!(
+        "footer rebuilt from {} row group(s); {discarded} trailing bytes discarded",
+        groups.len()
+    ));
+    Ok(outcome)
+}
+
+/// Decodes `src` one row group at a time and writes the groups that decode
+/// to `dest` with the sink's schema.
+fn copy_row_groups(src: &Path, dest: &Path, schema: &SchemaRef) -> Result<Outcome, MetadataError> {
+    let builder =
+        ParquetRecordBatchReaderBuilder::try_new(File::open(src)?).map_err(parquet_error)?;
+    let metadata = builder.metadata().clone();
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut writer =
+        ArrowWriter::try_new(File::create(dest)?, schema.clone(), None).map_err(parquet_error)?;
+    let mut outcome = Outcome::default();
+    for (index, group) in metadata.row_groups().iter().enumerate() {
+        let rows = group.num_rows().max(0) as u64;
+        match read_row_group(src, index, schema) {
+            Ok(batches) => {
+                for batch in &batches {
+                    writer.write(batch).map_err(parquet_error)?;
+                }
+                outcome.recovered += rows;
+            }
+            Err(err) => {
+                outcome.lost += rows;
+                outcome.discarded += group.compressed_size().max(0) as u64;
+                outcome.note = Some(format!("row group {index}: {err}"));
+            }
+        }
+    }
+    writer.close().map_err(parquet_error)?;
+    Ok(outcome)
+}
+
+fn read_row_group(
+    src: &Path,
+    index: usize,
+    schema: &SchemaRef,
+) -> Result<Vec<RecordBatch>, MetadataError> {
+    let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(src)?)
+        .map_err(parquet_error)?
+        .with_row_groups(vec![index])
+        .build()
+        .map_err(parquet_error)?;
+    reader
+        .map(|batch| {
+            let batch = batch.map_err(|err| MetadataError::Other(err.to_string()))?;
+            RecordBatch::try_new(schema.clone(), batch.columns().to_vec())
+                .map_err(|err| MetadataError::Other(err.to_string()))
+        })
+        .collect()
+}
+
+fn parquet_error(err: parquet::errors::ParquetError) -> MetadataError {
+    MetadataError::Other(format!("parquet error: {err}"))
+}
+
+/// A column chunk of a file without a footer. The writer follows the pages of
+/// every chunk with the chunk's `ColumnMetaData`, which is all the footer
+/// needs.
+#[derive(Debug)]
+struct Chunk {
+    start: u64,
+    /// Offset of the trailing `ColumnMetaData`.
+    metadata_offset: u64,
+    end: u64,
+    meta: ColumnMetaData,
+}
+
+fn read_thrift<T: TSerializable>(bytes: &[u8]) -> Option<(T, u64)> {
+    let mut cursor = Cursor::new(bytes);
+    let value = T::read_from_in_protocol(&mut TCompactInputProtocol::new(&mut cursor)).ok()?;
+    Some((value, cursor.position()))
+}
+
+/// Walks pages from the start of the file, closing a chunk at each
+/// `ColumnMetaData` that describes the pages since the previous one. Stops at the first
+/// structure that does not parse or runs past the end of the file.
+fn walk_chunks(bytes: &[u8]) -> Vec<Chunk> {
+    let len = bytes.len() as u64;
+    let mut chunks = Vec::new();
+    let mut start = PARQUET_MAGIC.len() as u64;
+    let mut offset = start;
+    while offset < len {
+        let rest = &bytes[offset as usize..];
+        if offset > start
+            && let Some((mut meta, size)) = read_thrift::<ColumnMetaData>(rest)
+            && meta.dictionary_page_offset.unwrap_or(meta.data_page_offset) == 0
+            && meta.total_compressed_size == (offset - start) as i64
+            && offset + size <= len
+        {
+            // Page offsets are relative to the chunk; the footer's are not.
+            let base = start as i64;
+            meta.data_page_offset += base;
+            meta.dictionary_page_offset = meta.dictionary_page_offset.map(|o| o + base);
+            meta.index_page_offset = meta.index_page_offset.map(|o| o + base);
+            chunks.push(Chunk {
+                start,
+                metadata_offset: offset,
+                end: offset + size,
+                meta,
+            });
+            offset += size;
+            start = offset;
+            continue;
+        }
+        let Some((header, size)) = read_thrift::<PageHeader>(rest) else {
+            break;
+        };
+        let Ok(page) = u64::try_from(header.compressed_page_size) else {
+            break;
+        };
+        if offset + size + page > len {
+            break;
+        }
+        offset += size + page;
+    }
+    chunks
+}
+
+/// Splits chunks into row groups of one chunk per column, in schema order,
+/// each with the same number of values (the schema is flat). Chunks after the
+/// last complete row group are dropped.
+fn complete_row_groups(chunks: Vec<Chunk>, columns: &[Vec<String>]) -> Vec<Vec<Chunk>> {
+    let mut groups = Vec::new();
+    let mut group: Vec<Chunk> = Vec::new();
+    for chunk in chunks {
+        let expected = &columns[group.len()];
+        let rows = group
+            .first()
+            .map_or(chunk.meta.num_values, |c| c.meta.num_values);
+        if &chunk.meta.path_in_schema != expected || chunk.meta.num_values != rows {
+            break;
+        }
+        group.push(chunk);
+        if group.len() == columns.len() {
+            groups.push(std::mem::take(&mut group));
+        }
+    }
+    groups
+}
+
+fn build_footer(groups: &[Vec<Chunk>], descr: &SchemaDescriptor) -> Result<Vec<u8>, MetadataError> {
+    let mut row_groups = Vec::new();
+    let mut num_rows = 0;
+    for (ordinal, group) in groups.iter().enumerate() {
+        let rows = group[0].meta.num_values;
+        let columns = group
+            .iter()
+            .map(|chunk| ColumnChunk {
+                file_path: None,
+                file_offset: chunk.metadata_offset as i64,
+                meta_data: Some(chunk.meta.clone()),
+                offset_index_offset: None,
+                offset_index_length: None,
+                column_index_offset: None,
+                column_index_length: None,
+                crypto_metadata: None,
+                encrypted_column_metadata: None,
+            })
+            .collect();
+        row_groups.push(RowGroup {
+            columns,
+            total_byte_size: group.iter().map(|c| c.meta.total_uncompressed_size).sum(),
+            num_rows: rows,
+            sorting_columns: None,
+            file_offset: Some(group[0].start as i64),
+            total_compressed_size: Some(group.iter().map(|c| c.meta.total_compressed_size).sum()),
+            ordinal: Some(ordinal as i16),
+        });
+        num_rows += rows;
+    }
+    let schema = parquet::schema::types::to_thrift(descr.root_schema()).map_err(parquet_error)?;
+    let metadata = FileMetaData::new(
+        1,
+        schema,
+        num_rows,
+        row_groups,
+        None,
+        Some(format!("swiftbeaver {} salvage", env!("CARGO_PKG_VERSION"))),
+        None,
+        None,
+        None,
+    );
+    let mut out = Vec::new();
+    let mut protocol = TCompactOutputProtocol::new(&mut out);
+    metadata
+        .write_to_out_protocol(&mut protocol)
+        .and_then(|()| protocol.flush())
+        .map_err(|err| MetadataError::Other(format!("parquet footer: {err}")))?;
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::carve::CarvedFile;
+    use crate::config;
+    use crate::metadata::{MetadataBackendKind, build_sink};
+
+    fn carved(index: u64) -> CarvedFile {
+        CarvedFile {
+            run_id: "run".to_string(),
+            file_type: "jpeg".to_string(),
+            signature_type: "jpeg".to_string(),
+            path: format!("carved/jpeg_{index:08}.jpg"),
+            extension: "jpg".to_string(),
+            global_start: index * 100,
+            global_end: index * 100 + 99,
+            size: 100,
+            md5: None,
+            sha256: Some(format!("{index:064x}")),
+            validated: true,
+            truncated: false,
+            errors: Vec::new(),
+            pattern_id: Some("jpeg_soi".to_string()),
+        }
+    }
+
+    #[test]
+    fn rebuilds_parquet_footer_from_complete_row_groups() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let run_dir = tmp.path().join("run");
+        let mut cfg = config::load_config(None).expect("config").config;
+        cfg.parquet_row_group_size = 2;
+        let sink = build_sink(
+            MetadataBackendKind::Parquet,
+            &cfg,
+            "run",
+            "0.1.0",
+            "hash",
+            Path::new("evidence.dd"),
+            "",
+            &run_dir,
+        )
+        .expect("sink");
+        for index in 0..5 {
+            sink.record_file(&carved(index)).expect("record");
+        }
+        drop(sink);
+
+        // Cut into the last row group, taking the footer with it.
+        let path = run_dir.join("parquet/files_jpeg.parquet");
+        let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(&path).expect("open"))
+            .expect("read");
+        let last = builder.metadata().row_group(2);
+        let cut = last.column(last.num_columns() - 1).byte_range().0 + 10;
+        let bytes = fs::read(&path).expect("read");
+        fs::write(&path, &bytes[..cut as usize]).expect("truncate");
+
+        let out = tmp.path().join("out");
+        let report = salvage_run(&run_dir, &out).expect("salvage");
+        let file = report
+            .files
+            .iter()
+            .find(|f| f.path == "parquet/files_jpeg.parquet")
+            .expect("jpeg file");
+        assert_eq!(file.status, SalvageStatus::Repaired, "{:?}", file.note);
+        assert_eq!(file.records_recovered, 4);
+        assert!(file.bytes_discarded > 0);
+
+        let reader = ParquetRecordBatchReaderBuilder::try_new(
+            File::open(out.join("parquet/files_jpeg.parquet")).expect("open"),
+        )
+        .expect("footer")
+        .build()
+        .expect("reader");
+        let rows: usize = reader.map(|batch| batch.expect("batch").num_rows()).sum();
+        assert_eq!(rows, 4);
+    }
+
+    #[test]
+    fn drops_partial_jsonl_and_csv_records() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let run_dir = tmp.path().join("run");
+        fs::create_dir_all(run_dir.join("metadata")).expect("dir");
+        fs::write(
+            run_dir.join("metadata/carved_files.jsonl"),
+            "{\"path\":\"a\"}\n{\"path\":\"b\"}\n{\"pa",
+        )
+        .expect("jsonl");
+        fs::write(
+            run_dir.join("metadata/carved_files.csv"),
+            "path,size\na,1\nb,2,extra\nc,3\nd,",
+        )
+        .expect("csv");
+
+        let out = tmp.path().join("out");
+        let report = salvage_run(&run_dir, &out).expect("salvage");
+        let by_path = |path: &str| report.files.iter().find(|f| f.path == path).expect(path);
+
+        let jsonl = by_path("metadata/carved_files.jsonl");
+        assert_eq!(jsonl.status, SalvageStatus::Repaired);
+        assert_eq!((jsonl.records_recovered, jsonl.records_lost), (2, 1));
+        assert_eq!(jsonl.bytes_discarded, 4);
+        let csv = by_path("metadata/carved_files.csv");
+        // Nothing after the record with an extra field is kept.
+        assert_eq!((csv.records_recovered, csv.records_lost), (1, 3));
+        assert_eq!(
+            fs::read_to_string(out.join("metadata/carved_files.csv")).expect("csv"),
+            "path,size\na,1\n"
+        );
+        report.write().expect("report");
+        assert!(out.join(REPORT_FILE).exists());
+    }
+
+    #[test]
+    fn keeps_csv_fields_that_span_lines() {
+        let tmp = tempfile::tempdir().expect("tempdir");
+        let run_dir = tmp.path().join("run");
+        fs::create_dir_all(run_dir.join("metadata")).expect("dir");
+        let kept = "path,note\na,\"first line\nsecond, \"\"quoted\"\"\"\nb,plain\n";
+        fs::write(
+            run_dir.join("metadata/log_events.csv"),
+            format!("{kept}c,\"cut off\nmid-field"),
+        )
+        .expect("csv");
+
+        let out = tmp.path().join("out");
+        let report = salvage_run(&run_dir, &out).expect("salvage");
+        let csv = &report.files[0];
+        assert_eq!(csv.status, SalvageStatus::Repaired);
+        assert_eq!((csv.records_recovered, csv.records_lost), (2, 1));
+        assert_eq!(csv.bytes_discarded, "c,\"cut off\nmid-field".len() as u64);
+        assert_eq!(
+            fs::read_to_string(out.join("metadata/log_events.csv")).expect("csv"),
+            kept
+        );
+    }
+}
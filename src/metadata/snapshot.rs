@@ -0,0 +1,289 @@
+//! Periodic snapshots of the metadata of a running run, for review before it
+//! ends (`metadata_snapshot_interval_secs`).
+//!
+//! The metadata thread is the only writer of the metadata streams, so it takes
+//! each snapshot itself between two events: it has the sink write out what it
+//! buffers (Parquet closes its open row groups), then copies the streams with
+//! [`salvage_run`] into `snapshots/<sequence>`. JSONL and CSV streams in a
+//! snapshot all end at the same event, on a complete record. Parquet streams
+//! keep the row groups that reached disk, with a rebuilt footer; the Parquet
+//! writer holds back up to 8 KiB of a file, so the row group a snapshot closes
+//! usually shows up only in the next one. A snapshot is built under a
+//! temporary name and renamed into place; `snapshots/latest.json` names the
+//! newest one, and only the newest `metadata_snapshot_keep` are kept.
+
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::config::Config;
+use crate::metadata::salvage::salvage_run;
+use crate::metadata::{MetadataError, MetadataSink};
+
+/// Snapshot directory, inside the run directory.
+pub const SNAPSHOT_DIR: &str = "snapshots";
+/// Description of a snapshot, inside the snapshot.
+pub const SNAPSHOT_FILE: &str = "snapshot.json";
+/// Pointer to the newest snapshot, inside [`SNAPSHOT_DIR`].
+pub const LATEST_FILE: &str = "latest.json";
+
+/// What one snapshot holds; written as `snapshot.json` and `latest.json`.
+#[derive(Debug, Serialize)]
+pub struct SnapshotInfo {
+    pub run_id: String,
+    pub sequence: u64,
+    /// Snapshot directory, relative to the run directory.
+    pub path: String,
+    pub taken_at: String,
+    /// Seconds since the metadata thread started.
+    pub elapsed_secs: f64,
+    pub files: Vec<SnapshotFile>,
+}
+
+/// One metadata stream of a snapshot.
+#[derive(Debug, Serialize)]
+pub struct SnapshotFile {
+    /// Path relative to the snapshot directory.
+    pub path: String,
+    pub records: u64,
+    /// Set when the stream could not be copied (e.g. it is encrypted).
+    pub note: Option<String>,
+}
+
+/// Takes a snapshot every `interval` of metadata recording.
+pub struct MetadataSnapshots {
+    run_id: String,
+    run_dir: PathBuf,
+    interval: Duration,
+    keep: usize,
+    started: Instant,
+    last: Instant,
+    sequence: u64,
+}
+
+impl MetadataSnapshots {
+    pub fn from_config(cfg: &Config, run_output_dir: &Path) -> Option<Self> {
+        if cfg.metadata_snapshot_interval_secs == 0 || !run_output_dir.is_dir() {
+            return None;
+        }
+        Some(Self::new(
+            &cfg.run_id,
+            run_output_dir,
+            Duration::from_secs(cfg.metadata_snapshot_interval_secs),
+            cfg.metadata_snapshot_keep,
+        ))
+    }
+
+    /// Numbering continues after the snapshots already in the run directory,
+    /// so later passes do not overwrite those of earlier ones.
+    pub fn new(run_id: &str, run_dir: &Path, interval: Duration, keep: usize) -> Self {
+        let now = Instant::now();
+        let sequence = existing(&run_dir.join(SNAPSHOT_DIR))
+            .last()
+            .map_or(0, |(sequence, _)| *sequence);
+        Self {
+            run_id: run_id.to_string(),
+            run_dir: run_dir.to_path_buf(),
+            interval,
+            keep,
+            started: now,
+            last: now,
+            sequence,
+        }
+    }
+
+    pub fn due(&self, now: Instant) -> bool {
+        now.duration_since(self.last) >= self.interval
+    }
+
+    /// Bring the sink's files up to date and take the next snapshot. The
+    /// interval restarts even when the snapshot fails, so a full disk does
+    /// not make every event retry it.
+    pub fn take(&mut self, sink: &dyn MetadataSink) -> Result<PathBuf, MetadataError> {
+        self.last = Instant::now();
+        sink.flush_for_snapshot()?;
+        self.sequence += 1;
+        let root = self.run_dir.join(SNAPSHOT_DIR);
+        let name = format!("{:04}", self.sequence);
+        let staging = root.join(format!(".tmp-{name}"));
+        let dest = root.join(&name);
+        if staging.exists() {
+            fs::remove_dir_all(&staging)?;
+        }
+        let report = match salvage_run(&self.run_dir, &staging) {
+            Ok(report) => report,
+            Err(err) => {
+                let _ = fs::remove_dir_all(&staging);
+                return Err(err);
+            }
+        };
+        let info = SnapshotInfo {
+            run_id: self.run_id.clone(),
+            sequence: self.sequence,
+            path: format!("{SNAPSHOT_DIR}/{name}"),
+            taken_at: chrono::Utc::now().to_rfc3339(),
+            elapsed_secs: self.started.elapsed().as_secs_f64(),
+            files: report
+                .files
+                .into_iter()
+                .map(|file| SnapshotFile {
+                    path: file.path,
+                    records: file.records_recovered,
+                    note: file.note,
+                })
+                .collect(),
+        };
+        write_json(&staging.join(SNAPSHOT_FILE), &info)?;
+        fs::rename(&staging, &dest)?;
+        let latest = root.join(format!(".{LATEST_FILE}"));
+        write_json(&latest, &info)?;
+        fs::rename(&latest, root.join(LATEST_FILE))?;
+        self.prune(&root);
+        info!(
+            "metadata snapshot {} taken in {:.1}s",
+            info.path,
+            self.last.elapsed().as_secs_f64()
+        );
+        Ok(dest)
+    }
+
+    fn prune(&self, root: &Path) {
+        if self.keep == 0 {
+            return;
+        }
+        let snapshots = existing(root);
+        let excess = snapshots.len().saturating_sub(self.keep);
+        for (_, path) in snapshots.into_iter().take(excess) {
+            if let Err(err) = fs::remove_dir_all(&path) {
+                warn!("failed to remove old snapshot {}: {err}", path.display());
+            }
+        }
+    }
+}
+
+/// Finished snapshots under `root`, oldest first.
+fn existing(root: &Path) -> Vec<(u64, PathBuf)> {
+    let Ok(entries) = fs::read_dir(root) else {
+        return Vec::new();
+    };
+    let mut snapshots: Vec<(u64, PathBuf)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let sequence = entry.file_name().to_str()?.parse().ok()?;
+            entry
+                .file_type()
+                .ok()?
+                .is_dir()
+                .then(|| (sequence, entry.path()))
+        })
+        .collect();
+    snapshots.sort();
+    snapshots
+}
+
+fn write_json(path: &Path, info: &SnapshotInfo) -> Result<(), MetadataError> {
+    let writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(writer, info)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::carve::CarvedFile;
+    use crate::config;
+    use crate::metadata::{MetadataBackendKind, build_sink};
+
+    fn carved(start: u64) -> CarvedFile {
+        CarvedFile {
+            run_id: "run".to_string(),
+            file_type: "jpeg".to_string(),
+            signature_type: "jpeg".to_string(),
+            path: format!("jpeg/{start}.jpg"),
+            extension: "jpg".to_string(),
+            global_start: start,
+            global_end: start + 99,
+            size: 100,
+            md5: None,
+            sha256: None,
+            validated: true,
+            truncated: false,
+            errors: Vec::new(),
+            pattern_id: None,
+        }
+    }
+
+    fn sink(backend: MetadataBackendKind, run_dir: &Path) -> Box<dyn MetadataSink> {
+        let cfg = config::load_config(None).expect("config").config;
+        build_sink(
+            backend,
+            &cfg,
+            "run",
+            "0.1.0",
+            "hash",
+            Path::new("evidence.dd"),
+            "",
+            run_dir,
+        )
+        .expect("sink")
+    }
+
+    #[test]
+    fn snapshots_hold_what_was_recorded_before_them() {
+        for backend in [MetadataBackendKind::Jsonl, MetadataBackendKind::Csv] {
+            let dir = tempfile::tempdir().expect("tempdir");
+            let sink = sink(backend, dir.path());
+            let mut snapshots =
+                MetadataSnapshots::new("run", dir.path(), Duration::from_secs(900), 0);
+            sink.record_file(&carved(0)).expect("record");
+            sink.record_file(&carved(100)).expect("record");
+            let first = snapshots.take(sink.as_ref()).expect("snapshot");
+            sink.record_file(&carved(200)).expect("record");
+            let second = snapshots.take(sink.as_ref()).expect("snapshot");
+
+            let records = |snapshot: &Path| -> u64 {
+                let info: serde_json::Value = serde_json::from_reader(
+                    File::open(snapshot.join(SNAPSHOT_FILE)).expect("snapshot.json"),
+                )
+                .expect("json");
+                info["files"]
+                    .as_array()
+                    .expect("files")
+                    .iter()
+                    .filter(|file| file["path"].as_str().unwrap().contains("files"))
+                    .map(|file| file["records"].as_u64().unwrap())
+                    .sum()
+            };
+            assert_eq!(records(&first), 2, "{backend:?}");
+            assert_eq!(records(&second), 3, "{backend:?}");
+            let latest: serde_json::Value = serde_json::from_reader(
+                File::open(dir.path().join(SNAPSHOT_DIR).join(LATEST_FILE)).expect("latest"),
+            )
+            .expect("json");
+            assert_eq!(latest["sequence"], 2);
+        }
+    }
+
+    #[test]
+    fn keeps_only_the_newest_snapshots() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let sink = sink(MetadataBackendKind::Jsonl, dir.path());
+        let mut snapshots = MetadataSnapshots::new("run", dir.path(), Duration::ZERO, 2);
+        assert!(snapshots.due(Instant::now()));
+        for _ in 0..3 {
+            snapshots.take(sink.as_ref()).expect("snapshot");
+        }
+        let root = dir.path().join(SNAPSHOT_DIR);
+        let kept: Vec<u64> = existing(&root).into_iter().map(|(seq, _)| seq).collect();
+        assert_eq!(kept, vec![2, 3]);
+
+        // A later pass numbers on from the existing snapshots.
+        let mut later = MetadataSnapshots::new("run", dir.path(), Duration::ZERO, 2);
+        let path = later.take(sink.as_ref()).expect("snapshot");
+        assert!(path.ends_with("0004"));
+    }
+}
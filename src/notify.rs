@@ -0,0 +1,558 @@
+//! Run notifications.
+//!
+//! `notifications` in the config lists hooks that are told when a run
+//! completes, fails, or writes a resume checkpoint, so a long run that ends at
+//! night does not go unnoticed. Every hook receives the same JSON document
+//! ([`Notification`]):
+//! - `webhook`: `POST` to a plain `http://` URL; any 2xx status is success.
+//!   There is no TLS client, so HTTPS endpoints are reached through a `command`
+//!   hook (for example `curl`).
+//! - `email`: a plain SMTP dialogue with a relay, with the JSON as the body.
+//! - `command`: a local program run with the JSON on stdin and the run
+//!   directory (or the configured `args`) on the command line.
+//!
+//! Hooks run one after another on the calling thread, each bounded by its
+//! `timeout_secs`. A failing hook is logged and never fails the run.
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::process::{Command, ExitStatus, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use serde::Serialize;
+use thiserror::Error;
+use tracing::{info, warn};
+
+use crate::config::{NotificationConfig, NotifyEvent};
+use crate::pipeline::PipelineStats;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+const SMTP_PORT: u16 = 25;
+
+/// The document sent to every hook.
+#[derive(Debug, Serialize)]
+pub struct Notification<'a> {
+    pub event: &'static str,
+    pub run_id: &'a str,
+    pub run_dir: &'a Path,
+    pub tool_version: &'static str,
+    pub timestamp: String,
+    /// Pipeline counters; absent when the run failed.
+    pub stats: Option<&'a PipelineStats>,
+    /// Error chain of a failed run.
+    pub error: Option<String>,
+    /// Checkpoint file, for `checkpoint` events.
+    pub checkpoint: Option<&'a Path>,
+    #[serde(skip)]
+    kind: NotifyEvent,
+}
+
+impl<'a> Notification<'a> {
+    fn new(kind: NotifyEvent, run_id: &'a str, run_dir: &'a Path) -> Self {
+        Self {
+            event: kind.as_str(),
+            run_id,
+            run_dir,
+            tool_version: env!("CARGO_PKG_VERSION"),
+            timestamp: Utc::now().to_rfc3339(),
+            stats: None,
+            error: None,
+            checkpoint: None,
+            kind,
+        }
+    }
+
+    pub fn completed(run_id: &'a str, run_dir: &'a Path, stats: &'a PipelineStats) -> Self {
+        Self {
+            stats: Some(stats),
+            ..Self::new(NotifyEvent::Completed, run_id, run_dir)
+        }
+    }
+
+    pub fn failed(run_id: &'a str, run_dir: &'a Path, error: String) -> Self {
+        Self {
+            error: Some(error),
+            ..Self::new(NotifyEvent::Failed, run_id, run_dir)
+        }
+    }
+
+    pub fn checkpoint(
+        run_id: &'a str,
+        run_dir: &'a Path,
+        stats: &'a PipelineStats,
+        path: &'a Path,
+    ) -> Self {
+        Self {
+            stats: Some(stats),
+            checkpoint: Some(path),
+            ..Self::new(NotifyEvent::Checkpoint, run_id, run_dir)
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum NotifyError {
+    #[error("io error: {0}")]
+    Io(#[from] io::Error),
+    #[error("json error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("unsupported webhook url `{0}` (only http:// is supported)")]
+    Url(String),
+    #[error("webhook returned {0}")]
+    Status(String),
+    #[error("smtp relay replied `{0}`")]
+    Smtp(String),
+    #[error("timed out after {0}s")]
+    Timeout(u64),
+    #[error("exited with {0}")]
+    Failed(ExitStatus),
+}
+
+/// Send `note` to every hook subscribed to its event, logging the outcome.
+/// Returns the number of hooks that failed.
+pub fn send(hooks: &[NotificationConfig], note: &Notification) -> usize {
+    let mut failures = 0;
+    for hook in hooks.iter().filter(|h| h.events().contains(&note.kind)) {
+        let target = describe(hook);
+        match deliver(hook, note) {
+            Ok(()) => info!("notified {target} of {}", note.event),
+            Err(err) => {
+                failures += 1;
+                warn!("notification {} to {target} failed: {err}", note.event);
+            }
+        }
+    }
+    failures
+}
+
+fn describe(hook: &NotificationConfig) -> String {
+    match hook {
+        NotificationConfig::Webhook { url, .. } => format!("webhook {url}"),
+        NotificationConfig::Email { to, .. } => format!("email {}", to.join(",")),
+        NotificationConfig::Command { program, .. } => format!("command {program}"),
+    }
+}
+
+fn deliver(hook: &NotificationConfig, note: &Notification) -> Result<(), NotifyError> {
+    match hook {
+        NotificationConfig::Webhook {
+            url, timeout_secs, ..
+        } => post_json(url, &serde_json::to_vec(note)?, *timeout_secs),
+        NotificationConfig::Email {
+            smtp_server,
+            from,
+            to,
+            timeout_secs,
+            ..
+        } => {
+            let subject = format!("[swiftbeaver] run {} {}", note.run_id, note.event);
+            let body = serde_json::to_string_pretty(note)?;
+            send_mail(smtp_server, from, to, &subject, &body, *timeout_secs)
+        }
+        NotificationConfig::Command {
+            program,
+            args,
+            timeout_secs,
+            ..
+        } => run_command(program, args, note, *timeout_secs),
+    }
+}
+
+/// Split `http://host[:port][/path]` into host, port, and path.
+fn parse_http_url(url: &str) -> Result<(&str, u16, &str), NotifyError> {
+    let rest = url
+        .strip_prefix("http://")
+        .ok_or_else(|| NotifyError::Url(url.to_string()))?;
+    let (authority, path) = match rest.find('/') {
+        Some(idx) => rest.split_at(idx),
+        None => (rest, "/"),
+    };
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) => (
+            host,
+            port.parse()
+                .map_err(|_| NotifyError::Url(url.to_string()))?,
+        ),
+        None => (authority, 80),
+    };
+    if host.is_empty() {
+        return Err(NotifyError::Url(url.to_string()));
+    }
+    Ok((host, port, path))
+}
+
+/// Connect with `timeout` applied to the connect and to every read and write.
+fn connect(host: &str, port: u16, timeout: Duration) -> Result<TcpStream, NotifyError> {
+    let mut last_err = io::Error::new(io::ErrorKind::NotFound, format!("{host}: no address"));
+    for addr in (host, port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, timeout) {
+            Ok(stream) => {
+                stream.set_read_timeout(Some(timeout))?;
+                stream.set_write_timeout(Some(timeout))?;
+                return Ok(stream);
+            }
+            Err(err) => last_err = err,
+        }
+    }
+    Err(last_err.into())
+}
+
+fn post_json(url: &str, body: &[u8], timeout_secs: u64) -> Result<(), NotifyError> {
+    let (host, port, path) = parse_http_url(url)?;
+    let mut stream = connect(host, port, Duration::from_secs(timeout_secs))?;
+    write!(
+        stream,
+        "POST {path} HTTP/1.1\r\nHost: {host}:{port}\r\nUser-Agent: swiftbeaver/{}\r\n\
+         Content-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        env!("CARGO_PKG_VERSION"),
+        body.len()
+    )?;
+    stream.write_all(body)?;
+    stream.flush()?;
+
+    let mut status_line = String::new();
+    BufReader::new(stream).read_line(&mut status_line)?;
+    let status = status_line.trim_end();
+    match status.split_whitespace().nth(1) {
+        Some(code) if code.starts_with('2') => Ok(()),
+        _ => Err(NotifyError::Status(status.to_string())),
+    }
+}
+
+fn send_mail(
+    server: &str,
+    from: &str,
+    to: &[String],
+    subject: &str,
+    body: &str,
+    timeout_secs: u64,
+) -> Result<(), NotifyError> {
+    let (host, port) = match server.rsplit_once(':') {
+        Some((host, port)) => (
+            host,
+            port.parse()
+                .map_err(|_| NotifyError::Smtp(format!("bad smtp_server `{server}`")))?,
+        ),
+        None => (server, SMTP_PORT),
+    };
+    let stream = connect(host, port, Duration::from_secs(timeout_secs))?;
+    let mut smtp = Smtp {
+        reader: BufReader::new(stream.try_clone()?),
+        writer: stream,
+    };
+    smtp.expect(None, "220")?;
+    smtp.expect(Some("HELO swiftbeaver"), "250")?;
+    smtp.expect(Some(&format!("MAIL FROM:<{from}>")), "250")?;
+    for rcpt in to {
+        smtp.expect(Some(&format!("RCPT TO:<{rcpt}>")), "25")?;
+    }
+    smtp.expect(Some("DATA"), "354")?;
+    let mut message = format!(
+        "From: <{from}>\r\nTo: {}\r\nSubject: {subject}\r\nDate: {}\r\n\
+         Content-Type: text/plain; charset=utf-8\r\n\r\n",
+        to.iter()
+            .map(|r| format!("<{r}>"))
+            .collect::<Vec<_>>()
+            .join(", "),
+        Utc::now().to_rfc2822()
+    );
+    for line in body.lines() {
+        // Dot-stuffing: a leading '.' is doubled so it cannot end the message.
+        if line.starts_with('.') {
+            message.push('.');
+        }
+        message.push_str(line);
+        message.push_str("\r\n");
+    }
+    message.push('.');
+    smtp.expect(Some(&message), "250")?;
+    smtp.expect(Some("QUIT"), "221")
+}
+
+struct Smtp {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl Smtp {
+    /// Send `command` (if any) and check that the final reply line starts
+    /// with `code`. Multi-line replies (`250-...`) are read to the end.
+    fn expect(&mut self, command: Option<&str>, code: &str) -> Result<(), NotifyError> {
+        if let Some(command) = command {
+            write!(self.writer, "{command}\r\n")?;
+            self.writer.flush()?;
+        }
+        loop {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Err(NotifyError::Smtp("connection closed".to_string()));
+            }
+            let line = line.trim_end();
+            if line.as_bytes().get(3) == Some(&b'-') {
+                continue;
+            }
+            return if line.starts_with(code) {
+                Ok(())
+            } else {
+                Err(NotifyError::Smtp(line.to_string()))
+            };
+        }
+    }
+}
+
+fn run_command(
+    program: &str,
+    args: &[String],
+    note: &Notification,
+    timeout_secs: u64,
+) -> Result<(), NotifyError> {
+    let run_dir = note.run_dir.to_string_lossy();
+    let args: Vec<String> = if args.is_empty() {
+        vec![run_dir.to_string()]
+    } else {
+        args.iter()
+            .map(|arg| {
+                arg.replace("{run_dir}", &run_dir)
+                    .replace("{run_id}", note.run_id)
+                    .replace("{event}", note.event)
+            })
+            .collect()
+    };
+    let mut child = Command::new(program)
+        .args(&args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // A program that ignores stdin may exit before reading it.
+        let _ = serde_json::to_writer(&mut stdin, note);
+    }
+
+    let deadline = Instant::now() + Duration::from_secs(timeout_secs);
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(NotifyError::Timeout(timeout_secs));
+        }
+        thread::sleep(POLL_INTERVAL);
+    };
+    if status.success() {
+        Ok(())
+    } else {
+        Err(NotifyError::Failed(status))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::net::TcpListener;
+
+    fn stats() -> PipelineStats {
+        PipelineStats {
+            bytes_scanned: 4096,
+            chunks_processed: 1,
+            hits_found: 3,
+            files_carved: 2,
+            string_spans: 0,
+            artefacts_extracted: 0,
+            read_errors: 0,
+            carve_errors: 0,
+            metadata_errors: 0,
+            worker_panics: 0,
+            hits_suppressed: 0,
+            hits_prefiltered: 0,
+            carves_suppressed: 0,
+            patterns_disabled: Vec::new(),
+            hits_pattern_disabled: 0,
+            lznt1_files: 0,
+            shadow_files: 0,
+            postprocess_errors: 0,
+            temp_space: Default::default(),
+            signature_backends: Default::default(),
+            string_backends: Default::default(),
+            stop_reason: Default::default(),
+            hits_abandoned: 0,
+            checkpoint: None,
+            evidence_hashes: None,
+            gpu_transfer: None,
+        }
+    }
+
+    #[test]
+    fn posts_notification_to_webhook() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+        let port = listener.local_addr().expect("addr").port();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().expect("accept");
+            let mut reader = BufReader::new(stream.try_clone().expect("clone"));
+            let mut head = Vec::new();
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).expect("header");
+                if let Some(value) = line.strip_prefix("Content-Length: ") {
+                    length = value.trim().parse().expect("length");
+                }
+                if line == "\r\n" {
+                    break;
+                }
+                head.push(line);
+            }
+            let mut body = vec![0u8; length];
+            reader.read_exact(&mut body).expect("body");
+            stream
+                .write_all(b"HTTP/1.1 204 No Content\r\n\r\n")
+                .expect("reply");
+            (head, body)
+        });
+
+        let stats = stats();
+        let run_dir = Path::new("/cases/out/run1");
+        let note = Notification::completed("run1", run_dir, &stats);
+        let hooks = vec![
+            NotificationConfig::Webhook {
+                url: format!("http://127.0.0.1:{port}/hooks/done"),
+                on: vec![NotifyEvent::Completed],
+                timeout_secs: 5,
+            },
+            // Not subscribed to `completed`, so never contacted.
+            NotificationConfig::Webhook {
+                url: "http://127.0.0.1:1/".to_string(),
+                on: vec![NotifyEvent::Failed],
+                timeout_secs: 5,
+            },
+        ];
+        assert_eq!(send(&hooks, &note), 0);
+
+        let (head, body) = server.join().expect("server");
+        assert_eq!(head[0], "POST /hooks/done HTTP/1.1\r\n");
+        let json: serde_json::Value = serde_json::from_slice(&body).expect("json");
+        assert_eq!(json["event"], "completed");
+        assert_eq!(json["run_dir"], "/cases/out/run1");
+        assert_eq!(json["stats"]["files_carved"], 2);
+    }
+
+    #[test]
+    fn sends_mail_through_smtp_relay() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+        let port = listener.local_addr().expect("addr").port();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().expect("accept");
+            let mut reader = BufReader::new(stream.try_clone().expect("clone"));
+            let mut transcript = Vec::new();
+            stream.write_all(b"220 relay ready\r\n").expect("greet");
+            let mut in_data = false;
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).expect("read") == 0 {
+                    break;
+                }
+                let line = line.trim_end().to_string();
+                let reply: &[u8] = if in_data {
+                    if line == "." {
+                        in_data = false;
+                        b"250 queued\r\n"
+                    } else {
+                        transcript.push(line);
+                        continue;
+                    }
+                } else if line.starts_with("HELO") {
+                    b"250-relay\r\n250 ok\r\n"
+                } else if line == "DATA" {
+                    in_data = true;
+                    b"354 go ahead\r\n"
+                } else if line == "QUIT" {
+                    stream.write_all(b"221 bye\r\n").expect("bye");
+                    break;
+                } else {
+                    b"250 ok\r\n"
+                };
+                if !in_data || line == "DATA" {
+                    transcript.push(line);
+                }
+                stream.write_all(reply).expect("reply");
+            }
+            transcript
+        });
+
+        let note = Notification::failed("run2", Path::new("/out/run2"), "disk full".to_string());
+        let hooks = vec![NotificationConfig::Email {
+            smtp_server: format!("127.0.0.1:{port}"),
+            from: "carver@lab.local".to_string(),
+            to: vec!["oncall@lab.local".to_string()],
+            on: vec![NotifyEvent::Failed],
+            timeout_secs: 5,
+        }];
+        assert_eq!(send(&hooks, &note), 0);
+
+        let transcript = server.join().expect("server");
+        assert!(transcript.contains(&"MAIL FROM:<carver@lab.local>".to_string()));
+        assert!(transcript.contains(&"RCPT TO:<oncall@lab.local>".to_string()));
+        assert!(transcript.contains(&"Subject: [swiftbeaver] run run2 failed".to_string()));
+        assert!(
+            transcript
+                .iter()
+                .any(|l| l.contains("\"error\": \"disk full\""))
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn runs_command_with_run_dir_and_reports_failures() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let out = dir.path().join("out.txt");
+        let stats = stats();
+        let checkpoint = dir.path().join("run.ckpt");
+        let note = Notification::checkpoint("run3", dir.path(), &stats, &checkpoint);
+        let hooks = vec![NotificationConfig::Command {
+            program: "sh".to_string(),
+            args: vec![
+                "-c".to_string(),
+                "echo \"$1 $2\" > \"$1/out.txt\"; cat >> \"$1/out.txt\"".to_string(),
+                "sh".to_string(),
+                "{run_dir}".to_string(),
+                "{event}".to_string(),
+            ],
+            on: vec![NotifyEvent::Checkpoint],
+            timeout_secs: 5,
+        }];
+        assert_eq!(send(&hooks, &note), 0);
+        let text = std::fs::read_to_string(&out).expect("output");
+        let (first, json) = text.split_once('\n').expect("two parts");
+        assert_eq!(first, format!("{} checkpoint", dir.path().display()));
+        let json: serde_json::Value = serde_json::from_str(json).expect("json");
+        assert_eq!(json["checkpoint"], checkpoint.to_string_lossy().as_ref());
+
+        let failing = vec![NotificationConfig::Command {
+            program: "false".to_string(),
+            args: Vec::new(),
+            on: vec![NotifyEvent::Checkpoint],
+            timeout_secs: 5,
+        }];
+        assert_eq!(send(&failing, &note), 1);
+    }
+
+    #[test]
+    fn parses_http_urls_only() {
+        assert_eq!(
+            parse_http_url("http://hooks.local:8080/run").expect("url"),
+            ("hooks.local", 8080, "/run")
+        );
+        assert_eq!(
+            parse_http_url("http://hooks.local").expect("url"),
+            ("hooks.local", 80, "/")
+        );
+        assert!(parse_http_url("https://hooks.local/").is_err());
+        assert!(parse_http_url("http://:80/").is_err());
+    }
+}
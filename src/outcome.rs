@@ -0,0 +1,251 @@
+//! Terminal state of a run for automation.
+//!
+//! The binary maps how a run ended to a [`RunStatus`], exits with its
+//! [`RunStatus::exit_code`], and writes the same state to [`RESULT_FILE`] in
+//! the run directory, so orchestration scripts can branch without parsing
+//! logs:
+//!
+//! | code | status | meaning |
+//! |------|--------|---------|
+//! | 0 | `success` | every chunk scanned, no errors |
+//! | 1 | `failed` | any other error |
+//! | 2 | `config_error` | invalid configuration or arguments |
+//! | 3 | `completed_with_carve_errors` | finished; some carves or post-processors failed |
+//! | 4 | `cancelled` | stopped by a cancellation; see `checkpoint` to resume |
+//! | 5 | `evidence_read_failure` | evidence could not be opened, hashed, or fully read |
+//! | 6 | `sink_failure` | metadata could not be written |
+//!
+//! A run that stops at `--max-bytes`, `--max-chunks`, or `max_files` did what
+//! was asked and is not an error; its `stop_reason` says which limit ended it.
+//! Errors raised before the pipeline carry their category as anyhow context
+//! (`err.context(RunStatus::ConfigError)`); [`RunStatus::of_error`] reads it
+//! back.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chrono::Utc;
+use serde::Serialize;
+
+use crate::pipeline::{PipelineStats, StopReason};
+
+/// Result file name inside the run output directory.
+pub const RESULT_FILE: &str = "result.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    Success,
+    Failed,
+    ConfigError,
+    CompletedWithCarveErrors,
+    Cancelled,
+    EvidenceReadFailure,
+    SinkFailure,
+}
+
+impl RunStatus {
+    pub fn exit_code(self) -> u8 {
+        match self {
+            Self::Success => 0,
+            Self::Failed => 1,
+            Self::ConfigError => 2,
+            Self::CompletedWithCarveErrors => 3,
+            Self::Cancelled => 4,
+            Self::EvidenceReadFailure => 5,
+            Self::SinkFailure => 6,
+        }
+    }
+
+    /// Status of a pipeline run that returned. Evidence read errors and
+    /// metadata errors lose data outright and rank above a cancellation;
+    /// carve and post-processor errors (and caught worker panics) only mark
+    /// an otherwise finished run.
+    pub fn of_stats(stats: &PipelineStats) -> Self {
+        if stats.read_errors > 0 {
+            Self::EvidenceReadFailure
+        } else if stats.metadata_errors > 0 {
+            Self::SinkFailure
+        } else if stats.stop_reason == StopReason::Cancelled {
+            Self::Cancelled
+        } else if stats.carve_errors > 0 || stats.postprocess_errors > 0 || stats.worker_panics > 0
+        {
+            Self::CompletedWithCarveErrors
+        } else {
+            Self::Success
+        }
+    }
+
+    /// Category attached to an error as context, or [`RunStatus::Failed`].
+    pub fn of_error(err: &anyhow::Error) -> Self {
+        err.downcast_ref::<RunStatus>()
+            .copied()
+            .unwrap_or(Self::Failed)
+    }
+}
+
+impl fmt::Display for RunStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Success => "success",
+            Self::Failed => "failed",
+            Self::ConfigError => "configuration error",
+            Self::CompletedWithCarveErrors => "completed with carve errors",
+            Self::Cancelled => "cancelled",
+            Self::EvidenceReadFailure => "evidence read failure",
+            Self::SinkFailure => "metadata sink failure",
+        })
+    }
+}
+
+/// Contents of `result.json`.
+#[derive(Debug, Serialize)]
+pub struct RunResult<'a> {
+    pub status: RunStatus,
+    pub exit_code: u8,
+    /// Absent when the run failed before its id was known.
+    pub run_id: Option<&'a str>,
+    pub run_dir: Option<&'a Path>,
+    pub finished_at: String,
+    /// Absent when the pipeline did not return.
+    pub stop_reason: Option<StopReason>,
+    /// Checkpoint to pass to `--resume-from`.
+    pub checkpoint: Option<&'a Path>,
+    /// Error chain of a failed run.
+    pub error: Option<String>,
+    pub stats: Option<&'a PipelineStats>,
+}
+
+impl<'a> RunResult<'a> {
+    pub fn completed(
+        run_id: Option<&'a str>,
+        run_dir: Option<&'a Path>,
+        stats: &'a PipelineStats,
+    ) -> Self {
+        let status = RunStatus::of_stats(stats);
+        Self {
+            status,
+            exit_code: status.exit_code(),
+            run_id,
+            run_dir,
+            finished_at: Utc::now().to_rfc3339(),
+            stop_reason: Some(stats.stop_reason),
+            checkpoint: stats.checkpoint.as_deref(),
+            error: None,
+            stats: Some(stats),
+        }
+    }
+
+    pub fn failed(run_id: Option<&'a str>, run_dir: Option<&'a Path>, err: &anyhow::Error) -> Self {
+        let status = RunStatus::of_error(err);
+        Self {
+            status,
+            exit_code: status.exit_code(),
+            run_id,
+            run_dir,
+            finished_at: Utc::now().to_rfc3339(),
+            stop_reason: None,
+            checkpoint: None,
+            error: Some(format!("{err:#}")),
+            stats: None,
+        }
+    }
+}
+
+/// Write `result` to `path`, replacing any previous result.
+pub fn write_result(path: &Path, result: &RunResult) -> io::Result<PathBuf> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_vec_pretty(result)?)?;
+    fs::rename(&tmp, path)?;
+    Ok(path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::{Context, anyhow};
+
+    fn stats() -> PipelineStats {
+        PipelineStats {
+            bytes_scanned: 4096,
+            chunks_processed: 1,
+            hits_found: 3,
+            files_carved: 2,
+            string_spans: 0,
+            artefacts_extracted: 0,
+            read_errors: 0,
+            carve_errors: 0,
+            metadata_errors: 0,
+            worker_panics: 0,
+            hits_suppressed: 0,
+            hits_prefiltered: 0,
+            carves_suppressed: 0,
+            patterns_disabled: Vec::new(),
+            hits_pattern_disabled: 0,
+            lznt1_files: 0,
+            shadow_files: 0,
+            postprocess_errors: 0,
+            temp_space: Default::default(),
+            signature_backends: Default::default(),
+            string_backends: Default::default(),
+            stop_reason: StopReason::Completed,
+            hits_abandoned: 0,
+            checkpoint: None,
+            evidence_hashes: None,
+            gpu_transfer: None,
+        }
+    }
+
+    #[test]
+    fn ranks_stats_into_statuses() {
+        let mut s = stats();
+        assert_eq!(RunStatus::of_stats(&s), RunStatus::Success);
+        s.stop_reason = StopReason::MaxFiles;
+        assert_eq!(RunStatus::of_stats(&s), RunStatus::Success);
+        s.carve_errors = 1;
+        assert_eq!(RunStatus::of_stats(&s), RunStatus::CompletedWithCarveErrors);
+        s.stop_reason = StopReason::Cancelled;
+        assert_eq!(RunStatus::of_stats(&s), RunStatus::Cancelled);
+        s.metadata_errors = 1;
+        assert_eq!(RunStatus::of_stats(&s), RunStatus::SinkFailure);
+        s.read_errors = 1;
+        assert_eq!(RunStatus::of_stats(&s), RunStatus::EvidenceReadFailure);
+        assert_eq!(RunStatus::of_stats(&s).exit_code(), 5);
+    }
+
+    #[test]
+    fn reads_status_from_error_context() {
+        let err = Err::<(), _>(anyhow!("no such file"))
+            .context(RunStatus::EvidenceReadFailure)
+            .context("open evidence image.dd")
+            .unwrap_err();
+        assert_eq!(RunStatus::of_error(&err), RunStatus::EvidenceReadFailure);
+        assert_eq!(RunStatus::of_error(&anyhow!("boom")), RunStatus::Failed);
+    }
+
+    #[test]
+    fn writes_result_json() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let mut s = stats();
+        s.stop_reason = StopReason::Cancelled;
+        s.checkpoint = Some(dir.path().join("chk.json"));
+        let path = dir.path().join(RESULT_FILE);
+        write_result(
+            &path,
+            &RunResult::completed(Some("run"), Some(dir.path()), &s),
+        )
+        .expect("write");
+        let value: serde_json::Value =
+            serde_json::from_slice(&fs::read(&path).expect("read")).expect("json");
+        assert_eq!(value["status"], "cancelled");
+        assert_eq!(value["exit_code"], 4);
+        assert_eq!(value["stop_reason"], "cancelled");
+        assert!(value["checkpoint"].as_str().unwrap().ends_with("chk.json"));
+        assert_eq!(value["stats"]["files_carved"], 2);
+    }
+}
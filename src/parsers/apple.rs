@@ -0,0 +1,480 @@
+//! Messages and usage events from Apple application databases.
+//!
+//! Recognised by their tables, so carved copies match whatever their file name:
+//! - Apple Notes `NoteStore.sqlite`: note bodies in `ZICNOTEDATA.ZDATA` are
+//!   gzip-compressed protobuf (`NoteStoreProto` → document → note → text).
+//! - iMessage `chat.db`: `message` joined to `handle` and `chat`. Since macOS
+//!   Ventura the text is often only in `attributedBody`, an `NSAttributedString`
+//!   typedstream whose first `NSString` holds the plain text.
+//! - `KnowledgeC.db`: `ZOBJECT` events (`/app/usage`, `/app/inFocus`,
+//!   `/device/isLocked`, ...) with the source bundle from `ZSOURCE`.
+//!
+//! Core Data dates are seconds since 2001-01-01; `chat.db` dates are
+//! nanoseconds since then on iOS 11 / macOS 10.13 and later, seconds before.
+
+use std::path::Path;
+
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use rusqlite::{Connection, OpenFlags};
+use serde::Serialize;
+
+use crate::parsers::inflate;
+use crate::parsers::sqlite_db::{has_table, select_col, table_columns};
+use crate::parsers::time::mac_absolute_to_datetime;
+
+/// Largest decompressed note body accepted.
+const MAX_NOTE_BYTES: usize = 16 * 1024 * 1024;
+
+/// A note or chat message.
+#[derive(Debug, Clone, Serialize)]
+pub struct MessageRecord {
+    pub run_id: String,
+    /// `apple_notes` or `imessage`.
+    pub app: String,
+    /// Note `ZIDENTIFIER` or message `guid`.
+    pub message_id: Option<String>,
+    /// Chat identifier (iMessage) or note title.
+    pub thread: Option<String>,
+    /// Phone number or email of the other party (iMessage).
+    pub handle: Option<String>,
+    pub is_from_me: Option<bool>,
+    /// `iMessage`, `SMS`, ... (iMessage).
+    pub service: Option<String>,
+    pub text: Option<String>,
+    /// Sent (iMessage) or created (note) time.
+    pub timestamp_utc: Option<NaiveDateTime>,
+    /// Last modification (note).
+    pub modified_utc: Option<NaiveDateTime>,
+    /// Carved path of the database.
+    pub source_file: String,
+}
+
+/// A device usage event (KnowledgeC).
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageEventRecord {
+    pub run_id: String,
+    /// `knowledgec`.
+    pub source: String,
+    /// Event stream, e.g. `/app/usage`.
+    pub stream: String,
+    /// Stream value, usually a bundle id.
+    pub value: Option<String>,
+    /// Bundle id of the app that reported the event.
+    pub bundle_id: Option<String>,
+    pub start_utc: Option<NaiveDateTime>,
+    pub end_utc: Option<NaiveDateTime>,
+    pub source_file: String,
+}
+
+/// Records found in one database.
+#[derive(Debug, Default)]
+pub struct AppleArtefacts {
+    pub messages: Vec<MessageRecord>,
+    pub usage_events: Vec<UsageEventRecord>,
+}
+
+/// Read notes, iMessages, and KnowledgeC events from `path`. Databases of
+/// other applications yield no records.
+pub fn extract_apple_artefacts(
+    path: &Path,
+    run_id: &str,
+    source_relative: &str,
+) -> Result<AppleArtefacts> {
+    let conn = Connection::open_with_flags(
+        path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )?;
+    let mut out = AppleArtefacts::default();
+    if has_table(&conn, "ZICNOTEDATA")? && has_table(&conn, "ZICCLOUDSYNCINGOBJECT")? {
+        out.messages
+            .extend(extract_notes(&conn, run_id, source_relative)?);
+    }
+    if has_table(&conn, "message")? && has_table(&conn, "handle")? {
+        out.messages
+            .extend(extract_imessages(&conn, run_id, source_relative)?);
+    }
+    if has_table(&conn, "ZOBJECT")? && has_table(&conn, "ZSTRUCTUREDMETADATA")? {
+        out.usage_events
+            .extend(extract_knowledgec(&conn, run_id, source_relative)?);
+    }
+    Ok(out)
+}
+
+fn extract_notes(
+    conn: &Connection,
+    run_id: &str,
+    source_relative: &str,
+) -> Result<Vec<MessageRecord>> {
+    let columns = table_columns(conn, "ZICCLOUDSYNCINGOBJECT")?;
+    let title = select_col(&columns, &["ZTITLE1", "ZTITLE"], "NULL");
+    let identifier = select_col(&columns, &["ZIDENTIFIER"], "NULL");
+    let created = select_col(
+        &columns,
+        &["ZCREATIONDATE3", "ZCREATIONDATE1", "ZCREATIONDATE"],
+        "NULL",
+    );
+    let modified = select_col(
+        &columns,
+        &["ZMODIFICATIONDATE1", "ZMODIFICATIONDATE"],
+        "NULL",
+    );
+    let query = format!(
+        "SELECT n.{identifier}, n.{title}, n.{created}, n.{modified}, d.ZDATA \
+         FROM ZICNOTEDATA d LEFT JOIN ZICCLOUDSYNCINGOBJECT n ON n.Z_PK = d.ZNOTE"
+    )
+    .replace("n.NULL", "NULL");
+    let mut stmt = conn.prepare(&query)?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, Option<String>>(0)?,
+            row.get::<_, Option<String>>(1)?,
+            row.get::<_, Option<f64>>(2)?,
+            row.get::<_, Option<f64>>(3)?,
+            row.get::<_, Option<Vec<u8>>>(4)?,
+        ))
+    })?;
+
+    let mut out = Vec::new();
+    for row in rows {
+        let (message_id, thread, created, modified, data) = row?;
+        let text = data.as_deref().and_then(note_text);
+        if text.is_none() && thread.is_none() {
+            continue;
+        }
+        out.push(MessageRecord {
+            run_id: run_id.to_string(),
+            app: "apple_notes".to_string(),
+            message_id,
+            thread,
+            handle: None,
+            is_from_me: None,
+            service: None,
+            text,
+            timestamp_utc: created.and_then(mac_absolute_to_datetime),
+            modified_utc: modified.and_then(mac_absolute_to_datetime),
+            source_file: source_relative.to_string(),
+        });
+    }
+    Ok(out)
+}
+
+fn extract_imessages(
+    conn: &Connection,
+    run_id: &str,
+    source_relative: &str,
+) -> Result<Vec<MessageRecord>> {
+    let columns = table_columns(conn, "message")?;
+    let body = select_col(&columns, &["attributedBody"], "NULL");
+    let service = select_col(&columns, &["service"], "NULL");
+    let chats = has_table(conn, "chat")? && has_table(conn, "chat_message_join")?;
+    let (chat_col, chat_join) = if chats {
+        (
+            "c.chat_identifier",
+            " LEFT JOIN chat_message_join cmj ON cmj.message_id = m.ROWID \
+             LEFT JOIN chat c ON c.ROWID = cmj.chat_id",
+        )
+    } else {
+        ("NULL", "")
+    };
+    let query = format!(
+        "SELECT m.guid, m.text, m.{body}, m.date, m.is_from_me, m.{service}, h.id, {chat_col} \
+         FROM message m LEFT JOIN handle h ON h.ROWID = m.handle_id{chat_join}"
+    )
+    .replace("m.NULL", "NULL");
+    let mut stmt = conn.prepare(&query)?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, Option<String>>(0)?,
+            row.get::<_, Option<String>>(1)?,
+            row.get::<_, Option<Vec<u8>>>(2)?,
+            row.get::<_, Option<i64>>(3)?,
+            row.get::<_, Option<i64>>(4)?,
+            row.get::<_, Option<String>>(5)?,
+            row.get::<_, Option<String>>(6)?,
+            row.get::<_, Option<String>>(7)?,
+        ))
+    })?;
+
+    let mut out = Vec::new();
+    for row in rows {
+        let (guid, text, body, date, is_from_me, service, handle, chat) = row?;
+        let text = text
+            .filter(|t| !t.trim().is_empty())
+            .or_else(|| body.as_deref().and_then(attributed_body_text));
+        out.push(MessageRecord {
+            run_id: run_id.to_string(),
+            app: "imessage".to_string(),
+            message_id: guid,
+            thread: chat,
+            handle: handle.filter(|h| !h.is_empty()),
+            is_from_me: is_from_me.map(|v| v != 0),
+            service,
+            text,
+            timestamp_utc: date.and_then(imessage_date),
+            modified_utc: None,
+            source_file: source_relative.to_string(),
+        });
+    }
+    Ok(out)
+}
+
+fn extract_knowledgec(
+    conn: &Connection,
+    run_id: &str,
+    source_relative: &str,
+) -> Result<Vec<UsageEventRecord>> {
+    let columns = table_columns(conn, "ZOBJECT")?;
+    let value = select_col(&columns, &["ZVALUESTRING"], "NULL");
+    let sources = has_table(conn, "ZSOURCE")? && columns.contains("zsource");
+    let (bundle_col, source_join) = if sources {
+        ("s.ZBUNDLEID", " LEFT JOIN ZSOURCE s ON s.Z_PK = o.ZSOURCE")
+    } else {
+        ("NULL", "")
+    };
+    let query = format!(
+        "SELECT o.ZSTREAMNAME, o.{value}, {bundle_col}, o.ZSTARTDATE, o.ZENDDATE \
+         FROM ZOBJECT o{source_join} WHERE o.ZSTREAMNAME IS NOT NULL"
+    )
+    .replace("o.NULL", "NULL");
+    let mut stmt = conn.prepare(&query)?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, Option<String>>(1)?,
+            row.get::<_, Option<String>>(2)?,
+            row.get::<_, Option<f64>>(3)?,
+            row.get::<_, Option<f64>>(4)?,
+        ))
+    })?;
+
+    let mut out = Vec::new();
+    for row in rows {
+        let (stream, value, bundle_id, start, end) = row?;
+        out.push(UsageEventRecord {
+            run_id: run_id.to_string(),
+            source: "knowledgec".to_string(),
+            stream,
+            value,
+            bundle_id,
+            start_utc: start.and_then(mac_absolute_to_datetime),
+            end_utc: end.and_then(mac_absolute_to_datetime),
+            source_file: source_relative.to_string(),
+        });
+    }
+    Ok(out)
+}
+
+/// `chat.db` dates: nanoseconds since 2001 on current systems, seconds on old ones.
+fn imessage_date(value: i64) -> Option<NaiveDateTime> {
+    if value > 1_000_000_000_000 {
+        mac_absolute_to_datetime(value as f64 / 1e9)
+    } else {
+        mac_absolute_to_datetime(value as f64)
+    }
+}
+
+/// Text of a gzip-compressed `NoteStoreProto` (field 2 → 3 → 2).
+pub fn note_text(data: &[u8]) -> Option<String> {
+    let proto = inflate::gunzip(data, MAX_NOTE_BYTES).ok()?;
+    let document = proto_field(&proto, 2)?;
+    let note = proto_field(document, 3)?;
+    let text = proto_field(note, 2)?;
+    Some(String::from_utf8_lossy(text).into_owned())
+}
+
+/// First length-delimited field `number` of a protobuf message.
+fn proto_field(mut buf: &[u8], number: u64) -> Option<&[u8]> {
+    while !buf.is_empty() {
+        let key = read_varint(&mut buf)?;
+        let len = match key & 7 {
+            0 => {
+                read_varint(&mut buf)?;
+                0
+            }
+            1 => 8,
+            2 => usize::try_from(read_varint(&mut buf)?).ok()?,
+            5 => 4,
+            _ => return None,
+        };
+        let value = buf.get(..len)?;
+        if key >> 3 == number && key & 7 == 2 {
+            return Some(value);
+        }
+        buf = &buf[len..];
+    }
+    None
+}
+
+fn read_varint(buf: &mut &[u8]) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = buf.split_first()?;
+        *buf = rest;
+        value |= u64::from(byte & 0x7F) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// Plain text of an `attributedBody` typedstream: the string that follows the
+/// first `NSString` class reference.
+pub fn attributed_body_text(data: &[u8]) -> Option<String> {
+    let class = memchr::memmem::find(data, b"NSString")?;
+    let rest = &data[class + b"NSString".len()..];
+    // Class chain terminator, then the `+` type tag of the string value.
+    let tag = rest.iter().take(8).position(|&b| b == b'+')?;
+    let rest = &rest[tag + 1..];
+    let (&marker, rest) = rest.split_first()?;
+    let (len, rest) = match marker {
+        0x81 => (
+            usize::from(u16::from_le_bytes(rest.get(..2)?.try_into().ok()?)),
+            &rest[2..],
+        ),
+        0x82 => (
+            usize::try_from(u32::from_le_bytes(rest.get(..4)?.try_into().ok()?)).ok()?,
+            &rest[4..],
+        ),
+        len if len < 0x80 => (usize::from(len), rest),
+        _ => return None,
+    };
+    let text = std::str::from_utf8(rest.get(..len)?).ok()?;
+    Some(text.to_string())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use chrono::Timelike;
+    use tempfile::tempdir;
+
+    /// Length-delimited protobuf field.
+    fn field(number: u8, value: &[u8]) -> Vec<u8> {
+        let mut out = vec![number << 3 | 2];
+        let mut len = value.len();
+        while len >= 0x80 {
+            out.push((len as u8 & 0x7F) | 0x80);
+            len >>= 7;
+        }
+        out.push(len as u8);
+        out.extend_from_slice(value);
+        out
+    }
+
+    /// `NoteStoreProto` for `text`, stored gzip-wrapped with stored blocks.
+    pub(crate) fn note_blob(text: &str) -> Vec<u8> {
+        let mut note = vec![0x08, 0x00]; // field 1 varint, skipped
+        note.extend(field(2, text.as_bytes()));
+        let document = [vec![0x08, 0x00], field(3, &note)].concat();
+        let proto = [vec![0x08, 0x00], field(2, &document)].concat();
+        let mut gz = vec![0x1F, 0x8B, 8, 0, 0, 0, 0, 0, 0, 3];
+        for (i, block) in proto.chunks(0xFFFF).enumerate() {
+            let last = i == proto.len().div_ceil(0xFFFF) - 1;
+            gz.push(u8::from(last));
+            gz.extend((block.len() as u16).to_le_bytes());
+            gz.extend((!(block.len() as u16)).to_le_bytes());
+            gz.extend_from_slice(block);
+        }
+        gz.extend([0u8; 8]);
+        gz
+    }
+
+    fn attributed_body(text: &str) -> Vec<u8> {
+        let mut out = b"\x04\x0bstreamtyped\x81\xe8\x03\x84\x01@\x84\x84\x84\x12NSAttributedString\x00\x84\x84\x08NSObject\x00\x85\x92\x84\x84\x84\x08NSString\x01\x94\x84\x01+".to_vec();
+        if text.len() < 0x80 {
+            out.push(text.len() as u8);
+        } else {
+            out.push(0x81);
+            out.extend((text.len() as u16).to_le_bytes());
+        }
+        out.extend_from_slice(text.as_bytes());
+        out.extend_from_slice(b"\x86\x84\x02iI\x01\x05");
+        out
+    }
+
+    #[test]
+    fn decodes_note_and_attributed_body() {
+        assert_eq!(
+            note_text(&note_blob("Shopping\nmilk")).as_deref(),
+            Some("Shopping\nmilk")
+        );
+        assert!(note_text(b"not gzip").is_none());
+        assert_eq!(
+            attributed_body_text(&attributed_body("See you at 8")).as_deref(),
+            Some("See you at 8")
+        );
+        let long = "x".repeat(300);
+        assert_eq!(attributed_body_text(&attributed_body(&long)), Some(long));
+    }
+
+    #[test]
+    fn extracts_notes_messages_and_usage() {
+        let dir = tempdir().expect("tempdir");
+        let path = dir.path().join("apple.sqlite");
+        let conn = Connection::open(&path).expect("conn");
+        conn.execute_batch(
+            "CREATE TABLE ZICCLOUDSYNCINGOBJECT (Z_PK INTEGER PRIMARY KEY, ZIDENTIFIER TEXT, ZTITLE1 TEXT, ZCREATIONDATE1 REAL, ZMODIFICATIONDATE1 REAL);
+             CREATE TABLE ZICNOTEDATA (Z_PK INTEGER PRIMARY KEY, ZNOTE INTEGER, ZDATA BLOB);
+             INSERT INTO ZICCLOUDSYNCINGOBJECT VALUES (1, 'A1B2', 'Shopping', 700000000.5, 700000100.0);
+             CREATE TABLE handle (ROWID INTEGER PRIMARY KEY, id TEXT);
+             CREATE TABLE message (ROWID INTEGER PRIMARY KEY, guid TEXT, text TEXT, attributedBody BLOB, handle_id INTEGER, service TEXT, date INTEGER, is_from_me INTEGER);
+             CREATE TABLE chat (ROWID INTEGER PRIMARY KEY, chat_identifier TEXT);
+             CREATE TABLE chat_message_join (chat_id INTEGER, message_id INTEGER);
+             INSERT INTO handle VALUES (1, '+15555550123');
+             INSERT INTO chat VALUES (1, 'chat123');
+             INSERT INTO message VALUES (1, 'G-1', 'hi there', NULL, 1, 'iMessage', 700000000000000000, 0);
+             INSERT INTO chat_message_join VALUES (1, 1);
+             CREATE TABLE ZSOURCE (Z_PK INTEGER PRIMARY KEY, ZBUNDLEID TEXT);
+             CREATE TABLE ZSTRUCTUREDMETADATA (Z_PK INTEGER PRIMARY KEY);
+             CREATE TABLE ZOBJECT (Z_PK INTEGER PRIMARY KEY, ZSTREAMNAME TEXT, ZVALUESTRING TEXT, ZSOURCE INTEGER, ZSTARTDATE REAL, ZENDDATE REAL);
+             INSERT INTO ZSOURCE VALUES (1, 'com.apple.springboard');
+             INSERT INTO ZOBJECT VALUES (1, '/app/usage', 'com.apple.MobileSMS', 1, 700000000, 700000060);",
+        )
+        .expect("schema");
+        conn.execute(
+            "INSERT INTO ZICNOTEDATA VALUES (1, 1, ?1)",
+            [note_blob("milk, eggs")],
+        )
+        .expect("note");
+        conn.execute(
+            "INSERT INTO message VALUES (2, 'G-2', NULL, ?1, 1, 'iMessage', 700000060, 1)",
+            [attributed_body("from body")],
+        )
+        .expect("message");
+        drop(conn);
+
+        let found = extract_apple_artefacts(&path, "run", "sqlite/x.sqlite").expect("extract");
+        let note = &found.messages[0];
+        assert_eq!(note.app, "apple_notes");
+        assert_eq!(note.thread.as_deref(), Some("Shopping"));
+        assert_eq!(note.text.as_deref(), Some("milk, eggs"));
+        assert_eq!(
+            note.timestamp_utc.map(|t| t.to_string()).as_deref(),
+            Some("2023-03-08 20:26:40.500")
+        );
+
+        let messages: Vec<_> = found
+            .messages
+            .iter()
+            .filter(|m| m.app == "imessage")
+            .collect();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].handle.as_deref(), Some("+15555550123"));
+        assert_eq!(messages[0].thread.as_deref(), Some("chat123"));
+        assert_eq!(messages[0].is_from_me, Some(false));
+        assert_eq!(
+            messages[0].timestamp_utc,
+            note.timestamp_utc.map(|t| t.with_nanosecond(0).unwrap())
+        );
+        assert_eq!(messages[1].text.as_deref(), Some("from body"));
+        assert_eq!(messages[1].thread, None);
+
+        let usage = &found.usage_events[0];
+        assert_eq!(usage.stream, "/app/usage");
+        assert_eq!(usage.value.as_deref(), Some("com.apple.MobileSMS"));
+        assert_eq!(usage.bundle_id.as_deref(), Some("com.apple.springboard"));
+        assert!(usage.end_utc > usage.start_utc);
+    }
+}
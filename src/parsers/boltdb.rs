@@ -0,0 +1,324 @@
+//! bbolt database parsing (containerd `meta.db`, Docker volume and network
+//! stores).
+//!
+//! A bolt file is a sequence of fixed-size pages. Pages 0 and 1 hold meta
+//! records; the one with the higher transaction ID and a valid checksum names
+//! the root bucket. Buckets are B+trees of branch and leaf pages; small buckets
+//! are stored inline in their parent's leaf value.
+
+/// Meta magic (`0xED0CDAED`), stored little-endian after the page header.
+pub const BOLT_MAGIC: u32 = 0xED0C_DAED;
+/// Offset of the meta magic within a meta page.
+pub const META_MAGIC_OFFSET: usize = 16;
+/// Page header plus meta record, the bytes needed by [`BoltMeta::parse`].
+pub const META_PAGE_LEN: usize = 80;
+
+const PAGE_HEADER_LEN: usize = 16;
+const ELEMENT_LEN: usize = 16;
+const BUCKET_HEADER_LEN: usize = 16;
+const BRANCH_PAGE: u16 = 0x01;
+const LEAF_PAGE: u16 = 0x02;
+const BUCKET_LEAF_FLAG: u32 = 0x01;
+const MAX_DEPTH: usize = 32;
+
+/// Meta record of a bolt file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoltMeta {
+    pub page_id: u64,
+    pub page_size: u32,
+    pub root: u64,
+    /// High-water mark: number of pages in the file.
+    pub page_count: u64,
+    pub txid: u64,
+}
+
+impl BoltMeta {
+    /// Parse a meta page (page header first). The checksum must match.
+    pub fn parse(page: &[u8]) -> Option<Self> {
+        if page.len() < META_PAGE_LEN || read_u32(page, META_MAGIC_OFFSET)? != BOLT_MAGIC {
+            return None;
+        }
+        if read_u32(page, 20)? != 2 {
+            return None;
+        }
+        let page_size = read_u32(page, 24)?;
+        if !(512..=65536).contains(&page_size) || !page_size.is_power_of_two() {
+            return None;
+        }
+        if fnv1a64(&page[16..72]) != read_u64(page, 72)? {
+            return None;
+        }
+        Some(Self {
+            page_id: read_u64(page, 0)?,
+            page_size,
+            root: read_u64(page, 32)?,
+            page_count: read_u64(page, 56)?,
+            txid: read_u64(page, 64)?,
+        })
+    }
+
+    /// On-disk size implied by the high-water mark.
+    pub fn file_size(&self) -> u64 {
+        self.page_count.saturating_mul(self.page_size as u64)
+    }
+}
+
+/// The newest valid meta record of a bolt file.
+pub fn current_meta(data: &[u8]) -> Option<BoltMeta> {
+    let first = BoltMeta::parse(data)?;
+    let second = data
+        .get(first.page_size as usize..)
+        .and_then(BoltMeta::parse);
+    Some(match second {
+        Some(second) if second.txid > first.txid => second,
+        _ => first,
+    })
+}
+
+/// A key/value pair found while walking a bolt file.
+pub struct BoltPair<'a> {
+    /// Names of the enclosing buckets, outermost first.
+    pub buckets: &'a [&'a [u8]],
+    pub key: &'a [u8],
+    pub value: &'a [u8],
+    /// File offset of the leaf page holding the pair.
+    pub page_offset: u64,
+}
+
+/// Visit every non-bucket key/value pair reachable from the root bucket.
+/// Pages outside `data` are skipped; corrupt page references that form cycles
+/// stop at a depth limit and a page budget proportional to the file size.
+pub fn walk(data: &[u8], visit: &mut dyn FnMut(&BoltPair)) -> Option<BoltMeta> {
+    let meta = current_meta(data)?;
+    let mut walker = Walker {
+        data,
+        page_size: meta.page_size as usize,
+        pages_left: (data.len() / meta.page_size as usize).saturating_mul(2) + 16,
+        visit,
+    };
+    walker.bucket(meta.root, None, &mut Vec::new(), 0);
+    Some(meta)
+}
+
+struct Walker<'a, 'v> {
+    data: &'a [u8],
+    page_size: usize,
+    pages_left: usize,
+    visit: &'v mut dyn FnMut(&BoltPair),
+}
+
+impl<'a> Walker<'a, '_> {
+    fn bucket(
+        &mut self,
+        root: u64,
+        inline: Option<(&'a [u8], u64)>,
+        path: &mut Vec<&'a [u8]>,
+        depth: usize,
+    ) {
+        if depth > MAX_DEPTH {
+            return;
+        }
+        match inline {
+            Some((page, page_offset)) => self.page(page, page_offset, path, depth),
+            None => {
+                if self.pages_left == 0 {
+                    return;
+                }
+                self.pages_left -= 1;
+                let Some(offset) = (root as usize).checked_mul(self.page_size) else {
+                    return;
+                };
+                if let Some(page) = self.data.get(offset..) {
+                    self.page(page, offset as u64, path, depth);
+                }
+            }
+        }
+    }
+
+    fn page(&mut self, page: &'a [u8], page_offset: u64, path: &mut Vec<&'a [u8]>, depth: usize) {
+        if page.len() < PAGE_HEADER_LEN || depth > MAX_DEPTH {
+            return;
+        }
+        let flags = u16::from_le_bytes([page[8], page[9]]);
+        let count = u16::from_le_bytes([page[10], page[11]]) as usize;
+        for i in 0..count {
+            let elem = PAGE_HEADER_LEN + i * ELEMENT_LEN;
+            if flags & BRANCH_PAGE != 0 {
+                let Some(child) = read_u64(page, elem + 8) else {
+                    return;
+                };
+                self.bucket(child, None, path, depth + 1);
+            } else if flags & LEAF_PAGE != 0 {
+                let (Some(elem_flags), Some(pos), Some(ksize), Some(vsize)) = (
+                    read_u32(page, elem),
+                    read_u32(page, elem + 4),
+                    read_u32(page, elem + 8),
+                    read_u32(page, elem + 12),
+                ) else {
+                    return;
+                };
+                let key_start = elem + pos as usize;
+                let value_start = key_start + ksize as usize;
+                let (Some(key), Some(value)) = (
+                    page.get(key_start..value_start),
+                    page.get(value_start..value_start + vsize as usize),
+                ) else {
+                    return;
+                };
+                if elem_flags & BUCKET_LEAF_FLAG != 0 {
+                    let (Some(root), Some(inline_page)) =
+                        (read_u64(value, 0), value.get(BUCKET_HEADER_LEN..))
+                    else {
+                        continue;
+                    };
+                    let inline = (root == 0).then_some((inline_page, page_offset));
+                    path.push(key);
+                    self.bucket(root, inline, path, depth + 1);
+                    path.pop();
+                } else {
+                    (self.visit)(&BoltPair {
+                        buckets: path,
+                        key,
+                        value,
+                        page_offset,
+                    });
+                }
+            } else {
+                return;
+            }
+        }
+    }
+}
+
+fn fnv1a64(data: &[u8]) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+    for &b in data {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_le_bytes(bytes.try_into().ok()?))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    let bytes = data.get(offset..offset.checked_add(8)?)?;
+    Some(u64::from_le_bytes(bytes.try_into().ok()?))
+}
+
+/// Test input for bolt files: a value or a nested (inline) bucket.
+#[cfg(test)]
+pub(crate) enum BoltValue<'a> {
+    Value(&'a [u8]),
+    Bucket(Vec<(&'a str, BoltValue<'a>)>),
+}
+
+/// Build a bolt file with 4 KiB pages whose root bucket holds `entries`.
+/// Nested buckets are stored inline.
+#[cfg(test)]
+pub(crate) fn bolt_bytes(entries: Vec<(&str, BoltValue)>) -> Vec<u8> {
+    const PAGE: usize = 4096;
+    fn leaf_page(entries: Vec<(&str, BoltValue)>) -> Vec<u8> {
+        let mut elems = Vec::new();
+        let mut kv = Vec::new();
+        let elems_len = entries.len() * ELEMENT_LEN;
+        for (i, (key, value)) in entries.into_iter().enumerate() {
+            let (flags, value) = match value {
+                BoltValue::Value(v) => (0u32, v.to_vec()),
+                BoltValue::Bucket(children) => {
+                    let mut v = vec![0u8; BUCKET_HEADER_LEN];
+                    v.extend_from_slice(&leaf_page(children));
+                    (BUCKET_LEAF_FLAG, v)
+                }
+            };
+            let pos = (elems_len - i * ELEMENT_LEN + kv.len()) as u32;
+            elems.extend_from_slice(&flags.to_le_bytes());
+            elems.extend_from_slice(&pos.to_le_bytes());
+            elems.extend_from_slice(&(key.len() as u32).to_le_bytes());
+            elems.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            kv.extend_from_slice(key.as_bytes());
+            kv.extend_from_slice(&value);
+        }
+        let mut page = vec![0u8; PAGE_HEADER_LEN];
+        page[8..10].copy_from_slice(&LEAF_PAGE.to_le_bytes());
+        page[10..12].copy_from_slice(&((elems_len / ELEMENT_LEN) as u16).to_le_bytes());
+        page.extend_from_slice(&elems);
+        page.extend_from_slice(&kv);
+        page
+    }
+
+    let root = leaf_page(entries);
+    assert!(root.len() <= PAGE, "test bolt root exceeds one page");
+    let mut out = vec![0u8; PAGE * 4];
+    for (page_id, txid) in [(0u64, 1u64), (1, 2)] {
+        let meta = &mut out[page_id as usize * PAGE..];
+        meta[0..8].copy_from_slice(&page_id.to_le_bytes());
+        meta[8..10].copy_from_slice(&0x04u16.to_le_bytes());
+        meta[16..20].copy_from_slice(&BOLT_MAGIC.to_le_bytes());
+        meta[20..24].copy_from_slice(&2u32.to_le_bytes());
+        meta[24..28].copy_from_slice(&(PAGE as u32).to_le_bytes());
+        meta[32..40].copy_from_slice(&3u64.to_le_bytes());
+        meta[48..56].copy_from_slice(&2u64.to_le_bytes());
+        meta[56..64].copy_from_slice(&4u64.to_le_bytes());
+        meta[64..72].copy_from_slice(&txid.to_le_bytes());
+        let checksum = fnv1a64(&meta[16..72]);
+        meta[72..80].copy_from_slice(&checksum.to_le_bytes());
+    }
+    out[3 * PAGE..3 * PAGE + root.len()].copy_from_slice(&root);
+    out[3 * PAGE..3 * PAGE + 8].copy_from_slice(&3u64.to_le_bytes());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn walks_nested_inline_buckets() {
+        let data = bolt_bytes(vec![
+            (
+                "outer",
+                BoltValue::Bucket(vec![
+                    ("a", BoltValue::Value(b"1")),
+                    (
+                        "inner",
+                        BoltValue::Bucket(vec![("b", BoltValue::Value(b"2"))]),
+                    ),
+                ]),
+            ),
+            ("top", BoltValue::Value(b"3")),
+        ]);
+        let mut seen = Vec::new();
+        let meta = walk(&data, &mut |pair| {
+            let mut path: Vec<String> = pair
+                .buckets
+                .iter()
+                .map(|b| String::from_utf8_lossy(b).into_owned())
+                .collect();
+            path.push(String::from_utf8_lossy(pair.key).into_owned());
+            seen.push((path.join("/"), pair.value.to_vec(), pair.page_offset));
+        })
+        .expect("bolt");
+        assert_eq!(meta.txid, 2, "newer meta page wins");
+        assert_eq!(meta.file_size(), 4 * 4096);
+        assert_eq!(
+            seen,
+            vec![
+                ("outer/a".to_string(), b"1".to_vec(), 3 * 4096),
+                ("outer/inner/b".to_string(), b"2".to_vec(), 3 * 4096),
+                ("top".to_string(), b"3".to_vec(), 3 * 4096),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_bad_meta_checksum() {
+        let mut data = bolt_bytes(vec![("k", BoltValue::Value(b"v"))]);
+        assert!(BoltMeta::parse(&data).is_some());
+        data[60] ^= 0xFF;
+        assert!(BoltMeta::parse(&data).is_none());
+    }
+}
@@ -0,0 +1,547 @@
+//! Binary property lists (`bplist00`), as written by macOS and iOS.
+//!
+//! A binary plist is the `bplist00` header, the objects, a table with the
+//! offset of each object, and a 32-byte trailer giving the width of the
+//! offsets and object references, the object count, the top object, and
+//! where the offset table starts. Nothing before the trailer gives the
+//! length, so [`find_end`] searches forward for a trailer whose offset table
+//! ends right where it starts and whose offsets all point into the objects.
+//! [`decode`] turns the object graph into JSON: data as base64, dates as
+//! ISO 8601 UTC strings, and `NSKeyedArchiver` UIDs as `{"UID": n}`.
+
+use serde::Serialize;
+use serde_json::{Map, Number, Value};
+
+use crate::parsers::time::mac_absolute_to_datetime;
+
+pub const MAGIC: &[u8; 8] = b"bplist00";
+pub const TRAILER_LEN: u64 = 32;
+/// Bytes searched per read for the trailer.
+const SEARCH_CHUNK: usize = 1024 * 1024;
+/// Object counts above this are taken as garbage.
+const MAX_OBJECTS: u64 = 16 * 1024 * 1024;
+/// Containers nested deeper than this (or referring to themselves) are not
+/// decoded.
+const MAX_DEPTH: usize = 128;
+/// Values emitted before decoding gives up; shared references can make the
+/// JSON form much larger than the plist.
+const MAX_VALUES: usize = 1_000_000;
+/// Keys of the top dictionary listed in the record.
+const MAX_TOP_KEYS: usize = 32;
+
+/// Why a binary plist could not be read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BplistError {
+    /// The data ends before the trailer.
+    Incomplete,
+    Invalid(&'static str),
+}
+
+/// The 32-byte trailer at the end of a binary plist.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Trailer {
+    pub offset_size: u8,
+    pub ref_size: u8,
+    pub object_count: u64,
+    pub top_object: u64,
+    pub offset_table: u64,
+}
+
+impl Trailer {
+    /// The trailer in `bytes`, if its fields are in range.
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        let bytes = bytes.get(..TRAILER_LEN as usize)?;
+        // Five unused bytes and the sort version, all zero.
+        if bytes[..6].iter().any(|byte| *byte != 0) {
+            return None;
+        }
+        let offset_size = bytes[6];
+        let ref_size = bytes[7];
+        let object_count = u64::from_be_bytes(bytes[8..16].try_into().ok()?);
+        let top_object = u64::from_be_bytes(bytes[16..24].try_into().ok()?);
+        let offset_table = u64::from_be_bytes(bytes[24..32].try_into().ok()?);
+        if !(1..=8).contains(&offset_size)
+            || !(1..=8).contains(&ref_size)
+            || object_count == 0
+            || object_count > MAX_OBJECTS
+            || top_object >= object_count
+            || offset_table <= MAGIC.len() as u64
+            || (ref_size < 8 && object_count > 1u64 << (8 * u32::from(ref_size)))
+        {
+            return None;
+        }
+        Some(Self {
+            offset_size,
+            ref_size,
+            object_count,
+            top_object,
+            offset_table,
+        })
+    }
+
+    /// Offset of the trailer itself: the end of the offset table.
+    pub fn offset(&self) -> u64 {
+        self.offset_table
+            .saturating_add(self.object_count * u64::from(self.offset_size))
+    }
+
+    /// Length of the whole plist.
+    pub fn file_len(&self) -> u64 {
+        self.offset().saturating_add(TRAILER_LEN)
+    }
+}
+
+fn be_uint(bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .fold(0u64, |value, byte| (value << 8) | u64::from(*byte))
+}
+
+/// Whether every entry of the offset table points between the header and
+/// the table.
+fn offsets_check_out(read: &mut dyn FnMut(u64, &mut [u8]) -> usize, trailer: &Trailer) -> bool {
+    let width = usize::from(trailer.offset_size);
+    let mut table = vec![0u8; trailer.object_count as usize * width];
+    if read(trailer.offset_table, &mut table) < table.len() {
+        return false;
+    }
+    table.chunks_exact(width).all(|entry| {
+        let offset = be_uint(entry);
+        offset >= MAGIC.len() as u64 && offset < trailer.offset_table
+    })
+}
+
+/// Search the data after the header for the trailer, up to `limit` bytes
+/// from the start of the plist.
+pub fn find_end(
+    read: &mut dyn FnMut(u64, &mut [u8]) -> usize,
+    limit: u64,
+) -> Result<Trailer, BplistError> {
+    let mut magic = [0u8; 8];
+    if read(0, &mut magic) < magic.len() {
+        return Err(BplistError::Incomplete);
+    }
+    if &magic != MAGIC {
+        return Err(BplistError::Invalid("missing bplist00 header"));
+    }
+
+    let overlap = TRAILER_LEN as usize - 1;
+    let mut buf = vec![0u8; SEARCH_CHUNK + overlap];
+    // The smallest plist holds one one-byte object and a one-byte offset.
+    let mut start = MAGIC.len() as u64 + 2;
+    while start + TRAILER_LEN <= limit {
+        let want = ((limit - start) as usize).min(buf.len());
+        let filled = read(start, &mut buf[..want]);
+        if filled < TRAILER_LEN as usize {
+            return Err(BplistError::Incomplete);
+        }
+        for at in 0..=filled - TRAILER_LEN as usize {
+            // Cheap filter on the widths before the full parse.
+            if !(1..=8).contains(&buf[at + 6]) || !(1..=8).contains(&buf[at + 7]) {
+                continue;
+            }
+            let Some(trailer) = Trailer::parse(&buf[at..]) else {
+                continue;
+            };
+            if trailer.offset() == start + at as u64 && offsets_check_out(read, &trailer) {
+                return Ok(trailer);
+            }
+        }
+        if filled < want {
+            return Err(BplistError::Incomplete);
+        }
+        start += (filled - overlap) as u64;
+    }
+    Err(BplistError::Invalid("no trailer within max_size"))
+}
+
+/// Objects of a plist held in memory.
+struct Objects<'a> {
+    data: &'a [u8],
+    trailer: Trailer,
+    values: usize,
+}
+
+impl Objects<'_> {
+    fn offset(&self, index: u64) -> Result<usize, BplistError> {
+        if index >= self.trailer.object_count {
+            return Err(BplistError::Invalid("object reference out of range"));
+        }
+        let width = usize::from(self.trailer.offset_size);
+        let at = self.trailer.offset_table as usize + index as usize * width;
+        let entry = self
+            .data
+            .get(at..at + width)
+            .ok_or(BplistError::Incomplete)?;
+        Ok(be_uint(entry) as usize)
+    }
+
+    fn bytes(&self, at: usize, len: usize) -> Result<&[u8], BplistError> {
+        let end = at.checked_add(len).ok_or(BplistError::Incomplete)?;
+        if end > self.trailer.offset_table as usize {
+            return Err(BplistError::Invalid("object runs into the offset table"));
+        }
+        self.data.get(at..end).ok_or(BplistError::Incomplete)
+    }
+
+    /// The length of a variable-size object (low nibble, or an integer
+    /// object after the marker when it is 0xF) and where its body starts.
+    fn length(&self, marker: u8, at: usize) -> Result<(usize, usize), BplistError> {
+        let nibble = marker & 0x0F;
+        if nibble != 0x0F {
+            return Ok((usize::from(nibble), at + 1));
+        }
+        let int_marker = self.bytes(at + 1, 1)?[0];
+        if int_marker & 0xF0 != 0x10 || int_marker & 0x0F > 3 {
+            return Err(BplistError::Invalid("bad length of a plist object"));
+        }
+        let width = 1usize << (int_marker & 0x0F);
+        let len = be_uint(self.bytes(at + 2, width)?);
+        Ok((len as usize, at + 2 + width))
+    }
+
+    fn refs(&self, at: usize, count: usize) -> Result<Vec<u64>, BplistError> {
+        let width = usize::from(self.trailer.ref_size);
+        let len = count
+            .checked_mul(width)
+            .ok_or(BplistError::Invalid("plist container too large"))?;
+        Ok(self
+            .bytes(at, len)?
+            .chunks_exact(width)
+            .map(be_uint)
+            .collect())
+    }
+
+    fn value(&mut self, index: u64, depth: usize) -> Result<Value, BplistError> {
+        if depth > MAX_DEPTH {
+            return Err(BplistError::Invalid("plist nested too deeply"));
+        }
+        self.values += 1;
+        if self.values > MAX_VALUES {
+            return Err(BplistError::Invalid("plist expands to too many values"));
+        }
+        let at = self.offset(index)?;
+        let marker = self.bytes(at, 1)?[0];
+        let value = match marker >> 4 {
+            0x0 => match marker {
+                0x00 | 0x0F => Value::Null,
+                0x08 => Value::Bool(false),
+                0x09 => Value::Bool(true),
+                _ => return Err(BplistError::Invalid("unknown plist marker")),
+            },
+            0x1 => {
+                if marker & 0x0F > 4 {
+                    return Err(BplistError::Invalid("bad plist integer width"));
+                }
+                let width = 1usize << (marker & 0x0F);
+                let bytes = self.bytes(at + 1, width)?;
+                match width {
+                    1 | 2 | 4 => Value::from(be_uint(bytes)),
+                    // Signed; 128-bit integers keep their low 64 bits, as
+                    // values past them do not occur.
+                    _ => Value::from(be_uint(&bytes[width - 8..]) as i64),
+                }
+            }
+            0x2 => {
+                let real = match marker & 0x0F {
+                    2 => f64::from(f32::from_be_bytes(
+                        self.bytes(at + 1, 4)?.try_into().unwrap_or_default(),
+                    )),
+                    3 => f64::from_be_bytes(self.bytes(at + 1, 8)?.try_into().unwrap_or_default()),
+                    _ => return Err(BplistError::Invalid("bad plist real width")),
+                };
+                Number::from_f64(real).map_or(Value::Null, Value::Number)
+            }
+            0x3 if marker == 0x33 => {
+                let seconds =
+                    f64::from_be_bytes(self.bytes(at + 1, 8)?.try_into().unwrap_or_default());
+                mac_absolute_to_datetime(seconds).map_or(Value::Null, |time| {
+                    Value::String(time.format("%Y-%m-%dT%H:%M:%S%.fZ").to_string())
+                })
+            }
+            0x4 => {
+                let (len, body) = self.length(marker, at)?;
+                Value::String(encode_base64(self.bytes(body, len)?))
+            }
+            0x5 => {
+                let (len, body) = self.length(marker, at)?;
+                let bytes = self.bytes(body, len)?;
+                Value::String(bytes.iter().map(|byte| char::from(*byte)).collect())
+            }
+            0x6 => {
+                let (len, body) = self.length(marker, at)?;
+                let byte_len = len
+                    .checked_mul(2)
+                    .ok_or(BplistError::Invalid("plist string too long"))?;
+                let units: Vec<u16> = self
+                    .bytes(body, byte_len)?
+                    .chunks_exact(2)
+                    .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                    .collect();
+                Value::String(String::from_utf16_lossy(&units))
+            }
+            0x8 => {
+                let width = usize::from(marker & 0x0F) + 1;
+                let uid = be_uint(self.bytes(at + 1, width)?);
+                let mut object = Map::new();
+                object.insert("UID".to_string(), Value::from(uid));
+                Value::Object(object)
+            }
+            0xA | 0xC => {
+                let (count, body) = self.length(marker, at)?;
+                let refs = self.refs(body, count)?;
+                let mut items = Vec::with_capacity(refs.len());
+                for item in refs {
+                    items.push(self.value(item, depth + 1)?);
+                }
+                Value::Array(items)
+            }
+            0xD => {
+                let (count, body) = self.length(marker, at)?;
+                let refs = self.refs(body, count.saturating_mul(2))?;
+                let (keys, values) = refs.split_at(count);
+                let mut object = Map::new();
+                for (key, value) in keys.iter().zip(values) {
+                    let key = match self.value(*key, depth + 1)? {
+                        Value::String(key) => key,
+                        other => other.to_string(),
+                    };
+                    let value = self.value(*value, depth + 1)?;
+                    object.insert(key, value);
+                }
+                Value::Object(object)
+            }
+            _ => return Err(BplistError::Invalid("unknown plist marker")),
+        };
+        Ok(value)
+    }
+}
+
+/// Standard base64 with padding.
+fn encode_base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let triple = chunk.iter().enumerate().fold(0u32, |acc, (i, byte)| {
+            acc | u32::from(*byte) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(char::from(
+                    ALPHABET[(triple >> (18 - 6 * i)) as usize & 0x3F],
+                ));
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// A plist decoded to JSON.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decoded {
+    pub trailer: Trailer,
+    pub value: Value,
+}
+
+impl Decoded {
+    /// Type of the top object (`dict`, `array`, `string`, ...).
+    pub fn top_type(&self) -> &'static str {
+        match &self.value {
+            Value::Object(object) if object.len() == 1 && object.contains_key("UID") => "uid",
+            Value::Object(_) => "dict",
+            Value::Array(_) => "array",
+            Value::String(_) => "string",
+            Value::Number(_) => "number",
+            Value::Bool(_) => "bool",
+            Value::Null => "null",
+        }
+    }
+
+    /// Archiver named by an `NSKeyedArchiver` plist (`$archiver`).
+    pub fn archiver(&self) -> Option<&str> {
+        self.value.get("$archiver").and_then(Value::as_str)
+    }
+
+    pub fn to_record(&self, run_id: &str, max_json_len: usize, source_file: &str) -> PlistRecord {
+        let top_keys = self.value.as_object().map(|object| {
+            object
+                .keys()
+                .take(MAX_TOP_KEYS)
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(",")
+        });
+        let json = self.value.to_string();
+        let json_len = json.len() as u64;
+        PlistRecord {
+            run_id: run_id.to_string(),
+            object_count: self.trailer.object_count,
+            top_type: self.top_type().to_string(),
+            top_keys,
+            archiver: self.archiver().map(str::to_string),
+            json_len,
+            json: (json.len() <= max_json_len).then_some(json),
+            source_file: source_file.to_string(),
+        }
+    }
+}
+
+/// Decode a whole binary plist held in memory.
+pub fn decode(data: &[u8]) -> Result<Decoded, BplistError> {
+    if data.len() < MAGIC.len() + TRAILER_LEN as usize {
+        return Err(BplistError::Incomplete);
+    }
+    if !data.starts_with(MAGIC) {
+        return Err(BplistError::Invalid("missing bplist00 header"));
+    }
+    let trailer = Trailer::parse(&data[data.len() - TRAILER_LEN as usize..])
+        .ok_or(BplistError::Invalid("bad plist trailer"))?;
+    if trailer.file_len() > data.len() as u64 {
+        return Err(BplistError::Incomplete);
+    }
+    let mut objects = Objects {
+        data,
+        trailer,
+        values: 0,
+    };
+    let value = objects.value(trailer.top_object, 0)?;
+    Ok(Decoded { trailer, value })
+}
+
+/// A carved binary plist, decoded for triage.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PlistRecord {
+    pub run_id: String,
+    pub object_count: u64,
+    /// Type of the top object (`dict`, `array`, `string`, ...).
+    pub top_type: String,
+    /// Keys of the top dictionary, comma-separated (first 32).
+    pub top_keys: Option<String>,
+    /// `$archiver` of an `NSKeyedArchiver` plist.
+    pub archiver: Option<String>,
+    /// Length of the JSON form.
+    pub json_len: u64,
+    /// JSON form of the plist; absent when longer than the configured limit.
+    pub json: Option<String>,
+    /// Carved path of the plist.
+    pub source_file: String,
+}
+
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::{MAGIC, TRAILER_LEN};
+
+    /// A plist with a dictionary of a string, an integer, a date, data, a
+    /// UTF-16 string, and a nested array of a bool and a real.
+    pub fn bplist_bytes() -> Vec<u8> {
+        let objects: Vec<Vec<u8>> = vec![
+            // 0: top dict, keys 1..=5 and values 6..=10.
+            [0xD5, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10].to_vec(),
+            b"\x54name".to_vec(),
+            b"\x55count".to_vec(),
+            b"\x58modified".to_vec(),
+            b"\x55token".to_vec(),
+            b"\x55items".to_vec(),
+            // 6: UTF-16 "Café"
+            [0x64, 0, b'C', 0, b'a', 0, b'f', 0, 0xE9].to_vec(),
+            [0x11, 0x01, 0x2C].to_vec(),
+            // 8: 2021-01-01T00:00:00Z is 631152000 s after 2001-01-01.
+            [&[0x33][..], &631_152_000f64.to_be_bytes()].concat(),
+            [0x43, 0xDE, 0xAD, 0xBE].to_vec(),
+            [0xA2, 11, 12].to_vec(),
+            [0x09].to_vec(),
+            [&[0x23][..], &1.5f64.to_be_bytes()].concat(),
+        ];
+        let mut out = MAGIC.to_vec();
+        let mut offsets = Vec::new();
+        for object in &objects {
+            offsets.push(out.len() as u8);
+            out.extend_from_slice(object);
+        }
+        let offset_table = out.len() as u64;
+        out.extend_from_slice(&offsets);
+        let mut trailer = vec![0u8; TRAILER_LEN as usize];
+        trailer[6] = 1;
+        trailer[7] = 1;
+        trailer[8..16].copy_from_slice(&(objects.len() as u64).to_be_bytes());
+        trailer[16..24].copy_from_slice(&0u64.to_be_bytes());
+        trailer[24..32].copy_from_slice(&offset_table.to_be_bytes());
+        out.extend_from_slice(&trailer);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::bplist_bytes;
+    use super::*;
+
+    fn reader(data: &[u8]) -> impl FnMut(u64, &mut [u8]) -> usize + '_ {
+        move |offset, buf| {
+            let start = (offset as usize).min(data.len());
+            let n = buf.len().min(data.len() - start);
+            buf[..n].copy_from_slice(&data[start..start + n]);
+            n
+        }
+    }
+
+    #[test]
+    fn finds_trailer_before_trailing_bytes() {
+        let plist = bplist_bytes();
+        let mut data = plist.clone();
+        data.extend_from_slice(&[0u8; 4096]);
+        let trailer = find_end(&mut reader(&data), data.len() as u64).expect("trailer");
+        assert_eq!(trailer.file_len(), plist.len() as u64);
+        assert_eq!(trailer.object_count, 13);
+
+        let cut = &plist[..plist.len() - 4];
+        assert_eq!(
+            find_end(&mut reader(cut), 1 << 20),
+            Err(BplistError::Incomplete)
+        );
+        assert_eq!(
+            find_end(&mut reader(&data), plist.len() as u64 - 1),
+            Err(BplistError::Invalid("no trailer within max_size"))
+        );
+    }
+
+    #[test]
+    fn decodes_objects_to_json() {
+        let decoded = decode(&bplist_bytes()).expect("decode");
+        assert_eq!(decoded.top_type(), "dict");
+        assert_eq!(
+            decoded.value,
+            serde_json::json!({
+                "name": "Café",
+                "count": 300,
+                "modified": "2021-01-01T00:00:00Z",
+                "token": "3q2+",
+                "items": [true, 1.5],
+            })
+        );
+
+        let record = decoded.to_record("run", 1024, "bplist/a.plist");
+        assert_eq!(
+            record.top_keys.as_deref(),
+            Some("count,items,modified,name,token")
+        );
+        assert_eq!(record.archiver, None);
+        assert!(record.json.is_some());
+        assert!(decoded.to_record("run", 10, "x").json.is_none());
+    }
+
+    #[test]
+    fn rejects_self_referencing_array() {
+        let mut data = bplist_bytes();
+        // Point the nested array's first item back at itself.
+        let trailer = Trailer::parse(&data[data.len() - TRAILER_LEN as usize..]).expect("trailer");
+        let array_at = usize::from(data[trailer.offset_table as usize + 10]);
+        data[array_at + 1] = 10;
+        assert_eq!(
+            decode(&data),
+            Err(BplistError::Invalid("plist nested too deeply"))
+        );
+    }
+}
@@ -29,6 +29,23 @@ pub struct BrowserCookieRecord {
     pub source_file: std::path::PathBuf,
 }
 
+#[derive(Debug, Clone, Serialize)]
+pub struct BrowserCacheRecord {
+    pub run_id: String,
+    pub browser: String,
+    pub url: String,
+    pub http_status: Option<u16>,
+    pub content_type: Option<String>,
+    pub content_encoding: Option<String>,
+    /// Response header lines joined with CRLF.
+    pub response_headers: Option<String>,
+    pub body_size: u64,
+    /// Carved path of the extracted body, when one was written.
+    pub body_path: Option<String>,
+    /// Carved path of the cache entry.
+    pub source_file: std::path::PathBuf,
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct BrowserDownloadRecord {
     pub run_id: String,
@@ -0,0 +1,430 @@
+//! Browser HTTP cache entry parsing.
+//!
+//! Supports two on-disk formats:
+//! - Chromium Simple Cache entry files (`<hash>_0`): a little-endian header
+//!   with the cache key, stream 1 (response body), an EOF record, stream 0
+//!   (pickled response info including the raw headers), and a final EOF record.
+//! - Firefox cache2 entries: the response body first, then big-endian metadata
+//!   (hash chunks, header, key, key/value elements including `response-head`),
+//!   and finally the 4-byte offset of the metadata, which equals the body size.
+
+use std::ops::Range;
+
+/// `kSimpleInitialMagicNumber`, little-endian.
+pub const CHROME_SIMPLE_INITIAL_MAGIC: [u8; 8] = 0xfcfb_6d1b_a772_5c30u64.to_le_bytes();
+/// `kSimpleFinalMagicNumber`, little-endian.
+pub const CHROME_SIMPLE_FINAL_MAGIC: [u8; 8] = 0xf4fa_6f45_970d_41d8u64.to_le_bytes();
+/// `SimpleFileHeader` and `SimpleFileEOF` are both 24 bytes (20 + padding).
+pub const CHROME_SIMPLE_HEADER_LEN: usize = 24;
+pub const CHROME_SIMPLE_EOF_LEN: usize = 24;
+const CHROME_SIMPLE_VERSIONS: std::ops::RangeInclusive<u32> = 5..=9;
+const CHROME_FLAG_HAS_KEY_SHA256: u32 = 2;
+const CHROME_KEY_SHA256_LEN: usize = 32;
+pub const CHROME_MAX_KEY_LEN: usize = 64 * 1024;
+
+/// Firefox hashes entry data in 256 KiB chunks, two bytes per chunk.
+const FIREFOX_CHUNK_SIZE: u64 = 256 * 1024;
+/// `CacheFileMetadataHeader` for versions 2 and 3 (eight big-endian u32 fields).
+const FIREFOX_HEADER_LEN: usize = 32;
+const FIREFOX_VERSIONS: std::ops::RangeInclusive<u32> = 2..=3;
+/// Longest key prefix (origin attributes) searched before a `:http` anchor.
+pub const FIREFOX_MAX_KEY_PREFIX: usize = 1024;
+const FIREFOX_MAX_ELEMENTS: usize = 64;
+/// Element names are short identifiers; anything longer is not metadata.
+const FIREFOX_MAX_ELEMENT_NAME: usize = 128;
+
+/// A reconstructed cached HTTP response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheEntry {
+    pub browser: &'static str,
+    pub key: String,
+    pub url: String,
+    /// Response header lines; the first is the status line.
+    pub headers: Vec<String>,
+    /// Body range within the entry bytes.
+    pub body: Range<usize>,
+    /// Total entry length.
+    pub len: usize,
+}
+
+impl CacheEntry {
+    pub fn http_status(&self) -> Option<u16> {
+        let status_line = self.headers.first()?;
+        if !status_line.starts_with("HTTP/") {
+            return None;
+        }
+        status_line.split_whitespace().nth(1)?.parse().ok()
+    }
+
+    /// Value of the first header named `name` (case-insensitive).
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().skip(1).find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            key.trim().eq_ignore_ascii_case(name).then(|| value.trim())
+        })
+    }
+}
+
+/// Length of a Chromium Simple Cache entry header plus key, when `data`
+/// starts with a plausible header.
+pub fn chrome_key_end(data: &[u8]) -> Option<usize> {
+    if data.len() < CHROME_SIMPLE_HEADER_LEN || data[..8] != CHROME_SIMPLE_INITIAL_MAGIC {
+        return None;
+    }
+    let version = le_u32(data, 8)?;
+    let key_len = le_u32(data, 12)? as usize;
+    if !CHROME_SIMPLE_VERSIONS.contains(&version) || key_len == 0 || key_len > CHROME_MAX_KEY_LEN {
+        return None;
+    }
+    Some(CHROME_SIMPLE_HEADER_LEN + key_len)
+}
+
+/// Parse a complete Chromium Simple Cache `_0` entry file.
+pub fn parse_chrome_simple_entry(data: &[u8]) -> Option<CacheEntry> {
+    let key_end = chrome_key_end(data)?;
+    let key = printable(data.get(CHROME_SIMPLE_HEADER_LEN..key_end)?)?;
+
+    let eof1 = key_end + find(&data[key_end..], &CHROME_SIMPLE_FINAL_MAGIC)?;
+    let stream0_start = eof1 + CHROME_SIMPLE_EOF_LEN;
+    let eof0 = stream0_start + find(data.get(stream0_start..)?, &CHROME_SIMPLE_FINAL_MAGIC)?;
+    let flags = le_u32(data, eof0 + 8)?;
+    let stream0_len = le_u32(data, eof0 + 16)? as usize;
+    let mut stream0_end = eof0;
+    if flags & CHROME_FLAG_HAS_KEY_SHA256 != 0 {
+        stream0_end = stream0_end.checked_sub(CHROME_KEY_SHA256_LEN)?;
+    }
+    if stream0_end.checked_sub(stream0_len)? != stream0_start {
+        return None;
+    }
+
+    Some(CacheEntry {
+        browser: "chrome",
+        url: chrome_url(&key),
+        key,
+        headers: chrome_headers(&data[stream0_start..stream0_end]),
+        body: key_end..eof1,
+        len: eof0 + CHROME_SIMPLE_EOF_LEN,
+    })
+}
+
+/// The URL is the last space-separated token of keys such as
+/// `1/0/_dk_https://a.example https://a.example https://a.example/x.png`.
+fn chrome_url(key: &str) -> String {
+    key.split_whitespace()
+        .last()
+        .map(|url| url.strip_prefix("_dk_").unwrap_or(url))
+        .unwrap_or(key)
+        .to_string()
+}
+
+/// Stream 0 is a pickled `HttpResponseInfo`; the raw headers inside it are
+/// NUL-separated lines terminated by an empty line.
+fn chrome_headers(stream0: &[u8]) -> Vec<String> {
+    let Some(start) = find(stream0, b"HTTP/") else {
+        return Vec::new();
+    };
+    let raw = &stream0[start..];
+    let end = find(raw, b"\0\0").unwrap_or(raw.len());
+    raw[..end]
+        .split(|&b| b == 0)
+        .map(|line| String::from_utf8_lossy(line).trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+/// Parse a complete Firefox cache2 entry file.
+pub fn parse_firefox_cache2_entry(data: &[u8]) -> Option<CacheEntry> {
+    let trailer = data.len().checked_sub(4)?;
+    let body_len = be_u32(data, trailer)? as usize;
+    let header = body_len.checked_add(firefox_hash_len(body_len as u64) as usize)?;
+    if header + FIREFOX_HEADER_LEN > trailer {
+        return None;
+    }
+    let metadata = parse_firefox_metadata(&data[..trailer], header)?;
+    if metadata.elements_end != trailer {
+        return None;
+    }
+    Some(CacheEntry {
+        browser: "firefox",
+        url: firefox_url(&metadata.key),
+        key: metadata.key,
+        headers: metadata.headers,
+        body: 0..body_len,
+        len: data.len(),
+    })
+}
+
+/// Location of a Firefox cache2 entry around a `:http` key anchor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cache2Location {
+    /// Entry start relative to the window start (may be negative: the body
+    /// usually lies before the window).
+    pub start: i64,
+    /// Entry end (exclusive) relative to the window start.
+    pub end: usize,
+}
+
+/// Find the cache2 entry whose key contains `anchor` (an index into `window`).
+///
+/// `window_offset` is the evidence offset of `window[0]`; it bounds how far
+/// back the body can start. Bodies larger than `max_body` are rejected.
+pub fn locate_firefox_cache2_entry(
+    window: &[u8],
+    anchor: usize,
+    window_offset: u64,
+    max_body: u64,
+) -> Option<Cache2Location> {
+    let lowest = anchor
+        .saturating_sub(FIREFOX_MAX_KEY_PREFIX)
+        .max(FIREFOX_HEADER_LEN);
+    for key_start in (lowest..=anchor).rev() {
+        let header = key_start - FIREFOX_HEADER_LEN;
+        let Some(version) = be_u32(window, header) else {
+            continue;
+        };
+        if !FIREFOX_VERSIONS.contains(&version) {
+            continue;
+        }
+        let Some(metadata) = parse_firefox_metadata_open(window, header) else {
+            continue;
+        };
+        if key_start + metadata.key.len() <= anchor {
+            continue;
+        }
+        // The elements end where a big-endian body size consistent with the
+        // header position follows.
+        for &end in &metadata.boundaries {
+            let Some(body_len) = be_u32(window, end) else {
+                break;
+            };
+            let body_len = body_len as u64;
+            if body_len > max_body {
+                continue;
+            }
+            let before = body_len + firefox_hash_len(body_len);
+            if window_offset + header as u64 >= before {
+                return Some(Cache2Location {
+                    start: header as i64 - before as i64,
+                    end: end + 4,
+                });
+            }
+        }
+    }
+    None
+}
+
+fn firefox_hash_len(body_len: u64) -> u64 {
+    4 + 2 * body_len.div_ceil(FIREFOX_CHUNK_SIZE)
+}
+
+/// `a,:https://example.com/` and `O^partitionKey=...,a,:https://...` both end
+/// with `:` followed by the URL.
+fn firefox_url(key: &str) -> String {
+    match key.find(":http") {
+        Some(idx) => key[idx + 1..].to_string(),
+        None => key.trim_start_matches(':').to_string(),
+    }
+}
+
+struct FirefoxMetadata {
+    key: String,
+    headers: Vec<String>,
+    elements_end: usize,
+    /// Offsets after each complete element pair (candidate element ends).
+    boundaries: Vec<usize>,
+}
+
+/// Parse metadata starting at `header` whose elements run to the end of `data`.
+fn parse_firefox_metadata(data: &[u8], header: usize) -> Option<FirefoxMetadata> {
+    let metadata = parse_firefox_metadata_open(data, header)?;
+    metadata.boundaries.last().copied().and_then(|end| {
+        (end == data.len()).then_some(FirefoxMetadata {
+            elements_end: end,
+            ..metadata
+        })
+    })
+}
+
+/// Parse metadata at `header`, reading element pairs while they look valid.
+fn parse_firefox_metadata_open(data: &[u8], header: usize) -> Option<FirefoxMetadata> {
+    let version = be_u32(data, header)?;
+    if !FIREFOX_VERSIONS.contains(&version) {
+        return None;
+    }
+    let key_len = be_u32(data, header + 24)? as usize;
+    let key_start = header + FIREFOX_HEADER_LEN;
+    let key_end = key_start.checked_add(key_len)?;
+    if key_len == 0 || data.get(key_end) != Some(&0) {
+        return None;
+    }
+    let key = printable(&data[key_start..key_end])?;
+
+    let mut headers = Vec::new();
+    let mut boundaries = Vec::new();
+    let mut pos = key_end + 1;
+    while boundaries.len() < FIREFOX_MAX_ELEMENTS {
+        let Some(name_len) = data.get(pos..).and_then(|rest| find(rest, &[0])) else {
+            break;
+        };
+        if name_len == 0 || name_len > FIREFOX_MAX_ELEMENT_NAME {
+            break;
+        }
+        let Some(name) = printable(&data[pos..pos + name_len]) else {
+            break;
+        };
+        let value_start = pos + name_len + 1;
+        let Some(value_len) = data.get(value_start..).and_then(|rest| find(rest, &[0])) else {
+            break;
+        };
+        if name == "response-head" {
+            headers = String::from_utf8_lossy(&data[value_start..value_start + value_len])
+                .lines()
+                .map(|line| line.trim().to_string())
+                .filter(|line| !line.is_empty())
+                .collect();
+        }
+        pos = value_start + value_len + 1;
+        boundaries.push(pos);
+    }
+    if boundaries.is_empty() {
+        return None;
+    }
+    Some(FirefoxMetadata {
+        key,
+        headers,
+        elements_end: pos,
+        boundaries,
+    })
+}
+
+fn printable(bytes: &[u8]) -> Option<String> {
+    if bytes.iter().all(|b| (0x20..0x7f).contains(b)) {
+        Some(String::from_utf8_lossy(bytes).to_string())
+    } else {
+        None
+    }
+}
+
+pub(crate) fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn le_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_le_bytes(bytes.try_into().ok()?))
+}
+
+fn be_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_be_bytes(bytes.try_into().ok()?))
+}
+
+/// Build a Chromium Simple Cache `_0` entry (for tests).
+#[cfg(test)]
+pub(crate) fn chrome_entry_bytes(key: &str, headers: &str, body: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(&CHROME_SIMPLE_INITIAL_MAGIC);
+    out.extend_from_slice(&5u32.to_le_bytes());
+    out.extend_from_slice(&(key.len() as u32).to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+    out.extend_from_slice(&[0u8; 4]);
+    out.extend_from_slice(key.as_bytes());
+    out.extend_from_slice(body);
+    push_chrome_eof(&mut out, 1, 0);
+    // Pickle: payload size, flags, request/response times, then headers.
+    let mut stream0 = Vec::new();
+    stream0.extend_from_slice(&[0u8; 24]);
+    let raw = format!("{}\0\0", headers.replace("\r\n", "\0"));
+    stream0.extend_from_slice(&(raw.len() as u32).to_le_bytes());
+    stream0.extend_from_slice(raw.as_bytes());
+    out.extend_from_slice(&stream0);
+    out.extend_from_slice(&[0xAB; CHROME_KEY_SHA256_LEN]);
+    push_chrome_eof(
+        &mut out,
+        1 | CHROME_FLAG_HAS_KEY_SHA256,
+        stream0.len() as u32,
+    );
+    out
+}
+
+#[cfg(test)]
+fn push_chrome_eof(out: &mut Vec<u8>, flags: u32, stream_size: u32) {
+    out.extend_from_slice(&CHROME_SIMPLE_FINAL_MAGIC);
+    out.extend_from_slice(&flags.to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+    out.extend_from_slice(&stream_size.to_le_bytes());
+    out.extend_from_slice(&[0u8; 4]);
+}
+
+/// Build a Firefox cache2 entry (for tests).
+#[cfg(test)]
+pub(crate) fn firefox_entry_bytes(key: &str, response_head: &str, body: &[u8]) -> Vec<u8> {
+    let mut out = body.to_vec();
+    out.extend_from_slice(&[0x11; 4]);
+    out.extend(std::iter::repeat_n(
+        0x22,
+        2 * body.len().div_ceil(256 * 1024),
+    ));
+    for field in [3u32, 1, 0, 0, 0, 0, key.len() as u32, 0] {
+        out.extend_from_slice(&field.to_be_bytes());
+    }
+    out.extend_from_slice(key.as_bytes());
+    out.push(0);
+    for (name, value) in [("request-method", "GET"), ("response-head", response_head)] {
+        out.extend_from_slice(name.as_bytes());
+        out.push(0);
+        out.extend_from_slice(value.as_bytes());
+        out.push(0);
+    }
+    out.extend_from_slice(&(body.len() as u32).to_be_bytes());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADERS: &str = "HTTP/1.1 200 OK\r\nContent-Type: image/png\r\nContent-Length: 5\r\n";
+
+    #[test]
+    fn parses_chrome_simple_entry() {
+        let data = chrome_entry_bytes(
+            "1/0/_dk_https://a.example https://a.example https://a.example/x.png",
+            HEADERS,
+            b"\x89PNG!",
+        );
+        let entry = parse_chrome_simple_entry(&data).expect("entry");
+        assert_eq!(entry.url, "https://a.example/x.png");
+        assert_eq!(&data[entry.body.clone()], b"\x89PNG!");
+        assert_eq!(entry.len, data.len());
+        assert_eq!(entry.http_status(), Some(200));
+        assert_eq!(entry.header("content-type"), Some("image/png"));
+
+        let mut truncated = data.clone();
+        truncated.truncate(data.len() - 10);
+        assert!(parse_chrome_simple_entry(&truncated).is_none());
+    }
+
+    #[test]
+    fn parses_firefox_entry_and_locates_it_from_key() {
+        let key = "O^partitionKey=%28https%2Cexample.com%29,a,:https://example.com/app.js";
+        let body = b"console.log(1);";
+        let data = firefox_entry_bytes(key, HEADERS, body);
+        let entry = parse_firefox_cache2_entry(&data).expect("entry");
+        assert_eq!(entry.url, "https://example.com/app.js");
+        assert_eq!(&data[entry.body.clone()], body);
+        assert_eq!(entry.header("Content-Length"), Some("5"));
+
+        // Window starting inside the body, as a carve handler would read it.
+        let window_start = 4;
+        let window = &data[window_start..];
+        let anchor = find(window, b":http").expect("anchor");
+        let location = locate_firefox_cache2_entry(window, anchor, window_start as u64, 1 << 20)
+            .expect("location");
+        assert_eq!(location.start, -(window_start as i64));
+        assert_eq!(location.end, window.len());
+
+        // The body cannot start before the evidence does.
+        assert!(locate_firefox_cache2_entry(window, anchor, 0, 1 << 20).is_none());
+    }
+}
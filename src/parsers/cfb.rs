@@ -0,0 +1,424 @@
+//! Compound File Binary (OLE2) stream reader (`[MS-CFB]`).
+//!
+//! A compound file is a FAT file system in a file: the header lists the
+//! sectors of the FAT (109 in the header, the rest in a DIFAT chain), the
+//! directory is a chain of 128-byte entries, and each stream is a sector chain.
+//! Streams below the mini stream cutoff (4096 bytes) live in 64-byte mini
+//! sectors inside the root entry's stream, allocated through the mini FAT.
+//!
+//! [`CompoundFile`] reads the FAT, mini FAT, and directory up front and then
+//! reads single streams on demand. Chains are bounded by the FAT length, so a
+//! corrupted or truncated file ends a stream early rather than looping.
+
+use std::io::{self, Read, Seek, SeekFrom};
+
+const SIGNATURE: [u8; 8] = [0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1];
+const HEADER_DIFAT_ENTRIES: usize = 109;
+/// Sector ids at or above this are markers (free, end of chain, FAT, DIFAT).
+const MAX_REG_SECT: u32 = 0xFFFF_FFFA;
+const END_OF_CHAIN: u32 = 0xFFFF_FFFE;
+const DIR_ENTRY_LEN: usize = 128;
+/// FAT sectors read at most (a 4 GiB file with 512-byte sectors).
+const MAX_FAT_SECTORS: usize = 65_536;
+/// Mini streams larger than this are not loaded.
+const MAX_MINI_STREAM: u64 = 16 * 1024 * 1024;
+
+pub const ENTRY_STORAGE: u8 = 1;
+pub const ENTRY_STREAM: u8 = 2;
+pub const ENTRY_ROOT: u8 = 5;
+
+/// One directory entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirEntry {
+    pub name: String,
+    pub entry_type: u8,
+    pub start_sector: u32,
+    pub size: u64,
+}
+
+/// An open compound file.
+pub struct CompoundFile<R> {
+    reader: R,
+    sector_size: u64,
+    mini_sector_size: u64,
+    mini_cutoff: u64,
+    fat: Vec<u32>,
+    mini_fat: Vec<u32>,
+    entries: Vec<DirEntry>,
+    mini_stream: Option<Vec<u8>>,
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("compound file: {msg}"))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+impl<R: Read + Seek> CompoundFile<R> {
+    pub fn open(mut reader: R) -> io::Result<Self> {
+        let mut header = [0u8; 512];
+        reader.seek(SeekFrom::Start(0))?;
+        reader.read_exact(&mut header)?;
+        if header[..8] != SIGNATURE {
+            return Err(invalid("bad signature"));
+        }
+        let major = u16::from_le_bytes([header[26], header[27]]);
+        let sector_shift = u16::from_le_bytes([header[30], header[31]]);
+        let mini_shift = u16::from_le_bytes([header[32], header[33]]);
+        let sector_size = match (major, sector_shift) {
+            (3, 9) => 512u64,
+            (4, 12) => 4096u64,
+            _ => return Err(invalid("unsupported version or sector size")),
+        };
+        if mini_shift != 6 {
+            return Err(invalid("unsupported mini sector size"));
+        }
+        let mut cfb = Self {
+            reader,
+            sector_size,
+            mini_sector_size: 64,
+            mini_cutoff: u64::from(u32_at(&header, 56)),
+            fat: Vec::new(),
+            mini_fat: Vec::new(),
+            entries: Vec::new(),
+            mini_stream: None,
+        };
+
+        let mut fat_sectors: Vec<u32> = (0..HEADER_DIFAT_ENTRIES)
+            .map(|i| u32_at(&header, 76 + i * 4))
+            .filter(|&id| id < MAX_REG_SECT)
+            .collect();
+        let mut difat = u32_at(&header, 68);
+        let mut difat_left = u32_at(&header, 72);
+        while difat < MAX_REG_SECT && difat_left > 0 && fat_sectors.len() < MAX_FAT_SECTORS {
+            let sector = cfb.read_sector(difat)?;
+            let per_sector = sector.len() / 4 - 1;
+            fat_sectors.extend(
+                (0..per_sector)
+                    .map(|i| u32_at(&sector, i * 4))
+                    .filter(|&id| id < MAX_REG_SECT),
+            );
+            difat = u32_at(&sector, per_sector * 4);
+            difat_left -= 1;
+        }
+        fat_sectors.truncate(MAX_FAT_SECTORS);
+        for id in fat_sectors {
+            let Ok(sector) = cfb.read_sector(id) else {
+                // A truncated file keeps the FAT read so far.
+                break;
+            };
+            cfb.fat.extend(sector.chunks_exact(4).map(|c| u32_at(c, 0)));
+        }
+        if cfb.fat.is_empty() {
+            return Err(invalid("no FAT sectors"));
+        }
+
+        let mini_fat_start = u32_at(&header, 60);
+        for id in cfb.chain(mini_fat_start) {
+            let sector = cfb.read_sector(id)?;
+            cfb.mini_fat
+                .extend(sector.chunks_exact(4).map(|c| u32_at(c, 0)));
+        }
+
+        let dir_start = u32_at(&header, 48);
+        for id in cfb.chain(dir_start) {
+            let Ok(sector) = cfb.read_sector(id) else {
+                break;
+            };
+            for raw in sector.chunks_exact(DIR_ENTRY_LEN) {
+                let name_len = u16::from_le_bytes([raw[64], raw[65]]) as usize;
+                let entry_type = raw[66];
+                if entry_type == 0 || !(2..=64).contains(&name_len) {
+                    continue;
+                }
+                let units: Vec<u16> = raw[..name_len - 2]
+                    .chunks_exact(2)
+                    .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                    .collect();
+                let mut size = u64::from_le_bytes(raw[120..128].try_into().unwrap_or_default());
+                if major == 3 {
+                    // Version 3 files may leave garbage in the high half.
+                    size &= 0xFFFF_FFFF;
+                }
+                cfb.entries.push(DirEntry {
+                    name: String::from_utf16_lossy(&units),
+                    entry_type,
+                    start_sector: u32_at(raw, 116),
+                    size,
+                });
+            }
+        }
+        if cfb.entries.first().map(|e| e.entry_type) != Some(ENTRY_ROOT) {
+            return Err(invalid("missing root entry"));
+        }
+        Ok(cfb)
+    }
+
+    pub fn entries(&self) -> &[DirEntry] {
+        &self.entries
+    }
+
+    /// First stream named `name`, at any depth.
+    pub fn stream(&self, name: &str) -> Option<&DirEntry> {
+        self.entries
+            .iter()
+            .find(|e| e.entry_type == ENTRY_STREAM && e.name == name)
+    }
+
+    /// Read up to `limit` bytes of a stream. Streams whose chain ends early
+    /// return the bytes that could be read.
+    pub fn read_stream(&mut self, entry: &DirEntry, limit: usize) -> io::Result<Vec<u8>> {
+        let want = entry.size.min(limit as u64) as usize;
+        let mut out = Vec::with_capacity(want.min(1 << 20));
+        if entry.size < self.mini_cutoff {
+            self.load_mini_stream()?;
+            let mini = self.mini_stream.as_deref().unwrap_or_default();
+            let mini_sector = self.mini_sector_size as usize;
+            for id in chain_in(&self.mini_fat, entry.start_sector) {
+                let start = id as usize * mini_sector;
+                let Some(bytes) = mini.get(start..start + mini_sector) else {
+                    break;
+                };
+                out.extend_from_slice(bytes);
+                if out.len() >= want {
+                    break;
+                }
+            }
+        } else {
+            for id in self.chain(entry.start_sector) {
+                let Ok(sector) = self.read_sector(id) else {
+                    break;
+                };
+                out.extend_from_slice(&sector);
+                if out.len() >= want {
+                    break;
+                }
+            }
+        }
+        out.truncate(want);
+        Ok(out)
+    }
+
+    fn load_mini_stream(&mut self) -> io::Result<()> {
+        if self.mini_stream.is_some() {
+            return Ok(());
+        }
+        let root = self.entries[0].clone();
+        if root.size > MAX_MINI_STREAM {
+            return Err(invalid("mini stream too large"));
+        }
+        let mut data = Vec::with_capacity(root.size as usize);
+        for id in self.chain(root.start_sector) {
+            let Ok(sector) = self.read_sector(id) else {
+                break;
+            };
+            data.extend_from_slice(&sector);
+        }
+        data.truncate(root.size as usize);
+        self.mini_stream = Some(data);
+        Ok(())
+    }
+
+    fn chain(&self, start: u32) -> Vec<u32> {
+        chain_in(&self.fat, start)
+    }
+
+    fn read_sector(&mut self, id: u32) -> io::Result<Vec<u8>> {
+        let mut sector = vec![0u8; self.sector_size as usize];
+        self.reader
+            .seek(SeekFrom::Start((u64::from(id) + 1) * self.sector_size))?;
+        self.reader.read_exact(&mut sector)?;
+        Ok(sector)
+    }
+}
+
+/// Sector ids of the chain starting at `start`, stopping at an end marker,
+/// an id outside `table`, or after `table.len()` steps.
+fn chain_in(table: &[u32], start: u32) -> Vec<u32> {
+    let mut out = Vec::new();
+    let mut current = start;
+    while current < MAX_REG_SECT && out.len() <= table.len() {
+        out.push(current);
+        match table.get(current as usize) {
+            Some(&next) if next != END_OF_CHAIN => current = next,
+            _ => break,
+        }
+    }
+    out
+}
+
+/// A version 3 compound file holding `streams` under the root. Streams
+/// shorter than 4096 bytes go to the mini stream.
+#[cfg(test)]
+pub(crate) fn compound_file(streams: &[(&str, Vec<u8>)]) -> Vec<u8> {
+    const FREE: u32 = 0xFFFF_FFFF;
+    const FAT_SECT: u32 = 0xFFFF_FFFD;
+    assert!(streams.len() <= 3, "one directory sector holds 4 entries");
+    let sectors_for = |len: usize| len.div_ceil(512).max(1);
+    let mut fat = vec![FAT_SECT, END_OF_CHAIN, END_OF_CHAIN];
+    let mut mini_fat: Vec<u32> = Vec::new();
+    let mut mini_stream: Vec<u8> = Vec::new();
+    let mut big: Vec<u8> = Vec::new();
+    let mut starts = Vec::new();
+    for (_, data) in streams {
+        if data.len() < 4096 {
+            let first = mini_fat.len() as u32;
+            let count = data.len().div_ceil(64).max(1);
+            for i in 0..count as u32 {
+                mini_fat.push(if i + 1 == count as u32 {
+                    END_OF_CHAIN
+                } else {
+                    first + i + 1
+                });
+            }
+            let mut padded = data.clone();
+            padded.resize(count * 64, 0);
+            mini_stream.extend(padded);
+            starts.push((first, true));
+        } else {
+            starts.push((big.len() as u32, false));
+            let mut padded = data.clone();
+            padded.resize(sectors_for(data.len()) * 512, 0);
+            big.extend(padded);
+        }
+    }
+    // Sector 0 FAT, 1 directory, 2 mini FAT, then the mini stream, then the
+    // large streams.
+    let mini_start = 3u32;
+    let mini_sectors = sectors_for(mini_stream.len()) as u32;
+    for i in 0..mini_sectors {
+        fat.push(if i + 1 == mini_sectors {
+            END_OF_CHAIN
+        } else {
+            mini_start + i + 1
+        });
+    }
+    let big_start = mini_start + mini_sectors;
+    for (i, (_, data)) in streams.iter().enumerate() {
+        if data.len() < 4096 {
+            continue;
+        }
+        let first = big_start + starts[i].0 / 512;
+        let count = sectors_for(data.len()) as u32;
+        starts[i].0 = first;
+        for k in 0..count {
+            fat.push(if k + 1 == count {
+                END_OF_CHAIN
+            } else {
+                first + k + 1
+            });
+        }
+    }
+    fat.resize(128, FREE);
+    mini_fat.resize(128, FREE);
+
+    let mut header = vec![0u8; 512];
+    header[..8].copy_from_slice(&SIGNATURE);
+    header[24..26].copy_from_slice(&0x3Eu16.to_le_bytes());
+    header[26..28].copy_from_slice(&3u16.to_le_bytes());
+    header[28..30].copy_from_slice(&0xFFFEu16.to_le_bytes());
+    header[30..32].copy_from_slice(&9u16.to_le_bytes());
+    header[32..34].copy_from_slice(&6u16.to_le_bytes());
+    header[44..48].copy_from_slice(&1u32.to_le_bytes());
+    header[48..52].copy_from_slice(&1u32.to_le_bytes());
+    header[56..60].copy_from_slice(&4096u32.to_le_bytes());
+    header[60..64].copy_from_slice(&2u32.to_le_bytes());
+    header[64..68].copy_from_slice(&1u32.to_le_bytes());
+    header[68..72].copy_from_slice(&END_OF_CHAIN.to_le_bytes());
+    for i in 0..HEADER_DIFAT_ENTRIES {
+        let id = if i == 0 { 0 } else { FREE };
+        header[76 + i * 4..80 + i * 4].copy_from_slice(&id.to_le_bytes());
+    }
+
+    let entry = |name: &str, entry_type: u8, child: u32, right: u32, start: u32, size: u64| {
+        let mut raw = vec![0u8; DIR_ENTRY_LEN];
+        let units: Vec<u16> = name.encode_utf16().collect();
+        for (i, unit) in units.iter().enumerate() {
+            raw[i * 2..i * 2 + 2].copy_from_slice(&unit.to_le_bytes());
+        }
+        raw[64..66].copy_from_slice(&((units.len() as u16 + 1) * 2).to_le_bytes());
+        raw[66] = entry_type;
+        raw[67] = 1;
+        raw[68..72].copy_from_slice(&FREE.to_le_bytes());
+        raw[72..76].copy_from_slice(&right.to_le_bytes());
+        raw[76..80].copy_from_slice(&child.to_le_bytes());
+        raw[116..120].copy_from_slice(&start.to_le_bytes());
+        raw[120..128].copy_from_slice(&size.to_le_bytes());
+        raw
+    };
+    let mut dir = entry(
+        "Root Entry",
+        ENTRY_ROOT,
+        if streams.is_empty() { FREE } else { 1 },
+        FREE,
+        mini_start,
+        mini_stream.len() as u64,
+    );
+    for (i, (name, data)) in streams.iter().enumerate() {
+        let right = if i + 1 < streams.len() {
+            i as u32 + 2
+        } else {
+            FREE
+        };
+        dir.extend(entry(
+            name,
+            ENTRY_STREAM,
+            FREE,
+            right,
+            starts[i].0,
+            data.len() as u64,
+        ));
+    }
+    dir.resize(512, 0);
+
+    let mut out = header;
+    out.extend(fat.iter().flat_map(|v| v.to_le_bytes()));
+    out.extend(dir);
+    out.extend(mini_fat.iter().flat_map(|v| v.to_le_bytes()));
+    mini_stream.resize(mini_sectors as usize * 512, 0);
+    out.extend(mini_stream);
+    out.extend(big);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn reads_mini_and_regular_streams() {
+        let small = b"small stream".to_vec();
+        let large: Vec<u8> = (0..5000u32).map(|i| i as u8).collect();
+        let data = compound_file(&[("Small", small.clone()), ("Large", large.clone())]);
+        let mut cfb = CompoundFile::open(Cursor::new(data)).expect("open");
+        assert_eq!(cfb.entries().len(), 3);
+        let entry = cfb.stream("Small").expect("small").clone();
+        assert_eq!(cfb.read_stream(&entry, usize::MAX).expect("read"), small);
+        let entry = cfb.stream("Large").expect("large").clone();
+        assert_eq!(cfb.read_stream(&entry, usize::MAX).expect("read"), large);
+        assert_eq!(cfb.read_stream(&entry, 8).expect("read"), large[..8]);
+        assert!(cfb.stream("Missing").is_none());
+    }
+
+    #[test]
+    fn truncated_file_ends_streams_early() {
+        let large: Vec<u8> = vec![7u8; 8192];
+        let mut data = compound_file(&[("Large", large)]);
+        data.truncate(data.len() - 4096);
+        let mut cfb = CompoundFile::open(Cursor::new(data)).expect("open");
+        let entry = cfb.stream("Large").expect("large").clone();
+        assert_eq!(
+            cfb.read_stream(&entry, usize::MAX).expect("read").len(),
+            4096
+        );
+        assert!(CompoundFile::open(Cursor::new(vec![0u8; 512])).is_err());
+    }
+}
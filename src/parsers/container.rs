@@ -0,0 +1,585 @@
+//! Docker/OCI container artefacts.
+//!
+//! - Image tarballs (`docker save`, OCI layouts): `manifest.json` and
+//!   `index.json` give image names, config digests, and layer paths.
+//! - Layer tarballs: overlayfs whiteouts (`.wh.<name>`) and opaque directory
+//!   markers (`.wh..wh..opq`) record deletions made by a layer.
+//! - containerd `meta.db` (bolt): images with their target digests,
+//!   containers with their image, and content blobs, per namespace.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::{self, Read};
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::parsers::boltdb;
+
+const TAR_BLOCK: usize = 512;
+const WHITEOUT_PREFIX: &str = ".wh.";
+const OPAQUE_MARKER: &str = ".wh..wh..opq";
+/// Largest `manifest.json`/`index.json` read into memory.
+const MAX_JSON_LEN: u64 = 4 * 1024 * 1024;
+/// Upper bound on records of one kind per file.
+const MAX_PER_KIND: usize = 10_000;
+
+/// A container artefact found in a carved file.
+#[derive(Debug, Clone, Serialize)]
+pub struct ContainerArtefactRecord {
+    pub run_id: String,
+    /// `image`, `layer`, `whiteout`, `opaque_dir`, `container`, or `content_blob`.
+    pub kind: String,
+    /// Image reference, layer path, deleted path, or container ID.
+    pub name: Option<String>,
+    /// `sha256:`-prefixed digest.
+    pub digest: Option<String>,
+    /// containerd namespace.
+    pub namespace: Option<String>,
+    /// Kind-specific detail (layer count, container image, media type).
+    pub detail: Option<String>,
+    /// Evidence offset of the tar entry or bolt page holding the artefact.
+    pub global_offset: u64,
+    /// Carved path of the tarball or database.
+    pub source_file: String,
+}
+
+/// An artefact with an offset relative to the carved file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ContainerArtefact {
+    pub kind: &'static str,
+    pub name: Option<String>,
+    pub digest: Option<String>,
+    pub namespace: Option<String>,
+    pub detail: Option<String>,
+    pub offset: u64,
+}
+
+/// Container traits of a tar archive.
+#[derive(Debug, Default)]
+pub struct TarScan {
+    /// Image, layer, whiteout, and opaque directory artefacts.
+    pub artefacts: Vec<ContainerArtefact>,
+    /// Whether the archive contains overlayfs whiteouts, i.e. is an image layer.
+    pub is_layer: bool,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct DockerManifest {
+    #[serde(rename = "Config")]
+    config: String,
+    #[serde(rename = "RepoTags", default)]
+    repo_tags: Option<Vec<String>>,
+    #[serde(rename = "Layers", default)]
+    layers: Vec<String>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct OciIndex {
+    #[serde(default)]
+    manifests: Vec<OciDescriptor>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct OciDescriptor {
+    digest: String,
+    #[serde(rename = "mediaType", default)]
+    media_type: Option<String>,
+    #[serde(default)]
+    annotations: HashMap<String, String>,
+}
+
+/// Walk a tar archive and report its container artefacts. Reading stops at
+/// the end-of-archive marker or the first invalid header.
+pub fn scan_tar<R: Read>(mut reader: R) -> io::Result<TarScan> {
+    let mut scan = TarScan::default();
+    let mut offset = 0u64;
+    let mut long_name: Option<String> = None;
+    let mut manifest: Option<Vec<u8>> = None;
+    let mut index: Option<Vec<u8>> = None;
+    let mut entry_offsets: HashMap<String, u64> = HashMap::new();
+    let mut layer_hashes: HashMap<String, String> = HashMap::new();
+    let mut whiteouts = 0usize;
+    let mut header = [0u8; TAR_BLOCK];
+
+    loop {
+        if !read_block(&mut reader, &mut header)? || header.iter().all(|b| *b == 0) {
+            break;
+        }
+        let Some(size) = tar_size(&header) else {
+            break;
+        };
+        if !tar_checksum_ok(&header) {
+            break;
+        }
+        let entry_offset = offset;
+        offset += TAR_BLOCK as u64;
+        let padded = size.next_multiple_of(TAR_BLOCK as u64);
+        let typeflag = header[156];
+
+        // GNU long names and pax `path` records rename the next entry.
+        if typeflag == b'L' || typeflag == b'x' {
+            let data = read_data(&mut reader, size, padded)?;
+            offset += padded;
+            long_name = if typeflag == b'L' {
+                Some(cstr(&data))
+            } else {
+                pax_path(&data).or(long_name)
+            };
+            continue;
+        }
+        let name = long_name.take().unwrap_or_else(|| ustar_name(&header));
+        let name = name.trim_start_matches("./").to_string();
+
+        let base = name.rsplit('/').next().unwrap_or(&name);
+        let dir = name[..name.len() - base.len()].trim_end_matches('/');
+        if base == OPAQUE_MARKER {
+            scan.is_layer = true;
+            if whiteouts < MAX_PER_KIND {
+                whiteouts += 1;
+                scan.artefacts.push(ContainerArtefact {
+                    kind: "opaque_dir",
+                    name: Some(format!("/{dir}")),
+                    offset: entry_offset,
+                    ..ContainerArtefact::default()
+                });
+            }
+        } else if let Some(deleted) = base.strip_prefix(WHITEOUT_PREFIX) {
+            scan.is_layer = true;
+            if whiteouts < MAX_PER_KIND {
+                whiteouts += 1;
+                let path = if dir.is_empty() {
+                    format!("/{deleted}")
+                } else {
+                    format!("/{dir}/{deleted}")
+                };
+                scan.artefacts.push(ContainerArtefact {
+                    kind: "whiteout",
+                    name: Some(path),
+                    offset: entry_offset,
+                    ..ContainerArtefact::default()
+                });
+            }
+        }
+
+        let is_file = typeflag == b'0' || typeflag == 0;
+        if is_file && (name == "manifest.json" || name == "index.json") && size <= MAX_JSON_LEN {
+            let data = read_data(&mut reader, size, padded)?;
+            if name == "manifest.json" {
+                manifest = Some(data);
+            } else {
+                index = Some(data);
+            }
+        } else if is_file && name.ends_with("/layer.tar") {
+            // Legacy `docker save` layers carry no digest in their path.
+            let mut hasher = Sha256::new();
+            copy_data(&mut reader, size, padded, Some(&mut hasher))?;
+            layer_hashes.insert(name.clone(), hex::encode(hasher.finalize()));
+        } else {
+            copy_data(&mut reader, size, padded, None)?;
+        }
+        entry_offsets.insert(name, entry_offset);
+        offset += padded;
+    }
+
+    if let Some(manifest) = manifest.and_then(|m| serde_json::from_slice(&m).ok()) {
+        push_manifest(&mut scan, manifest, &entry_offsets, &layer_hashes);
+    } else if let Some(index) = index.and_then(|i| serde_json::from_slice::<OciIndex>(&i).ok()) {
+        let offset = entry_offsets.get("index.json").copied().unwrap_or(0);
+        for descriptor in index.manifests.into_iter().take(MAX_PER_KIND) {
+            scan.artefacts.push(ContainerArtefact {
+                kind: "image",
+                name: descriptor
+                    .annotations
+                    .get("org.opencontainers.image.ref.name")
+                    .or_else(|| descriptor.annotations.get("io.containerd.image.name"))
+                    .cloned(),
+                digest: Some(descriptor.digest),
+                detail: descriptor.media_type,
+                offset,
+                ..ContainerArtefact::default()
+            });
+        }
+    }
+    Ok(scan)
+}
+
+fn push_manifest(
+    scan: &mut TarScan,
+    manifest: Vec<DockerManifest>,
+    entry_offsets: &HashMap<String, u64>,
+    layer_hashes: &HashMap<String, String>,
+) {
+    let manifest_offset = entry_offsets.get("manifest.json").copied().unwrap_or(0);
+    for image in manifest.into_iter().take(MAX_PER_KIND) {
+        let tags = image.repo_tags.unwrap_or_default();
+        let name = (!tags.is_empty()).then(|| tags.join(","));
+        scan.artefacts.push(ContainerArtefact {
+            kind: "image",
+            name: name.clone(),
+            digest: path_digest(&image.config),
+            detail: Some(format!("{} layers", image.layers.len())),
+            offset: manifest_offset,
+            ..ContainerArtefact::default()
+        });
+        for layer in image.layers {
+            let digest = path_digest(&layer)
+                .or_else(|| layer_hashes.get(&layer).map(|h| format!("sha256:{h}")));
+            scan.artefacts.push(ContainerArtefact {
+                kind: "layer",
+                offset: entry_offsets
+                    .get(&layer)
+                    .copied()
+                    .unwrap_or(manifest_offset),
+                name: Some(layer),
+                digest,
+                detail: name.clone(),
+                ..ContainerArtefact::default()
+            });
+        }
+    }
+}
+
+/// Digest encoded in a blob path (`blobs/sha256/<hex>`) or a legacy config
+/// name (`<hex>.json`).
+fn path_digest(path: &str) -> Option<String> {
+    let mut parts = path.rsplit('/');
+    let last = parts.next()?;
+    let hex = last.strip_suffix(".json").unwrap_or(last);
+    let algorithm = parts
+        .next()
+        .filter(|alg| matches!(*alg, "sha256" | "sha512"))
+        .unwrap_or("sha256");
+    let is_digest = hex.len() >= 64 && hex.bytes().all(|b| b.is_ascii_hexdigit());
+    is_digest.then(|| format!("{algorithm}:{}", hex.to_ascii_lowercase()))
+}
+
+/// containerd metadata from a bolt database (`v1/<namespace>/...`).
+pub fn containerd_artefacts(data: &[u8]) -> Option<Vec<ContainerArtefact>> {
+    let mut images: BTreeMap<(String, String), ContainerArtefact> = BTreeMap::new();
+    let mut containers: BTreeMap<(String, String), ContainerArtefact> = BTreeMap::new();
+    let mut blobs: BTreeSet<(String, String)> = BTreeSet::new();
+    let mut blob_offsets: HashMap<(String, String), u64> = HashMap::new();
+    boltdb::walk(data, &mut |pair| {
+        let path: Vec<String> = pair
+            .buckets
+            .iter()
+            .map(|b| String::from_utf8_lossy(b).into_owned())
+            .collect();
+        let key = String::from_utf8_lossy(pair.key);
+        let value = || String::from_utf8_lossy(pair.value).into_owned();
+        let [version, namespace, rest @ ..] = path.as_slice() else {
+            return;
+        };
+        if version != "v1" {
+            return;
+        }
+        match rest {
+            [images_bucket, name, target] if images_bucket == "images" && target == "target" => {
+                if images.len() >= MAX_PER_KIND
+                    && !images.contains_key(&(namespace.clone(), name.clone()))
+                {
+                    return;
+                }
+                let entry = images
+                    .entry((namespace.clone(), name.clone()))
+                    .or_insert_with(|| ContainerArtefact {
+                        kind: "image",
+                        name: Some(name.clone()),
+                        namespace: Some(namespace.clone()),
+                        offset: pair.page_offset,
+                        ..ContainerArtefact::default()
+                    });
+                match key.as_ref() {
+                    "digest" => entry.digest = Some(value()),
+                    "mediatype" => entry.detail = Some(value()),
+                    _ => {}
+                }
+            }
+            [containers_bucket, id]
+                if containers_bucket == "containers"
+                    && key == "image"
+                    && containers.len() < MAX_PER_KIND =>
+            {
+                containers.insert(
+                    (namespace.clone(), id.clone()),
+                    ContainerArtefact {
+                        kind: "container",
+                        name: Some(id.clone()),
+                        namespace: Some(namespace.clone()),
+                        detail: Some(value()),
+                        offset: pair.page_offset,
+                        ..ContainerArtefact::default()
+                    },
+                );
+            }
+            [content, blob, digest, ..]
+                if content == "content" && blob == "blob" && blobs.len() < MAX_PER_KIND =>
+            {
+                let id = (namespace.clone(), digest.clone());
+                blob_offsets.entry(id.clone()).or_insert(pair.page_offset);
+                blobs.insert(id);
+            }
+            _ => {}
+        }
+    })?;
+
+    let mut out: Vec<ContainerArtefact> = images.into_values().collect();
+    out.extend(containers.into_values());
+    for (namespace, digest) in blobs {
+        let offset = blob_offsets
+            .get(&(namespace.clone(), digest.clone()))
+            .copied()
+            .unwrap_or(0);
+        out.push(ContainerArtefact {
+            kind: "content_blob",
+            digest: Some(digest),
+            namespace: Some(namespace),
+            offset,
+            ..ContainerArtefact::default()
+        });
+    }
+    Some(out)
+}
+
+/// Read one block; `false` at a clean end of input.
+fn read_block<R: Read>(reader: &mut R, block: &mut [u8; TAR_BLOCK]) -> io::Result<bool> {
+    let mut filled = 0;
+    while filled < TAR_BLOCK {
+        let n = reader.read(&mut block[filled..])?;
+        if n == 0 {
+            return Ok(false);
+        }
+        filled += n;
+    }
+    Ok(true)
+}
+
+fn read_data<R: Read>(reader: &mut R, size: u64, padded: u64) -> io::Result<Vec<u8>> {
+    let mut data = Vec::with_capacity(size as usize);
+    reader.by_ref().take(size).read_to_end(&mut data)?;
+    io::copy(&mut reader.by_ref().take(padded - size), &mut io::sink())?;
+    Ok(data)
+}
+
+fn copy_data<R: Read>(
+    reader: &mut R,
+    size: u64,
+    padded: u64,
+    hasher: Option<&mut Sha256>,
+) -> io::Result<()> {
+    match hasher {
+        Some(hasher) => {
+            let mut buf = [0u8; 64 * 1024];
+            let mut left = size;
+            while left > 0 {
+                let want = (buf.len() as u64).min(left) as usize;
+                let n = reader.read(&mut buf[..want])?;
+                if n == 0 {
+                    return Ok(());
+                }
+                hasher.update(&buf[..n]);
+                left -= n as u64;
+            }
+            io::copy(&mut reader.by_ref().take(padded - size), &mut io::sink())?;
+        }
+        None => {
+            io::copy(&mut reader.by_ref().take(padded), &mut io::sink())?;
+        }
+    }
+    Ok(())
+}
+
+fn ustar_name(header: &[u8; TAR_BLOCK]) -> String {
+    let name = cstr(&header[..100]);
+    if &header[257..262] == b"ustar" && header[345] != 0 {
+        format!("{}/{name}", cstr(&header[345..500]))
+    } else {
+        name
+    }
+}
+
+fn pax_path(data: &[u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(data);
+    text.lines().find_map(|line| {
+        let (_, record) = line.split_once(' ')?;
+        record.strip_prefix("path=").map(str::to_string)
+    })
+}
+
+fn cstr(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+fn tar_size(header: &[u8; TAR_BLOCK]) -> Option<u64> {
+    let field = &header[124..136];
+    if field[0] & 0x80 != 0 {
+        // GNU base-256 encoding for entries of 8 GiB and more.
+        return field[4..]
+            .iter()
+            .try_fold(0u64, |acc, b| acc.checked_mul(256)?.checked_add(*b as u64));
+    }
+    parse_octal(field)
+}
+
+fn parse_octal(field: &[u8]) -> Option<u64> {
+    let text = cstr(field);
+    let text = text.trim();
+    if text.is_empty() {
+        return Some(0);
+    }
+    u64::from_str_radix(text, 8).ok()
+}
+
+fn tar_checksum_ok(header: &[u8; TAR_BLOCK]) -> bool {
+    let Some(stored) = parse_octal(&header[148..156]) else {
+        return false;
+    };
+    let sum: u64 = header
+        .iter()
+        .enumerate()
+        .map(|(i, b)| {
+            if (148..156).contains(&i) {
+                0x20
+            } else {
+                *b as u64
+            }
+        })
+        .sum();
+    sum == stored
+}
+
+/// Build a ustar archive of regular files.
+#[cfg(test)]
+pub(crate) fn tar_bytes(entries: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for (name, data) in entries {
+        let mut header = [0u8; TAR_BLOCK];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[100..108].copy_from_slice(b"0000644\0");
+        header[124..136].copy_from_slice(format!("{:011o}\0", data.len()).as_bytes());
+        header[136..148].copy_from_slice(b"00000000000\0");
+        header[156] = b'0';
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+        header[148..156].copy_from_slice(b"        ");
+        let sum: u32 = header.iter().map(|b| *b as u32).sum();
+        header[148..156].copy_from_slice(format!("{sum:06o}\0 ").as_bytes());
+        out.extend_from_slice(&header);
+        out.extend_from_slice(data);
+        out.resize(out.len().next_multiple_of(TAR_BLOCK), 0);
+    }
+    out.extend_from_slice(&[0u8; TAR_BLOCK * 2]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::boltdb::{BoltValue, bolt_bytes};
+
+    const CONFIG_HEX: &str = "a1b2c3d4e5f60718293a4b5c6d7e8f90a1b2c3d4e5f60718293a4b5c6d7e8f90";
+
+    #[test]
+    fn reports_whiteouts_in_layer_tarball() {
+        let tar = tar_bytes(&[
+            ("etc/passwd", b"root:x:0:0"),
+            ("var/log/.wh.auth.log", b""),
+            ("tmp/.wh..wh..opq", b""),
+        ]);
+        let scan = scan_tar(tar.as_slice()).expect("scan");
+        assert!(scan.is_layer);
+        assert_eq!(scan.artefacts.len(), 2);
+        assert_eq!(scan.artefacts[0].kind, "whiteout");
+        assert_eq!(scan.artefacts[0].name.as_deref(), Some("/var/log/auth.log"));
+        assert_eq!(scan.artefacts[0].offset, 1024);
+        assert_eq!(scan.artefacts[1].kind, "opaque_dir");
+        assert_eq!(scan.artefacts[1].name.as_deref(), Some("/tmp"));
+
+        let plain = tar_bytes(&[("notes.txt", b"hello")]);
+        let scan = scan_tar(plain.as_slice()).expect("scan");
+        assert!(!scan.is_layer);
+        assert!(scan.artefacts.is_empty());
+    }
+
+    #[test]
+    fn reads_docker_save_manifest() {
+        let layer = tar_bytes(&[("bin/sh", b"#!")]);
+        let manifest = format!(
+            r#"[{{"Config":"{CONFIG_HEX}.json","RepoTags":["web:1.0"],"Layers":["abc/layer.tar"]}}]"#
+        );
+        let tar = tar_bytes(&[
+            ("abc/layer.tar", &layer),
+            (&format!("{CONFIG_HEX}.json"), b"{}"),
+            ("manifest.json", manifest.as_bytes()),
+        ]);
+        let scan = scan_tar(tar.as_slice()).expect("scan");
+        assert!(!scan.is_layer);
+        assert_eq!(scan.artefacts.len(), 2);
+        let image = &scan.artefacts[0];
+        assert_eq!(image.kind, "image");
+        assert_eq!(image.name.as_deref(), Some("web:1.0"));
+        assert_eq!(image.digest, Some(format!("sha256:{CONFIG_HEX}")));
+        let layer_record = &scan.artefacts[1];
+        assert_eq!(layer_record.kind, "layer");
+        assert_eq!(layer_record.offset, 0);
+        assert_eq!(
+            layer_record.digest,
+            Some(format!("sha256:{}", hex::encode(Sha256::digest(&layer))))
+        );
+    }
+
+    #[test]
+    fn reads_containerd_metadata() {
+        let data = bolt_bytes(vec![(
+            "v1",
+            BoltValue::Bucket(vec![(
+                "k8s.io",
+                BoltValue::Bucket(vec![
+                    (
+                        "containers",
+                        BoltValue::Bucket(vec![(
+                            "c0ffee",
+                            BoltValue::Bucket(vec![(
+                                "image",
+                                BoltValue::Value(b"docker.io/library/nginx:1.25"),
+                            )]),
+                        )]),
+                    ),
+                    (
+                        "images",
+                        BoltValue::Bucket(vec![(
+                            "docker.io/library/nginx:1.25",
+                            BoltValue::Bucket(vec![(
+                                "target",
+                                BoltValue::Bucket(vec![
+                                    ("digest", BoltValue::Value(b"sha256:feed")),
+                                    (
+                                        "mediatype",
+                                        BoltValue::Value(
+                                            b"application/vnd.oci.image.index.v1+json",
+                                        ),
+                                    ),
+                                ]),
+                            )]),
+                        )]),
+                    ),
+                ]),
+            )]),
+        )]);
+        let artefacts = containerd_artefacts(&data).expect("bolt");
+        assert_eq!(artefacts.len(), 2);
+        assert_eq!(artefacts[0].kind, "image");
+        assert_eq!(
+            artefacts[0].name.as_deref(),
+            Some("docker.io/library/nginx:1.25")
+        );
+        assert_eq!(artefacts[0].digest.as_deref(), Some("sha256:feed"));
+        assert_eq!(artefacts[0].namespace.as_deref(), Some("k8s.io"));
+        assert_eq!(artefacts[1].kind, "container");
+        assert_eq!(
+            artefacts[1].detail.as_deref(),
+            Some("docker.io/library/nginx:1.25")
+        );
+    }
+}
@@ -0,0 +1,460 @@
+//! Cookie stores that are not SQLite databases.
+//!
+//! Safari and other WebKit clients on macOS and iOS keep cookies in
+//! `Cookies.binarycookies`: a big-endian header (`cook`, page count, page
+//! sizes), pages of little-endian cookie records, a checksum over the pages,
+//! and a trailer that sizes an optional binary plist. Cookie times are Cocoa
+//! absolute seconds stored as doubles.
+//!
+//! curl, wget, yt-dlp, and older browsers export the Netscape `cookies.txt`
+//! format: one cookie per line, seven tab-separated fields (domain, include
+//! subdomains, path, secure, expiry in Unix seconds, name, value). curl marks
+//! HttpOnly cookies with a `#HttpOnly_` domain prefix.
+//!
+//! Both map onto [`BrowserCookieRecord`] so they land next to the cookies read
+//! from Chromium and Firefox databases.
+
+use std::path::Path;
+
+use crate::parsers::browser::BrowserCookieRecord;
+use crate::parsers::time::{mac_absolute_to_datetime, unix_micro_to_datetime};
+
+/// File magic of `Cookies.binarycookies`.
+pub const BINARY_COOKIES_MAGIC: &[u8; 4] = b"cook";
+/// Header lines that start a Netscape cookie file.
+pub const NETSCAPE_HEADERS: [&str; 2] = ["# Netscape HTTP Cookie File", "# HTTP Cookie File"];
+
+/// Big-endian marker after the page checksum.
+const TRAILER_MAGIC: u32 = 0x0717_2005;
+/// Every page starts with these bytes.
+const PAGE_MAGIC: [u8; 4] = [0, 0, 1, 0];
+/// More pages than this is a false positive, not a cookie store.
+const MAX_PAGES: u32 = 4096;
+/// Largest trailing plist accepted; Safari writes about 75 bytes.
+const MAX_TRAILER_PLIST: u32 = 64 * 1024;
+/// Fixed part of a cookie record, through the creation time.
+const COOKIE_HEADER_LEN: usize = 56;
+const FLAG_SECURE: u32 = 0x1;
+const FLAG_HTTP_ONLY: u32 = 0x4;
+const HTTP_ONLY_PREFIX: &str = "#HttpOnly_";
+
+/// Layout of a `Cookies.binarycookies` file read from its header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BinaryCookiesLayout {
+    pub page_sizes: Vec<u32>,
+}
+
+impl BinaryCookiesLayout {
+    /// Reads the page table; `data` must hold the magic and the page sizes.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.get(..4)? != BINARY_COOKIES_MAGIC {
+            return None;
+        }
+        let pages = be_u32(data, 4)?;
+        if pages == 0 || pages > MAX_PAGES {
+            return None;
+        }
+        let page_sizes = (0..pages as usize)
+            .map(|i| be_u32(data, 8 + i * 4))
+            .collect::<Option<Vec<_>>>()?;
+        // A page holds at least its magic, count, and terminator.
+        if page_sizes.iter().any(|&size| size < 12) {
+            return None;
+        }
+        Some(Self { page_sizes })
+    }
+
+    /// Bytes needed to read the page table of a file with `pages` pages.
+    pub fn header_len_for(pages: u32) -> usize {
+        8 + pages as usize * 4
+    }
+
+    pub fn header_len(&self) -> usize {
+        Self::header_len_for(self.page_sizes.len() as u32)
+    }
+
+    /// Offset of the checksum that follows the last page.
+    pub fn pages_end(&self) -> u64 {
+        self.header_len() as u64 + self.page_sizes.iter().map(|&s| s as u64).sum::<u64>()
+    }
+
+    /// File length given the 12 bytes at [`Self::pages_end`] (checksum,
+    /// trailer magic, plist length). Without a trailer, the file ends after
+    /// the checksum.
+    pub fn file_len(&self, trailer: &[u8]) -> u64 {
+        let end = self.pages_end() + 4;
+        match (be_u32(trailer, 4), be_u32(trailer, 8)) {
+            (Some(TRAILER_MAGIC), Some(plist)) if plist <= MAX_TRAILER_PLIST => {
+                end + 8 + plist as u64
+            }
+            _ => end,
+        }
+    }
+}
+
+/// One cookie in the format's own terms, before it becomes a record.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredCookie {
+    pub host: String,
+    pub name: String,
+    pub value: String,
+    pub path: String,
+    pub expires: Option<chrono::NaiveDateTime>,
+    pub created: Option<chrono::NaiveDateTime>,
+    pub secure: bool,
+    pub http_only: bool,
+}
+
+/// Cookies of a binarycookies file and whether its checksum matched. Pages
+/// past the end of `data` (truncated carves) are skipped, as are records that
+/// point outside their page.
+pub fn parse_binary_cookies(data: &[u8]) -> Option<(Vec<StoredCookie>, bool)> {
+    let layout = BinaryCookiesLayout::parse(data)?;
+    let mut cookies = Vec::new();
+    let mut checksum = 0u32;
+    let mut offset = layout.header_len();
+    for &size in &layout.page_sizes {
+        let Some(page) = data.get(offset..offset + size as usize) else {
+            break;
+        };
+        offset += size as usize;
+        checksum = page
+            .iter()
+            .step_by(4)
+            .fold(checksum, |sum, &b| sum.wrapping_add(b as u32));
+        if page[..4] != PAGE_MAGIC {
+            continue;
+        }
+        cookies.extend(parse_page(page));
+    }
+    let complete = offset as u64 == layout.pages_end();
+    let checksum_ok = complete && be_u32(data, offset) == Some(checksum);
+    Some((cookies, checksum_ok))
+}
+
+fn parse_page(page: &[u8]) -> Vec<StoredCookie> {
+    let Some(count) = le_u32(page, 4) else {
+        return Vec::new();
+    };
+    (0..count as usize)
+        .map_while(|i| le_u32(page, 8 + i * 4))
+        .filter_map(|start| parse_cookie(page.get(start as usize..)?))
+        .collect()
+}
+
+fn parse_cookie(data: &[u8]) -> Option<StoredCookie> {
+    let size = le_u32(data, 0)? as usize;
+    if size < COOKIE_HEADER_LEN {
+        return None;
+    }
+    let record = data.get(..size)?;
+    let flags = le_u32(record, 8)?;
+    let field = |at: usize| -> Option<String> {
+        let start = le_u32(record, at)? as usize;
+        let bytes = record.get(start..)?;
+        let end = bytes.iter().position(|&b| b == 0)?;
+        Some(String::from_utf8_lossy(&bytes[..end]).into_owned())
+    };
+    let time = |at: usize| {
+        let bytes: [u8; 8] = record.get(at..at + 8)?.try_into().ok()?;
+        mac_absolute_to_datetime(f64::from_le_bytes(bytes))
+    };
+    Some(StoredCookie {
+        host: field(16)?,
+        name: field(20)?,
+        path: field(24)?,
+        value: field(28)?,
+        expires: time(40),
+        created: time(48),
+        secure: flags & FLAG_SECURE != 0,
+        http_only: flags & FLAG_HTTP_ONLY != 0,
+    })
+}
+
+/// Whether `data` starts with a Netscape cookie file header.
+pub fn is_netscape_cookies(data: &[u8]) -> bool {
+    NETSCAPE_HEADERS
+        .iter()
+        .any(|header| data.starts_with(header.as_bytes()))
+}
+
+/// Parses one line of a Netscape cookie file; `None` for comments, blank
+/// lines, and lines that are not cookies.
+pub fn parse_netscape_line(line: &str) -> Option<StoredCookie> {
+    let line = line.trim_end_matches(['\r', '\n']);
+    let (line, http_only) = match line.strip_prefix(HTTP_ONLY_PREFIX) {
+        Some(rest) => (rest, true),
+        None => (line, false),
+    };
+    if line.starts_with('#') {
+        return None;
+    }
+    let fields: Vec<&str> = line.split('\t').collect();
+    let [host, subdomains, path, secure, expiry, name, value] = fields[..] else {
+        return None;
+    };
+    let flag = |field: &str| match field {
+        "TRUE" => Some(true),
+        "FALSE" => Some(false),
+        _ => None,
+    };
+    flag(subdomains)?;
+    let expiry: i64 = expiry.parse().ok()?;
+    if host.is_empty() {
+        return None;
+    }
+    Some(StoredCookie {
+        host: host.to_string(),
+        name: name.to_string(),
+        value: value.to_string(),
+        path: path.to_string(),
+        // Zero marks a session cookie.
+        expires: (expiry > 0)
+            .then(|| unix_micro_to_datetime(expiry.saturating_mul(1_000_000)))
+            .flatten(),
+        created: None,
+        secure: flag(secure)?,
+        http_only,
+    })
+}
+
+/// Classification of one Netscape cookie file line, used to find where a
+/// carved file ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetscapeLine {
+    Cookie,
+    /// Comment or blank line.
+    Filler,
+    /// Anything else: the file has ended.
+    Foreign,
+}
+
+pub fn classify_netscape_line(line: &[u8]) -> NetscapeLine {
+    let Ok(text) = std::str::from_utf8(line) else {
+        return NetscapeLine::Foreign;
+    };
+    if parse_netscape_line(text).is_some() {
+        return NetscapeLine::Cookie;
+    }
+    let trimmed = text.trim_end_matches(['\r', '\n']);
+    if trimmed.trim().is_empty()
+        || (trimmed.starts_with('#') && !trimmed.chars().any(|c| c.is_control() && c != '\t'))
+    {
+        NetscapeLine::Filler
+    } else {
+        NetscapeLine::Foreign
+    }
+}
+
+pub fn parse_netscape_cookies(data: &[u8]) -> Vec<StoredCookie> {
+    String::from_utf8_lossy(data)
+        .lines()
+        .filter_map(parse_netscape_line)
+        .collect()
+}
+
+/// Cookie records from a carved binarycookies or Netscape cookie file; other
+/// content yields none.
+pub fn extract_cookie_file(
+    data: &[u8],
+    run_id: &str,
+    source_relative: &str,
+) -> Vec<BrowserCookieRecord> {
+    let (browser, cookies) = if data.starts_with(BINARY_COOKIES_MAGIC) {
+        let cookies = parse_binary_cookies(data)
+            .map(|(cookies, _)| cookies)
+            .unwrap_or_default();
+        ("safari", cookies)
+    } else if is_netscape_cookies(data) {
+        ("netscape", parse_netscape_cookies(data))
+    } else {
+        return Vec::new();
+    };
+    cookies
+        .into_iter()
+        .map(|cookie| BrowserCookieRecord {
+            run_id: run_id.to_string(),
+            browser: browser.to_string(),
+            profile: "Default".to_string(),
+            host: cookie.host,
+            name: cookie.name,
+            value: Some(cookie.value),
+            path: Some(cookie.path),
+            expires_utc: cookie.expires,
+            last_access_utc: None,
+            creation_utc: cookie.created,
+            is_secure: Some(cookie.secure),
+            is_http_only: Some(cookie.http_only),
+            source_file: Path::new(source_relative).to_path_buf(),
+        })
+        .collect()
+}
+
+fn be_u32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn le_u32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+/// One cookie record with the given flags and times.
+#[cfg(test)]
+fn cookie_record(host: &str, name: &str, path: &str, value: &str, flags: u32) -> Vec<u8> {
+    let mut strings = Vec::new();
+    let mut offsets = Vec::new();
+    for field in [host, name, path, value] {
+        offsets.push((COOKIE_HEADER_LEN + strings.len()) as u32);
+        strings.extend_from_slice(field.as_bytes());
+        strings.push(0);
+    }
+    let size = (COOKIE_HEADER_LEN + strings.len()) as u32;
+    let mut out = Vec::new();
+    out.extend_from_slice(&size.to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+    out.extend_from_slice(&flags.to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+    for offset in offsets {
+        out.extend_from_slice(&offset.to_le_bytes());
+    }
+    out.extend_from_slice(&[0u8; 8]);
+    // 2030-01-01 and 2024-01-01 in Cocoa absolute time.
+    out.extend_from_slice(&915_148_800f64.to_le_bytes());
+    out.extend_from_slice(&725_760_000f64.to_le_bytes());
+    out.extend_from_slice(&strings);
+    out
+}
+
+/// A two-page binarycookies file with three cookies and a trailing plist.
+#[cfg(test)]
+pub(crate) fn binary_cookies_bytes() -> Vec<u8> {
+    let page = |records: Vec<Vec<u8>>| {
+        let mut offset = 8 + records.len() * 4 + 4;
+        let mut out = PAGE_MAGIC.to_vec();
+        out.extend_from_slice(&(records.len() as u32).to_le_bytes());
+        for record in &records {
+            out.extend_from_slice(&(offset as u32).to_le_bytes());
+            offset += record.len();
+        }
+        out.extend_from_slice(&[0u8; 4]);
+        for record in records {
+            out.extend_from_slice(&record);
+        }
+        out
+    };
+    let pages = [
+        page(vec![
+            cookie_record(
+                ".example.com",
+                "sid",
+                "/",
+                "abc123",
+                FLAG_SECURE | FLAG_HTTP_ONLY,
+            ),
+            cookie_record("example.com", "pref", "/app", "dark", 0),
+        ]),
+        page(vec![cookie_record(
+            ".icloud.com",
+            "X-APPLE-WEBAUTH",
+            "/",
+            "t",
+            FLAG_SECURE,
+        )]),
+    ];
+    let mut out = BINARY_COOKIES_MAGIC.to_vec();
+    out.extend_from_slice(&(pages.len() as u32).to_be_bytes());
+    for page in &pages {
+        out.extend_from_slice(&(page.len() as u32).to_be_bytes());
+    }
+    let mut checksum = 0u32;
+    for page in &pages {
+        checksum = page
+            .iter()
+            .step_by(4)
+            .fold(checksum, |sum, &b| sum.wrapping_add(b as u32));
+        out.extend_from_slice(page);
+    }
+    out.extend_from_slice(&checksum.to_be_bytes());
+    out.extend_from_slice(&TRAILER_MAGIC.to_be_bytes());
+    let plist = b"bplist00\xd1\x01\x02";
+    out.extend_from_slice(&(plist.len() as u32).to_be_bytes());
+    out.extend_from_slice(plist);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_binary_cookies_pages_and_trailer() {
+        let data = binary_cookies_bytes();
+        let layout = BinaryCookiesLayout::parse(&data).expect("layout");
+        assert_eq!(layout.page_sizes.len(), 2);
+        let pages_end = layout.pages_end() as usize;
+        assert_eq!(layout.file_len(&data[pages_end..]), data.len() as u64);
+
+        let (cookies, checksum_ok) = parse_binary_cookies(&data).expect("cookies");
+        assert!(checksum_ok);
+        assert_eq!(cookies.len(), 3);
+        assert_eq!(cookies[0].host, ".example.com");
+        assert_eq!(cookies[0].value, "abc123");
+        assert!(cookies[0].secure && cookies[0].http_only);
+        assert_eq!(cookies[1].path, "/app");
+        assert!(!cookies[1].secure);
+        assert_eq!(
+            cookies[0].expires.unwrap().date(),
+            chrono::NaiveDate::from_ymd_opt(2030, 1, 1).unwrap()
+        );
+
+        // A truncated carve keeps the complete pages.
+        let (partial, checksum_ok) = parse_binary_cookies(&data[..pages_end - 4]).expect("cookies");
+        assert!(!checksum_ok);
+        assert_eq!(partial.len(), 2);
+    }
+
+    #[test]
+    fn parses_netscape_lines() {
+        let text = "# Netscape HTTP Cookie File\n\
+                    # https://curl.se/docs/http-cookies.html\n\
+                    \n\
+                    .example.com\tTRUE\t/\tTRUE\t1893456000\tsid\tabc\n\
+                    #HttpOnly_example.org\tFALSE\t/login\tFALSE\t0\ttoken\txyz\r\n";
+        let cookies = parse_netscape_cookies(text.as_bytes());
+        assert_eq!(cookies.len(), 2);
+        assert!(cookies[0].secure && !cookies[0].http_only);
+        assert!(cookies[0].expires.is_some());
+        assert_eq!(cookies[1].host, "example.org");
+        assert!(cookies[1].http_only);
+        assert_eq!(cookies[1].expires, None);
+        assert_eq!(cookies[1].value, "xyz");
+
+        assert_eq!(classify_netscape_line(b"# comment\n"), NetscapeLine::Filler);
+        assert_eq!(
+            classify_netscape_line(b"a.com\tTRUE\t/\tFALSE\t0\tn\tv\n"),
+            NetscapeLine::Cookie
+        );
+        assert_eq!(classify_netscape_line(b"<html>\n"), NetscapeLine::Foreign);
+        assert_eq!(
+            classify_netscape_line(b"a.com\tyes\t/\tFALSE\t0\tn\tv\n"),
+            NetscapeLine::Foreign
+        );
+    }
+
+    #[test]
+    fn maps_both_formats_onto_cookie_records() {
+        let records = extract_cookie_file(&binary_cookies_bytes(), "run", "binarycookies/x");
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[2].browser, "safari");
+        assert_eq!(records[2].name, "X-APPLE-WEBAUTH");
+        assert!(records[2].creation_utc.is_some());
+
+        let text = b"# HTTP Cookie File\nexample.com\tFALSE\t/\tFALSE\t0\tid\t1\n";
+        let records = extract_cookie_file(text, "run", "netscape_cookies/x");
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].browser, "netscape");
+        assert_eq!(records[0].is_http_only, Some(false));
+
+        assert!(extract_cookie_file(b"cookie jar", "run", "x").is_empty());
+    }
+}
@@ -0,0 +1,450 @@
+//! ELF headers: the file header, the program header table, and the section
+//! header table, in either byte order and class.
+//!
+//! An ELF file has no size field. Its bytes are the file header, the two
+//! header tables, the file contents of every segment (`p_offset + p_filesz`),
+//! and the contents of every section that occupies file space
+//! (`sh_offset + sh_size`, all types but `SHT_NOBITS`). Linkers put the section
+//! header table last, but stripped files and core dumps may have none, so the
+//! end is the largest of all of these.
+
+use super::executable::ExecutableRecord;
+
+const ELF_MAGIC: [u8; 4] = [0x7F, b'E', b'L', b'F'];
+const ET_REL: u16 = 1;
+const ET_EXEC: u16 = 2;
+const ET_DYN: u16 = 3;
+const ET_CORE: u16 = 4;
+const PT_INTERP: u32 = 3;
+const SHT_NOBITS: u32 = 8;
+/// `e_shstrndx` value meaning the index is stored in section 0.
+const SHN_XINDEX: u16 = 0xFFFF;
+/// Contents ending beyond this are taken as garbage rather than a file.
+const MAX_EXTENT: u64 = 1 << 40;
+
+/// Why an ELF file could not be read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ElfError {
+    /// The data ends inside the file header or a header table.
+    Incomplete,
+    Invalid(&'static str),
+}
+
+/// The ELF file header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ElfHeader {
+    pub is_64: bool,
+    pub big_endian: bool,
+    /// `e_type`: relocatable, executable, shared object, or core.
+    pub file_type: u16,
+    pub machine: u16,
+    pub ph_offset: u64,
+    pub ph_entry_size: u16,
+    pub ph_count: u16,
+    pub sh_offset: u64,
+    pub sh_entry_size: u16,
+    pub sh_count: u16,
+}
+
+/// What the header tables say about the file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ElfLayout {
+    /// End of the last header, table, segment, or section.
+    pub size: u64,
+    pub segment_count: u32,
+    pub section_count: u32,
+    /// A `PT_INTERP` segment: a dynamically linked executable, which for an
+    /// `ET_DYN` file means a position-independent executable.
+    pub has_interpreter: bool,
+}
+
+/// Parse the file header at the start of `data` (52 bytes for 32-bit files,
+/// 64 for 64-bit ones).
+pub fn parse_header(data: &[u8]) -> Result<ElfHeader, ElfError> {
+    if data.len() < 16 {
+        return Err(ElfError::Incomplete);
+    }
+    if data[..4] != ELF_MAGIC {
+        return Err(ElfError::Invalid("no ELF magic"));
+    }
+    let is_64 = match data[4] {
+        1 => false,
+        2 => true,
+        _ => return Err(ElfError::Invalid("unknown ELF class")),
+    };
+    let big_endian = match data[5] {
+        1 => false,
+        2 => true,
+        _ => return Err(ElfError::Invalid("unknown ELF byte order")),
+    };
+    if data[6] != 1 {
+        return Err(ElfError::Invalid("unknown ELF identification version"));
+    }
+    let header_len = if is_64 { 64 } else { 52 };
+    if data.len() < header_len {
+        return Err(ElfError::Incomplete);
+    }
+    let r = Reader { big_endian };
+    let file_type = r.u16(data, 16);
+    if !(ET_REL..=ET_CORE).contains(&file_type) {
+        return Err(ElfError::Invalid("unknown ELF file type"));
+    }
+    if r.u32(data, 20) != 1 {
+        return Err(ElfError::Invalid("unknown ELF version"));
+    }
+    let (ph_offset, sh_offset, rest) = if is_64 {
+        (r.u64(data, 32), r.u64(data, 40), 52)
+    } else {
+        (u64::from(r.u32(data, 28)), u64::from(r.u32(data, 32)), 40)
+    };
+    if usize::from(r.u16(data, rest)) != header_len {
+        return Err(ElfError::Invalid("ELF header size mismatch"));
+    }
+    let header = ElfHeader {
+        is_64,
+        big_endian,
+        file_type,
+        machine: r.u16(data, 18),
+        ph_offset,
+        ph_entry_size: r.u16(data, rest + 2),
+        ph_count: r.u16(data, rest + 4),
+        sh_offset,
+        sh_entry_size: r.u16(data, rest + 6),
+        sh_count: r.u16(data, rest + 8),
+    };
+    let (ph_len, sh_len) = if is_64 { (56, 64) } else { (32, 40) };
+    if header.ph_count > 0 && (header.ph_entry_size != ph_len || ph_offset < header_len as u64) {
+        return Err(ElfError::Invalid("program header table inconsistent"));
+    }
+    if header.sh_count > 0 && (header.sh_entry_size != sh_len || sh_offset < header_len as u64) {
+        return Err(ElfError::Invalid("section header table inconsistent"));
+    }
+    let strndx = r.u16(data, rest + 10);
+    if header.sh_count > 0 && strndx >= header.sh_count && strndx != SHN_XINDEX {
+        return Err(ElfError::Invalid("section name table index out of range"));
+    }
+    if header.ph_count == 0 && header.sh_count == 0 {
+        return Err(ElfError::Invalid("no program or section headers"));
+    }
+    if header.table_end() > MAX_EXTENT {
+        return Err(ElfError::Invalid("header table beyond any plausible file"));
+    }
+    Ok(header)
+}
+
+impl ElfHeader {
+    fn header_len(&self) -> u64 {
+        if self.is_64 { 64 } else { 52 }
+    }
+
+    /// End of the file header and both header tables; the size when the
+    /// tables cannot be read.
+    pub fn table_end(&self) -> u64 {
+        let ph = self.ph_offset + u64::from(self.ph_entry_size) * u64::from(self.ph_count);
+        let sh = self.sh_offset + u64::from(self.sh_entry_size) * u64::from(self.sh_count);
+        let ph = if self.ph_count > 0 { ph } else { 0 };
+        let sh = if self.sh_count > 0 { sh } else { 0 };
+        self.header_len().max(ph).max(sh)
+    }
+
+    /// Read both header tables through `read` (file offset, buffer; returns
+    /// the bytes read) and find the end of the file.
+    pub fn layout(
+        &self,
+        read: &mut dyn FnMut(u64, &mut [u8]) -> usize,
+    ) -> Result<ElfLayout, ElfError> {
+        let r = Reader {
+            big_endian: self.big_endian,
+        };
+        let mut size = self.table_end();
+        let mut has_interpreter = false;
+
+        let program = read_table(read, self.ph_offset, self.ph_entry_size, self.ph_count)?;
+        for entry in program.chunks_exact(usize::from(self.ph_entry_size.max(1))) {
+            let kind = r.u32(entry, 0);
+            let (offset, file_size) = if self.is_64 {
+                (r.u64(entry, 8), r.u64(entry, 32))
+            } else {
+                (u64::from(r.u32(entry, 4)), u64::from(r.u32(entry, 16)))
+            };
+            has_interpreter |= kind == PT_INTERP;
+            if file_size > 0 {
+                size = size.max(extent(offset, file_size)?);
+            }
+        }
+
+        let sections = read_table(read, self.sh_offset, self.sh_entry_size, self.sh_count)?;
+        for entry in sections.chunks_exact(usize::from(self.sh_entry_size.max(1))) {
+            let kind = r.u32(entry, 4);
+            let (offset, section_size) = if self.is_64 {
+                (r.u64(entry, 24), r.u64(entry, 32))
+            } else {
+                (u64::from(r.u32(entry, 16)), u64::from(r.u32(entry, 20)))
+            };
+            if kind != SHT_NOBITS && section_size > 0 {
+                size = size.max(extent(offset, section_size)?);
+            }
+        }
+
+        Ok(ElfLayout {
+            size,
+            segment_count: u32::from(self.ph_count),
+            section_count: u32::from(self.sh_count),
+            has_interpreter,
+        })
+    }
+
+    /// `exe` for executables, including position-independent ones, `so` for
+    /// shared objects, `object` for relocatable files, and `core` for core
+    /// dumps.
+    pub fn kind(&self, layout: &ElfLayout) -> &'static str {
+        match self.file_type {
+            ET_EXEC => "exe",
+            ET_DYN if layout.has_interpreter => "exe",
+            ET_DYN => "so",
+            ET_REL => "object",
+            _ => "core",
+        }
+    }
+
+    pub fn architecture(&self) -> &'static str {
+        match (self.machine, self.is_64) {
+            (3, _) => "x86",
+            (62, _) => "x86_64",
+            (40, _) => "arm",
+            (183, _) => "arm64",
+            (8, false) => "mips",
+            (8, true) => "mips64",
+            (20, _) => "ppc",
+            (21, _) => "ppc64",
+            (22, _) => "s390x",
+            (2, _) => "sparc",
+            (43, _) => "sparc64",
+            (243, false) => "riscv32",
+            (243, true) => "riscv64",
+            (258, _) => "loongarch64",
+            _ => "unknown",
+        }
+    }
+
+    pub fn to_record(
+        &self,
+        run_id: &str,
+        source_file: &str,
+        layout: &ElfLayout,
+    ) -> ExecutableRecord {
+        ExecutableRecord {
+            run_id: run_id.to_string(),
+            format: "elf".to_string(),
+            kind: self.kind(layout).to_string(),
+            architecture: self.architecture().to_string(),
+            bits: if self.is_64 { 64 } else { 32 },
+            compile_time_utc: None,
+            subsystem: None,
+            section_count: layout.section_count,
+            import_count: None,
+            import_hash: None,
+            signed: false,
+            source_file: source_file.to_string(),
+        }
+    }
+}
+
+fn read_table(
+    read: &mut dyn FnMut(u64, &mut [u8]) -> usize,
+    offset: u64,
+    entry_size: u16,
+    count: u16,
+) -> Result<Vec<u8>, ElfError> {
+    let mut table = vec![0u8; usize::from(entry_size) * usize::from(count)];
+    if !table.is_empty() && read(offset, &mut table) < table.len() {
+        return Err(ElfError::Incomplete);
+    }
+    Ok(table)
+}
+
+fn extent(offset: u64, len: u64) -> Result<u64, ElfError> {
+    offset
+        .checked_add(len)
+        .filter(|&end| end <= MAX_EXTENT)
+        .ok_or(ElfError::Invalid("contents beyond any plausible file"))
+}
+
+#[derive(Clone, Copy)]
+struct Reader {
+    big_endian: bool,
+}
+
+impl Reader {
+    fn u16(self, data: &[u8], at: usize) -> u16 {
+        let bytes = [data[at], data[at + 1]];
+        if self.big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        }
+    }
+
+    fn u32(self, data: &[u8], at: usize) -> u32 {
+        let bytes = data[at..at + 4].try_into().unwrap_or_default();
+        if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        }
+    }
+
+    fn u64(self, data: &[u8], at: usize) -> u64 {
+        let bytes = data[at..at + 8].try_into().unwrap_or_default();
+        if self.big_endian {
+            u64::from_be_bytes(bytes)
+        } else {
+            u64::from_le_bytes(bytes)
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod testing {
+    /// Length of [`elf_bytes`]; the section header table comes last.
+    pub(crate) const ELF_LEN: usize = 0x400 + 3 * 64;
+
+    /// A little-endian 64-bit position-independent executable for x86_64: an
+    /// interpreter and a load segment, a `.text` section, a `.bss` section
+    /// reaching past the end of the file, and the section header table.
+    pub(crate) fn elf_bytes() -> Vec<u8> {
+        let mut data = vec![0u8; ELF_LEN];
+        let put16 = |d: &mut Vec<u8>, at: usize, v: u16| {
+            d[at..at + 2].copy_from_slice(&v.to_le_bytes());
+        };
+        let put32 = |d: &mut Vec<u8>, at: usize, v: u32| {
+            d[at..at + 4].copy_from_slice(&v.to_le_bytes());
+        };
+        let put64 = |d: &mut Vec<u8>, at: usize, v: u64| {
+            d[at..at + 8].copy_from_slice(&v.to_le_bytes());
+        };
+        data[..7].copy_from_slice(&[0x7F, b'E', b'L', b'F', 2, 1, 1]);
+        put16(&mut data, 16, 3);
+        put16(&mut data, 18, 62);
+        put32(&mut data, 20, 1);
+        put64(&mut data, 32, 0x40);
+        put64(&mut data, 40, 0x400);
+        put16(&mut data, 52, 64);
+        put16(&mut data, 54, 56);
+        put16(&mut data, 56, 2);
+        put16(&mut data, 58, 64);
+        put16(&mut data, 60, 3);
+        put16(&mut data, 62, 0);
+        // PT_INTERP at 0x200 and PT_LOAD of 0x200..0x400.
+        put32(&mut data, 0x40, 3);
+        put64(&mut data, 0x40 + 8, 0x200);
+        put64(&mut data, 0x40 + 32, 0x1C);
+        put32(&mut data, 0x78, 1);
+        put64(&mut data, 0x78 + 8, 0x200);
+        put64(&mut data, 0x78 + 32, 0x200);
+        data[0x200..0x21B].copy_from_slice(b"/lib64/ld-linux-x86-64.so.2");
+        // Null section, .text (PROGBITS), .bss (NOBITS) at 0x400 + 64.
+        let text = 0x400 + 64;
+        put32(&mut data, text + 4, 1);
+        put64(&mut data, text + 24, 0x300);
+        put64(&mut data, text + 32, 0x100);
+        let bss = text + 64;
+        put32(&mut data, bss + 4, 8);
+        put64(&mut data, bss + 24, 0x400);
+        put64(&mut data, bss + 32, 0x10_0000);
+        data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::{ELF_LEN, elf_bytes};
+    use super::*;
+
+    fn read_from(data: &[u8]) -> impl FnMut(u64, &mut [u8]) -> usize + '_ {
+        |offset, buf| {
+            let start = (offset as usize).min(data.len());
+            let n = buf.len().min(data.len() - start);
+            buf[..n].copy_from_slice(&data[start..start + n]);
+            n
+        }
+    }
+
+    #[test]
+    fn sizes_by_tables_segments_and_sections() {
+        let data = elf_bytes();
+        let header = parse_header(&data).expect("header");
+        assert_eq!(header.table_end(), ELF_LEN as u64);
+        let layout = header.layout(&mut read_from(&data)).expect("layout");
+        assert_eq!(layout.size, ELF_LEN as u64);
+        assert!(layout.has_interpreter);
+        assert_eq!(header.kind(&layout), "exe");
+        assert_eq!(header.architecture(), "x86_64");
+
+        // A segment reaching past the section header table sets the end.
+        let mut data = elf_bytes();
+        data[0x78 + 32..0x78 + 40].copy_from_slice(&0x800u64.to_le_bytes());
+        let header = parse_header(&data).expect("header");
+        let layout = header.layout(&mut read_from(&data)).expect("layout");
+        assert_eq!(layout.size, 0xA00);
+
+        assert_eq!(
+            header.layout(&mut read_from(&data[..0x420])),
+            Err(ElfError::Incomplete)
+        );
+    }
+
+    #[test]
+    fn reads_big_endian_32_bit_files() {
+        let mut data = vec![0u8; 0x100];
+        data[..7].copy_from_slice(&[0x7F, b'E', b'L', b'F', 1, 2, 1]);
+        data[16..18].copy_from_slice(&2u16.to_be_bytes());
+        data[18..20].copy_from_slice(&8u16.to_be_bytes());
+        data[20..24].copy_from_slice(&1u32.to_be_bytes());
+        data[28..32].copy_from_slice(&0x34u32.to_be_bytes());
+        data[40..42].copy_from_slice(&52u16.to_be_bytes());
+        data[42..44].copy_from_slice(&32u16.to_be_bytes());
+        data[44..46].copy_from_slice(&1u16.to_be_bytes());
+        data[0x34..0x38].copy_from_slice(&1u32.to_be_bytes());
+        data[0x34 + 4..0x34 + 8].copy_from_slice(&0x80u32.to_be_bytes());
+        data[0x34 + 16..0x34 + 20].copy_from_slice(&0x80u32.to_be_bytes());
+        let header = parse_header(&data).expect("header");
+        let layout = header.layout(&mut read_from(&data)).expect("layout");
+        assert_eq!(layout.size, 0x100);
+        assert_eq!(header.kind(&layout), "exe");
+        assert_eq!(header.architecture(), "mips");
+        assert!(!layout.has_interpreter);
+    }
+
+    #[test]
+    fn classifies_shared_objects() {
+        let mut data = elf_bytes();
+        // Turn PT_INTERP into PT_NOTE.
+        data[0x40..0x44].copy_from_slice(&4u32.to_le_bytes());
+        let header = parse_header(&data).expect("header");
+        let layout = header.layout(&mut read_from(&data)).expect("layout");
+        assert_eq!(header.kind(&layout), "so");
+    }
+
+    #[test]
+    fn rejects_inconsistent_headers() {
+        let mut data = elf_bytes();
+        data[52] = 40;
+        assert_eq!(
+            parse_header(&data),
+            Err(ElfError::Invalid("ELF header size mismatch"))
+        );
+        let mut data = elf_bytes();
+        data[54] = 32;
+        assert_eq!(
+            parse_header(&data),
+            Err(ElfError::Invalid("program header table inconsistent"))
+        );
+        let mut data = elf_bytes();
+        data[16] = 9;
+        assert_eq!(
+            parse_header(&data),
+            Err(ElfError::Invalid("unknown ELF file type"))
+        );
+    }
+}
@@ -0,0 +1,683 @@
+//! Expert Witness (EWF) segment sets and acquisition metadata.
+//!
+//! Sector data is read through libewf (`ewf` feature); this module handles
+//! what libewf leaves implicit:
+//! - [`segment_paths`] lists the segments of a set from its first file
+//!   (`.E01`, `.Ex01`, `.L01`, `.Lx01`) and checks each one's signature and
+//!   segment number, that no segment is missing in the middle, and that the
+//!   last one closes the set with a `done` section.
+//! - [`acquisition_info`] reads case data (examiner, notes, ...) and the
+//!   stored MD5/SHA-1 from the section chain without libewf.
+//!
+//! EWF1 (`E01`/`L01`) section descriptors precede their data and chain
+//! forward from offset 13. EWF2 (`Ex01`/`Lx01`) descriptors follow their data
+//! and chain backwards from the end of the segment. Both carry an Adler-32 of
+//! the descriptor, which is checked before a section is trusted.
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use chrono::DateTime;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::parsers::inflate;
+
+const EWF1_EVIDENCE: &[u8; 8] = b"EVF\x09\x0d\x0a\xff\x00";
+const EWF1_LOGICAL: &[u8; 8] = b"LVF\x09\x0d\x0a\xff\x00";
+const EWF2_EVIDENCE: &[u8; 8] = b"EVF2\x0d\x0a\x81\x00";
+const EWF2_LOGICAL: &[u8; 8] = b"LEF2\x0d\x0a\x81\x00";
+
+const EWF1_HEADER_LEN: u64 = 13;
+const EWF1_DESCRIPTOR_LEN: u64 = 76;
+const EWF2_HEADER_LEN: u64 = 32;
+const EWF2_DESCRIPTOR_LEN: u64 = 64;
+
+const EWF2_CASE_DATA: u32 = 0x02;
+const EWF2_MD5_HASH: u32 = 0x08;
+const EWF2_SHA1_HASH: u32 = 0x09;
+const EWF2_DONE: u32 = 0x0F;
+
+/// Upper bound on sections walked per segment.
+const MAX_SECTIONS: usize = 1 << 20;
+/// Largest header section decompressed.
+const MAX_HEADER_BYTES: usize = 1024 * 1024;
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum EwfError {
+    #[error("io error on {path}: {source}")]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("{0} is not the first segment of an EWF set (.E01, .Ex01, .L01, .Lx01)")]
+    NotFirstSegment(PathBuf),
+    #[error("{0} is not an EWF segment of the expected format")]
+    BadSignature(PathBuf),
+    #[error("{path} holds segment {found}, expected {expected}")]
+    SegmentNumber {
+        path: PathBuf,
+        expected: u32,
+        found: u32,
+    },
+    #[error("segment {0} is missing")]
+    MissingSegment(PathBuf),
+    #[error("EWF set is incomplete: {0} does not end with a done section")]
+    Incomplete(PathBuf),
+}
+
+/// The four EWF container flavours.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EwfFormat {
+    /// `.E01`, EnCase 1-7 physical evidence.
+    E01,
+    /// `.L01`, EnCase 1-7 logical evidence.
+    L01,
+    /// `.Ex01`, EnCase 7+ physical evidence (EWF2).
+    Ex01,
+    /// `.Lx01`, EnCase 7+ logical evidence (EWF2).
+    Lx01,
+}
+
+impl EwfFormat {
+    /// Format of a first-segment extension, case-insensitive.
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        match ext.to_ascii_lowercase().as_str() {
+            "e01" => Some(Self::E01),
+            "l01" => Some(Self::L01),
+            "ex01" => Some(Self::Ex01),
+            "lx01" => Some(Self::Lx01),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::E01 => "E01",
+            Self::L01 => "L01",
+            Self::Ex01 => "Ex01",
+            Self::Lx01 => "Lx01",
+        }
+    }
+
+    fn signature(self) -> &'static [u8; 8] {
+        match self {
+            Self::E01 => EWF1_EVIDENCE,
+            Self::L01 => EWF1_LOGICAL,
+            Self::Ex01 => EWF2_EVIDENCE,
+            Self::Lx01 => EWF2_LOGICAL,
+        }
+    }
+
+    fn is_ewf2(self) -> bool {
+        matches!(self, Self::Ex01 | Self::Lx01)
+    }
+}
+
+/// Acquisition metadata stored in an EWF set.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct AcquisitionInfo {
+    /// `E01`, `L01`, `Ex01`, or `Lx01`.
+    pub format: String,
+    /// Segment files in order.
+    pub segments: Vec<String>,
+    pub case_number: Option<String>,
+    pub evidence_number: Option<String>,
+    pub description: Option<String>,
+    pub examiner: Option<String>,
+    pub notes: Option<String>,
+    /// Acquisition time as stored; Unix timestamps are converted to RFC 3339.
+    pub acquisition_date: Option<String>,
+    /// Version of the acquisition software.
+    pub acquisition_software: Option<String>,
+    /// Operating system the image was acquired on.
+    pub acquisition_os: Option<String>,
+    /// Hex MD5 of the media, as stored by the acquisition tool.
+    pub stored_md5: Option<String>,
+    /// Hex SHA-1 of the media, as stored by the acquisition tool.
+    pub stored_sha1: Option<String>,
+    /// Hex MD5 of the media read during this run (`--verify-evidence-hash`
+    /// or `evidence_hashes: [md5]`).
+    pub computed_md5: Option<String>,
+    /// Whether `computed_md5` matches `stored_md5`.
+    pub md5_verified: Option<bool>,
+    /// Hex SHA-1 of the media read during this run (`evidence_hashes: [sha1]`).
+    pub computed_sha1: Option<String>,
+    /// Whether `computed_sha1` matches `stored_sha1`.
+    pub sha1_verified: Option<bool>,
+}
+
+impl AcquisitionInfo {
+    /// Record digests hashed while scanning and compare them with the stored
+    /// ones. An MD5 already checked by `--verify-evidence-hash` is kept.
+    pub fn verify_hashes(&mut self, md5: Option<&str>, sha1: Option<&str>) {
+        fn check(stored: Option<&str>, computed: &str) -> Option<bool> {
+            stored.map(|stored| stored.eq_ignore_ascii_case(computed))
+        }
+        if let Some(md5) = md5
+            && self.computed_md5.is_none()
+        {
+            self.md5_verified = check(self.stored_md5.as_deref(), md5);
+            self.computed_md5 = Some(md5.to_string());
+        }
+        if let Some(sha1) = sha1 {
+            self.sha1_verified = check(self.stored_sha1.as_deref(), sha1);
+            self.computed_sha1 = Some(sha1.to_string());
+        }
+    }
+}
+
+/// Extension of segment `number` (1-based) for a set whose first segment
+/// has extension `first`. After `01`-`99` the last two characters run through
+/// `AA`-`ZZ`; EWF1 then advances the first letter (`E` → `F` ...).
+fn segment_extension(first: &str, number: u32) -> Option<String> {
+    let prefix = &first[..first.len() - 2];
+    let upper = first.chars().next()?.is_ascii_uppercase();
+    if number < 100 {
+        return Some(format!("{prefix}{number:02}"));
+    }
+    let index = number - 100;
+    let (lead, rest) = (index / (26 * 26), index % (26 * 26));
+    let mut ext = String::new();
+    if prefix.len() == 1 {
+        let base = prefix.as_bytes()[0].to_ascii_uppercase();
+        let letter = u32::from(base) + lead;
+        if letter > u32::from(b'Z') {
+            return None;
+        }
+        ext.push(char::from(letter as u8));
+    } else if lead > 0 {
+        return None;
+    } else {
+        ext.push_str(prefix);
+    }
+    ext.push(char::from(b'A' + (rest / 26) as u8));
+    ext.push(char::from(b'A' + (rest % 26) as u8));
+    Some(if upper { ext } else { ext.to_ascii_lowercase() })
+}
+
+/// Segment files of the set starting at `first`, verified in order.
+pub fn segment_paths(first: &Path) -> Result<(EwfFormat, Vec<PathBuf>), EwfError> {
+    let ext = first
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .ok_or_else(|| EwfError::NotFirstSegment(first.to_path_buf()))?;
+    let format = EwfFormat::from_extension(ext)
+        .ok_or_else(|| EwfError::NotFirstSegment(first.to_path_buf()))?;
+    let mut paths = Vec::new();
+    for number in 1.. {
+        let Some(segment_ext) = segment_extension(ext, number) else {
+            break;
+        };
+        let path = first.with_extension(segment_ext);
+        if !path.is_file() {
+            break;
+        }
+        check_segment(&path, format, number)?;
+        paths.push(path);
+    }
+    if paths.is_empty() {
+        return Err(EwfError::Io {
+            path: first.to_path_buf(),
+            source: std::io::Error::from(std::io::ErrorKind::NotFound),
+        });
+    }
+    // A gap leaves later segments behind the first missing one.
+    let count = paths.len() as u32;
+    for skip in 2..=3 {
+        if let Some(later) = segment_extension(ext, count + skip)
+            && first.with_extension(later).is_file()
+        {
+            let missing = segment_extension(ext, count + 1).unwrap_or_default();
+            return Err(EwfError::MissingSegment(first.with_extension(missing)));
+        }
+    }
+    let last = paths.last().expect("at least one segment");
+    if !ends_with_done(last, format)? {
+        return Err(EwfError::Incomplete(last.clone()));
+    }
+    Ok((format, paths))
+}
+
+fn open(path: &Path) -> Result<File, EwfError> {
+    File::open(path).map_err(|source| EwfError::Io {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+fn read_at(file: &mut File, offset: u64, len: usize) -> Option<Vec<u8>> {
+    let mut buf = vec![0u8; len];
+    file.seek(SeekFrom::Start(offset)).ok()?;
+    file.read_exact(&mut buf).ok()?;
+    Some(buf)
+}
+
+fn check_segment(path: &Path, format: EwfFormat, expected: u32) -> Result<(), EwfError> {
+    let mut file = open(path)?;
+    let header = read_at(&mut file, 0, EWF2_HEADER_LEN as usize)
+        .or_else(|| read_at(&mut file, 0, EWF1_HEADER_LEN as usize))
+        .ok_or_else(|| EwfError::BadSignature(path.to_path_buf()))?;
+    if &header[..8] != format.signature() {
+        return Err(EwfError::BadSignature(path.to_path_buf()));
+    }
+    let found = if format.is_ewf2() {
+        header
+            .get(12..16)
+            .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+    } else {
+        Some(u32::from(u16::from_le_bytes([header[9], header[10]])))
+    }
+    .ok_or_else(|| EwfError::BadSignature(path.to_path_buf()))?;
+    if found != expected {
+        return Err(EwfError::SegmentNumber {
+            path: path.to_path_buf(),
+            expected,
+            found,
+        });
+    }
+    Ok(())
+}
+
+fn ends_with_done(path: &Path, format: EwfFormat) -> Result<bool, EwfError> {
+    let mut file = open(path)?;
+    let mut done = false;
+    for_each_section(&mut file, format, |kind, _| {
+        done |= matches!(kind, SectionKind::Done);
+    });
+    Ok(done)
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += u32::from(byte);
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+enum SectionKind<'a> {
+    Ewf1(&'a str),
+    Ewf2(u32),
+    Done,
+}
+
+/// Call `visit` with each verified section and a reader for its data
+/// (`offset`, `len`), in chain order.
+fn for_each_section(
+    file: &mut File,
+    format: EwfFormat,
+    mut visit: impl FnMut(SectionKind, (u64, u64)),
+) {
+    let Ok(file_len) = file.metadata().map(|m| m.len()) else {
+        return;
+    };
+    if format.is_ewf2() {
+        let Some(mut offset) = file_len.checked_sub(EWF2_DESCRIPTOR_LEN) else {
+            return;
+        };
+        for _ in 0..MAX_SECTIONS {
+            let Some(desc) = read_at(file, offset, EWF2_DESCRIPTOR_LEN as usize) else {
+                return;
+            };
+            if adler32(&desc[..60]) != u32::from_le_bytes(desc[60..64].try_into().unwrap()) {
+                return;
+            }
+            let kind = u32::from_le_bytes(desc[0..4].try_into().unwrap());
+            let previous = u64::from_le_bytes(desc[8..16].try_into().unwrap());
+            let size = u64::from_le_bytes(desc[16..24].try_into().unwrap());
+            let padding = u64::from(u32::from_le_bytes(desc[28..32].try_into().unwrap()));
+            let start = offset.saturating_sub(padding).saturating_sub(size);
+            let kind = if matches!(kind, EWF2_DONE) {
+                SectionKind::Done
+            } else {
+                SectionKind::Ewf2(kind)
+            };
+            visit(kind, (start, size));
+            if previous < EWF2_HEADER_LEN || previous >= offset {
+                return;
+            }
+            offset = previous;
+        }
+    } else {
+        let mut offset = EWF1_HEADER_LEN;
+        for _ in 0..MAX_SECTIONS {
+            let Some(desc) = read_at(file, offset, EWF1_DESCRIPTOR_LEN as usize) else {
+                return;
+            };
+            if adler32(&desc[..72]) != u32::from_le_bytes(desc[72..76].try_into().unwrap()) {
+                return;
+            }
+            let name_end = desc[..16].iter().position(|&b| b == 0).unwrap_or(16);
+            let name = std::str::from_utf8(&desc[..name_end]).unwrap_or("");
+            let next = u64::from_le_bytes(desc[16..24].try_into().unwrap());
+            let size = u64::from_le_bytes(desc[24..32].try_into().unwrap());
+            let data = (
+                offset + EWF1_DESCRIPTOR_LEN,
+                size.saturating_sub(EWF1_DESCRIPTOR_LEN),
+            );
+            if name == "done" {
+                visit(SectionKind::Done, data);
+                return;
+            }
+            visit(SectionKind::Ewf1(name), data);
+            if name == "next" || next <= offset {
+                return;
+            }
+            offset = next;
+        }
+    }
+}
+
+/// Read case data and stored hashes from a verified segment set.
+pub fn acquisition_info(format: EwfFormat, segments: &[PathBuf]) -> AcquisitionInfo {
+    let mut info = AcquisitionInfo {
+        format: format.name().to_string(),
+        segments: segments.iter().map(|p| p.display().to_string()).collect(),
+        ..Default::default()
+    };
+    // EWF1 `header2` (UTF-16) supersedes `header` (ASCII).
+    let mut header: Option<(bool, String)> = None;
+    for path in segments {
+        let Ok(mut file) = open(path) else {
+            continue;
+        };
+        let mut sections = Vec::new();
+        for_each_section(&mut file, format, |kind, range| {
+            let wanted = match kind {
+                SectionKind::Ewf1(name @ ("header" | "header2" | "hash" | "digest")) => {
+                    Some(name.to_string())
+                }
+                SectionKind::Ewf2(EWF2_CASE_DATA) => Some("case_data".to_string()),
+                SectionKind::Ewf2(EWF2_MD5_HASH) => Some("md5".to_string()),
+                SectionKind::Ewf2(EWF2_SHA1_HASH) => Some("sha1".to_string()),
+                _ => None,
+            };
+            if let Some(name) = wanted {
+                sections.push((name, range));
+            }
+        });
+        for (name, (offset, len)) in sections {
+            let len = len.min(MAX_HEADER_BYTES as u64) as usize;
+            let Some(data) = read_at(&mut file, offset, len) else {
+                continue;
+            };
+            match name.as_str() {
+                "header2" | "case_data" => {
+                    if let Some(text) = header_text(&data, true) {
+                        header = Some((true, text));
+                    }
+                }
+                "header" if !header.as_ref().is_some_and(|(utf16, _)| *utf16) => {
+                    header = header_text(&data, false).map(|text| (false, text));
+                }
+                "hash" | "md5" => info.stored_md5 = digest_hex(data.get(..16)),
+                "digest" => {
+                    info.stored_md5 = digest_hex(data.get(..16));
+                    info.stored_sha1 = digest_hex(data.get(16..36));
+                }
+                "sha1" => info.stored_sha1 = digest_hex(data.get(..20)),
+                _ => {}
+            }
+        }
+    }
+    if let Some((_, text)) = header {
+        apply_header(&mut info, &text);
+    }
+    info
+}
+
+/// Decompress a header section and decode it as UTF-16LE or ASCII text.
+fn header_text(data: &[u8], utf16: bool) -> Option<String> {
+    let raw = inflate::unzlib(data, MAX_HEADER_BYTES).ok()?;
+    if utf16 {
+        let units: Vec<u16> = raw
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .collect();
+        let text = String::from_utf16_lossy(&units);
+        Some(text.trim_start_matches('\u{FEFF}').to_string())
+    } else {
+        Some(String::from_utf8_lossy(&raw).into_owned())
+    }
+}
+
+fn digest_hex(bytes: Option<&[u8]>) -> Option<String> {
+    bytes
+        .filter(|bytes| bytes.iter().any(|&b| b != 0))
+        .map(hex::encode)
+}
+
+/// Apply the `main` category of a header: a key line and a value line,
+/// tab-separated. EWF1 uses one-letter keys, EWF2 two-letter ones.
+fn apply_header(info: &mut AcquisitionInfo, text: &str) {
+    let mut lines = text.lines().map(|line| line.trim_end_matches('\r'));
+    if !lines.any(|line| line == "main") {
+        return;
+    }
+    let (Some(keys), Some(values)) = (lines.next(), lines.next()) else {
+        return;
+    };
+    for (key, value) in keys.split('\t').zip(values.split('\t')) {
+        let value = value.trim();
+        if value.is_empty() {
+            continue;
+        }
+        let value = Some(value.to_string());
+        match key {
+            "c" | "cn" => info.case_number = value,
+            "n" | "en" => info.evidence_number = value,
+            "a" => info.description = value,
+            "e" | "ex" => info.examiner = value,
+            "t" | "nt" => info.notes = value,
+            "av" => info.acquisition_software = value,
+            "ov" | "os" => info.acquisition_os = value,
+            "m" | "at" => info.acquisition_date = value.map(|date| acquisition_date(&date)),
+            _ => {}
+        }
+    }
+}
+
+/// `header2` and EWF2 store Unix timestamps; `header` stores
+/// `YYYY M D h m s`, which is kept as is.
+fn acquisition_date(value: &str) -> String {
+    value
+        .parse::<i64>()
+        .ok()
+        .and_then(|secs| DateTime::from_timestamp(secs, 0))
+        .map(|date| date.format("%Y-%m-%dT%H:%M:%SZ").to_string())
+        .unwrap_or_else(|| value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn zlib_stored(data: &[u8]) -> Vec<u8> {
+        let len = data.len() as u16;
+        let mut out = vec![0x78, 0x01, 0x01];
+        out.extend(len.to_le_bytes());
+        out.extend((!len).to_le_bytes());
+        out.extend(data);
+        out.extend(adler32(data).to_be_bytes());
+        out
+    }
+
+    fn ewf1_section(out: &mut Vec<u8>, name: &str, data: &[u8], last: bool) {
+        let start = out.len() as u64;
+        let size = EWF1_DESCRIPTOR_LEN + data.len() as u64;
+        let mut desc = [0u8; 76];
+        desc[..name.len()].copy_from_slice(name.as_bytes());
+        let next = if last { start } else { start + size };
+        desc[16..24].copy_from_slice(&next.to_le_bytes());
+        desc[24..32].copy_from_slice(&size.to_le_bytes());
+        let checksum = adler32(&desc[..72]);
+        desc[72..76].copy_from_slice(&checksum.to_le_bytes());
+        out.extend(desc);
+        out.extend(data);
+    }
+
+    fn ewf1_segment(number: u16, sections: &[(&str, Vec<u8>)], end: &str) -> Vec<u8> {
+        let mut out = EWF1_EVIDENCE.to_vec();
+        out.push(1);
+        out.extend(number.to_le_bytes());
+        out.extend([0, 0]);
+        for (name, data) in sections {
+            ewf1_section(&mut out, name, data, false);
+        }
+        ewf1_section(&mut out, end, &[], true);
+        out
+    }
+
+    fn header2() -> Vec<u8> {
+        let text = "1\nmain\nc\tn\ta\te\tt\tav\tov\tm\tu\tp\n\
+                    2024-17\tEV-3\tLaptop disk\tJ. Doe\tseized at desk\t20.1.0\tWindows\t1700000000\t1700000000\t\n\n";
+        let mut utf16 = vec![0xFF, 0xFE];
+        for unit in text.encode_utf16() {
+            utf16.extend(unit.to_le_bytes());
+        }
+        zlib_stored(&utf16)
+    }
+
+    #[test]
+    fn names_segments_past_99() {
+        assert_eq!(segment_extension("E01", 2).as_deref(), Some("E02"));
+        assert_eq!(segment_extension("e01", 99).as_deref(), Some("e99"));
+        assert_eq!(segment_extension("E01", 100).as_deref(), Some("EAA"));
+        assert_eq!(
+            segment_extension("E01", 100 + 26 * 26).as_deref(),
+            Some("FAA")
+        );
+        assert_eq!(segment_extension("Ex01", 101).as_deref(), Some("ExAB"));
+        assert_eq!(segment_extension("Ex01", 100 + 26 * 26), None);
+    }
+
+    #[test]
+    fn verifies_multi_segment_set_and_reads_metadata() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let first = dir.path().join("case.E01");
+        let md5 = [0x11u8; 16];
+        let mut digest = md5.to_vec();
+        digest.extend([0x22u8; 20]);
+        std::fs::write(&first, ewf1_segment(1, &[("header2", header2())], "next")).unwrap();
+        std::fs::write(
+            dir.path().join("case.E02"),
+            ewf1_segment(2, &[("digest", digest)], "done"),
+        )
+        .unwrap();
+
+        let (format, paths) = segment_paths(&first).expect("segments");
+        assert_eq!(format, EwfFormat::E01);
+        assert_eq!(paths.len(), 2);
+        let info = acquisition_info(format, &paths);
+        assert_eq!(info.format, "E01");
+        assert_eq!(info.case_number.as_deref(), Some("2024-17"));
+        assert_eq!(info.examiner.as_deref(), Some("J. Doe"));
+        assert_eq!(info.notes.as_deref(), Some("seized at desk"));
+        assert_eq!(info.description.as_deref(), Some("Laptop disk"));
+        assert_eq!(
+            info.acquisition_date.as_deref(),
+            Some("2023-11-14T22:13:20Z")
+        );
+        assert_eq!(info.stored_md5, Some("11".repeat(16)));
+        assert_eq!(info.stored_sha1, Some("22".repeat(20)));
+    }
+
+    #[test]
+    fn rejects_broken_sets() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let first = dir.path().join("case.E01");
+        std::fs::write(&first, ewf1_segment(1, &[], "next")).unwrap();
+        assert!(matches!(
+            segment_paths(&first),
+            Err(EwfError::Incomplete(_))
+        ));
+
+        // E02 missing while E03 is present.
+        std::fs::write(dir.path().join("case.E03"), ewf1_segment(3, &[], "done")).unwrap();
+        assert!(matches!(
+            segment_paths(&first),
+            Err(EwfError::MissingSegment(path)) if path.ends_with("case.E02")
+        ));
+
+        // E02 present but numbered wrongly.
+        std::fs::write(dir.path().join("case.E02"), ewf1_segment(5, &[], "next")).unwrap();
+        assert!(matches!(
+            segment_paths(&first),
+            Err(EwfError::SegmentNumber {
+                expected: 2,
+                found: 5,
+                ..
+            })
+        ));
+
+        let second = dir.path().join("case.E02");
+        assert!(matches!(
+            segment_paths(&second),
+            Err(EwfError::NotFirstSegment(_))
+        ));
+    }
+
+    #[test]
+    fn reads_ewf2_sections_backwards() {
+        fn section(out: &mut Vec<u8>, kind: u32, data: &[u8], previous: u64) -> u64 {
+            out.extend(data);
+            let offset = out.len() as u64;
+            let mut desc = [0u8; 64];
+            desc[0..4].copy_from_slice(&kind.to_le_bytes());
+            desc[8..16].copy_from_slice(&previous.to_le_bytes());
+            desc[16..24].copy_from_slice(&(data.len() as u64).to_le_bytes());
+            desc[24..28].copy_from_slice(&64u32.to_le_bytes());
+            let checksum = adler32(&desc[..60]);
+            desc[60..64].copy_from_slice(&checksum.to_le_bytes());
+            out.extend(desc);
+            offset
+        }
+        let text = "1\nmain\ncn\tex\tat\n42\tA. Analyst\t1700000000\n\n";
+        let mut utf16 = Vec::new();
+        for unit in text.encode_utf16() {
+            utf16.extend(unit.to_le_bytes());
+        }
+        let mut out = EWF2_EVIDENCE.to_vec();
+        out.extend([2, 1, 1, 0]);
+        out.extend(1u32.to_le_bytes());
+        out.extend([0u8; 16]);
+        let case = section(&mut out, EWF2_CASE_DATA, &zlib_stored(&utf16), 0);
+        let md5 = section(&mut out, EWF2_MD5_HASH, &[0xAB; 20], case);
+        section(&mut out, EWF2_DONE, &[], md5);
+
+        let dir = tempfile::tempdir().expect("tempdir");
+        let first = dir.path().join("image.Ex01");
+        std::fs::write(&first, out).unwrap();
+        let (format, paths) = segment_paths(&first).expect("segments");
+        assert_eq!(format, EwfFormat::Ex01);
+        let info = acquisition_info(format, &paths);
+        assert_eq!(info.case_number.as_deref(), Some("42"));
+        assert_eq!(info.examiner.as_deref(), Some("A. Analyst"));
+        assert_eq!(info.stored_md5, Some("ab".repeat(16)));
+    }
+
+    #[test]
+    fn verifies_hashes_computed_while_scanning() {
+        let mut info = AcquisitionInfo {
+            stored_md5: Some("AB".repeat(16)),
+            ..AcquisitionInfo::default()
+        };
+        info.verify_hashes(Some(&"ab".repeat(16)), Some(&"cd".repeat(20)));
+        assert_eq!(info.md5_verified, Some(true));
+        assert_eq!(info.computed_sha1, Some("cd".repeat(20)));
+        assert_eq!(info.sha1_verified, None);
+
+        info.stored_sha1 = Some("ef".repeat(20));
+        info.verify_hashes(Some(&"00".repeat(16)), Some(&"cd".repeat(20)));
+        assert_eq!(info.computed_md5, Some("ab".repeat(16)));
+        assert_eq!(info.sha1_verified, Some(false));
+    }
+}
@@ -0,0 +1,34 @@
+//! Metadata shared by the executable formats.
+
+use chrono::NaiveDateTime;
+use serde::Serialize;
+
+/// A carved executable, read by the `executables` post-processor.
+#[derive(Debug, Clone, Serialize)]
+pub struct ExecutableRecord {
+    pub run_id: String,
+    /// Container format (`pe`, `elf`, or `macho`).
+    pub format: String,
+    /// `exe`, `dll`, or `sys` for PE images; `exe`, `so`, `object`, or `core`
+    /// for ELF files; `exe`, `dylib`, `bundle`, `object`, ... for Mach-O.
+    pub kind: String,
+    /// Target machine (`x86`, `x86_64`, `arm64`, ...), `unknown` for machine
+    /// types not listed. Fat Mach-O files list each slice's, comma-separated.
+    pub architecture: String,
+    pub bits: u8,
+    /// Link time from the PE file header; absent when zero and for formats
+    /// without one. Reproducible builds store a hash here instead, so far-off
+    /// dates are expected.
+    pub compile_time_utc: Option<NaiveDateTime>,
+    pub subsystem: Option<String>,
+    pub section_count: u32,
+    /// Imported functions, absent when the import table could not be read.
+    pub import_count: Option<u32>,
+    /// MD5 over the imported functions (`imphash`), absent without imports.
+    pub import_hash: Option<String>,
+    /// Carries an Authenticode signature (PE) or a code signature (Mach-O,
+    /// every slice of a fat file); the signature is not verified.
+    pub signed: bool,
+    /// Carved path of the executable.
+    pub source_file: String,
+}
@@ -0,0 +1,462 @@
+//! Camera identity and capture time of carved photos.
+//!
+//! [`photo_exif`] reads the EXIF block of a JPEG (APP1 `Exif`) or the first
+//! IFD of a TIFF, and fills what EXIF lacks from the XMP packet (APP1
+//! `http://ns.adobe.com/xap/1.0/`): make, model, body serial number, lens,
+//! the original capture time, and the GPS position. Maker notes, where some
+//! vendors keep the serial, are not decoded.
+
+use chrono::NaiveDateTime;
+use serde::Serialize;
+
+const EXIF_HEADER: &[u8] = b"Exif\0\0";
+const XMP_HEADER: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+
+const TAG_MAKE: u16 = 0x010f;
+const TAG_MODEL: u16 = 0x0110;
+const TAG_DATE_TIME: u16 = 0x0132;
+const TAG_EXIF_IFD: u16 = 0x8769;
+const TAG_GPS_IFD: u16 = 0x8825;
+const TAG_GPS_LATITUDE_REF: u16 = 0x0001;
+const TAG_GPS_LATITUDE: u16 = 0x0002;
+const TAG_GPS_LONGITUDE_REF: u16 = 0x0003;
+const TAG_GPS_LONGITUDE: u16 = 0x0004;
+const TAG_DATE_TIME_ORIGINAL: u16 = 0x9003;
+const TAG_BODY_SERIAL: u16 = 0xa431;
+const TAG_LENS_MODEL: u16 = 0xa434;
+const TAG_LENS_SERIAL: u16 = 0xa435;
+
+/// Device fields of one photo; every field is optional.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PhotoExif {
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub serial: Option<String>,
+    pub lens: Option<String>,
+    pub lens_serial: Option<String>,
+    /// Capture time by the camera clock, without a time zone.
+    pub taken_at: Option<NaiveDateTime>,
+    /// GPS position as `(latitude, longitude)` in decimal degrees (WGS 84).
+    pub position: Option<(f64, f64)>,
+}
+
+/// One photo in `photo_devices`, with the device and session it was
+/// grouped into at the end of the run.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct PhotoDeviceRecord {
+    pub run_id: String,
+    /// Carved path of the photo.
+    pub source_file: String,
+    pub global_start: u64,
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub serial: Option<String>,
+    pub lens: Option<String>,
+    pub lens_serial: Option<String>,
+    pub taken_at: Option<NaiveDateTime>,
+    /// Decimal degrees, north positive; set together with `longitude`.
+    pub latitude: Option<f64>,
+    /// Decimal degrees, east positive.
+    pub longitude: Option<f64>,
+    /// `None` when the photo names no make, model, or serial.
+    pub device_id: Option<String>,
+    /// `None` without a device or a capture time.
+    pub session_id: Option<String>,
+}
+
+impl PhotoExif {
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Fill fields still missing from `other`.
+    fn fill_from(&mut self, other: PhotoExif) {
+        self.make = self.make.take().or(other.make);
+        self.model = self.model.take().or(other.model);
+        self.serial = self.serial.take().or(other.serial);
+        self.lens = self.lens.take().or(other.lens);
+        self.lens_serial = self.lens_serial.take().or(other.lens_serial);
+        self.taken_at = self.taken_at.or(other.taken_at);
+        self.position = self.position.or(other.position);
+    }
+}
+
+/// Device fields of a JPEG or TIFF; `None` when it has none.
+pub fn photo_exif(data: &[u8]) -> Option<PhotoExif> {
+    let mut exif = PhotoExif::default();
+    if data.starts_with(&[0xff, 0xd8]) {
+        let mut xmp = None;
+        for (marker, payload) in jpeg_segments(data) {
+            if marker != 0xe1 {
+                continue;
+            }
+            if let Some(tiff) = payload.strip_prefix(EXIF_HEADER) {
+                exif.fill_from(tiff_exif(tiff).unwrap_or_default());
+            } else if let Some(packet) = payload.strip_prefix(XMP_HEADER) {
+                xmp.get_or_insert(packet);
+            }
+        }
+        if let Some(packet) = xmp {
+            exif.fill_from(xmp_exif(&String::from_utf8_lossy(packet)));
+        }
+    } else if data.starts_with(b"II*\0") || data.starts_with(b"MM\0*") {
+        exif = tiff_exif(data).unwrap_or_default();
+    }
+    (!exif.is_empty()).then_some(exif)
+}
+
+/// Marker segments before the first scan, as `(marker, payload)`.
+fn jpeg_segments(data: &[u8]) -> impl Iterator<Item = (u8, &[u8])> {
+    let mut at = 2;
+    std::iter::from_fn(move || {
+        while data.get(at) == Some(&0xff) && data.get(at + 1) == Some(&0xff) {
+            at += 1;
+        }
+        if data.get(at) != Some(&0xff) {
+            return None;
+        }
+        let marker = *data.get(at + 1)?;
+        if marker == 0xda || marker == 0xd9 {
+            return None;
+        }
+        let len = usize::from(u16::from_be_bytes(
+            data.get(at + 2..at + 4)?.try_into().ok()?,
+        ));
+        let payload = data.get(at + 4..at + 2 + len.max(2))?;
+        at += 2 + len;
+        Some((marker, payload))
+    })
+}
+
+struct Tiff<'a> {
+    data: &'a [u8],
+    little: bool,
+}
+
+impl Tiff<'_> {
+    fn u16_at(&self, at: usize) -> Option<u16> {
+        let bytes: [u8; 2] = self.data.get(at..at + 2)?.try_into().ok()?;
+        Some(if self.little {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    }
+
+    fn u32_at(&self, at: usize) -> Option<u32> {
+        let bytes: [u8; 4] = self.data.get(at..at + 4)?.try_into().ok()?;
+        Some(if self.little {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+
+    /// `(tag, entry offset)` of each entry of the IFD at `offset`.
+    fn entries(&self, offset: usize) -> Vec<(u16, usize)> {
+        let count = self.u16_at(offset).map_or(0, usize::from);
+        (0..count)
+            .map(|i| offset + 2 + i * 12)
+            .map_while(|entry| Some((self.u16_at(entry)?, entry)))
+            .collect()
+    }
+
+    /// ASCII value of an entry, trimmed; `None` when empty or not ASCII.
+    fn ascii(&self, entry: usize) -> Option<String> {
+        if self.u16_at(entry + 2)? != 2 {
+            return None;
+        }
+        let count = self.u32_at(entry + 4)? as usize;
+        let start = if count <= 4 {
+            entry + 8
+        } else {
+            self.u32_at(entry + 8)? as usize
+        };
+        let raw = self.data.get(start..start.checked_add(count)?)?;
+        clean(&String::from_utf8_lossy(raw))
+    }
+
+    /// Degrees from a GPS coordinate entry: three RATIONALs (degrees,
+    /// minutes, seconds).
+    fn degrees(&self, entry: usize) -> Option<f64> {
+        if self.u16_at(entry + 2)? != 5 || self.u32_at(entry + 4)? != 3 {
+            return None;
+        }
+        let at = self.u32_at(entry + 8)? as usize;
+        let mut parts = [0f64; 3];
+        for (i, part) in parts.iter_mut().enumerate() {
+            let numerator = self.u32_at(at + i * 8)?;
+            let denominator = self.u32_at(at + i * 8 + 4)?;
+            if denominator == 0 {
+                return None;
+            }
+            *part = f64::from(numerator) / f64::from(denominator);
+        }
+        Some(parts[0] + parts[1] / 60.0 + parts[2] / 3600.0)
+    }
+}
+
+fn tiff_exif(data: &[u8]) -> Option<PhotoExif> {
+    let little = match data.get(..2)? {
+        b"II" => true,
+        b"MM" => false,
+        _ => return None,
+    };
+    let tiff = Tiff { data, little };
+    let ifd0 = tiff.u32_at(4)? as usize;
+    let mut exif = PhotoExif::default();
+    let mut modified = None;
+    let mut exif_ifd = None;
+    let mut gps_ifd = None;
+    for (tag, entry) in tiff.entries(ifd0) {
+        match tag {
+            TAG_MAKE => exif.make = tiff.ascii(entry),
+            TAG_MODEL => exif.model = tiff.ascii(entry),
+            TAG_DATE_TIME => modified = tiff.ascii(entry),
+            TAG_EXIF_IFD => exif_ifd = tiff.u32_at(entry + 8),
+            TAG_GPS_IFD => gps_ifd = tiff.u32_at(entry + 8),
+            _ => {}
+        }
+    }
+    if let Some(offset) = gps_ifd.filter(|&offset| offset as usize != ifd0) {
+        exif.position = gps_position(&tiff, offset as usize);
+    }
+    if let Some(offset) = exif_ifd.filter(|&offset| offset as usize != ifd0) {
+        for (tag, entry) in tiff.entries(offset as usize) {
+            match tag {
+                TAG_DATE_TIME_ORIGINAL => {
+                    exif.taken_at = tiff.ascii(entry).and_then(|s| exif_time(&s));
+                }
+                TAG_BODY_SERIAL => exif.serial = tiff.ascii(entry),
+                TAG_LENS_MODEL => exif.lens = tiff.ascii(entry),
+                TAG_LENS_SERIAL => exif.lens_serial = tiff.ascii(entry),
+                _ => {}
+            }
+        }
+    }
+    // The file modification time stands in when no original time is set.
+    exif.taken_at = exif
+        .taken_at
+        .or_else(|| modified.and_then(|s| exif_time(&s)));
+    Some(exif)
+}
+
+fn gps_position(tiff: &Tiff, ifd: usize) -> Option<(f64, f64)> {
+    let (mut lat, mut lat_ref, mut lon, mut lon_ref) = (None, None, None, None);
+    for (tag, entry) in tiff.entries(ifd) {
+        match tag {
+            TAG_GPS_LATITUDE_REF => lat_ref = tiff.ascii(entry),
+            TAG_GPS_LATITUDE => lat = tiff.degrees(entry),
+            TAG_GPS_LONGITUDE_REF => lon_ref = tiff.ascii(entry),
+            TAG_GPS_LONGITUDE => lon = tiff.degrees(entry),
+            _ => {}
+        }
+    }
+    let sign = |hemisphere: Option<String>, negative: &str| match hemisphere.as_deref() {
+        Some(h) if h.eq_ignore_ascii_case(negative) => -1.0,
+        _ => 1.0,
+    };
+    position(lat? * sign(lat_ref, "S"), lon? * sign(lon_ref, "W"))
+}
+
+/// XMP coordinate, `DDD,MM,SSk` or `DDD,MM.mmk` with `k` one of `NSEW`.
+fn xmp_degrees(value: &str) -> Option<f64> {
+    let (at, hemisphere) = value.char_indices().last()?;
+    let parts = value[..at]
+        .split(',')
+        .map(|part| part.trim().parse::<f64>().ok())
+        .collect::<Option<Vec<f64>>>()?;
+    let degrees = match parts.as_slice() {
+        [d, m] => d + m / 60.0,
+        [d, m, s] => d + m / 60.0 + s / 3600.0,
+        _ => return None,
+    };
+    match hemisphere.to_ascii_uppercase() {
+        'N' | 'E' => Some(degrees),
+        'S' | 'W' => Some(-degrees),
+        _ => None,
+    }
+}
+
+/// A valid position; `(0, 0)`, which devices write without a fix, is unset.
+fn position(lat: f64, lon: f64) -> Option<(f64, f64)> {
+    let valid = lat.abs() <= 90.0 && lon.abs() <= 180.0 && (lat, lon) != (0.0, 0.0);
+    valid.then_some((lat, lon))
+}
+
+fn xmp_exif(packet: &str) -> PhotoExif {
+    let first = |names: &[&str]| names.iter().find_map(|name| xmp_value(packet, name));
+    PhotoExif {
+        make: first(&["tiff:Make"]),
+        model: first(&["tiff:Model"]),
+        serial: first(&["exifEX:BodySerialNumber", "aux:SerialNumber"]),
+        lens: first(&["exifEX:LensModel", "aux:Lens"]),
+        lens_serial: first(&["exifEX:LensSerialNumber", "aux:LensSerialNumber"]),
+        taken_at: first(&["exif:DateTimeOriginal", "photoshop:DateCreated"])
+            .and_then(|s| xmp_time(&s)),
+        position: first(&["exif:GPSLatitude"])
+            .and_then(|s| xmp_degrees(&s))
+            .zip(first(&["exif:GPSLongitude"]).and_then(|s| xmp_degrees(&s)))
+            .and_then(|(lat, lon)| position(lat, lon)),
+    }
+}
+
+/// Value of an XMP property written as an attribute (`name="value"`) or as
+/// an element (`<name>value</name>`).
+fn xmp_value(packet: &str, name: &str) -> Option<String> {
+    let attribute = format!("{name}=\"");
+    if let Some(at) = packet.find(&attribute) {
+        let rest = &packet[at + attribute.len()..];
+        return clean(&rest[..rest.find('"')?]);
+    }
+    let open = format!("<{name}>");
+    let at = packet.find(&open)? + open.len();
+    let rest = &packet[at..];
+    clean(&rest[..rest.find('<')?])
+}
+
+fn clean(value: &str) -> Option<String> {
+    let value = value.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+/// `YYYY:MM:DD HH:MM:SS`; all-zero or blank dates are unset.
+fn exif_time(value: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(value.get(..19)?, "%Y:%m:%d %H:%M:%S").ok()
+}
+
+/// ISO 8601 local time; a zone or fractional seconds after it are ignored.
+fn xmp_time(value: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(value.get(..19)?, "%Y-%m-%dT%H:%M:%S").ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ascii_entry(tag: u16, value_offset: u32, value: &[u8]) -> Vec<u8> {
+        let mut entry = tag.to_le_bytes().to_vec();
+        entry.extend(2u16.to_le_bytes());
+        entry.extend((value.len() as u32).to_le_bytes());
+        entry.extend(value_offset.to_le_bytes());
+        entry
+    }
+
+    /// Little-endian TIFF with Make and Model in IFD0 and the serial and
+    /// capture time in the EXIF IFD.
+    fn exif_tiff() -> Vec<u8> {
+        let mut tiff = b"II*\0".to_vec();
+        tiff.extend(8u32.to_le_bytes());
+        // IFD0 at 8: three entries, then the EXIF IFD at 50.
+        tiff.extend(3u16.to_le_bytes());
+        tiff.extend(ascii_entry(TAG_MAKE, 100, b"Canon\0"));
+        tiff.extend(ascii_entry(TAG_MODEL, 110, b"Canon EOS R5\0"));
+        let mut pointer = TAG_EXIF_IFD.to_le_bytes().to_vec();
+        pointer.extend(4u16.to_le_bytes());
+        pointer.extend(1u32.to_le_bytes());
+        pointer.extend(50u32.to_le_bytes());
+        tiff.extend(pointer);
+        tiff.extend(0u32.to_le_bytes());
+        assert_eq!(tiff.len(), 50);
+        tiff.extend(2u16.to_le_bytes());
+        tiff.extend(ascii_entry(
+            TAG_DATE_TIME_ORIGINAL,
+            130,
+            b"2024:05:01 10:15:30\0",
+        ));
+        tiff.extend(ascii_entry(TAG_BODY_SERIAL, 160, b"032021001234\0"));
+        tiff.extend(0u32.to_le_bytes());
+        tiff.resize(100, 0);
+        tiff.extend(b"Canon\0\0\0\0\0");
+        tiff.extend(b"Canon EOS R5\0\0\0\0\0\0\0\0");
+        tiff.extend(b"2024:05:01 10:15:30\0\0\0\0\0\0\0\0\0\0\0");
+        tiff.extend(b"032021001234\0");
+        tiff
+    }
+
+    #[test]
+    fn reads_exif_and_fills_from_xmp() {
+        let tiff = exif_tiff();
+        let exif = photo_exif(&tiff).expect("tiff exif");
+        assert_eq!(exif.make.as_deref(), Some("Canon"));
+        assert_eq!(exif.model.as_deref(), Some("Canon EOS R5"));
+        assert_eq!(exif.serial.as_deref(), Some("032021001234"));
+        assert_eq!(
+            exif.taken_at.map(|t| t.to_string()).as_deref(),
+            Some("2024-05-01 10:15:30")
+        );
+        assert_eq!(exif.lens, None);
+
+        let mut jpeg = vec![0xff, 0xd8];
+        let mut app1 = EXIF_HEADER.to_vec();
+        app1.extend(&tiff);
+        let xmp = format!(
+            "{}<x:xmpmeta><rdf:Description aux:Lens=\"RF24-70mm F2.8 L IS USM\" \
+             aux:SerialNumber=\"999\"/></x:xmpmeta>",
+            String::from_utf8_lossy(XMP_HEADER)
+        );
+        for payload in [app1, xmp.into_bytes()] {
+            jpeg.extend([0xff, 0xe1]);
+            jpeg.extend(((payload.len() + 2) as u16).to_be_bytes());
+            jpeg.extend(payload);
+        }
+        jpeg.extend([0xff, 0xda, 0, 2]);
+        let exif = photo_exif(&jpeg).expect("jpeg exif");
+        // EXIF wins over XMP; XMP fills the lens.
+        assert_eq!(exif.serial.as_deref(), Some("032021001234"));
+        assert_eq!(exif.lens.as_deref(), Some("RF24-70mm F2.8 L IS USM"));
+
+        assert_eq!(photo_exif(&[0xff, 0xd8, 0xff, 0xda, 0, 2]), None);
+    }
+
+    #[test]
+    fn reads_gps_position() {
+        let mut tiff = b"II*\0".to_vec();
+        tiff.extend(8u32.to_le_bytes());
+        // IFD0 at 8: the GPS IFD pointer only.
+        tiff.extend(1u16.to_le_bytes());
+        tiff.extend(TAG_GPS_IFD.to_le_bytes());
+        tiff.extend(4u16.to_le_bytes());
+        tiff.extend(1u32.to_le_bytes());
+        tiff.extend(26u32.to_le_bytes());
+        tiff.extend(0u32.to_le_bytes());
+        // GPS IFD at 26, its rationals at 80.
+        tiff.extend(4u16.to_le_bytes());
+        tiff.extend(ascii_entry(
+            TAG_GPS_LATITUDE_REF,
+            u32::from_le_bytes(*b"S\0\0\0"),
+            b"S\0",
+        ));
+        for (tag, at) in [(TAG_GPS_LATITUDE, 80u32), (TAG_GPS_LONGITUDE, 104)] {
+            tiff.extend(tag.to_le_bytes());
+            tiff.extend(5u16.to_le_bytes());
+            tiff.extend(3u32.to_le_bytes());
+            tiff.extend(at.to_le_bytes());
+        }
+        tiff.extend(ascii_entry(
+            TAG_GPS_LONGITUDE_REF,
+            u32::from_le_bytes(*b"E\0\0\0"),
+            b"E\0",
+        ));
+        tiff.extend(0u32.to_le_bytes());
+        assert_eq!(tiff.len(), 80);
+        for value in [33u32, 51, 54, 151, 12, 36] {
+            tiff.extend(value.to_le_bytes());
+            tiff.extend(1u32.to_le_bytes());
+        }
+        let (lat, lon) = photo_exif(&tiff)
+            .and_then(|e| e.position)
+            .expect("position");
+        assert!((lat + 33.865).abs() < 1e-9, "{lat}");
+        assert!((lon - 151.21).abs() < 1e-9, "{lon}");
+
+        assert_eq!(xmp_degrees("48,51.396N"), Some(48.0 + 51.396 / 60.0));
+        assert_eq!(
+            xmp_degrees("2,17,24W"),
+            Some(-(2.0 + 17.0 / 60.0 + 24.0 / 3600.0))
+        );
+        assert_eq!(xmp_degrees("48.5N"), None);
+        assert_eq!(position(0.0, 0.0), None);
+        assert_eq!(position(91.0, 0.5), None);
+    }
+}
@@ -0,0 +1,275 @@
+//! ext2, ext3, and ext4 block bitmaps.
+//!
+//! The superblock, 1024 bytes into the volume, gives the block size, the
+//! block count, and the blocks per group. The group descriptor table starts
+//! in the block after the superblock; each descriptor names the block
+//! holding its group's block bitmap, one bit per block of the group. With
+//! `uninit_bg` or `metadata_csum`, a group flagged `BLOCK_UNINIT` has no
+//! bitmap on disk and is taken as free. Volumes using `meta_bg`, whose
+//! descriptors are spread over the volume, are not read.
+
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::evidence::{EvidenceError, EvidenceSource};
+
+/// Offset of the superblock inside the volume.
+const SUPERBLOCK_OFFSET: u64 = 1024;
+const SUPERBLOCK_LEN: usize = 1024;
+const EXT_MAGIC: u16 = 0xEF53;
+
+const INCOMPAT_META_BG: u32 = 0x10;
+const INCOMPAT_64BIT: u32 = 0x80;
+const RO_COMPAT_GDT_CSUM: u32 = 0x10;
+const RO_COMPAT_METADATA_CSUM: u32 = 0x400;
+const BG_BLOCK_UNINIT: u16 = 0x2;
+
+/// Upper bound on block groups read from one volume.
+const MAX_GROUPS: u64 = 1 << 24;
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Ext4Error {
+    #[error("evidence read failed: {0}")]
+    Evidence(#[from] EvidenceError),
+    #[error("malformed ext {what} at evidence offset {offset}")]
+    Malformed { what: &'static str, offset: u64 },
+    #[error("ext volume at evidence offset {offset} uses meta_bg, which is not supported")]
+    MetaBg { offset: u64 },
+}
+
+/// Geometry from an ext2/3/4 superblock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Ext4Superblock {
+    /// Evidence offset of the volume (not of the superblock).
+    pub offset: u64,
+    pub block_size: u64,
+    pub blocks: u64,
+    pub first_data_block: u64,
+    pub blocks_per_group: u64,
+    /// Bytes per group descriptor.
+    pub desc_size: u64,
+    pub incompat: u32,
+    pub ro_compat: u32,
+}
+
+impl Ext4Superblock {
+    /// Evidence offset of block `block`.
+    pub fn block_offset(&self, block: u64) -> u64 {
+        self.offset + block * self.block_size
+    }
+
+    pub fn groups(&self) -> u64 {
+        (self.blocks - self.first_data_block).div_ceil(self.blocks_per_group)
+    }
+
+    pub fn volume_size(&self) -> u64 {
+        self.blocks * self.block_size
+    }
+}
+
+/// Superblock of the ext volume at `offset`.
+pub fn read_superblock(
+    source: &dyn EvidenceSource,
+    offset: u64,
+) -> Result<Ext4Superblock, Ext4Error> {
+    let mut sb = [0u8; SUPERBLOCK_LEN];
+    read_exact(source, offset + SUPERBLOCK_OFFSET, &mut sb)?;
+    let malformed = Ext4Error::Malformed {
+        what: "superblock",
+        offset: offset + SUPERBLOCK_OFFSET,
+    };
+    if le_u16(&sb, 0x38) != EXT_MAGIC {
+        return Err(malformed);
+    }
+    let log_block_size = le_u32(&sb, 0x18);
+    let incompat = le_u32(&sb, 0x60);
+    let ro_compat = le_u32(&sb, 0x64);
+    let is_64bit = incompat & INCOMPAT_64BIT != 0;
+    let mut blocks = u64::from(le_u32(&sb, 0x04));
+    let desc_size = if is_64bit {
+        blocks |= u64::from(le_u32(&sb, 0x150)) << 32;
+        u64::from(le_u16(&sb, 0xFE))
+    } else {
+        32
+    };
+    let first_data_block = u64::from(le_u32(&sb, 0x14));
+    let blocks_per_group = u64::from(le_u32(&sb, 0x20));
+    if log_block_size > 6
+        || blocks_per_group == 0
+        || blocks <= first_data_block
+        || !(32..=1024).contains(&desc_size)
+        || !desc_size.is_power_of_two()
+    {
+        return Err(malformed);
+    }
+    let block_size = 1024u64 << log_block_size;
+    if blocks_per_group > block_size * 8 {
+        return Err(malformed);
+    }
+    Ok(Ext4Superblock {
+        offset,
+        block_size,
+        blocks,
+        first_data_block,
+        blocks_per_group,
+        desc_size,
+        incompat,
+        ro_compat,
+    })
+}
+
+/// Blocks in use on the ext volume at `offset`, as sorted evidence ranges
+/// `[start, end)`.
+pub fn read_allocated(
+    source: &dyn EvidenceSource,
+    offset: u64,
+) -> Result<(Ext4Superblock, Vec<(u64, u64)>), Ext4Error> {
+    let sb = read_superblock(source, offset)?;
+    if sb.incompat & INCOMPAT_META_BG != 0 {
+        return Err(Ext4Error::MetaBg { offset });
+    }
+    let groups = sb.groups();
+    if groups > MAX_GROUPS {
+        return Err(Ext4Error::Malformed {
+            what: "block group count",
+            offset: offset + SUPERBLOCK_OFFSET,
+        });
+    }
+    let uninit_flags = sb.ro_compat & (RO_COMPAT_GDT_CSUM | RO_COMPAT_METADATA_CSUM) != 0;
+    let table_offset = sb.block_offset(sb.first_data_block + 1);
+    let mut table = vec![0u8; (groups * sb.desc_size) as usize];
+    read_exact(source, table_offset, &mut table)?;
+
+    let mut ranges: Vec<(u64, u64)> = Vec::new();
+    let mut bitmap = vec![0u8; sb.block_size as usize];
+    for (group, desc) in table.chunks_exact(sb.desc_size as usize).enumerate() {
+        let group = group as u64;
+        if uninit_flags && le_u16(desc, 0x12) & BG_BLOCK_UNINIT != 0 {
+            continue;
+        }
+        let mut bitmap_block = u64::from(le_u32(desc, 0x00));
+        if sb.desc_size >= 64 {
+            bitmap_block |= u64::from(le_u32(desc, 0x20)) << 32;
+        }
+        if bitmap_block == 0 || bitmap_block >= sb.blocks {
+            return Err(Ext4Error::Malformed {
+                what: "group descriptor",
+                offset: table_offset + group * sb.desc_size,
+            });
+        }
+        read_exact(source, sb.block_offset(bitmap_block), &mut bitmap)?;
+        let first = sb.first_data_block + group * sb.blocks_per_group;
+        let count = sb.blocks_per_group.min(sb.blocks - first);
+        for bit in 0..count {
+            if bitmap[(bit / 8) as usize] & (1 << (bit % 8)) == 0 {
+                continue;
+            }
+            let start = sb.block_offset(first + bit);
+            match ranges.last_mut() {
+                Some(last) if last.1 == start => last.1 += sb.block_size,
+                _ => ranges.push((start, start + sb.block_size)),
+            }
+        }
+    }
+    Ok((sb, ranges))
+}
+
+fn read_exact(source: &dyn EvidenceSource, offset: u64, buf: &mut [u8]) -> Result<(), Ext4Error> {
+    let mut done = 0;
+    while done < buf.len() {
+        let n = source.read_at(offset + done as u64, &mut buf[done..])?;
+        if n == 0 {
+            return Err(Ext4Error::Malformed {
+                what: "structure past the end of the evidence",
+                offset,
+            });
+        }
+        done += n;
+    }
+    Ok(())
+}
+
+fn le_u16(data: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes(data[at..at + 2].try_into().unwrap_or_default())
+}
+
+fn le_u32(data: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(data[at..at + 4].try_into().unwrap_or_default())
+}
+
+/// Synthetic ext volumes.
+#[cfg(test)]
+pub(crate) mod testing {
+    pub(crate) const BLOCK: usize = 1024;
+    /// Blocks per group of [`ext_volume`].
+    pub(crate) const GROUP_BLOCKS: usize = 256;
+
+    /// An ext2-style volume of 1 KiB blocks (first data block 1) and two
+    /// 256-block groups whose bitmaps are blocks 3 and 4; `used` lists the
+    /// blocks marked in use. Group 1 is flagged `BLOCK_UNINIT` with
+    /// `uninit_bg` when `uninit` is set.
+    pub(crate) fn ext_volume(used: &[u64], uninit: bool) -> Vec<u8> {
+        let blocks = 1 + 2 * GROUP_BLOCKS;
+        let mut image = vec![0u8; blocks * BLOCK];
+        let sb = BLOCK;
+        image[sb + 0x04..sb + 0x08].copy_from_slice(&(blocks as u32).to_le_bytes());
+        image[sb + 0x14..sb + 0x18].copy_from_slice(&1u32.to_le_bytes());
+        image[sb + 0x20..sb + 0x24].copy_from_slice(&(GROUP_BLOCKS as u32).to_le_bytes());
+        image[sb + 0x38..sb + 0x3A].copy_from_slice(&0xEF53u16.to_le_bytes());
+        if uninit {
+            image[sb + 0x64..sb + 0x68].copy_from_slice(&0x10u32.to_le_bytes());
+        }
+        let gdt = 2 * BLOCK;
+        for (group, bitmap) in [3u32, 4].into_iter().enumerate() {
+            let desc = gdt + group * 32;
+            image[desc..desc + 4].copy_from_slice(&bitmap.to_le_bytes());
+            if uninit && group == 1 {
+                image[desc + 0x12..desc + 0x14].copy_from_slice(&0x2u16.to_le_bytes());
+            }
+        }
+        for &block in used {
+            let group = (block as usize - 1) / GROUP_BLOCKS;
+            let bit = (block as usize - 1) % GROUP_BLOCKS;
+            image[(3 + group) * BLOCK + bit / 8] |= 1 << (bit % 8);
+        }
+        image
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::{BLOCK, ext_volume};
+    use super::*;
+    use crate::evidence::MemorySource;
+
+    #[test]
+    fn maps_blocks_marked_in_each_group_bitmap() {
+        let b = BLOCK as u64;
+        let (sb, allocated) = read_allocated(
+            &MemorySource::new(ext_volume(&[1, 2, 3, 4, 9, 300, 301], false)),
+            0,
+        )
+        .expect("ext");
+        assert_eq!((sb.block_size, sb.blocks, sb.groups()), (b, 513, 2));
+        assert_eq!(
+            allocated,
+            vec![(b, 5 * b), (9 * b, 10 * b), (300 * b, 302 * b)]
+        );
+
+        // The bitmap of an uninitialized group is not read.
+        let (_, allocated) =
+            read_allocated(&MemorySource::new(ext_volume(&[1, 2, 300], true)), 0).expect("ext");
+        assert_eq!(allocated, vec![(b, 3 * b)]);
+    }
+
+    #[test]
+    fn refuses_meta_bg_volumes() {
+        let mut image = ext_volume(&[], false);
+        image[BLOCK + 0x60..BLOCK + 0x64].copy_from_slice(&INCOMPAT_META_BG.to_le_bytes());
+        assert!(matches!(
+            read_allocated(&MemorySource::new(image), 0),
+            Err(Ext4Error::MetaBg { offset: 0 })
+        ));
+    }
+}
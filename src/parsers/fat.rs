@@ -0,0 +1,347 @@
+//! FAT12, FAT16, and FAT32 allocation tables.
+//!
+//! The boot sector (BIOS parameter block) gives the sector and cluster
+//! sizes, the reserved sectors before the first FAT, the number and size of
+//! the FATs, and the root directory entries of FAT12/16. Data clusters are
+//! numbered from 2; a non-zero FAT entry marks a cluster in use (part of a
+//! chain, end of chain, or bad). The FAT type follows from the cluster
+//! count, as the specification prescribes, not from the label in the boot
+//! sector. exFAT, which keeps a separate allocation bitmap, is not read.
+
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::evidence::{EvidenceError, EvidenceSource};
+
+/// Bytes of FAT read at a time.
+const FAT_READ_SIZE: u64 = 1 << 20;
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum FatError {
+    #[error("evidence read failed: {0}")]
+    Evidence(#[from] EvidenceError),
+    #[error("malformed FAT {what} at evidence offset {offset}")]
+    Malformed { what: &'static str, offset: u64 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FatType {
+    Fat12,
+    Fat16,
+    Fat32,
+}
+
+impl FatType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FatType::Fat12 => "fat12",
+            FatType::Fat16 => "fat16",
+            FatType::Fat32 => "fat32",
+        }
+    }
+}
+
+/// Geometry from a FAT boot sector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct FatBoot {
+    /// Evidence offset of the boot sector.
+    pub offset: u64,
+    pub fat_type: FatType,
+    pub bytes_per_sector: u64,
+    pub cluster_size: u64,
+    /// Sectors before the first FAT.
+    pub reserved_sectors: u64,
+    pub fat_sectors: u64,
+    /// Sectors before cluster 2: reserved, FATs, and the FAT12/16 root
+    /// directory.
+    pub data_start_sectors: u64,
+    pub clusters: u64,
+    pub volume_size: u64,
+}
+
+impl FatBoot {
+    /// Evidence offset of data cluster `cluster` (2 or more).
+    pub fn cluster_offset(&self, cluster: u64) -> u64 {
+        self.offset
+            + self.data_start_sectors * self.bytes_per_sector
+            + (cluster - 2) * self.cluster_size
+    }
+}
+
+/// Boot sector geometry of the FAT volume at `offset`.
+pub fn read_boot(source: &dyn EvidenceSource, offset: u64) -> Result<FatBoot, FatError> {
+    let mut sector = [0u8; 512];
+    read_exact(source, offset, &mut sector)?;
+    let malformed = FatError::Malformed {
+        what: "boot sector",
+        offset,
+    };
+    if !matches!(sector[0], 0xEB | 0xE9) || sector[510..512] != [0x55, 0xAA] {
+        return Err(malformed);
+    }
+    let bytes_per_sector = u64::from(le_u16(&sector, 11));
+    let sectors_per_cluster = u64::from(sector[13]);
+    let reserved_sectors = u64::from(le_u16(&sector, 14));
+    let fats = u64::from(sector[16]);
+    let root_entries = u64::from(le_u16(&sector, 17));
+    let total_sectors = match le_u16(&sector, 19) {
+        0 => u64::from(le_u32(&sector, 32)),
+        n => u64::from(n),
+    };
+    let fat_sectors = match le_u16(&sector, 22) {
+        0 => u64::from(le_u32(&sector, 36)),
+        n => u64::from(n),
+    };
+    if !bytes_per_sector.is_power_of_two()
+        || !(512..=4096).contains(&bytes_per_sector)
+        || !sectors_per_cluster.is_power_of_two()
+        || reserved_sectors == 0
+        || !(1..=4).contains(&fats)
+        || fat_sectors == 0
+    {
+        return Err(malformed);
+    }
+    let root_sectors = (root_entries * 32).div_ceil(bytes_per_sector);
+    let data_start_sectors = reserved_sectors + fats * fat_sectors + root_sectors;
+    let Some(data_sectors) = total_sectors.checked_sub(data_start_sectors) else {
+        return Err(malformed);
+    };
+    let clusters = data_sectors / sectors_per_cluster;
+    let fat_type = match clusters {
+        0..4085 => FatType::Fat12,
+        4085..65525 => FatType::Fat16,
+        _ => FatType::Fat32,
+    };
+    // A FAT too small for its clusters is not a FAT.
+    let fat_bits = match fat_type {
+        FatType::Fat12 => 12,
+        FatType::Fat16 => 16,
+        FatType::Fat32 => 32,
+    };
+    if fat_sectors * bytes_per_sector * 8 < (clusters + 2) * fat_bits {
+        return Err(malformed);
+    }
+    Ok(FatBoot {
+        offset,
+        fat_type,
+        bytes_per_sector,
+        cluster_size: sectors_per_cluster * bytes_per_sector,
+        reserved_sectors,
+        fat_sectors,
+        data_start_sectors,
+        clusters,
+        volume_size: total_sectors * bytes_per_sector,
+    })
+}
+
+/// Bytes in use on the FAT volume at `offset`, as sorted evidence ranges
+/// `[start, end)`: the reserved sectors, FATs, and root directory, and every
+/// cluster the first FAT marks in use.
+pub fn read_allocated(
+    source: &dyn EvidenceSource,
+    offset: u64,
+) -> Result<(FatBoot, Vec<(u64, u64)>), FatError> {
+    let boot = read_boot(source, offset)?;
+    let mut ranges = vec![(offset, boot.cluster_offset(2))];
+    let fat_offset = offset + boot.reserved_sectors * boot.bytes_per_sector;
+    let fat_len = match boot.fat_type {
+        FatType::Fat12 => (boot.clusters + 2).div_ceil(2) * 3,
+        FatType::Fat16 => (boot.clusters + 2) * 2,
+        FatType::Fat32 => (boot.clusters + 2) * 4,
+    };
+    // FAT12 entries straddle bytes, so that table is read whole; it is at
+    // most 6 KiB.
+    let mut cluster = 0u64;
+    let mut done = 0u64;
+    while done < fat_len {
+        let len = (fat_len - done).min(FAT_READ_SIZE);
+        let mut data = vec![0u8; len as usize];
+        read_exact(source, fat_offset + done, &mut data)?;
+        let entries = match boot.fat_type {
+            FatType::Fat12 => len / 3 * 2,
+            FatType::Fat16 => len / 2,
+            FatType::Fat32 => len / 4,
+        };
+        for i in 0..entries {
+            let used = match boot.fat_type {
+                FatType::Fat12 => {
+                    let at = (i / 2 * 3) as usize;
+                    let pair = u32::from_le_bytes([data[at], data[at + 1], data[at + 2], 0]);
+                    (pair >> (12 * (i % 2))) & 0xFFF != 0
+                }
+                FatType::Fat16 => le_u16(&data, i as usize * 2) != 0,
+                FatType::Fat32 => le_u32(&data, i as usize * 4) & 0x0FFF_FFFF != 0,
+            };
+            if used && (2..boot.clusters + 2).contains(&cluster) {
+                let start = boot.cluster_offset(cluster);
+                match ranges.last_mut() {
+                    Some(last) if last.1 == start => last.1 += boot.cluster_size,
+                    _ => ranges.push((start, start + boot.cluster_size)),
+                }
+            }
+            cluster += 1;
+        }
+        done += len;
+    }
+    Ok((boot, ranges))
+}
+
+/// Clusters of the chain starting at `first`, in order and at most `max`;
+/// empty when the first FAT marks `first` free or bad. A chain running into
+/// a free, bad, or out-of-range cluster ends there.
+pub fn read_chain(
+    source: &dyn EvidenceSource,
+    boot: &FatBoot,
+    first: u64,
+    max: usize,
+) -> Result<Vec<u64>, FatError> {
+    let fat_offset = boot.offset + boot.reserved_sectors * boot.bytes_per_sector;
+    let bad = match boot.fat_type {
+        FatType::Fat12 => 0xFF7,
+        FatType::Fat16 => 0xFFF7,
+        FatType::Fat32 => 0x0FFF_FFF7,
+    };
+    let mut chain = Vec::new();
+    let mut cluster = first;
+    while chain.len() < max && (2..boot.clusters + 2).contains(&cluster) {
+        let mut entry = [0u8; 4];
+        let next = match boot.fat_type {
+            FatType::Fat12 => {
+                read_exact(source, fat_offset + cluster * 3 / 2, &mut entry[..2])?;
+                let pair = u64::from(le_u16(&entry, 0));
+                if cluster.is_multiple_of(2) {
+                    pair & 0xFFF
+                } else {
+                    pair >> 4
+                }
+            }
+            FatType::Fat16 => {
+                read_exact(source, fat_offset + cluster * 2, &mut entry[..2])?;
+                u64::from(le_u16(&entry, 0))
+            }
+            FatType::Fat32 => {
+                read_exact(source, fat_offset + cluster * 4, &mut entry)?;
+                u64::from(le_u32(&entry, 0) & 0x0FFF_FFFF)
+            }
+        };
+        if next == 0 || next == bad {
+            break;
+        }
+        chain.push(cluster);
+        if next > bad {
+            break;
+        }
+        cluster = next;
+    }
+    Ok(chain)
+}
+
+fn read_exact(source: &dyn EvidenceSource, offset: u64, buf: &mut [u8]) -> Result<(), FatError> {
+    let mut done = 0;
+    while done < buf.len() {
+        let n = source.read_at(offset + done as u64, &mut buf[done..])?;
+        if n == 0 {
+            return Err(FatError::Malformed {
+                what: "structure past the end of the evidence",
+                offset,
+            });
+        }
+        done += n;
+    }
+    Ok(())
+}
+
+fn le_u16(data: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes(data[at..at + 2].try_into().unwrap_or_default())
+}
+
+fn le_u32(data: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(data[at..at + 4].try_into().unwrap_or_default())
+}
+
+/// Synthetic FAT volumes.
+#[cfg(test)]
+pub(crate) mod testing {
+    /// A FAT16 volume of 512-byte sectors and 2 KiB clusters with one
+    /// reserved sector, two FATs, and a 512-entry root directory; `used`
+    /// lists the clusters chained in the FAT.
+    pub(crate) fn fat16_volume(total_sectors: u16, used: &[u16]) -> Vec<u8> {
+        const FAT_SECTORS: u16 = 32;
+        let mut image = vec![0u8; total_sectors as usize * 512];
+        image[0] = 0xEB;
+        image[3..11].copy_from_slice(b"MSDOS5.0");
+        image[11..13].copy_from_slice(&512u16.to_le_bytes());
+        image[13] = 4;
+        image[14..16].copy_from_slice(&1u16.to_le_bytes());
+        image[16] = 2;
+        image[17..19].copy_from_slice(&512u16.to_le_bytes());
+        image[19..21].copy_from_slice(&total_sectors.to_le_bytes());
+        image[22..24].copy_from_slice(&FAT_SECTORS.to_le_bytes());
+        image[54..62].copy_from_slice(b"FAT16   ");
+        image[510..512].copy_from_slice(&[0x55, 0xAA]);
+        for fat in 0..2 {
+            let at = 512 + fat * FAT_SECTORS as usize * 512;
+            image[at..at + 4].copy_from_slice(&[0xF8, 0xFF, 0xFF, 0xFF]);
+            for &cluster in used {
+                let entry = at + cluster as usize * 2;
+                image[entry..entry + 2].copy_from_slice(&0xFFFFu16.to_le_bytes());
+            }
+        }
+        image
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::fat16_volume;
+    use super::*;
+    use crate::evidence::MemorySource;
+
+    #[test]
+    fn maps_clusters_the_fat_marks_in_use() {
+        // 1 reserved + 2 × 32 FAT + 32 root directory sectors before cluster 2.
+        let (boot, allocated) =
+            read_allocated(&MemorySource::new(fat16_volume(20_000, &[2, 3, 10])), 0)
+                .expect("fat16");
+        assert_eq!(boot.fat_type, FatType::Fat16);
+        assert_eq!(boot.data_start_sectors, 97);
+        assert_eq!(boot.clusters, (20_000 - 97) / 4);
+        let data = 97 * 512;
+        assert_eq!(
+            allocated,
+            vec![(0, data + 2 * 2048), (data + 8 * 2048, data + 9 * 2048)]
+        );
+    }
+
+    #[test]
+    fn classifies_fat12_by_cluster_count() {
+        let mut image = fat16_volume(4000, &[]);
+        image[22..24].copy_from_slice(&12u16.to_le_bytes());
+        let boot = read_boot(&MemorySource::new(image), 0).expect("fat12");
+        assert_eq!(boot.fat_type, FatType::Fat12);
+
+        let mut no_fat = fat16_volume(4000, &[]);
+        no_fat[22..24].copy_from_slice(&0u16.to_le_bytes());
+        assert!(read_boot(&MemorySource::new(no_fat), 0).is_err());
+    }
+
+    #[test]
+    fn follows_cluster_chains() {
+        let mut image = fat16_volume(20_000, &[5, 6, 9, 12]);
+        // 5 -> 6 -> 9 -> end; 12 points at a free cluster.
+        for (cluster, next) in [(5usize, 6u16), (6, 9), (12, 13)] {
+            image[512 + cluster * 2..512 + cluster * 2 + 2].copy_from_slice(&next.to_le_bytes());
+        }
+        let image = MemorySource::new(image);
+        let boot = read_boot(&image, 0).expect("fat16");
+        assert_eq!(
+            read_chain(&image, &boot, 5, 64).expect("chain"),
+            vec![5, 6, 9]
+        );
+        assert_eq!(read_chain(&image, &boot, 5, 2).expect("chain"), vec![5, 6]);
+        assert_eq!(read_chain(&image, &boot, 12, 64).expect("chain"), vec![12]);
+        assert!(read_chain(&image, &boot, 7, 64).expect("free").is_empty());
+    }
+}
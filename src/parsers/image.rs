@@ -0,0 +1,722 @@
+//! Dimensions and decode completeness of carved images.
+//!
+//! [`image_info`] reads the header of JPEG, PNG, GIF, BMP, WebP, and TIFF
+//! files for width, height, and bits per pixel. No EXIF is needed, so images
+//! whose metadata was stripped or overwritten still get dimensions.
+//!
+//! Baseline JPEGs are also entropy-decoded, without the inverse DCT, to count
+//! the MCUs that decode before the data stops making sense: an invalid Huffman
+//! code, a coefficient past the end of a block, a misplaced marker, a missing
+//! restart marker, a sector-sized run of zero bytes, or the end of the file.
+//! The decoded share lets review queues put intact images first.
+
+use serde::Serialize;
+
+/// Zero bytes in a row that mark a zero-filled sector inside entropy data.
+/// Real encoders never produce this: an all-zero block still needs an EOB code.
+const ZERO_FILL_RUN: usize = 512;
+
+/// Dimensions and decode progress of one carved image.
+#[derive(Debug, Clone, Serialize)]
+pub struct ImageInfoRecord {
+    pub run_id: String,
+    /// `jpeg`, `png`, `gif`, `bmp`, `webp`, or `tiff`.
+    pub format: String,
+    pub width: u32,
+    pub height: u32,
+    pub bits_per_pixel: Option<u32>,
+    /// Share of baseline JPEG MCUs decoded before corruption, 0-100.
+    pub decoded_pct: Option<f64>,
+    /// File offset where JPEG decoding stopped, if it did not finish.
+    pub corrupt_offset: Option<u64>,
+    /// Carved path of the image.
+    pub source_file: String,
+}
+
+/// What [`image_info`] reads from an image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageInfo {
+    pub format: &'static str,
+    pub width: u32,
+    pub height: u32,
+    pub bits_per_pixel: Option<u32>,
+    pub decoded_pct: Option<f64>,
+    pub corrupt_offset: Option<u64>,
+}
+
+impl ImageInfo {
+    fn new(format: &'static str, width: u32, height: u32, bits_per_pixel: Option<u32>) -> Self {
+        Self {
+            format,
+            width,
+            height,
+            bits_per_pixel,
+            decoded_pct: None,
+            corrupt_offset: None,
+        }
+    }
+}
+
+/// Identify the image format by its magic bytes and read its header. Returns
+/// `None` for other data and for headers without usable dimensions.
+pub fn image_info(data: &[u8]) -> Option<ImageInfo> {
+    let info = if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        jpeg_info(data)
+    } else if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        png_info(data)
+    } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        gif_info(data)
+    } else if data.starts_with(b"BM") {
+        bmp_info(data)
+    } else if data.starts_with(b"RIFF") && data.get(8..12) == Some(b"WEBP") {
+        webp_info(data)
+    } else if data.starts_with(b"II*\0") || data.starts_with(b"MM\0*") {
+        tiff_info(data)
+    } else {
+        None
+    }?;
+    (info.width > 0 && info.height > 0).then_some(info)
+}
+
+fn be16(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn be32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn le16(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn le32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn png_info(data: &[u8]) -> Option<ImageInfo> {
+    if data.get(12..16) != Some(b"IHDR") {
+        return None;
+    }
+    let depth = u32::from(*data.get(24)?);
+    let channels = match *data.get(25)? {
+        0 | 3 => 1,
+        2 => 3,
+        4 => 2,
+        6 => 4,
+        _ => return None,
+    };
+    Some(ImageInfo::new(
+        "png",
+        be32(data, 16)?,
+        be32(data, 20)?,
+        Some(depth * channels),
+    ))
+}
+
+fn gif_info(data: &[u8]) -> Option<ImageInfo> {
+    let packed = *data.get(10)?;
+    let bits = (packed & 0x80 != 0).then_some(u32::from(packed & 0x07) + 1);
+    Some(ImageInfo::new(
+        "gif",
+        u32::from(le16(data, 6)?),
+        u32::from(le16(data, 8)?),
+        bits,
+    ))
+}
+
+fn bmp_info(data: &[u8]) -> Option<ImageInfo> {
+    let (width, height, bits) = if le32(data, 14)? == 12 {
+        (
+            u32::from(le16(data, 18)?),
+            u32::from(le16(data, 20)?),
+            le16(data, 24)?,
+        )
+    } else {
+        // Negative heights are top-down bitmaps.
+        (
+            le32(data, 18)?,
+            (le32(data, 22)? as i32).unsigned_abs(),
+            le16(data, 28)?,
+        )
+    };
+    Some(ImageInfo::new("bmp", width, height, Some(u32::from(bits))))
+}
+
+fn webp_info(data: &[u8]) -> Option<ImageInfo> {
+    let (width, height, alpha) = match data.get(12..16)? {
+        b"VP8 " => {
+            if data.get(23..26) != Some(&[0x9D, 0x01, 0x2A]) {
+                return None;
+            }
+            (
+                u32::from(le16(data, 26)? & 0x3FFF),
+                u32::from(le16(data, 28)? & 0x3FFF),
+                false,
+            )
+        }
+        b"VP8L" => {
+            if *data.get(20)? != 0x2F {
+                return None;
+            }
+            let bits = le32(data, 21)?;
+            (
+                (bits & 0x3FFF) + 1,
+                ((bits >> 14) & 0x3FFF) + 1,
+                bits & (1 << 28) != 0,
+            )
+        }
+        b"VP8X" => {
+            let width = le32(data, 24)? & 0x00FF_FFFF;
+            let height = le32(data, 27)? & 0x00FF_FFFF;
+            (width + 1, height + 1, *data.get(20)? & 0x10 != 0)
+        }
+        _ => return None,
+    };
+    Some(ImageInfo::new(
+        "webp",
+        width,
+        height,
+        Some(if alpha { 32 } else { 24 }),
+    ))
+}
+
+fn tiff_info(data: &[u8]) -> Option<ImageInfo> {
+    let little = data[0] == b'I';
+    let u16_at = |at: usize| {
+        if little {
+            le16(data, at)
+        } else {
+            be16(data, at)
+        }
+    };
+    let u32_at = |at: usize| {
+        if little {
+            le32(data, at)
+        } else {
+            be32(data, at)
+        }
+    };
+    let ifd = u32_at(4)? as usize;
+    let count = usize::from(u16_at(ifd)?);
+    let (mut width, mut height) = (None, None);
+    let (mut bits, mut samples) = (None, 1u32);
+    for i in 0..count {
+        let entry = ifd + 2 + i * 12;
+        let tag = u16_at(entry)?;
+        let kind = u16_at(entry + 2)?;
+        let value_count = u32_at(entry + 4)?;
+        let value = match kind {
+            3 => u32::from(u16_at(entry + 8)?),
+            4 => u32_at(entry + 8)?,
+            _ => continue,
+        };
+        match tag {
+            256 => width = Some(value),
+            257 => height = Some(value),
+            // Beyond two samples the values are stored elsewhere; the first
+            // one is representative for the usual equal-depth layouts.
+            258 if value_count <= 2 => bits = Some(value),
+            258 => {
+                let offset = u32_at(entry + 8)? as usize;
+                bits = u16_at(offset).map(u32::from);
+            }
+            277 => samples = value,
+            _ => {}
+        }
+    }
+    Some(ImageInfo::new(
+        "tiff",
+        width?,
+        height?,
+        bits.map(|bits| bits * samples.max(1)),
+    ))
+}
+
+/// Canonical Huffman table in the form of ITU T.81 Annex F.2.2.3.
+struct Huffman {
+    max_code: [i32; 18],
+    val_offset: [i32; 17],
+    values: Vec<u8>,
+}
+
+impl Huffman {
+    fn new(counts: &[u8], values: &[u8]) -> Self {
+        let mut max_code = [-1i32; 18];
+        let mut val_offset = [0i32; 17];
+        let mut code = 0i32;
+        let mut k = 0i32;
+        for len in 1..=16 {
+            let n = i32::from(counts[len - 1]);
+            val_offset[len] = k - code;
+            if n > 0 {
+                code += n;
+                k += n;
+                max_code[len] = code - 1;
+            }
+            code <<= 1;
+        }
+        // Sentinel so decoding always terminates.
+        max_code[17] = i32::MAX;
+        Self {
+            max_code,
+            val_offset,
+            values: values.to_vec(),
+        }
+    }
+
+    fn decode(&self, reader: &mut EntropyReader) -> Result<u8, Stop> {
+        let mut code = reader.bit()? as i32;
+        let mut len = 1;
+        while code > self.max_code[len] {
+            code = (code << 1) | reader.bit()? as i32;
+            len += 1;
+        }
+        if len > 16 {
+            return Err(Stop::Invalid);
+        }
+        let index = self.val_offset[len] + code;
+        usize::try_from(index)
+            .ok()
+            .and_then(|index| self.values.get(index).copied())
+            .ok_or(Stop::Invalid)
+    }
+}
+
+/// Why entropy decoding stopped early.
+#[derive(Debug, PartialEq)]
+enum Stop {
+    Eof,
+    Marker,
+    Invalid,
+    ZeroFill(usize),
+}
+
+/// Reads entropy-coded bits, undoing `FF 00` byte stuffing. Stops in front of
+/// the next marker, leaving `pos` on its `FF`.
+struct EntropyReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    /// Offset of the byte holding the current bits.
+    at: usize,
+    byte: u8,
+    bits: u8,
+    zeros: usize,
+}
+
+impl EntropyReader<'_> {
+    fn bit(&mut self) -> Result<u32, Stop> {
+        if self.bits == 0 {
+            let &byte = self.data.get(self.pos).ok_or(Stop::Eof)?;
+            self.at = self.pos;
+            if byte == 0xFF {
+                match self.data.get(self.pos + 1) {
+                    Some(0) => self.pos += 1,
+                    Some(_) => return Err(Stop::Marker),
+                    None => return Err(Stop::Eof),
+                }
+            }
+            self.pos += 1;
+            if byte == 0 {
+                self.zeros += 1;
+                if self.zeros >= ZERO_FILL_RUN {
+                    return Err(Stop::ZeroFill(self.pos - self.zeros));
+                }
+            } else {
+                self.zeros = 0;
+            }
+            self.byte = byte;
+            self.bits = 8;
+        }
+        self.bits -= 1;
+        Ok(u32::from(self.byte >> self.bits) & 1)
+    }
+
+    fn receive(&mut self, count: u8) -> Result<(), Stop> {
+        for _ in 0..count {
+            self.bit()?;
+        }
+        Ok(())
+    }
+
+    /// Skip the padding bits and consume restart marker `RSTn`.
+    fn restart(&mut self, n: u8) -> Result<(), Stop> {
+        self.bits = 0;
+        while self.data.get(self.pos) == Some(&0xFF) && self.data.get(self.pos + 1) == Some(&0xFF) {
+            self.pos += 1;
+        }
+        match (self.data.get(self.pos), self.data.get(self.pos + 1)) {
+            (Some(0xFF), Some(&marker)) if marker == 0xD0 + n => {
+                self.pos += 2;
+                self.zeros = 0;
+                Ok(())
+            }
+            (None, _) | (Some(0xFF), None) => Err(Stop::Eof),
+            _ => Err(Stop::Invalid),
+        }
+    }
+}
+
+struct Component {
+    id: u8,
+    h: u8,
+    v: u8,
+}
+
+fn jpeg_info(data: &[u8]) -> Option<ImageInfo> {
+    let mut dc_tables: [Option<Huffman>; 4] = Default::default();
+    let mut ac_tables: [Option<Huffman>; 4] = Default::default();
+    let mut frame: Option<(ImageInfo, Vec<Component>, bool)> = None;
+    let mut restart_interval = 0u32;
+    let mut pos = 2;
+    loop {
+        if data.get(pos) != Some(&0xFF) {
+            // Lost segment sync before the scan: dimensions are all we have.
+            return frame.map(|(info, _, _)| info);
+        }
+        while data.get(pos + 1) == Some(&0xFF) {
+            pos += 1;
+        }
+        let Some(&marker) = data.get(pos + 1) else {
+            return frame.map(|(info, _, _)| info);
+        };
+        if marker == 0x01 || (0xD0..=0xD8).contains(&marker) {
+            pos += 2;
+            continue;
+        }
+        if marker == 0xD9 {
+            return frame.map(|(info, _, _)| info);
+        }
+        let Some(len) = be16(data, pos + 2).map(usize::from) else {
+            return frame.map(|(info, _, _)| info);
+        };
+        let Some(segment) = data.get(pos + 4..pos + 2 + len).filter(|_| len >= 2) else {
+            return frame.map(|(info, _, _)| info);
+        };
+        let body_end = pos + 2 + len;
+        match marker {
+            0xC0..=0xCF if !matches!(marker, 0xC4 | 0xC8 | 0xCC) => {
+                let precision = u32::from(*segment.first()?);
+                let height = u32::from(be16(segment, 1)?);
+                let width = u32::from(be16(segment, 3)?);
+                let count = *segment.get(5)?;
+                let components = (0..usize::from(count))
+                    .map(|i| {
+                        let at = 6 + i * 3;
+                        let sampling = *segment.get(at + 1)?;
+                        Some(Component {
+                            id: *segment.get(at)?,
+                            h: (sampling >> 4).max(1),
+                            v: (sampling & 0x0F).max(1),
+                        })
+                    })
+                    .collect::<Option<Vec<_>>>()?;
+                let info =
+                    ImageInfo::new("jpeg", width, height, Some(precision * u32::from(count)));
+                // Baseline and extended sequential Huffman frames only.
+                let decodable = matches!(marker, 0xC0 | 0xC1) && height > 0;
+                frame = Some((info, components, decodable));
+            }
+            0xC4 => {
+                let mut at = 0;
+                while at < segment.len() {
+                    let class_id = segment[at];
+                    let counts = segment.get(at + 1..at + 17)?;
+                    let total: usize = counts.iter().map(|&c| usize::from(c)).sum();
+                    let values = segment.get(at + 17..at + 17 + total)?;
+                    let table = Some(Huffman::new(counts, values));
+                    let slot = usize::from(class_id & 0x03);
+                    if class_id >> 4 == 0 {
+                        dc_tables[slot] = table;
+                    } else {
+                        ac_tables[slot] = table;
+                    }
+                    at += 17 + total;
+                }
+            }
+            0xDD => restart_interval = u32::from(be16(segment, 0)?),
+            0xDA => {
+                let (mut info, components, decodable) = frame?;
+                if decodable {
+                    let scan = ScanTables {
+                        dc: &dc_tables,
+                        ac: &ac_tables,
+                    };
+                    if let Some((pct, stop)) = decode_scan(
+                        data,
+                        body_end,
+                        segment,
+                        &components,
+                        &info,
+                        &scan,
+                        restart_interval,
+                    ) {
+                        info.decoded_pct = Some(pct);
+                        info.corrupt_offset = stop.map(|offset| offset as u64);
+                    }
+                }
+                return Some(info);
+            }
+            _ => {}
+        }
+        pos = body_end;
+    }
+}
+
+struct ScanTables<'a> {
+    dc: &'a [Option<Huffman>; 4],
+    ac: &'a [Option<Huffman>; 4],
+}
+
+/// Entropy-decode the first scan. Returns the decoded MCU share and, if
+/// decoding stopped early, the offset where it did. `None` when the scan does
+/// not cover every component or references missing tables.
+fn decode_scan(
+    data: &[u8],
+    start: usize,
+    header: &[u8],
+    components: &[Component],
+    info: &ImageInfo,
+    tables: &ScanTables,
+    restart_interval: u32,
+) -> Option<(f64, Option<usize>)> {
+    let count = usize::from(*header.first()?);
+    if count != components.len() || count == 0 {
+        return None;
+    }
+    // (DC table, AC table, blocks per MCU) per scan component.
+    let mut plan = Vec::with_capacity(count);
+    for i in 0..count {
+        let id = *header.get(1 + i * 2)?;
+        let selectors = *header.get(2 + i * 2)?;
+        let component = components.iter().find(|c| c.id == id)?;
+        let dc = tables.dc[usize::from(selectors >> 4 & 0x03)].as_ref()?;
+        let ac = tables.ac[usize::from(selectors & 0x03)].as_ref()?;
+        let blocks = if count == 1 {
+            1
+        } else {
+            u32::from(component.h) * u32::from(component.v)
+        };
+        plan.push((dc, ac, blocks));
+    }
+    let (mcu_w, mcu_h) = if count == 1 {
+        (8, 8)
+    } else {
+        let h = components.iter().map(|c| u32::from(c.h)).max()?;
+        let v = components.iter().map(|c| u32::from(c.v)).max()?;
+        (8 * h, 8 * v)
+    };
+    let total = u64::from(info.width.div_ceil(mcu_w)) * u64::from(info.height.div_ceil(mcu_h));
+    let mut reader = EntropyReader {
+        data,
+        pos: start,
+        at: start,
+        byte: 0,
+        bits: 0,
+        zeros: 0,
+    };
+    let mut decoded = 0u64;
+    // MCUs decoded before the current zero run; zeros decode as valid codes.
+    let mut before_zeros = 0u64;
+    let stop = loop {
+        if decoded == total {
+            break None;
+        }
+        if reader.zeros == 0 {
+            before_zeros = decoded;
+        }
+        if restart_interval > 0
+            && decoded > 0
+            && decoded.is_multiple_of(u64::from(restart_interval))
+        {
+            let n = ((decoded / u64::from(restart_interval) - 1) % 8) as u8;
+            if let Err(stop) = reader.restart(n) {
+                break Some(stop);
+            }
+        }
+        if let Err(stop) = decode_mcu(&mut reader, &plan) {
+            break Some(stop);
+        }
+        decoded += 1;
+    };
+    let offset = stop.map(|stop| match stop {
+        Stop::ZeroFill(offset) => {
+            decoded = before_zeros;
+            offset
+        }
+        Stop::Invalid => reader.at,
+        Stop::Eof | Stop::Marker => reader.pos,
+    });
+    let pct = decoded as f64 * 100.0 / total as f64;
+    Some((pct, offset))
+}
+
+fn decode_mcu(reader: &mut EntropyReader, plan: &[(&Huffman, &Huffman, u32)]) -> Result<(), Stop> {
+    for &(dc, ac, blocks) in plan {
+        for _ in 0..blocks {
+            let size = dc.decode(reader)?;
+            if size > 11 {
+                return Err(Stop::Invalid);
+            }
+            reader.receive(size)?;
+            let mut k = 1;
+            while k < 64 {
+                let rs = ac.decode(reader)?;
+                let (run, size) = (rs >> 4, rs & 0x0F);
+                if size == 0 {
+                    if run != 15 {
+                        break;
+                    }
+                    k += 16;
+                    continue;
+                }
+                k += usize::from(run);
+                if k > 63 {
+                    return Err(Stop::Invalid);
+                }
+                reader.receive(size)?;
+                k += 1;
+            }
+            if k > 64 {
+                return Err(Stop::Invalid);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Baseline greyscale JPEG, 8 pixels high, with the given DC table, an AC
+    /// table holding only EOB (code `0`), and optional restart after each MCU.
+    fn jpeg(
+        width: u16,
+        dc_counts: [u8; 16],
+        dc_values: &[u8],
+        restart: bool,
+        scan: &[u8],
+    ) -> Vec<u8> {
+        let mut out = vec![0xFF, 0xD8, 0xFF, 0xC0, 0x00, 0x0B, 8, 0x00, 0x08];
+        out.extend(width.to_be_bytes());
+        out.extend([1, 1, 0x11, 0]);
+        let dc_len = 19 + dc_values.len() as u16;
+        out.extend([0xFF, 0xC4]);
+        out.extend(dc_len.to_be_bytes());
+        out.push(0x00);
+        out.extend(dc_counts);
+        out.extend(dc_values);
+        out.extend([0xFF, 0xC4, 0x00, 0x14, 0x10, 1]);
+        out.extend([0u8; 15]);
+        out.push(0x00);
+        if restart {
+            out.extend([0xFF, 0xDD, 0x00, 0x04, 0x00, 0x01]);
+        }
+        out.extend([0xFF, 0xDA, 0x00, 0x08, 1, 1, 0x00, 0, 63, 0]);
+        out.extend(scan);
+        out.extend([0xFF, 0xD9]);
+        out
+    }
+
+    /// DC table with the single code `0` for size 0: a block is `00`.
+    const DC_ZERO: [u8; 16] = [1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+
+    #[test]
+    fn decodes_complete_baseline_jpeg() {
+        // Two MCUs, each `00` padded with ones, separated by RST0.
+        let data = jpeg(16, DC_ZERO, &[0x00], true, &[0x3F, 0xFF, 0xD0, 0x3F]);
+        let info = image_info(&data).expect("jpeg");
+        assert_eq!((info.width, info.height), (16, 8));
+        assert_eq!(info.bits_per_pixel, Some(8));
+        assert_eq!(info.decoded_pct, Some(100.0));
+        assert_eq!(info.corrupt_offset, None);
+    }
+
+    #[test]
+    fn reports_progress_before_corruption() {
+        // The second MCU starts with an invalid code.
+        let data = jpeg(16, DC_ZERO, &[0x00], true, &[0x3F, 0xFF, 0xD0, 0x80, 0, 0]);
+        let info = image_info(&data).expect("jpeg");
+        assert_eq!(info.decoded_pct, Some(50.0));
+        assert_eq!(info.corrupt_offset, Some(data.len() as u64 - 3));
+
+        // A code cut short by a marker stops at the marker.
+        let data = jpeg(16, DC_ZERO, &[0x00], true, &[0x3F, 0xFF, 0xD0, 0x80]);
+        let info = image_info(&data).expect("jpeg");
+        assert_eq!(info.corrupt_offset, Some(data.len() as u64 - 2));
+
+        // A truncated file stops at the end of the data.
+        let mut cut = jpeg(16, DC_ZERO, &[0x00], true, &[0x3F, 0xFF, 0xD0, 0x3F]);
+        cut.truncate(cut.len() - 5);
+        let info = image_info(&cut).expect("jpeg");
+        assert_eq!(info.decoded_pct, Some(50.0));
+        assert_eq!(info.corrupt_offset, Some(cut.len() as u64));
+    }
+
+    #[test]
+    fn zero_filled_sector_stops_decoding() {
+        // DC codes `0` (size 1) and `10` (size 0): a clean block is `100`, but
+        // zeros also decode, as size-1 blocks. 16 clean MCUs, then a sector of
+        // zeros, in a 2048-MCU image.
+        let mut dc = [0u8; 16];
+        dc[0] = 1;
+        dc[1] = 1;
+        let mut scan = [0x92, 0x49, 0x24].repeat(2);
+        scan.extend([0u8; 1024]);
+        let data = jpeg(8 * 2048, dc, &[0x01, 0x00], false, &scan);
+        let info = image_info(&data).expect("jpeg");
+        assert_eq!(info.decoded_pct, Some(16.0 * 100.0 / 2048.0));
+        let zeros = data.len() - 2 - 1024;
+        assert_eq!(info.corrupt_offset, Some(zeros as u64));
+    }
+
+    #[test]
+    fn reads_other_format_headers() {
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        png.extend(640u32.to_be_bytes());
+        png.extend(480u32.to_be_bytes());
+        png.extend([8, 6, 0, 0, 0]);
+        let info = image_info(&png).expect("png");
+        assert_eq!(
+            (info.format, info.width, info.height, info.bits_per_pixel),
+            ("png", 640, 480, Some(32))
+        );
+
+        let gif = b"GIF89a\x20\x00\x10\x00\xF7\x00\x00";
+        let info = image_info(gif).expect("gif");
+        assert_eq!(
+            (info.width, info.height, info.bits_per_pixel),
+            (32, 16, Some(8))
+        );
+
+        let mut bmp = b"BM".to_vec();
+        bmp.extend([0u8; 12]);
+        bmp.extend(40u32.to_le_bytes());
+        bmp.extend(100i32.to_le_bytes());
+        bmp.extend((-50i32).to_le_bytes());
+        bmp.extend(1u16.to_le_bytes());
+        bmp.extend(24u16.to_le_bytes());
+        let info = image_info(&bmp).expect("bmp");
+        assert_eq!(
+            (info.width, info.height, info.bits_per_pixel),
+            (100, 50, Some(24))
+        );
+
+        let mut tiff = b"II*\0\x08\0\0\0".to_vec();
+        tiff.extend(3u16.to_le_bytes());
+        for (tag, kind, value) in [(256u16, 3u16, 300u32), (257, 4, 200), (258, 3, 16)] {
+            tiff.extend(tag.to_le_bytes());
+            tiff.extend(kind.to_le_bytes());
+            tiff.extend(1u32.to_le_bytes());
+            tiff.extend(value.to_le_bytes());
+        }
+        let info = image_info(&tiff).expect("tiff");
+        assert_eq!(
+            (info.width, info.height, info.bits_per_pixel),
+            (300, 200, Some(16))
+        );
+
+        assert!(image_info(b"not an image").is_none());
+    }
+}
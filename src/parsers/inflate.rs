@@ -0,0 +1,362 @@
+//! Minimal DEFLATE (RFC 1951) decoder with gzip (RFC 1952) and zlib (RFC 1950)
+//! wrappers, for compressed blobs inside carved files (Apple Notes bodies).
+//!
+//! Output is capped by the caller so a corrupt or hostile stream cannot
+//! allocate without bound. Checksums are not verified: carved blobs are often
+//! truncated, and the caller validates the decoded structure instead.
+
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum InflateError {
+    #[error("unexpected end of input")]
+    Truncated,
+    #[error("invalid block type")]
+    BlockType,
+    #[error("stored block length mismatch")]
+    StoredLength,
+    #[error("invalid huffman code")]
+    Code,
+    #[error("distance beyond start of output")]
+    Distance,
+    #[error("output exceeds {0} bytes")]
+    Limit(usize),
+    #[error("invalid gzip or zlib header")]
+    Header,
+}
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// Order of code length code lengths in a dynamic block header.
+const CLEN_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bit: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            pos: 0,
+            bit: 0,
+        }
+    }
+
+    fn bits(&mut self, count: u32) -> Result<u32, InflateError> {
+        let mut value = 0u32;
+        for i in 0..count {
+            let byte = *self.data.get(self.pos).ok_or(InflateError::Truncated)?;
+            value |= u32::from((byte >> self.bit) & 1) << i;
+            self.bit += 1;
+            if self.bit == 8 {
+                self.bit = 0;
+                self.pos += 1;
+            }
+        }
+        Ok(value)
+    }
+
+    fn align(&mut self) {
+        if self.bit != 0 {
+            self.bit = 0;
+            self.pos += 1;
+        }
+    }
+}
+
+/// Canonical Huffman decoding table: code counts per length and symbols in
+/// code order.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0u16; 16];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut offsets = [0u16; 16];
+        for len in 1..16 {
+            offsets[len] = offsets[len - 1] + counts[len - 1];
+        }
+        let mut symbols = vec![0u16; lengths.len()];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[offsets[len as usize] as usize] = symbol as u16;
+                offsets[len as usize] += 1;
+            }
+        }
+        Self { counts, symbols }
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, InflateError> {
+        let mut code = 0i32;
+        let mut first = 0i32;
+        let mut index = 0i32;
+        for len in 1..16 {
+            code |= reader.bits(1)? as i32;
+            let count = i32::from(self.counts[len]);
+            if code - count < first {
+                return Ok(self.symbols[(index + (code - first)) as usize]);
+            }
+            index += count;
+            first += count;
+            first <<= 1;
+            code <<= 1;
+        }
+        Err(InflateError::Code)
+    }
+}
+
+/// Decode a raw DEFLATE stream, failing once output would exceed `limit`.
+pub fn inflate(data: &[u8], limit: usize) -> Result<Vec<u8>, InflateError> {
+    let mut reader = BitReader::new(data);
+    let mut out = Vec::new();
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => stored_block(&mut reader, &mut out, limit)?,
+            1 => {
+                let (lit, dist) = fixed_tables();
+                codes(&mut reader, &mut out, &lit, &dist, limit)?;
+            }
+            2 => {
+                let (lit, dist) = dynamic_tables(&mut reader)?;
+                codes(&mut reader, &mut out, &lit, &dist, limit)?;
+            }
+            _ => return Err(InflateError::BlockType),
+        }
+        if last {
+            return Ok(out);
+        }
+    }
+}
+
+/// Decode a gzip member (header fields are skipped, the trailer is ignored).
+pub fn gunzip(data: &[u8], limit: usize) -> Result<Vec<u8>, InflateError> {
+    if data.len() < 18 || data[0] != 0x1F || data[1] != 0x8B || data[2] != 8 {
+        return Err(InflateError::Header);
+    }
+    let flags = data[3];
+    let mut pos = 10;
+    if flags & 0x04 != 0 {
+        let extra = data.get(pos..pos + 2).ok_or(InflateError::Truncated)?;
+        pos += 2 + usize::from(u16::from_le_bytes([extra[0], extra[1]]));
+    }
+    for flag in [0x08, 0x10] {
+        if flags & flag != 0 {
+            let rest = data.get(pos..).ok_or(InflateError::Truncated)?;
+            let end = rest
+                .iter()
+                .position(|&b| b == 0)
+                .ok_or(InflateError::Truncated)?;
+            pos += end + 1;
+        }
+    }
+    if flags & 0x02 != 0 {
+        pos += 2;
+    }
+    inflate(data.get(pos..).ok_or(InflateError::Truncated)?, limit)
+}
+
+/// Decode a zlib stream (the Adler-32 trailer is ignored).
+pub fn unzlib(data: &[u8], limit: usize) -> Result<Vec<u8>, InflateError> {
+    if data.len() < 2
+        || data[0] & 0x0F != 8
+        || !(u16::from(data[0]) << 8 | u16::from(data[1])).is_multiple_of(31)
+        || data[1] & 0x20 != 0
+    {
+        return Err(InflateError::Header);
+    }
+    inflate(&data[2..], limit)
+}
+
+fn stored_block(
+    reader: &mut BitReader,
+    out: &mut Vec<u8>,
+    limit: usize,
+) -> Result<(), InflateError> {
+    reader.align();
+    let header = reader
+        .data
+        .get(reader.pos..reader.pos + 4)
+        .ok_or(InflateError::Truncated)?;
+    let len = u16::from_le_bytes([header[0], header[1]]);
+    let nlen = u16::from_le_bytes([header[2], header[3]]);
+    if len != !nlen {
+        return Err(InflateError::StoredLength);
+    }
+    reader.pos += 4;
+    let block = reader
+        .data
+        .get(reader.pos..reader.pos + usize::from(len))
+        .ok_or(InflateError::Truncated)?;
+    if out.len() + block.len() > limit {
+        return Err(InflateError::Limit(limit));
+    }
+    out.extend_from_slice(block);
+    reader.pos += block.len();
+    Ok(())
+}
+
+fn fixed_tables() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    (Huffman::new(&lengths), Huffman::new(&[5u8; 30]))
+}
+
+fn dynamic_tables(reader: &mut BitReader) -> Result<(Huffman, Huffman), InflateError> {
+    let hlit = reader.bits(5)? as usize + 257;
+    let hdist = reader.bits(5)? as usize + 1;
+    let hclen = reader.bits(4)? as usize + 4;
+    if hlit > 286 || hdist > 30 {
+        return Err(InflateError::Code);
+    }
+    let mut clen = [0u8; 19];
+    for &index in &CLEN_ORDER[..hclen] {
+        clen[index] = reader.bits(3)? as u8;
+    }
+    let clen_table = Huffman::new(&clen);
+    let mut lengths = vec![0u8; hlit + hdist];
+    let mut i = 0;
+    while i < lengths.len() {
+        let symbol = clen_table.decode(reader)?;
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths[..i].last().ok_or(InflateError::Code)?;
+                (previous, 3 + reader.bits(2)? as usize)
+            }
+            17 => (0, 3 + reader.bits(3)? as usize),
+            18 => (0, 11 + reader.bits(7)? as usize),
+            _ => return Err(InflateError::Code),
+        };
+        if i + repeat > lengths.len() {
+            return Err(InflateError::Code);
+        }
+        lengths[i..i + repeat].fill(value);
+        i += repeat;
+    }
+    if lengths[256] == 0 {
+        return Err(InflateError::Code);
+    }
+    Ok((
+        Huffman::new(&lengths[..hlit]),
+        Huffman::new(&lengths[hlit..]),
+    ))
+}
+
+fn codes(
+    reader: &mut BitReader,
+    out: &mut Vec<u8>,
+    lit: &Huffman,
+    dist: &Huffman,
+    limit: usize,
+) -> Result<(), InflateError> {
+    loop {
+        let symbol = lit.decode(reader)?;
+        match symbol {
+            0..=255 => {
+                if out.len() >= limit {
+                    return Err(InflateError::Limit(limit));
+                }
+                out.push(symbol as u8);
+            }
+            256 => return Ok(()),
+            257..=285 => {
+                let index = usize::from(symbol - 257);
+                let len = usize::from(LENGTH_BASE[index])
+                    + reader.bits(u32::from(LENGTH_EXTRA[index]))? as usize;
+                let dsym = usize::from(dist.decode(reader)?);
+                if dsym >= DIST_BASE.len() {
+                    return Err(InflateError::Code);
+                }
+                let distance = usize::from(DIST_BASE[dsym])
+                    + reader.bits(u32::from(DIST_EXTRA[dsym]))? as usize;
+                if distance > out.len() {
+                    return Err(InflateError::Distance);
+                }
+                if out.len() + len > limit {
+                    return Err(InflateError::Limit(limit));
+                }
+                let start = out.len() - distance;
+                for k in 0..len {
+                    out.push(out[start + k]);
+                }
+            }
+            _ => return Err(InflateError::Code),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inflates_stored_and_fixed_blocks() {
+        // Stored block holding "hello".
+        let stored = [0x01, 0x05, 0x00, 0xFA, 0xFF, b'h', b'e', b'l', b'l', b'o'];
+        assert_eq!(inflate(&stored, 64).unwrap(), b"hello");
+        // zlib.compress(b"hello hello hello hello")
+        let zlib = hex::decode("789ccb48cdc9c957c8402701680308b1").unwrap();
+        assert_eq!(unzlib(&zlib, 64).unwrap(), b"hello hello hello hello");
+        assert_eq!(unzlib(&zlib, 10), Err(InflateError::Limit(10)));
+    }
+
+    #[test]
+    fn gunzips_dynamic_block() {
+        // gzip.compress of 200 lines "line N: the quick brown fox\n" (dynamic Huffman).
+        let text: String = (0..200)
+            .map(|n| format!("line {n}: the quick brown fox\n"))
+            .collect();
+        let gz = hex::decode(GZ_LINES).unwrap();
+        assert_eq!(gunzip(&gz, 1 << 20).unwrap(), text.as_bytes());
+        assert_eq!(
+            gunzip(&gz[..gz.len() / 2], 1 << 20),
+            Err(InflateError::Truncated)
+        );
+        assert_eq!(gunzip(b"not gzip at all...", 64), Err(InflateError::Header));
+    }
+
+    const GZ_LINES: &str = concat!(
+        "1f8b080000000000020385d8cd4de0301485d13d55a4045fff9b72068140201068464cf9880238ac",
+        "df2a4789f3f9bd3cbdde5fe5f6fafb787fbdff7bba7bbefe7cbc7dbe5e0f6fff6f5ebe67c1ac62d6",
+        "30eb980dcc26660bb38dd9d1b3134632114d6413e1443a114fe413014542554295ef8e84aa84aa84",
+        "aa84aa84aa84aa84aa849a849a841a3f2f09350935093509350935093509750975097509759e4012",
+        "ea12ea12ea12ea12ea121a121a121a121a121a3ca425342434243424342434253425342534253425",
+        "34f91f93d094d094d094d092d092d092d092d092d092d0e2af5e424b424b425b425b425b425b425b",
+        "425b425b429b3524a12da123a123a123a123a123a123a123a123a1c360743132190b9bb1301a0bab",
+        "b1301b0bbbb1301c0bcbb1301d0bad7ec96b5a39b05dd84e6c37b623db95edcc666787a19deabb08",
+        "add8da616c87b51de676d8db617087c51d2677d8dc69beb8d18ad91d767718de617987e91db67718",
+        "df617d87f99dee5b2ead58e06182870d1e467858e16186871d1e867858e2195e09d08a311ed67898",
+        "e3618f87411e167998e4619387519ee9fd09add8e5619887651ea679d8e6619c87751ee679d8e759",
+        "5e36d18a891e367a18e961a587991e767a18ea61a987a99eedcd1cad58eb61ae87bd1e067b58ec61",
+        "b287cd1e467b58ed395e63fe60f505f2ef0f3402170000",
+    );
+}
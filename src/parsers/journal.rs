@@ -0,0 +1,255 @@
+//! systemd journal file (`*.journal`) parsing.
+//!
+//! Only the fields needed for log events are decoded. Objects are walked in
+//! file order; entries reference their DATA objects by offset, so a
+//! truncated file still yields every entry whose data objects survived.
+//! Compressed DATA payloads (XZ/LZ4/ZSTD) are skipped.
+
+use chrono::NaiveDateTime;
+
+use crate::parsers::time::unix_micro_to_datetime;
+
+/// `LPKSHHRH`, the first eight bytes of every journal file.
+pub const JOURNAL_SIGNATURE: &[u8; 8] = b"LPKSHHRH";
+/// Smallest header written by any systemd release.
+pub const MIN_HEADER_SIZE: u64 = 192;
+
+const HEADER_INCOMPATIBLE_COMPACT: u32 = 1 << 4;
+const OBJECT_HEADER_SIZE: usize = 16;
+const OBJECT_DATA: u8 = 1;
+const OBJECT_ENTRY: u8 = 3;
+const OBJECT_COMPRESSED_MASK: u8 = 0x07;
+const ENTRY_ITEMS_OFFSET: usize = 64;
+/// Upper bound on entries decoded from one file.
+const MAX_ENTRIES: usize = 1_000_000;
+
+/// Fixed header fields used to bound and walk a journal file.
+#[derive(Debug, Clone, Copy)]
+pub struct JournalHeader {
+    pub incompatible_flags: u32,
+    pub state: u8,
+    pub header_size: u64,
+    pub arena_size: u64,
+}
+
+impl JournalHeader {
+    /// Parse the first 104 bytes of a journal file.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < 104 || &data[..8] != JOURNAL_SIGNATURE {
+            return None;
+        }
+        let header = Self {
+            incompatible_flags: read_u32(data, 12)?,
+            state: data[16],
+            header_size: read_u64(data, 88)?,
+            arena_size: read_u64(data, 96)?,
+        };
+        // States are offline, online, and archived.
+        let plausible = header.state <= 2
+            && header.header_size >= MIN_HEADER_SIZE
+            && header.header_size <= 64 * 1024
+            && header.header_size.is_multiple_of(8);
+        plausible.then_some(header)
+    }
+
+    /// Header plus arena, the on-disk size of the file.
+    pub fn file_size(&self) -> u64 {
+        self.header_size.saturating_add(self.arena_size)
+    }
+
+    fn compact(&self) -> bool {
+        self.incompatible_flags & HEADER_INCOMPATIBLE_COMPACT != 0
+    }
+}
+
+/// One journal entry with the fields reported as a log event.
+#[derive(Debug, Clone, Default)]
+pub struct JournalEntry {
+    /// Offset of the ENTRY object within the file.
+    pub offset: u64,
+    pub realtime: Option<NaiveDateTime>,
+    pub message: Option<String>,
+    pub hostname: Option<String>,
+    /// `SYSLOG_IDENTIFIER`, falling back to `_COMM`.
+    pub identifier: Option<String>,
+    pub pid: Option<u32>,
+    pub priority: Option<u8>,
+}
+
+/// Decode the entries of a journal file, or `None` if the header is invalid.
+pub fn parse_journal(data: &[u8]) -> Option<Vec<JournalEntry>> {
+    let header = JournalHeader::parse(data)?;
+    let end = (header.file_size().min(data.len() as u64)) as usize;
+    let mut offset = header.header_size as usize;
+    let mut entries = Vec::new();
+    while offset + OBJECT_HEADER_SIZE <= end && entries.len() < MAX_ENTRIES {
+        let kind = data[offset];
+        let size = read_u64(data, offset + 8)? as usize;
+        if size < OBJECT_HEADER_SIZE || offset.checked_add(size)? > end {
+            break;
+        }
+        if kind == OBJECT_ENTRY
+            && let Some(entry) = parse_entry(data, &header, offset, size)
+        {
+            entries.push(entry);
+        }
+        offset += size.next_multiple_of(8);
+    }
+    Some(entries)
+}
+
+fn parse_entry(
+    data: &[u8],
+    header: &JournalHeader,
+    offset: usize,
+    size: usize,
+) -> Option<JournalEntry> {
+    let realtime = read_u64(data, offset + 24)?;
+    let mut entry = JournalEntry {
+        offset: offset as u64,
+        realtime: i64::try_from(realtime)
+            .ok()
+            .and_then(unix_micro_to_datetime),
+        ..JournalEntry::default()
+    };
+    let item_size = if header.compact() { 4 } else { 16 };
+    let mut item = offset + ENTRY_ITEMS_OFFSET;
+    let mut comm = None;
+    while item + item_size <= offset + size {
+        let data_offset = if header.compact() {
+            read_u32(data, item)? as usize
+        } else {
+            read_u64(data, item)? as usize
+        };
+        item += item_size;
+        let Some(payload) = data_payload(data, header, data_offset) else {
+            continue;
+        };
+        let Some((name, value)) = payload.split_once('=') else {
+            continue;
+        };
+        match name {
+            "MESSAGE" => entry.message = Some(value.to_string()),
+            "_HOSTNAME" => entry.hostname = Some(value.to_string()),
+            "SYSLOG_IDENTIFIER" => entry.identifier = Some(value.to_string()),
+            "_COMM" => comm = Some(value.to_string()),
+            "_PID" => entry.pid = value.parse().ok(),
+            "PRIORITY" => entry.priority = value.parse().ok().filter(|p| *p <= 7),
+            _ => {}
+        }
+    }
+    if entry.identifier.is_none() {
+        entry.identifier = comm;
+    }
+    entry.message.is_some().then_some(entry)
+}
+
+/// Payload of the DATA object at `offset`, if it is uncompressed text.
+fn data_payload<'a>(data: &'a [u8], header: &JournalHeader, offset: usize) -> Option<&'a str> {
+    if data.get(offset).copied()? != OBJECT_DATA {
+        return None;
+    }
+    if data[offset + 1] & OBJECT_COMPRESSED_MASK != 0 {
+        return None;
+    }
+    let size = read_u64(data, offset + 8)? as usize;
+    let payload_start = offset + if header.compact() { 72 } else { 64 };
+    let payload_end = offset.checked_add(size)?;
+    if payload_start > payload_end {
+        return None;
+    }
+    std::str::from_utf8(data.get(payload_start..payload_end)?).ok()
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes(bytes.try_into().ok()?))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    let bytes = data.get(offset..offset + 8)?;
+    Some(u64::from_le_bytes(bytes.try_into().ok()?))
+}
+
+/// Build an uncompressed, non-compact journal file holding `entries`
+/// (realtime in microseconds, `NAME=value` fields).
+#[cfg(test)]
+pub(crate) fn journal_bytes(entries: &[(u64, &[&str])]) -> Vec<u8> {
+    const HEADER_SIZE: usize = 256;
+    let mut out = vec![0u8; HEADER_SIZE];
+    out[..8].copy_from_slice(JOURNAL_SIGNATURE);
+    out[16] = 2;
+    out[88..96].copy_from_slice(&(HEADER_SIZE as u64).to_le_bytes());
+    for (realtime, fields) in entries {
+        let mut items = Vec::new();
+        for field in *fields {
+            let offset = out.len() as u64;
+            let size = 64 + field.len();
+            let mut object = vec![0u8; size.next_multiple_of(8)];
+            object[0] = OBJECT_DATA;
+            object[8..16].copy_from_slice(&(size as u64).to_le_bytes());
+            object[64..size].copy_from_slice(field.as_bytes());
+            out.extend_from_slice(&object);
+            items.push(offset);
+        }
+        let size = ENTRY_ITEMS_OFFSET + items.len() * 16;
+        let mut object = vec![0u8; size];
+        object[0] = OBJECT_ENTRY;
+        object[8..16].copy_from_slice(&(size as u64).to_le_bytes());
+        object[24..32].copy_from_slice(&realtime.to_le_bytes());
+        for (i, item) in items.iter().enumerate() {
+            let at = ENTRY_ITEMS_OFFSET + i * 16;
+            object[at..at + 8].copy_from_slice(&item.to_le_bytes());
+        }
+        out.extend_from_slice(&object);
+    }
+    let arena = (out.len() - HEADER_SIZE) as u64;
+    out[96..104].copy_from_slice(&arena.to_le_bytes());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_entries_and_fields() {
+        let data = journal_bytes(&[
+            (
+                1_700_000_000_000_000,
+                &[
+                    "_HOSTNAME=web01",
+                    "_COMM=sshd",
+                    "_PID=42",
+                    "PRIORITY=6",
+                    "MESSAGE=Accepted",
+                ],
+            ),
+            (1_700_000_001_000_000, &["_HOSTNAME=web01"]),
+            (
+                1_700_000_002_000_000,
+                &["SYSLOG_IDENTIFIER=cron", "_COMM=crond", "MESSAGE=job"],
+            ),
+        ]);
+        let entries = parse_journal(&data).expect("journal");
+        assert_eq!(entries.len(), 2, "entries without MESSAGE are skipped");
+        assert_eq!(entries[0].hostname.as_deref(), Some("web01"));
+        assert_eq!(entries[0].identifier.as_deref(), Some("sshd"));
+        assert_eq!(entries[0].pid, Some(42));
+        assert_eq!(entries[0].priority, Some(6));
+        assert_eq!(
+            entries[0].realtime.map(|t| t.to_string()).as_deref(),
+            Some("2023-11-14 22:13:20")
+        );
+        assert_eq!(entries[1].identifier.as_deref(), Some("cron"));
+        assert_eq!(entries[1].message.as_deref(), Some("job"));
+    }
+
+    #[test]
+    fn truncated_file_keeps_complete_entries() {
+        let data = journal_bytes(&[(1, &["MESSAGE=first"]), (2, &["MESSAGE=second"])]);
+        let entries = parse_journal(&data[..data.len() - 8]).expect("journal");
+        assert_eq!(entries.len(), 1);
+        assert!(JournalHeader::parse(b"LPKSHHRH").is_none());
+    }
+}
@@ -0,0 +1,482 @@
+//! Windows shell link (`.lnk`) files (`[MS-SHLLINK]`).
+//!
+//! A shell link is a 76-byte header (size `0x4C`, the link CLSID, flags,
+//! the target's attributes, times, and size) followed by optional sections
+//! the flags announce, in order: the target ID list (a `u16` size), the link
+//! info (a `u32` size; local base path, volume, or network share), up to
+//! five counted strings (description, relative path, working directory,
+//! arguments, icon location), and extra data blocks ending with a block
+//! smaller than 4 bytes. No field records the file size, so [`parse`] walks
+//! the sections to find the end.
+//!
+//! ANSI strings are read as Latin-1; the code page of the writer is not
+//! recorded.
+
+use chrono::NaiveDateTime;
+use serde::Serialize;
+
+use crate::parsers::time::webkit_timestamp_to_datetime;
+
+pub const HEADER_LEN: usize = 0x4C;
+/// `00021401-0000-0000-C000-000000000046` as stored.
+pub const LINK_CLSID: [u8; 16] = [
+    0x01, 0x14, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0xC0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46,
+];
+
+const HAS_TARGET_ID_LIST: u32 = 0x01;
+const HAS_LINK_INFO: u32 = 0x02;
+const HAS_NAME: u32 = 0x04;
+const HAS_RELATIVE_PATH: u32 = 0x08;
+const HAS_WORKING_DIR: u32 = 0x10;
+const HAS_ARGUMENTS: u32 = 0x20;
+const HAS_ICON_LOCATION: u32 = 0x40;
+const IS_UNICODE: u32 = 0x80;
+
+const VOLUME_ID_AND_LOCAL_BASE_PATH: u32 = 0x01;
+const COMMON_NETWORK_RELATIVE_LINK: u32 = 0x02;
+const LINK_INFO_MIN_HEADER: u32 = 0x1C;
+/// Header size from which the Unicode path offsets are present.
+const LINK_INFO_UNICODE_HEADER: u32 = 0x24;
+
+/// Extra data block signatures defined by the format.
+const FIRST_BLOCK_SIGNATURE: u32 = 0xA000_0001;
+const LAST_BLOCK_SIGNATURE: u32 = 0xA000_000C;
+const ENVIRONMENT_BLOCK: u32 = 0xA000_0001;
+const TRACKER_BLOCK: u32 = 0xA000_0003;
+
+/// A shortcut, read by the `shortcuts` post-processor.
+#[derive(Debug, Clone, Serialize)]
+pub struct ShortcutRecord {
+    pub run_id: String,
+    /// Full path of the target from the link info (local or network), or
+    /// from the environment variable block.
+    pub target_path: Option<String>,
+    pub arguments: Option<String>,
+    pub working_dir: Option<String>,
+    pub relative_path: Option<String>,
+    pub description: Option<String>,
+    pub icon_location: Option<String>,
+    /// Target's size and attributes when the link was last resolved.
+    pub target_size: u32,
+    pub file_attributes: u32,
+    pub target_created_utc: Option<NaiveDateTime>,
+    pub target_accessed_utc: Option<NaiveDateTime>,
+    pub target_modified_utc: Option<NaiveDateTime>,
+    /// Volume serial number of a local target, as `XXXX-XXXX`.
+    pub volume_serial: Option<String>,
+    /// NetBIOS name of the machine the link was created on (tracker block).
+    pub machine_id: Option<String>,
+    /// Carved path of the shortcut.
+    pub source_file: String,
+}
+
+/// Why [`parse`] could not read a shell link.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LnkError {
+    /// The data ends inside a section.
+    Incomplete,
+    Invalid(&'static str),
+}
+
+/// A parsed shell link.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShellLink {
+    /// Bytes from the header to the end of the terminal block.
+    pub len: usize,
+    /// Whether the extra data ended with a terminal block. Without one, `len`
+    /// ends before the first block with an unknown signature.
+    pub terminated: bool,
+    pub flags: u32,
+    pub file_attributes: u32,
+    pub created: u64,
+    pub accessed: u64,
+    pub modified: u64,
+    pub target_size: u32,
+    pub local_base_path: Option<String>,
+    pub net_name: Option<String>,
+    pub common_path_suffix: Option<String>,
+    pub volume_serial: Option<u32>,
+    pub description: Option<String>,
+    pub relative_path: Option<String>,
+    pub working_dir: Option<String>,
+    pub arguments: Option<String>,
+    pub icon_location: Option<String>,
+    pub environment_target: Option<String>,
+    pub machine_id: Option<String>,
+}
+
+impl ShellLink {
+    /// Target path as Explorer resolves it: the local base path or network
+    /// share joined with the common path suffix.
+    pub fn target_path(&self) -> Option<String> {
+        let suffix = self.common_path_suffix.as_deref().unwrap_or_default();
+        let join = |base: &str| {
+            if suffix.is_empty() {
+                base.to_string()
+            } else if base.ends_with('\\') {
+                format!("{base}{suffix}")
+            } else {
+                format!("{base}\\{suffix}")
+            }
+        };
+        self.local_base_path
+            .as_deref()
+            .map(join)
+            .or_else(|| self.net_name.as_deref().map(join))
+            .or_else(|| self.environment_target.clone())
+    }
+
+    pub fn to_record(&self, run_id: &str, source_file: &str) -> ShortcutRecord {
+        ShortcutRecord {
+            run_id: run_id.to_string(),
+            target_path: self.target_path(),
+            arguments: self.arguments.clone(),
+            working_dir: self.working_dir.clone(),
+            relative_path: self.relative_path.clone(),
+            description: self.description.clone(),
+            icon_location: self.icon_location.clone(),
+            target_size: self.target_size,
+            file_attributes: self.file_attributes,
+            target_created_utc: filetime(self.created),
+            target_accessed_utc: filetime(self.accessed),
+            target_modified_utc: filetime(self.modified),
+            volume_serial: self
+                .volume_serial
+                .map(|s| format!("{:04X}-{:04X}", s >> 16, s & 0xFFFF)),
+            machine_id: self.machine_id.clone(),
+            source_file: source_file.to_string(),
+        }
+    }
+}
+
+/// Parse the shell link at the start of `data`.
+pub fn parse(data: &[u8]) -> Result<ShellLink, LnkError> {
+    let header = data.get(..HEADER_LEN).ok_or(LnkError::Incomplete)?;
+    if le_u32(header, 0) != HEADER_LEN as u32 || header[4..20] != LINK_CLSID {
+        return Err(LnkError::Invalid("not a shell link header"));
+    }
+    let flags = le_u32(header, 0x14);
+    let mut link = ShellLink {
+        flags,
+        file_attributes: le_u32(header, 0x18),
+        created: le_u64(header, 0x1C),
+        accessed: le_u64(header, 0x24),
+        modified: le_u64(header, 0x2C),
+        target_size: le_u32(header, 0x34),
+        ..Default::default()
+    };
+    let mut pos = HEADER_LEN;
+
+    if flags & HAS_TARGET_ID_LIST != 0 {
+        let size = usize::from(le_u16(take(data, pos, 2)?, 0));
+        let list = take(data, pos + 2, size)?;
+        check_id_list(list)?;
+        pos += 2 + size;
+    }
+
+    if flags & HAS_LINK_INFO != 0 {
+        let size = le_u32(take(data, pos, 4)?, 0) as usize;
+        if size < LINK_INFO_MIN_HEADER as usize {
+            return Err(LnkError::Invalid("link info too small"));
+        }
+        read_link_info(take(data, pos, size)?, &mut link)?;
+        pos += size;
+    }
+
+    let unicode = flags & IS_UNICODE != 0;
+    for (flag, slot) in [
+        (HAS_NAME, &mut link.description),
+        (HAS_RELATIVE_PATH, &mut link.relative_path),
+        (HAS_WORKING_DIR, &mut link.working_dir),
+        (HAS_ARGUMENTS, &mut link.arguments),
+        (HAS_ICON_LOCATION, &mut link.icon_location),
+    ] {
+        if flags & flag == 0 {
+            continue;
+        }
+        let count = usize::from(le_u16(take(data, pos, 2)?, 0));
+        let len = if unicode { count * 2 } else { count };
+        let text = take(data, pos + 2, len)?;
+        *slot = Some(if unicode { utf16(text) } else { latin1(text) });
+        pos += 2 + len;
+    }
+
+    loop {
+        let size = le_u32(take(data, pos, 4)?, 0) as usize;
+        if size < 4 {
+            link.terminated = true;
+            pos += 4;
+            break;
+        }
+        let signature = le_u32(take(data, pos, 8)?, 4);
+        if size < 8 || !(FIRST_BLOCK_SIGNATURE..=LAST_BLOCK_SIGNATURE).contains(&signature) {
+            break;
+        }
+        let block = take(data, pos, size)?;
+        match signature {
+            ENVIRONMENT_BLOCK if size >= 0x314 => {
+                let target = utf16_z(&block[0x10C..0x314]);
+                link.environment_target = Some(if target.is_empty() {
+                    latin1_z(&block[8..0x10C])
+                } else {
+                    target
+                })
+                .filter(|t| !t.is_empty());
+            }
+            TRACKER_BLOCK if size >= 0x60 => {
+                link.machine_id = Some(latin1_z(&block[0x10..0x20])).filter(|m| !m.is_empty());
+            }
+            _ => {}
+        }
+        pos += size;
+    }
+    link.len = pos;
+    Ok(link)
+}
+
+/// Item IDs must fill the list exactly, ending with a zero size.
+fn check_id_list(list: &[u8]) -> Result<(), LnkError> {
+    let mut at = 0;
+    loop {
+        let size = list
+            .get(at..at + 2)
+            .map(|b| usize::from(le_u16(b, 0)))
+            .ok_or(LnkError::Invalid("target ID list overruns its size"))?;
+        if size == 0 {
+            return if at + 2 == list.len() {
+                Ok(())
+            } else {
+                Err(LnkError::Invalid("target ID list ends early"))
+            };
+        }
+        if size < 2 {
+            return Err(LnkError::Invalid("target ID list item too small"));
+        }
+        at += size;
+    }
+}
+
+fn read_link_info(info: &[u8], link: &mut ShellLink) -> Result<(), LnkError> {
+    let header_size = le_u32(info, 4);
+    if header_size != LINK_INFO_MIN_HEADER
+        && !(LINK_INFO_UNICODE_HEADER..info.len() as u32).contains(&header_size)
+    {
+        return Err(LnkError::Invalid("bad link info header size"));
+    }
+    let info_flags = le_u32(info, 8);
+    let offset = |at: usize| le_u32(info, at) as usize;
+    let unicode_offsets = header_size >= LINK_INFO_UNICODE_HEADER;
+    let field = |at: usize| -> Result<&[u8], LnkError> {
+        let start = offset(at);
+        if start < header_size as usize || start >= info.len() {
+            return Err(LnkError::Invalid("link info offset outside the link info"));
+        }
+        Ok(&info[start..])
+    };
+
+    if info_flags & VOLUME_ID_AND_LOCAL_BASE_PATH != 0 {
+        let volume = field(0x0C)?;
+        if volume.len() >= 0x10 {
+            link.volume_serial = Some(le_u32(volume, 8));
+        }
+        link.local_base_path = Some(if unicode_offsets && offset(0x1C) != 0 {
+            utf16_z(field(0x1C)?)
+        } else {
+            latin1_z(field(0x10)?)
+        });
+    }
+    if info_flags & COMMON_NETWORK_RELATIVE_LINK != 0 {
+        let net = field(0x14)?;
+        if net.len() < 0x14 {
+            return Err(LnkError::Invalid("network link too small"));
+        }
+        let net_name_offset = le_u32(net, 8) as usize;
+        let unicode_name = net_name_offset > 0x14 && net.len() >= 0x1C && le_u32(net, 0x14) != 0;
+        let name = if unicode_name {
+            net.get(le_u32(net, 0x14) as usize..).map(utf16_z)
+        } else {
+            net.get(net_name_offset..).map(latin1_z)
+        };
+        link.net_name = Some(name.ok_or(LnkError::Invalid("network name outside the link"))?);
+    }
+    let suffix = if unicode_offsets && offset(0x20) != 0 {
+        utf16_z(field(0x20)?)
+    } else if offset(0x18) != 0 {
+        latin1_z(field(0x18)?)
+    } else {
+        String::new()
+    };
+    link.common_path_suffix = Some(suffix).filter(|s| !s.is_empty());
+    Ok(())
+}
+
+fn take(data: &[u8], at: usize, len: usize) -> Result<&[u8], LnkError> {
+    data.get(at..at + len).ok_or(LnkError::Incomplete)
+}
+
+fn latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| char::from(b)).collect()
+}
+
+fn latin1_z(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    latin1(&bytes[..end])
+}
+
+fn utf16(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+fn utf16_z(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .take_while(|&unit| unit != 0)
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+/// FILETIME (100 ns since 1601) to a UTC time; zero is unset.
+fn filetime(value: u64) -> Option<NaiveDateTime> {
+    (value != 0)
+        .then(|| webkit_timestamp_to_datetime((value / 10) as i64))
+        .flatten()
+}
+
+fn le_u16(data: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([data[at], data[at + 1]])
+}
+
+fn le_u32(data: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(data[at..at + 4].try_into().unwrap_or_default())
+}
+
+fn le_u64(data: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(data[at..at + 8].try_into().unwrap_or_default())
+}
+
+/// Synthetic shell links.
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::*;
+
+    /// FILETIME of 2024-05-01 10:15:30 UTC.
+    pub(crate) const WRITE_TIME: u64 = 133_590_321_300_000_000;
+
+    /// A Unicode link to `C:\Windows\System32\cmd.exe` with an ID list,
+    /// local link info, a working directory and arguments, and a tracker
+    /// block naming `WORKSTATION7`.
+    pub(crate) fn lnk_bytes() -> Vec<u8> {
+        let flags =
+            HAS_TARGET_ID_LIST | HAS_LINK_INFO | HAS_WORKING_DIR | HAS_ARGUMENTS | IS_UNICODE;
+        let mut data = vec![0u8; HEADER_LEN];
+        data[0..4].copy_from_slice(&(HEADER_LEN as u32).to_le_bytes());
+        data[4..20].copy_from_slice(&LINK_CLSID);
+        data[0x14..0x18].copy_from_slice(&flags.to_le_bytes());
+        data[0x18..0x1C].copy_from_slice(&0x20u32.to_le_bytes());
+        data[0x2C..0x34].copy_from_slice(&WRITE_TIME.to_le_bytes());
+        data[0x34..0x38].copy_from_slice(&289_792u32.to_le_bytes());
+
+        // One 20-byte item (a root folder) and the terminator.
+        let mut items = vec![0u8; 22];
+        items[0..2].copy_from_slice(&20u16.to_le_bytes());
+        items[2] = 0x1F;
+        data.extend_from_slice(&(items.len() as u16).to_le_bytes());
+        data.extend_from_slice(&items);
+
+        let base = b"C:\\Windows\\System32\\cmd.exe\0";
+        let mut volume = vec![0u8; 0x11];
+        volume[0..4].copy_from_slice(&0x11u32.to_le_bytes());
+        volume[4..8].copy_from_slice(&3u32.to_le_bytes());
+        volume[8..12].copy_from_slice(&0x1234_ABCDu32.to_le_bytes());
+        volume[12..16].copy_from_slice(&0x10u32.to_le_bytes());
+        let volume_at = 0x1C;
+        let base_at = volume_at + volume.len();
+        let suffix_at = base_at + base.len();
+        let size = suffix_at + 1;
+        let mut info = vec![0u8; 0x1C];
+        info[0..4].copy_from_slice(&(size as u32).to_le_bytes());
+        info[4..8].copy_from_slice(&0x1Cu32.to_le_bytes());
+        info[8..12].copy_from_slice(&VOLUME_ID_AND_LOCAL_BASE_PATH.to_le_bytes());
+        info[12..16].copy_from_slice(&(volume_at as u32).to_le_bytes());
+        info[16..20].copy_from_slice(&(base_at as u32).to_le_bytes());
+        info[24..28].copy_from_slice(&(suffix_at as u32).to_le_bytes());
+        info.extend_from_slice(&volume);
+        info.extend_from_slice(base);
+        info.push(0);
+        data.extend_from_slice(&info);
+
+        for text in [r"C:\Users\alice", "/c whoami"] {
+            let units: Vec<u16> = text.encode_utf16().collect();
+            data.extend_from_slice(&(units.len() as u16).to_le_bytes());
+            data.extend(units.iter().flat_map(|u| u.to_le_bytes()));
+        }
+
+        let mut tracker = vec![0u8; 0x60];
+        tracker[0..4].copy_from_slice(&0x60u32.to_le_bytes());
+        tracker[4..8].copy_from_slice(&TRACKER_BLOCK.to_le_bytes());
+        tracker[8..12].copy_from_slice(&0x58u32.to_le_bytes());
+        tracker[0x10..0x1C].copy_from_slice(b"WORKSTATION7");
+        data.extend_from_slice(&tracker);
+        data.extend_from_slice(&[0; 4]);
+        data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::lnk_bytes;
+    use super::*;
+
+    #[test]
+    fn walks_every_section() {
+        let data = lnk_bytes();
+        let mut padded = data.clone();
+        padded.extend_from_slice(&[0xAB; 64]);
+        let link = parse(&padded).expect("parse");
+        assert_eq!(link.len, data.len());
+        assert!(link.terminated);
+        let record = link.to_record("run", "lnk/a.lnk");
+        assert_eq!(
+            record.target_path.as_deref(),
+            Some(r"C:\Windows\System32\cmd.exe")
+        );
+        assert_eq!(record.working_dir.as_deref(), Some(r"C:\Users\alice"));
+        assert_eq!(record.arguments.as_deref(), Some("/c whoami"));
+        assert_eq!(record.volume_serial.as_deref(), Some("1234-ABCD"));
+        assert_eq!(record.machine_id.as_deref(), Some("WORKSTATION7"));
+        assert_eq!(record.target_size, 289_792);
+        assert_eq!(
+            record.target_modified_utc.map(|t| t.to_string()).as_deref(),
+            Some("2024-05-01 10:15:30")
+        );
+        assert!(record.target_created_utc.is_none());
+
+        for len in [HEADER_LEN - 1, 100, data.len() - 1] {
+            assert_eq!(parse(&data[..len]), Err(LnkError::Incomplete), "{len}");
+        }
+    }
+
+    #[test]
+    fn rejects_damaged_links() {
+        let mut data = lnk_bytes();
+        data[4] ^= 0xFF;
+        assert!(matches!(parse(&data), Err(LnkError::Invalid(_))));
+
+        let mut data = lnk_bytes();
+        // Item size past the list's end.
+        data[HEADER_LEN + 2] = 0x40;
+        assert!(matches!(parse(&data), Err(LnkError::Invalid(_))));
+
+        let mut data = lnk_bytes();
+        let tracker_at = data.len() - 4 - 0x60;
+        data[tracker_at + 4..tracker_at + 8].copy_from_slice(&0x1234_5678u32.to_le_bytes());
+        let link = parse(&data).expect("unknown block");
+        assert!(!link.terminated);
+        assert_eq!(link.len, tracker_at);
+    }
+}
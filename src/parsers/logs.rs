@@ -0,0 +1,192 @@
+//! Log event records and syslog line parsing.
+//!
+//! Three line formats are recognised:
+//! - RFC 3164 / traditional: `Jan  2 03:04:05 host sshd[123]: message`
+//! - RFC 5424: `<34>1 2024-01-02T03:04:05.123Z host app 123 ID47 - message`
+//! - ISO 8601 prefixed (rsyslog high precision):
+//!   `2024-01-02T03:04:05.123456+01:00 host app[123]: message`
+//!
+//! Traditional timestamps carry no year or zone; they are kept in
+//! `timestamp_raw` and `timestamp` stays empty.
+
+use chrono::NaiveDateTime;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Serialize;
+
+/// A recovered log event (syslog line or journal entry).
+#[derive(Debug, Clone, Serialize)]
+pub struct LogEventRecord {
+    pub run_id: String,
+    /// `syslog` or `journald`.
+    pub source: String,
+    /// UTC timestamp, when the format carries a full date.
+    pub timestamp: Option<NaiveDateTime>,
+    /// Timestamp text as found in the line (syslog only).
+    pub timestamp_raw: Option<String>,
+    pub host: Option<String>,
+    pub program: Option<String>,
+    pub pid: Option<u32>,
+    /// Syslog severity (0-7).
+    pub severity: Option<u8>,
+    pub message: String,
+    /// Evidence offset of the line or journal entry object.
+    pub global_offset: u64,
+    /// Carved path of the journal file (journald only).
+    pub source_file: Option<String>,
+}
+
+static RFC3164_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"^(?:<(\d{1,3})>)?((?:Jan|Feb|Mar|Apr|May|Jun|Jul|Aug|Sep|Oct|Nov|Dec) [ 0-3]\d \d\d:\d\d:\d\d) (\S+) ([^\s:\[]+)(?:\[(\d+)\])?: (.*)$",
+    )
+    .expect("rfc3164 regex")
+});
+static RFC5424_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^<(\d{1,3})>1 (\S+) (\S+) (\S+) (\S+) \S+ (?:-|(?:\[[^\]]*\])+) ?(.*)$")
+        .expect("rfc5424 regex")
+});
+static ISO_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"^(\d{4}-\d\d-\d\dT\d\d:\d\d:\d\d(?:\.\d+)?(?:Z|[+-]\d\d:\d\d)) (\S+) ([^\s:\[]+)(?:\[(\d+)\])?: (.*)$",
+    )
+    .expect("iso syslog regex")
+});
+
+/// A parsed syslog line (without provenance).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyslogLine {
+    pub timestamp: Option<NaiveDateTime>,
+    pub timestamp_raw: String,
+    pub host: String,
+    pub program: String,
+    pub pid: Option<u32>,
+    pub severity: Option<u8>,
+    pub message: String,
+}
+
+/// Parse one syslog line, or `None` if it does not match a known format.
+pub fn parse_syslog_line(line: &str) -> Option<SyslogLine> {
+    let line = line.trim_end_matches(['\r', '\n']);
+    if let Some(caps) = RFC5424_RE.captures(line) {
+        let raw = caps[2].to_string();
+        let pid = caps[5].parse().ok();
+        return Some(SyslogLine {
+            timestamp: parse_iso(&raw),
+            timestamp_raw: raw,
+            host: caps[3].to_string(),
+            program: caps[4].to_string(),
+            pid,
+            severity: severity(&caps[1]),
+            message: caps[6].to_string(),
+        });
+    }
+    if let Some(caps) = ISO_RE.captures(line) {
+        let raw = caps[1].to_string();
+        return Some(SyslogLine {
+            timestamp: Some(parse_iso(&raw)?),
+            timestamp_raw: raw,
+            host: caps[2].to_string(),
+            program: caps[3].to_string(),
+            pid: caps.get(4).and_then(|m| m.as_str().parse().ok()),
+            severity: None,
+            message: caps[5].to_string(),
+        });
+    }
+    let caps = RFC3164_RE.captures(line)?;
+    Some(SyslogLine {
+        timestamp: None,
+        timestamp_raw: caps[2].to_string(),
+        host: caps[3].to_string(),
+        program: caps[4].to_string(),
+        pid: caps.get(5).and_then(|m| m.as_str().parse().ok()),
+        severity: caps.get(1).and_then(|m| severity(m.as_str())),
+        message: caps[6].to_string(),
+    })
+}
+
+/// Syslog events in a string span, one per matching line.
+pub fn extract_syslog_events(run_id: &str, global_start: u64, text: &str) -> Vec<LogEventRecord> {
+    let mut out = Vec::new();
+    let mut offset = 0usize;
+    for line in text.split_inclusive('\n') {
+        if let Some(parsed) = parse_syslog_line(line) {
+            out.push(LogEventRecord {
+                run_id: run_id.to_string(),
+                source: "syslog".to_string(),
+                timestamp: parsed.timestamp,
+                timestamp_raw: Some(parsed.timestamp_raw),
+                host: Some(parsed.host),
+                program: Some(parsed.program),
+                pid: parsed.pid,
+                severity: parsed.severity,
+                message: parsed.message,
+                global_offset: global_start + offset as u64,
+                source_file: None,
+            });
+        }
+        offset += line.len();
+    }
+    out
+}
+
+fn parse_iso(raw: &str) -> Option<NaiveDateTime> {
+    chrono::DateTime::parse_from_rfc3339(raw)
+        .ok()
+        .map(|dt| dt.naive_utc())
+}
+
+/// Severity from a `<PRI>` value (facility * 8 + severity).
+fn severity(pri: &str) -> Option<u8> {
+    let pri: u16 = pri.parse().ok()?;
+    (pri <= 191).then_some((pri % 8) as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_syslog_formats() {
+        let line =
+            parse_syslog_line("Jan  2 03:04:05 web01 sshd[4242]: Accepted publickey for root")
+                .expect("rfc3164");
+        assert_eq!(line.timestamp, None);
+        assert_eq!(line.timestamp_raw, "Jan  2 03:04:05");
+        assert_eq!(line.host, "web01");
+        assert_eq!(line.program, "sshd");
+        assert_eq!(line.pid, Some(4242));
+        assert_eq!(line.message, "Accepted publickey for root");
+
+        let line = parse_syslog_line(
+            "<165>1 2024-01-02T03:04:05.123Z web01 app 77 ID47 [meta a=\"b\"] started",
+        )
+        .expect("rfc5424");
+        assert_eq!(line.severity, Some(5));
+        assert_eq!(line.pid, Some(77));
+        assert_eq!(line.message, "started");
+        assert_eq!(
+            line.timestamp.map(|t| t.to_string()).as_deref(),
+            Some("2024-01-02 03:04:05.123")
+        );
+
+        let line =
+            parse_syslog_line("2024-01-02T03:04:05+01:00 web01 kernel: oom-killer").expect("iso");
+        assert_eq!(
+            line.timestamp.map(|t| t.to_string()).as_deref(),
+            Some("2024-01-02 02:04:05")
+        );
+        assert_eq!(line.pid, None);
+
+        assert!(parse_syslog_line("Jan 2 is not a log line").is_none());
+    }
+
+    #[test]
+    fn extracts_events_with_line_offsets() {
+        let text = "noise\nFeb 10 11:12:13 host cron[1]: job ran\n";
+        let events = extract_syslog_events("run", 1000, text);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].global_offset, 1006);
+        assert_eq!(events[0].program.as_deref(), Some("cron"));
+    }
+}
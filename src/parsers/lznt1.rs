@@ -0,0 +1,240 @@
+//! LZNT1 decompression (NTFS file compression, `[MS-XCA]` 2.5).
+//!
+//! NTFS compresses a file in compression units of 16 clusters. Each unit is a
+//! series of chunks that decompress to at most 4 KiB; a chunk starts with a
+//! 16-bit header: bits 0-11 hold the chunk length minus 3, bits 12-14 are the
+//! signature `0b011`, and bit 15 marks a compressed chunk. Uncompressed chunks
+//! hold 4096 raw bytes. A zero header, or the end of the unit, ends the unit.
+//!
+//! [`decompress_unit`] is strict so it can be used to detect units in
+//! unallocated space: a malformed chunk ends the unit, and the chunks decoded
+//! so far are kept with a map from decompressed offsets back to the
+//! compressed bytes they came from.
+
+/// Bytes one chunk decompresses to at most.
+pub const CHUNK_SIZE: usize = 4096;
+const SIGNATURE_MASK: u16 = 0x7000;
+const SIGNATURE: u16 = 0x3000;
+const COMPRESSED: u16 = 0x8000;
+
+/// One chunk of a decompressed unit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkMap {
+    /// Offset of the chunk header in the compressed input.
+    pub compressed_offset: usize,
+    /// Chunk length including its header.
+    pub compressed_len: usize,
+    pub decompressed_offset: usize,
+    pub decompressed_len: usize,
+    pub compressed: bool,
+}
+
+impl ChunkMap {
+    /// Input offset of decompressed byte `offset`, which must lie in this
+    /// chunk: exact for stored chunks, the chunk header for compressed ones.
+    pub fn source_offset(&self, offset: usize) -> usize {
+        if self.compressed {
+            self.compressed_offset
+        } else {
+            self.compressed_offset + 2 + (offset - self.decompressed_offset)
+        }
+    }
+
+    /// Last input byte that contributes to decompressed byte `offset`.
+    pub fn source_end(&self, offset: usize) -> usize {
+        if self.compressed {
+            self.compressed_offset + self.compressed_len - 1
+        } else {
+            self.source_offset(offset)
+        }
+    }
+}
+
+/// A decompressed compression unit.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Lznt1Unit {
+    pub data: Vec<u8>,
+    pub chunks: Vec<ChunkMap>,
+    /// Compressed bytes consumed, including a terminating zero header.
+    pub consumed: usize,
+}
+
+impl Lznt1Unit {
+    /// Chunk that produced decompressed byte `offset`.
+    pub fn chunk_at(&self, offset: usize) -> Option<&ChunkMap> {
+        let idx = self
+            .chunks
+            .partition_point(|c| c.decompressed_offset + c.decompressed_len <= offset);
+        self.chunks
+            .get(idx)
+            .filter(|c| offset >= c.decompressed_offset)
+    }
+
+    /// Whether at least one chunk was compressed; units of stored chunks
+    /// only are indistinguishable from plain data.
+    pub fn has_compressed_chunk(&self) -> bool {
+        self.chunks.iter().any(|c| c.compressed)
+    }
+}
+
+/// Whether `data` starts with a compressed LZNT1 chunk header.
+pub fn is_compressed_chunk_header(data: &[u8]) -> bool {
+    data.len() >= 2 && {
+        let header = u16::from_le_bytes([data[0], data[1]]);
+        header & (SIGNATURE_MASK | COMPRESSED) == SIGNATURE | COMPRESSED
+    }
+}
+
+/// Decompress the unit at the start of `input`, producing at most
+/// `max_output` bytes.
+pub fn decompress_unit(input: &[u8], max_output: usize) -> Lznt1Unit {
+    let mut unit = Lznt1Unit::default();
+    let mut pos = 0usize;
+    while pos + 2 <= input.len() && unit.data.len() + CHUNK_SIZE <= max_output {
+        let header = u16::from_le_bytes([input[pos], input[pos + 1]]);
+        if header == 0 {
+            pos += 2;
+            break;
+        }
+        if header & SIGNATURE_MASK != SIGNATURE {
+            break;
+        }
+        let len = (header & 0x0FFF) as usize + 3;
+        let Some(body) = input.get(pos + 2..pos + len) else {
+            break;
+        };
+        let compressed = header & COMPRESSED != 0;
+        let start = unit.data.len();
+        if compressed {
+            if decompress_chunk(body, &mut unit.data).is_none() {
+                unit.data.truncate(start);
+                break;
+            }
+        } else {
+            if body.len() != CHUNK_SIZE {
+                break;
+            }
+            unit.data.extend_from_slice(body);
+        }
+        unit.chunks.push(ChunkMap {
+            compressed_offset: pos,
+            compressed_len: len,
+            decompressed_offset: start,
+            decompressed_len: unit.data.len() - start,
+            compressed,
+        });
+        pos += len;
+    }
+    unit.consumed = pos;
+    unit
+}
+
+/// Decode one compressed chunk body onto `out`. `None` if a token is
+/// malformed or the chunk would exceed [`CHUNK_SIZE`].
+fn decompress_chunk(body: &[u8], out: &mut Vec<u8>) -> Option<()> {
+    let base = out.len();
+    let mut i = 0usize;
+    while i < body.len() {
+        let flags = body[i];
+        i += 1;
+        for bit in 0..8 {
+            if i >= body.len() {
+                break;
+            }
+            let pos = out.len() - base;
+            if flags & (1 << bit) == 0 {
+                if pos >= CHUNK_SIZE {
+                    return None;
+                }
+                out.push(body[i]);
+                i += 1;
+                continue;
+            }
+            if pos == 0 || i + 2 > body.len() {
+                return None;
+            }
+            let token = u16::from_le_bytes([body[i], body[i + 1]]);
+            i += 2;
+            // Offset bits grow with the position in the chunk: 4 bits up to
+            // 16 bytes, one more per doubling, up to 12.
+            let mut offset_bits = 4u32;
+            let mut p = pos - 1;
+            while p >= 0x10 {
+                offset_bits += 1;
+                p >>= 1;
+            }
+            let length_bits = 16 - offset_bits;
+            let length = (token & ((1 << length_bits) - 1)) as usize + 3;
+            let offset = (token >> length_bits) as usize + 1;
+            if offset > pos || pos + length > CHUNK_SIZE {
+                return None;
+            }
+            let from = out.len() - offset;
+            // Overlapping copies repeat the last `offset` bytes.
+            for k in 0..length {
+                let byte = out[from + k];
+                out.push(byte);
+            }
+        }
+    }
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// "abcabcabcabc" + "xyz": three literals, a back-reference of offset 3
+    /// and length 9, then three literals.
+    fn sample_chunk() -> Vec<u8> {
+        // At position 3 a token has 4 offset and 12 length bits, so offset 3
+        // and length 9 are `(3 - 1) << 12 | (9 - 3)`.
+        let token: u16 = (2 << 12) | 6;
+        let mut body = vec![0b0000_1000, b'a', b'b', b'c'];
+        body.extend(token.to_le_bytes());
+        body.extend([b'x', b'y', b'z']);
+        let header = SIGNATURE | COMPRESSED | (body.len() as u16 + 2 - 3);
+        let mut chunk = header.to_le_bytes().to_vec();
+        chunk.extend(body);
+        chunk
+    }
+
+    #[test]
+    fn decompresses_back_references() {
+        let mut input = sample_chunk();
+        let compressed_len = input.len();
+        input.extend([0, 0, 0xAA]);
+        let unit = decompress_unit(&input, 64 * 1024);
+        assert_eq!(unit.data, b"abcabcabcabcxyz");
+        assert_eq!(unit.consumed, compressed_len + 2);
+        assert!(unit.has_compressed_chunk());
+        let chunk = unit.chunk_at(14).expect("chunk");
+        assert_eq!(chunk.source_offset(14), 0);
+        assert_eq!(chunk.source_end(14), compressed_len - 1);
+    }
+
+    #[test]
+    fn stored_chunks_map_exactly_and_bad_chunks_end_the_unit() {
+        let mut input = (SIGNATURE | (CHUNK_SIZE as u16 + 2 - 3))
+            .to_le_bytes()
+            .to_vec();
+        input.extend((0..CHUNK_SIZE).map(|i| i as u8));
+        input.extend(sample_chunk());
+        // A back-reference before the start of the chunk.
+        let bad = [0x03, 0xB0, 0b0000_0001, 0xFF, 0xFF, b'q'];
+        input.extend(bad);
+        let unit = decompress_unit(&input, 64 * 1024);
+        assert_eq!(unit.chunks.len(), 2);
+        assert_eq!(unit.data.len(), CHUNK_SIZE + 15);
+        let stored = unit.chunk_at(100).expect("stored chunk");
+        assert!(!stored.compressed);
+        assert_eq!(stored.source_offset(100), 102);
+        assert_eq!(
+            unit.chunk_at(CHUNK_SIZE).unwrap().compressed_offset,
+            CHUNK_SIZE + 2
+        );
+        assert!(unit.chunk_at(CHUNK_SIZE + 15).is_none());
+        assert!(is_compressed_chunk_header(&sample_chunk()));
+        assert!(!is_compressed_chunk_header(&[0x00, 0x30]));
+    }
+}
@@ -0,0 +1,585 @@
+//! Mach-O headers: thin files (32- and 64-bit, either byte order) and
+//! universal ("fat") files holding one slice per architecture.
+//!
+//! A thin file ends where the last data its load commands point at ends:
+//! segment contents (`fileoff + filesize`), section relocations, the symbol
+//! and string tables, and the `__LINKEDIT` blobs named by `linkedit_data`
+//! and `dyld_info` commands (code signature, function starts, chained fixups,
+//! ...). A fat file ends after its last slice, as the fat header lists them.
+//! The fat magic is shared with Java class files, whose version fields read
+//! as an architecture count above the limit here.
+
+use super::executable::ExecutableRecord;
+
+const MH_MAGIC: u32 = 0xFEED_FACE;
+const MH_MAGIC_64: u32 = 0xFEED_FACF;
+const FAT_MAGIC: u32 = 0xCAFE_BABE;
+const FAT_MAGIC_64: u32 = 0xCAFE_BABF;
+
+const LC_SEGMENT: u32 = 0x1;
+const LC_SYMTAB: u32 = 0x2;
+const LC_SEGMENT_64: u32 = 0x19;
+const LC_CODE_SIGNATURE: u32 = 0x1D;
+const LC_DYLD_INFO: u32 = 0x22;
+const LC_DYLD_INFO_ONLY: u32 = 0x8000_0022;
+/// Commands whose body is a `linkedit_data_command` (`dataoff`, `datasize`).
+const LINKEDIT_DATA_COMMANDS: &[u32] = &[
+    LC_CODE_SIGNATURE,
+    0x1E,        // LC_SEGMENT_SPLIT_INFO
+    0x26,        // LC_FUNCTION_STARTS
+    0x29,        // LC_DATA_IN_CODE
+    0x2B,        // LC_DYLIB_CODE_SIGN_DRS
+    0x2E,        // LC_LINKER_OPTIMIZATION_HINT
+    0x8000_0033, // LC_DYLD_EXPORTS_TRIE
+    0x8000_0034, // LC_DYLD_CHAINED_FIXUPS
+];
+
+/// Architectures a fat header may list; `file(1)` uses a lower limit.
+pub const MAX_FAT_ARCHES: u32 = 32;
+/// Load commands larger than this are taken as garbage.
+const MAX_COMMANDS_LEN: u32 = 16 * 1024 * 1024;
+const MAX_EXTENT: u64 = 1 << 40;
+const CPU_ARCH_ABI64: u32 = 0x0100_0000;
+
+/// Why a Mach-O file could not be read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MachOError {
+    /// The data ends inside a header or the load commands.
+    Incomplete,
+    Invalid(&'static str),
+}
+
+/// One thin Mach-O image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MachO {
+    pub is_64: bool,
+    pub big_endian: bool,
+    pub cpu_type: u32,
+    /// `filetype`: object, executable, dylib, bundle, ...
+    pub file_type: u32,
+    pub command_count: u32,
+    pub section_count: u32,
+    /// Carries an `LC_CODE_SIGNATURE` command.
+    pub signed: bool,
+    /// End of the last data the load commands point at.
+    pub size: u64,
+}
+
+/// One architecture of a fat file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FatArch {
+    pub cpu_type: u32,
+    pub offset: u64,
+    pub size: u64,
+}
+
+/// A thin or fat Mach-O file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MachOFile {
+    Thin(MachO),
+    /// The slices are parsed at their offsets; the first is used for the
+    /// kind and the section count. Slices after the first that the data ends
+    /// in are left out of `slices`.
+    Fat {
+        arches: Vec<FatArch>,
+        slices: Vec<MachO>,
+    },
+}
+
+/// Parse the file read through `read` (file offset, buffer; returns the bytes
+/// read).
+pub fn parse(read: &mut dyn FnMut(u64, &mut [u8]) -> usize) -> Result<MachOFile, MachOError> {
+    let mut magic = [0u8; 4];
+    if read(0, &mut magic) < 4 {
+        return Err(MachOError::Incomplete);
+    }
+    match u32::from_be_bytes(magic) {
+        FAT_MAGIC => parse_fat(read, false),
+        FAT_MAGIC_64 => parse_fat(read, true),
+        _ => parse_thin(read, 0).map(MachOFile::Thin),
+    }
+}
+
+fn parse_fat(
+    read: &mut dyn FnMut(u64, &mut [u8]) -> usize,
+    wide: bool,
+) -> Result<MachOFile, MachOError> {
+    let mut header = [0u8; 8];
+    if read(0, &mut header) < 8 {
+        return Err(MachOError::Incomplete);
+    }
+    let count = be_u32(&header, 4);
+    if count == 0 || count > MAX_FAT_ARCHES {
+        return Err(MachOError::Invalid("fat architecture count out of range"));
+    }
+    let entry_len = if wide { 32 } else { 20 };
+    let mut table = vec![0u8; count as usize * entry_len];
+    if read(8, &mut table) < table.len() {
+        return Err(MachOError::Incomplete);
+    }
+    let table_end = 8 + table.len() as u64;
+    let mut arches = Vec::with_capacity(count as usize);
+    for entry in table.chunks_exact(entry_len) {
+        let (offset, size, align) = if wide {
+            (be_u64(entry, 8), be_u64(entry, 16), be_u32(entry, 24))
+        } else {
+            (
+                u64::from(be_u32(entry, 8)),
+                u64::from(be_u32(entry, 12)),
+                be_u32(entry, 16),
+            )
+        };
+        if align > 16 || offset < table_end || !offset.is_multiple_of(1 << align) || size == 0 {
+            return Err(MachOError::Invalid("fat architecture entry inconsistent"));
+        }
+        if offset.saturating_add(size) > MAX_EXTENT {
+            return Err(MachOError::Invalid("fat slice beyond any plausible file"));
+        }
+        arches.push(FatArch {
+            cpu_type: be_u32(entry, 0),
+            offset,
+            size,
+        });
+    }
+    let mut slices = Vec::with_capacity(arches.len());
+    for arch in &arches {
+        let slice = match parse_thin(read, arch.offset) {
+            Ok(slice) => slice,
+            // The data ends inside a later slice; the fat header still gives
+            // the size.
+            Err(MachOError::Incomplete) if !slices.is_empty() => break,
+            Err(err) => return Err(err),
+        };
+        if slice.cpu_type != arch.cpu_type {
+            return Err(MachOError::Invalid(
+                "fat slice CPU type differs from its entry",
+            ));
+        }
+        if slice.size > arch.size {
+            return Err(MachOError::Invalid("fat slice larger than its entry"));
+        }
+        slices.push(slice);
+    }
+    Ok(MachOFile::Fat { arches, slices })
+}
+
+/// Parse the thin image at `base`; its offsets are relative to `base`.
+fn parse_thin(
+    read: &mut dyn FnMut(u64, &mut [u8]) -> usize,
+    base: u64,
+) -> Result<MachO, MachOError> {
+    let mut header = [0u8; 32];
+    let n = read(base, &mut header);
+    if n < 28 {
+        return Err(MachOError::Incomplete);
+    }
+    let (is_64, big_endian) = match (
+        be_u32(&header, 0),
+        u32::from_le_bytes([header[0], header[1], header[2], header[3]]),
+    ) {
+        (MH_MAGIC, _) => (false, true),
+        (MH_MAGIC_64, _) => (true, true),
+        (_, MH_MAGIC) => (false, false),
+        (_, MH_MAGIC_64) => (true, false),
+        _ => return Err(MachOError::Invalid("no Mach-O magic")),
+    };
+    let header_len: u64 = if is_64 { 32 } else { 28 };
+    if n < header_len as usize {
+        return Err(MachOError::Incomplete);
+    }
+    let r = Reader { big_endian };
+    let cpu_type = r.u32(&header, 4);
+    let file_type = r.u32(&header, 12);
+    let command_count = r.u32(&header, 16);
+    let commands_len = r.u32(&header, 20);
+    if !(1..=0xC).contains(&file_type) {
+        return Err(MachOError::Invalid("unknown Mach-O file type"));
+    }
+    if command_count == 0
+        || commands_len > MAX_COMMANDS_LEN
+        || u64::from(commands_len) < u64::from(command_count) * 8
+    {
+        return Err(MachOError::Invalid("load commands inconsistent"));
+    }
+    if (cpu_type & CPU_ARCH_ABI64 != 0) != is_64 {
+        return Err(MachOError::Invalid(
+            "CPU type differs from the header width",
+        ));
+    }
+    let mut commands = vec![0u8; commands_len as usize];
+    if read(base + header_len, &mut commands) < commands.len() {
+        return Err(MachOError::Incomplete);
+    }
+
+    let mut image = MachO {
+        is_64,
+        big_endian,
+        cpu_type,
+        file_type,
+        command_count,
+        section_count: 0,
+        signed: false,
+        size: header_len + u64::from(commands_len),
+    };
+    let mut extend = |offset: u64, len: u64| -> Result<(), MachOError> {
+        if len == 0 {
+            return Ok(());
+        }
+        let end = offset
+            .checked_add(len)
+            .filter(|&end| end <= MAX_EXTENT)
+            .ok_or(MachOError::Invalid("contents beyond any plausible file"))?;
+        image.size = image.size.max(end);
+        Ok(())
+    };
+    let mut at = 0usize;
+    let mut section_count = 0u32;
+    let mut signed = false;
+    for _ in 0..command_count {
+        if at + 8 > commands.len() {
+            return Err(MachOError::Invalid("load command beyond sizeofcmds"));
+        }
+        let cmd = r.u32(&commands, at);
+        let cmd_len = r.u32(&commands, at + 4) as usize;
+        if cmd_len < 8 || !cmd_len.is_multiple_of(4) || at + cmd_len > commands.len() {
+            return Err(MachOError::Invalid("load command size inconsistent"));
+        }
+        let body = &commands[at..at + cmd_len];
+        match cmd {
+            LC_SEGMENT | LC_SEGMENT_64 => {
+                let wide = cmd == LC_SEGMENT_64;
+                let (fixed, section_len) = if wide { (72, 80) } else { (56, 68) };
+                if body.len() < fixed {
+                    return Err(MachOError::Invalid("segment command too short"));
+                }
+                let (file_offset, file_size, nsects) = if wide {
+                    (r.u64(body, 40), r.u64(body, 48), r.u32(body, 64))
+                } else {
+                    (
+                        u64::from(r.u32(body, 32)),
+                        u64::from(r.u32(body, 36)),
+                        r.u32(body, 48),
+                    )
+                };
+                if fixed + nsects as usize * section_len > body.len() {
+                    return Err(MachOError::Invalid("segment sections beyond its command"));
+                }
+                extend(file_offset, file_size)?;
+                for i in 0..nsects as usize {
+                    let section = &body[fixed + i * section_len..];
+                    let (relocs_at, count_at) = if wide { (56, 60) } else { (48, 52) };
+                    let relocations = r.u32(section, relocs_at);
+                    let count = r.u32(section, count_at);
+                    extend(u64::from(relocations), u64::from(count) * 8)?;
+                }
+                section_count += nsects;
+            }
+            LC_SYMTAB if body.len() >= 24 => {
+                let symbol_len = if is_64 { 16 } else { 12 };
+                extend(
+                    u64::from(r.u32(body, 8)),
+                    u64::from(r.u32(body, 12)) * symbol_len,
+                )?;
+                extend(u64::from(r.u32(body, 16)), u64::from(r.u32(body, 20)))?;
+            }
+            LC_DYLD_INFO | LC_DYLD_INFO_ONLY if body.len() >= 48 => {
+                for pair in 0..5 {
+                    let at = 8 + pair * 8;
+                    extend(u64::from(r.u32(body, at)), u64::from(r.u32(body, at + 4)))?;
+                }
+            }
+            cmd if LINKEDIT_DATA_COMMANDS.contains(&cmd) && body.len() >= 16 => {
+                signed |= cmd == LC_CODE_SIGNATURE;
+                extend(u64::from(r.u32(body, 8)), u64::from(r.u32(body, 12)))?;
+            }
+            _ => {}
+        }
+        at += cmd_len;
+    }
+    image.section_count = section_count;
+    image.signed = signed;
+    Ok(image)
+}
+
+impl MachO {
+    /// `exe`, `dylib`, `bundle`, `object`, `core`, `kext`, ... from the file
+    /// type.
+    pub fn kind(&self) -> &'static str {
+        match self.file_type {
+            1 => "object",
+            2 => "exe",
+            3 => "fvmlib",
+            4 => "core",
+            5 => "preload",
+            6 => "dylib",
+            7 => "dylinker",
+            8 => "bundle",
+            9 => "dylib_stub",
+            10 => "dsym",
+            _ => "kext",
+        }
+    }
+
+    pub fn architecture(&self) -> &'static str {
+        cpu_name(self.cpu_type)
+    }
+}
+
+fn cpu_name(cpu_type: u32) -> &'static str {
+    match cpu_type {
+        7 => "x86",
+        0x0100_0007 => "x86_64",
+        12 => "arm",
+        0x0100_000C => "arm64",
+        0x0200_000C => "arm64_32",
+        18 => "ppc",
+        0x0100_0012 => "ppc64",
+        _ => "unknown",
+    }
+}
+
+impl MachOFile {
+    /// End of the thin image, or of the last fat slice.
+    pub fn size(&self) -> u64 {
+        match self {
+            Self::Thin(image) => image.size,
+            Self::Fat { arches, .. } => arches
+                .iter()
+                .map(|arch| arch.offset + arch.size)
+                .max()
+                .unwrap_or(0),
+        }
+    }
+
+    /// The thin image, or the first slice of a fat file.
+    pub fn primary(&self) -> &MachO {
+        match self {
+            Self::Thin(image) => image,
+            Self::Fat { slices, .. } => &slices[0],
+        }
+    }
+
+    /// Architecture of a thin image; for a fat file, those of its slices
+    /// joined with commas in header order.
+    pub fn architecture(&self) -> String {
+        match self {
+            Self::Thin(image) => image.architecture().to_string(),
+            Self::Fat { arches, .. } => arches
+                .iter()
+                .map(|arch| cpu_name(arch.cpu_type))
+                .collect::<Vec<_>>()
+                .join(","),
+        }
+    }
+
+    pub fn to_record(&self, run_id: &str, source_file: &str) -> ExecutableRecord {
+        let primary = self.primary();
+        let (bits, signed) = match self {
+            Self::Thin(image) => (if image.is_64 { 64 } else { 32 }, image.signed),
+            Self::Fat { slices, .. } => (
+                if slices.iter().any(|s| s.is_64) {
+                    64
+                } else {
+                    32
+                },
+                slices.iter().all(|s| s.signed),
+            ),
+        };
+        ExecutableRecord {
+            run_id: run_id.to_string(),
+            format: "macho".to_string(),
+            kind: primary.kind().to_string(),
+            architecture: self.architecture(),
+            bits,
+            compile_time_utc: None,
+            subsystem: None,
+            section_count: primary.section_count,
+            import_count: None,
+            import_hash: None,
+            signed,
+            source_file: source_file.to_string(),
+        }
+    }
+}
+
+fn be_u32(data: &[u8], at: usize) -> u32 {
+    u32::from_be_bytes(data[at..at + 4].try_into().unwrap_or_default())
+}
+
+fn be_u64(data: &[u8], at: usize) -> u64 {
+    u64::from_be_bytes(data[at..at + 8].try_into().unwrap_or_default())
+}
+
+#[derive(Clone, Copy)]
+struct Reader {
+    big_endian: bool,
+}
+
+impl Reader {
+    fn u32(self, data: &[u8], at: usize) -> u32 {
+        let bytes = data[at..at + 4].try_into().unwrap_or_default();
+        if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        }
+    }
+
+    fn u64(self, data: &[u8], at: usize) -> u64 {
+        let bytes = data[at..at + 8].try_into().unwrap_or_default();
+        if self.big_endian {
+            u64::from_be_bytes(bytes)
+        } else {
+            u64::from_le_bytes(bytes)
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod testing {
+    /// Length of [`macho_bytes`]: the `__TEXT` segment, then `__LINKEDIT`
+    /// with the symbol table, string table, and code signature.
+    pub(crate) const MACHO_LEN: usize = 0x2000;
+
+    /// A little-endian 64-bit executable for `cpu_type` with a `__TEXT`
+    /// segment holding one section, a `__LINKEDIT` segment, a symbol table,
+    /// and a code signature ending the file.
+    pub(crate) fn macho_bytes(cpu_type: u32) -> Vec<u8> {
+        let mut data = vec![0u8; MACHO_LEN];
+        let put32 = |d: &mut Vec<u8>, at: usize, v: u32| {
+            d[at..at + 4].copy_from_slice(&v.to_le_bytes());
+        };
+        let put64 = |d: &mut Vec<u8>, at: usize, v: u64| {
+            d[at..at + 8].copy_from_slice(&v.to_le_bytes());
+        };
+        let text = 32;
+        let linkedit = text + 72 + 80;
+        let symtab = linkedit + 72;
+        let signature = symtab + 24;
+        let commands_len = signature + 16 - 32;
+        put32(&mut data, 0, 0xFEED_FACF);
+        put32(&mut data, 4, cpu_type);
+        put32(&mut data, 12, 2);
+        put32(&mut data, 16, 4);
+        put32(&mut data, 20, commands_len as u32);
+        // __TEXT: file 0..0x1000, one section.
+        put32(&mut data, text, 0x19);
+        put32(&mut data, text + 4, 72 + 80);
+        data[text + 8..text + 14].copy_from_slice(b"__TEXT");
+        put64(&mut data, text + 40, 0);
+        put64(&mut data, text + 48, 0x1000);
+        put32(&mut data, text + 64, 1);
+        data[text + 72..text + 78].copy_from_slice(b"__text");
+        // __LINKEDIT: file 0x1000..0x2000.
+        put32(&mut data, linkedit, 0x19);
+        put32(&mut data, linkedit + 4, 72);
+        data[linkedit + 8..linkedit + 18].copy_from_slice(b"__LINKEDIT");
+        put64(&mut data, linkedit + 40, 0x1000);
+        put64(&mut data, linkedit + 48, 0x1000);
+        // Symbols at 0x1000 (4 x 16 bytes), strings at 0x1040.
+        put32(&mut data, symtab, 0x2);
+        put32(&mut data, symtab + 4, 24);
+        put32(&mut data, symtab + 8, 0x1000);
+        put32(&mut data, symtab + 12, 4);
+        put32(&mut data, symtab + 16, 0x1040);
+        put32(&mut data, symtab + 20, 0x40);
+        // Code signature 0x1800..0x2000.
+        put32(&mut data, signature, 0x1D);
+        put32(&mut data, signature + 4, 16);
+        put32(&mut data, signature + 8, 0x1800);
+        put32(&mut data, signature + 12, 0x800);
+        data
+    }
+
+    /// A fat file with an x86_64 and an arm64 slice at 16 KiB alignment.
+    pub(crate) fn fat_bytes() -> Vec<u8> {
+        let mut data = vec![0u8; 0x4000];
+        data[..4].copy_from_slice(&0xCAFE_BABEu32.to_be_bytes());
+        data[4..8].copy_from_slice(&2u32.to_be_bytes());
+        for (i, cpu) in [0x0100_0007u32, 0x0100_000C].into_iter().enumerate() {
+            let entry = 8 + i * 20;
+            let offset = 0x4000 * (i as u32 + 1);
+            data[entry..entry + 4].copy_from_slice(&cpu.to_be_bytes());
+            data[entry + 8..entry + 12].copy_from_slice(&offset.to_be_bytes());
+            data[entry + 12..entry + 16].copy_from_slice(&(MACHO_LEN as u32).to_be_bytes());
+            data[entry + 16..entry + 20].copy_from_slice(&14u32.to_be_bytes());
+        }
+        data.extend_from_slice(&macho_bytes(0x0100_0007));
+        data.resize(0x8000, 0);
+        data.extend_from_slice(&macho_bytes(0x0100_000C));
+        data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::{MACHO_LEN, fat_bytes, macho_bytes};
+    use super::*;
+
+    fn read_from(data: &[u8]) -> impl FnMut(u64, &mut [u8]) -> usize + '_ {
+        |offset, buf| {
+            let start = (offset as usize).min(data.len());
+            let n = buf.len().min(data.len() - start);
+            buf[..n].copy_from_slice(&data[start..start + n]);
+            n
+        }
+    }
+
+    #[test]
+    fn sizes_thin_files_by_load_commands() {
+        let data = macho_bytes(0x0100_000C);
+        let file = parse(&mut read_from(&data)).expect("macho");
+        assert_eq!(file.size(), MACHO_LEN as u64);
+        let image = file.primary();
+        assert_eq!(image.kind(), "exe");
+        assert_eq!(image.section_count, 1);
+        assert!(image.signed);
+        assert_eq!(file.architecture(), "arm64");
+
+        // The code signature reaching past __LINKEDIT sets the end.
+        let mut data = macho_bytes(0x0100_000C);
+        let signature = 32 + 72 + 80 + 72 + 24;
+        data[signature + 12..signature + 16].copy_from_slice(&0x1000u32.to_le_bytes());
+        let file = parse(&mut read_from(&data)).expect("macho");
+        assert_eq!(file.size(), 0x2800);
+    }
+
+    #[test]
+    fn sizes_fat_files_by_their_slices() {
+        let data = fat_bytes();
+        let file = parse(&mut read_from(&data)).expect("fat");
+        assert_eq!(file.size(), data.len() as u64);
+        assert_eq!(file.architecture(), "x86_64,arm64");
+        let record = file.to_record("run", "macho/macho_0.macho");
+        assert_eq!(record.bits, 64);
+        assert!(record.signed);
+        assert_eq!(record.kind, "exe");
+    }
+
+    #[test]
+    fn rejects_java_classes_and_bad_commands() {
+        let mut class = 0xCAFE_BABEu32.to_be_bytes().to_vec();
+        class.extend_from_slice(&[0, 0, 0, 0x34]);
+        class.resize(64, 0);
+        assert_eq!(
+            parse(&mut read_from(&class)),
+            Err(MachOError::Invalid("fat architecture count out of range"))
+        );
+
+        let mut data = macho_bytes(0x0100_0007);
+        data[32 + 4..32 + 8].copy_from_slice(&7u32.to_le_bytes());
+        assert_eq!(
+            parse(&mut read_from(&data)),
+            Err(MachOError::Invalid("load command size inconsistent"))
+        );
+
+        let mut data = fat_bytes();
+        data[8..12].copy_from_slice(&7u32.to_be_bytes());
+        assert_eq!(
+            parse(&mut read_from(&data)),
+            Err(MachOError::Invalid(
+                "fat slice CPU type differs from its entry"
+            ))
+        );
+    }
+}
@@ -1,4 +1,39 @@
+pub mod apple;
+pub mod boltdb;
+pub mod bplist;
 pub mod browser;
+pub mod browser_cache;
+pub mod cfb;
+pub mod container;
+pub mod cookies;
+pub mod credentials;
+pub mod elf;
+pub mod ewf;
+pub mod executable;
+pub mod exif;
+pub mod ext4;
+pub mod fat;
+pub mod image;
+pub mod inflate;
+pub mod journal;
+pub mod lnk;
+pub mod logs;
+pub mod lznt1;
+pub mod macho;
+pub mod mp4;
+pub mod nal;
+pub mod ntfs;
+pub mod office_crypto;
+pub mod p2p;
+pub mod partitions;
+pub mod pcap;
+pub mod pe;
+pub mod prefetch;
+pub mod sqlite_apps;
 pub mod sqlite_db;
 pub mod sqlite_pages;
+pub mod system;
+pub mod thumbcache;
 pub mod time;
+pub mod vss;
+pub mod xpress;
@@ -0,0 +1,542 @@
+//! MP4 and QuickTime boxes for rebuilding the `moov` index of a recording.
+//!
+//! A recorder that stops before it finalizes a file leaves the `ftyp` box and
+//! the frames in `mdat`, but not the `moov` box that says where the frames
+//! are and how to decode them. The frames are found by walking the NAL units
+//! in `mdat` (`parsers::nal`); how to decode them comes from a
+//! [`VideoTemplate`], read from a donor file the same device recorded
+//! intact, or built from the parameter sets the recorder wrote in the media
+//! data. [`build_moov`] then writes an index with one video track.
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+use serde::Serialize;
+
+use crate::parsers::nal::{self, Sample, VideoCodec};
+
+/// `moov` boxes larger than this are not read from a donor.
+const MAX_MOOV_LEN: u64 = 64 * 1024 * 1024;
+/// Timescale of an index built from in-band parameter sets.
+const IN_BAND_TIMESCALE: u32 = 90_000;
+/// Fixed fields of a visual sample entry after its box header.
+const VISUAL_ENTRY_LEN: usize = 78;
+const UNITY_MATRIX: [u32; 9] = [0x0001_0000, 0, 0, 0, 0x0001_0000, 0, 0, 0, 0x4000_0000];
+
+/// A top-level box.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoxInfo {
+    pub kind: [u8; 4],
+    pub offset: u64,
+    pub header_len: u64,
+    /// Box size; `None` when the box runs to the end of the file (size 0, or
+    /// a 64-bit size still 0 because the recorder never wrote it).
+    pub size: Option<u64>,
+}
+
+impl BoxInfo {
+    pub fn payload_start(&self) -> u64 {
+        self.offset + self.header_len
+    }
+
+    /// End of the box, or `len` for a box running to the end of the file.
+    pub fn end(&self, len: u64) -> u64 {
+        self.size
+            .map_or(len, |size| self.offset.saturating_add(size))
+    }
+}
+
+/// Whether `kind` reads as a box type (printable ASCII).
+pub fn is_box_type(kind: &[u8]) -> bool {
+    kind.iter()
+        .all(|byte| byte.is_ascii_graphic() || *byte == b' ')
+}
+
+/// The box header at `offset`, if one is there.
+pub fn box_at(read: &mut dyn FnMut(u64, &mut [u8]) -> usize, offset: u64) -> Option<BoxInfo> {
+    let mut header = [0u8; 16];
+    if read(offset, &mut header[..8]) < 8 || !is_box_type(&header[4..8]) {
+        return None;
+    }
+    let kind = [header[4], header[5], header[6], header[7]];
+    let size32 = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+    let (size, header_len) = match size32 {
+        0 => (None, 8),
+        1 => {
+            if read(offset + 8, &mut header[8..16]) < 8 {
+                return None;
+            }
+            let size64 = u64::from_be_bytes(header[8..16].try_into().ok()?);
+            match size64 {
+                0 => (None, 16),
+                16.. => (Some(size64), 16),
+                _ => return None,
+            }
+        }
+        2..=7 => return None,
+        _ => (Some(u64::from(size32)), 8),
+    };
+    Some(BoxInfo {
+        kind,
+        offset,
+        header_len,
+        size,
+    })
+}
+
+/// The top-level boxes of a file of `len` bytes, up to the first one that
+/// does not check out or runs to the end.
+pub fn top_level_boxes(read: &mut dyn FnMut(u64, &mut [u8]) -> usize, len: u64) -> Vec<BoxInfo> {
+    let mut boxes = Vec::new();
+    let mut offset = 0;
+    while offset < len {
+        let Some(info) = box_at(read, offset) else {
+            break;
+        };
+        boxes.push(info);
+        match info.size {
+            Some(size) => offset = offset.saturating_add(size),
+            None => break,
+        }
+    }
+    boxes
+}
+
+/// The boxes inside a box payload held in memory.
+fn children(data: &[u8]) -> impl Iterator<Item = (&[u8], &[u8])> {
+    let mut offset = 0;
+    std::iter::from_fn(move || {
+        let header = data.get(offset..offset + 8)?;
+        let size = u32::from_be_bytes(header[..4].try_into().ok()?) as usize;
+        if size < 8 || offset + size > data.len() {
+            return None;
+        }
+        let child = (&header[4..8], &data[offset + 8..offset + size]);
+        offset += size;
+        Some(child)
+    })
+}
+
+fn child<'a>(data: &'a [u8], kind: &[u8; 4]) -> Option<&'a [u8]> {
+    children(data).find_map(|(found, payload)| (found == kind).then_some(payload))
+}
+
+fn path<'a>(data: &'a [u8], kinds: &[&[u8; 4]]) -> Option<&'a [u8]> {
+    kinds.iter().try_fold(data, |data, kind| child(data, kind))
+}
+
+fn be_u16(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn be_u32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+/// How to decode the frames of a video track.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VideoTemplate {
+    pub codec: VideoCodec,
+    /// The `stsd` sample entry (`avc1`, `hvc1`, ...) with its decoder
+    /// configuration.
+    pub sample_entry: Vec<u8>,
+    /// Bytes of the length before each NAL unit.
+    pub length_size: usize,
+    pub timescale: u32,
+    /// Duration of one frame in `timescale` units.
+    pub sample_delta: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl VideoTemplate {
+    /// Read the first H.264 or H.265 video track of an intact donor file.
+    pub fn from_donor(path: &Path) -> Result<Self> {
+        let mut file =
+            File::open(path).with_context(|| format!("open donor file {}", path.display()))?;
+        let len = file.metadata()?.len();
+        let mut read = |offset: u64, buf: &mut [u8]| -> usize {
+            if file.seek(SeekFrom::Start(offset)).is_err() {
+                return 0;
+            }
+            let mut filled = 0;
+            while filled < buf.len() {
+                match file.read(&mut buf[filled..]) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => filled += n,
+                }
+            }
+            filled
+        };
+        let Some(moov) = top_level_boxes(&mut read, len)
+            .into_iter()
+            .find(|info| &info.kind == b"moov")
+        else {
+            bail!("donor file {} has no moov box", path.display());
+        };
+        let moov_len = moov.end(len).saturating_sub(moov.payload_start());
+        if moov_len > MAX_MOOV_LEN {
+            bail!("moov box of donor file {} is too large", path.display());
+        }
+        let mut payload = vec![0u8; moov_len as usize];
+        if read(moov.payload_start(), &mut payload) < payload.len() {
+            bail!("donor file {} ends inside its moov box", path.display());
+        }
+        Self::from_moov(&payload)
+            .map_err(|reason| anyhow::anyhow!("donor file {}: {reason}", path.display()))
+    }
+
+    /// Read the first H.264 or H.265 video track of a `moov` payload.
+    pub fn from_moov(moov: &[u8]) -> std::result::Result<Self, &'static str> {
+        let mut found_video = false;
+        for (kind, trak) in children(moov) {
+            if kind != b"trak" {
+                continue;
+            }
+            let Some(mdia) = child(trak, b"mdia") else {
+                continue;
+            };
+            if child(mdia, b"hdlr").and_then(|hdlr| hdlr.get(8..12)) != Some(b"vide") {
+                continue;
+            }
+            found_video = true;
+            let mdhd = child(mdia, b"mdhd").ok_or("video track has no mdhd box")?;
+            let timescale = match mdhd.first() {
+                Some(1) => be_u32(mdhd, 20),
+                _ => be_u32(mdhd, 12),
+            }
+            .filter(|timescale| *timescale > 0)
+            .ok_or("video track has no timescale")?;
+            let stbl = path(mdia, &[b"minf", b"stbl"]).ok_or("video track has no stbl box")?;
+            let stsd = child(stbl, b"stsd").ok_or("video track has no stsd box")?;
+            let entry_len = be_u32(stsd, 8).ok_or("empty stsd box")? as usize;
+            let entry = stsd
+                .get(8..8 + entry_len)
+                .filter(|entry| entry.len() >= 8 + VISUAL_ENTRY_LEN)
+                .ok_or("stsd sample entry is cut short")?;
+            let (codec, config_kind, length_at) = match &entry[4..8] {
+                b"avc1" | b"avc3" => (VideoCodec::H264, b"avcC", 4),
+                b"hvc1" | b"hev1" => (VideoCodec::H265, b"hvcC", 21),
+                _ => continue,
+            };
+            let config = child(&entry[8 + VISUAL_ENTRY_LEN..], config_kind)
+                .ok_or("sample entry has no decoder configuration")?;
+            let length_size =
+                usize::from(config.get(length_at).ok_or("short decoder configuration")? & 3) + 1;
+            let sample_delta = child(stbl, b"stts")
+                .and_then(|stts| be_u32(stts, 12))
+                .filter(|delta| *delta > 0)
+                .unwrap_or((timescale / 30).max(1));
+            return Ok(Self {
+                codec,
+                sample_entry: entry.to_vec(),
+                length_size,
+                timescale,
+                sample_delta,
+                width: u32::from(be_u16(entry, 32).unwrap_or(0)),
+                height: u32::from(be_u16(entry, 34).unwrap_or(0)),
+            });
+        }
+        Err(if found_video {
+            "video track is not H.264 or H.265"
+        } else {
+            "no video track"
+        })
+    }
+
+    /// Build an H.264 template from the SPS and PPS a recorder wrote in the
+    /// media data, playing at `frame_rate`.
+    pub fn from_parameter_sets(sps: &[u8], pps: &[u8], frame_rate: f64) -> Option<Self> {
+        let info = nal::parse_h264_sps(sps)?;
+        let mut avcc = vec![1, sps[1], sps[2], sps[3], 0xFF, 0xE1];
+        avcc.extend_from_slice(&u16::try_from(sps.len()).ok()?.to_be_bytes());
+        avcc.extend_from_slice(sps);
+        avcc.push(1);
+        avcc.extend_from_slice(&u16::try_from(pps.len()).ok()?.to_be_bytes());
+        avcc.extend_from_slice(pps);
+
+        let mut entry = vec![0u8; 6];
+        entry.extend_from_slice(&1u16.to_be_bytes()); // data_reference_index
+        entry.extend_from_slice(&[0u8; 16]);
+        entry.extend_from_slice(&(info.width as u16).to_be_bytes());
+        entry.extend_from_slice(&(info.height as u16).to_be_bytes());
+        entry.extend_from_slice(&0x0048_0000u32.to_be_bytes()); // 72 dpi
+        entry.extend_from_slice(&0x0048_0000u32.to_be_bytes());
+        entry.extend_from_slice(&0u32.to_be_bytes());
+        entry.extend_from_slice(&1u16.to_be_bytes()); // frame_count
+        entry.extend_from_slice(&[0u8; 32]); // compressorname
+        entry.extend_from_slice(&0x0018u16.to_be_bytes()); // depth
+        entry.extend_from_slice(&(-1i16).to_be_bytes());
+        entry.extend_from_slice(&boxed(b"avcC", &avcc));
+        Some(Self {
+            codec: VideoCodec::H264,
+            sample_entry: boxed(b"avc1", &entry),
+            length_size: 4,
+            timescale: IN_BAND_TIMESCALE,
+            sample_delta: (f64::from(IN_BAND_TIMESCALE) / frame_rate).round().max(1.0) as u32,
+            width: info.width,
+            height: info.height,
+        })
+    }
+
+    pub fn duration(&self, frames: usize) -> u64 {
+        frames as u64 * u64::from(self.sample_delta)
+    }
+}
+
+fn boxed(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(payload.len() + 8);
+    out.extend_from_slice(&(payload.len() as u32 + 8).to_be_bytes());
+    out.extend_from_slice(kind);
+    out.extend_from_slice(payload);
+    out
+}
+
+fn full_box(kind: &[u8; 4], version: u8, flags: u32, payload: &[u8]) -> Vec<u8> {
+    let mut body = Vec::with_capacity(payload.len() + 4);
+    body.extend_from_slice(&((u32::from(version) << 24) | flags).to_be_bytes());
+    body.extend_from_slice(payload);
+    boxed(kind, &body)
+}
+
+/// Creation and modification times (left at zero: not known) and a duration,
+/// in the width the box version calls for.
+fn times(out: &mut Vec<u8>, wide: bool, middle: &[u8], duration: u64) {
+    if wide {
+        out.extend_from_slice(&[0u8; 16]);
+        out.extend_from_slice(middle);
+        out.extend_from_slice(&duration.to_be_bytes());
+    } else {
+        out.extend_from_slice(&[0u8; 8]);
+        out.extend_from_slice(middle);
+        out.extend_from_slice(&(duration as u32).to_be_bytes());
+    }
+}
+
+fn matrix(out: &mut Vec<u8>) {
+    for value in UNITY_MATRIX {
+        out.extend_from_slice(&value.to_be_bytes());
+    }
+}
+
+/// Write a `moov` box indexing `samples` (offsets in the repaired file) as
+/// one video track, one frame per chunk.
+pub fn build_moov(template: &VideoTemplate, samples: &[Sample]) -> Vec<u8> {
+    let duration = template.duration(samples.len());
+    let wide = duration > u64::from(u32::MAX);
+    let version = u8::from(wide);
+
+    let mut mvhd = Vec::new();
+    times(&mut mvhd, wide, &template.timescale.to_be_bytes(), duration);
+    mvhd.extend_from_slice(&0x0001_0000u32.to_be_bytes()); // rate 1.0
+    mvhd.extend_from_slice(&0x0100u16.to_be_bytes()); // volume 1.0
+    mvhd.extend_from_slice(&[0u8; 10]);
+    matrix(&mut mvhd);
+    mvhd.extend_from_slice(&[0u8; 24]);
+    mvhd.extend_from_slice(&2u32.to_be_bytes()); // next_track_ID
+
+    let mut tkhd_ids = 1u32.to_be_bytes().to_vec(); // track_ID
+    tkhd_ids.extend_from_slice(&[0u8; 4]);
+    let mut tkhd = Vec::new();
+    times(&mut tkhd, wide, &tkhd_ids, duration);
+    tkhd.extend_from_slice(&[0u8; 16]); // reserved, layer, group, volume
+    matrix(&mut tkhd);
+    tkhd.extend_from_slice(&(template.width << 16).to_be_bytes());
+    tkhd.extend_from_slice(&(template.height << 16).to_be_bytes());
+
+    let mut mdhd = Vec::new();
+    times(&mut mdhd, wide, &template.timescale.to_be_bytes(), duration);
+    mdhd.extend_from_slice(&0x55C4u16.to_be_bytes()); // language `und`
+    mdhd.extend_from_slice(&[0u8; 2]);
+
+    let mut hdlr = vec![0u8; 4];
+    hdlr.extend_from_slice(b"vide");
+    hdlr.extend_from_slice(&[0u8; 12]);
+    hdlr.extend_from_slice(b"VideoHandler\0");
+
+    let url = full_box(b"url ", 0, 1, &[]);
+    let mut dref = 1u32.to_be_bytes().to_vec();
+    dref.extend_from_slice(&url);
+
+    let mut stsd = 1u32.to_be_bytes().to_vec();
+    stsd.extend_from_slice(&template.sample_entry);
+    let mut stts = 1u32.to_be_bytes().to_vec();
+    stts.extend_from_slice(&(samples.len() as u32).to_be_bytes());
+    stts.extend_from_slice(&template.sample_delta.to_be_bytes());
+    let keyframes: Vec<u32> = (1..)
+        .zip(samples)
+        .filter(|(_, sample)| sample.keyframe)
+        .map(|(number, _)| number)
+        .collect();
+    let mut stsc = 1u32.to_be_bytes().to_vec();
+    for value in [1u32, 1, 1] {
+        stsc.extend_from_slice(&value.to_be_bytes());
+    }
+    let mut stsz = 0u32.to_be_bytes().to_vec();
+    stsz.extend_from_slice(&(samples.len() as u32).to_be_bytes());
+    let mut offsets = (samples.len() as u32).to_be_bytes().to_vec();
+    let large = samples
+        .last()
+        .is_some_and(|sample| sample.offset > u64::from(u32::MAX));
+    for sample in samples {
+        stsz.extend_from_slice(&sample.size.to_be_bytes());
+        if large {
+            offsets.extend_from_slice(&sample.offset.to_be_bytes());
+        } else {
+            offsets.extend_from_slice(&(sample.offset as u32).to_be_bytes());
+        }
+    }
+
+    let mut stbl = full_box(b"stsd", 0, 0, &stsd);
+    stbl.extend_from_slice(&full_box(b"stts", 0, 0, &stts));
+    // Without `stss` every frame is a sync frame.
+    if keyframes.len() < samples.len() {
+        let mut stss = (keyframes.len() as u32).to_be_bytes().to_vec();
+        for number in keyframes {
+            stss.extend_from_slice(&number.to_be_bytes());
+        }
+        stbl.extend_from_slice(&full_box(b"stss", 0, 0, &stss));
+    }
+    stbl.extend_from_slice(&full_box(b"stsc", 0, 0, &stsc));
+    stbl.extend_from_slice(&full_box(b"stsz", 0, 0, &stsz));
+    stbl.extend_from_slice(&full_box(
+        if large { b"co64" } else { b"stco" },
+        0,
+        0,
+        &offsets,
+    ));
+
+    let mut minf = full_box(b"vmhd", 0, 1, &[0u8; 8]);
+    minf.extend_from_slice(&boxed(b"dinf", &full_box(b"dref", 0, 0, &dref)));
+    minf.extend_from_slice(&boxed(b"stbl", &stbl));
+
+    let mut mdia = full_box(b"mdhd", version, 0, &mdhd);
+    mdia.extend_from_slice(&full_box(b"hdlr", 0, 0, &hdlr));
+    mdia.extend_from_slice(&boxed(b"minf", &minf));
+
+    let mut trak = full_box(b"tkhd", version, 3, &tkhd);
+    trak.extend_from_slice(&boxed(b"mdia", &mdia));
+
+    let mut moov = full_box(b"mvhd", version, 0, &mvhd);
+    moov.extend_from_slice(&boxed(b"trak", &trak));
+    boxed(b"moov", &moov)
+}
+
+/// A carved recording whose `moov` index was rebuilt by the `video_repair`
+/// post-processor.
+#[derive(Debug, Clone, Serialize)]
+pub struct VideoRepairRecord {
+    pub run_id: String,
+    /// `donor` when the decoder setup came from a donor file, `in_band` when
+    /// it was built from parameter sets in the media data.
+    pub method: String,
+    /// `h264` or `h265`.
+    pub codec: String,
+    pub width: u32,
+    pub height: u32,
+    pub frame_count: u64,
+    pub keyframe_count: u64,
+    /// Frames times the frame duration of the donor or of `frame_rate`.
+    pub duration_secs: f64,
+    /// Bytes of the frames indexed.
+    pub media_bytes: u64,
+    /// Bytes of `mdat` between frames that no frame covers (other tracks,
+    /// damage).
+    pub skipped_bytes: u64,
+    pub donor_file: Option<String>,
+    /// Carved path of the repaired file.
+    pub repaired_path: String,
+    /// Carved path of the recording.
+    pub source_file: String,
+}
+
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::boxed;
+
+    /// `ftyp` box with major brand `brand`.
+    pub fn ftyp(brand: &[u8; 4]) -> Vec<u8> {
+        let mut payload = brand.to_vec();
+        payload.extend_from_slice(&0u32.to_be_bytes());
+        payload.extend_from_slice(brand);
+        payload.extend_from_slice(b"isom");
+        boxed(b"ftyp", &payload)
+    }
+
+    /// A recording cut off before its `moov` box: `ftyp`, a `free` box, and
+    /// an `mdat` of size 0 holding `media`.
+    pub fn unfinished(brand: &[u8; 4], media: &[u8]) -> Vec<u8> {
+        let mut out = ftyp(brand);
+        out.extend_from_slice(&boxed(b"free", &[0u8; 8]));
+        out.extend_from_slice(&0u32.to_be_bytes());
+        out.extend_from_slice(b"mdat");
+        out.extend_from_slice(media);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::ftyp;
+    use super::*;
+    use crate::parsers::nal::testing::{PPS, SPS_720P};
+
+    fn reader(data: &[u8]) -> impl FnMut(u64, &mut [u8]) -> usize + '_ {
+        move |offset, buf| {
+            let start = (offset as usize).min(data.len());
+            let n = buf.len().min(data.len() - start);
+            buf[..n].copy_from_slice(&data[start..start + n]);
+            n
+        }
+    }
+
+    fn samples() -> Vec<Sample> {
+        (0..10)
+            .map(|i| Sample {
+                offset: 1000 + i * 500,
+                size: 400,
+                keyframe: i % 5 == 0,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn rebuilt_moov_serves_as_donor() {
+        let template = VideoTemplate::from_parameter_sets(SPS_720P, PPS, 25.0).expect("template");
+        assert_eq!((template.width, template.height), (1280, 720));
+        assert_eq!(template.sample_delta, 3600);
+
+        let moov = build_moov(&template, &samples());
+        let payload = &moov[8..];
+        assert_eq!(VideoTemplate::from_moov(payload), Ok(template));
+        let stbl = path(payload, &[b"trak", b"mdia", b"minf", b"stbl"]).expect("stbl");
+        let stss = child(stbl, b"stss").expect("stss");
+        assert_eq!(be_u32(stss, 4), Some(2));
+        assert_eq!(be_u32(stss, 12), Some(6));
+        let stco = child(stbl, b"stco").expect("stco");
+        assert_eq!(be_u32(stco, 4), Some(10));
+        assert_eq!(be_u32(stco, 8), Some(1000));
+    }
+
+    #[test]
+    fn walks_top_level_boxes_to_an_open_mdat() {
+        let mut file = ftyp(b"isom");
+        file.extend_from_slice(&1u32.to_be_bytes());
+        file.extend_from_slice(b"mdat");
+        file.extend_from_slice(&0u64.to_be_bytes());
+        file.extend_from_slice(&[0xAB; 64]);
+        let mut read = reader(&file);
+        let boxes = top_level_boxes(&mut read, file.len() as u64);
+        assert_eq!(boxes.len(), 2);
+        assert_eq!(&boxes[1].kind, b"mdat");
+        assert_eq!(boxes[1].size, None);
+        assert_eq!(boxes[1].payload_start(), boxes[1].offset + 16);
+
+        assert_eq!(
+            VideoTemplate::from_moov(&boxed(b"trak", &[])),
+            Err("no video track")
+        );
+    }
+}
@@ -0,0 +1,644 @@
+//! Length-prefixed H.264 and H.265 NAL units in an MP4 or QuickTime `mdat`.
+//!
+//! Recorders write each frame (access unit) as a run of NAL units, each
+//! behind a big-endian length. Without the `moov` index the frames can still
+//! be found by walking that chain: a frame starts at a parameter set, access
+//! unit delimiter, or SEI unit following a slice, or at a slice that begins a
+//! new picture. Bytes that do not continue the chain (interleaved audio,
+//! GPS, or other tracks) are skipped by searching for the next place where
+//! two units in a row check out; when none turns up within
+//! [`RESYNC_WINDOW`] the media data is taken to have ended.
+
+/// Bytes searched for the next NAL unit after a break in the chain.
+pub const RESYNC_WINDOW: u64 = 1024 * 1024;
+/// Units longer than this are taken as garbage (4K intra frames stay below).
+const MAX_NAL_LEN: u64 = 32 * 1024 * 1024;
+/// Parameter sets longer than this are not kept.
+const MAX_PARAMETER_SET_LEN: u64 = 1024;
+/// Units walked with each codec to tell them apart.
+const PROBE_UNITS: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VideoCodec {
+    H264,
+    H265,
+}
+
+impl VideoCodec {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::H264 => "h264",
+            Self::H265 => "h265",
+        }
+    }
+
+    fn header_len(self) -> usize {
+        match self {
+            Self::H264 => 1,
+            Self::H265 => 2,
+        }
+    }
+
+    /// Unit type, or `None` when the header cannot start a unit of this codec.
+    fn unit_type(self, header: &[u8]) -> Option<u8> {
+        if header[0] & 0x80 != 0 {
+            return None;
+        }
+        match self {
+            Self::H264 => {
+                let ref_idc = (header[0] >> 5) & 3;
+                let unit = header[0] & 0x1F;
+                match unit {
+                    1 => Some(unit),
+                    5 | 7 | 8 if ref_idc != 0 => Some(unit),
+                    6 | 9 if ref_idc == 0 => Some(unit),
+                    _ => None,
+                }
+            }
+            Self::H265 => {
+                let unit = (header[0] >> 1) & 0x3F;
+                let layer = ((header[0] & 1) << 5) | (header[1] >> 3);
+                let temporal_id_plus1 = header[1] & 7;
+                if layer != 0 || temporal_id_plus1 == 0 {
+                    return None;
+                }
+                match unit {
+                    0..=9 | 16..=21 | 32..=40 => Some(unit),
+                    _ => None,
+                }
+            }
+        }
+    }
+
+    fn is_slice(self, unit: u8) -> bool {
+        match self {
+            Self::H264 => unit == 1 || unit == 5,
+            Self::H265 => unit < 32,
+        }
+    }
+
+    fn is_keyframe(self, unit: u8) -> bool {
+        match self {
+            Self::H264 => unit == 5,
+            Self::H265 => (16..=21).contains(&unit),
+        }
+    }
+
+    /// Units that can only come before the slices of a picture.
+    fn starts_access_unit(self, unit: u8) -> bool {
+        match self {
+            Self::H264 => matches!(unit, 6..=9),
+            Self::H265 => matches!(unit, 32..=35 | 39),
+        }
+    }
+
+    /// Whether a slice is the first of its picture: `first_mb_in_slice` is 0
+    /// (a lone `1` bit) in H.264, `first_slice_segment_in_pic_flag` in H.265.
+    fn first_slice(self, after_header: u8) -> bool {
+        after_header & 0x80 != 0
+    }
+}
+
+/// One frame of the media data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sample {
+    pub offset: u64,
+    pub size: u32,
+    pub keyframe: bool,
+}
+
+/// Frames found in a run of media data.
+#[derive(Debug, Default)]
+pub struct MediaScan {
+    pub samples: Vec<Sample>,
+    /// First sequence and picture parameter sets found (H.264 only).
+    pub sps: Option<Vec<u8>>,
+    pub pps: Option<Vec<u8>>,
+    /// End of the last unit that continued the chain.
+    pub end: u64,
+    /// Bytes between `start` and `end` that belong to no frame.
+    pub skipped: u64,
+}
+
+impl MediaScan {
+    pub fn keyframes(&self) -> usize {
+        self.samples.iter().filter(|sample| sample.keyframe).count()
+    }
+
+    pub fn media_bytes(&self) -> u64 {
+        self.samples
+            .iter()
+            .map(|sample| u64::from(sample.size))
+            .sum()
+    }
+}
+
+/// A unit whose header checked out.
+struct Unit {
+    offset: u64,
+    len: u64,
+    kind: u8,
+    first_slice: bool,
+}
+
+struct Walker<'a> {
+    read: &'a mut dyn FnMut(u64, &mut [u8]) -> usize,
+    codec: VideoCodec,
+    length_size: usize,
+    end: u64,
+}
+
+impl Walker<'_> {
+    fn prefix_len(&self) -> u64 {
+        (self.length_size + self.codec.header_len() + 1) as u64
+    }
+
+    /// The unit whose length prefix starts at `offset`, if it checks out.
+    fn unit_at(&mut self, offset: u64) -> Option<Unit> {
+        let mut buf = [0u8; 7];
+        let want = self.prefix_len() as usize;
+        if offset.saturating_add(want as u64) > self.end
+            || (self.read)(offset, &mut buf[..want]) < want
+        {
+            return None;
+        }
+        self.parse(offset, &buf[..want])
+    }
+
+    fn parse(&self, offset: u64, prefix: &[u8]) -> Option<Unit> {
+        let length_size = self.length_size;
+        let len = prefix[..length_size]
+            .iter()
+            .fold(0u64, |acc, byte| (acc << 8) | u64::from(*byte));
+        let header_len = self.codec.header_len();
+        let available = self.end.saturating_sub(offset + length_size as u64);
+        if len <= header_len as u64 || len > MAX_NAL_LEN || len > available {
+            return None;
+        }
+        let header = &prefix[length_size..length_size + header_len];
+        let kind = self.codec.unit_type(header)?;
+        Some(Unit {
+            offset,
+            len: length_size as u64 + len,
+            kind,
+            first_slice: self.codec.first_slice(prefix[length_size + header_len]),
+        })
+    }
+
+    /// Whether the unit at `offset` checks out and is followed by another one
+    /// or by the end of the data.
+    fn confirmed(&mut self, offset: u64) -> Option<Unit> {
+        let unit = self.unit_at(offset)?;
+        let next = unit.offset + unit.len;
+        if next + self.prefix_len() > self.end || self.unit_at(next).is_some() {
+            Some(unit)
+        } else {
+            None
+        }
+    }
+
+    /// Search the window after `from` for the next confirmed unit.
+    fn resync(&mut self, from: u64) -> Option<Unit> {
+        let limit = from.saturating_add(RESYNC_WINDOW).min(self.end);
+        let prefix_len = self.prefix_len() as usize;
+        let mut block = vec![0u8; 64 * 1024];
+        let mut base = from;
+        while base < limit {
+            let want = ((limit - base) as usize + prefix_len - 1).min(block.len());
+            let n = (self.read)(base, &mut block[..want]);
+            if n < prefix_len {
+                return None;
+            }
+            for at in 0..=n - prefix_len {
+                let offset = base + at as u64;
+                if offset >= limit {
+                    return None;
+                }
+                if self.parse(offset, &block[at..at + prefix_len]).is_some()
+                    && let Some(unit) = self.confirmed(offset)
+                {
+                    return Some(unit);
+                }
+            }
+            base += (n - prefix_len + 1) as u64;
+        }
+        None
+    }
+
+    /// Read the payload of a parameter set unit.
+    fn payload(&mut self, unit: &Unit) -> Option<Vec<u8>> {
+        let len = unit.len - self.length_size as u64;
+        if len > MAX_PARAMETER_SET_LEN {
+            return None;
+        }
+        let mut buf = vec![0u8; len as usize];
+        let n = (self.read)(unit.offset + self.length_size as u64, &mut buf);
+        (n == buf.len()).then_some(buf)
+    }
+}
+
+/// Frame being assembled from units.
+#[derive(Default)]
+struct Pending {
+    start: u64,
+    end: u64,
+    has_slice: bool,
+    keyframe: bool,
+}
+
+/// Walk the media data in `[start, end)` and split it into frames.
+pub fn scan(
+    read: &mut dyn FnMut(u64, &mut [u8]) -> usize,
+    start: u64,
+    end: u64,
+    codec: VideoCodec,
+    length_size: usize,
+) -> MediaScan {
+    let mut walker = Walker {
+        read,
+        codec,
+        length_size,
+        end,
+    };
+    let mut scan = MediaScan {
+        end: start,
+        ..MediaScan::default()
+    };
+    let mut pending: Option<Pending> = None;
+    let mut offset = start;
+    loop {
+        let unit = match walker.unit_at(offset) {
+            Some(unit) => unit,
+            None => {
+                close(&mut scan, pending.take());
+                match walker.resync(offset.saturating_add(1)) {
+                    Some(unit) => unit,
+                    None => break,
+                }
+            }
+        };
+        if unit.offset != offset {
+            close(&mut scan, pending.take());
+        }
+        let slice = codec.is_slice(unit.kind);
+        let starts = if slice {
+            unit.first_slice
+        } else {
+            codec.starts_access_unit(unit.kind)
+        };
+        if starts && pending.as_ref().is_some_and(|frame| frame.has_slice) {
+            close(&mut scan, pending.take());
+        }
+        let frame = pending.get_or_insert(Pending {
+            start: unit.offset,
+            ..Pending::default()
+        });
+        frame.end = unit.offset + unit.len;
+        frame.has_slice |= slice;
+        frame.keyframe |= codec.is_keyframe(unit.kind);
+        if codec == VideoCodec::H264 {
+            if unit.kind == 7 && scan.sps.is_none() {
+                scan.sps = walker.payload(&unit);
+            } else if unit.kind == 8 && scan.pps.is_none() {
+                scan.pps = walker.payload(&unit);
+            }
+        }
+        offset = unit.offset + unit.len;
+    }
+    close(&mut scan, pending);
+    if scan.samples.is_empty() {
+        scan.end = start;
+    }
+    let covered = scan.media_bytes();
+    scan.skipped = scan.end.saturating_sub(start).saturating_sub(covered);
+    scan
+}
+
+fn close(scan: &mut MediaScan, frame: Option<Pending>) {
+    let Some(frame) = frame else {
+        return;
+    };
+    if frame.has_slice && frame.end - frame.start <= u64::from(u32::MAX) {
+        scan.samples.push(Sample {
+            offset: frame.start,
+            size: (frame.end - frame.start) as u32,
+            keyframe: frame.keyframe,
+        });
+        scan.end = frame.end;
+    }
+}
+
+/// The codec whose units continue the chain at `start` the longest, with
+/// 4-byte lengths as recorders write them.
+pub fn detect_codec(
+    read: &mut dyn FnMut(u64, &mut [u8]) -> usize,
+    start: u64,
+    end: u64,
+) -> Option<VideoCodec> {
+    let mut best = None;
+    let mut best_units = 0;
+    for codec in [VideoCodec::H264, VideoCodec::H265] {
+        let mut walker = Walker {
+            read: &mut *read,
+            codec,
+            length_size: 4,
+            end,
+        };
+        let mut offset = start;
+        let mut units = 0;
+        while units < PROBE_UNITS {
+            let Some(unit) = walker.unit_at(offset) else {
+                break;
+            };
+            offset = unit.offset + unit.len;
+            units += 1;
+        }
+        // Two units in a row, or one filling the data, count as a match.
+        let fills = units == 1 && offset + walker.prefix_len() > end;
+        if units > best_units && (units >= 2 || fills) {
+            best = Some(codec);
+            best_units = units;
+        }
+    }
+    best
+}
+
+/// Where H.264 or H.265 media data starting at `start` ends, or `None` when
+/// it holds no frame.
+pub fn media_end(
+    read: &mut dyn FnMut(u64, &mut [u8]) -> usize,
+    start: u64,
+    end: u64,
+) -> Option<u64> {
+    let codec = detect_codec(read, start, end)?;
+    let scan = scan(read, start, end, codec, 4);
+    (!scan.samples.is_empty()).then_some(scan.end)
+}
+
+/// Picture size of an H.264 sequence parameter set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpsInfo {
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Read the picture size from an H.264 SPS unit (header byte included).
+pub fn parse_h264_sps(unit: &[u8]) -> Option<SpsInfo> {
+    let rbsp = unescape(unit.get(1..)?);
+    let mut bits = BitReader::new(&rbsp);
+    let profile = bits.bits(8)?;
+    bits.bits(16)?; // constraint flags and level
+    bits.ue()?; // seq_parameter_set_id
+    let mut chroma_format = 1;
+    if matches!(
+        profile,
+        100 | 110 | 122 | 244 | 44 | 83 | 86 | 118 | 128 | 138 | 139 | 134 | 135
+    ) {
+        chroma_format = bits.ue()?;
+        if chroma_format == 3 {
+            bits.bits(1)?; // separate_colour_plane_flag
+        }
+        bits.ue()?; // bit_depth_luma_minus8
+        bits.ue()?; // bit_depth_chroma_minus8
+        bits.bits(1)?; // qpprime_y_zero_transform_bypass_flag
+        if bits.bits(1)? == 1 {
+            let lists = if chroma_format == 3 { 12 } else { 8 };
+            for list in 0..lists {
+                if bits.bits(1)? == 1 {
+                    skip_scaling_list(&mut bits, if list < 6 { 16 } else { 64 })?;
+                }
+            }
+        }
+    }
+    bits.ue()?; // log2_max_frame_num_minus4
+    match bits.ue()? {
+        0 => {
+            bits.ue()?;
+        }
+        1 => {
+            bits.bits(1)?;
+            bits.se()?;
+            bits.se()?;
+            for _ in 0..bits.ue()?.min(255) {
+                bits.se()?;
+            }
+        }
+        _ => {}
+    }
+    bits.ue()?; // max_num_ref_frames
+    bits.bits(1)?; // gaps_in_frame_num_value_allowed_flag
+    let width_mbs = bits.ue()? + 1;
+    let height_units = bits.ue()? + 1;
+    let frame_mbs_only = bits.bits(1)?;
+    if frame_mbs_only == 0 {
+        bits.bits(1)?; // mb_adaptive_frame_field_flag
+    }
+    bits.bits(1)?; // direct_8x8_inference_flag
+    let field_factor = 2 - frame_mbs_only;
+    let mut width = width_mbs * 16;
+    let mut height = field_factor * height_units * 16;
+    if bits.bits(1)? == 1 {
+        let (crop_x, crop_y) = match chroma_format {
+            1 => (2, 2 * field_factor),
+            2 => (2, field_factor),
+            _ => (1, field_factor),
+        };
+        let (left, right, top, bottom) = (bits.ue()?, bits.ue()?, bits.ue()?, bits.ue()?);
+        width = width.checked_sub(crop_x * (left + right))?;
+        height = height.checked_sub(crop_y * (top + bottom))?;
+    }
+    (width > 0 && height > 0 && width <= 16384 && height <= 16384)
+        .then_some(SpsInfo { width, height })
+}
+
+fn skip_scaling_list(bits: &mut BitReader, size: usize) -> Option<()> {
+    let mut last = 8i64;
+    let mut next = 8i64;
+    for _ in 0..size {
+        if next != 0 {
+            next = (last + bits.se()? + 256) % 256;
+        }
+        if next != 0 {
+            last = next;
+        }
+    }
+    Some(())
+}
+
+/// Drop the emulation prevention bytes (`00 00 03`) from a unit payload.
+fn unescape(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut zeros = 0;
+    for &byte in data {
+        if zeros >= 2 && byte == 3 {
+            zeros = 0;
+            continue;
+        }
+        zeros = if byte == 0 { zeros + 1 } else { 0 };
+        out.push(byte);
+    }
+    out
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn bits(&mut self, count: u32) -> Option<u32> {
+        let mut value = 0u32;
+        for _ in 0..count {
+            let byte = *self.data.get(self.pos / 8)?;
+            let bit = (byte >> (7 - self.pos % 8)) & 1;
+            value = (value << 1) | u32::from(bit);
+            self.pos += 1;
+        }
+        Some(value)
+    }
+
+    /// Unsigned Exp-Golomb code.
+    fn ue(&mut self) -> Option<u32> {
+        let mut zeros = 0;
+        while self.bits(1)? == 0 {
+            zeros += 1;
+            if zeros > 31 {
+                return None;
+            }
+        }
+        Some((1u32 << zeros) - 1 + self.bits(zeros)?)
+    }
+
+    /// Signed Exp-Golomb code.
+    fn se(&mut self) -> Option<i64> {
+        let code = i64::from(self.ue()?);
+        Some(if code % 2 == 1 {
+            (code + 1) / 2
+        } else {
+            -(code / 2)
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod testing {
+    /// SPS of a 1280x720 High profile stream (cropped from 1280x736).
+    pub const SPS_720P: &[u8] = &[
+        0x67, 0x64, 0x00, 0x1F, 0xAC, 0xD9, 0x40, 0x50, 0x05, 0xBB, 0x01, 0x10, 0x00, 0x00, 0x03,
+        0x00, 0x10, 0x00, 0x00, 0x03, 0x03, 0xC0, 0xF1, 0x83, 0x19, 0x60,
+    ];
+    pub const PPS: &[u8] = &[0x68, 0xEB, 0xE3, 0xCB, 0x22, 0xC0];
+
+    /// Append one length-prefixed unit.
+    pub fn unit(out: &mut Vec<u8>, payload: &[u8]) {
+        out.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        out.extend_from_slice(payload);
+    }
+
+    /// H.264 media data: `frames` frames, a keyframe with in-band parameter
+    /// sets every `gop` frames, and an audio-like chunk after every
+    /// `audio_every` frames (0 for none).
+    pub fn h264_media(frames: usize, gop: usize, audio_every: usize) -> Vec<u8> {
+        let mut out = Vec::new();
+        for frame in 0..frames {
+            if frame % gop == 0 {
+                unit(&mut out, SPS_720P);
+                unit(&mut out, PPS);
+                let mut idr = vec![0x65, 0x88];
+                idr.extend((0..400).map(|i| (i * 7 + frame) as u8 | 1));
+                unit(&mut out, &idr);
+            } else {
+                let mut slice = vec![0x41, 0x9A];
+                slice.extend((0..120).map(|i| (i * 3 + frame) as u8 | 1));
+                unit(&mut out, &slice);
+            }
+            if audio_every > 0 && (frame + 1) % audio_every == 0 {
+                // AAC frames start with 0x21 or 0xDE; neither continues the chain.
+                out.extend((0..300).map(|i| if i % 2 == 0 { 0xDE } else { 0xFF }));
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::{PPS, SPS_720P, h264_media, unit};
+    use super::*;
+
+    fn reader(data: &[u8]) -> impl FnMut(u64, &mut [u8]) -> usize + '_ {
+        move |offset, buf| {
+            let start = (offset as usize).min(data.len());
+            let n = buf.len().min(data.len() - start);
+            buf[..n].copy_from_slice(&data[start..start + n]);
+            n
+        }
+    }
+
+    #[test]
+    fn splits_frames_and_skips_interleaved_audio() {
+        let media = h264_media(20, 5, 3);
+        let mut read = reader(&media);
+        let end = media.len() as u64;
+        assert_eq!(detect_codec(&mut read, 0, end), Some(VideoCodec::H264));
+        let scan = scan(&mut read, 0, end, VideoCodec::H264, 4);
+        assert_eq!(scan.samples.len(), 20);
+        assert_eq!(scan.keyframes(), 4);
+        assert!(scan.samples[0].keyframe && !scan.samples[1].keyframe);
+        // The keyframe carries its parameter sets.
+        assert_eq!(scan.samples[0].offset, 0);
+        assert_eq!(
+            scan.samples[0].size as usize,
+            3 * 4 + SPS_720P.len() + PPS.len() + 402
+        );
+        assert_eq!(scan.sps.as_deref(), Some(SPS_720P));
+        assert_eq!(scan.pps.as_deref(), Some(PPS));
+        assert_eq!(scan.skipped, 6 * 300);
+        assert_eq!(scan.end, end);
+    }
+
+    #[test]
+    fn media_ends_where_the_chain_stops() {
+        let mut data = h264_media(6, 3, 0);
+        let media_len = data.len() as u64;
+        data.extend(std::iter::repeat_n(0u8, 4096));
+        data.extend((0..4096u32).map(|i| (i * 31 % 251) as u8));
+        let end = data.len() as u64;
+        let mut read = reader(&data);
+        assert_eq!(media_end(&mut read, 0, end), Some(media_len));
+        assert_eq!(media_end(&mut read, media_len, end), None);
+    }
+
+    #[test]
+    fn tells_hevc_from_h264() {
+        let mut media = Vec::new();
+        unit(&mut media, &[0x40, 0x01, 0x0C, 0x01, 0xFF]); // VPS
+        unit(&mut media, &[0x42, 0x01, 0x01, 0x01, 0x60]); // SPS
+        unit(&mut media, &[0x44, 0x01, 0xC1, 0x72]); // PPS
+        unit(&mut media, &[0x26, 0x01, 0xAF, 0x11, 0x22]); // IDR_W_RADL
+        unit(&mut media, &[0x02, 0x01, 0xD0, 0x33]); // TRAIL_R
+        let mut read = reader(&media);
+        let end = media.len() as u64;
+        assert_eq!(detect_codec(&mut read, 0, end), Some(VideoCodec::H265));
+        let scan = scan(&mut read, 0, end, VideoCodec::H265, 4);
+        assert_eq!(scan.samples.len(), 2);
+        assert!(scan.samples[0].keyframe);
+        assert_eq!(scan.end, end);
+    }
+
+    #[test]
+    fn reads_picture_size_from_sps() {
+        assert_eq!(
+            parse_h264_sps(SPS_720P),
+            Some(SpsInfo {
+                width: 1280,
+                height: 720
+            })
+        );
+        assert_eq!(parse_h264_sps(&SPS_720P[..4]), None);
+    }
+}
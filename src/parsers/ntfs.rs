@@ -0,0 +1,836 @@
+//! NTFS master file table and cluster bitmap.
+//!
+//! The boot sector gives the cluster size and the cluster of `$MFT`, whose
+//! record 0 describes the MFT itself: its unnamed `$DATA` run list locates
+//! the remaining file records. Each 1 KiB record (fixed up with its update
+//! sequence array) holds `$STANDARD_INFORMATION` timestamps, one or more
+//! `$FILE_NAME` attributes with the parent directory, and the `$DATA` run
+//! list mapping the file's clusters. Records not marked in use are deleted
+//! files whose clusters may since have been reused.
+//!
+//! [`read_volume`] reads every record of one volume and resolves their paths;
+//! record 6 (`$Bitmap`) gives the clusters in use, returned as evidence
+//! extents. Attribute lists spilling into further records are followed only
+//! as far as the extension records name their base record.
+
+use std::collections::HashMap;
+
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::evidence::{EvidenceError, EvidenceSource};
+use crate::parsers::time::webkit_timestamp_to_datetime;
+
+/// MFT record of the root directory.
+pub const ROOT_RECORD: u64 = 5;
+/// MFT record of the volume cluster bitmap.
+const BITMAP_RECORD: u64 = 6;
+
+const ATTR_STANDARD_INFORMATION: u32 = 0x10;
+const ATTR_FILE_NAME: u32 = 0x30;
+const ATTR_DATA: u32 = 0x80;
+const ATTR_END: u32 = 0xFFFF_FFFF;
+
+const FLAG_IN_USE: u16 = 0x1;
+const FLAG_DIRECTORY: u16 = 0x2;
+
+/// `$FILE_NAME` namespace holding only the 8.3 short name.
+const NAMESPACE_DOS: u8 = 2;
+/// Upper bound on records read from one MFT.
+const MAX_RECORDS: u64 = 1 << 26;
+/// Bytes of MFT read at a time.
+const MFT_READ_SIZE: u64 = 1 << 20;
+/// Upper bound on path components, against parent loops.
+const MAX_DEPTH: usize = 256;
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum NtfsError {
+    #[error("evidence read failed: {0}")]
+    Evidence(#[from] EvidenceError),
+    #[error("malformed NTFS {what} at evidence offset {offset}")]
+    Malformed { what: &'static str, offset: u64 },
+}
+
+/// Geometry from an NTFS boot sector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct NtfsBoot {
+    /// Evidence offset of the boot sector.
+    pub offset: u64,
+    pub bytes_per_sector: u64,
+    pub cluster_size: u64,
+    pub total_clusters: u64,
+    pub mft_cluster: u64,
+    pub record_size: u64,
+}
+
+impl NtfsBoot {
+    /// Evidence offset of cluster `lcn`.
+    pub fn cluster_offset(&self, lcn: u64) -> u64 {
+        self.offset + lcn * self.cluster_size
+    }
+}
+
+/// Consecutive clusters of a file; `lcn` is `None` for a sparse run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DataRun {
+    pub lcn: Option<u64>,
+    pub clusters: u64,
+}
+
+/// One file record of the MFT.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MftEntry {
+    pub record: u64,
+    pub sequence: u16,
+    /// Cleared when the file was deleted.
+    pub in_use: bool,
+    pub directory: bool,
+    /// Parent directory record from the preferred `$FILE_NAME`.
+    pub parent: Option<u64>,
+    pub name: Option<String>,
+    /// Size of the unnamed `$DATA` stream.
+    pub size: u64,
+    /// The data is stored in the record itself, not in clusters.
+    pub resident: bool,
+    pub runs: Vec<DataRun>,
+    pub created: Option<NaiveDateTime>,
+    pub modified: Option<NaiveDateTime>,
+    pub mft_modified: Option<NaiveDateTime>,
+    pub accessed: Option<NaiveDateTime>,
+}
+
+impl MftEntry {
+    /// Evidence offset of the first allocated cluster of the data.
+    pub fn first_offset(&self, boot: &NtfsBoot) -> Option<u64> {
+        self.runs
+            .first()
+            .and_then(|run| run.lcn)
+            .map(|lcn| boot.cluster_offset(lcn))
+    }
+}
+
+/// One MFT record in `ntfs_files`, with the carve starting at its data.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct NtfsFileRecord {
+    pub run_id: String,
+    /// Evidence offset of the volume's boot sector.
+    pub volume_offset: u64,
+    pub mft_record: u64,
+    pub sequence: u16,
+    pub path: String,
+    pub name: String,
+    /// `false` for deleted files.
+    pub in_use: bool,
+    pub directory: bool,
+    pub size: u64,
+    pub resident: bool,
+    /// Evidence `offset+length` of each cluster run, `;`-separated;
+    /// sparse runs are `sparse+length`.
+    pub data_runs: String,
+    pub created: Option<NaiveDateTime>,
+    pub modified: Option<NaiveDateTime>,
+    pub mft_modified: Option<NaiveDateTime>,
+    pub accessed: Option<NaiveDateTime>,
+    /// Carved file starting at the first cluster of the data, if any.
+    pub carved_path: Option<String>,
+}
+
+/// The file records and in-use clusters of one volume.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NtfsVolumeFiles {
+    pub boot: NtfsBoot,
+    /// Records with a file name, by record number.
+    pub entries: Vec<MftEntry>,
+    /// Full path of each entry, `\`-separated from the root; deleted
+    /// parents keep their name, unknown ones become `$Orphan`.
+    pub paths: Vec<String>,
+    /// In-use clusters as sorted evidence ranges `[start, end)`; empty when
+    /// `$Bitmap` could not be read.
+    pub allocated: Vec<(u64, u64)>,
+}
+
+impl NtfsVolumeFiles {
+    /// `ntfs_files` records of the entries, without carved paths.
+    pub fn records(&self, run_id: &str) -> Vec<NtfsFileRecord> {
+        let boot = &self.boot;
+        self.entries
+            .iter()
+            .zip(&self.paths)
+            .map(|(entry, path)| NtfsFileRecord {
+                run_id: run_id.to_string(),
+                volume_offset: boot.offset,
+                mft_record: entry.record,
+                sequence: entry.sequence,
+                path: path.clone(),
+                name: entry.name.clone().unwrap_or_default(),
+                in_use: entry.in_use,
+                directory: entry.directory,
+                size: entry.size,
+                resident: entry.resident,
+                data_runs: entry
+                    .runs
+                    .iter()
+                    .map(|run| {
+                        let len = run.clusters * boot.cluster_size;
+                        match run.lcn {
+                            Some(lcn) => format!("{}+{len}", boot.cluster_offset(lcn)),
+                            None => format!("sparse+{len}"),
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join(";"),
+                created: entry.created,
+                modified: entry.modified,
+                mft_modified: entry.mft_modified,
+                accessed: entry.accessed,
+                carved_path: None,
+            })
+            .collect()
+    }
+}
+
+/// Boot sector geometry of the NTFS volume at `offset`.
+pub fn read_boot(source: &dyn EvidenceSource, offset: u64) -> Result<NtfsBoot, NtfsError> {
+    let mut sector = [0u8; 512];
+    read_exact(source, offset, &mut sector)?;
+    let malformed = NtfsError::Malformed {
+        what: "boot sector",
+        offset,
+    };
+    if &sector[3..11] != b"NTFS    " {
+        return Err(malformed);
+    }
+    let bytes_per_sector = u64::from(le_u16(&sector, 11));
+    let cluster_size = match sector[13] {
+        0 => return Err(malformed),
+        n if n <= 0x80 => u64::from(n) * bytes_per_sector,
+        n => 1u64 << (256 - u32::from(n)).min(31),
+    };
+    let record_size = match sector[64] as i8 {
+        n if n > 0 => n as u64 * cluster_size,
+        n => 1u64 << (-i32::from(n)).min(31),
+    };
+    if !bytes_per_sector.is_power_of_two()
+        || !(256..=4096).contains(&bytes_per_sector)
+        || !(512..=65536).contains(&record_size)
+    {
+        return Err(malformed);
+    }
+    Ok(NtfsBoot {
+        offset,
+        bytes_per_sector,
+        cluster_size,
+        total_clusters: le_u64(&sector, 40) * bytes_per_sector / cluster_size,
+        mft_cluster: le_u64(&sector, 48),
+        record_size,
+    })
+}
+
+/// Every named record of the volume at `offset`, with paths and the
+/// clusters in use.
+pub fn read_volume(source: &dyn EvidenceSource, offset: u64) -> Result<NtfsVolumeFiles, NtfsError> {
+    let boot = read_boot(source, offset)?;
+    let mut record = vec![0u8; boot.record_size as usize];
+    let mft_offset = boot.cluster_offset(boot.mft_cluster);
+    read_exact(source, mft_offset, &mut record)?;
+    let mft = parse_record(&mut record, 0, boot.bytes_per_sector).ok_or(NtfsError::Malformed {
+        what: "$MFT record",
+        offset: mft_offset,
+    })?;
+
+    let count = (mft.size / boot.record_size).min(MAX_RECORDS);
+    let mut entries: Vec<MftEntry> = Vec::new();
+    let mut extensions: Vec<(u64, MftEntry)> = Vec::new();
+    let record_size = boot.record_size as usize;
+    let mut index = 0u64;
+    'runs: for run in &mft.runs {
+        let run_len = run.clusters * boot.cluster_size;
+        let Some(lcn) = run.lcn else {
+            index += run_len / boot.record_size;
+            continue;
+        };
+        let start = boot.cluster_offset(lcn);
+        let mut done = 0u64;
+        while done + boot.record_size <= run_len {
+            let len = (run_len - done).min(MFT_READ_SIZE) / boot.record_size * boot.record_size;
+            let mut data = vec![0u8; len as usize];
+            if read_exact(source, start + done, &mut data).is_err() {
+                index += len / boot.record_size;
+                done += len;
+                continue;
+            }
+            for chunk in data.chunks_exact_mut(record_size) {
+                if index >= count {
+                    break 'runs;
+                }
+                match parse_record(chunk, index, boot.bytes_per_sector) {
+                    Some(entry) if base_record(chunk) != 0 => {
+                        extensions.push((base_record(chunk), entry));
+                    }
+                    Some(entry) if entry.name.is_some() => entries.push(entry),
+                    _ => {}
+                }
+                index += 1;
+            }
+            done += len;
+        }
+    }
+
+    let positions: HashMap<u64, usize> = entries
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| (entry.record, i))
+        .collect();
+    for (base, extension) in extensions {
+        if let Some(&i) = positions.get(&base) {
+            let entry = &mut entries[i];
+            if entry.runs.is_empty() && !entry.resident {
+                entry.runs = extension.runs;
+                entry.size = entry.size.max(extension.size);
+            }
+        }
+    }
+
+    let paths = entries
+        .iter()
+        .map(|entry| resolve_path(entry, &entries, &positions))
+        .collect();
+    let allocated = positions
+        .get(&BITMAP_RECORD)
+        .map(|&i| read_bitmap(source, &boot, &entries[i]))
+        .unwrap_or_default();
+    Ok(NtfsVolumeFiles {
+        boot,
+        entries,
+        paths,
+        allocated,
+    })
+}
+
+/// Clusters `$Bitmap` marks in use on the volume at `offset`, read without
+/// walking the rest of the MFT.
+pub fn read_allocated(
+    source: &dyn EvidenceSource,
+    offset: u64,
+) -> Result<(NtfsBoot, Vec<(u64, u64)>), NtfsError> {
+    let boot = read_boot(source, offset)?;
+    let mut record = vec![0u8; boot.record_size as usize];
+    let mft_offset = boot.cluster_offset(boot.mft_cluster);
+    read_exact(source, mft_offset, &mut record)?;
+    let mft = parse_record(&mut record, 0, boot.bytes_per_sector).ok_or(NtfsError::Malformed {
+        what: "$MFT record",
+        offset: mft_offset,
+    })?;
+    let mut index = 0u64;
+    for run in &mft.runs {
+        let records = run.clusters * boot.cluster_size / boot.record_size;
+        if BITMAP_RECORD >= index + records {
+            index += records;
+            continue;
+        }
+        let Some(lcn) = run.lcn else {
+            break;
+        };
+        let at = boot.cluster_offset(lcn) + (BITMAP_RECORD - index) * boot.record_size;
+        read_exact(source, at, &mut record)?;
+        let bitmap = parse_record(&mut record, BITMAP_RECORD, boot.bytes_per_sector).ok_or(
+            NtfsError::Malformed {
+                what: "$Bitmap record",
+                offset: at,
+            },
+        )?;
+        let allocated = read_bitmap(source, &boot, &bitmap);
+        return Ok((boot, allocated));
+    }
+    Err(NtfsError::Malformed {
+        what: "$MFT run list",
+        offset: mft_offset,
+    })
+}
+
+/// `\`-separated path of `entry` from the root directory.
+fn resolve_path(entry: &MftEntry, entries: &[MftEntry], positions: &HashMap<u64, usize>) -> String {
+    if entry.record == ROOT_RECORD {
+        return "\\".to_string();
+    }
+    let mut parts = vec![entry.name.clone().unwrap_or_default()];
+    let mut parent = entry.parent;
+    while let Some(record) = parent {
+        if record == ROOT_RECORD || parts.len() >= MAX_DEPTH {
+            break;
+        }
+        match positions.get(&record).map(|&i| &entries[i]) {
+            Some(dir) if dir.record != entry.record => {
+                parts.push(dir.name.clone().unwrap_or_default());
+                parent = dir.parent;
+            }
+            _ => {
+                parts.push("$Orphan".to_string());
+                break;
+            }
+        }
+    }
+    parts.reverse();
+    format!("\\{}", parts.join("\\"))
+}
+
+/// Runs of set bits in the `$Bitmap` data as evidence ranges.
+fn read_bitmap(source: &dyn EvidenceSource, boot: &NtfsBoot, entry: &MftEntry) -> Vec<(u64, u64)> {
+    let mut ranges: Vec<(u64, u64)> = Vec::new();
+    let mut cluster = 0u64;
+    let bytes = entry.size.min(boot.total_clusters.div_ceil(8));
+    let mut read = 0u64;
+    for run in &entry.runs {
+        let Some(lcn) = run.lcn else {
+            break;
+        };
+        let len = (run.clusters * boot.cluster_size).min(bytes - read);
+        let mut data = vec![0u8; len as usize];
+        if read_exact(source, boot.cluster_offset(lcn), &mut data).is_err() {
+            return Vec::new();
+        }
+        for byte in data {
+            for bit in 0..8 {
+                if byte & (1 << bit) != 0 && cluster < boot.total_clusters {
+                    let start = boot.cluster_offset(cluster);
+                    match ranges.last_mut() {
+                        Some(last) if last.1 == start => last.1 += boot.cluster_size,
+                        _ => ranges.push((start, start + boot.cluster_size)),
+                    }
+                }
+                cluster += 1;
+            }
+        }
+        read += len;
+        if read >= bytes {
+            break;
+        }
+    }
+    ranges
+}
+
+/// Base record of an extension record (0 for base records).
+fn base_record(record: &[u8]) -> u64 {
+    le_u64(record, 0x20) & 0xFFFF_FFFF_FFFF
+}
+
+/// Apply the fixups and read the attributes of a `FILE` record.
+fn parse_record(record: &mut [u8], index: u64, sector_size: u64) -> Option<MftEntry> {
+    if &record[..4] != b"FILE" || !apply_fixups(record, sector_size as usize) {
+        return None;
+    }
+    let flags = le_u16(record, 0x16);
+    let mut entry = MftEntry {
+        record: index,
+        sequence: le_u16(record, 0x10),
+        in_use: flags & FLAG_IN_USE != 0,
+        directory: flags & FLAG_DIRECTORY != 0,
+        ..Default::default()
+    };
+    let mut name_namespace = None;
+    let mut at = usize::from(le_u16(record, 0x14));
+    while at + 16 <= record.len() {
+        let kind = le_u32(record, at);
+        let len = le_u32(record, at + 4) as usize;
+        if kind == ATTR_END || len < 16 || at + len > record.len() {
+            break;
+        }
+        let attr = &record[at..at + len];
+        let non_resident = attr[8] != 0;
+        let named = attr[9] != 0;
+        match kind {
+            ATTR_STANDARD_INFORMATION if !non_resident => {
+                if let Some(value) = resident_value(attr).filter(|v| v.len() >= 32) {
+                    entry.created = filetime(le_u64(value, 0));
+                    entry.modified = filetime(le_u64(value, 8));
+                    entry.mft_modified = filetime(le_u64(value, 16));
+                    entry.accessed = filetime(le_u64(value, 24));
+                }
+            }
+            ATTR_FILE_NAME if !non_resident => {
+                if let Some(value) = resident_value(attr).filter(|v| v.len() >= 0x42) {
+                    let namespace = value[0x41];
+                    let chars = usize::from(value[0x40]);
+                    // Any long name replaces an 8.3 short name.
+                    let better = match name_namespace {
+                        None => true,
+                        Some(current) => current == NAMESPACE_DOS && namespace != NAMESPACE_DOS,
+                    };
+                    if let Some(raw) = value.get(0x42..0x42 + chars * 2)
+                        && better
+                    {
+                        let units: Vec<u16> = raw
+                            .chunks_exact(2)
+                            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                            .collect();
+                        entry.name = Some(String::from_utf16_lossy(&units));
+                        entry.parent = Some(le_u64(value, 0) & 0xFFFF_FFFF_FFFF);
+                        name_namespace = Some(namespace);
+                    }
+                }
+            }
+            ATTR_DATA if !named => {
+                if non_resident && attr.len() >= 0x40 {
+                    // Only the first extent (starting VCN 0) carries the size.
+                    if le_u64(attr, 0x10) == 0 {
+                        entry.size = le_u64(attr, 0x30);
+                    }
+                    let runs_at = usize::from(le_u16(attr, 0x20));
+                    entry.runs.extend(decode_runs(attr.get(runs_at..)?));
+                } else if let Some(value) = resident_value(attr) {
+                    entry.resident = true;
+                    entry.size = value.len() as u64;
+                }
+            }
+            _ => {}
+        }
+        at += len;
+    }
+    Some(entry)
+}
+
+/// Restore the last two bytes of each sector from the update sequence
+/// array; `false` when a sector was torn.
+fn apply_fixups(record: &mut [u8], sector_size: usize) -> bool {
+    let offset = usize::from(le_u16(record, 4));
+    let count = usize::from(le_u16(record, 6));
+    if count == 0 || offset + count * 2 > record.len() {
+        return false;
+    }
+    let check = [record[offset], record[offset + 1]];
+    for i in 1..count {
+        let end = i * sector_size;
+        if end > record.len() {
+            break;
+        }
+        if record[end - 2..end] != check {
+            return false;
+        }
+        let fix = [record[offset + i * 2], record[offset + i * 2 + 1]];
+        record[end - 2..end].copy_from_slice(&fix);
+    }
+    true
+}
+
+fn resident_value(attr: &[u8]) -> Option<&[u8]> {
+    let len = le_u32(attr, 0x10) as usize;
+    let at = usize::from(le_u16(attr, 0x14));
+    attr.get(at..at.checked_add(len)?)
+}
+
+/// Decode a mapping pairs array into runs with absolute cluster numbers.
+pub fn decode_runs(data: &[u8]) -> Vec<DataRun> {
+    let mut runs = Vec::new();
+    let mut at = 0;
+    let mut lcn: i64 = 0;
+    while let Some(&header) = data.get(at) {
+        if header == 0 {
+            break;
+        }
+        let len_size = usize::from(header & 0x0F);
+        let offset_size = usize::from(header >> 4);
+        let Some(fields) = data.get(at + 1..at + 1 + len_size + offset_size) else {
+            break;
+        };
+        if len_size == 0 || len_size > 8 || offset_size > 8 {
+            break;
+        }
+        let clusters = le_var(&fields[..len_size], false) as u64;
+        let run_lcn = if offset_size == 0 {
+            None
+        } else {
+            lcn = lcn.wrapping_add(le_var(&fields[len_size..], true));
+            u64::try_from(lcn).ok()
+        };
+        runs.push(DataRun {
+            lcn: run_lcn,
+            clusters,
+        });
+        at += 1 + len_size + offset_size;
+    }
+    runs
+}
+
+/// Little-endian integer of up to 8 bytes, sign-extended when `signed`.
+fn le_var(bytes: &[u8], signed: bool) -> i64 {
+    let mut buf = [0u8; 8];
+    buf[..bytes.len()].copy_from_slice(bytes);
+    if signed && bytes.last().is_some_and(|b| b & 0x80 != 0) {
+        buf[bytes.len()..].fill(0xFF);
+    }
+    i64::from_le_bytes(buf)
+}
+
+/// FILETIME (100 ns since 1601) to a UTC time; zero is unset.
+fn filetime(value: u64) -> Option<NaiveDateTime> {
+    (value != 0)
+        .then(|| webkit_timestamp_to_datetime((value / 10) as i64))
+        .flatten()
+}
+
+fn read_exact(source: &dyn EvidenceSource, offset: u64, buf: &mut [u8]) -> Result<(), NtfsError> {
+    let mut done = 0;
+    while done < buf.len() {
+        let n = source.read_at(offset + done as u64, &mut buf[done..])?;
+        if n == 0 {
+            return Err(NtfsError::Malformed {
+                what: "structure past the end of the evidence",
+                offset,
+            });
+        }
+        done += n;
+    }
+    Ok(())
+}
+
+fn le_u16(data: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes(data[at..at + 2].try_into().unwrap_or_default())
+}
+
+fn le_u32(data: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(data[at..at + 4].try_into().unwrap_or_default())
+}
+
+fn le_u64(data: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(data[at..at + 8].try_into().unwrap_or_default())
+}
+
+/// Synthetic NTFS volumes with a small MFT.
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::*;
+
+    pub(crate) const CLUSTER: usize = 4096;
+    pub(crate) const RECORD: usize = 1024;
+    /// First of the eight clusters of the MFT.
+    const MFT_CLUSTER: usize = 4;
+    const MFT_CLUSTERS: usize = 8;
+    /// Cluster of the `$Bitmap` data.
+    const BITMAP_CLUSTER: usize = 12;
+
+    /// A file to place in the MFT: record, parent, name, in use, data
+    /// clusters (`None` for resident data), and size.
+    pub(crate) struct File {
+        pub record: u64,
+        pub parent: u64,
+        pub name: &'static str,
+        pub in_use: bool,
+        pub clusters: Option<(u64, u64)>,
+        pub size: u64,
+    }
+
+    /// A volume of `clusters` 4 KiB clusters with records 0 (`$MFT`),
+    /// 5 (root), and 6 (`$Bitmap`) plus `files`; `allocated` lists the
+    /// clusters marked in use besides those of the MFT and bitmap.
+    pub(crate) fn volume(clusters: usize, files: &[File], allocated: &[u64]) -> Vec<u8> {
+        let mut image = vec![0u8; clusters * CLUSTER];
+        image[3..11].copy_from_slice(b"NTFS    ");
+        image[11..13].copy_from_slice(&512u16.to_le_bytes());
+        image[13] = (CLUSTER / 512) as u8;
+        image[40..48].copy_from_slice(&((clusters * CLUSTER / 512) as u64).to_le_bytes());
+        image[48..56].copy_from_slice(&(MFT_CLUSTER as u64).to_le_bytes());
+        image[64] = (-10i8) as u8;
+        image[510..512].copy_from_slice(&[0x55, 0xAA]);
+
+        let mft = File {
+            record: 0,
+            parent: ROOT_RECORD,
+            name: "$MFT",
+            in_use: true,
+            clusters: Some((MFT_CLUSTER as u64, MFT_CLUSTERS as u64)),
+            size: (MFT_CLUSTERS * CLUSTER) as u64,
+        };
+        let root = File {
+            record: ROOT_RECORD,
+            parent: ROOT_RECORD,
+            name: ".",
+            in_use: true,
+            clusters: None,
+            size: 0,
+        };
+        let bitmap = File {
+            record: BITMAP_RECORD,
+            parent: ROOT_RECORD,
+            name: "$Bitmap",
+            in_use: true,
+            clusters: Some((BITMAP_CLUSTER as u64, 1)),
+            size: clusters.div_ceil(8) as u64,
+        };
+        for file in [&mft, &root, &bitmap].into_iter().chain(files) {
+            let at = MFT_CLUSTER * CLUSTER + file.record as usize * RECORD;
+            image[at..at + RECORD].copy_from_slice(&record(file));
+        }
+        let used = (MFT_CLUSTER..=BITMAP_CLUSTER).map(|c| c as u64);
+        for cluster in used.chain(allocated.iter().copied()) {
+            image[BITMAP_CLUSTER * CLUSTER + cluster as usize / 8] |= 1 << (cluster % 8);
+        }
+        image
+    }
+
+    fn record(file: &File) -> Vec<u8> {
+        let mut rec = vec![0u8; RECORD];
+        rec[..4].copy_from_slice(b"FILE");
+        rec[4..6].copy_from_slice(&0x30u16.to_le_bytes());
+        rec[6..8].copy_from_slice(&3u16.to_le_bytes());
+        rec[0x10..0x12].copy_from_slice(&1u16.to_le_bytes());
+        rec[0x14..0x16].copy_from_slice(&0x38u16.to_le_bytes());
+        let flags = if file.in_use { FLAG_IN_USE } else { 0 };
+        rec[0x16..0x18].copy_from_slice(&flags.to_le_bytes());
+
+        let mut attrs = Vec::new();
+        let mut info = vec![0u8; 48];
+        info[..8].copy_from_slice(&133_485_408_000_000_000u64.to_le_bytes());
+        attrs.extend(resident(ATTR_STANDARD_INFORMATION, &info));
+        let units: Vec<u16> = file.name.encode_utf16().collect();
+        let mut name = vec![0u8; 0x42];
+        name[..8].copy_from_slice(&file.parent.to_le_bytes());
+        name[0x40] = units.len() as u8;
+        name[0x41] = 1;
+        name.extend(units.iter().flat_map(|u| u.to_le_bytes()));
+        attrs.extend(resident(ATTR_FILE_NAME, &name));
+        match file.clusters {
+            Some((lcn, count)) => {
+                let mut attr = vec![0u8; 0x40];
+                attr[..4].copy_from_slice(&ATTR_DATA.to_le_bytes());
+                attr[8] = 1;
+                attr[0x20..0x22].copy_from_slice(&0x40u16.to_le_bytes());
+                attr[0x30..0x38].copy_from_slice(&file.size.to_le_bytes());
+                attr.extend([0x44]);
+                attr.extend((count as u32).to_le_bytes());
+                attr.extend((lcn as u32).to_le_bytes());
+                attr.push(0);
+                attr.resize(attr.len().next_multiple_of(8), 0);
+                let len = attr.len() as u32;
+                attr[4..8].copy_from_slice(&len.to_le_bytes());
+                attrs.extend(attr);
+            }
+            None => attrs.extend(resident(ATTR_DATA, &vec![b'r'; file.size as usize])),
+        }
+        attrs.extend(ATTR_END.to_le_bytes());
+        rec[0x38..0x38 + attrs.len()].copy_from_slice(&attrs);
+
+        // Update sequence: number 1, sector ends saved in the array.
+        rec[0x30..0x32].copy_from_slice(&1u16.to_le_bytes());
+        for i in 1..3 {
+            let end = i * 512;
+            let saved = [rec[end - 2], rec[end - 1]];
+            rec[0x30 + i * 2..0x32 + i * 2].copy_from_slice(&saved);
+            rec[end - 2..end].copy_from_slice(&1u16.to_le_bytes());
+        }
+        rec
+    }
+
+    fn resident(kind: u32, value: &[u8]) -> Vec<u8> {
+        let mut attr = vec![0u8; 0x18];
+        attr[..4].copy_from_slice(&kind.to_le_bytes());
+        attr[0x10..0x14].copy_from_slice(&(value.len() as u32).to_le_bytes());
+        attr[0x14..0x16].copy_from_slice(&0x18u16.to_le_bytes());
+        attr.extend(value);
+        attr.resize(attr.len().next_multiple_of(8), 0);
+        let len = attr.len() as u32;
+        attr[4..8].copy_from_slice(&len.to_le_bytes());
+        attr
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::{CLUSTER, File, volume};
+    use super::*;
+    use crate::evidence::MemorySource;
+
+    #[test]
+    fn reads_records_paths_and_bitmap() {
+        let image = volume(
+            64,
+            &[
+                File {
+                    record: 16,
+                    parent: ROOT_RECORD,
+                    name: "Users",
+                    in_use: true,
+                    clusters: None,
+                    size: 0,
+                },
+                File {
+                    record: 17,
+                    parent: 16,
+                    name: "photo.jpg",
+                    in_use: false,
+                    clusters: Some((20, 3)),
+                    size: 10_000,
+                },
+                File {
+                    record: 18,
+                    parent: 40,
+                    name: "lost.txt",
+                    in_use: true,
+                    clusters: None,
+                    size: 12,
+                },
+            ],
+            &[30, 31],
+        );
+        let image = MemorySource::new(image);
+        let files = read_volume(&image, 0).expect("volume");
+        assert_eq!(files.boot.cluster_size, CLUSTER as u64);
+        assert_eq!(files.boot.record_size, 1024);
+
+        let by_name = |name: &str| {
+            let i = files
+                .entries
+                .iter()
+                .position(|e| e.name.as_deref() == Some(name))
+                .expect(name);
+            (&files.entries[i], files.paths[i].as_str())
+        };
+        let (photo, path) = by_name("photo.jpg");
+        assert_eq!(path, "\\Users\\photo.jpg");
+        assert!(!photo.in_use);
+        assert_eq!(photo.size, 10_000);
+        assert_eq!(
+            photo.runs,
+            vec![DataRun {
+                lcn: Some(20),
+                clusters: 3
+            }]
+        );
+        assert_eq!(photo.first_offset(&files.boot), Some(20 * CLUSTER as u64));
+        assert!(photo.created.is_some());
+        let (lost, path) = by_name("lost.txt");
+        assert_eq!(path, "\\$Orphan\\lost.txt");
+        assert!(lost.resident);
+
+        let c = CLUSTER as u64;
+        assert_eq!(files.allocated, vec![(4 * c, 13 * c), (30 * c, 32 * c)]);
+        let (boot, allocated) = read_allocated(&image, 0).expect("bitmap");
+        assert_eq!(boot, files.boot);
+        assert_eq!(allocated, files.allocated);
+    }
+
+    #[test]
+    fn decodes_sparse_and_negative_runs() {
+        // 4 clusters at 100, 2 sparse, 3 at 100 - 60 = 40.
+        let runs = decode_runs(&[0x11, 4, 100, 0x01, 2, 0x11, 3, 0xC4, 0]);
+        assert_eq!(
+            runs,
+            vec![
+                DataRun {
+                    lcn: Some(100),
+                    clusters: 4
+                },
+                DataRun {
+                    lcn: None,
+                    clusters: 2
+                },
+                DataRun {
+                    lcn: Some(40),
+                    clusters: 3
+                },
+            ]
+        );
+        assert!(read_boot(&MemorySource::new(vec![0u8; 4096]), 0).is_err());
+    }
+}
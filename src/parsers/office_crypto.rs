@@ -0,0 +1,302 @@
+//! Encryption metadata of password-protected Office documents (`[MS-OFFCRYPTO]`).
+//!
+//! ECMA-376 encryption wraps the whole OOXML zip in a compound file with two
+//! streams: `EncryptionInfo`, which describes the cipher and key derivation,
+//! and `EncryptedPackage`, an 8-byte plaintext size followed by the ciphertext.
+//! Neither needs the password to read, so an encrypted carve still says how it
+//! was protected and how large the document is.
+//!
+//! The `EncryptionInfo` version selects the layout: 4.4 is agile encryption
+//! with an XML descriptor, minor version 2 is standard encryption with a binary
+//! header, and minor version 3 is extensible encryption with a custom provider.
+
+use std::io::{Read, Seek};
+
+use serde::Serialize;
+
+use crate::parsers::cfb::CompoundFile;
+
+pub const ENCRYPTION_INFO_STREAM: &str = "EncryptionInfo";
+pub const ENCRYPTED_PACKAGE_STREAM: &str = "EncryptedPackage";
+
+/// `EncryptionInfo` bytes read; agile descriptors are a few KiB.
+const MAX_INFO_LEN: usize = 64 * 1024;
+/// Standard encryption header flag: the cipher is AES.
+const FLAG_AES: u32 = 0x20;
+
+/// Encryption metadata of one carved encrypted document.
+#[derive(Debug, Clone, Serialize)]
+pub struct EncryptedDocumentRecord {
+    pub run_id: String,
+    /// `agile`, `standard`, `extensible`, or `unknown`.
+    pub encryption_type: String,
+    /// `EncryptionInfo` version as `major.minor`.
+    pub version: Option<String>,
+    pub cipher_algorithm: Option<String>,
+    pub key_bits: Option<u32>,
+    pub hash_algorithm: Option<String>,
+    /// Hash iterations of the password key derivation (agile only).
+    pub spin_count: Option<u32>,
+    /// Cryptographic provider named in a standard encryption header.
+    pub csp_name: Option<String>,
+    /// Plaintext size of the OOXML package.
+    pub original_size: Option<u64>,
+    /// Carved path of the compound file.
+    pub source_file: String,
+}
+
+/// What [`read_encryption`] and [`parse_encryption_info`] read.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EncryptionInfo {
+    pub encryption_type: &'static str,
+    pub version: Option<String>,
+    pub cipher_algorithm: Option<String>,
+    pub key_bits: Option<u32>,
+    pub hash_algorithm: Option<String>,
+    pub spin_count: Option<u32>,
+    pub csp_name: Option<String>,
+    pub original_size: Option<u64>,
+}
+
+/// Encryption metadata of an open compound file, or `None` when it lacks the
+/// `EncryptionInfo` and `EncryptedPackage` streams. Unreadable streams leave
+/// their fields empty.
+pub fn read_encryption<R: Read + Seek>(cfb: &mut CompoundFile<R>) -> Option<EncryptionInfo> {
+    let info_entry = cfb.stream(ENCRYPTION_INFO_STREAM)?.clone();
+    let package_entry = cfb.stream(ENCRYPTED_PACKAGE_STREAM)?.clone();
+    let mut info = cfb
+        .read_stream(&info_entry, MAX_INFO_LEN)
+        .ok()
+        .map(|data| parse_encryption_info(&data))
+        .unwrap_or(EncryptionInfo {
+            encryption_type: "unknown",
+            ..EncryptionInfo::default()
+        });
+    info.original_size = cfb
+        .read_stream(&package_entry, 8)
+        .ok()
+        .and_then(|data| Some(u64::from_le_bytes(data.get(..8)?.try_into().ok()?)));
+    Some(info)
+}
+
+/// Parse an `EncryptionInfo` stream.
+pub fn parse_encryption_info(data: &[u8]) -> EncryptionInfo {
+    let mut info = EncryptionInfo {
+        encryption_type: "unknown",
+        ..EncryptionInfo::default()
+    };
+    if data.len() < 8 {
+        return info;
+    }
+    let major = u16::from_le_bytes([data[0], data[1]]);
+    let minor = u16::from_le_bytes([data[2], data[3]]);
+    info.version = Some(format!("{major}.{minor}"));
+    match (major, minor) {
+        (4, 4) => {
+            info.encryption_type = "agile";
+            parse_agile(&data[8..], &mut info);
+        }
+        (2..=4, 2) => {
+            info.encryption_type = "standard";
+            parse_standard(&data[8..], &mut info);
+        }
+        (3 | 4, 3) => info.encryption_type = "extensible",
+        _ => {}
+    }
+    info
+}
+
+/// Agile encryption: an XML descriptor whose `keyData` element names the
+/// cipher used for the package and whose password `encryptedKey` element
+/// carries the key derivation spin count.
+fn parse_agile(xml: &[u8], info: &mut EncryptionInfo) {
+    let xml = String::from_utf8_lossy(xml);
+    if let Some(key_data) = element(&xml, "keyData") {
+        info.cipher_algorithm = attribute(key_data, "cipherAlgorithm").map(|alg| {
+            match attribute(key_data, "keyBits") {
+                Some(bits) if alg.eq_ignore_ascii_case("AES") => format!("AES-{bits}"),
+                _ => alg.to_string(),
+            }
+        });
+        info.key_bits = attribute(key_data, "keyBits").and_then(|v| v.parse().ok());
+        info.hash_algorithm = attribute(key_data, "hashAlgorithm").map(str::to_string);
+    }
+    if let Some(key) = element(&xml, "encryptedKey") {
+        info.spin_count = attribute(key, "spinCount").and_then(|v| v.parse().ok());
+    }
+}
+
+/// Standard encryption: header size, then the binary `EncryptionHeader` with
+/// the CryptoAPI algorithm ids and a UTF-16 provider name.
+fn parse_standard(data: &[u8], info: &mut EncryptionInfo) {
+    let u32_at = |offset: usize| {
+        data.get(offset..offset + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    };
+    let Some(header_size) = u32_at(0) else {
+        return;
+    };
+    let (Some(flags), Some(alg_id), Some(hash_id), Some(key_size)) =
+        (u32_at(4), u32_at(12), u32_at(16), u32_at(20))
+    else {
+        return;
+    };
+    info.cipher_algorithm = match alg_id {
+        0x6801 => Some("RC4".to_string()),
+        0x660E => Some("AES-128".to_string()),
+        0x660F => Some("AES-192".to_string()),
+        0x6610 => Some("AES-256".to_string()),
+        0 if flags & FLAG_AES != 0 => Some("AES-128".to_string()),
+        0 => None,
+        other => Some(format!("0x{other:04X}")),
+    };
+    info.hash_algorithm = match hash_id {
+        0 | 0x8004 => Some("SHA-1".to_string()),
+        other => Some(format!("0x{other:04X}")),
+    };
+    info.key_bits = match key_size {
+        // Zero means the algorithm's default.
+        0 if info.cipher_algorithm.as_deref() == Some("RC4") => Some(40),
+        0 if info.cipher_algorithm.as_deref() == Some("AES-128") => Some(128),
+        0 => None,
+        bits => Some(bits),
+    };
+    let csp_end = (4 + header_size as usize).min(data.len());
+    if let Some(csp) = data.get(36..csp_end) {
+        let units: Vec<u16> = csp
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .take_while(|&u| u != 0)
+            .collect();
+        if !units.is_empty() {
+            info.csp_name = Some(String::from_utf16_lossy(&units));
+        }
+    }
+}
+
+/// Attribute text of the first `<name` or `<prefix:name` start tag.
+fn element<'a>(xml: &'a str, name: &str) -> Option<&'a str> {
+    let mut search = 0;
+    while let Some(found) = xml[search..].find(name) {
+        let at = search + found;
+        search = at + name.len();
+        let before = xml[..at].chars().next_back();
+        let after = xml[search..].chars().next();
+        let local = match before {
+            Some('<') => true,
+            Some(':') => xml[..at]
+                .rfind('<')
+                .is_some_and(|lt| !xml[lt..at].contains(['>', ' '])),
+            _ => false,
+        };
+        if local && after.is_some_and(|c| c.is_whitespace() || c == '>' || c == '/') {
+            let end = xml[search..].find('>').map_or(xml.len(), |e| search + e);
+            return Some(&xml[search..end]);
+        }
+    }
+    None
+}
+
+/// Value of `name="..."` within a start tag's attribute text.
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let mut search = 0;
+    while let Some(found) = tag[search..].find(name) {
+        let at = search + found;
+        search = at + name.len();
+        let preceded = at == 0 || tag[..at].ends_with(char::is_whitespace);
+        let rest = tag[search..].trim_start();
+        if preceded && let Some(rest) = rest.strip_prefix('=') {
+            let rest = rest.trim_start();
+            let quote = rest.chars().next()?;
+            if quote != '"' && quote != '\'' {
+                return None;
+            }
+            let value = &rest[1..];
+            return value.find(quote).map(|end| &value[..end]);
+        }
+    }
+    None
+}
+
+/// An encrypted OOXML compound file with an agile `EncryptionInfo` and a
+/// package of `original_size` plaintext bytes.
+#[cfg(test)]
+pub(crate) fn encrypted_ooxml(original_size: u64) -> Vec<u8> {
+    let mut info = vec![4, 0, 4, 0, 0x40, 0, 0, 0];
+    info.extend_from_slice(AGILE_XML.as_bytes());
+    let mut package = original_size.to_le_bytes().to_vec();
+    package.extend_from_slice(&[0x5Au8; 4096]);
+    crate::parsers::cfb::compound_file(&[
+        (ENCRYPTION_INFO_STREAM, info),
+        (ENCRYPTED_PACKAGE_STREAM, package),
+    ])
+}
+
+#[cfg(test)]
+const AGILE_XML: &str = concat!(
+    r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+    r#"<encryption xmlns="http://schemas.microsoft.com/office/2006/encryption" "#,
+    r#"xmlns:p="http://schemas.microsoft.com/office/2006/keyEncryptor/password">"#,
+    r#"<keyData saltSize="16" blockSize="16" keyBits="256" hashSize="64" "#,
+    r#"cipherAlgorithm="AES" cipherChaining="ChainingModeCBC" hashAlgorithm="SHA512" "#,
+    r#"saltValue="AAAAAAAAAAAAAAAAAAAAAA=="/>"#,
+    r#"<keyEncryptors><keyEncryptor uri="http://schemas.microsoft.com/office/2006/keyEncryptor/password">"#,
+    r#"<p:encryptedKey spinCount="100000" saltSize="16" blockSize="16" keyBits="256" "#,
+    r#"hashSize="64" cipherAlgorithm="AES" hashAlgorithm="SHA512"/>"#,
+    r#"</keyEncryptor></keyEncryptors></encryption>"#,
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn reads_agile_descriptor_and_package_size() {
+        let data = encrypted_ooxml(123_456);
+        let mut cfb = CompoundFile::open(Cursor::new(data)).expect("open");
+        let info = read_encryption(&mut cfb).expect("encrypted");
+        assert_eq!(info.encryption_type, "agile");
+        assert_eq!(info.version.as_deref(), Some("4.4"));
+        assert_eq!(info.cipher_algorithm.as_deref(), Some("AES-256"));
+        assert_eq!(info.key_bits, Some(256));
+        assert_eq!(info.hash_algorithm.as_deref(), Some("SHA512"));
+        assert_eq!(info.spin_count, Some(100_000));
+        assert_eq!(info.original_size, Some(123_456));
+
+        let plain = crate::parsers::cfb::compound_file(&[("WordDocument", vec![0u8; 64])]);
+        let mut cfb = CompoundFile::open(Cursor::new(plain)).expect("open");
+        assert!(read_encryption(&mut cfb).is_none());
+    }
+
+    #[test]
+    fn reads_standard_header() {
+        let csp: Vec<u8> = "Microsoft Enhanced RSA and AES Cryptographic Provider\0"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        let mut data = vec![3, 0, 2, 0, 0x24, 0, 0, 0];
+        data.extend_from_slice(&(32 + csp.len() as u32).to_le_bytes());
+        for value in [0x24u32, 0, 0x660E, 0x8004, 128, 0x18, 0, 0] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.extend_from_slice(&csp);
+        data.extend_from_slice(&[0u8; 16]);
+
+        let info = parse_encryption_info(&data);
+        assert_eq!(info.encryption_type, "standard");
+        assert_eq!(info.version.as_deref(), Some("3.2"));
+        assert_eq!(info.cipher_algorithm.as_deref(), Some("AES-128"));
+        assert_eq!(info.key_bits, Some(128));
+        assert_eq!(info.hash_algorithm.as_deref(), Some("SHA-1"));
+        assert_eq!(
+            info.csp_name.as_deref(),
+            Some("Microsoft Enhanced RSA and AES Cryptographic Provider")
+        );
+        assert_eq!(
+            parse_encryption_info(&[4, 0, 3, 0, 0, 0, 0, 0]).encryption_type,
+            "extensible"
+        );
+        assert_eq!(parse_encryption_info(&[1, 0]).encryption_type, "unknown");
+    }
+}
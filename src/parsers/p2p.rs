@@ -0,0 +1,487 @@
+//! BitTorrent metainfo files and P2P links.
+//!
+//! A `.torrent` file is one bencoded dictionary. Bencode has four types:
+//! integers (`i42e`), byte strings (`4:spam`), lists (`l...e`), and
+//! dictionaries (`d...e`, string keys). The info hash that identifies a torrent
+//! on trackers and in the DHT is the SHA-1 (v1) or SHA-256 (v2, `meta version`
+//! 2) of the raw bytes of the `info` dictionary, so it is computed from the
+//! span the dictionary occupies rather than from a re-encoding.
+//!
+//! String spans are searched for magnet URIs with an `xt=urn:` exact topic and
+//! eD2k file links (`ed2k://|file|name|size|md4|/`).
+
+use std::ops::Range;
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use sha2::{Digest, Sha256};
+
+use crate::strings::artifacts::ArtefactKind;
+
+/// Deepest nesting accepted; real metainfo files nest a handful of levels.
+const MAX_DEPTH: usize = 64;
+/// Longest span searched for links.
+const MAX_SPAN_LEN: usize = 8192;
+
+static MAGNET_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"(?i)\bmagnet:\?[^\s"'<>]+"#).expect("magnet regex"));
+static MAGNET_TOPIC_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)(?:^|&)xt(?:\.\d+)?=urn:[a-z0-9]+:[^&]+").expect("magnet topic regex")
+});
+static ED2K_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"(?i)\bed2k://\|file\|[^|\r\n]{1,512}\|\d{1,20}\|[0-9a-f]{32}\|(?:[^|\s/][^|\s]*\|)*/?",
+    )
+    .expect("ed2k regex")
+});
+
+/// Why a buffer does not hold a bencoded value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BencodeError {
+    /// The value continues past the end of the buffer.
+    Incomplete,
+    Invalid,
+}
+
+/// A decoded bencode value. Every value keeps its span in the input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node<'a> {
+    pub span: Range<usize>,
+    pub value: Value<'a>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value<'a> {
+    Int(i64),
+    /// Byte string; `span` of the node covers the length prefix, the
+    /// contents are at `span.end - bytes.len()`.
+    Bytes(&'a [u8]),
+    List(Vec<Node<'a>>),
+    Dict(Vec<(&'a [u8], Node<'a>)>),
+}
+
+impl<'a> Node<'a> {
+    pub fn get(&self, key: &str) -> Option<&Node<'a>> {
+        match &self.value {
+            Value::Dict(entries) => entries
+                .iter()
+                .find(|(k, _)| *k == key.as_bytes())
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn bytes(&self) -> Option<&'a [u8]> {
+        match self.value {
+            Value::Bytes(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    pub fn int(&self) -> Option<i64> {
+        match self.value {
+            Value::Int(i) => Some(i),
+            _ => None,
+        }
+    }
+
+    pub fn list(&self) -> &[Node<'a>] {
+        match &self.value {
+            Value::List(items) => items,
+            _ => &[],
+        }
+    }
+
+    /// Input range of a byte string's contents.
+    pub fn content_span(&self) -> Range<usize> {
+        match self.value {
+            Value::Bytes(b) => self.span.end - b.len()..self.span.end,
+            _ => self.span.clone(),
+        }
+    }
+}
+
+/// Decode the value at the start of `data`; trailing bytes are ignored.
+pub fn decode(data: &[u8]) -> Result<Node<'_>, BencodeError> {
+    decode_at(data, 0, 0)
+}
+
+fn decode_at(data: &[u8], start: usize, depth: usize) -> Result<Node<'_>, BencodeError> {
+    if depth > MAX_DEPTH {
+        return Err(BencodeError::Invalid);
+    }
+    let first = *data.get(start).ok_or(BencodeError::Incomplete)?;
+    match first {
+        b'i' => {
+            let (digits, end) = read_until(data, start + 1, b'e')?;
+            let text = std::str::from_utf8(digits).map_err(|_| BencodeError::Invalid)?;
+            let valid = match text.strip_prefix('-') {
+                Some(rest) => !rest.is_empty() && !rest.starts_with('0'),
+                None => text == "0" || !text.starts_with('0'),
+            };
+            let value = text
+                .parse::<i64>()
+                .ok()
+                .filter(|_| valid)
+                .ok_or(BencodeError::Invalid)?;
+            Ok(Node {
+                span: start..end + 1,
+                value: Value::Int(value),
+            })
+        }
+        b'0'..=b'9' => {
+            let (digits, colon) = read_until(data, start, b':')?;
+            if digits.len() > 1 && digits[0] == b'0' {
+                return Err(BencodeError::Invalid);
+            }
+            let len: usize = std::str::from_utf8(digits)
+                .ok()
+                .and_then(|t| t.parse().ok())
+                .ok_or(BencodeError::Invalid)?;
+            let end = (colon + 1).checked_add(len).ok_or(BencodeError::Invalid)?;
+            let bytes = data.get(colon + 1..end).ok_or(BencodeError::Incomplete)?;
+            Ok(Node {
+                span: start..end,
+                value: Value::Bytes(bytes),
+            })
+        }
+        b'l' => {
+            let mut pos = start + 1;
+            let mut items = Vec::new();
+            loop {
+                match data.get(pos) {
+                    None => return Err(BencodeError::Incomplete),
+                    Some(b'e') => break,
+                    Some(_) => {
+                        let item = decode_at(data, pos, depth + 1)?;
+                        pos = item.span.end;
+                        items.push(item);
+                    }
+                }
+            }
+            Ok(Node {
+                span: start..pos + 1,
+                value: Value::List(items),
+            })
+        }
+        b'd' => {
+            let mut pos = start + 1;
+            let mut entries = Vec::new();
+            loop {
+                match data.get(pos) {
+                    None => return Err(BencodeError::Incomplete),
+                    Some(b'e') => break,
+                    Some(b'0'..=b'9') => {
+                        let key = decode_at(data, pos, depth + 1)?;
+                        let value = decode_at(data, key.span.end, depth + 1)?;
+                        pos = value.span.end;
+                        entries.push((key.bytes().unwrap_or_default(), value));
+                    }
+                    Some(_) => return Err(BencodeError::Invalid),
+                }
+            }
+            Ok(Node {
+                span: start..pos + 1,
+                value: Value::Dict(entries),
+            })
+        }
+        _ => Err(BencodeError::Invalid),
+    }
+}
+
+/// Digits from `start` up to `terminator`, and the terminator's offset.
+fn read_until(data: &[u8], start: usize, terminator: u8) -> Result<(&[u8], usize), BencodeError> {
+    for (i, &b) in data.iter().enumerate().skip(start).take(21) {
+        if b == terminator {
+            return Ok((&data[start..i], i));
+        }
+        if !(b.is_ascii_digit() || (b == b'-' && i == start && terminator == b'e')) {
+            return Err(BencodeError::Invalid);
+        }
+    }
+    if data.len() < start + 21 {
+        Err(BencodeError::Incomplete)
+    } else {
+        Err(BencodeError::Invalid)
+    }
+}
+
+/// A value found in a metainfo file, with its byte range in the file.
+#[derive(Debug, Clone)]
+pub struct TorrentArtefact {
+    pub kind: ArtefactKind,
+    pub value: String,
+    pub range: Range<usize>,
+}
+
+/// The fields of a `.torrent` file investigators search for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Torrent {
+    /// Bytes of the metainfo dictionary.
+    pub len: usize,
+    pub info_span: Range<usize>,
+    /// SHA-1 of the info dictionary; present for v1 and hybrid torrents.
+    pub info_hash_v1: Option<[u8; 20]>,
+    /// SHA-256 of the info dictionary; present for `meta version` 2.
+    pub info_hash_v2: Option<[u8; 32]>,
+    /// `name.utf-8`, else `name`, of the info dictionary.
+    pub name: (String, Range<usize>),
+    /// `announce` and `announce-list` URLs, without duplicates.
+    pub trackers: Vec<(String, Range<usize>)>,
+    /// DHT bootstrap nodes (`nodes`) as `host:port`.
+    pub nodes: Vec<(String, Range<usize>)>,
+}
+
+impl Torrent {
+    /// Parse a metainfo file. The top-level value must be a dictionary with
+    /// an `info` dictionary that has a `name` and either `pieces` (v1) or a
+    /// `file tree` (v2).
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let root = decode(data).ok()?;
+        let info = root.get("info")?;
+        if !matches!(info.value, Value::Dict(_)) {
+            return None;
+        }
+        let raw_info = &data[info.span.clone()];
+        let v2 = info.get("meta version").and_then(Node::int) == Some(2)
+            && info.get("file tree").is_some();
+        let v1 = info.get("pieces").and_then(Node::bytes).is_some();
+        if !v1 && !v2 {
+            return None;
+        }
+        let name = info.get("name.utf-8").or_else(|| info.get("name"))?;
+        let name = (
+            String::from_utf8_lossy(name.bytes()?).into_owned(),
+            name.content_span(),
+        );
+
+        let mut trackers: Vec<(String, Range<usize>)> = Vec::new();
+        let announce_list = root.get("announce-list");
+        let tiers = announce_list.map(Node::list).unwrap_or_default();
+        for url in root
+            .get("announce")
+            .into_iter()
+            .chain(tiers.iter().flat_map(Node::list))
+        {
+            let Some(bytes) = url.bytes().filter(|b| !b.is_empty()) else {
+                continue;
+            };
+            let url_text = String::from_utf8_lossy(bytes).into_owned();
+            if !trackers.iter().any(|(t, _)| *t == url_text) {
+                trackers.push((url_text, url.content_span()));
+            }
+        }
+
+        let nodes = root
+            .get("nodes")
+            .map(Node::list)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|node| {
+                let [host, port] = node.list() else {
+                    return None;
+                };
+                let host = String::from_utf8_lossy(host.bytes()?);
+                let port = u16::try_from(port.int()?).ok()?;
+                Some((format!("{host}:{port}"), node.span.clone()))
+            })
+            .collect();
+
+        Some(Self {
+            len: root.span.end,
+            info_span: info.span.clone(),
+            info_hash_v1: v1.then(|| sha1(raw_info)),
+            info_hash_v2: v2.then(|| Sha256::digest(raw_info).into()),
+            name,
+            trackers,
+            nodes,
+        })
+    }
+
+    /// Info hashes (hex), name, trackers, and DHT nodes as artefacts.
+    pub fn artefacts(&self) -> Vec<TorrentArtefact> {
+        let mut out = Vec::new();
+        let hashes = [
+            self.info_hash_v1.map(hex::encode),
+            self.info_hash_v2.map(hex::encode),
+        ];
+        for hash in hashes.into_iter().flatten() {
+            out.push(TorrentArtefact {
+                kind: ArtefactKind::TorrentInfoHash,
+                value: hash,
+                range: self.info_span.clone(),
+            });
+        }
+        out.push(TorrentArtefact {
+            kind: ArtefactKind::TorrentName,
+            value: self.name.0.clone(),
+            range: self.name.1.clone(),
+        });
+        for (kind, list) in [
+            (ArtefactKind::TorrentTracker, &self.trackers),
+            (ArtefactKind::DhtNode, &self.nodes),
+        ] {
+            for (value, range) in list {
+                out.push(TorrentArtefact {
+                    kind: kind.clone(),
+                    value: value.clone(),
+                    range: range.clone(),
+                });
+            }
+        }
+        out
+    }
+}
+
+/// Magnet URIs and eD2k file links in one string span, with their byte
+/// ranges. Trailing sentence punctuation is not part of a link.
+pub fn find_links(text: &str) -> Vec<(ArtefactKind, Range<usize>)> {
+    if text.len() > MAX_SPAN_LEN {
+        return Vec::new();
+    }
+    let mut out = Vec::new();
+    for mat in MAGNET_RE.find_iter(text) {
+        let uri = mat.as_str().trim_end_matches(['.', ',', ';', ')', ']']);
+        let query = &uri["magnet:?".len()..];
+        if MAGNET_TOPIC_RE.is_match(query) {
+            out.push((
+                ArtefactKind::MagnetUri,
+                mat.start()..mat.start() + uri.len(),
+            ));
+        }
+    }
+    for mat in ED2K_RE.find_iter(text) {
+        out.push((ArtefactKind::Ed2kLink, mat.range()));
+    }
+    out
+}
+
+/// SHA-1 (FIPS 180-4). Only used for BitTorrent v1 info hashes.
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64).wrapping_mul(8)).to_be_bytes());
+    for block in message.chunks_exact(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, &word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (state, value) in h.iter_mut().zip([a, b, c, d, e]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+    let mut out = [0u8; 20];
+    for (chunk, word) in out.chunks_exact_mut(4).zip(h) {
+        chunk.copy_from_slice(&word.to_be_bytes());
+    }
+    out
+}
+
+/// A v1 metainfo file with two trackers and one DHT node.
+#[cfg(test)]
+pub(crate) fn torrent_bytes() -> Vec<u8> {
+    let mut out = b"d8:announce28:http://tracker.test/announce".to_vec();
+    out.extend_from_slice(
+        b"13:announce-listll28:http://tracker.test/announceel19:udp://other.test:80ee",
+    );
+    out.extend_from_slice(
+        b"4:infod6:lengthi1024e4:name9:movie.mkv12:piece lengthi16384e6:pieces20:",
+    );
+    out.extend_from_slice(&[0xAB; 20]);
+    out.extend_from_slice(b"e5:nodesll9:127.0.0.1i6881eee");
+    out.push(b'e');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sha1_matches_known_vectors() {
+        assert_eq!(
+            hex::encode(sha1(b"abc")),
+            "a9993e364706816aba3e25717850c26c9cd0d89d"
+        );
+        assert_eq!(
+            hex::encode(sha1(&[b'a'; 1000])),
+            "291e9a6c66994949b57ba5e650361e98fc36b1ba"
+        );
+    }
+
+    #[test]
+    fn parses_metainfo_and_hashes_info_bytes() {
+        let data = torrent_bytes();
+        let mut with_tail = data.clone();
+        with_tail.extend_from_slice(&[0u8; 64]);
+        let torrent = Torrent::parse(&with_tail).expect("torrent");
+        assert_eq!(torrent.len, data.len());
+        assert_eq!(&data[torrent.info_span.clone()][..6], b"d6:len");
+        assert_eq!(
+            torrent.info_hash_v1,
+            Some(sha1(&data[torrent.info_span.clone()]))
+        );
+        assert!(torrent.info_hash_v2.is_none());
+
+        let artefacts = torrent.artefacts();
+        let values: Vec<&str> = artefacts.iter().map(|a| a.value.as_str()).collect();
+        assert_eq!(
+            values[1..],
+            [
+                "movie.mkv",
+                "http://tracker.test/announce",
+                "udp://other.test:80",
+                "127.0.0.1:6881"
+            ]
+        );
+        let name = &artefacts[1];
+        assert_eq!(&data[name.range.clone()], b"movie.mkv");
+
+        assert_eq!(
+            decode(&data[..data.len() - 1]).unwrap_err(),
+            BencodeError::Incomplete
+        );
+        assert_eq!(decode(b"d3:keyi01ee").unwrap_err(), BencodeError::Invalid);
+        assert!(Torrent::parse(b"d4:infod4:name1:xee").is_none());
+    }
+
+    #[test]
+    fn finds_magnet_and_ed2k_links() {
+        let text = "get magnet:?xt=urn:btih:c12fe1c06bba254a9dc9f519b335aa7c1367a88a&dn=x. or \
+                    ed2k://|file|movie.avi|734003200|0123456789ABCDEF0123456789ABCDEF|/ \
+                    but not magnet:?dn=nothing";
+        let links = find_links(text);
+        assert_eq!(links.len(), 2);
+        assert!(matches!(links[0].0, ArtefactKind::MagnetUri));
+        assert!(text[links[0].1.clone()].ends_with("&dn=x"));
+        assert!(matches!(links[1].0, ArtefactKind::Ed2kLink));
+        assert!(text[links[1].1.clone()].ends_with("ABCDEF|/"));
+    }
+}
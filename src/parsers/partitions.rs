@@ -0,0 +1,113 @@
+//! MBR and GPT partition tables.
+//!
+//! [`partitions`] lists the partitions of the MBR, or of the GPT a
+//! protective MBR points to, and [`partition_offsets`] where they start.
+//! Sectors are taken to be 512 bytes. Extended MBR partitions are not
+//! followed.
+
+use crate::evidence::EvidenceSource;
+
+const SECTOR: u64 = 512;
+/// MBR partition types that hold no file system of their own.
+const MBR_EXTENDED: [u8; 3] = [0x05, 0x0F, 0x85];
+const MBR_GPT_PROTECTIVE: u8 = 0xEE;
+/// Upper bound on GPT entries read.
+const MAX_GPT_ENTRIES: u64 = 1024;
+
+/// A partition table entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Partition {
+    /// 1-based slot in the MBR, or entry in the GPT.
+    pub number: u32,
+    /// Evidence offset of the first sector.
+    pub offset: u64,
+    pub size: u64,
+}
+
+/// Evidence offsets of the partitions, ascending; empty without a partition
+/// table.
+pub fn partition_offsets(source: &dyn EvidenceSource) -> Vec<u64> {
+    let mut offsets: Vec<u64> = partitions(source).iter().map(|p| p.offset).collect();
+    offsets.sort_unstable();
+    offsets.dedup();
+    offsets
+}
+
+/// Partitions in table order; empty without a partition table.
+pub fn partitions(source: &dyn EvidenceSource) -> Vec<Partition> {
+    let mut found = Vec::new();
+    let mut mbr = [0u8; SECTOR as usize];
+    if !read_exact(source, 0, &mut mbr) || mbr[510..512] != [0x55, 0xAA] {
+        return found;
+    }
+    for (slot, entry) in mbr[446..510].chunks_exact(16).enumerate() {
+        let kind = entry[4];
+        let lba = u64::from(le_u32(entry, 8));
+        let sectors = u64::from(le_u32(entry, 12));
+        if kind == MBR_GPT_PROTECTIVE {
+            found.extend(gpt_partitions(source));
+        } else if kind != 0 && lba != 0 && !MBR_EXTENDED.contains(&kind) {
+            found.push(Partition {
+                number: slot as u32 + 1,
+                offset: lba * SECTOR,
+                size: sectors * SECTOR,
+            });
+        }
+    }
+    found
+}
+
+/// Partitions from the GPT header at LBA 1.
+fn gpt_partitions(source: &dyn EvidenceSource) -> Vec<Partition> {
+    let mut header = [0u8; SECTOR as usize];
+    if !read_exact(source, SECTOR, &mut header) || &header[..8] != b"EFI PART" {
+        return Vec::new();
+    }
+    let entries_lba = le_u64(&header, 72);
+    let count = u64::from(le_u32(&header, 80)).min(MAX_GPT_ENTRIES);
+    let entry_size = u64::from(le_u32(&header, 84));
+    if entry_size < 128 {
+        return Vec::new();
+    }
+    let mut table = vec![0u8; (count * entry_size) as usize];
+    if !read_exact(source, entries_lba.saturating_mul(SECTOR), &mut table) {
+        return Vec::new();
+    }
+    table
+        .chunks_exact(entry_size as usize)
+        .enumerate()
+        // An all-zero type GUID marks an unused entry.
+        .filter(|(_, entry)| entry[..16].iter().any(|&b| b != 0))
+        .filter(|(_, entry)| le_u64(entry, 32) != 0)
+        .map(|(i, entry)| {
+            let (first, last) = (le_u64(entry, 32), le_u64(entry, 40));
+            Partition {
+                number: i as u32 + 1,
+                offset: first.saturating_mul(SECTOR),
+                size: last
+                    .saturating_sub(first)
+                    .saturating_add(1)
+                    .saturating_mul(SECTOR),
+            }
+        })
+        .collect()
+}
+
+fn read_exact(source: &dyn EvidenceSource, offset: u64, buf: &mut [u8]) -> bool {
+    let mut done = 0;
+    while done < buf.len() {
+        match source.read_at(offset + done as u64, &mut buf[done..]) {
+            Ok(0) | Err(_) => return false,
+            Ok(n) => done += n,
+        }
+    }
+    true
+}
+
+fn le_u32(data: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(data[at..at + 4].try_into().unwrap_or_default())
+}
+
+fn le_u64(data: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(data[at..at + 8].try_into().unwrap_or_default())
+}
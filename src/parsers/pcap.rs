@@ -0,0 +1,751 @@
+//! Packet capture parsing (libpcap and pcapng).
+//!
+//! [`PacketReader`] walks the records of a capture from any [`Read`] source and
+//! stops at the first record that does not look valid, so it doubles as the
+//! extent check for the carvers. [`extract_artefacts`] decodes Ethernet, raw IP,
+//! and Linux cooked captures down to TCP/UDP and reports connection tuples, DNS
+//! query names, and HTTP `Host` headers.
+
+use std::collections::HashSet;
+use std::io::{self, Read};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use crate::strings::artifacts::ArtefactKind;
+
+/// Classic libpcap magic numbers as they appear on disk.
+pub const PCAP_MAGICS: [[u8; 4]; 4] = [
+    [0xD4, 0xC3, 0xB2, 0xA1], // microseconds, little-endian
+    [0xA1, 0xB2, 0xC3, 0xD4], // microseconds, big-endian
+    [0x4D, 0x3C, 0xB2, 0xA1], // nanoseconds, little-endian
+    [0xA1, 0xB2, 0x3C, 0x4D], // nanoseconds, big-endian
+];
+/// pcapng Section Header Block type (palindromic, so endian-independent).
+pub const PCAPNG_SHB_TYPE: [u8; 4] = [0x0A, 0x0D, 0x0D, 0x0A];
+const PCAPNG_BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+
+const PCAP_HEADER_LEN: usize = 24;
+const PCAP_RECORD_HEADER_LEN: usize = 16;
+/// Largest packet accepted; real link layers stay far below this.
+const MAX_PACKET_LEN: u32 = 256 * 1024;
+/// Largest pcapng block accepted.
+const MAX_BLOCK_LEN: u32 = 16 * 1024 * 1024;
+
+const BLOCK_IDB: u32 = 1;
+const BLOCK_PACKET: u32 = 2;
+const BLOCK_SPB: u32 = 3;
+const BLOCK_EPB: u32 = 6;
+const BLOCK_SHB: u32 = 0x0A0D_0D0A;
+
+const LINKTYPE_NULL: u32 = 0;
+const LINKTYPE_ETHERNET: u32 = 1;
+const LINKTYPE_RAW_BSD: u32 = 12;
+const LINKTYPE_RAW_OPENBSD: u32 = 14;
+const LINKTYPE_RAW: u32 = 101;
+const LINKTYPE_LINUX_SLL: u32 = 113;
+const LINKTYPE_IPV4: u32 = 228;
+const LINKTYPE_IPV6: u32 = 229;
+
+/// Unique artefacts of each kind reported per capture.
+const MAX_ARTEFACTS_PER_KIND: usize = 10_000;
+
+/// One captured packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Packet {
+    /// Offset of the packet record within the capture.
+    pub record_offset: u64,
+    /// Length of the packet record (header, data, and padding).
+    pub record_len: u64,
+    pub link_type: u32,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureFormat {
+    Pcap,
+    PcapNg,
+}
+
+enum State {
+    Pcap {
+        big_endian: bool,
+        nanos: bool,
+        snaplen: u32,
+        link_type: u32,
+    },
+    PcapNg {
+        big_endian: bool,
+        interfaces: Vec<u32>,
+    },
+}
+
+/// Sequential reader over the packets of a capture.
+pub struct PacketReader<R> {
+    reader: R,
+    state: State,
+    /// End of the last complete record.
+    pos: u64,
+}
+
+impl<R: Read> PacketReader<R> {
+    /// Read the file or section header. Returns `None` when `reader` does not
+    /// start with a valid capture header.
+    pub fn new(mut reader: R) -> io::Result<Option<Self>> {
+        let mut magic = [0u8; 4];
+        if !read_full(&mut reader, &mut magic)? {
+            return Ok(None);
+        }
+        if magic == PCAPNG_SHB_TYPE {
+            let mut fixed = [0u8; 8];
+            if !read_full(&mut reader, &mut fixed)? {
+                return Ok(None);
+            }
+            let big_endian = match bom_endianness(&fixed[4..8]) {
+                Some(big_endian) => big_endian,
+                None => return Ok(None),
+            };
+            let block_len = u32_at(&fixed, 0, big_endian);
+            let mut this = Self {
+                reader,
+                state: State::PcapNg {
+                    big_endian,
+                    interfaces: Vec::new(),
+                },
+                pos: 0,
+            };
+            if this.read_block_body(block_len, 4)?.is_none() {
+                return Ok(None);
+            }
+            return Ok(Some(this));
+        }
+
+        let Some(index) = PCAP_MAGICS.iter().position(|m| *m == magic) else {
+            return Ok(None);
+        };
+        let mut rest = [0u8; PCAP_HEADER_LEN - 4];
+        if !read_full(&mut reader, &mut rest)? {
+            return Ok(None);
+        }
+        let big_endian = index % 2 == 1;
+        let major = u16_at(&rest, 0, big_endian);
+        let snaplen = u32_at(&rest, 12, big_endian);
+        // The top bits of the link type field carry FCS information.
+        let link_type = u32_at(&rest, 16, big_endian) & 0x0FFF_FFFF;
+        if major != 2 {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            reader,
+            state: State::Pcap {
+                big_endian,
+                nanos: index >= 2,
+                snaplen,
+                link_type,
+            },
+            pos: PCAP_HEADER_LEN as u64,
+        }))
+    }
+
+    pub fn format(&self) -> CaptureFormat {
+        match self.state {
+            State::Pcap { .. } => CaptureFormat::Pcap,
+            State::PcapNg { .. } => CaptureFormat::PcapNg,
+        }
+    }
+
+    /// Length of the capture up to the end of the last valid record.
+    pub fn position(&self) -> u64 {
+        self.pos
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Next packet, or `None` at the end of the capture or the first invalid
+    /// or truncated record.
+    pub fn next_packet(&mut self) -> io::Result<Option<Packet>> {
+        loop {
+            let packet = match self.state {
+                State::Pcap { .. } => return self.next_pcap_record(),
+                State::PcapNg { .. } => self.next_pcapng_block()?,
+            };
+            match packet {
+                Step::Packet(packet) => return Ok(Some(packet)),
+                Step::Skipped => continue,
+                Step::End => return Ok(None),
+            }
+        }
+    }
+
+    fn next_pcap_record(&mut self) -> io::Result<Option<Packet>> {
+        let State::Pcap {
+            big_endian,
+            nanos,
+            snaplen,
+            link_type,
+        } = self.state
+        else {
+            return Ok(None);
+        };
+        let mut header = [0u8; PCAP_RECORD_HEADER_LEN];
+        if !read_full(&mut self.reader, &mut header)? {
+            return Ok(None);
+        }
+        let ts_frac = u32_at(&header, 4, big_endian);
+        let incl_len = u32_at(&header, 8, big_endian);
+        let orig_len = u32_at(&header, 12, big_endian);
+        let frac_limit = if nanos { 1_000_000_000 } else { 1_000_000 };
+        if ts_frac >= frac_limit
+            || incl_len == 0
+            || incl_len > MAX_PACKET_LEN
+            || (snaplen > 0 && incl_len > snaplen)
+            || orig_len < incl_len
+        {
+            return Ok(None);
+        }
+        let mut data = vec![0u8; incl_len as usize];
+        if !read_full(&mut self.reader, &mut data)? {
+            return Ok(None);
+        }
+        let record_len = (PCAP_RECORD_HEADER_LEN as u32 + incl_len) as u64;
+        let packet = Packet {
+            record_offset: self.pos,
+            record_len,
+            link_type,
+            data,
+        };
+        self.pos += record_len;
+        Ok(Some(packet))
+    }
+
+    fn next_pcapng_block(&mut self) -> io::Result<Step> {
+        let State::PcapNg { big_endian, .. } = self.state else {
+            return Ok(Step::End);
+        };
+        let mut header = [0u8; 8];
+        if !read_full(&mut self.reader, &mut header)? {
+            return Ok(Step::End);
+        }
+        let block_type = if header[..4] == PCAPNG_SHB_TYPE {
+            BLOCK_SHB
+        } else {
+            u32_at(&header, 0, big_endian)
+        };
+        if block_type == BLOCK_SHB {
+            // A new section may switch byte order.
+            let mut bom = [0u8; 4];
+            if !read_full(&mut self.reader, &mut bom)? {
+                return Ok(Step::End);
+            }
+            let Some(section_big_endian) = bom_endianness(&bom) else {
+                return Ok(Step::End);
+            };
+            self.state = State::PcapNg {
+                big_endian: section_big_endian,
+                interfaces: Vec::new(),
+            };
+            let block_len = u32_at(&header, 4, section_big_endian);
+            return Ok(match self.read_block_body(block_len, 4)? {
+                Some(_) => Step::Skipped,
+                None => Step::End,
+            });
+        }
+
+        let block_len = u32_at(&header, 4, big_endian);
+        let record_offset = self.pos;
+        let Some(body) = self.read_block_body(block_len, 0)? else {
+            return Ok(Step::End);
+        };
+        let State::PcapNg { interfaces, .. } = &mut self.state else {
+            return Ok(Step::End);
+        };
+        let (interface, data) = match block_type {
+            BLOCK_IDB if body.len() >= 2 => {
+                interfaces.push(u16_at(&body, 0, big_endian) as u32);
+                return Ok(Step::Skipped);
+            }
+            BLOCK_EPB | BLOCK_PACKET if body.len() >= 20 => {
+                let interface = if block_type == BLOCK_EPB {
+                    u32_at(&body, 0, big_endian)
+                } else {
+                    u16_at(&body, 0, big_endian) as u32
+                };
+                let cap_len = u32_at(&body, 12, big_endian) as usize;
+                let end = 20usize.saturating_add(cap_len).min(body.len());
+                (interface, body[20..end].to_vec())
+            }
+            BLOCK_SPB if body.len() >= 4 => {
+                let orig_len = u32_at(&body, 0, big_endian) as usize;
+                let end = 4usize.saturating_add(orig_len).min(body.len());
+                (0, body[4..end].to_vec())
+            }
+            _ => return Ok(Step::Skipped),
+        };
+        let Some(&link_type) = interfaces.get(interface as usize) else {
+            return Ok(Step::Skipped);
+        };
+        Ok(Step::Packet(Packet {
+            record_offset,
+            record_len: block_len as u64,
+            link_type,
+            data,
+        }))
+    }
+
+    /// Read the body and trailing length of a block after its 8-byte header
+    /// (and `already` further bytes). Advances `pos` when the block is valid.
+    fn read_block_body(&mut self, block_len: u32, already: usize) -> io::Result<Option<Vec<u8>>> {
+        let big_endian = match self.state {
+            State::PcapNg { big_endian, .. } => big_endian,
+            State::Pcap { .. } => return Ok(None),
+        };
+        if block_len < 12 + already as u32
+            || !block_len.is_multiple_of(4)
+            || block_len > MAX_BLOCK_LEN
+        {
+            return Ok(None);
+        }
+        let mut body = vec![0u8; block_len as usize - 12 - already];
+        let mut trailer = [0u8; 4];
+        if !read_full(&mut self.reader, &mut body)? || !read_full(&mut self.reader, &mut trailer)? {
+            return Ok(None);
+        }
+        if u32_at(&trailer, 0, big_endian) != block_len {
+            return Ok(None);
+        }
+        self.pos += block_len as u64;
+        Ok(Some(body))
+    }
+}
+
+enum Step {
+    Packet(Packet),
+    Skipped,
+    End,
+}
+
+/// A network artefact decoded from a packet.
+#[derive(Debug, Clone)]
+pub struct PacketArtefact {
+    pub kind: ArtefactKind,
+    pub value: String,
+    /// Record offset within the capture of the first packet it was seen in.
+    pub record_offset: u64,
+    pub record_len: u64,
+}
+
+/// Unique connection tuples, DNS query names, and HTTP hosts in a capture, in
+/// order of first appearance.
+pub fn extract_artefacts<R: Read>(reader: &mut PacketReader<R>) -> io::Result<Vec<PacketArtefact>> {
+    let mut seen: HashSet<(u8, String)> = HashSet::new();
+    let mut counts = [0usize; 3];
+    let mut out = Vec::new();
+    while let Some(packet) = reader.next_packet()? {
+        let Some(segment) = decode(&packet) else {
+            continue;
+        };
+        let mut found = vec![(
+            ArtefactKind::Connection,
+            format!(
+                "{} {} -> {}",
+                segment.protocol,
+                SocketAddr::new(segment.src, segment.src_port),
+                SocketAddr::new(segment.dst, segment.dst_port)
+            ),
+        )];
+        if segment.protocol == "udp" && (segment.src_port == 53 || segment.dst_port == 53) {
+            found.extend(dns_question(segment.payload).map(|q| (ArtefactKind::DnsQuery, q)));
+        }
+        if segment.protocol == "tcp" {
+            found.extend(http_host(segment.payload).map(|h| (ArtefactKind::HttpHost, h)));
+        }
+        for (kind, value) in found {
+            let slot = match kind {
+                ArtefactKind::Connection => 0,
+                ArtefactKind::DnsQuery => 1,
+                _ => 2,
+            };
+            if counts[slot] >= MAX_ARTEFACTS_PER_KIND || !seen.insert((slot as u8, value.clone())) {
+                continue;
+            }
+            counts[slot] += 1;
+            out.push(PacketArtefact {
+                kind,
+                value,
+                record_offset: packet.record_offset,
+                record_len: packet.record_len,
+            });
+        }
+    }
+    Ok(out)
+}
+
+struct Segment<'a> {
+    protocol: &'static str,
+    src: IpAddr,
+    dst: IpAddr,
+    src_port: u16,
+    dst_port: u16,
+    payload: &'a [u8],
+}
+
+fn decode(packet: &Packet) -> Option<Segment<'_>> {
+    let data = packet.data.as_slice();
+    let ip = match packet.link_type {
+        LINKTYPE_ETHERNET => {
+            let mut ethertype = be16(data, 12)?;
+            let mut offset = 14;
+            // 802.1Q / 802.1ad VLAN tags.
+            while ethertype == 0x8100 || ethertype == 0x88A8 {
+                ethertype = be16(data, offset + 2)?;
+                offset += 4;
+            }
+            match ethertype {
+                0x0800 | 0x86DD => data.get(offset..)?,
+                _ => return None,
+            }
+        }
+        LINKTYPE_LINUX_SLL => match be16(data, 14)? {
+            0x0800 | 0x86DD => data.get(16..)?,
+            _ => return None,
+        },
+        LINKTYPE_NULL => data.get(4..)?,
+        LINKTYPE_RAW | LINKTYPE_RAW_BSD | LINKTYPE_RAW_OPENBSD | LINKTYPE_IPV4 | LINKTYPE_IPV6 => {
+            data
+        }
+        _ => return None,
+    };
+    decode_ip(ip)
+}
+
+fn decode_ip(ip: &[u8]) -> Option<Segment<'_>> {
+    let (protocol, src, dst, transport) = match ip.first()? >> 4 {
+        4 => {
+            let ihl = ((ip[0] & 0x0F) as usize) * 4;
+            // Snap-length captures may cut the packet inside its own header.
+            if ihl < 20 || ihl > ip.len() {
+                return None;
+            }
+            let total = (be16(ip, 2)? as usize).clamp(ihl, ip.len());
+            // Only the first fragment carries the transport header.
+            if be16(ip, 6)? & 0x1FFF != 0 {
+                return None;
+            }
+            let src: [u8; 4] = ip.get(12..16)?.try_into().ok()?;
+            let dst: [u8; 4] = ip.get(16..20)?.try_into().ok()?;
+            (
+                ip[9],
+                IpAddr::V4(Ipv4Addr::from(src)),
+                IpAddr::V4(Ipv4Addr::from(dst)),
+                ip.get(ihl..total)?,
+            )
+        }
+        6 => {
+            let src: [u8; 16] = ip.get(8..24)?.try_into().ok()?;
+            let dst: [u8; 16] = ip.get(24..40)?.try_into().ok()?;
+            let end = (40 + be16(ip, 4)? as usize).min(ip.len());
+            (
+                ip[6],
+                IpAddr::V6(Ipv6Addr::from(src)),
+                IpAddr::V6(Ipv6Addr::from(dst)),
+                ip.get(40..end)?,
+            )
+        }
+        _ => return None,
+    };
+    let (protocol, header_len) = match protocol {
+        6 => ("tcp", ((*transport.get(12)? >> 4) as usize) * 4),
+        17 => ("udp", 8),
+        _ => return None,
+    };
+    if header_len < 8 || (protocol == "tcp" && header_len < 20) {
+        return None;
+    }
+    Some(Segment {
+        protocol,
+        src,
+        dst,
+        src_port: be16(transport, 0)?,
+        dst_port: be16(transport, 2)?,
+        payload: transport.get(header_len..)?,
+    })
+}
+
+/// Name in the first question of a DNS message.
+fn dns_question(msg: &[u8]) -> Option<String> {
+    if be16(msg, 4)? == 0 {
+        return None;
+    }
+    let mut labels = Vec::new();
+    let mut pos = 12;
+    loop {
+        let len = *msg.get(pos)? as usize;
+        if len == 0 {
+            break;
+        }
+        // Compression pointers do not occur in the first question.
+        if len > 63 || labels.len() >= 127 {
+            return None;
+        }
+        let label = msg.get(pos + 1..pos + 1 + len)?;
+        if !label
+            .iter()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_'))
+        {
+            return None;
+        }
+        labels.push(String::from_utf8_lossy(label).to_ascii_lowercase());
+        pos += 1 + len;
+    }
+    (!labels.is_empty()).then(|| labels.join("."))
+}
+
+/// `Host` header of an HTTP/1.x request at the start of a TCP payload.
+fn http_host(payload: &[u8]) -> Option<String> {
+    const METHODS: [&[u8]; 8] = [
+        b"GET ",
+        b"POST ",
+        b"HEAD ",
+        b"PUT ",
+        b"DELETE ",
+        b"OPTIONS ",
+        b"PATCH ",
+        b"CONNECT ",
+    ];
+    if !METHODS.iter().any(|m| payload.starts_with(m)) {
+        return None;
+    }
+    let head_end = payload
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .unwrap_or(payload.len());
+    let head = std::str::from_utf8(&payload[..head_end]).ok()?;
+    head.split("\r\n").skip(1).find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        let value = value.trim();
+        (name.eq_ignore_ascii_case("host") && !value.is_empty()).then(|| value.to_ascii_lowercase())
+    })
+}
+
+fn bom_endianness(bytes: &[u8]) -> Option<bool> {
+    if u32_at(bytes, 0, false) == PCAPNG_BYTE_ORDER_MAGIC {
+        Some(false)
+    } else if u32_at(bytes, 0, true) == PCAPNG_BYTE_ORDER_MAGIC {
+        Some(true)
+    } else {
+        None
+    }
+}
+
+/// Fill `buf`; `false` on a clean or partial EOF.
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<bool> {
+    match reader.read_exact(buf) {
+        Ok(()) => Ok(true),
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+fn u32_at(bytes: &[u8], offset: usize, big_endian: bool) -> u32 {
+    let raw: [u8; 4] = bytes[offset..offset + 4].try_into().expect("4 bytes");
+    if big_endian {
+        u32::from_be_bytes(raw)
+    } else {
+        u32::from_le_bytes(raw)
+    }
+}
+
+fn u16_at(bytes: &[u8], offset: usize, big_endian: bool) -> u16 {
+    let raw = [bytes[offset], bytes[offset + 1]];
+    if big_endian {
+        u16::from_be_bytes(raw)
+    } else {
+        u16::from_le_bytes(raw)
+    }
+}
+
+fn be16(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(
+        bytes.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+/// Ethernet/IPv4 frame for tests.
+#[cfg(test)]
+pub(crate) fn ipv4_frame(protocol: u8, src_port: u16, dst_port: u16, payload: &[u8]) -> Vec<u8> {
+    let header_len = if protocol == 6 { 20 } else { 8 };
+    let mut transport = Vec::new();
+    transport.extend_from_slice(&src_port.to_be_bytes());
+    transport.extend_from_slice(&dst_port.to_be_bytes());
+    if protocol == 6 {
+        transport.extend_from_slice(&[0; 8]);
+        transport.push(5 << 4);
+        transport.extend_from_slice(&[0; 7]);
+    } else {
+        transport.extend_from_slice(&((header_len + payload.len()) as u16).to_be_bytes());
+        transport.extend_from_slice(&[0; 2]);
+    }
+    transport.extend_from_slice(payload);
+
+    let mut frame = vec![0u8; 12];
+    frame.extend_from_slice(&0x0800u16.to_be_bytes());
+    frame.push(0x45);
+    frame.push(0);
+    frame.extend_from_slice(&((20 + transport.len()) as u16).to_be_bytes());
+    frame.extend_from_slice(&[0, 0, 0, 0, 64, protocol, 0, 0]);
+    frame.extend_from_slice(&[10, 0, 0, 1]);
+    frame.extend_from_slice(&[93, 184, 216, 34]);
+    frame.extend_from_slice(&transport);
+    frame
+}
+
+/// Little-endian microsecond pcap with Ethernet frames, for tests.
+#[cfg(test)]
+pub(crate) fn pcap_bytes(frames: &[Vec<u8>]) -> Vec<u8> {
+    let mut out = PCAP_MAGICS[0].to_vec();
+    out.extend_from_slice(&2u16.to_le_bytes());
+    out.extend_from_slice(&4u16.to_le_bytes());
+    out.extend_from_slice(&[0; 8]);
+    out.extend_from_slice(&65535u32.to_le_bytes());
+    out.extend_from_slice(&LINKTYPE_ETHERNET.to_le_bytes());
+    for (i, frame) in frames.iter().enumerate() {
+        out.extend_from_slice(&(1_700_000_000u32 + i as u32).to_le_bytes());
+        out.extend_from_slice(&500u32.to_le_bytes());
+        out.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        out.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        out.extend_from_slice(frame);
+    }
+    out
+}
+
+/// Little-endian pcapng with one Ethernet interface, for tests.
+#[cfg(test)]
+pub(crate) fn pcapng_bytes(frames: &[Vec<u8>]) -> Vec<u8> {
+    fn block(out: &mut Vec<u8>, block_type: u32, body: &[u8]) {
+        let padded = body.len().div_ceil(4) * 4;
+        let len = (12 + padded) as u32;
+        out.extend_from_slice(&block_type.to_le_bytes());
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(body);
+        out.resize(out.len() + padded - body.len(), 0);
+        out.extend_from_slice(&len.to_le_bytes());
+    }
+    let mut out = Vec::new();
+    let mut shb = PCAPNG_BYTE_ORDER_MAGIC.to_le_bytes().to_vec();
+    shb.extend_from_slice(&1u16.to_le_bytes());
+    shb.extend_from_slice(&0u16.to_le_bytes());
+    shb.extend_from_slice(&(-1i64).to_le_bytes());
+    block(&mut out, BLOCK_SHB, &shb);
+    let mut idb = (LINKTYPE_ETHERNET as u16).to_le_bytes().to_vec();
+    idb.extend_from_slice(&[0, 0]);
+    idb.extend_from_slice(&65535u32.to_le_bytes());
+    block(&mut out, BLOCK_IDB, &idb);
+    for frame in frames {
+        let mut epb = Vec::new();
+        epb.extend_from_slice(&0u32.to_le_bytes());
+        epb.extend_from_slice(&[0; 8]);
+        epb.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        epb.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        epb.extend_from_slice(frame);
+        block(&mut out, BLOCK_EPB, &epb);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dns_query(name: &str) -> Vec<u8> {
+        let mut msg = vec![0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+        for label in name.split('.') {
+            msg.push(label.len() as u8);
+            msg.extend_from_slice(label.as_bytes());
+        }
+        msg.extend_from_slice(&[0, 0, 1, 0, 1]);
+        msg
+    }
+
+    fn frames() -> Vec<Vec<u8>> {
+        vec![
+            ipv4_frame(17, 53000, 53, &dns_query("Example.COM")),
+            ipv4_frame(
+                6,
+                49152,
+                80,
+                b"GET / HTTP/1.1\r\nHost: example.com\r\nAccept: */*\r\n\r\n",
+            ),
+            ipv4_frame(6, 49152, 80, b"more data"),
+        ]
+    }
+
+    fn artefacts(capture: &[u8]) -> (Vec<PacketArtefact>, u64) {
+        let mut reader = PacketReader::new(capture).expect("read").expect("capture");
+        let found = extract_artefacts(&mut reader).expect("extract");
+        (found, reader.position())
+    }
+
+    #[test]
+    fn extracts_artefacts_from_pcap_and_stops_at_garbage() {
+        let mut capture = pcap_bytes(&frames());
+        let len = capture.len() as u64;
+        capture.extend_from_slice(&[0u8; 64]);
+        let (found, end) = artefacts(&capture);
+        assert_eq!(end, len);
+
+        let values: Vec<(&ArtefactKind, &str)> =
+            found.iter().map(|a| (&a.kind, a.value.as_str())).collect();
+        assert_eq!(values.len(), 4, "{values:?}");
+        assert!(matches!(
+            values[0],
+            (
+                ArtefactKind::Connection,
+                "udp 10.0.0.1:53000 -> 93.184.216.34:53"
+            )
+        ));
+        assert!(matches!(values[1], (ArtefactKind::DnsQuery, "example.com")));
+        assert!(matches!(
+            values[2],
+            (
+                ArtefactKind::Connection,
+                "tcp 10.0.0.1:49152 -> 93.184.216.34:80"
+            )
+        ));
+        assert!(matches!(values[3], (ArtefactKind::HttpHost, "example.com")));
+        assert_eq!(found[0].record_offset, PCAP_HEADER_LEN as u64);
+    }
+
+    #[test]
+    fn skips_packets_cut_inside_their_headers() {
+        // IHL of 15 words in a 24-byte IPv4 packet.
+        let mut short_ip = ipv4_frame(17, 53000, 53, b"");
+        short_ip.truncate(14 + 24);
+        short_ip[14] = 0x4F;
+        // TCP data offset of 2 words.
+        let mut short_tcp = ipv4_frame(6, 49152, 80, b"GET / HTTP/1.1\r\n\r\n");
+        short_tcp[14 + 20 + 12] = 2 << 4;
+        let (found, _) = artefacts(&pcap_bytes(&[short_ip, short_tcp]));
+        assert!(found.is_empty(), "{found:?}");
+    }
+
+    #[test]
+    fn reads_pcapng_blocks() {
+        let capture = pcapng_bytes(&frames());
+        let (found, end) = artefacts(&capture);
+        assert_eq!(end, capture.len() as u64);
+        assert_eq!(found.len(), 4);
+
+        // A truncated final block ends the capture at the previous block.
+        let truncated = &capture[..capture.len() - 2];
+        let mut reader = PacketReader::new(truncated)
+            .expect("read")
+            .expect("capture");
+        assert_eq!(reader.format(), CaptureFormat::PcapNg);
+        let mut packets = 0;
+        while reader.next_packet().expect("packet").is_some() {
+            packets += 1;
+        }
+        assert_eq!(packets, 2);
+        assert!(reader.position() < truncated.len() as u64);
+    }
+}
@@ -0,0 +1,551 @@
+//! Portable Executable (PE) headers: the MS-DOS header, the COFF file header,
+//! the optional header with its data directories, and the section table.
+//!
+//! A PE file has no size field. The raw data of every section lies at
+//! `PointerToRawData` for `SizeOfRawData` bytes, and an Authenticode
+//! signature is appended after the sections, located by the certificate
+//! table directory, which unlike the other directories holds a file offset.
+//! The end of the last of these is the end of the image as the linker and
+//! signing tool wrote it; overlay data appended later (installer payloads) is
+//! not described by any header.
+
+use chrono::{DateTime, NaiveDateTime};
+
+use super::executable::ExecutableRecord;
+
+/// `e_lfanew` must point inside this many bytes; linkers put the PE header
+/// within the first few hundred.
+pub const MAX_PE_OFFSET: u32 = 0x1_0000;
+/// Sections a loader accepts.
+const MAX_SECTIONS: usize = 96;
+const SECTION_HEADER_LEN: usize = 40;
+const FILE_HEADER_LEN: usize = 20;
+
+const PE32_MAGIC: u16 = 0x10B;
+const PE32_PLUS_MAGIC: u16 = 0x20B;
+const IMAGE_FILE_EXECUTABLE_IMAGE: u16 = 0x0002;
+const IMAGE_FILE_DLL: u16 = 0x2000;
+const SUBSYSTEM_NATIVE: u16 = 1;
+const DIRECTORY_IMPORT: usize = 1;
+const DIRECTORY_SECURITY: usize = 4;
+
+/// Import descriptors and thunks read before the import walk gives up.
+const MAX_IMPORT_DLLS: usize = 4096;
+const MAX_IMPORTS_PER_DLL: usize = 65_536;
+const MAX_NAME_LEN: usize = 256;
+
+/// Why [`parse_headers`] could not read a PE image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PeError {
+    /// The headers need this many bytes from the start of the image.
+    Incomplete(usize),
+    Invalid(&'static str),
+}
+
+/// One entry of the section table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    pub name: String,
+    pub virtual_address: u32,
+    pub virtual_size: u32,
+    pub raw_offset: u32,
+    pub raw_size: u32,
+}
+
+/// The headers of a PE image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeHeaders {
+    pub machine: u16,
+    pub characteristics: u16,
+    /// `TimeDateStamp`; a build hash rather than a time for reproducible
+    /// builds.
+    pub timestamp: u32,
+    /// PE32+ (64-bit) optional header.
+    pub is_64: bool,
+    pub subsystem: u16,
+    pub size_of_headers: u32,
+    pub size_of_image: u32,
+    /// Data directories as (RVA, size); the certificate table's first field
+    /// is a file offset.
+    pub directories: Vec<(u32, u32)>,
+    pub sections: Vec<Section>,
+}
+
+/// Parse the headers at the start of `data`.
+pub fn parse_headers(data: &[u8]) -> Result<PeHeaders, PeError> {
+    if data.len() < 0x40 {
+        return Err(PeError::Incomplete(0x40));
+    }
+    if &data[..2] != b"MZ" {
+        return Err(PeError::Invalid("no MZ signature"));
+    }
+    let pe_offset = le_u32(data, 0x3C);
+    if !(0x40..MAX_PE_OFFSET).contains(&pe_offset) || !pe_offset.is_multiple_of(4) {
+        return Err(PeError::Invalid("e_lfanew out of range"));
+    }
+    let pe = pe_offset as usize;
+    let file_header = pe + 4;
+    let optional = file_header + FILE_HEADER_LEN;
+    if data.len() < optional + 2 {
+        return Err(PeError::Incomplete(optional + 2));
+    }
+    if &data[pe..pe + 4] != b"PE\0\0" {
+        return Err(PeError::Invalid("no PE signature"));
+    }
+    let machine = le_u16(data, file_header);
+    let section_count = le_u16(data, file_header + 2) as usize;
+    let timestamp = le_u32(data, file_header + 4);
+    let optional_len = le_u16(data, file_header + 16) as usize;
+    let characteristics = le_u16(data, file_header + 18);
+    if characteristics & IMAGE_FILE_EXECUTABLE_IMAGE == 0 {
+        return Err(PeError::Invalid("not an executable image"));
+    }
+    if section_count == 0 || section_count > MAX_SECTIONS {
+        return Err(PeError::Invalid("section count out of range"));
+    }
+    let is_64 = match le_u16(data, optional) {
+        PE32_MAGIC => false,
+        PE32_PLUS_MAGIC => true,
+        _ => return Err(PeError::Invalid("unknown optional header magic")),
+    };
+    let directories_at = if is_64 { 112 } else { 96 };
+    if optional_len < directories_at {
+        return Err(PeError::Invalid("optional header too short"));
+    }
+    let table = optional + optional_len;
+    let headers_end = table + section_count * SECTION_HEADER_LEN;
+    if data.len() < headers_end {
+        return Err(PeError::Incomplete(headers_end));
+    }
+
+    let file_alignment = le_u32(data, optional + 36);
+    // Tiny images go down to 32-byte alignment; the specification allows up
+    // to 64 KiB.
+    if !file_alignment.is_power_of_two() || !(0x20..=0x1_0000).contains(&file_alignment) {
+        return Err(PeError::Invalid("file alignment out of range"));
+    }
+    let size_of_image = le_u32(data, optional + 56);
+    let size_of_headers = le_u32(data, optional + 60);
+    if (size_of_headers as usize) < headers_end.min(0x200) || size_of_headers > size_of_image {
+        return Err(PeError::Invalid("SizeOfHeaders inconsistent"));
+    }
+    let subsystem = le_u16(data, optional + 68);
+    let directory_count = le_u32(data, optional + directories_at - 4) as usize;
+    let directory_count = directory_count.min((optional_len - directories_at) / 8);
+    let directories = (0..directory_count)
+        .map(|i| {
+            let at = optional + directories_at + i * 8;
+            (le_u32(data, at), le_u32(data, at + 4))
+        })
+        .collect();
+
+    let sections = (0..section_count)
+        .map(|i| {
+            let at = table + i * SECTION_HEADER_LEN;
+            let name = &data[at..at + 8];
+            let name_len = name.iter().position(|&b| b == 0).unwrap_or(8);
+            Section {
+                name: String::from_utf8_lossy(&name[..name_len]).into_owned(),
+                virtual_size: le_u32(data, at + 8),
+                virtual_address: le_u32(data, at + 12),
+                raw_size: le_u32(data, at + 16),
+                raw_offset: le_u32(data, at + 20),
+            }
+        })
+        .collect::<Vec<_>>();
+    for section in &sections {
+        if section.raw_size > 0 && section.raw_offset < headers_end as u32 {
+            return Err(PeError::Invalid("section data overlaps the headers"));
+        }
+        if section.virtual_address >= size_of_image {
+            return Err(PeError::Invalid("section beyond SizeOfImage"));
+        }
+    }
+
+    Ok(PeHeaders {
+        machine,
+        characteristics,
+        timestamp,
+        is_64,
+        subsystem,
+        size_of_headers,
+        size_of_image,
+        directories,
+        sections,
+    })
+}
+
+impl PeHeaders {
+    /// End of the headers, the section data, and the certificate table.
+    pub fn file_size(&self) -> u64 {
+        let sections = self
+            .sections
+            .iter()
+            .filter(|s| s.raw_size > 0)
+            .map(|s| u64::from(s.raw_offset) + u64::from(s.raw_size))
+            .max()
+            .unwrap_or(0);
+        let end = sections.max(u64::from(self.size_of_headers));
+        match self.certificate_table() {
+            Some((offset, size)) => end.max(u64::from(offset) + u64::from(size)),
+            None => end,
+        }
+    }
+
+    /// File offset and size of the Authenticode signature.
+    pub fn certificate_table(&self) -> Option<(u32, u32)> {
+        self.directories
+            .get(DIRECTORY_SECURITY)
+            .copied()
+            .filter(|&(offset, size)| offset > 0 && size > 0)
+    }
+
+    /// `dll` for the DLL flag, `sys` for native images (drivers), else `exe`.
+    pub fn kind(&self) -> &'static str {
+        if self.characteristics & IMAGE_FILE_DLL != 0 {
+            "dll"
+        } else if self.subsystem == SUBSYSTEM_NATIVE {
+            "sys"
+        } else {
+            "exe"
+        }
+    }
+
+    pub fn architecture(&self) -> &'static str {
+        match self.machine {
+            0x014C => "x86",
+            0x8664 => "x86_64",
+            0xAA64 => "arm64",
+            0x01C0 | 0x01C2 | 0x01C4 => "arm",
+            0x0200 => "ia64",
+            0x0EBC => "efi_bytecode",
+            0x5064 => "riscv64",
+            _ => "unknown",
+        }
+    }
+
+    pub fn subsystem_name(&self) -> Option<&'static str> {
+        Some(match self.subsystem {
+            1 => "native",
+            2 => "windows_gui",
+            3 => "windows_cui",
+            7 => "posix_cui",
+            9 => "windows_ce_gui",
+            10 => "efi_application",
+            11 => "efi_boot_service_driver",
+            12 => "efi_runtime_driver",
+            13 => "efi_rom",
+            14 => "xbox",
+            16 => "windows_boot_application",
+            _ => return None,
+        })
+    }
+
+    /// `TimeDateStamp` as a time; `None` when it is zero.
+    pub fn compile_time(&self) -> Option<NaiveDateTime> {
+        (self.timestamp != 0)
+            .then(|| DateTime::from_timestamp(i64::from(self.timestamp), 0))
+            .flatten()
+            .map(|t| t.naive_utc())
+    }
+
+    /// File offset of `rva`, through the section holding it.
+    pub fn rva_to_offset(&self, rva: u32) -> Option<u64> {
+        if rva < self.size_of_headers {
+            return Some(u64::from(rva));
+        }
+        self.sections.iter().find_map(|s| {
+            let span = s.virtual_size.max(s.raw_size);
+            let delta = rva.checked_sub(s.virtual_address)?;
+            (delta < span && delta < s.raw_size).then(|| u64::from(s.raw_offset) + u64::from(delta))
+        })
+    }
+
+    /// Imported functions as `dll.function`, lower case, with the `.dll`,
+    /// `.ocx`, or `.sys` extension dropped and imports by ordinal written as
+    /// `ordN`. `read` fills a buffer from a file offset and returns the bytes
+    /// read. `None` without an import directory or when it cannot be read;
+    /// the walk stops at an address past the end of the address space.
+    pub fn imports(&self, read: &mut dyn FnMut(u64, &mut [u8]) -> usize) -> Option<Vec<String>> {
+        let &(rva, size) = self.directories.get(DIRECTORY_IMPORT)?;
+        if rva == 0 || size == 0 {
+            return None;
+        }
+        let thunk_len = if self.is_64 { 8 } else { 4 };
+        let ordinal_flag = if self.is_64 { 1u64 << 63 } else { 1u64 << 31 };
+        let mut imports = Vec::new();
+        for index in 0..MAX_IMPORT_DLLS as u32 {
+            let mut descriptor = [0u8; 20];
+            let Some(descriptor_rva) = index.checked_mul(20).and_then(|o| rva.checked_add(o))
+            else {
+                break;
+            };
+            let at = self.rva_to_offset(descriptor_rva)?;
+            if read(at, &mut descriptor) < descriptor.len() {
+                return None;
+            }
+            if descriptor.iter().all(|&b| b == 0) {
+                return Some(imports);
+            }
+            let name = self.read_name(le_u32(&descriptor, 12), read)?;
+            let dll = name.to_ascii_lowercase();
+            let dll = match dll.rsplit_once('.') {
+                Some((stem, "dll" | "ocx" | "sys")) => stem.to_string(),
+                _ => dll,
+            };
+            let thunks = match le_u32(&descriptor, 0) {
+                0 => le_u32(&descriptor, 16),
+                original => original,
+            };
+            for slot in 0..MAX_IMPORTS_PER_DLL as u32 {
+                let mut thunk = [0u8; 8];
+                let Some(thunk_rva) = slot
+                    .checked_mul(thunk_len as u32)
+                    .and_then(|o| thunks.checked_add(o))
+                else {
+                    break;
+                };
+                let at = self.rva_to_offset(thunk_rva)?;
+                if read(at, &mut thunk[..thunk_len]) < thunk_len {
+                    return None;
+                }
+                let value = u64::from_le_bytes(thunk);
+                if value == 0 {
+                    break;
+                }
+                if value & ordinal_flag != 0 {
+                    imports.push(format!("{dll}.ord{}", value & 0xFFFF));
+                } else {
+                    // The hint comes first, then the name.
+                    let Some(name_rva) = u32::try_from(value).ok().and_then(|v| v.checked_add(2))
+                    else {
+                        break;
+                    };
+                    let function = self.read_name(name_rva, read)?;
+                    imports.push(format!("{dll}.{}", function.to_ascii_lowercase()));
+                }
+            }
+        }
+        Some(imports)
+    }
+
+    fn read_name(&self, rva: u32, read: &mut dyn FnMut(u64, &mut [u8]) -> usize) -> Option<String> {
+        let mut buf = [0u8; MAX_NAME_LEN];
+        let n = read(self.rva_to_offset(rva)?, &mut buf);
+        let len = buf[..n].iter().position(|&b| b == 0)?;
+        (len > 0).then(|| String::from_utf8_lossy(&buf[..len]).into_owned())
+    }
+
+    pub fn to_record(
+        &self,
+        run_id: &str,
+        source_file: &str,
+        imports: Option<&[String]>,
+    ) -> ExecutableRecord {
+        ExecutableRecord {
+            run_id: run_id.to_string(),
+            format: "pe".to_string(),
+            kind: self.kind().to_string(),
+            architecture: self.architecture().to_string(),
+            bits: if self.is_64 { 64 } else { 32 },
+            compile_time_utc: self.compile_time(),
+            subsystem: self.subsystem_name().map(str::to_string),
+            section_count: self.sections.len() as u32,
+            import_count: imports.map(|i| i.len() as u32),
+            import_hash: imports
+                .filter(|i| !i.is_empty())
+                .map(|i| format!("{:x}", md5::compute(i.join(",")))),
+            signed: self.certificate_table().is_some(),
+            source_file: source_file.to_string(),
+        }
+    }
+}
+
+fn le_u16(data: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([data[at], data[at + 1]])
+}
+
+fn le_u32(data: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(data[at..at + 4].try_into().unwrap_or_default())
+}
+
+#[cfg(test)]
+pub(crate) mod testing {
+    /// 2024-04-29 21:13:56 UTC.
+    pub(crate) const TIMESTAMP: u32 = 0x6630_0D34;
+    /// Length of [`pe_bytes`]: two sections and a certificate table.
+    pub(crate) const PE_LEN: usize = 0x618;
+
+    /// A PE32+ console executable for x86_64 with a `.text` and an `.rdata`
+    /// section, importing `CreateFileW` and ordinal 16 from `KERNEL32.dll`,
+    /// and a 24-byte certificate table.
+    pub(crate) fn pe_bytes() -> Vec<u8> {
+        let mut data = vec![0u8; PE_LEN];
+        let put16 = |d: &mut Vec<u8>, at: usize, v: u16| {
+            d[at..at + 2].copy_from_slice(&v.to_le_bytes());
+        };
+        let put32 = |d: &mut Vec<u8>, at: usize, v: u32| {
+            d[at..at + 4].copy_from_slice(&v.to_le_bytes());
+        };
+        data[..2].copy_from_slice(b"MZ");
+        put32(&mut data, 0x3C, 0x80);
+        data[0x80..0x84].copy_from_slice(b"PE\0\0");
+        put16(&mut data, 0x84, 0x8664);
+        put16(&mut data, 0x86, 2);
+        put32(&mut data, 0x88, TIMESTAMP);
+        put16(&mut data, 0x94, 0xF0);
+        put16(&mut data, 0x96, 0x0022);
+        let opt = 0x98;
+        put16(&mut data, opt, 0x20B);
+        put32(&mut data, opt + 32, 0x1000);
+        put32(&mut data, opt + 36, 0x200);
+        put32(&mut data, opt + 56, 0x3000);
+        put32(&mut data, opt + 60, 0x200);
+        put16(&mut data, opt + 68, 3);
+        put32(&mut data, opt + 108, 16);
+        // Import directory and certificate table.
+        put32(&mut data, opt + 112 + 8, 0x2000);
+        put32(&mut data, opt + 112 + 12, 40);
+        put32(&mut data, opt + 112 + 32, 0x600);
+        put32(&mut data, opt + 112 + 36, 0x18);
+        let table = opt + 0xF0;
+        for (i, (name, va, raw)) in [(b".text", 0x1000, 0x200), (b".rdat", 0x2000, 0x400)]
+            .into_iter()
+            .enumerate()
+        {
+            let at = table + i * 40;
+            data[at..at + 5].copy_from_slice(name);
+            put32(&mut data, at + 8, 0x200);
+            put32(&mut data, at + 12, va);
+            put32(&mut data, at + 16, 0x200);
+            put32(&mut data, at + 20, raw);
+        }
+        // .rdata at file offset 0x400, RVA 0x2000.
+        let rdata = |rva: usize| 0x400 + rva - 0x2000;
+        put32(&mut data, rdata(0x2000), 0x2040);
+        put32(&mut data, rdata(0x2000) + 12, 0x2080);
+        put32(&mut data, rdata(0x2000) + 16, 0x2040);
+        data[rdata(0x2040)..rdata(0x2040) + 8].copy_from_slice(&0x2090u64.to_le_bytes());
+        data[rdata(0x2048)..rdata(0x2048) + 8]
+            .copy_from_slice(&(0x8000_0000_0000_0010u64).to_le_bytes());
+        data[rdata(0x2080)..rdata(0x2080) + 12].copy_from_slice(b"KERNEL32.dll");
+        data[rdata(0x2092)..rdata(0x2092) + 11].copy_from_slice(b"CreateFileW");
+        put32(&mut data, 0x600, 0x18);
+        put16(&mut data, 0x604, 0x200);
+        put16(&mut data, 0x606, 2);
+        data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::{PE_LEN, TIMESTAMP, pe_bytes};
+    use super::*;
+
+    fn read_from(data: &[u8]) -> impl FnMut(u64, &mut [u8]) -> usize + '_ {
+        |offset, buf| {
+            let start = (offset as usize).min(data.len());
+            let n = buf.len().min(data.len() - start);
+            buf[..n].copy_from_slice(&data[start..start + n]);
+            n
+        }
+    }
+
+    #[test]
+    fn sizes_sections_and_certificate_table() {
+        let data = pe_bytes();
+        let headers = parse_headers(&data).expect("headers");
+        assert_eq!(headers.file_size(), PE_LEN as u64);
+        assert_eq!(headers.sections.len(), 2);
+        assert_eq!(headers.kind(), "exe");
+        assert_eq!(headers.architecture(), "x86_64");
+        assert_eq!(headers.subsystem_name(), Some("windows_cui"));
+        assert_eq!(
+            headers.compile_time().expect("time").and_utc().timestamp(),
+            i64::from(TIMESTAMP)
+        );
+        assert_eq!(headers.certificate_table(), Some((0x600, 0x18)));
+        assert_eq!(
+            parse_headers(&data[..0x100]),
+            Err(PeError::Incomplete(0x1D8))
+        );
+    }
+
+    #[test]
+    fn lists_imports_for_the_import_hash() {
+        let data = pe_bytes();
+        let headers = parse_headers(&data).expect("headers");
+        let imports = headers
+            .imports(&mut read_from(&data))
+            .expect("import directory");
+        assert_eq!(imports, vec!["kernel32.createfilew", "kernel32.ord16"]);
+        let record = headers.to_record("run", "pe/pe_0.exe", Some(&imports));
+        assert_eq!(
+            record.import_hash.as_deref(),
+            Some(format!("{:x}", md5::compute("kernel32.createfilew,kernel32.ord16")).as_str())
+        );
+        assert!(record.signed);
+        assert_eq!(record.bits, 64);
+    }
+
+    #[test]
+    fn stops_walking_imports_at_the_end_of_the_address_space() {
+        let mut data = pe_bytes();
+        // The thunk array starts 8 bytes before the end of the RVA space, in a
+        // section whose contents are all imports by ordinal 1.
+        let descriptor = 0x400;
+        data[descriptor..descriptor + 4].copy_from_slice(&0xFFFF_FFF8u32.to_le_bytes());
+        let mut headers = parse_headers(&data).expect("headers");
+        headers.sections.push(Section {
+            name: ".high".to_string(),
+            virtual_address: 0xFFFF_F000,
+            virtual_size: 0x1000,
+            raw_offset: 0x10_0000,
+            raw_size: 0x1000,
+        });
+        let mut read = |offset: u64, buf: &mut [u8]| {
+            if offset < 0x10_0000 {
+                return read_from(&data)(offset, buf);
+            }
+            let ordinal = 0x8000_0000_0000_0001u64.to_le_bytes();
+            for (i, byte) in buf.iter_mut().enumerate() {
+                *byte = ordinal[i % 8];
+            }
+            buf.len()
+        };
+        let imports = headers.imports(&mut read).expect("import directory");
+        assert_eq!(imports, vec!["kernel32.ord1"]);
+    }
+
+    #[test]
+    fn classifies_dlls_and_drivers() {
+        let mut data = pe_bytes();
+        data[0x96..0x98].copy_from_slice(&(0x2022u16).to_le_bytes());
+        assert_eq!(parse_headers(&data).expect("dll").kind(), "dll");
+        let mut data = pe_bytes();
+        data[0x98 + 68..0x98 + 70].copy_from_slice(&1u16.to_le_bytes());
+        assert_eq!(parse_headers(&data).expect("driver").kind(), "sys");
+    }
+
+    #[test]
+    fn rejects_objects_and_bad_headers() {
+        let mut data = pe_bytes();
+        data[0x96..0x98].copy_from_slice(&0u16.to_le_bytes());
+        assert_eq!(
+            parse_headers(&data),
+            Err(PeError::Invalid("not an executable image"))
+        );
+        let mut data = pe_bytes();
+        data[0x80] = b'N';
+        assert_eq!(
+            parse_headers(&data),
+            Err(PeError::Invalid("no PE signature"))
+        );
+        let mut data = pe_bytes();
+        data[0x98 + 0xF0 + 20..0x98 + 0xF0 + 24].copy_from_slice(&0x100u32.to_le_bytes());
+        assert_eq!(
+            parse_headers(&data),
+            Err(PeError::Invalid("section data overlaps the headers"))
+        );
+    }
+}
@@ -0,0 +1,341 @@
+//! Windows Prefetch files (`.pf`).
+//!
+//! A prefetch file starts with its format version (17 on XP, 23 on Vista
+//! and 7, 26 on 8, 30 or 31 on 10 and 11), the `SCCA` signature, the file
+//! size, the executable name as up to 29 UTF-16LE characters, and the hash
+//! of the executable's path. The file information that follows holds the
+//! offsets and sizes of the metrics, trace chain, file name, and volume
+//! sections, the last run times (one before Windows 8, eight after), and
+//! the run count, at offsets that depend on the version.
+//!
+//! Windows 10 and later store the file compressed: a `MAM` header with the
+//! compression format (4 = LZXPRESS Huffman) and the decompressed size,
+//! optionally a CRC32, then the compressed data.
+
+use std::borrow::Cow;
+
+use chrono::NaiveDateTime;
+use serde::Serialize;
+
+use crate::parsers::time::webkit_timestamp_to_datetime;
+use crate::parsers::xpress;
+
+pub const SCCA_MAGIC: &[u8; 4] = b"SCCA";
+pub const MAM_MAGIC: &[u8; 3] = b"MAM";
+/// Bytes of the file header the carver reads before trusting a hit.
+pub const SCCA_PROBE_LEN: usize = 0x58;
+/// Largest decompressed size accepted from a `MAM` header.
+pub const MAX_PREFETCH_SIZE: u32 = 16 * 1024 * 1024;
+
+const EXECUTABLE_OFFSET: usize = 0x10;
+const EXECUTABLE_LEN: usize = 60;
+/// `MAM` format nibble of LZXPRESS Huffman, the only one Windows uses here.
+const MAM_HUFFMAN: u8 = 4;
+const MAM_CRC_FLAG: u8 = 0x80;
+
+/// A prefetch file, read by the `prefetch_files` post-processor.
+#[derive(Debug, Clone, Serialize)]
+pub struct PrefetchRecord {
+    pub run_id: String,
+    /// Executable name as Windows stored it, e.g. `CHROME.EXE`.
+    pub executable: String,
+    /// Hash of the executable's path, as in the `.pf` file name.
+    pub prefetch_hash: String,
+    pub format_version: u32,
+    /// Stored `MAM`-compressed.
+    pub compressed: bool,
+    pub run_count: u32,
+    pub last_run_utc: Option<NaiveDateTime>,
+    /// Earlier run times (Windows 8 and later keep seven), newest first,
+    /// as ISO 8601 joined by `;`.
+    pub earlier_runs_utc: Option<String>,
+    /// Carved path of the prefetch file.
+    pub source_file: String,
+}
+
+/// Fields of the file header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SccaHeader {
+    pub version: u32,
+    /// Size of the uncompressed file.
+    pub file_size: u32,
+    pub executable: String,
+    pub prefetch_hash: u32,
+    layout: Layout,
+}
+
+/// Where the version-dependent fields are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Layout {
+    /// End of the file information; the metrics section starts here.
+    header_len: usize,
+    last_run: usize,
+    last_run_slots: usize,
+    run_count: usize,
+}
+
+impl Layout {
+    fn of(version: u32, metrics_offset: u32) -> Option<Self> {
+        let (header_len, last_run, last_run_slots, run_count) = match version {
+            17 => (0x98, 0x78, 1, 0x90),
+            23 => (0xF0, 0x80, 1, 0x98),
+            26 => (0x130, 0x80, 8, 0xD0),
+            // Later Windows 10 builds dropped 8 bytes before the run count.
+            30 | 31 if metrics_offset == 0x128 => (0x128, 0x80, 8, 0xC8),
+            30 | 31 => (0x130, 0x80, 8, 0xD0),
+            _ => return None,
+        };
+        Some(Self {
+            header_len,
+            last_run,
+            last_run_slots,
+            run_count,
+        })
+    }
+}
+
+impl SccaHeader {
+    /// Parse the header at the start of `data` (at least
+    /// [`SCCA_PROBE_LEN`] bytes); `None` when it is not a prefetch file.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < SCCA_PROBE_LEN || &data[4..8] != SCCA_MAGIC {
+            return None;
+        }
+        let version = le_u32(data, 0);
+        let layout = Layout::of(version, le_u32(data, 0x54))?;
+        let file_size = le_u32(data, 0x0C);
+        if (file_size as usize) < layout.header_len
+            || le_u32(data, 0x54) as usize > file_size as usize
+        {
+            return None;
+        }
+        let units: Vec<u16> = data[EXECUTABLE_OFFSET..EXECUTABLE_OFFSET + EXECUTABLE_LEN]
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .take_while(|&unit| unit != 0)
+            .collect();
+        let executable = String::from_utf16(&units).ok()?;
+        if executable.is_empty() || executable.chars().any(char::is_control) {
+            return None;
+        }
+        Some(Self {
+            version,
+            file_size,
+            executable,
+            prefetch_hash: le_u32(data, 0x4C),
+            layout,
+        })
+    }
+}
+
+/// A parsed prefetch file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Prefetch {
+    pub header: SccaHeader,
+    pub run_count: u32,
+    /// Set run times, newest first.
+    pub last_run_times: Vec<NaiveDateTime>,
+    /// The metrics, file name, and volume sections lie inside the file.
+    pub sections_ok: bool,
+}
+
+impl Prefetch {
+    /// Parse an uncompressed prefetch file.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let header = SccaHeader::parse(data)?;
+        let layout = header.layout;
+        if data.len() < layout.header_len {
+            return None;
+        }
+        let last_run_times = (0..layout.last_run_slots)
+            .filter_map(|slot| filetime(le_u64(data, layout.last_run + slot * 8)))
+            .collect();
+        let size = u64::from(header.file_size);
+        let within = |offset: usize, len: usize| {
+            u64::from(le_u32(data, offset)) + u64::from(le_u32(data, len)) <= size
+        };
+        let sections_ok = le_u32(data, 0x54) as usize >= layout.header_len
+            && within(0x64, 0x68)
+            && within(0x6C, 0x74);
+        Some(Self {
+            run_count: le_u32(data, layout.run_count),
+            last_run_times,
+            sections_ok,
+            header,
+        })
+    }
+
+    pub fn to_record(&self, run_id: &str, compressed: bool, source_file: &str) -> PrefetchRecord {
+        let earlier: Vec<String> = self
+            .last_run_times
+            .iter()
+            .skip(1)
+            .map(|t| t.format("%Y-%m-%dT%H:%M:%S%.f").to_string())
+            .collect();
+        PrefetchRecord {
+            run_id: run_id.to_string(),
+            executable: self.header.executable.clone(),
+            prefetch_hash: format!("{:08X}", self.header.prefetch_hash),
+            format_version: self.header.version,
+            compressed,
+            run_count: self.run_count,
+            last_run_utc: self.last_run_times.first().copied(),
+            earlier_runs_utc: (!earlier.is_empty()).then(|| earlier.join(";")),
+            source_file: source_file.to_string(),
+        }
+    }
+}
+
+/// Fields of a `MAM` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MamHeader {
+    pub decompressed_size: u32,
+    /// Offset of the compressed data: 8, or 12 when a CRC32 is stored.
+    pub data_offset: usize,
+}
+
+impl MamHeader {
+    /// Parse a `MAM` header holding LZXPRESS Huffman data of a plausible
+    /// prefetch size.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < 8 || &data[..3] != MAM_MAGIC || data[3] & 0x0F != MAM_HUFFMAN {
+            return None;
+        }
+        let decompressed_size = le_u32(data, 4);
+        if !(SCCA_PROBE_LEN as u32..=MAX_PREFETCH_SIZE).contains(&decompressed_size) {
+            return None;
+        }
+        Some(Self {
+            decompressed_size,
+            data_offset: if data[3] & MAM_CRC_FLAG != 0 { 12 } else { 8 },
+        })
+    }
+}
+
+/// The uncompressed contents of a prefetch file, whether stored plain or
+/// `MAM`-compressed, and whether it was compressed.
+pub fn decode(data: &[u8]) -> Option<(Cow<'_, [u8]>, bool)> {
+    match MamHeader::parse(data) {
+        Some(mam) => {
+            let out = xpress::decompress(&data[mam.data_offset..], mam.decompressed_size as usize)
+                .ok()?;
+            Some((Cow::Owned(out.data), true))
+        }
+        None => Some((Cow::Borrowed(data), false)),
+    }
+}
+
+/// FILETIME (100 ns since 1601) to a UTC time; zero is unset.
+fn filetime(value: u64) -> Option<NaiveDateTime> {
+    (value != 0)
+        .then(|| webkit_timestamp_to_datetime((value / 10) as i64))
+        .flatten()
+}
+
+fn le_u32(data: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(data[at..at + 4].try_into().unwrap_or_default())
+}
+
+fn le_u64(data: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(data[at..at + 8].try_into().unwrap_or_default())
+}
+
+/// Synthetic prefetch files.
+#[cfg(test)]
+pub(crate) mod testing {
+    use crate::parsers::xpress::testing::{Token, compress};
+
+    /// FILETIME of 2024-05-01 10:15:30 UTC.
+    pub(crate) const RUN_TIME: u64 = 133_590_321_300_000_000;
+
+    /// A version 30 prefetch file of `executable` with a file name section
+    /// and the given run count and run times.
+    pub(crate) fn prefetch_bytes(executable: &str, run_count: u32, runs: &[u64]) -> Vec<u8> {
+        let names: Vec<u8> = r"\VOLUME{01}\WINDOWS\SYSTEM32\CMD.EXE"
+            .encode_utf16()
+            .chain([0])
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        let mut data = vec![0u8; 0x130];
+        data[0..4].copy_from_slice(&30u32.to_le_bytes());
+        data[4..8].copy_from_slice(b"SCCA");
+        data[8..12].copy_from_slice(&0x11u32.to_le_bytes());
+        for (i, unit) in executable.encode_utf16().enumerate() {
+            data[0x10 + i * 2..0x12 + i * 2].copy_from_slice(&unit.to_le_bytes());
+        }
+        data[0x4C..0x50].copy_from_slice(&0xD7B8_1A4Cu32.to_le_bytes());
+        data[0x54..0x58].copy_from_slice(&0x130u32.to_le_bytes());
+        data[0x64..0x68].copy_from_slice(&0x130u32.to_le_bytes());
+        data[0x68..0x6C].copy_from_slice(&(names.len() as u32).to_le_bytes());
+        for (slot, run) in runs.iter().enumerate() {
+            data[0x80 + slot * 8..0x88 + slot * 8].copy_from_slice(&run.to_le_bytes());
+        }
+        data[0xD0..0xD4].copy_from_slice(&run_count.to_le_bytes());
+        data.extend_from_slice(&names);
+        let size = data.len() as u32;
+        data[0x0C..0x10].copy_from_slice(&size.to_le_bytes());
+        data
+    }
+
+    /// `data` compressed behind a `MAM` header without CRC.
+    pub(crate) fn mam_bytes(data: &[u8]) -> Vec<u8> {
+        let blocks: Vec<Vec<Token>> = data
+            .chunks(64 * 1024)
+            .map(|block| block.iter().map(|&b| Token::Literal(b)).collect())
+            .collect();
+        let mut out = b"MAM\x04".to_vec();
+        out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        out.extend_from_slice(&compress(&blocks));
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::{RUN_TIME, mam_bytes, prefetch_bytes};
+    use super::*;
+
+    #[test]
+    fn reads_plain_and_compressed_files() {
+        let plain = prefetch_bytes("CMD.EXE", 42, &[RUN_TIME, RUN_TIME - 36_000_000_000]);
+        let (data, compressed) = decode(&plain).expect("plain");
+        assert!(!compressed);
+        let prefetch = Prefetch::parse(&data).expect("parse");
+        assert_eq!(prefetch.header.executable, "CMD.EXE");
+        assert_eq!(prefetch.header.version, 30);
+        assert_eq!(prefetch.run_count, 42);
+        assert!(prefetch.sections_ok);
+        let record = prefetch.to_record("run", false, "prefetch/a.pf");
+        assert_eq!(record.prefetch_hash, "D7B81A4C");
+        assert_eq!(
+            record.last_run_utc.map(|t| t.to_string()).as_deref(),
+            Some("2024-05-01 10:15:30")
+        );
+        assert_eq!(
+            record.earlier_runs_utc.as_deref(),
+            Some("2024-05-01T09:15:30")
+        );
+
+        let mam = mam_bytes(&plain);
+        let (data, compressed) = decode(&mam).expect("mam");
+        assert!(compressed);
+        assert_eq!(&*data, &plain[..]);
+    }
+
+    #[test]
+    fn rejects_other_data() {
+        let mut bad = prefetch_bytes("CMD.EXE", 1, &[]);
+        bad[0..4].copy_from_slice(&12u32.to_le_bytes());
+        assert!(SccaHeader::parse(&bad).is_none());
+        let mut short = prefetch_bytes("CMD.EXE", 1, &[]);
+        short[0x0C..0x10].copy_from_slice(&0x40u32.to_le_bytes());
+        assert!(SccaHeader::parse(&short).is_none());
+        assert!(MamHeader::parse(b"MAM\x02\x00\x10\x00\x00").is_none());
+        assert_eq!(
+            MamHeader::parse(b"MAM\x84\x00\x10\x00\x00")
+                .expect("mam")
+                .data_offset,
+            12
+        );
+    }
+}
@@ -0,0 +1,574 @@
+//! Application databases recognised by schema and read by catalog plans.
+//!
+//! An [`AppCatalog`] lists applications by the tables, and optionally
+//! columns, their SQLite databases always have. Each entry carries
+//! extraction plans: a table or query, the columns mapped to normalized
+//! field names, and the column holding the row's time with its encoding.
+//! Carved databases whose schema matches an entry are read by its plans
+//! into [`AppRecord`]s, so supporting another application is a catalog
+//! entry rather than code. The built-in catalog (`config/sqlite_apps.yml`)
+//! is extended by a user catalog in the same format; user entries replace
+//! built-in ones with the same id.
+
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, NaiveDateTime};
+use rusqlite::types::ValueRef;
+use rusqlite::{Connection, OpenFlags};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::parsers::sqlite_db::{has_table, table_columns};
+use crate::parsers::time::{
+    mac_absolute_to_datetime, unix_micro_to_datetime, webkit_timestamp_to_datetime,
+};
+
+/// A normalized record read by an extraction plan.
+#[derive(Debug, Clone, Serialize)]
+pub struct AppRecord {
+    pub run_id: String,
+    /// Catalog id of the application, e.g. `whatsapp_android`.
+    pub app: String,
+    /// Plan that read the record, e.g. `messages`.
+    pub plan: String,
+    /// `rowid` of the source row (table plans).
+    pub row_id: Option<i64>,
+    /// The row's time, from the plan's `time` column.
+    pub timestamp_utc: Option<NaiveDateTime>,
+    /// What the time marks, e.g. `Sent`.
+    pub timestamp_desc: Option<String>,
+    /// Mapped fields as a JSON object; null columns are left out.
+    pub fields: String,
+    /// Carved path of the database.
+    pub source_file: String,
+}
+
+/// Applications known by schema.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AppCatalog {
+    pub apps: Vec<AppEntry>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AppEntry {
+    /// Recorded as `app` and as the classification label.
+    pub id: String,
+    /// Human-readable name.
+    #[serde(default)]
+    pub name: String,
+    /// Tables a database must have.
+    pub tables: Vec<String>,
+    /// Columns a table must have, by table.
+    #[serde(default)]
+    pub columns: BTreeMap<String, Vec<String>>,
+    pub plans: Vec<ExtractionPlan>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExtractionPlan {
+    pub name: String,
+    /// Table read row by row; columns it lacks are null.
+    #[serde(default)]
+    pub table: Option<String>,
+    /// `SELECT` read instead of a table; fields name its result columns.
+    #[serde(default)]
+    pub query: Option<String>,
+    #[serde(default)]
+    pub fields: Vec<FieldMapping>,
+    #[serde(default)]
+    pub time: Option<TimeMapping>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FieldMapping {
+    /// Normalized field name.
+    pub name: String,
+    pub column: String,
+    /// Time encoding; the value is written as ISO 8601 text.
+    #[serde(default)]
+    pub format: Option<TimeFormat>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TimeMapping {
+    pub column: String,
+    pub format: TimeFormat,
+    #[serde(default)]
+    pub desc: Option<String>,
+}
+
+/// How a time column is encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeFormat {
+    UnixSeconds,
+    UnixMillis,
+    UnixMicros,
+    UnixNanos,
+    /// Microseconds since 1601-01-01 (Chromium).
+    Webkit,
+    /// Seconds since 2001-01-01 (Core Data).
+    MacAbsolute,
+    /// Nanoseconds since 2001-01-01.
+    MacAbsoluteNanos,
+    /// Text such as `2024-05-01 10:15:30`, optionally with an offset.
+    Iso8601,
+}
+
+impl TimeFormat {
+    /// UTC time of a column value; `None` for null, zero, or negative
+    /// values and text that does not parse.
+    pub fn to_datetime(self, value: ValueRef) -> Option<NaiveDateTime> {
+        if self == Self::Iso8601 {
+            let ValueRef::Text(text) = value else {
+                return None;
+            };
+            return parse_iso(std::str::from_utf8(text).ok()?.trim());
+        }
+        let number = match value {
+            ValueRef::Integer(i) => i as f64,
+            ValueRef::Real(r) => r,
+            ValueRef::Text(text) => std::str::from_utf8(text).ok()?.trim().parse().ok()?,
+            _ => return None,
+        };
+        if !number.is_finite() || number <= 0.0 {
+            return None;
+        }
+        match self {
+            Self::UnixSeconds => unix_micro_to_datetime((number * 1e6) as i64),
+            Self::UnixMillis => unix_micro_to_datetime((number * 1e3) as i64),
+            Self::UnixMicros => unix_micro_to_datetime(number as i64),
+            Self::UnixNanos => unix_micro_to_datetime((number / 1e3) as i64),
+            Self::Webkit => webkit_timestamp_to_datetime(number as i64),
+            Self::MacAbsolute => mac_absolute_to_datetime(number),
+            Self::MacAbsoluteNanos => mac_absolute_to_datetime(number / 1e9),
+            Self::Iso8601 => None,
+        }
+    }
+}
+
+fn parse_iso(text: &str) -> Option<NaiveDateTime> {
+    if let Ok(t) = DateTime::parse_from_rfc3339(text) {
+        return Some(t.naive_utc());
+    }
+    if let Ok(t) = DateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f %:z") {
+        return Some(t.naive_utc());
+    }
+    ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+}
+
+impl AppCatalog {
+    /// The catalog shipped with the binary.
+    pub fn builtin() -> Self {
+        serde_yaml::from_str(include_str!("../../config/sqlite_apps.yml"))
+            .expect("built-in sqlite app catalog")
+    }
+
+    /// The built-in catalog extended by the one at `path`.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let mut catalog = Self::builtin();
+        if let Some(path) = path {
+            let text = std::fs::read_to_string(path)
+                .with_context(|| format!("read sqlite app catalog {}", path.display()))?;
+            let user: AppCatalog = serde_yaml::from_str(&text)
+                .with_context(|| format!("invalid sqlite app catalog {}", path.display()))?;
+            user.check()
+                .with_context(|| format!("invalid sqlite app catalog {}", path.display()))?;
+            catalog.extend(user);
+        }
+        Ok(catalog)
+    }
+
+    /// Add the entries of `other`, replacing entries with the same id.
+    pub fn extend(&mut self, other: AppCatalog) {
+        for entry in other.apps {
+            match self.apps.iter_mut().find(|e| e.id == entry.id) {
+                Some(existing) => *existing = entry,
+                None => self.apps.push(entry),
+            }
+        }
+    }
+
+    /// Reject entries that could never match or read anything.
+    pub fn check(&self) -> Result<()> {
+        let mut ids = HashSet::new();
+        for entry in &self.apps {
+            if entry.id.trim().is_empty() {
+                bail!("app with empty id");
+            }
+            if !ids.insert(entry.id.as_str()) {
+                bail!("duplicate app id {}", entry.id);
+            }
+            if entry.tables.is_empty() {
+                bail!("app {} lists no tables", entry.id);
+            }
+            if entry.plans.is_empty() {
+                bail!("app {} has no plans", entry.id);
+            }
+            for plan in &entry.plans {
+                if plan.table.is_some() == plan.query.is_some() {
+                    bail!(
+                        "plan {} of app {} needs exactly one of table and query",
+                        plan.name,
+                        entry.id
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Entries whose tables and columns are all in the database.
+    pub fn matching(&self, conn: &Connection) -> Result<Vec<&AppEntry>> {
+        let mut out = Vec::new();
+        for entry in &self.apps {
+            if entry.matches(conn)? {
+                out.push(entry);
+            }
+        }
+        Ok(out)
+    }
+}
+
+impl AppEntry {
+    fn matches(&self, conn: &Connection) -> Result<bool> {
+        for table in &self.tables {
+            if !has_table(conn, table)? {
+                return Ok(false);
+            }
+        }
+        for (table, required) in &self.columns {
+            if !has_table(conn, table)? {
+                return Ok(false);
+            }
+            let columns = table_columns(conn, &quote(table))?;
+            if !required
+                .iter()
+                .all(|c| columns.contains(&c.to_ascii_lowercase()))
+            {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// What a database yielded.
+#[derive(Debug, Default)]
+pub struct AppExtraction {
+    /// Ids of the matching apps, in catalog order.
+    pub apps: Vec<String>,
+    pub records: Vec<AppRecord>,
+    /// Plans that failed, with the error.
+    pub errors: Vec<String>,
+}
+
+/// Match the database at `path` against `catalog` and run the plans of every
+/// matching app. A failing plan is reported in `errors`; the others still run.
+pub fn extract_app_records(
+    path: &Path,
+    catalog: &AppCatalog,
+    run_id: &str,
+    source_relative: &str,
+) -> Result<AppExtraction> {
+    let conn = Connection::open_with_flags(
+        path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )?;
+    let mut out = AppExtraction::default();
+    for entry in catalog.matching(&conn)? {
+        out.apps.push(entry.id.clone());
+        for plan in &entry.plans {
+            match run_plan(&conn, entry, plan, run_id, source_relative) {
+                Ok(records) => out.records.extend(records),
+                Err(err) => out
+                    .errors
+                    .push(format!("{} plan {}: {err}", entry.id, plan.name)),
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn run_plan(
+    conn: &Connection,
+    entry: &AppEntry,
+    plan: &ExtractionPlan,
+    run_id: &str,
+    source_relative: &str,
+) -> Result<Vec<AppRecord>> {
+    // Source columns in output order: the time column, then the fields.
+    let mut sources: Vec<&str> = Vec::new();
+    if let Some(time) = &plan.time {
+        sources.push(&time.column);
+    }
+    sources.extend(plan.fields.iter().map(|f| f.column.as_str()));
+
+    let (sql, has_row_id) = match (&plan.table, &plan.query) {
+        (Some(table), _) => {
+            let present = table_columns(conn, &quote(table))?;
+            let select: Vec<String> = std::iter::once("rowid".to_string())
+                .chain(sources.iter().map(|column| {
+                    if present.contains(&column.to_ascii_lowercase()) {
+                        quote(column)
+                    } else {
+                        "NULL".to_string()
+                    }
+                }))
+                .collect();
+            (
+                format!("SELECT {} FROM {}", select.join(", "), quote(table)),
+                true,
+            )
+        }
+        (None, Some(query)) => {
+            let mut select: Vec<String> = sources.iter().map(|c| quote(c)).collect();
+            if select.is_empty() {
+                select.push("NULL".to_string());
+            }
+            (
+                format!("SELECT {} FROM ({query})", select.join(", ")),
+                false,
+            )
+        }
+        (None, None) => bail!("plan has neither table nor query"),
+    };
+
+    let mut stmt = conn.prepare(&sql)?;
+    let mut rows = stmt.query([])?;
+    let mut out = Vec::new();
+    while let Some(row) = rows.next()? {
+        let offset = usize::from(has_row_id);
+        let row_id = if has_row_id {
+            row.get::<_, Option<i64>>(0)?
+        } else {
+            None
+        };
+        let mut column = offset;
+        let timestamp_utc = match &plan.time {
+            Some(time) => {
+                column += 1;
+                time.format.to_datetime(row.get_ref(offset)?)
+            }
+            None => None,
+        };
+        let mut fields = Map::new();
+        for field in &plan.fields {
+            let value = row.get_ref(column)?;
+            column += 1;
+            let value = match field.format {
+                Some(format) => format
+                    .to_datetime(value)
+                    .map(|t| Value::from(t.format("%Y-%m-%dT%H:%M:%S%.f").to_string()))
+                    .unwrap_or(Value::Null),
+                None => json_value(value),
+            };
+            if !value.is_null() {
+                fields.insert(field.name.clone(), value);
+            }
+        }
+        out.push(AppRecord {
+            run_id: run_id.to_string(),
+            app: entry.id.clone(),
+            plan: plan.name.clone(),
+            row_id,
+            timestamp_utc,
+            timestamp_desc: plan.time.as_ref().and_then(|t| t.desc.clone()),
+            fields: Value::Object(fields).to_string(),
+            source_file: source_relative.to_string(),
+        });
+    }
+    Ok(out)
+}
+
+/// JSON of a column value; blobs are text when they are UTF-8, hex otherwise.
+fn json_value(value: ValueRef) -> Value {
+    match value {
+        ValueRef::Null => Value::Null,
+        ValueRef::Integer(i) => Value::from(i),
+        ValueRef::Real(r) => Value::from(r),
+        ValueRef::Text(text) => Value::from(String::from_utf8_lossy(text).into_owned()),
+        ValueRef::Blob(blob) => match std::str::from_utf8(blob) {
+            Ok(text) => Value::from(text),
+            Err(_) => Value::from(hex::encode(blob)),
+        },
+    }
+}
+
+/// SQL identifier quoting.
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn database(dir: &Path, sql: &str) -> std::path::PathBuf {
+        let path = dir.join("carved.sqlite");
+        let conn = Connection::open(&path).expect("conn");
+        conn.execute_batch(sql).expect("schema");
+        path
+    }
+
+    #[test]
+    fn builtin_catalog_is_valid() {
+        let catalog = AppCatalog::builtin();
+        catalog.check().expect("check");
+        assert!(catalog.apps.iter().any(|a| a.id == "whatsapp_android"));
+    }
+
+    #[test]
+    fn reads_matching_app_by_plan() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = database(
+            dir.path(),
+            "CREATE TABLE sms (_id INTEGER PRIMARY KEY, thread_id INTEGER, address TEXT,
+                               date INTEGER, type INTEGER, body TEXT);
+             CREATE TABLE threads (_id INTEGER PRIMARY KEY);
+             INSERT INTO sms VALUES (7, 1, '+15550100', 1714558530000, 1, 'on my way');
+             INSERT INTO sms VALUES (8, 1, '+15550100', 0, 2, NULL);",
+        );
+        let found = extract_app_records(&path, &AppCatalog::builtin(), "run", "sqlite/a.sqlite")
+            .expect("extract");
+        assert_eq!(found.apps, ["android_sms"]);
+        assert!(found.errors.is_empty(), "{:?}", found.errors);
+        assert_eq!(found.records.len(), 2);
+
+        let first = &found.records[0];
+        assert_eq!(
+            (first.app.as_str(), first.plan.as_str()),
+            ("android_sms", "sms")
+        );
+        assert_eq!(first.row_id, Some(7));
+        assert_eq!(
+            first.timestamp_utc.map(|t| t.to_string()).as_deref(),
+            Some("2024-05-01 10:15:30")
+        );
+        assert_eq!(first.timestamp_desc.as_deref(), Some("Sent or received"));
+        let fields: Value = serde_json::from_str(&first.fields).expect("fields");
+        assert_eq!(fields["address"], "+15550100");
+        assert_eq!(fields["body"], "on my way");
+        assert_eq!(fields["type"], 1);
+        // `read` is not in this schema.
+        assert!(fields.get("read").is_none());
+
+        let second = &found.records[1];
+        assert_eq!(second.timestamp_utc, None);
+        assert!(!second.fields.contains("body"));
+    }
+
+    #[test]
+    fn user_catalog_adds_and_replaces_apps() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let catalog_path = dir.path().join("apps.yml");
+        std::fs::write(
+            &catalog_path,
+            r#"
+apps:
+  - id: "android_sms"
+    tables: ["sms"]
+    plans:
+      - name: "bodies"
+        query: "SELECT body AS text, date / 1000 AS sent FROM sms WHERE body IS NOT NULL"
+        fields: [{ name: "text", column: "text" }]
+        time: { column: "sent", format: "unix_seconds" }
+  - id: "notes_app"
+    tables: ["notes"]
+    columns: { notes: ["created"] }
+    plans:
+      - name: "notes"
+        table: "notes"
+        fields: [{ name: "title", column: "title" }]
+        time: { column: "created", format: "iso8601", desc: "Created" }
+"#,
+        )
+        .expect("write");
+        let catalog = AppCatalog::load(Some(&catalog_path)).expect("load");
+        assert_eq!(
+            catalog
+                .apps
+                .iter()
+                .filter(|a| a.id == "android_sms")
+                .count(),
+            1
+        );
+
+        let path = database(
+            dir.path(),
+            "CREATE TABLE sms (date INTEGER, body TEXT);
+             CREATE TABLE notes (title TEXT, created TEXT);
+             INSERT INTO sms VALUES (1714558530000, 'hi');
+             INSERT INTO sms VALUES (1714558531000, NULL);
+             INSERT INTO notes VALUES ('plans', '2024-05-01 12:15:30 +02:00');",
+        );
+        let found = extract_app_records(&path, &catalog, "run", "a.sqlite").expect("extract");
+        assert_eq!(found.apps, ["android_sms", "notes_app"]);
+        assert_eq!(found.records.len(), 2);
+        assert_eq!(found.records[0].plan, "bodies");
+        assert_eq!(found.records[0].row_id, None);
+        assert_eq!(found.records[0].fields, r#"{"text":"hi"}"#);
+        assert_eq!(
+            found.records[1]
+                .timestamp_utc
+                .map(|t| t.to_string())
+                .as_deref(),
+            Some("2024-05-01 10:15:30")
+        );
+    }
+
+    #[test]
+    fn rejects_plans_without_a_source() {
+        let catalog: AppCatalog = serde_yaml::from_str(
+            "apps: [{ id: x, tables: [t], plans: [{ name: p, table: t, query: 'SELECT 1' }] }]",
+        )
+        .expect("yaml");
+        assert!(catalog.check().is_err());
+    }
+
+    #[test]
+    fn converts_time_formats() {
+        let at =
+            |format: TimeFormat, value: ValueRef| format.to_datetime(value).map(|t| t.to_string());
+        let expected = Some("2024-05-01 10:15:30".to_string());
+        assert_eq!(
+            at(TimeFormat::UnixSeconds, ValueRef::Integer(1_714_558_530)),
+            expected
+        );
+        assert_eq!(
+            at(
+                TimeFormat::UnixNanos,
+                ValueRef::Integer(1_714_558_530_000_000_000)
+            ),
+            expected
+        );
+        assert_eq!(
+            at(
+                TimeFormat::Webkit,
+                ValueRef::Integer(13_359_032_130_000_000)
+            ),
+            expected
+        );
+        assert_eq!(
+            at(TimeFormat::MacAbsolute, ValueRef::Real(736_251_330.0)),
+            expected
+        );
+        assert_eq!(
+            at(
+                TimeFormat::MacAbsoluteNanos,
+                ValueRef::Integer(736_251_330_000_000_000)
+            ),
+            expected
+        );
+        assert_eq!(
+            at(TimeFormat::Iso8601, ValueRef::Text(b"2024-05-01T10:15:30Z")),
+            expected
+        );
+        assert_eq!(at(TimeFormat::UnixMillis, ValueRef::Integer(0)), None);
+        assert_eq!(at(TimeFormat::UnixMillis, ValueRef::Null), None);
+    }
+}
@@ -94,13 +94,13 @@ pub fn extract_browser_downloads(
     Ok(out)
 }
 
-fn has_table(conn: &Connection, name: &str) -> Result<bool> {
+pub(crate) fn has_table(conn: &Connection, name: &str) -> Result<bool> {
     let mut stmt = conn.prepare("SELECT name FROM sqlite_master WHERE type='table' AND name=?1")?;
     let mut rows = stmt.query([name])?;
     Ok(rows.next()?.is_some())
 }
 
-fn table_columns(conn: &Connection, table: &str) -> Result<HashSet<String>> {
+pub(crate) fn table_columns(conn: &Connection, table: &str) -> Result<HashSet<String>> {
     let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
     let rows = stmt.query_map([], |row| row.get::<_, String>(1))?;
     let mut out = HashSet::new();
@@ -119,7 +119,7 @@ fn pick_col<'a>(columns: &HashSet<String>, candidates: &'a [&'a str]) -> Option<
     None
 }
 
-fn select_col<'a>(
+pub(crate) fn select_col<'a>(
     columns: &HashSet<String>,
     candidates: &'a [&'a str],
     fallback: &'a str,
@@ -0,0 +1,396 @@
+//! Windows Explorer thumbnail caches (`thumbcache_<size>.db`, Vista and later).
+//!
+//! A cache starts with a `CMMM` header giving the format version, the cache
+//! type (the thumbnail size the file holds), and the offsets of the first
+//! entry and of the first free byte. Entries follow back to back, each with
+//! its own `CMMM` signature, size, 64-bit cache ID, identifier string,
+//! padding, and image data; Windows 8 and later entries also hold the
+//! thumbnail's width and height. The cache ID is the key Explorer and the
+//! Windows Search index (`System.ThumbnailCacheId`) use for the thumbnail.
+//! The walk stops at the first free byte or at the first entry that does not
+//! check out. `thumbcache_idx.db` (`IMMM`) is not read.
+
+use serde::Serialize;
+
+const SIGNATURE: &[u8; 4] = b"CMMM";
+/// Bytes of the file header read before the entries.
+pub const HEADER_LEN: usize = 24;
+/// Entries larger than this are taken as garbage.
+const MAX_ENTRY_LEN: u32 = 64 * 1024 * 1024;
+/// The first entry follows the header; offsets past this are garbage.
+const MAX_FIRST_ENTRY: u32 = 4096;
+/// Identifier strings are short hex or extension strings.
+const MAX_IDENTIFIER_LEN: u32 = 1024;
+/// Highest cache type (Windows 10 `custom_stream`) plus slack.
+const MAX_CACHE_TYPE: u32 = 32;
+
+/// Why a thumbnail cache could not be read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThumbcacheError {
+    /// The data ends inside the file header.
+    Incomplete,
+    Invalid(&'static str),
+}
+
+/// The `CMMM` file header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheHeader {
+    pub version: u32,
+    /// Thumbnail size the cache holds; the numbering differs per release.
+    pub cache_type: u32,
+    pub first_entry: u32,
+    /// Offset of the first free byte; the used part of the cache ends here.
+    pub available: u32,
+}
+
+impl CacheHeader {
+    /// Windows release that writes this format version.
+    pub fn windows_release(&self) -> &'static str {
+        match self.version {
+            20 => "vista",
+            21 => "7",
+            26 | 28 | 30 => "8",
+            31 => "8.1",
+            _ => "10",
+        }
+    }
+
+    fn entry_header_len(&self) -> usize {
+        if self.version == 21 { 48 } else { 56 }
+    }
+}
+
+/// One cache entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheEntry {
+    /// Offset of the entry in the cache.
+    pub offset: u64,
+    pub size: u32,
+    pub cache_id: u64,
+    /// Identifier string: the cache ID in hex on Windows 7 and later.
+    pub identifier: String,
+    /// Offset of the image data in the cache.
+    pub data_offset: u64,
+    pub data_size: u32,
+    /// Thumbnail dimensions, stored from Windows 8 on.
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+}
+
+impl CacheEntry {
+    /// The cache ID as Windows shows it, 16 hex digits.
+    pub fn cache_id_hex(&self) -> String {
+        format!("{:016x}", self.cache_id)
+    }
+}
+
+/// A cache header and the entries walked from it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Thumbcache {
+    pub header: CacheHeader,
+    pub entries: Vec<CacheEntry>,
+    /// End of the last entry that checked out.
+    pub end: u64,
+}
+
+impl Thumbcache {
+    /// The entries reached the first free byte without a gap.
+    pub fn complete(&self) -> bool {
+        self.end == u64::from(self.header.available)
+    }
+
+    /// Bytes the cache uses: up to the first free byte, or the end of the
+    /// last entry when one runs past it.
+    pub fn size(&self) -> u64 {
+        self.end.max(u64::from(self.header.available))
+    }
+}
+
+/// A thumbnail extracted from a carved cache, from the
+/// `thumbcache_images` post-processor.
+#[derive(Debug, Clone, Serialize)]
+pub struct ThumbnailRecord {
+    pub run_id: String,
+    /// Cache ID of the entry, 16 hex digits.
+    pub cache_id: String,
+    /// Windows release of the cache format (`vista`, `7`, `8`, `8.1`, `10`).
+    pub cache_version: String,
+    /// Cache type from the header, the thumbnail size of the cache.
+    pub cache_type: u32,
+    /// Offset of the entry in the carved cache.
+    pub entry_offset: u64,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// `jpg`, `png`, `bmp`, or `bin` for data of another format.
+    pub image_format: String,
+    pub data_size: u64,
+    /// Carved path of the extracted image.
+    pub image_path: String,
+    /// Carved path of the cache.
+    pub source_file: String,
+}
+
+/// Parse the file header at the start of `data`.
+pub fn parse_header(data: &[u8]) -> Result<CacheHeader, ThumbcacheError> {
+    if data.len() < HEADER_LEN {
+        return Err(ThumbcacheError::Incomplete);
+    }
+    if &data[..4] != SIGNATURE {
+        return Err(ThumbcacheError::Invalid("missing CMMM signature"));
+    }
+    let version = le_u32(data, 4);
+    // Windows 8 added a field before the entry offsets.
+    let offsets_at = match version {
+        20 | 21 => 12,
+        26 | 28 | 30 | 31 | 32 => 16,
+        _ => return Err(ThumbcacheError::Invalid("unknown thumbcache version")),
+    };
+    let cache_type = le_u32(data, 8);
+    if cache_type > MAX_CACHE_TYPE {
+        return Err(ThumbcacheError::Invalid("cache type out of range"));
+    }
+    let first_entry = le_u32(data, offsets_at);
+    let available = le_u32(data, offsets_at + 4);
+    if !(HEADER_LEN as u32..=MAX_FIRST_ENTRY).contains(&first_entry) {
+        return Err(ThumbcacheError::Invalid("first entry offset out of range"));
+    }
+    if available < first_entry {
+        return Err(ThumbcacheError::Invalid(
+            "free space starts before the first entry",
+        ));
+    }
+    Ok(CacheHeader {
+        version,
+        cache_type,
+        first_entry,
+        available,
+    })
+}
+
+/// Read the header and walk the entries through `read(offset, buf)`, which
+/// returns the bytes it filled. Entries are walked up to the first free
+/// byte; an entry that is cut off or does not check out ends the walk.
+pub fn walk(read: &mut dyn FnMut(u64, &mut [u8]) -> usize) -> Result<Thumbcache, ThumbcacheError> {
+    let mut buf = [0u8; HEADER_LEN];
+    let n = read(0, &mut buf);
+    let header = parse_header(&buf[..n])?;
+    let header_len = header.entry_header_len();
+    let mut entries = Vec::new();
+    let mut offset = u64::from(header.first_entry);
+    let mut entry_header = vec![0u8; header_len];
+    while offset < u64::from(header.available) {
+        if read(offset, &mut entry_header) < header_len {
+            break;
+        }
+        let Some((mut entry, identifier_len)) = parse_entry(&header, offset, &entry_header) else {
+            break;
+        };
+        let mut identifier = vec![0u8; identifier_len as usize];
+        if read(offset + header_len as u64, &mut identifier) < identifier.len() {
+            break;
+        }
+        entry.identifier = utf16_string(&identifier);
+        offset += u64::from(entry.size);
+        entries.push(entry);
+    }
+    Ok(Thumbcache {
+        header,
+        entries,
+        end: offset,
+    })
+}
+
+/// Check one entry header. The identifier is left empty; its length in
+/// bytes is returned with the entry.
+fn parse_entry(header: &CacheHeader, offset: u64, data: &[u8]) -> Option<(CacheEntry, u32)> {
+    if &data[..4] != SIGNATURE {
+        return None;
+    }
+    let size = le_u32(data, 4);
+    let cache_id = u64::from_le_bytes(data[8..16].try_into().ok()?);
+    // Vista entries hold the file extension before the sizes.
+    let sizes_at = if header.version == 20 { 24 } else { 16 };
+    let identifier_len = le_u32(data, sizes_at);
+    let padding_len = le_u32(data, sizes_at + 4);
+    let data_size = le_u32(data, sizes_at + 8);
+    let (width, height) = if header.version >= 26 {
+        (Some(le_u32(data, 28)), Some(le_u32(data, 32)))
+    } else {
+        (None, None)
+    };
+    if size > MAX_ENTRY_LEN
+        || identifier_len > MAX_IDENTIFIER_LEN
+        || !identifier_len.is_multiple_of(2)
+    {
+        return None;
+    }
+    let used = data.len() as u64
+        + u64::from(identifier_len)
+        + u64::from(padding_len)
+        + u64::from(data_size);
+    if used > u64::from(size) {
+        return None;
+    }
+    let entry = CacheEntry {
+        offset,
+        size,
+        cache_id,
+        identifier: String::new(),
+        data_offset: offset
+            + data.len() as u64
+            + u64::from(identifier_len)
+            + u64::from(padding_len),
+        data_size,
+        width,
+        height,
+    };
+    Some((entry, identifier_len))
+}
+
+/// Extension for thumbnail data, from its leading bytes.
+pub fn image_extension(magic: &[u8]) -> &'static str {
+    if magic.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        "jpg"
+    } else if magic.starts_with(b"\x89PNG\r\n\x1a\n") {
+        "png"
+    } else if magic.starts_with(b"BM") {
+        "bmp"
+    } else {
+        "bin"
+    }
+}
+
+fn utf16_string(data: &[u8]) -> String {
+    let units: Vec<u16> = data
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .take_while(|&unit| unit != 0)
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+fn le_u32(data: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]])
+}
+
+#[cfg(test)]
+pub(crate) mod testing {
+    /// JPEG and PNG thumbnails in the test caches.
+    pub const JPEG: &[u8] = b"\xFF\xD8\xFF\xE0thumbnail one\xFF\xD9";
+    pub const PNG: &[u8] = b"\x89PNG\r\n\x1a\nthumbnail two";
+    pub const CACHE_IDS: [u64; 3] = [0x1122_3344_5566_7788, 0x0A0B_0C0D_0E0F_1011, 0x99AA_BBCC];
+
+    /// A Windows 10 (`version` 32) or Windows 7 (21) cache with a JPEG, an
+    /// empty entry, and a PNG.
+    pub fn thumbcache_bytes(version: u32) -> Vec<u8> {
+        let header_len = if version == 21 { 48 } else { 56 };
+        let mut out = Vec::new();
+        out.extend_from_slice(b"CMMM");
+        out.extend_from_slice(&version.to_le_bytes());
+        out.extend_from_slice(&4u32.to_le_bytes());
+        if version != 21 {
+            out.extend_from_slice(&0u32.to_le_bytes());
+        }
+        let first_entry = out.len() + 12;
+        out.extend_from_slice(&(first_entry as u32).to_le_bytes());
+        out.extend_from_slice(&0u32.to_le_bytes());
+        out.extend_from_slice(&3u32.to_le_bytes());
+        for (id, data) in CACHE_IDS.into_iter().zip([JPEG, &[][..], PNG]) {
+            let identifier: Vec<u8> = format!("{id:016x}")
+                .encode_utf16()
+                .flat_map(u16::to_le_bytes)
+                .collect();
+            let padding = (8 - (header_len + identifier.len() + data.len()) % 8) % 8;
+            let size = header_len + identifier.len() + padding + data.len();
+            let mut entry = Vec::with_capacity(size);
+            entry.extend_from_slice(b"CMMM");
+            entry.extend_from_slice(&(size as u32).to_le_bytes());
+            entry.extend_from_slice(&id.to_le_bytes());
+            entry.extend_from_slice(&(identifier.len() as u32).to_le_bytes());
+            entry.extend_from_slice(&(padding as u32).to_le_bytes());
+            entry.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            if version != 21 {
+                entry.extend_from_slice(&96u32.to_le_bytes());
+                entry.extend_from_slice(&64u32.to_le_bytes());
+            }
+            entry.resize(header_len, 0);
+            entry.extend_from_slice(&identifier);
+            entry.resize(entry.len() + padding, 0);
+            entry.extend_from_slice(data);
+            out.extend_from_slice(&entry);
+        }
+        let available = out.len() as u32;
+        let at = if version == 21 { 16 } else { 20 };
+        out[at..at + 4].copy_from_slice(&available.to_le_bytes());
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::*;
+    use super::*;
+
+    fn walk_bytes(data: &[u8]) -> Result<Thumbcache, ThumbcacheError> {
+        walk(&mut |offset, buf: &mut [u8]| {
+            let start = (offset as usize).min(data.len());
+            let n = buf.len().min(data.len() - start);
+            buf[..n].copy_from_slice(&data[start..start + n]);
+            n
+        })
+    }
+
+    #[test]
+    fn walks_windows_7_and_10_caches() {
+        for (version, release) in [(21, "7"), (32, "10")] {
+            let data = thumbcache_bytes(version);
+            let cache = walk_bytes(&data).expect("cache");
+            assert_eq!(cache.header.windows_release(), release);
+            assert!(cache.complete());
+            assert_eq!(cache.size(), data.len() as u64);
+            let ids: Vec<u64> = cache.entries.iter().map(|e| e.cache_id).collect();
+            assert_eq!(ids, CACHE_IDS);
+            let first = &cache.entries[0];
+            assert_eq!(first.identifier, first.cache_id_hex());
+            let start = first.data_offset as usize;
+            assert_eq!(&data[start..start + first.data_size as usize], JPEG);
+            assert_eq!(cache.entries[1].data_size, 0);
+            let expected = (version == 32).then_some(96);
+            assert_eq!(first.width, expected);
+        }
+    }
+
+    #[test]
+    fn stops_at_a_broken_entry() {
+        let mut data = thumbcache_bytes(32);
+        let second = walk_bytes(&data).expect("cache").entries[1].offset as usize;
+        data[second..second + 4].copy_from_slice(b"XXXX");
+        let cache = walk_bytes(&data).expect("cache");
+        assert_eq!(cache.entries.len(), 1);
+        assert!(!cache.complete());
+        assert_eq!(cache.size(), data.len() as u64);
+    }
+
+    #[test]
+    fn rejects_bad_headers() {
+        let data = thumbcache_bytes(32);
+        assert_eq!(parse_header(&data[..10]), Err(ThumbcacheError::Incomplete));
+        let mut bad = data.clone();
+        bad[4] = 19;
+        assert_eq!(
+            parse_header(&bad),
+            Err(ThumbcacheError::Invalid("unknown thumbcache version"))
+        );
+        let mut bad = data;
+        bad[20..24].copy_from_slice(&8u32.to_le_bytes());
+        assert_eq!(
+            parse_header(&bad),
+            Err(ThumbcacheError::Invalid(
+                "free space starts before the first entry"
+            ))
+        );
+        assert_eq!(image_extension(JPEG), "jpg");
+        assert_eq!(image_extension(PNG), "png");
+        assert_eq!(image_extension(b"GIF89a"), "bin");
+    }
+}
@@ -19,3 +19,14 @@ pub fn unix_micro_to_datetime(microseconds: i64) -> Option<chrono::NaiveDateTime
     let nsecs = ((microseconds % 1_000_000).abs() as u32) * 1000;
     chrono::DateTime::<chrono::Utc>::from_timestamp(secs, nsecs).map(|dt| dt.naive_utc())
 }
+
+/// Seconds since 2001-01-01 UTC (Core Data / Cocoa absolute time).
+pub fn mac_absolute_to_datetime(seconds: f64) -> Option<chrono::NaiveDateTime> {
+    if !seconds.is_finite() || seconds <= 0.0 {
+        return None;
+    }
+    let mac_epoch_offset = 978_307_200i64;
+    let secs = seconds.trunc() as i64 + mac_epoch_offset;
+    let nsecs = (seconds.fract() * 1e9) as u32;
+    chrono::DateTime::<chrono::Utc>::from_timestamp(secs, nsecs).map(|dt| dt.naive_utc())
+}
@@ -0,0 +1,467 @@
+//! Volume Shadow Copy (VSS) stores on NTFS volumes.
+//!
+//! Windows keeps copy-on-write snapshots of an NTFS volume inside the volume
+//! itself. A volume header at offset `0x1E00` points to a catalog of 16 KiB
+//! blocks whose 128-byte entries describe each store (type 2: size, store
+//! GUID, sequence, creation time) and where its data lives (type 3: block
+//! list offset). A store's block list maps 16 KiB blocks of the original
+//! volume to the copies made before they were overwritten.
+//!
+//! Offsets inside these structures are relative to the start of the volume.
+//! [`find_ntfs_volumes`] locates NTFS volumes in the evidence (at offset 0 or
+//! in an MBR partition), [`read_stores`] lists the stores of one volume
+//! oldest first, and [`ShadowCopySource`](crate::evidence::ShadowCopySource)
+//! presents one snapshot as evidence.
+
+use std::collections::HashMap;
+
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::evidence::{EvidenceError, EvidenceSource};
+use crate::parsers::time::webkit_timestamp_to_datetime;
+
+/// Offset of the VSS volume header inside an NTFS volume.
+pub const VOLUME_HEADER_OFFSET: u64 = 0x1E00;
+/// Size of catalog, block list, and copied data blocks.
+pub const BLOCK_SIZE: u64 = 0x4000;
+
+const VSS_IDENTIFIER: [u8; 16] = [
+    0x6B, 0x87, 0x08, 0x38, 0x76, 0xC1, 0x48, 0x4E, 0xB7, 0xAE, 0x04, 0x04, 0x6E, 0x6C, 0xC7, 0x52,
+];
+const RECORD_VOLUME_HEADER: u32 = 1;
+const RECORD_CATALOG: u32 = 2;
+const RECORD_BLOCK_LIST: u32 = 3;
+const BLOCK_HEADER_LEN: usize = 128;
+const CATALOG_ENTRY_LEN: usize = 128;
+const DESCRIPTOR_LEN: usize = 32;
+/// Upper bound on catalog and block-list blocks followed per chain.
+const MAX_LIST_BLOCKS: usize = 1 << 20;
+
+/// Descriptor flag: the block lives at `relative_offset` in the next store.
+pub const FLAG_FORWARDER: u32 = 0x1;
+/// Descriptor flag: only the 512-byte sectors set in `bitmap` are stored.
+pub const FLAG_OVERLAY: u32 = 0x2;
+/// Descriptor flag: the descriptor is not in use.
+pub const FLAG_NOT_USED: u32 = 0x4;
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum VssError {
+    #[error("evidence read failed: {0}")]
+    Evidence(#[from] EvidenceError),
+    #[error("malformed VSS {what} at volume offset {offset}")]
+    Malformed { what: &'static str, offset: u64 },
+}
+
+/// An NTFS volume inside the evidence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct NtfsVolume {
+    /// Evidence offset of the boot sector.
+    pub offset: u64,
+    /// Volume size from the boot sector.
+    pub size: u64,
+}
+
+/// One copied block in a store's block list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockDescriptor {
+    /// Volume offset of the block in the snapshot.
+    pub original_offset: u64,
+    pub relative_offset: u64,
+    /// Volume offset of the copied data.
+    pub store_offset: u64,
+    pub flags: u32,
+    /// Sectors held by an overlay descriptor, one bit per 512 bytes.
+    pub bitmap: u32,
+}
+
+/// One shadow copy store.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShadowStore {
+    /// Store GUID in registry format.
+    pub store_id: String,
+    pub sequence: u64,
+    pub created: Option<NaiveDateTime>,
+    pub volume_size: u64,
+    pub block_list_offset: u64,
+    /// Descriptors by original block offset; later list entries win.
+    pub blocks: HashMap<u64, BlockDescriptor>,
+}
+
+impl ShadowStore {
+    /// Original offsets of the blocks this store holds data for, sorted.
+    pub fn changed_blocks(&self) -> Vec<u64> {
+        let mut out: Vec<u64> = self
+            .blocks
+            .values()
+            .filter(|d| d.flags & (FLAG_FORWARDER | FLAG_NOT_USED) == 0)
+            .map(|d| d.original_offset)
+            .collect();
+        out.sort_unstable();
+        out
+    }
+}
+
+/// NTFS volumes at offset 0 or in a primary MBR partition.
+pub fn find_ntfs_volumes(source: &dyn EvidenceSource) -> Vec<NtfsVolume> {
+    let mut sector = [0u8; 512];
+    if read_exact(source, 0, &mut sector).is_err() {
+        return Vec::new();
+    }
+    if let Some(volume) = ntfs_boot_sector(&sector, 0) {
+        return vec![volume];
+    }
+    if sector[510..512] != [0x55, 0xAA] {
+        return Vec::new();
+    }
+    let mut volumes = Vec::new();
+    for entry in sector[446..510].chunks_exact(16) {
+        let lba = u32::from_le_bytes([entry[8], entry[9], entry[10], entry[11]]) as u64;
+        // 0x07 is NTFS/exFAT/HPFS, 0x27 a Windows recovery partition.
+        if !matches!(entry[4], 0x07 | 0x27) || lba == 0 {
+            continue;
+        }
+        let mut boot = [0u8; 512];
+        if read_exact(source, lba * 512, &mut boot).is_ok()
+            && let Some(volume) = ntfs_boot_sector(&boot, lba * 512)
+        {
+            volumes.push(volume);
+        }
+    }
+    volumes
+}
+
+fn ntfs_boot_sector(sector: &[u8; 512], offset: u64) -> Option<NtfsVolume> {
+    if &sector[3..11] != b"NTFS    " || sector[510..512] != [0x55, 0xAA] {
+        return None;
+    }
+    let bytes_per_sector = u16::from_le_bytes([sector[11], sector[12]]) as u64;
+    let total_sectors = le_u64(sector, 40);
+    if !bytes_per_sector.is_power_of_two() || !(256..=4096).contains(&bytes_per_sector) {
+        return None;
+    }
+    Some(NtfsVolume {
+        offset,
+        size: total_sectors.checked_mul(bytes_per_sector)?,
+    })
+}
+
+/// Stores of the volume at `volume_offset`, oldest first. Empty if the volume
+/// has no VSS header or no catalog.
+pub fn read_stores(
+    source: &dyn EvidenceSource,
+    volume_offset: u64,
+) -> Result<Vec<ShadowStore>, VssError> {
+    let mut header = [0u8; 512];
+    if read_exact(source, volume_offset + VOLUME_HEADER_OFFSET, &mut header).is_err()
+        || !is_record(&header, RECORD_VOLUME_HEADER)
+    {
+        return Ok(Vec::new());
+    }
+    let catalog_offset = le_u64(&header, 48);
+    if catalog_offset == 0 {
+        return Ok(Vec::new());
+    }
+
+    // Type 2 and type 3 entries of one store follow each other; pair them
+    // by store GUID in case they do not.
+    let mut infos: Vec<(String, u64, u64, u64)> = Vec::new();
+    let mut locations: HashMap<String, u64> = HashMap::new();
+    for block in read_list(source, volume_offset, catalog_offset, RECORD_CATALOG)? {
+        for entry in block[BLOCK_HEADER_LEN..].chunks_exact(CATALOG_ENTRY_LEN) {
+            match le_u64(entry, 0) {
+                2 => infos.push((
+                    guid(&entry[16..32]),
+                    le_u64(entry, 8),
+                    le_u64(entry, 32),
+                    le_u64(entry, 48),
+                )),
+                3 => {
+                    locations.insert(guid(&entry[16..32]), le_u64(entry, 8));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut stores = Vec::new();
+    for (store_id, volume_size, sequence, created) in infos {
+        let Some(&block_list_offset) = locations.get(&store_id) else {
+            continue;
+        };
+        let mut blocks = HashMap::new();
+        for block in read_list(source, volume_offset, block_list_offset, RECORD_BLOCK_LIST)? {
+            for entry in block[BLOCK_HEADER_LEN..].chunks_exact(DESCRIPTOR_LEN) {
+                let descriptor = BlockDescriptor {
+                    original_offset: le_u64(entry, 0),
+                    relative_offset: le_u64(entry, 8),
+                    store_offset: le_u64(entry, 16),
+                    flags: le_u32(entry, 24),
+                    bitmap: le_u32(entry, 28),
+                };
+                if descriptor.original_offset == 0
+                    && descriptor.store_offset == 0
+                    && descriptor.flags == 0
+                {
+                    continue;
+                }
+                if !descriptor.original_offset.is_multiple_of(BLOCK_SIZE) {
+                    continue;
+                }
+                blocks.insert(descriptor.original_offset, descriptor);
+            }
+        }
+        stores.push(ShadowStore {
+            store_id,
+            sequence,
+            created: webkit_timestamp_to_datetime((created / 10) as i64),
+            volume_size,
+            block_list_offset,
+            blocks,
+        });
+    }
+    stores.sort_by_key(|s| s.sequence);
+    Ok(stores)
+}
+
+/// Blocks of a chained catalog or block list, starting at `offset`.
+fn read_list(
+    source: &dyn EvidenceSource,
+    volume_offset: u64,
+    mut offset: u64,
+    record_type: u32,
+) -> Result<Vec<Vec<u8>>, VssError> {
+    let mut blocks = Vec::new();
+    while offset != 0 && blocks.len() < MAX_LIST_BLOCKS {
+        let mut block = vec![0u8; BLOCK_SIZE as usize];
+        read_exact(source, volume_offset + offset, &mut block)?;
+        if !is_record(&block, record_type) || le_u64(&block, 32) != offset {
+            return Err(VssError::Malformed {
+                what: if record_type == RECORD_CATALOG {
+                    "catalog block"
+                } else {
+                    "store block list"
+                },
+                offset,
+            });
+        }
+        let next = le_u64(&block, 40);
+        blocks.push(block);
+        // A list that points back at itself ends here.
+        if next <= offset {
+            break;
+        }
+        offset = next;
+    }
+    Ok(blocks)
+}
+
+fn is_record(data: &[u8], record_type: u32) -> bool {
+    data.len() >= 24 && data[..16] == VSS_IDENTIFIER && le_u32(data, 20) == record_type
+}
+
+fn read_exact(source: &dyn EvidenceSource, offset: u64, buf: &mut [u8]) -> Result<(), VssError> {
+    let mut done = 0;
+    while done < buf.len() {
+        let n = source.read_at(offset + done as u64, &mut buf[done..])?;
+        if n == 0 {
+            return Err(VssError::Malformed {
+                what: "structure past the end of the evidence",
+                offset,
+            });
+        }
+        done += n;
+    }
+    Ok(())
+}
+
+/// Registry-format GUID (`{...}` without braces, upper case).
+fn guid(bytes: &[u8]) -> String {
+    format!(
+        "{:08X}-{:04X}-{:04X}-{}-{}",
+        le_u32(bytes, 0),
+        u16::from_le_bytes([bytes[4], bytes[5]]),
+        u16::from_le_bytes([bytes[6], bytes[7]]),
+        hex::encode_upper(&bytes[8..10]),
+        hex::encode_upper(&bytes[10..16])
+    )
+}
+
+fn le_u32(data: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(data[at..at + 4].try_into().unwrap_or_default())
+}
+
+fn le_u64(data: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(data[at..at + 8].try_into().unwrap_or_default())
+}
+
+/// Synthetic NTFS volumes with shadow copy stores.
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::*;
+
+    /// A copied block of a store: original offset, flags, overlay bitmap,
+    /// and the 16 KiB of data.
+    pub(crate) type Copy = (u64, u32, u32, Vec<u8>);
+
+    /// FILETIME of 2024-01-01 00:00:00 UTC.
+    pub(crate) const CREATED: u64 = 133_485_408_000_000_000;
+
+    /// A volume of `size` bytes filled by `fill`, with one store per entry
+    /// of `stores` (oldest first). Store structures and data are written
+    /// from the middle of the volume on.
+    pub(crate) fn volume(size: usize, fill: impl Fn(usize) -> u8, stores: &[Vec<Copy>]) -> Vec<u8> {
+        let block = BLOCK_SIZE as usize;
+        let mut image: Vec<u8> = (0..size).map(fill).collect();
+        image[..512].fill(0);
+        image[3..11].copy_from_slice(b"NTFS    ");
+        image[11..13].copy_from_slice(&512u16.to_le_bytes());
+        image[40..48].copy_from_slice(&((size / 512) as u64).to_le_bytes());
+        image[510..512].copy_from_slice(&[0x55, 0xAA]);
+
+        let mut cursor = size / 2;
+        let catalog = cursor;
+        cursor += block;
+        let header = VOLUME_HEADER_OFFSET as usize;
+        write_record(&mut image, header, RECORD_VOLUME_HEADER, 0);
+        image[header + 48..header + 56].copy_from_slice(&(catalog as u64).to_le_bytes());
+        write_record(&mut image, catalog, RECORD_CATALOG, 0);
+
+        for (k, copies) in stores.iter().enumerate() {
+            let id = [k as u8 + 1; 16];
+            let list = cursor;
+            cursor += block;
+            write_record(&mut image, list, RECORD_BLOCK_LIST, 0);
+            for (i, (original, flags, bitmap, data)) in copies.iter().enumerate() {
+                let store_offset = cursor;
+                cursor += block;
+                image[store_offset..store_offset + block].copy_from_slice(data);
+                let at = list + BLOCK_HEADER_LEN + i * DESCRIPTOR_LEN;
+                image[at..at + 8].copy_from_slice(&original.to_le_bytes());
+                image[at + 16..at + 24].copy_from_slice(&(store_offset as u64).to_le_bytes());
+                image[at + 24..at + 28].copy_from_slice(&flags.to_le_bytes());
+                image[at + 28..at + 32].copy_from_slice(&bitmap.to_le_bytes());
+            }
+            let info = catalog + BLOCK_HEADER_LEN + k * 2 * CATALOG_ENTRY_LEN;
+            image[info..info + 8].copy_from_slice(&2u64.to_le_bytes());
+            image[info + 8..info + 16].copy_from_slice(&(size as u64).to_le_bytes());
+            image[info + 16..info + 32].copy_from_slice(&id);
+            image[info + 32..info + 40].copy_from_slice(&(k as u64 + 1).to_le_bytes());
+            image[info + 48..info + 56].copy_from_slice(&CREATED.to_le_bytes());
+            let location = info + CATALOG_ENTRY_LEN;
+            image[location..location + 8].copy_from_slice(&3u64.to_le_bytes());
+            image[location + 8..location + 16].copy_from_slice(&(list as u64).to_le_bytes());
+            image[location + 16..location + 32].copy_from_slice(&id);
+        }
+        assert!(cursor <= size, "volume too small for its stores");
+        image
+    }
+
+    fn write_record(image: &mut [u8], at: usize, record_type: u32, next: u64) {
+        image[at..at + 16].copy_from_slice(&VSS_IDENTIFIER);
+        image[at + 16..at + 20].copy_from_slice(&1u32.to_le_bytes());
+        image[at + 20..at + 24].copy_from_slice(&record_type.to_le_bytes());
+        if record_type != RECORD_VOLUME_HEADER {
+            image[at + 32..at + 40].copy_from_slice(&(at as u64).to_le_bytes());
+            image[at + 40..at + 48].copy_from_slice(&next.to_le_bytes());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::{CREATED, volume};
+    use super::*;
+    use crate::evidence::{MemorySource, ShadowCopySource};
+    use std::sync::Arc;
+
+    const BLOCK: usize = BLOCK_SIZE as usize;
+
+    #[test]
+    fn lists_stores_and_reads_snapshots() {
+        let mut overlay = vec![b'v'; BLOCK];
+        overlay[..512].fill(b'?');
+        let image = volume(
+            64 * BLOCK,
+            |_| b'c',
+            &[
+                vec![
+                    (2 * BLOCK as u64, 0, 0, vec![b'0'; BLOCK]),
+                    // Sector 1 of block 4 only.
+                    (4 * BLOCK as u64, FLAG_OVERLAY, 0b10, overlay),
+                ],
+                vec![
+                    (2 * BLOCK as u64, 0, 0, vec![b'1'; BLOCK]),
+                    (3 * BLOCK as u64, 0, 0, vec![b'x'; BLOCK]),
+                ],
+            ],
+        );
+        let source: Arc<dyn EvidenceSource> = Arc::new(MemorySource::new(image));
+        assert_eq!(
+            find_ntfs_volumes(source.as_ref()),
+            vec![NtfsVolume {
+                offset: 0,
+                size: 64 * BLOCK_SIZE
+            }]
+        );
+
+        let stores = Arc::new(read_stores(source.as_ref(), 0).expect("stores"));
+        assert_eq!(stores.len(), 2);
+        assert_eq!(stores[0].store_id, "01010101-0101-0101-0101-010101010101");
+        assert_eq!(
+            stores[0].created,
+            webkit_timestamp_to_datetime((CREATED / 10) as i64)
+        );
+        assert_eq!(
+            stores[0].changed_blocks(),
+            vec![2 * BLOCK_SIZE, 4 * BLOCK_SIZE]
+        );
+
+        let byte = |snapshot: &ShadowCopySource, offset: usize| {
+            let mut b = [0u8; 1];
+            snapshot.read_at(offset as u64, &mut b).expect("read");
+            b[0]
+        };
+        let oldest = ShadowCopySource::new(source.clone(), 0, stores.clone(), 0);
+        assert_eq!(oldest.len(), 64 * BLOCK_SIZE);
+        assert_eq!(byte(&oldest, 2 * BLOCK + 5), b'0');
+        // Not copied by the oldest store: the newer store's copy.
+        assert_eq!(byte(&oldest, 3 * BLOCK + 5), b'x');
+        assert_eq!(byte(&oldest, 4 * BLOCK + 5), b'c');
+        assert_eq!(byte(&oldest, 4 * BLOCK + 600), b'v');
+        let newest = ShadowCopySource::new(source.clone(), 0, stores, 1);
+        assert_eq!(byte(&newest, 2 * BLOCK + 5), b'1');
+        assert_eq!(byte(&newest, 4 * BLOCK + 600), b'c');
+
+        // A read across blocks.
+        let mut buf = vec![0u8; BLOCK + 2];
+        assert_eq!(
+            oldest.read_at(3 * BLOCK as u64 - 1, &mut buf).unwrap(),
+            buf.len()
+        );
+        assert_eq!(
+            (buf[0], buf[1], buf[BLOCK], buf[BLOCK + 1]),
+            (b'0', b'x', b'x', b'c')
+        );
+    }
+
+    #[test]
+    fn volumes_without_vss_have_no_stores() {
+        let mut image = volume(64 * BLOCK, |_| 0, &[]);
+        assert!(
+            read_stores(&MemorySource::new(image.clone()), 0)
+                .unwrap()
+                .is_empty()
+        );
+        let header = VOLUME_HEADER_OFFSET as usize;
+        image[header..header + 16].fill(0);
+        assert!(
+            read_stores(&MemorySource::new(image), 0)
+                .unwrap()
+                .is_empty()
+        );
+        assert!(find_ntfs_volumes(&MemorySource::new(vec![0u8; 4096])).is_empty());
+    }
+}
@@ -0,0 +1,360 @@
+//! LZXPRESS Huffman decompression (`[MS-XCA]` 2.1 and 2.2.4).
+//!
+//! Windows 10 compresses prefetch files, among others, with this format. The
+//! output is produced in blocks of 64 KiB; each block starts with 256 bytes
+//! holding the 4-bit code lengths of 512 symbols (literals 0-255 and match
+//! symbols 256-511), followed by a bit stream read MSB-first in 16-bit
+//! little-endian words. A match symbol encodes a length in its low 4 bits and
+//! the bit count of its offset in the high 4 bits; long lengths continue in
+//! bytes taken from the input between words.
+//!
+//! The compressed length is not stored anywhere, so [`decompress`] reports
+//! how much input it consumed, which carvers use as the end of the data.
+
+use thiserror::Error;
+
+/// Output bytes per block; each block carries its own code lengths.
+const BLOCK_SIZE: usize = 64 * 1024;
+const TABLE_LEN: usize = 256;
+const SYMBOLS: usize = 512;
+const MAX_CODE_LEN: u32 = 15;
+/// Lookup entry of codes an incomplete table leaves unassigned.
+const UNASSIGNED: u16 = SYMBOLS as u16;
+
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum XpressError {
+    #[error("input ends inside a block")]
+    Eof,
+    #[error("invalid code lengths")]
+    InvalidTable,
+    #[error("match before the start of the output")]
+    InvalidOffset,
+    #[error("invalid match length")]
+    InvalidLength,
+}
+
+/// Output of [`decompress`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decompressed {
+    pub data: Vec<u8>,
+    /// Input bytes read: the code length tables, every 16-bit word the bit
+    /// reader loaded, and the extra length bytes.
+    pub consumed: usize,
+}
+
+/// Decompress `output_len` bytes from the start of `input`.
+pub fn decompress(input: &[u8], output_len: usize) -> Result<Decompressed, XpressError> {
+    let mut out = Vec::with_capacity(output_len);
+    let mut pos = 0usize;
+    while out.len() < output_len {
+        let table = input.get(pos..pos + TABLE_LEN).ok_or(XpressError::Eof)?;
+        let decoder = Decoder::new(table)?;
+        pos += TABLE_LEN;
+        let mut bits = BitReader::new(input, pos)?;
+        let block_end = (out.len() + BLOCK_SIZE).min(output_len);
+        while out.len() < block_end {
+            let symbol = decoder.decode(&mut bits)?;
+            if symbol < 256 {
+                out.push(symbol as u8);
+                continue;
+            }
+            let symbol = symbol - 256;
+            let mut length = symbol & 15;
+            let offset_bits = (symbol >> 4) as u32;
+            if length == 15 {
+                length = usize::from(bits.byte()?);
+                if length == 255 {
+                    length = usize::from(bits.u16()?);
+                    if length == 0 {
+                        length = bits.u32()? as usize;
+                    }
+                    length = length.checked_sub(15).ok_or(XpressError::InvalidLength)?;
+                }
+                length += 15;
+            }
+            length += 3;
+            let offset = (bits.take(offset_bits)? as usize) | (1usize << offset_bits);
+            if offset > out.len() {
+                return Err(XpressError::InvalidOffset);
+            }
+            // Matches may run past the end of a block, but not the output.
+            let length = length.min(output_len - out.len());
+            let start = out.len() - offset;
+            for i in 0..length {
+                let byte = out[start + i];
+                out.push(byte);
+            }
+        }
+        pos = bits.pos;
+    }
+    Ok(Decompressed {
+        data: out,
+        consumed: pos,
+    })
+}
+
+/// Canonical Huffman code of one block, as a 2^15-entry lookup table.
+struct Decoder {
+    table: Vec<u16>,
+    lengths: [u8; SYMBOLS],
+}
+
+impl Decoder {
+    fn new(raw: &[u8]) -> Result<Self, XpressError> {
+        let mut lengths = [0u8; SYMBOLS];
+        for (i, byte) in raw.iter().enumerate() {
+            lengths[2 * i] = byte & 0x0F;
+            lengths[2 * i + 1] = byte >> 4;
+        }
+        let mut table = vec![UNASSIGNED; 1 << MAX_CODE_LEN];
+        let mut next = 0usize;
+        for len in 1..=MAX_CODE_LEN {
+            for (symbol, _) in lengths
+                .iter()
+                .enumerate()
+                .filter(|&(_, &l)| u32::from(l) == len)
+            {
+                let span = 1usize << (MAX_CODE_LEN - len);
+                if next + span > table.len() {
+                    return Err(XpressError::InvalidTable);
+                }
+                table[next..next + span].fill(symbol as u16);
+                next += span;
+            }
+        }
+        if next == 0 {
+            return Err(XpressError::InvalidTable);
+        }
+        Ok(Self { table, lengths })
+    }
+
+    fn decode(&self, bits: &mut BitReader) -> Result<usize, XpressError> {
+        let symbol = self.table[(bits.next >> (32 - MAX_CODE_LEN)) as usize];
+        if symbol == UNASSIGNED {
+            return Err(XpressError::InvalidTable);
+        }
+        bits.take(u32::from(self.lengths[usize::from(symbol)]))?;
+        Ok(usize::from(symbol))
+    }
+}
+
+/// The 32-bit window of `[MS-XCA]`: `next` holds the upcoming bits at the
+/// top, `extra` how many bits past the first 16 are loaded.
+struct BitReader<'a> {
+    input: &'a [u8],
+    pos: usize,
+    next: u32,
+    extra: i32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(input: &'a [u8], pos: usize) -> Result<Self, XpressError> {
+        let mut reader = Self {
+            input,
+            pos,
+            next: 0,
+            extra: 16,
+        };
+        let high = u32::from(reader.u16()?);
+        let low = u32::from(reader.u16()?);
+        reader.next = (high << 16) | low;
+        Ok(reader)
+    }
+
+    /// Consume the top `count` bits and return them.
+    fn take(&mut self, count: u32) -> Result<u32, XpressError> {
+        if count == 0 {
+            return Ok(0);
+        }
+        let value = self.next >> (32 - count);
+        self.next = if count == 32 { 0 } else { self.next << count };
+        self.extra -= count as i32;
+        if self.extra < 0 {
+            self.next |= u32::from(self.u16()?) << (-self.extra);
+            self.extra += 16;
+        }
+        Ok(value)
+    }
+
+    fn byte(&mut self) -> Result<u8, XpressError> {
+        let byte = *self.input.get(self.pos).ok_or(XpressError::Eof)?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn u16(&mut self) -> Result<u16, XpressError> {
+        let bytes = self
+            .input
+            .get(self.pos..self.pos + 2)
+            .ok_or(XpressError::Eof)?;
+        self.pos += 2;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, XpressError> {
+        let bytes = self
+            .input
+            .get(self.pos..self.pos + 4)
+            .ok_or(XpressError::Eof)?;
+        self.pos += 4;
+        Ok(u32::from_le_bytes(bytes.try_into().expect("4 bytes")))
+    }
+}
+
+/// An encoder for tests: every symbol gets a 9-bit code, so the code of a
+/// symbol is its value.
+#[cfg(test)]
+pub(crate) mod testing {
+    /// One token of the compressed stream.
+    #[derive(Clone, Copy)]
+    pub(crate) enum Token {
+        Literal(u8),
+        Match { length: usize, offset: usize },
+    }
+
+    struct Writer {
+        out: Vec<u8>,
+        slots: [usize; 2],
+        acc: u32,
+        count: u32,
+    }
+
+    impl Writer {
+        fn new(out: Vec<u8>) -> Self {
+            let start = out.len();
+            let mut out = out;
+            out.extend_from_slice(&[0; 4]);
+            Self {
+                out,
+                slots: [start, start + 2],
+                acc: 0,
+                count: 0,
+            }
+        }
+
+        fn bits(&mut self, value: u32, count: u32) {
+            for i in (0..count).rev() {
+                if self.count == 16 {
+                    self.flush_word();
+                }
+                self.acc = (self.acc << 1) | ((value >> i) & 1);
+                self.count += 1;
+            }
+        }
+
+        fn flush_word(&mut self) {
+            let word = (self.acc as u16).to_le_bytes();
+            self.out[self.slots[0]..self.slots[0] + 2].copy_from_slice(&word);
+            self.slots = [self.slots[1], self.out.len()];
+            self.out.extend_from_slice(&[0; 2]);
+            self.acc = 0;
+            self.count = 0;
+        }
+
+        fn finish(mut self) -> Vec<u8> {
+            if self.count > 0 {
+                self.acc <<= 16 - self.count;
+                self.count = 16;
+                let word = (self.acc as u16).to_le_bytes();
+                self.out[self.slots[0]..self.slots[0] + 2].copy_from_slice(&word);
+            }
+            self.out
+        }
+    }
+
+    /// Compress `blocks`, each the tokens of one 64 KiB output block.
+    pub(crate) fn compress(blocks: &[Vec<Token>]) -> Vec<u8> {
+        let mut out = Vec::new();
+        for tokens in blocks {
+            out.extend_from_slice(&[0x99; 256]);
+            let mut writer = Writer::new(out);
+            for token in tokens {
+                match *token {
+                    Token::Literal(byte) => writer.bits(u32::from(byte), 9),
+                    Token::Match { length, offset } => {
+                        let offset_bits = usize::BITS - 1 - offset.leading_zeros();
+                        let extra = length - 3;
+                        let symbol = 256 + ((offset_bits as usize) << 4) + extra.min(15);
+                        writer.bits(symbol as u32, 9);
+                        if extra >= 15 {
+                            if extra - 15 < 255 {
+                                writer.out.push((extra - 15) as u8);
+                            } else {
+                                writer.out.push(255);
+                                writer.out.extend_from_slice(&(extra as u16).to_le_bytes());
+                            }
+                        }
+                        writer.bits((offset - (1 << offset_bits)) as u32, offset_bits);
+                    }
+                }
+            }
+            out = writer.finish();
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::{Token, compress};
+    use super::*;
+
+    #[test]
+    fn decodes_literals_and_matches() {
+        let tokens = vec![
+            Token::Literal(b'a'),
+            Token::Literal(b'b'),
+            Token::Literal(b'c'),
+            Token::Match {
+                length: 6,
+                offset: 3,
+            },
+            Token::Literal(b'!'),
+            Token::Match {
+                length: 40,
+                offset: 1,
+            },
+            Token::Match {
+                length: 300,
+                offset: 50,
+            },
+        ];
+        let mut compressed = compress(&[tokens]);
+        let expected_len = 3 + 6 + 1 + 40 + 300;
+        let consumed = compressed.len();
+        compressed.extend_from_slice(b"trailing");
+
+        let out = decompress(&compressed, expected_len).expect("decompress");
+        assert_eq!(&out.data[..10], b"abcabcabc!");
+        assert!(out.data[10..50].iter().all(|&b| b == b'!'));
+        assert_eq!(&out.data[50..60], b"abcabcabc!");
+        assert_eq!(out.consumed, consumed);
+    }
+
+    #[test]
+    fn decodes_every_block() {
+        let first: Vec<Token> = (0..BLOCK_SIZE).map(|i| Token::Literal(i as u8)).collect();
+        let second = vec![Token::Literal(b'x'), Token::Literal(b'y')];
+        let compressed = compress(&[first, second]);
+        let out = decompress(&compressed, BLOCK_SIZE + 2).expect("decompress");
+        assert_eq!(out.data.len(), BLOCK_SIZE + 2);
+        assert_eq!(out.data[255], 255);
+        assert_eq!(&out.data[BLOCK_SIZE..], b"xy");
+        assert_eq!(out.consumed, compressed.len());
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        assert_eq!(decompress(&[0u8; 300], 10), Err(XpressError::InvalidTable));
+        let compressed = compress(&[vec![Token::Match {
+            length: 4,
+            offset: 2,
+        }]]);
+        assert_eq!(decompress(&compressed, 4), Err(XpressError::InvalidOffset));
+        let compressed = compress(&[vec![Token::Literal(1); 20]]);
+        assert_eq!(
+            decompress(&compressed[..compressed.len() - 4], 20),
+            Err(XpressError::Eof)
+        );
+    }
+}
@@ -0,0 +1,404 @@
+//! Passes over one run output directory.
+//!
+//! A run directory can take more than one pass over the same evidence, for
+//! example a second pass with file types or scanners left out of the first.
+//! Each pass stamps its `pass_id` on every metadata record and appends to the
+//! streams of the earlier passes (see [`crate::metadata::SinkIdentity`]), so
+//! joins across passes stay within one directory.
+//!
+//! [`PassLog`] in `summaries/passes.json` records what each pass ran with and
+//! how it ended, and sums the counters of completed passes. Appending checks
+//! that the evidence and metadata backend match the first pass and reuses its
+//! evidence hash. The summary files of the previous pass are moved to
+//! `summaries/pass<N>/` before the new pass writes its own.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::inputs::InputFile;
+use crate::metadata::SinkIdentity;
+use crate::pipeline::PipelineStats;
+use crate::report::SUMMARIES_DIR;
+
+/// Pass log file name inside `summaries/`.
+pub const PASS_LOG_FILE: &str = "passes.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PassStatus {
+    /// Started and not finished; a pass that crashed stays in this state.
+    Running,
+    Completed,
+    Failed,
+}
+
+/// Counters of one pass, or their sum over passes.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PassCounts {
+    pub bytes_scanned: u64,
+    pub hits_found: u64,
+    pub files_carved: u64,
+    pub string_spans: u64,
+    pub artefacts_extracted: u64,
+    pub carve_errors: u64,
+    pub metadata_errors: u64,
+}
+
+impl PassCounts {
+    pub fn from_stats(stats: &PipelineStats) -> Self {
+        Self {
+            bytes_scanned: stats.bytes_scanned,
+            hits_found: stats.hits_found,
+            files_carved: stats.files_carved,
+            string_spans: stats.string_spans,
+            artefacts_extracted: stats.artefacts_extracted,
+            carve_errors: stats.carve_errors,
+            metadata_errors: stats.metadata_errors,
+        }
+    }
+
+    fn add(&mut self, other: &PassCounts) {
+        self.bytes_scanned += other.bytes_scanned;
+        self.hits_found += other.hits_found;
+        self.files_carved += other.files_carved;
+        self.string_spans += other.string_spans;
+        self.artefacts_extracted += other.artefacts_extracted;
+        self.carve_errors += other.carve_errors;
+        self.metadata_errors += other.metadata_errors;
+    }
+}
+
+/// One pass over the run directory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PassRecord {
+    pub pass_id: u32,
+    pub status: PassStatus,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+    pub tool_version: String,
+    pub config_hash: String,
+    /// `jsonl`, `csv`, or `parquet`.
+    pub metadata_backend: String,
+    pub evidence_path: String,
+    /// Empty when no hash was given or computed.
+    pub evidence_sha256: String,
+    /// File type ids enabled for the pass.
+    pub file_types: Vec<String>,
+    /// String scanning was enabled.
+    pub string_scan: bool,
+    /// Config, signature, and hash list files read by the pass.
+    #[serde(default)]
+    pub input_files: Vec<InputFile>,
+    pub counts: Option<PassCounts>,
+    pub error: Option<String>,
+}
+
+impl PassRecord {
+    /// A pass that starts now. Counts are filled in by [`PassLog::finish`].
+    pub fn started(
+        identity: &SinkIdentity,
+        metadata_backend: &str,
+        file_types: Vec<String>,
+        string_scan: bool,
+        input_files: Vec<InputFile>,
+    ) -> Self {
+        Self {
+            pass_id: identity.pass_id,
+            status: PassStatus::Running,
+            started_at: Utc::now().to_rfc3339(),
+            finished_at: None,
+            tool_version: identity.tool_version.to_string(),
+            config_hash: identity.config_hash.to_string(),
+            metadata_backend: metadata_backend.to_string(),
+            evidence_path: identity.evidence_path.to_string_lossy().to_string(),
+            evidence_sha256: identity.evidence_sha256.to_string(),
+            file_types,
+            string_scan,
+            input_files,
+            counts: None,
+            error: None,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum PassError {
+    #[error(
+        "{} has no {PASS_LOG_FILE}; only run directories written with a pass log can be appended to",
+        .0.display()
+    )]
+    Missing(PathBuf),
+    #[error(
+        "evidence {given} does not match pass 1 ({expected}); give --evidence-sha256 if the same image moved"
+    )]
+    EvidenceMismatch { expected: String, given: String },
+    #[error("metadata backend {given} differs from pass 1 ({expected})")]
+    BackendMismatch { expected: String, given: String },
+    #[error("pass log io error: {0}")]
+    Io(#[from] io::Error),
+    #[error("pass log json error: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// Contents of `summaries/passes.json`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PassLog {
+    pub run_id: String,
+    pub passes: Vec<PassRecord>,
+    /// Sum of the counts of completed passes.
+    pub totals: PassCounts,
+}
+
+impl PassLog {
+    pub fn new(run_id: &str) -> Self {
+        Self {
+            run_id: run_id.to_string(),
+            passes: Vec::new(),
+            totals: PassCounts::default(),
+        }
+    }
+
+    pub fn path(run_output_dir: &Path) -> PathBuf {
+        run_output_dir.join(SUMMARIES_DIR).join(PASS_LOG_FILE)
+    }
+
+    /// Read the pass log of an existing run directory.
+    pub fn load(run_output_dir: &Path) -> Result<Self, PassError> {
+        let path = Self::path(run_output_dir);
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                return Err(PassError::Missing(run_output_dir.to_path_buf()));
+            }
+            Err(err) => return Err(err.into()),
+        };
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    /// Write the log, replacing the previous version atomically.
+    pub fn save(&self, run_output_dir: &Path) -> Result<PathBuf, PassError> {
+        let path = Self::path(run_output_dir);
+        fs::create_dir_all(path.parent().unwrap_or(run_output_dir))?;
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(self)?)?;
+        fs::rename(&tmp, &path)?;
+        Ok(path)
+    }
+
+    pub fn next_pass_id(&self) -> u32 {
+        self.passes.iter().map(|p| p.pass_id).max().unwrap_or(0) + 1
+    }
+
+    /// Evidence hash recorded by an earlier pass.
+    pub fn evidence_sha256(&self) -> Option<&str> {
+        self.passes
+            .iter()
+            .map(|p| p.evidence_sha256.as_str())
+            .find(|hash| !hash.is_empty())
+    }
+
+    /// Check that a new pass reads the evidence of the first pass with the
+    /// same metadata backend. The evidence matches when both hashes are known
+    /// and equal, or otherwise when the paths are equal.
+    pub fn check_append(
+        &self,
+        evidence_path: &Path,
+        evidence_sha256: &str,
+        metadata_backend: &str,
+    ) -> Result<(), PassError> {
+        let Some(first) = self.passes.first() else {
+            return Ok(());
+        };
+        if first.metadata_backend != metadata_backend {
+            return Err(PassError::BackendMismatch {
+                expected: first.metadata_backend.clone(),
+                given: metadata_backend.to_string(),
+            });
+        }
+        let recorded = self.evidence_sha256().unwrap_or("");
+        let same = if !recorded.is_empty() && !evidence_sha256.is_empty() {
+            recorded.eq_ignore_ascii_case(evidence_sha256)
+        } else {
+            Path::new(&first.evidence_path) == evidence_path
+        };
+        if same {
+            Ok(())
+        } else {
+            Err(PassError::EvidenceMismatch {
+                expected: first.evidence_path.clone(),
+                given: evidence_path.display().to_string(),
+            })
+        }
+    }
+
+    pub fn begin(&mut self, record: PassRecord) {
+        self.passes.push(record);
+    }
+
+    /// Record the SHA-256 hashed while pass `pass_id` scanned, unless the pass
+    /// already has one. Returns the hash it already had when that differs.
+    pub fn record_evidence_sha256(&mut self, pass_id: u32, sha256: &str) -> Option<String> {
+        let pass = self.passes.iter_mut().find(|p| p.pass_id == pass_id)?;
+        if pass.evidence_sha256.is_empty() {
+            pass.evidence_sha256 = sha256.to_string();
+            None
+        } else if pass.evidence_sha256.eq_ignore_ascii_case(sha256) {
+            None
+        } else {
+            Some(pass.evidence_sha256.clone())
+        }
+    }
+
+    /// Mark pass `pass_id` finished and recompute the totals.
+    pub fn finish(&mut self, pass_id: u32, result: Result<PassCounts, String>) {
+        if let Some(pass) = self.passes.iter_mut().find(|p| p.pass_id == pass_id) {
+            pass.finished_at = Some(Utc::now().to_rfc3339());
+            match result {
+                Ok(counts) => {
+                    pass.status = PassStatus::Completed;
+                    pass.counts = Some(counts);
+                }
+                Err(err) => {
+                    pass.status = PassStatus::Failed;
+                    pass.error = Some(err);
+                }
+            }
+        }
+        let mut totals = PassCounts::default();
+        for pass in &self.passes {
+            if let (PassStatus::Completed, Some(counts)) = (pass.status, &pass.counts) {
+                totals.add(counts);
+            }
+        }
+        self.totals = totals;
+    }
+}
+
+/// Move the summary files of pass `pass_id` from `summaries/` into
+/// `summaries/pass<pass_id>/`, leaving the pass log and earlier pass
+/// directories in place.
+pub fn archive_summaries(run_output_dir: &Path, pass_id: u32) -> io::Result<()> {
+    let dir = run_output_dir.join(SUMMARIES_DIR);
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+    };
+    let archive = dir.join(format!("pass{pass_id}"));
+    for entry in entries {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() || entry.file_name() == PASS_LOG_FILE {
+            continue;
+        }
+        fs::create_dir_all(&archive)?;
+        fs::rename(entry.path(), archive.join(entry.file_name()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(pass_id: u32, evidence: &str, sha256: &str) -> PassRecord {
+        let identity = SinkIdentity {
+            run_id: "run",
+            tool_version: "0.3.0",
+            config_hash: "hash",
+            evidence_path: Path::new(evidence),
+            evidence_sha256: sha256,
+            pass_id,
+        };
+        PassRecord::started(
+            &identity,
+            "jsonl",
+            vec!["jpeg".to_string()],
+            false,
+            Vec::new(),
+        )
+    }
+
+    fn carved(files_carved: u64) -> PassCounts {
+        PassCounts {
+            files_carved,
+            ..PassCounts::default()
+        }
+    }
+
+    #[test]
+    fn appends_passes_and_sums_completed_counts() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        assert!(matches!(
+            PassLog::load(dir.path()),
+            Err(PassError::Missing(_))
+        ));
+        let mut log = PassLog::new("run");
+        log.begin(record(1, "/img.dd", ""));
+        log.finish(1, Ok(carved(3)));
+        log.save(dir.path()).expect("save");
+
+        let mut log = PassLog::load(dir.path()).expect("load");
+        assert_eq!(log.next_pass_id(), 2);
+        log.check_append(Path::new("/img.dd"), "", "jsonl")
+            .expect("same evidence");
+        assert!(matches!(
+            log.check_append(Path::new("/other.dd"), "", "jsonl"),
+            Err(PassError::EvidenceMismatch { .. })
+        ));
+        assert!(matches!(
+            log.check_append(Path::new("/img.dd"), "", "csv"),
+            Err(PassError::BackendMismatch { .. })
+        ));
+        log.begin(record(2, "/img.dd", ""));
+        log.finish(2, Ok(carved(4)));
+        log.begin(record(3, "/img.dd", ""));
+        log.finish(3, Err("cancelled".to_string()));
+        assert_eq!(log.totals.files_carved, 7);
+        assert_eq!(log.passes[2].status, PassStatus::Failed);
+    }
+
+    #[test]
+    fn moved_evidence_matches_by_hash() {
+        let mut log = PassLog::new("run");
+        log.begin(record(1, "/mnt/a/img.dd", "ABCD"));
+        assert_eq!(log.evidence_sha256(), Some("ABCD"));
+        log.check_append(Path::new("/mnt/b/img.dd"), "abcd", "jsonl")
+            .expect("same hash");
+        assert!(
+            log.check_append(Path::new("/mnt/a/img.dd"), "ffff", "jsonl")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn records_sha256_hashed_while_scanning() {
+        let mut log = PassLog::new("run");
+        log.begin(record(1, "/img.dd", ""));
+        assert_eq!(log.record_evidence_sha256(1, "abcd"), None);
+        assert_eq!(log.evidence_sha256(), Some("abcd"));
+        log.begin(record(2, "/img.dd", "ABCD"));
+        assert_eq!(log.record_evidence_sha256(2, "abcd"), None);
+        assert_eq!(
+            log.record_evidence_sha256(2, "ffff"),
+            Some("ABCD".to_string())
+        );
+    }
+
+    #[test]
+    fn archives_previous_summaries() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let summaries = dir.path().join(SUMMARIES_DIR);
+        fs::create_dir_all(&summaries).expect("mkdir");
+        fs::write(summaries.join("pattern_stats.json"), "{}").expect("write");
+        PassLog::new("run").save(dir.path()).expect("save");
+        archive_summaries(dir.path(), 1).expect("archive");
+        assert!(summaries.join("pass1").join("pattern_stats.json").is_file());
+        assert!(!summaries.join("pattern_stats.json").exists());
+        assert!(summaries.join(PASS_LOG_FILE).is_file());
+    }
+}
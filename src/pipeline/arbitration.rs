@@ -0,0 +1,603 @@
+//! # Overlap Arbitration
+//!
+//! The same bytes can match several handlers: a RIFF container as WAV and as
+//! WebP, an OLE file as a generic compound document and as a mail message.
+//! [`CarveArbiter`] compares each carve with the carves of other handlers that
+//! start at the same offset and overlap it by at least `arbitration_overlap`
+//! of the larger one, and keeps the most confident; the others are deleted and listed in
+//! `summaries/carve_arbitration.json` with the carve that won.
+//!
+//! Carve workers run in parallel, so a carve waits (briefly) before it is
+//! kept while another handler is still carving a hit at its offset. A more
+//! confident competitor arriving after that replaces the kept carve: the
+//! earlier file is deleted once its worker has finished writing it, and
+//! listed as replaced. Its `carved_files` record stays in the metadata.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::carve::CarvedFile;
+use crate::config::Config;
+use crate::report::SUMMARIES_DIR;
+use crate::scanner::NormalizedHit;
+
+/// File name of the arbitration summary under `summaries/`.
+pub const ARBITRATION_SUMMARY_FILE: &str = "carve_arbitration.json";
+
+/// Longest a carve waits for competing carves still running.
+const SETTLE_WAIT: Duration = Duration::from_secs(2);
+/// Kept carves remembered for comparison; the lowest offsets go first.
+const MAX_CLAIMS: usize = 16 * 1024;
+/// Suppressed carves listed in the summary; all are counted.
+const MAX_LISTED: usize = 10_000;
+
+/// Whether a settled carve stays in the output.
+#[derive(Debug)]
+pub enum Verdict<'a> {
+    Keep(KeptCarve<'a>),
+    Suppress,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Status {
+    /// Waiting for competitors still being carved.
+    Pending,
+    Kept,
+    /// A more confident carve arrived while this one was pending.
+    Beaten,
+    /// Kept, then replaced by a more confident carve; deleted on release.
+    Displaced,
+}
+
+#[derive(Debug, Clone)]
+struct Claim {
+    handler: String,
+    file_type: String,
+    path: String,
+    start: u64,
+    end: u64,
+    confidence: u8,
+    status: Status,
+    /// Where the kept carve ended up once its worker was done with it.
+    output: Option<PathBuf>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// Hits being carved: offset and handler.
+    in_flight: Vec<(u64, String)>,
+    /// Pending and kept carves by start offset and arrival.
+    claims: BTreeMap<(u64, u64), Claim>,
+    next_id: u64,
+    alternatives: Vec<SuppressedCarve>,
+}
+
+/// Picks one carve among overlapping carves of different handlers, shared by
+/// carve workers.
+#[derive(Debug)]
+pub struct CarveArbiter {
+    min_overlap: f64,
+    state: Mutex<State>,
+    settled: Condvar,
+    suppressed: AtomicU64,
+    replaced: AtomicU64,
+}
+
+/// A hit being carved, from [`CarveArbiter::begin`] until it is settled.
+/// Dropping it, on a failed carve or a panic, withdraws the hit so that
+/// competitors stop waiting for it.
+#[derive(Debug)]
+pub struct ArbitrationAttempt<'a> {
+    arbiter: &'a CarveArbiter,
+    offset: u64,
+    handler: String,
+    settled: bool,
+}
+
+/// A kept carve whose worker is still writing it. Release it with the final
+/// output path once the file is in place; dropping it releases the carve
+/// without a path.
+#[derive(Debug)]
+pub struct KeptCarve<'a> {
+    arbiter: &'a CarveArbiter,
+    key: (u64, u64),
+    /// Outputs of earlier kept carves this one replaced, to be deleted.
+    replaced: Vec<PathBuf>,
+    released: bool,
+}
+
+/// Suppressed carves written at the end of a run.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ArbitrationSummary {
+    pub run_id: String,
+    pub min_overlap: f64,
+    pub suppressed: u64,
+    /// Suppressed carves that had been kept before a better one arrived.
+    pub replaced: u64,
+    /// At most 10000 of the suppressed carves.
+    pub alternatives: Vec<SuppressedCarve>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct SuppressedCarve {
+    pub file_type: String,
+    pub handler: String,
+    pub global_start: u64,
+    pub global_end: u64,
+    pub confidence: u8,
+    pub kept_file_type: String,
+    pub kept_path: String,
+    pub kept_start: u64,
+    pub kept_end: u64,
+    pub kept_confidence: u8,
+    /// The carve had been kept, and written, before the winner arrived.
+    pub replaced: bool,
+}
+
+/// Rank of a carve: validated (4), not truncated (2), no handler errors (1).
+pub fn confidence(file: &CarvedFile) -> u8 {
+    u8::from(file.validated) * 4 + u8::from(!file.truncated) * 2 + u8::from(file.errors.is_empty())
+}
+
+impl Claim {
+    fn len(&self) -> u64 {
+        self.end - self.start
+    }
+
+    /// Higher confidence wins, then the larger carve, then the handler id,
+    /// so the result does not depend on which carve finished first.
+    fn beats(&self, other: &Claim) -> bool {
+        (self.confidence, self.len(), &other.handler)
+            > (other.confidence, other.len(), &self.handler)
+    }
+
+    fn suppressed_by(&self, winner: &Claim) -> SuppressedCarve {
+        let replaced = matches!(self.status, Status::Kept | Status::Displaced);
+        SuppressedCarve {
+            file_type: self.file_type.clone(),
+            handler: self.handler.clone(),
+            global_start: self.start,
+            global_end: self.end,
+            confidence: self.confidence,
+            kept_file_type: winner.file_type.clone(),
+            kept_path: winner.path.clone(),
+            kept_start: winner.start,
+            kept_end: winner.end,
+            kept_confidence: winner.confidence,
+            replaced,
+        }
+    }
+}
+
+impl CarveArbiter {
+    /// `None` when `arbitration_overlap` is 0.
+    pub fn from_config(cfg: &Config) -> Option<Self> {
+        (cfg.arbitration_overlap > 0.0).then(|| Self::new(cfg.arbitration_overlap))
+    }
+
+    fn new(min_overlap: f64) -> Self {
+        Self {
+            min_overlap,
+            state: Mutex::new(State::default()),
+            settled: Condvar::new(),
+            suppressed: AtomicU64::new(0),
+            replaced: AtomicU64::new(0),
+        }
+    }
+
+    /// Note that `hit` is being carved; settle the returned attempt with the
+    /// carved file, or drop it when the hit produced none.
+    pub fn begin(&self, hit: &NormalizedHit) -> ArbitrationAttempt<'_> {
+        if let Ok(mut state) = self.state.lock() {
+            state
+                .in_flight
+                .push((hit.global_offset, hit.file_type_id.clone()));
+        }
+        ArbitrationAttempt {
+            arbiter: self,
+            offset: hit.global_offset,
+            handler: hit.file_type_id.clone(),
+            settled: false,
+        }
+    }
+
+    /// Decide whether the carve is kept. Carves it beats that are still
+    /// pending are suppressed, and kept ones are replaced; a kept carve waits
+    /// up to two seconds for other handlers' hits at its offset that are
+    /// still being carved.
+    fn settle<'a>(&'a self, offset: u64, handler: &str, file: &CarvedFile) -> Verdict<'a> {
+        let Ok(mut state) = self.state.lock() else {
+            return Verdict::Keep(self.kept((0, 0), Vec::new()));
+        };
+        remove_in_flight(&mut state, offset, handler);
+        let claim = Claim {
+            handler: handler.to_string(),
+            file_type: file.file_type.clone(),
+            path: file.path.clone(),
+            start: file.global_start,
+            end: file.global_end.max(file.global_start) + 1,
+            confidence: confidence(file),
+            status: Status::Pending,
+            output: None,
+        };
+
+        let rivals = self.rivals(&state, &claim);
+        if let Some(winner) = rivals.iter().map(|key| &state.claims[key]).find(|rival| {
+            matches!(rival.status, Status::Pending | Status::Kept) && rival.beats(&claim)
+        }) {
+            let record = claim.suppressed_by(winner);
+            self.record(&mut state, record);
+            drop(state);
+            self.settled.notify_all();
+            return Verdict::Suppress;
+        }
+        let mut replaced = Vec::new();
+        for key in rivals {
+            let rival = &state.claims[&key];
+            let record = rival.suppressed_by(&claim);
+            match rival.status {
+                Status::Pending => {
+                    if let Some(rival) = state.claims.get_mut(&key) {
+                        rival.status = Status::Beaten;
+                    }
+                    self.record(&mut state, record);
+                }
+                Status::Kept => {
+                    // A released carve is deleted by this worker; one still
+                    // being written is deleted by its own worker on release.
+                    if let Some(output) = rival.output.clone() {
+                        state.claims.remove(&key);
+                        replaced.push(output);
+                    } else if let Some(rival) = state.claims.get_mut(&key) {
+                        rival.status = Status::Displaced;
+                    }
+                    self.replaced.fetch_add(1, Ordering::Relaxed);
+                    self.record(&mut state, record);
+                }
+                Status::Beaten | Status::Displaced => {}
+            }
+        }
+
+        let key = (claim.start, state.next_id);
+        state.next_id += 1;
+        let (start, handler) = (claim.start, claim.handler.clone());
+        state.claims.insert(key, claim);
+        self.settled.notify_all();
+
+        let deadline = Instant::now() + SETTLE_WAIT;
+        loop {
+            let status = state.claims.get(&key).map(|c| c.status.clone());
+            let contested = state
+                .in_flight
+                .iter()
+                .any(|(offset, other)| *offset == start && *other != handler);
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if status != Some(Status::Pending) || !contested || remaining.is_zero() {
+                break;
+            }
+            state = match self.settled.wait_timeout(state, remaining) {
+                Ok((state, _)) => state,
+                Err(_) => return Verdict::Keep(self.kept(key, replaced)),
+            };
+        }
+        let verdict = match state.claims.get_mut(&key) {
+            Some(claim) if claim.status == Status::Beaten => {
+                state.claims.remove(&key);
+                Verdict::Suppress
+            }
+            Some(claim) => {
+                claim.status = Status::Kept;
+                Verdict::Keep(self.kept(key, replaced))
+            }
+            None => Verdict::Keep(self.kept(key, replaced)),
+        };
+        prune(&mut state);
+        drop(state);
+        self.settled.notify_all();
+        verdict
+    }
+
+    fn kept(&self, key: (u64, u64), replaced: Vec<PathBuf>) -> KeptCarve<'_> {
+        KeptCarve {
+            arbiter: self,
+            key,
+            replaced,
+            released: false,
+        }
+    }
+
+    /// Record where the kept carve `key` was written; without an output
+    /// (quarantined, or the worker panicked) the claim is forgotten. Returns
+    /// whether it was replaced in the meantime and has to be deleted.
+    fn release(&self, key: (u64, u64), output: Option<&Path>) -> bool {
+        let Ok(mut state) = self.state.lock() else {
+            return false;
+        };
+        let status = state.claims.get(&key).map(|claim| claim.status.clone());
+        match (status, output) {
+            (Some(Status::Kept), Some(output)) => {
+                if let Some(claim) = state.claims.get_mut(&key) {
+                    claim.output = Some(output.to_path_buf());
+                }
+                false
+            }
+            (Some(Status::Displaced), _) => {
+                state.claims.remove(&key);
+                output.is_some()
+            }
+            (Some(_), None) => {
+                state.claims.remove(&key);
+                false
+            }
+            _ => false,
+        }
+    }
+
+    /// Claims of other handlers starting where `claim` does and overlapping
+    /// it by at least the threshold share of the larger of the two. Carves
+    /// starting elsewhere are different files, such as a truncated carve
+    /// running into the next one.
+    fn rivals(&self, state: &State, claim: &Claim) -> Vec<(u64, u64)> {
+        state
+            .claims
+            .range((claim.start, 0)..=(claim.start, u64::MAX))
+            .filter(|(_, other)| other.handler != claim.handler)
+            .filter(|(_, other)| {
+                let (shorter, longer) =
+                    (other.len().min(claim.len()), other.len().max(claim.len()));
+                shorter as f64 >= self.min_overlap * longer as f64
+            })
+            .map(|(key, _)| *key)
+            .collect()
+    }
+
+    fn record(&self, state: &mut State, record: SuppressedCarve) {
+        self.suppressed.fetch_add(1, Ordering::Relaxed);
+        if state.alternatives.len() < MAX_LISTED {
+            state.alternatives.push(record);
+        }
+    }
+
+    /// Carves suppressed so far.
+    pub fn suppressed(&self) -> u64 {
+        self.suppressed.load(Ordering::Relaxed)
+    }
+
+    pub fn summarize(&self, run_id: &str) -> ArbitrationSummary {
+        let mut alternatives = self
+            .state
+            .lock()
+            .map(|state| state.alternatives.clone())
+            .unwrap_or_default();
+        alternatives.sort_by_key(|a| (a.global_start, a.handler.clone()));
+        ArbitrationSummary {
+            run_id: run_id.to_string(),
+            min_overlap: self.min_overlap,
+            suppressed: self.suppressed(),
+            replaced: self.replaced.load(Ordering::Relaxed),
+            alternatives,
+        }
+    }
+}
+
+impl<'a> ArbitrationAttempt<'a> {
+    /// Settle the attempt with the carved file.
+    pub fn settle(mut self, file: &CarvedFile) -> Verdict<'a> {
+        self.settled = true;
+        self.arbiter.settle(self.offset, &self.handler, file)
+    }
+}
+
+impl Drop for ArbitrationAttempt<'_> {
+    fn drop(&mut self) {
+        if self.settled {
+            return;
+        }
+        if let Ok(mut state) = self.arbiter.state.lock() {
+            remove_in_flight(&mut state, self.offset, &self.handler);
+        }
+        self.arbiter.settled.notify_all();
+    }
+}
+
+fn remove_in_flight(state: &mut State, offset: u64, handler: &str) {
+    if let Some(index) = state
+        .in_flight
+        .iter()
+        .position(|(at, other)| *at == offset && other == handler)
+    {
+        state.in_flight.swap_remove(index);
+    }
+}
+
+impl KeptCarve<'_> {
+    /// Outputs of earlier kept carves this carve replaced; the caller deletes
+    /// them.
+    pub fn replaced(&self) -> &[PathBuf] {
+        &self.replaced
+    }
+
+    /// Record that the carve now lives at `output`. Returns true when a
+    /// better carve replaced it meanwhile, and the caller has to delete it.
+    pub fn release(mut self, output: &Path) -> bool {
+        self.released = true;
+        self.arbiter.release(self.key, Some(output))
+    }
+}
+
+impl Drop for KeptCarve<'_> {
+    fn drop(&mut self) {
+        if !self.released {
+            self.arbiter.release(self.key, None);
+        }
+    }
+}
+
+/// Forget the kept carves with the lowest offsets beyond `MAX_CLAIMS`.
+fn prune(state: &mut State) {
+    while state.claims.len() > MAX_CLAIMS {
+        let Some(key) = state
+            .claims
+            .iter()
+            .find(|(_, claim)| claim.status == Status::Kept)
+            .map(|(key, _)| *key)
+        else {
+            break;
+        };
+        state.claims.remove(&key);
+    }
+}
+
+/// Write the summary to `<run_output_dir>/summaries/carve_arbitration.json`.
+pub fn write_summary(
+    run_output_dir: &Path,
+    summary: &ArbitrationSummary,
+) -> std::io::Result<PathBuf> {
+    let dir = run_output_dir.join(SUMMARIES_DIR);
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(ARBITRATION_SUMMARY_FILE);
+    let writer = BufWriter::new(File::create(&path)?);
+    serde_json::to_writer_pretty(writer, summary)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hit(handler: &str, offset: u64) -> NormalizedHit {
+        NormalizedHit {
+            global_offset: offset,
+            file_type_id: handler.to_string(),
+            pattern_id: format!("{handler}_header"),
+        }
+    }
+
+    fn file(file_type: &str, start: u64, end: u64, validated: bool) -> CarvedFile {
+        CarvedFile {
+            run_id: "run".to_string(),
+            file_type: file_type.to_string(),
+            signature_type: file_type.to_string(),
+            path: format!("{file_type}/{file_type}_{start:012X}.bin"),
+            extension: "bin".to_string(),
+            global_start: start,
+            global_end: end,
+            size: end - start + 1,
+            md5: None,
+            sha256: None,
+            validated,
+            truncated: false,
+            errors: Vec::new(),
+            pattern_id: None,
+        }
+    }
+
+    fn settle<'a>(
+        arbiter: &'a CarveArbiter,
+        hit: &NormalizedHit,
+        file: &CarvedFile,
+    ) -> Verdict<'a> {
+        arbiter.begin(hit).settle(file)
+    }
+
+    #[test]
+    fn a_later_better_carve_replaces_a_kept_one() {
+        let arbiter = CarveArbiter::new(0.8);
+        let Verdict::Keep(webp) =
+            settle(&arbiter, &hit("webp", 100), &file("webp", 100, 1099, false))
+        else {
+            panic!("first carve suppressed");
+        };
+        assert!(!webp.release(Path::new("out/webp.bin")));
+
+        let Verdict::Keep(wav) = settle(&arbiter, &hit("wav", 100), &file("wav", 100, 1099, true))
+        else {
+            panic!("better carve suppressed");
+        };
+        assert_eq!(wav.replaced(), [PathBuf::from("out/webp.bin")]);
+        drop(wav);
+
+        // A carve starting elsewhere, such as a nested file, is not a rival.
+        let jpeg = settle(&arbiter, &hit("jpeg", 200), &file("jpeg", 200, 299, true));
+        assert!(matches!(jpeg, Verdict::Keep(ref kept) if kept.replaced().is_empty()));
+
+        let summary = arbiter.summarize("run");
+        assert_eq!((summary.suppressed, summary.replaced), (1, 1));
+        assert!(summary.alternatives[0].replaced);
+        assert_eq!(summary.alternatives[0].kept_file_type, "wav");
+    }
+
+    #[test]
+    fn a_carve_replaced_while_being_written_is_deleted_on_release() {
+        let arbiter = CarveArbiter::new(0.8);
+        let Verdict::Keep(ole) = settle(&arbiter, &hit("ole", 0), &file("ole", 0, 4095, false))
+        else {
+            panic!("first carve suppressed");
+        };
+        let msg = settle(&arbiter, &hit("msg", 0), &file("msg", 0, 4095, true));
+        assert!(matches!(msg, Verdict::Keep(ref kept) if kept.replaced().is_empty()));
+        assert!(ole.release(Path::new("out/ole.bin")));
+        assert_eq!(arbiter.summarize("run").replaced, 1);
+    }
+
+    #[test]
+    fn suppresses_a_pending_carve_beaten_by_a_competitor() {
+        let arbiter = std::sync::Arc::new(CarveArbiter::new(0.8));
+        let (ole, msg) = (hit("ole", 0), hit("msg", 0));
+        let ole_attempt = arbiter.begin(&ole);
+        let msg_attempt = arbiter.begin(&msg);
+        std::thread::scope(|scope| {
+            let waiting = scope.spawn(|| {
+                matches!(
+                    ole_attempt.settle(&file("ole", 0, 4095, false)),
+                    Verdict::Suppress
+                )
+            });
+            // Wait until the OLE carve is pending, then settle the better one.
+            while arbiter.state.lock().unwrap().claims.is_empty() {
+                std::thread::yield_now();
+            }
+            let Verdict::Keep(kept) = msg_attempt.settle(&file("msg", 0, 4095, true)) else {
+                panic!("better carve suppressed");
+            };
+            assert!(waiting.join().unwrap());
+            assert!(!kept.release(Path::new("out/msg.bin")));
+        });
+
+        // A later, weaker carve of the same bytes is suppressed outright.
+        let late = settle(&arbiter, &hit("doc", 0), &file("doc", 0, 4000, false));
+        assert!(matches!(late, Verdict::Suppress));
+        let summary = arbiter.summarize("run");
+        assert_eq!((summary.suppressed, summary.replaced), (2, 0));
+        assert!(
+            summary
+                .alternatives
+                .iter()
+                .all(|a| a.kept_file_type == "msg")
+        );
+    }
+
+    #[test]
+    fn an_abandoned_attempt_stops_blocking_competitors() {
+        let arbiter = CarveArbiter::new(0.8);
+        let stuck = arbiter.begin(&hit("msg", 0));
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(move || {
+            let _stuck = stuck;
+            panic!("carve panicked");
+        }));
+        assert!(result.is_err());
+        assert!(arbiter.state.lock().unwrap().in_flight.is_empty());
+
+        let started = Instant::now();
+        let ole = settle(&arbiter, &hit("ole", 0), &file("ole", 0, 4095, false));
+        assert!(matches!(ole, Verdict::Keep(_)));
+        assert!(started.elapsed() < SETTLE_WAIT);
+    }
+}
@@ -0,0 +1,412 @@
+//! # NTFS-Compressed Streams
+//!
+//! Files compressed by NTFS are stored as LZNT1 compression units, so their
+//! headers never appear in the raw evidence. With `enable_lznt1_scan`, scan
+//! workers look for a compressed LZNT1 chunk header at every sector boundary
+//! of a chunk, decompress the unit, and follow it with the units at each
+//! further `lznt1_unit_size` for as long as the previous unit filled its
+//! whole size (a contiguously allocated compressed file). The decompressed
+//! chain is scanned with the signature scanner and hits are carved from it
+//! by the normal handlers.
+//!
+//! Carved files are written under `carved/lznt1/<chain offset>/` and their
+//! `global_start`/`global_end` are mapped back to the physical evidence bytes:
+//! the exact byte in stored chunks, the enclosing compressed chunk otherwise.
+//! A unit that directly follows a full unit is left to the chain that starts
+//! there, so each chain is carved once.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use tracing::{debug, warn};
+
+use crate::carve::{self, CarveRegistry, CarvedFile, ExtractionContext};
+use crate::chunk::ScanChunk;
+use crate::config::Config;
+use crate::evidence::{EvidenceError, EvidenceSource};
+use crate::parsers::lznt1::{self, Lznt1Unit};
+use crate::scanner::{NormalizedHit, SignatureScanner};
+
+use super::events::MetadataEvent;
+use super::queue::StageSender;
+
+/// Units are looked for at sector boundaries.
+const SECTOR: usize = 512;
+/// Decompressed bytes a unit needs before it is scanned, to skip chance
+/// matches of a single short chunk.
+const MIN_UNIT_OUTPUT: usize = 512;
+/// Longest chain followed from one start.
+const MAX_CHAIN_UNITS: usize = 4096;
+/// Output directory under `carved/`.
+const OUTPUT_DIR: &str = "lznt1";
+
+/// Where each decompressed unit of a chain came from.
+struct UnitSpan {
+    physical_start: u64,
+    decompressed_start: usize,
+    unit: Lznt1Unit,
+}
+
+/// A chain of consecutive units, decompressed.
+struct Chain {
+    data: Vec<u8>,
+    units: Vec<UnitSpan>,
+}
+
+impl Chain {
+    fn unit_at(&self, offset: usize) -> Option<&UnitSpan> {
+        let idx = self
+            .units
+            .partition_point(|u| u.decompressed_start + u.unit.data.len() <= offset);
+        self.units.get(idx)
+    }
+
+    /// First physical byte behind decompressed byte `offset`.
+    fn physical_start(&self, offset: usize) -> Option<u64> {
+        let span = self.unit_at(offset)?;
+        let local = offset - span.decompressed_start;
+        let chunk = span.unit.chunk_at(local)?;
+        Some(span.physical_start + chunk.source_offset(local) as u64)
+    }
+
+    /// Last physical byte behind decompressed byte `offset`.
+    fn physical_end(&self, offset: usize) -> Option<u64> {
+        let span = self.unit_at(offset)?;
+        let local = offset - span.decompressed_start;
+        let chunk = span.unit.chunk_at(local)?;
+        Some(span.physical_start + chunk.source_end(local) as u64)
+    }
+}
+
+/// Decompressed data as an evidence source for the carve handlers.
+struct BufferSource<T>(T);
+
+impl<T: AsRef<[u8]> + Send + Sync> EvidenceSource for BufferSource<T> {
+    fn len(&self) -> u64 {
+        self.0.as_ref().len() as u64
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize, EvidenceError> {
+        let data = usize::try_from(offset)
+            .ok()
+            .and_then(|start| self.0.as_ref().get(start..))
+            .unwrap_or_default();
+        let n = data.len().min(buf.len());
+        buf[..n].copy_from_slice(&data[..n]);
+        Ok(n)
+    }
+}
+
+/// Counts reported at the end of the run.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Lznt1Stats {
+    pub chains: u64,
+    pub units: u64,
+    pub bytes_decompressed: u64,
+    pub files_carved: u64,
+}
+
+/// LZNT1 detection, decompression, and carving, shared by scan workers.
+pub struct Lznt1Scan {
+    unit_size: usize,
+    scanner: Arc<dyn SignatureScanner>,
+    registry: Arc<CarveRegistry>,
+    evidence: Arc<dyn EvidenceSource>,
+    run_id: String,
+    carved_root: PathBuf,
+    meta_tx: StageSender<MetadataEvent>,
+    files_carved: Arc<AtomicU64>,
+    chains: AtomicU64,
+    units: AtomicU64,
+    bytes_decompressed: AtomicU64,
+    lznt1_files: AtomicU64,
+}
+
+impl Lznt1Scan {
+    pub fn new(
+        cfg: &Config,
+        scanner: Arc<dyn SignatureScanner>,
+        registry: Arc<CarveRegistry>,
+        evidence: Arc<dyn EvidenceSource>,
+        run_output_dir: &Path,
+        meta_tx: StageSender<MetadataEvent>,
+        files_carved: Arc<AtomicU64>,
+    ) -> Self {
+        Self {
+            unit_size: (cfg.lznt1_unit_size as usize).max(lznt1::CHUNK_SIZE),
+            scanner,
+            registry,
+            evidence,
+            run_id: cfg.run_id.clone(),
+            carved_root: run_output_dir.join("carved"),
+            meta_tx,
+            files_carved,
+            chains: AtomicU64::new(0),
+            units: AtomicU64::new(0),
+            bytes_decompressed: AtomicU64::new(0),
+            lznt1_files: AtomicU64::new(0),
+        }
+    }
+
+    pub fn stats(&self) -> Lznt1Stats {
+        Lznt1Stats {
+            chains: self.chains.load(Ordering::Relaxed),
+            units: self.units.load(Ordering::Relaxed),
+            bytes_decompressed: self.bytes_decompressed.load(Ordering::Relaxed),
+            files_carved: self.lznt1_files.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Look for chains starting in the valid part of `chunk`.
+    pub fn scan_chunk(&self, chunk: &ScanChunk, data: &[u8]) {
+        let valid = (chunk.valid_length as usize).min(data.len());
+        let mut local = (SECTOR - (chunk.start % SECTOR as u64) as usize) % SECTOR;
+        while local < valid {
+            let physical = chunk.start + local as u64;
+            if !lznt1::is_compressed_chunk_header(&data[local..]) {
+                local += SECTOR;
+                continue;
+            }
+            match self.chain_at(physical) {
+                Some(chain) => {
+                    self.carve_chain(physical, &chain);
+                    local += chain.units.len() * self.unit_size;
+                }
+                None => local += SECTOR,
+            }
+        }
+    }
+
+    fn read_unit(&self, physical: u64) -> Option<Lznt1Unit> {
+        let mut buf = vec![0u8; self.unit_size];
+        let n = self.evidence.read_at(physical, &mut buf).ok()?;
+        buf.truncate(n);
+        if !lznt1::is_compressed_chunk_header(&buf) {
+            return None;
+        }
+        let unit = lznt1::decompress_unit(&buf, self.unit_size);
+        (unit.has_compressed_chunk() && unit.data.len() >= MIN_UNIT_OUTPUT).then_some(unit)
+    }
+
+    fn chain_at(&self, physical: u64) -> Option<Chain> {
+        let step = self.unit_size as u64;
+        // Continuations belong to the chain that starts before them.
+        if let Some(prev) = physical.checked_sub(step)
+            && self
+                .read_unit(prev)
+                .is_some_and(|unit| unit.data.len() == self.unit_size)
+        {
+            return None;
+        }
+        let mut chain = Chain {
+            data: Vec::new(),
+            units: Vec::new(),
+        };
+        let mut next = physical;
+        while chain.units.len() < MAX_CHAIN_UNITS {
+            let Some(unit) = self.read_unit(next) else {
+                break;
+            };
+            let full = unit.data.len() == self.unit_size;
+            let decompressed_start = chain.data.len();
+            chain.data.extend_from_slice(&unit.data);
+            chain.units.push(UnitSpan {
+                physical_start: next,
+                decompressed_start,
+                unit,
+            });
+            if !full {
+                break;
+            }
+            next += step;
+        }
+        if chain.units.is_empty() {
+            return None;
+        }
+        self.chains.fetch_add(1, Ordering::Relaxed);
+        self.units
+            .fetch_add(chain.units.len() as u64, Ordering::Relaxed);
+        self.bytes_decompressed
+            .fetch_add(chain.data.len() as u64, Ordering::Relaxed);
+        Some(chain)
+    }
+
+    fn carve_chain(&self, physical: u64, chain: &Chain) {
+        let virtual_chunk = ScanChunk {
+            id: 0,
+            start: 0,
+            length: chain.data.len() as u64,
+            valid_length: chain.data.len() as u64,
+        };
+        let hits = self.scanner.scan_chunk(&virtual_chunk, &chain.data);
+        if hits.is_empty() {
+            return;
+        }
+        debug!(
+            "lznt1 chain at {physical}: {} unit(s), {} bytes, {} hit(s)",
+            chain.units.len(),
+            chain.data.len(),
+            hits.len()
+        );
+        let dir = format!("{OUTPUT_DIR}/{physical:012X}");
+        let output_root = self.carved_root.join(&dir);
+        let source = BufferSource(chain.data.as_slice());
+        let ctx = ExtractionContext {
+            run_id: &self.run_id,
+            output_root: &output_root,
+            evidence: &source,
+        };
+        for hit in hits {
+            let Some(handler) = self.registry.get(&hit.file_type_id) else {
+                continue;
+            };
+            let hit = NormalizedHit {
+                global_offset: hit.local_offset,
+                file_type_id: hit.file_type_id,
+                pattern_id: hit.pattern_id,
+            };
+            let file = match carve::confine(&output_root, handler.process_hit(&hit, &ctx)) {
+                Ok(Some(file)) => file,
+                Ok(None) => continue,
+                Err(err) => {
+                    warn!("carve error in lznt1 chain at {physical}: {err}");
+                    continue;
+                }
+            };
+            let Some(file) = self.to_physical(file, chain, &dir) else {
+                continue;
+            };
+            self.files_carved.fetch_add(1, Ordering::Relaxed);
+            self.lznt1_files.fetch_add(1, Ordering::Relaxed);
+            if !self.registry.keeps(&hit.file_type_id, &file) {
+                carve::discard(&self.carved_root.join(&file.path));
+            }
+            if let Err(err) = self.meta_tx.send(MetadataEvent::File(file)) {
+                warn!("metadata channel closed while sending carved file: {err}");
+                return;
+            }
+        }
+    }
+
+    /// Rewrite a file carved from decompressed data with physical offsets and
+    /// a path relative to `carved/`.
+    fn to_physical(&self, mut file: CarvedFile, chain: &Chain, dir: &str) -> Option<CarvedFile> {
+        let start = file.global_start as usize;
+        let last = start + (file.size as usize).max(1) - 1;
+        file.global_start = chain.physical_start(start)?;
+        file.global_end = chain.physical_end(last.min(chain.data.len() - 1))?;
+        file.path = format!("{dir}/{}", file.path);
+        Some(file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config;
+    use crate::pipeline::testing::{MagicScanner, magic_registry};
+
+    const UNIT: usize = 8192;
+
+    /// Compress a 4 KiB chunk using only runs of the previous byte.
+    fn compress_chunk(plain: &[u8]) -> Vec<u8> {
+        let mut body = Vec::new();
+        let mut pos = 0usize;
+        while pos < plain.len() {
+            let flag_at = body.len();
+            body.push(0u8);
+            for bit in 0..8 {
+                if pos >= plain.len() {
+                    break;
+                }
+                let mut length_bits = 12u32;
+                let mut p = pos.saturating_sub(1);
+                while p >= 0x10 {
+                    length_bits -= 1;
+                    p >>= 1;
+                }
+                let max_len = ((1usize << length_bits) - 1 + 3).min(plain.len() - pos);
+                let run = plain[pos..]
+                    .iter()
+                    .take(max_len)
+                    .take_while(|&&b| pos > 0 && b == plain[pos - 1])
+                    .count();
+                if run >= 3 {
+                    body[flag_at] |= 1 << bit;
+                    body.extend(((run - 3) as u16).to_le_bytes());
+                    pos += run;
+                } else {
+                    body.push(plain[pos]);
+                    pos += 1;
+                }
+            }
+        }
+        let mut out = (0xB000u16 | (body.len() as u16 + 2 - 3))
+            .to_le_bytes()
+            .to_vec();
+        out.extend(body);
+        out
+    }
+
+    /// One full unit: a compressed chunk, then a stored one.
+    fn unit(fill: u8, magic_at: usize) -> Vec<u8> {
+        let mut plain = vec![fill; UNIT];
+        plain[magic_at..magic_at + 8].copy_from_slice(b"MAGIC!!!");
+        let mut out = compress_chunk(&plain[..4096]);
+        out.extend((0x3000u16 | 0x0FFF).to_le_bytes());
+        out.extend_from_slice(&plain[4096..]);
+        out
+    }
+
+    #[test]
+    fn carves_from_unit_chains_with_physical_offsets() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let mut image = vec![0u8; 8 * UNIT];
+        let first = unit(b'a', 100);
+        let second = unit(b'b', 4096 + 10);
+        image[UNIT..UNIT + first.len()].copy_from_slice(&first);
+        image[2 * UNIT..2 * UNIT + second.len()].copy_from_slice(&second);
+
+        let mut cfg = config::load_config(None).expect("config").config;
+        cfg.lznt1_unit_size = UNIT as u64;
+        let (meta_tx, meta_rx) = crossbeam_channel::unbounded();
+        let meta_tx = meta_tx.into();
+        let evidence: Arc<dyn EvidenceSource> = Arc::new(BufferSource(image.clone()));
+        let scan = Lznt1Scan::new(
+            &cfg,
+            Arc::new(MagicScanner),
+            Arc::new(magic_registry()),
+            evidence,
+            dir.path(),
+            meta_tx,
+            Arc::new(AtomicU64::new(0)),
+        );
+        let chunk = ScanChunk {
+            id: 0,
+            start: 0,
+            length: image.len() as u64,
+            valid_length: image.len() as u64,
+        };
+        scan.scan_chunk(&chunk, &image);
+
+        let stats = scan.stats();
+        assert_eq!((stats.chains, stats.units, stats.files_carved), (1, 2, 2));
+        let files: Vec<CarvedFile> = meta_rx
+            .try_iter()
+            .filter_map(|event| match event {
+                MetadataEvent::File(file) => Some(file),
+                _ => None,
+            })
+            .collect();
+        // Inside the compressed chunk of the first unit: the whole chunk.
+        let compressed_len = (first.len() - 4098) as u64;
+        assert_eq!(files[0].global_start, UNIT as u64);
+        assert_eq!(files[0].global_end, UNIT as u64 + compressed_len - 1);
+        assert_eq!(files[0].path, format!("lznt1/{UNIT:012X}/magic/100.bin"));
+        // Inside the stored chunk of the second unit: exact bytes.
+        let stored_data = (2 * UNIT + second.len() - 4096) as u64;
+        assert_eq!(files[1].global_start, stored_data + 10);
+        assert_eq!(files[1].global_end, stored_data + 17);
+    }
+}
@@ -0,0 +1,226 @@
+//! # Carve Coverage
+//!
+//! How much of the evidence the carved files explain. Carve workers record
+//! every kept carve in [`CarvedRanges`](super::gaps::CarvedRanges); at the end
+//! of the run the merged map is compared with the scanned bytes, so
+//! overlapping carves count once. Bytes outside every carve are slack. The
+//! summary gives the slack share for the whole scan and per region of
+//! `coverage_region_size` bytes, and with `slack_export_min_size` the slack
+//! extents themselves are listed for manual review.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::report::SUMMARIES_DIR;
+
+use super::gaps::CarveMap;
+
+/// File name of the coverage summary under `summaries/`.
+pub const COVERAGE_SUMMARY_FILE: &str = "coverage.json";
+/// File name of the slack extent list under `summaries/`.
+pub const SLACK_EXTENTS_FILE: &str = "slack_extents.jsonl";
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct CoverageSummary {
+    pub run_id: String,
+    /// Scanned range the coverage refers to.
+    pub scan_start: u64,
+    pub scan_end: u64,
+    pub covered_bytes: u64,
+    pub slack_bytes: u64,
+    pub slack_percent: f64,
+    /// Disjoint extents the carves cover after merging overlaps.
+    pub carved_extents: u64,
+    pub region_size: u64,
+    pub regions: Vec<RegionCoverage>,
+    /// Set when slack extents were exported.
+    pub slack_extents: Option<SlackExport>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct RegionCoverage {
+    pub start: u64,
+    pub end: u64,
+    pub covered_bytes: u64,
+    pub slack_percent: f64,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct SlackExport {
+    pub file: String,
+    pub min_size: u64,
+    pub extents: u64,
+    pub bytes: u64,
+}
+
+/// One uncovered extent, a line of `slack_extents.jsonl`.
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+pub struct SlackExtent {
+    pub start: u64,
+    pub end: u64,
+    pub length: u64,
+}
+
+/// Coverage of `scan_start..scan_end` by `map`.
+pub fn summarize(
+    run_id: &str,
+    cfg: &Config,
+    map: &CarveMap,
+    scan_start: u64,
+    scan_end: u64,
+) -> CoverageSummary {
+    let scan_end = scan_end.max(scan_start);
+    let covered_bytes = covered(map, scan_start, scan_end);
+    let mut regions = Vec::new();
+    if cfg.coverage_region_size > 0 {
+        let mut start = scan_start - scan_start % cfg.coverage_region_size;
+        while start < scan_end {
+            let end = start.saturating_add(cfg.coverage_region_size).min(scan_end);
+            let from = start.max(scan_start);
+            let covered_bytes = covered(map, from, end);
+            regions.push(RegionCoverage {
+                start: from,
+                end,
+                covered_bytes,
+                slack_percent: slack_percent(covered_bytes, end - from),
+            });
+            start = end;
+        }
+    }
+    CoverageSummary {
+        run_id: run_id.to_string(),
+        scan_start,
+        scan_end,
+        covered_bytes,
+        slack_bytes: scan_end - scan_start - covered_bytes,
+        slack_percent: slack_percent(covered_bytes, scan_end - scan_start),
+        carved_extents: map.ranges().len() as u64,
+        region_size: cfg.coverage_region_size,
+        regions,
+        slack_extents: None,
+    }
+}
+
+fn covered(map: &CarveMap, start: u64, end: u64) -> u64 {
+    let ranges = map.ranges();
+    let first = ranges.partition_point(|&(_, e)| e <= start);
+    ranges[first..]
+        .iter()
+        .take_while(|&&(s, _)| s < end)
+        .map(|&(s, e)| e.min(end) - s.max(start))
+        .sum()
+}
+
+fn slack_percent(covered: u64, len: u64) -> f64 {
+    if len == 0 {
+        return 0.0;
+    }
+    let slack = (len - covered) as f64 * 100.0 / len as f64;
+    (slack * 100.0).round() / 100.0
+}
+
+/// Uncovered extents of `scan_start..scan_end` at least `min_size` long.
+pub fn slack_extents(
+    map: &CarveMap,
+    scan_start: u64,
+    scan_end: u64,
+    min_size: u64,
+) -> Vec<SlackExtent> {
+    let mut extents = Vec::new();
+    let mut cursor = scan_start;
+    let mut push = |start: u64, end: u64| {
+        if end > start && end - start >= min_size.max(1) {
+            extents.push(SlackExtent {
+                start,
+                end,
+                length: end - start,
+            });
+        }
+    };
+    for &(s, e) in map.ranges() {
+        if e <= cursor {
+            continue;
+        }
+        if s >= scan_end {
+            break;
+        }
+        push(cursor, s.min(scan_end));
+        cursor = e;
+    }
+    push(cursor, scan_end);
+    extents
+}
+
+/// Write the summary to `<run_output_dir>/summaries/coverage.json`, and the
+/// slack extents to `slack_extents.jsonl` when `slack_export_min_size` is set.
+pub fn write_summary(
+    run_output_dir: &Path,
+    cfg: &Config,
+    map: &CarveMap,
+    mut summary: CoverageSummary,
+) -> std::io::Result<PathBuf> {
+    let dir = run_output_dir.join(SUMMARIES_DIR);
+    std::fs::create_dir_all(&dir)?;
+    if cfg.slack_export_min_size > 0 {
+        let extents = slack_extents(
+            map,
+            summary.scan_start,
+            summary.scan_end,
+            cfg.slack_export_min_size,
+        );
+        let mut writer = BufWriter::new(File::create(dir.join(SLACK_EXTENTS_FILE))?);
+        for extent in &extents {
+            serde_json::to_writer(&mut writer, extent)?;
+            writer.write_all(b"\n")?;
+        }
+        writer.flush()?;
+        summary.slack_extents = Some(SlackExport {
+            file: format!("{SUMMARIES_DIR}/{SLACK_EXTENTS_FILE}"),
+            min_size: cfg.slack_export_min_size,
+            extents: extents.len() as u64,
+            bytes: extents.iter().map(|e| e.length).sum(),
+        });
+    }
+    let path = dir.join(COVERAGE_SUMMARY_FILE);
+    let writer = BufWriter::new(File::create(&path)?);
+    serde_json::to_writer_pretty(writer, &summary)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::gaps::CarvedRanges;
+
+    #[test]
+    fn counts_overlapping_carves_once() {
+        let ranges = CarvedRanges::default();
+        ranges.insert(100, 299);
+        ranges.insert(200, 399);
+        ranges.insert(900, 1199);
+        let map = ranges.snapshot();
+        let mut cfg = crate::config::load_config(None).expect("config").config;
+        cfg.coverage_region_size = 500;
+
+        let summary = summarize("run", &cfg, &map, 0, 1000);
+        assert_eq!(summary.covered_bytes, 400);
+        assert_eq!(summary.slack_bytes, 600);
+        assert_eq!(summary.slack_percent, 60.0);
+        assert_eq!(summary.carved_extents, 2);
+        let regions: Vec<(u64, u64, u64)> = summary
+            .regions
+            .iter()
+            .map(|r| (r.start, r.end, r.covered_bytes))
+            .collect();
+        assert_eq!(regions, vec![(0, 500, 300), (500, 1000, 100)]);
+
+        let extents = slack_extents(&map, 0, 1000, 200);
+        let spans: Vec<(u64, u64)> = extents.iter().map(|e| (e.start, e.end)).collect();
+        assert_eq!(spans, vec![(400, 900)]);
+        assert_eq!(slack_extents(&map, 0, 1000, 1).len(), 2);
+    }
+}
@@ -0,0 +1,351 @@
+//! # Run-Time Estimation
+//!
+//! Models completion time from per-stage costs instead of extrapolating
+//! bytes/sec. Each stage (read, scan, string extraction, carve, metadata) records
+//! its busy time; the model turns these into per-byte and per-hit costs, takes the
+//! slowest stage as the pipeline bottleneck, and calibrates against wall-clock
+//! time observed so far.
+//!
+//! Carve cost depends on hit density, which varies wildly across an image. The
+//! central estimate uses the mean density of chunks seen so far; the low and high
+//! bounds use the 10th and 90th percentile densities. Densities are kept in a
+//! fixed log-scale [`DensityHistogram`], so the cost of a snapshot does not grow
+//! with the number of chunks. Hits already queued but not yet carved are added as
+//! backlog, so entering a dense region raises the ETA immediately rather than
+//! after the region has been scanned.
+
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Chunks that must be scanned before the model produces an estimate.
+pub const MIN_CALIBRATION_CHUNKS: usize = 3;
+
+/// Bounds on the wall-clock calibration factor (elapsed / modelled time).
+const CALIBRATION_RANGE: (f64, f64) = (0.25, 4.0);
+
+/// Histogram buckets per decade of hit density.
+const BUCKETS_PER_DECADE: usize = 8;
+/// Decades covered by the histogram, from `MIN_DENSITY` up to one hit per byte.
+const DECADES: usize = 10;
+const MIN_DENSITY: f64 = 1e-10;
+const BUCKETS: usize = BUCKETS_PER_DECADE * DECADES;
+
+/// Busy-time counters shared by pipeline stages.
+#[derive(Debug, Default)]
+pub struct StageTimings {
+    read_ns: AtomicU64,
+    scan_ns: AtomicU64,
+    string_ns: AtomicU64,
+    carve_ns: AtomicU64,
+    metadata_ns: AtomicU64,
+    bytes_scanned: AtomicU64,
+    hits_scanned: AtomicU64,
+    hits_carved: AtomicU64,
+    /// Hits per byte of the scanned chunks.
+    densities: Mutex<DensityHistogram>,
+}
+
+/// Hits-per-byte of scanned chunks: an exact count and mean, plus
+/// log-scale buckets for percentiles. Densities of zero are counted apart;
+/// ones outside the bucket range fall into the first or last bucket.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DensityHistogram {
+    chunks: u64,
+    sum: f64,
+    zero: u64,
+    buckets: [u64; BUCKETS],
+}
+
+impl Default for DensityHistogram {
+    fn default() -> Self {
+        Self {
+            chunks: 0,
+            sum: 0.0,
+            zero: 0,
+            buckets: [0; BUCKETS],
+        }
+    }
+}
+
+impl DensityHistogram {
+    pub fn record(&mut self, density: f64) {
+        self.chunks += 1;
+        self.sum += density;
+        if density <= 0.0 {
+            self.zero += 1;
+            return;
+        }
+        let pos = (density / MIN_DENSITY).log10() * BUCKETS_PER_DECADE as f64;
+        let idx = (pos.max(0.0) as usize).min(BUCKETS - 1);
+        self.buckets[idx] += 1;
+    }
+
+    /// Number of chunks recorded.
+    pub fn chunks(&self) -> u64 {
+        self.chunks
+    }
+
+    pub fn mean(&self) -> f64 {
+        if self.chunks == 0 {
+            return 0.0;
+        }
+        self.sum / self.chunks as f64
+    }
+
+    /// Density at fraction `p` of the recorded chunks, as the geometric
+    /// centre of its bucket.
+    pub fn percentile(&self, p: f64) -> f64 {
+        if self.chunks == 0 {
+            return 0.0;
+        }
+        let rank = ((self.chunks - 1) as f64 * p).round() as u64;
+        let mut seen = self.zero;
+        if rank < seen {
+            return 0.0;
+        }
+        for (idx, count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if rank < seen {
+                let centre = (idx as f64 + 0.5) / BUCKETS_PER_DECADE as f64;
+                return MIN_DENSITY * 10f64.powf(centre);
+            }
+        }
+        self.mean()
+    }
+}
+
+/// Point-in-time copy of [`StageTimings`] used by the estimator.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StageCosts {
+    pub read_secs: f64,
+    pub scan_secs: f64,
+    pub string_secs: f64,
+    pub carve_secs: f64,
+    pub metadata_secs: f64,
+    pub bytes_scanned: u64,
+    pub hits_scanned: u64,
+    pub hits_carved: u64,
+    pub densities: DensityHistogram,
+}
+
+/// Projected time to completion with confidence bounds, in seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EtaEstimate {
+    pub seconds: u64,
+    pub low_seconds: u64,
+    pub high_seconds: u64,
+}
+
+impl StageTimings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record time spent reading evidence.
+    pub fn record_read(&self, elapsed: Duration) {
+        add_nanos(&self.read_ns, elapsed);
+    }
+
+    /// Record a scanned chunk and the hits it produced.
+    pub fn record_scan(&self, bytes: u64, hits: u64, elapsed: Duration) {
+        add_nanos(&self.scan_ns, elapsed);
+        self.bytes_scanned.fetch_add(bytes, Ordering::Relaxed);
+        self.hits_scanned.fetch_add(hits, Ordering::Relaxed);
+        if bytes > 0
+            && let Ok(mut densities) = self.densities.lock()
+        {
+            densities.record(hits as f64 / bytes as f64);
+        }
+    }
+
+    /// Record time spent extracting artefacts from string spans.
+    pub fn record_strings(&self, elapsed: Duration) {
+        add_nanos(&self.string_ns, elapsed);
+    }
+
+    /// Record one processed hit (carved or rejected).
+    pub fn record_carve(&self, elapsed: Duration) {
+        add_nanos(&self.carve_ns, elapsed);
+        self.hits_carved.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record time spent writing one metadata event.
+    pub fn record_metadata(&self, elapsed: Duration) {
+        add_nanos(&self.metadata_ns, elapsed);
+    }
+
+    pub fn snapshot(&self) -> StageCosts {
+        let secs = |v: &AtomicU64| v.load(Ordering::Relaxed) as f64 / 1e9;
+        StageCosts {
+            read_secs: secs(&self.read_ns),
+            scan_secs: secs(&self.scan_ns),
+            string_secs: secs(&self.string_ns),
+            carve_secs: secs(&self.carve_ns),
+            metadata_secs: secs(&self.metadata_ns),
+            bytes_scanned: self.bytes_scanned.load(Ordering::Relaxed),
+            hits_scanned: self.hits_scanned.load(Ordering::Relaxed),
+            hits_carved: self.hits_carved.load(Ordering::Relaxed),
+            densities: self.densities.lock().map(|d| d.clone()).unwrap_or_default(),
+        }
+    }
+}
+
+fn add_nanos(counter: &AtomicU64, elapsed: Duration) {
+    let nanos = u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX);
+    counter.fetch_add(nanos, Ordering::Relaxed);
+}
+
+impl StageCosts {
+    /// Project the time to scan `remaining_bytes` and drain the carve backlog.
+    ///
+    /// Returns `None` until enough chunks have been observed to calibrate.
+    pub fn estimate(
+        &self,
+        workers: usize,
+        elapsed_secs: f64,
+        remaining_bytes: u64,
+    ) -> Option<EtaEstimate> {
+        if self.densities.chunks() < MIN_CALIBRATION_CHUNKS as u64
+            || self.bytes_scanned == 0
+            || elapsed_secs <= 0.0
+        {
+            return None;
+        }
+        let workers = workers.max(1) as f64;
+        let bytes = self.bytes_scanned as f64;
+        let read_pb = self.read_secs / bytes;
+        let scan_pb = self.scan_secs / bytes / workers;
+        let string_pb = self.string_secs / bytes / workers;
+        let metadata_pb = self.metadata_secs / bytes;
+        let carve_ph = if self.hits_carved > 0 {
+            self.carve_secs / self.hits_carved as f64 / workers
+        } else {
+            0.0
+        };
+        // Stages run concurrently, so the slowest one sets the pace.
+        let secs_per_byte = |density: f64| {
+            read_pb
+                .max(scan_pb)
+                .max(string_pb)
+                .max(metadata_pb)
+                .max(carve_ph * density)
+        };
+
+        let mean = self.densities.mean();
+        let low_density = self.densities.percentile(0.10);
+        let high_density = self.densities.percentile(0.90);
+
+        let modelled = bytes * secs_per_byte(mean);
+        let calibration = if modelled > 0.0 {
+            (elapsed_secs / modelled).clamp(CALIBRATION_RANGE.0, CALIBRATION_RANGE.1)
+        } else {
+            1.0
+        };
+        let backlog = self.hits_scanned.saturating_sub(self.hits_carved) as f64 * carve_ph;
+        let project = |density: f64| {
+            let secs = calibration * (remaining_bytes as f64 * secs_per_byte(density) + backlog);
+            secs.max(0.0).round() as u64
+        };
+
+        let seconds = project(mean);
+        Some(EtaEstimate {
+            seconds,
+            low_seconds: project(low_density).min(seconds),
+            high_seconds: project(high_density).max(seconds),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn costs(chunks: Vec<f64>) -> StageCosts {
+        let mut densities = DensityHistogram::default();
+        for density in chunks {
+            densities.record(density);
+        }
+        StageCosts {
+            read_secs: 1.0,
+            scan_secs: 4.0,
+            string_secs: 0.0,
+            carve_secs: 0.0,
+            metadata_secs: 0.0,
+            bytes_scanned: 1_000,
+            hits_scanned: 0,
+            hits_carved: 0,
+            densities,
+        }
+    }
+
+    #[test]
+    fn needs_calibration_chunks() {
+        assert_eq!(costs(vec![0.0, 0.0]).estimate(4, 1.0, 1_000), None);
+    }
+
+    #[test]
+    fn linear_stages_match_naive_rate() {
+        // 4 workers share 4s of scan time -> 1ms/byte, same as read.
+        let est = costs(vec![0.0; 4])
+            .estimate(4, 1.0, 2_000)
+            .expect("estimate");
+        assert_eq!(est.seconds, 2);
+        assert_eq!(est.low_seconds, 2);
+        assert_eq!(est.high_seconds, 2);
+    }
+
+    #[test]
+    fn carve_heavy_regions_widen_bounds() {
+        let mut c = costs(vec![0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.1, 0.1]);
+        c.hits_scanned = 10;
+        c.hits_carved = 10;
+        c.carve_secs = 40.0; // 4s per hit over 4 workers -> 1s per hit
+        let est = c.estimate(4, 11.0, 1_000).expect("estimate");
+        assert!(est.low_seconds < est.seconds, "{est:?}");
+        assert!(est.seconds < est.high_seconds, "{est:?}");
+    }
+
+    #[test]
+    fn carve_backlog_adds_time() {
+        let mut c = costs(vec![0.0; 4]);
+        c.hits_carved = 1;
+        c.carve_secs = 4.0;
+        let idle = c.estimate(4, 1.0, 1_000).expect("estimate");
+        c.hits_scanned = 11; // 10 hits still queued
+        let busy = c.estimate(4, 1.0, 1_000).expect("estimate");
+        assert!(busy.seconds >= idle.seconds + 10, "{idle:?} {busy:?}");
+    }
+
+    #[test]
+    fn histogram_percentiles_land_in_the_right_bucket() {
+        let mut densities = DensityHistogram::default();
+        for _ in 0..8 {
+            densities.record(0.0);
+        }
+        densities.record(1e-4);
+        densities.record(1e-2);
+        assert_eq!(densities.percentile(0.10), 0.0);
+        let high = densities.percentile(0.90);
+        let width = 10f64.powf(1.0 / BUCKETS_PER_DECADE as f64);
+        assert!(high > 1e-4 / width && high < 1e-4 * width, "{high}");
+        let top = densities.percentile(1.0);
+        assert!(top > 1e-2 / width && top < 1e-2 * width, "{top}");
+        assert!((densities.mean() - 0.00101).abs() < 1e-12);
+    }
+
+    #[test]
+    fn records_stage_timings() {
+        let timings = StageTimings::new();
+        timings.record_read(Duration::from_millis(5));
+        timings.record_scan(100, 2, Duration::from_millis(10));
+        timings.record_carve(Duration::from_millis(3));
+        let snap = timings.snapshot();
+        assert_eq!(snap.bytes_scanned, 100);
+        assert_eq!(snap.hits_scanned, 2);
+        assert_eq!(snap.hits_carved, 1);
+        assert_eq!(snap.densities.chunks(), 1);
+        assert!((snap.densities.mean() - 0.02).abs() < 1e-12);
+        assert!((snap.scan_secs - 0.010).abs() < 1e-9);
+    }
+}
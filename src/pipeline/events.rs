@@ -3,8 +3,26 @@
 //! Events that flow through the pipeline for metadata recording.
 
 use crate::carve::CarvedFile;
-use crate::metadata::{EntropyRegion, RunSummary};
-use crate::parsers::browser::{BrowserCookieRecord, BrowserDownloadRecord, BrowserHistoryRecord};
+use crate::metadata::{
+    CarveCompanionRecord, CarveSizeRecord, ClassificationRecord, EntropyRegion, RunSummary,
+};
+use crate::parsers::apple::MessageRecord;
+use crate::parsers::apple::UsageEventRecord;
+use crate::parsers::bplist::PlistRecord;
+use crate::parsers::browser::{
+    BrowserCacheRecord, BrowserCookieRecord, BrowserDownloadRecord, BrowserHistoryRecord,
+};
+use crate::parsers::container::ContainerArtefactRecord;
+use crate::parsers::executable::ExecutableRecord;
+use crate::parsers::exif::PhotoDeviceRecord;
+use crate::parsers::image::ImageInfoRecord;
+use crate::parsers::lnk::ShortcutRecord;
+use crate::parsers::logs::LogEventRecord;
+use crate::parsers::mp4::VideoRepairRecord;
+use crate::parsers::office_crypto::EncryptedDocumentRecord;
+use crate::parsers::prefetch::PrefetchRecord;
+use crate::parsers::sqlite_apps::AppRecord;
+use crate::parsers::thumbcache::ThumbnailRecord;
 use crate::strings::artifacts::StringArtefact;
 
 /// Events sent to the metadata recording thread
@@ -20,10 +38,54 @@ pub enum MetadataEvent {
     Cookie(BrowserCookieRecord),
     /// A browser download record was parsed
     Download(BrowserDownloadRecord),
-    /// Run summary statistics
+    /// A cached HTTP response was reconstructed
+    Cache(BrowserCacheRecord),
+    /// A syslog line or journal entry was recovered
+    LogEvent(LogEventRecord),
+    /// A container image, layer, or runtime record was found
+    Container(ContainerArtefactRecord),
+    /// A note or chat message was recovered
+    Message(MessageRecord),
+    /// A device usage event was recovered
+    UsageEvent(UsageEventRecord),
+    /// A row read by a SQLite application catalog plan
+    AppRecord(AppRecord),
+    /// Executable and run count of a carved prefetch file
+    Prefetch(PrefetchRecord),
+    /// Target of a carved shortcut
+    Shortcut(ShortcutRecord),
+    /// Architecture, compile time, and imports of a carved executable
+    Executable(ExecutableRecord),
+    /// Cache ID and extracted image of a thumbnail cache entry
+    Thumbnail(ThumbnailRecord),
+    /// Decoded contents of a carved binary plist
+    Plist(PlistRecord),
+    /// Rebuilt index of a recording that was never finalized
+    VideoRepair(VideoRepairRecord),
+    /// Dimensions and decode progress of a carved image
+    ImageInfo(ImageInfoRecord),
+    /// Camera fields of a carved photo; written with its device and session
+    /// once the channel closes
+    PhotoExif(PhotoDeviceRecord),
+    /// Encryption metadata of a password-protected document
+    EncryptedDocument(EncryptedDocumentRecord),
+    /// Declared size or truncation cause of a carved file
+    CarveSize(CarveSizeRecord),
+    /// Link between a carved file and a companion carved with it
+    CarveCompanion(CarveCompanionRecord),
+    /// Label and score a content classifier gave a carved file
+    Classification(ClassificationRecord),
+    /// Run summary statistics; recorded after every other event, once all
+    /// senders are gone
     RunSummary(RunSummary),
     /// High entropy region detected
     Entropy(EntropyRegion),
+    /// Start of records that belong together (a carved file, its size
+    /// record, and its post-processor records); a flush waits until every
+    /// open group has ended, so it does not split one
+    GroupStart,
+    /// End of the records opened by a `GroupStart`
+    GroupEnd,
     /// Flush buffered data to disk
     Flush,
 }
@@ -0,0 +1,343 @@
+//! # Gap-Only String Scanning
+//!
+//! With `string_scan_gaps_only`, artefacts are extracted only from string spans
+//! that lie outside every carved file. Carved files are handled again by the
+//! per-file post-processors, so strings inside them mostly duplicate what that
+//! pass already reports.
+//!
+//! Whether a span is covered is only known once carving has finished, so the
+//! scan workers still find spans as usual, but a collector thread keeps just
+//! their offsets (not the chunk data) in [`DeferredSpans`], merging spans seen
+//! twice in chunk overlaps as they arrive. Carve workers record each carved
+//! file in [`CarvedRanges`]. After the carve stage joins, [`gap_jobs`] drops
+//! spans that are fully covered by a carved file and groups the rest into
+//! windows of at most [`MAX_GAP_WINDOW`] bytes that are re-read from the
+//! evidence and handed to the string workers.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::thread;
+
+use crossbeam_channel::Receiver;
+
+use crate::chunk::ScanChunk;
+use crate::strings::StringSpan;
+
+use super::workers::StringJob;
+
+/// Byte ranges of carved files, filled by carve workers.
+#[derive(Debug, Default)]
+pub struct CarvedRanges {
+    ranges: Mutex<Vec<(u64, u64)>>,
+}
+
+impl CarvedRanges {
+    /// Record a carved file spanning `start..=end` in evidence offsets.
+    pub fn insert(&self, start: u64, end: u64) {
+        if end < start {
+            return;
+        }
+        if let Ok(mut ranges) = self.ranges.lock() {
+            ranges.push((start, end.saturating_add(1)));
+        }
+    }
+
+    /// Sorted, merged view of the ranges recorded so far.
+    pub fn snapshot(&self) -> CarveMap {
+        let mut ranges = self.ranges.lock().map(|r| r.clone()).unwrap_or_default();
+        ranges.sort_unstable();
+        let mut merged: Vec<(u64, u64)> = Vec::with_capacity(ranges.len());
+        for (start, end) in ranges {
+            match merged.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+        CarveMap { ranges: merged }
+    }
+}
+
+/// Disjoint, sorted half-open ranges covered by carved files.
+#[derive(Debug, Default)]
+pub struct CarveMap {
+    ranges: Vec<(u64, u64)>,
+}
+
+impl CarveMap {
+    /// True if `start..end` lies entirely inside one carved file range.
+    pub fn covers(&self, start: u64, end: u64) -> bool {
+        let idx = self.ranges.partition_point(|&(s, _)| s <= start);
+        idx > 0 && self.ranges[idx - 1].1 >= end
+    }
+
+    pub fn len(&self) -> usize {
+        self.ranges.len()
+    }
+
+    pub fn ranges(&self) -> &[(u64, u64)] {
+        &self.ranges
+    }
+}
+
+/// Longest window re-read for one gap job, unless a single span is longer.
+pub const MAX_GAP_WINDOW: u64 = 1024 * 1024;
+
+/// A held-back span, keyed by its evidence offset in [`DeferredSpans`].
+#[derive(Debug, Clone, Copy)]
+struct Deferred {
+    chunk_id: u64,
+    length: u32,
+    flags: u32,
+}
+
+impl Deferred {
+    fn end(&self, start: u64) -> u64 {
+        start + self.length as u64
+    }
+}
+
+/// Spans held back for the gap pass, keyed by evidence offset, without the
+/// chunk data. A span inside one already held (found again in the overlap of
+/// the next chunk) is dropped, and one that contains held spans replaces them.
+#[derive(Debug, Default)]
+pub struct DeferredSpans {
+    spans: BTreeMap<u64, Deferred>,
+}
+
+impl DeferredSpans {
+    /// Hold back the spans found in `chunk`.
+    pub fn add(&mut self, chunk: &ScanChunk, spans: Vec<StringSpan>) {
+        for span in spans {
+            let start = chunk.start + span.local_start;
+            let deferred = Deferred {
+                chunk_id: span.chunk_id,
+                length: span.length,
+                flags: span.flags,
+            };
+            let end = deferred.end(start);
+            if let Some((&at, held)) = self.spans.range(..=start).next_back()
+                && held.end(at) >= end
+            {
+                continue;
+            }
+            let inside: Vec<u64> = self
+                .spans
+                .range(start..end)
+                .filter(|&(&at, held)| held.end(at) <= end)
+                .map(|(&at, _)| at)
+                .collect();
+            for at in inside {
+                self.spans.remove(&at);
+            }
+            self.spans.insert(start, deferred);
+        }
+    }
+}
+
+/// A window of evidence to re-read and the spans inside it, relative to the
+/// window start.
+pub struct GapJob {
+    pub chunk: ScanChunk,
+    pub spans: Vec<StringSpan>,
+}
+
+impl GapJob {
+    pub fn into_string_job(self, data: Vec<u8>) -> StringJob {
+        StringJob {
+            chunk: self.chunk,
+            data: std::sync::Arc::new(data),
+            spans: self.spans,
+        }
+    }
+}
+
+/// Span counts from the gap filter, logged at the end of the run.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct GapStats {
+    pub spans_kept: u64,
+    pub spans_skipped: u64,
+    pub bytes_skipped: u64,
+}
+
+/// Drain the string channel, keeping span offsets for the post-carve pass.
+pub fn spawn_collector(rx: Receiver<StringJob>) -> thread::JoinHandle<DeferredSpans> {
+    thread::spawn(move || {
+        let mut deferred = DeferredSpans::default();
+        for job in rx.iter() {
+            deferred.add(&job.chunk, job.spans);
+        }
+        deferred
+    })
+}
+
+/// Drop spans covered by a carved file and group the rest, in evidence
+/// order, into windows of at most [`MAX_GAP_WINDOW`] bytes.
+pub fn gap_jobs(deferred: DeferredSpans, carved: &CarveMap) -> (Vec<GapJob>, GapStats) {
+    let mut stats = GapStats::default();
+    let mut jobs = Vec::new();
+    let mut window: Option<Window> = None;
+    for (start, span) in deferred.spans {
+        let end = span.end(start);
+        if carved.covers(start, end) {
+            stats.spans_skipped += 1;
+            stats.bytes_skipped += span.length as u64;
+            continue;
+        }
+        stats.spans_kept += 1;
+        match &mut window {
+            Some(open) if end - open.first <= MAX_GAP_WINDOW => {
+                open.last = open.last.max(end);
+                open.spans.push((start, span));
+            }
+            _ => {
+                let next = Window {
+                    first: start,
+                    last: end,
+                    spans: vec![(start, span)],
+                };
+                jobs.extend(window.replace(next).map(Window::into_job));
+            }
+        }
+    }
+    jobs.extend(window.map(Window::into_job));
+    (jobs, stats)
+}
+
+/// Kept spans gathered into one re-read from `first` to `last`.
+struct Window {
+    first: u64,
+    last: u64,
+    spans: Vec<(u64, Deferred)>,
+}
+
+impl Window {
+    fn into_job(self) -> GapJob {
+        let first = self.first;
+        GapJob {
+            chunk: ScanChunk {
+                id: self.spans.first().map_or(0, |(_, span)| span.chunk_id),
+                start: first,
+                length: self.last - first,
+                valid_length: self.last - first,
+            },
+            spans: self
+                .spans
+                .into_iter()
+                .map(|(start, span)| StringSpan {
+                    chunk_id: span.chunk_id,
+                    local_start: start - first,
+                    length: span.length,
+                    flags: span.flags,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(local_start: u64, length: u32) -> StringSpan {
+        StringSpan {
+            chunk_id: 0,
+            local_start,
+            length,
+            flags: 0,
+        }
+    }
+
+    fn chunk(start: u64, length: u64) -> ScanChunk {
+        ScanChunk {
+            id: 1,
+            start,
+            length,
+            valid_length: length,
+        }
+    }
+
+    #[test]
+    fn merges_spans_found_again_in_chunk_overlaps() {
+        let mut deferred = DeferredSpans::default();
+        // Cut short at the end of the first chunk.
+        deferred.add(&chunk(0, 1000), vec![span(10, 20), span(980, 20)]);
+        // The second chunk starts 100 bytes before the first one ends.
+        deferred.add(&chunk(900, 1000), vec![span(80, 50), span(90, 10)]);
+        let starts: Vec<(u64, u32)> = deferred
+            .spans
+            .iter()
+            .map(|(&at, span)| (at, span.length))
+            .collect();
+        assert_eq!(starts, vec![(10, 20), (980, 50)]);
+    }
+
+    #[test]
+    fn splits_gap_windows_at_the_window_bound() {
+        let mut deferred = DeferredSpans::default();
+        deferred.add(
+            &chunk(0, 4 * MAX_GAP_WINDOW),
+            vec![
+                span(0, 16),
+                span(MAX_GAP_WINDOW - 16, 16),
+                span(MAX_GAP_WINDOW, 16),
+                span(3 * MAX_GAP_WINDOW, 16),
+            ],
+        );
+        let (jobs, stats) = gap_jobs(deferred, &CarveMap::default());
+        assert_eq!(stats.spans_kept, 4);
+        let windows: Vec<(u64, u64, usize)> = jobs
+            .iter()
+            .map(|job| (job.chunk.start, job.chunk.length, job.spans.len()))
+            .collect();
+        assert_eq!(
+            windows,
+            vec![
+                (0, MAX_GAP_WINDOW, 2),
+                (MAX_GAP_WINDOW, 16, 1),
+                (3 * MAX_GAP_WINDOW, 16, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn merges_ranges_and_checks_coverage() {
+        let ranges = CarvedRanges::default();
+        ranges.insert(100, 199);
+        ranges.insert(150, 299);
+        ranges.insert(1000, 1099);
+        let map = ranges.snapshot();
+        assert_eq!(map.len(), 2);
+        assert!(map.covers(100, 300));
+        assert!(map.covers(1000, 1100));
+        assert!(!map.covers(90, 110));
+        assert!(!map.covers(290, 310));
+        assert!(!map.covers(500, 510));
+    }
+
+    #[test]
+    fn gap_jobs_keep_only_uncovered_spans() {
+        let ranges = CarvedRanges::default();
+        ranges.insert(4096, 8191);
+        let mut deferred = DeferredSpans::default();
+        deferred.add(
+            &chunk(4000, 8192),
+            // Partly before the carve, inside it, and after it.
+            vec![span(50, 100), span(200, 32), span(5000, 16)],
+        );
+        let (jobs, stats) = gap_jobs(deferred, &ranges.snapshot());
+        assert_eq!(
+            stats,
+            GapStats {
+                spans_kept: 2,
+                spans_skipped: 1,
+                bytes_skipped: 32,
+            }
+        );
+        assert_eq!(jobs.len(), 1);
+        let job = &jobs[0];
+        assert_eq!(job.chunk.start, 4050);
+        assert_eq!(job.chunk.length, 4966);
+        let starts: Vec<u64> = job.spans.iter().map(|s| s.local_start).collect();
+        assert_eq!(starts, vec![0, 4950]);
+    }
+}
@@ -0,0 +1,293 @@
+//! Evidence digests computed from the chunks the scan loop reads.
+//!
+//! With `evidence_hashes` set (`--hash-evidence`), the reader hands every
+//! chunk it reads to [`FusedHasher`], which forwards the bytes not hashed yet
+//! (chunks overlap) to one thread per digest, so hashing costs no extra pass.
+//! The single-threaded pipeline hashes on the reader's thread instead.
+//! Bytes the scan does not read are read here: the prefix before a resume
+//! offset, and the tail after a run stopped at `--max-bytes`, `--max-chunks`,
+//! or `max_files`. A cancelled run, or one with unreadable chunks, reports no
+//! digests.
+
+use std::ops::Range;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use crossbeam_channel::{Sender, bounded};
+use serde::Serialize;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+
+use crate::config::EvidenceDigest;
+use crate::evidence::EvidenceSource;
+
+/// Pieces queued per digest thread before the reader waits.
+const QUEUE_DEPTH: usize = 4;
+/// Read size for evidence the scan loop does not read.
+const CATCH_UP_READ: usize = 8 * 1024 * 1024;
+
+/// Hex digests of the whole evidence; digests not requested are absent.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct EvidenceHashes {
+    pub md5: Option<String>,
+    pub sha1: Option<String>,
+    pub sha256: Option<String>,
+}
+
+impl EvidenceHashes {
+    fn set(&mut self, digest: EvidenceDigest, hex: String) {
+        match digest {
+            EvidenceDigest::Md5 => self.md5 = Some(hex),
+            EvidenceDigest::Sha1 => self.sha1 = Some(hex),
+            EvidenceDigest::Sha256 => self.sha256 = Some(hex),
+        }
+    }
+}
+
+/// Bytes `range` of a chunk read by the scan loop.
+type Piece = (Arc<Vec<u8>>, Range<usize>);
+
+enum Lane {
+    Thread {
+        tx: Sender<Piece>,
+        handle: JoinHandle<(EvidenceDigest, String)>,
+    },
+    Inline(EvidenceDigest, Hasher),
+}
+
+/// Hashes evidence in offset order as the scan loop reads it.
+pub(crate) struct FusedHasher {
+    lanes: Vec<Lane>,
+    /// First evidence offset not hashed yet.
+    next: u64,
+    /// Why the digests cannot cover the evidence, once known.
+    broken: Option<String>,
+}
+
+impl FusedHasher {
+    /// One thread per requested digest, or none with `inline`; `None` when
+    /// no digest is requested.
+    pub(crate) fn start(digests: &[EvidenceDigest], inline: bool) -> Option<Self> {
+        let mut unique = digests.to_vec();
+        unique.sort();
+        unique.dedup();
+        if unique.is_empty() {
+            return None;
+        }
+        let lanes = unique
+            .into_iter()
+            .map(|digest| {
+                if inline {
+                    return Lane::Inline(digest, Hasher::new(digest));
+                }
+                let (tx, rx) = bounded::<Piece>(QUEUE_DEPTH);
+                let handle = thread::spawn(move || {
+                    let mut hasher = Hasher::new(digest);
+                    for (data, range) in rx {
+                        hasher.update(&data[range]);
+                    }
+                    (digest, hasher.finish())
+                });
+                Lane::Thread { tx, handle }
+            })
+            .collect();
+        Some(Self {
+            lanes,
+            next: 0,
+            broken: None,
+        })
+    }
+
+    /// Hash `data`, read at evidence offset `start`, skipping bytes already
+    /// hashed. A gap before `start` leaves the digests incomplete.
+    pub(crate) fn feed(&mut self, start: u64, data: &Arc<Vec<u8>>) {
+        if self.broken.is_some() {
+            return;
+        }
+        if start > self.next {
+            self.abandon(format!("bytes {}..{start} were not read", self.next));
+            return;
+        }
+        let skip = (self.next - start).min(data.len() as u64) as usize;
+        if skip == data.len() {
+            return;
+        }
+        for lane in &mut self.lanes {
+            match lane {
+                // Fails only if the lane panicked, which `finish` reports.
+                Lane::Thread { tx, .. } => {
+                    let _ = tx.send((data.clone(), skip..data.len()));
+                }
+                Lane::Inline(_, hasher) => hasher.update(&data[skip..]),
+            }
+        }
+        self.next = start + data.len() as u64;
+    }
+
+    /// Read and hash the evidence the scan loop did not read, up to `end`.
+    pub(crate) fn catch_up(&mut self, evidence: &dyn EvidenceSource, end: u64) {
+        if self.broken.is_none() && self.next < end {
+            info!(
+                "hashing evidence bytes {}..{end} outside the scanned range",
+                self.next
+            );
+        }
+        while self.broken.is_none() && self.next < end {
+            let len = (end - self.next).min(CATCH_UP_READ as u64) as usize;
+            let mut buf = vec![0u8; len];
+            match evidence.read_at(self.next, &mut buf) {
+                Ok(0) => break,
+                Ok(n) => {
+                    buf.truncate(n);
+                    let start = self.next;
+                    self.feed(start, &Arc::new(buf));
+                }
+                Err(err) => {
+                    self.abandon(format!("read error at offset {}: {err}", self.next));
+                }
+            }
+        }
+    }
+
+    /// Give up on the digests; `finish` then returns `None`.
+    pub(crate) fn abandon(&mut self, reason: String) {
+        self.broken.get_or_insert(reason);
+    }
+
+    /// Digests of the first `total` bytes, or `None` when not all of them
+    /// were hashed.
+    pub(crate) fn finish(mut self, total: u64) -> Option<EvidenceHashes> {
+        if self.next < total {
+            let next = self.next;
+            self.abandon(format!("only {next} of {total} bytes were read"));
+        }
+        let mut hashes = EvidenceHashes::default();
+        for lane in self.lanes {
+            let (tx, handle) = match lane {
+                Lane::Thread { tx, handle } => (tx, handle),
+                Lane::Inline(digest, hasher) => {
+                    hashes.set(digest, hasher.finish());
+                    continue;
+                }
+            };
+            drop(tx);
+            match handle.join() {
+                Ok((digest, hex)) => hashes.set(digest, hex),
+                Err(_) => {
+                    self.broken
+                        .get_or_insert_with(|| "a hash thread panicked".to_string());
+                }
+            }
+        }
+        match self.broken {
+            Some(reason) => {
+                warn!("evidence hashes not computed: {reason}");
+                None
+            }
+            None => Some(hashes),
+        }
+    }
+}
+
+enum Hasher {
+    Md5(md5::Context),
+    Sha1(Sha1),
+    Sha256(Sha256),
+}
+
+impl Hasher {
+    fn new(digest: EvidenceDigest) -> Self {
+        match digest {
+            EvidenceDigest::Md5 => Self::Md5(md5::Context::new()),
+            EvidenceDigest::Sha1 => Self::Sha1(Sha1::new()),
+            EvidenceDigest::Sha256 => Self::Sha256(Sha256::new()),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Md5(context) => context.consume(data),
+            Self::Sha1(hasher) => hasher.update(data),
+            Self::Sha256(hasher) => hasher.update(data),
+        }
+    }
+
+    fn finish(self) -> String {
+        match self {
+            Self::Md5(context) => format!("{:x}", context.compute()),
+            Self::Sha1(hasher) => hex::encode(hasher.finalize()),
+            Self::Sha256(hasher) => hex::encode(hasher.finalize()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evidence::MemorySource;
+
+    #[test]
+    fn digests_match_reference_vectors() {
+        let expected = [
+            (EvidenceDigest::Md5, "900150983cd24fb0d6963f7d28e17f72"),
+            (
+                EvidenceDigest::Sha1,
+                "a9993e364706816aba3e25717850c26c9cd0d89d",
+            ),
+            (
+                EvidenceDigest::Sha256,
+                "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            ),
+        ];
+        for (digest, hex) in expected {
+            let mut hasher = Hasher::new(digest);
+            hasher.update(b"ab");
+            hasher.update(b"c");
+            assert_eq!(hasher.finish(), hex);
+        }
+    }
+
+    #[test]
+    fn overlapping_chunks_hash_like_one_pass() {
+        let data: Vec<u8> = (0..10_000u32).map(|i| (i * 7) as u8).collect();
+        let source = MemorySource::new(data.clone());
+        let all = [
+            EvidenceDigest::Sha256,
+            EvidenceDigest::Md5,
+            EvidenceDigest::Sha1,
+        ];
+        // Threaded and inline lanes alike.
+        for inline in [false, true] {
+            let mut hasher = FusedHasher::start(&all, inline).expect("digests requested");
+            // Resume at 3000, then 2048-byte chunks with 512 bytes of overlap,
+            // stopping early at 8000.
+            hasher.catch_up(&source, 3000);
+            let mut start = 3000;
+            while start < 8000 {
+                let end = (start + 2048 + 512).min(8000);
+                hasher.feed(start as u64, &Arc::new(data[start..end].to_vec()));
+                start += 2048;
+            }
+            hasher.catch_up(&source, data.len() as u64);
+            let hashes = hasher.finish(data.len() as u64).expect("complete");
+            assert_eq!(hashes.sha256, Some(hex::encode(Sha256::digest(&data))));
+            assert_eq!(hashes.md5, Some(format!("{:x}", md5::compute(&data))));
+            assert_eq!(hashes.sha1, Some(hex::encode(Sha1::digest(&data))));
+        }
+    }
+
+    #[test]
+    fn gaps_and_short_reads_leave_no_digest() {
+        let mut hasher = FusedHasher::start(&[EvidenceDigest::Md5], false).unwrap();
+        hasher.feed(0, &Arc::new(vec![1; 100]));
+        hasher.feed(200, &Arc::new(vec![1; 100]));
+        assert_eq!(hasher.finish(300), None);
+
+        let mut hasher = FusedHasher::start(&[EvidenceDigest::Md5], false).unwrap();
+        hasher.feed(0, &Arc::new(vec![1; 100]));
+        assert_eq!(hasher.finish(300), None);
+
+        assert!(FusedHasher::start(&[], false).is_none());
+    }
+}
@@ -0,0 +1,344 @@
+//! # Hit Context Export
+//!
+//! Training a classifier to rank carves needs the raw bytes around each hit
+//! together with what the carver made of it. With `hit_context_window` set,
+//! [`HitContextExport`] reads a fixed-size window around every carve attempt
+//! of the types in `hit_context_types` (and, with `hit_context_entropy`,
+//! around every high-entropy region) and appends it as one row of
+//! `hit_context/windows.npy`, a NumPy `uint8` array of shape
+//! `(rows, hit_context_window)`, with the hit `hit_context_before` bytes into
+//! each row. Windows cut short by the start or end of the evidence are
+//! zero-padded. `hit_context/index.jsonl` describes each row:
+//! its kind, type, pattern, offsets, valid bytes, and the carve outcome that
+//! serves as the label.
+
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use tracing::warn;
+
+use crate::config::Config;
+use crate::evidence::EvidenceSource;
+use crate::metadata::EntropyRegion;
+use crate::scanner::NormalizedHit;
+
+/// Directory (relative to the run output directory) holding the export.
+pub const HIT_CONTEXT_DIR: &str = "hit_context";
+pub const WINDOWS_FILE: &str = "windows.npy";
+pub const INDEX_FILE: &str = "index.jsonl";
+
+/// The `.npy` header is rewritten with the final row count, so it is padded
+/// to a fixed length that fits any count.
+const NPY_HEADER_LEN: usize = 128;
+
+/// What the carver made of a hit; the label of its row.
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HitOutcome {
+    /// Carved and validated.
+    Validated,
+    /// Carved but not validated (truncated or failed checks).
+    Carved,
+    /// Rejected by the handler as not a file of its type.
+    Rejected,
+    /// The carve failed with an error.
+    Failed,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WindowKind {
+    Hit,
+    Entropy,
+}
+
+/// One row of `index.jsonl`.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct HitContextRow {
+    /// Row of the window in `windows.npy`.
+    pub row: u64,
+    pub kind: WindowKind,
+    pub file_type: Option<String>,
+    pub pattern_id: Option<String>,
+    /// Offset of the hit, or start of the entropy region.
+    pub global_offset: u64,
+    /// Evidence offset of the first evidence byte in the window.
+    pub window_start: u64,
+    /// Bytes of the window read from the evidence; the rest is padding.
+    pub valid_bytes: u64,
+    pub outcome: Option<HitOutcome>,
+    pub entropy: Option<f64>,
+}
+
+struct Writers {
+    windows: BufWriter<File>,
+    index: BufWriter<File>,
+    rows: u64,
+}
+
+/// Window exporter shared by the scan and carve workers.
+pub struct HitContextExport {
+    evidence: Arc<dyn EvidenceSource>,
+    window: usize,
+    before: u64,
+    types: Vec<String>,
+    entropy: bool,
+    max_windows: u64,
+    dir: PathBuf,
+    writers: Mutex<Option<Writers>>,
+}
+
+impl HitContextExport {
+    /// `None` when `hit_context_window` is 0 or there is no run output
+    /// directory to write to (dry runs).
+    pub fn from_config(
+        cfg: &Config,
+        evidence: Arc<dyn EvidenceSource>,
+        run_output_dir: &Path,
+    ) -> Option<Self> {
+        if cfg.hit_context_window == 0 || !run_output_dir.is_dir() {
+            return None;
+        }
+        Some(Self {
+            evidence,
+            window: cfg.hit_context_window as usize,
+            before: cfg.hit_context_before,
+            types: cfg.hit_context_types.clone(),
+            entropy: cfg.hit_context_entropy,
+            max_windows: cfg.hit_context_max_windows,
+            dir: run_output_dir.join(HIT_CONTEXT_DIR),
+            writers: Mutex::new(None),
+        })
+    }
+
+    /// Whether attempts of `file_type` are exported.
+    pub fn wants(&self, file_type: &str) -> bool {
+        self.types.is_empty() || self.types.iter().any(|t| t == file_type)
+    }
+
+    /// Export the window around a carve attempt.
+    pub fn record_hit(&self, hit: &NormalizedHit, outcome: HitOutcome) {
+        if !self.wants(&hit.file_type_id) {
+            return;
+        }
+        self.record(HitContextRow {
+            row: 0,
+            kind: WindowKind::Hit,
+            file_type: Some(hit.file_type_id.clone()),
+            pattern_id: Some(hit.pattern_id.clone()),
+            global_offset: hit.global_offset,
+            window_start: 0,
+            valid_bytes: 0,
+            outcome: Some(outcome),
+            entropy: None,
+        });
+    }
+
+    /// Export the window at the start of a high-entropy region.
+    pub fn record_entropy(&self, region: &EntropyRegion) {
+        if !self.entropy {
+            return;
+        }
+        self.record(HitContextRow {
+            row: 0,
+            kind: WindowKind::Entropy,
+            file_type: None,
+            pattern_id: None,
+            global_offset: region.global_start,
+            window_start: 0,
+            valid_bytes: 0,
+            outcome: None,
+            entropy: Some(region.entropy),
+        });
+    }
+
+    /// Rows written so far.
+    pub fn rows(&self) -> u64 {
+        self.writers
+            .lock()
+            .map(|w| w.as_ref().map_or(0, |w| w.rows))
+            .unwrap_or(0)
+    }
+
+    fn record(&self, mut row: HitContextRow) {
+        if self.max_windows > 0 && self.rows() >= self.max_windows {
+            return;
+        }
+        // Windows near the evidence start are padded in front, so the hit
+        // always sits `before` bytes into its row.
+        let lead = row.global_offset.min(self.before);
+        let pad = ((self.before - lead) as usize).min(self.window);
+        row.window_start = row.global_offset - lead;
+        let mut buf = vec![0u8; self.window];
+        let read = self
+            .evidence
+            .read_vectored_at(&mut [(row.window_start, &mut buf[pad..])])
+            .map(|lens| lens[0]);
+        row.valid_bytes = match read {
+            Ok(n) => n as u64,
+            Err(err) => {
+                warn!(
+                    "hit context read at offset {} failed: {err}",
+                    row.window_start
+                );
+                return;
+            }
+        };
+
+        let Ok(mut writers) = self.writers.lock() else {
+            return;
+        };
+        if self.max_windows > 0 && writers.as_ref().is_some_and(|w| w.rows >= self.max_windows) {
+            return;
+        }
+        if writers.is_none() {
+            match self.open() {
+                Ok(opened) => *writers = Some(opened),
+                Err(err) => {
+                    warn!("failed to create {}: {err}", self.dir.display());
+                    return;
+                }
+            }
+        }
+        let Some(w) = writers.as_mut() else {
+            return;
+        };
+        row.row = w.rows;
+        let written = w.windows.write_all(&buf).and_then(|_| {
+            serde_json::to_writer(&mut w.index, &row)?;
+            w.index.write_all(b"\n")
+        });
+        match written {
+            Ok(()) => w.rows += 1,
+            Err(err) => warn!("failed to write hit context window: {err}"),
+        }
+    }
+
+    fn open(&self) -> std::io::Result<Writers> {
+        std::fs::create_dir_all(&self.dir)?;
+        let mut windows = BufWriter::new(File::create(self.dir.join(WINDOWS_FILE))?);
+        windows.write_all(&npy_header(0, self.window))?;
+        let index = BufWriter::new(File::create(self.dir.join(INDEX_FILE))?);
+        Ok(Writers {
+            windows,
+            index,
+            rows: 0,
+        })
+    }
+
+    /// Write the final row count into the `.npy` header and flush both
+    /// files. Returns the number of rows.
+    pub fn finish(&self) -> std::io::Result<u64> {
+        let Ok(mut writers) = self.writers.lock() else {
+            return Ok(0);
+        };
+        let Some(w) = writers.as_mut() else {
+            return Ok(0);
+        };
+        w.index.flush()?;
+        w.windows.flush()?;
+        let file = w.windows.get_mut();
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(&npy_header(w.rows, self.window))?;
+        file.seek(SeekFrom::End(0))?;
+        Ok(w.rows)
+    }
+}
+
+/// NumPy format 1.0 header for a C-order `uint8` array of `rows` x `cols`.
+fn npy_header(rows: u64, cols: usize) -> Vec<u8> {
+    let dict = format!("{{'descr': '|u1', 'fortran_order': False, 'shape': ({rows}, {cols}), }}");
+    let mut header = b"\x93NUMPY\x01\x00".to_vec();
+    header.extend_from_slice(&((NPY_HEADER_LEN - 10) as u16).to_le_bytes());
+    header.extend_from_slice(dict.as_bytes());
+    header.resize(NPY_HEADER_LEN - 1, b' ');
+    header.push(b'\n');
+    header
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evidence::RawFileSource;
+
+    fn export(dir: &Path, image: &[u8], cfg: &Config) -> HitContextExport {
+        let path = dir.join("image.bin");
+        std::fs::write(&path, image).unwrap();
+        let evidence: Arc<dyn EvidenceSource> = Arc::new(RawFileSource::open(&path).unwrap());
+        let run_dir = dir.join("run");
+        std::fs::create_dir_all(&run_dir).unwrap();
+        HitContextExport::from_config(cfg, evidence, &run_dir).expect("enabled")
+    }
+
+    fn hit(file_type: &str, offset: u64) -> NormalizedHit {
+        NormalizedHit {
+            global_offset: offset,
+            file_type_id: file_type.to_string(),
+            pattern_id: format!("{file_type}_header"),
+        }
+    }
+
+    #[test]
+    fn writes_padded_windows_and_index_rows() {
+        let dir = tempfile::tempdir().unwrap();
+        let image: Vec<u8> = (0..=255u8).collect();
+        let mut cfg = crate::config::load_config(None).unwrap().config;
+        cfg.hit_context_window = 16;
+        cfg.hit_context_before = 4;
+        cfg.hit_context_types = vec!["jpeg".to_string()];
+        cfg.hit_context_entropy = true;
+        let export = export(dir.path(), &image, &cfg);
+
+        export.record_hit(&hit("jpeg", 2), HitOutcome::Validated);
+        export.record_hit(&hit("png", 64), HitOutcome::Rejected);
+        export.record_hit(&hit("jpeg", 250), HitOutcome::Carved);
+        export.record_entropy(&EntropyRegion {
+            run_id: "run".to_string(),
+            global_start: 100,
+            global_end: 200,
+            entropy: 7.9,
+            window_size: 16,
+        });
+        assert_eq!(export.finish().unwrap(), 3);
+
+        let out = dir.path().join("run").join(HIT_CONTEXT_DIR);
+        let npy = std::fs::read(out.join(WINDOWS_FILE)).unwrap();
+        assert_eq!(&npy[..6], b"\x93NUMPY");
+        let header = String::from_utf8_lossy(&npy[10..NPY_HEADER_LEN]);
+        assert!(header.contains("'shape': (3, 16)"), "{header}");
+        let rows = &npy[NPY_HEADER_LEN..];
+        assert_eq!(rows.len(), 48);
+        assert_eq!(&rows[..5], &[0, 0, 0, 1, 2]);
+        assert_eq!(&rows[16..22], &[246, 247, 248, 249, 250, 251]);
+        assert!(rows[26..32].iter().all(|&b| b == 0));
+        assert_eq!(rows[32], 96);
+
+        let index: Vec<serde_json::Value> = std::fs::read_to_string(out.join(INDEX_FILE))
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(index[0]["window_start"], 0);
+        assert_eq!(index[0]["valid_bytes"], 14);
+        assert_eq!(index[0]["outcome"], "validated");
+        assert_eq!(index[1]["valid_bytes"], 10);
+        assert_eq!(index[2]["kind"], "entropy");
+        assert_eq!(index[2]["row"], 2);
+    }
+
+    #[test]
+    fn stops_at_max_windows() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut cfg = crate::config::load_config(None).unwrap().config;
+        cfg.hit_context_window = 8;
+        cfg.hit_context_max_windows = 2;
+        let export = export(dir.path(), &[0xAA; 64], &cfg);
+        for offset in [0, 8, 16] {
+            export.record_hit(&hit("zip", offset), HitOutcome::Failed);
+        }
+        assert_eq!(export.finish().unwrap(), 2);
+    }
+}
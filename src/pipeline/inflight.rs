@@ -0,0 +1,67 @@
+//! Chunks and hits between the reader and the carve workers.
+//!
+//! A streamed input can only be read inside its window, so before the reader
+//! lets the window move past a chunk it waits for every chunk read so far to
+//! be scanned and every hit found in them to be carved.
+
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+/// How often a waiting reader asks whether the run is stopping.
+const CANCEL_POLL: Duration = Duration::from_millis(100);
+
+#[derive(Default)]
+pub struct InFlight {
+    count: Mutex<u64>,
+    idle: Condvar,
+}
+
+impl InFlight {
+    /// Count `n` more chunks or hits queued.
+    pub fn add(&self, n: u64) {
+        if let Ok(mut count) = self.count.lock() {
+            *count += n;
+        }
+    }
+
+    /// Take one queued chunk or hit, done when the returned guard drops.
+    pub fn claim(&self) -> Pending<'_> {
+        Pending(self)
+    }
+
+    fn finish(&self) {
+        if let Ok(mut count) = self.count.lock() {
+            *count = count.saturating_sub(1);
+            if *count == 0 {
+                self.idle.notify_all();
+            }
+        }
+    }
+
+    /// Block until nothing is in flight or `stopping` returns true: carve
+    /// workers that stop early leave their hits unclaimed.
+    pub fn wait_idle(&self, stopping: &dyn Fn() -> bool) {
+        let Ok(mut count) = self.count.lock() else {
+            return;
+        };
+        while *count > 0 {
+            if stopping() {
+                return;
+            }
+            count = match self.idle.wait_timeout(count, CANCEL_POLL) {
+                Ok((count, _)) => count,
+                Err(_) => return,
+            };
+        }
+    }
+}
+
+/// A claimed chunk or hit; dropping it, also while a worker unwinds from a
+/// panic, marks it done.
+pub struct Pending<'a>(&'a InFlight);
+
+impl Drop for Pending<'_> {
+    fn drop(&mut self) {
+        self.0.finish();
+    }
+}
@@ -1,10 +1,39 @@
 //! # Pipeline Module
 //!
 //! Orchestrates the scanning, carving, and metadata recording pipeline.
-//! This module handles multi-threaded processing of evidence sources.
+//! This module handles multi-threaded processing of evidence sources, or
+//! runs every stage on the calling thread with `pipeline_mode:
+//! single_threaded`.
 
+mod arbitration;
+mod compressed;
+mod coverage;
+mod eta;
 pub mod events;
-pub mod workers;
+mod gaps;
+mod hashing;
+mod hit_context;
+mod inflight;
+mod ntfs;
+mod output_sync;
+mod patterns;
+mod prefilter;
+mod profile;
+mod qa_sample;
+mod quarantine;
+pub mod queue;
+mod resources;
+mod shadow;
+mod single_threaded;
+mod supervisor;
+#[cfg(test)]
+mod testing;
+mod throttle;
+pub(crate) mod trace;
+mod transform_detect;
+mod unallocated;
+mod volume_locations;
+mod workers;
 
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
@@ -12,22 +41,62 @@ use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::time::{Duration, Instant};
 
 use anyhow::{Context, Result};
-use crossbeam_channel::bounded;
+use crossbeam_channel::{Receiver, bounded};
 use tracing::{info, warn};
 
 use crate::carve::CarveRegistry;
-use crate::checkpoint::{CheckpointState, save_checkpoint};
-use crate::chunk::{ScanChunk, build_chunks};
-use crate::config::Config;
+use crate::checkpoint::{CheckpointState, sample_evidence, save_checkpoint, verify_evidence};
+use crate::chunk::{ScanChunk, align_chunk_size, chunk_iter};
+use crate::config::{Config, EvidenceTransform, PipelineMode};
 use crate::constants::{CHANNEL_CAPACITY_MULTIPLIER, MIN_CHANNEL_CAPACITY};
-use crate::evidence::EvidenceSource;
+use crate::encryption::{DEFAULT_STAGING_DIR, OutputCipher, StagedOutput, is_memory_backed};
+use crate::evidence::{
+    BlockCacheSource, ByteTransform, EvidenceSource, TransformedSource, UNKNOWN_LEN,
+};
+use crate::inputs::{self, InputFile};
+use crate::metadata::snapshot::MetadataSnapshots;
 use crate::metadata::{MetadataSink, RunSummary};
-use crate::scanner::SignatureScanner;
+use crate::notify;
+use crate::outcome::RunStatus;
+use crate::postprocess::PostProcessRegistry;
+use crate::report::ntfs as ntfs_report;
+use crate::report::{
+    FileClocks, MetadataAggregates, PhotoDevices, clocks as file_clocks, frequency, photo_devices,
+};
+use crate::scanner::hybrid::BackendCounts;
+use crate::scanner::staging::GpuTransferCounts;
+use crate::scanner::{NormalizedHit, SignatureScanner};
 use crate::strings::StringScanner;
 use crate::strings::artifacts::ArtefactScanConfig;
+use crate::tempspace::{TempSpace, TempSpaceStats};
 
+use arbitration::CarveArbiter;
+use compressed::Lznt1Scan;
+use eta::StageTimings;
 use events::MetadataEvent;
-use workers::{ScanJob, StringJob};
+use gaps::{CarvedRanges, DeferredSpans};
+pub use hashing::EvidenceHashes;
+use hashing::FusedHasher;
+use hit_context::HitContextExport;
+use inflight::InFlight;
+use output_sync::OutputSync;
+use patterns::PatternStats;
+use prefilter::HitPrefilter;
+use profile::{ProfileEvent, Queue, RunProfiler};
+use qa_sample::QaSampler;
+use quarantine::Quarantine;
+use queue::{InlineQueue, StageSender};
+use resources::{ResourceLedger, ResourceSummary, Stage, ThreadUsage};
+use shadow::ShadowScan;
+use single_threaded::InlineStages;
+use supervisor::PanicLog;
+use throttle::HitThrottle;
+use trace::OffsetTrace;
+use volume_locations::VolumeLocator;
+use workers::{
+    CarveLimits, CarveRecorders, CarveStage, CarveWorker, MetadataRecorder, ScanBackends, ScanJob,
+    ScanWorker, StringJob, StringWorker, WorkerMonitors,
+};
 
 /// Configuration for entropy detection during scanning
 #[derive(Debug, Clone, Copy)]
@@ -37,7 +106,7 @@ pub struct EntropyConfig {
 }
 
 /// Pipeline statistics collected during a run
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize)]
 pub struct PipelineStats {
     pub bytes_scanned: u64,
     pub chunks_processed: u64,
@@ -45,24 +114,87 @@ pub struct PipelineStats {
     pub files_carved: u64,
     pub string_spans: u64,
     pub artefacts_extracted: u64,
+    /// Chunks skipped because the evidence read failed.
+    pub read_errors: u64,
+    pub carve_errors: u64,
+    pub metadata_errors: u64,
+    /// Panics caught in workers (each drops one job; the worker restarts).
+    pub worker_panics: u64,
+    /// Hits skipped by `hit_cluster_window` without a carve attempt.
+    pub hits_suppressed: u64,
+    /// Hits dropped by `hit_prefilter` because their first checksum failed.
+    pub hits_prefiltered: u64,
+    /// Carves deleted by `arbitration_overlap` in favour of a more confident
+    /// carve of another handler (not included in `files_carved`).
+    pub carves_suppressed: u64,
+    /// Patterns disabled by `pattern_disable_min_attempts`.
+    pub patterns_disabled: Vec<String>,
+    /// Hits skipped because their pattern was disabled.
+    pub hits_pattern_disabled: u64,
+    /// Files carved from NTFS LZNT1-compressed data (included in `files_carved`).
+    pub lznt1_files: u64,
+    /// Files carved from Volume Shadow Copy snapshots (included in `files_carved`).
+    pub shadow_files: u64,
+    /// Post-carve processor runs that failed (timeouts, non-zero exits, I/O).
+    pub postprocess_errors: u64,
+    /// Scratch space used by derived processing (`temp_space_limit`).
+    pub temp_space: TempSpaceStats,
+    /// Chunks the signature scanner ran on each backend.
+    pub signature_backends: BackendCounts,
+    /// Chunks the string scanner ran on each backend.
+    pub string_backends: BackendCounts,
+    /// Why the scan loop ended.
+    pub stop_reason: StopReason,
+    /// Hits left uncarved, or whose carve was stopped, by a cancellation;
+    /// the checkpoint resumes at the chunk of the first of them.
+    pub hits_abandoned: u64,
+    /// Checkpoint written because the run stopped early.
+    pub checkpoint: Option<PathBuf>,
+    /// Digests requested by `evidence_hashes`; absent when none were
+    /// requested or not all of the evidence could be hashed.
+    pub evidence_hashes: Option<EvidenceHashes>,
+    /// Device time of the GPU signature scanner; absent without `--gpu`.
+    pub gpu_transfer: Option<GpuTransferCounts>,
+}
+
+/// Why a pipeline run stopped scanning.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StopReason {
+    /// All chunks were scanned.
+    #[default]
+    Completed,
+    /// The cancellation flag was set (e.g. Ctrl+C).
+    Cancelled,
+    MaxBytes,
+    MaxChunks,
+    MaxFiles,
 }
 
 /// Progress snapshot reported during a run.
 #[derive(Debug, Clone)]
 pub struct ProgressSnapshot {
     pub bytes_scanned: u64,
+    /// 0 while a streamed input has not ended.
     pub total_bytes: u64,
     pub chunks_processed: u64,
     pub hits_found: u64,
     pub files_carved: u64,
     pub string_spans: u64,
     pub artefacts_extracted: u64,
+    pub read_errors: u64,
     pub carve_errors: u64,
     pub metadata_errors: u64,
     pub sqlite_errors: u64,
+    pub worker_panics: u64,
     pub elapsed_seconds: f64,
     pub throughput_mib: f64,
+    /// Estimated seconds to completion (stage cost model once calibrated,
+    /// bytes/sec extrapolation before that).
     pub eta_seconds: Option<u64>,
+    /// Optimistic and pessimistic ETA bounds from the stage cost model.
+    pub eta_low_seconds: Option<u64>,
+    pub eta_high_seconds: Option<u64>,
     /// Completion percentage (0.0 - 100.0)
     pub completion_pct: f64,
     /// Number of files that passed validation (if validation enabled)
@@ -84,6 +216,9 @@ pub struct ProgressConfig {
 pub struct CheckpointConfig {
     pub path: PathBuf,
     pub resume: Option<CheckpointState>,
+    /// Input files of this run, saved with the checkpoint and compared with
+    /// those of `resume`.
+    pub input_files: Vec<InputFile>,
 }
 
 /// Run the main processing pipeline.
@@ -182,10 +317,81 @@ fn run_pipeline_inner(
     progress: Option<ProgressConfig>,
     checkpoint: Option<CheckpointConfig>,
 ) -> Result<PipelineStats> {
+    let single_threaded = cfg.pipeline_mode == PipelineMode::SingleThreaded;
+    let workers = if single_threaded {
+        info!("pipeline_mode single_threaded: all stages run on one thread");
+        1
+    } else {
+        workers
+    };
     let total_bytes = evidence.len();
-    let (resume_state, checkpoint_path) = match &checkpoint {
-        Some(cfg) => (cfg.resume.clone(), Some(cfg.path.clone())),
-        None => (None, None),
+    let stream_window = evidence.stream_window();
+    if stream_window.is_some() {
+        let conflicts = stream_conflicts(cfg, checkpoint.is_some());
+        if !conflicts.is_empty() {
+            return Err(anyhow::anyhow!(
+                "a streamed input is read once, front to back, but {} need random access",
+                conflicts.join(", ")
+            )
+            .context(RunStatus::ConfigError));
+        }
+    }
+    let block_size = evidence.block_size();
+    let requested_chunk_size = chunk_size;
+    let chunk_size = align_chunk_size(chunk_size, block_size);
+    if chunk_size != requested_chunk_size {
+        info!(
+            "chunk_size {requested_chunk_size} rounded up to {chunk_size} to align with evidence block size {}",
+            block_size.unwrap_or_default()
+        );
+    }
+    let block_cache = match block_size {
+        Some(block) if cfg.evidence_cache_size > 0 => {
+            if cfg.evidence_cache_size < chunk_size.saturating_add(overlap) {
+                warn!(
+                    "evidence_cache_size {} is smaller than one scan chunk; carve reads will decode blocks again",
+                    cfg.evidence_cache_size
+                );
+            }
+            Some(Arc::new(BlockCacheSource::new(
+                evidence.clone(),
+                block,
+                cfg.evidence_cache_size,
+            )))
+        }
+        _ => None,
+    };
+    let evidence: Arc<dyn EvidenceSource> = match &block_cache {
+        Some(cache) => cache.clone(),
+        None => evidence,
+    };
+    // Hashes cover the bytes as acquired, so the hasher keeps the source
+    // before any transform.
+    let acquired = evidence.clone();
+    let (transform, transform_summary) = transform_detect::resolve(
+        cfg.evidence_transform,
+        &cfg.run_id,
+        evidence.as_ref(),
+        sig_scanner.as_ref(),
+        &transform_detect::counted_patterns(cfg),
+    );
+    let evidence: Arc<dyn EvidenceSource> = match transform {
+        Some(transform) => Arc::new(TransformedSource::new(evidence, transform)),
+        None => evidence,
+    };
+    // Swapped words must not straddle two chunks.
+    let chunk_size = if transform == Some(ByteTransform::ByteSwap16) {
+        chunk_size.next_multiple_of(2)
+    } else {
+        chunk_size
+    };
+    let (resume_state, checkpoint_path, input_files) = match &checkpoint {
+        Some(cfg) => (
+            cfg.resume.clone(),
+            Some(cfg.path.clone()),
+            cfg.input_files.clone(),
+        ),
+        None => (None, None, Vec::new()),
     };
     if let Some(state) = &resume_state {
         if state.chunk_size != chunk_size {
@@ -193,28 +399,32 @@ fn run_pipeline_inner(
                 "checkpoint chunk_size {} does not match requested {}",
                 state.chunk_size,
                 chunk_size
-            ));
+            )
+            .context(RunStatus::ConfigError));
         }
         if state.overlap != overlap {
             return Err(anyhow::anyhow!(
                 "checkpoint overlap {} does not match requested {}",
                 state.overlap,
                 overlap
-            ));
+            )
+            .context(RunStatus::ConfigError));
         }
         if state.evidence_len != total_bytes {
             return Err(anyhow::anyhow!(
                 "checkpoint evidence size {} does not match evidence length {}",
                 state.evidence_len,
                 total_bytes
-            ));
+            )
+            .context(RunStatus::ConfigError));
         }
         if state.next_offset >= total_bytes {
             return Err(anyhow::anyhow!(
                 "checkpoint offset {} is beyond evidence size {}",
                 state.next_offset,
                 total_bytes
-            ));
+            )
+            .context(RunStatus::ConfigError));
         }
         if state.run_id != cfg.run_id {
             warn!(
@@ -222,6 +432,34 @@ fn run_pipeline_inner(
                 state.run_id, cfg.run_id
             );
         }
+        if state.version < 2 {
+            warn!(
+                "checkpoint version {} has no input file hashes; config and signature files are not checked",
+                state.version
+            );
+        } else {
+            inputs::check_unchanged(&state.input_files, &input_files)
+                .context("refusing to resume")
+                .context(RunStatus::ConfigError)?;
+        }
+        if state.evidence_samples.is_empty() {
+            warn!(
+                "checkpoint version {} has no evidence samples; the evidence is not checked",
+                state.version
+            );
+        } else {
+            match verify_evidence(acquired.as_ref(), &state.evidence_samples) {
+                Ok(checked) => info!("resume: {checked} sampled evidence regions unchanged"),
+                Err(err) if cfg.resume_allow_evidence_change => {
+                    warn!("{err}; resuming anyway (resume_allow_evidence_change)")
+                }
+                Err(err) => {
+                    return Err(anyhow::Error::new(err)
+                        .context("refusing to resume")
+                        .context(RunStatus::ConfigError));
+                }
+            }
+        }
     }
     let resume_offset = resume_state.as_ref().map(|s| s.next_offset).unwrap_or(0);
     let resume_chunks = if chunk_size > 0 {
@@ -229,27 +467,58 @@ fn run_pipeline_inner(
     } else {
         0
     };
-    let chunks = build_chunks(total_bytes, chunk_size, overlap);
-    info!(
-        "chunk_count={} chunk_size={} overlap={}",
-        chunks.len(),
-        chunk_size,
-        overlap
-    );
+    // A stream's chunks go on until a read comes back empty.
+    let chunks = chunk_iter(total_bytes, chunk_size, overlap);
+    let mut hasher = FusedHasher::start(&cfg.evidence_hashes, single_threaded);
+    if let Some(hasher) = hasher.as_mut() {
+        hasher.catch_up(acquired.as_ref(), resume_offset);
+    }
+    if stream_window.is_some() {
+        info!("chunk_count=unknown (streamed input) chunk_size={chunk_size} overlap={overlap}");
+    } else {
+        info!(
+            "chunk_count={} chunk_size={} overlap={}",
+            total_bytes.div_ceil(chunk_size.max(1)),
+            chunk_size,
+            overlap
+        );
+    }
 
-    // Create channels
+    // Create channels, or single-threaded the queues drained after each chunk
     let channel_cap = workers
         .saturating_mul(CHANNEL_CAPACITY_MULTIPLIER)
         .max(MIN_CHANNEL_CAPACITY);
-    let (scan_tx, scan_rx) = bounded::<ScanJob>(channel_cap);
-    let (hit_tx, hit_rx) = bounded(channel_cap * 2);
-    let (meta_tx, meta_rx) = bounded::<MetadataEvent>(channel_cap * 2);
-
-    let (string_tx, string_rx) = if string_scanner.is_some() {
-        let (tx, rx) = bounded::<StringJob>(channel_cap);
-        (Some(tx), Some(rx))
+    let want_strings = string_scanner.is_some();
+    let (scan_tx, hit_tx, string_tx, meta_tx, receivers) = if single_threaded {
+        let (hit_tx, hits) = queue::inline();
+        let (string_tx, strings) = want_strings.then(queue::inline).unzip();
+        let (meta_tx, meta) = queue::inline();
+        let receivers = StageReceivers::Inline {
+            hits,
+            strings,
+            meta,
+        };
+        (None, hit_tx, string_tx, meta_tx, receivers)
     } else {
-        (None, None)
+        let (scan_tx, scans) = bounded::<ScanJob>(channel_cap);
+        let (hit_tx, hits) = bounded(channel_cap * 2);
+        let (string_tx, strings) = want_strings
+            .then(|| bounded::<StringJob>(channel_cap))
+            .unzip();
+        let (meta_tx, meta) = bounded::<MetadataEvent>(channel_cap * 2);
+        let receivers = StageReceivers::Workers {
+            scans,
+            hits,
+            strings,
+            meta,
+        };
+        (
+            Some(scan_tx),
+            hit_tx.into(),
+            string_tx.map(StageSender::from),
+            meta_tx.into(),
+            receivers,
+        )
     };
 
     // Atomic counters for statistics
@@ -259,12 +528,97 @@ fn run_pipeline_inner(
     let files_carved = Arc::new(AtomicU64::new(0));
     let string_spans = Arc::new(AtomicU64::new(0));
     let artefacts_found = Arc::new(AtomicU64::new(0));
+    let read_errors = Arc::new(AtomicU64::new(0));
     let carve_errors = Arc::new(AtomicU64::new(0));
     let metadata_errors = Arc::new(AtomicU64::new(0));
     let sqlite_errors = Arc::new(AtomicU64::new(0));
+    let timings = Arc::new(StageTimings::new());
+    let panics = Arc::new(PanicLog::new());
+    let throttle = Arc::new(HitThrottle::from_config(cfg));
+    let pattern_stats = Arc::new(PatternStats::from_config(cfg));
+    let prefilter = HitPrefilter::from_config(cfg).map(Arc::new);
+    let carved_ranges = Arc::new(CarvedRanges::default());
+    let gaps_only = cfg.string_scan_gaps_only && string_scanner.is_some();
+    let qa_sample = Arc::new(QaSampler::from_config(cfg));
+    let arbiter = CarveArbiter::from_config(cfg).map(Arc::new);
+    let quarantine = Quarantine::from_config(cfg, run_output_dir)?.map(Arc::new);
+    // An encrypted run writes every plaintext output to a memory filesystem
+    // and seals it into the run directory; only metadata streams, which are
+    // encrypted as they are written, and quarantined files go there directly.
+    let staged = match OutputCipher::from_config(cfg)?.filter(|_| run_output_dir.is_dir()) {
+        Some(cipher) => Some(Arc::new(stage_outputs(cfg, cipher, run_output_dir)?)),
+        None => None,
+    };
+    let sealed_dir = run_output_dir;
+    let run_output_dir = staged.as_deref().map_or(run_output_dir, StagedOutput::root);
+    let temp_root = match (&cfg.temp_dir, &staged) {
+        (Some(dir), _) => dir.as_path(),
+        (None, Some(_)) => staging_parent(cfg),
+        (None, None) => run_output_dir,
+    };
+    let temp_space = Arc::new(TempSpace::new(temp_root, &cfg.run_id, cfg.temp_space_limit));
+    let postprocessors = Arc::new(PostProcessRegistry::from_config(
+        cfg,
+        sqlite_errors.clone(),
+        temp_space.clone(),
+    ));
+    let trace = OffsetTrace::from_config(cfg).map(Arc::new);
+    if let Some(trace) = &trace {
+        trace.begin(total_bytes, cfg.file_types.len());
+    }
+    let resources = Arc::new(ResourceLedger::new());
+    let profiler = RunProfiler::from_config(cfg, run_output_dir).map(Arc::new);
+    let backends = Arc::new(ScanBackends::default());
+    let hit_context =
+        HitContextExport::from_config(cfg, evidence.clone(), run_output_dir).map(Arc::new);
+    let in_flight = stream_window
+        .filter(|_| !single_threaded)
+        .map(|_| Arc::new(InFlight::default()));
+    let monitors = WorkerMonitors {
+        timings: timings.clone(),
+        panics: panics.clone(),
+        trace: trace.clone(),
+        resources: resources.clone(),
+        backends: backends.clone(),
+        hit_context: hit_context.clone(),
+        profiler: profiler.clone(),
+        in_flight: in_flight.clone(),
+    };
+
+    // File system allocation maps are read before the scan so chunks can be
+    // skipped.
+    let (ntfs_files, ntfs_allocated) = if cfg.enable_ntfs_scan {
+        let (files, allocated) = ntfs::discover(&cfg.run_id, evidence.as_ref());
+        (files, cfg.ntfs_unallocated_only.then_some(allocated))
+    } else {
+        Default::default()
+    };
+    let (fs_volumes, allocated) = if cfg.unallocated_only {
+        let (volumes, allocated) = unallocated::discover(evidence.as_ref());
+        (Some(volumes), Some(allocated))
+    } else {
+        (None, ntfs_allocated)
+    };
+    let locator = cfg
+        .enable_volume_locations
+        .then(|| VolumeLocator::discover(&cfg.run_id, evidence.clone()))
+        .flatten();
+    let mut allocated_chunks_skipped = 0u64;
+    let mut allocated_bytes_skipped = 0u64;
 
-    // Start metadata recording thread
-    let meta_handle = workers::spawn_metadata_thread(meta_sink, meta_rx, metadata_errors.clone());
+    let recorder = MetadataRecorder::new(
+        meta_sink,
+        metadata_errors.clone(),
+        MetadataAggregates {
+            photos: PhotoDevices::from_config(cfg),
+            ntfs: ntfs_files,
+            clocks: FileClocks::from_config(cfg),
+            ..Default::default()
+        },
+        locator,
+        monitors.clone(),
+    )
+    .with_snapshots(MetadataSnapshots::from_config(cfg, sealed_dir));
 
     // Build entropy config if enabled
     let entropy_cfg = if cfg.enable_entropy_detection && cfg.entropy_window_size > 0 {
@@ -276,54 +630,142 @@ fn run_pipeline_inner(
         None
     };
 
-    // Spawn worker threads
-    let scan_handles = workers::spawn_scan_workers(
-        workers,
-        sig_scanner,
-        string_scanner.clone(),
-        scan_rx,
-        hit_tx.clone(),
-        string_tx.clone(),
-        meta_tx.clone(),
-        cfg.run_id.clone(),
+    let lznt1 = cfg.enable_lznt1_scan.then(|| {
+        Arc::new(Lznt1Scan::new(
+            cfg,
+            sig_scanner.clone(),
+            carve_registry.clone(),
+            evidence.clone(),
+            run_output_dir,
+            meta_tx.clone(),
+            files_carved.clone(),
+        ))
+    });
+
+    let shadow = cfg.enable_vss_scan.then(|| {
+        ShadowScan::new(
+            cfg,
+            sig_scanner.clone(),
+            carve_registry.clone(),
+            evidence.clone(),
+            run_output_dir,
+            meta_tx.clone(),
+            files_carved.clone(),
+        )
+    });
+
+    let scan_worker = ScanWorker {
+        scanner: sig_scanner.clone(),
+        string_scanner: string_scanner.clone(),
+        hit_tx: hit_tx.clone(),
+        string_tx: string_tx.clone(),
+        meta_tx: meta_tx.clone(),
+        run_id: cfg.run_id.clone(),
         entropy_cfg,
-        hits_found.clone(),
-        string_spans.clone(),
-    );
+        hits_found: hits_found.clone(),
+        string_spans: string_spans.clone(),
+        lznt1: lznt1.clone(),
+        prefilter: prefilter.clone(),
+        monitors: monitors.clone(),
+    };
 
-    let carve_handles = workers::spawn_carve_workers(
-        workers,
-        carve_registry,
-        evidence.clone(),
-        cfg.run_id.clone(),
-        run_output_dir.to_path_buf(),
-        hit_rx,
-        meta_tx.clone(),
-        files_carved.clone(),
-        cfg.enable_sqlite_page_recovery,
+    let output_sync = Arc::new(OutputSync::from_config(cfg));
+    let carve_limits = CarveLimits::new(
         cfg.max_files,
-        carve_errors.clone(),
-        sqlite_errors.clone(),
+        (cfg.carve_timeout_secs > 0).then(|| Duration::from_secs(cfg.carve_timeout_secs)),
+        cancel_flag.clone(),
+    );
+    let carve_stage = CarveStage {
+        registry: carve_registry,
+        evidence: evidence.clone(),
+        run_id: cfg.run_id.clone(),
+        run_output_dir: run_output_dir.to_path_buf(),
+        meta_tx: meta_tx.clone(),
+        files_carved: files_carved.clone(),
+        limits: carve_limits.clone(),
+        carve_errors: carve_errors.clone(),
+        postprocessors: postprocessors.clone(),
+        throttle: throttle.clone(),
+        patterns: pattern_stats.clone(),
+    };
+    let carve_worker = CarveWorker::new(
+        carve_stage,
+        CarveRecorders {
+            carved_ranges: carved_ranges.clone(),
+            qa_sample: qa_sample.clone(),
+            quarantine: quarantine.clone(),
+            staged: staged.clone(),
+            arbiter: arbiter.clone(),
+            output_sync: output_sync.clone(),
+        },
+        monitors.clone(),
     );
 
-    let string_handles = if let Some(rx) = string_rx {
-        let scan_cfg = ArtefactScanConfig {
-            urls: cfg.enable_url_scan,
-            emails: cfg.enable_email_scan,
-            phones: cfg.enable_phone_scan,
-        };
-        workers::spawn_string_workers(
-            workers,
-            cfg.run_id.clone(),
-            rx,
-            meta_tx.clone(),
-            artefacts_found.clone(),
-            scan_cfg,
-        )
-    } else {
-        Vec::new()
+    let scan_cfg = ArtefactScanConfig {
+        urls: cfg.enable_url_scan,
+        emails: cfg.enable_email_scan,
+        phones: cfg.enable_phone_scan,
+        logs: cfg.enable_log_scan,
+        system: cfg.enable_system_scan,
+        credentials: cfg.enable_credential_scan,
+        p2p: cfg.enable_p2p_scan,
+    };
+    let string_worker = StringWorker {
+        run_id: cfg.run_id.clone(),
+        meta_tx: meta_tx.clone(),
+        artefacts_found: artefacts_found.clone(),
+        scan_cfg,
+        monitors: monitors.clone(),
     };
 
+    // Single-threaded, the stages run from the chunk loop below; otherwise
+    // each gets its worker threads.
+    let mut meta_handle = None;
+    let mut scan_handles = Vec::new();
+    let mut carve_handles = Vec::new();
+    let mut string_handles = Vec::new();
+    // In gaps-only mode, spans are held back until carving has finished.
+    let mut gap_collector = None;
+    let mut inline = None;
+    match receivers {
+        StageReceivers::Inline {
+            hits,
+            strings,
+            meta,
+        } => {
+            carve_worker.prepare_thread();
+            inline = Some(InlineStages {
+                scan: scan_worker,
+                carve: carve_worker,
+                strings: string_worker.clone(),
+                hits,
+                string_jobs: strings,
+                meta,
+                recorder,
+                deferred: gaps_only.then(DeferredSpans::default),
+                panics: panics.clone(),
+            });
+        }
+        StageReceivers::Workers {
+            scans,
+            hits,
+            strings,
+            meta,
+        } => {
+            meta_handle = Some(workers::spawn_metadata_thread(recorder, meta));
+            scan_handles = workers::spawn_scan_workers(workers, scan_worker, scans);
+            carve_handles = workers::spawn_carve_workers(workers, carve_worker, hits);
+            match strings {
+                Some(rx) if gaps_only => gap_collector = Some(gaps::spawn_collector(rx)),
+                Some(rx) => {
+                    string_handles =
+                        workers::spawn_string_workers(workers, string_worker.clone(), rx)
+                }
+                None => {}
+            }
+        }
+    }
+
     // Process chunks
     let max_bytes = max_bytes.unwrap_or(u64::MAX);
     let max_chunks = max_chunks.unwrap_or(u64::MAX);
@@ -333,14 +775,36 @@ fn run_pipeline_inner(
     let mut hit_max_files = false;
     let mut cancelled = false;
     let start_time = Instant::now();
+    let counters = ProgressCounters {
+        start_time: &start_time,
+        bytes_scanned: &bytes_scanned,
+        chunks_processed: &chunks_processed,
+        hits_found: &hits_found,
+        files_carved: &files_carved,
+        string_spans: &string_spans,
+        artefacts_found: &artefacts_found,
+        read_errors: &read_errors,
+        carve_errors: &carve_errors,
+        metadata_errors: &metadata_errors,
+        sqlite_errors: &sqlite_errors,
+        timings: &timings,
+        panics: &panics,
+        workers,
+    };
     let mut last_progress = Instant::now();
     let mut next_offset = resume_offset;
+    let mut read_end = resume_offset;
+    let mut window_start = resume_offset;
+    let read_usage = ThreadUsage::current_thread();
 
     for chunk in chunks {
         if hit_max_bytes || hit_max_chunks {
             break;
         }
         if chunk.start < resume_offset {
+            if let Some(trace) = &trace {
+                trace.chunk(&chunk, Err("before the resume offset"));
+            }
             continue;
         }
         if let Some(limit) = cfg.max_files {
@@ -367,38 +831,118 @@ fn run_pipeline_inner(
             hit_max_bytes = true;
             break;
         }
+        let chunk_end = chunk.start.saturating_add(chunk.valid_length);
+        if let Some(allocated) = &allocated
+            && allocated.covers(chunk.start, chunk_end)
+        {
+            if let Some(trace) = &trace {
+                trace.chunk(&chunk, Err("inside allocated file system clusters"));
+            }
+            // Evidence hashes still need these bytes.
+            if let Some(hasher) = hasher.as_mut() {
+                hasher.catch_up(acquired.as_ref(), chunk_end);
+            }
+            allocated_chunks_skipped += 1;
+            allocated_bytes_skipped += chunk.valid_length;
+            chunks_seen += 1;
+            next_offset = chunk.start.saturating_add(chunk_size);
+            continue;
+        }
+        if let Some(window) = stream_window
+            && chunk.start.saturating_add(chunk.length) > window_start.saturating_add(window)
+        {
+            // The chunk does not fit the stream window: once the chunks read
+            // so far are scanned and their hits carved, drop them.
+            if let Some(in_flight) = &in_flight {
+                in_flight.wait_idle(&|| {
+                    cancel_flag
+                        .as_ref()
+                        .is_some_and(|flag| flag.load(Ordering::Relaxed))
+                        || cfg
+                            .max_files
+                            .is_some_and(|limit| files_carved.load(Ordering::Relaxed) >= limit)
+                });
+            }
+            evidence.release_before(chunk.start);
+            window_start = chunk.start;
+        }
         let remaining = (max_bytes - scanned_total).min(chunk.length) as usize;
-        let data = read_chunk_limited(evidence.as_ref(), &chunk, remaining)?;
+        let read_started = Instant::now();
+        let read = read_chunk_limited(evidence.as_ref(), &chunk, remaining);
+        timings.record_read(read_started.elapsed());
+        let data = match read {
+            Ok(data) => data,
+            Err(err) => {
+                // An unreadable region must not abort the whole run; skip it and
+                // keep going so the rest of the evidence is still processed.
+                read_errors.fetch_add(1, Ordering::Relaxed);
+                if let Some(trace) = &trace {
+                    trace.chunk(&chunk, Err("evidence read error"));
+                }
+                warn!(
+                    "read error in chunk {} at offset {}: {err}; skipping chunk",
+                    chunk.id, chunk.start
+                );
+                if let Some(hasher) = hasher.as_mut() {
+                    hasher.abandon(format!("chunk {} could not be read", chunk.id));
+                }
+                chunks_seen += 1;
+                next_offset = chunk.start.saturating_add(chunk_size);
+                continue;
+            }
+        };
         if data.is_empty() {
             break;
         }
+        // The source can load the next chunk while this one is dispatched.
+        evidence.prefetch(
+            chunk.start.saturating_add(chunk_size),
+            chunk_size.saturating_add(overlap),
+        );
+        read_end = chunk.start.saturating_add(data.len() as u64);
+        if let Some(trace) = &trace {
+            trace.chunk(&chunk, Ok(()));
+        }
         bytes_scanned.fetch_add(data.len() as u64, Ordering::Relaxed);
         chunks_processed.fetch_add(1, Ordering::Relaxed);
         chunks_seen += 1;
         next_offset = chunk.start.saturating_add(chunk_size);
         let chunk_id = chunk.id;
-        scan_tx
-            .send(ScanJob {
-                chunk,
-                data: Arc::new(data),
-            })
-            .with_context(|| format!("scan channel closed while sending chunk {chunk_id}"))?;
+        let data = Arc::new(data);
+        if let Some(hasher) = hasher.as_mut() {
+            match transform {
+                // Both transforms are their own inverse.
+                Some(transform) => {
+                    let mut acquired = data.to_vec();
+                    transform.apply(&mut acquired);
+                    hasher.feed(chunk.start, &Arc::new(acquired));
+                }
+                None => hasher.feed(chunk.start, &data),
+            }
+        }
+        let chunk_start = chunk.start;
+        if let Some(stages) = &mut inline {
+            stages.scan(ScanJob { chunk, data });
+        } else if let Some(scan_tx) = &scan_tx {
+            if let Some(in_flight) = &in_flight {
+                in_flight.add(1);
+            }
+            let send_started = Instant::now();
+            scan_tx
+                .send(ScanJob { chunk, data })
+                .with_context(|| format!("scan channel closed while sending chunk {chunk_id}"))?;
+            if let Some(profiler) = &profiler {
+                profiler.queue_wait(
+                    Queue::Scan,
+                    ProfileEvent::SendWait,
+                    chunk_start,
+                    send_started.elapsed(),
+                );
+            }
+        }
         if let Some(progress) = &progress {
             if progress.interval.is_zero() || last_progress.elapsed() >= progress.interval {
-                let snapshot = build_progress_snapshot(
-                    total_bytes,
-                    resume_offset,
-                    &start_time,
-                    &bytes_scanned,
-                    &chunks_processed,
-                    &hits_found,
-                    &files_carved,
-                    &string_spans,
-                    &artefacts_found,
-                    &carve_errors,
-                    &metadata_errors,
-                    &sqlite_errors,
-                );
+                let snapshot = counters.snapshot(total_bytes, resume_offset);
                 progress.reporter.on_progress(&snapshot);
                 last_progress = Instant::now();
 
@@ -415,20 +959,118 @@ fn run_pipeline_inner(
         }
     }
 
+    resources.add(
+        Stage::Read,
+        read_usage
+            .zip(ThreadUsage::current_thread())
+            .map(|(before, after)| after.since(before)),
+    );
+
     // Close channels and wait for workers
     drop(scan_tx);
     drop(hit_tx);
     drop(string_tx);
 
+    // A streamed input's length is known once it has been read to the end.
+    let stream_unread = evidence.len() == UNKNOWN_LEN;
+    let total_bytes = if stream_unread {
+        read_end
+    } else {
+        evidence.len()
+    };
+    // The tail a limit left unscanned is hashed while the workers drain.
+    let evidence_hashes = hasher.and_then(|mut hasher| {
+        if cancelled {
+            hasher.abandon("the run was cancelled".to_string());
+        } else if stream_unread {
+            hasher.abandon("the input stream was not read to its end".to_string());
+        } else {
+            hasher.catch_up(acquired.as_ref(), total_bytes);
+        }
+        hasher.finish(total_bytes)
+    });
+    if let Some(hashes) = &evidence_hashes {
+        info!(
+            "evidence_hashes md5={} sha1={} sha256={}",
+            hashes.md5.as_deref().unwrap_or("-"),
+            hashes.sha1.as_deref().unwrap_or("-"),
+            hashes.sha256.as_deref().unwrap_or("-")
+        );
+    }
+
     for handle in scan_handles {
         let _ = handle.join();
     }
+    // Releases its metadata sender before the metadata thread is joined.
+    let lznt1_stats = lznt1.map(|scan| scan.stats());
     for handle in carve_handles {
         let _ = handle.join();
     }
+    output_sync.finish();
+    // The flag may be set after the last chunk was read but before every
+    // hit was carved.
+    cancelled |= carve_limits.first_abandoned().is_some();
+    // Shadow copies are scanned once the evidence itself has been carved.
+    let shadow_summary = shadow.and_then(|scan| {
+        (!cancelled && !hit_max_files).then(|| {
+            let threads = if single_threaded { 0 } else { workers };
+            scan.run(threads, chunk_size, overlap, cancel_flag.as_deref())
+        })
+    });
     for handle in string_handles {
         let _ = handle.join();
     }
+    let deferred = match (gap_collector, &mut inline) {
+        (Some(collector), _) => Some(collector.join().unwrap_or_default()),
+        (None, Some(stages)) if gaps_only => Some(stages.take_deferred()),
+        _ => None,
+    };
+    if let Some(deferred) = deferred {
+        let carved = carved_ranges.snapshot();
+        let (jobs, gap_stats) = gaps::gap_jobs(deferred, &carved);
+        let (tx, handles) = match inline {
+            Some(_) => (None, Vec::new()),
+            None => {
+                let (tx, rx) = bounded::<StringJob>(channel_cap);
+                let handles = workers::spawn_string_workers(workers, string_worker.clone(), rx);
+                (Some(tx), handles)
+            }
+        };
+        for job in jobs {
+            let len = job.chunk.length as usize;
+            match read_chunk_limited(evidence.as_ref(), &job.chunk, len) {
+                Ok(data) => {
+                    let job = job.into_string_job(data);
+                    if let Some(stages) = &mut inline {
+                        stages.extract(job);
+                    } else if let Some(tx) = &tx
+                        && tx.send(job).is_err()
+                    {
+                        warn!("string channel closed while sending gap spans");
+                        break;
+                    }
+                }
+                Err(err) => {
+                    read_errors.fetch_add(1, Ordering::Relaxed);
+                    warn!(
+                        "read error re-reading string spans at offset {}: {err}",
+                        job.chunk.start
+                    );
+                }
+            }
+        }
+        drop(tx);
+        for handle in handles {
+            let _ = handle.join();
+        }
+        info!(
+            "string_gaps carved_ranges={} spans_kept={} spans_skipped={} bytes_skipped={}",
+            carved.len(),
+            gap_stats.spans_kept,
+            gap_stats.spans_skipped,
+            gap_stats.bytes_skipped
+        );
+    }
 
     // Send run summary
     let bytes_scanned_total = bytes_scanned
@@ -450,24 +1092,267 @@ fn run_pipeline_inner(
         warn!("metadata channel closed while sending run summary: {err}");
     }
 
+    drop(string_worker);
     drop(meta_tx);
-    let _ = meta_handle.join();
+    let aggregates = match (inline, meta_handle) {
+        (Some(stages), _) => stages.finish(),
+        (None, Some(handle)) => handle.join().unwrap_or_default(),
+        (None, None) => MetadataAggregates::default(),
+    };
 
-    if let Some(progress) = &progress {
-        let snapshot = build_progress_snapshot(
-            total_bytes,
-            resume_offset,
-            &start_time,
-            &bytes_scanned,
-            &chunks_processed,
-            &hits_found,
-            &files_carved,
-            &string_spans,
-            &artefacts_found,
-            &carve_errors,
-            &metadata_errors,
-            &sqlite_errors,
+    // Dry runs never create the run directory, so summaries are skipped there.
+    if string_scanner.is_some() && run_output_dir.is_dir() {
+        let summary = aggregates
+            .frequencies
+            .summarize(&cfg.run_id, cfg.artefact_summary_top_n);
+        match frequency::write_summary(run_output_dir, &summary) {
+            Ok(path) => info!("artefact frequency summary written to {}", path.display()),
+            Err(err) => warn!("failed to write artefact frequency summary: {err}"),
+        }
+    }
+
+    if !aggregates.photos.is_empty() && run_output_dir.is_dir() {
+        let summary = aggregates.photos.summarize(&cfg.run_id);
+        info!(
+            "photo_devices photos={} devices={} unattributed={}",
+            summary.photos,
+            summary.devices.len(),
+            summary.unattributed
+        );
+        match photo_devices::write_summary(run_output_dir, &summary) {
+            Ok(path) => info!("photo device summary written to {}", path.display()),
+            Err(err) => warn!("failed to write photo device summary: {err}"),
+        }
+    }
+
+    if !aggregates.clocks.is_empty() && run_output_dir.is_dir() {
+        let summary = aggregates.clocks.summarize(&cfg.run_id);
+        info!(
+            "file_clocks dated={} cross_dated={} in_conflict={}",
+            summary.files_dated, summary.files_cross_dated, summary.files_in_conflict
         );
+        match file_clocks::write_summary(run_output_dir, &summary) {
+            Ok(path) => info!("file clock summary written to {}", path.display()),
+            Err(err) => warn!("failed to write file clock summary: {err}"),
+        }
+    }
+
+    if cfg.enable_ntfs_scan && run_output_dir.is_dir() {
+        let summary = aggregates.ntfs.summarize(
+            &cfg.run_id,
+            allocated_chunks_skipped,
+            allocated_bytes_skipped,
+        );
+        info!(
+            "ntfs volumes={} files_attributed={} chunks_skipped={}",
+            summary.volumes.len(),
+            summary.files_attributed,
+            summary.chunks_skipped
+        );
+        match ntfs_report::write_summary(run_output_dir, &summary) {
+            Ok(path) => info!("NTFS summary written to {}", path.display()),
+            Err(err) => warn!("failed to write NTFS summary: {err}"),
+        }
+    }
+
+    if let Some(summary) = &transform_summary
+        && run_output_dir.is_dir()
+    {
+        match transform_detect::write_summary(run_output_dir, summary) {
+            Ok(path) => info!("evidence transform summary written to {}", path.display()),
+            Err(err) => warn!("failed to write evidence transform summary: {err}"),
+        }
+    }
+
+    if let Some(volumes) = fs_volumes
+        && run_output_dir.is_dir()
+    {
+        let summary = unallocated::UnallocatedSummary {
+            run_id: cfg.run_id.clone(),
+            volumes,
+            allocated_bytes: allocated.as_ref().map_or(0, |map| map.allocated_bytes()),
+            chunks_skipped: allocated_chunks_skipped,
+            bytes_skipped: allocated_bytes_skipped,
+        };
+        info!(
+            "unallocated_only volumes={} chunks_skipped={} bytes_skipped={}",
+            summary.volumes.len(),
+            summary.chunks_skipped,
+            summary.bytes_skipped
+        );
+        match unallocated::write_summary(run_output_dir, &summary) {
+            Ok(path) => info!("unallocated scan summary written to {}", path.display()),
+            Err(err) => warn!("failed to write unallocated scan summary: {err}"),
+        }
+    }
+
+    if let Some(summary) = &shadow_summary {
+        let snapshots: usize = summary.volumes.iter().map(|v| v.snapshots.len()).sum();
+        info!(
+            "shadow_copies volumes={} snapshots={snapshots}",
+            summary.volumes.len()
+        );
+        if run_output_dir.is_dir() {
+            match shadow::write_summary(run_output_dir, summary) {
+                Ok(path) => info!("shadow copy summary written to {}", path.display()),
+                Err(err) => warn!("failed to write shadow copy summary: {err}"),
+            }
+        }
+    }
+
+    let usage = resources.finished();
+    if run_output_dir.is_dir() && !usage.is_empty() {
+        let summary = ResourceSummary {
+            run_id: &cfg.run_id,
+            stages: &usage,
+        };
+        match resources::write_summary(run_output_dir, &summary) {
+            Ok(path) => info!("resource usage summary written to {}", path.display()),
+            Err(err) => warn!("failed to write resource usage summary: {err}"),
+        }
+    }
+    if let Some(process) = usage.iter().find(|row| row.stage == "process") {
+        info!(
+            "resource_usage wall_secs={:.1} user_cpu_secs={:?} sys_cpu_secs={:?} bytes_read={:?} bytes_written={:?} gpu_secs={:?} peak_rss_bytes={:?}",
+            process.busy_secs,
+            process.user_cpu_secs,
+            process.sys_cpu_secs,
+            process.bytes_read,
+            process.bytes_written,
+            process.gpu_secs,
+            process.peak_rss_bytes
+        );
+    }
+
+    if let Some(profiler) = &profiler {
+        let summary = profiler.finish().and_then(|summary| {
+            let path = profile::write_summary(run_output_dir, &summary)?;
+            Ok((path, summary))
+        });
+        match summary {
+            Ok((path, summary)) => {
+                info!(
+                    "profile rows={} written to {}, summary to {}",
+                    summary.rows,
+                    run_output_dir.join(profile::PROFILE_FILE).display(),
+                    path.display()
+                );
+                if let Some(slowest) = summary.types.first() {
+                    info!(
+                        "profile slowest_type={} carve_secs={:.1} attempts={}",
+                        slowest.file_type, slowest.total_secs, slowest.attempts
+                    );
+                }
+            }
+            Err(err) => warn!("failed to write profile: {err}"),
+        }
+    }
+
+    if throttle.is_enabled() && run_output_dir.is_dir() {
+        let summary = throttle.summarize(&cfg.run_id);
+        match throttle::write_summary(run_output_dir, &summary) {
+            Ok(path) => info!("hit suppression summary written to {}", path.display()),
+            Err(err) => warn!("failed to write hit suppression summary: {err}"),
+        }
+    }
+
+    if let Some(prefilter) = &prefilter
+        && run_output_dir.is_dir()
+    {
+        let summary = prefilter.summarize(&cfg.run_id);
+        match prefilter::write_summary(run_output_dir, &summary) {
+            Ok(path) => info!("hit prefilter summary written to {}", path.display()),
+            Err(err) => warn!("failed to write hit prefilter summary: {err}"),
+        }
+    }
+
+    if let Some(arbiter) = &arbiter
+        && run_output_dir.is_dir()
+    {
+        let summary = arbiter.summarize(&cfg.run_id);
+        match arbitration::write_summary(run_output_dir, &summary) {
+            Ok(path) => info!("carve arbitration summary written to {}", path.display()),
+            Err(err) => warn!("failed to write carve arbitration summary: {err}"),
+        }
+    }
+
+    if run_output_dir.is_dir() {
+        let carved = carved_ranges.snapshot();
+        let scan_end = bytes_scanned_total.min(total_bytes);
+        let summary = coverage::summarize(&cfg.run_id, cfg, &carved, resume_offset, scan_end);
+        let slack_percent = summary.slack_percent;
+        match coverage::write_summary(run_output_dir, cfg, &carved, summary) {
+            Ok(path) => info!(
+                "coverage summary written to {} (slack {slack_percent}%)",
+                path.display()
+            ),
+            Err(err) => warn!("failed to write coverage summary: {err}"),
+        }
+    }
+
+    if run_output_dir.is_dir() {
+        let summary = pattern_stats.summarize(&cfg.run_id);
+        match patterns::write_summary(run_output_dir, &summary) {
+            Ok(path) => info!("pattern statistics written to {}", path.display()),
+            Err(err) => warn!("failed to write pattern statistics: {err}"),
+        }
+    }
+
+    if qa_sample.is_enabled() && run_output_dir.is_dir() {
+        let summary = qa_sample.summarize(&cfg.run_id);
+        match qa_sample::write_summary(run_output_dir, &summary) {
+            Ok(path) => info!(
+                "QA sample of {} files listed in {}",
+                summary.files.len(),
+                path.display()
+            ),
+            Err(err) => warn!("failed to write QA sample list: {err}"),
+        }
+    }
+
+    if let Some(quarantine) = &quarantine {
+        match quarantine.finish() {
+            Ok((0, _)) => {}
+            Ok((entries, failed)) => info!(
+                "quarantined {entries} carves ({failed} could not be stored) in {}",
+                quarantine.root().display()
+            ),
+            Err(err) => warn!("failed to finish quarantine index: {err}"),
+        }
+    }
+
+    if let Some(export) = &hit_context {
+        match export.finish() {
+            Ok(rows) => info!(
+                "hit context export wrote {rows} windows to {}",
+                sealed_dir.join(hit_context::HIT_CONTEXT_DIR).display()
+            ),
+            Err(err) => warn!("failed to finish hit context export: {err}"),
+        }
+    }
+
+    if let Some(staged) = &staged {
+        let sealed = staged.seal_remaining();
+        info!(
+            "encrypted {sealed} output files into {}",
+            sealed_dir.display()
+        );
+        let failed = staged.failed();
+        if failed > 0 {
+            return Err(anyhow::anyhow!(
+                "{failed} output files could not be encrypted and were discarded"
+            )
+            .context(RunStatus::SinkFailure));
+        }
+    }
+
+    if let Some(cache) = &block_cache {
+        let (hits, misses) = cache.stats();
+        info!("evidence block cache hits={hits} misses={misses}");
+    }
+
+    if let Some(progress) = &progress {
+        let snapshot = counters.snapshot(total_bytes, resume_offset);
         progress.reporter.on_progress(&snapshot);
     }
 
@@ -484,104 +1369,338 @@ fn run_pipeline_inner(
         info!("max_chunks limit reached; stopping early");
     }
 
-    let stats = PipelineStats {
+    let mut stats = PipelineStats {
         bytes_scanned: bytes_scanned_total,
         chunks_processed: chunks_processed_total,
         hits_found: hits_found.load(Ordering::Relaxed),
         files_carved: files_carved.load(Ordering::Relaxed),
         string_spans: string_spans.load(Ordering::Relaxed),
         artefacts_extracted: artefacts_found.load(Ordering::Relaxed),
+        read_errors: read_errors.load(Ordering::Relaxed),
+        carve_errors: carve_errors.load(Ordering::Relaxed),
+        metadata_errors: metadata_errors.load(Ordering::Relaxed),
+        worker_panics: panics.count(),
+        hits_suppressed: throttle.suppressed(),
+        hits_prefiltered: prefilter.as_ref().map_or(0, |p| p.rejected()),
+        carves_suppressed: arbiter.as_ref().map_or(0, |a| a.suppressed()),
+        patterns_disabled: pattern_stats.disabled(),
+        hits_pattern_disabled: pattern_stats.skipped(),
+        lznt1_files: lznt1_stats.map_or(0, |s| s.files_carved),
+        shadow_files: shadow_summary.as_ref().map_or(0, |s| {
+            s.volumes
+                .iter()
+                .flat_map(|v| &v.snapshots)
+                .map(|snapshot| snapshot.files_carved)
+                .sum()
+        }),
+        postprocess_errors: postprocessors.errors(),
+        temp_space: temp_space.stats(),
+        signature_backends: backends.signature.snapshot(),
+        string_backends: backends.strings.snapshot(),
+        stop_reason: if cancelled {
+            StopReason::Cancelled
+        } else if hit_max_files {
+            StopReason::MaxFiles
+        } else if hit_max_bytes {
+            StopReason::MaxBytes
+        } else if hit_max_chunks {
+            StopReason::MaxChunks
+        } else {
+            StopReason::Completed
+        },
+        hits_abandoned: carve_limits.abandoned(),
+        checkpoint: None,
+        evidence_hashes,
+        gpu_transfer: sig_scanner.gpu_transfer(),
     };
 
     info!(
-        "run_summary bytes_scanned={} chunks_processed={} hits_found={} files_carved={} string_spans={} artefacts_extracted={}",
+        "run_summary bytes_scanned={} chunks_processed={} hits_found={} hits_suppressed={} hits_prefiltered={} files_carved={} string_spans={} artefacts_extracted={}",
         stats.bytes_scanned,
         stats.chunks_processed,
         stats.hits_found,
+        stats.hits_suppressed,
+        stats.hits_prefiltered,
         stats.files_carved,
         stats.string_spans,
         stats.artefacts_extracted
     );
-
-    if cancelled || hit_max_bytes || hit_max_chunks || hit_max_files {
-        if let Some(path) = checkpoint_path {
-            let state = CheckpointState::new(
-                &cfg.run_id,
-                chunk_size,
-                overlap,
-                next_offset.min(total_bytes),
-                total_bytes,
+    if stats.read_errors > 0
+        || stats.carve_errors > 0
+        || stats.metadata_errors > 0
+        || stats.postprocess_errors > 0
+    {
+        warn!(
+            "run completed with errors read_errors={} carve_errors={} metadata_errors={} postprocess_errors={}",
+            stats.read_errors, stats.carve_errors, stats.metadata_errors, stats.postprocess_errors
+        );
+    }
+    if let Some(lznt1) = lznt1_stats {
+        info!(
+            "lznt1 chains={} units={} bytes_decompressed={} files_carved={}",
+            lznt1.chains, lznt1.units, lznt1.bytes_decompressed, lznt1.files_carved
+        );
+    }
+    if stats.temp_space.reservations > 0 || stats.temp_space.denied > 0 {
+        info!(
+            "temp_space peak_bytes={} reservations={} denied={} limit_bytes={}",
+            stats.temp_space.peak_bytes,
+            stats.temp_space.reservations,
+            stats.temp_space.denied,
+            stats.temp_space.limit_bytes
+        );
+    }
+    if stats.temp_space.denied > 0 {
+        warn!(
+            "{} file(s) did not fit in temp_space_limit and were processed without scratch space",
+            stats.temp_space.denied
+        );
+    }
+    if !stats.patterns_disabled.is_empty() {
+        warn!(
+            "{} pattern(s) auto-disabled for carving too rarely ({}); {} hit(s) skipped, see summaries/pattern_stats.json",
+            stats.patterns_disabled.len(),
+            stats.patterns_disabled.join(", "),
+            stats.hits_pattern_disabled
+        );
+    }
+    for (scanner, counts) in [
+        ("signature", &stats.signature_backends),
+        ("string", &stats.string_backends),
+    ] {
+        if counts.gpu_chunks > 0
+            || counts.small_chunk_fallbacks > 0
+            || counts.gpu_busy_fallbacks > 0
+        {
+            info!(
+                "scan_backends scanner={scanner} gpu_chunks={} cpu_chunks={} small_chunk_fallbacks={} gpu_busy_fallbacks={}",
+                counts.gpu_chunks,
+                counts.cpu_chunks,
+                counts.small_chunk_fallbacks,
+                counts.gpu_busy_fallbacks
             );
-            if let Err(err) = save_checkpoint(&path, &state) {
-                warn!("failed to write checkpoint {}: {err}", path.display());
-            } else {
-                info!("checkpoint saved to {}", path.display());
-            }
+        }
+    }
+    if let Some(gpu) = &stats.gpu_transfer {
+        info!(
+            "gpu_transfer chunks={} upload_secs={:.3} kernel_secs={:.3} download_secs={:.3} overlapped_chunks={} part_bytes={} split_chunks={} parts={}",
+            gpu.chunks,
+            gpu.upload_secs,
+            gpu.kernel_secs,
+            gpu.download_secs,
+            gpu.overlapped_chunks,
+            gpu.part_bytes,
+            gpu.split_chunks,
+            gpu.parts
+        );
+    }
+    if let Some(summary) = panics.summary() {
+        warn!("{summary}");
+    }
+    if let Some(trace) = &trace {
+        trace.finish();
+    }
+
+    if stats.stop_reason != StopReason::Completed
+        && let Some(path) = checkpoint_path
+    {
+        // Chunks whose hits were not all carved are scanned again on resume.
+        let resume_at = match carve_limits.first_abandoned() {
+            Some(offset) => next_offset.min(offset - offset % chunk_size.max(1)),
+            None => next_offset,
+        };
+        let resume_at = resume_at.min(total_bytes);
+        let samples = sample_evidence(
+            acquired.as_ref(),
+            resume_at,
+            cfg.resume_verify_samples,
+            cfg.resume_verify_sample_size,
+        );
+        let state = CheckpointState::new(
+            &cfg.run_id,
+            chunk_size,
+            overlap,
+            resume_at,
+            total_bytes,
+            input_files,
+        )
+        .with_evidence_samples(samples);
+        if let Err(err) = save_checkpoint(&path, &state) {
+            warn!("failed to write checkpoint {}: {err}", path.display());
+        } else {
+            info!("checkpoint saved to {}", path.display());
+            let note = notify::Notification::checkpoint(&cfg.run_id, sealed_dir, &stats, &path);
+            notify::send(&cfg.notifications, &note);
+            stats.checkpoint = Some(path);
         }
     }
 
     Ok(stats)
 }
 
-fn build_progress_snapshot(
-    total_bytes: u64,
-    baseline_bytes: u64,
-    start_time: &Instant,
-    bytes_scanned: &AtomicU64,
-    chunks_processed: &AtomicU64,
-    hits_found: &AtomicU64,
-    files_carved: &AtomicU64,
-    string_spans: &AtomicU64,
-    artefacts_found: &AtomicU64,
-    carve_errors: &AtomicU64,
-    metadata_errors: &AtomicU64,
-    sqlite_errors: &AtomicU64,
-) -> ProgressSnapshot {
-    let elapsed_seconds = start_time.elapsed().as_secs_f64();
-    let scanned = bytes_scanned.load(Ordering::Relaxed);
-    let scanned_total = scanned.saturating_add(baseline_bytes);
-    let throughput_mib = if elapsed_seconds > 0.0 {
-        scanned as f64 / crate::constants::MIB as f64 / elapsed_seconds
-    } else {
-        0.0
-    };
-    let bytes_per_sec = if elapsed_seconds > 0.0 {
-        scanned as f64 / elapsed_seconds
-    } else {
-        0.0
-    };
-    let eta_seconds = if bytes_per_sec > 0.0 && scanned_total < total_bytes {
-        Some(((total_bytes - scanned_total) as f64 / bytes_per_sec).round() as u64)
-    } else {
-        None
-    };
+/// `encryption_staging_dir`, or `/dev/shm`.
+fn staging_parent(cfg: &Config) -> &Path {
+    cfg.encryption_staging_dir
+        .as_deref()
+        .unwrap_or(Path::new(DEFAULT_STAGING_DIR))
+}
 
-    let completion_pct = if total_bytes > 0 {
-        (scanned_total as f64 / total_bytes as f64) * 100.0
-    } else {
-        0.0
-    };
+/// Staging for the plaintext outputs of an encrypted run. A `temp_dir` has
+/// to be a memory filesystem too, since scratch files hold evidence data.
+fn stage_outputs(
+    cfg: &Config,
+    cipher: OutputCipher,
+    run_output_dir: &Path,
+) -> Result<StagedOutput> {
+    if let Some(dir) = &cfg.temp_dir
+        && !is_memory_backed(dir).unwrap_or(false)
+    {
+        return Err(anyhow::anyhow!(
+            "temp_dir {} is not a memory filesystem; an encrypted run keeps its scratch files in memory",
+            dir.display()
+        )
+        .context(RunStatus::ConfigError));
+    }
+    StagedOutput::new(cipher, staging_parent(cfg), run_output_dir).context(RunStatus::ConfigError)
+}
 
-    ProgressSnapshot {
-        bytes_scanned: scanned_total,
-        total_bytes,
-        chunks_processed: chunks_processed.load(Ordering::Relaxed),
-        hits_found: hits_found.load(Ordering::Relaxed),
-        files_carved: files_carved.load(Ordering::Relaxed),
-        string_spans: string_spans.load(Ordering::Relaxed),
-        artefacts_extracted: artefacts_found.load(Ordering::Relaxed),
-        carve_errors: carve_errors.load(Ordering::Relaxed),
-        metadata_errors: metadata_errors.load(Ordering::Relaxed),
-        sqlite_errors: sqlite_errors.load(Ordering::Relaxed),
-        elapsed_seconds,
-        throughput_mib,
-        eta_seconds,
-        completion_pct,
-        validation_pass: 0, // To be populated when validation is enabled
-        validation_fail: 0, // To be populated when validation is enabled
+/// The run's counters, read for each progress snapshot.
+struct ProgressCounters<'a> {
+    start_time: &'a Instant,
+    bytes_scanned: &'a AtomicU64,
+    chunks_processed: &'a AtomicU64,
+    hits_found: &'a AtomicU64,
+    files_carved: &'a AtomicU64,
+    string_spans: &'a AtomicU64,
+    artefacts_found: &'a AtomicU64,
+    read_errors: &'a AtomicU64,
+    carve_errors: &'a AtomicU64,
+    metadata_errors: &'a AtomicU64,
+    sqlite_errors: &'a AtomicU64,
+    timings: &'a StageTimings,
+    panics: &'a PanicLog,
+    workers: usize,
+}
+
+impl ProgressCounters<'_> {
+    /// Progress over `total_bytes`, with `baseline_bytes` scanned by the run
+    /// this one resumes.
+    fn snapshot(&self, total_bytes: u64, baseline_bytes: u64) -> ProgressSnapshot {
+        let ProgressCounters {
+            start_time,
+            bytes_scanned,
+            chunks_processed,
+            hits_found,
+            files_carved,
+            string_spans,
+            artefacts_found,
+            read_errors,
+            carve_errors,
+            metadata_errors,
+            sqlite_errors,
+            timings,
+            panics,
+            workers,
+        } = *self;
+        let elapsed_seconds = start_time.elapsed().as_secs_f64();
+        let scanned = bytes_scanned.load(Ordering::Relaxed);
+        let scanned_total = scanned.saturating_add(baseline_bytes);
+        let throughput_mib = if elapsed_seconds > 0.0 {
+            scanned as f64 / crate::constants::MIB as f64 / elapsed_seconds
+        } else {
+            0.0
+        };
+        let bytes_per_sec = if elapsed_seconds > 0.0 {
+            scanned as f64 / elapsed_seconds
+        } else {
+            0.0
+        };
+        // A stream of unknown length has no remaining bytes to estimate.
+        let total_bytes = if total_bytes == UNKNOWN_LEN {
+            0
+        } else {
+            total_bytes
+        };
+        let remaining = total_bytes.saturating_sub(scanned_total);
+        let model = timings
+            .snapshot()
+            .estimate(workers, elapsed_seconds, remaining);
+        let eta_seconds = match model {
+            Some(estimate) => Some(estimate.seconds),
+            None if bytes_per_sec > 0.0 && remaining > 0 => {
+                Some((remaining as f64 / bytes_per_sec).round() as u64)
+            }
+            None => None,
+        };
+
+        let completion_pct = if total_bytes > 0 {
+            (scanned_total as f64 / total_bytes as f64) * 100.0
+        } else {
+            0.0
+        };
+
+        ProgressSnapshot {
+            bytes_scanned: scanned_total,
+            total_bytes,
+            chunks_processed: chunks_processed.load(Ordering::Relaxed),
+            hits_found: hits_found.load(Ordering::Relaxed),
+            files_carved: files_carved.load(Ordering::Relaxed),
+            string_spans: string_spans.load(Ordering::Relaxed),
+            artefacts_extracted: artefacts_found.load(Ordering::Relaxed),
+            read_errors: read_errors.load(Ordering::Relaxed),
+            carve_errors: carve_errors.load(Ordering::Relaxed),
+            metadata_errors: metadata_errors.load(Ordering::Relaxed),
+            sqlite_errors: sqlite_errors.load(Ordering::Relaxed),
+            worker_panics: panics.count(),
+            elapsed_seconds,
+            throughput_mib,
+            eta_seconds,
+            eta_low_seconds: model.map(|estimate| estimate.low_seconds),
+            eta_high_seconds: model.map(|estimate| estimate.high_seconds),
+            completion_pct,
+            validation_pass: 0, // To be populated when validation is enabled
+            validation_fail: 0, // To be populated when validation is enabled
+        }
     }
 }
 
+/// Options that read the evidence out of scan order, which a streamed input
+/// cannot serve.
+fn stream_conflicts(cfg: &Config, checkpoint: bool) -> Vec<&'static str> {
+    [
+        (cfg.enable_ntfs_scan, "enable_ntfs_scan"),
+        (cfg.unallocated_only, "unallocated_only"),
+        (cfg.enable_volume_locations, "enable_volume_locations"),
+        (cfg.enable_vss_scan, "enable_vss_scan"),
+        (cfg.string_scan_gaps_only, "string_scan_gaps_only"),
+        (
+            cfg.evidence_transform == EvidenceTransform::Auto,
+            "evidence_transform: auto",
+        ),
+        (checkpoint, "checkpoints"),
+    ]
+    .into_iter()
+    .filter_map(|(set, name)| set.then_some(name))
+    .collect()
+}
+
+/// Receiving ends of the queues into the scan, carve, string, and metadata
+/// stages: channels read by worker threads, or queues the pipeline thread
+/// drains after each chunk.
+enum StageReceivers {
+    Workers {
+        scans: Receiver<ScanJob>,
+        hits: Receiver<NormalizedHit>,
+        strings: Option<Receiver<StringJob>>,
+        meta: Receiver<MetadataEvent>,
+    },
+    Inline {
+        hits: InlineQueue<NormalizedHit>,
+        strings: Option<InlineQueue<StringJob>>,
+        meta: InlineQueue<MetadataEvent>,
+    },
+}
+
 /// Read a chunk from evidence, limited to max_len bytes
 fn read_chunk_limited(
     evidence: &dyn EvidenceSource,
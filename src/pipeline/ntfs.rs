@@ -0,0 +1,79 @@
+//! # NTFS-Aware Scanning
+//!
+//! With `enable_ntfs_scan`, the NTFS volumes in the evidence (at offset 0 or
+//! in an MBR partition) are found before the scan starts and their MFTs read
+//! (`parsers::ntfs`). The file records go to the metadata thread, which
+//! attributes carves to them ([`NtfsFiles`]); the clusters `$Bitmap` marks in
+//! use form an [`AllocatedMap`]. With `ntfs_unallocated_only`, scan chunks
+//! lying wholly inside it are skipped, so carving concentrates on unallocated
+//! space, where deleted files are found. Files still on the file system can
+//! be exported from it intact.
+
+use tracing::{info, warn};
+
+use crate::evidence::EvidenceSource;
+use crate::parsers::{ntfs, vss};
+use crate::report::NtfsFiles;
+
+use super::unallocated::AllocatedMap;
+
+/// Read the MFT of each NTFS volume in `evidence`.
+pub fn discover(run_id: &str, evidence: &dyn EvidenceSource) -> (NtfsFiles, AllocatedMap) {
+    let mut files = NtfsFiles::default();
+    let mut ranges = Vec::new();
+    for volume in vss::find_ntfs_volumes(evidence) {
+        match ntfs::read_volume(evidence, volume.offset) {
+            Ok(volume) => {
+                info!(
+                    "ntfs volume at {}: {} file record(s)",
+                    volume.boot.offset,
+                    volume.entries.len()
+                );
+                files.add_volume(run_id, &volume);
+                ranges.extend(volume.allocated);
+            }
+            Err(err) => {
+                warn!(
+                    "the MFT of the NTFS volume at {} is unreadable: {err}",
+                    volume.offset
+                );
+                files.add_error(volume.offset, volume.size, err.to_string());
+            }
+        }
+    }
+    (files, AllocatedMap::new(ranges))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evidence::MemorySource;
+    use crate::parsers::ntfs::testing::{CLUSTER, File, volume};
+
+    #[test]
+    fn maps_allocated_clusters_of_each_volume() {
+        let image = volume(
+            64,
+            &[File {
+                record: 16,
+                parent: ntfs::ROOT_RECORD,
+                name: "a.bin",
+                in_use: true,
+                clusters: Some((30, 2)),
+                size: 2 * CLUSTER as u64,
+            }],
+            &[30, 31],
+        );
+        let (files, allocated) = discover("run", &MemorySource::new(image));
+        let summary = files.summarize("run", 0, 0);
+        assert_eq!(summary.volumes.len(), 1);
+        assert_eq!(summary.volumes[0].error, None);
+
+        let c = CLUSTER as u64;
+        assert!(allocated.covers(30 * c, 32 * c));
+        assert!(allocated.covers(5 * c, 6 * c));
+        assert!(!allocated.covers(29 * c, 31 * c));
+        assert!(!allocated.covers(31 * c, 33 * c));
+        assert!(!allocated.covers(40 * c, 41 * c));
+    }
+}
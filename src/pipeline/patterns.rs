@@ -0,0 +1,254 @@
+//! # Per-Pattern Statistics
+//!
+//! A signature that matches noise (a short header on unknown media, a pattern
+//! that collides with a common structure) can send thousands of hits to the
+//! carve workers without producing a single file. [`PatternStats`] counts hits,
+//! carve attempts, and carved files per signature pattern. With
+//! `pattern_disable_min_attempts` set, a pattern whose success ratio is still at
+//! most `pattern_disable_max_success` after that many attempts is disabled for
+//! the rest of the run: a warning is logged and its later hits are skipped.
+//! The counts and the disabled patterns are written to
+//! `summaries/pattern_stats.json`.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tracing::warn;
+
+use crate::config::Config;
+use crate::report::SUMMARIES_DIR;
+use crate::scanner::NormalizedHit;
+
+/// File name of the pattern summary under `summaries/`.
+pub const PATTERN_SUMMARY_FILE: &str = "pattern_stats.json";
+
+#[derive(Debug, Clone, Default)]
+struct Counts {
+    file_type: String,
+    hits: u64,
+    attempts: u64,
+    carved: u64,
+    skipped: u64,
+    disabled_after: Option<u64>,
+}
+
+/// Hit and carve counters per pattern, shared by carve workers.
+#[derive(Debug, Default)]
+pub struct PatternStats {
+    /// Attempts before a pattern may be disabled (0 = never).
+    min_attempts: u64,
+    max_success: f64,
+    counts: Mutex<HashMap<String, Counts>>,
+}
+
+/// Pattern counters written at the end of a run.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct PatternSummary {
+    pub run_id: String,
+    pub disable_min_attempts: u64,
+    pub disable_max_success: f64,
+    pub patterns: Vec<PatternCounts>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct PatternCounts {
+    pub pattern_id: String,
+    pub file_type: String,
+    /// Hits that reached the carve stage.
+    pub hits: u64,
+    pub attempts: u64,
+    pub carved: u64,
+    /// `carved / attempts`, 0 without attempts.
+    pub success_ratio: f64,
+    /// Hits skipped after the pattern was disabled.
+    pub skipped: u64,
+    pub disabled: bool,
+    /// Attempts when the pattern was disabled.
+    pub disabled_after_attempts: Option<u64>,
+}
+
+impl PatternStats {
+    pub fn from_config(cfg: &Config) -> Self {
+        Self::new(
+            cfg.pattern_disable_min_attempts,
+            cfg.pattern_disable_max_success,
+        )
+    }
+
+    pub fn new(min_attempts: u64, max_success: f64) -> Self {
+        Self {
+            min_attempts,
+            max_success,
+            counts: Mutex::default(),
+        }
+    }
+
+    /// Count a hit arriving at the carve stage. Returns false if its pattern
+    /// has been disabled, in which case the hit must not be carved.
+    pub fn admit(&self, hit: &NormalizedHit) -> bool {
+        let Ok(mut counts) = self.counts.lock() else {
+            return true;
+        };
+        let entry = counts
+            .entry(hit.pattern_id.clone())
+            .or_insert_with(|| Counts {
+                file_type: hit.file_type_id.clone(),
+                ..Counts::default()
+            });
+        entry.hits += 1;
+        if entry.disabled_after.is_some() {
+            entry.skipped += 1;
+            return false;
+        }
+        true
+    }
+
+    /// Record the outcome of a carve attempt, disabling the pattern if it
+    /// crosses the threshold. Returns true when this call disabled it.
+    pub fn finish(&self, hit: &NormalizedHit, carved: bool) -> bool {
+        let Ok(mut counts) = self.counts.lock() else {
+            return false;
+        };
+        let Some(entry) = counts.get_mut(&hit.pattern_id) else {
+            return false;
+        };
+        entry.attempts += 1;
+        if carved {
+            entry.carved += 1;
+        }
+        if self.min_attempts == 0
+            || entry.disabled_after.is_some()
+            || entry.attempts < self.min_attempts
+            || entry.carved as f64 > entry.attempts as f64 * self.max_success
+        {
+            return false;
+        }
+        entry.disabled_after = Some(entry.attempts);
+        warn!(
+            "pattern {} ({}) auto-disabled: {} of {} carve attempts succeeded; its remaining hits are skipped",
+            hit.pattern_id, entry.file_type, entry.carved, entry.attempts
+        );
+        true
+    }
+
+    /// Patterns disabled so far, sorted.
+    pub fn disabled(&self) -> Vec<String> {
+        let mut out: Vec<String> = self
+            .counts
+            .lock()
+            .map(|counts| {
+                counts
+                    .iter()
+                    .filter(|(_, c)| c.disabled_after.is_some())
+                    .map(|(id, _)| id.clone())
+                    .collect()
+            })
+            .unwrap_or_default();
+        out.sort();
+        out
+    }
+
+    /// Hits skipped because their pattern was disabled.
+    pub fn skipped(&self) -> u64 {
+        self.counts
+            .lock()
+            .map(|counts| counts.values().map(|c| c.skipped).sum())
+            .unwrap_or(0)
+    }
+
+    pub fn summarize(&self, run_id: &str) -> PatternSummary {
+        let counts = self.counts.lock().map(|c| c.clone()).unwrap_or_default();
+        let mut patterns: Vec<PatternCounts> = counts
+            .into_iter()
+            .map(|(pattern_id, c)| PatternCounts {
+                pattern_id,
+                file_type: c.file_type,
+                hits: c.hits,
+                attempts: c.attempts,
+                carved: c.carved,
+                success_ratio: if c.attempts == 0 {
+                    0.0
+                } else {
+                    c.carved as f64 / c.attempts as f64
+                },
+                skipped: c.skipped,
+                disabled: c.disabled_after.is_some(),
+                disabled_after_attempts: c.disabled_after,
+            })
+            .collect();
+        patterns.sort_by(|a, b| {
+            b.hits
+                .cmp(&a.hits)
+                .then_with(|| a.pattern_id.cmp(&b.pattern_id))
+        });
+        PatternSummary {
+            run_id: run_id.to_string(),
+            disable_min_attempts: self.min_attempts,
+            disable_max_success: self.max_success,
+            patterns,
+        }
+    }
+}
+
+/// Write the summary to `<run_output_dir>/summaries/pattern_stats.json`.
+pub fn write_summary(run_output_dir: &Path, summary: &PatternSummary) -> std::io::Result<PathBuf> {
+    let dir = run_output_dir.join(SUMMARIES_DIR);
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(PATTERN_SUMMARY_FILE);
+    let writer = BufWriter::new(File::create(&path)?);
+    serde_json::to_writer_pretty(writer, summary)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hit(pattern: &str, offset: u64) -> NormalizedHit {
+        NormalizedHit {
+            global_offset: offset,
+            file_type_id: "jpeg".to_string(),
+            pattern_id: pattern.to_string(),
+        }
+    }
+
+    #[test]
+    fn disables_pattern_without_successful_carves() {
+        let stats = PatternStats::new(4, 0.25);
+        for offset in 0..4 {
+            assert!(stats.admit(&hit("noisy", offset)));
+            let disabled = stats.finish(&hit("noisy", offset), offset == 0);
+            // One success in four attempts is at the 0.25 limit.
+            assert_eq!(disabled, offset == 3);
+            assert!(stats.admit(&hit("good", offset)));
+            assert!(!stats.finish(&hit("good", offset), true));
+        }
+        assert!(!stats.admit(&hit("noisy", 10)));
+        assert!(stats.admit(&hit("good", 10)));
+        assert_eq!(stats.disabled(), vec!["noisy".to_string()]);
+        assert_eq!(stats.skipped(), 1);
+
+        let summary = stats.summarize("run");
+        let [good, noisy] = summary.patterns.as_slice() else {
+            panic!("two patterns expected");
+        };
+        assert_eq!(noisy.pattern_id, "noisy");
+        assert_eq!((noisy.hits, noisy.attempts, noisy.carved), (5, 4, 1));
+        assert_eq!(noisy.disabled_after_attempts, Some(4));
+        assert!(!good.disabled);
+    }
+
+    #[test]
+    fn zero_min_attempts_only_counts() {
+        let stats = PatternStats::new(0, 1.0);
+        for offset in 0..100 {
+            assert!(stats.admit(&hit("noisy", offset)));
+            assert!(!stats.finish(&hit("noisy", offset), false));
+        }
+        assert!(stats.disabled().is_empty());
+    }
+}
@@ -0,0 +1,334 @@
+//! # Hit Prefilter
+//!
+//! Some handlers read far past the hit before they can tell a damaged file
+//! from a good one: a PNG is walked chunk by chunk, a 7z, EVTX, registry
+//! hive, or PST is carved to the size its header declares. On corrupt media
+//! each false or damaged hit costs that I/O. With `hit_prefilter`, the scan
+//! workers verify the checksum of the first structure of such hits (the PNG
+//! `IHDR` CRC, the 7z start header CRC, the EVTX, regf, and PST header
+//! checksums) on the chunk bytes they already hold, and drop hits that fail
+//! before they are queued for carving. Hits whose first structure does not
+//! parse or runs past the chunk are passed on unchecked. Counts per type are
+//! written to `summaries/hit_prefilter.json`.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde::Serialize;
+
+use crate::carve::crc32;
+use crate::carve::evtx::FileHeader;
+use crate::carve::pst::PstHeader;
+use crate::carve::regf::BaseBlock;
+use crate::config::Config;
+use crate::report::SUMMARIES_DIR;
+
+/// File name of the prefilter summary under `summaries/`.
+pub const PREFILTER_SUMMARY_FILE: &str = "hit_prefilter.json";
+
+const PNG_SIGNATURE_LEN: usize = 8;
+const SEVENZ_HEADER_LEN: usize = 32;
+
+/// Structure checked for a type, by validator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Check {
+    PngIhdrCrc,
+    SevenzStartHeaderCrc,
+    EvtxHeaderCrc,
+    RegfBaseBlockChecksum,
+    PstHeaderCrc,
+}
+
+impl Check {
+    fn for_validator(validator: &str) -> Option<Self> {
+        match validator {
+            "png" => Some(Self::PngIhdrCrc),
+            "sevenz" => Some(Self::SevenzStartHeaderCrc),
+            "evtx" => Some(Self::EvtxHeaderCrc),
+            "regf" => Some(Self::RegfBaseBlockChecksum),
+            "pst" => Some(Self::PstHeaderCrc),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::PngIhdrCrc => "png_ihdr_crc",
+            Self::SevenzStartHeaderCrc => "sevenz_start_header_crc",
+            Self::EvtxHeaderCrc => "evtx_header_crc",
+            Self::RegfBaseBlockChecksum => "regf_base_block_checksum",
+            Self::PstHeaderCrc => "pst_header_crc",
+        }
+    }
+
+    /// Whether the checksum of the structure at the start of `data` holds;
+    /// `None` when it does not parse or is not wholly in `data`.
+    fn verify(self, data: &[u8]) -> Option<bool> {
+        match self {
+            Self::PngIhdrCrc => {
+                let len_at = PNG_SIGNATURE_LEN;
+                let len = u32::from_be_bytes(data.get(len_at..len_at + 4)?.try_into().ok()?);
+                let body = data.get(len_at + 4..(len_at + 8).checked_add(len as usize)?)?;
+                let crc_at = len_at + 8 + len as usize;
+                let stored = u32::from_be_bytes(data.get(crc_at..crc_at + 4)?.try_into().ok()?);
+                Some(crc32(body) == stored)
+            }
+            Self::SevenzStartHeaderCrc => {
+                let header = data.get(..SEVENZ_HEADER_LEN)?;
+                let stored = u32::from_le_bytes(header[8..12].try_into().ok()?);
+                Some(crc32(&header[12..]) == stored)
+            }
+            Self::EvtxHeaderCrc => FileHeader::parse(data).map(|h| h.checksum_ok),
+            Self::RegfBaseBlockChecksum => BaseBlock::parse(data).map(|b| b.checksum_ok),
+            Self::PstHeaderCrc => PstHeader::parse(data).map(|h| h.checksum_ok),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct TypeCounters {
+    checked: AtomicU64,
+    rejected: AtomicU64,
+    unchecked: AtomicU64,
+}
+
+/// First-structure checksum checks per type, shared by scan workers.
+#[derive(Debug, Default)]
+pub struct HitPrefilter {
+    checks: HashMap<String, (Check, TypeCounters)>,
+}
+
+/// Prefilter counts written at the end of a run.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct PrefilterSummary {
+    pub run_id: String,
+    pub total_checked: u64,
+    pub total_rejected: u64,
+    pub types: Vec<TypePrefilter>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct TypePrefilter {
+    pub file_type: String,
+    /// Structure whose checksum is verified.
+    pub check: &'static str,
+    /// Hits whose first structure was in the chunk and parsed.
+    pub checked: u64,
+    /// Checked hits whose checksum failed; never carved.
+    pub rejected: u64,
+    /// `rejected / checked`, 0 without checked hits.
+    pub rejection_ratio: f64,
+    /// Hits passed on because their first structure did not parse or ran
+    /// past the chunk.
+    pub unchecked: u64,
+}
+
+impl HitPrefilter {
+    /// Checks for the configured types that have one; `None` unless
+    /// `hit_prefilter` is set.
+    pub fn from_config(cfg: &Config) -> Option<Self> {
+        if !cfg.hit_prefilter {
+            return None;
+        }
+        let checks = cfg
+            .file_types
+            .iter()
+            .filter_map(|ft| {
+                let validator = if ft.validator.trim().is_empty() {
+                    ft.id.as_str()
+                } else {
+                    ft.validator.as_str()
+                };
+                Check::for_validator(validator)
+                    .map(|check| (ft.id.clone(), (check, TypeCounters::default())))
+            })
+            .collect();
+        Some(Self { checks })
+    }
+
+    /// Whether a hit of `file_type_id` whose bytes in the chunk start
+    /// `data` should be carved: false only when its first structure's
+    /// checksum fails.
+    pub fn admit(&self, file_type_id: &str, data: &[u8]) -> bool {
+        let Some((check, counters)) = self.checks.get(file_type_id) else {
+            return true;
+        };
+        match check.verify(data) {
+            Some(ok) => {
+                counters.checked.fetch_add(1, Ordering::Relaxed);
+                if !ok {
+                    counters.rejected.fetch_add(1, Ordering::Relaxed);
+                }
+                ok
+            }
+            None => {
+                counters.unchecked.fetch_add(1, Ordering::Relaxed);
+                true
+            }
+        }
+    }
+
+    /// Hits rejected so far.
+    pub fn rejected(&self) -> u64 {
+        self.checks
+            .values()
+            .map(|(_, c)| c.rejected.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    pub fn summarize(&self, run_id: &str) -> PrefilterSummary {
+        let mut types: Vec<TypePrefilter> = self
+            .checks
+            .iter()
+            .map(|(file_type, (check, counters))| {
+                let checked = counters.checked.load(Ordering::Relaxed);
+                let rejected = counters.rejected.load(Ordering::Relaxed);
+                TypePrefilter {
+                    file_type: file_type.clone(),
+                    check: check.name(),
+                    checked,
+                    rejected,
+                    rejection_ratio: if checked == 0 {
+                        0.0
+                    } else {
+                        rejected as f64 / checked as f64
+                    },
+                    unchecked: counters.unchecked.load(Ordering::Relaxed),
+                }
+            })
+            .collect();
+        types.sort_by(|a, b| {
+            b.rejected
+                .cmp(&a.rejected)
+                .then_with(|| a.file_type.cmp(&b.file_type))
+        });
+        PrefilterSummary {
+            run_id: run_id.to_string(),
+            total_checked: types.iter().map(|t| t.checked).sum(),
+            total_rejected: types.iter().map(|t| t.rejected).sum(),
+            types,
+        }
+    }
+}
+
+/// Write the summary to `<run_output_dir>/summaries/hit_prefilter.json`.
+pub fn write_summary(
+    run_output_dir: &Path,
+    summary: &PrefilterSummary,
+) -> std::io::Result<PathBuf> {
+    let dir = run_output_dir.join(SUMMARIES_DIR);
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(PREFILTER_SUMMARY_FILE);
+    let writer = BufWriter::new(File::create(&path)?);
+    serde_json::to_writer_pretty(writer, summary)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prefilter() -> HitPrefilter {
+        HitPrefilter {
+            checks: HashMap::from([
+                (
+                    "png".to_string(),
+                    (Check::PngIhdrCrc, TypeCounters::default()),
+                ),
+                (
+                    "7z".to_string(),
+                    (Check::SevenzStartHeaderCrc, TypeCounters::default()),
+                ),
+            ]),
+        }
+    }
+
+    fn png() -> Vec<u8> {
+        let mut data = b"\x89PNG\r\n\x1a\n".to_vec();
+        let mut ihdr = b"IHDR".to_vec();
+        ihdr.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0, 1, 8, 2, 0, 0, 0]);
+        data.extend_from_slice(&13u32.to_be_bytes());
+        data.extend_from_slice(&ihdr);
+        data.extend_from_slice(&crc32(&ihdr).to_be_bytes());
+        data.extend_from_slice(b"rest of the file");
+        data
+    }
+
+    fn sevenz() -> Vec<u8> {
+        let mut data = vec![0x37, 0x7A, 0xBC, 0xAF, 0x27, 0x1C, 0, 4];
+        let start_header = [7u8; 20];
+        data.extend_from_slice(&crc32(&start_header).to_le_bytes());
+        data.extend_from_slice(&start_header);
+        data
+    }
+
+    #[test]
+    fn rejects_hits_whose_first_checksum_fails() {
+        let prefilter = prefilter();
+        assert!(prefilter.admit("png", &png()));
+        assert!(prefilter.admit("7z", &sevenz()));
+
+        let mut bad_png = png();
+        bad_png[20] ^= 1;
+        assert!(!prefilter.admit("png", &bad_png));
+        let mut bad_7z = sevenz();
+        bad_7z[31] ^= 1;
+        assert!(!prefilter.admit("7z", &bad_7z));
+        assert_eq!(prefilter.rejected(), 2);
+    }
+
+    #[test]
+    fn passes_hits_it_cannot_check() {
+        let prefilter = prefilter();
+        // The IHDR CRC lies past the end of the chunk.
+        assert!(prefilter.admit("png", &png()[..25]));
+        // A length field that overflows is not a verdict either.
+        let mut huge = png();
+        huge[8..12].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(prefilter.admit("png", &huge));
+        assert!(prefilter.admit("jpeg", &[0u8; 4]));
+
+        let summary = prefilter.summarize("run");
+        assert_eq!((summary.total_checked, summary.total_rejected), (0, 0));
+        let png = summary
+            .types
+            .iter()
+            .find(|t| t.file_type == "png")
+            .expect("png");
+        assert_eq!((png.checked, png.unchecked), (0, 2));
+        assert_eq!(png.check, "png_ihdr_crc");
+    }
+
+    #[test]
+    fn summarizes_rejection_ratio_per_type() {
+        let prefilter = prefilter();
+        let mut bad = png();
+        bad[20] ^= 1;
+        for data in [png(), bad.clone(), bad.clone(), bad] {
+            prefilter.admit("png", &data);
+        }
+        let summary = prefilter.summarize("run");
+        assert_eq!(summary.types[0].file_type, "png");
+        assert_eq!(
+            (summary.types[0].checked, summary.types[0].rejected),
+            (4, 3)
+        );
+        assert_eq!(summary.types[0].rejection_ratio, 0.75);
+        assert_eq!(summary.total_rejected, 3);
+    }
+
+    #[test]
+    fn builds_checks_only_when_enabled() {
+        let mut cfg = crate::config::load_config(None).expect("config").config;
+        assert!(HitPrefilter::from_config(&cfg).is_none());
+        cfg.hit_prefilter = true;
+        let prefilter = HitPrefilter::from_config(&cfg).expect("prefilter");
+        for id in ["png", "7z", "evtx", "regf", "pst"] {
+            assert!(prefilter.checks.contains_key(id), "{id}");
+        }
+        assert!(!prefilter.checks.contains_key("jpeg"));
+    }
+}
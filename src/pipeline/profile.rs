@@ -0,0 +1,625 @@
+//! # Throughput Profiling
+//!
+//! Where the time of a run goes, for tuning on unusual media. With
+//! `enable_profiling` (`--profile-throughput`), the scan workers time each chunk, the
+//! carve workers each hit (handler, post-processors, and bookkeeping), and
+//! both stages the time they spend blocked on the scan and carve queues:
+//! `send_wait` while a full queue holds the producer back, `recv_wait` while
+//! an idle worker waits for the next job.
+//!
+//! Every measurement is a row of `profile.parquet` in the run directory,
+//! written in row groups of [`ROW_GROUP_ROWS`] as the run goes, and is folded
+//! into `summaries/profile.json`: totals per file type, throughput per
+//! evidence region of `profile_region_size` bytes, queue wait totals, and the
+//! `profile_top_n` slowest chunks and carves.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use arrow_array::builder::{Float64Builder, Int64Builder, StringBuilder};
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use parquet::arrow::ArrowWriter;
+use serde::Serialize;
+use tracing::warn;
+
+use crate::config::Config;
+use crate::report::SUMMARIES_DIR;
+use crate::scanner::NormalizedHit;
+
+use super::hit_context::HitOutcome;
+
+/// File name of the profile rows in the run directory.
+pub const PROFILE_FILE: &str = "profile.parquet";
+/// File name of the profile summary under `summaries/`.
+pub const PROFILE_SUMMARY_FILE: &str = "profile.json";
+/// Rows buffered before they are written as one row group.
+pub const ROW_GROUP_ROWS: usize = 65_536;
+
+/// What a profile row measures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProfileEvent {
+    /// A scan worker searching one chunk, string scan included.
+    ScanChunk,
+    /// A carve worker handling one hit.
+    Carve,
+    /// A producer blocked on a full queue.
+    SendWait,
+    /// A worker waiting on an empty queue.
+    RecvWait,
+}
+
+impl ProfileEvent {
+    pub fn name(self) -> &'static str {
+        match self {
+            ProfileEvent::ScanChunk => "scan_chunk",
+            ProfileEvent::Carve => "carve",
+            ProfileEvent::SendWait => "send_wait",
+            ProfileEvent::RecvWait => "recv_wait",
+        }
+    }
+}
+
+/// Queue between two stages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Queue {
+    /// Chunks from the reader to the scan workers.
+    Scan,
+    /// Hits from the scan workers to the carve workers.
+    Carve,
+}
+
+impl Queue {
+    pub fn name(self) -> &'static str {
+        match self {
+            Queue::Scan => "scan",
+            Queue::Carve => "carve",
+        }
+    }
+}
+
+/// One row of `profile.parquet`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProfileRow {
+    pub event: ProfileEvent,
+    /// Set for queue waits.
+    pub queue: Option<Queue>,
+    /// Set for carves.
+    pub file_type: Option<String>,
+    /// Chunk start, or hit offset for carves and carve queue receives.
+    pub offset: u64,
+    /// Bytes scanned, or bytes carved.
+    pub bytes: u64,
+    /// Handler outcome of a carve; `None` for hits skipped before the handler.
+    pub outcome: Option<HitOutcome>,
+    pub duration: Duration,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ProfileSummary {
+    pub run_id: String,
+    /// Rows written to `profile.parquet`.
+    pub rows: u64,
+    /// File types by total carve time, most expensive first.
+    pub types: Vec<TypeProfile>,
+    pub region_size: u64,
+    pub regions: Vec<RegionProfile>,
+    pub queues: Vec<QueueProfile>,
+    /// Slowest chunks and carves, slowest first.
+    pub slowest: Vec<SlowEvent>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct TypeProfile {
+    pub file_type: String,
+    pub attempts: u64,
+    /// Attempts that produced a file.
+    pub carved: u64,
+    pub bytes_carved: u64,
+    pub total_secs: f64,
+    pub mean_secs: f64,
+    pub max_secs: f64,
+    /// Hit offset of the slowest attempt.
+    pub max_offset: u64,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct RegionProfile {
+    pub start: u64,
+    pub end: u64,
+    pub chunks: u64,
+    pub bytes_scanned: u64,
+    pub scan_secs: f64,
+    /// Scan throughput of one worker; `None` when no time was measured.
+    pub scan_mib_per_sec: Option<f64>,
+    pub carves: u64,
+    pub carve_secs: f64,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct QueueProfile {
+    pub queue: Queue,
+    pub event: ProfileEvent,
+    pub waits: u64,
+    pub total_secs: f64,
+    pub max_secs: f64,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct SlowEvent {
+    pub event: ProfileEvent,
+    pub file_type: Option<String>,
+    pub offset: u64,
+    pub bytes: u64,
+    pub secs: f64,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Totals {
+    count: u64,
+    produced: u64,
+    bytes: u64,
+    total: Duration,
+    max: Duration,
+    max_offset: u64,
+}
+
+impl Totals {
+    fn add(&mut self, row: &ProfileRow) {
+        self.count += 1;
+        self.bytes += row.bytes;
+        self.total += row.duration;
+        if matches!(
+            row.outcome,
+            Some(HitOutcome::Validated | HitOutcome::Carved)
+        ) {
+            self.produced += 1;
+        }
+        if row.duration >= self.max {
+            self.max = row.duration;
+            self.max_offset = row.offset;
+        }
+    }
+}
+
+/// Heap entry of the slowest events; ordered by duration first.
+type Slow = (Duration, u64, ProfileEvent, Option<String>, u64);
+
+#[derive(Default)]
+struct ProfileState {
+    pending: Vec<ProfileRow>,
+    writer: Option<ArrowWriter<File>>,
+    /// Set once writing failed; rows are still summarized.
+    failed: bool,
+    rows: u64,
+    types: BTreeMap<String, Totals>,
+    /// Scan and carve totals per region start.
+    regions: BTreeMap<u64, (Totals, Totals)>,
+    queues: BTreeMap<(Queue, ProfileEvent), Totals>,
+    slowest: BinaryHeap<Reverse<Slow>>,
+}
+
+/// Profile rows and running totals shared by the pipeline threads.
+pub struct RunProfiler {
+    run_id: String,
+    path: PathBuf,
+    region_size: u64,
+    top_n: usize,
+    state: Mutex<ProfileState>,
+}
+
+impl RunProfiler {
+    /// `None` unless `enable_profiling` is set and there is a run output
+    /// directory to write to (dry runs have none).
+    pub fn from_config(cfg: &Config, run_output_dir: &Path) -> Option<Self> {
+        if !cfg.enable_profiling || !run_output_dir.is_dir() {
+            return None;
+        }
+        Some(Self::new(
+            &cfg.run_id,
+            run_output_dir.join(PROFILE_FILE),
+            cfg.profile_region_size,
+            cfg.profile_top_n,
+        ))
+    }
+
+    pub fn new(run_id: &str, path: PathBuf, region_size: u64, top_n: usize) -> Self {
+        Self {
+            run_id: run_id.to_string(),
+            path,
+            region_size,
+            top_n,
+            state: Mutex::default(),
+        }
+    }
+
+    /// A scan worker finished a chunk of `bytes` valid bytes at `offset`.
+    pub fn scan_chunk(&self, offset: u64, bytes: u64, elapsed: Duration) {
+        self.record(ProfileRow {
+            event: ProfileEvent::ScanChunk,
+            queue: None,
+            file_type: None,
+            offset,
+            bytes,
+            outcome: None,
+            duration: elapsed,
+        });
+    }
+
+    /// A carve worker finished with `hit`; `bytes` is the carved size.
+    pub fn carve(
+        &self,
+        hit: &NormalizedHit,
+        outcome: Option<HitOutcome>,
+        bytes: u64,
+        elapsed: Duration,
+    ) {
+        self.record(ProfileRow {
+            event: ProfileEvent::Carve,
+            queue: None,
+            file_type: Some(hit.file_type_id.clone()),
+            offset: hit.global_offset,
+            bytes,
+            outcome,
+            duration: elapsed,
+        });
+    }
+
+    /// Time spent blocked on `queue` sending or receiving the job at `offset`.
+    pub fn queue_wait(&self, queue: Queue, event: ProfileEvent, offset: u64, elapsed: Duration) {
+        self.record(ProfileRow {
+            event,
+            queue: Some(queue),
+            file_type: None,
+            offset,
+            bytes: 0,
+            outcome: None,
+            duration: elapsed,
+        });
+    }
+
+    pub fn record(&self, row: ProfileRow) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        state.rows += 1;
+        match row.event {
+            ProfileEvent::ScanChunk | ProfileEvent::Carve => {
+                let region = if self.region_size > 0 {
+                    row.offset - row.offset % self.region_size
+                } else {
+                    0
+                };
+                let (scan, carve) = state.regions.entry(region).or_default();
+                if row.event == ProfileEvent::ScanChunk {
+                    scan.add(&row);
+                } else {
+                    carve.add(&row);
+                }
+                if let Some(file_type) = &row.file_type {
+                    match state.types.get_mut(file_type) {
+                        Some(totals) => totals.add(&row),
+                        None => {
+                            let mut totals = Totals::default();
+                            totals.add(&row);
+                            state.types.insert(file_type.clone(), totals);
+                        }
+                    }
+                }
+                if self.top_n > 0 {
+                    state.slowest.push(Reverse((
+                        row.duration,
+                        row.offset,
+                        row.event,
+                        row.file_type.clone(),
+                        row.bytes,
+                    )));
+                    if state.slowest.len() > self.top_n {
+                        state.slowest.pop();
+                    }
+                }
+            }
+            ProfileEvent::SendWait | ProfileEvent::RecvWait => {
+                if let Some(queue) = row.queue {
+                    state
+                        .queues
+                        .entry((queue, row.event))
+                        .or_default()
+                        .add(&row);
+                }
+            }
+        }
+        state.pending.push(row);
+        if state.pending.len() >= ROW_GROUP_ROWS {
+            self.write_pending(&mut state);
+        }
+    }
+
+    /// Write the remaining rows, close `profile.parquet`, and summarize.
+    pub fn finish(&self) -> std::io::Result<ProfileSummary> {
+        let mut state = self
+            .state
+            .lock()
+            .map_err(|_| std::io::Error::other("profile state poisoned"))?;
+        self.write_pending(&mut state);
+        if state.writer.is_none() && !state.failed {
+            state.writer = Some(self.open()?);
+        }
+        if let Some(writer) = state.writer.take() {
+            writer.close().map_err(std::io::Error::other)?;
+        }
+        Ok(self.summarize(&state))
+    }
+
+    fn open(&self) -> std::io::Result<ArrowWriter<File>> {
+        ArrowWriter::try_new(File::create(&self.path)?, profile_schema(), None)
+            .map_err(std::io::Error::other)
+    }
+
+    fn write_pending(&self, state: &mut ProfileState) {
+        let rows = std::mem::take(&mut state.pending);
+        if rows.is_empty() || state.failed {
+            return;
+        }
+        let result = (|| {
+            if state.writer.is_none() {
+                state.writer = Some(self.open()?);
+            }
+            let batch = build_batch(&self.run_id, &rows)?;
+            match &mut state.writer {
+                Some(writer) => writer.write(&batch).map_err(std::io::Error::other),
+                None => Ok(()),
+            }
+        })();
+        if let Err(err) = result {
+            warn!(
+                "failed to write {}: {err}; profiling continues in the summary only",
+                self.path.display()
+            );
+            state.failed = true;
+            state.writer = None;
+        }
+    }
+
+    fn summarize(&self, state: &ProfileState) -> ProfileSummary {
+        let mut types: Vec<TypeProfile> = state
+            .types
+            .iter()
+            .map(|(file_type, totals)| TypeProfile {
+                file_type: file_type.clone(),
+                attempts: totals.count,
+                carved: totals.produced,
+                bytes_carved: totals.bytes,
+                total_secs: totals.total.as_secs_f64(),
+                mean_secs: totals.total.as_secs_f64() / totals.count.max(1) as f64,
+                max_secs: totals.max.as_secs_f64(),
+                max_offset: totals.max_offset,
+            })
+            .collect();
+        types.sort_by(|a, b| b.total_secs.total_cmp(&a.total_secs));
+
+        let regions = state
+            .regions
+            .iter()
+            .map(|(&start, (scan, carve))| {
+                let scan_secs = scan.total.as_secs_f64();
+                RegionProfile {
+                    start,
+                    end: match self.region_size {
+                        0 => u64::MAX,
+                        size => start.saturating_add(size),
+                    },
+                    chunks: scan.count,
+                    bytes_scanned: scan.bytes,
+                    scan_secs,
+                    scan_mib_per_sec: (scan_secs > 0.0)
+                        .then(|| scan.bytes as f64 / (1024.0 * 1024.0) / scan_secs),
+                    carves: carve.count,
+                    carve_secs: carve.total.as_secs_f64(),
+                }
+            })
+            .collect();
+
+        let queues = state
+            .queues
+            .iter()
+            .map(|(&(queue, event), totals)| QueueProfile {
+                queue,
+                event,
+                waits: totals.count,
+                total_secs: totals.total.as_secs_f64(),
+                max_secs: totals.max.as_secs_f64(),
+            })
+            .collect();
+
+        let mut slowest: Vec<Slow> = state.slowest.iter().map(|entry| entry.0.clone()).collect();
+        slowest.sort_by(|a, b| b.cmp(a));
+        let slowest = slowest
+            .into_iter()
+            .map(|(duration, offset, event, file_type, bytes)| SlowEvent {
+                event,
+                file_type,
+                offset,
+                bytes,
+                secs: duration.as_secs_f64(),
+            })
+            .collect();
+
+        ProfileSummary {
+            run_id: self.run_id.clone(),
+            rows: state.rows,
+            types,
+            region_size: self.region_size,
+            regions,
+            queues,
+            slowest,
+        }
+    }
+}
+
+fn profile_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("run_id", DataType::Utf8, false),
+        Field::new("event", DataType::Utf8, false),
+        Field::new("queue", DataType::Utf8, true),
+        Field::new("file_type", DataType::Utf8, true),
+        Field::new("offset", DataType::Int64, false),
+        Field::new("bytes", DataType::Int64, false),
+        Field::new("outcome", DataType::Utf8, true),
+        Field::new("duration_secs", DataType::Float64, false),
+    ]))
+}
+
+fn build_batch(run_id: &str, rows: &[ProfileRow]) -> std::io::Result<RecordBatch> {
+    let mut run_ids = StringBuilder::new();
+    let mut events = StringBuilder::new();
+    let mut queues = StringBuilder::new();
+    let mut file_types = StringBuilder::new();
+    let mut offsets = Int64Builder::new();
+    let mut bytes = Int64Builder::new();
+    let mut outcomes = StringBuilder::new();
+    let mut durations = Float64Builder::new();
+    for row in rows {
+        run_ids.append_value(run_id);
+        events.append_value(row.event.name());
+        queues.append_option(row.queue.map(Queue::name));
+        file_types.append_option(row.file_type.as_deref());
+        offsets.append_value(row.offset as i64);
+        bytes.append_value(row.bytes as i64);
+        outcomes.append_option(row.outcome.map(outcome_name));
+        durations.append_value(row.duration.as_secs_f64());
+    }
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(run_ids.finish()),
+        Arc::new(events.finish()),
+        Arc::new(queues.finish()),
+        Arc::new(file_types.finish()),
+        Arc::new(offsets.finish()),
+        Arc::new(bytes.finish()),
+        Arc::new(outcomes.finish()),
+        Arc::new(durations.finish()),
+    ];
+    RecordBatch::try_new(profile_schema(), columns).map_err(std::io::Error::other)
+}
+
+fn outcome_name(outcome: HitOutcome) -> &'static str {
+    match outcome {
+        HitOutcome::Validated => "validated",
+        HitOutcome::Carved => "carved",
+        HitOutcome::Rejected => "rejected",
+        HitOutcome::Failed => "failed",
+    }
+}
+
+/// Write the summary to `<run_output_dir>/summaries/profile.json`.
+pub fn write_summary(run_output_dir: &Path, summary: &ProfileSummary) -> std::io::Result<PathBuf> {
+    let dir = run_output_dir.join(SUMMARIES_DIR);
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(PROFILE_SUMMARY_FILE);
+    let writer = BufWriter::new(File::create(&path)?);
+    serde_json::to_writer_pretty(writer, summary)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    fn hit(file_type: &str, offset: u64) -> NormalizedHit {
+        NormalizedHit {
+            global_offset: offset,
+            file_type_id: file_type.to_string(),
+            pattern_id: format!("{file_type}_header"),
+        }
+    }
+
+    #[test]
+    fn summarizes_types_regions_queues_and_slowest() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join(PROFILE_FILE);
+        let profiler = RunProfiler::new("run", path.clone(), 1024, 2);
+        let ms = Duration::from_millis;
+
+        profiler.scan_chunk(0, 1024 * 1024, ms(500));
+        profiler.scan_chunk(1024, 1024 * 1024, ms(250));
+        profiler.carve(&hit("jpeg", 100), Some(HitOutcome::Validated), 4096, ms(10));
+        profiler.carve(&hit("jpeg", 2000), Some(HitOutcome::Rejected), 0, ms(30));
+        profiler.carve(
+            &hit("sqlite", 1500),
+            Some(HitOutcome::Carved),
+            8192,
+            ms(900),
+        );
+        profiler.carve(&hit("sqlite", 1600), None, 0, ms(1));
+        profiler.queue_wait(Queue::Carve, ProfileEvent::RecvWait, 100, ms(40));
+        profiler.queue_wait(Queue::Carve, ProfileEvent::RecvWait, 2000, ms(60));
+        profiler.queue_wait(Queue::Scan, ProfileEvent::SendWait, 1024, ms(5));
+
+        let summary = profiler.finish().expect("finish");
+        assert_eq!(summary.rows, 9);
+
+        assert_eq!(summary.types.len(), 2);
+        let sqlite = &summary.types[0];
+        assert_eq!(sqlite.file_type, "sqlite");
+        assert_eq!((sqlite.attempts, sqlite.carved), (2, 1));
+        assert_eq!(sqlite.bytes_carved, 8192);
+        assert_eq!(sqlite.max_offset, 1500);
+        let jpeg = &summary.types[1];
+        assert_eq!((jpeg.attempts, jpeg.carved), (2, 1));
+        assert!((jpeg.mean_secs - 0.02).abs() < 1e-9);
+
+        assert_eq!(summary.regions.len(), 2);
+        let first = &summary.regions[0];
+        assert_eq!((first.start, first.end), (0, 1024));
+        assert_eq!((first.chunks, first.carves), (1, 1));
+        assert_eq!(first.scan_mib_per_sec, Some(2.0));
+        let second = &summary.regions[1];
+        assert_eq!((second.chunks, second.carves), (1, 3));
+        assert_eq!(second.scan_mib_per_sec, Some(4.0));
+
+        assert_eq!(summary.queues.len(), 2);
+        let recv = summary
+            .queues
+            .iter()
+            .find(|q| q.event == ProfileEvent::RecvWait)
+            .expect("recv waits");
+        assert_eq!((recv.queue, recv.waits), (Queue::Carve, 2));
+        assert!((recv.max_secs - 0.06).abs() < 1e-9);
+
+        let slowest: Vec<(ProfileEvent, u64)> = summary
+            .slowest
+            .iter()
+            .map(|e| (e.event, e.offset))
+            .collect();
+        assert_eq!(
+            slowest,
+            vec![(ProfileEvent::Carve, 1500), (ProfileEvent::ScanChunk, 0)]
+        );
+
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&path).expect("open"))
+            .expect("parquet")
+            .build()
+            .expect("reader");
+        let rows: usize = reader.map(|batch| batch.expect("batch").num_rows()).sum();
+        assert_eq!(rows, 9);
+    }
+
+    #[test]
+    fn writes_an_empty_profile_when_nothing_was_measured() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join(PROFILE_FILE);
+        let profiler = RunProfiler::new("run", path.clone(), 0, 20);
+        let summary = profiler.finish().expect("finish");
+        assert_eq!(summary.rows, 0);
+        assert!(summary.regions.is_empty() && summary.slowest.is_empty());
+        assert!(path.is_file());
+    }
+}
@@ -0,0 +1,262 @@
+//! # QA Sampling
+//!
+//! Lab procedures often require a reviewer to open a random share of the
+//! carved files of each type. With `qa_sample_rate` set, [`QaSampler`] picks
+//! each carved file with that probability and copies it to
+//! `qa_sample/<type>/` before retention can delete it. The pick depends only
+//! on `qa_sample_seed`, the type, and the file's start offset, so the same
+//! seed selects the same files on every run over the same evidence, whatever
+//! order the carve workers finish in. Per-type counts and the sampled files
+//! are written to `summaries/qa_sample.json`.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tracing::warn;
+
+use crate::carve::{CarvedFile, sanitize_component};
+use crate::config::Config;
+use crate::report::SUMMARIES_DIR;
+
+/// Directory (relative to the run output directory) holding sampled copies.
+pub const QA_SAMPLE_DIR: &str = "qa_sample";
+/// File name of the sample list under `summaries/`.
+pub const QA_SAMPLE_SUMMARY_FILE: &str = "qa_sample.json";
+
+/// One sampled carve.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct QaSampleEntry {
+    pub file_type: String,
+    /// Path of the carve relative to `carved/`.
+    pub carved_path: String,
+    /// Path of the copy relative to the run output directory.
+    pub sample_path: String,
+    pub global_start: u64,
+    pub size: u64,
+    pub validated: bool,
+    pub sha256: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct QaTypeCounts {
+    pub file_type: String,
+    pub carved: u64,
+    pub sampled: u64,
+}
+
+/// Sample list written at the end of a run.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct QaSampleSummary {
+    pub run_id: String,
+    pub rate: f64,
+    pub seed: u64,
+    pub types: Vec<QaTypeCounts>,
+    pub files: Vec<QaSampleEntry>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    carved: BTreeMap<String, u64>,
+    files: Vec<QaSampleEntry>,
+}
+
+/// Seeded per-file sampler shared by carve workers.
+#[derive(Debug, Default)]
+pub struct QaSampler {
+    rate: f64,
+    seed: u64,
+    state: Mutex<State>,
+}
+
+impl QaSampler {
+    pub fn from_config(cfg: &Config) -> Self {
+        Self::new(cfg.qa_sample_rate, cfg.qa_sample_seed)
+    }
+
+    pub fn new(rate: f64, seed: u64) -> Self {
+        Self {
+            rate: rate.clamp(0.0, 1.0),
+            seed,
+            state: Mutex::default(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.rate > 0.0
+    }
+
+    /// Whether the carve of `file_type` at `global_start` is in the sample.
+    pub fn selects(&self, file_type: &str, global_start: u64) -> bool {
+        let mut key = self.seed ^ global_start.rotate_left(17);
+        for &b in file_type.as_bytes() {
+            key = splitmix64(key ^ b as u64);
+        }
+        let unit = (splitmix64(key) >> 11) as f64 / (1u64 << 53) as f64;
+        unit < self.rate
+    }
+
+    /// Count a carved file and, when it is selected, copy it from
+    /// `carved_path` into `qa_sample/` under `run_output_dir`. A failed copy
+    /// is logged and leaves the file out of the sample.
+    pub fn offer(&self, file: &CarvedFile, carved_path: &Path, run_output_dir: &Path) {
+        if !self.is_enabled() {
+            return;
+        }
+        let selected = self.selects(&file.file_type, file.global_start);
+        if let Ok(mut state) = self.state.lock() {
+            *state.carved.entry(file.file_type.clone()).or_default() += 1;
+        }
+        if !selected {
+            return;
+        }
+        let Some(name) = carved_path.file_name() else {
+            return;
+        };
+        let rel = Path::new(QA_SAMPLE_DIR)
+            .join(sanitize_component(&file.file_type))
+            .join(name);
+        let dest = run_output_dir.join(&rel);
+        let copied = dest
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::copy(carved_path, &dest));
+        if let Err(err) = copied {
+            warn!(
+                "failed to copy {} to the QA sample: {err}",
+                carved_path.display()
+            );
+            return;
+        }
+        let entry = QaSampleEntry {
+            file_type: file.file_type.clone(),
+            carved_path: file.path.clone(),
+            sample_path: rel.to_string_lossy().to_string(),
+            global_start: file.global_start,
+            size: file.size,
+            validated: file.validated,
+            sha256: file.sha256.clone(),
+        };
+        if let Ok(mut state) = self.state.lock() {
+            state.files.push(entry);
+        }
+    }
+
+    pub fn summarize(&self, run_id: &str) -> QaSampleSummary {
+        let (carved, mut files) = self
+            .state
+            .lock()
+            .map(|s| (s.carved.clone(), s.files.clone()))
+            .unwrap_or_default();
+        files.sort_by(|a, b| {
+            a.file_type
+                .cmp(&b.file_type)
+                .then(a.global_start.cmp(&b.global_start))
+        });
+        let types = carved
+            .into_iter()
+            .map(|(file_type, carved)| {
+                let sampled = files.iter().filter(|f| f.file_type == file_type).count() as u64;
+                QaTypeCounts {
+                    file_type,
+                    carved,
+                    sampled,
+                }
+            })
+            .collect();
+        QaSampleSummary {
+            run_id: run_id.to_string(),
+            rate: self.rate,
+            seed: self.seed,
+            types,
+            files,
+        }
+    }
+}
+
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Write the summary to `<run_output_dir>/summaries/qa_sample.json`.
+pub fn write_summary(run_output_dir: &Path, summary: &QaSampleSummary) -> std::io::Result<PathBuf> {
+    let dir = run_output_dir.join(SUMMARIES_DIR);
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(QA_SAMPLE_SUMMARY_FILE);
+    let writer = BufWriter::new(File::create(&path)?);
+    serde_json::to_writer_pretty(writer, summary)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn carved(file_type: &str, global_start: u64) -> CarvedFile {
+        CarvedFile {
+            run_id: "run".to_string(),
+            file_type: file_type.to_string(),
+            signature_type: file_type.to_string(),
+            path: format!("{file_type}/{file_type}_{global_start:012X}.bin"),
+            extension: "bin".to_string(),
+            global_start,
+            global_end: global_start + 3,
+            size: 4,
+            md5: None,
+            sha256: None,
+            validated: true,
+            truncated: false,
+            errors: Vec::new(),
+            pattern_id: None,
+        }
+    }
+
+    #[test]
+    fn selection_is_seeded_and_near_the_rate() {
+        let sampler = QaSampler::new(0.01, 7);
+        let picked: Vec<u64> = (0..100_000u64)
+            .map(|i| i * 512)
+            .filter(|&offset| sampler.selects("jpeg", offset))
+            .collect();
+        assert!((800..1200).contains(&picked.len()), "{}", picked.len());
+        let again = QaSampler::new(0.01, 7);
+        assert!(picked.iter().all(|&o| again.selects("jpeg", o)));
+        let other_seed = QaSampler::new(0.01, 8);
+        assert!(picked.iter().any(|&o| !other_seed.selects("jpeg", o)));
+        assert!(!QaSampler::new(0.0, 7).selects("jpeg", picked[0]));
+    }
+
+    #[test]
+    fn copies_selected_files_and_lists_them() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let sampler = QaSampler::new(1.0, 0);
+        for (file_type, offset) in [("png", 0x200), ("jpeg", 0x1000), ("jpeg", 0x400)] {
+            let file = carved(file_type, offset);
+            let path = dir.path().join("carved").join(&file.path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, b"data").unwrap();
+            sampler.offer(&file, &path, dir.path());
+        }
+        let summary = sampler.summarize("run");
+        assert_eq!(summary.files.len(), 3);
+        assert_eq!(summary.files[0].file_type, "jpeg");
+        assert_eq!(summary.files[0].global_start, 0x400);
+        assert_eq!(
+            summary.types[0],
+            QaTypeCounts {
+                file_type: "jpeg".to_string(),
+                carved: 2,
+                sampled: 2,
+            }
+        );
+        let copy = dir.path().join(&summary.files[2].sample_path);
+        assert!(copy.starts_with(dir.path().join(QA_SAMPLE_DIR).join("png")));
+        assert_eq!(std::fs::read(copy).unwrap(), b"data");
+    }
+}
@@ -0,0 +1,608 @@
+//! # Quarantine
+//!
+//! Some carves must not sit with the rest of the output: known contraband
+//! from a hash list, or images a classifier labels as such. With
+//! `quarantine_rules` set, [`Quarantine`] checks every carve against the
+//! rules and moves the ones that match into the quarantine area
+//! (`quarantine_dir/<run_id>/`, or `quarantine/` in the run directory) instead
+//! of leaving them under `carved/`. The area is created owner-only (mode
+//! `0700`, files `0600`), and with `quarantine_recipient` (or
+//! `encryption_recipient`) every file is encrypted to that age X25519 key.
+//!
+//! Quarantined carves are left out of the metadata streams; `index.jsonl` in
+//! the quarantine area lists them with the rule that matched, their offsets,
+//! and their digests; it is written encrypted (`index.jsonl.age`) when all
+//! outputs are. Rules on hash lists and file types are checked before
+//! post-processing, so no post-processor sees those files. Rules on
+//! classifier labels are checked after it, with the post-processor records
+//! held back until the carve is cleared.
+
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::carve::{CarvedFile, sanitize_component};
+use crate::config::{Config, QuarantineRule};
+use crate::encryption::{ENCRYPTED_EXT, OutputCipher, OutputFile, parse_recipient, sealed_path};
+use crate::metadata::ClassificationRecord;
+use crate::pipeline::events::MetadataEvent;
+
+/// Directory (relative to the run output directory) of the default
+/// quarantine area.
+pub const QUARANTINE_DIR: &str = "quarantine";
+pub const INDEX_FILE: &str = "index.jsonl";
+
+/// The rule a carve matched and why.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuarantineMatch {
+    pub rule: String,
+    pub reason: String,
+}
+
+/// One line of `index.jsonl`.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct QuarantineEntry {
+    pub run_id: String,
+    pub rule: String,
+    pub reason: String,
+    pub file_type: String,
+    /// Path the carve was written to under `carved/`.
+    pub carved_path: String,
+    /// Path of the stored copy, relative to the quarantine area; `None`
+    /// when it could not be stored and the carve was deleted.
+    pub quarantine_path: Option<String>,
+    pub global_start: u64,
+    pub global_end: u64,
+    pub size: u64,
+    pub validated: bool,
+    pub md5: Option<String>,
+    pub sha256: Option<String>,
+    pub encrypted: bool,
+    pub error: Option<String>,
+}
+
+struct Rule {
+    name: String,
+    hashes: Option<(HashSet<String>, String)>,
+    file_types: Vec<String>,
+    labels: Vec<String>,
+    classifier: Option<String>,
+    min_score: f64,
+}
+
+impl Rule {
+    fn load(rule: &QuarantineRule) -> Result<Self> {
+        let hashes = match &rule.hash_list {
+            Some(path) => {
+                let set = load_hash_list(path)
+                    .with_context(|| format!("read hash list {}", path.display()))?;
+                if set.is_empty() {
+                    warn!(
+                        "hash list {} of quarantine rule `{}` has no MD5 or SHA-256 digests",
+                        path.display(),
+                        rule.name
+                    );
+                }
+                let label = path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                Some((set, label))
+            }
+            None => None,
+        };
+        Ok(Self {
+            name: rule.name.clone(),
+            hashes,
+            file_types: rule.file_types.clone(),
+            labels: rule.labels.clone(),
+            classifier: rule.classifier.clone(),
+            min_score: rule.min_score,
+        })
+    }
+
+    fn needs_labels(&self) -> bool {
+        !self.labels.is_empty()
+    }
+
+    /// Reason the rule matches, or `None`.
+    fn evaluate(
+        &self,
+        file: &CarvedFile,
+        labels: &[&ClassificationRecord],
+        digests: &mut Digests,
+    ) -> Option<String> {
+        let mut reasons = Vec::new();
+        if !self.file_types.is_empty() {
+            if !self.file_types.contains(&file.file_type) {
+                return None;
+            }
+            reasons.push(format!("file type {}", file.file_type));
+        }
+        if self.needs_labels() {
+            let label = labels.iter().find(|record| {
+                self.labels.contains(&record.label)
+                    && record.score >= self.min_score
+                    && self
+                        .classifier
+                        .as_ref()
+                        .is_none_or(|c| c == &record.classifier)
+            })?;
+            reasons.push(format!(
+                "label {} {:.3} from {}",
+                label.label, label.score, label.classifier
+            ));
+        }
+        if let Some((set, list)) = &self.hashes {
+            let (md5, sha256) = digests.get()?;
+            let kind = if set.contains(sha256) {
+                "sha256"
+            } else if set.contains(md5) {
+                "md5"
+            } else {
+                return None;
+            };
+            reasons.push(format!("{kind} in {list}"));
+        }
+        Some(reasons.join("; "))
+    }
+}
+
+/// MD5 and SHA-256 of a carve, read from the file when the carve did not
+/// hash it.
+struct Digests<'a> {
+    file: &'a CarvedFile,
+    path: &'a Path,
+    value: Option<Option<(String, String)>>,
+}
+
+impl<'a> Digests<'a> {
+    fn new(file: &'a CarvedFile, path: &'a Path) -> Self {
+        Self {
+            file,
+            path,
+            value: None,
+        }
+    }
+
+    fn get(&mut self) -> Option<(&str, &str)> {
+        let value = self.value.get_or_insert_with(|| {
+            if let (Some(md5), Some(sha256)) = (&self.file.md5, &self.file.sha256) {
+                return Some((md5.to_ascii_lowercase(), sha256.to_ascii_lowercase()));
+            }
+            match hash_file(self.path) {
+                Ok(digests) => Some(digests),
+                Err(err) => {
+                    warn!(
+                        "failed to hash {} for quarantine: {err}",
+                        self.path.display()
+                    );
+                    None
+                }
+            }
+        });
+        value
+            .as_ref()
+            .map(|(md5, sha256)| (md5.as_str(), sha256.as_str()))
+    }
+}
+
+struct Index {
+    writer: BufWriter<OutputFile>,
+    entries: u64,
+}
+
+/// Rule checks and storage shared by the carve workers.
+pub struct Quarantine {
+    run_id: String,
+    rules: Vec<Rule>,
+    root: PathBuf,
+    cipher: Option<OutputCipher>,
+    /// Set by `encryption_recipient`; the index is written encrypted.
+    index_cipher: Option<OutputCipher>,
+    index: Mutex<Option<Index>>,
+    failed: AtomicU64,
+}
+
+impl Quarantine {
+    /// `None` without rules or without a run output directory (dry runs).
+    /// Fails when a hash list cannot be read or the recipient is invalid.
+    pub fn from_config(cfg: &Config, run_output_dir: &Path) -> Result<Option<Self>> {
+        if cfg.quarantine_rules.is_empty() || !run_output_dir.is_dir() {
+            return Ok(None);
+        }
+        let rules = cfg
+            .quarantine_rules
+            .iter()
+            .map(Rule::load)
+            .collect::<Result<Vec<_>>>()?;
+        let cipher = match &cfg.quarantine_recipient {
+            Some(key) => {
+                Some(OutputCipher::new(parse_recipient(key).map_err(|err| {
+                    anyhow::anyhow!("invalid quarantine_recipient: {err}")
+                })?))
+            }
+            None => OutputCipher::from_config(cfg)?,
+        };
+        let root = match &cfg.quarantine_dir {
+            Some(dir) => dir.join(sanitize_component(&cfg.run_id)),
+            None => run_output_dir.join(QUARANTINE_DIR),
+        };
+        Ok(Some(Self {
+            run_id: cfg.run_id.clone(),
+            rules,
+            root,
+            cipher,
+            index_cipher: OutputCipher::from_config(cfg)?,
+            index: Mutex::new(None),
+            failed: AtomicU64::new(0),
+        }))
+    }
+
+    /// Directory holding quarantined files and the index.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Whether a rule checks classifier labels, so post-processor records
+    /// have to wait for [`Quarantine::screen_labels`].
+    pub fn needs_labels(&self) -> bool {
+        self.rules.iter().any(Rule::needs_labels)
+    }
+
+    /// First rule without label conditions that `file` matches.
+    pub fn screen(&self, file: &CarvedFile, path: &Path) -> Option<QuarantineMatch> {
+        let mut digests = Digests::new(file, path);
+        self.rules
+            .iter()
+            .filter(|rule| !rule.needs_labels())
+            .find_map(|rule| self.matched(rule, file, &[], &mut digests))
+    }
+
+    /// First rule with label conditions that `file` matches, given the
+    /// records its post-processors produced.
+    pub fn screen_labels(
+        &self,
+        file: &CarvedFile,
+        path: &Path,
+        events: &[MetadataEvent],
+    ) -> Option<QuarantineMatch> {
+        let labels: Vec<&ClassificationRecord> = events
+            .iter()
+            .filter_map(|event| match event {
+                MetadataEvent::Classification(record) => Some(record),
+                _ => None,
+            })
+            .collect();
+        let mut digests = Digests::new(file, path);
+        self.rules
+            .iter()
+            .filter(|rule| rule.needs_labels())
+            .find_map(|rule| self.matched(rule, file, &labels, &mut digests))
+    }
+
+    fn matched(
+        &self,
+        rule: &Rule,
+        file: &CarvedFile,
+        labels: &[&ClassificationRecord],
+        digests: &mut Digests,
+    ) -> Option<QuarantineMatch> {
+        rule.evaluate(file, labels, digests)
+            .map(|reason| QuarantineMatch {
+                rule: rule.name.clone(),
+                reason,
+            })
+    }
+
+    /// Move the carve at `path` into the quarantine area and list it in the
+    /// index. A carve that cannot be stored is deleted all the same and
+    /// listed with the error.
+    pub fn isolate(&self, file: &CarvedFile, path: &Path, matched: QuarantineMatch) {
+        let (md5, sha256) = Digests::new(file, path)
+            .get()
+            .map(|(md5, sha256)| (Some(md5.to_string()), Some(sha256.to_string())))
+            .unwrap_or_default();
+        let mut name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| format!("{}_{:012X}", file.file_type, file.global_start));
+        if self.cipher.is_some() {
+            name = format!("{name}.{ENCRYPTED_EXT}");
+        }
+        let rel = Path::new(&sanitize_component(&file.file_type)).join(name);
+        let stored = self.store(path, &self.root.join(&rel));
+        if let Err(err) = fs::remove_file(path)
+            && err.kind() != io::ErrorKind::NotFound
+        {
+            warn!(
+                "failed to remove quarantined carve {}: {err}",
+                path.display()
+            );
+        }
+        if let Err(err) = &stored {
+            self.failed.fetch_add(1, Ordering::Relaxed);
+            warn!("failed to quarantine {}: {err}", path.display());
+        }
+        let entry = QuarantineEntry {
+            run_id: self.run_id.clone(),
+            rule: matched.rule,
+            reason: matched.reason,
+            file_type: file.file_type.clone(),
+            carved_path: file.path.clone(),
+            quarantine_path: stored.is_ok().then(|| rel.to_string_lossy().to_string()),
+            global_start: file.global_start,
+            global_end: file.global_end,
+            size: file.size,
+            validated: file.validated,
+            md5,
+            sha256,
+            encrypted: self.cipher.is_some(),
+            error: stored.err().map(|err| err.to_string()),
+        };
+        if let Err(err) = self.append(&entry) {
+            warn!("failed to write quarantine index: {err}");
+        }
+    }
+
+    fn store(&self, src: &Path, dest: &Path) -> io::Result<()> {
+        if let Some(parent) = dest.parent() {
+            create_private_dir(parent)?;
+        }
+        let output = create_private_file(dest)?;
+        let copied = match &self.cipher {
+            Some(cipher) => cipher.copy_sealed(src, output),
+            None => {
+                let mut output = BufWriter::new(output);
+                File::open(src)
+                    .and_then(|mut input| io::copy(&mut input, &mut output))
+                    .and_then(|_| output.flush())
+            }
+        };
+        if copied.is_err() {
+            let _ = fs::remove_file(dest);
+        }
+        copied
+    }
+
+    fn append(&self, entry: &QuarantineEntry) -> io::Result<()> {
+        let mut index = self
+            .index
+            .lock()
+            .map_err(|_| io::Error::other("quarantine index lock poisoned"))?;
+        if index.is_none() {
+            create_private_dir(&self.root)?;
+            let path = match &self.index_cipher {
+                Some(_) => sealed_path(&self.root.join(INDEX_FILE)),
+                None => self.root.join(INDEX_FILE),
+            };
+            let file = OutputFile::wrap(create_private_file(&path)?, self.index_cipher.as_ref())?;
+            *index = Some(Index {
+                writer: BufWriter::new(file),
+                entries: 0,
+            });
+        }
+        let Some(index) = index.as_mut() else {
+            return Ok(());
+        };
+        serde_json::to_writer(&mut index.writer, entry)?;
+        index.writer.write_all(b"\n")?;
+        index.entries += 1;
+        Ok(())
+    }
+
+    /// Close the index, writing the last chunk when it is encrypted. Returns the
+    /// number of quarantined carves and of those that could not be stored.
+    pub fn finish(&self) -> io::Result<(u64, u64)> {
+        let failed = self.failed.load(Ordering::Relaxed);
+        let Ok(mut index) = self.index.lock() else {
+            return Ok((0, failed));
+        };
+        let Some(Index { writer, entries }) = index.take() else {
+            return Ok((0, failed));
+        };
+        writer
+            .into_inner()
+            .map_err(|err| err.into_error())?
+            .finish()?;
+        Ok((entries, failed))
+    }
+}
+
+fn create_private_dir(dir: &Path) -> io::Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    builder.create(dir)
+}
+
+fn create_private_file(path: &Path) -> io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)
+}
+
+/// Lower-case MD5 and SHA-256 digests in a hash list. The first field of
+/// each line (split on whitespace or commas) is taken; header, comment, and
+/// other lines are skipped.
+fn load_hash_list(path: &Path) -> io::Result<HashSet<String>> {
+    let text = fs::read_to_string(path)?;
+    Ok(text
+        .lines()
+        .filter_map(|line| {
+            line.split(|c: char| c.is_whitespace() || c == ',')
+                .find(|field| !field.is_empty())
+        })
+        .map(|field| field.trim_matches('"'))
+        .filter(|field| {
+            matches!(field.len(), 32 | 64) && field.bytes().all(|b| b.is_ascii_hexdigit())
+        })
+        .map(str::to_ascii_lowercase)
+        .collect())
+}
+
+fn hash_file(path: &Path) -> io::Result<(String, String)> {
+    let mut file = File::open(path)?;
+    let mut md5 = md5::Context::new();
+    let mut sha256 = Sha256::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        md5.consume(&buf[..n]);
+        sha256.update(&buf[..n]);
+    }
+    Ok((
+        format!("{:x}", md5.compute()),
+        hex::encode(sha256.finalize()),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn carved(file_type: &str, global_start: u64) -> CarvedFile {
+        CarvedFile {
+            run_id: "run".to_string(),
+            file_type: file_type.to_string(),
+            signature_type: file_type.to_string(),
+            path: format!("{file_type}/{file_type}_{global_start:012X}.bin"),
+            extension: "bin".to_string(),
+            global_start,
+            global_end: global_start + 3,
+            size: 4,
+            md5: None,
+            sha256: None,
+            validated: true,
+            truncated: false,
+            errors: Vec::new(),
+            pattern_id: None,
+        }
+    }
+
+    fn write_carve(dir: &Path, file: &CarvedFile, data: &[u8]) -> PathBuf {
+        let path = dir.join("carved").join(&file.path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, data).unwrap();
+        path
+    }
+
+    #[test]
+    fn hash_list_matches_are_encrypted_and_indexed() {
+        let dir = tempfile::tempdir().unwrap();
+        let list = dir.path().join("known.csv");
+        let sha256 = hex::encode(Sha256::digest(b"evil"));
+        fs::write(&list, format!("sha256,name\n\"{sha256}\",x\n# comment\n")).unwrap();
+        let identity = age::x25519::Identity::generate();
+        let mut cfg = crate::config::load_config(None).unwrap().config;
+        cfg.run_id = "run".to_string();
+        cfg.quarantine_rules = vec![QuarantineRule {
+            name: "known".to_string(),
+            hash_list: Some(list),
+            ..Default::default()
+        }];
+        cfg.quarantine_recipient = Some(identity.to_public().to_string());
+        let quarantine = Quarantine::from_config(&cfg, dir.path())
+            .unwrap()
+            .expect("enabled");
+        assert!(!quarantine.needs_labels());
+
+        let clean = carved("jpeg", 0x200);
+        let clean_path = write_carve(dir.path(), &clean, b"fine");
+        assert_eq!(quarantine.screen(&clean, &clean_path), None);
+
+        let bad = carved("jpeg", 0x1000);
+        let bad_path = write_carve(dir.path(), &bad, b"evil");
+        let matched = quarantine.screen(&bad, &bad_path).expect("match");
+        assert_eq!(matched.reason, "sha256 in known.csv");
+        quarantine.isolate(&bad, &bad_path, matched);
+        assert_eq!(quarantine.finish().unwrap(), (1, 0));
+        assert!(!bad_path.exists());
+
+        let root = dir.path().join(QUARANTINE_DIR);
+        let index = fs::read_to_string(root.join(INDEX_FILE)).unwrap();
+        let entry: serde_json::Value = serde_json::from_str(index.trim()).unwrap();
+        assert_eq!(entry["rule"], "known");
+        assert_eq!(entry["sha256"], sha256.as_str());
+        let stored = root.join(entry["quarantine_path"].as_str().unwrap());
+        assert_eq!(stored.extension().unwrap(), ENCRYPTED_EXT);
+        let sealed = fs::read(&stored).unwrap();
+        assert_eq!(age::decrypt(&identity, &sealed).unwrap(), b"evil");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = |p: &Path| fs::metadata(p).unwrap().permissions().mode() & 0o777;
+            assert_eq!(mode(&stored), 0o600);
+            assert_eq!(mode(&root), 0o700);
+        }
+    }
+
+    #[test]
+    fn label_rules_need_type_label_and_score() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut cfg = crate::config::load_config(None).unwrap().config;
+        cfg.quarantine_rules = vec![QuarantineRule {
+            name: "nsfw".to_string(),
+            file_types: vec!["jpeg".to_string()],
+            labels: vec!["nudity".to_string()],
+            min_score: 0.8,
+            ..Default::default()
+        }];
+        let quarantine = Quarantine::from_config(&cfg, dir.path())
+            .unwrap()
+            .expect("enabled");
+        assert!(quarantine.needs_labels());
+        let label = |file: &CarvedFile, label: &str, score: f64| {
+            MetadataEvent::Classification(ClassificationRecord {
+                run_id: "run".to_string(),
+                file_type: file.file_type.clone(),
+                path: file.path.clone(),
+                global_start: file.global_start,
+                classifier: "nsfw".to_string(),
+                label: label.to_string(),
+                score,
+                rank: 1,
+            })
+        };
+        let jpeg = carved("jpeg", 0);
+        let png = carved("png", 0);
+        let path = dir.path().join("unused");
+        assert_eq!(quarantine.screen(&jpeg, &path), None);
+        assert_eq!(
+            quarantine.screen_labels(&jpeg, &path, &[label(&jpeg, "nudity", 0.5)]),
+            None
+        );
+        assert_eq!(
+            quarantine.screen_labels(&png, &path, &[label(&png, "nudity", 0.9)]),
+            None
+        );
+        let matched = quarantine
+            .screen_labels(&jpeg, &path, &[label(&jpeg, "nudity", 0.9)])
+            .expect("match");
+        assert_eq!(matched.rule, "nsfw");
+        assert_eq!(
+            matched.reason,
+            "file type jpeg; label nudity 0.900 from nsfw"
+        );
+    }
+}
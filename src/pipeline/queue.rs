@@ -0,0 +1,104 @@
+//! # Stage Queues
+//!
+//! Every stage hands its output to the next one through a [`StageSender`].
+//! Threaded, it wraps a `crossbeam-channel` sender whose receiver the next
+//! stage's workers block on. Single-threaded, it pushes onto an
+//! [`InlineQueue`] that the pipeline thread drains after each chunk, so that
+//! mode runs without channels or extra threads while the stages' code stays
+//! the same.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, Weak};
+
+use crossbeam_channel::{SendError, Sender};
+
+/// Sending end of the queue into a pipeline stage.
+pub struct StageSender<T>(Route<T>);
+
+enum Route<T> {
+    Channel(Sender<T>),
+    Inline(Weak<Mutex<VecDeque<T>>>),
+}
+
+impl<T> Clone for StageSender<T> {
+    fn clone(&self) -> Self {
+        Self(match &self.0 {
+            Route::Channel(tx) => Route::Channel(tx.clone()),
+            Route::Inline(queue) => Route::Inline(queue.clone()),
+        })
+    }
+}
+
+impl<T> From<Sender<T>> for StageSender<T> {
+    fn from(tx: Sender<T>) -> Self {
+        Self(Route::Channel(tx))
+    }
+}
+
+impl<T> StageSender<T> {
+    /// Queue `value` for the next stage. As with a channel, this fails once
+    /// the receiving end has been dropped.
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        match &self.0 {
+            Route::Channel(tx) => tx.send(value),
+            Route::Inline(queue) => match queue.upgrade() {
+                Some(queue) => {
+                    queue
+                        .lock()
+                        .unwrap_or_else(|poisoned| poisoned.into_inner())
+                        .push_back(value);
+                    Ok(())
+                }
+                None => Err(SendError(value)),
+            },
+        }
+    }
+}
+
+/// A queue drained by the thread that also fills it, and the sender that
+/// feeds it. It is unbounded: a full queue would block the only thread that
+/// empties it.
+pub fn inline<T>() -> (StageSender<T>, InlineQueue<T>) {
+    let queue = Arc::new(Mutex::new(VecDeque::new()));
+    let tx = StageSender(Route::Inline(Arc::downgrade(&queue)));
+    (tx, InlineQueue(queue))
+}
+
+/// Receiving end of an [`inline`] queue.
+pub struct InlineQueue<T>(Arc<Mutex<VecDeque<T>>>);
+
+impl<T> InlineQueue<T> {
+    /// Take the oldest queued value, if any.
+    pub fn pop(&self) -> Option<T> {
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .pop_front()
+    }
+
+    /// Take everything queued so far.
+    pub fn drain(&self) -> Vec<T> {
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .drain(..)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inline_queue_keeps_order_and_closes_when_dropped() {
+        let (tx, queue) = inline();
+        tx.send(1).expect("send");
+        tx.clone().send(2).expect("send");
+        assert_eq!(queue.pop(), Some(1));
+        assert_eq!(queue.pop(), Some(2));
+        assert_eq!(queue.pop(), None);
+        drop(queue);
+        assert!(tx.send(3).is_err());
+    }
+}
@@ -0,0 +1,372 @@
+//! # Resource Accounting
+//!
+//! Per-stage CPU time, I/O bytes, and GPU time, plus process peak memory, for
+//! capacity planning. Each worker thread adds its own usage when it exits:
+//! user and system CPU from `getrusage(RUSAGE_THREAD)` and bytes from
+//! `/proc/thread-self/io` (`rchar`/`wchar`, so page-cache hits count as reads).
+//! The read stage is the main thread's usage over the chunk loop. Per-thread
+//! counters need Linux; elsewhere those fields are reported as null.
+//!
+//! The metadata thread closes the ledger when it records the run summary, the
+//! last event of a run, so the rows it writes cover every stage.
+//!
+//! GPU time is the wall time spent in scanner calls of GPU-backed scanners,
+//! including transfers.
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::metadata::ResourceUsage;
+use crate::report::SUMMARIES_DIR;
+
+use super::eta::StageCosts;
+
+/// File name of the resource summary under `summaries/`.
+pub const RESOURCE_SUMMARY_FILE: &str = "resource_usage.json";
+
+/// Pipeline stages with their own threads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Read,
+    Scan,
+    Strings,
+    Carve,
+    Metadata,
+}
+
+impl Stage {
+    const ALL: [Stage; 5] = [
+        Stage::Read,
+        Stage::Scan,
+        Stage::Strings,
+        Stage::Carve,
+        Stage::Metadata,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Stage::Read => "read",
+            Stage::Scan => "scan",
+            Stage::Strings => "strings",
+            Stage::Carve => "carve",
+            Stage::Metadata => "metadata",
+        }
+    }
+}
+
+/// CPU and I/O counters of one thread or of the whole process.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ThreadUsage {
+    pub user_secs: f64,
+    pub sys_secs: f64,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+}
+
+impl ThreadUsage {
+    /// Counters of the calling thread since it started.
+    pub fn current_thread() -> Option<Self> {
+        #[cfg(target_os = "linux")]
+        {
+            let (user_secs, sys_secs) = rusage(libc::RUSAGE_THREAD)?;
+            let (bytes_read, bytes_written) = proc_io("/proc/thread-self/io")?;
+            Some(Self {
+                user_secs,
+                sys_secs,
+                bytes_read,
+                bytes_written,
+            })
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            None
+        }
+    }
+
+    /// Counters of the whole process since it started.
+    pub fn process() -> Option<Self> {
+        #[cfg(target_os = "linux")]
+        {
+            let (user_secs, sys_secs) = rusage(libc::RUSAGE_SELF)?;
+            let (bytes_read, bytes_written) = proc_io("/proc/self/io")?;
+            Some(Self {
+                user_secs,
+                sys_secs,
+                bytes_read,
+                bytes_written,
+            })
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            None
+        }
+    }
+
+    /// Usage accumulated since `earlier` was taken on the same thread.
+    pub fn since(self, earlier: Self) -> Self {
+        Self {
+            user_secs: (self.user_secs - earlier.user_secs).max(0.0),
+            sys_secs: (self.sys_secs - earlier.sys_secs).max(0.0),
+            bytes_read: self.bytes_read.saturating_sub(earlier.bytes_read),
+            bytes_written: self.bytes_written.saturating_sub(earlier.bytes_written),
+        }
+    }
+}
+
+/// Peak resident set size of the process in bytes.
+pub fn peak_rss_bytes() -> Option<u64> {
+    #[cfg(unix)]
+    {
+        let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+        if unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) } != 0 {
+            return None;
+        }
+        let max_rss = u64::try_from(usage.ru_maxrss).ok()?;
+        // Linux reports KiB, macOS bytes.
+        if cfg!(target_os = "macos") {
+            Some(max_rss)
+        } else {
+            Some(max_rss.saturating_mul(1024))
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        None
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn rusage(who: libc::c_int) -> Option<(f64, f64)> {
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    if unsafe { libc::getrusage(who, &mut usage) } != 0 {
+        return None;
+    }
+    let secs = |tv: libc::timeval| tv.tv_sec as f64 + tv.tv_usec as f64 / 1e6;
+    Some((secs(usage.ru_utime), secs(usage.ru_stime)))
+}
+
+#[cfg(target_os = "linux")]
+fn proc_io(path: &str) -> Option<(u64, u64)> {
+    parse_proc_io(&std::fs::read_to_string(path).ok()?)
+}
+
+/// `rchar` and `wchar` from a `/proc/<pid>/io` file.
+fn parse_proc_io(text: &str) -> Option<(u64, u64)> {
+    let field = |name: &str| {
+        text.lines()
+            .find_map(|line| line.strip_prefix(name)?.trim().parse::<u64>().ok())
+    };
+    Some((field("rchar:")?, field("wchar:")?))
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct StageTotals {
+    threads: u64,
+    /// Threads that exited without counters (unsupported platform).
+    unmeasured: u64,
+    usage: ThreadUsage,
+    gpu_secs: Option<f64>,
+}
+
+/// Per-stage resource totals shared by the pipeline threads.
+#[derive(Debug)]
+pub struct ResourceLedger {
+    started: Instant,
+    stages: Mutex<[StageTotals; 5]>,
+    finished: Mutex<Vec<ResourceUsage>>,
+}
+
+impl Default for ResourceLedger {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            stages: Mutex::default(),
+            finished: Mutex::default(),
+        }
+    }
+}
+
+impl ResourceLedger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the usage of a finished thread (or of the reader loop) to `stage`.
+    pub fn add(&self, stage: Stage, usage: Option<ThreadUsage>) {
+        let Ok(mut stages) = self.stages.lock() else {
+            return;
+        };
+        let totals = &mut stages[stage as usize];
+        totals.threads += 1;
+        match usage {
+            Some(usage) => {
+                totals.usage.user_secs += usage.user_secs;
+                totals.usage.sys_secs += usage.sys_secs;
+                totals.usage.bytes_read += usage.bytes_read;
+                totals.usage.bytes_written += usage.bytes_written;
+            }
+            None => totals.unmeasured += 1,
+        }
+    }
+
+    /// Add the calling thread's usage to `stage`; call as the thread exits.
+    pub fn add_current_thread(&self, stage: Stage) {
+        self.add(stage, ThreadUsage::current_thread());
+    }
+
+    /// Record time spent in a GPU-backed scanner call.
+    pub fn record_gpu(&self, stage: Stage, elapsed: Duration) {
+        if let Ok(mut stages) = self.stages.lock() {
+            let gpu = stages[stage as usize].gpu_secs.get_or_insert(0.0);
+            *gpu += elapsed.as_secs_f64();
+        }
+    }
+
+    /// Close the ledger: build the rows with the wall time since the ledger
+    /// was created and keep them for [`ResourceLedger::finished`].
+    pub fn finish(&self, run_id: &str, costs: &StageCosts) -> Vec<ResourceUsage> {
+        let records = self.records(run_id, costs, self.started.elapsed().as_secs_f64());
+        if let Ok(mut finished) = self.finished.lock() {
+            *finished = records.clone();
+        }
+        records
+    }
+
+    /// Rows built by [`ResourceLedger::finish`]; empty if it was never called.
+    pub fn finished(&self) -> Vec<ResourceUsage> {
+        self.finished
+            .lock()
+            .map(|rows| rows.clone())
+            .unwrap_or_default()
+    }
+
+    /// One row per stage plus a `process` row with run totals.
+    pub fn records(&self, run_id: &str, costs: &StageCosts, wall_secs: f64) -> Vec<ResourceUsage> {
+        let stages = self.stages.lock().map(|s| *s).unwrap_or_default();
+        let mut records: Vec<ResourceUsage> = Stage::ALL
+            .iter()
+            .map(|&stage| {
+                let totals = stages[stage as usize];
+                let measured = totals.threads > 0 && totals.unmeasured == 0;
+                let busy_secs = match stage {
+                    Stage::Read => costs.read_secs,
+                    Stage::Scan => costs.scan_secs,
+                    Stage::Strings => costs.string_secs,
+                    Stage::Carve => costs.carve_secs,
+                    Stage::Metadata => costs.metadata_secs,
+                };
+                ResourceUsage {
+                    run_id: run_id.to_string(),
+                    stage: stage.name().to_string(),
+                    threads: totals.threads,
+                    busy_secs,
+                    user_cpu_secs: measured.then_some(totals.usage.user_secs),
+                    sys_cpu_secs: measured.then_some(totals.usage.sys_secs),
+                    bytes_read: measured.then_some(totals.usage.bytes_read),
+                    bytes_written: measured.then_some(totals.usage.bytes_written),
+                    gpu_secs: totals.gpu_secs,
+                    peak_rss_bytes: None,
+                }
+            })
+            .collect();
+
+        let process = ThreadUsage::process();
+        records.push(ResourceUsage {
+            run_id: run_id.to_string(),
+            stage: "process".to_string(),
+            threads: stages.iter().map(|s| s.threads).sum(),
+            busy_secs: wall_secs,
+            user_cpu_secs: process.map(|u| u.user_secs),
+            sys_cpu_secs: process.map(|u| u.sys_secs),
+            bytes_read: process.map(|u| u.bytes_read),
+            bytes_written: process.map(|u| u.bytes_written),
+            gpu_secs: stages
+                .iter()
+                .filter_map(|s| s.gpu_secs)
+                .reduce(|a, b| a + b),
+            peak_rss_bytes: peak_rss_bytes(),
+        });
+        records
+    }
+}
+
+/// Contents of `summaries/resource_usage.json`.
+#[derive(Debug, Serialize)]
+pub struct ResourceSummary<'a> {
+    pub run_id: &'a str,
+    pub stages: &'a [ResourceUsage],
+}
+
+/// Write the summary to `<run_output_dir>/summaries/resource_usage.json`.
+pub fn write_summary(run_output_dir: &Path, summary: &ResourceSummary) -> std::io::Result<PathBuf> {
+    let dir = run_output_dir.join(SUMMARIES_DIR);
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(RESOURCE_SUMMARY_FILE);
+    let writer = BufWriter::new(File::create(&path)?);
+    serde_json::to_writer_pretty(writer, summary)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_proc_io() {
+        let text = "rchar: 4096\nwchar: 512\nsyscr: 3\nsyscw: 1\nread_bytes: 0\n";
+        assert_eq!(parse_proc_io(text), Some((4096, 512)));
+        assert_eq!(parse_proc_io("rchar: 1\n"), None);
+    }
+
+    #[test]
+    fn unmeasured_thread_nulls_stage_counters() {
+        let ledger = ResourceLedger::new();
+        let usage = ThreadUsage {
+            user_secs: 1.5,
+            sys_secs: 0.25,
+            bytes_read: 1000,
+            bytes_written: 10,
+        };
+        ledger.add(Stage::Scan, Some(usage));
+        ledger.add(Stage::Scan, Some(usage));
+        ledger.add(Stage::Carve, Some(usage));
+        ledger.add(Stage::Carve, None);
+        ledger.record_gpu(Stage::Scan, Duration::from_millis(500));
+
+        let records = ledger.records("run", &StageCosts::default(), 2.0);
+        let scan = records.iter().find(|r| r.stage == "scan").expect("scan");
+        assert_eq!(scan.threads, 2);
+        assert_eq!(scan.user_cpu_secs, Some(3.0));
+        assert_eq!(scan.bytes_read, Some(2000));
+        assert_eq!(scan.gpu_secs, Some(0.5));
+        let carve = records.iter().find(|r| r.stage == "carve").expect("carve");
+        assert_eq!(carve.user_cpu_secs, None);
+        assert_eq!(carve.gpu_secs, None);
+        let process = records.last().expect("process");
+        assert_eq!(process.stage, "process");
+        assert_eq!(process.threads, 4);
+        assert_eq!(process.busy_secs, 2.0);
+        assert_eq!(process.gpu_secs, Some(0.5));
+
+        assert!(ledger.finished().is_empty());
+        let rows = ledger.finish("run", &StageCosts::default());
+        assert_eq!(ledger.finished().len(), rows.len());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn reads_linux_counters() {
+        let path = std::env::temp_dir().join("swiftbeaver_resources_test.bin");
+        let before = ThreadUsage::current_thread().expect("thread usage");
+        std::fs::write(&path, vec![0u8; 8192]).expect("write");
+        let after = ThreadUsage::current_thread().expect("thread usage");
+        let _ = std::fs::remove_file(&path);
+        assert!(after.since(before).bytes_written >= 8192);
+        assert!(peak_rss_bytes().unwrap_or(0) > 0);
+    }
+}
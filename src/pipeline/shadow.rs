@@ -0,0 +1,428 @@
+//! # Shadow Copy Scanning
+//!
+//! Volume Shadow Copies keep the old contents of every 16 KiB block that
+//! changed after a snapshot was taken, which is often the unedited original
+//! of a file. With `enable_vss_scan`, once the evidence has been carved,
+//! [`ShadowScan`] finds the NTFS volumes with shadow copy stores
+//! (`parsers::vss`) and scans each snapshot differentially: only the blocks
+//! its own store copied are searched for signatures, since every other block
+//! is either the current data (already scanned) or a newer store's copy
+//! (scanned with that snapshot). Carving reads through the snapshot view
+//! ([`ShadowCopySource`]), so a file may continue into unchanged blocks.
+//!
+//! Files are written under `carved/vss/<store GUID>/`, and their
+//! `global_start`/`global_end` are snapshot volume offsets plus the volume's
+//! evidence offset. The stores and per-snapshot counts are written to
+//! `summaries/shadow_copies.json`.
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::thread;
+
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::carve::{self, CarveRegistry, ExtractionContext};
+use crate::chunk::ScanChunk;
+use crate::config::Config;
+use crate::evidence::{EvidenceSource, ShadowCopySource};
+use crate::parsers::vss::{self, ShadowStore};
+use crate::report::SUMMARIES_DIR;
+use crate::scanner::{NormalizedHit, SignatureScanner};
+
+use super::events::MetadataEvent;
+use super::queue::StageSender;
+
+/// File name of the shadow copy summary under `summaries/`.
+pub const SHADOW_SUMMARY_FILE: &str = "shadow_copies.json";
+/// Output directory under `carved/`.
+const OUTPUT_DIR: &str = "vss";
+
+/// Shadow copies found and scanned, written at the end of a run.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ShadowSummary {
+    pub run_id: String,
+    pub volumes: Vec<VolumeSummary>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct VolumeSummary {
+    /// Evidence offset of the NTFS volume.
+    pub volume_offset: u64,
+    pub volume_size: u64,
+    /// Why the stores could not be read, if they could not.
+    pub error: Option<String>,
+    /// Oldest first.
+    pub snapshots: Vec<SnapshotSummary>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct SnapshotSummary {
+    pub store_id: String,
+    pub sequence: u64,
+    pub created: Option<String>,
+    /// 16 KiB blocks the store copied, which were scanned.
+    pub changed_blocks: u64,
+    pub hits: u64,
+    pub files_carved: u64,
+}
+
+#[derive(Default)]
+struct SnapshotCounts {
+    hits: AtomicU64,
+    files: AtomicU64,
+}
+
+/// A run of consecutive changed blocks of one snapshot.
+struct Job {
+    volume: usize,
+    snapshot: usize,
+    start: u64,
+    len: u64,
+}
+
+struct Volume {
+    offset: u64,
+    size: u64,
+    error: Option<String>,
+    stores: Arc<Vec<ShadowStore>>,
+    snapshots: Vec<ShadowCopySource>,
+    counts: Vec<SnapshotCounts>,
+}
+
+/// Differential scan of the shadow copies in the evidence.
+pub struct ShadowScan {
+    scanner: Arc<dyn SignatureScanner>,
+    registry: Arc<CarveRegistry>,
+    evidence: Arc<dyn EvidenceSource>,
+    run_id: String,
+    max_files: Option<u64>,
+    carved_root: PathBuf,
+    meta_tx: StageSender<MetadataEvent>,
+    files_carved: Arc<AtomicU64>,
+}
+
+impl ShadowScan {
+    pub fn new(
+        cfg: &Config,
+        scanner: Arc<dyn SignatureScanner>,
+        registry: Arc<CarveRegistry>,
+        evidence: Arc<dyn EvidenceSource>,
+        run_output_dir: &Path,
+        meta_tx: StageSender<MetadataEvent>,
+        files_carved: Arc<AtomicU64>,
+    ) -> Self {
+        Self {
+            scanner,
+            registry,
+            evidence,
+            run_id: cfg.run_id.clone(),
+            max_files: cfg.max_files,
+            carved_root: run_output_dir.join("carved"),
+            meta_tx,
+            files_carved,
+        }
+    }
+
+    /// Find and scan the snapshots with `workers` threads (on the calling
+    /// thread with 0), in runs of at most `chunk_size` bytes read with
+    /// `overlap` bytes past their end.
+    pub fn run(
+        &self,
+        workers: usize,
+        chunk_size: u64,
+        overlap: u64,
+        cancel: Option<&AtomicBool>,
+    ) -> ShadowSummary {
+        let volumes = self.discover();
+        let jobs = plan_jobs(&volumes, chunk_size);
+        if !jobs.is_empty() {
+            info!(
+                "shadow copies: scanning {} snapshot(s) on {} volume(s), {} run(s) of changed blocks",
+                volumes.iter().map(|v| v.snapshots.len()).sum::<usize>(),
+                volumes.len(),
+                jobs.len()
+            );
+        }
+        let next = AtomicUsize::new(0);
+        let drain = || {
+            while let Some(job) = jobs.get(next.fetch_add(1, Ordering::Relaxed)) {
+                if cancel.is_some_and(|flag| flag.load(Ordering::Relaxed)) || self.limit_reached() {
+                    break;
+                }
+                self.scan_job(&volumes[job.volume], job, overlap);
+            }
+        };
+        if workers == 0 {
+            drain();
+        } else {
+            thread::scope(|scope| {
+                for _ in 0..workers {
+                    scope.spawn(drain);
+                }
+            });
+        }
+        self.summarize(&volumes)
+    }
+
+    fn discover(&self) -> Vec<Volume> {
+        vss::find_ntfs_volumes(self.evidence.as_ref())
+            .into_iter()
+            .map(|volume| {
+                let (stores, error) = match vss::read_stores(self.evidence.as_ref(), volume.offset)
+                {
+                    Ok(stores) => (stores, None),
+                    Err(err) => {
+                        warn!(
+                            "shadow copies on the volume at {} are unreadable: {err}",
+                            volume.offset
+                        );
+                        (Vec::new(), Some(err.to_string()))
+                    }
+                };
+                let stores = Arc::new(stores);
+                let snapshots = (0..stores.len())
+                    .map(|index| {
+                        ShadowCopySource::new(
+                            self.evidence.clone(),
+                            volume.offset,
+                            stores.clone(),
+                            index,
+                        )
+                    })
+                    .collect();
+                Volume {
+                    offset: volume.offset,
+                    size: volume.size,
+                    error,
+                    counts: (0..stores.len())
+                        .map(|_| SnapshotCounts::default())
+                        .collect(),
+                    stores,
+                    snapshots,
+                }
+            })
+            .collect()
+    }
+
+    fn limit_reached(&self) -> bool {
+        self.max_files
+            .is_some_and(|limit| self.files_carved.load(Ordering::Relaxed) >= limit)
+    }
+
+    fn scan_job(&self, volume: &Volume, job: &Job, overlap: u64) {
+        let snapshot = &volume.snapshots[job.snapshot];
+        let store_id = &snapshot.store().store_id;
+        let read_len = (job.len + overlap).min(snapshot.len().saturating_sub(job.start));
+        let mut data = vec![0u8; read_len as usize];
+        let data = match snapshot.read_at(job.start, &mut data) {
+            Ok(n) => &data[..n],
+            Err(err) => {
+                warn!(
+                    "read error in shadow copy {store_id} at volume offset {}: {err}",
+                    job.start
+                );
+                return;
+            }
+        };
+        let chunk = ScanChunk {
+            id: 0,
+            start: job.start,
+            length: data.len() as u64,
+            valid_length: job.len,
+        };
+        let hits: Vec<_> = self
+            .scanner
+            .scan_chunk(&chunk, data)
+            .into_iter()
+            .filter(|hit| hit.local_offset < job.len)
+            .collect();
+        let counts = &volume.counts[job.snapshot];
+        counts.hits.fetch_add(hits.len() as u64, Ordering::Relaxed);
+
+        let dir = format!("{OUTPUT_DIR}/{store_id}");
+        let output_root = self.carved_root.join(&dir);
+        let ctx = ExtractionContext {
+            run_id: &self.run_id,
+            output_root: &output_root,
+            evidence: snapshot,
+        };
+        for hit in hits {
+            if self.limit_reached() {
+                return;
+            }
+            let Some(handler) = self.registry.get(&hit.file_type_id) else {
+                continue;
+            };
+            let hit = NormalizedHit {
+                global_offset: job.start + hit.local_offset,
+                file_type_id: hit.file_type_id,
+                pattern_id: hit.pattern_id,
+            };
+            let mut file = match carve::confine(&output_root, handler.process_hit(&hit, &ctx)) {
+                Ok(Some(file)) => file,
+                Ok(None) => continue,
+                Err(err) => {
+                    warn!(
+                        "carve error in shadow copy {store_id} at volume offset {}: {err}",
+                        hit.global_offset
+                    );
+                    continue;
+                }
+            };
+            file.global_start += volume.offset;
+            file.global_end += volume.offset;
+            file.path = format!("{dir}/{}", file.path);
+            self.files_carved.fetch_add(1, Ordering::Relaxed);
+            counts.files.fetch_add(1, Ordering::Relaxed);
+            if !self.registry.keeps(&hit.file_type_id, &file) {
+                carve::discard(&self.carved_root.join(&file.path));
+            }
+            if let Err(err) = self.meta_tx.send(MetadataEvent::File(file)) {
+                warn!("metadata channel closed while sending carved file: {err}");
+                return;
+            }
+        }
+    }
+
+    fn summarize(&self, volumes: &[Volume]) -> ShadowSummary {
+        ShadowSummary {
+            run_id: self.run_id.clone(),
+            volumes: volumes
+                .iter()
+                .map(|volume| VolumeSummary {
+                    volume_offset: volume.offset,
+                    volume_size: volume.size,
+                    error: volume.error.clone(),
+                    snapshots: volume
+                        .stores
+                        .iter()
+                        .zip(&volume.counts)
+                        .map(|(store, counts)| SnapshotSummary {
+                            store_id: store.store_id.clone(),
+                            sequence: store.sequence,
+                            created: store.created.map(|t| t.and_utc().to_rfc3339()),
+                            changed_blocks: store.changed_blocks().len() as u64,
+                            hits: counts.hits.load(Ordering::Relaxed),
+                            files_carved: counts.files.load(Ordering::Relaxed),
+                        })
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Split each snapshot's changed blocks into runs of consecutive blocks of
+/// at most `chunk_size` bytes.
+fn plan_jobs(volumes: &[Volume], chunk_size: u64) -> Vec<Job> {
+    let max_len = chunk_size.max(vss::BLOCK_SIZE);
+    let mut jobs: Vec<Job> = Vec::new();
+    for (v, volume) in volumes.iter().enumerate() {
+        for (s, store) in volume.stores.iter().enumerate() {
+            for block in store.changed_blocks() {
+                match jobs.last_mut() {
+                    Some(job)
+                        if job.volume == v
+                            && job.snapshot == s
+                            && job.start + job.len == block
+                            && job.len + vss::BLOCK_SIZE <= max_len =>
+                    {
+                        job.len += vss::BLOCK_SIZE;
+                    }
+                    _ => jobs.push(Job {
+                        volume: v,
+                        snapshot: s,
+                        start: block,
+                        len: vss::BLOCK_SIZE,
+                    }),
+                }
+            }
+        }
+    }
+    jobs
+}
+
+/// Write the summary to `<run_output_dir>/summaries/shadow_copies.json`.
+pub fn write_summary(run_output_dir: &Path, summary: &ShadowSummary) -> std::io::Result<PathBuf> {
+    let dir = run_output_dir.join(SUMMARIES_DIR);
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(SHADOW_SUMMARY_FILE);
+    let writer = BufWriter::new(File::create(&path)?);
+    serde_json::to_writer_pretty(writer, summary)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::carve::CarvedFile;
+    use crate::config;
+    use crate::evidence::MemorySource;
+    use crate::parsers::vss::testing::volume;
+    use crate::pipeline::testing::{MagicScanner, magic_registry};
+
+    const BLOCK: usize = vss::BLOCK_SIZE as usize;
+
+    #[test]
+    fn carves_changed_blocks_through_the_snapshot_view() {
+        let mut old = vec![b'o'; BLOCK];
+        old[100..108].copy_from_slice(b"MAGIC!!!");
+        // Continues into the next block, which only the newer store copied.
+        old[BLOCK - 4..].copy_from_slice(b"MAGI");
+        let mut newer = vec![b'n'; BLOCK];
+        newer[..4].copy_from_slice(b"C!!!");
+        let image = volume(
+            64 * BLOCK,
+            |_| b'c',
+            &[
+                vec![(2 * BLOCK as u64, 0, 0, old)],
+                vec![(3 * BLOCK as u64, 0, 0, newer)],
+            ],
+        );
+
+        let dir = tempfile::tempdir().expect("tempdir");
+        let cfg = config::load_config(None).expect("config").config;
+        let (meta_tx, meta_rx) = crossbeam_channel::unbounded();
+        let meta_tx = meta_tx.into();
+        let scan = ShadowScan::new(
+            &cfg,
+            Arc::new(MagicScanner),
+            Arc::new(magic_registry()),
+            Arc::new(MemorySource::new(image)),
+            dir.path(),
+            meta_tx,
+            Arc::new(AtomicU64::new(0)),
+        );
+        let summary = scan.run(2, 1024 * 1024, 64, None);
+
+        let [volume] = summary.volumes.as_slice() else {
+            panic!("one volume expected");
+        };
+        let counts: Vec<(u64, u64, u64)> = volume
+            .snapshots
+            .iter()
+            .map(|s| (s.changed_blocks, s.hits, s.files_carved))
+            .collect();
+        assert_eq!(counts, vec![(1, 2, 2), (1, 0, 0)]);
+        let oldest = &volume.snapshots[0].store_id;
+
+        let mut files: Vec<CarvedFile> = meta_rx
+            .try_iter()
+            .filter_map(|event| match event {
+                MetadataEvent::File(file) => Some(file),
+                _ => None,
+            })
+            .collect();
+        files.sort_by_key(|f| f.global_start);
+        let starts: Vec<u64> = files.iter().map(|f| f.global_start).collect();
+        assert_eq!(starts, vec![2 * BLOCK as u64 + 100, 3 * BLOCK as u64 - 4]);
+        assert_eq!(
+            files[0].path,
+            format!("vss/{oldest}/magic/{}.bin", 2 * BLOCK + 100)
+        );
+    }
+}
@@ -0,0 +1,123 @@
+//! # Single-Threaded Stages
+//!
+//! With `pipeline_mode: single_threaded` the scan, carve, string, and
+//! metadata stages run on the pipeline's own thread. Each chunk is scanned,
+//! its hits carved in the order the scanner reported them, its string spans
+//! extracted, and the resulting records written before the next chunk is
+//! read, so at most one chunk and its records are held in memory and a run
+//! over the same evidence writes the same records in the same order.
+//!
+//! The stages are the workers' own per-job code ([`ScanWorker`],
+//! [`CarveWorker`], [`StringWorker`], [`MetadataRecorder`]). They send into
+//! [`InlineQueue`]s instead of channels, which are drained on this thread and
+//! never block, and no thread is spawned. Each job runs under [`supervise`],
+//! so a panic drops that job as it does on a worker.
+
+use std::sync::Arc;
+
+use crate::report::MetadataAggregates;
+use crate::scanner::NormalizedHit;
+
+use super::events::MetadataEvent;
+use super::gaps::DeferredSpans;
+use super::queue::InlineQueue;
+use super::supervisor::{PanicLog, supervise};
+use super::workers::{CarveWorker, MetadataRecorder, ScanJob, ScanWorker, StringJob, StringWorker};
+
+/// The pipeline stages, run one job at a time on the calling thread.
+pub struct InlineStages {
+    pub scan: ScanWorker,
+    pub carve: CarveWorker,
+    pub strings: StringWorker,
+    pub hits: InlineQueue<NormalizedHit>,
+    pub string_jobs: Option<InlineQueue<StringJob>>,
+    pub meta: InlineQueue<MetadataEvent>,
+    pub recorder: MetadataRecorder,
+    /// Spans held back for the gap pass (`string_scan_gaps_only`).
+    pub deferred: Option<DeferredSpans>,
+    pub panics: Arc<PanicLog>,
+}
+
+impl InlineStages {
+    /// Scan one chunk and carry everything it produced through the other
+    /// stages.
+    pub fn scan(&mut self, job: ScanJob) {
+        let mut job = Some(job);
+        supervise("scan", &self.panics, |cursor| {
+            if let Some(job) = job.take() {
+                cursor.begin(job.chunk.start);
+                self.scan.scan(&job);
+                cursor.end();
+            }
+        });
+        self.drain();
+    }
+
+    /// Extract the artefacts of one string job and record them.
+    pub fn extract(&mut self, job: StringJob) {
+        let mut job = Some(job);
+        supervise("string", &self.panics, |cursor| {
+            if let Some(job) = job.take() {
+                cursor.begin(job.chunk.start);
+                self.strings.extract(job);
+                cursor.end();
+            }
+        });
+        self.drain();
+    }
+
+    /// Spans held back for the gap pass.
+    pub fn take_deferred(&mut self) -> DeferredSpans {
+        self.deferred.take().unwrap_or_default()
+    }
+
+    /// Record what is still queued and what the recorder holds back.
+    pub fn finish(mut self) -> MetadataAggregates {
+        self.drain();
+        self.recorder.finish()
+    }
+
+    /// Carve queued hits, extract or defer queued string jobs, and record
+    /// queued metadata events. As on the workers, a panic drops one job and
+    /// the queue is drained on.
+    pub fn drain(&mut self) {
+        let Self {
+            carve,
+            strings,
+            hits,
+            string_jobs,
+            meta,
+            recorder,
+            deferred,
+            panics,
+            ..
+        } = self;
+        supervise("carve", panics, |cursor| {
+            // Once `max_files` is reached the carve skips every further
+            // hit itself, so its flow is not needed here.
+            while let Some(hit) = hits.pop() {
+                cursor.begin(hit.global_offset);
+                let _ = carve.carve(hit);
+            }
+        });
+        if let Some(string_jobs) = string_jobs {
+            supervise("string", panics, |cursor| {
+                while let Some(job) = string_jobs.pop() {
+                    match deferred {
+                        Some(deferred) => deferred.add(&job.chunk, job.spans),
+                        None => {
+                            cursor.begin(job.chunk.start);
+                            strings.extract(job);
+                            cursor.end();
+                        }
+                    }
+                }
+            });
+        }
+        supervise("metadata", panics, |_| {
+            while let Some(event) = meta.pop() {
+                recorder.record(event);
+            }
+        });
+    }
+}
@@ -0,0 +1,192 @@
+//! # Worker Supervision
+//!
+//! Pipeline workers run under [`supervise`], which catches panics, records the
+//! offending offset in a shared [`PanicLog`], and restarts the worker loop with
+//! fresh state on the same thread. The job that triggered the panic is dropped;
+//! the rest of the queue keeps being processed, so a pathological input costs
+//! one hit or chunk instead of a worker for the rest of the run.
+
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Offsets of at most this many panics are kept for the end-of-run summary.
+const MAX_RECORDED_PANICS: usize = 100;
+
+/// Sentinel for "no job in progress".
+const NO_OFFSET: u64 = u64::MAX;
+
+/// A panic caught in a pipeline worker.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkerPanic {
+    /// Pipeline stage (`scan`, `carve`, `string`, `metadata`).
+    pub stage: &'static str,
+    /// Evidence offset of the job being processed, when known.
+    pub offset: Option<u64>,
+    pub message: String,
+}
+
+/// Shared record of worker panics for a run.
+#[derive(Debug, Default)]
+pub struct PanicLog {
+    count: AtomicU64,
+    records: Mutex<Vec<WorkerPanic>>,
+}
+
+impl PanicLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    /// Recorded panics (capped; see `count` for the total).
+    pub fn records(&self) -> Vec<WorkerPanic> {
+        self.records.lock().map(|r| r.clone()).unwrap_or_default()
+    }
+
+    fn record(&self, panic: WorkerPanic) {
+        self.count.fetch_add(1, Ordering::Relaxed);
+        if let Ok(mut records) = self.records.lock()
+            && records.len() < MAX_RECORDED_PANICS
+        {
+            records.push(panic);
+        }
+    }
+
+    /// One-line summary for the end-of-run warning, or `None` if nothing panicked.
+    pub fn summary(&self) -> Option<String> {
+        let count = self.count();
+        if count == 0 {
+            return None;
+        }
+        let records = self.records();
+        let details: Vec<String> = records
+            .iter()
+            .map(|p| match p.offset {
+                Some(offset) => format!("{}@0x{offset:X}: {}", p.stage, p.message),
+                None => format!("{}: {}", p.stage, p.message),
+            })
+            .collect();
+        let more = count.saturating_sub(records.len() as u64);
+        let suffix = if more > 0 {
+            format!(" (+{more} more)")
+        } else {
+            String::new()
+        };
+        Some(format!(
+            "{count} worker panic(s) contained: {}{suffix}",
+            details.join("; ")
+        ))
+    }
+}
+
+/// Tracks the offset of the job a worker is processing.
+#[derive(Debug)]
+pub struct JobCursor(AtomicU64);
+
+impl JobCursor {
+    fn new() -> Self {
+        Self(AtomicU64::new(NO_OFFSET))
+    }
+
+    /// Mark the start of a job at `offset`.
+    pub fn begin(&self, offset: u64) {
+        self.0.store(offset, Ordering::Relaxed);
+    }
+
+    /// Mark the current job as finished.
+    pub fn end(&self) {
+        self.0.store(NO_OFFSET, Ordering::Relaxed);
+    }
+
+    fn take(&self) -> Option<u64> {
+        let offset = self.0.swap(NO_OFFSET, Ordering::Relaxed);
+        (offset != NO_OFFSET).then_some(offset)
+    }
+}
+
+/// Run `body` until it returns normally, restarting it after each panic.
+///
+/// `body` should drain its job queue and return when the queue closes; it calls
+/// [`JobCursor::begin`] before each job so a panic can be attributed.
+pub fn supervise<F>(stage: &'static str, log: &PanicLog, mut body: F)
+where
+    F: FnMut(&JobCursor),
+{
+    let cursor = JobCursor::new();
+    loop {
+        match panic::catch_unwind(AssertUnwindSafe(|| body(&cursor))) {
+            Ok(()) => return,
+            Err(payload) => {
+                let offset = cursor.take();
+                let message = panic_message(payload.as_ref());
+                match offset {
+                    Some(offset) => tracing::warn!(
+                        "{stage} worker panicked at offset {offset}: {message}; restarting worker"
+                    ),
+                    None => tracing::warn!("{stage} worker panicked: {message}; restarting worker"),
+                }
+                log.record(WorkerPanic {
+                    stage,
+                    offset,
+                    message,
+                });
+            }
+        }
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        (*s).to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "non-string panic payload".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restarts_after_panic_and_records_offset() {
+        let log = PanicLog::new();
+        let jobs = std::cell::RefCell::new(vec![30u64, 20, 10].into_iter());
+        let mut done = Vec::new();
+        supervise("carve", &log, |cursor| {
+            while let Some(offset) = jobs.borrow_mut().next() {
+                cursor.begin(offset);
+                if offset == 20 {
+                    panic!("bad input");
+                }
+                done.push(offset);
+                cursor.end();
+            }
+        });
+        assert_eq!(done, vec![30, 10]);
+        assert_eq!(log.count(), 1);
+        assert_eq!(
+            log.records(),
+            vec![WorkerPanic {
+                stage: "carve",
+                offset: Some(20),
+                message: "bad input".to_string(),
+            }]
+        );
+        let summary = log.summary().expect("summary");
+        assert!(summary.contains("carve@0x14: bad input"), "{summary}");
+    }
+
+    #[test]
+    fn summary_is_none_without_panics() {
+        let log = PanicLog::new();
+        supervise("scan", &log, |_| {});
+        assert_eq!(log.summary(), None);
+    }
+}
@@ -0,0 +1,74 @@
+//! Scanner and carve handler stubs shared by pipeline tests.
+
+use std::collections::HashMap;
+
+use crate::carve::{CarveError, CarveHandler, CarveRegistry, CarvedFile, ExtractionContext};
+use crate::chunk::ScanChunk;
+use crate::scanner::{Hit, NormalizedHit, SignatureScanner};
+
+/// Reports a `magic` hit at every `MAGI`.
+pub(crate) struct MagicScanner;
+
+impl SignatureScanner for MagicScanner {
+    fn scan_chunk(&self, chunk: &ScanChunk, data: &[u8]) -> Vec<Hit> {
+        data.windows(4)
+            .enumerate()
+            .filter(|(_, w)| *w == b"MAGI")
+            .map(|(i, _)| Hit {
+                chunk_id: chunk.id,
+                local_offset: i as u64,
+                pattern_id: "magic".to_string(),
+                file_type_id: "magic".to_string(),
+            })
+            .collect()
+    }
+}
+
+/// Records the hit without writing a file.
+pub(crate) struct MagicHandler;
+
+impl CarveHandler for MagicHandler {
+    fn file_type(&self) -> &str {
+        "magic"
+    }
+
+    fn extension(&self) -> &str {
+        "bin"
+    }
+
+    fn process_hit(
+        &self,
+        hit: &NormalizedHit,
+        ctx: &ExtractionContext,
+    ) -> Result<Option<CarvedFile>, CarveError> {
+        let mut head = [0u8; 8];
+        ctx.evidence
+            .read_at(hit.global_offset, &mut head)
+            .map_err(|e| CarveError::Evidence(e.to_string()))?;
+        assert_eq!(&head, b"MAGIC!!!");
+        Ok(Some(CarvedFile {
+            run_id: ctx.run_id.to_string(),
+            file_type: "magic".to_string(),
+            signature_type: "magic".to_string(),
+            path: format!("magic/{}.bin", hit.global_offset),
+            extension: "bin".to_string(),
+            global_start: hit.global_offset,
+            global_end: hit.global_offset + 7,
+            size: 8,
+            md5: None,
+            sha256: None,
+            validated: true,
+            truncated: false,
+            errors: Vec::new(),
+            pattern_id: Some(hit.pattern_id.clone()),
+        }))
+    }
+}
+
+/// A registry with only [`MagicHandler`].
+pub(crate) fn magic_registry() -> CarveRegistry {
+    CarveRegistry::new(HashMap::from([(
+        "magic".to_string(),
+        Box::new(MagicHandler) as Box<dyn CarveHandler>,
+    )]))
+}
@@ -0,0 +1,311 @@
+//! # Hit Clustering
+//!
+//! Corrupt or repetitive regions can produce thousands of hits of one type within
+//! a few KiB, each of which would otherwise cost a carve attempt. [`HitThrottle`]
+//! admits at most one attempt per type per `hit_cluster_window` bytes unless the
+//! previous attempt produced a file. Hits that fall inside the window of a failed
+//! attempt are suppressed and counted per type; the counts are written to
+//! `summaries/hit_suppression.json` so nothing is dropped silently. A hit inside
+//! the window of a still-running attempt waits for its outcome, so which hits
+//! are carved does not depend on which carve worker finishes first.
+//! An admitted attempt that is never finished, because its carve panicked,
+//! counts as failed when its [`ThrottleAttempt`] is dropped.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Condvar, Mutex};
+
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::report::SUMMARIES_DIR;
+use crate::scanner::NormalizedHit;
+
+/// File name of the suppression summary under `summaries/`.
+pub const SUPPRESSION_SUMMARY_FILE: &str = "hit_suppression.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Pending,
+    Failed,
+    Carved,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Attempt {
+    offset: u64,
+    outcome: Outcome,
+}
+
+/// Per-type rate limiter for carve attempts, shared by carve workers.
+#[derive(Debug, Default)]
+pub struct HitThrottle {
+    windows: HashMap<String, u64>,
+    last: Mutex<HashMap<String, Attempt>>,
+    /// Signalled whenever an attempt in `last` settles.
+    settled: Condvar,
+    suppressed: Mutex<HashMap<String, u64>>,
+    total_suppressed: AtomicU64,
+}
+
+/// An admitted hit whose carve is running; dropping it without
+/// [`ThrottleAttempt::finish`] records the attempt as failed.
+#[derive(Debug)]
+pub struct ThrottleAttempt<'a> {
+    throttle: &'a HitThrottle,
+    file_type: String,
+    offset: u64,
+    finished: bool,
+}
+
+/// Suppressed-hit counts written at the end of a run.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct SuppressionSummary {
+    pub run_id: String,
+    pub total_suppressed: u64,
+    pub types: Vec<TypeSuppression>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct TypeSuppression {
+    pub file_type: String,
+    pub window_bytes: u64,
+    pub suppressed: u64,
+}
+
+impl HitThrottle {
+    /// Build from the global window and per-type overrides; types with a zero
+    /// window are never throttled.
+    pub fn from_config(cfg: &Config) -> Self {
+        let windows = cfg
+            .file_types
+            .iter()
+            .map(|ft| {
+                (
+                    ft.id.clone(),
+                    ft.hit_cluster_window.unwrap_or(cfg.hit_cluster_window),
+                )
+            })
+            .filter(|(_, window)| *window > 0)
+            .collect();
+        Self {
+            windows,
+            ..Self::default()
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.windows.is_empty()
+    }
+
+    /// Decide whether `hit` should be carved; `None` when it is suppressed.
+    /// Blocks while the reference attempt it falls near is still running.
+    /// Admitted hits become the new reference attempt for their type, settled
+    /// through the returned attempt.
+    pub fn admit(&self, hit: &NormalizedHit) -> Option<ThrottleAttempt<'_>> {
+        let attempt = ThrottleAttempt {
+            throttle: self,
+            file_type: hit.file_type_id.clone(),
+            offset: hit.global_offset,
+            finished: false,
+        };
+        let Some(&window) = self.windows.get(&hit.file_type_id) else {
+            return Some(attempt);
+        };
+        let Ok(mut last) = self.last.lock() else {
+            return Some(attempt);
+        };
+        while let Some(prev) = last.get(&hit.file_type_id)
+            && hit.global_offset.abs_diff(prev.offset) < window
+        {
+            match prev.outcome {
+                Outcome::Carved => break,
+                Outcome::Pending => match self.settled.wait(last) {
+                    Ok(guard) => last = guard,
+                    Err(_) => return Some(attempt),
+                },
+                Outcome::Failed => {
+                    drop(last);
+                    self.total_suppressed.fetch_add(1, Ordering::Relaxed);
+                    if let Ok(mut suppressed) = self.suppressed.lock() {
+                        *suppressed.entry(hit.file_type_id.clone()).or_insert(0) += 1;
+                    }
+                    return None;
+                }
+            }
+        }
+        last.insert(
+            hit.file_type_id.clone(),
+            Attempt {
+                offset: hit.global_offset,
+                outcome: Outcome::Pending,
+            },
+        );
+        Some(attempt)
+    }
+
+    /// Record the outcome of an admitted attempt.
+    fn settle(&self, file_type: &str, offset: u64, carved: bool) {
+        if !self.windows.contains_key(file_type) {
+            return;
+        }
+        if let Ok(mut last) = self.last.lock()
+            && let Some(attempt) = last.get_mut(file_type)
+            && attempt.offset == offset
+        {
+            attempt.outcome = if carved {
+                Outcome::Carved
+            } else {
+                Outcome::Failed
+            };
+            self.settled.notify_all();
+        }
+    }
+
+    /// Total hits suppressed so far.
+    pub fn suppressed(&self) -> u64 {
+        self.total_suppressed.load(Ordering::Relaxed)
+    }
+
+    pub fn summarize(&self, run_id: &str) -> SuppressionSummary {
+        let suppressed = self
+            .suppressed
+            .lock()
+            .map(|s| s.clone())
+            .unwrap_or_default();
+        let mut types: Vec<TypeSuppression> = self
+            .windows
+            .iter()
+            .map(|(file_type, window)| TypeSuppression {
+                file_type: file_type.clone(),
+                window_bytes: *window,
+                suppressed: suppressed.get(file_type).copied().unwrap_or(0),
+            })
+            .collect();
+        types.sort_by(|a, b| {
+            b.suppressed
+                .cmp(&a.suppressed)
+                .then_with(|| a.file_type.cmp(&b.file_type))
+        });
+        SuppressionSummary {
+            run_id: run_id.to_string(),
+            total_suppressed: self.suppressed(),
+            types,
+        }
+    }
+}
+
+impl ThrottleAttempt<'_> {
+    /// Record whether the carve produced a file.
+    pub fn finish(mut self, carved: bool) {
+        self.finished = true;
+        self.throttle.settle(&self.file_type, self.offset, carved);
+    }
+}
+
+impl Drop for ThrottleAttempt<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.throttle.settle(&self.file_type, self.offset, false);
+        }
+    }
+}
+
+/// Write the summary to `<run_output_dir>/summaries/hit_suppression.json`.
+pub fn write_summary(
+    run_output_dir: &Path,
+    summary: &SuppressionSummary,
+) -> std::io::Result<PathBuf> {
+    let dir = run_output_dir.join(SUMMARIES_DIR);
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(SUPPRESSION_SUMMARY_FILE);
+    let writer = BufWriter::new(File::create(&path)?);
+    serde_json::to_writer_pretty(writer, summary)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn throttle(window: u64) -> HitThrottle {
+        HitThrottle {
+            windows: HashMap::from([("jpeg".to_string(), window)]),
+            ..HitThrottle::default()
+        }
+    }
+
+    fn hit(file_type: &str, offset: u64) -> NormalizedHit {
+        NormalizedHit {
+            global_offset: offset,
+            file_type_id: file_type.to_string(),
+            pattern_id: format!("{file_type}_header"),
+        }
+    }
+
+    #[test]
+    fn suppresses_hits_near_failed_attempts() {
+        let t = throttle(4096);
+        t.admit(&hit("jpeg", 0)).expect("admitted").finish(false);
+        assert!(t.admit(&hit("jpeg", 16)).is_none());
+        assert!(t.admit(&hit("jpeg", 4095)).is_none());
+        t.admit(&hit("jpeg", 4096)).expect("admitted").finish(false);
+        assert!(t.admit(&hit("jpeg", 4100)).is_none());
+        // Other types are not throttled.
+        assert!(t.admit(&hit("png", 8)).is_some());
+        assert!(t.admit(&hit("png", 9)).is_some());
+
+        let summary = t.summarize("run");
+        assert_eq!(summary.total_suppressed, 3);
+        assert_eq!(
+            summary.types,
+            vec![TypeSuppression {
+                file_type: "jpeg".to_string(),
+                window_bytes: 4096,
+                suppressed: 3,
+            }]
+        );
+    }
+
+    #[test]
+    fn successful_attempt_admits_next_hit() {
+        let t = throttle(4096);
+        t.admit(&hit("jpeg", 0)).expect("admitted").finish(true);
+        t.admit(&hit("jpeg", 512)).expect("admitted").finish(false);
+        assert!(t.admit(&hit("jpeg", 1024)).is_none());
+        assert_eq!(t.suppressed(), 1);
+    }
+
+    #[test]
+    fn hits_near_a_running_attempt_wait_for_its_outcome() {
+        let t = throttle(4096);
+        let running = t.admit(&hit("jpeg", 0)).expect("admitted");
+        std::thread::scope(|scope| {
+            let waiting = scope.spawn(|| t.admit(&hit("jpeg", 16)).map(|a| a.finish(true)));
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            assert!(
+                !waiting.is_finished(),
+                "admitted before the outcome was known"
+            );
+            running.finish(true);
+            assert!(waiting.join().expect("join").is_some());
+        });
+        assert_eq!(t.suppressed(), 0);
+    }
+
+    #[test]
+    fn a_panicking_carve_settles_its_attempt_as_failed() {
+        let t = throttle(4096);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _attempt = t.admit(&hit("jpeg", 0)).expect("admitted");
+            panic!("carve panicked");
+        }));
+        assert!(result.is_err());
+        // Failed, not pending: the next window is admitted again.
+        assert!(t.admit(&hit("jpeg", 16)).is_none());
+        assert!(t.admit(&hit("jpeg", 4096)).is_some());
+    }
+}
@@ -0,0 +1,293 @@
+//! # Offset Trace
+//!
+//! Debug mode for "why wasn't my file carved?". With `trace_offset` set
+//! (`--trace-offset 0x12AB3000`), every pipeline decision that touches the
+//! region `trace_offset ± trace_window` is logged at info level with a `trace`
+//! prefix: the chunk that covered it, signature hits and whether they were
+//! deferred to the next chunk, throttling and `max_files` skips, the handler
+//! result with its rejection reason, and the metadata row that was written.
+//! A summary at the end of the run points at the first stage that saw nothing.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use tracing::info;
+
+use crate::carve::{CarveError, CarvedFile, Rejection};
+use crate::chunk::ScanChunk;
+use crate::config::Config;
+use crate::metadata::MetadataError;
+use crate::scanner::NormalizedHit;
+
+/// Evidence region whose pipeline decisions are logged.
+#[derive(Debug, Default)]
+pub struct OffsetTrace {
+    offset: u64,
+    start: u64,
+    /// Inclusive, so a region ending at `u64::MAX` is representable.
+    end: u64,
+    chunks: AtomicU64,
+    hits: AtomicU64,
+    carved: AtomicU64,
+}
+
+/// Counters of traced decisions, for the end-of-run summary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceCounts {
+    pub chunks: u64,
+    pub hits: u64,
+    pub carved: u64,
+}
+
+impl OffsetTrace {
+    pub fn new(offset: u64, window: u64) -> Self {
+        Self {
+            offset,
+            start: offset.saturating_sub(window),
+            end: offset.saturating_add(window),
+            ..Self::default()
+        }
+    }
+
+    pub fn from_config(cfg: &Config) -> Option<Self> {
+        cfg.trace_offset
+            .map(|offset| Self::new(offset, cfg.trace_window))
+    }
+
+    /// Whether `offset` lies in the traced region.
+    pub fn contains(&self, offset: u64) -> bool {
+        (self.start..=self.end).contains(&offset)
+    }
+
+    /// Whether the inclusive range `start..=end` touches the traced region.
+    pub fn overlaps(&self, start: u64, end: u64) -> bool {
+        start <= self.end && end >= self.start
+    }
+
+    pub fn counts(&self) -> TraceCounts {
+        TraceCounts {
+            chunks: self.chunks.load(Ordering::Relaxed),
+            hits: self.hits.load(Ordering::Relaxed),
+            carved: self.carved.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Log the traced region at the start of the run.
+    pub fn begin(&self, evidence_len: u64, file_types: usize) {
+        info!(
+            "trace region={:#x}..={:#x} offset={:#x} evidence_len={:#x} file_types={}",
+            self.start, self.end, self.offset, evidence_len, file_types
+        );
+        if self.start >= evidence_len {
+            info!("trace region starts beyond the end of the evidence");
+        }
+    }
+
+    /// A chunk was queued for scanning, skipped, or failed to read.
+    pub fn chunk(&self, chunk: &ScanChunk, outcome: Result<(), &str>) {
+        let end = chunk.start + chunk.length.max(1) - 1;
+        if !self.overlaps(chunk.start, end) {
+            return;
+        }
+        let valid_end = chunk.start + chunk.valid_length;
+        match outcome {
+            Ok(()) => {
+                self.chunks.fetch_add(1, Ordering::Relaxed);
+                info!(
+                    "trace chunk id={} range={:#x}..={:#x} owns={:#x}..{:#x} scanned",
+                    chunk.id, chunk.start, end, chunk.start, valid_end
+                );
+            }
+            Err(reason) => info!(
+                "trace chunk id={} range={:#x}..={:#x} not scanned: {reason}",
+                chunk.id, chunk.start, end
+            ),
+        }
+    }
+
+    /// A signature hit in the region. `deferred` hits lie in the chunk overlap
+    /// and are dropped here because the next chunk reports them again.
+    pub fn hit(&self, hit: &NormalizedHit, chunk_id: u64, deferred: bool) {
+        if !self.contains(hit.global_offset) {
+            return;
+        }
+        if deferred {
+            info!(
+                "trace hit offset={:#x} type={} pattern={} chunk={} deferred: in overlap, owned by next chunk",
+                hit.global_offset, hit.file_type_id, hit.pattern_id, chunk_id
+            );
+            return;
+        }
+        self.hits.fetch_add(1, Ordering::Relaxed);
+        info!(
+            "trace hit offset={:#x} type={} pattern={} chunk={} queued for carving",
+            hit.global_offset, hit.file_type_id, hit.pattern_id, chunk_id
+        );
+    }
+
+    /// A hit in the region never reached its handler.
+    pub fn skipped(&self, hit: &NormalizedHit, reason: &str) {
+        if self.contains(hit.global_offset) {
+            info!(
+                "trace carve offset={:#x} type={} skipped: {reason}",
+                hit.global_offset, hit.file_type_id
+            );
+        }
+    }
+
+    /// Handler outcome for a hit in the region, or for any hit whose carved
+    /// file covers the region.
+    pub fn carve_result(
+        &self,
+        hit: &NormalizedHit,
+        handler: &str,
+        result: &Result<Option<CarvedFile>, CarveError>,
+        rejection: Option<Rejection>,
+    ) {
+        let in_region = self.contains(hit.global_offset);
+        match result {
+            Ok(Some(file)) if in_region || self.overlaps(file.global_start, file.global_end) => {
+                self.carved.fetch_add(1, Ordering::Relaxed);
+                info!(
+                    "trace carve offset={:#x} handler={handler} carved path={} extent={:#x}..={:#x} size={} validated={} truncated={} errors={:?}",
+                    hit.global_offset,
+                    file.path,
+                    file.global_start,
+                    file.global_end,
+                    file.size,
+                    file.validated,
+                    file.truncated,
+                    file.errors
+                );
+            }
+            Ok(None) if in_region => {
+                let reason = rejection.map_or_else(
+                    || "header or structure did not validate".to_string(),
+                    |r| r.to_string(),
+                );
+                info!(
+                    "trace carve offset={:#x} handler={handler} rejected: {reason}",
+                    hit.global_offset
+                );
+            }
+            Err(err) if in_region => info!(
+                "trace carve offset={:#x} handler={handler} error: {err}",
+                hit.global_offset
+            ),
+            _ => {}
+        }
+    }
+
+    /// A `carved_files` row touching the region was handed to the sink.
+    pub fn metadata_row(&self, file: &CarvedFile, result: &Result<(), MetadataError>) {
+        if !self.overlaps(file.global_start, file.global_end) {
+            return;
+        }
+        match result {
+            Ok(()) => info!(
+                "trace metadata carved_files row path={} extent={:#x}..={:#x} recorded",
+                file.path, file.global_start, file.global_end
+            ),
+            Err(err) => info!(
+                "trace metadata carved_files row path={} failed: {err}",
+                file.path
+            ),
+        }
+    }
+
+    /// Log the summary once all workers have finished.
+    pub fn finish(&self) {
+        let counts = self.counts();
+        let verdict = if counts.chunks == 0 {
+            "region was not scanned (beyond evidence end, before the resume offset, or past max_bytes/max_chunks)"
+        } else if counts.hits == 0 {
+            "no signature hits in region (type disabled by --types, or header pattern does not match)"
+        } else if counts.carved == 0 {
+            "hits were found but no file was carved (see rejections above)"
+        } else {
+            "region carved"
+        };
+        info!(
+            "trace summary chunks={} hits={} carved={}: {verdict}",
+            counts.chunks, counts.hits, counts.carved
+        );
+    }
+}
+
+/// Parse a `--trace-offset` value: hex with `0x` prefix or decimal.
+pub fn parse_offset(value: &str) -> Result<u64, String> {
+    let value = value.trim().replace('_', "");
+    let parsed = match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => value.parse(),
+    };
+    parsed.map_err(|err| format!("invalid offset `{value}`: {err}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hit(offset: u64) -> NormalizedHit {
+        NormalizedHit {
+            global_offset: offset,
+            file_type_id: "jpeg".to_string(),
+            pattern_id: "jpeg_soi".to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_hex_and_decimal_offsets() {
+        assert_eq!(parse_offset("0x12AB3000"), Ok(0x12AB_3000));
+        assert_eq!(parse_offset("4096"), Ok(4096));
+        assert!(parse_offset("0xZZ").is_err());
+    }
+
+    #[test]
+    fn counts_only_decisions_in_region() {
+        let trace = OffsetTrace::new(0x10000, 0x100);
+        assert!(trace.contains(0xFF00));
+        assert!(!trace.contains(0x10101));
+        let chunk = ScanChunk {
+            id: 0,
+            start: 0,
+            length: 0x20000,
+            valid_length: 0x18000,
+        };
+        trace.chunk(&chunk, Ok(()));
+        trace.hit(&hit(0x10000), 0, false);
+        trace.hit(&hit(0x10010), 0, true);
+        trace.hit(&hit(0x40000), 0, false);
+
+        // A file carved from a hit outside the region that spans it still counts.
+        let file = CarvedFile {
+            run_id: "run".to_string(),
+            file_type: "zip".to_string(),
+            signature_type: "zip".to_string(),
+            path: "zip/zip_000000000000.zip".to_string(),
+            extension: "zip".to_string(),
+            global_start: 0,
+            global_end: 0x1FFFF,
+            size: 0x20000,
+            md5: None,
+            sha256: None,
+            validated: true,
+            truncated: false,
+            errors: Vec::new(),
+            pattern_id: None,
+        };
+        trace.carve_result(&hit(0), "zip", &Ok(Some(file)), None);
+        trace.carve_result(&hit(0x10000), "jpeg", &Ok(None), None);
+
+        assert_eq!(
+            trace.counts(),
+            TraceCounts {
+                chunks: 1,
+                hits: 1,
+                carved: 1,
+            }
+        );
+    }
+}
@@ -0,0 +1,303 @@
+//! # Evidence Transform Detection
+//!
+//! Images from damaged or misconfigured acquisitions sometimes store every
+//! 16-bit word byte-swapped or every bit inverted; scanned as read, they
+//! yield no carves and no error. With `evidence_transform: auto`, samples
+//! spread over the evidence are scanned as read and under each
+//! [`ByteTransform`] before the run starts, and a transform that finds
+//! clearly more signature hits than the bytes as read is applied to the
+//! whole run. Only patterns of at least [`MIN_PATTERN_LEN`] bytes made of
+//! at least [`MIN_DISTINCT_BYTES`] byte values count: short headers match
+//! noise, and mostly-zero ones such as ICO's match zero fill, under any
+//! transform. The
+//! transform is applied through [`TransformedSource`](crate::evidence::TransformedSource).
+//! The hit counts and the decision are written to
+//! `summaries/evidence_transform.json`, also when a transform is forced.
+
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::chunk::ScanChunk;
+use crate::config::{Config, EvidenceTransform};
+use crate::constants::MIB;
+use crate::evidence::{ByteTransform, EvidenceSource};
+use crate::report::SUMMARIES_DIR;
+use crate::scanner::SignatureScanner;
+
+use super::read_chunk_limited;
+
+/// File name of the transform summary under `summaries/`.
+pub const TRANSFORM_SUMMARY_FILE: &str = "evidence_transform.json";
+
+/// Samples scanned per candidate, spread evenly over the evidence.
+const SAMPLE_COUNT: u64 = 32;
+const SAMPLE_SIZE: u64 = MIB;
+/// Shortest header pattern whose hits are counted.
+pub const MIN_PATTERN_LEN: usize = 4;
+/// Fewest distinct byte values in a header pattern whose hits are counted.
+pub const MIN_DISTINCT_BYTES: usize = 3;
+/// A transform is applied only with at least this many hits...
+const MIN_HITS: u64 = 4;
+/// ...and this many times the hits of the bytes as read, so the matches
+/// random data gives under any transform do not decide.
+const MIN_GAIN: u64 = 2;
+
+/// Serialized form of `summaries/evidence_transform.json`.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct TransformSummary {
+    pub run_id: String,
+    /// `evidence_transform` as configured.
+    pub mode: EvidenceTransform,
+    /// Transform the run read the evidence through, if any.
+    pub applied: Option<&'static str>,
+    pub samples: u64,
+    pub sampled_bytes: u64,
+    /// Hits of the counted patterns in the samples, per candidate (`auto`
+    /// only).
+    pub candidates: Vec<CandidateHits>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct CandidateHits {
+    /// `none` for the bytes as read, else the transform name.
+    pub transform: &'static str,
+    pub hits: u64,
+}
+
+/// Ids of the header patterns of at least [`MIN_PATTERN_LEN`] bytes and
+/// [`MIN_DISTINCT_BYTES`] byte values.
+pub fn counted_patterns(cfg: &Config) -> HashSet<String> {
+    cfg.file_types
+        .iter()
+        .flat_map(|ft| &ft.header_patterns)
+        .filter(|pattern| {
+            let Ok(bytes) = hex::decode(&pattern.hex) else {
+                return false;
+            };
+            let distinct: HashSet<u8> = bytes.iter().copied().collect();
+            bytes.len() >= MIN_PATTERN_LEN && distinct.len() >= MIN_DISTINCT_BYTES
+        })
+        .map(|pattern| pattern.id.clone())
+        .collect()
+}
+
+/// The transform `mode` asks for, detected from `evidence` for `auto` by
+/// the hits of `counted` patterns, and the summary to write; `None` for
+/// both with `evidence_transform: none`.
+pub fn resolve(
+    mode: EvidenceTransform,
+    run_id: &str,
+    evidence: &dyn EvidenceSource,
+    scanner: &dyn SignatureScanner,
+    counted: &HashSet<String>,
+) -> (Option<ByteTransform>, Option<TransformSummary>) {
+    let mut summary = TransformSummary {
+        run_id: run_id.to_string(),
+        mode,
+        applied: None,
+        samples: 0,
+        sampled_bytes: 0,
+        candidates: Vec::new(),
+    };
+    let transform = match mode {
+        EvidenceTransform::None => return (None, None),
+        EvidenceTransform::ByteSwap16 => Some(ByteTransform::ByteSwap16),
+        EvidenceTransform::InvertBits => Some(ByteTransform::InvertBits),
+        EvidenceTransform::Auto => detect(evidence, scanner, counted, &mut summary),
+    };
+    summary.applied = transform.map(ByteTransform::name);
+    match transform {
+        Some(transform) if mode == EvidenceTransform::Auto => warn!(
+            "evidence matches signatures best under {}; reading it through that transform",
+            transform.name()
+        ),
+        Some(transform) => info!("reading evidence through {}", transform.name()),
+        None => info!("evidence_transform auto: evidence is scanned as read"),
+    }
+    (transform, Some(summary))
+}
+
+/// Scan the samples under each candidate and pick the winner, if any.
+fn detect(
+    evidence: &dyn EvidenceSource,
+    scanner: &dyn SignatureScanner,
+    counted: &HashSet<String>,
+    summary: &mut TransformSummary,
+) -> Option<ByteTransform> {
+    let hits_in = |chunk: &ScanChunk, data: &[u8]| {
+        scanner
+            .scan_chunk(chunk, data)
+            .iter()
+            .filter(|hit| counted.contains(&hit.pattern_id))
+            .count() as u64
+    };
+    let mut as_read = 0u64;
+    let mut hits = [0u64; ByteTransform::ALL.len()];
+    for (id, start) in sample_offsets(evidence.len()).into_iter().enumerate() {
+        let length = SAMPLE_SIZE.min(evidence.len() - start);
+        let chunk = ScanChunk {
+            id: id as u64,
+            start,
+            length,
+            valid_length: length,
+        };
+        let data = match read_chunk_limited(evidence, &chunk, length as usize) {
+            Ok(data) => data,
+            Err(err) => {
+                warn!("evidence_transform auto: sample at {start} unreadable: {err}");
+                continue;
+            }
+        };
+        summary.samples += 1;
+        summary.sampled_bytes += data.len() as u64;
+        as_read += hits_in(&chunk, &data);
+        for (transform, hits) in ByteTransform::ALL.iter().zip(hits.iter_mut()) {
+            let mut data = data.clone();
+            transform.apply(&mut data);
+            *hits += hits_in(&chunk, &data);
+        }
+    }
+    summary.candidates.push(CandidateHits {
+        transform: "none",
+        hits: as_read,
+    });
+    summary.candidates.extend(
+        ByteTransform::ALL
+            .iter()
+            .zip(hits)
+            .map(|(t, hits)| CandidateHits {
+                transform: t.name(),
+                hits,
+            }),
+    );
+    let (best, best_hits) = ByteTransform::ALL
+        .into_iter()
+        .zip(hits)
+        .max_by_key(|&(_, hits)| hits)?;
+    (best_hits >= MIN_HITS && best_hits >= as_read.saturating_mul(MIN_GAIN)).then_some(best)
+}
+
+/// Even start offsets of the samples: the whole evidence when it is small,
+/// otherwise `SAMPLE_COUNT` samples from the first byte to the last.
+fn sample_offsets(len: u64) -> Vec<u64> {
+    if len <= SAMPLE_COUNT * SAMPLE_SIZE {
+        return (0..len.div_ceil(SAMPLE_SIZE))
+            .map(|i| i * SAMPLE_SIZE)
+            .collect();
+    }
+    let step = ((len - SAMPLE_SIZE) / (SAMPLE_COUNT - 1)) & !1;
+    (0..SAMPLE_COUNT).map(|i| i * step).collect()
+}
+
+pub fn write_summary(
+    run_output_dir: &Path,
+    summary: &TransformSummary,
+) -> std::io::Result<PathBuf> {
+    let dir = run_output_dir.join(SUMMARIES_DIR);
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(TRANSFORM_SUMMARY_FILE);
+    let writer = BufWriter::new(File::create(&path)?);
+    serde_json::to_writer_pretty(writer, summary)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evidence::MemorySource;
+    use crate::pipeline::testing::MagicScanner;
+
+    fn image_with_magic(count: usize) -> Vec<u8> {
+        let mut image = vec![0u8; 256 * 1024];
+        for i in 0..count {
+            image[i * 4096..i * 4096 + 8].copy_from_slice(b"MAGIC!!!");
+        }
+        image
+    }
+
+    fn magic() -> HashSet<String> {
+        HashSet::from(["magic".to_string()])
+    }
+
+    fn auto(image: Vec<u8>) -> (Option<ByteTransform>, TransformSummary) {
+        let (transform, summary) = resolve(
+            EvidenceTransform::Auto,
+            "run",
+            &MemorySource::new(image),
+            &MagicScanner,
+            &magic(),
+        );
+        (transform, summary.expect("summary"))
+    }
+
+    #[test]
+    fn detects_swapped_and_inverted_images() {
+        for transform in ByteTransform::ALL {
+            let mut image = image_with_magic(20);
+            transform.apply(&mut image);
+            let (found, summary) = auto(image);
+            assert_eq!(found, Some(transform));
+            assert_eq!(summary.applied, Some(transform.name()));
+            assert_eq!(summary.samples, 1);
+            assert_eq!(summary.candidates[0].hits, 0);
+        }
+    }
+
+    #[test]
+    fn keeps_images_that_match_as_read() {
+        let (found, summary) = auto(image_with_magic(20));
+        assert_eq!(found, None);
+        assert_eq!(summary.candidates[0].hits, 20);
+        // Too few hits under any transform to decide.
+        let mut image = image_with_magic(3);
+        ByteTransform::ByteSwap16.apply(&mut image);
+        assert_eq!(auto(image).0, None);
+        // Hits of patterns too short to count are ignored.
+        let mut image = image_with_magic(20);
+        ByteTransform::ByteSwap16.apply(&mut image);
+        let (found, _) = resolve(
+            EvidenceTransform::Auto,
+            "run",
+            &MemorySource::new(image),
+            &MagicScanner,
+            &HashSet::new(),
+        );
+        assert_eq!(found, None);
+    }
+
+    #[test]
+    fn forced_transforms_skip_detection() {
+        let image = MemorySource::new(image_with_magic(20));
+        let (found, summary) = resolve(
+            EvidenceTransform::InvertBits,
+            "run",
+            &image,
+            &MagicScanner,
+            &magic(),
+        );
+        assert_eq!(found, Some(ByteTransform::InvertBits));
+        assert_eq!(summary.expect("summary").samples, 0);
+        let none = resolve(
+            EvidenceTransform::None,
+            "run",
+            &image,
+            &MagicScanner,
+            &magic(),
+        );
+        assert_eq!(none, (None, None));
+    }
+
+    #[test]
+    fn spreads_samples_over_large_evidence() {
+        assert_eq!(sample_offsets(3 * MIB + 1), vec![0, MIB, 2 * MIB, 3 * MIB]);
+        let offsets = sample_offsets(1 << 40);
+        assert_eq!(offsets.len(), SAMPLE_COUNT as usize);
+        assert!(offsets.iter().all(|offset| offset % 2 == 0));
+        assert!(*offsets.last().unwrap() + SAMPLE_SIZE <= 1 << 40);
+    }
+}
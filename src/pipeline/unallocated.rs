@@ -0,0 +1,253 @@
+//! # Unallocated-Only Scanning
+//!
+//! With `unallocated_only`, the file systems in the evidence (at offset 0,
+//! or in each MBR or GPT partition) are found before the scan starts and
+//! their allocation maps read: the NTFS `$Bitmap`, the FAT, or the ext2/3/4
+//! block bitmaps. Scan chunks lying wholly in space a file system marks in
+//! use are skipped, so carving covers free space, where deleted files are
+//! found, and live files are not recovered a second time. Volumes whose map
+//! cannot be read are scanned in full. The volumes found and the chunks
+//! skipped are written to `summaries/unallocated.json`.
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::evidence::EvidenceSource;
+use crate::parsers::{ext4, fat, ntfs, partitions};
+use crate::report::SUMMARIES_DIR;
+
+/// File name of the unallocated scan summary under `summaries/`.
+pub const UNALLOCATED_SUMMARY_FILE: &str = "unallocated.json";
+
+/// Space file systems mark in use, as sorted, disjoint evidence ranges.
+#[derive(Debug, Default)]
+pub struct AllocatedMap {
+    ranges: Vec<(u64, u64)>,
+}
+
+impl AllocatedMap {
+    /// Map of `ranges`, which may overlap and be in any order.
+    pub fn new(mut ranges: Vec<(u64, u64)>) -> Self {
+        ranges.sort_unstable();
+        let mut merged: Vec<(u64, u64)> = Vec::with_capacity(ranges.len());
+        for (start, end) in ranges {
+            match merged.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+        Self { ranges: merged }
+    }
+
+    /// Whether every byte of `start..end` is in use.
+    pub fn covers(&self, start: u64, end: u64) -> bool {
+        let at = self
+            .ranges
+            .partition_point(|&(_, range_end)| range_end <= start);
+        self.ranges
+            .get(at)
+            .is_some_and(|&(range_start, range_end)| range_start <= start && end <= range_end)
+    }
+
+    pub fn allocated_bytes(&self) -> u64 {
+        self.ranges.iter().map(|(start, end)| end - start).sum()
+    }
+}
+
+/// Serialized form of `summaries/unallocated.json`.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct UnallocatedSummary {
+    pub run_id: String,
+    pub volumes: Vec<FsVolumeSummary>,
+    /// Bytes in use across all volumes.
+    pub allocated_bytes: u64,
+    /// Scan chunks skipped as wholly in use.
+    pub chunks_skipped: u64,
+    pub bytes_skipped: u64,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct FsVolumeSummary {
+    /// Evidence offset of the boot sector or, for ext, of the volume.
+    pub volume_offset: u64,
+    /// `ntfs`, `fat12`, `fat16`, `fat32`, or `ext`.
+    pub file_system: &'static str,
+    pub volume_size: u64,
+    /// Cluster or block size.
+    pub cluster_size: u64,
+    pub allocated_bytes: u64,
+    /// Why the allocation map could not be read, if it could not.
+    pub error: Option<String>,
+}
+
+/// Read the allocation map of each file system in `evidence`.
+pub fn discover(evidence: &dyn EvidenceSource) -> (Vec<FsVolumeSummary>, AllocatedMap) {
+    let mut volumes = Vec::new();
+    let mut ranges = Vec::new();
+    // A volume at offset 0 has no partition table; its boot code could be
+    // misread as one.
+    let found: Vec<Probe> = match probe(evidence, 0) {
+        Some(found) => vec![found],
+        None => partitions::partition_offsets(evidence)
+            .into_iter()
+            .filter_map(|offset| probe(evidence, offset))
+            .collect(),
+    };
+    for found in found {
+        match found {
+            Ok((volume, allocated)) => {
+                info!(
+                    "{} volume at {}: {} byte(s) allocated",
+                    volume.file_system, volume.volume_offset, volume.allocated_bytes
+                );
+                ranges.extend(allocated);
+                volumes.push(volume);
+            }
+            Err(volume) => {
+                warn!(
+                    "the allocation map of the {} volume at {} is unreadable, \
+                     scanning it in full: {}",
+                    volume.file_system,
+                    volume.volume_offset,
+                    volume.error.as_deref().unwrap_or_default()
+                );
+                volumes.push(volume);
+            }
+        }
+    }
+    (volumes, AllocatedMap::new(ranges))
+}
+
+type Probe = Result<(FsVolumeSummary, Vec<(u64, u64)>), FsVolumeSummary>;
+
+/// The file system at `offset` and its allocated ranges; `None` when no
+/// known file system starts there.
+fn probe(evidence: &dyn EvidenceSource, offset: u64) -> Option<Probe> {
+    let volume =
+        |file_system, volume_size, cluster_size, allocated: &[(u64, u64)]| FsVolumeSummary {
+            volume_offset: offset,
+            file_system,
+            volume_size,
+            cluster_size,
+            allocated_bytes: allocated.iter().map(|(s, e)| e - s).sum(),
+            error: None,
+        };
+    let failed = |file_system, volume_size, error: String| FsVolumeSummary {
+        volume_offset: offset,
+        file_system,
+        volume_size,
+        cluster_size: 0,
+        allocated_bytes: 0,
+        error: Some(error),
+    };
+    if let Ok(boot) = ntfs::read_boot(evidence, offset) {
+        let size = boot.total_clusters * boot.cluster_size;
+        return Some(match ntfs::read_allocated(evidence, offset) {
+            Ok((_, allocated)) if allocated.is_empty() => Err(failed(
+                "ntfs",
+                size,
+                "$Bitmap marks no cluster in use".to_string(),
+            )),
+            Ok((_, allocated)) => Ok((
+                volume("ntfs", size, boot.cluster_size, &allocated),
+                allocated,
+            )),
+            Err(err) => Err(failed("ntfs", size, err.to_string())),
+        });
+    }
+    if let Ok(boot) = fat::read_boot(evidence, offset) {
+        let name = boot.fat_type.as_str();
+        return Some(match fat::read_allocated(evidence, offset) {
+            Ok((_, allocated)) => Ok((
+                volume(name, boot.volume_size, boot.cluster_size, &allocated),
+                allocated,
+            )),
+            Err(err) => Err(failed(name, boot.volume_size, err.to_string())),
+        });
+    }
+    if let Ok(sb) = ext4::read_superblock(evidence, offset) {
+        return Some(match ext4::read_allocated(evidence, offset) {
+            Ok((_, allocated)) => Ok((
+                volume("ext", sb.volume_size(), sb.block_size, &allocated),
+                allocated,
+            )),
+            Err(err) => Err(failed("ext", sb.volume_size(), err.to_string())),
+        });
+    }
+    None
+}
+
+/// Write the summary to `<run_output_dir>/summaries/unallocated.json`.
+pub fn write_summary(
+    run_output_dir: &Path,
+    summary: &UnallocatedSummary,
+) -> std::io::Result<PathBuf> {
+    let dir = run_output_dir.join(SUMMARIES_DIR);
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(UNALLOCATED_SUMMARY_FILE);
+    let writer = BufWriter::new(File::create(&path)?);
+    serde_json::to_writer_pretty(writer, summary)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evidence::MemorySource;
+    use crate::parsers::ext4::testing::{BLOCK, ext_volume};
+    use crate::parsers::fat::testing::fat16_volume;
+
+    #[test]
+    fn maps_fat_and_ext_partitions_of_an_mbr_disk() {
+        const FAT_LBA: usize = 64;
+        let fat = fat16_volume(20_000, &[2]);
+        let ext_lba = FAT_LBA + fat.len() / 512;
+        let ext = ext_volume(&[1, 2, 3, 4, 100], false);
+        let mut disk = vec![0u8; ext_lba * 512];
+        for (i, (kind, lba)) in [(0x06u8, FAT_LBA), (0x83, ext_lba)].iter().enumerate() {
+            let entry = 446 + i * 16;
+            disk[entry + 4] = *kind;
+            disk[entry + 8..entry + 12].copy_from_slice(&(*lba as u32).to_le_bytes());
+        }
+        disk[510..512].copy_from_slice(&[0x55, 0xAA]);
+        disk[FAT_LBA * 512..].copy_from_slice(&fat);
+        disk.extend_from_slice(&ext);
+
+        let (volumes, allocated) = discover(&MemorySource::new(disk));
+        let kinds: Vec<(u64, &str)> = volumes
+            .iter()
+            .map(|v| (v.volume_offset, v.file_system))
+            .collect();
+        let fat_start = (FAT_LBA * 512) as u64;
+        let ext_start = (ext_lba * 512) as u64;
+        assert_eq!(kinds, vec![(fat_start, "fat16"), (ext_start, "ext")]);
+        assert!(volumes.iter().all(|v| v.error.is_none()));
+
+        // FAT system area and cluster 2.
+        let data = fat_start + 97 * 512;
+        assert!(allocated.covers(fat_start, data + 2048));
+        assert!(!allocated.covers(data + 2048, data + 4096));
+        // ext blocks 1-4 and 100.
+        let b = BLOCK as u64;
+        assert!(allocated.covers(ext_start + b, ext_start + 5 * b));
+        assert!(allocated.covers(ext_start + 100 * b, ext_start + 101 * b));
+        assert!(!allocated.covers(ext_start + 99 * b, ext_start + 101 * b));
+        assert_eq!(
+            allocated.allocated_bytes(),
+            volumes.iter().map(|v| v.allocated_bytes).sum::<u64>()
+        );
+    }
+
+    #[test]
+    fn merges_adjacent_and_overlapping_ranges() {
+        let map = AllocatedMap::new(vec![(30, 40), (0, 10), (10, 20), (35, 50)]);
+        assert!(map.covers(5, 15));
+        assert!(map.covers(30, 50));
+        assert!(!map.covers(15, 25));
+        assert_eq!(map.allocated_bytes(), 40);
+    }
+}
@@ -0,0 +1,329 @@
+//! # Volume Locations
+//!
+//! With `enable_volume_locations`, the partitions of the evidence (MBR or
+//! GPT entries, or a file system at offset 0 taken as partition 0) and the
+//! file system in each are found before the scan starts. The metadata
+//! thread then writes a `volume_locations` record for every carved file and
+//! artefact that starts inside a partition: the partition, the offset and
+//! 512-byte sector relative to its start, and the NTFS cluster, FAT data
+//! cluster, or ext block holding the first byte. For a carved file whose
+//! FAT cluster is in use, the chain from that cluster is read from the FAT,
+//! tying the carve to the allocation it overlaps. Bytes outside every
+//! partition get no record.
+
+use std::sync::Arc;
+
+use tracing::info;
+
+use crate::carve::CarvedFile;
+use crate::evidence::EvidenceSource;
+use crate::metadata::{VolumeLocationRecord, artefact_kind_label};
+use crate::parsers::fat::{self, FatBoot};
+use crate::parsers::ntfs::{self, NtfsBoot};
+use crate::parsers::{ext4, partitions};
+use crate::strings::artifacts::StringArtefact;
+
+const SECTOR: u64 = 512;
+/// Upper bound on the clusters of a FAT chain read for one carve.
+const MAX_CHAIN_CLUSTERS: usize = 65_536;
+
+/// Partitions of the evidence and their file systems; shared with the
+/// metadata thread.
+pub struct VolumeLocator {
+    run_id: String,
+    evidence: Arc<dyn EvidenceSource>,
+    volumes: Vec<Volume>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Volume {
+    partition: u32,
+    offset: u64,
+    size: u64,
+    file_system: Option<FileSystem>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum FileSystem {
+    Ntfs(NtfsBoot),
+    Fat(FatBoot),
+    Ext { block_size: u64 },
+}
+
+impl FileSystem {
+    fn name(&self) -> &'static str {
+        match self {
+            FileSystem::Ntfs(_) => "ntfs",
+            FileSystem::Fat(boot) => boot.fat_type.as_str(),
+            FileSystem::Ext { .. } => "ext",
+        }
+    }
+}
+
+impl VolumeLocator {
+    /// Locator for the partitions of `evidence`; `None` when it has neither
+    /// a partition table nor a known file system at offset 0.
+    pub fn discover(run_id: &str, evidence: Arc<dyn EvidenceSource>) -> Option<Self> {
+        let source = evidence.as_ref();
+        // A volume at offset 0 has no partition table; its boot code could be
+        // misread as one.
+        let volumes: Vec<Volume> = match probe(source, 0) {
+            Some((file_system, size)) => vec![Volume {
+                partition: 0,
+                offset: 0,
+                size,
+                file_system: Some(file_system),
+            }],
+            None => partitions::partitions(source)
+                .into_iter()
+                .map(|partition| {
+                    let file_system = probe(source, partition.offset).map(|(fs, _)| fs);
+                    Volume {
+                        partition: partition.number,
+                        offset: partition.offset,
+                        size: partition.size,
+                        file_system,
+                    }
+                })
+                .collect(),
+        };
+        if volumes.is_empty() {
+            info!("no partitions or file systems found; volume locations are not recorded");
+            return None;
+        }
+        for volume in &volumes {
+            info!(
+                "partition {} at {}: {} byte(s), {}",
+                volume.partition,
+                volume.offset,
+                volume.size,
+                volume
+                    .file_system
+                    .map_or("unknown file system", |fs| fs.name())
+            );
+        }
+        Some(Self {
+            run_id: run_id.to_string(),
+            evidence,
+            volumes,
+        })
+    }
+
+    /// Location of a carved file, with its FAT cluster chain when in use.
+    pub fn locate_file(&self, file: &CarvedFile) -> Option<VolumeLocationRecord> {
+        let (volume, mut record) = self.locate("file", &file.path, file.global_start)?;
+        if let (Some(FileSystem::Fat(boot)), Some(cluster)) = (volume.file_system, record.cluster) {
+            record.cluster_chain =
+                fat::read_chain(self.evidence.as_ref(), &boot, cluster, MAX_CHAIN_CLUSTERS)
+                    .ok()
+                    .filter(|chain| !chain.is_empty())
+                    .map(|chain| cluster_runs(&chain));
+        }
+        Some(record)
+    }
+
+    /// Location of an artefact.
+    pub fn locate_artefact(&self, artefact: &StringArtefact) -> Option<VolumeLocationRecord> {
+        let kind = artefact_kind_label(&artefact.artefact_kind);
+        self.locate("artefact", kind, artefact.global_start)
+            .map(|(_, record)| record)
+    }
+
+    fn locate(
+        &self,
+        subject: &str,
+        reference: &str,
+        global_start: u64,
+    ) -> Option<(Volume, VolumeLocationRecord)> {
+        let volume = *self
+            .volumes
+            .iter()
+            .find(|v| global_start >= v.offset && global_start - v.offset < v.size)?;
+        let relative_offset = global_start - volume.offset;
+        let cluster = volume
+            .file_system
+            .and_then(|fs| cluster_at(&fs, volume.offset, global_start));
+        let record = VolumeLocationRecord {
+            run_id: self.run_id.clone(),
+            subject: subject.to_string(),
+            reference: reference.to_string(),
+            global_start,
+            partition: volume.partition,
+            partition_offset: volume.offset,
+            relative_offset,
+            relative_sector: relative_offset / SECTOR,
+            file_system: volume.file_system.map(|fs| fs.name().to_string()),
+            cluster,
+            cluster_chain: None,
+        };
+        Some((volume, record))
+    }
+}
+
+/// The file system at `offset` and its size; `None` when no known file
+/// system starts there.
+fn probe(evidence: &dyn EvidenceSource, offset: u64) -> Option<(FileSystem, u64)> {
+    if let Ok(boot) = ntfs::read_boot(evidence, offset) {
+        let size = boot.total_clusters * boot.cluster_size;
+        return Some((FileSystem::Ntfs(boot), size));
+    }
+    if let Ok(boot) = fat::read_boot(evidence, offset) {
+        return Some((FileSystem::Fat(boot), boot.volume_size));
+    }
+    if let Ok(sb) = ext4::read_superblock(evidence, offset) {
+        let block_size = sb.block_size;
+        return Some((FileSystem::Ext { block_size }, sb.volume_size()));
+    }
+    None
+}
+
+/// Cluster or block of the file system at `volume_offset` holding
+/// `global_start`; FAT has clusters in its data area only.
+fn cluster_at(file_system: &FileSystem, volume_offset: u64, global_start: u64) -> Option<u64> {
+    let relative = global_start - volume_offset;
+    match file_system {
+        FileSystem::Ntfs(boot) => {
+            let cluster = relative / boot.cluster_size;
+            (cluster < boot.total_clusters).then_some(cluster)
+        }
+        FileSystem::Fat(boot) => {
+            let data = global_start.checked_sub(boot.cluster_offset(2))?;
+            let cluster = data / boot.cluster_size;
+            (cluster < boot.clusters).then_some(cluster + 2)
+        }
+        FileSystem::Ext { block_size } => Some(relative / block_size),
+    }
+}
+
+/// `chain` as comma-separated runs of consecutive clusters (`5-8,12`).
+fn cluster_runs(chain: &[u64]) -> String {
+    let mut runs: Vec<(u64, u64)> = Vec::new();
+    for &cluster in chain {
+        match runs.last_mut() {
+            Some(run) if run.1 + 1 == cluster => run.1 = cluster,
+            _ => runs.push((cluster, cluster)),
+        }
+    }
+    runs.iter()
+        .map(|&(first, last)| {
+            if first == last {
+                first.to_string()
+            } else {
+                format!("{first}-{last}")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evidence::MemorySource;
+    use crate::parsers::ext4::testing::{BLOCK, ext_volume};
+    use crate::parsers::fat::testing::fat16_volume;
+    use crate::strings::artifacts::{ArtefactKind, SOURCE_STRING_SPAN};
+
+    const FAT_LBA: usize = 64;
+
+    /// An MBR disk with a FAT16 partition in slot 1, chaining clusters
+    /// 5, 6, 7, and 10, and an ext partition in slot 3.
+    fn disk() -> (Vec<u8>, usize) {
+        let mut fat = fat16_volume(20_000, &[5, 6, 7, 10]);
+        for (cluster, next) in [(5usize, 6u16), (6, 7), (7, 10)] {
+            fat[512 + cluster * 2..512 + cluster * 2 + 2].copy_from_slice(&next.to_le_bytes());
+        }
+        let ext_lba = FAT_LBA + fat.len() / 512;
+        let ext = ext_volume(&[1, 2], false);
+        let mut disk = vec![0u8; ext_lba * 512];
+        for (slot, kind, lba, len) in [
+            (0usize, 0x06u8, FAT_LBA, fat.len()),
+            (2, 0x83, ext_lba, ext.len()),
+        ] {
+            let entry = 446 + slot * 16;
+            disk[entry + 4] = kind;
+            disk[entry + 8..entry + 12].copy_from_slice(&(lba as u32).to_le_bytes());
+            disk[entry + 12..entry + 16].copy_from_slice(&((len / 512) as u32).to_le_bytes());
+        }
+        disk[510..512].copy_from_slice(&[0x55, 0xAA]);
+        disk[FAT_LBA * 512..].copy_from_slice(&fat);
+        disk.extend_from_slice(&ext);
+        (disk, ext_lba)
+    }
+
+    fn carved(global_start: u64) -> CarvedFile {
+        CarvedFile {
+            run_id: "run".to_string(),
+            file_type: "jpeg".to_string(),
+            signature_type: "jpeg".to_string(),
+            path: "jpeg/a.jpg".to_string(),
+            extension: "jpg".to_string(),
+            global_start,
+            global_end: global_start + 100,
+            size: 100,
+            md5: None,
+            sha256: None,
+            validated: true,
+            truncated: false,
+            errors: Vec::new(),
+            pattern_id: None,
+        }
+    }
+
+    #[test]
+    fn locates_carves_in_partitions_and_reads_fat_chains() {
+        let (disk, ext_lba) = disk();
+        let locator =
+            VolumeLocator::discover("run", Arc::new(MemorySource::new(disk))).expect("partitions");
+
+        // 1 reserved + 2 × 32 FAT + 32 root directory sectors before cluster 2.
+        let fat_start = (FAT_LBA * 512) as u64;
+        let cluster_6 = fat_start + 97 * 512 + 4 * 2048;
+        let record = locator.locate_file(&carved(cluster_6 + 100)).expect("fat");
+        assert_eq!((record.partition, record.partition_offset), (1, fat_start));
+        assert_eq!(record.relative_offset, 97 * 512 + 4 * 2048 + 100);
+        assert_eq!(record.relative_sector, 97 + 16);
+        assert_eq!(record.file_system.as_deref(), Some("fat16"));
+        assert_eq!(record.cluster, Some(6));
+        assert_eq!(record.cluster_chain.as_deref(), Some("6-7,10"));
+
+        // A free cluster has no chain; the FAT itself has no cluster.
+        let free = locator
+            .locate_file(&carved(cluster_6 + 2048 * 2))
+            .expect("fat");
+        assert_eq!((free.cluster, free.cluster_chain), (Some(8), None));
+        let system_area = locator.locate_file(&carved(fat_start + 600)).expect("fat");
+        assert_eq!(
+            (system_area.cluster, system_area.relative_sector),
+            (None, 1)
+        );
+
+        let ext_start = (ext_lba * 512) as u64;
+        let artefact = StringArtefact {
+            run_id: "run".to_string(),
+            artefact_kind: ArtefactKind::Url,
+            content: "http://example.com".to_string(),
+            encoding: "ascii".to_string(),
+            global_start: ext_start + 3 * BLOCK as u64 + 10,
+            global_end: ext_start + 3 * BLOCK as u64 + 28,
+            source_kind: SOURCE_STRING_SPAN.to_string(),
+            source_detail: String::new(),
+        };
+        let record = locator.locate_artefact(&artefact).expect("ext");
+        assert_eq!(
+            (record.subject.as_str(), record.reference.as_str()),
+            ("artefact", "url")
+        );
+        assert_eq!((record.partition, record.cluster), (3, Some(3)));
+        assert_eq!(record.file_system.as_deref(), Some("ext"));
+
+        // The MBR gap lies outside every partition.
+        assert!(locator.locate_file(&carved(4096)).is_none());
+    }
+
+    #[test]
+    fn writes_cluster_chains_as_runs() {
+        assert_eq!(cluster_runs(&[5, 6, 7, 8, 12, 14, 15]), "5-8,12,14-15");
+        assert_eq!(cluster_runs(&[9]), "9");
+    }
+}
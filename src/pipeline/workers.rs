@@ -2,25 +2,55 @@
 //!
 //! Worker thread spawning and management for the processing pipeline.
 
-use std::path::PathBuf;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::thread;
+use std::time::{Duration, Instant};
 
-use crossbeam_channel::{Receiver, Sender};
+use crossbeam_channel::Receiver;
 use tracing::{debug, warn};
 
-use crate::carve::{CarveRegistry, ExtractionContext};
+use crate::carve::{
+    self, CancellationToken, CarveError, CarveRegistry, ExtractionContext, Truncation,
+};
 use crate::chunk::ScanChunk;
+use crate::encryption::StagedOutput;
 use crate::entropy;
 use crate::evidence::EvidenceSource;
-use crate::metadata::MetadataSink;
-use crate::scanner::{NormalizedHit, SignatureScanner};
+use crate::metadata::snapshot::MetadataSnapshots;
+use crate::metadata::{
+    CarveCompanionRecord, CarveSizeRecord, MetadataError, MetadataSink, RunSummary,
+};
+use crate::postprocess::browser_cache::CACHE_BODY_TYPE;
+use crate::postprocess::{PostProcessRegistry, ProcessContext};
+use crate::report::MetadataAggregates;
+use crate::scanner::hybrid::BackendCounters;
+use crate::scanner::{NormalizedHit, ScanRoute, SignatureScanner};
 use crate::strings::artifacts::ArtefactScanConfig;
 use crate::strings::{self, StringScanner, StringSpan};
 
 use super::EntropyConfig;
+use super::arbitration::{CarveArbiter, Verdict};
+use super::compressed::Lznt1Scan;
+use super::eta::StageTimings;
 use super::events::MetadataEvent;
+use super::gaps::CarvedRanges;
+use super::hit_context::{HitContextExport, HitOutcome};
+use super::inflight::InFlight;
+use super::output_sync::OutputSync;
+use super::patterns::PatternStats;
+use super::prefilter::HitPrefilter;
+use super::profile::{ProfileEvent, Queue, RunProfiler};
+use super::qa_sample::QaSampler;
+use super::quarantine::Quarantine;
+use super::queue::{self, StageSender};
+use super::resources::{ResourceLedger, Stage};
+use super::supervisor::{PanicLog, supervise};
+use super::throttle::HitThrottle;
+use super::trace::OffsetTrace;
+use super::volume_locations::VolumeLocator;
 
 /// Job containing a chunk of data to scan
 pub struct ScanJob {
@@ -35,384 +65,1263 @@ pub struct StringJob {
     pub spans: Vec<StringSpan>,
 }
 
-/// Spawn the metadata recording thread
+/// When the carve workers stop carving.
+#[derive(Clone)]
+pub struct CarveLimits {
+    pub max_files: Option<u64>,
+    /// Time budget of one carve (`carve_timeout_secs`).
+    pub timeout: Option<Duration>,
+    /// Run cancellation flag. Once set, in-flight carves stop at their next
+    /// check and queued hits are skipped.
+    pub cancel: Option<Arc<AtomicBool>>,
+    /// Lowest offset of a hit left uncarved by the cancellation.
+    first_abandoned: Arc<AtomicU64>,
+    abandoned: Arc<AtomicU64>,
+}
+
+impl CarveLimits {
+    pub fn new(
+        max_files: Option<u64>,
+        timeout: Option<Duration>,
+        cancel: Option<Arc<AtomicBool>>,
+    ) -> Self {
+        Self {
+            max_files,
+            timeout,
+            cancel,
+            first_abandoned: Arc::new(AtomicU64::new(u64::MAX)),
+            abandoned: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    fn cancelled(&self) -> bool {
+        self.cancel
+            .as_ref()
+            .is_some_and(|flag| flag.load(Ordering::Relaxed))
+    }
+
+    /// Token for one carve starting now.
+    fn token(&self) -> CancellationToken {
+        let token = self
+            .cancel
+            .clone()
+            .map(CancellationToken::new)
+            .unwrap_or_default();
+        match self.timeout {
+            Some(timeout) => token.with_timeout(timeout),
+            None => token,
+        }
+    }
+
+    /// Cancel the run, as when an output cannot be written safely.
+    fn stop(&self) {
+        if let Some(flag) = &self.cancel {
+            flag.store(true, Ordering::Relaxed);
+        }
+    }
+
+    fn abandon(&self, offset: u64) {
+        self.first_abandoned.fetch_min(offset, Ordering::Relaxed);
+        self.abandoned.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Lowest offset of a hit not carved because the run was cancelled; a
+    /// resume has to start at or before it.
+    pub fn first_abandoned(&self) -> Option<u64> {
+        let offset = self.first_abandoned.load(Ordering::Relaxed);
+        (offset != u64::MAX).then_some(offset)
+    }
+
+    /// Hits not carved because the run was cancelled.
+    pub fn abandoned(&self) -> u64 {
+        self.abandoned.load(Ordering::Relaxed)
+    }
+}
+
+/// Per-file bookkeeping of the carve workers besides the metadata record.
+#[derive(Clone, Default)]
+pub struct CarveRecorders {
+    /// Extents of kept carves, for the coverage summary and gap-only string
+    /// scanning.
+    pub carved_ranges: Arc<CarvedRanges>,
+    pub qa_sample: Arc<QaSampler>,
+    /// Set by `quarantine_rules`.
+    pub quarantine: Option<Arc<Quarantine>>,
+    /// Set by `encryption_recipient`; seals each kept carve into the run
+    /// directory.
+    pub staged: Option<Arc<StagedOutput>>,
+    /// Set by `arbitration_overlap`.
+    pub arbiter: Option<Arc<CarveArbiter>>,
+    /// `carve_fsync` and `carve_write_buffer`.
+    pub output_sync: Arc<OutputSync>,
+}
+
+/// Shared instrumentation handed to every worker.
+#[derive(Clone, Default)]
+pub struct WorkerMonitors {
+    pub timings: Arc<StageTimings>,
+    pub panics: Arc<PanicLog>,
+    /// Set by `--trace-offset`.
+    pub trace: Option<Arc<OffsetTrace>>,
+    pub resources: Arc<ResourceLedger>,
+    /// Chunks per backend for the signature and string scanners.
+    pub backends: Arc<ScanBackends>,
+    /// Set by `hit_context_window`.
+    pub hit_context: Option<Arc<HitContextExport>>,
+    /// Set by `enable_profiling`.
+    pub profiler: Option<Arc<RunProfiler>>,
+    /// Set for a streamed input scanned by worker threads.
+    pub in_flight: Option<Arc<InFlight>>,
+}
+
+/// Chunk routes counted by the scan workers.
+#[derive(Debug, Default)]
+pub struct ScanBackends {
+    pub signature: BackendCounters,
+    pub strings: BackendCounters,
+}
+
+/// Spawn the metadata recording thread.
+///
+/// All worker threads run under [`supervise`]: a panic is recorded in `panics`
+/// and the worker loop restarts with the next job.
+///
+/// Events are recorded in the order they arrive. A flush that arrives while a
+/// carve's records are open ([`MetadataEvent::GroupStart`] without its
+/// [`MetadataEvent::GroupEnd`]) is held until no group is open, so it does
+/// not land inside one carve's records; when carves overlap for a whole flush
+/// interval, the next flush is written anyway. Snapshots are taken between
+/// groups only. The run
+/// summary is held until every sender is gone and written last, after the
+/// records of all stages, followed by the resource usage and the final flush.
+///
+/// Photo records are held until then too: once the channel closes they are
+/// grouped by device and session and written before the run summary. NTFS
+/// file records, given in the recorder's aggregates, follow them with the
+/// carved file attributed to each.
+///
+/// With a volume locator, each carved file and artefact is followed by its
+/// volume location, when it lies in a partition.
+///
+/// The thread returns the aggregates with the artefact frequency counters
+/// and the photo groups, so the pipeline can write run summaries after it
+/// joins.
 pub fn spawn_metadata_thread(
-    sink: Box<dyn MetadataSink>,
+    mut recorder: MetadataRecorder,
     rx: Receiver<MetadataEvent>,
-    error_count: Arc<AtomicU64>,
-) -> thread::JoinHandle<()> {
+) -> thread::JoinHandle<MetadataAggregates> {
+    let panics = recorder.monitors.panics.clone();
     thread::spawn(move || {
-        for event in rx {
-            match event {
-                MetadataEvent::File(file) => {
-                    if let Err(err) = sink.record_file(&file) {
-                        error_count.fetch_add(1, Ordering::Relaxed);
-                        warn!("metadata record error: {err}");
-                    }
+        supervise("metadata", &panics, |_| {
+            for event in rx.iter() {
+                recorder.record(event);
+            }
+        });
+        // The channel is closed: no stage can send anything after this.
+        recorder.finish()
+    })
+}
+
+/// Writes events to the sink, on the metadata thread or, in single-threaded
+/// mode, on the pipeline's own thread.
+pub struct MetadataRecorder {
+    sink: Box<dyn MetadataSink>,
+    error_count: Arc<AtomicU64>,
+    locator: Option<VolumeLocator>,
+    monitors: WorkerMonitors,
+    aggregates: MetadataAggregates,
+    /// Held until [`MetadataRecorder::finish`].
+    summary: Option<RunSummary>,
+    snapshots: Option<MetadataSnapshots>,
+    /// Record groups started and not ended yet.
+    open_groups: usize,
+    /// A flush waiting for the open groups to end.
+    flush_held: bool,
+}
+
+impl MetadataRecorder {
+    pub fn new(
+        sink: Box<dyn MetadataSink>,
+        error_count: Arc<AtomicU64>,
+        aggregates: MetadataAggregates,
+        locator: Option<VolumeLocator>,
+        monitors: WorkerMonitors,
+    ) -> Self {
+        Self {
+            sink,
+            error_count,
+            locator,
+            monitors,
+            aggregates,
+            summary: None,
+            snapshots: None,
+            open_groups: 0,
+            flush_held: false,
+        }
+    }
+
+    /// Take periodic snapshots of the metadata between events.
+    pub fn with_snapshots(mut self, snapshots: Option<MetadataSnapshots>) -> Self {
+        self.snapshots = snapshots;
+        self
+    }
+
+    /// Record one event, timed as metadata work.
+    pub fn record(&mut self, event: MetadataEvent) {
+        let started = Instant::now();
+        self.write(event);
+        if self.open_groups == 0
+            && let Some(snapshots) = self.snapshots.as_mut().filter(|s| s.due(started))
+            && let Err(err) = snapshots.take(self.sink.as_ref())
+        {
+            warn!("metadata snapshot failed: {err}");
+        }
+        self.monitors.timings.record_metadata(started.elapsed());
+    }
+
+    /// Write what is held back once no stage can send anything: photos,
+    /// NTFS file records, the run summary, resource usage, and the final
+    /// flush. Returns the aggregates for the run summaries.
+    pub fn finish(mut self) -> MetadataAggregates {
+        let mut photos = std::mem::take(&mut self.aggregates.photos);
+        for photo in photos.assign() {
+            self.aggregates.clocks.record_photo(photo);
+            let result = self.sink.record_photo_device(photo);
+            self.check(result);
+        }
+        self.aggregates.photos = photos;
+        for record in self.aggregates.ntfs.records() {
+            let result = self.sink.record_ntfs_file(record);
+            self.check(result);
+        }
+        if let Some(summary) = self.summary.take() {
+            let result = self.sink.record_run_summary(&summary);
+            self.check(result);
+            let WorkerMonitors {
+                timings, resources, ..
+            } = &self.monitors;
+            resources.add_current_thread(Stage::Metadata);
+            for usage in resources.finish(&summary.run_id, &timings.snapshot()) {
+                let result = self.sink.record_resource_usage(&usage);
+                self.check(result);
+            }
+        }
+        self.flush();
+        self.aggregates
+    }
+
+    fn write(&mut self, event: MetadataEvent) {
+        let sink = self.sink.as_ref();
+        let result = match event {
+            MetadataEvent::File(file) => {
+                let MetadataAggregates { ntfs, clocks, .. } = &mut self.aggregates;
+                if let Some(record) = ntfs.attribute(&file) {
+                    clocks.record_ntfs(file.global_start, &file.path, record);
                 }
-                MetadataEvent::String(artefact) => {
-                    if let Err(err) = sink.record_string(&artefact) {
-                        error_count.fetch_add(1, Ordering::Relaxed);
-                        warn!("metadata record error: {err}");
-                    }
+                if file.file_type == CACHE_BODY_TYPE {
+                    clocks.note_cache_body(&file.path, file.global_start);
                 }
-                MetadataEvent::History(record) => {
-                    if let Err(err) = sink.record_history(&record) {
-                        error_count.fetch_add(1, Ordering::Relaxed);
-                        warn!("metadata record error: {err}");
-                    }
+                let result = sink.record_file(&file);
+                if let Some(trace) = &self.monitors.trace {
+                    trace.metadata_row(&file, &result);
                 }
-                MetadataEvent::Cookie(record) => {
-                    if let Err(err) = sink.record_cookie(&record) {
-                        error_count.fetch_add(1, Ordering::Relaxed);
-                        warn!("metadata record error: {err}");
-                    }
+                if let Some(record) = self.locator.as_ref().and_then(|l| l.locate_file(&file)) {
+                    self.check(sink.record_volume_location(&record));
                 }
-                MetadataEvent::Download(record) => {
-                    if let Err(err) = sink.record_download(&record) {
-                        error_count.fetch_add(1, Ordering::Relaxed);
-                        warn!("metadata record error: {err}");
-                    }
+                result
+            }
+            MetadataEvent::String(artefact) => {
+                self.aggregates.frequencies.record(&artefact);
+                if let Some(record) = self
+                    .locator
+                    .as_ref()
+                    .and_then(|l| l.locate_artefact(&artefact))
+                {
+                    self.check(sink.record_volume_location(&record));
                 }
-                MetadataEvent::RunSummary(summary) => {
-                    if let Err(err) = sink.record_run_summary(&summary) {
-                        error_count.fetch_add(1, Ordering::Relaxed);
-                        warn!("metadata record error: {err}");
-                    }
+                sink.record_string(&artefact)
+            }
+            MetadataEvent::History(record) => sink.record_history(&record),
+            MetadataEvent::Cookie(record) => sink.record_cookie(&record),
+            MetadataEvent::Download(record) => sink.record_download(&record),
+            MetadataEvent::Cache(record) => {
+                self.aggregates.clocks.record_cache(&record);
+                sink.record_cache(&record)
+            }
+            MetadataEvent::LogEvent(record) => sink.record_log_event(&record),
+            MetadataEvent::Container(record) => sink.record_container(&record),
+            MetadataEvent::Message(record) => sink.record_message(&record),
+            MetadataEvent::UsageEvent(record) => sink.record_usage_event(&record),
+            MetadataEvent::AppRecord(record) => sink.record_app_record(&record),
+            MetadataEvent::Prefetch(record) => sink.record_prefetch(&record),
+            MetadataEvent::Shortcut(record) => sink.record_shortcut(&record),
+            MetadataEvent::Executable(record) => sink.record_executable(&record),
+            MetadataEvent::Thumbnail(record) => sink.record_thumbnail(&record),
+            MetadataEvent::Plist(record) => sink.record_plist(&record),
+            MetadataEvent::VideoRepair(record) => sink.record_video_repair(&record),
+            MetadataEvent::ImageInfo(record) => sink.record_image_info(&record),
+            MetadataEvent::PhotoExif(record) => {
+                self.aggregates.photos.record(record);
+                return;
+            }
+            MetadataEvent::EncryptedDocument(record) => sink.record_encrypted_document(&record),
+            MetadataEvent::CarveSize(record) => sink.record_carve_size(&record),
+            MetadataEvent::CarveCompanion(record) => sink.record_carve_companion(&record),
+            MetadataEvent::Classification(record) => sink.record_classification(&record),
+            MetadataEvent::Entropy(region) => sink.record_entropy(&region),
+            MetadataEvent::GroupStart => {
+                self.open_groups += 1;
+                return;
+            }
+            MetadataEvent::GroupEnd => {
+                self.open_groups = self.open_groups.saturating_sub(1);
+                if self.open_groups == 0 && std::mem::take(&mut self.flush_held) {
+                    self.flush();
+                }
+                return;
+            }
+            MetadataEvent::RunSummary(summary) => {
+                if self.summary.replace(summary).is_some() {
+                    warn!("run summary sent twice; keeping the last one");
                 }
-                MetadataEvent::Entropy(region) => {
-                    if let Err(err) = sink.record_entropy(&region) {
-                        error_count.fetch_add(1, Ordering::Relaxed);
-                        warn!("metadata record error: {err}");
+                return;
+            }
+            MetadataEvent::Flush => {
+                // A second flush while groups are still open means carves
+                // keep overlapping; waiting longer would never flush.
+                if self.open_groups == 0 || std::mem::take(&mut self.flush_held) {
+                    self.flush();
+                } else {
+                    self.flush_held = true;
+                }
+                return;
+            }
+        };
+        self.check(result);
+    }
+
+    fn check(&self, result: Result<(), MetadataError>) {
+        if let Err(err) = result {
+            self.error_count.fetch_add(1, Ordering::Relaxed);
+            warn!("metadata record error: {err}");
+        }
+    }
+
+    fn flush(&self) {
+        if let Err(err) = self.sink.flush() {
+            self.error_count.fetch_add(1, Ordering::Relaxed);
+            warn!("metadata flush error: {err}");
+        }
+    }
+}
+
+/// Per-chunk work of a scan worker: signature hits go to the carve queue,
+/// string spans to the string queue, and entropy regions to the metadata
+/// queue.
+#[derive(Clone)]
+pub struct ScanWorker {
+    pub scanner: Arc<dyn SignatureScanner>,
+    pub string_scanner: Option<Arc<dyn StringScanner>>,
+    pub hit_tx: StageSender<NormalizedHit>,
+    pub string_tx: Option<StageSender<StringJob>>,
+    pub meta_tx: StageSender<MetadataEvent>,
+    pub run_id: String,
+    pub entropy_cfg: Option<EntropyConfig>,
+    pub hits_found: Arc<AtomicU64>,
+    pub string_spans: Arc<AtomicU64>,
+    pub lznt1: Option<Arc<Lznt1Scan>>,
+    /// Set by `hit_prefilter`.
+    pub prefilter: Option<Arc<HitPrefilter>>,
+    pub monitors: WorkerMonitors,
+}
+
+impl ScanWorker {
+    /// Scan one chunk.
+    pub fn scan(&self, job: &ScanJob) {
+        let started = Instant::now();
+        let mut chunk_hits = 0u64;
+        let mut send_wait = Duration::ZERO;
+        let effective_valid = job.chunk.valid_length.min(job.data.len() as u64);
+        let valid_len = effective_valid as usize;
+
+        // Scan for file signatures
+        let scan_started = Instant::now();
+        let (hits, route) = self.scanner.scan_chunk_routed(&job.chunk, &job.data);
+        self.monitors.backends.signature.record(route);
+        if route == ScanRoute::Gpu {
+            self.monitors
+                .resources
+                .record_gpu(Stage::Scan, scan_started.elapsed());
+        }
+        for hit in hits {
+            let deferred = hit.local_offset >= effective_valid;
+            let normalized = NormalizedHit {
+                global_offset: job.chunk.start + hit.local_offset,
+                file_type_id: hit.file_type_id,
+                pattern_id: hit.pattern_id,
+            };
+            if let Some(trace) = &self.monitors.trace {
+                trace.hit(&normalized, job.chunk.id, deferred);
+            }
+            if deferred {
+                continue;
+            }
+            self.hits_found.fetch_add(1, Ordering::Relaxed);
+            chunk_hits += 1;
+            if let Some(prefilter) = &self.prefilter {
+                let data = job
+                    .data
+                    .get(hit.local_offset as usize..)
+                    .unwrap_or_default();
+                if !prefilter.admit(&normalized.file_type_id, data) {
+                    if let Some(trace) = &self.monitors.trace {
+                        trace.skipped(&normalized, "first checksum failed in hit_prefilter");
                     }
+                    continue;
                 }
-                MetadataEvent::Flush => {
-                    if let Err(err) = sink.flush() {
-                        error_count.fetch_add(1, Ordering::Relaxed);
-                        warn!("metadata flush error: {err}");
+            }
+            if let Some(in_flight) = &self.monitors.in_flight {
+                in_flight.add(1);
+            }
+            let send_started = Instant::now();
+            let sent = self.hit_tx.send(normalized);
+            send_wait += send_started.elapsed();
+            if let Err(err) = sent {
+                warn!("hit channel closed while sending hit: {err}");
+                break;
+            }
+        }
+
+        // Scan for strings if enabled
+        if let (Some(scanner), Some(tx)) = (&self.string_scanner, &self.string_tx) {
+            let scan_started = Instant::now();
+            let (spans, route) = scanner.scan_chunk_routed(&job.chunk, &job.data);
+            self.monitors.backends.strings.record(route);
+            if route == ScanRoute::Gpu {
+                self.monitors
+                    .resources
+                    .record_gpu(Stage::Scan, scan_started.elapsed());
+            }
+            if !spans.is_empty() {
+                let filtered: Vec<StringSpan> = spans
+                    .into_iter()
+                    .filter(|span| span.local_start < effective_valid)
+                    .collect();
+                if !filtered.is_empty() {
+                    self.string_spans
+                        .fetch_add(filtered.len() as u64, Ordering::Relaxed);
+                    let string_job = StringJob {
+                        chunk: job.chunk.clone(),
+                        data: Arc::clone(&job.data),
+                        spans: filtered,
+                    };
+                    if let Err(err) = tx.send(string_job) {
+                        warn!("string channel closed while sending spans: {err}");
+                        return;
                     }
                 }
             }
         }
-        // Final flush when channel closes
-        if let Err(err) = sink.flush() {
-            error_count.fetch_add(1, Ordering::Relaxed);
-            warn!("metadata flush error: {err}");
+
+        // Detect high entropy regions if enabled
+        if let Some(cfg) = self.entropy_cfg
+            && valid_len >= cfg.window_size
+        {
+            let regions = entropy::detect_entropy_regions(
+                &self.run_id,
+                job.chunk.start,
+                &job.data[..valid_len],
+                cfg.window_size,
+                cfg.threshold,
+            );
+            for region in regions {
+                if let Some(export) = &self.monitors.hit_context {
+                    export.record_entropy(&region);
+                }
+                if let Err(err) = self.meta_tx.send(MetadataEvent::Entropy(region)) {
+                    warn!("metadata channel closed while sending entropy region: {err}");
+                    break;
+                }
+            }
         }
-    })
+
+        // Carve from NTFS-compressed units if enabled
+        if let Some(lznt1) = &self.lznt1 {
+            lznt1.scan_chunk(&job.chunk, &job.data);
+        }
+        let elapsed = started.elapsed();
+        self.monitors
+            .timings
+            .record_scan(effective_valid, chunk_hits, elapsed);
+        if let Some(profiler) = &self.monitors.profiler {
+            profiler.scan_chunk(job.chunk.start, effective_valid, elapsed);
+            if chunk_hits > 0 {
+                profiler.queue_wait(
+                    Queue::Carve,
+                    ProfileEvent::SendWait,
+                    job.chunk.start,
+                    send_wait,
+                );
+            }
+        }
+    }
 }
 
 /// Spawn signature scanning worker threads
 pub fn spawn_scan_workers(
     workers: usize,
-    scanner: Arc<dyn SignatureScanner>,
-    string_scanner: Option<Arc<dyn StringScanner>>,
+    worker: ScanWorker,
     rx: Receiver<ScanJob>,
-    hit_tx: Sender<NormalizedHit>,
-    string_tx: Option<Sender<StringJob>>,
-    meta_tx: Sender<MetadataEvent>,
-    run_id: String,
-    entropy_cfg: Option<EntropyConfig>,
-    hits_found: Arc<AtomicU64>,
-    string_spans: Arc<AtomicU64>,
 ) -> Vec<thread::JoinHandle<()>> {
     let mut handles = Vec::new();
     let worker_count = workers.max(1);
 
     for _ in 0..worker_count {
-        let scanner = scanner.clone();
+        let worker = worker.clone();
         let rx = rx.clone();
-        let hit_tx = hit_tx.clone();
-        let string_scanner = string_scanner.clone();
-        let string_tx = string_tx.clone();
-        let hits_found = hits_found.clone();
-        let string_spans = string_spans.clone();
-        let meta_tx = meta_tx.clone();
-        let run_id = run_id.clone();
-        let entropy_cfg = entropy_cfg;
 
         handles.push(thread::spawn(move || {
-            for job in rx {
-                let effective_valid = job.chunk.valid_length.min(job.data.len() as u64);
-                let valid_len = effective_valid as usize;
+            supervise("scan", &worker.monitors.panics, |cursor| {
+                let mut idle = Instant::now();
+                for job in rx.iter() {
+                    if let Some(profiler) = &worker.monitors.profiler {
+                        profiler.queue_wait(
+                            Queue::Scan,
+                            ProfileEvent::RecvWait,
+                            job.chunk.start,
+                            idle.elapsed(),
+                        );
+                    }
+                    cursor.begin(job.chunk.start);
+                    let _claimed = worker.monitors.in_flight.as_ref().map(|f| f.claim());
+                    worker.scan(&job);
+                    cursor.end();
+                    idle = Instant::now();
+                }
+            });
+            worker.monitors.resources.add_current_thread(Stage::Scan);
+        }));
+    }
 
-                // Scan for file signatures
-                for hit in scanner.scan_chunk(&job.chunk, &job.data) {
-                    if hit.local_offset >= effective_valid {
-                        continue;
+    handles
+}
+
+/// Per-hit work of a carve worker: runs the handler and sends the carve's
+/// records to the metadata queue.
+#[derive(Clone)]
+pub struct CarveWorker {
+    registry: Arc<CarveRegistry>,
+    evidence: Arc<dyn EvidenceSource>,
+    run_id: String,
+    run_output_dir: PathBuf,
+    carved_root: PathBuf,
+    meta_tx: StageSender<MetadataEvent>,
+    files_carved: Arc<AtomicU64>,
+    limits: CarveLimits,
+    carve_errors: Arc<AtomicU64>,
+    postprocessors: Arc<PostProcessRegistry>,
+    throttle: Arc<HitThrottle>,
+    patterns: Arc<PatternStats>,
+    recorders: CarveRecorders,
+    monitors: WorkerMonitors,
+}
+
+/// What every carve worker of a run shares: the handlers and evidence, where
+/// carves and their records go, and the run's carve limits and counters.
+pub struct CarveStage {
+    pub registry: Arc<CarveRegistry>,
+    pub evidence: Arc<dyn EvidenceSource>,
+    pub run_id: String,
+    pub run_output_dir: PathBuf,
+    pub meta_tx: StageSender<MetadataEvent>,
+    pub files_carved: Arc<AtomicU64>,
+    pub limits: CarveLimits,
+    pub carve_errors: Arc<AtomicU64>,
+    pub postprocessors: Arc<PostProcessRegistry>,
+    pub throttle: Arc<HitThrottle>,
+    pub patterns: Arc<PatternStats>,
+}
+
+impl CarveWorker {
+    pub fn new(stage: CarveStage, recorders: CarveRecorders, monitors: WorkerMonitors) -> Self {
+        let CarveStage {
+            registry,
+            evidence,
+            run_id,
+            run_output_dir,
+            meta_tx,
+            files_carved,
+            limits,
+            carve_errors,
+            postprocessors,
+            throttle,
+            patterns,
+        } = stage;
+        Self {
+            registry,
+            evidence,
+            run_id,
+            carved_root: run_output_dir.join("carved"),
+            run_output_dir,
+            meta_tx,
+            files_carved,
+            limits,
+            carve_errors,
+            postprocessors,
+            throttle,
+            patterns,
+            recorders,
+            monitors,
+        }
+    }
+
+    /// Install `carve_write_buffer` on the calling thread and forget the
+    /// output directories an earlier run created from it.
+    pub fn prepare_thread(&self) {
+        self.recorders.output_sync.prepare_thread();
+    }
+
+    /// Carve one hit; `Break` once `max_files` is reached.
+    pub fn carve(&self, hit: NormalizedHit) -> ControlFlow<()> {
+        let mut attempt = CarveAttempt::default();
+        let Some(profiler) = &self.monitors.profiler else {
+            return self.carve_hit(hit, &mut attempt);
+        };
+        let started = Instant::now();
+        let profiled = hit.clone();
+        let flow = self.carve_hit(hit, &mut attempt);
+        profiler.carve(&profiled, attempt.outcome, attempt.bytes, started.elapsed());
+        flow
+    }
+
+    /// [`CarveWorker::carve`], noting the handler's outcome in `attempt`.
+    fn carve_hit(&self, hit: NormalizedHit, attempt: &mut CarveAttempt) -> ControlFlow<()> {
+        let Self {
+            registry,
+            evidence,
+            run_id,
+            run_output_dir,
+            carved_root,
+            meta_tx,
+            files_carved,
+            limits,
+            carve_errors,
+            postprocessors,
+            throttle,
+            patterns,
+            recorders:
+                CarveRecorders {
+                    carved_ranges,
+                    qa_sample,
+                    quarantine,
+                    staged,
+                    arbiter,
+                    output_sync,
+                },
+            monitors:
+                WorkerMonitors {
+                    timings,
+                    trace,
+                    hit_context,
+                    ..
+                },
+        } = self;
+        let ctx = ExtractionContext {
+            run_id,
+            output_root: carved_root,
+            evidence: evidence.as_ref(),
+        };
+        let process_ctx = ProcessContext {
+            run_id,
+            run_output_dir,
+            meta_tx,
+        };
+
+        if limits.cancelled() {
+            limits.abandon(hit.global_offset);
+            if let Some(trace) = &trace {
+                trace.skipped(&hit, "run cancelled");
+            }
+            return ControlFlow::Continue(());
+        }
+        if let Some(limit) = limits.max_files
+            && files_carved.load(Ordering::Relaxed) >= limit
+        {
+            if let Some(trace) = &trace {
+                trace.skipped(&hit, "max_files reached");
+            }
+            return ControlFlow::Break(());
+        }
+        let started = Instant::now();
+        let handler = match registry.get(&hit.file_type_id) {
+            Some(handler) => handler,
+            None => {
+                debug!("no handler for file_type={}", hit.file_type_id);
+                if let Some(trace) = &trace {
+                    trace.skipped(&hit, "no handler registered for type");
+                }
+                timings.record_carve(started.elapsed());
+                return ControlFlow::Continue(());
+            }
+        };
+        if !patterns.admit(&hit) {
+            if let Some(trace) = &trace {
+                trace.skipped(&hit, "pattern auto-disabled");
+            }
+            timings.record_carve(started.elapsed());
+            return ControlFlow::Continue(());
+        }
+        let Some(throttled) = throttle.admit(&hit) else {
+            if let Some(trace) = &trace {
+                trace.skipped(&hit, "suppressed by hit_cluster_window");
+            }
+            timings.record_carve(started.elapsed());
+            return ControlFlow::Continue(());
+        };
+
+        carve::take_rejection();
+        carve::take_declared_size();
+        carve::take_companions();
+        let arbitration = arbiter.as_deref().map(|arbiter| arbiter.begin(&hit));
+        let result = carve::with_cancellation(limits.token(), || handler.process_hit(&hit, &ctx));
+        let result = carve::confine(carved_root, result);
+        let mut companions = carve::take_companions();
+        companions.retain(
+            |companion| match carve::confined_path(carved_root, &companion.path) {
+                Ok(_) => true,
+                Err(err) => {
+                    warn!("dropping companion of hit at {}: {err}", hit.global_offset);
+                    false
+                }
+            },
+        );
+        attempt.outcome = hit_outcome(&result);
+        if let Ok(Some(file)) = &result {
+            attempt.bytes = file.size;
+        }
+        if let Some(trace) = &trace {
+            trace.carve_result(&hit, handler.file_type(), &result, carve::take_rejection());
+        }
+        // Windows around quarantined carves are not exported, so
+        // the window of a carve is recorded once it is cleared.
+        let context = hit_context.as_ref().zip(hit_outcome(&result));
+        if !matches!(result, Ok(Some(_)))
+            && let Some((export, outcome)) = context
+        {
+            export.record_hit(&hit, outcome);
+        }
+        throttled.finish(matches!(result, Ok(Some(_))));
+        patterns.finish(&hit, matches!(result, Ok(Some(_))));
+        let (result, kept) = match (result, arbitration) {
+            (Ok(Some(file)), Some(arbitration)) => match arbitration.settle(&file) {
+                Verdict::Keep(kept) => {
+                    for output in kept.replaced() {
+                        carve::discard(output);
                     }
-                    hits_found.fetch_add(1, Ordering::Relaxed);
-                    let global_offset = job.chunk.start + hit.local_offset;
-                    let normalized = NormalizedHit {
-                        global_offset,
-                        file_type_id: hit.file_type_id,
-                        pattern_id: hit.pattern_id,
-                    };
-                    if let Err(err) = hit_tx.send(normalized) {
-                        warn!("hit channel closed while sending hit: {err}");
-                        break;
+                    (Ok(Some(file)), Some(kept))
+                }
+                Verdict::Suppress => {
+                    carve::discard(&carved_root.join(&file.path));
+                    if let Some(trace) = &trace {
+                        trace.skipped(&hit, "suppressed by overlap arbitration");
                     }
+                    (Ok(None), None)
                 }
-
-                // Scan for strings if enabled
-                if let (Some(scanner), Some(tx)) = (&string_scanner, &string_tx) {
-                    let spans = scanner.scan_chunk(&job.chunk, &job.data);
-                    if !spans.is_empty() {
-                        let filtered: Vec<StringSpan> = spans
-                            .into_iter()
-                            .filter(|span| span.local_start < effective_valid)
-                            .collect();
-                        if !filtered.is_empty() {
-                            string_spans.fetch_add(filtered.len() as u64, Ordering::Relaxed);
-                            let string_job = StringJob {
-                                chunk: job.chunk.clone(),
-                                data: Arc::clone(&job.data),
-                                spans: filtered,
-                            };
-                            if let Err(err) = tx.send(string_job) {
-                                warn!("string channel closed while sending spans: {err}");
-                                break;
-                            }
-                        }
+            },
+            (result, _) => (result, None),
+        };
+        if !matches!(result, Ok(Some(_))) {
+            for companion in &companions {
+                carve::discard(&carved_root.join(&companion.path));
+            }
+        }
+        match result {
+            Ok(Some(file)) => {
+                let carved = 1 + companions.len() as u64;
+                let new_total = files_carved.fetch_add(carved, Ordering::Relaxed) + carved;
+                carved_ranges.insert(file.global_start, file.global_end);
+                for companion in &companions {
+                    carved_ranges.insert(companion.global_start, companion.global_end);
+                }
+                let path = carved_root.join(&file.path);
+                let held = match &quarantine {
+                    Some(quarantine) => screen_carve(
+                        quarantine,
+                        postprocessors,
+                        &hit.file_type_id,
+                        &file,
+                        &path,
+                        &process_ctx,
+                    ),
+                    None => Screening::Clear(None),
+                };
+                let Screening::Clear(held) = held else {
+                    for companion in &companions {
+                        carve::discard(&carved_root.join(&companion.path));
                     }
+                    timings.record_carve(started.elapsed());
+                    if limits.max_files.is_some_and(|limit| new_total >= limit) {
+                        return ControlFlow::Break(());
+                    }
+                    return ControlFlow::Continue(());
+                };
+                if let Some((export, outcome)) = context {
+                    export.record_hit(&hit, outcome);
                 }
-
-                // Detect high entropy regions if enabled
-                if let Some(cfg) = entropy_cfg {
-                    if valid_len >= cfg.window_size {
-                        let regions = entropy::detect_entropy_regions(
-                            &run_id,
-                            job.chunk.start,
-                            &job.data[..valid_len],
-                            cfg.window_size,
-                            cfg.threshold,
-                        );
-                        for region in regions {
-                            if let Err(err) = meta_tx.send(MetadataEvent::Entropy(region)) {
+                let keep = registry.keeps(&hit.file_type_id, &file);
+                let sizing = carve_size_record(&file, evidence.len());
+                let linked: Vec<MetadataEvent> = companions
+                    .iter()
+                    .flat_map(|companion| {
+                        [
+                            MetadataEvent::File(companion.clone()),
+                            MetadataEvent::CarveCompanion(companion_record(&file, companion)),
+                        ]
+                    })
+                    .collect();
+                let group = RecordGroup::open(meta_tx);
+                postprocessors.run(&hit.file_type_id, &file, &path, &process_ctx);
+                qa_sample.offer(&file, &path, run_output_dir);
+                let companion_paths = companions.iter().map(|c| carved_root.join(&c.path));
+                let mut output = None;
+                for (index, path) in std::iter::once(path).chain(companion_paths).enumerate() {
+                    if !keep {
+                        carve::discard(&path);
+                        continue;
+                    }
+                    let path = match &staged {
+                        Some(staged) => match staged.seal(&path) {
+                            Ok(sealed) => sealed,
+                            Err(err) => {
                                 warn!(
-                                    "metadata channel closed while sending entropy region: {err}"
+                                    "failed to encrypt {}: {err}; stopping the run",
+                                    path.display()
                                 );
-                                break;
+                                limits.stop();
+                                continue;
                             }
-                        }
+                        },
+                        None => path,
+                    };
+                    if index == 0 {
+                        output = Some(path.clone());
                     }
+                    output_sync.kept(path);
+                }
+                // The file's own records follow its sync, so with `per_file`
+                // a recorded carve is already durable.
+                let records = std::iter::once(MetadataEvent::File(file))
+                    .chain(sizing.map(MetadataEvent::CarveSize))
+                    .chain(linked)
+                    .chain(held.into_iter().flatten());
+                for event in records {
+                    if let Err(err) = meta_tx.send(event) {
+                        warn!("metadata channel closed while sending carved file: {err}");
+                    }
+                }
+                drop(group);
+                // A better carve of the same bytes may have arrived while
+                // this one was written; it is deleted in that case.
+                if let (Some(kept), Some(output)) = (kept, output)
+                    && kept.release(&output)
+                {
+                    carve::discard(&output);
+                }
+                timings.record_carve(started.elapsed());
+                if let Some(limit) = limits.max_files
+                    && new_total >= limit
+                {
+                    return ControlFlow::Break(());
                 }
             }
-        }));
+            Ok(None) => timings.record_carve(started.elapsed()),
+            Err(CarveError::Cancelled) => {
+                timings.record_carve(started.elapsed());
+                limits.abandon(hit.global_offset);
+                debug!(
+                    "carve at offset {} stopped: run cancelled",
+                    hit.global_offset
+                );
+            }
+            Err(err) => {
+                timings.record_carve(started.elapsed());
+                carve_errors.fetch_add(1, Ordering::Relaxed);
+                warn!("carve error at offset {}: {err}", hit.global_offset);
+            }
+        }
+        ControlFlow::Continue(())
     }
-
-    handles
 }
 
 /// Spawn file carving worker threads
 pub fn spawn_carve_workers(
     workers: usize,
-    registry: Arc<CarveRegistry>,
-    evidence: Arc<dyn EvidenceSource>,
-    run_id: String,
-    run_output_dir: PathBuf,
+    worker: CarveWorker,
     rx: Receiver<NormalizedHit>,
-    meta_tx: Sender<MetadataEvent>,
-    files_carved: Arc<AtomicU64>,
-    enable_sqlite_page_recovery: bool,
-    max_files: Option<u64>,
-    carve_errors: Arc<AtomicU64>,
-    sqlite_errors: Arc<AtomicU64>,
 ) -> Vec<thread::JoinHandle<()>> {
     let mut handles = Vec::new();
     let worker_count = workers.max(1);
 
     for _ in 0..worker_count {
-        let registry = registry.clone();
-        let evidence = evidence.clone();
-        let run_id = run_id.clone();
-        let run_output_dir = run_output_dir.clone();
+        let worker = worker.clone();
         let rx = rx.clone();
-        let meta_tx = meta_tx.clone();
-        let files_carved = files_carved.clone();
-        let max_files = max_files;
-        let carve_errors = carve_errors.clone();
-        let sqlite_errors = sqlite_errors.clone();
 
         handles.push(thread::spawn(move || {
-            let carved_root = run_output_dir.join("carved");
-            let ctx = ExtractionContext {
-                run_id: &run_id,
-                output_root: &carved_root,
-                evidence: evidence.as_ref(),
-            };
-
-            for hit in rx {
-                if let Some(limit) = max_files {
-                    if files_carved.load(Ordering::Relaxed) >= limit {
-                        break;
-                    }
-                }
-                let handler = match registry.get(&hit.file_type_id) {
-                    Some(handler) => handler,
-                    None => {
-                        debug!("no handler for file_type={}", hit.file_type_id);
-                        continue;
-                    }
-                };
-
-                match handler.process_hit(&hit, &ctx) {
-                    Ok(Some(file)) => {
-                        let new_total = files_carved.fetch_add(1, Ordering::Relaxed) + 1;
-                        let path = carved_root.join(&file.path);
-                        let file_type = file.file_type.clone();
-                        let rel_path = file.path.clone();
-                        if let Err(err) = meta_tx.send(MetadataEvent::File(file)) {
-                            warn!("metadata channel closed while sending carved file: {err}");
-                        }
-
-                        // Process SQLite files for browser artifacts
-                        if file_type == "sqlite" {
-                            process_sqlite_artifacts(
-                                &path,
-                                &run_id,
-                                &rel_path,
-                                &meta_tx,
-                                enable_sqlite_page_recovery,
-                                &sqlite_errors,
-                            );
-                        }
-                        if let Some(limit) = max_files {
-                            if new_total >= limit {
-                                break;
-                            }
-                        }
+            supervise("carve", &worker.monitors.panics, |cursor| {
+                worker.prepare_thread();
+                let mut idle = Instant::now();
+                for hit in rx.iter() {
+                    if let Some(profiler) = &worker.monitors.profiler {
+                        profiler.queue_wait(
+                            Queue::Carve,
+                            ProfileEvent::RecvWait,
+                            hit.global_offset,
+                            idle.elapsed(),
+                        );
                     }
-                    Ok(None) => {}
-                    Err(err) => {
-                        carve_errors.fetch_add(1, Ordering::Relaxed);
-                        warn!("carve error at offset {}: {err}", hit.global_offset);
+                    cursor.begin(hit.global_offset);
+                    let _claimed = worker.monitors.in_flight.as_ref().map(|f| f.claim());
+                    if worker.carve(hit).is_break() {
+                        break;
                     }
+                    idle = Instant::now();
                 }
-            }
+            });
+            worker.monitors.resources.add_current_thread(Stage::Carve);
         }));
     }
 
     handles
 }
 
-/// Process SQLite files for browser artifacts (history, cookies, downloads)
-fn process_sqlite_artifacts(
-    path: &std::path::Path,
-    run_id: &str,
-    rel_path: &str,
-    meta_tx: &Sender<MetadataEvent>,
-    enable_page_recovery: bool,
-    sqlite_errors: &Arc<AtomicU64>,
-) {
-    // Extract browser history
-    let mut records =
-        match crate::parsers::sqlite_db::extract_browser_history(path, run_id, rel_path) {
-            Ok(records) => records,
-            Err(err) => {
-                sqlite_errors.fetch_add(1, Ordering::Relaxed);
-                warn!("sqlite parse failed for {}: {err}", path.display());
-                Vec::new()
-            }
-        };
+/// What the handler made of a hit, for the profiler; the default is a hit
+/// skipped before its handler ran.
+#[derive(Debug, Default)]
+struct CarveAttempt {
+    outcome: Option<HitOutcome>,
+    bytes: u64,
+}
 
-    // Try page-level recovery if no records found
-    if records.is_empty() && enable_page_recovery {
-        match crate::parsers::sqlite_pages::extract_history_from_pages(path, run_id, rel_path) {
-            Ok(mut recovered) => records.append(&mut recovered),
-            Err(err) => {
-                sqlite_errors.fetch_add(1, Ordering::Relaxed);
-                warn!("sqlite page recovery failed for {}: {err}", path.display());
-            }
+/// Whether a carve stays in the output. `Clear` holds the records of
+/// post-processors that already ran because a rule needed their labels.
+enum Screening {
+    Quarantined,
+    Clear(Option<Vec<MetadataEvent>>),
+}
+
+/// Check a carve against the quarantine rules and move it there when one
+/// matches. With label rules, the post-processors run here and their
+/// records are held until the labels are checked.
+fn screen_carve(
+    quarantine: &Quarantine,
+    postprocessors: &PostProcessRegistry,
+    file_type_id: &str,
+    file: &carve::CarvedFile,
+    path: &Path,
+    ctx: &ProcessContext,
+) -> Screening {
+    if let Some(matched) = quarantine.screen(file, path) {
+        quarantine.isolate(file, path, matched);
+        return Screening::Quarantined;
+    }
+    if !quarantine.needs_labels() || !postprocessors.handles(file_type_id) {
+        return Screening::Clear(None);
+    }
+    let (held_tx, held) = queue::inline();
+    let held_ctx = ProcessContext {
+        meta_tx: &held_tx,
+        ..*ctx
+    };
+    postprocessors.run(file_type_id, file, path, &held_ctx);
+    let events = held.drain();
+    match quarantine.screen_labels(file, path, &events) {
+        Some(matched) => {
+            quarantine.isolate(file, path, matched);
+            Screening::Quarantined
         }
+        None => Screening::Clear(Some(events)),
     }
+}
 
-    for record in records {
-        if let Err(err) = meta_tx.send(MetadataEvent::History(record)) {
-            warn!("metadata channel closed while sending history record: {err}");
-            return;
-        }
+/// Brackets one carve's records with [`MetadataEvent::GroupStart`] and
+/// [`MetadataEvent::GroupEnd`]; the end is sent on drop, so a post-processor
+/// panic does not leave the group open.
+struct RecordGroup<'a>(&'a StageSender<MetadataEvent>);
+
+impl<'a> RecordGroup<'a> {
+    fn open(meta_tx: &'a StageSender<MetadataEvent>) -> Self {
+        let _ = meta_tx.send(MetadataEvent::GroupStart);
+        Self(meta_tx)
     }
+}
 
-    // Extract browser cookies
-    match crate::parsers::sqlite_db::extract_browser_cookies(path, run_id, rel_path) {
-        Ok(records) => {
-            for record in records {
-                if let Err(err) = meta_tx.send(MetadataEvent::Cookie(record)) {
-                    warn!("metadata channel closed while sending cookie record: {err}");
-                    return;
-                }
-            }
-        }
-        Err(err) => {
-            sqlite_errors.fetch_add(1, Ordering::Relaxed);
-            warn!("sqlite cookie parse failed for {}: {err}", path.display());
-        }
+impl Drop for RecordGroup<'_> {
+    fn drop(&mut self) {
+        let _ = self.0.send(MetadataEvent::GroupEnd);
     }
+}
 
-    // Extract browser downloads
-    match crate::parsers::sqlite_db::extract_browser_downloads(path, run_id, rel_path) {
-        Ok(records) => {
-            for record in records {
-                if let Err(err) = meta_tx.send(MetadataEvent::Download(record)) {
-                    warn!("metadata channel closed while sending download record: {err}");
-                    return;
+/// Label of a carve attempt for the hit context export; `None` for a carve
+/// stopped by cancellation.
+fn hit_outcome(result: &Result<Option<carve::CarvedFile>, CarveError>) -> Option<HitOutcome> {
+    match result {
+        Ok(Some(file)) if file.validated => Some(HitOutcome::Validated),
+        Ok(Some(_)) => Some(HitOutcome::Carved),
+        Ok(None) => Some(HitOutcome::Rejected),
+        Err(CarveError::Cancelled) => None,
+        Err(_) => Some(HitOutcome::Failed),
+    }
+}
+
+/// Sizing record for a carve whose handler noted a declared size or that came
+/// out truncated; `None` for a complete carve of unknown declared size.
+fn carve_size_record(file: &carve::CarvedFile, evidence_len: u64) -> Option<CarveSizeRecord> {
+    let declared = carve::take_declared_size();
+    let truncation = Truncation::of(file, evidence_len);
+    if declared.is_none() && truncation.is_none() {
+        return None;
+    }
+    Some(CarveSizeRecord {
+        run_id: file.run_id.clone(),
+        file_type: file.file_type.clone(),
+        path: file.path.clone(),
+        global_start: file.global_start,
+        size: file.size,
+        declared_size: declared.map(|d| d.size),
+        declared_by: declared.map(|d| d.source.to_string()),
+        truncation,
+    })
+}
+
+/// Link between `file` and a companion its handler carved with it.
+fn companion_record(
+    file: &carve::CarvedFile,
+    companion: &carve::CarvedFile,
+) -> CarveCompanionRecord {
+    CarveCompanionRecord {
+        run_id: file.run_id.clone(),
+        file_type: file.file_type.clone(),
+        path: file.path.clone(),
+        global_start: file.global_start,
+        companion_type: companion.file_type.clone(),
+        companion_path: companion.path.clone(),
+        companion_start: companion.global_start,
+        companion_size: companion.size,
+    }
+}
+
+/// Per-job work of a string worker: artefacts and log events from the
+/// spans of one chunk go to the metadata queue.
+#[derive(Clone)]
+pub struct StringWorker {
+    pub run_id: String,
+    pub meta_tx: StageSender<MetadataEvent>,
+    pub artefacts_found: Arc<AtomicU64>,
+    pub scan_cfg: ArtefactScanConfig,
+    pub monitors: WorkerMonitors,
+}
+
+impl StringWorker {
+    /// Extract artefacts from the spans of one chunk.
+    pub fn extract(&self, job: StringJob) {
+        let started = Instant::now();
+        for span in job.spans {
+            let start = span.local_start as usize;
+            let end = start.saturating_add(span.length as usize);
+            if end > job.data.len() {
+                continue;
+            }
+            let slice = &job.data[start..end];
+            let mut artefacts = strings::artifacts::extract_artefacts(
+                &self.run_id,
+                job.chunk.start,
+                span.local_start,
+                span.flags,
+                slice,
+                self.scan_cfg,
+            );
+            if !artefacts.is_empty() {
+                let detail = strings::artifacts::span_source_detail(
+                    span.chunk_id,
+                    job.chunk.start + span.local_start,
+                    span.length as u64,
+                );
+                for artefact in &mut artefacts {
+                    artefact.source_detail.clone_from(&detail);
+                }
+            }
+            self.artefacts_found
+                .fetch_add(artefacts.len() as u64, Ordering::Relaxed);
+            for artefact in artefacts {
+                if let Err(err) = self.meta_tx.send(MetadataEvent::String(artefact)) {
+                    warn!("metadata channel closed while sending string artefact: {err}");
+                    break;
+                }
+            }
+            if !self.scan_cfg.logs {
+                continue;
+            }
+            let events = strings::artifacts::extract_log_events(
+                &self.run_id,
+                job.chunk.start,
+                span.local_start,
+                span.flags,
+                slice,
+            );
+            self.artefacts_found
+                .fetch_add(events.len() as u64, Ordering::Relaxed);
+            for event in events {
+                if let Err(err) = self.meta_tx.send(MetadataEvent::LogEvent(event)) {
+                    warn!("metadata channel closed while sending log event: {err}");
+                    break;
                 }
             }
         }
-        Err(err) => {
-            sqlite_errors.fetch_add(1, Ordering::Relaxed);
-            warn!("sqlite download parse failed for {}: {err}", path.display());
-        }
+        self.monitors.timings.record_strings(started.elapsed());
     }
 }
 
 /// Spawn string artefact extraction worker threads
 pub fn spawn_string_workers(
     workers: usize,
-    run_id: String,
+    worker: StringWorker,
     rx: Receiver<StringJob>,
-    meta_tx: Sender<MetadataEvent>,
-    artefacts_found: Arc<AtomicU64>,
-    scan_cfg: ArtefactScanConfig,
 ) -> Vec<thread::JoinHandle<()>> {
     let mut handles = Vec::new();
     let worker_count = workers.max(1);
 
     for _ in 0..worker_count {
+        let worker = worker.clone();
         let rx = rx.clone();
-        let meta_tx = meta_tx.clone();
-        let run_id = run_id.clone();
-        let artefacts_found = artefacts_found.clone();
 
         handles.push(thread::spawn(move || {
-            for job in rx {
-                for span in job.spans {
-                    let start = span.local_start as usize;
-                    let end = start.saturating_add(span.length as usize);
-                    if end > job.data.len() {
-                        continue;
-                    }
-                    let slice = &job.data[start..end];
-                    let artefacts = strings::artifacts::extract_artefacts(
-                        &run_id,
-                        job.chunk.start,
-                        span.local_start,
-                        span.flags,
-                        slice,
-                        scan_cfg,
-                    );
-                    artefacts_found.fetch_add(artefacts.len() as u64, Ordering::Relaxed);
-                    for artefact in artefacts {
-                        if let Err(err) = meta_tx.send(MetadataEvent::String(artefact)) {
-                            warn!("metadata channel closed while sending string artefact: {err}");
-                            break;
-                        }
-                    }
+            supervise("string", &worker.monitors.panics, |cursor| {
+                for job in rx.iter() {
+                    cursor.begin(job.chunk.start);
+                    worker.extract(job);
+                    cursor.end();
                 }
-            }
+            });
+            worker.monitors.resources.add_current_thread(Stage::Strings);
         }));
     }
 
     handles
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::metadata::ResourceUsage;
+
+    /// Logs the order of the calls it receives.
+    struct OrderSink {
+        calls: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl OrderSink {
+        fn log(&self, call: String) -> Result<(), MetadataError> {
+            self.calls.lock().unwrap().push(call);
+            Ok(())
+        }
+    }
+
+    impl MetadataSink for OrderSink {
+        fn record_file(&self, file: &carve::CarvedFile) -> Result<(), MetadataError> {
+            self.log(format!("file {}", file.path))
+        }
+
+        fn record_run_summary(&self, _summary: &RunSummary) -> Result<(), MetadataError> {
+            self.log("summary".to_string())
+        }
+
+        fn record_resource_usage(&self, _usage: &ResourceUsage) -> Result<(), MetadataError> {
+            self.log("usage".to_string())
+        }
+
+        fn flush(&self) -> Result<(), MetadataError> {
+            self.log("flush".to_string())
+        }
+    }
+
+    fn file(path: &str) -> MetadataEvent {
+        MetadataEvent::File(carve::CarvedFile {
+            run_id: "run".to_string(),
+            file_type: "magic".to_string(),
+            signature_type: "magic".to_string(),
+            path: path.to_string(),
+            extension: "bin".to_string(),
+            global_start: 0,
+            global_end: 7,
+            size: 8,
+            md5: None,
+            sha256: None,
+            validated: true,
+            truncated: false,
+            errors: Vec::new(),
+            pattern_id: None,
+        })
+    }
+
+    fn summary() -> MetadataEvent {
+        MetadataEvent::RunSummary(RunSummary {
+            run_id: "run".to_string(),
+            bytes_scanned: 0,
+            chunks_processed: 0,
+            hits_found: 0,
+            files_carved: 3,
+            string_spans: 0,
+            artefacts_extracted: 0,
+        })
+    }
+
+    #[test]
+    fn writes_the_summary_after_late_events() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let sink = Box::new(OrderSink {
+            calls: calls.clone(),
+        });
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let monitors = WorkerMonitors {
+            timings: Arc::new(StageTimings::new()),
+            panics: Arc::new(PanicLog::new()),
+            trace: None,
+            resources: Arc::new(ResourceLedger::new()),
+            backends: Arc::new(ScanBackends::default()),
+            hit_context: None,
+            profiler: None,
+            in_flight: None,
+        };
+        let recorder = MetadataRecorder::new(
+            sink,
+            Arc::new(AtomicU64::new(0)),
+            MetadataAggregates::default(),
+            None,
+            monitors,
+        );
+        let handle = spawn_metadata_thread(recorder, rx);
+
+        // A carve worker still running when the summary is sent.
+        let late = tx.clone();
+        tx.send(file("a.bin")).unwrap();
+        tx.send(summary()).unwrap();
+        drop(tx);
+        late.send(MetadataEvent::GroupStart).unwrap();
+        late.send(file("b.bin")).unwrap();
+        late.send(MetadataEvent::Flush).unwrap();
+        late.send(file("c.bin")).unwrap();
+        late.send(MetadataEvent::GroupEnd).unwrap();
+        drop(late);
+        handle.join().unwrap();
+
+        let calls = calls.lock().unwrap();
+        assert_eq!(
+            &calls[..5],
+            ["file a.bin", "file b.bin", "file c.bin", "flush", "summary"]
+        );
+        assert_eq!(calls.last().map(String::as_str), Some("flush"));
+        assert!(calls[5..calls.len() - 1].iter().all(|call| call == "usage"));
+    }
+}
@@ -0,0 +1,128 @@
+//! Notes, iMessages, and usage events from carved Apple databases.
+
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use tracing::warn;
+
+use crate::carve::CarvedFile;
+use crate::parsers::apple;
+use crate::pipeline::events::MetadataEvent;
+
+use super::{PostProcessError, PostProcessor, ProcessContext};
+
+/// Reads Apple Notes, iMessage `chat.db`, and KnowledgeC databases. Other
+/// SQLite databases produce no records. Parse failures are counted in the
+/// shared SQLite error counter, like [`super::SqliteBrowserProcessor`].
+pub struct AppleDatabaseProcessor {
+    errors: Arc<AtomicU64>,
+}
+
+impl AppleDatabaseProcessor {
+    pub fn new(errors: Arc<AtomicU64>) -> Self {
+        Self { errors }
+    }
+}
+
+impl PostProcessor for AppleDatabaseProcessor {
+    fn name(&self) -> &str {
+        "apple_databases"
+    }
+
+    fn process(
+        &self,
+        file: &CarvedFile,
+        path: &Path,
+        ctx: &ProcessContext,
+    ) -> Result<(), PostProcessError> {
+        let found = match apple::extract_apple_artefacts(path, ctx.run_id, &file.path) {
+            Ok(found) => found,
+            Err(err) => {
+                self.errors.fetch_add(1, Ordering::Relaxed);
+                warn!("apple database parse failed for {}: {err}", path.display());
+                return Ok(());
+            }
+        };
+        let events = found
+            .messages
+            .into_iter()
+            .map(MetadataEvent::Message)
+            .chain(
+                found
+                    .usage_events
+                    .into_iter()
+                    .map(MetadataEvent::UsageEvent),
+            );
+        for event in events {
+            if let Err(err) = ctx.meta_tx.send(event) {
+                warn!("metadata channel closed while sending apple record: {err}");
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::apple::tests::note_blob;
+
+    #[test]
+    fn sends_notes_as_messages() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("NoteStore.sqlite");
+        let conn = rusqlite::Connection::open(&path).expect("conn");
+        conn.execute_batch(
+            "CREATE TABLE ZICCLOUDSYNCINGOBJECT (Z_PK INTEGER PRIMARY KEY, ZTITLE1 TEXT);
+             CREATE TABLE ZICNOTEDATA (Z_PK INTEGER PRIMARY KEY, ZNOTE INTEGER, ZDATA BLOB);
+             INSERT INTO ZICCLOUDSYNCINGOBJECT VALUES (1, 'Plans');",
+        )
+        .expect("schema");
+        conn.execute(
+            "INSERT INTO ZICNOTEDATA VALUES (1, 1, ?1)",
+            [note_blob("meet at noon")],
+        )
+        .expect("note");
+        drop(conn);
+        let file = CarvedFile {
+            run_id: "run".to_string(),
+            file_type: "sqlite".to_string(),
+            signature_type: "sqlite".to_string(),
+            path: "sqlite/sqlite_000000004000.sqlite".to_string(),
+            extension: "sqlite".to_string(),
+            global_start: 0x4000,
+            global_end: 0x4FFF,
+            size: 0x1000,
+            md5: None,
+            sha256: None,
+            validated: true,
+            truncated: false,
+            errors: Vec::new(),
+            pattern_id: None,
+        };
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let tx = tx.into();
+        let ctx = ProcessContext {
+            run_id: "run",
+            run_output_dir: dir.path(),
+            meta_tx: &tx,
+        };
+        let errors = Arc::new(AtomicU64::new(0));
+        AppleDatabaseProcessor::new(errors.clone())
+            .process(&file, &path, &ctx)
+            .expect("process");
+        drop(tx);
+        let events: Vec<MetadataEvent> = rx.iter().collect();
+        assert_eq!(events.len(), 1);
+        match &events[0] {
+            MetadataEvent::Message(record) => {
+                assert_eq!(record.text.as_deref(), Some("meet at noon"));
+                assert_eq!(record.source_file, "sqlite/sqlite_000000004000.sqlite");
+            }
+            _ => panic!("expected message"),
+        }
+        assert_eq!(errors.load(Ordering::Relaxed), 0);
+    }
+}
@@ -0,0 +1,208 @@
+//! Cached HTTP response reconstruction from carved browser cache entries.
+
+use std::io::Write;
+use std::path::Path;
+
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::carve::{CarvedFile, create_output, output_path};
+use crate::parsers::browser::BrowserCacheRecord;
+use crate::parsers::browser_cache::{self, CacheEntry};
+use crate::pipeline::events::MetadataEvent;
+
+use super::{PostProcessError, PostProcessor, ProcessContext};
+
+/// File type of extracted response bodies (`carved/cache_body/`).
+pub const CACHE_BODY_TYPE: &str = "cache_body";
+
+/// Parses a carved Chromium Simple Cache or Firefox cache2 entry, writes the
+/// response body as a child carved file, and records the URL and headers.
+pub struct BrowserCacheProcessor;
+
+impl PostProcessor for BrowserCacheProcessor {
+    fn name(&self) -> &str {
+        "browser_cache"
+    }
+
+    fn process(
+        &self,
+        file: &CarvedFile,
+        path: &Path,
+        ctx: &ProcessContext,
+    ) -> Result<(), PostProcessError> {
+        let data = std::fs::read(path)?;
+        let entry = browser_cache::parse_chrome_simple_entry(&data)
+            .or_else(|| browser_cache::parse_firefox_cache2_entry(&data))
+            .ok_or_else(|| PostProcessError::Other("not a browser cache entry".to_string()))?;
+
+        let body = &data[entry.body.clone()];
+        let body_file = if body.is_empty() {
+            None
+        } else {
+            Some(write_body(file, &entry, body, ctx)?)
+        };
+        let body_path = body_file.as_ref().map(|child| child.path.clone());
+        if let Some(child) = body_file
+            && ctx.meta_tx.send(MetadataEvent::File(child)).is_err()
+        {
+            warn!("metadata channel closed while sending cache body");
+            return Ok(());
+        }
+
+        let record = BrowserCacheRecord {
+            run_id: ctx.run_id.to_string(),
+            browser: entry.browser.to_string(),
+            url: entry.url.clone(),
+            http_status: entry.http_status(),
+            content_type: entry.header("content-type").map(str::to_string),
+            content_encoding: entry.header("content-encoding").map(str::to_string),
+            response_headers: (!entry.headers.is_empty()).then(|| entry.headers.join("\r\n")),
+            body_size: body.len() as u64,
+            body_path,
+            source_file: file.path.clone().into(),
+        };
+        if let Err(err) = ctx.meta_tx.send(MetadataEvent::Cache(record)) {
+            warn!("metadata channel closed while sending cache record: {err}");
+        }
+        Ok(())
+    }
+}
+
+fn write_body(
+    file: &CarvedFile,
+    entry: &CacheEntry,
+    body: &[u8],
+    ctx: &ProcessContext,
+) -> Result<CarvedFile, PostProcessError> {
+    let extension = body_extension(entry);
+    let global_start = file.global_start + entry.body.start as u64;
+    let carved_root = ctx.run_output_dir.join("carved");
+    let (full_path, rel_path) = output_path(&carved_root, CACHE_BODY_TYPE, extension, global_start)
+        .map_err(|err| PostProcessError::Other(err.to_string()))?;
+    let mut out = create_output(&full_path)?;
+    out.write_all(body)?;
+    out.flush()?;
+
+    Ok(CarvedFile {
+        run_id: ctx.run_id.to_string(),
+        file_type: CACHE_BODY_TYPE.to_string(),
+        signature_type: CACHE_BODY_TYPE.to_string(),
+        path: rel_path,
+        extension: extension.to_string(),
+        global_start,
+        global_end: global_start + body.len() as u64 - 1,
+        size: body.len() as u64,
+        md5: Some(format!("{:x}", md5::compute(body))),
+        sha256: Some(hex::encode(Sha256::digest(body))),
+        validated: true,
+        truncated: false,
+        errors: Vec::new(),
+        pattern_id: None,
+    })
+}
+
+/// Extension for a response body. Encoded bodies are stored as-is, so they
+/// get the encoding's extension rather than the content type's.
+fn body_extension(entry: &CacheEntry) -> &'static str {
+    match entry
+        .header("content-encoding")
+        .map(str::to_ascii_lowercase)
+    {
+        Some(enc) if enc == "gzip" || enc == "x-gzip" => return "gz",
+        Some(enc) if enc == "br" => return "br",
+        Some(enc) if enc == "zstd" => return "zst",
+        Some(enc) if enc == "deflate" => return "zlib",
+        _ => {}
+    }
+    let content_type = entry
+        .header("content-type")
+        .and_then(|v| v.split(';').next())
+        .map(|v| v.trim().to_ascii_lowercase())
+        .unwrap_or_default();
+    match content_type.as_str() {
+        "image/jpeg" | "image/jpg" => "jpg",
+        "image/png" => "png",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        "image/avif" => "avif",
+        "image/svg+xml" => "svg",
+        "image/x-icon" | "image/vnd.microsoft.icon" => "ico",
+        "text/html" => "html",
+        "text/css" => "css",
+        "text/plain" => "txt",
+        "text/javascript" | "application/javascript" | "application/x-javascript" => "js",
+        "application/json" => "json",
+        "application/xml" | "text/xml" => "xml",
+        "application/pdf" => "pdf",
+        "font/woff" => "woff",
+        "font/woff2" => "woff2",
+        "video/mp4" => "mp4",
+        "audio/mpeg" => "mp3",
+        _ => "bin",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::browser_cache::firefox_entry_bytes;
+
+    #[test]
+    fn writes_body_and_cache_record() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let entry = firefox_entry_bytes(
+            ":https://example.com/logo.png",
+            "HTTP/1.1 200 OK\r\nContent-Type: image/png\r\n",
+            b"\x89PNG body",
+        );
+        let path = dir
+            .path()
+            .join("carved/firefox_cache/firefox_cache_000000001000.cache");
+        std::fs::create_dir_all(path.parent().unwrap()).expect("dir");
+        std::fs::write(&path, &entry).expect("write entry");
+        let file = CarvedFile {
+            run_id: "run".to_string(),
+            file_type: "firefox_cache".to_string(),
+            signature_type: "firefox_cache".to_string(),
+            path: "firefox_cache/firefox_cache_000000001000.cache".to_string(),
+            extension: "cache".to_string(),
+            global_start: 0x1000,
+            global_end: 0x1000 + entry.len() as u64 - 1,
+            size: entry.len() as u64,
+            md5: None,
+            sha256: None,
+            validated: true,
+            truncated: false,
+            errors: Vec::new(),
+            pattern_id: None,
+        };
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let tx = tx.into();
+        let ctx = ProcessContext {
+            run_id: "run",
+            run_output_dir: dir.path(),
+            meta_tx: &tx,
+        };
+        BrowserCacheProcessor
+            .process(&file, &path, &ctx)
+            .expect("process");
+        drop(tx);
+
+        let events: Vec<MetadataEvent> = rx.iter().collect();
+        let Some(MetadataEvent::File(child)) = events.first() else {
+            panic!("expected body file");
+        };
+        assert_eq!(child.path, "cache_body/cache_body_000000001000.png");
+        assert_eq!(
+            std::fs::read(dir.path().join("carved").join(&child.path)).expect("body"),
+            b"\x89PNG body"
+        );
+        let Some(MetadataEvent::Cache(record)) = events.get(1) else {
+            panic!("expected cache record");
+        };
+        assert_eq!(record.url, "https://example.com/logo.png");
+        assert_eq!(record.http_status, Some(200));
+        assert_eq!(record.body_path.as_deref(), Some(child.path.as_str()));
+    }
+}
@@ -0,0 +1,396 @@
+//! Content classifier processor.
+//!
+//! Scores carved files with a user-supplied model. The model runs in a
+//! separate server process, so any inference runtime (ONNX Runtime, PyTorch,
+//! a vendor SDK) can be used without linking it into the carver. The server
+//! is started on the first file, with a cleared environment (only `PATH` is
+//! kept) and its working directory set to `postprocess/<name>/`; its stderr
+//! goes to `stderr.log` there.
+//!
+//! The carver writes one JSON request per line to the server's stdin:
+//!
+//! ```text
+//! {"path":"/abs/carved/jpeg/jpeg_000000001000.jpg","file_type":"jpeg","size":48213}
+//! ```
+//!
+//! and reads one JSON response per line from its stdout, either
+//! `{"labels":[{"label":"document","score":0.93}]}` or `{"error":"..."}`.
+//! The file is read by the server right after the carve worker wrote it, so
+//! it normally comes from the page cache. Requests are sent one at a time.
+//! A server that does not answer within `timeout_secs` is killed and started
+//! again for the next file. At the end of the run its stdin is closed and it
+//! should exit.
+
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::{Receiver, RecvTimeoutError};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::carve::CarvedFile;
+use crate::metadata::ClassificationRecord;
+use crate::pipeline::events::MetadataEvent;
+
+use super::command::processor_name;
+use super::{POSTPROCESS_DIR, PostProcessError, PostProcessor, ProcessContext};
+
+/// Log of the server's stderr, in its working directory.
+pub const STDERR_LOG: &str = "stderr.log";
+
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+/// How long a server may take to exit after its stdin is closed.
+const STOP_GRACE: Duration = Duration::from_secs(2);
+
+#[derive(Serialize)]
+struct Request<'a> {
+    path: &'a str,
+    file_type: &'a str,
+    size: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct Response {
+    #[serde(default)]
+    labels: Vec<LabelScore>,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LabelScore {
+    label: String,
+    score: f64,
+}
+
+/// A running model server.
+struct Server {
+    child: Child,
+    stdin: ChildStdin,
+    responses: Receiver<std::io::Result<String>>,
+}
+
+impl Server {
+    fn ask(&mut self, request: &str, timeout_secs: u64) -> Result<String, PostProcessError> {
+        writeln!(self.stdin, "{request}")?;
+        self.stdin.flush()?;
+        match self
+            .responses
+            .recv_timeout(Duration::from_secs(timeout_secs))
+        {
+            Ok(line) => Ok(line?),
+            Err(RecvTimeoutError::Timeout) => Err(PostProcessError::Timeout(timeout_secs)),
+            Err(RecvTimeoutError::Disconnected) => Err(PostProcessError::Other(
+                "model server closed its output".to_string(),
+            )),
+        }
+    }
+
+    /// Close stdin and give the server `grace` to exit before killing it.
+    fn stop(self, grace: Duration) {
+        let Server {
+            mut child, stdin, ..
+        } = self;
+        drop(stdin);
+        let deadline = Instant::now() + grace;
+        while Instant::now() < deadline {
+            if let Ok(Some(_)) = child.try_wait() {
+                return;
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+}
+
+/// Records the `top_k` labels scoring at least `min_score` for each file as
+/// `classifications`, ranked from the highest score.
+pub struct ClassifierProcessor {
+    name: String,
+    program: String,
+    args: Vec<String>,
+    timeout_secs: u64,
+    min_score: f64,
+    top_k: u32,
+    server: Mutex<Option<Server>>,
+}
+
+impl ClassifierProcessor {
+    pub fn new(
+        name: Option<String>,
+        program: String,
+        args: Vec<String>,
+        timeout_secs: u64,
+        min_score: f64,
+        top_k: u32,
+    ) -> Self {
+        Self {
+            name: processor_name(name, &program),
+            program,
+            args,
+            timeout_secs,
+            min_score,
+            top_k,
+            server: Mutex::new(None),
+        }
+    }
+
+    fn start(&self, run_output_dir: &Path) -> Result<Server, PostProcessError> {
+        let workdir = run_output_dir.join(POSTPROCESS_DIR).join(&self.name);
+        fs::create_dir_all(&workdir)?;
+        let stderr = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(workdir.join(STDERR_LOG))?;
+        let mut command = Command::new(&self.program);
+        command
+            .args(&self.args)
+            .env_clear()
+            .current_dir(&workdir)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(stderr);
+        if let Some(search_path) = std::env::var_os("PATH") {
+            command.env("PATH", search_path);
+        }
+        let mut child = command.spawn()?;
+        let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(PostProcessError::Other(
+                "model server pipes unavailable".to_string(),
+            ));
+        };
+        let (tx, responses) = crossbeam_channel::unbounded();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let failed = line.is_err();
+                if tx.send(line).is_err() || failed {
+                    break;
+                }
+            }
+        });
+        Ok(Server {
+            child,
+            stdin,
+            responses,
+        })
+    }
+
+    /// Send one request, starting the server if needed. A server that fails
+    /// to answer is stopped so the next file gets a fresh one.
+    fn ask(&self, request: &str, run_output_dir: &Path) -> Result<String, PostProcessError> {
+        let mut server = self
+            .server
+            .lock()
+            .map_err(|_| PostProcessError::Other("classifier lock poisoned".to_string()))?;
+        if server.is_none() {
+            *server = Some(self.start(run_output_dir)?);
+        }
+        let Some(running) = server.as_mut() else {
+            return Err(PostProcessError::Other(
+                "model server not running".to_string(),
+            ));
+        };
+        let answer = running.ask(request, self.timeout_secs);
+        if answer.is_err()
+            && let Some(failed) = server.take()
+        {
+            failed.stop(Duration::ZERO);
+        }
+        answer
+    }
+
+    fn select(&self, mut labels: Vec<LabelScore>) -> Vec<LabelScore> {
+        labels.retain(|l| l.score.is_finite() && l.score >= self.min_score);
+        labels.sort_by(|a, b| b.score.total_cmp(&a.score));
+        labels.truncate(self.top_k as usize);
+        labels
+    }
+}
+
+impl PostProcessor for ClassifierProcessor {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn process(
+        &self,
+        file: &CarvedFile,
+        path: &Path,
+        ctx: &ProcessContext,
+    ) -> Result<(), PostProcessError> {
+        let path = fs::canonicalize(path)?;
+        let request = serde_json::to_string(&Request {
+            path: &path.to_string_lossy(),
+            file_type: &file.file_type,
+            size: file.size,
+        })
+        .map_err(|err| PostProcessError::Other(err.to_string()))?;
+        let line = self.ask(&request, ctx.run_output_dir)?;
+        let response: Response = serde_json::from_str(&line).map_err(|err| {
+            PostProcessError::Other(format!("invalid model server response: {err}"))
+        })?;
+        if let Some(error) = response.error {
+            return Err(PostProcessError::Other(format!("model server: {error}")));
+        }
+        for (rank, scored) in self.select(response.labels).into_iter().enumerate() {
+            let record = ClassificationRecord {
+                run_id: ctx.run_id.to_string(),
+                file_type: file.file_type.clone(),
+                path: file.path.clone(),
+                global_start: file.global_start,
+                classifier: self.name.clone(),
+                label: scored.label,
+                score: scored.score,
+                rank: rank as u32 + 1,
+            };
+            if let Err(err) = ctx.meta_tx.send(MetadataEvent::Classification(record)) {
+                warn!("metadata channel closed while sending classification: {err}");
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for ClassifierProcessor {
+    fn drop(&mut self) {
+        let server = match self.server.get_mut() {
+            Ok(server) => server.take(),
+            Err(poisoned) => poisoned.into_inner().take(),
+        };
+        if let Some(server) = server {
+            server.stop(STOP_GRACE);
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    const SERVER: &str = r#"echo started >&2
+while read -r line; do
+  case "$line" in
+    *'"file_type":"jpeg"'*) echo '{"labels":[{"label":"document","score":0.2},{"label":"nudity","score":0.91},{"label":"weapon","score":0.6}]}' ;;
+    *'"file_type":"png"'*) echo '{"error":"unsupported input"}' ;;
+    *) sleep 5 ;;
+  esac
+done"#;
+
+    fn classifier(timeout_secs: u64) -> ClassifierProcessor {
+        ClassifierProcessor::new(
+            Some("nsfw".to_string()),
+            "sh".to_string(),
+            vec!["-c".to_string(), SERVER.to_string()],
+            timeout_secs,
+            0.5,
+            2,
+        )
+    }
+
+    fn carved(file_type: &str) -> CarvedFile {
+        CarvedFile {
+            run_id: "run".to_string(),
+            file_type: file_type.to_string(),
+            signature_type: file_type.to_string(),
+            path: format!("{file_type}/a.{file_type}"),
+            extension: file_type.to_string(),
+            global_start: 4096,
+            global_end: 4099,
+            size: 4,
+            md5: None,
+            sha256: None,
+            validated: true,
+            truncated: false,
+            errors: Vec::new(),
+            pattern_id: None,
+        }
+    }
+
+    fn run(
+        processor: &ClassifierProcessor,
+        dir: &Path,
+        file_type: &str,
+    ) -> (Result<(), PostProcessError>, Vec<ClassificationRecord>) {
+        let file = carved(file_type);
+        let carved_path = dir.join("carved").join(&file.path);
+        fs::create_dir_all(carved_path.parent().expect("parent")).expect("mkdir");
+        fs::write(&carved_path, b"data").expect("write");
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let tx = tx.into();
+        let ctx = ProcessContext {
+            run_id: "run",
+            run_output_dir: dir,
+            meta_tx: &tx,
+        };
+        let result = processor.process(&file, &carved_path, &ctx);
+        let records = rx
+            .try_iter()
+            .filter_map(|event| match event {
+                MetadataEvent::Classification(record) => Some(record),
+                _ => None,
+            })
+            .collect();
+        (result, records)
+    }
+
+    #[test]
+    fn records_top_labels_and_keeps_the_server() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let processor = classifier(10);
+        let (result, records) = run(&processor, dir.path(), "jpeg");
+        result.expect("classify");
+        let labels: Vec<(&str, u32)> = records.iter().map(|r| (r.label.as_str(), r.rank)).collect();
+        assert_eq!(labels, vec![("nudity", 1), ("weapon", 2)]);
+        assert_eq!(records[0].classifier, "nsfw");
+        assert_eq!(records[0].path, "jpeg/a.jpeg");
+
+        let (result, records) = run(&processor, dir.path(), "png");
+        let err = result.expect_err("server error");
+        assert!(err.to_string().contains("unsupported input"), "{err}");
+        assert!(records.is_empty());
+
+        let (result, records) = run(&processor, dir.path(), "jpeg");
+        result.expect("classify again");
+        assert_eq!(records.len(), 2);
+        drop(processor);
+        let log = dir
+            .path()
+            .join(POSTPROCESS_DIR)
+            .join("nsfw")
+            .join(STDERR_LOG);
+        assert_eq!(fs::read_to_string(log).expect("stderr log"), "started\n");
+    }
+
+    #[test]
+    fn restarts_the_server_after_a_timeout() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let processor = classifier(1);
+        let started = Instant::now();
+        let (result, _) = run(&processor, dir.path(), "gif");
+        assert!(matches!(result, Err(PostProcessError::Timeout(1))));
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        let (result, records) = run(&processor, dir.path(), "jpeg");
+        result.expect("classify after restart");
+        assert_eq!(records.len(), 2);
+        drop(processor);
+        let log = dir
+            .path()
+            .join(POSTPROCESS_DIR)
+            .join("nsfw")
+            .join(STDERR_LOG);
+        assert_eq!(
+            fs::read_to_string(log).expect("stderr log"),
+            "started\nstarted\n"
+        );
+    }
+}
@@ -0,0 +1,226 @@
+//! External command processor.
+//!
+//! Runs a program once per carved file with a cleared environment (only `PATH`
+//! is kept), no stdin, and its working directory set to the processor's output
+//! directory. Stdout and stderr are written to
+//! `postprocess/<name>/<carved path>.out` / `.err`; an empty `.err` is removed.
+//! The process is killed if it outlives `timeout_secs`.
+
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::carve::{CarvedFile, sanitize_component};
+
+use super::{POSTPROCESS_DIR, PostProcessError, PostProcessor, ProcessContext};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+pub struct CommandProcessor {
+    name: String,
+    program: String,
+    args: Vec<String>,
+    timeout_secs: u64,
+}
+
+impl CommandProcessor {
+    pub fn new(
+        name: Option<String>,
+        program: String,
+        args: Vec<String>,
+        timeout_secs: u64,
+    ) -> Self {
+        Self {
+            name: processor_name(name, &program),
+            program,
+            args,
+            timeout_secs,
+        }
+    }
+
+    fn expand(&self, file: &CarvedFile, path: &Path) -> Vec<String> {
+        let path = path.to_string_lossy();
+        let offset = file.global_start.to_string();
+        self.args
+            .iter()
+            .map(|arg| {
+                arg.replace("{path}", &path)
+                    .replace("{type}", &file.file_type)
+                    .replace("{offset}", &offset)
+            })
+            .collect()
+    }
+
+    fn output_base(&self, run_output_dir: &Path, file: &CarvedFile) -> PathBuf {
+        let dir = run_output_dir.join(POSTPROCESS_DIR).join(&self.name);
+        let rel = Path::new(&file.path);
+        let mut base = dir;
+        for component in rel.components() {
+            base.push(sanitize_component(&component.as_os_str().to_string_lossy()));
+        }
+        base
+    }
+}
+
+impl PostProcessor for CommandProcessor {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn process(
+        &self,
+        file: &CarvedFile,
+        path: &Path,
+        ctx: &ProcessContext,
+    ) -> Result<(), PostProcessError> {
+        let base = self.output_base(ctx.run_output_dir, file);
+        let workdir = ctx.run_output_dir.join(POSTPROCESS_DIR).join(&self.name);
+        if let Some(parent) = base.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let out_path = base.with_extension(append_ext(&base, "out"));
+        let err_path = base.with_extension(append_ext(&base, "err"));
+        let path = fs::canonicalize(path)?;
+
+        let mut command = Command::new(&self.program);
+        command
+            .args(self.expand(file, &path))
+            .env_clear()
+            .current_dir(&workdir)
+            .stdin(Stdio::null())
+            .stdout(File::create(&out_path)?)
+            .stderr(File::create(&err_path)?);
+        if let Some(search_path) = std::env::var_os("PATH") {
+            command.env("PATH", search_path);
+        }
+        let mut child = command.spawn()?;
+
+        let deadline = Instant::now() + Duration::from_secs(self.timeout_secs);
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if Instant::now() >= deadline {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(PostProcessError::Timeout(self.timeout_secs));
+            }
+            thread::sleep(POLL_INTERVAL);
+        };
+        if fs::metadata(&err_path)
+            .map(|m| m.len() == 0)
+            .unwrap_or(false)
+        {
+            let _ = fs::remove_file(&err_path);
+        }
+        if status.success() {
+            Ok(())
+        } else {
+            Err(PostProcessError::Failed(status))
+        }
+    }
+}
+
+/// `name`, or the file stem of `program`, made safe for a directory name.
+pub(super) fn processor_name(name: Option<String>, program: &str) -> String {
+    let name = name.unwrap_or_else(|| {
+        Path::new(program)
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| program.to_string())
+    });
+    sanitize_component(&name)
+}
+
+/// Extension for `base` with `suffix` appended (`a.jpg` -> `jpg.out`).
+fn append_ext(base: &Path, suffix: &str) -> String {
+    match base.extension() {
+        Some(ext) => format!("{}.{suffix}", ext.to_string_lossy()),
+        None => suffix.to_string(),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn carved(path: &str) -> CarvedFile {
+        CarvedFile {
+            run_id: "run".to_string(),
+            file_type: "jpeg".to_string(),
+            signature_type: "jpeg".to_string(),
+            path: path.to_string(),
+            extension: "jpg".to_string(),
+            global_start: 4096,
+            global_end: 4100,
+            size: 4,
+            md5: None,
+            sha256: None,
+            validated: true,
+            truncated: false,
+            errors: Vec::new(),
+            pattern_id: None,
+        }
+    }
+
+    fn run(processor: &CommandProcessor, dir: &Path) -> Result<(), PostProcessError> {
+        let carved_path = dir.join("carved").join("jpeg").join("a.jpg");
+        fs::create_dir_all(carved_path.parent().expect("parent")).expect("mkdir");
+        fs::write(&carved_path, b"data").expect("write");
+        let (tx, _rx) = crossbeam_channel::unbounded();
+        let tx = tx.into();
+        let ctx = ProcessContext {
+            run_id: "run",
+            run_output_dir: dir,
+            meta_tx: &tx,
+        };
+        processor.process(&carved("jpeg/a.jpg"), &carved_path, &ctx)
+    }
+
+    #[test]
+    fn writes_stdout_with_substituted_args() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let processor = CommandProcessor::new(
+            None,
+            "sh".to_string(),
+            vec![
+                "-c".to_string(),
+                "echo $1 $2; cat \"$0\"".to_string(),
+                "{path}".to_string(),
+                "{type}".to_string(),
+                "{offset}".to_string(),
+            ],
+            10,
+        );
+        run(&processor, dir.path()).expect("process");
+        let out_dir = dir.path().join(POSTPROCESS_DIR).join("sh").join("jpeg");
+        let out = fs::read_to_string(out_dir.join("a.jpg.out")).expect("stdout");
+        assert_eq!(out, "jpeg 4096\ndata");
+        assert!(!out_dir.join("a.jpg.err").exists());
+    }
+
+    #[test]
+    fn kills_on_timeout_and_reports_failures() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let slow = CommandProcessor::new(
+            Some("slow".to_string()),
+            "sleep".to_string(),
+            vec!["5".to_string()],
+            1,
+        );
+        let started = Instant::now();
+        assert!(matches!(
+            run(&slow, dir.path()),
+            Err(PostProcessError::Timeout(1))
+        ));
+        assert!(started.elapsed() < Duration::from_secs(4));
+
+        let failing = CommandProcessor::new(None, "false".to_string(), Vec::new(), 5);
+        assert!(matches!(
+            run(&failing, dir.path()),
+            Err(PostProcessError::Failed(_))
+        ));
+    }
+}
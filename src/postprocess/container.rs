@@ -0,0 +1,146 @@
+//! Container context from carved tarballs and containerd metadata databases.
+
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use tracing::warn;
+
+use crate::carve::CarvedFile;
+use crate::parsers::boltdb::{BoltMeta, META_PAGE_LEN};
+use crate::parsers::container::{self, ContainerArtefact, ContainerArtefactRecord};
+use crate::pipeline::events::MetadataEvent;
+
+use super::{PostProcessError, PostProcessor, ProcessContext};
+
+/// Tags carved tar archives that are image layers (overlayfs whiteouts) or
+/// image tarballs (`manifest.json`, `index.json`), and reads images,
+/// containers, and content blobs from containerd bolt databases. Plain tar
+/// archives produce no records.
+pub struct ContainerArtefactProcessor;
+
+impl PostProcessor for ContainerArtefactProcessor {
+    fn name(&self) -> &str {
+        "container_artefacts"
+    }
+
+    fn process(
+        &self,
+        file: &CarvedFile,
+        path: &Path,
+        ctx: &ProcessContext,
+    ) -> Result<(), PostProcessError> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut head = [0u8; META_PAGE_LEN];
+        let n = read_prefix(&mut reader, &mut head)?;
+        let artefacts = if BoltMeta::parse(&head[..n]).is_some() {
+            let data = std::fs::read(path)?;
+            container::containerd_artefacts(&data)
+                .ok_or_else(|| PostProcessError::Other("not a bolt database".to_string()))?
+        } else {
+            let scan = container::scan_tar(head[..n].chain(reader))?;
+            let mut artefacts = Vec::with_capacity(scan.artefacts.len() + 1);
+            // An uncompressed layer's sha256 is its diff ID in the image config.
+            if scan.is_layer {
+                artefacts.push(ContainerArtefact {
+                    kind: "layer",
+                    digest: file.sha256.as_ref().map(|h| format!("sha256:{h}")),
+                    detail: Some("diff_id".to_string()),
+                    ..ContainerArtefact::default()
+                });
+            }
+            artefacts.extend(scan.artefacts);
+            artefacts
+        };
+
+        for artefact in artefacts {
+            let record = ContainerArtefactRecord {
+                run_id: ctx.run_id.to_string(),
+                kind: artefact.kind.to_string(),
+                name: artefact.name,
+                digest: artefact.digest,
+                namespace: artefact.namespace,
+                detail: artefact.detail,
+                global_offset: file.global_start + artefact.offset,
+                source_file: file.path.clone(),
+            };
+            if let Err(err) = ctx.meta_tx.send(MetadataEvent::Container(record)) {
+                warn!("metadata channel closed while sending container artefact: {err}");
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Fill `buf` as far as the input allows.
+fn read_prefix<R: Read>(reader: &mut R, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        let n = reader.read(&mut buf[filled..])?;
+        if n == 0 {
+            break;
+        }
+        filled += n;
+    }
+    Ok(filled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::container::tar_bytes;
+
+    #[test]
+    fn tags_layer_tarball() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let tar = tar_bytes(&[("app/config.yml", b"a: 1"), ("app/.wh.secret.env", b"")]);
+        let path = dir.path().join("layer.tar");
+        std::fs::write(&path, &tar).expect("write tar");
+        let file = CarvedFile {
+            run_id: "run".to_string(),
+            file_type: "tar".to_string(),
+            signature_type: "tar".to_string(),
+            path: "tar/tar_000000100000.tar".to_string(),
+            extension: "tar".to_string(),
+            global_start: 0x100000,
+            global_end: 0x100000 + tar.len() as u64 - 1,
+            size: tar.len() as u64,
+            md5: None,
+            sha256: Some("ab".repeat(32)),
+            validated: true,
+            truncated: false,
+            errors: Vec::new(),
+            pattern_id: None,
+        };
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let tx = tx.into();
+        let ctx = ProcessContext {
+            run_id: "run",
+            run_output_dir: dir.path(),
+            meta_tx: &tx,
+        };
+        ContainerArtefactProcessor
+            .process(&file, &path, &ctx)
+            .expect("process");
+        drop(tx);
+
+        let records: Vec<ContainerArtefactRecord> = rx
+            .iter()
+            .filter_map(|event| match event {
+                MetadataEvent::Container(record) => Some(record),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].kind, "layer");
+        assert_eq!(
+            records[0].digest,
+            Some(format!("sha256:{}", "ab".repeat(32)))
+        );
+        assert_eq!(records[1].kind, "whiteout");
+        assert_eq!(records[1].name.as_deref(), Some("/app/secret.env"));
+        assert_eq!(records[1].global_offset, 0x100000 + 1024);
+        assert_eq!(records[1].source_file, file.path);
+    }
+}
@@ -0,0 +1,91 @@
+//! Cookies from carved Safari binarycookies and Netscape cookie files.
+
+use std::path::Path;
+
+use tracing::warn;
+
+use crate::carve::CarvedFile;
+use crate::parsers::cookies;
+use crate::pipeline::events::MetadataEvent;
+
+use super::{PostProcessError, PostProcessor, ProcessContext};
+
+/// Records each cookie of a carved cookie store in `browser_cookies`, with
+/// `browser` set to `safari` (binarycookies) or `netscape` (`cookies.txt`).
+/// Truncated stores yield the cookies of their complete pages or lines.
+pub struct BrowserCookieProcessor;
+
+impl PostProcessor for BrowserCookieProcessor {
+    fn name(&self) -> &str {
+        "browser_cookies"
+    }
+
+    fn process(
+        &self,
+        file: &CarvedFile,
+        path: &Path,
+        ctx: &ProcessContext,
+    ) -> Result<(), PostProcessError> {
+        let data = std::fs::read(path)?;
+        for record in cookies::extract_cookie_file(&data, ctx.run_id, &file.path) {
+            if let Err(err) = ctx.meta_tx.send(MetadataEvent::Cookie(record)) {
+                warn!("metadata channel closed while sending cookie record: {err}");
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::cookies::binary_cookies_bytes;
+
+    #[test]
+    fn sends_cookie_records_for_carved_store() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let data = binary_cookies_bytes();
+        let path = dir.path().join("store.binarycookies");
+        std::fs::write(&path, &data).expect("write store");
+        let file = CarvedFile {
+            run_id: "run".to_string(),
+            file_type: "binarycookies".to_string(),
+            signature_type: "binarycookies".to_string(),
+            path: "binarycookies/binarycookies_000000000000.binarycookies".to_string(),
+            extension: "binarycookies".to_string(),
+            global_start: 0,
+            global_end: data.len() as u64 - 1,
+            size: data.len() as u64,
+            md5: None,
+            sha256: None,
+            validated: true,
+            truncated: false,
+            errors: Vec::new(),
+            pattern_id: None,
+        };
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let tx = tx.into();
+        let ctx = ProcessContext {
+            run_id: "run",
+            run_output_dir: dir.path(),
+            meta_tx: &tx,
+        };
+        BrowserCookieProcessor
+            .process(&file, &path, &ctx)
+            .expect("process");
+        drop(tx);
+
+        let cookies: Vec<_> = rx
+            .iter()
+            .filter_map(|event| match event {
+                MetadataEvent::Cookie(record) => Some(record),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(cookies.len(), 3);
+        assert_eq!(cookies[0].browser, "safari");
+        assert_eq!(cookies[0].host, ".example.com");
+        assert_eq!(cookies[0].source_file, Path::new(&file.path));
+    }
+}
@@ -0,0 +1,139 @@
+//! Encryption metadata of password-protected OOXML documents.
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use tracing::warn;
+
+use crate::carve::CarvedFile;
+use crate::carve::ole::ENCRYPTED_OOXML_KIND;
+use crate::parsers::cfb::CompoundFile;
+use crate::parsers::office_crypto::{EncryptedDocumentRecord, read_encryption};
+use crate::pipeline::events::MetadataEvent;
+
+use super::{PostProcessError, PostProcessor, ProcessContext};
+
+/// Records the encryption type, cipher, hash, key derivation spin count, and
+/// plaintext size of compound files the OLE carver labelled `encrypted_ooxml`.
+/// Other OLE carves are skipped. A file whose directory can no longer be read
+/// still gets a record with encryption type `unknown`, so every encrypted
+/// carve is listed for follow-up.
+pub struct EncryptedDocumentProcessor;
+
+impl PostProcessor for EncryptedDocumentProcessor {
+    fn name(&self) -> &str {
+        "encrypted_documents"
+    }
+
+    fn process(
+        &self,
+        file: &CarvedFile,
+        path: &Path,
+        ctx: &ProcessContext,
+    ) -> Result<(), PostProcessError> {
+        if file.file_type != ENCRYPTED_OOXML_KIND {
+            return Ok(());
+        }
+        let reader = BufReader::new(File::open(path)?);
+        let info = CompoundFile::open(reader)
+            .ok()
+            .and_then(|mut cfb| read_encryption(&mut cfb))
+            .unwrap_or_default();
+        let record = EncryptedDocumentRecord {
+            run_id: ctx.run_id.to_string(),
+            encryption_type: if info.encryption_type.is_empty() {
+                "unknown".to_string()
+            } else {
+                info.encryption_type.to_string()
+            },
+            version: info.version,
+            cipher_algorithm: info.cipher_algorithm,
+            key_bits: info.key_bits,
+            hash_algorithm: info.hash_algorithm,
+            spin_count: info.spin_count,
+            csp_name: info.csp_name,
+            original_size: info.original_size,
+            source_file: file.path.clone(),
+        };
+        if let Err(err) = ctx.meta_tx.send(MetadataEvent::EncryptedDocument(record)) {
+            warn!("metadata channel closed while sending encrypted document: {err}");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::office_crypto::encrypted_ooxml;
+
+    fn carved(file_type: &str, size: u64) -> CarvedFile {
+        CarvedFile {
+            run_id: "run".to_string(),
+            file_type: file_type.to_string(),
+            signature_type: "ole".to_string(),
+            path: format!("{file_type}/{file_type}_000000000000.ooxml"),
+            extension: "ooxml".to_string(),
+            global_start: 0,
+            global_end: size - 1,
+            size,
+            md5: None,
+            sha256: None,
+            validated: true,
+            truncated: false,
+            errors: Vec::new(),
+            pattern_id: None,
+        }
+    }
+
+    #[test]
+    fn records_encryption_of_encrypted_ooxml_only() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let data = encrypted_ooxml(9_999);
+        let path = dir.path().join("doc.ooxml");
+        std::fs::write(&path, &data).expect("write");
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let tx = tx.into();
+        let ctx = ProcessContext {
+            run_id: "run",
+            run_output_dir: dir.path(),
+            meta_tx: &tx,
+        };
+
+        EncryptedDocumentProcessor
+            .process(&carved("doc", data.len() as u64), &path, &ctx)
+            .expect("process");
+        EncryptedDocumentProcessor
+            .process(
+                &carved(ENCRYPTED_OOXML_KIND, data.len() as u64),
+                &path,
+                &ctx,
+            )
+            .expect("process");
+        // Only the header survived: still listed, with nothing readable.
+        std::fs::write(&path, &data[..512]).expect("write");
+        EncryptedDocumentProcessor
+            .process(&carved(ENCRYPTED_OOXML_KIND, 512), &path, &ctx)
+            .expect("process");
+        drop(tx);
+
+        let records: Vec<EncryptedDocumentRecord> = rx
+            .iter()
+            .filter_map(|event| match event {
+                MetadataEvent::EncryptedDocument(record) => Some(record),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].encryption_type, "agile");
+        assert_eq!(records[0].cipher_algorithm.as_deref(), Some("AES-256"));
+        assert_eq!(records[0].original_size, Some(9_999));
+        assert_eq!(
+            records[0].source_file,
+            "encrypted_ooxml/encrypted_ooxml_000000000000.ooxml"
+        );
+        assert_eq!(records[1].encryption_type, "unknown");
+        assert_eq!(records[1].original_size, None);
+    }
+}
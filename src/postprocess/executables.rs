@@ -0,0 +1,186 @@
+//! Architecture, compile time, and imports of carved executables.
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+use tracing::warn;
+
+use crate::carve::CarvedFile;
+use crate::parsers::executable::ExecutableRecord;
+use crate::parsers::pe::{self, PeError};
+use crate::parsers::{elf, macho};
+use crate::pipeline::events::MetadataEvent;
+
+use super::{PostProcessError, PostProcessor, ProcessContext};
+
+/// Header bytes read before the headers say how many they need.
+const HEADER_READ: usize = 4096;
+
+/// Records the kind and architecture of each carved PE, ELF, or Mach-O
+/// executable in `executables`, with the compile time, subsystem, and import
+/// hash of PE images. The format is told by the leading magic, whatever type
+/// the file was carved as. Only the headers and tables are read, not the
+/// whole file. Files whose headers no longer parse are skipped; when only the
+/// PE import table is cut off the record is written without an import hash.
+pub struct ExecutableProcessor;
+
+impl PostProcessor for ExecutableProcessor {
+    fn name(&self) -> &str {
+        "executables"
+    }
+
+    fn process(
+        &self,
+        file: &CarvedFile,
+        path: &Path,
+        ctx: &ProcessContext,
+    ) -> Result<(), PostProcessError> {
+        let mut handle = File::open(path)?;
+        let mut read = |offset: u64, buf: &mut [u8]| -> usize {
+            if handle.seek(SeekFrom::Start(offset)).is_err() {
+                return 0;
+            }
+            let mut filled = 0;
+            while filled < buf.len() {
+                match handle.read(&mut buf[filled..]) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => filled += n,
+                }
+            }
+            filled
+        };
+        let mut header = vec![0u8; HEADER_READ];
+        let n = read(0, &mut header);
+        header.truncate(n);
+        let record = if header.starts_with(b"MZ") {
+            pe_record(&mut header, &mut read, ctx.run_id, &file.path)
+        } else if header.starts_with(b"\x7FELF") {
+            elf::parse_header(&header).ok().and_then(|elf| {
+                let layout = elf.layout(&mut read).ok()?;
+                Some(elf.to_record(ctx.run_id, &file.path, &layout))
+            })
+        } else {
+            macho::parse(&mut read)
+                .ok()
+                .map(|macho| macho.to_record(ctx.run_id, &file.path))
+        };
+        let Some(record) = record else {
+            return Ok(());
+        };
+        if let Err(err) = ctx.meta_tx.send(MetadataEvent::Executable(record)) {
+            warn!("metadata channel closed while sending executable record: {err}");
+        }
+        Ok(())
+    }
+}
+
+fn pe_record(
+    header: &mut Vec<u8>,
+    read: &mut dyn FnMut(u64, &mut [u8]) -> usize,
+    run_id: &str,
+    source_file: &str,
+) -> Option<ExecutableRecord> {
+    let headers = match pe::parse_headers(header) {
+        Ok(headers) => headers,
+        Err(PeError::Incomplete(needed)) if header.len() == HEADER_READ => {
+            header.resize(needed, 0);
+            let n = read(0, header);
+            header.truncate(n);
+            pe::parse_headers(header).ok()?
+        }
+        Err(_) => return None,
+    };
+    let imports = headers.imports(read);
+    Some(headers.to_record(run_id, source_file, imports.as_deref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::elf::testing::elf_bytes;
+    use crate::parsers::macho::testing::fat_bytes;
+    use crate::parsers::pe::testing::{TIMESTAMP, pe_bytes};
+
+    fn records(data: &[u8], file_type: &str) -> Vec<ExecutableRecord> {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("carved.bin");
+        std::fs::write(&path, data).expect("write executable");
+        let file = CarvedFile {
+            run_id: "run".to_string(),
+            file_type: file_type.to_string(),
+            signature_type: file_type.to_string(),
+            path: format!("{file_type}/{file_type}_000000000000.bin"),
+            extension: "bin".to_string(),
+            global_start: 0,
+            global_end: data.len() as u64 - 1,
+            size: data.len() as u64,
+            md5: None,
+            sha256: None,
+            validated: true,
+            truncated: false,
+            errors: Vec::new(),
+            pattern_id: None,
+        };
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let tx = tx.into();
+        let ctx = ProcessContext {
+            run_id: "run",
+            run_output_dir: dir.path(),
+            meta_tx: &tx,
+        };
+        ExecutableProcessor
+            .process(&file, &path, &ctx)
+            .expect("process");
+        drop(tx);
+        rx.iter()
+            .filter_map(|event| match event {
+                MetadataEvent::Executable(record) => Some(record),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn records_compile_time_and_import_hash() {
+        let records = records(&pe_bytes(), "pe");
+        assert_eq!(records.len(), 1);
+        let record = &records[0];
+        assert_eq!(record.format, "pe");
+        assert_eq!(record.kind, "exe");
+        assert_eq!(record.architecture, "x86_64");
+        assert_eq!(
+            record.compile_time_utc.map(|t| t.and_utc().timestamp()),
+            Some(i64::from(TIMESTAMP))
+        );
+        assert_eq!(record.import_count, Some(2));
+        assert_eq!(
+            record.import_hash.as_deref(),
+            Some(format!("{:x}", md5::compute("kernel32.createfilew,kernel32.ord16")).as_str())
+        );
+        assert_eq!(record.source_file, "pe/pe_000000000000.bin");
+    }
+
+    #[test]
+    fn records_elf_and_mach_o_architectures() {
+        let elf = records(&elf_bytes(), "elf");
+        assert_eq!(elf.len(), 1);
+        assert_eq!(
+            (
+                elf[0].format.as_str(),
+                elf[0].kind.as_str(),
+                elf[0].architecture.as_str()
+            ),
+            ("elf", "exe", "x86_64")
+        );
+        assert_eq!(elf[0].section_count, 3);
+
+        let fat = records(&fat_bytes(), "macho");
+        assert_eq!(fat.len(), 1);
+        assert_eq!(fat[0].format, "macho");
+        assert_eq!(fat[0].architecture, "x86_64,arm64");
+        assert!(fat[0].compile_time_utc.is_none() && fat[0].import_hash.is_none());
+
+        assert!(records(b"not an executable", "elf").is_empty());
+    }
+}
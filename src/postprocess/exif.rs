@@ -0,0 +1,53 @@
+//! Camera identity and capture time of carved photos.
+
+use std::path::Path;
+
+use tracing::warn;
+
+use crate::carve::CarvedFile;
+use crate::parsers::exif::{PhotoDeviceRecord, photo_exif};
+use crate::pipeline::events::MetadataEvent;
+
+use super::{PostProcessError, PostProcessor, ProcessContext};
+
+/// Sends the make, model, serial, lens, capture time, and GPS position of JPEG and TIFF
+/// files to the metadata thread, which groups the photos by device and
+/// session when the run ends. Photos without any of them produce no record.
+pub struct PhotoExifProcessor;
+
+impl PostProcessor for PhotoExifProcessor {
+    fn name(&self) -> &str {
+        "photo_exif"
+    }
+
+    fn process(
+        &self,
+        file: &CarvedFile,
+        path: &Path,
+        ctx: &ProcessContext,
+    ) -> Result<(), PostProcessError> {
+        let data = std::fs::read(path)?;
+        let Some(exif) = photo_exif(&data) else {
+            return Ok(());
+        };
+        let record = PhotoDeviceRecord {
+            run_id: ctx.run_id.to_string(),
+            source_file: file.path.clone(),
+            global_start: file.global_start,
+            make: exif.make,
+            model: exif.model,
+            serial: exif.serial,
+            lens: exif.lens,
+            lens_serial: exif.lens_serial,
+            taken_at: exif.taken_at,
+            latitude: exif.position.map(|(lat, _)| lat),
+            longitude: exif.position.map(|(_, lon)| lon),
+            device_id: None,
+            session_id: None,
+        };
+        if let Err(err) = ctx.meta_tx.send(MetadataEvent::PhotoExif(record)) {
+            warn!("metadata channel closed while sending photo exif: {err}");
+        }
+        Ok(())
+    }
+}
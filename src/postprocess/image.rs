@@ -0,0 +1,101 @@
+//! Dimensions and decode completeness of carved images.
+
+use std::path::Path;
+
+use tracing::warn;
+
+use crate::carve::CarvedFile;
+use crate::parsers::image::{ImageInfoRecord, image_info};
+use crate::pipeline::events::MetadataEvent;
+
+use super::{PostProcessError, PostProcessor, ProcessContext};
+
+/// Records width, height, and bits per pixel of JPEG, PNG, GIF, BMP, WebP,
+/// and TIFF files, and for baseline JPEGs the share of MCUs that decode before
+/// corruption. Files whose header cannot be read produce no record.
+pub struct ImageInfoProcessor;
+
+impl PostProcessor for ImageInfoProcessor {
+    fn name(&self) -> &str {
+        "image_info"
+    }
+
+    fn process(
+        &self,
+        file: &CarvedFile,
+        path: &Path,
+        ctx: &ProcessContext,
+    ) -> Result<(), PostProcessError> {
+        let data = std::fs::read(path)?;
+        let Some(info) = image_info(&data) else {
+            return Ok(());
+        };
+        let record = ImageInfoRecord {
+            run_id: ctx.run_id.to_string(),
+            format: info.format.to_string(),
+            width: info.width,
+            height: info.height,
+            bits_per_pixel: info.bits_per_pixel,
+            decoded_pct: info.decoded_pct,
+            corrupt_offset: info.corrupt_offset,
+            source_file: file.path.clone(),
+        };
+        if let Err(err) = ctx.meta_tx.send(MetadataEvent::ImageInfo(record)) {
+            warn!("metadata channel closed while sending image info: {err}");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_png_dimensions() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("image.png");
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        png.extend(1920u32.to_be_bytes());
+        png.extend(1080u32.to_be_bytes());
+        png.extend([8, 2, 0, 0, 0]);
+        std::fs::write(&path, &png).expect("write");
+        let file = CarvedFile {
+            run_id: "run".to_string(),
+            file_type: "png".to_string(),
+            signature_type: "png".to_string(),
+            path: "png/png_000000002000.png".to_string(),
+            extension: "png".to_string(),
+            global_start: 0x2000,
+            global_end: 0x2000 + png.len() as u64 - 1,
+            size: png.len() as u64,
+            md5: None,
+            sha256: None,
+            validated: true,
+            truncated: false,
+            errors: Vec::new(),
+            pattern_id: None,
+        };
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let tx = tx.into();
+        let ctx = ProcessContext {
+            run_id: "run",
+            run_output_dir: dir.path(),
+            meta_tx: &tx,
+        };
+        ImageInfoProcessor
+            .process(&file, &path, &ctx)
+            .expect("process");
+        drop(tx);
+        let events: Vec<MetadataEvent> = rx.iter().collect();
+        match events.as_slice() {
+            [MetadataEvent::ImageInfo(record)] => {
+                assert_eq!((record.width, record.height), (1920, 1080));
+                assert_eq!(record.bits_per_pixel, Some(24));
+                assert_eq!(record.decoded_pct, None);
+                assert_eq!(record.source_file, "png/png_000000002000.png");
+            }
+            _ => panic!("expected one image info event"),
+        }
+    }
+}
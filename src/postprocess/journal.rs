@@ -0,0 +1,116 @@
+//! Log events from carved systemd journal files.
+
+use std::path::Path;
+
+use tracing::warn;
+
+use crate::carve::CarvedFile;
+use crate::parsers::journal;
+use crate::parsers::logs::LogEventRecord;
+use crate::pipeline::events::MetadataEvent;
+
+use super::{PostProcessError, PostProcessor, ProcessContext};
+
+/// Reports every journal entry that carries a `MESSAGE` field as a log event.
+/// Offsets point at the entry object in the evidence.
+pub struct JournalEventProcessor;
+
+impl PostProcessor for JournalEventProcessor {
+    fn name(&self) -> &str {
+        "journal_events"
+    }
+
+    fn process(
+        &self,
+        file: &CarvedFile,
+        path: &Path,
+        ctx: &ProcessContext,
+    ) -> Result<(), PostProcessError> {
+        let data = std::fs::read(path)?;
+        let entries = journal::parse_journal(&data)
+            .ok_or_else(|| PostProcessError::Other("not a journal file".to_string()))?;
+        for entry in entries {
+            let record = LogEventRecord {
+                run_id: ctx.run_id.to_string(),
+                source: "journald".to_string(),
+                timestamp: entry.realtime,
+                timestamp_raw: None,
+                host: entry.hostname,
+                program: entry.identifier,
+                pid: entry.pid,
+                severity: entry.priority,
+                message: entry.message.unwrap_or_default(),
+                global_offset: file.global_start + entry.offset,
+                source_file: Some(file.path.clone()),
+            };
+            if let Err(err) = ctx.meta_tx.send(MetadataEvent::LogEvent(record)) {
+                warn!("metadata channel closed while sending journal event: {err}");
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::journal::journal_bytes;
+
+    #[test]
+    fn sends_entries_as_log_events() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let journal = journal_bytes(&[(
+            1_700_000_000_000_000,
+            &[
+                "_HOSTNAME=web01",
+                "SYSLOG_IDENTIFIER=sshd",
+                "MESSAGE=Accepted",
+            ],
+        )]);
+        let path = dir.path().join("system.journal");
+        std::fs::write(&path, &journal).expect("write journal");
+        let file = CarvedFile {
+            run_id: "run".to_string(),
+            file_type: "journald".to_string(),
+            signature_type: "journald".to_string(),
+            path: "journald/journald_000000200000.journal".to_string(),
+            extension: "journal".to_string(),
+            global_start: 0x200000,
+            global_end: 0x200000 + journal.len() as u64 - 1,
+            size: journal.len() as u64,
+            md5: None,
+            sha256: None,
+            validated: true,
+            truncated: false,
+            errors: Vec::new(),
+            pattern_id: None,
+        };
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let tx = tx.into();
+        let ctx = ProcessContext {
+            run_id: "run",
+            run_output_dir: dir.path(),
+            meta_tx: &tx,
+        };
+        JournalEventProcessor
+            .process(&file, &path, &ctx)
+            .expect("process");
+        drop(tx);
+
+        let events: Vec<LogEventRecord> = rx
+            .iter()
+            .filter_map(|event| match event {
+                MetadataEvent::LogEvent(record) => Some(record),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].source, "journald");
+        assert_eq!(events[0].program.as_deref(), Some("sshd"));
+        assert_eq!(events[0].message, "Accepted");
+        // Header (256) plus three DATA objects before the entry.
+        assert!(events[0].global_offset > 0x200000 + 256);
+        assert_eq!(events[0].source_file.as_deref(), Some(file.path.as_str()));
+    }
+}
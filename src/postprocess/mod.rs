@@ -0,0 +1,632 @@
+//! # Post-Carve Processors
+//!
+//! Per-type enrichment that runs on a carved file after it has been written.
+//! Processors are configured per file type (`post_processors`) and looked up by
+//! the carve workers, so adding enrichment for a type does not touch the workers.
+//!
+//! Built-in processors:
+//! - `sqlite_browser` ([`SqliteBrowserProcessor`]): browser history, cookies, and
+//!   downloads from SQLite databases. Default for types using the `sqlite` validator.
+//! - `apple_databases` ([`AppleDatabaseProcessor`]): notes, iMessages, and
+//!   KnowledgeC usage events from Apple SQLite databases. Also a default for the
+//!   `sqlite` validator.
+//! - `sqlite_apps` ([`SqliteAppProcessor`]): labels SQLite databases with the
+//!   application catalog entries their schema matches and reads the rows named
+//!   by the entries' extraction plans. Also a default for the `sqlite` validator.
+//! - `browser_cache` ([`BrowserCacheProcessor`]): cached HTTP responses from
+//!   Chromium and Firefox cache entries. Default for the `chrome_cache` and
+//!   `firefox_cache` validators.
+//! - `pcap_artefacts` ([`PcapArtefactProcessor`]): connection tuples, DNS queries,
+//!   and HTTP hosts from packet captures. Default for the `pcap` and `pcapng`
+//!   validators.
+//! - `journal_events` ([`JournalEventProcessor`]): log events from systemd
+//!   journal files. Default for the `journald` validator.
+//! - `container_artefacts` ([`ContainerArtefactProcessor`]): image layers,
+//!   whiteouts, and image tarball manifests from tar archives, and images and
+//!   containers from containerd bolt databases. Default for the `tar` and
+//!   `boltdb` validators.
+//! - `image_info` ([`ImageInfoProcessor`]): dimensions, bits per pixel, and
+//!   for baseline JPEGs the share decoded before corruption. Default for the
+//!   `jpeg`, `png`, `gif`, `bmp`, `webp`, and `tiff` validators.
+//! - `photo_exif` ([`PhotoExifProcessor`]): camera make, model, serial, lens,
+//!   and capture time from EXIF and XMP, grouped by device and session at the
+//!   end of the run. Default for the `jpeg` and `tiff` validators.
+//! - `p2p_artefacts` ([`P2pArtefactProcessor`]): info hash, name, trackers,
+//!   and DHT nodes from BitTorrent metainfo files. Default for the `torrent`
+//!   validator.
+//! - `encrypted_documents` ([`EncryptedDocumentProcessor`]): encryption type,
+//!   cipher, and plaintext size of password-protected OOXML documents. Default
+//!   for the `ole` validator; acts on carves labelled `encrypted_ooxml`.
+//! - `browser_cookies` ([`BrowserCookieProcessor`]): cookies from Safari
+//!   `Cookies.binarycookies` stores and Netscape `cookies.txt` files. Default
+//!   for the `binarycookies` and `netscape_cookies` validators.
+//! - `prefetch_files` ([`PrefetchFileProcessor`]): executable, run count, and
+//!   run times of Windows Prefetch files, decompressing `MAM` files first.
+//!   Default for the `prefetch` validator.
+//! - `shortcuts` ([`ShortcutProcessor`]): target path, arguments, working
+//!   directory, and target times of Windows shortcuts. Default for the `lnk`
+//!   validator.
+//! - `executables` ([`ExecutableProcessor`]): kind and architecture of PE,
+//!   ELF, and Mach-O executables, and compile time and import hash of PE
+//!   images. Default for the `pe`, `elf`, and `macho` validators.
+//! - `thumbcache_images` ([`ThumbcacheProcessor`]): writes each thumbnail of
+//!   a Windows thumbnail cache to `carved/thumbnail/` and records its cache
+//!   ID in `thumbnails`. Default for the `thumbcache` validator.
+//! - `video_repair` ([`VideoRepairProcessor`]): rebuilds the `moov` index of
+//!   MP4 and QuickTime recordings that were never finalized, from a donor
+//!   file or from the H.264 parameter sets in the media data, and writes a
+//!   playable copy to `carved/repaired_video/`. Not a default; configured on
+//!   the `mp4` and `mov` types.
+//! - `plist_contents` ([`PlistProcessor`]): decodes binary plists and
+//!   records their contents as JSON in `plists`. Default for the `bplist`
+//!   validator.
+//! - `command` ([`CommandProcessor`]): runs an external program with a timeout.
+//! - `classifier` ([`ClassifierProcessor`]): scores carved files with a
+//!   user-supplied model server and records the labels in `classifications`.
+//!
+//! A database carved with a `-wal` companion is copied with it into the run's
+//! scratch space ([`crate::tempspace`]) before its processors run, so SQLite
+//! merges the WAL into the copy and its `-shm` index is not left among the
+//! carved files. When the pair does not fit in `temp_space_limit`, the
+//! processors read the carved database in place.
+
+pub mod apple;
+pub mod browser_cache;
+pub mod classify;
+pub mod command;
+pub mod container;
+pub mod cookies;
+pub mod encrypted;
+pub mod executables;
+pub mod exif;
+pub mod image;
+pub mod journal;
+pub mod p2p;
+pub mod pcap;
+pub mod plist;
+pub mod prefetch;
+pub mod shortcuts;
+pub mod sqlite;
+pub mod sqlite_apps;
+pub mod thumbcache;
+pub mod video_repair;
+
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use thiserror::Error;
+use tracing::{debug, warn};
+
+use crate::carve::CarvedFile;
+use crate::config::{Config, FileTypeConfig, PostProcessorConfig};
+use crate::parsers::sqlite_apps::AppCatalog;
+use crate::pipeline::events::MetadataEvent;
+use crate::pipeline::queue::StageSender;
+use crate::tempspace::{Scratch, TempSpace};
+
+pub use apple::AppleDatabaseProcessor;
+pub use browser_cache::BrowserCacheProcessor;
+pub use classify::ClassifierProcessor;
+pub use command::CommandProcessor;
+pub use container::ContainerArtefactProcessor;
+pub use cookies::BrowserCookieProcessor;
+pub use encrypted::EncryptedDocumentProcessor;
+pub use executables::ExecutableProcessor;
+pub use exif::PhotoExifProcessor;
+pub use image::ImageInfoProcessor;
+pub use journal::JournalEventProcessor;
+pub use p2p::P2pArtefactProcessor;
+pub use pcap::PcapArtefactProcessor;
+pub use plist::PlistProcessor;
+pub use prefetch::PrefetchFileProcessor;
+pub use shortcuts::ShortcutProcessor;
+pub use sqlite::SqliteBrowserProcessor;
+pub use sqlite_apps::SqliteAppProcessor;
+pub use thumbcache::ThumbcacheProcessor;
+pub use video_repair::VideoRepairProcessor;
+
+/// Directory (relative to the run output directory) holding processor output.
+pub const POSTPROCESS_DIR: &str = "postprocess";
+
+#[derive(Debug, Error)]
+pub enum PostProcessError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("timed out after {0}s")]
+    Timeout(u64),
+    #[error("exited with {0}")]
+    Failed(std::process::ExitStatus),
+    #[error("{0}")]
+    Other(String),
+}
+
+/// Where a processor sends its results.
+pub struct ProcessContext<'a> {
+    pub run_id: &'a str,
+    pub run_output_dir: &'a Path,
+    pub meta_tx: &'a StageSender<MetadataEvent>,
+}
+
+pub trait PostProcessor: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Process one carved file stored at `path`.
+    fn process(
+        &self,
+        file: &CarvedFile,
+        path: &Path,
+        ctx: &ProcessContext,
+    ) -> Result<(), PostProcessError>;
+}
+
+/// Bytes reserved for the `-shm` index SQLite builds next to a staged WAL:
+/// 32 KiB per 4096 frames, which is at least 64 bytes of WAL per index byte.
+const SHM_BASE: u64 = 32 * 1024;
+const WAL_BYTES_PER_SHM_BYTE: u64 = 64;
+
+/// Processors keyed by file type id.
+#[derive(Default)]
+pub struct PostProcessRegistry {
+    processors: HashMap<String, Vec<Arc<dyn PostProcessor>>>,
+    errors: AtomicU64,
+    temp: Option<Arc<TempSpace>>,
+}
+
+impl PostProcessRegistry {
+    pub fn new(processors: HashMap<String, Vec<Arc<dyn PostProcessor>>>) -> Self {
+        Self {
+            processors,
+            errors: AtomicU64::new(0),
+            temp: None,
+        }
+    }
+
+    /// Build processors for every configured file type. SQLite parse failures
+    /// are counted in `sqlite_errors`; databases with a WAL are staged in
+    /// `temp`.
+    pub fn from_config(cfg: &Config, sqlite_errors: Arc<AtomicU64>, temp: Arc<TempSpace>) -> Self {
+        let sqlite: Arc<dyn PostProcessor> = Arc::new(SqliteBrowserProcessor::new(
+            cfg.enable_sqlite_page_recovery,
+            sqlite_errors.clone(),
+        ));
+        let apple: Arc<dyn PostProcessor> =
+            Arc::new(AppleDatabaseProcessor::new(sqlite_errors.clone()));
+        let catalog = AppCatalog::load(cfg.sqlite_app_catalog.as_deref()).unwrap_or_else(|err| {
+            warn!("{err:#}; using the built-in sqlite app catalog");
+            AppCatalog::builtin()
+        });
+        let sqlite_apps: Arc<dyn PostProcessor> =
+            Arc::new(SqliteAppProcessor::new(Arc::new(catalog), sqlite_errors));
+        let browser_cache: Arc<dyn PostProcessor> = Arc::new(BrowserCacheProcessor);
+        let pcap: Arc<dyn PostProcessor> = Arc::new(PcapArtefactProcessor);
+        let journal: Arc<dyn PostProcessor> = Arc::new(JournalEventProcessor);
+        let container: Arc<dyn PostProcessor> = Arc::new(ContainerArtefactProcessor);
+        let image: Arc<dyn PostProcessor> = Arc::new(ImageInfoProcessor);
+        let photo_exif: Arc<dyn PostProcessor> = Arc::new(PhotoExifProcessor);
+        let p2p: Arc<dyn PostProcessor> = Arc::new(P2pArtefactProcessor);
+        let encrypted: Arc<dyn PostProcessor> = Arc::new(EncryptedDocumentProcessor);
+        let cookies: Arc<dyn PostProcessor> = Arc::new(BrowserCookieProcessor);
+        let prefetch: Arc<dyn PostProcessor> = Arc::new(PrefetchFileProcessor);
+        let shortcuts: Arc<dyn PostProcessor> = Arc::new(ShortcutProcessor);
+        let executables: Arc<dyn PostProcessor> = Arc::new(ExecutableProcessor);
+        let thumbcache: Arc<dyn PostProcessor> = Arc::new(ThumbcacheProcessor);
+        let plists: Arc<dyn PostProcessor> = Arc::new(PlistProcessor);
+        // One server per classifier name, shared by every type naming it.
+        let mut classifiers: HashMap<String, Arc<dyn PostProcessor>> = HashMap::new();
+        let mut processors = HashMap::new();
+        for file_type in &cfg.file_types {
+            let list: Vec<Arc<dyn PostProcessor>> = configured(file_type)
+                .into_iter()
+                .map(|pc| match pc {
+                    PostProcessorConfig::SqliteBrowser => sqlite.clone(),
+                    PostProcessorConfig::BrowserCache => browser_cache.clone(),
+                    PostProcessorConfig::PcapArtefacts => pcap.clone(),
+                    PostProcessorConfig::JournalEvents => journal.clone(),
+                    PostProcessorConfig::ContainerArtefacts => container.clone(),
+                    PostProcessorConfig::AppleDatabases => apple.clone(),
+                    PostProcessorConfig::SqliteApps => sqlite_apps.clone(),
+                    PostProcessorConfig::ImageInfo => image.clone(),
+                    PostProcessorConfig::PhotoExif => photo_exif.clone(),
+                    PostProcessorConfig::P2pArtefacts => p2p.clone(),
+                    PostProcessorConfig::EncryptedDocuments => encrypted.clone(),
+                    PostProcessorConfig::BrowserCookies => cookies.clone(),
+                    PostProcessorConfig::PrefetchFiles => prefetch.clone(),
+                    PostProcessorConfig::Shortcuts => shortcuts.clone(),
+                    PostProcessorConfig::Executables => executables.clone(),
+                    PostProcessorConfig::ThumbcacheImages => thumbcache.clone(),
+                    PostProcessorConfig::PlistContents => plists.clone(),
+                    PostProcessorConfig::VideoRepair { donor, frame_rate } => {
+                        let donor = donor.and_then(|path| {
+                            match crate::parsers::mp4::VideoTemplate::from_donor(&path) {
+                                Ok(template) => Some((template, path)),
+                                Err(err) => {
+                                    warn!("{err:#}; repairing {} without a donor", file_type.id);
+                                    None
+                                }
+                            }
+                        });
+                        Arc::new(VideoRepairProcessor::new(donor, frame_rate))
+                    }
+                    PostProcessorConfig::Command {
+                        name,
+                        program,
+                        args,
+                        timeout_secs,
+                    } => Arc::new(CommandProcessor::new(name, program, args, timeout_secs)),
+                    PostProcessorConfig::Classifier {
+                        name,
+                        program,
+                        args,
+                        timeout_secs,
+                        min_score,
+                        top_k,
+                    } => {
+                        let classifier = ClassifierProcessor::new(
+                            name,
+                            program,
+                            args,
+                            timeout_secs,
+                            min_score,
+                            top_k,
+                        );
+                        classifiers
+                            .entry(classifier.name().to_string())
+                            .or_insert_with(|| Arc::new(classifier))
+                            .clone()
+                    }
+                })
+                .collect();
+            if !list.is_empty() {
+                processors.insert(file_type.id.clone(), list);
+            }
+        }
+        Self {
+            temp: Some(temp),
+            ..Self::new(processors)
+        }
+    }
+
+    /// Whether any processor is registered for `file_type_id`.
+    pub fn handles(&self, file_type_id: &str) -> bool {
+        self.processors.contains_key(file_type_id)
+    }
+
+    /// Run the processors registered for `file_type_id` in order. Failures are
+    /// logged and counted; later processors still run.
+    pub fn run(&self, file_type_id: &str, file: &CarvedFile, path: &Path, ctx: &ProcessContext) {
+        let Some(list) = self.processors.get(file_type_id) else {
+            return;
+        };
+        let staged = self.stage_wal(path);
+        let path = staged.as_ref().map_or(path, |(_, copy)| copy.as_path());
+        for processor in list {
+            if let Err(err) = processor.process(file, path, ctx) {
+                self.errors.fetch_add(1, Ordering::Relaxed);
+                warn!(
+                    "post-processor {} failed for {}: {err}",
+                    processor.name(),
+                    path.display()
+                );
+            }
+        }
+    }
+
+    /// Number of processor runs that returned an error.
+    pub fn errors(&self) -> u64 {
+        self.errors.load(Ordering::Relaxed)
+    }
+
+    /// Copy `path` and the `-wal` carved next to it into scratch space;
+    /// `None` without a WAL or when the pair cannot be staged.
+    fn stage_wal(&self, path: &Path) -> Option<(Scratch<'_>, PathBuf)> {
+        let temp = self.temp.as_ref()?;
+        let wal = with_suffix(path, "-wal");
+        let wal_len = fs::metadata(&wal).ok()?.len();
+        let db_len = fs::metadata(path).ok()?.len();
+        let shm_len = SHM_BASE + wal_len / WAL_BYTES_PER_SHM_BYTE;
+        let scratch = match temp.reserve(db_len + wal_len + shm_len) {
+            Ok(scratch) => scratch,
+            Err(err) => {
+                debug!("reading {} in place: {err}", path.display());
+                return None;
+            }
+        };
+        let copy = scratch.dir().join(path.file_name()?);
+        let copied = fs::copy(path, &copy).and_then(|_| fs::copy(&wal, with_suffix(&copy, "-wal")));
+        match copied {
+            Ok(_) => Some((scratch, copy)),
+            Err(err) => {
+                warn!("failed to stage {} with its WAL: {err}", path.display());
+                None
+            }
+        }
+    }
+}
+
+/// `path` with `suffix` appended to its file name, as SQLite names the WAL.
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Processors configured for a type, falling back to the built-in defaults.
+fn configured(file_type: &FileTypeConfig) -> Vec<PostProcessorConfig> {
+    if let Some(list) = &file_type.post_processors {
+        return list.clone();
+    }
+    let validator = if file_type.validator.trim().is_empty() {
+        file_type.id.as_str()
+    } else {
+        file_type.validator.as_str()
+    };
+    match validator {
+        "sqlite" => vec![
+            PostProcessorConfig::SqliteBrowser,
+            PostProcessorConfig::AppleDatabases,
+            PostProcessorConfig::SqliteApps,
+        ],
+        "chrome_cache" | "firefox_cache" => vec![PostProcessorConfig::BrowserCache],
+        "pcap" | "pcapng" => vec![PostProcessorConfig::PcapArtefacts],
+        "journald" => vec![PostProcessorConfig::JournalEvents],
+        "tar" | "boltdb" => vec![PostProcessorConfig::ContainerArtefacts],
+        "jpeg" | "tiff" => vec![
+            PostProcessorConfig::ImageInfo,
+            PostProcessorConfig::PhotoExif,
+        ],
+        "png" | "gif" | "bmp" | "webp" => vec![PostProcessorConfig::ImageInfo],
+        "torrent" => vec![PostProcessorConfig::P2pArtefacts],
+        "ole" => vec![PostProcessorConfig::EncryptedDocuments],
+        "binarycookies" | "netscape_cookies" => vec![PostProcessorConfig::BrowserCookies],
+        "prefetch" => vec![PostProcessorConfig::PrefetchFiles],
+        "lnk" => vec![PostProcessorConfig::Shortcuts],
+        "pe" | "elf" | "macho" => vec![PostProcessorConfig::Executables],
+        "thumbcache" => vec![PostProcessorConfig::ThumbcacheImages],
+        "bplist" => vec![PostProcessorConfig::PlistContents],
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file_type(id: &str, post_processors: Option<Vec<PostProcessorConfig>>) -> FileTypeConfig {
+        FileTypeConfig {
+            id: id.to_string(),
+            extensions: vec![id.to_string()],
+            header_patterns: Vec::new(),
+            footer_patterns: Vec::new(),
+            max_size: 0,
+            min_size: 0,
+            validator: String::new(),
+            require_eocd: false,
+            footer_mode: Default::default(),
+            retention: Default::default(),
+            hit_cluster_window: None,
+            declared_size_factor: None,
+            post_processors,
+        }
+    }
+
+    #[test]
+    fn browser_types_get_default_processors() {
+        assert_eq!(
+            configured(&file_type("sqlite", None)),
+            vec![
+                PostProcessorConfig::SqliteBrowser,
+                PostProcessorConfig::AppleDatabases,
+                PostProcessorConfig::SqliteApps
+            ]
+        );
+        assert!(configured(&file_type("sqlite", Some(Vec::new()))).is_empty());
+        assert_eq!(
+            configured(&file_type("jpeg", None)),
+            vec![
+                PostProcessorConfig::ImageInfo,
+                PostProcessorConfig::PhotoExif
+            ]
+        );
+        assert!(configured(&file_type("ogg", None)).is_empty());
+        assert_eq!(
+            configured(&file_type("chrome_cache", None)),
+            vec![PostProcessorConfig::BrowserCache]
+        );
+        assert_eq!(
+            configured(&file_type("torrent", None)),
+            vec![PostProcessorConfig::P2pArtefacts]
+        );
+        assert_eq!(
+            configured(&file_type("ole", None)),
+            vec![PostProcessorConfig::EncryptedDocuments]
+        );
+        assert_eq!(
+            configured(&file_type("netscape_cookies", None)),
+            vec![PostProcessorConfig::BrowserCookies]
+        );
+        assert_eq!(
+            configured(&file_type("prefetch", None)),
+            vec![PostProcessorConfig::PrefetchFiles]
+        );
+        assert_eq!(
+            configured(&file_type("lnk", None)),
+            vec![PostProcessorConfig::Shortcuts]
+        );
+        assert_eq!(
+            configured(&file_type("pe", None)),
+            vec![PostProcessorConfig::Executables]
+        );
+        assert_eq!(
+            configured(&file_type("macho", None)),
+            vec![PostProcessorConfig::Executables]
+        );
+        assert_eq!(
+            configured(&file_type("thumbcache", None)),
+            vec![PostProcessorConfig::ThumbcacheImages]
+        );
+        assert_eq!(
+            configured(&file_type("bplist", None)),
+            vec![PostProcessorConfig::PlistContents]
+        );
+    }
+
+    #[test]
+    fn registry_follows_config() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let mut cfg = crate::config::load_config(None).expect("config").config;
+        cfg.file_types = vec![
+            file_type("sqlite", None),
+            file_type(
+                "jpeg",
+                Some(vec![PostProcessorConfig::Command {
+                    name: Some("exif".to_string()),
+                    program: "exiftool".to_string(),
+                    args: vec!["{path}".to_string()],
+                    timeout_secs: 5,
+                }]),
+            ),
+            file_type("png", None),
+            file_type(
+                "pdf",
+                Some(vec![PostProcessorConfig::Classifier {
+                    name: None,
+                    program: "/opt/models/doc-class".to_string(),
+                    args: Vec::new(),
+                    timeout_secs: 5,
+                    min_score: 0.0,
+                    top_k: 1,
+                }]),
+            ),
+            file_type(
+                "docx",
+                Some(vec![PostProcessorConfig::Classifier {
+                    name: None,
+                    program: "/opt/models/doc-class".to_string(),
+                    args: Vec::new(),
+                    timeout_secs: 5,
+                    min_score: 0.0,
+                    top_k: 1,
+                }]),
+            ),
+        ];
+        let temp = Arc::new(TempSpace::new(dir.path(), "run", 1 << 20));
+        let registry = PostProcessRegistry::from_config(&cfg, Arc::new(AtomicU64::new(0)), temp);
+        let names = |id: &str| -> Vec<String> {
+            registry
+                .processors
+                .get(id)
+                .map(|l| l.iter().map(|p| p.name().to_string()).collect())
+                .unwrap_or_default()
+        };
+        assert_eq!(
+            names("sqlite"),
+            vec!["sqlite_browser", "apple_databases", "sqlite_apps"]
+        );
+        assert_eq!(names("jpeg"), vec!["exif"]);
+        assert_eq!(names("png"), vec!["image_info"]);
+        assert_eq!(names("pdf"), vec!["doc-class"]);
+        assert!(Arc::ptr_eq(
+            &registry.processors["pdf"][0],
+            &registry.processors["docx"][0]
+        ));
+    }
+
+    /// Counts the rows of `t` in the database it is given.
+    struct RowCounter(std::sync::Mutex<Vec<(PathBuf, i64)>>);
+
+    impl PostProcessor for RowCounter {
+        fn name(&self) -> &str {
+            "row_counter"
+        }
+
+        fn process(
+            &self,
+            _file: &CarvedFile,
+            path: &Path,
+            _ctx: &ProcessContext,
+        ) -> Result<(), PostProcessError> {
+            let conn = rusqlite::Connection::open_with_flags(
+                path,
+                rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY,
+            )
+            .map_err(|err| PostProcessError::Other(err.to_string()))?;
+            let rows = conn
+                .query_row("SELECT count(*) FROM t", [], |row| row.get(0))
+                .map_err(|err| PostProcessError::Other(err.to_string()))?;
+            self.0.lock().unwrap().push((path.to_path_buf(), rows));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn databases_with_a_wal_are_read_from_scratch_space() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let source = dir.path().join("source.db");
+        let conn = rusqlite::Connection::open(&source).expect("conn");
+        conn.execute_batch(
+            "PRAGMA journal_mode = WAL;
+             PRAGMA wal_autocheckpoint = 0;
+             CREATE TABLE t (x);
+             INSERT INTO t VALUES (1);",
+        )
+        .expect("schema");
+        let carved = dir.path().join("carved");
+        fs::create_dir(&carved).expect("mkdir");
+        let db = carved.join("sqlite_000000000000.sqlite");
+        fs::copy(&source, &db).expect("copy db");
+        fs::copy(with_suffix(&source, "-wal"), with_suffix(&db, "-wal")).expect("copy wal");
+        drop(conn);
+
+        let file = CarvedFile {
+            run_id: "run".to_string(),
+            file_type: "sqlite".to_string(),
+            signature_type: "sqlite".to_string(),
+            path: "sqlite/sqlite_000000000000.sqlite".to_string(),
+            extension: "sqlite".to_string(),
+            global_start: 0,
+            global_end: 0,
+            size: 0,
+            md5: None,
+            sha256: None,
+            validated: true,
+            truncated: false,
+            errors: Vec::new(),
+            pattern_id: None,
+        };
+        let (tx, _rx) = crossbeam_channel::unbounded();
+        let tx = tx.into();
+        let ctx = ProcessContext {
+            run_id: "run",
+            run_output_dir: dir.path(),
+            meta_tx: &tx,
+        };
+        for limit in [1 << 20, 0] {
+            let counter = Arc::new(RowCounter(Default::default()));
+            let temp = Arc::new(TempSpace::new(&dir.path().join("tmp"), "run", limit));
+            let registry = PostProcessRegistry {
+                temp: Some(temp.clone()),
+                ..PostProcessRegistry::new(HashMap::from([(
+                    "sqlite".to_string(),
+                    vec![counter.clone() as Arc<dyn PostProcessor>],
+                )]))
+            };
+            registry.run("sqlite", &file, &db, &ctx);
+            let seen = counter.0.lock().unwrap().clone();
+            assert_eq!(seen.len(), 1);
+            assert_eq!(seen[0].1, 1, "WAL rows visible");
+            if limit > 0 {
+                assert!(seen[0].0.starts_with(temp.dir()));
+                assert!(!seen[0].0.exists(), "scratch copy removed");
+                assert!(!with_suffix(&db, "-shm").exists());
+                assert_eq!(temp.stats().reservations, 1);
+            } else {
+                assert_eq!(seen[0].0, db);
+                assert_eq!(temp.stats().denied, 1);
+            }
+        }
+    }
+}
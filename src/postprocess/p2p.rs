@@ -0,0 +1,126 @@
+//! P2P artefacts from carved BitTorrent metainfo files.
+
+use std::path::Path;
+
+use tracing::warn;
+
+use crate::carve::CarvedFile;
+use crate::parsers::p2p::Torrent;
+use crate::pipeline::events::MetadataEvent;
+use crate::strings::artifacts::StringArtefact;
+
+use super::{PostProcessError, PostProcessor, ProcessContext};
+
+/// Encoding and source kind recorded on artefacts read from metainfo files.
+pub const TORRENT_ENCODING: &str = "bencode";
+
+/// Reports the info hash, name, trackers, and DHT nodes of a carved `.torrent`
+/// file as string artefacts. Offsets cover the bencoded value each artefact
+/// was read from; for the info hash that is the whole `info` dictionary.
+pub struct P2pArtefactProcessor;
+
+impl PostProcessor for P2pArtefactProcessor {
+    fn name(&self) -> &str {
+        "p2p_artefacts"
+    }
+
+    fn process(
+        &self,
+        file: &CarvedFile,
+        path: &Path,
+        ctx: &ProcessContext,
+    ) -> Result<(), PostProcessError> {
+        let data = std::fs::read(path)?;
+        let Some(torrent) = Torrent::parse(&data) else {
+            // Truncated carves keep the prefix of a dictionary.
+            return Ok(());
+        };
+        for artefact in torrent.artefacts() {
+            let global_start = file.global_start + artefact.range.start as u64;
+            let record = StringArtefact {
+                run_id: ctx.run_id.to_string(),
+                artefact_kind: artefact.kind,
+                content: artefact.value,
+                encoding: TORRENT_ENCODING.to_string(),
+                global_start,
+                global_end: global_start + (artefact.range.len() as u64).saturating_sub(1),
+                source_kind: "torrent".to_string(),
+                source_detail: file.path.clone(),
+            };
+            if let Err(err) = ctx.meta_tx.send(MetadataEvent::String(record)) {
+                warn!("metadata channel closed while sending p2p artefact: {err}");
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::p2p::torrent_bytes;
+    use crate::strings::artifacts::ArtefactKind;
+
+    #[test]
+    fn sends_info_hash_and_trackers_with_evidence_offsets() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let data = torrent_bytes();
+        let path = dir.path().join("file.torrent");
+        std::fs::write(&path, &data).expect("write torrent");
+        let file = CarvedFile {
+            run_id: "run".to_string(),
+            file_type: "torrent".to_string(),
+            signature_type: "torrent".to_string(),
+            path: "torrent/torrent_000000001000.torrent".to_string(),
+            extension: "torrent".to_string(),
+            global_start: 0x1000,
+            global_end: 0x1000 + data.len() as u64 - 1,
+            size: data.len() as u64,
+            md5: None,
+            sha256: None,
+            validated: true,
+            truncated: false,
+            errors: Vec::new(),
+            pattern_id: None,
+        };
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let tx = tx.into();
+        let ctx = ProcessContext {
+            run_id: "run",
+            run_output_dir: dir.path(),
+            meta_tx: &tx,
+        };
+        P2pArtefactProcessor
+            .process(&file, &path, &ctx)
+            .expect("process");
+        drop(tx);
+
+        let artefacts: Vec<StringArtefact> = rx
+            .iter()
+            .filter_map(|event| match event {
+                MetadataEvent::String(artefact) => Some(artefact),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(artefacts.len(), 5);
+        let hash = &artefacts[0];
+        assert!(matches!(hash.artefact_kind, ArtefactKind::TorrentInfoHash));
+        assert_eq!(hash.content.len(), 40);
+        let info_start = data
+            .windows(6)
+            .position(|w| w == b"4:info")
+            .expect("info key")
+            + 6;
+        assert_eq!(hash.global_start, 0x1000 + info_start as u64);
+        let tracker = &artefacts[2];
+        assert!(matches!(
+            tracker.artefact_kind,
+            ArtefactKind::TorrentTracker
+        ));
+        assert_eq!(tracker.content, "http://tracker.test/announce");
+        assert_eq!(tracker.global_start, 0x1000 + 14);
+        assert_eq!(tracker.source_kind, "torrent");
+        assert_eq!(tracker.source_detail, file.path);
+    }
+}
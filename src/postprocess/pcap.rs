@@ -0,0 +1,120 @@
+//! Network artefacts from carved packet captures.
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use tracing::warn;
+
+use crate::carve::CarvedFile;
+use crate::parsers::pcap::{self, PacketReader};
+use crate::pipeline::events::MetadataEvent;
+use crate::strings::artifacts::StringArtefact;
+
+use super::{PostProcessError, PostProcessor, ProcessContext};
+
+/// Encoding recorded on artefacts decoded from packets.
+pub const PCAP_ENCODING: &str = "pcap";
+
+/// Reports connection tuples, DNS query names, and HTTP hosts from a carved
+/// capture as string artefacts. Offsets point at the first packet record each
+/// artefact was seen in.
+pub struct PcapArtefactProcessor;
+
+impl PostProcessor for PcapArtefactProcessor {
+    fn name(&self) -> &str {
+        "pcap_artefacts"
+    }
+
+    fn process(
+        &self,
+        file: &CarvedFile,
+        path: &Path,
+        ctx: &ProcessContext,
+    ) -> Result<(), PostProcessError> {
+        let reader = BufReader::new(File::open(path)?);
+        let Some(mut reader) = PacketReader::new(reader)? else {
+            return Err(PostProcessError::Other("not a packet capture".to_string()));
+        };
+        for artefact in pcap::extract_artefacts(&mut reader)? {
+            let global_start = file.global_start + artefact.record_offset;
+            let record = StringArtefact {
+                run_id: ctx.run_id.to_string(),
+                artefact_kind: artefact.kind,
+                content: artefact.value,
+                encoding: PCAP_ENCODING.to_string(),
+                global_start,
+                global_end: global_start + artefact.record_len.saturating_sub(1),
+                source_kind: PCAP_ENCODING.to_string(),
+                source_detail: file.path.clone(),
+            };
+            if let Err(err) = ctx.meta_tx.send(MetadataEvent::String(record)) {
+                warn!("metadata channel closed while sending network artefact: {err}");
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::pcap::{ipv4_frame, pcap_bytes};
+    use crate::strings::artifacts::ArtefactKind;
+
+    #[test]
+    fn sends_artefacts_with_evidence_offsets() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let capture = pcap_bytes(&[ipv4_frame(
+            6,
+            50000,
+            8080,
+            b"POST /api HTTP/1.1\r\nHost: Internal.Example:8080\r\n\r\n",
+        )]);
+        let path = dir.path().join("capture.pcap");
+        std::fs::write(&path, &capture).expect("write capture");
+        let file = CarvedFile {
+            run_id: "run".to_string(),
+            file_type: "pcap".to_string(),
+            signature_type: "pcap".to_string(),
+            path: "pcap/pcap_000000010000.pcap".to_string(),
+            extension: "pcap".to_string(),
+            global_start: 0x10000,
+            global_end: 0x10000 + capture.len() as u64 - 1,
+            size: capture.len() as u64,
+            md5: None,
+            sha256: None,
+            validated: true,
+            truncated: false,
+            errors: Vec::new(),
+            pattern_id: None,
+        };
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let tx = tx.into();
+        let ctx = ProcessContext {
+            run_id: "run",
+            run_output_dir: dir.path(),
+            meta_tx: &tx,
+        };
+        PcapArtefactProcessor
+            .process(&file, &path, &ctx)
+            .expect("process");
+        drop(tx);
+
+        let artefacts: Vec<StringArtefact> = rx
+            .iter()
+            .filter_map(|event| match event {
+                MetadataEvent::String(artefact) => Some(artefact),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(artefacts.len(), 2);
+        assert!(matches!(artefacts[1].artefact_kind, ArtefactKind::HttpHost));
+        assert_eq!(artefacts[1].content, "internal.example:8080");
+        assert_eq!(artefacts[1].global_start, 0x10000 + 24);
+        assert_eq!(artefacts[1].global_end, file.global_end);
+        assert_eq!(artefacts[1].source_kind, "pcap");
+        assert_eq!(artefacts[1].source_detail, file.path);
+    }
+}
@@ -0,0 +1,101 @@
+//! Contents of carved binary property lists.
+
+use std::path::Path;
+
+use tracing::warn;
+
+use crate::carve::CarvedFile;
+use crate::parsers::bplist;
+use crate::pipeline::events::MetadataEvent;
+
+use super::{PostProcessError, PostProcessor, ProcessContext};
+
+/// JSON forms longer than this are left out of the record.
+const MAX_JSON_LEN: usize = 1024 * 1024;
+
+/// Decodes each carved binary plist and records its top-level keys,
+/// `NSKeyedArchiver` marker, and JSON form in `plists`. Plists that no longer
+/// decode are skipped.
+pub struct PlistProcessor;
+
+impl PostProcessor for PlistProcessor {
+    fn name(&self) -> &str {
+        "plist_contents"
+    }
+
+    fn process(
+        &self,
+        file: &CarvedFile,
+        path: &Path,
+        ctx: &ProcessContext,
+    ) -> Result<(), PostProcessError> {
+        let data = std::fs::read(path)?;
+        let Ok(decoded) = bplist::decode(&data) else {
+            return Ok(());
+        };
+        let record = decoded.to_record(ctx.run_id, MAX_JSON_LEN, &file.path);
+        if let Err(err) = ctx.meta_tx.send(MetadataEvent::Plist(record)) {
+            warn!("metadata channel closed while sending plist record: {err}");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::bplist::testing::bplist_bytes;
+
+    #[test]
+    fn records_plist_contents() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let data = bplist_bytes();
+        let path = dir.path().join("prefs.plist");
+        std::fs::write(&path, &data).expect("write plist");
+        let file = CarvedFile {
+            run_id: "run".to_string(),
+            file_type: "bplist".to_string(),
+            signature_type: "bplist".to_string(),
+            path: "bplist/bplist_000000000000.plist".to_string(),
+            extension: "plist".to_string(),
+            global_start: 0,
+            global_end: data.len() as u64 - 1,
+            size: data.len() as u64,
+            md5: None,
+            sha256: None,
+            validated: true,
+            truncated: false,
+            errors: Vec::new(),
+            pattern_id: None,
+        };
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let tx = tx.into();
+        let ctx = ProcessContext {
+            run_id: "run",
+            run_output_dir: dir.path(),
+            meta_tx: &tx,
+        };
+        PlistProcessor.process(&file, &path, &ctx).expect("process");
+        std::fs::write(&path, &data[..data.len() - 8]).expect("write cut plist");
+        PlistProcessor
+            .process(&file, &path, &ctx)
+            .expect("process cut");
+        drop(tx);
+
+        let records: Vec<_> = rx
+            .iter()
+            .filter_map(|event| match event {
+                MetadataEvent::Plist(record) => Some(record),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(records.len(), 1);
+        let record = &records[0];
+        assert_eq!(record.top_type, "dict");
+        assert_eq!(record.object_count, 13);
+        let json: serde_json::Value =
+            serde_json::from_str(record.json.as_deref().expect("json")).expect("valid json");
+        assert_eq!(json["name"], "Café");
+        assert_eq!(record.source_file, file.path);
+    }
+}
@@ -0,0 +1,105 @@
+//! Executable names and run counts of carved Windows Prefetch files.
+
+use std::path::Path;
+
+use tracing::warn;
+
+use crate::carve::CarvedFile;
+use crate::parsers::prefetch::{Prefetch, decode};
+use crate::pipeline::events::MetadataEvent;
+
+use super::{PostProcessError, PostProcessor, ProcessContext};
+
+/// Records the executable, hash, run count, and run times of each carved
+/// prefetch file in `prefetch_files`, decompressing `MAM` files first.
+/// Truncated files that no longer decompress or parse are skipped.
+pub struct PrefetchFileProcessor;
+
+impl PostProcessor for PrefetchFileProcessor {
+    fn name(&self) -> &str {
+        "prefetch_files"
+    }
+
+    fn process(
+        &self,
+        file: &CarvedFile,
+        path: &Path,
+        ctx: &ProcessContext,
+    ) -> Result<(), PostProcessError> {
+        let data = std::fs::read(path)?;
+        let Some((plain, compressed)) = decode(&data) else {
+            return Ok(());
+        };
+        let Some(prefetch) = Prefetch::parse(&plain) else {
+            return Ok(());
+        };
+        let record = prefetch.to_record(ctx.run_id, compressed, &file.path);
+        if let Err(err) = ctx.meta_tx.send(MetadataEvent::Prefetch(record)) {
+            warn!("metadata channel closed while sending prefetch record: {err}");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::prefetch::testing::{RUN_TIME, mam_bytes, prefetch_bytes};
+
+    #[test]
+    fn records_compressed_prefetch_file() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let data = mam_bytes(&prefetch_bytes(
+            "NOTEPAD.EXE",
+            12,
+            &[RUN_TIME, RUN_TIME - 36_000_000_000],
+        ));
+        let path = dir.path().join("NOTEPAD.EXE-D7B81A4C.pf");
+        std::fs::write(&path, &data).expect("write prefetch");
+        let file = CarvedFile {
+            run_id: "run".to_string(),
+            file_type: "prefetch".to_string(),
+            signature_type: "prefetch".to_string(),
+            path: "prefetch/prefetch_000000000000_NOTEPAD.EXE-D7B81A4C.pf".to_string(),
+            extension: "pf".to_string(),
+            global_start: 0,
+            global_end: data.len() as u64 - 1,
+            size: data.len() as u64,
+            md5: None,
+            sha256: None,
+            validated: true,
+            truncated: false,
+            errors: Vec::new(),
+            pattern_id: None,
+        };
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let tx = tx.into();
+        let ctx = ProcessContext {
+            run_id: "run",
+            run_output_dir: dir.path(),
+            meta_tx: &tx,
+        };
+        PrefetchFileProcessor
+            .process(&file, &path, &ctx)
+            .expect("process");
+        drop(tx);
+
+        let records: Vec<_> = rx
+            .iter()
+            .filter_map(|event| match event {
+                MetadataEvent::Prefetch(record) => Some(record),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(records.len(), 1);
+        let record = &records[0];
+        assert_eq!(record.executable, "NOTEPAD.EXE");
+        assert_eq!(record.prefetch_hash, "D7B81A4C");
+        assert_eq!(record.format_version, 30);
+        assert!(record.compressed);
+        assert_eq!(record.run_count, 12);
+        assert!(record.last_run_utc.is_some());
+        assert!(record.earlier_runs_utc.is_some());
+        assert_eq!(record.source_file, file.path);
+    }
+}
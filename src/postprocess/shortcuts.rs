@@ -0,0 +1,97 @@
+//! Targets of carved Windows shortcuts.
+
+use std::path::Path;
+
+use tracing::warn;
+
+use crate::carve::CarvedFile;
+use crate::parsers::lnk;
+use crate::pipeline::events::MetadataEvent;
+
+use super::{PostProcessError, PostProcessor, ProcessContext};
+
+/// Records the target path, arguments, working directory, target times, and
+/// creating machine of each carved shell link in `shortcuts`. Truncated links
+/// that no longer parse are skipped.
+pub struct ShortcutProcessor;
+
+impl PostProcessor for ShortcutProcessor {
+    fn name(&self) -> &str {
+        "shortcuts"
+    }
+
+    fn process(
+        &self,
+        file: &CarvedFile,
+        path: &Path,
+        ctx: &ProcessContext,
+    ) -> Result<(), PostProcessError> {
+        let data = std::fs::read(path)?;
+        let Ok(link) = lnk::parse(&data) else {
+            return Ok(());
+        };
+        let record = link.to_record(ctx.run_id, &file.path);
+        if let Err(err) = ctx.meta_tx.send(MetadataEvent::Shortcut(record)) {
+            warn!("metadata channel closed while sending shortcut record: {err}");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::lnk::testing::lnk_bytes;
+
+    #[test]
+    fn records_shortcut_target() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let data = lnk_bytes();
+        let path = dir.path().join("cmd.lnk");
+        std::fs::write(&path, &data).expect("write lnk");
+        let file = CarvedFile {
+            run_id: "run".to_string(),
+            file_type: "lnk".to_string(),
+            signature_type: "lnk".to_string(),
+            path: "lnk/lnk_000000000000.lnk".to_string(),
+            extension: "lnk".to_string(),
+            global_start: 0,
+            global_end: data.len() as u64 - 1,
+            size: data.len() as u64,
+            md5: None,
+            sha256: None,
+            validated: true,
+            truncated: false,
+            errors: Vec::new(),
+            pattern_id: None,
+        };
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let tx = tx.into();
+        let ctx = ProcessContext {
+            run_id: "run",
+            run_output_dir: dir.path(),
+            meta_tx: &tx,
+        };
+        ShortcutProcessor
+            .process(&file, &path, &ctx)
+            .expect("process");
+        drop(tx);
+
+        let records: Vec<_> = rx
+            .iter()
+            .filter_map(|event| match event {
+                MetadataEvent::Shortcut(record) => Some(record),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(records.len(), 1);
+        let record = &records[0];
+        assert_eq!(
+            record.target_path.as_deref(),
+            Some(r"C:\Windows\System32\cmd.exe")
+        );
+        assert_eq!(record.arguments.as_deref(), Some("/c whoami"));
+        assert_eq!(record.machine_id.as_deref(), Some("WORKSTATION7"));
+        assert_eq!(record.source_file, file.path);
+    }
+}
@@ -0,0 +1,102 @@
+//! Browser artefact extraction from carved SQLite databases.
+
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use tracing::warn;
+
+use crate::carve::CarvedFile;
+use crate::parsers::{sqlite_db, sqlite_pages};
+use crate::pipeline::events::MetadataEvent;
+
+use super::{PostProcessError, PostProcessor, ProcessContext};
+
+/// Extracts browser history, cookies, and downloads. Parse failures are logged
+/// and counted in the shared SQLite error counter rather than failing the
+/// processor, since one table failing should not hide the others.
+pub struct SqliteBrowserProcessor {
+    enable_page_recovery: bool,
+    errors: Arc<AtomicU64>,
+}
+
+impl SqliteBrowserProcessor {
+    pub fn new(enable_page_recovery: bool, errors: Arc<AtomicU64>) -> Self {
+        Self {
+            enable_page_recovery,
+            errors,
+        }
+    }
+
+    fn fail(&self, what: &str, path: &Path, err: impl std::fmt::Display) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+        warn!("{what} failed for {}: {err}", path.display());
+    }
+}
+
+impl PostProcessor for SqliteBrowserProcessor {
+    fn name(&self) -> &str {
+        "sqlite_browser"
+    }
+
+    fn process(
+        &self,
+        file: &CarvedFile,
+        path: &Path,
+        ctx: &ProcessContext,
+    ) -> Result<(), PostProcessError> {
+        let run_id = ctx.run_id;
+        let rel_path = file.path.as_str();
+
+        // Extract browser history
+        let mut records = match sqlite_db::extract_browser_history(path, run_id, rel_path) {
+            Ok(records) => records,
+            Err(err) => {
+                self.fail("sqlite parse", path, err);
+                Vec::new()
+            }
+        };
+
+        // Try page-level recovery if no records found
+        if records.is_empty() && self.enable_page_recovery {
+            match sqlite_pages::extract_history_from_pages(path, run_id, rel_path) {
+                Ok(mut recovered) => records.append(&mut recovered),
+                Err(err) => self.fail("sqlite page recovery", path, err),
+            }
+        }
+
+        for record in records {
+            if let Err(err) = ctx.meta_tx.send(MetadataEvent::History(record)) {
+                warn!("metadata channel closed while sending history record: {err}");
+                return Ok(());
+            }
+        }
+
+        // Extract browser cookies
+        match sqlite_db::extract_browser_cookies(path, run_id, rel_path) {
+            Ok(records) => {
+                for record in records {
+                    if let Err(err) = ctx.meta_tx.send(MetadataEvent::Cookie(record)) {
+                        warn!("metadata channel closed while sending cookie record: {err}");
+                        return Ok(());
+                    }
+                }
+            }
+            Err(err) => self.fail("sqlite cookie parse", path, err),
+        }
+
+        // Extract browser downloads
+        match sqlite_db::extract_browser_downloads(path, run_id, rel_path) {
+            Ok(records) => {
+                for record in records {
+                    if let Err(err) = ctx.meta_tx.send(MetadataEvent::Download(record)) {
+                        warn!("metadata channel closed while sending download record: {err}");
+                        return Ok(());
+                    }
+                }
+            }
+            Err(err) => self.fail("sqlite download parse", path, err),
+        }
+        Ok(())
+    }
+}
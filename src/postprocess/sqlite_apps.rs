@@ -0,0 +1,154 @@
+//! Application classification and catalog extraction of carved SQLite databases.
+
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use tracing::warn;
+
+use crate::carve::CarvedFile;
+use crate::metadata::ClassificationRecord;
+use crate::parsers::sqlite_apps::{AppCatalog, extract_app_records};
+use crate::pipeline::events::MetadataEvent;
+
+use super::{PostProcessError, PostProcessor, ProcessContext};
+
+/// Classifier name recorded for catalog matches.
+const CLASSIFIER: &str = "sqlite_apps";
+
+/// Labels each carved database with the catalog apps its schema matches (in
+/// `classifications`, score 1) and sends the rows read by their plans as
+/// app records. Databases that fail to open and failing plans are counted in
+/// the shared SQLite error counter, like [`super::SqliteBrowserProcessor`].
+pub struct SqliteAppProcessor {
+    catalog: Arc<AppCatalog>,
+    errors: Arc<AtomicU64>,
+}
+
+impl SqliteAppProcessor {
+    pub fn new(catalog: Arc<AppCatalog>, errors: Arc<AtomicU64>) -> Self {
+        Self { catalog, errors }
+    }
+}
+
+impl PostProcessor for SqliteAppProcessor {
+    fn name(&self) -> &str {
+        "sqlite_apps"
+    }
+
+    fn process(
+        &self,
+        file: &CarvedFile,
+        path: &Path,
+        ctx: &ProcessContext,
+    ) -> Result<(), PostProcessError> {
+        let found = match extract_app_records(path, &self.catalog, ctx.run_id, &file.path) {
+            Ok(found) => found,
+            Err(err) => {
+                self.errors.fetch_add(1, Ordering::Relaxed);
+                warn!("sqlite app matching failed for {}: {err}", path.display());
+                return Ok(());
+            }
+        };
+        for err in &found.errors {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+            warn!("sqlite app plan failed for {}: {err}", path.display());
+        }
+        let classifications = found.apps.into_iter().enumerate().map(|(rank, app)| {
+            MetadataEvent::Classification(ClassificationRecord {
+                run_id: ctx.run_id.to_string(),
+                file_type: file.file_type.clone(),
+                path: file.path.clone(),
+                global_start: file.global_start,
+                classifier: CLASSIFIER.to_string(),
+                label: app,
+                score: 1.0,
+                rank: rank as u32 + 1,
+            })
+        });
+        let events = classifications.chain(found.records.into_iter().map(MetadataEvent::AppRecord));
+        for event in events {
+            if let Err(err) = ctx.meta_tx.send(event) {
+                warn!("metadata channel closed while sending sqlite app record: {err}");
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_and_extracts_android_sms() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("mmssms.db");
+        let conn = rusqlite::Connection::open(&path).expect("conn");
+        conn.execute_batch(
+            "CREATE TABLE threads (_id INTEGER PRIMARY KEY);
+             CREATE TABLE sms (_id INTEGER PRIMARY KEY, thread_id INTEGER, address TEXT,
+                               date INTEGER, type INTEGER, read INTEGER, body TEXT);
+             INSERT INTO sms VALUES (1, 3, '+15550100', 1714558530000, 1, 1, 'see you');",
+        )
+        .expect("schema");
+        drop(conn);
+        let file = CarvedFile {
+            run_id: "run".to_string(),
+            file_type: "sqlite".to_string(),
+            signature_type: "sqlite".to_string(),
+            path: "sqlite/sqlite_000000008000.sqlite".to_string(),
+            extension: "sqlite".to_string(),
+            global_start: 0x8000,
+            global_end: 0x8FFF,
+            size: 0x1000,
+            md5: None,
+            sha256: None,
+            validated: true,
+            truncated: false,
+            errors: Vec::new(),
+            pattern_id: None,
+        };
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let tx = tx.into();
+        let ctx = ProcessContext {
+            run_id: "run",
+            run_output_dir: dir.path(),
+            meta_tx: &tx,
+        };
+        let errors = Arc::new(AtomicU64::new(0));
+        SqliteAppProcessor::new(Arc::new(AppCatalog::builtin()), errors.clone())
+            .process(&file, &path, &ctx)
+            .expect("process");
+        drop(tx);
+        let events: Vec<MetadataEvent> = rx.iter().collect();
+        assert_eq!(events.len(), 2);
+        match &events[0] {
+            MetadataEvent::Classification(record) => {
+                assert_eq!(record.classifier, "sqlite_apps");
+                assert_eq!(record.label, "android_sms");
+                assert_eq!(record.rank, 1);
+                assert_eq!(record.global_start, 0x8000);
+            }
+            _ => panic!("expected classification"),
+        }
+        match &events[1] {
+            MetadataEvent::AppRecord(record) => {
+                assert_eq!(record.app, "android_sms");
+                assert_eq!(record.plan, "sms");
+                assert_eq!(record.row_id, Some(1));
+                assert_eq!(
+                    record.timestamp_utc.map(|t| t.to_string()).as_deref(),
+                    Some("2024-05-01 10:15:30")
+                );
+                let fields: serde_json::Value =
+                    serde_json::from_str(&record.fields).expect("fields");
+                assert_eq!(fields["body"], "see you");
+                assert_eq!(record.source_file, "sqlite/sqlite_000000008000.sqlite");
+            }
+            _ => panic!("expected app record"),
+        }
+        assert_eq!(errors.load(Ordering::Relaxed), 0);
+    }
+}
@@ -0,0 +1,186 @@
+//! Thumbnails extracted from carved Windows thumbnail caches.
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::carve::{CarvedFile, create_output, output_path};
+use crate::parsers::thumbcache::{self, CacheEntry, ThumbnailRecord};
+use crate::pipeline::events::MetadataEvent;
+
+use super::{PostProcessError, PostProcessor, ProcessContext};
+
+/// File type of extracted thumbnails (`carved/thumbnail/`).
+pub const THUMBNAIL_TYPE: &str = "thumbnail";
+
+/// Walks a carved `thumbcache_*.db`, writes the image of every entry that
+/// holds one as a child carved file, and records its cache ID in
+/// `thumbnails`. Entries past the point where the walk stops are not read.
+pub struct ThumbcacheProcessor;
+
+impl PostProcessor for ThumbcacheProcessor {
+    fn name(&self) -> &str {
+        "thumbcache_images"
+    }
+
+    fn process(
+        &self,
+        file: &CarvedFile,
+        path: &Path,
+        ctx: &ProcessContext,
+    ) -> Result<(), PostProcessError> {
+        let mut handle = File::open(path)?;
+        let mut read = |offset: u64, buf: &mut [u8]| -> usize {
+            if handle.seek(SeekFrom::Start(offset)).is_err() {
+                return 0;
+            }
+            let mut filled = 0;
+            while filled < buf.len() {
+                match handle.read(&mut buf[filled..]) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => filled += n,
+                }
+            }
+            filled
+        };
+        let cache = thumbcache::walk(&mut read)
+            .map_err(|_| PostProcessError::Other("not a thumbnail cache".to_string()))?;
+        for entry in cache.entries.iter().filter(|entry| entry.data_size > 0) {
+            let mut data = vec![0u8; entry.data_size as usize];
+            let n = read(entry.data_offset, &mut data);
+            if n == 0 {
+                break;
+            }
+            data.truncate(n);
+            let child = write_thumbnail(file, entry, &data, ctx)?;
+            let record = ThumbnailRecord {
+                run_id: ctx.run_id.to_string(),
+                cache_id: entry.cache_id_hex(),
+                cache_version: cache.header.windows_release().to_string(),
+                cache_type: cache.header.cache_type,
+                entry_offset: entry.offset,
+                width: entry.width,
+                height: entry.height,
+                image_format: child.extension.clone(),
+                data_size: child.size,
+                image_path: child.path.clone(),
+                source_file: file.path.clone(),
+            };
+            if ctx.meta_tx.send(MetadataEvent::File(child)).is_err()
+                || ctx.meta_tx.send(MetadataEvent::Thumbnail(record)).is_err()
+            {
+                warn!("metadata channel closed while sending thumbnails");
+                return Ok(());
+            }
+        }
+        Ok(())
+    }
+}
+
+fn write_thumbnail(
+    file: &CarvedFile,
+    entry: &CacheEntry,
+    data: &[u8],
+    ctx: &ProcessContext,
+) -> Result<CarvedFile, PostProcessError> {
+    let extension = thumbcache::image_extension(data);
+    let global_start = file.global_start + entry.data_offset;
+    let carved_root = ctx.run_output_dir.join("carved");
+    let (full_path, rel_path) = output_path(&carved_root, THUMBNAIL_TYPE, extension, global_start)
+        .map_err(|err| PostProcessError::Other(err.to_string()))?;
+    let mut out = create_output(&full_path)?;
+    out.write_all(data)?;
+    out.flush()?;
+
+    let truncated = data.len() < entry.data_size as usize;
+    Ok(CarvedFile {
+        run_id: ctx.run_id.to_string(),
+        file_type: THUMBNAIL_TYPE.to_string(),
+        signature_type: THUMBNAIL_TYPE.to_string(),
+        path: rel_path,
+        extension: extension.to_string(),
+        global_start,
+        global_end: global_start + data.len() as u64 - 1,
+        size: data.len() as u64,
+        md5: Some(format!("{:x}", md5::compute(data))),
+        sha256: Some(hex::encode(Sha256::digest(data))),
+        validated: !truncated,
+        truncated,
+        errors: if truncated {
+            vec!["cache ended inside the thumbnail".to_string()]
+        } else {
+            Vec::new()
+        },
+        pattern_id: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::thumbcache::testing::{CACHE_IDS, JPEG, PNG, thumbcache_bytes};
+
+    #[test]
+    fn writes_each_thumbnail_with_its_cache_id() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let cache = thumbcache_bytes(32);
+        let path = dir.path().join("thumbcache.db");
+        std::fs::write(&path, &cache).expect("write cache");
+        let file = CarvedFile {
+            run_id: "run".to_string(),
+            file_type: "thumbcache".to_string(),
+            signature_type: "thumbcache".to_string(),
+            path: "thumbcache/thumbcache_000000001000.db".to_string(),
+            extension: "db".to_string(),
+            global_start: 0x1000,
+            global_end: 0x1000 + cache.len() as u64 - 1,
+            size: cache.len() as u64,
+            md5: None,
+            sha256: None,
+            validated: true,
+            truncated: false,
+            errors: Vec::new(),
+            pattern_id: None,
+        };
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let tx = tx.into();
+        let ctx = ProcessContext {
+            run_id: "run",
+            run_output_dir: dir.path(),
+            meta_tx: &tx,
+        };
+        ThumbcacheProcessor
+            .process(&file, &path, &ctx)
+            .expect("process");
+        drop(tx);
+
+        let mut children = Vec::new();
+        let mut records = Vec::new();
+        for event in rx.iter() {
+            match event {
+                MetadataEvent::File(child) => children.push(child),
+                MetadataEvent::Thumbnail(record) => records.push(record),
+                _ => {}
+            }
+        }
+        // The empty middle entry has no image.
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].cache_id, format!("{:016x}", CACHE_IDS[0]));
+        assert_eq!(records[1].cache_id, format!("{:016x}", CACHE_IDS[2]));
+        assert_eq!(records[0].cache_version, "10");
+        assert_eq!((records[0].width, records[0].height), (Some(96), Some(64)));
+        assert_eq!(records[1].image_format, "png");
+
+        for (child, expected) in children.iter().zip([JPEG, PNG]) {
+            assert!(child.validated);
+            let written =
+                std::fs::read(dir.path().join("carved").join(&child.path)).expect("thumbnail");
+            assert_eq!(written, expected);
+        }
+        assert!(children[0].path.starts_with("thumbnail/"));
+        assert_eq!(records[0].image_path, children[0].path);
+    }
+}
@@ -0,0 +1,394 @@
+//! Rebuilt `moov` indexes for carved recordings that were never finalized.
+
+use std::fs::File;
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::carve::{CarvedFile, create_output, output_path};
+use crate::parsers::mp4::{self, VideoRepairRecord, VideoTemplate};
+use crate::parsers::nal::{self, Sample, VideoCodec};
+use crate::pipeline::events::MetadataEvent;
+
+use super::{PostProcessError, PostProcessor, ProcessContext};
+
+/// File type of repaired recordings (`carved/repaired_video/`).
+pub const REPAIRED_TYPE: &str = "repaired_video";
+
+/// Bytes copied per read when writing the repaired file.
+const COPY_BLOCK: usize = 1024 * 1024;
+
+/// Writes a playable copy of each carved MP4 or QuickTime recording that has
+/// an `mdat` but no `moov` box: the frames are found by walking the NAL units
+/// in `mdat`, and the decoder setup comes from the donor file, or without
+/// one from the H.264 parameter sets the recorder wrote with its keyframes.
+/// Only the video track is indexed. Carves that have a `moov` are left alone.
+pub struct VideoRepairProcessor {
+    donor: Option<(VideoTemplate, PathBuf)>,
+    frame_rate: f64,
+}
+
+impl VideoRepairProcessor {
+    /// `frame_rate` sets the frame duration when there is no donor.
+    pub fn new(donor: Option<(VideoTemplate, PathBuf)>, frame_rate: f64) -> Self {
+        Self { donor, frame_rate }
+    }
+}
+
+impl PostProcessor for VideoRepairProcessor {
+    fn name(&self) -> &str {
+        "video_repair"
+    }
+
+    fn process(
+        &self,
+        file: &CarvedFile,
+        path: &Path,
+        ctx: &ProcessContext,
+    ) -> Result<(), PostProcessError> {
+        let mut handle = File::open(path)?;
+        let len = handle.metadata()?.len();
+        let mut read = |offset: u64, buf: &mut [u8]| -> usize {
+            if handle.seek(SeekFrom::Start(offset)).is_err() {
+                return 0;
+            }
+            let mut filled = 0;
+            while filled < buf.len() {
+                match handle.read(&mut buf[filled..]) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => filled += n,
+                }
+            }
+            filled
+        };
+        let boxes = mp4::top_level_boxes(&mut read, len);
+        if boxes.iter().any(|info| &info.kind == b"moov") {
+            return Ok(());
+        }
+        let Some(mdat) = boxes.iter().find(|info| &info.kind == b"mdat").copied() else {
+            return Ok(());
+        };
+        let start = mdat.payload_start();
+        let end = mdat.end(len).min(len);
+
+        let (template, scan) = match &self.donor {
+            Some((template, _)) => {
+                let scan = nal::scan(&mut read, start, end, template.codec, template.length_size);
+                (template.clone(), scan)
+            }
+            None => {
+                let codec = nal::detect_codec(&mut read, start, end).ok_or_else(|| {
+                    PostProcessError::Other("no H.264 or H.265 frames in mdat".to_string())
+                })?;
+                if codec == VideoCodec::H265 {
+                    return Err(PostProcessError::Other(
+                        "H.265 recordings need a donor file".to_string(),
+                    ));
+                }
+                let scan = nal::scan(&mut read, start, end, codec, 4);
+                let template = match (&scan.sps, &scan.pps) {
+                    (Some(sps), Some(pps)) => {
+                        VideoTemplate::from_parameter_sets(sps, pps, self.frame_rate)
+                    }
+                    _ => None,
+                }
+                .ok_or_else(|| {
+                    PostProcessError::Other(
+                        "no usable SPS and PPS in the media data; a donor file is needed"
+                            .to_string(),
+                    )
+                })?;
+                (template, scan)
+            }
+        };
+        if scan.samples.is_empty() {
+            return Err(PostProcessError::Other(format!(
+                "no {} frames in mdat",
+                template.codec.name()
+            )));
+        }
+
+        // The repaired file keeps everything before `mdat`, gets an `mdat`
+        // header with the size of the frames found, and ends with the index.
+        let payload_len = scan.end - start;
+        let header_len: u64 = if payload_len + 8 > u64::from(u32::MAX) {
+            16
+        } else {
+            8
+        };
+        let mut header = Vec::with_capacity(header_len as usize);
+        if header_len == 16 {
+            header.extend_from_slice(&1u32.to_be_bytes());
+            header.extend_from_slice(b"mdat");
+            header.extend_from_slice(&(payload_len + 16).to_be_bytes());
+        } else {
+            header.extend_from_slice(&((payload_len + 8) as u32).to_be_bytes());
+            header.extend_from_slice(b"mdat");
+        }
+        let shifted_start = mdat.offset + header_len;
+        let samples: Vec<Sample> = scan
+            .samples
+            .iter()
+            .map(|sample| Sample {
+                offset: shifted_start + (sample.offset - start),
+                ..*sample
+            })
+            .collect();
+        let moov = mp4::build_moov(&template, &samples);
+
+        let carved_root = ctx.run_output_dir.join("carved");
+        let (full_path, rel_path) = output_path(
+            &carved_root,
+            REPAIRED_TYPE,
+            &file.extension,
+            file.global_start,
+        )
+        .map_err(|err| PostProcessError::Other(err.to_string()))?;
+        let mut out = Hashed::new(BufWriter::new(create_output(&full_path)?));
+        let copied = copy_range(&mut read, 0, mdat.offset, &mut out).and_then(|()| {
+            out.write(&header)?;
+            copy_range(&mut read, start, scan.end, &mut out)
+        });
+        if let Err(err) = copied {
+            let _ = std::fs::remove_file(&full_path);
+            return Err(err);
+        }
+        out.write(&moov)?;
+        let (size, md5, sha256) = out.finish()?;
+
+        let child = CarvedFile {
+            run_id: ctx.run_id.to_string(),
+            file_type: REPAIRED_TYPE.to_string(),
+            signature_type: REPAIRED_TYPE.to_string(),
+            path: rel_path.clone(),
+            extension: file.extension.clone(),
+            global_start: file.global_start,
+            global_end: file.global_start + size - 1,
+            size,
+            md5: Some(md5),
+            sha256: Some(sha256),
+            validated: true,
+            truncated: false,
+            errors: Vec::new(),
+            pattern_id: None,
+        };
+        let record = VideoRepairRecord {
+            run_id: ctx.run_id.to_string(),
+            method: if self.donor.is_some() {
+                "donor"
+            } else {
+                "in_band"
+            }
+            .to_string(),
+            codec: template.codec.name().to_string(),
+            width: template.width,
+            height: template.height,
+            frame_count: samples.len() as u64,
+            keyframe_count: scan.keyframes() as u64,
+            duration_secs: template.duration(samples.len()) as f64 / f64::from(template.timescale),
+            media_bytes: scan.media_bytes(),
+            skipped_bytes: scan.skipped,
+            donor_file: self
+                .donor
+                .as_ref()
+                .map(|(_, donor)| donor.display().to_string()),
+            repaired_path: rel_path,
+            source_file: file.path.clone(),
+        };
+        if ctx.meta_tx.send(MetadataEvent::File(child)).is_err()
+            || ctx
+                .meta_tx
+                .send(MetadataEvent::VideoRepair(record))
+                .is_err()
+        {
+            warn!("metadata channel closed while sending video repairs");
+        }
+        Ok(())
+    }
+}
+
+fn copy_range<W: Write>(
+    read: &mut dyn FnMut(u64, &mut [u8]) -> usize,
+    from: u64,
+    to: u64,
+    out: &mut Hashed<W>,
+) -> Result<(), PostProcessError> {
+    let mut block = vec![0u8; COPY_BLOCK.min((to - from) as usize)];
+    let mut offset = from;
+    while offset < to {
+        let want = ((to - offset) as usize).min(block.len());
+        let n = read(offset, &mut block[..want]);
+        if n == 0 {
+            return Err(PostProcessError::Other(format!(
+                "carved file ended at {offset} while copying"
+            )));
+        }
+        out.write(&block[..n])?;
+        offset += n as u64;
+    }
+    Ok(())
+}
+
+/// A writer that hashes what it writes.
+struct Hashed<W: Write> {
+    inner: W,
+    len: u64,
+    md5: md5::Context,
+    sha256: Sha256,
+}
+
+impl<W: Write> Hashed<W> {
+    fn new(inner: W) -> Self {
+        Self {
+            inner,
+            len: 0,
+            md5: md5::Context::new(),
+            sha256: Sha256::new(),
+        }
+    }
+
+    fn write(&mut self, data: &[u8]) -> std::io::Result<()> {
+        self.inner.write_all(data)?;
+        self.md5.consume(data);
+        self.sha256.update(data);
+        self.len += data.len() as u64;
+        Ok(())
+    }
+
+    fn finish(mut self) -> std::io::Result<(u64, String, String)> {
+        self.inner.flush()?;
+        Ok((
+            self.len,
+            format!("{:x}", self.md5.compute()),
+            hex::encode(self.sha256.finalize()),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::mp4::testing::unfinished;
+    use crate::parsers::nal::testing::h264_media;
+
+    fn run(processor: &VideoRepairProcessor, recording: &[u8]) -> Vec<MetadataEvent> {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("recording.mp4");
+        std::fs::write(&path, recording).expect("write recording");
+        let file = CarvedFile {
+            run_id: "run".to_string(),
+            file_type: "mp4".to_string(),
+            signature_type: "mp4".to_string(),
+            path: "mp4/mp4_000000004000.mp4".to_string(),
+            extension: "mp4".to_string(),
+            global_start: 0x4000,
+            global_end: 0x4000 + recording.len() as u64 - 1,
+            size: recording.len() as u64,
+            md5: None,
+            sha256: None,
+            validated: false,
+            truncated: false,
+            errors: Vec::new(),
+            pattern_id: None,
+        };
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let tx = tx.into();
+        let ctx = ProcessContext {
+            run_id: "run",
+            run_output_dir: dir.path(),
+            meta_tx: &tx,
+        };
+        processor.process(&file, &path, &ctx).expect("process");
+        drop(tx);
+        let events: Vec<MetadataEvent> = rx.iter().collect();
+        for event in &events {
+            if let MetadataEvent::File(child) = event {
+                let repaired =
+                    std::fs::read(dir.path().join("carved").join(&child.path)).expect("repaired");
+                assert_eq!(repaired.len() as u64, child.size);
+                check_repaired(&repaired);
+            }
+        }
+        events
+    }
+
+    /// The repaired file reads back as `ftyp`, `free`, a sized `mdat`, and a
+    /// `moov` whose chunk offsets point at the frames.
+    fn check_repaired(repaired: &[u8]) {
+        let mut read = |offset: u64, buf: &mut [u8]| {
+            let start = (offset as usize).min(repaired.len());
+            let n = buf.len().min(repaired.len() - start);
+            buf[..n].copy_from_slice(&repaired[start..start + n]);
+            n
+        };
+        let boxes = mp4::top_level_boxes(&mut read, repaired.len() as u64);
+        let kinds: Vec<&[u8; 4]> = boxes.iter().map(|info| &info.kind).collect();
+        assert_eq!(kinds, [b"ftyp", b"free", b"mdat", b"moov"]);
+        let moov = &boxes[3];
+        assert_eq!(moov.end(0), repaired.len() as u64);
+        let payload = &repaired[moov.payload_start() as usize..];
+        VideoTemplate::from_moov(payload).expect("rebuilt index");
+        // The first chunk offset is the start of the `mdat` payload.
+        let stco = payload
+            .windows(4)
+            .position(|window| window == b"stco")
+            .expect("stco");
+        let first = u32::from_be_bytes(payload[stco + 12..stco + 16].try_into().unwrap());
+        assert_eq!(u64::from(first), boxes[2].payload_start());
+    }
+
+    #[test]
+    fn rebuilds_index_from_in_band_parameter_sets() {
+        let recording = unfinished(b"isom", &h264_media(30, 10, 4));
+        let events = run(&VideoRepairProcessor::new(None, 30.0), &recording);
+        let record = events
+            .iter()
+            .find_map(|event| match event {
+                MetadataEvent::VideoRepair(record) => Some(record),
+                _ => None,
+            })
+            .expect("repair record");
+        assert_eq!(record.method, "in_band");
+        assert_eq!(record.codec, "h264");
+        assert_eq!((record.width, record.height), (1280, 720));
+        assert_eq!(record.frame_count, 30);
+        assert_eq!(record.keyframe_count, 3);
+        assert!((record.duration_secs - 1.0).abs() < 1e-9);
+        assert_eq!(record.skipped_bytes, 7 * 300);
+        assert!(record.repaired_path.starts_with("repaired_video/"));
+    }
+
+    #[test]
+    fn uses_donor_and_leaves_finalized_files() {
+        let template = VideoTemplate::from_parameter_sets(
+            crate::parsers::nal::testing::SPS_720P,
+            crate::parsers::nal::testing::PPS,
+            25.0,
+        )
+        .expect("template");
+        let donor = VideoRepairProcessor::new(Some((template, PathBuf::from("donor.mp4"))), 30.0);
+        // Parameter sets only in the donor: the media data starts at a slice.
+        let media = h264_media(8, 8, 0);
+        let first_slice = media.len() - 7 * (4 + 122);
+        let recording = unfinished(b"isom", &media[first_slice..]);
+        let events = run(&donor, &recording);
+        let Some(MetadataEvent::VideoRepair(record)) = events.last() else {
+            panic!("no repair record");
+        };
+        assert_eq!(record.method, "donor");
+        assert_eq!(record.frame_count, 7);
+        assert_eq!(record.donor_file.as_deref(), Some("donor.mp4"));
+        assert!((record.duration_secs - 7.0 / 25.0).abs() < 1e-9);
+
+        let mut finalized = recording.clone();
+        finalized.extend_from_slice(&8u32.to_be_bytes());
+        finalized.extend_from_slice(b"moov");
+        // Size the open mdat so the moov after it is reachable.
+        let mdat_at = recording.len() - media[first_slice..].len() - 8;
+        let mdat_len = (media.len() - first_slice + 8) as u32;
+        finalized[mdat_at..mdat_at + 4].copy_from_slice(&mdat_len.to_be_bytes());
+        assert!(run(&donor, &finalized).is_empty());
+    }
+}
@@ -0,0 +1,438 @@
+//! Times of carved files by every clock that dates them.
+//!
+//! One carved file can be dated by several clocks that need not agree: the
+//! camera clock in its EXIF data (no time zone), the NTFS record whose data
+//! starts where the carve does (UTC), and the `Date` header of the cached
+//! HTTP response it was extracted from (server clock, UTC). Candidates are
+//! keyed by evidence offset, so a cached photo carved both as a cache body
+//! and as a JPEG collects all of them. Every candidate is kept with its
+//! source; the consensus is the time most sources agree on within
+//! `clock_tolerance_secs`, a camera clock agreeing at a whole or half hour
+//! offset from UTC, and the confidence is the share of sources that agree.
+//! Files dated by two or more sources are written to
+//! `summaries/file_clocks.json`.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::parsers::browser::BrowserCacheRecord;
+use crate::parsers::exif::PhotoDeviceRecord;
+use crate::parsers::ntfs::NtfsFileRecord;
+
+use super::SUMMARIES_DIR;
+
+/// File name of the file clock summary inside `summaries/`.
+pub const FILE_CLOCKS_SUMMARY_FILE: &str = "file_clocks.json";
+
+/// Largest camera clock offset from UTC, in minutes (UTC+14).
+const MAX_CAMERA_OFFSET_MINUTES: i64 = 14 * 60;
+
+/// Steps of camera clock offsets, in minutes; zones at quarter hours
+/// (Nepal, Chatham) are reported as disagreeing.
+const CAMERA_OFFSET_STEP_MINUTES: i64 = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClockSource {
+    /// Capture time in the photo's EXIF data, by the camera clock.
+    Exif,
+    /// `$STANDARD_INFORMATION` times of the NTFS record of the carve.
+    Ntfs,
+    /// `Date` header of the cached HTTP response.
+    Http,
+}
+
+impl ClockSource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Exif => "exif",
+            Self::Ntfs => "ntfs",
+            Self::Http => "http",
+        }
+    }
+
+    /// Whether the clock's times are UTC; camera clocks have no zone.
+    pub fn is_utc(self) -> bool {
+        !matches!(self, Self::Exif)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimeCandidate {
+    pub source: ClockSource,
+    /// Field of the source's metadata the time comes from.
+    pub field: String,
+    pub time: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClockConsensus {
+    /// UTC unless only the camera clock dates the file.
+    pub time: NaiveDateTime,
+    pub utc: bool,
+    /// Sources with a candidate agreeing with `time`.
+    pub agreeing: Vec<ClockSource>,
+    /// Sources none of whose candidates agree.
+    pub disagreeing: Vec<ClockSource>,
+    /// Agreeing sources over all sources (0-1).
+    pub confidence: f64,
+    /// Camera clock minus UTC when the camera clock agrees at an offset.
+    pub camera_offset_minutes: Option<i64>,
+}
+
+/// Candidates of one carved file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileClock {
+    pub global_start: u64,
+    /// Carved paths starting at `global_start` that brought a candidate.
+    pub paths: Vec<String>,
+    /// By time.
+    pub candidates: Vec<TimeCandidate>,
+    pub consensus: ClockConsensus,
+}
+
+/// Serialized form of `summaries/file_clocks.json`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileClockSummary {
+    pub run_id: String,
+    pub tolerance_secs: u64,
+    /// Carved files dated by at least one clock.
+    pub files_dated: u64,
+    /// Files dated by two or more sources, listed in `files`.
+    pub files_cross_dated: u64,
+    /// Cross-dated files whose sources do not all agree.
+    pub files_in_conflict: u64,
+    /// Lowest confidence first, then by evidence offset.
+    pub files: Vec<FileClock>,
+}
+
+#[derive(Debug, Default, Clone)]
+struct Dated {
+    paths: Vec<String>,
+    candidates: Vec<TimeCandidate>,
+}
+
+/// Time candidates held until the end of the run, by evidence offset.
+#[derive(Debug, Default, Clone)]
+pub struct FileClocks {
+    tolerance_secs: u64,
+    files: BTreeMap<u64, Dated>,
+    /// Evidence offset of each extracted cache body, by carved path.
+    cache_bodies: HashMap<String, u64>,
+}
+
+impl FileClocks {
+    pub fn from_config(cfg: &Config) -> Self {
+        Self {
+            tolerance_secs: cfg.clock_tolerance_secs,
+            ..Default::default()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    pub fn record_photo(&mut self, photo: &PhotoDeviceRecord) {
+        if let Some(time) = photo.taken_at {
+            self.add(
+                photo.global_start,
+                &photo.source_file,
+                ClockSource::Exif,
+                "taken_at",
+                time,
+            );
+        }
+    }
+
+    /// Times of the NTFS record attributed to the carve at `global_start`.
+    pub fn record_ntfs(&mut self, global_start: u64, path: &str, record: &NtfsFileRecord) {
+        for (field, time) in [("created", record.created), ("modified", record.modified)] {
+            if let Some(time) = time {
+                self.add(global_start, path, ClockSource::Ntfs, field, time);
+            }
+        }
+    }
+
+    /// Note where a cache body starts, for the cache record that follows it.
+    pub fn note_cache_body(&mut self, path: &str, global_start: u64) {
+        self.cache_bodies.insert(path.to_string(), global_start);
+    }
+
+    pub fn record_cache(&mut self, record: &BrowserCacheRecord) {
+        let Some(path) = record.body_path.as_deref() else {
+            return;
+        };
+        let Some(&start) = self.cache_bodies.get(path) else {
+            return;
+        };
+        if let Some(time) = record.response_headers.as_deref().and_then(http_date) {
+            self.add(start, path, ClockSource::Http, "date", time);
+        }
+    }
+
+    fn add(
+        &mut self,
+        global_start: u64,
+        path: &str,
+        source: ClockSource,
+        field: &str,
+        time: NaiveDateTime,
+    ) {
+        let dated = self.files.entry(global_start).or_default();
+        if !dated.paths.iter().any(|p| p == path) {
+            dated.paths.push(path.to_string());
+        }
+        let candidate = TimeCandidate {
+            source,
+            field: field.to_string(),
+            time,
+        };
+        if !dated.candidates.contains(&candidate) {
+            dated.candidates.push(candidate);
+        }
+    }
+
+    pub fn summarize(&self, run_id: &str) -> FileClockSummary {
+        let mut files = Vec::new();
+        let mut in_conflict = 0;
+        for (&global_start, dated) in &self.files {
+            let sources: BTreeSet<ClockSource> =
+                dated.candidates.iter().map(|c| c.source).collect();
+            if sources.len() < 2 {
+                continue;
+            }
+            let Some(consensus) = consensus(&dated.candidates, self.tolerance_secs) else {
+                continue;
+            };
+            if !consensus.disagreeing.is_empty() {
+                in_conflict += 1;
+            }
+            let mut candidates = dated.candidates.clone();
+            candidates.sort_by_key(|c| (c.time, c.source));
+            files.push(FileClock {
+                global_start,
+                paths: dated.paths.clone(),
+                candidates,
+                consensus,
+            });
+        }
+        files.sort_by(|a, b| {
+            a.consensus
+                .confidence
+                .total_cmp(&b.consensus.confidence)
+                .then(a.global_start.cmp(&b.global_start))
+        });
+        FileClockSummary {
+            run_id: run_id.to_string(),
+            tolerance_secs: self.tolerance_secs,
+            files_dated: self.files.len() as u64,
+            files_cross_dated: files.len() as u64,
+            files_in_conflict: in_conflict,
+            files,
+        }
+    }
+}
+
+/// The time most sources agree on. UTC candidates agree within
+/// `tolerance_secs` of each other; the camera clock agrees with a UTC time
+/// at a whole or half hour offset. Ties go to agreement without a camera
+/// offset, then to the earlier time.
+pub fn consensus(candidates: &[TimeCandidate], tolerance_secs: u64) -> Option<ClockConsensus> {
+    let sources: BTreeSet<ClockSource> = candidates.iter().map(|c| c.source).collect();
+    let tolerance = tolerance_secs as i64;
+    let utc_anchors: Vec<&TimeCandidate> =
+        candidates.iter().filter(|c| c.source.is_utc()).collect();
+    // Only the camera clock: its own times, in no zone.
+    let anchors = if utc_anchors.is_empty() {
+        candidates.iter().collect()
+    } else {
+        utc_anchors
+    };
+    let step = CAMERA_OFFSET_STEP_MINUTES * 60;
+    let max_offset = MAX_CAMERA_OFFSET_MINUTES * 60;
+    let mut best: Option<(GroupRank, ClockConsensus)> = None;
+    for anchor in anchors {
+        let mut agreeing = BTreeSet::new();
+        // Seconds the camera clock is ahead of the anchor.
+        let mut camera_offset: Option<i64> = None;
+        for candidate in candidates {
+            let diff = (candidate.time - anchor.time).num_seconds();
+            if candidate.source.is_utc() == anchor.source.is_utc() {
+                if diff.abs() <= tolerance {
+                    agreeing.insert(candidate.source);
+                }
+                continue;
+            }
+            let offset =
+                ((diff as f64 / step as f64).round() as i64 * step).clamp(-max_offset, max_offset);
+            if (diff - offset).abs() <= tolerance {
+                agreeing.insert(candidate.source);
+                if camera_offset.is_none_or(|held| offset.abs() < held.abs()) {
+                    camera_offset = Some(offset);
+                }
+            }
+        }
+        let camera_offset = camera_offset.filter(|&offset| offset != 0);
+        let rank = (
+            agreeing.len(),
+            camera_offset.is_none(),
+            std::cmp::Reverse(anchor.time),
+        );
+        if best.as_ref().is_some_and(|(held, _)| *held >= rank) {
+            continue;
+        }
+        let disagreeing = sources.difference(&agreeing).copied().collect();
+        let confidence = agreeing.len() as f64 / sources.len() as f64;
+        let consensus = ClockConsensus {
+            time: anchor.time,
+            utc: anchor.source.is_utc(),
+            agreeing: agreeing.into_iter().collect(),
+            disagreeing,
+            confidence: (confidence * 100.0).round() / 100.0,
+            camera_offset_minutes: camera_offset.map(|offset| offset / 60),
+        };
+        best = Some((rank, consensus));
+    }
+    best.map(|(_, consensus)| consensus)
+}
+
+/// Agreeing sources, agreement without a camera offset, earlier anchor.
+type GroupRank = (usize, bool, std::cmp::Reverse<NaiveDateTime>);
+
+/// `Date` header of CRLF-joined response header lines, as UTC.
+fn http_date(headers: &str) -> Option<NaiveDateTime> {
+    headers.split("\r\n").find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        if !name.trim().eq_ignore_ascii_case("date") {
+            return None;
+        }
+        DateTime::parse_from_rfc2822(value.trim())
+            .ok()
+            .map(|time| time.naive_utc())
+    })
+}
+
+/// Write the summary to `<run_output_dir>/summaries/file_clocks.json`.
+pub fn write_summary(
+    run_output_dir: &Path,
+    summary: &FileClockSummary,
+) -> std::io::Result<PathBuf> {
+    let dir = run_output_dir.join(SUMMARIES_DIR);
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(FILE_CLOCKS_SUMMARY_FILE);
+    let writer = BufWriter::new(File::create(&path)?);
+    serde_json::to_writer_pretty(writer, summary)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(text: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S").expect("time")
+    }
+
+    fn candidate(source: ClockSource, field: &str, time: &str) -> TimeCandidate {
+        TimeCandidate {
+            source,
+            field: field.to_string(),
+            time: at(time),
+        }
+    }
+
+    #[test]
+    fn agrees_across_zones_and_names_the_odd_clock_out() {
+        let candidates = [
+            candidate(ClockSource::Exif, "taken_at", "2024-05-01 12:00:05"),
+            candidate(ClockSource::Ntfs, "created", "2024-06-10 08:00:00"),
+            candidate(ClockSource::Ntfs, "modified", "2024-05-01 10:00:00"),
+            candidate(ClockSource::Http, "date", "2024-03-02 00:00:00"),
+        ];
+        let agreed = consensus(&candidates, 120).expect("consensus");
+        assert_eq!(agreed.time, at("2024-05-01 10:00:00"));
+        assert!(agreed.utc);
+        assert_eq!(agreed.agreeing, [ClockSource::Exif, ClockSource::Ntfs]);
+        assert_eq!(agreed.disagreeing, [ClockSource::Http]);
+        assert_eq!(agreed.confidence, 0.67);
+        assert_eq!(agreed.camera_offset_minutes, Some(120));
+
+        // Clocks agreeing without an offset win a tie; a lone camera clock
+        // keeps its own time.
+        let candidates = [
+            candidate(ClockSource::Exif, "taken_at", "2024-05-01 10:01:00"),
+            candidate(ClockSource::Ntfs, "modified", "2024-05-01 09:00:00"),
+            candidate(ClockSource::Http, "date", "2024-05-01 10:00:00"),
+        ];
+        let agreed = consensus(&candidates, 120).expect("consensus");
+        assert_eq!(agreed.time, at("2024-05-01 10:00:00"));
+        assert_eq!(agreed.camera_offset_minutes, None);
+        assert_eq!(agreed.disagreeing, [ClockSource::Ntfs]);
+        let lone = consensus(&candidates[..1], 120).expect("consensus");
+        assert!(!lone.utc && lone.confidence == 1.0);
+    }
+
+    #[test]
+    fn lists_files_dated_by_several_sources() {
+        let mut clocks = FileClocks {
+            tolerance_secs: 120,
+            ..Default::default()
+        };
+        let photo = |path: &str, global_start, taken: &str| PhotoDeviceRecord {
+            run_id: "run".to_string(),
+            source_file: path.to_string(),
+            global_start,
+            make: None,
+            model: None,
+            serial: None,
+            lens: None,
+            lens_serial: None,
+            taken_at: Some(at(taken)),
+            latitude: None,
+            longitude: None,
+            device_id: None,
+            session_id: None,
+        };
+        clocks.record_photo(&photo("jpeg/a.jpg", 4096, "2024-05-01 12:00:00"));
+        clocks.record_photo(&photo("jpeg/b.jpg", 8192, "2024-05-01 12:00:00"));
+        clocks.note_cache_body("cache_body/a.jpg", 4096);
+        clocks.record_cache(&BrowserCacheRecord {
+            run_id: "run".to_string(),
+            browser: "chrome".to_string(),
+            url: "https://example.com/a.jpg".to_string(),
+            http_status: Some(200),
+            content_type: Some("image/jpeg".to_string()),
+            content_encoding: None,
+            response_headers: Some(
+                "HTTP/1.1 200 OK\r\nDate: Wed, 01 May 2024 20:00:00 GMT".to_string(),
+            ),
+            body_size: 100,
+            body_path: Some("cache_body/a.jpg".to_string()),
+            source_file: "chrome_cache/x".into(),
+        });
+
+        let summary = clocks.summarize("run");
+        assert_eq!(
+            (
+                summary.files_dated,
+                summary.files_cross_dated,
+                summary.files_in_conflict
+            ),
+            (2, 1, 0)
+        );
+        let file = &summary.files[0];
+        assert_eq!(file.global_start, 4096);
+        assert_eq!(file.paths, ["jpeg/a.jpg", "cache_body/a.jpg"]);
+        assert_eq!(file.candidates[1].time, at("2024-05-01 20:00:00"));
+        // A camera set to UTC-8.
+        assert_eq!(file.consensus.confidence, 1.0);
+        assert_eq!(file.consensus.camera_offset_minutes, Some(-480));
+    }
+}
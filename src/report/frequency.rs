@@ -0,0 +1,322 @@
+//! Ranked frequency tables for string artefacts.
+//!
+//! Counts email domains, URL hosts, and phone country calling codes across a run
+//! and renders them as top-N tables in `summaries/artefact_frequencies.json`.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::strings::artifacts::{ArtefactKind, StringArtefact};
+
+use super::SUMMARIES_DIR;
+
+/// File name of the artefact frequency summary inside `summaries/`.
+pub const FREQUENCY_SUMMARY_FILE: &str = "artefact_frequencies.json";
+
+/// Label used for phone numbers without an international prefix.
+const UNKNOWN_COUNTRY_CODE: &str = "unknown";
+
+/// Two-digit ITU-T E.164 country calling codes. Codes starting with 1 or 7 are
+/// single-digit zones; everything not listed here is treated as three digits.
+const TWO_DIGIT_CALLING_CODES: &[&str] = &[
+    "20", "27", "30", "31", "32", "33", "34", "36", "39", "40", "41", "43", "44", "45", "46", "47",
+    "48", "49", "51", "52", "53", "54", "55", "56", "57", "58", "60", "61", "62", "63", "64", "65",
+    "66", "81", "82", "84", "86", "90", "91", "92", "93", "94", "95", "98",
+];
+
+/// Running counters for artefact values seen during a run.
+#[derive(Debug, Default, Clone)]
+pub struct ArtefactFrequencies {
+    email_domains: HashMap<String, u64>,
+    url_hosts: HashMap<String, u64>,
+    phone_country_codes: HashMap<String, u64>,
+}
+
+/// A single ranked value and its occurrence count.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FrequencyEntry {
+    pub value: String,
+    pub count: u64,
+}
+
+/// Ranked table for one artefact dimension.
+#[derive(Debug, Clone, Serialize)]
+pub struct FrequencyTable {
+    /// Total number of artefacts counted.
+    pub total: u64,
+    /// Number of distinct values seen (before top-N truncation).
+    pub distinct: u64,
+    /// Entries sorted by count (descending), then value (ascending).
+    pub top: Vec<FrequencyEntry>,
+}
+
+/// Serialized form of `summaries/artefact_frequencies.json`.
+#[derive(Debug, Clone, Serialize)]
+pub struct FrequencySummary {
+    pub run_id: String,
+    /// Maximum entries per table (0 = unlimited).
+    pub top_n: usize,
+    pub email_domains: FrequencyTable,
+    pub url_hosts: FrequencyTable,
+    pub phone_country_codes: FrequencyTable,
+}
+
+impl ArtefactFrequencies {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a string artefact. Generic strings and network artefacts are ignored.
+    pub fn record(&mut self, artefact: &StringArtefact) {
+        match artefact.artefact_kind {
+            ArtefactKind::Email => {
+                if let Some(domain) = email_domain(&artefact.content) {
+                    *self.email_domains.entry(domain).or_insert(0) += 1;
+                }
+            }
+            ArtefactKind::Url => {
+                if let Some(host) = url_host(&artefact.content) {
+                    *self.url_hosts.entry(host).or_insert(0) += 1;
+                }
+            }
+            ArtefactKind::Phone => {
+                let code = phone_country_code(&artefact.content)
+                    .unwrap_or_else(|| UNKNOWN_COUNTRY_CODE.to_string());
+                *self.phone_country_codes.entry(code).or_insert(0) += 1;
+            }
+            ArtefactKind::GenericString
+            | ArtefactKind::HttpHost
+            | ArtefactKind::DnsQuery
+            | ArtefactKind::Connection
+            | ArtefactKind::SshPublicKey
+            | ArtefactKind::SshPrivateKey
+            | ArtefactKind::SshKnownHost
+            | ArtefactKind::ShellHistory
+            | ArtefactKind::Credential
+            | ArtefactKind::MagnetUri
+            | ArtefactKind::Ed2kLink
+            | ArtefactKind::TorrentInfoHash
+            | ArtefactKind::TorrentName
+            | ArtefactKind::TorrentTracker
+            | ArtefactKind::DhtNode => {}
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.email_domains.is_empty()
+            && self.url_hosts.is_empty()
+            && self.phone_country_codes.is_empty()
+    }
+
+    /// Build ranked tables limited to `top_n` entries each (0 = unlimited).
+    pub fn summarize(&self, run_id: &str, top_n: usize) -> FrequencySummary {
+        FrequencySummary {
+            run_id: run_id.to_string(),
+            top_n,
+            email_domains: rank(&self.email_domains, top_n),
+            url_hosts: rank(&self.url_hosts, top_n),
+            phone_country_codes: rank(&self.phone_country_codes, top_n),
+        }
+    }
+}
+
+/// Write the summary to `<run_output_dir>/summaries/artefact_frequencies.json`.
+pub fn write_summary(
+    run_output_dir: &Path,
+    summary: &FrequencySummary,
+) -> std::io::Result<PathBuf> {
+    let dir = run_output_dir.join(SUMMARIES_DIR);
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(FREQUENCY_SUMMARY_FILE);
+    let writer = BufWriter::new(File::create(&path)?);
+    serde_json::to_writer_pretty(writer, summary)?;
+    Ok(path)
+}
+
+fn rank(counts: &HashMap<String, u64>, top_n: usize) -> FrequencyTable {
+    let mut entries: Vec<FrequencyEntry> = counts
+        .iter()
+        .map(|(value, count)| FrequencyEntry {
+            value: value.clone(),
+            count: *count,
+        })
+        .collect();
+    entries.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.value.cmp(&b.value)));
+    if top_n > 0 {
+        entries.truncate(top_n);
+    }
+    FrequencyTable {
+        total: counts.values().sum(),
+        distinct: counts.len() as u64,
+        top: entries,
+    }
+}
+
+fn email_domain(email: &str) -> Option<String> {
+    let (_, domain) = email.rsplit_once('@')?;
+    let domain = domain.trim_end_matches('.');
+    if domain.is_empty() {
+        return None;
+    }
+    Some(domain.to_ascii_lowercase())
+}
+
+fn url_host(url: &str) -> Option<String> {
+    let rest = match url.find("://") {
+        Some(idx) => &url[idx + 3..],
+        None => url,
+    };
+    let authority_end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+    let authority = &rest[..authority_end];
+    let host_port = authority.rsplit_once('@').map_or(authority, |(_, h)| h);
+    let host = host_port.split(':').next().unwrap_or("");
+    let host = host.trim_end_matches('.');
+    if host.is_empty() {
+        return None;
+    }
+    Some(host.to_ascii_lowercase())
+}
+
+/// Extract the country calling code (e.g. `+44`) from an internationally
+/// formatted number (`+44 ...` or `0044 ...`).
+fn phone_country_code(phone: &str) -> Option<String> {
+    let trimmed = phone.trim_start();
+    let international = if let Some(rest) = trimmed.strip_prefix('+') {
+        rest
+    } else {
+        trimmed.strip_prefix("00")?
+    };
+    let digits: String = international
+        .chars()
+        .filter(|c| c.is_ascii_digit())
+        .take(3)
+        .collect();
+    if digits.is_empty() || digits.starts_with('0') {
+        return None;
+    }
+    let len = if digits.starts_with('1') || digits.starts_with('7') {
+        1
+    } else if digits.len() >= 2 && TWO_DIGIT_CALLING_CODES.contains(&&digits[..2]) {
+        2
+    } else {
+        digits.len().min(3)
+    };
+    Some(format!("+{}", &digits[..len]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn artefact(kind: ArtefactKind, content: &str) -> StringArtefact {
+        StringArtefact {
+            run_id: "run1".to_string(),
+            artefact_kind: kind,
+            content: content.to_string(),
+            encoding: "ascii".to_string(),
+            global_start: 0,
+            global_end: content.len() as u64,
+            source_kind: "string_span".to_string(),
+            source_detail: String::new(),
+        }
+    }
+
+    #[test]
+    fn ranks_email_domains_by_count_then_value() {
+        let mut freq = ArtefactFrequencies::new();
+        for email in [
+            "a@Example.com",
+            "b@example.com",
+            "c@mail.test.org",
+            "d@beta.net",
+            "e@alpha.net",
+        ] {
+            freq.record(&artefact(ArtefactKind::Email, email));
+        }
+        let summary = freq.summarize("run1", 3);
+        let table = summary.email_domains;
+        assert_eq!(table.total, 5);
+        assert_eq!(table.distinct, 4);
+        let values: Vec<(&str, u64)> = table
+            .top
+            .iter()
+            .map(|e| (e.value.as_str(), e.count))
+            .collect();
+        assert_eq!(
+            values,
+            vec![("example.com", 2), ("alpha.net", 1), ("beta.net", 1)]
+        );
+    }
+
+    #[test]
+    fn extracts_url_hosts() {
+        assert_eq!(
+            url_host("https://user:pw@WWW.Example.com:8443/path?q=1").as_deref(),
+            Some("www.example.com")
+        );
+        assert_eq!(
+            url_host("www.example.org/index.html").as_deref(),
+            Some("www.example.org")
+        );
+        assert_eq!(
+            url_host("http://example.net?x=1").as_deref(),
+            Some("example.net")
+        );
+    }
+
+    #[test]
+    fn extracts_phone_country_codes() {
+        assert_eq!(
+            phone_country_code("+1 (415) 555-1234").as_deref(),
+            Some("+1")
+        );
+        assert_eq!(
+            phone_country_code("+44 20 7946 0958").as_deref(),
+            Some("+44")
+        );
+        assert_eq!(
+            phone_country_code("0049 30 1234567").as_deref(),
+            Some("+49")
+        );
+        assert_eq!(
+            phone_country_code("+353 1 234 5678").as_deref(),
+            Some("+353")
+        );
+        assert_eq!(phone_country_code("(415) 555-1234"), None);
+    }
+
+    #[test]
+    fn counts_national_numbers_as_unknown() {
+        let mut freq = ArtefactFrequencies::new();
+        freq.record(&artefact(ArtefactKind::Phone, "(415) 555-1234"));
+        freq.record(&artefact(ArtefactKind::Phone, "+41 44 668 1800"));
+        let summary = freq.summarize("run1", 0);
+        let values: Vec<&str> = summary
+            .phone_country_codes
+            .top
+            .iter()
+            .map(|e| e.value.as_str())
+            .collect();
+        assert_eq!(values, vec!["+41", "unknown"]);
+    }
+
+    #[test]
+    fn writes_summary_file() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let mut freq = ArtefactFrequencies::new();
+        freq.record(&artefact(ArtefactKind::Url, "https://example.com/a"));
+        let path = write_summary(dir.path(), &freq.summarize("run1", 10)).expect("write");
+        assert_eq!(
+            path,
+            dir.path().join(SUMMARIES_DIR).join(FREQUENCY_SUMMARY_FILE)
+        );
+        let value: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(path).expect("read")).expect("json");
+        assert_eq!(value["url_hosts"]["top"][0]["value"], "example.com");
+        assert_eq!(value["url_hosts"]["top"][0]["count"], 1);
+    }
+}
@@ -0,0 +1,26 @@
+//! # Report Module
+//!
+//! Run-level aggregations computed from metadata events and written under
+//! `summaries/` in the run output directory once a run completes.
+
+pub mod clocks;
+pub mod frequency;
+pub mod ntfs;
+pub mod photo_devices;
+
+pub use clocks::{FileClockSummary, FileClocks};
+pub use frequency::{ArtefactFrequencies, FrequencyEntry, FrequencySummary, FrequencyTable};
+pub use ntfs::{NtfsFiles, NtfsSummary};
+pub use photo_devices::{PhotoDeviceSummary, PhotoDevices};
+
+/// Aggregates the metadata thread builds from the events it records.
+#[derive(Debug, Default)]
+pub struct MetadataAggregates {
+    pub frequencies: ArtefactFrequencies,
+    pub photos: PhotoDevices,
+    pub ntfs: NtfsFiles,
+    pub clocks: FileClocks,
+}
+
+/// Directory (relative to the run output directory) holding run summaries.
+pub const SUMMARIES_DIR: &str = "summaries";
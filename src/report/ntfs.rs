@@ -0,0 +1,230 @@
+//! File records of NTFS volumes and the carves they explain.
+//!
+//! With `enable_ntfs_scan`, the MFT of each NTFS volume is read before the
+//! scan starts. Its records, allocated and deleted, are held by the metadata
+//! thread: a carved file starting at the first cluster of a record's data is
+//! attributed to that record's path, and once the channel closes the records
+//! are written to `ntfs_files` metadata. Volume totals go to
+//! `summaries/ntfs.json`.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::carve::CarvedFile;
+use crate::parsers::ntfs::{NtfsFileRecord, NtfsVolumeFiles};
+
+use super::SUMMARIES_DIR;
+
+/// File name of the NTFS summary inside `summaries/`.
+pub const NTFS_SUMMARY_FILE: &str = "ntfs.json";
+
+/// NTFS file records held until the end of the run.
+#[derive(Debug, Default, Clone)]
+pub struct NtfsFiles {
+    volumes: Vec<NtfsVolumeSummary>,
+    records: Vec<NtfsFileRecord>,
+    /// Record index by evidence offset of the first data cluster.
+    by_start: HashMap<u64, usize>,
+    attributed: u64,
+}
+
+/// Serialized form of `summaries/ntfs.json`.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct NtfsSummary {
+    pub run_id: String,
+    pub volumes: Vec<NtfsVolumeSummary>,
+    /// Carved files attributed to a file record.
+    pub files_attributed: u64,
+    /// Scan chunks skipped by `ntfs_unallocated_only`.
+    pub chunks_skipped: u64,
+    pub bytes_skipped: u64,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct NtfsVolumeSummary {
+    /// Evidence offset of the boot sector.
+    pub volume_offset: u64,
+    pub volume_size: u64,
+    pub cluster_size: u64,
+    /// Why the MFT could not be read, if it could not.
+    pub error: Option<String>,
+    /// Named MFT records.
+    pub records: u64,
+    pub in_use: u64,
+    pub deleted: u64,
+    pub directories: u64,
+    /// Bytes in clusters marked in use by `$Bitmap`.
+    pub allocated_bytes: u64,
+}
+
+impl NtfsFiles {
+    /// Hold the records of `volume`.
+    pub fn add_volume(&mut self, run_id: &str, volume: &NtfsVolumeFiles) {
+        let boot = &volume.boot;
+        let first = self.records.len();
+        self.records.extend(volume.records(run_id));
+        for (i, entry) in volume.entries.iter().enumerate() {
+            let Some(start) = entry.first_offset(boot).filter(|_| !entry.directory) else {
+                continue;
+            };
+            // A deleted record whose first cluster was reused gives way to
+            // the file now using it.
+            match self.by_start.get(&start) {
+                Some(&held) if self.records[held].in_use || !entry.in_use => {}
+                _ => {
+                    self.by_start.insert(start, first + i);
+                }
+            }
+        }
+        self.volumes.push(NtfsVolumeSummary {
+            volume_offset: boot.offset,
+            volume_size: boot.total_clusters * boot.cluster_size,
+            cluster_size: boot.cluster_size,
+            error: None,
+            records: volume.entries.len() as u64,
+            in_use: volume.entries.iter().filter(|e| e.in_use).count() as u64,
+            deleted: volume.entries.iter().filter(|e| !e.in_use).count() as u64,
+            directories: volume.entries.iter().filter(|e| e.directory).count() as u64,
+            allocated_bytes: volume.allocated.iter().map(|(s, e)| e - s).sum(),
+        });
+    }
+
+    /// Note a volume whose MFT could not be read.
+    pub fn add_error(&mut self, volume_offset: u64, volume_size: u64, error: String) {
+        self.volumes.push(NtfsVolumeSummary {
+            volume_offset,
+            volume_size,
+            cluster_size: 0,
+            error: Some(error),
+            records: 0,
+            in_use: 0,
+            deleted: 0,
+            directories: 0,
+            allocated_bytes: 0,
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.volumes.is_empty()
+    }
+
+    /// Set the carved path of the record whose data starts where `file` does,
+    /// and return that record.
+    pub fn attribute(&mut self, file: &CarvedFile) -> Option<&NtfsFileRecord> {
+        let &index = self.by_start.get(&file.global_start)?;
+        let record = &mut self.records[index];
+        if record.carved_path.is_none() {
+            record.carved_path = Some(file.path.clone());
+            self.attributed += 1;
+        }
+        Some(record)
+    }
+
+    /// The records, by volume and record number.
+    pub fn records(&self) -> &[NtfsFileRecord] {
+        &self.records
+    }
+
+    pub fn summarize(&self, run_id: &str, chunks_skipped: u64, bytes_skipped: u64) -> NtfsSummary {
+        NtfsSummary {
+            run_id: run_id.to_string(),
+            volumes: self.volumes.clone(),
+            files_attributed: self.attributed,
+            chunks_skipped,
+            bytes_skipped,
+        }
+    }
+}
+
+/// Write the summary to `<run_output_dir>/summaries/ntfs.json`.
+pub fn write_summary(run_output_dir: &Path, summary: &NtfsSummary) -> std::io::Result<PathBuf> {
+    let dir = run_output_dir.join(SUMMARIES_DIR);
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(NTFS_SUMMARY_FILE);
+    let writer = BufWriter::new(File::create(&path)?);
+    serde_json::to_writer_pretty(writer, summary)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evidence::MemorySource;
+    use crate::parsers::ntfs::testing::{CLUSTER, File, volume};
+    use crate::parsers::ntfs::{ROOT_RECORD, read_volume};
+
+    fn carved(path: &str, start: u64) -> CarvedFile {
+        CarvedFile {
+            run_id: "run".to_string(),
+            file_type: "jpeg".to_string(),
+            signature_type: "jpeg".to_string(),
+            path: path.to_string(),
+            extension: "jpg".to_string(),
+            global_start: start,
+            global_end: start + 99,
+            size: 100,
+            md5: None,
+            sha256: None,
+            validated: true,
+            truncated: false,
+            errors: Vec::new(),
+            pattern_id: None,
+        }
+    }
+
+    #[test]
+    fn attributes_carves_to_the_file_using_the_cluster() {
+        let file = |record, name, in_use, lcn| File {
+            record,
+            parent: ROOT_RECORD,
+            name,
+            in_use,
+            clusters: Some((lcn, 1)),
+            size: CLUSTER as u64,
+        };
+        let image = volume(
+            64,
+            &[
+                file(16, "old.jpg", false, 20),
+                file(17, "new.jpg", true, 20),
+                file(18, "gone.jpg", false, 40),
+            ],
+            &[20],
+        );
+        let mut files = NtfsFiles::default();
+        files.add_volume(
+            "run",
+            &read_volume(&MemorySource::new(image), 0).expect("volume"),
+        );
+        let c = CLUSTER as u64;
+        files.attribute(&carved("jpeg/a.jpg", 20 * c));
+        files.attribute(&carved("jpeg/b.jpg", 40 * c));
+        files.attribute(&carved("jpeg/c.jpg", 41 * c));
+
+        let carved: Vec<(&str, Option<&str>)> = files
+            .records()
+            .iter()
+            .filter(|r| r.name.ends_with(".jpg"))
+            .map(|r| (r.name.as_str(), r.carved_path.as_deref()))
+            .collect();
+        assert_eq!(
+            carved,
+            vec![
+                ("old.jpg", None),
+                ("new.jpg", Some("jpeg/a.jpg")),
+                ("gone.jpg", Some("jpeg/b.jpg")),
+            ]
+        );
+        let summary = files.summarize("run", 3, 3 * c);
+        assert_eq!(summary.files_attributed, 2);
+        assert_eq!(
+            (summary.volumes[0].in_use, summary.volumes[0].deleted),
+            (4, 2)
+        );
+        assert_eq!(files.records()[2].data_runs, format!("{}+{c}", 12 * c));
+    }
+}
@@ -0,0 +1,343 @@
+//! Grouping of carved photos by the camera that took them.
+//!
+//! Photos are keyed by make, model, and body serial number; photos without a
+//! serial share a device per make and model, since they cannot be told apart.
+//! Within a device, photos sorted by capture time form one session until the
+//! gap to the next exceeds `photo_session_gap_secs`. Capture times come from the
+//! camera clock, so sessions of devices in different time zones do not line
+//! up. The groups are written to `photo_devices` metadata and summarized in
+//! `summaries/photo_devices.json`.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use chrono::NaiveDateTime;
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::parsers::exif::PhotoDeviceRecord;
+
+use super::SUMMARIES_DIR;
+
+/// File name of the photo device summary inside `summaries/`.
+pub const PHOTO_DEVICES_SUMMARY_FILE: &str = "photo_devices.json";
+
+/// Photos held until the end of the run, when they are grouped.
+#[derive(Debug, Default, Clone)]
+pub struct PhotoDevices {
+    session_gap_secs: u64,
+    photos: Vec<PhotoDeviceRecord>,
+}
+
+/// Serialized form of `summaries/photo_devices.json`.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct PhotoDeviceSummary {
+    pub run_id: String,
+    pub session_gap_secs: u64,
+    /// Photos with a device field or a capture time.
+    pub photos: u64,
+    /// Photos naming no make, model, or serial.
+    pub unattributed: u64,
+    /// Most photos first.
+    pub devices: Vec<DeviceGroup>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct DeviceGroup {
+    pub device_id: String,
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub serial: Option<String>,
+    pub photos: u64,
+    /// Photos without a capture time, in no session.
+    pub undated: u64,
+    pub first_taken: Option<NaiveDateTime>,
+    pub last_taken: Option<NaiveDateTime>,
+    pub lenses: Vec<LensCount>,
+    pub sessions: Vec<SessionGroup>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct LensCount {
+    pub lens: String,
+    pub photos: u64,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct SessionGroup {
+    pub session_id: String,
+    pub photos: u64,
+    pub first_taken: NaiveDateTime,
+    pub last_taken: NaiveDateTime,
+    pub lenses: Vec<String>,
+}
+
+type DeviceKey = (Option<String>, Option<String>, Option<String>);
+
+impl PhotoDevices {
+    pub fn from_config(cfg: &Config) -> Self {
+        Self {
+            session_gap_secs: cfg.photo_session_gap_secs,
+            photos: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, photo: PhotoDeviceRecord) {
+        self.photos.push(photo);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.photos.is_empty()
+    }
+
+    /// Set `device_id` and `session_id` of every photo, ordered by device,
+    /// then capture time.
+    pub fn assign(&mut self) -> &[PhotoDeviceRecord] {
+        let groups = self.groups();
+        let mut ordered = Vec::with_capacity(self.photos.len());
+        let mut photos: Vec<Option<PhotoDeviceRecord>> = self.photos.drain(..).map(Some).collect();
+        for (device, members) in groups.iter().enumerate() {
+            let device_id = format!("device-{}", device + 1);
+            let mut session = 0;
+            let mut last: Option<NaiveDateTime> = None;
+            for &index in members {
+                let Some(mut photo) = photos[index].take() else {
+                    continue;
+                };
+                if let Some(taken) = photo.taken_at {
+                    let gap = last.map(|last| (taken - last).num_seconds());
+                    if gap.is_none_or(|gap| gap > self.session_gap_secs as i64) {
+                        session += 1;
+                    }
+                    last = Some(taken);
+                    photo.session_id = Some(format!("{device_id}/session-{session}"));
+                }
+                photo.device_id = Some(device_id.clone());
+                ordered.push(photo);
+            }
+        }
+        // Unattributed photos last, in arrival order.
+        ordered.extend(photos.into_iter().flatten());
+        self.photos = ordered;
+        &self.photos
+    }
+
+    /// Indexes of the photos of each device, most photos first; within a
+    /// device, dated photos by time, then undated ones.
+    fn groups(&self) -> Vec<Vec<usize>> {
+        let mut by_key: BTreeMap<DeviceKey, Vec<usize>> = BTreeMap::new();
+        for (index, photo) in self.photos.iter().enumerate() {
+            let key = (
+                photo.make.clone(),
+                photo.model.clone(),
+                photo.serial.clone(),
+            );
+            if key != (None, None, None) {
+                by_key.entry(key).or_default().push(index);
+            }
+        }
+        let mut groups: Vec<Vec<usize>> = by_key.into_values().collect();
+        for members in &mut groups {
+            members.sort_by_key(|&index| {
+                let photo = &self.photos[index];
+                (
+                    photo.taken_at.is_none(),
+                    photo.taken_at,
+                    photo.source_file.clone(),
+                )
+            });
+        }
+        // Stable: equal counts keep the key order.
+        groups.sort_by_key(|members| std::cmp::Reverse(members.len()));
+        groups
+    }
+
+    /// Summary of the groups set by [`Self::assign`].
+    pub fn summarize(&self, run_id: &str) -> PhotoDeviceSummary {
+        let mut devices: Vec<DeviceGroup> = Vec::new();
+        let mut unattributed = 0;
+        for photo in &self.photos {
+            let Some(device_id) = &photo.device_id else {
+                unattributed += 1;
+                continue;
+            };
+            if devices.last().is_none_or(|d| &d.device_id != device_id) {
+                devices.push(DeviceGroup {
+                    device_id: device_id.clone(),
+                    make: photo.make.clone(),
+                    model: photo.model.clone(),
+                    serial: photo.serial.clone(),
+                    photos: 0,
+                    undated: 0,
+                    first_taken: None,
+                    last_taken: None,
+                    lenses: Vec::new(),
+                    sessions: Vec::new(),
+                });
+            }
+            let Some(device) = devices.last_mut() else {
+                continue;
+            };
+            device.photos += 1;
+            if let Some(lens) = &photo.lens {
+                match device.lenses.iter_mut().find(|l| &l.lens == lens) {
+                    Some(count) => count.photos += 1,
+                    None => device.lenses.push(LensCount {
+                        lens: lens.clone(),
+                        photos: 1,
+                    }),
+                }
+            }
+            let (Some(taken), Some(session_id)) = (photo.taken_at, &photo.session_id) else {
+                device.undated += 1;
+                continue;
+            };
+            device.first_taken = device.first_taken.or(Some(taken));
+            device.last_taken = Some(taken);
+            if device
+                .sessions
+                .last()
+                .is_none_or(|s| &s.session_id != session_id)
+            {
+                device.sessions.push(SessionGroup {
+                    session_id: session_id.clone(),
+                    photos: 0,
+                    first_taken: taken,
+                    last_taken: taken,
+                    lenses: Vec::new(),
+                });
+            }
+            if let Some(session) = device.sessions.last_mut() {
+                session.photos += 1;
+                session.last_taken = taken;
+                if let Some(lens) = &photo.lens
+                    && !session.lenses.contains(lens)
+                {
+                    session.lenses.push(lens.clone());
+                }
+            }
+        }
+        for device in &mut devices {
+            device
+                .lenses
+                .sort_by(|a, b| b.photos.cmp(&a.photos).then_with(|| a.lens.cmp(&b.lens)));
+        }
+        PhotoDeviceSummary {
+            run_id: run_id.to_string(),
+            session_gap_secs: self.session_gap_secs,
+            photos: self.photos.len() as u64,
+            unattributed,
+            devices,
+        }
+    }
+}
+
+/// Write the summary to `<run_output_dir>/summaries/photo_devices.json`.
+pub fn write_summary(
+    run_output_dir: &Path,
+    summary: &PhotoDeviceSummary,
+) -> std::io::Result<PathBuf> {
+    let dir = run_output_dir.join(SUMMARIES_DIR);
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join(PHOTO_DEVICES_SUMMARY_FILE);
+    let writer = BufWriter::new(File::create(&path)?);
+    serde_json::to_writer_pretty(writer, summary)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn photo(
+        path: &str,
+        model: Option<&str>,
+        serial: Option<&str>,
+        taken: &str,
+    ) -> PhotoDeviceRecord {
+        PhotoDeviceRecord {
+            run_id: "run".to_string(),
+            source_file: path.to_string(),
+            global_start: 0,
+            make: model.map(|_| "Canon".to_string()),
+            model: model.map(str::to_string),
+            serial: serial.map(str::to_string),
+            lens: model.map(|_| "RF50mm F1.8 STM".to_string()),
+            lens_serial: None,
+            taken_at: NaiveDateTime::parse_from_str(taken, "%Y-%m-%d %H:%M:%S").ok(),
+            latitude: None,
+            longitude: None,
+            device_id: None,
+            session_id: None,
+        }
+    }
+
+    #[test]
+    fn groups_by_serial_and_splits_sessions_at_gaps() {
+        let mut photos = PhotoDevices {
+            session_gap_secs: 3600,
+            photos: Vec::new(),
+        };
+        photos.record(photo(
+            "a.jpg",
+            Some("EOS R5"),
+            Some("111"),
+            "2024-05-01 10:00:00",
+        ));
+        photos.record(photo(
+            "b.jpg",
+            Some("EOS R5"),
+            Some("111"),
+            "2024-05-01 10:30:00",
+        ));
+        photos.record(photo(
+            "c.jpg",
+            Some("EOS R5"),
+            Some("111"),
+            "2024-05-01 15:00:00",
+        ));
+        photos.record(photo(
+            "d.jpg",
+            Some("EOS R5"),
+            Some("222"),
+            "2024-05-01 10:10:00",
+        ));
+        photos.record(photo("e.jpg", Some("EOS R5"), Some("111"), ""));
+        photos.record(photo("f.jpg", None, None, "2024-05-01 10:00:00"));
+
+        let assigned: Vec<(String, Option<String>, Option<String>)> = photos
+            .assign()
+            .iter()
+            .map(|p| {
+                (
+                    p.source_file.clone(),
+                    p.device_id.clone(),
+                    p.session_id.clone(),
+                )
+            })
+            .collect();
+        let id = |s: &str| Some(s.to_string());
+        assert_eq!(
+            assigned,
+            vec![
+                ("a.jpg".into(), id("device-1"), id("device-1/session-1")),
+                ("b.jpg".into(), id("device-1"), id("device-1/session-1")),
+                ("c.jpg".into(), id("device-1"), id("device-1/session-2")),
+                ("e.jpg".into(), id("device-1"), None),
+                ("d.jpg".into(), id("device-2"), id("device-2/session-1")),
+                ("f.jpg".into(), None, None),
+            ]
+        );
+
+        let summary = photos.summarize("run");
+        assert_eq!((summary.photos, summary.unattributed), (6, 1));
+        let device = &summary.devices[0];
+        assert_eq!((device.photos, device.undated), (4, 1));
+        assert_eq!(device.sessions.len(), 2);
+        assert_eq!(device.sessions[0].photos, 2);
+        assert_eq!(device.lenses[0].photos, 4);
+        assert_eq!(summary.devices[1].serial.as_deref(), Some("222"));
+    }
+}
@@ -0,0 +1,431 @@
+//! Exclusive lock on a run output directory.
+//!
+//! Two processes writing the same run directory (typically a `--resume-from`
+//! started while the first run is still going) interleave carved files and
+//! metadata in a way that cannot be untangled afterwards. [`RunLock`] claims
+//! the directory by holding an exclusive `flock` on [`LOCK_FILE`], which
+//! records the owner's PID, host, and a heartbeat that a background thread
+//! refreshes every [`HEARTBEAT_INTERVAL`]. The kernel releases the lock when
+//! the owner exits, so two runs can never both hold it, however they race.
+//!
+//! A lock file left behind by a crashed run on the same host (its PID is no
+//! longer running) is taken over with a warning, so resuming after a crash
+//! needs no extra step. A lock file from another host is refused, since file
+//! locks may not reach across hosts on a network file system. Either can be
+//! overridden with `--force-unlock`, which atomically replaces the lock file
+//! with a new locked one; the error names the holder and the age of its last
+//! heartbeat. A holder whose lock file was replaced cancels its run at its
+//! next heartbeat (see [`RunLock::cancel_on_loss`]).
+
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, OnceLock};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::{error, warn};
+
+/// Lock file name inside the run output directory.
+pub const LOCK_FILE: &str = ".swiftbeaver.lock";
+/// How often the holder rewrites `heartbeat_at`.
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Contents of the lock file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockInfo {
+    pub pid: u32,
+    pub host: String,
+    pub run_id: String,
+    pub started_at: String,
+    pub heartbeat_at: String,
+}
+
+impl LockInfo {
+    fn current(run_id: &str) -> Self {
+        let now = Utc::now().to_rfc3339();
+        Self {
+            pid: std::process::id(),
+            host: hostname(),
+            run_id: run_id.to_string(),
+            started_at: now.clone(),
+            heartbeat_at: now,
+        }
+    }
+
+    /// Same process instance, regardless of heartbeat.
+    fn same_owner(&self, other: &LockInfo) -> bool {
+        self.pid == other.pid && self.host == other.host && self.started_at == other.started_at
+    }
+
+    /// Seconds since the last heartbeat, if the timestamp parses.
+    pub fn heartbeat_age_secs(&self) -> Option<i64> {
+        let at = DateTime::parse_from_rfc3339(&self.heartbeat_at).ok()?;
+        Some((Utc::now() - at.with_timezone(&Utc)).num_seconds())
+    }
+
+    fn describe(&self) -> String {
+        let age = self
+            .heartbeat_age_secs()
+            .map(|secs| format!("{secs}s ago"))
+            .unwrap_or_else(|| self.heartbeat_at.clone());
+        format!(
+            "pid {} on {} (run {}, started {}, last heartbeat {age})",
+            self.pid, self.host, self.run_id, self.started_at
+        )
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum RunLockError {
+    #[error(
+        "run directory {} is in use by {}; if that process is gone, re-run with --force-unlock",
+        .dir.display(),
+        .holder.describe()
+    )]
+    Held { dir: PathBuf, holder: Box<LockInfo> },
+    #[error(
+        "lock file {} is unreadable ({reason}); if no other run uses the directory, re-run with --force-unlock",
+        .path.display()
+    )]
+    Unreadable { path: PathBuf, reason: String },
+    #[error("io error: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// Held lock on a run directory; released when dropped.
+#[derive(Debug)]
+pub struct RunLock {
+    path: PathBuf,
+    info: LockInfo,
+    file: Arc<File>,
+    /// Set by [`RunLock::cancel_on_loss`].
+    cancel: Arc<OnceLock<Arc<AtomicBool>>>,
+    stop: Option<Sender<()>>,
+    heartbeat: Option<JoinHandle<()>>,
+}
+
+impl RunLock {
+    /// Claim `run_dir` for `run_id`. With `force`, an existing lock is taken
+    /// over whoever holds it.
+    pub fn acquire(run_dir: &Path, run_id: &str, force: bool) -> Result<Self, RunLockError> {
+        let path = run_dir.join(LOCK_FILE);
+        let info = LockInfo::current(run_id);
+        // A later attempt follows a lock file that was removed or replaced
+        // between opening and locking it.
+        for _ in 0..3 {
+            let file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(&path)?;
+            match file.try_lock() {
+                Ok(()) => {}
+                Err(TryLockError::WouldBlock) if force => {
+                    match read_lock(&path) {
+                        Ok(holder) => warn!(
+                            "--force-unlock: taking over lock held by {}",
+                            holder.describe()
+                        ),
+                        Err(reason) => {
+                            warn!("--force-unlock: taking over unreadable lock file ({reason})")
+                        }
+                    }
+                    match replace_locked(&path, &info)? {
+                        Some(file) => return Ok(Self::start(path, file, info)),
+                        None => continue,
+                    }
+                }
+                Err(TryLockError::WouldBlock) => {
+                    return Err(match read_lock(&path) {
+                        Ok(holder) => RunLockError::Held {
+                            dir: run_dir.to_path_buf(),
+                            holder: Box::new(holder),
+                        },
+                        Err(reason) => RunLockError::Unreadable { path, reason },
+                    });
+                }
+                Err(TryLockError::Error(err)) => return Err(err.into()),
+            }
+            if !same_file(&file, &path) {
+                continue;
+            }
+            // Locked: whoever wrote the file no longer holds it, unless it
+            // runs on another host or never took a file lock.
+            match read_lock(&path) {
+                Err(_) if file.metadata()?.len() == 0 => {}
+                Ok(holder) if force => {
+                    warn!(
+                        "--force-unlock: taking over lock held by {}",
+                        holder.describe()
+                    );
+                }
+                Ok(holder) if holder.host == info.host && !pid_alive(holder.pid) => {
+                    warn!(
+                        "taking over stale lock on {}: {} is no longer running",
+                        run_dir.display(),
+                        holder.describe()
+                    );
+                }
+                Ok(holder) => {
+                    return Err(RunLockError::Held {
+                        dir: run_dir.to_path_buf(),
+                        holder: Box::new(holder),
+                    });
+                }
+                Err(reason) if force => {
+                    warn!("--force-unlock: taking over unreadable lock file ({reason})");
+                }
+                Err(reason) => return Err(RunLockError::Unreadable { path, reason }),
+            }
+            write_lock(&file, &info)?;
+            return Ok(Self::start(path, file, info));
+        }
+        Err(io::Error::other(format!(
+            "lock file {} keeps being replaced by another process",
+            path.display()
+        ))
+        .into())
+    }
+
+    fn start(path: PathBuf, file: File, info: LockInfo) -> Self {
+        let file = Arc::new(file);
+        let cancel = Arc::new(OnceLock::<Arc<AtomicBool>>::new());
+        let (stop, stopped) = mpsc::channel::<()>();
+        let heartbeat = {
+            let path = path.clone();
+            let file = file.clone();
+            let cancel = cancel.clone();
+            let mut info = info.clone();
+            thread::spawn(move || {
+                while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(HEARTBEAT_INTERVAL)
+                {
+                    if !still_held(&file, &path, &info) {
+                        error!(
+                            "run lock {} was removed or taken over; cancelling the run",
+                            path.display()
+                        );
+                        if let Some(flag) = cancel.get() {
+                            flag.store(true, Ordering::Relaxed);
+                        }
+                        return;
+                    }
+                    info.heartbeat_at = Utc::now().to_rfc3339();
+                    if let Err(err) = write_lock(&file, &info) {
+                        warn!("run lock heartbeat failed: {err}");
+                    }
+                }
+            })
+        };
+        Self {
+            path,
+            info,
+            file,
+            cancel,
+            stop: Some(stop),
+            heartbeat: Some(heartbeat),
+        }
+    }
+
+    /// Set `flag` (the run's cancellation flag) when the heartbeat finds the
+    /// lock removed or taken over, so the run stops instead of writing
+    /// alongside the new holder.
+    pub fn cancel_on_loss(&self, flag: Arc<AtomicBool>) {
+        let _ = self.cancel.set(flag);
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn info(&self) -> &LockInfo {
+        &self.info
+    }
+}
+
+impl Drop for RunLock {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(handle) = self.heartbeat.take() {
+            let _ = handle.join();
+        }
+        // Leave a lock that `--force-unlock` handed to another run alone. The
+        // file is removed while still locked; a process that opened it just
+        // before finds it gone once it gets the lock and starts over.
+        if same_file(&self.file, &self.path) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Whether the lock file at `path` is still `file` and names this owner.
+fn still_held(file: &File, path: &Path, info: &LockInfo) -> bool {
+    same_file(file, path) && read_lock(path).is_ok_and(|current| current.same_owner(info))
+}
+
+/// Replace the lock file with a new one that is already locked and holds
+/// `info`, in one rename. `None` when another process replaced it again
+/// before this one could check.
+fn replace_locked(path: &Path, info: &LockInfo) -> io::Result<Option<File>> {
+    let tmp = path.with_extension(format!("lock.{}", info.pid));
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&tmp)?;
+    let prepared = file
+        .try_lock()
+        .map_err(io::Error::from)
+        .and_then(|()| write_lock(&file, info))
+        .and_then(|()| fs::rename(&tmp, path));
+    if let Err(err) = prepared {
+        let _ = fs::remove_file(&tmp);
+        return Err(err);
+    }
+    Ok(same_file(&file, path).then_some(file))
+}
+
+/// Overwrite the lock file in place; its inode carries the file lock.
+fn write_lock(mut file: &File, info: &LockInfo) -> io::Result<()> {
+    let contents = serde_json::to_vec_pretty(info)?;
+    file.seek(SeekFrom::Start(0))?;
+    file.write_all(&contents)?;
+    file.set_len(contents.len() as u64)?;
+    file.flush()
+}
+
+/// Whether `path` still names the open `file`.
+fn same_file(file: &File, path: &Path) -> bool {
+    let (Ok(open), Ok(named)) = (file.metadata(), fs::metadata(path)) else {
+        return false;
+    };
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        open.dev() == named.dev() && open.ino() == named.ino()
+    }
+    #[cfg(not(unix))]
+    {
+        open.len() == named.len() && open.modified().ok() == named.modified().ok()
+    }
+}
+
+fn read_lock(path: &Path) -> Result<LockInfo, String> {
+    let contents = fs::read(path).map_err(|err| err.to_string())?;
+    serde_json::from_slice(&contents).map_err(|err| err.to_string())
+}
+
+pub(crate) fn hostname() -> String {
+    #[cfg(unix)]
+    {
+        let mut buf = [0u8; 256];
+        // SAFETY: the buffer is valid for `buf.len()` bytes.
+        if unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) } == 0 {
+            let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+            return String::from_utf8_lossy(&buf[..len]).into_owned();
+        }
+    }
+    std::env::var("COMPUTERNAME")
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+/// Whether a process with `pid` exists on this host. Assumed true where it
+/// cannot be checked.
+pub(crate) fn pid_alive(pid: u32) -> bool {
+    #[cfg(unix)]
+    {
+        let Ok(pid) = libc::pid_t::try_from(pid) else {
+            return false;
+        };
+        if pid <= 0 {
+            return false;
+        }
+        // SAFETY: signal 0 only checks that the process exists.
+        if unsafe { libc::kill(pid, 0) } == 0 {
+            return true;
+        }
+        io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = pid;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_foreign_lock(dir: &Path, pid: u32, host: &str) {
+        let mut info = LockInfo::current("other");
+        info.pid = pid;
+        info.host = host.to_string();
+        fs::write(dir.join(LOCK_FILE), serde_json::to_vec(&info).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn second_lock_on_a_run_dir_is_refused_until_released() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let lock = RunLock::acquire(dir.path(), "run1", false).expect("first lock");
+        let err = RunLock::acquire(dir.path(), "run1", false).expect_err("second lock");
+        match &err {
+            RunLockError::Held { holder, .. } => assert_eq!(holder.as_ref(), lock.info()),
+            other => panic!("unexpected error {other}"),
+        }
+        assert!(err.to_string().contains("--force-unlock"));
+        drop(lock);
+        assert!(!dir.path().join(LOCK_FILE).exists());
+        RunLock::acquire(dir.path(), "run1", false).expect("lock after release");
+    }
+
+    #[test]
+    fn stale_and_foreign_locks() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        // A crashed run on this host: taken over.
+        write_foreign_lock(dir.path(), i32::MAX as u32, &hostname());
+        let lock = RunLock::acquire(dir.path(), "run1", false).expect("stale lock");
+        assert_eq!(read_lock(lock.path()).unwrap().pid, std::process::id());
+        drop(lock);
+
+        // A run on another host cannot be checked: refused unless forced.
+        write_foreign_lock(dir.path(), 1, "elsewhere");
+        assert!(matches!(
+            RunLock::acquire(dir.path(), "run1", false),
+            Err(RunLockError::Held { .. })
+        ));
+        fs::write(dir.path().join(LOCK_FILE), b"{").unwrap();
+        assert!(matches!(
+            RunLock::acquire(dir.path(), "run1", false),
+            Err(RunLockError::Unreadable { .. })
+        ));
+        let lock = RunLock::acquire(dir.path(), "run1", true).expect("forced lock");
+        assert_eq!(read_lock(lock.path()).unwrap().run_id, "run1");
+    }
+
+    #[test]
+    fn forced_takeover_replaces_a_held_lock() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let first = RunLock::acquire(dir.path(), "run1", false).expect("first lock");
+        let second = RunLock::acquire(dir.path(), "run2", true).expect("forced lock");
+        assert!(!still_held(&first.file, &first.path, &first.info));
+        assert!(still_held(&second.file, &second.path, &second.info));
+
+        // The replaced holder leaves the new lock alone, and it stays held.
+        drop(first);
+        assert!(matches!(
+            RunLock::acquire(dir.path(), "run3", false),
+            Err(RunLockError::Held { holder, .. }) if holder.run_id == "run2"
+        ));
+        drop(second);
+        assert!(!dir.path().join(LOCK_FILE).exists());
+    }
+}
@@ -1,12 +1,21 @@
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::{Result, anyhow};
-use cudarc::driver::{CudaDevice, CudaFunction, CudaSlice, LaunchAsync, LaunchConfig};
-use tracing::warn;
+use cudarc::driver::{
+    CudaDevice, CudaFunction, CudaSlice, CudaStream, DevicePtr, LaunchAsync, LaunchConfig, result,
+    sys,
+};
+use tracing::{info, warn};
 
 use crate::chunk::ScanChunk;
 use crate::config::Config;
+use crate::constants::MIB;
 use crate::scanner::cpu::CpuScanner;
+use crate::scanner::staging::{
+    DeviceScan, GpuTransferCounts, GpuTransferTimes, STAGING_SLOTS, SlotPool, SubChunking,
+    grown_capacity,
+};
 use crate::scanner::{Hit, SignatureScanner};
 
 const KERNEL_SRC: &str = r#"
@@ -60,18 +69,144 @@ struct Pattern {
 }
 
 pub struct CudaScanner {
-    /// Mutex wraps the device to serialize kernel operations for thread safety.
-    /// While CudaDevice is Send+Sync, kernel launches should be serialized.
-    device: Mutex<Arc<CudaDevice>>,
+    device: Arc<CudaDevice>,
+    slots: SlotPool<CudaSlot>,
+    transfer: GpuTransferTimes,
     patterns: Vec<Pattern>,
     pattern_count: u32,
     pattern_bytes: CudaSlice<u8>,
     pattern_offsets: CudaSlice<u32>,
     pattern_lengths: CudaSlice<u32>,
     max_hits_per_chunk: u32,
+    split: SubChunking,
     cpu_fallback: CpuScanner,
 }
 
+/// Page-locked host memory from `cuMemHostAlloc`, so stream copies from it
+/// run as DMA instead of going through a driver bounce buffer.
+struct PinnedBuffer {
+    device: Arc<CudaDevice>,
+    ptr: *mut u8,
+    len: usize,
+}
+
+impl PinnedBuffer {
+    fn alloc(device: &Arc<CudaDevice>, len: usize) -> Result<Self> {
+        device.bind_to_thread()?;
+        let mut ptr = std::ptr::null_mut();
+        unsafe { sys::lib().cuMemHostAlloc(&mut ptr, len, 0).result() }?;
+        Ok(Self {
+            device: device.clone(),
+            ptr: ptr as *mut u8,
+            len,
+        })
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr, self.len) }
+    }
+}
+
+impl Drop for PinnedBuffer {
+    fn drop(&mut self) {
+        if self.device.bind_to_thread().is_ok() {
+            let _ = unsafe { sys::lib().cuMemFreeHost(self.ptr as *mut _) };
+        }
+    }
+}
+
+/// Chunk data buffers of one slot, grown on demand.
+struct CudaStaging {
+    pinned: PinnedBuffer,
+    data: CudaSlice<u8>,
+}
+
+/// Events bracketing the stages of one chunk on a slot's stream.
+const EVENT_COUNT: usize = 5;
+
+/// One staging slot: its own stream and buffers, so two slots run their
+/// uploads and kernels concurrently.
+struct CudaSlot {
+    stream: CudaStream,
+    staging: Option<CudaStaging>,
+    hits: CudaSlice<u32>,
+    hit_patterns: CudaSlice<u32>,
+    count: CudaSlice<u32>,
+    events: [sys::CUevent; EVENT_COUNT],
+}
+
+// SAFETY: the stream, the events, and the pinned buffer belong to the slot;
+// the slot pool hands a slot to one scan worker at a time, and every use
+// binds the device context to the calling thread first.
+unsafe impl Send for CudaSlot {}
+
+impl CudaSlot {
+    fn new(device: &Arc<CudaDevice>, max_hits: usize) -> Result<Self> {
+        let stream = device.fork_default_stream()?;
+        let mut events = [std::ptr::null_mut(); EVENT_COUNT];
+        for event in &mut events {
+            *event = result::event::create(sys::CUevent_flags::CU_EVENT_DEFAULT)?;
+        }
+        Ok(Self {
+            stream,
+            staging: None,
+            hits: device.alloc_zeros(max_hits)?,
+            hit_patterns: device.alloc_zeros(max_hits)?,
+            count: device.alloc_zeros(1)?,
+            events,
+        })
+    }
+
+    /// Make room for a part of `len` bytes, growing to at most `limit`.
+    fn reserve(
+        &mut self,
+        device: &Arc<CudaDevice>,
+        len: usize,
+        limit: usize,
+    ) -> Result<&mut CudaStaging> {
+        let current = self.staging.as_ref().map_or(0, |s| s.pinned.len);
+        if len > current {
+            self.staging = None;
+            let capacity = grown_capacity(current, len, limit);
+            self.staging = Some(CudaStaging {
+                pinned: PinnedBuffer::alloc(device, capacity)?,
+                data: unsafe { device.alloc::<u8>(capacity) }?,
+            });
+        }
+        Ok(self.staging.as_mut().expect("reserved above"))
+    }
+
+    fn record(&self, event: usize) -> Result<()> {
+        unsafe { result::event::record(self.events[event], self.stream.stream) }?;
+        Ok(())
+    }
+
+    fn synchronize(&self) -> Result<()> {
+        unsafe { result::stream::synchronize(self.stream.stream) }?;
+        Ok(())
+    }
+
+    /// Time between two recorded events of this slot.
+    fn elapsed(&self, from: usize, to: usize) -> Duration {
+        unsafe { result::event::elapsed(self.events[from], self.events[to]) }
+            .map(|ms| Duration::from_secs_f64(ms.max(0.0) as f64 / 1000.0))
+            .unwrap_or_default()
+    }
+}
+
+impl Drop for CudaSlot {
+    fn drop(&mut self) {
+        let _ = self.synchronize();
+        for event in self.events {
+            let _ = unsafe { result::event::destroy(event) };
+        }
+    }
+}
+
 impl CudaScanner {
     pub fn new(cfg: &Config) -> Result<Self> {
         let patterns = parse_patterns(cfg)?;
@@ -104,129 +239,112 @@ impl CudaScanner {
             .map_err(|e| anyhow!("CUDA pattern lengths copy failed: {e}"))?;
 
         let max_hits = cfg.gpu_max_hits_per_chunk.min(u32::MAX as usize).max(1) as u32;
+        // Free memory already excludes the pattern buffers; CUDA has no
+        // per-buffer limit below it.
+        let (free_mem, total_mem) =
+            result::mem_get_info().map_err(|e| anyhow!("CUDA memory query failed: {e}"))?;
+        let reserved = STAGING_SLOTS as u64 * (8 * max_hits as u64 + 4);
+        let overlap = patterns.iter().map(|p| p.bytes.len()).max().unwrap_or(1) - 1;
+        let split = SubChunking::for_device(free_mem as u64, u64::MAX, reserved, overlap)?;
+        info!(
+            "CUDA device memory {} MiB free of {} MiB; chunks over {} MiB are scanned in parts",
+            free_mem as u64 / MIB,
+            total_mem as u64 / MIB,
+            split.part_len as u64 / MIB
+        );
+        // Slot streams wait for the pattern uploads on the default stream.
+        let slots = (0..STAGING_SLOTS)
+            .map(|_| CudaSlot::new(&device, max_hits as usize))
+            .collect::<Result<Vec<_>>>()
+            .map_err(|e| anyhow!("CUDA staging slot setup failed: {e}"))?;
 
         Ok(Self {
-            device: Mutex::new(device),
+            device,
+            slots: SlotPool::new(slots),
+            transfer: GpuTransferTimes::new(split),
             patterns,
             pattern_count,
             pattern_bytes,
             pattern_offsets,
             pattern_lengths,
             max_hits_per_chunk: max_hits,
+            split,
             cpu_fallback,
         })
     }
-}
 
-impl SignatureScanner for CudaScanner {
-    fn scan_chunk(&self, chunk: &ScanChunk, data: &[u8]) -> Vec<Hit> {
-        if data.is_empty() {
-            return Vec::new();
-        }
-        if data.len() > u32::MAX as usize {
-            warn!("chunk length exceeds u32::MAX; using cpu fallback");
-            return self.cpu_fallback.scan_chunk(chunk, data);
+    /// Stage `data` in the slot's pinned buffer, upload it on the slot's
+    /// stream, run the kernel, and read back only the hits it found.
+    fn scan_on_slot(
+        &self,
+        slot: &mut CudaSlot,
+        chunk: &ScanChunk,
+        data: &[u8],
+    ) -> Result<DeviceScan> {
+        self.device
+            .bind_to_thread()
+            .map_err(|err| anyhow!("CUDA context bind failed: {err}"))?;
+        // The previous chunk's copies finished before its hits were read.
+        slot.reserve(&self.device, data.len(), self.split.part_len)
+            .map_err(|err| anyhow!("CUDA staging alloc failed: {err}"))?
+            .pinned
+            .as_mut_slice()[..data.len()]
+            .copy_from_slice(data);
+        let staging = slot.staging.as_ref().expect("reserved above");
+        let stream = slot.stream.stream;
+
+        unsafe { result::memset_d8_async(*slot.count.device_ptr(), 0, 4, stream) }
+            .map_err(|err| anyhow!("CUDA count reset failed: {err}"))?;
+        slot.record(0)?;
+        unsafe {
+            result::memcpy_htod_async(
+                *staging.data.device_ptr(),
+                &staging.pinned.as_slice()[..data.len()],
+                stream,
+            )
         }
+        .map_err(|err| anyhow!("CUDA data copy failed: {err}"))?;
+        slot.record(1)?;
 
-        // Acquire device lock for thread-safe GPU operations
-        let device: std::sync::MutexGuard<'_, Arc<CudaDevice>> = match self.device.lock() {
-            Ok(d) => d,
-            Err(err) => {
-                warn!("CUDA device lock poisoned: {err}; using cpu fallback");
-                return self.cpu_fallback.scan_chunk(chunk, data);
-            }
-        };
-
-        let data_len = data.len() as u64;
-
-        // Copy data to device once
-        let data_gpu: CudaSlice<u8> = match device.htod_copy(data.to_vec()) {
-            Ok(buf) => buf,
-            Err(err) => {
-                warn!("CUDA data copy failed: {err}; using cpu fallback");
-                return self.cpu_fallback.scan_chunk(chunk, data);
-            }
-        };
-
-        let hits_gpu: CudaSlice<u32> = match device.alloc_zeros(self.max_hits_per_chunk as usize) {
-            Ok(buf) => buf,
-            Err(err) => {
-                warn!("CUDA hits alloc failed: {err}; using cpu fallback");
-                return self.cpu_fallback.scan_chunk(chunk, data);
-            }
-        };
-        let hit_patterns_gpu: CudaSlice<u32> =
-            match device.alloc_zeros(self.max_hits_per_chunk as usize) {
-                Ok(buf) => buf,
-                Err(err) => {
-                    warn!("CUDA hit pattern alloc failed: {err}; using cpu fallback");
-                    return self.cpu_fallback.scan_chunk(chunk, data);
-                }
-            };
-        let count_gpu: CudaSlice<u32> = match device.alloc_zeros(1) {
-            Ok(buf) => buf,
-            Err(err) => {
-                warn!("CUDA count alloc failed: {err}; using cpu fallback");
-                return self.cpu_fallback.scan_chunk(chunk, data);
-            }
-        };
-
-        // Calculate grid dimensions
         let num_threads = data.len() as u32;
-        let num_blocks = (num_threads + BLOCK_SIZE - 1) / BLOCK_SIZE;
+        let num_blocks = num_threads.div_ceil(BLOCK_SIZE);
         let launch_cfg = LaunchConfig {
             grid_dim: (num_blocks, 1, 1),
             block_dim: (BLOCK_SIZE, 1, 1),
             shared_mem_bytes: 0,
         };
-
-        // Get the kernel function
-        let func: CudaFunction = match device.get_func("scanner", "scan_patterns") {
-            Some(f) => f,
-            None => {
-                warn!("CUDA kernel not found; using cpu fallback");
-                return self.cpu_fallback.scan_chunk(chunk, data);
-            }
-        };
-
-        // Launch kernel
-        let launch_result = unsafe {
-            func.launch(
+        let func: CudaFunction = self
+            .device
+            .get_func("scanner", "scan_patterns")
+            .ok_or_else(|| anyhow!("CUDA kernel not found"))?;
+        unsafe {
+            func.launch_on_stream(
+                &slot.stream,
                 launch_cfg,
                 (
-                    &data_gpu,
-                    data_len,
+                    &staging.data,
+                    data.len() as u64,
                     &self.pattern_bytes,
                     &self.pattern_offsets,
                     &self.pattern_lengths,
                     self.pattern_count,
-                    &hits_gpu,
-                    &hit_patterns_gpu,
-                    &count_gpu,
+                    &slot.hits,
+                    &slot.hit_patterns,
+                    &slot.count,
                     self.max_hits_per_chunk,
                 ),
             )
-        };
-
-        if let Err(err) = launch_result {
-            warn!("CUDA kernel launch failed: {err}; using cpu fallback");
-            return self.cpu_fallback.scan_chunk(chunk, data);
-        }
-
-        // Synchronize
-        if let Err(err) = device.synchronize() {
-            warn!("CUDA synchronize failed: {err}; using cpu fallback");
-            return self.cpu_fallback.scan_chunk(chunk, data);
         }
+        .map_err(|err| anyhow!("CUDA kernel launch failed: {err}"))?;
+        slot.record(2)?;
 
-        // Read back hit count
-        let count_host = match device.dtoh_sync_copy(&count_gpu) {
-            Ok(v) => v,
-            Err(err) => {
-                warn!("CUDA count read failed: {err}; using cpu fallback");
-                return self.cpu_fallback.scan_chunk(chunk, data);
-            }
-        };
+        let mut count_host = [0u32];
+        unsafe { result::memcpy_dtoh_async(&mut count_host, *slot.count.device_ptr(), stream) }
+            .map_err(|err| anyhow!("CUDA count read failed: {err}"))?;
+        slot.record(3)?;
+        slot.synchronize()
+            .map_err(|err| anyhow!("CUDA synchronize failed: {err}"))?;
+        let mut download = slot.elapsed(2, 3);
 
         let mut count = count_host[0] as usize;
         if count > self.max_hits_per_chunk as usize {
@@ -236,40 +354,79 @@ impl SignatureScanner for CudaScanner {
             );
             count = self.max_hits_per_chunk as usize;
         }
-        if count == 0 {
+
+        let mut hits = Vec::with_capacity(count);
+        if count > 0 {
+            let mut hits_host = vec![0u32; count];
+            let mut hit_patterns_host = vec![0u32; count];
+            unsafe {
+                result::memcpy_dtoh_async(&mut hits_host, *slot.hits.device_ptr(), stream).and_then(
+                    |_| {
+                        result::memcpy_dtoh_async(
+                            &mut hit_patterns_host,
+                            *slot.hit_patterns.device_ptr(),
+                            stream,
+                        )
+                    },
+                )
+            }
+            .map_err(|err| anyhow!("CUDA hits read failed: {err}"))?;
+            slot.record(4)?;
+            slot.synchronize()
+                .map_err(|err| anyhow!("CUDA hits read failed: {err}"))?;
+            download += slot.elapsed(3, 4);
+
+            for (offset, pattern_idx) in hits_host.into_iter().zip(hit_patterns_host) {
+                let Some(pattern) = self.patterns.get(pattern_idx as usize) else {
+                    continue;
+                };
+                hits.push(Hit {
+                    chunk_id: chunk.id,
+                    local_offset: offset as u64,
+                    pattern_id: pattern.id.clone(),
+                    file_type_id: pattern.file_type_id.clone(),
+                });
+            }
+        }
+
+        Ok(DeviceScan {
+            hits,
+            upload: slot.elapsed(0, 1),
+            kernel: slot.elapsed(1, 2),
+            download,
+        })
+    }
+}
+
+impl SignatureScanner for CudaScanner {
+    fn scan_chunk(&self, chunk: &ScanChunk, data: &[u8]) -> Vec<Hit> {
+        if data.is_empty() {
             return Vec::new();
         }
 
-        let hits_host: Vec<u32> = match device.dtoh_sync_copy(&hits_gpu) {
-            Ok(v) => v,
-            Err(err) => {
-                warn!("CUDA hits read failed: {err}; using cpu fallback");
-                return self.cpu_fallback.scan_chunk(chunk, data);
+        let mut slot = self.slots.acquire();
+        let overlapped = slot.overlapped();
+        match self
+            .split
+            .scan(data, |part| self.scan_on_slot(&mut slot, chunk, part))
+        {
+            Ok((scan, parts)) => {
+                self.transfer.record(&scan, overlapped, parts);
+                scan.hits
             }
-        };
-        let hit_patterns_host: Vec<u32> = match device.dtoh_sync_copy(&hit_patterns_gpu) {
-            Ok(v) => v,
             Err(err) => {
-                warn!("CUDA hit patterns read failed: {err}; using cpu fallback");
-                return self.cpu_fallback.scan_chunk(chunk, data);
+                warn!("{err}; using cpu fallback");
+                self.cpu_fallback.scan_chunk(chunk, data)
             }
-        };
-
-        let mut hits = Vec::with_capacity(count);
-        for idx in 0..count {
-            let pattern_idx = hit_patterns_host[idx] as usize;
-            let Some(pattern) = self.patterns.get(pattern_idx) else {
-                continue;
-            };
-            hits.push(Hit {
-                chunk_id: chunk.id,
-                local_offset: hits_host[idx] as u64,
-                pattern_id: pattern.id.clone(),
-                file_type_id: pattern.file_type_id.clone(),
-            });
         }
+    }
+
+    fn uses_gpu(&self) -> bool {
+        true
+    }
 
-        hits
+    fn gpu_transfer(&self) -> Option<GpuTransferCounts> {
+        Some(self.transfer.snapshot())
     }
 }
 
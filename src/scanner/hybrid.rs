@@ -0,0 +1,325 @@
+//! Per-chunk GPU/CPU routing under `--gpu`.
+//!
+//! A GPU scanner pays launch and transfer costs per chunk, so small chunks (the
+//! evidence tail, gap re-reads) are faster on the CPU, and while every scan
+//! worker waits on the device the CPU cores sit idle. [`GpuGate`] counts the
+//! chunks queued on or running on the device: a chunk goes to the GPU only if
+//! it has at least `gpu_min_chunk_size` bytes and fewer than
+//! `gpu_max_inflight` chunks are already there, otherwise the CPU scanner
+//! takes it. [`HybridScanner`] and [`HybridStringScanner`] each own a gate for
+//! their device, and the pipeline counts the route of every chunk in
+//! [`BackendCounters`].
+
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+use anyhow::Result;
+use serde::Serialize;
+
+use super::staging::GpuTransferCounts;
+use super::{Hit, ScanRoute, SignatureScanner, cpu::CpuScanner};
+use crate::chunk::ScanChunk;
+use crate::config::Config;
+use crate::strings::{StringMinLens, StringScanner, StringSpan, cpu::CpuStringScanner};
+
+/// Admission control for one GPU device.
+#[derive(Debug)]
+pub struct GpuGate {
+    /// Chunks shorter than this go to the CPU (0 = no minimum).
+    min_chunk: u64,
+    /// Chunks allowed on the device at once (0 = unlimited).
+    max_in_flight: usize,
+    in_flight: AtomicUsize,
+}
+
+impl GpuGate {
+    pub fn new(min_chunk: u64, max_in_flight: usize) -> Self {
+        Self {
+            min_chunk,
+            max_in_flight,
+            in_flight: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn from_config(cfg: &Config) -> Self {
+        Self::new(cfg.gpu_min_chunk_size, cfg.gpu_max_inflight)
+    }
+
+    /// Reserve a device slot for a chunk of `len` bytes, or return the CPU
+    /// route to take instead. The slot is released when dropped.
+    pub fn admit(&self, len: u64) -> Result<GpuSlot<'_>, ScanRoute> {
+        if len < self.min_chunk {
+            return Err(ScanRoute::CpuSmallChunk);
+        }
+        self.in_flight
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+                (self.max_in_flight == 0 || n < self.max_in_flight).then_some(n + 1)
+            })
+            .map(|_| GpuSlot { gate: self })
+            .map_err(|_| ScanRoute::CpuGpuBusy)
+    }
+
+    /// Chunks currently queued on or running on the device.
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Acquire)
+    }
+}
+
+/// A reserved place in a [`GpuGate`].
+pub struct GpuSlot<'a> {
+    gate: &'a GpuGate,
+}
+
+impl Drop for GpuSlot<'_> {
+    fn drop(&mut self) {
+        self.gate.in_flight.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Signature scanner that sends each chunk to the GPU or the CPU.
+pub struct HybridScanner {
+    gpu: Box<dyn SignatureScanner>,
+    cpu: Box<dyn SignatureScanner>,
+    gate: GpuGate,
+}
+
+impl HybridScanner {
+    pub fn new(
+        gpu: Box<dyn SignatureScanner>,
+        cpu: Box<dyn SignatureScanner>,
+        gate: GpuGate,
+    ) -> Self {
+        Self { gpu, cpu, gate }
+    }
+
+    /// Pair a GPU scanner with a CPU scanner for the same config.
+    pub fn with_cpu_fallback(
+        cfg: &Config,
+        gpu: Box<dyn SignatureScanner>,
+    ) -> Result<Box<dyn SignatureScanner>> {
+        let cpu = Box::new(CpuScanner::new(cfg)?);
+        Ok(Box::new(Self::new(gpu, cpu, GpuGate::from_config(cfg))))
+    }
+}
+
+impl SignatureScanner for HybridScanner {
+    fn scan_chunk(&self, chunk: &ScanChunk, data: &[u8]) -> Vec<Hit> {
+        self.scan_chunk_routed(chunk, data).0
+    }
+
+    /// Some chunks run on the GPU; [`scan_chunk_routed`](Self::scan_chunk_routed)
+    /// says which.
+    fn uses_gpu(&self) -> bool {
+        true
+    }
+
+    fn scan_chunk_routed(&self, chunk: &ScanChunk, data: &[u8]) -> (Vec<Hit>, ScanRoute) {
+        match self.gate.admit(data.len() as u64) {
+            Ok(_slot) => (self.gpu.scan_chunk(chunk, data), ScanRoute::Gpu),
+            Err(route) => (self.cpu.scan_chunk(chunk, data), route),
+        }
+    }
+
+    fn gpu_transfer(&self) -> Option<GpuTransferCounts> {
+        self.gpu.gpu_transfer()
+    }
+}
+
+/// String scanner that sends each chunk to the GPU or the CPU.
+pub struct HybridStringScanner {
+    gpu: Box<dyn StringScanner>,
+    cpu: Box<dyn StringScanner>,
+    gate: GpuGate,
+}
+
+impl HybridStringScanner {
+    pub fn new(gpu: Box<dyn StringScanner>, cpu: Box<dyn StringScanner>, gate: GpuGate) -> Self {
+        Self { gpu, cpu, gate }
+    }
+
+    /// Pair a GPU string scanner with a CPU string scanner for the same config.
+    pub fn with_cpu_fallback(cfg: &Config, gpu: Box<dyn StringScanner>) -> Box<dyn StringScanner> {
+        let cpu = Box::new(CpuStringScanner::new(
+            StringMinLens::from_config(cfg),
+            cfg.string_max_len,
+            cfg.string_scan_utf16,
+        ));
+        Box::new(Self::new(gpu, cpu, GpuGate::from_config(cfg)))
+    }
+}
+
+impl StringScanner for HybridStringScanner {
+    fn scan_chunk(&self, chunk: &ScanChunk, data: &[u8]) -> Vec<StringSpan> {
+        self.scan_chunk_routed(chunk, data).0
+    }
+
+    fn uses_gpu(&self) -> bool {
+        true
+    }
+
+    fn scan_chunk_routed(&self, chunk: &ScanChunk, data: &[u8]) -> (Vec<StringSpan>, ScanRoute) {
+        match self.gate.admit(data.len() as u64) {
+            Ok(_slot) => (self.gpu.scan_chunk(chunk, data), ScanRoute::Gpu),
+            Err(route) => (self.cpu.scan_chunk(chunk, data), route),
+        }
+    }
+}
+
+/// Chunks scanned per backend, as reported in `PipelineStats`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct BackendCounts {
+    pub gpu_chunks: u64,
+    /// All CPU chunks, including the fallbacks below.
+    pub cpu_chunks: u64,
+    /// CPU chunks a GPU scanner passed on for being below `gpu_min_chunk_size`.
+    pub small_chunk_fallbacks: u64,
+    /// CPU chunks a GPU scanner passed on because its queue was full.
+    pub gpu_busy_fallbacks: u64,
+}
+
+/// Shared counters behind [`BackendCounts`].
+#[derive(Debug, Default)]
+pub struct BackendCounters {
+    gpu: AtomicU64,
+    cpu: AtomicU64,
+    small_chunk: AtomicU64,
+    gpu_busy: AtomicU64,
+}
+
+impl BackendCounters {
+    pub fn record(&self, route: ScanRoute) {
+        let counter = match route {
+            ScanRoute::Gpu => &self.gpu,
+            ScanRoute::Cpu => &self.cpu,
+            ScanRoute::CpuSmallChunk => {
+                self.small_chunk.fetch_add(1, Ordering::Relaxed);
+                &self.cpu
+            }
+            ScanRoute::CpuGpuBusy => {
+                self.gpu_busy.fetch_add(1, Ordering::Relaxed);
+                &self.cpu
+            }
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> BackendCounts {
+        BackendCounts {
+            gpu_chunks: self.gpu.load(Ordering::Relaxed),
+            cpu_chunks: self.cpu.load(Ordering::Relaxed),
+            small_chunk_fallbacks: self.small_chunk.load(Ordering::Relaxed),
+            gpu_busy_fallbacks: self.gpu_busy.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Barrier};
+    use std::thread;
+
+    /// Stands in for a GPU scanner; blocks on `barrier` so tests control how
+    /// many chunks are on the device.
+    struct FakeGpu {
+        barrier: Option<Arc<Barrier>>,
+    }
+
+    impl SignatureScanner for FakeGpu {
+        fn scan_chunk(&self, chunk: &ScanChunk, _data: &[u8]) -> Vec<Hit> {
+            if let Some(barrier) = &self.barrier {
+                barrier.wait();
+                barrier.wait();
+            }
+            vec![Hit {
+                chunk_id: chunk.id,
+                local_offset: 0,
+                pattern_id: "gpu".to_string(),
+                file_type_id: "gpu".to_string(),
+            }]
+        }
+
+        fn uses_gpu(&self) -> bool {
+            true
+        }
+    }
+
+    struct FakeCpu;
+
+    impl SignatureScanner for FakeCpu {
+        fn scan_chunk(&self, chunk: &ScanChunk, _data: &[u8]) -> Vec<Hit> {
+            vec![Hit {
+                chunk_id: chunk.id,
+                local_offset: 0,
+                pattern_id: "cpu".to_string(),
+                file_type_id: "cpu".to_string(),
+            }]
+        }
+    }
+
+    fn chunk(len: u64) -> ScanChunk {
+        ScanChunk {
+            id: 0,
+            start: 0,
+            length: len,
+            valid_length: len,
+        }
+    }
+
+    #[test]
+    fn small_chunks_go_to_cpu() {
+        let scanner = HybridScanner::new(
+            Box::new(FakeGpu { barrier: None }),
+            Box::new(FakeCpu),
+            GpuGate::new(64, 0),
+        );
+        let (hits, route) = scanner.scan_chunk_routed(&chunk(16), &[0u8; 16]);
+        assert_eq!(route, ScanRoute::CpuSmallChunk);
+        assert_eq!(hits[0].pattern_id, "cpu");
+        let (hits, route) = scanner.scan_chunk_routed(&chunk(64), &[0u8; 64]);
+        assert_eq!(route, ScanRoute::Gpu);
+        assert_eq!(hits[0].pattern_id, "gpu");
+    }
+
+    #[test]
+    fn full_gpu_queue_falls_back_to_cpu() {
+        let barrier = Arc::new(Barrier::new(2));
+        let scanner = Arc::new(HybridScanner::new(
+            Box::new(FakeGpu {
+                barrier: Some(barrier.clone()),
+            }),
+            Box::new(FakeCpu),
+            GpuGate::new(0, 1),
+        ));
+        let busy = {
+            let scanner = scanner.clone();
+            thread::spawn(move || scanner.scan_chunk_routed(&chunk(8), &[0u8; 8]).1)
+        };
+        // The first chunk is now on the device.
+        barrier.wait();
+        assert_eq!(scanner.gate.in_flight(), 1);
+        let (hits, route) = scanner.scan_chunk_routed(&chunk(8), &[0u8; 8]);
+        assert_eq!(route, ScanRoute::CpuGpuBusy);
+        assert_eq!(hits[0].pattern_id, "cpu");
+        barrier.wait();
+        assert_eq!(busy.join().expect("gpu thread"), ScanRoute::Gpu);
+        assert_eq!(scanner.gate.in_flight(), 0);
+
+        let counters = BackendCounters::default();
+        for route in [
+            ScanRoute::Gpu,
+            ScanRoute::CpuGpuBusy,
+            ScanRoute::CpuSmallChunk,
+        ] {
+            counters.record(route);
+        }
+        assert_eq!(
+            counters.snapshot(),
+            BackendCounts {
+                gpu_chunks: 1,
+                cpu_chunks: 2,
+                small_chunk_fallbacks: 1,
+                gpu_busy_fallbacks: 1,
+            }
+        );
+    }
+}
@@ -1,19 +1,25 @@
 pub mod cpu;
 #[cfg(feature = "gpu-cuda")]
 pub mod cuda;
+pub mod hybrid;
 #[cfg(feature = "gpu-opencl")]
 pub mod opencl;
+pub mod staging;
 
 use crate::chunk::ScanChunk;
+use crate::scanner::staging::GpuTransferCounts;
 
+/// A signature match, relative to the scanned chunk.
 #[derive(Debug, Clone)]
 pub struct Hit {
     pub chunk_id: u64,
+    /// Offset of the match from `ScanChunk::start`.
     pub local_offset: u64,
     pub pattern_id: String,
     pub file_type_id: String,
 }
 
+/// A signature match at an absolute evidence offset, as passed to carve handlers.
 #[derive(Debug, Clone)]
 pub struct NormalizedHit {
     pub global_offset: u64,
@@ -21,8 +27,25 @@ pub struct NormalizedHit {
     pub pattern_id: String,
 }
 
+/// Which backend scanned a chunk (see [`hybrid`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ScanRoute {
+    Cpu,
+    Gpu,
+    /// CPU, because the chunk was below `gpu_min_chunk_size`.
+    CpuSmallChunk,
+    /// CPU, because `gpu_max_inflight` chunks were already on the GPU.
+    CpuGpuBusy,
+}
+
 /// Signature scanner for file headers within a scan chunk.
 ///
+/// Part of the stable API (see `docs/api.md`). `data` holds the whole chunk
+/// including its overlap; scanners report every match in `data`, and the
+/// pipeline drops matches at or beyond `chunk.valid_length` because the next
+/// chunk reports them again.
+///
 /// # Example
 /// ```rust
 /// use swiftbeaver::chunk::ScanChunk;
@@ -43,6 +66,30 @@ pub struct NormalizedHit {
 /// ```
 pub trait SignatureScanner: Send + Sync {
     fn scan_chunk(&self, chunk: &ScanChunk, data: &[u8]) -> Vec<Hit>;
+
+    /// Whether `scan_chunk` runs on a GPU; its wall time is then reported as
+    /// GPU time in the resource usage rows.
+    fn uses_gpu(&self) -> bool {
+        false
+    }
+
+    /// Scan a chunk and report the backend that ran it. The default follows
+    /// [`uses_gpu`](Self::uses_gpu); scanners that pick a backend per chunk
+    /// override it.
+    fn scan_chunk_routed(&self, chunk: &ScanChunk, data: &[u8]) -> (Vec<Hit>, ScanRoute) {
+        let route = if self.uses_gpu() {
+            ScanRoute::Gpu
+        } else {
+            ScanRoute::Cpu
+        };
+        (self.scan_chunk(chunk, data), route)
+    }
+
+    /// Upload, kernel, and download time of the chunks scanned on a GPU so
+    /// far; `None` for scanners without a device.
+    fn gpu_transfer(&self) -> Option<GpuTransferCounts> {
+        None
+    }
 }
 
 use crate::config::Config;
@@ -54,14 +101,18 @@ pub fn build_signature_scanner(cfg: &Config, use_gpu: bool) -> Result<Box<dyn Si
         #[cfg(feature = "gpu-opencl")]
         {
             match opencl::OpenClScanner::new(cfg) {
-                Ok(scanner) => return Ok(Box::new(scanner)),
+                Ok(scanner) => {
+                    return hybrid::HybridScanner::with_cpu_fallback(cfg, Box::new(scanner));
+                }
                 Err(err) => warn!("opencl scanner init failed: {err}; falling back to cpu"),
             }
         }
         #[cfg(feature = "gpu-cuda")]
         {
             match cuda::CudaScanner::new(cfg) {
-                Ok(scanner) => return Ok(Box::new(scanner)),
+                Ok(scanner) => {
+                    return hybrid::HybridScanner::with_cpu_fallback(cfg, Box::new(scanner));
+                }
                 Err(err) => warn!("cuda scanner init failed: {err}; falling back to cpu"),
             }
         }
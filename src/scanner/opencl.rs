@@ -1,22 +1,29 @@
 use std::ptr;
-use std::sync::Mutex;
+use std::time::Duration;
 
 use anyhow::{Result, anyhow};
-use opencl3::command_queue::{CL_BLOCKING, CommandQueue};
+use opencl3::command_queue::{CL_BLOCKING, CL_QUEUE_PROFILING_ENABLE, CommandQueue};
 use opencl3::context::Context;
 use opencl3::device::{CL_DEVICE_TYPE_GPU, Device};
+use opencl3::event::Event;
 use opencl3::kernel::Kernel;
 use opencl3::memory::{
-    Buffer, CL_MEM_COPY_HOST_PTR, CL_MEM_READ_ONLY, CL_MEM_READ_WRITE, CL_MEM_WRITE_ONLY, ClMem,
+    Buffer, CL_MAP_WRITE, CL_MEM_ALLOC_HOST_PTR, CL_MEM_COPY_HOST_PTR, CL_MEM_READ_ONLY,
+    CL_MEM_READ_WRITE, CL_MEM_WRITE_ONLY, ClMem,
 };
 use opencl3::platform::get_platforms;
 use opencl3::program::Program;
-use opencl3::types::{cl_uint, cl_ulong};
-use tracing::warn;
+use opencl3::types::{CL_NON_BLOCKING, cl_uint, cl_ulong};
+use tracing::{info, warn};
 
 use crate::chunk::ScanChunk;
 use crate::config::Config;
+use crate::constants::MIB;
 use crate::scanner::cpu::CpuScanner;
+use crate::scanner::staging::{
+    DeviceScan, GpuTransferCounts, GpuTransferTimes, STAGING_SLOTS, SlotPool, SubChunking,
+    grown_capacity,
+};
 use crate::scanner::{Hit, SignatureScanner};
 
 const KERNEL_SRC: &str = r#"
@@ -69,17 +76,122 @@ struct Pattern {
 
 pub struct OpenClScanner {
     context: Context,
-    queue: CommandQueue,
-    kernel: Mutex<Kernel>,
+    slots: SlotPool<ClSlot>,
+    transfer: GpuTransferTimes,
     patterns: Vec<Pattern>,
     pattern_count: u32,
     pattern_bytes: Buffer<u8>,
     pattern_offsets: Buffer<cl_uint>,
     pattern_lengths: Buffer<cl_uint>,
     max_hits_per_chunk: u32,
+    split: SubChunking,
     cpu_fallback: CpuScanner,
 }
 
+/// Pinned host buffer and device buffer for chunk data. The host buffer is
+/// allocated with `CL_MEM_ALLOC_HOST_PTR` and stays mapped for its lifetime,
+/// so writes from it run as DMA without an extra driver-side copy.
+struct ClStaging {
+    capacity: usize,
+    pinned: Buffer<u8>,
+    mapped: *mut u8,
+    data: Buffer<u8>,
+}
+
+/// One staging slot: its own in-order queue and kernel, so two slots run
+/// their uploads and kernels concurrently.
+struct ClSlot {
+    queue: CommandQueue,
+    kernel: Kernel,
+    staging: Option<ClStaging>,
+    hit_offsets: Buffer<cl_uint>,
+    hit_pattern_ids: Buffer<cl_uint>,
+    count: Buffer<cl_uint>,
+}
+
+// SAFETY: `mapped` points into `pinned`, which the slot owns; the slot pool
+// hands a slot to one scan worker at a time.
+unsafe impl Send for ClSlot {}
+
+impl ClSlot {
+    fn new(context: &Context, program: &Program, max_hits: usize) -> Result<Self> {
+        #[allow(deprecated)]
+        let queue = CommandQueue::create_default(context, CL_QUEUE_PROFILING_ENABLE)?;
+        let kernel = Kernel::create(program, "scan_patterns")?;
+        let create = |len: usize, flags| unsafe {
+            Buffer::<cl_uint>::create(context, flags, len, ptr::null_mut())
+        };
+        Ok(Self {
+            queue,
+            kernel,
+            staging: None,
+            hit_offsets: create(max_hits, CL_MEM_WRITE_ONLY)?,
+            hit_pattern_ids: create(max_hits, CL_MEM_WRITE_ONLY)?,
+            count: create(1, CL_MEM_READ_WRITE)?,
+        })
+    }
+
+    /// Make room for a part of `len` bytes, growing to at most `limit`.
+    fn reserve(&mut self, context: &Context, len: usize, limit: usize) -> Result<()> {
+        let current = self.staging.as_ref().map_or(0, |s| s.capacity);
+        if len <= current {
+            return Ok(());
+        }
+        self.release_staging();
+        let capacity = grown_capacity(current, len, limit);
+        let pinned = unsafe {
+            Buffer::<u8>::create(
+                context,
+                CL_MEM_READ_ONLY | CL_MEM_ALLOC_HOST_PTR,
+                capacity,
+                ptr::null_mut(),
+            )
+        }?;
+        let data =
+            unsafe { Buffer::<u8>::create(context, CL_MEM_READ_ONLY, capacity, ptr::null_mut()) }?;
+        let mut mapped = ptr::null_mut();
+        unsafe {
+            self.queue.enqueue_map_buffer(
+                &pinned,
+                CL_BLOCKING,
+                CL_MAP_WRITE,
+                0,
+                capacity,
+                &mut mapped,
+                &[],
+            )
+        }?;
+        self.staging = Some(ClStaging {
+            capacity,
+            pinned,
+            mapped: mapped as *mut u8,
+            data,
+        });
+        Ok(())
+    }
+
+    fn release_staging(&mut self) {
+        if let Some(staging) = self.staging.take() {
+            let unmapped = unsafe {
+                self.queue.enqueue_unmap_mem_object(
+                    staging.pinned.get(),
+                    staging.mapped as *mut _,
+                    &[],
+                )
+            };
+            if unmapped.is_ok() {
+                let _ = self.queue.finish();
+            }
+        }
+    }
+}
+
+impl Drop for ClSlot {
+    fn drop(&mut self) {
+        self.release_staging();
+    }
+}
+
 impl OpenClScanner {
     pub fn new(cfg: &Config) -> Result<Self> {
         let patterns = parse_patterns(cfg)?;
@@ -92,12 +204,9 @@ impl OpenClScanner {
         let (pattern_bytes, pattern_offsets, pattern_lengths) = build_pattern_buffers(&patterns)?;
         let pattern_count = patterns.len() as u32;
 
-        let (_device, context) = select_device(cfg)?;
-        #[allow(deprecated)]
-        let queue = CommandQueue::create_default(&context, 0)?;
+        let (device, context) = select_device(cfg)?;
         let program = Program::create_and_build_from_source(&context, KERNEL_SRC, "")
             .map_err(|err| anyhow!(err))?;
-        let kernel = Kernel::create(&program, "scan_patterns")?;
 
         let pattern_bytes_buffer = unsafe {
             Buffer::<u8>::create(
@@ -128,114 +237,84 @@ impl OpenClScanner {
         .map_err(|err| anyhow!(err))?;
 
         let max_hits = cfg.gpu_max_hits_per_chunk.min(u32::MAX as usize).max(1) as u32;
+        let device_mem = device.global_mem_size().map_err(|err| anyhow!(err))?;
+        let max_alloc = device.max_mem_alloc_size().map_err(|err| anyhow!(err))?;
+        let reserved = pattern_bytes.len() as u64
+            + 8 * pattern_offsets.len() as u64
+            + STAGING_SLOTS as u64 * (8 * max_hits as u64 + 4);
+        let overlap = patterns.iter().map(|p| p.bytes.len()).max().unwrap_or(1) - 1;
+        let split = SubChunking::for_device(device_mem, max_alloc, reserved, overlap)?;
+        info!(
+            "opencl device memory {} MiB, largest buffer {} MiB; chunks over {} MiB are scanned in parts",
+            device_mem / MIB,
+            max_alloc / MIB,
+            split.part_len as u64 / MIB
+        );
+        let slots = (0..STAGING_SLOTS)
+            .map(|_| ClSlot::new(&context, &program, max_hits as usize))
+            .collect::<Result<Vec<_>>>()?;
 
         Ok(Self {
             context,
-            queue,
-            kernel: Mutex::new(kernel),
+            slots: SlotPool::new(slots),
+            transfer: GpuTransferTimes::new(split),
             patterns,
             pattern_count,
             pattern_bytes: pattern_bytes_buffer,
             pattern_offsets: pattern_offsets_buffer,
             pattern_lengths: pattern_lengths_buffer,
             max_hits_per_chunk: max_hits,
+            split,
             cpu_fallback,
         })
     }
-}
 
-impl SignatureScanner for OpenClScanner {
-    fn scan_chunk(&self, chunk: &ScanChunk, data: &[u8]) -> Vec<Hit> {
-        if data.is_empty() {
-            return Vec::new();
-        }
-        if data.len() > u32::MAX as usize {
-            warn!("chunk length exceeds u32::MAX; using cpu fallback");
-            return self.cpu_fallback.scan_chunk(chunk, data);
-        }
-        if self.patterns.is_empty() {
-            return Vec::new();
+    /// Stage `data` in the slot's pinned buffer, upload it, run the kernel,
+    /// and read back only the hits it found.
+    fn scan_on_slot(
+        &self,
+        slot: &mut ClSlot,
+        chunk: &ScanChunk,
+        data: &[u8],
+    ) -> Result<DeviceScan> {
+        slot.reserve(&self.context, data.len(), self.split.part_len)
+            .map_err(|err| anyhow!("opencl staging buffer create failed: {err}"))?;
+        let ClSlot {
+            queue,
+            kernel,
+            staging,
+            hit_offsets,
+            hit_pattern_ids,
+            count,
+        } = slot;
+        let staging = staging.as_mut().expect("reserved above");
+        // The previous chunk's upload finished before its hits were read.
+        let pinned = unsafe { std::slice::from_raw_parts_mut(staging.mapped, staging.capacity) };
+        pinned[..data.len()].copy_from_slice(data);
+
+        unsafe { queue.enqueue_fill_buffer(count, &[0 as cl_uint], 0, 4, &[]) }
+            .map_err(|err| anyhow!("opencl count reset failed: {err}"))?;
+        let upload = unsafe {
+            queue.enqueue_write_buffer(
+                &mut staging.data,
+                CL_NON_BLOCKING,
+                0,
+                &pinned[..data.len()],
+                &[],
+            )
         }
+        .map_err(|err| anyhow!("opencl data upload failed: {err}"))?;
 
         let data_len = data.len() as cl_ulong;
-
-        let data_buffer = match unsafe {
-            Buffer::<u8>::create(
-                &self.context,
-                CL_MEM_READ_ONLY | CL_MEM_COPY_HOST_PTR,
-                data.len(),
-                data.as_ptr() as *mut _,
-            )
-        } {
-            Ok(buf) => buf,
-            Err(err) => {
-                warn!("opencl data buffer create failed: {err}; using cpu fallback");
-                return self.cpu_fallback.scan_chunk(chunk, data);
-            }
-        };
-
-        let hits_buffer = match unsafe {
-            Buffer::<cl_uint>::create(
-                &self.context,
-                CL_MEM_WRITE_ONLY,
-                self.max_hits_per_chunk as usize,
-                ptr::null_mut(),
-            )
-        } {
-            Ok(buf) => buf,
-            Err(err) => {
-                warn!("opencl hits buffer create failed: {err}; using cpu fallback");
-                return self.cpu_fallback.scan_chunk(chunk, data);
-            }
-        };
-        let pattern_ids_buffer = match unsafe {
-            Buffer::<cl_uint>::create(
-                &self.context,
-                CL_MEM_WRITE_ONLY,
-                self.max_hits_per_chunk as usize,
-                ptr::null_mut(),
-            )
-        } {
-            Ok(buf) => buf,
-            Err(err) => {
-                warn!("opencl hit pattern buffer create failed: {err}; using cpu fallback");
-                return self.cpu_fallback.scan_chunk(chunk, data);
-            }
-        };
-
-        let mut zero = [0u32];
-        let count_buffer = match unsafe {
-            Buffer::<cl_uint>::create(
-                &self.context,
-                CL_MEM_READ_WRITE | CL_MEM_COPY_HOST_PTR,
-                1,
-                zero.as_mut_ptr() as *mut _,
-            )
-        } {
-            Ok(buf) => buf,
-            Err(err) => {
-                warn!("opencl count buffer create failed: {err}; using cpu fallback");
-                return self.cpu_fallback.scan_chunk(chunk, data);
-            }
-        };
-
-        let kernel = match self.kernel.lock() {
-            Ok(guard) => guard,
-            Err(poisoned) => poisoned.into_inner(),
-        };
-
-        let data_mem = data_buffer.get();
+        let data_mem = staging.data.get();
         let patterns_mem = self.pattern_bytes.get();
         let offsets_mem = self.pattern_offsets.get();
         let lengths_mem = self.pattern_lengths.get();
-        let hits_mem = hits_buffer.get();
-        let pattern_ids_mem = pattern_ids_buffer.get();
-        let count_mem = count_buffer.get();
-
-        if let Err(err) = unsafe { kernel.set_arg(0, &data_mem) } {
-            warn!("opencl kernel arg error: {err}; using cpu fallback");
-            return self.cpu_fallback.scan_chunk(chunk, data);
-        }
+        let hits_mem = hit_offsets.get();
+        let pattern_ids_mem = hit_pattern_ids.get();
+        let count_mem = count.get();
+        unsafe { kernel.set_arg(0, &data_mem) }
+            .map_err(|err| anyhow!("opencl kernel arg error: {err}"))?;
         let _ = unsafe { kernel.set_arg(1, &data_len) };
         let _ = unsafe { kernel.set_arg(2, &patterns_mem) };
         let _ = unsafe { kernel.set_arg(3, &offsets_mem) };
@@ -246,9 +325,9 @@ impl SignatureScanner for OpenClScanner {
         let _ = unsafe { kernel.set_arg(8, &count_mem) };
         let _ = unsafe { kernel.set_arg(9, &self.max_hits_per_chunk) };
 
-        let global_work_size = [data.len() as usize];
-        if let Err(err) = unsafe {
-            self.queue.enqueue_nd_range_kernel(
+        let global_work_size = [data.len()];
+        let scan = unsafe {
+            queue.enqueue_nd_range_kernel(
                 kernel.get(),
                 1,
                 ptr::null(),
@@ -256,25 +335,16 @@ impl SignatureScanner for OpenClScanner {
                 ptr::null(),
                 &[],
             )
-        } {
-            warn!("opencl kernel launch failed: {err}; using cpu fallback");
-            return self.cpu_fallback.scan_chunk(chunk, data);
-        }
-
-        if let Err(err) = self.queue.finish() {
-            warn!("opencl queue finish failed: {err}; using cpu fallback");
-            return self.cpu_fallback.scan_chunk(chunk, data);
         }
+        .map_err(|err| anyhow!("opencl kernel launch failed: {err}"))?;
 
-        if let Err(err) = unsafe {
-            self.queue
-                .enqueue_read_buffer(&count_buffer, CL_BLOCKING, 0, &mut zero, &[])
-        } {
-            warn!("opencl read count failed: {err}; using cpu fallback");
-            return self.cpu_fallback.scan_chunk(chunk, data);
-        }
+        let mut count_host = [0 as cl_uint];
+        let count_read =
+            unsafe { queue.enqueue_read_buffer(count, CL_BLOCKING, 0, &mut count_host, &[]) }
+                .map_err(|err| anyhow!("opencl read count failed: {err}"))?;
+        let mut download = event_time(&count_read);
 
-        let mut count = zero[0] as usize;
+        let mut count = count_host[0] as usize;
         if count > self.max_hits_per_chunk as usize {
             warn!(
                 "opencl hits overflow: count={} max={}",
@@ -283,47 +353,91 @@ impl SignatureScanner for OpenClScanner {
             count = self.max_hits_per_chunk as usize;
         }
 
-        if count == 0 {
-            return Vec::new();
+        let mut hits = Vec::with_capacity(count);
+        if count > 0 {
+            let mut offsets_host = vec![0 as cl_uint; count];
+            let mut pattern_ids_host = vec![0 as cl_uint; count];
+            let offsets_read = unsafe {
+                queue.enqueue_read_buffer(hit_offsets, CL_NON_BLOCKING, 0, &mut offsets_host, &[])
+            }
+            .map_err(|err| anyhow!("opencl read hits failed: {err}"))?;
+            let ids_read = unsafe {
+                queue.enqueue_read_buffer(
+                    hit_pattern_ids,
+                    CL_BLOCKING,
+                    0,
+                    &mut pattern_ids_host,
+                    &[],
+                )
+            }
+            .map_err(|err| anyhow!("opencl read hit patterns failed: {err}"))?;
+            download += event_time(&offsets_read) + event_time(&ids_read);
+
+            for (offset, pattern_idx) in offsets_host.into_iter().zip(pattern_ids_host) {
+                let Some(pattern) = self.patterns.get(pattern_idx as usize) else {
+                    continue;
+                };
+                hits.push(Hit {
+                    chunk_id: chunk.id,
+                    local_offset: offset as u64,
+                    pattern_id: pattern.id.clone(),
+                    file_type_id: pattern.file_type_id.clone(),
+                });
+            }
         }
 
-        let mut hit_offsets = vec![0u32; self.max_hits_per_chunk as usize];
-        if let Err(err) = unsafe {
-            self.queue
-                .enqueue_read_buffer(&hits_buffer, CL_BLOCKING, 0, &mut hit_offsets, &[])
-        } {
-            warn!("opencl read hits failed: {err}; using cpu fallback");
-            return self.cpu_fallback.scan_chunk(chunk, data);
+        Ok(DeviceScan {
+            hits,
+            upload: event_time(&upload),
+            kernel: event_time(&scan),
+            download,
+        })
+    }
+}
+
+/// Device time of a completed command, from queue profiling.
+fn event_time(event: &Event) -> Duration {
+    match (
+        event.profiling_command_start(),
+        event.profiling_command_end(),
+    ) {
+        (Ok(start), Ok(end)) => Duration::from_nanos(end.saturating_sub(start)),
+        _ => Duration::ZERO,
+    }
+}
+
+impl SignatureScanner for OpenClScanner {
+    fn scan_chunk(&self, chunk: &ScanChunk, data: &[u8]) -> Vec<Hit> {
+        if data.is_empty() {
+            return Vec::new();
         }
-        let mut hit_pattern_ids = vec![0u32; self.max_hits_per_chunk as usize];
-        if let Err(err) = unsafe {
-            self.queue.enqueue_read_buffer(
-                &pattern_ids_buffer,
-                CL_BLOCKING,
-                0,
-                &mut hit_pattern_ids,
-                &[],
-            )
-        } {
-            warn!("opencl read hit patterns failed: {err}; using cpu fallback");
-            return self.cpu_fallback.scan_chunk(chunk, data);
+        if self.patterns.is_empty() {
+            return Vec::new();
         }
 
-        let mut hits = Vec::with_capacity(count);
-        for idx in 0..count {
-            let pattern_idx = hit_pattern_ids[idx] as usize;
-            let Some(pattern) = self.patterns.get(pattern_idx) else {
-                continue;
-            };
-            hits.push(Hit {
-                chunk_id: chunk.id,
-                local_offset: hit_offsets[idx] as u64,
-                pattern_id: pattern.id.clone(),
-                file_type_id: pattern.file_type_id.clone(),
-            });
+        let mut slot = self.slots.acquire();
+        let overlapped = slot.overlapped();
+        match self
+            .split
+            .scan(data, |part| self.scan_on_slot(&mut slot, chunk, part))
+        {
+            Ok((scan, parts)) => {
+                self.transfer.record(&scan, overlapped, parts);
+                scan.hits
+            }
+            Err(err) => {
+                warn!("{err}; using cpu fallback");
+                self.cpu_fallback.scan_chunk(chunk, data)
+            }
         }
+    }
+
+    fn uses_gpu(&self) -> bool {
+        true
+    }
 
-        hits
+    fn gpu_transfer(&self) -> Option<GpuTransferCounts> {
+        Some(self.transfer.snapshot())
     }
 }
 
@@ -0,0 +1,401 @@
+//! Chunk staging for the GPU signature scanners.
+//!
+//! Each GPU scanner owns [`STAGING_SLOTS`] slots. A slot holds everything one
+//! chunk needs on its way through the device: a pinned (page-locked) host
+//! buffer the chunk is copied into, the device buffers, and its own queue or
+//! stream. Uploads from pinned memory run as asynchronous DMA, and because the
+//! slots use separate queues, the upload of one scan worker's chunk runs while
+//! the kernel of another worker's chunk is still on the device. Workers that
+//! find every slot busy wait for one in [`SlotPool::acquire`].
+//!
+//! Chunks larger than the device can hold are not sent to the CPU: each
+//! scanner sizes its buffers from the device memory ([`SubChunking`]) and
+//! scans longer chunks in overlapping parts.
+//!
+//! [`GpuTransferTimes`] adds up the device-side time of the uploads, kernels,
+//! and downloads, as measured by queue events, and counts the chunks that
+//! were staged while another chunk was on the device.
+
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+use anyhow::{Result, anyhow};
+use serde::Serialize;
+
+use crate::constants::MIB;
+use crate::scanner::Hit;
+
+/// Slots per GPU scanner: one chunk uploads while the other scans.
+pub const STAGING_SLOTS: usize = 2;
+
+/// Smallest part worth a device pass; a device with less room for chunk
+/// buffers is not used.
+const MIN_PART_LEN: usize = 1024 * 1024;
+
+/// Device time of a GPU scanner, as reported in `PipelineStats`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct GpuTransferCounts {
+    /// Chunks scanned on the device.
+    pub chunks: u64,
+    /// Host-to-device copies of chunk data.
+    pub upload_secs: f64,
+    pub kernel_secs: f64,
+    /// Device-to-host copies of hit counts and hits.
+    pub download_secs: f64,
+    /// Chunks staged while another chunk was on the device.
+    pub overlapped_chunks: u64,
+    /// Largest part of a chunk scanned in one device pass, sized from the
+    /// device memory.
+    pub part_bytes: u64,
+    /// Chunks longer than `part_bytes`, scanned in parts.
+    pub split_chunks: u64,
+    /// Device passes over the parts of split chunks.
+    pub parts: u64,
+}
+
+/// Shared counters behind [`GpuTransferCounts`].
+#[derive(Debug, Default)]
+pub struct GpuTransferTimes {
+    chunks: AtomicU64,
+    upload_ns: AtomicU64,
+    kernel_ns: AtomicU64,
+    download_ns: AtomicU64,
+    overlapped: AtomicU64,
+    part_bytes: u64,
+    split_chunks: AtomicU64,
+    parts: AtomicU64,
+}
+
+impl GpuTransferTimes {
+    pub fn new(split: SubChunking) -> Self {
+        Self {
+            part_bytes: split.part_len as u64,
+            ..Self::default()
+        }
+    }
+
+    /// Add one chunk, scanned in `parts` device passes.
+    pub fn record(&self, scan: &DeviceScan, overlapped: bool, parts: usize) {
+        self.chunks.fetch_add(1, Ordering::Relaxed);
+        for (counter, time) in [
+            (&self.upload_ns, scan.upload),
+            (&self.kernel_ns, scan.kernel),
+            (&self.download_ns, scan.download),
+        ] {
+            counter.fetch_add(time.as_nanos() as u64, Ordering::Relaxed);
+        }
+        if overlapped {
+            self.overlapped.fetch_add(1, Ordering::Relaxed);
+        }
+        if parts > 1 {
+            self.split_chunks.fetch_add(1, Ordering::Relaxed);
+            self.parts.fetch_add(parts as u64, Ordering::Relaxed);
+        }
+    }
+
+    pub fn snapshot(&self) -> GpuTransferCounts {
+        let secs = |counter: &AtomicU64| counter.load(Ordering::Relaxed) as f64 / 1e9;
+        GpuTransferCounts {
+            chunks: self.chunks.load(Ordering::Relaxed),
+            upload_secs: secs(&self.upload_ns),
+            kernel_secs: secs(&self.kernel_ns),
+            download_secs: secs(&self.download_ns),
+            overlapped_chunks: self.overlapped.load(Ordering::Relaxed),
+            part_bytes: self.part_bytes,
+            split_chunks: self.split_chunks.load(Ordering::Relaxed),
+            parts: self.parts.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Hits and device times of one device pass, or the sum over the parts of a
+/// chunk.
+#[derive(Debug, Default)]
+pub struct DeviceScan {
+    pub hits: Vec<Hit>,
+    pub upload: Duration,
+    pub kernel: Duration,
+    pub download: Duration,
+}
+
+/// How much of a chunk a GPU scanner sends to the device in one pass.
+///
+/// Chunks longer than `part_len` are scanned in parts that share `overlap`
+/// bytes, the longest pattern less one, so a match across a cut lies whole
+/// in the part it starts in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubChunking {
+    pub part_len: usize,
+    pub overlap: usize,
+}
+
+impl SubChunking {
+    /// Size the parts for a device with `device_mem` bytes and buffers of at
+    /// most `max_alloc` bytes, after `reserved` bytes of pattern and hit
+    /// buffers. A quarter of the memory is left to the driver and other
+    /// programs, and every staging slot holds one part. Hit offsets are
+    /// 32-bit, which caps a part at 4 GiB.
+    pub fn for_device(
+        device_mem: u64,
+        max_alloc: u64,
+        reserved: u64,
+        overlap: usize,
+    ) -> Result<Self> {
+        let usable = (device_mem / 4 * 3).saturating_sub(reserved);
+        let part_len = (usable / STAGING_SLOTS as u64)
+            .min(max_alloc)
+            .min(u32::MAX as u64) as usize;
+        if part_len < MIN_PART_LEN.max(overlap.saturating_mul(2)) {
+            return Err(anyhow!(
+                "device has {} MiB free for chunk buffers, too little to stage a chunk",
+                usable / MIB
+            ));
+        }
+        Ok(Self { part_len, overlap })
+    }
+
+    /// Run `scan` over `data` in one pass, or part by part when `data` is
+    /// longer than `part_len`. Hit offsets in the result are relative to
+    /// `data`; the second value is the number of passes.
+    pub fn scan(
+        &self,
+        data: &[u8],
+        mut scan: impl FnMut(&[u8]) -> Result<DeviceScan>,
+    ) -> Result<(DeviceScan, usize)> {
+        if data.len() <= self.part_len {
+            return scan(data).map(|pass| (pass, 1));
+        }
+        let stride = self.part_len - self.overlap;
+        let mut total = DeviceScan::default();
+        let mut parts = 0;
+        let mut start = 0;
+        loop {
+            let end = (start + self.part_len).min(data.len());
+            let last = end == data.len();
+            let pass = scan(&data[start..end])?;
+            parts += 1;
+            total.upload += pass.upload;
+            total.kernel += pass.kernel;
+            total.download += pass.download;
+            // Matches starting in the shared tail are found again by the
+            // next part.
+            total.hits.extend(
+                pass.hits
+                    .into_iter()
+                    .filter(|hit| last || hit.local_offset < stride as u64)
+                    .map(|mut hit| {
+                        hit.local_offset += start as u64;
+                        hit
+                    }),
+            );
+            if last {
+                return Ok((total, parts));
+            }
+            start += stride;
+        }
+    }
+}
+
+/// A fixed set of staging slots shared by the scan workers.
+#[derive(Debug)]
+pub struct SlotPool<T> {
+    slots: Mutex<Vec<T>>,
+    available: Condvar,
+    total: usize,
+}
+
+impl<T> SlotPool<T> {
+    pub fn new(slots: Vec<T>) -> Self {
+        let total = slots.len();
+        Self {
+            slots: Mutex::new(slots),
+            available: Condvar::new(),
+            total,
+        }
+    }
+
+    /// Take a slot, waiting while all of them are in use. The slot goes
+    /// back to the pool when the guard drops.
+    pub fn acquire(&self) -> PooledSlot<'_, T> {
+        let mut slots = self.slots.lock().unwrap_or_else(|p| p.into_inner());
+        loop {
+            if let Some(slot) = slots.pop() {
+                // Slots not in the pool hold a chunk on the device.
+                let overlapped = slots.len() + 1 < self.total;
+                return PooledSlot {
+                    pool: self,
+                    slot: Some(slot),
+                    overlapped,
+                };
+            }
+            slots = self
+                .available
+                .wait(slots)
+                .unwrap_or_else(|p| p.into_inner());
+        }
+    }
+}
+
+/// A slot taken from a [`SlotPool`].
+pub struct PooledSlot<'a, T> {
+    pool: &'a SlotPool<T>,
+    slot: Option<T>,
+    overlapped: bool,
+}
+
+impl<T> PooledSlot<'_, T> {
+    /// Whether another slot was in use when this one was taken.
+    pub fn overlapped(&self) -> bool {
+        self.overlapped
+    }
+}
+
+impl<T> Deref for PooledSlot<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.slot.as_ref().expect("slot held until drop")
+    }
+}
+
+impl<T> DerefMut for PooledSlot<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.slot.as_mut().expect("slot held until drop")
+    }
+}
+
+impl<T> Drop for PooledSlot<'_, T> {
+    fn drop(&mut self) {
+        if let Some(slot) = self.slot.take() {
+            let mut slots = self.pool.slots.lock().unwrap_or_else(|p| p.into_inner());
+            slots.push(slot);
+            self.pool.available.notify_one();
+        }
+    }
+}
+
+/// Capacity to allocate for a chunk of `len` bytes: at least double the
+/// current one, so a growing tail does not reallocate on every chunk, but
+/// no more than `limit`.
+pub fn grown_capacity(current: usize, len: usize, limit: usize) -> usize {
+    if len <= current {
+        current
+    } else {
+        len.max(current.saturating_mul(2).min(limit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Barrier};
+    use std::thread;
+
+    #[test]
+    fn pool_hands_out_each_slot_once_and_flags_overlap() {
+        let pool = Arc::new(SlotPool::new(vec![0u32, 1]));
+        let first = pool.acquire();
+        assert!(!first.overlapped());
+        let second = pool.acquire();
+        assert!(second.overlapped());
+        assert_ne!(*first, *second);
+
+        let barrier = Arc::new(Barrier::new(2));
+        let waiter = {
+            let pool = pool.clone();
+            let barrier = barrier.clone();
+            thread::spawn(move || {
+                barrier.wait();
+                *pool.acquire()
+            })
+        };
+        barrier.wait();
+        let released = *first;
+        drop(first);
+        assert_eq!(waiter.join().expect("waiter"), released);
+        drop(second);
+        assert!(!pool.acquire().overlapped());
+    }
+
+    #[test]
+    fn transfer_times_add_up() {
+        let times = GpuTransferTimes::default();
+        let pass = |upload| DeviceScan {
+            hits: Vec::new(),
+            upload: Duration::from_millis(upload),
+            kernel: Duration::from_millis(10),
+            download: Duration::from_millis(1),
+        };
+        times.record(&pass(3), false, 1);
+        times.record(&pass(2), true, 3);
+        let counts = times.snapshot();
+        assert_eq!(counts.chunks, 2);
+        assert!((counts.upload_secs - 0.005).abs() < 1e-9);
+        assert!((counts.kernel_secs - 0.02).abs() < 1e-9);
+        assert_eq!(counts.overlapped_chunks, 1);
+        assert_eq!((counts.split_chunks, counts.parts), (1, 3));
+        assert_eq!(grown_capacity(0, 100, 1000), 100);
+        assert_eq!(grown_capacity(100, 120, 1000), 200);
+        assert_eq!(grown_capacity(100, 120, 150), 150);
+        assert_eq!(grown_capacity(100, 50, 1000), 100);
+    }
+
+    #[test]
+    fn parts_are_sized_from_device_memory() {
+        let gib = 1024 * MIB;
+        let split = SubChunking::for_device(8 * gib, 2 * gib, 0, 15).expect("8 GiB device");
+        assert_eq!(split.part_len as u64, 2 * gib);
+        let split = SubChunking::for_device(gib, 4 * gib, 64 * MIB, 15).expect("1 GiB device");
+        assert_eq!(split.part_len as u64, (768 - 64) * MIB / 2);
+        let split = SubChunking::for_device(64 * gib, 64 * gib, 0, 15).expect("large device");
+        assert_eq!(split.part_len, u32::MAX as usize);
+        assert!(SubChunking::for_device(2 * MIB, gib, 0, 15).is_err());
+    }
+
+    #[test]
+    fn split_scan_finds_each_match_once() {
+        let pattern = b"NEEDLE";
+        let mut data = vec![0u8; 1000];
+        // Across the first cut, in the shared bytes, and at the very end.
+        for at in [97, 180, 994] {
+            data[at..at + pattern.len()].copy_from_slice(pattern);
+        }
+        let split = SubChunking {
+            part_len: 100,
+            overlap: pattern.len() - 1,
+        };
+        let mut passes = Vec::new();
+        let (scan, parts) = split
+            .scan(&data, |part| {
+                passes.push(part.len());
+                let hits = part
+                    .windows(pattern.len())
+                    .enumerate()
+                    .filter(|(_, window)| *window == pattern)
+                    .map(|(offset, _)| Hit {
+                        chunk_id: 0,
+                        local_offset: offset as u64,
+                        pattern_id: "needle".to_string(),
+                        file_type_id: "needle".to_string(),
+                    })
+                    .collect();
+                Ok(DeviceScan {
+                    hits,
+                    ..DeviceScan::default()
+                })
+            })
+            .expect("scan");
+        let offsets: Vec<u64> = scan.hits.iter().map(|hit| hit.local_offset).collect();
+        assert_eq!(offsets, vec![97, 180, 994]);
+        assert_eq!(parts, passes.len());
+        assert!(passes.iter().all(|&len| len <= 100));
+
+        let (whole, parts) = split
+            .scan(&data[..100], |_| Ok(DeviceScan::default()))
+            .expect("short scan");
+        assert!(whole.hits.is_empty());
+        assert_eq!(parts, 1);
+    }
+}
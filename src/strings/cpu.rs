@@ -1,14 +1,14 @@
 use crate::chunk::ScanChunk;
-use crate::strings::{StringScanner, StringSpan, flags};
+use crate::strings::{StringMinLens, StringScanner, StringSpan, flags};
 
 pub struct CpuStringScanner {
-    min_len: usize,
+    min_len: StringMinLens,
     max_len: usize,
     scan_utf16: bool,
 }
 
 impl CpuStringScanner {
-    pub fn new(min_len: usize, max_len: usize, scan_utf16: bool) -> Self {
+    pub fn new(min_len: StringMinLens, max_len: usize, scan_utf16: bool) -> Self {
         let max_len = if max_len == 0 { usize::MAX } else { max_len };
         Self {
             min_len,
@@ -20,14 +20,15 @@ impl CpuStringScanner {
 
 impl StringScanner for CpuStringScanner {
     fn scan_chunk(&self, chunk: &ScanChunk, data: &[u8]) -> Vec<StringSpan> {
-        let mut spans = scan_ascii_runs(data, chunk, self.min_len, self.max_len);
-        let mut utf8_spans = scan_utf8_runs(data, chunk, self.min_len, self.max_len);
+        let mut spans = scan_ascii_runs(data, chunk, self.min_len.ascii, self.max_len);
+        let mut utf8_spans = scan_utf8_runs(data, chunk, self.min_len.utf8, self.max_len);
         spans.append(&mut utf8_spans);
 
         if self.scan_utf16 {
-            let mut utf16_spans = scan_utf16_runs(data, chunk, self.min_len, self.max_len, true);
+            let min_len = self.min_len.utf16;
+            let mut utf16_spans = scan_utf16_runs(data, chunk, min_len, self.max_len, true);
             spans.append(&mut utf16_spans);
-            let mut utf16_spans = scan_utf16_runs(data, chunk, self.min_len, self.max_len, false);
+            let mut utf16_spans = scan_utf16_runs(data, chunk, min_len, self.max_len, false);
             spans.append(&mut utf16_spans);
         }
 
@@ -79,6 +80,63 @@ fn scan_ascii_runs(
     spans
 }
 
+/// Turn the printable runs a GPU kernel found into the spans
+/// [`CpuStringScanner`] reports for the same bytes.
+///
+/// `runs` are `(start, length)` pairs in any order, each the first `max_len`
+/// bytes of a printable run. They are sorted, long runs are split into
+/// `max_len` pieces, and hint flags are computed here from the bytes rather
+/// than by the kernel, so artefact extraction sees the same spans with the
+/// same flags whichever backend scanned the chunk.
+#[cfg(any(test, feature = "gpu-opencl", feature = "gpu-cuda"))]
+pub(crate) fn classify_ascii_runs(
+    chunk: &ScanChunk,
+    data: &[u8],
+    runs: impl IntoIterator<Item = (u32, u32)>,
+    min_len: usize,
+    max_len: usize,
+) -> Vec<StringSpan> {
+    let mut runs: Vec<(u32, u32)> = runs.into_iter().collect();
+    runs.sort_unstable();
+    let mut spans = Vec::with_capacity(runs.len());
+    for (start, len) in runs {
+        let start = start as usize;
+        let len = len as usize;
+        if start + len > data.len() {
+            continue;
+        }
+        spans.push(StringSpan {
+            chunk_id: chunk.id,
+            local_start: start as u64,
+            length: len as u32,
+            flags: span_flags_ascii(&data[start..start + len]),
+        });
+        if len < max_len {
+            continue;
+        }
+        // The kernel stops at max_len; the CPU scanner carries on with the
+        // rest of the run as further spans.
+        let end = data[start + len..]
+            .iter()
+            .position(|&b| !is_printable(b))
+            .map_or(data.len(), |p| start + len + p);
+        let mut piece = start + len;
+        while piece < end {
+            let piece_len = (end - piece).min(max_len);
+            if piece_len >= min_len {
+                spans.push(StringSpan {
+                    chunk_id: chunk.id,
+                    local_start: piece as u64,
+                    length: piece_len as u32,
+                    flags: span_flags_ascii(&data[piece..piece + piece_len]),
+                });
+            }
+            piece += piece_len;
+        }
+    }
+    spans
+}
+
 pub(crate) fn scan_utf8_runs(
     data: &[u8],
     chunk: &ScanChunk,
@@ -140,6 +198,10 @@ pub(crate) fn scan_utf8_runs(
     spans
 }
 
+/// UTF-16 runs start on a printable ASCII code unit and may continue through
+/// letters and digits of one other script block (see [`utf16_block`]), so
+/// non-Latin hosts and mailbox names stay in one span while random binary,
+/// which jumps between blocks, does not run on.
 pub(crate) fn scan_utf16_runs(
     data: &[u8],
     chunk: &ScanChunk,
@@ -153,14 +215,7 @@ pub(crate) fn scan_utf16_runs(
     while start_offset < 2 {
         let mut i = start_offset;
         while i + 1 < data.len() {
-            let (first, second) = (data[i], data[i + 1]);
-            let pair_ok = if little_endian {
-                is_printable(first) && second == 0
-            } else {
-                first == 0 && is_printable(second)
-            };
-
-            if !pair_ok {
+            if utf16_ascii(utf16_unit(data, i, little_endian)).is_none() {
                 i += 2;
                 continue;
             }
@@ -168,19 +223,20 @@ pub(crate) fn scan_utf16_runs(
             let run_start = i;
             let mut len = 0usize;
             let mut ascii_bytes = Vec::new();
+            let mut block = None;
             let mut j = i;
             while j + 1 < data.len() {
-                let (a, b) = (data[j], data[j + 1]);
-                let ok = if little_endian {
-                    is_printable(a) && b == 0
+                let unit = utf16_unit(data, j, little_endian);
+                if let Some(ascii) = utf16_ascii(unit) {
+                    ascii_bytes.push(ascii);
+                } else if is_utf16_word_char(unit)
+                    && *block.get_or_insert(utf16_block(unit)) == utf16_block(unit)
+                {
+                    // Stands in for the letter so hint flags see a word character.
+                    ascii_bytes.push(b'a');
                 } else {
-                    a == 0 && is_printable(b)
-                };
-                if !ok {
                     break;
                 }
-                let ascii = if little_endian { a } else { b };
-                ascii_bytes.push(ascii);
                 len += 1;
                 if len >= max_len {
                     break;
@@ -203,11 +259,7 @@ pub(crate) fn scan_utf16_runs(
                 });
             }
 
-            if len >= max_len {
-                i = j + 2;
-            } else {
-                i = j + 2;
-            }
+            i = j + 2;
         }
         start_offset += 1;
     }
@@ -215,6 +267,34 @@ pub(crate) fn scan_utf16_runs(
     spans
 }
 
+fn utf16_unit(data: &[u8], idx: usize, little_endian: bool) -> u16 {
+    let pair = [data[idx], data[idx + 1]];
+    if little_endian {
+        u16::from_le_bytes(pair)
+    } else {
+        u16::from_be_bytes(pair)
+    }
+}
+
+fn utf16_ascii(unit: u16) -> Option<u8> {
+    u8::try_from(unit).ok().filter(|&b| is_printable(b))
+}
+
+/// A non-ASCII letter or digit in the Basic Multilingual Plane.
+fn is_utf16_word_char(unit: u16) -> bool {
+    unit >= 0x80 && char::from_u32(unit as u32).is_some_and(char::is_alphanumeric)
+}
+
+/// Script block of a code unit: its high byte, with the CJK ideograph and
+/// Hangul syllable ranges each counted as one block.
+fn utf16_block(unit: u16) -> u8 {
+    match unit {
+        0x4E00..=0x9FFF => 0x4E,
+        0xAC00..=0xD7A3 => 0xAC,
+        _ => (unit >> 8) as u8,
+    }
+}
+
 pub(crate) fn span_flags_ascii(slice: &[u8]) -> u32 {
     let mut flags_out = 0u32;
     if contains_case_insensitive(slice, b"http") || contains_case_insensitive(slice, b"www.") {
@@ -324,7 +404,7 @@ mod tests {
 
     #[test]
     fn scans_printable_runs() {
-        let scanner = CpuStringScanner::new(4, 1024, false);
+        let scanner = CpuStringScanner::new(StringMinLens::uniform(4), 1024, false);
         let chunk = ScanChunk {
             id: 1,
             start: 0,
@@ -340,7 +420,7 @@ mod tests {
 
     #[test]
     fn splits_long_strings() {
-        let scanner = CpuStringScanner::new(2, 4, false);
+        let scanner = CpuStringScanner::new(StringMinLens::uniform(2), 4, false);
         let chunk = ScanChunk {
             id: 1,
             start: 0,
@@ -357,7 +437,7 @@ mod tests {
 
     #[test]
     fn scans_utf16le_runs() {
-        let scanner = CpuStringScanner::new(3, 1024, true);
+        let scanner = CpuStringScanner::new(StringMinLens::uniform(3), 1024, true);
         let chunk = ScanChunk {
             id: 1,
             start: 0,
@@ -371,6 +451,75 @@ mod tests {
         assert!(spans.iter().any(|span| span.length == 8));
     }
 
+    #[test]
+    fn applies_each_encoding_minimum() {
+        let scanner = CpuStringScanner::new(
+            StringMinLens {
+                ascii: 8,
+                utf8: 6,
+                utf16: 3,
+            },
+            1024,
+            true,
+        );
+        let scan = |data: &[u8]| {
+            let chunk = ScanChunk {
+                id: 1,
+                start: 0,
+                length: data.len() as u64,
+                valid_length: data.len() as u64,
+            };
+            // Big-endian runs of the same bytes are one byte out of step.
+            let mut found: Vec<(u64, u32)> = scanner
+                .scan_chunk(&chunk, data)
+                .into_iter()
+                .filter(|span| span.flags & flags::UTF16_BE == 0)
+                .map(|span| (span.local_start, span.length))
+                .collect();
+            found.sort_unstable();
+            found
+        };
+        // ASCII needs eight characters and UTF-8 six.
+        assert_eq!(
+            scan("short\0a longer one\0café\0crème brûlée".as_bytes()),
+            vec![(6, 12), (25, 15)]
+        );
+        // UTF-16 needs three code units.
+        let wide: Vec<u8> = "ab\0key\0"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        assert_eq!(scan(&wide), vec![(6, 6)]);
+    }
+
+    #[test]
+    fn utf16_runs_continue_through_one_script() {
+        let scanner = CpuStringScanner::new(StringMinLens::uniform(4), 1024, true);
+        let mut data: Vec<u8> = "ivan@пример.рф"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        // A letter from another block ends the run.
+        data.extend(
+            ['a' as u16, 'ж' as u16, 'Ω' as u16]
+                .into_iter()
+                .flat_map(u16::to_le_bytes),
+        );
+        let chunk = ScanChunk {
+            id: 1,
+            start: 0,
+            length: data.len() as u64,
+            valid_length: data.len() as u64,
+        };
+        let spans = scanner.scan_chunk(&chunk, &data);
+        let span = spans
+            .iter()
+            .find(|span| (span.flags & flags::UTF16_LE) != 0 && span.local_start == 0)
+            .expect("utf-16 span");
+        assert_eq!(span.length, 16 * 2);
+        assert!((span.flags & flags::EMAIL_LIKE) != 0);
+    }
+
     #[test]
     fn sets_hint_flags_for_ascii() {
         let data = b"see http://example.com mail test@example.com call 4155551234";
@@ -380,6 +529,53 @@ mod tests {
         assert!((flags & flags::PHONE_LIKE) != 0);
     }
 
+    /// What the GPU kernels report: the first `max_len` bytes of every
+    /// printable run, in no particular order.
+    fn kernel_runs(data: &[u8], min_len: usize, max_len: usize) -> Vec<(u32, u32)> {
+        let mut runs: Vec<(u32, u32)> = (0..data.len())
+            .filter(|&i| is_printable(data[i]) && (i == 0 || !is_printable(data[i - 1])))
+            .map(|i| {
+                let len = data[i..]
+                    .iter()
+                    .take(max_len)
+                    .take_while(|&&b| is_printable(b))
+                    .count();
+                (i as u32, len as u32)
+            })
+            .filter(|&(_, len)| len as usize >= min_len)
+            .collect();
+        runs.reverse();
+        runs
+    }
+
+    #[test]
+    fn gpu_runs_classify_like_cpu_scan() {
+        let chunk = ScanChunk {
+            id: 3,
+            start: 0,
+            length: 0,
+            valid_length: 0,
+        };
+        let mut data = b"\0mail bob@example.org\0\0tel +1 415 555 0100 x\0ab\0".to_vec();
+        data.extend_from_slice(b"xxxxxxxxxxxxxxxxxxxxhttps://example.com/a/very/long/path\0");
+        data.extend_from_slice(b"\x01WWW.EXAMPLE.NET\t0123456789\x7f");
+        for max_len in [16, 1024, usize::MAX] {
+            let cpu = scan_ascii_runs(&data, &chunk, 4, max_len);
+            let gpu =
+                classify_ascii_runs(&chunk, &data, kernel_runs(&data, 4, max_len), 4, max_len);
+            let key = |s: &StringSpan| (s.local_start, s.length, s.flags);
+            assert_eq!(
+                gpu.iter().map(key).collect::<Vec<_>>(),
+                cpu.iter().map(key).collect::<Vec<_>>(),
+                "max_len {max_len}"
+            );
+        }
+        let spans = scan_ascii_runs(&data, &chunk, 4, 16);
+        assert!(spans.iter().any(|s| s.flags & flags::URL_LIKE != 0));
+        assert!(spans.iter().any(|s| s.flags & flags::EMAIL_LIKE != 0));
+        assert!(spans.iter().any(|s| s.flags & flags::PHONE_LIKE != 0));
+    }
+
     #[test]
     fn scans_utf8_runs() {
         let chunk = ScanChunk {
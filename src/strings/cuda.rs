@@ -7,7 +7,7 @@ use tracing::warn;
 use crate::chunk::ScanChunk;
 use crate::config::Config;
 use crate::strings::cpu::CpuStringScanner;
-use crate::strings::{StringScanner, StringSpan};
+use crate::strings::{StringMinLens, StringScanner, StringSpan};
 
 const KERNEL_SRC: &str = r#"
 extern "C" __global__ void scan_ascii_spans(
@@ -17,7 +17,6 @@ extern "C" __global__ void scan_ascii_spans(
     unsigned int max_len,
     unsigned int* span_starts,
     unsigned int* span_lens,
-    unsigned int* span_flags,
     unsigned int* span_count,
     unsigned int max_spans) {
 
@@ -37,46 +36,22 @@ extern "C" __global__ void scan_ascii_spans(
     }
 
     unsigned int len = 0;
-    unsigned int digits = 0;
-    unsigned int flags = 0;
-    unsigned int window = 0;
-    const unsigned int HTTP = 0x68747470;
-    const unsigned int WWW = 0x7777772e;
-
     while ((gid + len) < data_len) {
         unsigned char c = data[gid + len];
         if (!(c == 9 || (c >= 32 && c <= 126))) {
             break;
         }
-        unsigned char lower = c;
-        if (lower >= 'A' && lower <= 'Z') {
-            lower = lower + 32;
-        }
-        window = (window << 8) | (unsigned int)lower;
-        if (window == HTTP || window == WWW) {
-            flags |= 16;
-        }
-        if (c == '@') {
-            flags |= 32;
-        }
-        if (c >= '0' && c <= '9') {
-            digits += 1;
-        }
         len += 1;
         if (len >= max_len) {
             break;
         }
     }
 
-    if (digits >= 10) {
-        flags |= 64;
-    }
     if (len >= min_len) {
         unsigned int idx = atomicAdd(span_count, 1);
         if (idx < max_spans) {
             span_starts[idx] = (unsigned int)gid;
             span_lens[idx] = len;
-            span_flags[idx] = flags;
         }
     }
 }
@@ -87,7 +62,7 @@ const BLOCK_SIZE: u32 = 256;
 pub struct CudaStringScanner {
     /// Mutex wraps the device to serialize kernel operations for thread safety.
     device: Mutex<Arc<CudaDevice>>,
-    min_len: usize,
+    min_len: StringMinLens,
     max_len: usize,
     min_len_u32: u32,
     max_len_u32: u32,
@@ -113,7 +88,10 @@ impl CudaStringScanner {
         } else {
             cfg.string_max_len
         };
-        let min_len_u32 = cfg.string_min_len.min(u32::MAX as usize) as u32;
+        let min_len = StringMinLens::from_config(cfg);
+        // The kernel finds ASCII runs only; UTF-8 and UTF-16 are scanned on
+        // the CPU with their own minimums.
+        let min_len_u32 = min_len.ascii.min(u32::MAX as usize) as u32;
         let max_len_u32 = if max_len > u32::MAX as usize {
             u32::MAX
         } else {
@@ -126,17 +104,13 @@ impl CudaStringScanner {
 
         Ok(Self {
             device: Mutex::new(device),
-            min_len: cfg.string_min_len,
+            min_len,
             max_len,
             min_len_u32,
             max_len_u32,
             max_spans_per_chunk,
             scan_utf16: cfg.string_scan_utf16,
-            cpu_fallback: CpuStringScanner::new(
-                cfg.string_min_len,
-                cfg.string_max_len,
-                cfg.string_scan_utf16,
-            ),
+            cpu_fallback: CpuStringScanner::new(min_len, cfg.string_max_len, cfg.string_scan_utf16),
         })
     }
 }
@@ -186,13 +160,6 @@ impl StringScanner for CudaStringScanner {
                 return self.cpu_fallback.scan_chunk(chunk, data);
             }
         };
-        let flags_gpu: CudaSlice<u32> = match device.alloc_zeros(span_capacity) {
-            Ok(buf) => buf,
-            Err(err) => {
-                warn!("CUDA span flags alloc failed: {err}; using cpu fallback");
-                return self.cpu_fallback.scan_chunk(chunk, data);
-            }
-        };
         let count_gpu: CudaSlice<u32> = match device.alloc_zeros(1) {
             Ok(buf) => buf,
             Err(err) => {
@@ -230,7 +197,6 @@ impl StringScanner for CudaStringScanner {
                     self.max_len_u32,
                     &starts_gpu,
                     &lens_gpu,
-                    &flags_gpu,
                     &count_gpu,
                     self.max_spans_per_chunk,
                 ),
@@ -279,92 +245,36 @@ impl StringScanner for CudaStringScanner {
                 return self.cpu_fallback.scan_chunk(chunk, data);
             }
         };
-        let flags: Vec<u32> = match device.dtoh_sync_copy(&flags_gpu) {
-            Ok(v) => v,
-            Err(err) => {
-                warn!("CUDA span flags read failed: {err}; using cpu fallback");
-                return self.cpu_fallback.scan_chunk(chunk, data);
-            }
-        };
 
-        let mut spans = Vec::with_capacity(count);
-        for idx in 0..count {
-            spans.push(StringSpan {
-                chunk_id: chunk.id,
-                local_start: starts[idx] as u64,
-                length: lens[idx],
-                flags: flags[idx],
-            });
-        }
-        let mut spans = extend_long_ascii_spans(chunk, data, spans, self.min_len, self.max_len);
-        let mut utf8 = crate::strings::cpu::scan_utf8_runs(data, chunk, self.min_len, self.max_len);
+        let runs = starts[..count]
+            .iter()
+            .copied()
+            .zip(lens[..count].iter().copied());
+        let mut spans = crate::strings::cpu::classify_ascii_runs(
+            chunk,
+            data,
+            runs,
+            self.min_len.ascii,
+            self.max_len,
+        );
+        let mut utf8 =
+            crate::strings::cpu::scan_utf8_runs(data, chunk, self.min_len.utf8, self.max_len);
         spans.append(&mut utf8);
         if self.scan_utf16 {
+            let min_len = self.min_len.utf16;
             let mut utf16 =
-                crate::strings::cpu::scan_utf16_runs(data, chunk, self.min_len, self.max_len, true);
+                crate::strings::cpu::scan_utf16_runs(data, chunk, min_len, self.max_len, true);
             spans.append(&mut utf16);
-            let mut utf16 = crate::strings::cpu::scan_utf16_runs(
-                data,
-                chunk,
-                self.min_len,
-                self.max_len,
-                false,
-            );
+            let mut utf16 =
+                crate::strings::cpu::scan_utf16_runs(data, chunk, min_len, self.max_len, false);
             spans.append(&mut utf16);
         }
         spans
     }
-}
 
-fn extend_long_ascii_spans(
-    chunk: &ScanChunk,
-    data: &[u8],
-    spans: Vec<StringSpan>,
-    min_len: usize,
-    max_len: usize,
-) -> Vec<StringSpan> {
-    if max_len == usize::MAX {
-        return spans;
+    fn uses_gpu(&self) -> bool {
+        true
     }
-    let mut out = Vec::with_capacity(spans.len());
-    for span in spans {
-        let start = span.local_start as usize;
-        let len = span.length as usize;
-        out.push(span);
-        if len < max_len {
-            continue;
-        }
-        let mut idx = start + len;
-        if idx >= data.len() || !is_printable(data[idx]) {
-            continue;
-        }
-        while idx < data.len() && is_printable(data[idx]) {
-            let run_start = idx;
-            let mut run_len = 0usize;
-            while idx < data.len() && is_printable(data[idx]) {
-                idx += 1;
-                run_len += 1;
-                if run_len >= max_len {
-                    break;
-                }
-            }
-            if run_len >= min_len {
-                let slice = &data[run_start..run_start + run_len];
-                let flags = crate::strings::cpu::span_flags_ascii(slice);
-                out.push(StringSpan {
-                    chunk_id: chunk.id,
-                    local_start: run_start as u64,
-                    length: run_len as u32,
-                    flags,
-                });
-            }
-        }
-    }
-    out
-}
-
-fn is_printable(byte: u8) -> bool {
-    matches!(byte, b'\t' | 0x20..=0x7E)
 }
 
 #[cfg(test)]
@@ -17,7 +17,7 @@ use tracing::warn;
 use crate::chunk::ScanChunk;
 use crate::config::Config;
 use crate::strings::cpu::CpuStringScanner;
-use crate::strings::{StringScanner, StringSpan};
+use crate::strings::{StringMinLens, StringScanner, StringSpan};
 
 const KERNEL_SRC: &str = r#"
 #pragma OPENCL EXTENSION cl_khr_global_int32_base_atomics : enable
@@ -28,7 +28,6 @@ __kernel void scan_ascii_spans(
     uint max_len,
     __global uint* span_starts,
     __global uint* span_lens,
-    __global uint* span_flags,
     __global uint* span_count,
     uint max_spans) {
     size_t gid = get_global_id(0);
@@ -47,46 +46,22 @@ __kernel void scan_ascii_spans(
     }
 
     uint len = 0;
-    uint digits = 0;
-    uint flags = 0;
-    uint window = 0;
-    const uint HTTP = 0x68747470;
-    const uint WWW = 0x7777772e;
-
     while ((ulong)(gid + len) < data_len) {
         uchar c = data[gid + len];
         if (!(c == 9 || (c >= 32 && c <= 126))) {
             break;
         }
-        uchar lower = c;
-        if (lower >= 'A' && lower <= 'Z') {
-            lower = (uchar)(lower + 32);
-        }
-        window = (window << 8) | (uint)lower;
-        if (window == HTTP || window == WWW) {
-            flags |= 16;
-        }
-        if (c == '@') {
-            flags |= 32;
-        }
-        if (c >= '0' && c <= '9') {
-            digits += 1;
-        }
         len += 1;
         if (len >= max_len) {
             break;
         }
     }
 
-    if (digits >= 10) {
-        flags |= 64;
-    }
     if (len >= min_len) {
         uint idx = atomic_inc(span_count);
         if (idx < max_spans) {
             span_starts[idx] = (uint)gid;
             span_lens[idx] = len;
-            span_flags[idx] = flags;
         }
     }
 }
@@ -96,7 +71,7 @@ pub struct OpenClStringScanner {
     context: Context,
     queue: CommandQueue,
     kernel: Mutex<Kernel>,
-    min_len: usize,
+    min_len: StringMinLens,
     max_len: usize,
     min_len_u32: u32,
     max_len_u32: u32,
@@ -118,7 +93,10 @@ impl OpenClStringScanner {
         } else {
             cfg.string_max_len
         };
-        let min_len_u32 = cfg.string_min_len.min(u32::MAX as usize) as u32;
+        let min_len = StringMinLens::from_config(cfg);
+        // The kernel finds ASCII runs only; UTF-8 and UTF-16 are scanned on
+        // the CPU with their own minimums.
+        let min_len_u32 = min_len.ascii.min(u32::MAX as usize) as u32;
         let max_len_u32 = if max_len > u32::MAX as usize {
             u32::MAX
         } else {
@@ -133,17 +111,13 @@ impl OpenClStringScanner {
             context,
             queue,
             kernel: Mutex::new(kernel),
-            min_len: cfg.string_min_len,
+            min_len,
             max_len,
             min_len_u32,
             max_len_u32,
             max_spans_per_chunk,
             scan_utf16: cfg.string_scan_utf16,
-            cpu_fallback: CpuStringScanner::new(
-                cfg.string_min_len,
-                cfg.string_max_len,
-                cfg.string_scan_utf16,
-            ),
+            cpu_fallback: CpuStringScanner::new(min_len, cfg.string_max_len, cfg.string_scan_utf16),
         })
     }
 }
@@ -204,20 +178,6 @@ impl StringScanner for OpenClStringScanner {
                 return self.cpu_fallback.scan_chunk(chunk, data);
             }
         };
-        let flags_buffer = match unsafe {
-            Buffer::<cl_uint>::create(
-                &self.context,
-                CL_MEM_WRITE_ONLY,
-                span_capacity,
-                ptr::null_mut(),
-            )
-        } {
-            Ok(buf) => buf,
-            Err(err) => {
-                warn!("opencl span flags buffer create failed: {err}; using cpu fallback");
-                return self.cpu_fallback.scan_chunk(chunk, data);
-            }
-        };
 
         let mut zero = [0u32];
         let count_buffer = match unsafe {
@@ -243,7 +203,6 @@ impl StringScanner for OpenClStringScanner {
         let data_mem = data_buffer.get();
         let starts_mem = starts_buffer.get();
         let lens_mem = lens_buffer.get();
-        let flags_mem = flags_buffer.get();
         let count_mem = count_buffer.get();
 
         if let Err(err) = unsafe { kernel.set_arg(0, &data_mem) } {
@@ -255,9 +214,8 @@ impl StringScanner for OpenClStringScanner {
         let _ = unsafe { kernel.set_arg(3, &self.max_len_u32) };
         let _ = unsafe { kernel.set_arg(4, &starts_mem) };
         let _ = unsafe { kernel.set_arg(5, &lens_mem) };
-        let _ = unsafe { kernel.set_arg(6, &flags_mem) };
-        let _ = unsafe { kernel.set_arg(7, &count_mem) };
-        let _ = unsafe { kernel.set_arg(8, &self.max_spans_per_chunk) };
+        let _ = unsafe { kernel.set_arg(6, &count_mem) };
+        let _ = unsafe { kernel.set_arg(7, &self.max_spans_per_chunk) };
 
         let global_work_size = [data.len() as usize];
         if let Err(err) = unsafe {
@@ -299,7 +257,6 @@ impl StringScanner for OpenClStringScanner {
 
         let mut starts = vec![0u32; span_capacity];
         let mut lens = vec![0u32; span_capacity];
-        let mut flags = vec![0u32; span_capacity];
         if let Err(err) = unsafe {
             self.queue
                 .enqueue_read_buffer(&starts_buffer, CL_BLOCKING, 0, &mut starts, &[])
@@ -314,92 +271,36 @@ impl StringScanner for OpenClStringScanner {
             warn!("opencl read span lengths failed: {err}; using cpu fallback");
             return self.cpu_fallback.scan_chunk(chunk, data);
         }
-        if let Err(err) = unsafe {
-            self.queue
-                .enqueue_read_buffer(&flags_buffer, CL_BLOCKING, 0, &mut flags, &[])
-        } {
-            warn!("opencl read span flags failed: {err}; using cpu fallback");
-            return self.cpu_fallback.scan_chunk(chunk, data);
-        }
 
-        let mut spans = Vec::with_capacity(count);
-        for idx in 0..count {
-            spans.push(StringSpan {
-                chunk_id: chunk.id,
-                local_start: starts[idx] as u64,
-                length: lens[idx],
-                flags: flags[idx],
-            });
-        }
-        spans = extend_long_ascii_spans(chunk, data, spans, self.min_len, self.max_len);
-        let mut utf8 = crate::strings::cpu::scan_utf8_runs(data, chunk, self.min_len, self.max_len);
+        let runs = starts[..count]
+            .iter()
+            .copied()
+            .zip(lens[..count].iter().copied());
+        let mut spans = crate::strings::cpu::classify_ascii_runs(
+            chunk,
+            data,
+            runs,
+            self.min_len.ascii,
+            self.max_len,
+        );
+        let mut utf8 =
+            crate::strings::cpu::scan_utf8_runs(data, chunk, self.min_len.utf8, self.max_len);
         spans.append(&mut utf8);
         if self.scan_utf16 {
+            let min_len = self.min_len.utf16;
             let mut utf16 =
-                crate::strings::cpu::scan_utf16_runs(data, chunk, self.min_len, self.max_len, true);
+                crate::strings::cpu::scan_utf16_runs(data, chunk, min_len, self.max_len, true);
             spans.append(&mut utf16);
-            let mut utf16 = crate::strings::cpu::scan_utf16_runs(
-                data,
-                chunk,
-                self.min_len,
-                self.max_len,
-                false,
-            );
+            let mut utf16 =
+                crate::strings::cpu::scan_utf16_runs(data, chunk, min_len, self.max_len, false);
             spans.append(&mut utf16);
         }
         spans
     }
-}
 
-fn extend_long_ascii_spans(
-    chunk: &ScanChunk,
-    data: &[u8],
-    spans: Vec<StringSpan>,
-    min_len: usize,
-    max_len: usize,
-) -> Vec<StringSpan> {
-    if max_len == usize::MAX {
-        return spans;
+    fn uses_gpu(&self) -> bool {
+        true
     }
-    let mut out = Vec::with_capacity(spans.len());
-    for span in spans {
-        let start = span.local_start as usize;
-        let len = span.length as usize;
-        out.push(span);
-        if len < max_len {
-            continue;
-        }
-        let mut idx = start + len;
-        if idx >= data.len() || !is_printable(data[idx]) {
-            continue;
-        }
-        while idx < data.len() && is_printable(data[idx]) {
-            let run_start = idx;
-            let mut run_len = 0usize;
-            while idx < data.len() && is_printable(data[idx]) {
-                idx += 1;
-                run_len += 1;
-                if run_len >= max_len {
-                    break;
-                }
-            }
-            if run_len >= min_len {
-                let slice = &data[run_start..run_start + run_len];
-                let flags = crate::strings::cpu::span_flags_ascii(slice);
-                out.push(StringSpan {
-                    chunk_id: chunk.id,
-                    local_start: run_start as u64,
-                    length: run_len as u32,
-                    flags,
-                });
-            }
-        }
-    }
-    out
-}
-
-fn is_printable(byte: u8) -> bool {
-    matches!(byte, b'\t' | 0x20..=0x7E)
 }
 
 fn select_device(cfg: &Config) -> Result<(Device, Context)> {
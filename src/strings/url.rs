@@ -0,0 +1,248 @@
+//! Parts of URL artefacts and their readable forms.
+//!
+//! Internationalized host names appear in URLs as punycode labels
+//! (`xn--mnchen-3ya`, RFC 3492) and non-ASCII path characters as UTF-8
+//! percent escapes (`stra%C3%9Fe`). [`decode_url`] gives the Unicode host and
+//! the decoded path next to the raw URL, which is kept as found, so a search
+//! for a name in its own script finds URLs that spell it either way.
+
+/// Readable forms of a URL; `None` where the raw form is already readable
+/// or cannot be decoded.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DecodedUrl {
+    /// Host with every `xn--` label decoded and ASCII lowercased.
+    pub host_unicode: Option<String>,
+    /// Path with `%XX` escapes decoded, when the bytes are UTF-8.
+    pub path_decoded: Option<String>,
+}
+
+/// Scheme, host, port, path, query, and fragment of `url`.
+pub fn parse_url_parts(
+    url: &str,
+) -> (
+    String,
+    String,
+    Option<i32>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+) {
+    let mut scheme = String::new();
+    let mut rest = url;
+    if let Some(stripped) = url.strip_prefix("http://") {
+        scheme = "http".to_string();
+        rest = stripped;
+    } else if let Some(stripped) = url.strip_prefix("https://") {
+        scheme = "https".to_string();
+        rest = stripped;
+    } else if url.starts_with("www.") {
+        scheme = "http".to_string();
+        rest = url;
+    }
+
+    let mut fragment = None;
+    let mut query = None;
+    let mut path = None;
+
+    let mut base = rest;
+    if let Some(pos) = base.find('#') {
+        fragment = Some(base[pos + 1..].to_string());
+        base = &base[..pos];
+    }
+    if let Some(pos) = base.find('?') {
+        query = Some(base[pos + 1..].to_string());
+        base = &base[..pos];
+    }
+    if let Some(pos) = base.find('/') {
+        path = Some(base[pos..].to_string());
+        base = &base[..pos];
+    }
+
+    let mut host = base.to_string();
+    let mut port = None;
+    if let Some(pos) = base.rfind(':') {
+        let candidate = &base[pos + 1..];
+        if !candidate.is_empty()
+            && candidate.chars().all(|c| c.is_ascii_digit())
+            && let Ok(parsed) = candidate.parse::<i32>()
+        {
+            port = Some(parsed);
+            host = base[..pos].to_string();
+        }
+    }
+
+    (scheme, host, port, path, query, fragment)
+}
+
+/// Unicode host and decoded path of `url`.
+pub fn decode_url(url: &str) -> DecodedUrl {
+    let (_, host, _, path, _, _) = parse_url_parts(url);
+    DecodedUrl {
+        host_unicode: unicode_host(&host),
+        path_decoded: path.as_deref().and_then(percent_decode),
+    }
+}
+
+/// `host` with its punycode labels decoded; `None` when it has none or one
+/// does not decode.
+pub fn unicode_host(host: &str) -> Option<String> {
+    let mut decoded = false;
+    let mut labels = Vec::new();
+    for label in host.split('.') {
+        let lower = label.to_ascii_lowercase();
+        match lower.strip_prefix("xn--") {
+            Some(encoded) => {
+                let label = punycode_decode(encoded)?;
+                // A label that decodes to ASCII was not made by IDNA.
+                if label.is_ascii() {
+                    return None;
+                }
+                labels.push(label);
+                decoded = true;
+            }
+            None => labels.push(lower),
+        }
+    }
+    decoded.then(|| labels.join("."))
+}
+
+/// `value` with `%XX` escapes decoded; `None` when it has none or the
+/// decoded bytes are not UTF-8. Malformed escapes are kept as written.
+pub fn percent_decode(value: &str) -> Option<String> {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut escaped = false;
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = |at: usize| bytes.get(at).and_then(|&b| (b as char).to_digit(16));
+        match (bytes[i], hex(i + 1), hex(i + 2)) {
+            (b'%', Some(high), Some(low)) => {
+                out.push((high * 16 + low) as u8);
+                escaped = true;
+                i += 3;
+            }
+            (byte, _, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    if !escaped {
+        return None;
+    }
+    String::from_utf8(out).ok()
+}
+
+const BASE: u32 = 36;
+const T_MIN: u32 = 1;
+const T_MAX: u32 = 26;
+const SKEW: u32 = 38;
+const DAMP: u32 = 700;
+const INITIAL_BIAS: u32 = 72;
+const INITIAL_N: u32 = 128;
+
+/// Decode one punycode label (without `xn--`), per RFC 3492 section 6.2.
+fn punycode_decode(input: &str) -> Option<String> {
+    let (basic, extended) = match input.rfind('-') {
+        Some(pos) => (&input[..pos], &input[pos + 1..]),
+        None => ("", input),
+    };
+    if !basic.is_ascii() {
+        return None;
+    }
+    let mut output: Vec<char> = basic.chars().collect();
+    let mut n = INITIAL_N;
+    let mut i = 0u32;
+    let mut bias = INITIAL_BIAS;
+    let mut digits = extended.bytes();
+    let mut next = digits.next();
+    while let Some(first) = next {
+        let old_i = i;
+        let mut w = 1u32;
+        let mut k = BASE;
+        let mut byte = first;
+        loop {
+            let digit = match byte {
+                b'a'..=b'z' => u32::from(byte - b'a'),
+                b'A'..=b'Z' => u32::from(byte - b'A'),
+                b'0'..=b'9' => u32::from(byte - b'0') + 26,
+                _ => return None,
+            };
+            i = i.checked_add(digit.checked_mul(w)?)?;
+            let t = if k <= bias {
+                T_MIN
+            } else if k >= bias + T_MAX {
+                T_MAX
+            } else {
+                k - bias
+            };
+            if digit < t {
+                break;
+            }
+            w = w.checked_mul(BASE - t)?;
+            k += BASE;
+            byte = digits.next()?;
+        }
+        let len = output.len() as u32 + 1;
+        bias = adapt(i - old_i, len, old_i == 0);
+        n = n.checked_add(i / len)?;
+        i %= len;
+        output.insert(i as usize, char::from_u32(n)?);
+        i += 1;
+        next = digits.next();
+    }
+    Some(output.into_iter().collect())
+}
+
+fn adapt(delta: u32, points: u32, first: bool) -> u32 {
+    let mut delta = if first { delta / DAMP } else { delta / 2 };
+    delta += delta / points;
+    let mut k = 0;
+    while delta > ((BASE - T_MIN) * T_MAX) / 2 {
+        delta /= BASE - T_MIN;
+        k += BASE;
+    }
+    k + (BASE - T_MIN + 1) * delta / (delta + SKEW)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_punycode_hosts() {
+        for (host, expected) in [
+            ("xn--mnchen-3ya.de", "münchen.de"),
+            ("www.XN--BCHER-KVA.example", "www.bücher.example"),
+            ("xn--e1afmkfd.xn--80akhbyknj4f", "пример.испытание"),
+            ("xn--wgv71a119e.jp", "日本語.jp"),
+            ("xn--r8jz45g.jp", "例え.jp"),
+        ] {
+            assert_eq!(unicode_host(host).as_deref(), Some(expected), "{host}");
+        }
+        // Nothing to decode, an invalid digit, and a label decoding to ASCII.
+        assert_eq!(unicode_host("example.com"), None);
+        assert_eq!(unicode_host("xn--mnchen-3y!.de"), None);
+        assert_eq!(unicode_host("xn--abc-.com"), None);
+    }
+
+    #[test]
+    fn percent_decodes_paths_and_keeps_the_raw_url() {
+        let url = "https://xn--mnchen-3ya.de/stra%C3%9Fe/caf%c3%a9%20bar?q=%C3%A4#top";
+        assert_eq!(
+            decode_url(url),
+            DecodedUrl {
+                host_unicode: Some("münchen.de".to_string()),
+                path_decoded: Some("/straße/café bar".to_string()),
+            }
+        );
+        assert_eq!(percent_decode("/a%2"), None);
+        assert_eq!(percent_decode("/100%zz%41"), Some("/100%zzA".to_string()));
+        // Latin-1 escapes are not UTF-8; the raw path stays the only form.
+        assert_eq!(percent_decode("/caf%E9"), None);
+        assert_eq!(
+            decode_url("http://example.com/index.html"),
+            DecodedUrl::default()
+        );
+    }
+}
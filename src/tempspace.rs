@@ -0,0 +1,336 @@
+//! Bounded scratch space for derived processing.
+//!
+//! Work that needs files of its own before it can read a carve (merging a
+//! SQLite WAL into a copy of its database, expanding an archive, decompressing
+//! a stream) takes them from a [`TempSpace`] instead of the system temporary
+//! directory. Each run gets one directory under `temp_dir` (the run directory
+//! by default), named [`SCRATCH_PREFIX`] plus the process ID, holding an
+//! [`OWNER_FILE`] with the owner's PID and host like the run lock.
+//!
+//! Users [`TempSpace::reserve`] the bytes they will write before writing
+//! them; a reservation that would take the total above `temp_space_limit` is
+//! refused, so the caller falls back to working without scratch space. A
+//! [`Scratch`] deletes its directory and returns its bytes when dropped, and
+//! the run's directory is removed when the [`TempSpace`] is dropped. A run
+//! that crashed leaves its directory behind; the janitor in [`TempSpace::new`]
+//! removes directories whose owner on this host is no longer running.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::{info, warn};
+
+use crate::runlock::{hostname, pid_alive};
+
+/// Name prefix of the per-run scratch directories under `temp_dir`.
+pub const SCRATCH_PREFIX: &str = ".swiftbeaver-scratch-";
+/// Owner record inside each scratch directory.
+pub const OWNER_FILE: &str = "owner.json";
+
+#[derive(Debug, Error)]
+pub enum TempSpaceError {
+    #[error("temp space limit reached ({requested} bytes requested, {in_use} of {limit} in use)")]
+    Full {
+        requested: u64,
+        in_use: u64,
+        limit: u64,
+    },
+    #[error("io error: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// Owner of a scratch directory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Owner {
+    pid: u32,
+    host: String,
+    run_id: String,
+    started_at: String,
+}
+
+/// Scratch space accounting for the run summary.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct TempSpaceStats {
+    pub limit_bytes: u64,
+    /// Most bytes reserved at once.
+    pub peak_bytes: u64,
+    pub reservations: u64,
+    /// Reservations refused because they would exceed the limit.
+    pub denied: u64,
+    /// Directories of crashed runs removed at startup, and their size.
+    pub stale_dirs_removed: u64,
+    pub stale_bytes_removed: u64,
+}
+
+#[derive(Debug, Default)]
+struct Ledger {
+    in_use: u64,
+    next_id: u64,
+    /// Created on the first reservation, so runs that need no scratch space
+    /// (and dry runs) leave nothing behind.
+    dir_created: bool,
+    stats: TempSpaceStats,
+}
+
+/// The run's scratch directory and its byte budget.
+#[derive(Debug)]
+pub struct TempSpace {
+    dir: PathBuf,
+    owner: Owner,
+    ledger: Mutex<Ledger>,
+}
+
+impl TempSpace {
+    /// Scratch space of `run_id` under `root`, at most `limit` bytes
+    /// (0 refuses every reservation). Directories left under `root` by
+    /// crashed runs are removed first.
+    pub fn new(root: &Path, run_id: &str, limit: u64) -> Self {
+        let pid = std::process::id();
+        let dir = root.join(format!("{SCRATCH_PREFIX}{pid}"));
+        let (stale_dirs_removed, stale_bytes_removed) = sweep(root, &dir);
+        if stale_dirs_removed > 0 {
+            info!(
+                "removed {stale_dirs_removed} scratch directories ({stale_bytes_removed} bytes) left by crashed runs in {}",
+                root.display()
+            );
+        }
+        Self {
+            dir,
+            owner: Owner {
+                pid,
+                host: hostname(),
+                run_id: run_id.to_string(),
+                started_at: Utc::now().to_rfc3339(),
+            },
+            ledger: Mutex::new(Ledger {
+                stats: TempSpaceStats {
+                    limit_bytes: limit,
+                    stale_dirs_removed,
+                    stale_bytes_removed,
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Reserve `bytes` and an empty directory to write them to.
+    pub fn reserve(&self, bytes: u64) -> Result<Scratch<'_>, TempSpaceError> {
+        let id = {
+            let mut ledger = self.ledger.lock().unwrap_or_else(|e| e.into_inner());
+            let limit = ledger.stats.limit_bytes;
+            if ledger.in_use.saturating_add(bytes) > limit {
+                ledger.stats.denied += 1;
+                return Err(TempSpaceError::Full {
+                    requested: bytes,
+                    in_use: ledger.in_use,
+                    limit,
+                });
+            }
+            if !ledger.dir_created {
+                fs::create_dir_all(&self.dir)?;
+                fs::write(
+                    self.dir.join(OWNER_FILE),
+                    serde_json::to_vec_pretty(&self.owner).map_err(io::Error::from)?,
+                )?;
+                ledger.dir_created = true;
+            }
+            ledger.in_use += bytes;
+            ledger.stats.peak_bytes = ledger.stats.peak_bytes.max(ledger.in_use);
+            ledger.stats.reservations += 1;
+            ledger.next_id += 1;
+            ledger.next_id
+        };
+        let scratch = Scratch {
+            space: self,
+            dir: self.dir.join(id.to_string()),
+            bytes,
+        };
+        fs::create_dir(&scratch.dir)?;
+        Ok(scratch)
+    }
+
+    pub fn stats(&self) -> TempSpaceStats {
+        let ledger = self.ledger.lock().unwrap_or_else(|e| e.into_inner());
+        ledger.stats.clone()
+    }
+
+    fn release(&self, bytes: u64) {
+        let mut ledger = self.ledger.lock().unwrap_or_else(|e| e.into_inner());
+        ledger.in_use = ledger.in_use.saturating_sub(bytes);
+    }
+}
+
+impl Drop for TempSpace {
+    fn drop(&mut self) {
+        let created = self
+            .ledger
+            .get_mut()
+            .map(|ledger| ledger.dir_created)
+            .unwrap_or(true);
+        if created && let Err(err) = fs::remove_dir_all(&self.dir) {
+            warn!(
+                "failed to remove scratch directory {}: {err}",
+                self.dir.display()
+            );
+        }
+    }
+}
+
+/// A reserved directory inside the run's scratch space.
+#[derive(Debug)]
+pub struct Scratch<'a> {
+    space: &'a TempSpace,
+    dir: PathBuf,
+    bytes: u64,
+}
+
+impl Scratch<'_> {
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl Drop for Scratch<'_> {
+    fn drop(&mut self) {
+        if let Err(err) = fs::remove_dir_all(&self.dir) {
+            warn!(
+                "failed to remove scratch directory {}: {err}",
+                self.dir.display()
+            );
+        }
+        self.space.release(self.bytes);
+    }
+}
+
+/// Remove scratch directories under `root` whose owner on this host is no
+/// longer running; returns how many and their size. Directories of other
+/// hosts, or without a readable owner, are left alone.
+fn sweep(root: &Path, own: &Path) -> (u64, u64) {
+    let Ok(entries) = fs::read_dir(root) else {
+        return (0, 0);
+    };
+    let host = hostname();
+    let (mut dirs, mut bytes) = (0, 0);
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let is_scratch = entry
+            .file_name()
+            .to_str()
+            .is_some_and(|name| name.starts_with(SCRATCH_PREFIX));
+        if !is_scratch || !path.is_dir() {
+            continue;
+        }
+        let Some(owner) = fs::read(path.join(OWNER_FILE))
+            .ok()
+            .and_then(|data| serde_json::from_slice::<Owner>(&data).ok())
+        else {
+            continue;
+        };
+        // A directory named after this process is a leftover of an earlier
+        // process that had the same PID.
+        let stale = owner.host == host && (path == own || !pid_alive(owner.pid));
+        if !stale {
+            continue;
+        }
+        let size = dir_size(&path);
+        match fs::remove_dir_all(&path) {
+            Ok(()) => {
+                dirs += 1;
+                bytes += size;
+            }
+            Err(err) => warn!(
+                "failed to remove stale scratch directory {}: {err}",
+                path.display()
+            ),
+        }
+    }
+    (dirs, bytes)
+}
+
+fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.file_type() {
+            Ok(kind) if kind.is_dir() => dir_size(&entry.path()),
+            Ok(_) => entry.metadata().map_or(0, |m| m.len()),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reservations_stay_within_the_limit() {
+        let root = tempfile::tempdir().expect("tempdir");
+        let space = TempSpace::new(root.path(), "run", 100);
+        assert!(!space.dir().exists());
+
+        let first = space.reserve(60).expect("first");
+        fs::write(first.dir().join("copy"), [0u8; 60]).expect("write");
+        assert!(matches!(
+            space.reserve(50),
+            Err(TempSpaceError::Full { in_use: 60, .. })
+        ));
+        let first_dir = first.dir().to_path_buf();
+        drop(first);
+        assert!(!first_dir.exists());
+        let second = space.reserve(100).expect("after release");
+        drop(second);
+
+        let stats = space.stats();
+        assert_eq!(stats.peak_bytes, 100);
+        assert_eq!(stats.reservations, 2);
+        assert_eq!(stats.denied, 1);
+        let dir = space.dir().to_path_buf();
+        assert!(dir.join(OWNER_FILE).exists());
+        drop(space);
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn janitor_removes_directories_of_crashed_runs() {
+        let root = tempfile::tempdir().expect("tempdir");
+        let write_owner = |name: &str, pid: u32, host: &str| {
+            let dir = root.path().join(format!("{SCRATCH_PREFIX}{name}"));
+            fs::create_dir_all(dir.join("1")).expect("mkdir");
+            fs::write(dir.join("1").join("copy"), [0u8; 10]).expect("write");
+            let owner = Owner {
+                pid,
+                host: host.to_string(),
+                run_id: "old".to_string(),
+                started_at: Utc::now().to_rfc3339(),
+            };
+            fs::write(dir.join(OWNER_FILE), serde_json::to_vec(&owner).unwrap()).unwrap();
+            dir
+        };
+        let crashed = write_owner("crashed", i32::MAX as u32, &hostname());
+        let running = write_owner("running", 1, &hostname());
+        let remote = write_owner("remote", i32::MAX as u32, "elsewhere");
+        let unrelated = root.path().join("carved");
+        fs::create_dir(&unrelated).expect("mkdir");
+
+        let space = TempSpace::new(root.path(), "run", 0);
+        assert!(!crashed.exists());
+        assert!(running.exists() && remote.exists() && unrelated.exists());
+        let stats = space.stats();
+        assert_eq!(stats.stale_dirs_removed, 1);
+        assert!(stats.stale_bytes_removed >= 10);
+        assert!(matches!(space.reserve(1), Err(TempSpaceError::Full { .. })));
+    }
+}
@@ -3,7 +3,7 @@
 //! Utility functions for the SwiftBeaver crate, including file type filtering
 //! and carve registry building.
 
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::fs::OpenOptions;
 use std::path::Path;
 
@@ -13,7 +13,7 @@ use tracing::info;
 use tracing::{debug, warn};
 
 use crate::carve::{self, CarveRegistry};
-use crate::config::Config;
+use crate::config::{Config, RetentionPolicy};
 use crate::metadata::MetadataBackendKind;
 
 /// Convert CLI metadata backend to internal enum
@@ -225,33 +225,42 @@ pub fn build_carve_registry(cfg: &Config, dry_run: bool) -> Result<CarveRegistry
             "pdf" => {
                 handlers.insert(
                     file_type.id.clone(),
-                    Box::new(carve::pdf::PdfCarveHandler::new(
-                        ext,
-                        file_type.min_size,
-                        file_type.max_size,
-                    )),
+                    Box::new(
+                        carve::pdf::PdfCarveHandler::new(
+                            ext,
+                            file_type.min_size,
+                            file_type.max_size,
+                        )
+                        .with_declared_size_factor(file_type.declared_size_factor),
+                    ),
                 );
             }
             "zip" => {
                 handlers.insert(
                     file_type.id.clone(),
-                    Box::new(carve::zip::ZipCarveHandler::new(
-                        ext,
-                        file_type.min_size,
-                        file_type.max_size,
-                        file_type.require_eocd,
-                        cfg.zip_allowed_kinds.clone(),
-                    )),
+                    Box::new(
+                        carve::zip::ZipCarveHandler::new(
+                            ext,
+                            file_type.min_size,
+                            file_type.max_size,
+                            file_type.require_eocd,
+                            cfg.zip_allowed_kinds.clone(),
+                        )
+                        .with_declared_size_factor(file_type.declared_size_factor),
+                    ),
                 );
             }
             "gzip" => {
                 handlers.insert(
                     file_type.id.clone(),
-                    Box::new(carve::gzip::GzipCarveHandler::new(
-                        ext,
-                        file_type.min_size,
-                        file_type.max_size,
-                    )),
+                    Box::new(
+                        carve::gzip::GzipCarveHandler::new(
+                            ext,
+                            file_type.min_size,
+                            file_type.max_size,
+                        )
+                        .with_decompress(cfg.gzip_decompress),
+                    ),
                 );
             }
             "bzip2" => {
@@ -419,11 +428,24 @@ pub fn build_carve_registry(cfg: &Config, dry_run: bool) -> Result<CarveRegistry
             "ole" => {
                 handlers.insert(
                     file_type.id.clone(),
-                    Box::new(carve::ole::OleCarveHandler::new(
+                    Box::new(
+                        carve::ole::OleCarveHandler::new(
+                            ext,
+                            file_type.min_size,
+                            file_type.max_size,
+                            cfg.ole_allowed_kinds.clone(),
+                        )
+                        .with_declared_size_factor(file_type.declared_size_factor),
+                    ),
+                );
+            }
+            "flac" => {
+                handlers.insert(
+                    file_type.id.clone(),
+                    Box::new(carve::flac::FlacCarveHandler::new(
                         ext,
                         file_type.min_size,
                         file_type.max_size,
-                        cfg.ole_allowed_kinds.clone(),
                     )),
                 );
             }
@@ -507,6 +529,202 @@ pub fn build_carve_registry(cfg: &Config, dry_run: bool) -> Result<CarveRegistry
                     )),
                 );
             }
+            "pcap" | "pcapng" => {
+                let format = if validator == "pcap" {
+                    crate::parsers::pcap::CaptureFormat::Pcap
+                } else {
+                    crate::parsers::pcap::CaptureFormat::PcapNg
+                };
+                handlers.insert(
+                    file_type.id.clone(),
+                    Box::new(carve::pcap::PcapCarveHandler::new(
+                        format,
+                        ext,
+                        file_type.min_size,
+                        file_type.max_size,
+                    )),
+                );
+            }
+            "journald" => {
+                handlers.insert(
+                    file_type.id.clone(),
+                    Box::new(carve::journald::JournaldCarveHandler::new(
+                        ext,
+                        file_type.min_size,
+                        file_type.max_size,
+                    )),
+                );
+            }
+            "boltdb" => {
+                handlers.insert(
+                    file_type.id.clone(),
+                    Box::new(carve::boltdb::BoltDbCarveHandler::new(
+                        ext,
+                        file_type.min_size,
+                        file_type.max_size,
+                    )),
+                );
+            }
+            "regf" => {
+                handlers.insert(
+                    file_type.id.clone(),
+                    Box::new(carve::regf::RegfCarveHandler::new(
+                        ext,
+                        file_type.min_size,
+                        file_type.max_size,
+                    )),
+                );
+            }
+            "evtx" => {
+                handlers.insert(
+                    file_type.id.clone(),
+                    Box::new(carve::evtx::EvtxCarveHandler::new(
+                        ext,
+                        file_type.min_size,
+                        file_type.max_size,
+                    )),
+                );
+            }
+            "prefetch" => {
+                handlers.insert(
+                    file_type.id.clone(),
+                    Box::new(carve::prefetch::PrefetchCarveHandler::new(
+                        ext,
+                        file_type.min_size,
+                        file_type.max_size,
+                    )),
+                );
+            }
+            "lnk" => {
+                handlers.insert(
+                    file_type.id.clone(),
+                    Box::new(carve::lnk::LnkCarveHandler::new(
+                        ext,
+                        file_type.min_size,
+                        file_type.max_size,
+                    )),
+                );
+            }
+            "bplist" => {
+                handlers.insert(
+                    file_type.id.clone(),
+                    Box::new(carve::bplist::BplistCarveHandler::new(
+                        ext,
+                        file_type.min_size,
+                        file_type.max_size,
+                    )),
+                );
+            }
+            "thumbcache" => {
+                handlers.insert(
+                    file_type.id.clone(),
+                    Box::new(carve::thumbcache::ThumbcacheCarveHandler::new(
+                        ext,
+                        file_type.min_size,
+                        file_type.max_size,
+                    )),
+                );
+            }
+            "heif" => {
+                handlers.insert(
+                    file_type.id.clone(),
+                    Box::new(carve::heif::HeifCarveHandler::new(
+                        ext,
+                        file_type.min_size,
+                        file_type.max_size,
+                    )),
+                );
+            }
+            "ese" => {
+                handlers.insert(
+                    file_type.id.clone(),
+                    Box::new(carve::ese::EseCarveHandler::new(
+                        ext,
+                        file_type.min_size,
+                        file_type.max_size,
+                    )),
+                );
+            }
+            "macho" => {
+                handlers.insert(
+                    file_type.id.clone(),
+                    Box::new(carve::macho::MachOCarveHandler::new(
+                        ext,
+                        file_type.min_size,
+                        file_type.max_size,
+                    )),
+                );
+            }
+            "pe" => {
+                handlers.insert(
+                    file_type.id.clone(),
+                    Box::new(carve::pe::PeCarveHandler::new(
+                        ext,
+                        file_type.min_size,
+                        file_type.max_size,
+                    )),
+                );
+            }
+            "pst" => {
+                handlers.insert(
+                    file_type.id.clone(),
+                    Box::new(carve::pst::PstCarveHandler::new(
+                        ext,
+                        file_type.min_size,
+                        file_type.max_size,
+                    )),
+                );
+            }
+            "binarycookies" => {
+                handlers.insert(
+                    file_type.id.clone(),
+                    Box::new(carve::cookies::BinaryCookiesCarveHandler::new(
+                        ext,
+                        file_type.min_size,
+                        file_type.max_size,
+                    )),
+                );
+            }
+            "netscape_cookies" => {
+                handlers.insert(
+                    file_type.id.clone(),
+                    Box::new(carve::cookies::NetscapeCookiesCarveHandler::new(
+                        ext,
+                        file_type.min_size,
+                        file_type.max_size,
+                    )),
+                );
+            }
+            "torrent" => {
+                handlers.insert(
+                    file_type.id.clone(),
+                    Box::new(carve::torrent::TorrentCarveHandler::new(
+                        ext,
+                        file_type.min_size,
+                        file_type.max_size,
+                    )),
+                );
+            }
+            "chrome_cache" => {
+                handlers.insert(
+                    file_type.id.clone(),
+                    Box::new(carve::browser_cache::ChromeCacheCarveHandler::new(
+                        ext,
+                        file_type.min_size,
+                        file_type.max_size,
+                    )),
+                );
+            }
+            "firefox_cache" => {
+                handlers.insert(
+                    file_type.id.clone(),
+                    Box::new(carve::browser_cache::FirefoxCacheCarveHandler::new(
+                        ext,
+                        file_type.min_size,
+                        file_type.max_size,
+                    )),
+                );
+            }
             "footer" => {
                 let headers = decode_patterns(&file_type.header_patterns, &file_type.id, "header")?;
                 let footers = decode_patterns(&file_type.footer_patterns, &file_type.id, "footer")?;
@@ -517,25 +735,41 @@ pub fn build_carve_registry(cfg: &Config, dry_run: bool) -> Result<CarveRegistry
                     );
                     continue;
                 }
-                if footers.is_empty() {
+                if footers.is_empty() && file_type.max_size == 0 {
                     debug!(
-                        "footer handler skipped for file_type={} (no footer patterns)",
+                        "footer handler skipped for file_type={} (no footer patterns or max_size)",
                         file_type.id
                     );
                     continue;
                 }
                 handlers.insert(
                     file_type.id.clone(),
-                    Box::new(carve::footer::FooterCarveHandler::new(
-                        file_type.id.clone(),
-                        ext,
-                        file_type.min_size,
-                        file_type.max_size,
-                        headers,
-                        footers,
-                    )),
+                    Box::new(
+                        carve::footer::FooterCarveHandler::new(
+                            file_type.id.clone(),
+                            ext,
+                            file_type.min_size,
+                            file_type.max_size,
+                            headers,
+                            footers,
+                        )
+                        .with_footer_mode(file_type.footer_mode),
+                    ),
                 );
             }
+            "crashdump" | "hiberfil" | "hbin" | "pool_process" => {
+                if let Some(structure) = carve::memory::MemoryStructure::from_validator(validator) {
+                    handlers.insert(
+                        file_type.id.clone(),
+                        Box::new(carve::memory::MemoryCarveHandler::new(
+                            structure,
+                            ext,
+                            file_type.min_size,
+                            file_type.max_size,
+                        )),
+                    );
+                }
+            }
             _ => {
                 debug!(
                     "no carve handler for file_type={} validator={}",
@@ -545,7 +779,13 @@ pub fn build_carve_registry(cfg: &Config, dry_run: bool) -> Result<CarveRegistry
         }
     }
 
-    Ok(CarveRegistry::new(handlers))
+    let retention = cfg
+        .file_types
+        .iter()
+        .filter(|file_type| file_type.retention != RetentionPolicy::KeepAll)
+        .map(|file_type| (file_type.id.clone(), file_type.retention))
+        .collect();
+    Ok(CarveRegistry::new(handlers).with_retention(retention))
 }
 
 fn decode_patterns(
@@ -589,35 +829,9 @@ pub fn filter_file_types(
             allow.insert(trimmed.to_ascii_lowercase());
         }
 
-        let mut known = HashSet::new();
-        let mut has_zip = false;
-        let mut has_ole = false;
-        for file_type in &cfg.file_types {
-            known.insert(file_type.id.to_ascii_lowercase());
-            if !file_type.validator.trim().is_empty() {
-                known.insert(file_type.validator.to_ascii_lowercase());
-            }
-            if file_type.id.eq_ignore_ascii_case("zip")
-                || file_type.validator.eq_ignore_ascii_case("zip")
-            {
-                has_zip = true;
-            }
-            if file_type.id.eq_ignore_ascii_case("ole")
-                || file_type.validator.eq_ignore_ascii_case("ole")
-            {
-                has_ole = true;
-            }
-        }
-        if has_zip {
-            for kind in ["zip", "docx", "xlsx", "pptx", "odt", "ods", "odp", "epub"] {
-                known.insert(kind.to_string());
-            }
-        }
-        if has_ole {
-            for kind in ["ole", "doc", "xls", "ppt"] {
-                known.insert(kind.to_string());
-            }
-        }
+        let known = known_type_names(cfg);
+        let has_zip = known.contains("zip");
+        let has_ole = known.contains("ole");
 
         for entry in &allow {
             if !known.contains(entry) {
@@ -661,7 +875,7 @@ pub fn filter_file_types(
                 cfg.ole_allowed_kinds = None;
             } else {
                 let mut kinds = Vec::new();
-                for kind in ["doc", "xls", "ppt"] {
+                for kind in ["doc", "xls", "ppt", "encrypted_ooxml"] {
                     if allow.contains(kind) {
                         kinds.push(kind.to_string());
                     }
@@ -684,21 +898,154 @@ pub fn filter_file_types(
     unknown
 }
 
+/// Names accepted in a type filter: file type ids, validators, and the kinds
+/// the ZIP and OLE handlers classify into when those handlers are configured.
+pub fn known_type_names(cfg: &Config) -> BTreeSet<String> {
+    let mut known = BTreeSet::new();
+    for file_type in &cfg.file_types {
+        known.insert(file_type.id.to_ascii_lowercase());
+        if !file_type.validator.trim().is_empty() {
+            known.insert(file_type.validator.to_ascii_lowercase());
+        }
+    }
+    if known.contains("zip") {
+        known.extend(ZIP_KINDS.iter().map(|kind| kind.to_string()));
+    }
+    if known.contains("ole") {
+        known.extend(["ole", "doc", "xls", "ppt"].map(String::from));
+    }
+    known
+}
+
+/// Resolve a `--types` expression into the allow list for
+/// [`filter_file_types`].
+///
+/// Terms are applied left to right: `name` or `+name` adds a file type or a
+/// `type_groups` entry, `-name` removes it. An expression starting with a
+/// removal starts from every configured type. Group members unknown to this
+/// config are skipped; any other unknown name is an error.
+pub fn resolve_type_expression(cfg: &Config, terms: &[String]) -> Result<Vec<String>> {
+    let known = known_type_names(cfg);
+    let mut unknown = Vec::new();
+    let mut selected = BTreeSet::new();
+    let terms: Vec<&str> = terms
+        .iter()
+        .map(|term| term.trim())
+        .filter(|term| !term.is_empty())
+        .collect();
+    if terms.first().is_some_and(|term| term.starts_with('-')) {
+        selected.extend(cfg.file_types.iter().map(|ft| ft.id.to_ascii_lowercase()));
+    }
+    for term in terms {
+        let (remove, name) = match term.strip_prefix('-') {
+            Some(name) => (true, name),
+            None => (false, term.strip_prefix('+').unwrap_or(term)),
+        };
+        let name = name.trim().to_ascii_lowercase();
+        let members: Vec<String> = match cfg.type_groups.get(&name) {
+            Some(members) => members
+                .iter()
+                .map(|member| member.trim().to_ascii_lowercase())
+                .filter(|member| known.contains(member))
+                .collect(),
+            None if known.contains(&name) => vec![name],
+            None => {
+                unknown.push(name);
+                continue;
+            }
+        };
+        for member in members {
+            if remove {
+                remove_type_name(&mut selected, &member);
+            } else {
+                selected.insert(member);
+            }
+        }
+    }
+    if !unknown.is_empty() {
+        return Err(anyhow!(
+            "--types names unknown file types or groups: {} (see `list-types`; groups: {})",
+            unknown.join(", "),
+            cfg.type_groups
+                .keys()
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+    Ok(selected.into_iter().collect())
+}
+
+/// The enabled file type ids, with the ZIP and OLE kinds in parentheses when
+/// they are restricted, e.g. `jpeg, zip (docx, xlsx)`.
+pub fn describe_file_types(cfg: &Config) -> String {
+    cfg.file_types
+        .iter()
+        .map(|file_type| {
+            let kinds = if file_type.id.eq_ignore_ascii_case("zip") {
+                cfg.zip_allowed_kinds.as_ref()
+            } else if file_type.id.eq_ignore_ascii_case("ole") {
+                cfg.ole_allowed_kinds.as_ref()
+            } else {
+                None
+            };
+            match kinds {
+                Some(kinds) => format!("{} ({})", file_type.id, kinds.join(", ")),
+                None => file_type.id.clone(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Removes `name` from a type selection. Removing a family member from a
+/// selection holding its container type keeps the other members, so
+/// `archives,-docx` still carves every other ZIP kind.
+fn remove_type_name(selected: &mut BTreeSet<String>, name: &str) {
+    selected.remove(name);
+    let family: &[&str] = if is_zip_kind(name) {
+        &ZIP_KINDS
+    } else if is_ole_kind(name) {
+        &OLE_KINDS
+    } else {
+        return;
+    };
+    if name == family[0] {
+        for kind in family {
+            selected.remove(*kind);
+        }
+    } else if selected.remove(family[0]) {
+        selected.extend(
+            family[1..]
+                .iter()
+                .filter(|kind| **kind != name)
+                .map(|kind| kind.to_string()),
+        );
+    }
+}
+
+/// The ZIP handler's own type first, then the kinds it classifies into.
+const ZIP_KINDS: [&str; 8] = ["zip", "docx", "xlsx", "pptx", "odt", "ods", "odp", "epub"];
+
+/// The OLE handler's own type first, then the kinds it classifies into.
+const OLE_KINDS: [&str; 5] = ["ole", "doc", "xls", "ppt", "encrypted_ooxml"];
+
 fn is_zip_kind(value: &str) -> bool {
-    matches!(
-        value,
-        "zip" | "docx" | "xlsx" | "pptx" | "odt" | "ods" | "odp" | "epub"
-    )
+    ZIP_KINDS.contains(&value)
 }
 
 fn is_ole_kind(value: &str) -> bool {
-    matches!(value, "ole" | "doc" | "xls" | "ppt")
+    OLE_KINDS.contains(&value)
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{ensure_output_dir, filter_file_types};
-    use crate::config;
+    use super::{
+        build_carve_registry, describe_file_types, ensure_output_dir, filter_file_types,
+        resolve_type_expression,
+    };
+    use crate::carve::CarvedFile;
+    use crate::config::{self, RetentionPolicy};
     use std::fs::File;
     use tempfile::tempdir;
 
@@ -753,6 +1100,79 @@ mod tests {
         assert_eq!(kinds, vec!["docx"]);
     }
 
+    fn terms(expression: &str) -> Vec<String> {
+        expression.split(',').map(String::from).collect()
+    }
+
+    #[test]
+    fn resolves_groups_with_exclusions() {
+        let mut cfg = config::load_config(None).expect("config").config;
+        let allow = resolve_type_expression(&cfg, &terms("images,-gif,+SQLite")).expect("resolve");
+        assert!(allow.contains(&"jpeg".to_string()));
+        assert!(allow.contains(&"sqlite".to_string()));
+        assert!(!allow.contains(&"gif".to_string()));
+
+        let _ = filter_file_types(&mut cfg, Some(&allow), false);
+        assert!(cfg.file_types.iter().all(|ft| ft.id != "gif"));
+        assert!(cfg.file_types.iter().any(|ft| ft.id == "png"));
+    }
+
+    #[test]
+    fn leading_exclusion_starts_from_all_types() {
+        let mut cfg = config::load_config(None).expect("config").config;
+        let total = cfg.file_types.len();
+        let allow = resolve_type_expression(&cfg, &terms("-video,-docx")).expect("resolve");
+        let _ = filter_file_types(&mut cfg, Some(&allow), false);
+        assert_eq!(cfg.file_types.len(), total - 5);
+        let kinds = cfg.zip_allowed_kinds.clone().expect("zip kinds");
+        assert!(!kinds.contains(&"docx".to_string()));
+        assert!(kinds.contains(&"xlsx".to_string()));
+        assert!(describe_file_types(&cfg).contains("zip (xlsx"));
+    }
+
+    #[test]
+    fn rejects_unknown_expression_terms() {
+        let cfg = config::load_config(None).expect("config").config;
+        let err = resolve_type_expression(&cfg, &terms("images,-nope,pictures")).unwrap_err();
+        assert!(err.to_string().contains("nope, pictures"));
+    }
+
+    #[test]
+    fn registry_applies_retention_per_type() {
+        let mut cfg = config::load_config(None).expect("config").config;
+        for file_type in &mut cfg.file_types {
+            file_type.retention = match file_type.id.as_str() {
+                "jpeg" => RetentionPolicy::KeepValidated,
+                "png" => RetentionPolicy::MetadataOnly,
+                _ => RetentionPolicy::KeepAll,
+            };
+        }
+        let registry = build_carve_registry(&cfg, false).expect("registry");
+        let file = |validated| CarvedFile {
+            run_id: "run".to_string(),
+            file_type: "jpeg".to_string(),
+            signature_type: "jpeg".to_string(),
+            path: "jpeg/a.jpg".to_string(),
+            extension: "jpg".to_string(),
+            global_start: 0,
+            global_end: 9,
+            size: 10,
+            md5: None,
+            sha256: None,
+            validated,
+            truncated: !validated,
+            errors: Vec::new(),
+            pattern_id: None,
+        };
+        assert!(registry.keeps("jpeg", &file(true)));
+        assert!(!registry.keeps("jpeg", &file(false)));
+        assert!(!registry.keeps("png", &file(true)));
+        assert!(registry.keeps("gif", &file(false)));
+
+        let parsed: RetentionPolicy = serde_yaml::from_str("metadata_only").expect("parse");
+        assert_eq!(parsed, RetentionPolicy::MetadataOnly);
+    }
+
     #[test]
     fn ensures_output_dir_is_writable() {
         let dir = tempdir().expect("tempdir");
@@ -0,0 +1,673 @@
+//! Backend conformance: identical inputs through every scanner backend and
+//! every metadata sink must give identical results.
+//!
+//! Scanner backends are compared on the hits and spans they report for the
+//! same chunks. GPU backends are included when the crate is built with their
+//! feature and a device is present; the hybrid scanners (`--gpu`) are always
+//! included and fall back to the CPU where no GPU is available.
+//!
+//! Sinks are compared on what they write: each backend's files are read back
+//! and projected onto the fields every backend stores, so a new sink only
+//! needs a reader in [`read_backend`] to be covered.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use parquet::file::reader::{FileReader, SerializedFileReader};
+use parquet::record::Field;
+use serde_json::Value;
+
+use swiftbeaver::carve::{CarvedFile, Truncation};
+use swiftbeaver::chunk::build_chunks;
+use swiftbeaver::config::{self, Config};
+use swiftbeaver::metadata::{
+    self, CarveSizeRecord, ClassificationRecord, EntropyRegion, MetadataBackendKind, RunSummary,
+    SinkIdentity,
+};
+use swiftbeaver::scanner::{self, cpu::CpuScanner};
+use swiftbeaver::strings::{self, artifacts::ArtefactKind, artifacts::StringArtefact};
+use swiftbeaver::{ScanChunk, SignatureScanner, StringScanner};
+
+const CHUNK_SIZE: u64 = 64 * 1024;
+const OVERLAP: u64 = 4096;
+
+/// Deterministic filler so runs are reproducible.
+fn noise(len: usize) -> Vec<u8> {
+    let mut state = 0x2545_F491_4F6C_DD1Du64;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> 24) as u8
+        })
+        .collect()
+}
+
+/// Noise with every configured header pattern planted, some of them across
+/// chunk boundaries.
+fn signature_corpus(cfg: &Config) -> Vec<u8> {
+    let mut data = noise(512 * 1024);
+    let patterns: Vec<Vec<u8>> = cfg
+        .file_types
+        .iter()
+        .flat_map(|ft| &ft.header_patterns)
+        .filter_map(|pattern| hex::decode(pattern.hex.trim()).ok())
+        .collect();
+    let mut offset = 37usize;
+    for (i, pattern) in patterns.iter().enumerate() {
+        let at = if i % 5 == 0 {
+            // Straddle the next chunk boundary.
+            let boundary = (offset as u64 / CHUNK_SIZE + 1) * CHUNK_SIZE;
+            boundary as usize - pattern.len() / 2
+        } else {
+            offset
+        };
+        if at + pattern.len() > data.len() {
+            break;
+        }
+        data[at..at + pattern.len()].copy_from_slice(pattern);
+        offset = at + pattern.len() + 1013;
+    }
+    data
+}
+
+/// Text in every encoding the string scanners handle, with artefact hints.
+fn string_corpus() -> Vec<u8> {
+    let mut data = noise(256 * 1024);
+    let utf16 = |text: &str, little: bool| -> Vec<u8> {
+        text.encode_utf16()
+            .flat_map(|u| {
+                if little {
+                    u.to_le_bytes()
+                } else {
+                    u.to_be_bytes()
+                }
+            })
+            .collect()
+    };
+    let long_url = format!("https://example.com/{}", "a".repeat(3000));
+    let samples: Vec<Vec<u8>> = vec![
+        b"\0visit https://example.com/index.html today\0".to_vec(),
+        b"\0mail alice@example.org or WWW.EXAMPLE.NET\0".to_vec(),
+        b"\0call +1 415 555 0100 ext 12\0".to_vec(),
+        "\0caf\u{e9} na\u{ef}ve r\u{e9}sum\u{e9} bob@example.de\0"
+            .as_bytes()
+            .to_vec(),
+        utf16("\0carol@example.com http://utf16.example\0", true),
+        utf16("\0dave@example.com 0044 20 7946 0958\0", false),
+        format!("\0{long_url}\0").into_bytes(),
+    ];
+    let mut offset = 101usize;
+    for (i, sample) in samples.iter().cycle().take(40).enumerate() {
+        let at = if i % 7 == 3 {
+            let boundary = (offset as u64 / CHUNK_SIZE + 1) * CHUNK_SIZE;
+            boundary as usize - sample.len() / 2
+        } else {
+            offset
+        };
+        if at + sample.len() > data.len() {
+            break;
+        }
+        data[at..at + sample.len()].copy_from_slice(sample);
+        offset = at + sample.len() + 977;
+    }
+    data
+}
+
+fn chunks(data: &[u8]) -> Vec<(ScanChunk, &[u8])> {
+    build_chunks(data.len() as u64, CHUNK_SIZE, OVERLAP)
+        .into_iter()
+        .map(|chunk| {
+            let start = chunk.start as usize;
+            let end = start + chunk.length as usize;
+            (chunk, &data[start..end])
+        })
+        .collect()
+}
+
+type Candidate<T> = (&'static str, anyhow::Result<Box<T>>);
+
+/// Keep the backends that could be built. The CPU and hybrid backends must
+/// always build; GPU backends are skipped when no device is available.
+fn available<T: ?Sized>(candidates: Vec<Candidate<T>>) -> Vec<(&'static str, Box<T>)> {
+    candidates
+        .into_iter()
+        .filter_map(|(name, backend)| match backend {
+            Ok(backend) => Some((name, backend)),
+            Err(err) if name != "cpu" && name != "hybrid" => {
+                eprintln!("skipping {name} backend: {err:#}");
+                None
+            }
+            Err(err) => panic!("{name} backend: {err:#}"),
+        })
+        .collect()
+}
+
+fn signature_backends(cfg: &Config) -> Vec<(&'static str, Box<dyn SignatureScanner>)> {
+    fn boxed<S: SignatureScanner + 'static>(scanner: S) -> Box<dyn SignatureScanner> {
+        Box::new(scanner)
+    }
+    available(vec![
+        ("cpu", CpuScanner::new(cfg).map(boxed)),
+        ("hybrid", scanner::build_signature_scanner(cfg, true)),
+        #[cfg(feature = "gpu-opencl")]
+        (
+            "opencl",
+            scanner::opencl::OpenClScanner::new(cfg).map(boxed),
+        ),
+        #[cfg(feature = "gpu-cuda")]
+        ("cuda", scanner::cuda::CudaScanner::new(cfg).map(boxed)),
+    ])
+}
+
+fn string_backends(cfg: &Config) -> Vec<(&'static str, Box<dyn StringScanner>)> {
+    #[cfg(any(feature = "gpu-opencl", feature = "gpu-cuda"))]
+    fn boxed<S: StringScanner + 'static>(scanner: S) -> Box<dyn StringScanner> {
+        Box::new(scanner)
+    }
+    available(vec![
+        (
+            "cpu",
+            Ok(Box::new(strings::cpu::CpuStringScanner::new(
+                strings::StringMinLens::from_config(cfg),
+                cfg.string_max_len,
+                cfg.string_scan_utf16,
+            ))),
+        ),
+        ("hybrid", strings::build_string_scanner(cfg, true)),
+        #[cfg(feature = "gpu-opencl")]
+        (
+            "opencl",
+            strings::opencl::OpenClStringScanner::new(cfg).map(boxed),
+        ),
+        #[cfg(feature = "gpu-cuda")]
+        (
+            "cuda",
+            strings::cuda::CudaStringScanner::new(cfg).map(boxed),
+        ),
+    ])
+}
+
+/// Assert every backend's output equals the first backend's.
+fn assert_same<T: PartialEq + std::fmt::Debug>(kind: &str, results: &[(&str, Vec<T>)]) {
+    let (reference, expected) = &results[0];
+    assert!(!expected.is_empty(), "{kind}: {reference} found nothing");
+    for (name, actual) in &results[1..] {
+        assert_eq!(
+            actual.len(),
+            expected.len(),
+            "{kind}: {name} and {reference} differ in count"
+        );
+        assert_eq!(actual, expected, "{kind}: {name} differs from {reference}");
+    }
+}
+
+/// Hits as (chunk_id, local_offset, pattern_id, file_type_id).
+type HitRows = Vec<(u64, u64, String, String)>;
+/// Spans as (chunk_id, local_start, length, flags).
+type SpanRows = Vec<(u64, u64, u32, u32)>;
+
+#[test]
+fn signature_scanners_report_identical_hits() {
+    let cfg = config::load_config(None).expect("config").config;
+    let data = signature_corpus(&cfg);
+    let results: Vec<(&str, HitRows)> = signature_backends(&cfg)
+        .into_iter()
+        .map(|(name, backend)| {
+            let mut hits: Vec<_> = chunks(&data)
+                .into_iter()
+                .flat_map(|(chunk, bytes)| backend.scan_chunk(&chunk, bytes))
+                .map(|hit| {
+                    (
+                        hit.chunk_id,
+                        hit.local_offset,
+                        hit.pattern_id,
+                        hit.file_type_id,
+                    )
+                })
+                .collect();
+            hits.sort();
+            (name, hits)
+        })
+        .collect();
+    assert_same("signature hits", &results);
+}
+
+#[test]
+fn string_scanners_report_identical_spans() {
+    let mut cfg = config::load_config(None).expect("config").config;
+    cfg.string_scan_utf16 = true;
+    cfg.string_max_len = 1024;
+    let data = string_corpus();
+    let results: Vec<(&str, SpanRows)> = string_backends(&cfg)
+        .into_iter()
+        .map(|(name, backend)| {
+            // Order is part of the contract: artefacts are extracted in span order.
+            let spans = chunks(&data)
+                .into_iter()
+                .flat_map(|(chunk, bytes)| backend.scan_chunk(&chunk, bytes))
+                .map(|span| (span.chunk_id, span.local_start, span.length, span.flags))
+                .collect();
+            (name, spans)
+        })
+        .collect();
+    assert_same("string spans", &results);
+}
+
+// ---------------------------------------------------------------------------
+// Metadata sinks
+// ---------------------------------------------------------------------------
+
+const RUN_ID: &str = "run_conformance";
+
+type Row = BTreeMap<String, String>;
+
+/// Rows per record category, projected onto the shared fields.
+type Records = BTreeMap<&'static str, Vec<Row>>;
+
+const FILE_FIELDS: &[&str] = &[
+    "run_id",
+    "file_type",
+    "path",
+    "global_start",
+    "global_end",
+    "size",
+    "md5",
+    "sha256",
+    "pattern_id",
+    "validated",
+    "truncated",
+    "evidence_path",
+    "evidence_sha256",
+    "pass_id",
+];
+const STRING_FIELDS: &[&str] = &[
+    "run_id",
+    "artefact_kind",
+    "content",
+    "global_start",
+    "global_end",
+    "source_kind",
+    "source_detail",
+    "host_unicode",
+    "path_decoded",
+    "pass_id",
+];
+const SUMMARY_FIELDS: &[&str] = &[
+    "run_id",
+    "bytes_scanned",
+    "chunks_processed",
+    "hits_found",
+    "files_carved",
+    "string_spans",
+    "artefacts_extracted",
+    "pass_id",
+];
+const CARVE_SIZE_FIELDS: &[&str] = &[
+    "run_id",
+    "file_type",
+    "path",
+    "global_start",
+    "size",
+    "declared_size",
+    "declared_by",
+    "truncation",
+    "pass_id",
+];
+const CLASSIFICATION_FIELDS: &[&str] = &[
+    "run_id",
+    "file_type",
+    "path",
+    "global_start",
+    "classifier",
+    "label",
+    "score",
+    "rank",
+    "pass_id",
+];
+const ENTROPY_FIELDS: &[&str] = &[
+    "run_id",
+    "global_start",
+    "global_end",
+    "entropy",
+    "window_size",
+    "pass_id",
+];
+
+fn write_records(backend: MetadataBackendKind, dir: &Path) {
+    let loaded = config::load_config(None).expect("config");
+    let identity = SinkIdentity {
+        run_id: RUN_ID,
+        tool_version: "0.0.0",
+        config_hash: &loaded.config_hash,
+        evidence_path: Path::new("/cases/image.dd"),
+        evidence_sha256: "ab12",
+        pass_id: 1,
+    };
+    let sink = metadata::build_pass_sink(backend, &loaded.config, &identity, dir).expect("sink");
+
+    for (i, file_type) in ["jpeg", "png", "sqlite", "tar"].iter().enumerate() {
+        let start = 4096 * (i as u64 + 1);
+        sink.record_file(&CarvedFile {
+            run_id: RUN_ID.to_string(),
+            file_type: file_type.to_string(),
+            signature_type: file_type.to_string(),
+            path: format!("{file_type}/{file_type}_{start:012X}.{file_type}"),
+            extension: file_type.to_string(),
+            global_start: start,
+            global_end: start + 99,
+            size: 100,
+            md5: (i % 2 == 0).then(|| "d41d8cd98f00b204e9800998ecf8427e".to_string()),
+            sha256: None,
+            validated: i != 3,
+            truncated: i == 3,
+            errors: Vec::new(),
+            pattern_id: Some(format!("{file_type}_header")),
+        })
+        .expect("record file");
+    }
+    for (kind, content, start) in [
+        (
+            ArtefactKind::Url,
+            "https://xn--mnchen-3ya.example/stra%C3%9Fe/index.html",
+            100u64,
+        ),
+        (ArtefactKind::Email, "alice@example.org", 200),
+        (ArtefactKind::Phone, "+14155550100", 300),
+        (ArtefactKind::DnsQuery, "example.net", 400),
+    ] {
+        sink.record_string(&StringArtefact {
+            run_id: RUN_ID.to_string(),
+            artefact_kind: kind,
+            content: content.to_string(),
+            encoding: "ascii".to_string(),
+            global_start: start,
+            global_end: start + content.len() as u64 - 1,
+            source_kind: "string_span".to_string(),
+            source_detail: format!("chunk=0 span={start}"),
+        })
+        .expect("record string");
+    }
+    for (file_type, declared, truncation) in [
+        ("ole", Some(8192), None),
+        ("zip", Some(1 << 30), Some(Truncation::Policy)),
+        ("pdf", None, Some(Truncation::Evidence)),
+    ] {
+        sink.record_carve_size(&CarveSizeRecord {
+            run_id: RUN_ID.to_string(),
+            file_type: file_type.to_string(),
+            path: format!("{file_type}/{file_type}_000000001000.{file_type}"),
+            global_start: 4096,
+            size: 8192,
+            declared_size: declared,
+            declared_by: declared.map(|_| "fat".to_string()),
+            truncation,
+        })
+        .expect("record carve size");
+    }
+    for (rank, (label, score)) in [("document", 0.875), ("screenshot", 0.125)]
+        .into_iter()
+        .enumerate()
+    {
+        sink.record_classification(&ClassificationRecord {
+            run_id: RUN_ID.to_string(),
+            file_type: "jpeg".to_string(),
+            path: "jpeg/jpeg_000000001000.jpg".to_string(),
+            global_start: 4096,
+            classifier: "doc_class".to_string(),
+            label: label.to_string(),
+            score,
+            rank: rank as u32 + 1,
+        })
+        .expect("record classification");
+    }
+    sink.record_entropy(&EntropyRegion {
+        run_id: RUN_ID.to_string(),
+        global_start: 65536,
+        global_end: 131071,
+        entropy: 7.5,
+        window_size: 4096,
+    })
+    .expect("record entropy");
+    sink.record_run_summary(&RunSummary {
+        run_id: RUN_ID.to_string(),
+        bytes_scanned: 1 << 20,
+        chunks_processed: 16,
+        hits_found: 9,
+        files_carved: 4,
+        string_spans: 30,
+        artefacts_extracted: 4,
+    })
+    .expect("record summary");
+    sink.flush().expect("flush");
+}
+
+fn project(row: &Row, fields: &[&str]) -> Row {
+    fields
+        .iter()
+        .map(|field| {
+            let value = row.get(*field).cloned().unwrap_or_default();
+            (field.to_string(), value)
+        })
+        .collect()
+}
+
+fn json_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn read_jsonl(path: &Path) -> Vec<Row> {
+    let Ok(file) = File::open(path) else {
+        return Vec::new();
+    };
+    BufReader::new(file)
+        .lines()
+        .map(|line| {
+            let value: Value = serde_json::from_str(&line.expect("line")).expect("json");
+            value
+                .as_object()
+                .expect("object")
+                .iter()
+                .map(|(k, v)| (k.clone(), json_text(v)))
+                .collect()
+        })
+        .collect()
+}
+
+fn read_csv(path: &Path) -> Vec<Row> {
+    let Ok(mut reader) = csv::Reader::from_path(path) else {
+        return Vec::new();
+    };
+    let headers = reader.headers().expect("headers").clone();
+    reader
+        .records()
+        .map(|record| {
+            headers
+                .iter()
+                .zip(record.expect("record").iter())
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        })
+        .collect()
+}
+
+fn parquet_text(field: &Field) -> String {
+    match field {
+        Field::Null => String::new(),
+        Field::Str(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn read_parquet(path: &Path) -> Vec<Row> {
+    let Ok(file) = File::open(path) else {
+        return Vec::new();
+    };
+    let reader = SerializedFileReader::new(file).expect("parquet reader");
+    reader
+        .get_row_iter(None)
+        .expect("row iter")
+        .map(|row| {
+            row.expect("row")
+                .get_column_iter()
+                .map(|(k, v)| (k.clone(), parquet_text(v)))
+                .collect()
+        })
+        .collect()
+}
+
+/// Read a sink's output back into [`Records`], renaming backend-specific
+/// columns to the shared field names.
+fn read_backend(backend: MetadataBackendKind, dir: &Path) -> Records {
+    let mut records = Records::new();
+    match backend {
+        MetadataBackendKind::Jsonl | MetadataBackendKind::Csv => {
+            let meta = dir.join("metadata");
+            let (read, ext): (fn(&Path) -> Vec<Row>, &str) =
+                if matches!(backend, MetadataBackendKind::Jsonl) {
+                    (read_jsonl, "jsonl")
+                } else {
+                    (read_csv, "csv")
+                };
+            let file = |name: &str| meta.join(format!("{name}.{ext}"));
+            let mut strings = read(&file("string_artefacts"));
+            // JSONL spells kinds as variant names (`DnsQuery`); the others use labels.
+            for row in &mut strings {
+                if let Some(kind) = row.get_mut("artefact_kind") {
+                    *kind = snake_case(kind);
+                }
+            }
+            records.insert("files", read(&file("carved_files")));
+            records.insert("strings", strings);
+            records.insert("run_summary", read(&file("run_summary")));
+            records.insert("carve_sizes", read(&file("carve_sizes")));
+            records.insert("classifications", read(&file("classifications")));
+            records.insert("entropy", read(&file("entropy_regions")));
+        }
+        MetadataBackendKind::Parquet => {
+            let parquet = dir.join("parquet");
+            let files = std::fs::read_dir(&parquet)
+                .expect("parquet dir")
+                .map(|entry| entry.expect("entry").path())
+                .filter(|path| file_name(path).starts_with("files_"))
+                .flat_map(|path| read_parquet(&path))
+                .map(|mut row| {
+                    if let Some(path) = row.remove("carved_path") {
+                        row.insert("path".to_string(), path);
+                    }
+                    row
+                })
+                .collect();
+            records.insert("files", files);
+            let mut strings = Vec::new();
+            for (name, kind, column) in [
+                ("artefacts_urls", Some("url"), "url"),
+                ("artefacts_emails", Some("email"), "email"),
+                ("artefacts_phones", Some("phone"), "phone_raw"),
+                ("artefacts_network", None, "value"),
+            ] {
+                for mut row in read_parquet(&parquet.join(format!("{name}.parquet"))) {
+                    if let Some(content) = row.remove(column) {
+                        row.insert("content".to_string(), content);
+                    }
+                    if let Some(kind) = kind {
+                        row.insert("artefact_kind".to_string(), kind.to_string());
+                    }
+                    strings.push(row);
+                }
+            }
+            records.insert("strings", strings);
+            records.insert(
+                "run_summary",
+                read_parquet(&parquet.join("run_summary.parquet")),
+            );
+            records.insert(
+                "carve_sizes",
+                read_parquet(&parquet.join("carve_sizes.parquet")),
+            );
+            records.insert(
+                "classifications",
+                read_parquet(&parquet.join("classifications.parquet")),
+            );
+            records.insert(
+                "entropy",
+                read_parquet(&parquet.join("entropy_regions.parquet")),
+            );
+        }
+    }
+    for (category, fields) in [
+        ("files", FILE_FIELDS),
+        ("strings", STRING_FIELDS),
+        ("run_summary", SUMMARY_FIELDS),
+        ("carve_sizes", CARVE_SIZE_FIELDS),
+        ("classifications", CLASSIFICATION_FIELDS),
+        ("entropy", ENTROPY_FIELDS),
+    ] {
+        let rows = records.get_mut(category).expect("category");
+        *rows = rows.iter().map(|row| project(row, fields)).collect();
+        rows.sort();
+    }
+    records
+}
+
+fn snake_case(name: &str) -> String {
+    let mut out = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i > 0 {
+                out.push('_');
+            }
+            out.push(c.to_ascii_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+#[test]
+fn metadata_sinks_store_equivalent_records() {
+    let tmp = tempfile::tempdir().expect("tempdir");
+    let backends = [
+        ("jsonl", MetadataBackendKind::Jsonl),
+        ("csv", MetadataBackendKind::Csv),
+        ("parquet", MetadataBackendKind::Parquet),
+    ];
+    let results: Vec<(&str, Records)> = backends
+        .iter()
+        .map(|&(name, backend)| {
+            let dir: PathBuf = tmp.path().join(name);
+            write_records(backend, &dir);
+            (name, read_backend(backend, &dir))
+        })
+        .collect();
+
+    let (reference, expected) = &results[0];
+    assert_eq!(expected["files"].len(), 4);
+    assert_eq!(expected["strings"].len(), 4);
+    assert_eq!(expected["run_summary"].len(), 1);
+    assert_eq!(expected["carve_sizes"].len(), 3);
+    assert_eq!(expected["classifications"].len(), 2);
+    assert_eq!(expected["entropy"].len(), 1);
+    for (name, actual) in &results[1..] {
+        for (category, rows) in expected {
+            assert_eq!(
+                &actual[category], rows,
+                "{category}: {name} differs from {reference}"
+            );
+        }
+    }
+}
@@ -7,6 +7,7 @@ use serde_json::Value;
 use swiftbeaver::checkpoint;
 use swiftbeaver::config;
 use swiftbeaver::evidence::RawFileSource;
+use swiftbeaver::inputs;
 use swiftbeaver::metadata::{self, MetadataBackendKind};
 use swiftbeaver::pipeline;
 use swiftbeaver::scanner;
@@ -86,6 +87,7 @@ fn resume_from_checkpoint_skips_scanned_chunks() {
     let checkpoint_cfg = Some(pipeline::CheckpointConfig {
         path: checkpoint_path.clone(),
         resume: None,
+        input_files: Vec::new(),
     });
 
     let cancel_flag = Arc::new(AtomicBool::new(false));
@@ -136,6 +138,7 @@ fn resume_from_checkpoint_skips_scanned_chunks() {
     let checkpoint_cfg = Some(pipeline::CheckpointConfig {
         path: checkpoint_path,
         resume: Some(resume_state),
+        input_files: Vec::new(),
     });
 
     let cancel_flag = Arc::new(AtomicBool::new(false));
@@ -166,3 +169,165 @@ fn resume_from_checkpoint_skips_scanned_chunks() {
         .expect("global_start");
     assert!(start >= 64, "expected carved file from resumed chunk");
 }
+
+#[test]
+fn resume_refuses_changed_input_files() {
+    let mut data = vec![0u8; 160];
+    insert_bytes(&mut data, 96, &minimal_jpeg());
+
+    let temp_dir = tempfile::tempdir().expect("tempdir");
+    let input_path = temp_dir.path().join("input.bin");
+    fs::write(&input_path, data).expect("write input");
+    let signatures = temp_dir.path().join("scalpel.conf");
+    fs::write(&signatures, "jpg y 200000 \\xff\\xd8\\xff \\xff\\xd9\n").expect("write signatures");
+    let hash = || {
+        vec![
+            inputs::InputFile::hash(inputs::InputKind::Signatures, &signatures)
+                .expect("hash signatures"),
+        ]
+    };
+
+    let loaded = config::load_config(None).expect("config");
+    let mut cfg = loaded.config;
+    cfg.run_id = "checkpoint_inputs_test".to_string();
+    let checkpoint_path = temp_dir.path().join("checkpoint.json");
+
+    let run = |run_dir: &str, checkpoint_cfg: pipeline::CheckpointConfig, max_chunks| {
+        let evidence = RawFileSource::open(&input_path).expect("evidence");
+        let run_output_dir = temp_dir.path().join(run_dir);
+        fs::create_dir_all(&run_output_dir).expect("output dir");
+        let meta_sink = metadata::build_sink(
+            MetadataBackendKind::Jsonl,
+            &cfg,
+            &cfg.run_id,
+            env!("CARGO_PKG_VERSION"),
+            &loaded.config_hash,
+            &input_path,
+            "",
+            &run_output_dir,
+        )
+        .expect("metadata sink");
+        let sig_scanner = scanner::build_signature_scanner(&cfg, false).expect("scanner");
+        pipeline::run_pipeline_with_cancel(
+            &cfg,
+            Arc::new(evidence),
+            Arc::from(sig_scanner),
+            None,
+            meta_sink,
+            &run_output_dir,
+            1,
+            64,
+            0,
+            None,
+            max_chunks,
+            Arc::new(util::build_carve_registry(&cfg, false).expect("registry")),
+            Arc::new(AtomicBool::new(false)),
+            None,
+            Some(checkpoint_cfg),
+        )
+    };
+
+    run(
+        "run1",
+        pipeline::CheckpointConfig {
+            path: checkpoint_path.clone(),
+            resume: None,
+            input_files: hash(),
+        },
+        Some(1),
+    )
+    .expect("pipeline");
+    let resume_state = checkpoint::load_checkpoint(&checkpoint_path).expect("load checkpoint");
+    assert_eq!(resume_state.input_files, hash());
+
+    fs::write(&signatures, "png y 200000 \\x89PNG\n").expect("rewrite signatures");
+    let err = run(
+        "run2",
+        pipeline::CheckpointConfig {
+            path: checkpoint_path,
+            resume: Some(resume_state),
+            input_files: hash(),
+        },
+        None,
+    )
+    .expect_err("resume with changed signatures");
+    assert!(
+        format!("{err:#}").contains("changed since the checkpoint"),
+        "{err:#}"
+    );
+}
+
+#[test]
+fn resume_refuses_changed_evidence() {
+    let mut data = vec![0u8; 160];
+    insert_bytes(&mut data, 96, &minimal_jpeg());
+
+    let temp_dir = tempfile::tempdir().expect("tempdir");
+    let input_path = temp_dir.path().join("input.bin");
+    fs::write(&input_path, &data).expect("write input");
+
+    let loaded = config::load_config(None).expect("config");
+    let mut cfg = loaded.config;
+    cfg.run_id = "checkpoint_evidence_test".to_string();
+    let checkpoint_path = temp_dir.path().join("checkpoint.json");
+
+    let run = |cfg: &config::Config,
+               run_dir: &str,
+               resume: Option<checkpoint::CheckpointState>,
+               max_chunks| {
+        let evidence = RawFileSource::open(&input_path).expect("evidence");
+        let run_output_dir = temp_dir.path().join(run_dir);
+        fs::create_dir_all(&run_output_dir).expect("output dir");
+        let meta_sink = metadata::build_sink(
+            MetadataBackendKind::Jsonl,
+            cfg,
+            &cfg.run_id,
+            env!("CARGO_PKG_VERSION"),
+            &loaded.config_hash,
+            &input_path,
+            "",
+            &run_output_dir,
+        )
+        .expect("metadata sink");
+        let sig_scanner = scanner::build_signature_scanner(cfg, false).expect("scanner");
+        pipeline::run_pipeline_with_cancel(
+            cfg,
+            Arc::new(evidence),
+            Arc::from(sig_scanner),
+            None,
+            meta_sink,
+            &run_output_dir,
+            1,
+            64,
+            0,
+            None,
+            max_chunks,
+            Arc::new(util::build_carve_registry(cfg, false).expect("registry")),
+            Arc::new(AtomicBool::new(false)),
+            None,
+            Some(pipeline::CheckpointConfig {
+                path: checkpoint_path.clone(),
+                resume,
+                input_files: Vec::new(),
+            }),
+        )
+    };
+
+    run(&cfg, "run1", None, Some(1)).expect("pipeline");
+    let resume_state = checkpoint::load_checkpoint(&checkpoint_path).expect("load checkpoint");
+    assert_eq!(resume_state.evidence_samples.len(), 1);
+    assert_eq!(resume_state.evidence_samples[0].len, 64);
+
+    // A byte of the scanned first chunk differs in the "same" image.
+    data[10] = 0xAA;
+    fs::write(&input_path, &data).expect("rewrite input");
+    let err = run(&cfg, "run2", Some(resume_state.clone()), None)
+        .expect_err("resume against changed evidence");
+    assert!(
+        format!("{err:#}").contains("evidence changed since the checkpoint"),
+        "{err:#}"
+    );
+
+    cfg.resume_allow_evidence_change = true;
+    run(&cfg, "run3", Some(resume_state), None).expect("resume allowed with a warning");
+}
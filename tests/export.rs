@@ -0,0 +1,125 @@
+//! Exports read the same records back from every metadata backend.
+
+use std::path::Path;
+
+use chrono::NaiveDateTime;
+use serde_json::Value;
+
+use swiftbeaver::config;
+use swiftbeaver::export::{self, ExportFormat};
+use swiftbeaver::metadata::{self, MetadataBackendKind, SinkIdentity};
+use swiftbeaver::parsers::exif::PhotoDeviceRecord;
+
+const RUN_ID: &str = "export_run";
+
+fn photo(path: &str, start: u64, position: Option<(f64, f64)>, taken: &str) -> PhotoDeviceRecord {
+    PhotoDeviceRecord {
+        run_id: RUN_ID.to_string(),
+        source_file: path.to_string(),
+        global_start: start,
+        make: Some("Canon".to_string()),
+        model: Some("EOS R5".to_string()),
+        serial: None,
+        lens: None,
+        lens_serial: None,
+        taken_at: NaiveDateTime::parse_from_str(taken, "%Y-%m-%d %H:%M:%S").ok(),
+        latitude: position.map(|(lat, _)| lat),
+        longitude: position.map(|(_, lon)| lon),
+        device_id: None,
+        session_id: None,
+    }
+}
+
+fn write_run(backend: MetadataBackendKind, dir: &Path) {
+    let loaded = config::load_config(None).expect("config");
+    let identity = SinkIdentity {
+        run_id: RUN_ID,
+        tool_version: "0.0.0",
+        config_hash: &loaded.config_hash,
+        evidence_path: Path::new("/cases/image.dd"),
+        evidence_sha256: "ab12",
+        pass_id: 1,
+    };
+    let sink = metadata::build_pass_sink(backend, &loaded.config, &identity, dir).expect("sink");
+    for record in [
+        photo(
+            "jpeg/jpeg_000000002000.jpg",
+            0x2000,
+            Some((-33.865, 151.21)),
+            "2024-05-01 10:15:30",
+        ),
+        photo(
+            "jpeg/jpeg_000000001000.jpg",
+            0x1000,
+            None,
+            "2024-05-01 09:00:00",
+        ),
+        photo(
+            "jpeg/jpeg_000000003000.jpg",
+            0x3000,
+            Some((48.5, 2.25)),
+            "not a time",
+        ),
+    ] {
+        sink.record_photo_device(&record).expect("record photo");
+    }
+    sink.flush().expect("flush");
+}
+
+#[test]
+fn exports_locations_and_timeline_from_every_backend() {
+    for backend in [
+        MetadataBackendKind::Jsonl,
+        MetadataBackendKind::Csv,
+        MetadataBackendKind::Parquet,
+    ] {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let run_dir = dir.path().join(RUN_ID);
+        std::fs::create_dir_all(&run_dir).expect("run dir");
+        write_run(backend, &run_dir);
+
+        let report = export::export_run(&run_dir, ExportFormat::Geojson, None).expect("geojson");
+        assert_eq!(report.records, 2, "{backend:?}");
+        assert_eq!(report.path, run_dir.join("exports/locations.geojson"));
+        let geojson: Value =
+            serde_json::from_str(&std::fs::read_to_string(&report.path).expect("read"))
+                .expect("json");
+        let coordinates: Vec<&Value> = geojson["features"]
+            .as_array()
+            .expect("features")
+            .iter()
+            .map(|f| &f["geometry"]["coordinates"])
+            .collect();
+        assert_eq!(
+            coordinates,
+            [
+                &serde_json::json!([151.21, -33.865]),
+                &serde_json::json!([2.25, 48.5])
+            ],
+            "{backend:?}"
+        );
+
+        let kml_path = dir.path().join("case.kml");
+        let report = export::export_run(&run_dir, ExportFormat::Kml, Some(&kml_path)).expect("kml");
+        let kml = std::fs::read_to_string(&report.path).expect("read");
+        assert_eq!(kml.matches("<Placemark>").count(), 2, "{backend:?}");
+        assert!(kml.contains("<name>export_run</name>"));
+        assert!(kml.contains("<TimeStamp><when>2024-05-01T10:15:30</when></TimeStamp>"));
+
+        let report =
+            export::export_run(&run_dir, ExportFormat::Timesketch, None).expect("timeline");
+        assert_eq!(report.records, 2, "{backend:?}");
+        let lines: Vec<Value> = std::fs::read_to_string(&report.path)
+            .expect("read")
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("json line"))
+            .collect();
+        assert_eq!(lines[0]["datetime"], "2024-05-01T09:00:00.000000+00:00");
+        assert_eq!(lines[0]["timestamp_desc"], "Photo taken");
+        assert_eq!(
+            lines[0]["message"],
+            "Canon EOS R5 jpeg/jpeg_000000001000.jpg"
+        );
+        assert_eq!(lines[1]["data_type"], "swiftbeaver:photo_devices");
+    }
+}
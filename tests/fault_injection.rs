@@ -0,0 +1,263 @@
+//! Pipeline robustness under injected faults.
+//!
+//! Requires the `fault-injection` feature:
+//! `cargo test --features fault-injection --test fault_injection`
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use swiftbeaver::carve::{CarveError, CarveHandler, CarveRegistry, CarvedFile, ExtractionContext};
+use swiftbeaver::config::{self, Config};
+use swiftbeaver::evidence::{EvidenceSource, RawFileSource};
+use swiftbeaver::fault::{
+    FaultSchedule, FlakyEvidence, FlakySink, PanickingCarveHandler, PanickingScanner,
+};
+use swiftbeaver::metadata::{self, MetadataBackendKind, MetadataSink};
+use swiftbeaver::pipeline::{self, CheckpointConfig, PipelineStats};
+use swiftbeaver::scanner::{self, NormalizedHit, SignatureScanner};
+use swiftbeaver::util;
+
+const CHUNK_SIZE: u64 = 4096;
+const CHUNKS: usize = 8;
+const PIPELINE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Handler that accepts every hit without writing anything.
+struct NoopHandler;
+
+impl CarveHandler for NoopHandler {
+    fn file_type(&self) -> &str {
+        "jpeg"
+    }
+
+    fn extension(&self) -> &str {
+        "jpg"
+    }
+
+    fn process_hit(
+        &self,
+        _hit: &NormalizedHit,
+        _ctx: &ExtractionContext,
+    ) -> Result<Option<CarvedFile>, CarveError> {
+        Ok(None)
+    }
+}
+
+struct Fixture {
+    _temp_dir: tempfile::TempDir,
+    input_path: PathBuf,
+    run_output_dir: PathBuf,
+    checkpoint_path: PathBuf,
+    cfg: Config,
+    config_hash: String,
+}
+
+/// Evidence with a JPEG header every 1 KiB, spanning several chunks.
+fn fixture(run_id: &str) -> Fixture {
+    let temp_dir = tempfile::tempdir().expect("tempdir");
+    let mut data = vec![0u8; CHUNK_SIZE as usize * CHUNKS];
+    for offset in (0..data.len()).step_by(1024) {
+        data[offset..offset + 4].copy_from_slice(&[0xFF, 0xD8, 0xFF, 0xE0]);
+    }
+    let input_path = temp_dir.path().join("input.bin");
+    fs::write(&input_path, data).expect("write input");
+    let run_output_dir = temp_dir.path().join("run");
+    fs::create_dir_all(&run_output_dir).expect("output dir");
+
+    let loaded = config::load_config(None).expect("config");
+    let mut cfg = loaded.config;
+    cfg.run_id = run_id.to_string();
+    Fixture {
+        checkpoint_path: temp_dir.path().join("checkpoint.json"),
+        _temp_dir: temp_dir,
+        input_path,
+        run_output_dir,
+        cfg,
+        config_hash: loaded.config_hash,
+    }
+}
+
+impl Fixture {
+    fn evidence(&self) -> Arc<dyn EvidenceSource> {
+        Arc::new(RawFileSource::open(&self.input_path).expect("evidence"))
+    }
+
+    fn sink(&self) -> Box<dyn MetadataSink> {
+        metadata::build_sink(
+            MetadataBackendKind::Jsonl,
+            &self.cfg,
+            &self.cfg.run_id,
+            env!("CARGO_PKG_VERSION"),
+            &self.config_hash,
+            &self.input_path,
+            "",
+            &self.run_output_dir,
+        )
+        .expect("metadata sink")
+    }
+
+    fn scanner(&self) -> Arc<dyn SignatureScanner> {
+        Arc::from(scanner::build_signature_scanner(&self.cfg, false).expect("scanner"))
+    }
+
+    fn registry(&self) -> Arc<CarveRegistry> {
+        Arc::new(util::build_carve_registry(&self.cfg, false).expect("registry"))
+    }
+
+    /// Run the pipeline on a separate thread and fail the test if it hangs.
+    fn run(
+        &self,
+        evidence: Arc<dyn EvidenceSource>,
+        sig_scanner: Arc<dyn SignatureScanner>,
+        sink: Box<dyn MetadataSink>,
+        registry: Arc<CarveRegistry>,
+        max_chunks: Option<u64>,
+    ) -> anyhow::Result<PipelineStats> {
+        let cfg = self.cfg.clone();
+        let run_output_dir = self.run_output_dir.clone();
+        let checkpoint = CheckpointConfig {
+            path: self.checkpoint_path.clone(),
+            resume: None,
+            input_files: Vec::new(),
+        };
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let result = pipeline::run_pipeline_with_cancel(
+                &cfg,
+                evidence,
+                sig_scanner,
+                None,
+                sink,
+                &run_output_dir,
+                2,
+                CHUNK_SIZE,
+                0,
+                None,
+                max_chunks,
+                registry,
+                Arc::new(AtomicBool::new(false)),
+                None,
+                Some(checkpoint),
+            );
+            let _ = tx.send(result);
+        });
+        rx.recv_timeout(PIPELINE_TIMEOUT)
+            .expect("pipeline hung under injected faults")
+    }
+}
+
+fn panicking_registry(schedule: Arc<FaultSchedule>) -> Arc<CarveRegistry> {
+    let mut handlers: HashMap<String, Box<dyn CarveHandler>> = HashMap::new();
+    handlers.insert(
+        "jpeg".to_string(),
+        Box::new(PanickingCarveHandler::new(Box::new(NoopHandler), schedule)),
+    );
+    Arc::new(CarveRegistry::new(handlers))
+}
+
+fn checkpoint_exists(path: &Path) -> bool {
+    path.is_file() && fs::metadata(path).map(|m| m.len() > 0).unwrap_or(false)
+}
+
+#[test]
+fn read_faults_skip_chunks_and_are_counted() {
+    let fx = fixture("fault_reads");
+    let schedule = Arc::new(FaultSchedule::every(3));
+    let evidence: Arc<dyn EvidenceSource> =
+        Arc::new(FlakyEvidence::new(fx.evidence(), schedule.clone()));
+
+    let stats = fx
+        .run(evidence, fx.scanner(), fx.sink(), fx.registry(), None)
+        .expect("pipeline should survive read faults");
+
+    assert!(stats.read_errors > 0, "{stats:?}");
+    assert_eq!(
+        stats.chunks_processed + stats.read_errors,
+        CHUNKS as u64,
+        "{stats:?}"
+    );
+    assert!(stats.hits_found > 0);
+    assert!(schedule.injected() >= stats.read_errors);
+}
+
+#[test]
+fn sink_faults_are_counted_without_aborting() {
+    let fx = fixture("fault_sink");
+    let schedule = Arc::new(FaultSchedule::percent(50, 42));
+    let sink: Box<dyn MetadataSink> = Box::new(FlakySink::new(fx.sink(), schedule.clone()));
+
+    let stats = fx
+        .run(fx.evidence(), fx.scanner(), sink, fx.registry(), None)
+        .expect("pipeline should survive sink faults");
+
+    assert_eq!(stats.chunks_processed, CHUNKS as u64);
+    assert_eq!(stats.metadata_errors, schedule.injected(), "{stats:?}");
+    assert!(schedule.calls() > 0);
+}
+
+#[test]
+fn carve_worker_panics_are_contained() {
+    let fx = fixture("fault_carve_panic");
+    let schedule = Arc::new(FaultSchedule::every(5));
+
+    let stats = fx
+        .run(
+            fx.evidence(),
+            fx.scanner(),
+            fx.sink(),
+            panicking_registry(schedule.clone()),
+            None,
+        )
+        .expect("pipeline should finish despite carve panics");
+
+    assert_eq!(stats.chunks_processed, CHUNKS as u64);
+    assert!(schedule.injected() > 0);
+    assert_eq!(stats.worker_panics, schedule.injected(), "{stats:?}");
+    // Workers restart after each panic, so every hit still reaches a handler.
+    assert_eq!(schedule.calls(), stats.hits_found, "{stats:?}");
+}
+
+#[test]
+fn scan_worker_panic_is_contained() {
+    let fx = fixture("fault_scan_panic");
+    // Only the second scanned chunk panics; its hits are lost, the rest are scanned.
+    let schedule = Arc::new(FaultSchedule::nth(2));
+    let scanner: Arc<dyn SignatureScanner> =
+        Arc::new(PanickingScanner::new(fx.scanner(), schedule.clone()));
+
+    let stats = fx
+        .run(fx.evidence(), scanner, fx.sink(), fx.registry(), None)
+        .expect("pipeline should finish after a scan panic");
+
+    assert_eq!(schedule.injected(), 1);
+    assert_eq!(stats.worker_panics, 1);
+    assert_eq!(stats.chunks_processed, CHUNKS as u64);
+    assert_eq!(schedule.calls(), CHUNKS as u64);
+    assert!(stats.hits_found > 0);
+}
+
+#[test]
+fn checkpoint_written_when_faults_and_limits_combine() {
+    let fx = fixture("fault_checkpoint");
+    let evidence: Arc<dyn EvidenceSource> = Arc::new(FlakyEvidence::new(
+        fx.evidence(),
+        Arc::new(FaultSchedule::every(2)),
+    ));
+    let sink: Box<dyn MetadataSink> = Box::new(FlakySink::new(
+        fx.sink(),
+        Arc::new(FaultSchedule::percent(30, 9)),
+    ));
+
+    let stats = fx
+        .run(evidence, fx.scanner(), sink, fx.registry(), Some(4))
+        .expect("pipeline");
+
+    assert!(stats.read_errors > 0);
+    assert!(checkpoint_exists(&fx.checkpoint_path));
+    let state = swiftbeaver::checkpoint::load_checkpoint(&fx.checkpoint_path).expect("load");
+    assert_eq!(state.next_offset, 4 * CHUNK_SIZE);
+}
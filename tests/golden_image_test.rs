@@ -102,16 +102,19 @@ fn derive_extension(path: &str) -> String {
 #[cfg(feature = "ewf")]
 fn cli_opts_for_input(path: PathBuf) -> CliOptions {
     CliOptions {
-        input: path,
+        command: None,
+        input: Some(path),
         output: PathBuf::from("./output"),
         config_path: None,
         gpu: false,
         workers: 2,
         chunk_size_mib: 64,
+        stream_window_mib: 2048,
         overlap_kib: None,
         metadata_backend: MetadataBackend::Jsonl,
         log_format: swiftbeaver::cli::LogFormat::Text,
         progress_interval_secs: 0,
+        profile: None,
         scan_strings: false,
         scan_utf16: false,
         scan_urls: false,
@@ -120,11 +123,32 @@ fn cli_opts_for_input(path: PathBuf) -> CliOptions {
         no_scan_emails: false,
         scan_phones: false,
         no_scan_phones: false,
+        scan_logs: false,
+        no_scan_logs: false,
+        scan_system: false,
+        no_scan_system: false,
+        scan_credentials: false,
+        no_scan_credentials: false,
+        scan_p2p: false,
+        no_scan_p2p: false,
         string_min_len: None,
+        string_min_len_utf8: None,
+        string_min_len_utf16: None,
+        strings_gaps_only: false,
         scan_entropy: false,
         entropy_window_bytes: None,
         entropy_threshold: None,
+        hit_prefilter: false,
+        gzip_decompress: false,
         scan_sqlite_pages: false,
+        sqlite_app_catalog: None,
+        scan_shadow_copies: false,
+        ntfs: false,
+        unallocated_only: false,
+        volume_locations: false,
+        carve_fsync: None,
+        pipeline_mode: None,
+        evidence_transform: None,
         max_bytes: None,
         max_chunks: None,
         max_files: None,
@@ -132,14 +156,33 @@ fn cli_opts_for_input(path: PathBuf) -> CliOptions {
         max_open_files: None,
         checkpoint_path: None,
         resume_from: None,
+        allow_evidence_change: false,
         evidence_sha256: None,
         compute_evidence_sha256: false,
+        verify_evidence_hash: false,
+        mmap: false,
         disable_zip: false,
         types: None,
         enable_types: None,
+        scalpel_conf: None,
         dry_run: false,
+        force_unlock: false,
+        append_to: None,
+        result_path: None,
+        carve_timeout_secs: None,
+        qa_sample: None,
+        qa_sample_seed: None,
+        export_slack: None,
+        temp_dir: None,
+        temp_space_limit: None,
+        hit_context_window: None,
+        encrypt_to: None,
         validate_carved: false,
         remove_invalid: false,
+        trace_offset: None,
+        trace_window: None,
+        profile_throughput: false,
+        metadata_snapshot_interval: None,
     }
 }
 
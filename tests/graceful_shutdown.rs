@@ -1,13 +1,19 @@
+use std::collections::HashMap;
 use std::fs;
 use std::sync::Arc;
-use std::sync::atomic::AtomicBool;
+use std::sync::atomic::{AtomicBool, Ordering};
 
+use swiftbeaver::checkpoint;
 use swiftbeaver::config;
 use swiftbeaver::evidence::RawFileSource;
 use swiftbeaver::metadata::{self, MetadataBackendKind};
 use swiftbeaver::pipeline;
 use swiftbeaver::scanner;
 use swiftbeaver::util;
+use swiftbeaver::{
+    CarveError, CarveHandler, CarveRegistry, CarvedFile, ExtractionContext, Hit, NormalizedHit,
+    ScanChunk, SignatureScanner,
+};
 
 #[test]
 fn cancel_flag_stops_pipeline_early() {
@@ -69,3 +75,113 @@ fn cancel_flag_stops_pipeline_early() {
     assert_eq!(stats.string_spans, 0);
     assert_eq!(stats.artefacts_extracted, 0);
 }
+
+/// Reports a `spin` hit at every `SPIN`.
+struct SpinScanner;
+
+impl SignatureScanner for SpinScanner {
+    fn scan_chunk(&self, chunk: &ScanChunk, data: &[u8]) -> Vec<Hit> {
+        data.windows(4)
+            .enumerate()
+            .filter(|(_, w)| *w == b"SPIN")
+            .map(|(i, _)| Hit {
+                chunk_id: chunk.id,
+                local_offset: i as u64,
+                pattern_id: "spin".to_string(),
+                file_type_id: "spin".to_string(),
+            })
+            .collect()
+    }
+}
+
+/// Cancels the run, then works until the cancellation reaches it.
+struct SpinHandler(Arc<AtomicBool>);
+
+impl CarveHandler for SpinHandler {
+    fn file_type(&self) -> &str {
+        "spin"
+    }
+
+    fn extension(&self) -> &str {
+        "bin"
+    }
+
+    fn process_hit(
+        &self,
+        _hit: &NormalizedHit,
+        ctx: &ExtractionContext,
+    ) -> Result<Option<CarvedFile>, CarveError> {
+        self.0.store(true, Ordering::Relaxed);
+        loop {
+            ctx.check_cancelled()?;
+            std::thread::yield_now();
+        }
+    }
+}
+
+#[test]
+fn cancel_stops_in_flight_carve_and_checkpoints_its_chunk() {
+    let temp_dir = tempfile::tempdir().expect("tempdir");
+    let input_path = temp_dir.path().join("input.bin");
+    let mut data = vec![0u8; 256];
+    data[100..104].copy_from_slice(b"SPIN");
+    data[200..204].copy_from_slice(b"SPIN");
+    fs::write(&input_path, data).expect("write input");
+
+    let loaded = config::load_config(None).expect("config");
+    let mut cfg = loaded.config;
+    cfg.run_id = "cancel_carve_test".to_string();
+
+    let evidence = RawFileSource::open(&input_path).expect("evidence");
+    let evidence: Arc<dyn swiftbeaver::evidence::EvidenceSource> = Arc::new(evidence);
+    let run_output_dir = temp_dir.path().join("run");
+    fs::create_dir_all(&run_output_dir).expect("output dir");
+    let meta_sink = metadata::build_sink(
+        MetadataBackendKind::Jsonl,
+        &cfg,
+        &cfg.run_id,
+        env!("CARGO_PKG_VERSION"),
+        &loaded.config_hash,
+        &input_path,
+        "",
+        &run_output_dir,
+    )
+    .expect("metadata sink");
+
+    let cancel_flag = Arc::new(AtomicBool::new(false));
+    let registry = CarveRegistry::new(HashMap::from([(
+        "spin".to_string(),
+        Box::new(SpinHandler(cancel_flag.clone())) as Box<dyn CarveHandler>,
+    )]));
+    let checkpoint_path = temp_dir.path().join("checkpoint.json");
+    let stats = pipeline::run_pipeline_with_cancel(
+        &cfg,
+        evidence,
+        Arc::new(SpinScanner),
+        None,
+        meta_sink,
+        &run_output_dir,
+        1,
+        64,
+        0,
+        None,
+        None,
+        Arc::new(registry),
+        cancel_flag,
+        None,
+        Some(pipeline::CheckpointConfig {
+            path: checkpoint_path.clone(),
+            resume: None,
+            input_files: Vec::new(),
+        }),
+    )
+    .expect("pipeline");
+
+    assert_eq!(stats.stop_reason, pipeline::StopReason::Cancelled);
+    assert_eq!(stats.files_carved, 0);
+    assert_eq!(stats.carve_errors, 0);
+    assert!(stats.hits_abandoned >= 1);
+    // The first hit (offset 100) is in the chunk starting at 64.
+    let state = checkpoint::load_checkpoint(&checkpoint_path).expect("checkpoint");
+    assert_eq!(state.next_offset, 64);
+}
@@ -2,7 +2,7 @@ use std::fs;
 use std::sync::Arc;
 
 use swiftbeaver::config;
-use swiftbeaver::evidence::RawFileSource;
+use swiftbeaver::evidence::{MemorySource, RawFileSource};
 use swiftbeaver::metadata::{self, MetadataBackendKind};
 use swiftbeaver::pipeline;
 use swiftbeaver::scanner;
@@ -336,3 +336,733 @@ fn integration_carves_basic_formats() {
     assert!(types.contains(&"rar".to_string()));
     assert!(types.contains(&"7z".to_string()));
 }
+
+#[test]
+fn integration_strings_gaps_only_skips_carved_files() {
+    let temp_dir = tempfile::tempdir().expect("tempdir");
+    let input_path = temp_dir.path().join("image.bin");
+
+    let mut pdf = Vec::new();
+    pdf.extend_from_slice(b"%PDF-1.4\n1 0 obj\n<< /Author (inside@carved.org) >>\nendobj\n");
+    while pdf.len() < 64 {
+        pdf.push(b' ');
+    }
+    pdf.extend_from_slice(b"%%EOF");
+    let mut image = vec![0u8; 128 * 1024];
+    insert_bytes(&mut image, 4_096, &pdf);
+    insert_bytes(&mut image, 20_000, b" outside@slack.org ");
+    fs::write(&input_path, &image).expect("write input");
+
+    let loaded = config::load_config(None).expect("config");
+    let mut cfg = loaded.config;
+    cfg.run_id = "gaps_run".to_string();
+    cfg.enable_string_scan = true;
+    cfg.string_scan_gaps_only = true;
+
+    let evidence = RawFileSource::open(&input_path).expect("evidence");
+    let evidence: Arc<dyn swiftbeaver::evidence::EvidenceSource> = Arc::new(evidence);
+
+    let run_output_dir = temp_dir.path().join("run");
+    fs::create_dir_all(&run_output_dir).expect("output dir");
+
+    let meta_sink = metadata::build_sink(
+        MetadataBackendKind::Jsonl,
+        &cfg,
+        &cfg.run_id,
+        "0.1.0",
+        &loaded.config_hash,
+        &input_path,
+        "",
+        &run_output_dir,
+    )
+    .expect("metadata sink");
+
+    let sig_scanner = scanner::build_signature_scanner(&cfg, false).expect("scanner");
+    let sig_scanner: Arc<dyn swiftbeaver::scanner::SignatureScanner> = Arc::from(sig_scanner);
+    let string_scanner: Arc<dyn swiftbeaver::strings::StringScanner> =
+        Arc::from(swiftbeaver::strings::build_string_scanner(&cfg, false).expect("string scanner"));
+    let carve_registry = Arc::new(util::build_carve_registry(&cfg, false).expect("registry"));
+
+    let stats = pipeline::run_pipeline(
+        &cfg,
+        evidence,
+        sig_scanner,
+        Some(string_scanner),
+        meta_sink,
+        &run_output_dir,
+        2,
+        64 * 1024,
+        64,
+        None,
+        None,
+        carve_registry,
+    )
+    .expect("pipeline");
+    assert!(stats.files_carved >= 1);
+
+    let strings = fs::read_to_string(
+        run_output_dir
+            .join("metadata")
+            .join("string_artefacts.jsonl"),
+    )
+    .expect("string artefacts");
+    assert!(strings.contains("outside@slack.org"));
+    assert!(!strings.contains("inside@carved.org"));
+    assert!(strings.contains(r#""source_kind":"string_span","source_detail":"chunk="#));
+}
+
+#[test]
+fn integration_writes_artefact_frequency_summary() {
+    let temp_dir = tempfile::tempdir().expect("tempdir");
+    let input_path = temp_dir.path().join("image.bin");
+
+    let mut image = vec![0u8; 128 * 1024];
+    insert_bytes(&mut image, 1_000, b" alice@example.com ");
+    insert_bytes(&mut image, 2_000, b" bob@example.com ");
+    insert_bytes(&mut image, 3_000, b" carol@other.org ");
+    insert_bytes(&mut image, 4_000, b" https://www.example.net/login ");
+    insert_bytes(&mut image, 5_000, b" call +44 20 7946 0958 now ");
+    fs::write(&input_path, &image).expect("write input");
+
+    let loaded = config::load_config(None).expect("config");
+    let mut cfg = loaded.config;
+    cfg.run_id = "summary_run".to_string();
+    cfg.enable_string_scan = true;
+
+    let evidence = RawFileSource::open(&input_path).expect("evidence");
+    let evidence: Arc<dyn swiftbeaver::evidence::EvidenceSource> = Arc::new(evidence);
+
+    let run_output_dir = temp_dir.path().join("run");
+    fs::create_dir_all(&run_output_dir).expect("output dir");
+
+    let meta_sink = metadata::build_sink(
+        MetadataBackendKind::Jsonl,
+        &cfg,
+        &cfg.run_id,
+        "0.1.0",
+        &loaded.config_hash,
+        &input_path,
+        "",
+        &run_output_dir,
+    )
+    .expect("metadata sink");
+
+    let sig_scanner = scanner::build_signature_scanner(&cfg, false).expect("scanner");
+    let sig_scanner: Arc<dyn swiftbeaver::scanner::SignatureScanner> = Arc::from(sig_scanner);
+    let string_scanner: Arc<dyn swiftbeaver::strings::StringScanner> =
+        Arc::from(swiftbeaver::strings::build_string_scanner(&cfg, false).expect("string scanner"));
+    let carve_registry = Arc::new(util::build_carve_registry(&cfg, false).expect("registry"));
+
+    pipeline::run_pipeline(
+        &cfg,
+        evidence,
+        sig_scanner,
+        Some(string_scanner),
+        meta_sink,
+        &run_output_dir,
+        2,
+        64 * 1024,
+        64,
+        None,
+        None,
+        carve_registry,
+    )
+    .expect("pipeline");
+
+    let summary_path = run_output_dir
+        .join("summaries")
+        .join("artefact_frequencies.json");
+    let contents = fs::read_to_string(summary_path).expect("summary read");
+    let v: serde_json::Value = serde_json::from_str(&contents).expect("json");
+    assert_eq!(v["run_id"], "summary_run");
+    assert_eq!(v["email_domains"]["top"][0]["value"], "example.com");
+    assert_eq!(v["email_domains"]["top"][0]["count"], 2);
+    assert_eq!(v["email_domains"]["distinct"], 2);
+    assert_eq!(v["url_hosts"]["top"][0]["value"], "www.example.net");
+    assert_eq!(v["phone_country_codes"]["top"][0]["value"], "+44");
+
+    let usage_path = run_output_dir.join("summaries").join("resource_usage.json");
+    let usage: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(usage_path).expect("usage read")).expect("json");
+    let stages = usage["stages"].as_array().expect("stages");
+    assert_eq!(stages.len(), 6);
+    assert_eq!(stages[1]["stage"], "scan");
+    assert_eq!(stages[1]["threads"], 2);
+    assert_eq!(stages[5]["stage"], "process");
+    let rows = fs::read_to_string(run_output_dir.join("metadata").join("resource_usage.jsonl"))
+        .expect("usage rows");
+    assert_eq!(rows.lines().count(), 6);
+}
+
+/// In-memory source that decodes in 3000-byte blocks and counts decodes.
+struct BlockSource {
+    data: Vec<u8>,
+    decodes: Vec<std::sync::atomic::AtomicU64>,
+}
+
+impl swiftbeaver::evidence::EvidenceSource for BlockSource {
+    fn len(&self) -> u64 {
+        self.data.len() as u64
+    }
+
+    fn read_at(
+        &self,
+        offset: u64,
+        buf: &mut [u8],
+    ) -> Result<usize, swiftbeaver::evidence::EvidenceError> {
+        let data = self.data.get(offset as usize..).unwrap_or_default();
+        let n = data.len().min(buf.len());
+        let first = offset / 3000;
+        let last = (offset + n as u64).div_ceil(3000);
+        for block in first..last {
+            self.decodes[block as usize].fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        }
+        buf[..n].copy_from_slice(&data[..n]);
+        Ok(n)
+    }
+
+    fn block_size(&self) -> Option<u64> {
+        Some(3000)
+    }
+}
+
+#[test]
+fn integration_decodes_evidence_blocks_once() {
+    let temp_dir = tempfile::tempdir().expect("tempdir");
+    let mut image = vec![0u8; 300_000];
+    insert_bytes(&mut image, 1024, &sample_jpeg());
+    insert_bytes(&mut image, 65_500, &sample_png());
+    let source = Arc::new(BlockSource {
+        decodes: (0..image.len().div_ceil(3000))
+            .map(|_| Default::default())
+            .collect(),
+        data: image,
+    });
+
+    let loaded = config::load_config(None).expect("config");
+    let mut cfg = loaded.config;
+    cfg.run_id = "block_run".to_string();
+    for ft in cfg.file_types.iter_mut() {
+        if ft.id == "jpeg" || ft.id == "png" {
+            ft.min_size = 16;
+        }
+    }
+    let run_output_dir = temp_dir.path().join("run");
+    fs::create_dir_all(&run_output_dir).expect("output dir");
+    let meta_sink = metadata::build_sink(
+        MetadataBackendKind::Jsonl,
+        &cfg,
+        &cfg.run_id,
+        "0.1.0",
+        &loaded.config_hash,
+        &temp_dir.path().join("image.bin"),
+        "",
+        &run_output_dir,
+    )
+    .expect("metadata sink");
+    let sig_scanner = scanner::build_signature_scanner(&cfg, false).expect("scanner");
+    let carve_registry = Arc::new(util::build_carve_registry(&cfg, false).expect("registry"));
+
+    let stats = pipeline::run_pipeline(
+        &cfg,
+        source.clone(),
+        Arc::from(sig_scanner),
+        None,
+        meta_sink,
+        &run_output_dir,
+        2,
+        64 * 1024,
+        64,
+        None,
+        None,
+        carve_registry,
+    )
+    .expect("pipeline");
+
+    assert_eq!(stats.files_carved, 2);
+    // 64 KiB chunks are rounded up to 66000 bytes (22 blocks); the cache
+    // serves chunk overlaps and carve reads without decoding again.
+    assert_eq!(stats.chunks_processed, 5);
+    for (block, decodes) in source.decodes.iter().enumerate() {
+        assert_eq!(
+            decodes.load(std::sync::atomic::Ordering::Relaxed),
+            1,
+            "block {block}"
+        );
+    }
+}
+
+/// Carves and string artefacts of one run, as JSONL lines.
+fn run_with_mode(mode: config::PipelineMode, workers: usize) -> (Vec<String>, Vec<String>) {
+    let temp_dir = tempfile::tempdir().expect("tempdir");
+    let input_path = temp_dir.path().join("image.bin");
+
+    let mut image = vec![0u8; 400_000];
+    insert_bytes(&mut image, 1024, &sample_jpeg());
+    insert_bytes(&mut image, 65_530, &sample_png());
+    insert_bytes(&mut image, 131_072, &sample_gif());
+    insert_bytes(&mut image, 200_000, &sample_pdf());
+    insert_bytes(&mut image, 220_000, &sample_docx_zip());
+    insert_bytes(&mut image, 320_000, &sample_bmp());
+    insert_bytes(&mut image, 90_000, b" alice@example.com ");
+    insert_bytes(&mut image, 300_000, b" https://example.org/a ");
+    fs::write(&input_path, &image).expect("write input");
+
+    let loaded = config::load_config(None).expect("config");
+    let mut cfg = loaded.config;
+    cfg.run_id = "mode_run".to_string();
+    cfg.enable_string_scan = true;
+    cfg.pipeline_mode = mode;
+    for ft in cfg.file_types.iter_mut() {
+        if ft.id == "jpeg" || ft.id == "gif" || ft.id == "png" || ft.id == "bmp" {
+            ft.min_size = 16;
+        }
+    }
+
+    // Single-threaded runs read the image from memory, as an embedding
+    // application without a file system would.
+    let evidence: Arc<dyn swiftbeaver::evidence::EvidenceSource> = match mode {
+        config::PipelineMode::SingleThreaded => Arc::new(MemorySource::new(image)),
+        config::PipelineMode::Threaded => {
+            Arc::new(RawFileSource::open(&input_path).expect("evidence"))
+        }
+    };
+    let run_output_dir = temp_dir.path().join("run");
+    fs::create_dir_all(&run_output_dir).expect("output dir");
+    let meta_sink = metadata::build_sink(
+        MetadataBackendKind::Jsonl,
+        &cfg,
+        &cfg.run_id,
+        "0.1.0",
+        &loaded.config_hash,
+        &input_path,
+        "",
+        &run_output_dir,
+    )
+    .expect("metadata sink");
+    let sig_scanner = scanner::build_signature_scanner(&cfg, false).expect("scanner");
+    let sig_scanner: Arc<dyn swiftbeaver::scanner::SignatureScanner> = Arc::from(sig_scanner);
+    let string_scanner: Arc<dyn swiftbeaver::strings::StringScanner> =
+        Arc::from(swiftbeaver::strings::build_string_scanner(&cfg, false).expect("string scanner"));
+    let carve_registry = Arc::new(util::build_carve_registry(&cfg, false).expect("registry"));
+
+    pipeline::run_pipeline(
+        &cfg,
+        evidence,
+        sig_scanner,
+        Some(string_scanner),
+        meta_sink,
+        &run_output_dir,
+        workers,
+        64 * 1024,
+        64,
+        None,
+        None,
+        carve_registry,
+    )
+    .expect("pipeline");
+
+    // The evidence path differs per temp dir; everything else must match.
+    let lines = |name: &str| -> Vec<String> {
+        fs::read_to_string(run_output_dir.join("metadata").join(name))
+            .expect("metadata read")
+            .lines()
+            .map(|line| {
+                let mut v: serde_json::Value = serde_json::from_str(line).expect("json");
+                v.as_object_mut().expect("object").remove("evidence_path");
+                v.to_string()
+            })
+            .collect()
+    };
+    (lines("carved_files.jsonl"), lines("string_artefacts.jsonl"))
+}
+
+#[test]
+fn integration_single_threaded_mode_is_deterministic() {
+    let (files, artefacts) = run_with_mode(config::PipelineMode::SingleThreaded, 8);
+    assert!(files.len() >= 5, "{files:?}");
+    assert!(
+        artefacts
+            .iter()
+            .any(|line| line.contains("alice@example.com"))
+    );
+
+    // Same records in the same order on every run.
+    let again = run_with_mode(config::PipelineMode::SingleThreaded, 8);
+    assert_eq!((files.clone(), artefacts.clone()), again);
+
+    // Same records as the threaded pipeline, in offset order.
+    let starts: Vec<u64> = files
+        .iter()
+        .map(|line| {
+            let v: serde_json::Value = serde_json::from_str(line).expect("json");
+            v["global_start"].as_u64().expect("global_start")
+        })
+        .collect();
+    assert!(starts.is_sorted(), "{starts:?}");
+    let (mut threaded, mut threaded_artefacts) = run_with_mode(config::PipelineMode::Threaded, 4);
+    let (mut files, mut artefacts) = (files, artefacts);
+    for records in [
+        &mut files,
+        &mut artefacts,
+        &mut threaded,
+        &mut threaded_artefacts,
+    ] {
+        records.sort();
+    }
+    assert_eq!(files, threaded);
+    assert_eq!(artefacts, threaded_artefacts);
+}
+
+#[test]
+fn integration_carves_byte_swapped_image() {
+    let temp_dir = tempfile::tempdir().expect("tempdir");
+    let input_path = temp_dir.path().join("image.bin");
+
+    let mut image = vec![0u8; 400_000];
+    insert_bytes(&mut image, 1024, &sample_jpeg());
+    insert_bytes(&mut image, 65_536, &sample_png());
+    insert_bytes(&mut image, 131_072, &sample_gif());
+    insert_bytes(&mut image, 200_000, &sample_pdf());
+    insert_bytes(&mut image, 220_000, &sample_docx_zip());
+    insert_bytes(&mut image, 320_000, &sample_bmp());
+    // Stored as a wrong-endian imager would have written it.
+    swiftbeaver::evidence::ByteTransform::ByteSwap16.apply(&mut image);
+    fs::write(&input_path, &image).expect("write input");
+
+    let loaded = config::load_config(None).expect("config");
+    let mut cfg = loaded.config;
+    cfg.run_id = "swap_run".to_string();
+    cfg.evidence_transform = config::EvidenceTransform::Auto;
+    cfg.evidence_hashes = vec![config::EvidenceDigest::Md5];
+    for ft in cfg.file_types.iter_mut() {
+        if ft.id == "jpeg" || ft.id == "gif" || ft.id == "png" || ft.id == "bmp" {
+            ft.min_size = 16;
+        }
+    }
+
+    let evidence = RawFileSource::open(&input_path).expect("evidence");
+    let evidence: Arc<dyn swiftbeaver::evidence::EvidenceSource> = Arc::new(evidence);
+    let acquired_md5 =
+        swiftbeaver::evidence::compute_md5(evidence.as_ref(), 64 * 1024).expect("md5");
+    let run_output_dir = temp_dir.path().join("run");
+    fs::create_dir_all(&run_output_dir).expect("output dir");
+    let meta_sink = metadata::build_sink(
+        MetadataBackendKind::Jsonl,
+        &cfg,
+        &cfg.run_id,
+        "0.1.0",
+        &loaded.config_hash,
+        &input_path,
+        "",
+        &run_output_dir,
+    )
+    .expect("metadata sink");
+    let sig_scanner = scanner::build_signature_scanner(&cfg, false).expect("scanner");
+    let sig_scanner: Arc<dyn swiftbeaver::scanner::SignatureScanner> = Arc::from(sig_scanner);
+    let carve_registry = Arc::new(util::build_carve_registry(&cfg, false).expect("registry"));
+
+    let stats = pipeline::run_pipeline(
+        &cfg,
+        evidence,
+        sig_scanner,
+        None,
+        meta_sink,
+        &run_output_dir,
+        2,
+        64 * 1024,
+        64,
+        None,
+        None,
+        carve_registry,
+    )
+    .expect("pipeline");
+
+    let jpeg = run_output_dir.join("carved/jpeg/jpeg_000000000400.jpg");
+    assert_eq!(fs::read(jpeg).expect("jpeg"), sample_jpeg());
+    assert!(stats.files_carved >= 5, "{}", stats.files_carved);
+
+    // Hashes cover the image as acquired, not as carved.
+    let hashes = stats.evidence_hashes.expect("hashes");
+    assert_eq!(hashes.md5.as_deref(), Some(acquired_md5.as_str()));
+
+    let summary: serde_json::Value = serde_json::from_str(
+        &fs::read_to_string(run_output_dir.join("summaries/evidence_transform.json"))
+            .expect("transform summary"),
+    )
+    .expect("json");
+    assert_eq!(summary["mode"], "auto");
+    assert_eq!(summary["applied"], "byte_swap16");
+    assert_eq!(summary["candidates"][0]["transform"], "none");
+}
+
+#[test]
+fn integration_prefilter_drops_hits_with_bad_first_checksum() {
+    let temp_dir = tempfile::tempdir().expect("tempdir");
+    let input_path = temp_dir.path().join("image.bin");
+
+    let mut good_png = sample_png();
+    // IHDR CRC of a 1x1 RGB image; `sample_png` leaves it zeroed.
+    good_png[29..33].copy_from_slice(&[0x90, 0x77, 0x53, 0xDE]);
+    let mut image = vec![0u8; 200_000];
+    insert_bytes(&mut image, 4096, &good_png);
+    insert_bytes(&mut image, 65_536, &sample_png());
+    insert_bytes(&mut image, 131_072, &sample_jpeg());
+    fs::write(&input_path, &image).expect("write input");
+
+    let loaded = config::load_config(None).expect("config");
+    let mut cfg = loaded.config;
+    cfg.run_id = "prefilter_run".to_string();
+    cfg.hit_prefilter = true;
+    for ft in cfg.file_types.iter_mut() {
+        if ft.id == "jpeg" || ft.id == "png" {
+            ft.min_size = 16;
+        }
+    }
+
+    let evidence = RawFileSource::open(&input_path).expect("evidence");
+    let evidence: Arc<dyn swiftbeaver::evidence::EvidenceSource> = Arc::new(evidence);
+    let run_output_dir = temp_dir.path().join("run");
+    fs::create_dir_all(&run_output_dir).expect("output dir");
+    let meta_sink = metadata::build_sink(
+        MetadataBackendKind::Jsonl,
+        &cfg,
+        &cfg.run_id,
+        "0.1.0",
+        &loaded.config_hash,
+        &input_path,
+        "",
+        &run_output_dir,
+    )
+    .expect("metadata sink");
+    let sig_scanner = scanner::build_signature_scanner(&cfg, false).expect("scanner");
+    let sig_scanner: Arc<dyn swiftbeaver::scanner::SignatureScanner> = Arc::from(sig_scanner);
+    let carve_registry = Arc::new(util::build_carve_registry(&cfg, false).expect("registry"));
+
+    let stats = pipeline::run_pipeline(
+        &cfg,
+        evidence,
+        sig_scanner,
+        None,
+        meta_sink,
+        &run_output_dir,
+        2,
+        64 * 1024,
+        64,
+        None,
+        None,
+        carve_registry,
+    )
+    .expect("pipeline");
+
+    assert_eq!(stats.hits_prefiltered, 1);
+    assert!(
+        run_output_dir
+            .join("carved/png/png_000000001000.png")
+            .exists()
+    );
+    assert!(
+        !run_output_dir
+            .join("carved/png/png_000000010000.png")
+            .exists()
+    );
+    // Types without a check are carved as before.
+    assert!(
+        run_output_dir
+            .join("carved/jpeg/jpeg_000000020000.jpg")
+            .exists()
+    );
+
+    let summary: serde_json::Value = serde_json::from_str(
+        &fs::read_to_string(run_output_dir.join("summaries/hit_prefilter.json"))
+            .expect("prefilter summary"),
+    )
+    .expect("json");
+    assert_eq!(summary["total_checked"], 2);
+    assert_eq!(summary["total_rejected"], 1);
+    assert_eq!(summary["types"][0]["file_type"], "png");
+    assert_eq!(summary["types"][0]["check"], "png_ihdr_crc");
+    assert_eq!(summary["types"][0]["rejection_ratio"], 0.5);
+}
+
+/// Hands out at most 5000 bytes per read, like a pipe fed by an imager.
+struct TrickleReader(std::io::Cursor<Vec<u8>>);
+
+impl std::io::Read for TrickleReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = buf.len().min(5000);
+        self.0.read(&mut buf[..n])
+    }
+}
+
+#[test]
+fn integration_carves_streamed_input() {
+    use swiftbeaver::evidence::{EvidenceSource, StreamSource};
+
+    let temp_dir = tempfile::tempdir().expect("tempdir");
+    let mut image = vec![0u8; 600_000];
+    insert_bytes(&mut image, 1024, &sample_jpeg());
+    insert_bytes(&mut image, 450_000, &sample_png());
+
+    let loaded = config::load_config(None).expect("config");
+    let mut cfg = loaded.config;
+    cfg.run_id = "stream_run".to_string();
+    cfg.evidence_hashes = vec![config::EvidenceDigest::Md5];
+    for ft in cfg.file_types.iter_mut() {
+        if ft.id == "jpeg" || ft.id == "png" {
+            ft.min_size = 16;
+        }
+    }
+    let run_output_dir = temp_dir.path().join("run");
+    fs::create_dir_all(&run_output_dir).expect("output dir");
+    let run = |cfg: &config::Config| {
+        let source = StreamSource::new(
+            Box::new(TrickleReader(std::io::Cursor::new(image.clone()))),
+            256 * 1024,
+        );
+        assert_eq!(source.stream_window(), Some(256 * 1024));
+        let meta_sink = metadata::build_sink(
+            MetadataBackendKind::Jsonl,
+            cfg,
+            &cfg.run_id,
+            "0.1.0",
+            &loaded.config_hash,
+            std::path::Path::new("-"),
+            "",
+            &run_output_dir,
+        )
+        .expect("metadata sink");
+        pipeline::run_pipeline(
+            cfg,
+            Arc::new(source),
+            Arc::from(scanner::build_signature_scanner(cfg, false).expect("scanner")),
+            None,
+            meta_sink,
+            &run_output_dir,
+            2,
+            64 * 1024,
+            64,
+            None,
+            None,
+            Arc::new(util::build_carve_registry(cfg, false).expect("registry")),
+        )
+    };
+
+    let stats = run(&cfg).expect("pipeline");
+    assert_eq!(stats.files_carved, 2);
+    assert_eq!(stats.chunks_processed, 10);
+    let md5 = stats.evidence_hashes.and_then(|hashes| hashes.md5);
+    assert_eq!(md5, Some(format!("{:x}", md5::compute(&image))));
+
+    cfg.enable_vss_scan = true;
+    let err = run(&cfg).expect_err("shadow copies need random access");
+    assert!(format!("{err:#}").contains("enable_vss_scan"), "{err:#}");
+}
+
+#[test]
+fn integration_encrypted_run_writes_no_plaintext() {
+    use age::secrecy::ExposeSecret;
+    use swiftbeaver::encryption::{self, Identities};
+
+    let shm = std::path::Path::new(encryption::DEFAULT_STAGING_DIR);
+    if !encryption::is_memory_backed(shm).unwrap_or(false) {
+        eprintln!("Skipping: /dev/shm is not a memory filesystem");
+        return;
+    }
+    let temp_dir = tempfile::tempdir().expect("tempdir");
+    let input_path = temp_dir.path().join("image.bin");
+    let mut image = vec![0u8; 200_000];
+    insert_bytes(&mut image, 1024, &sample_jpeg());
+    insert_bytes(&mut image, 65_536, &sample_png());
+    fs::write(&input_path, &image).expect("write input");
+
+    let key = age::x25519::Identity::generate();
+    let loaded = config::load_config(None).expect("config");
+    let mut cfg = loaded.config;
+    cfg.run_id = "encrypted_run".to_string();
+    cfg.encryption_recipient = Some(key.to_public().to_string());
+    for ft in cfg.file_types.iter_mut() {
+        if ft.id == "jpeg" || ft.id == "png" {
+            ft.min_size = 16;
+        }
+    }
+
+    let run = |cfg: &config::Config, run_output_dir: &std::path::Path| {
+        fs::create_dir_all(run_output_dir).expect("output dir");
+        let evidence = RawFileSource::open(&input_path).expect("evidence");
+        let meta_sink = metadata::build_sink(
+            MetadataBackendKind::Jsonl,
+            cfg,
+            &cfg.run_id,
+            "0.1.0",
+            &loaded.config_hash,
+            &input_path,
+            "",
+            run_output_dir,
+        )
+        .expect("metadata sink");
+        let sig_scanner = scanner::build_signature_scanner(cfg, false).expect("scanner");
+        pipeline::run_pipeline(
+            cfg,
+            Arc::new(evidence),
+            Arc::from(sig_scanner),
+            None,
+            meta_sink,
+            run_output_dir,
+            2,
+            64 * 1024,
+            64,
+            None,
+            None,
+            Arc::new(util::build_carve_registry(cfg, false).expect("registry")),
+        )
+    };
+
+    let run_output_dir = temp_dir.path().join("run");
+    run(&cfg, &run_output_dir).expect("pipeline");
+    let mut pending = vec![run_output_dir.clone()];
+    let mut files = Vec::new();
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(&dir).expect("list").flatten() {
+            if entry.file_type().expect("type").is_dir() {
+                pending.push(entry.path());
+            } else {
+                files.push(entry.path());
+            }
+        }
+    }
+    assert!(
+        files
+            .iter()
+            .all(|path| path.extension().is_some_and(|ext| ext == "age")),
+        "plaintext in the run directory: {files:?}"
+    );
+    let staging = shm.join(format!(
+        "{}{}",
+        encryption::STAGING_PREFIX,
+        std::process::id()
+    ));
+    assert!(!staging.exists());
+
+    let key_path = temp_dir.path().join("key.txt");
+    fs::write(&key_path, key.to_string().expose_secret()).expect("key");
+    let plain = temp_dir.path().join("plain");
+    Identities::from_file(&key_path)
+        .expect("identity")
+        .decrypt_tree(&run_output_dir, &plain)
+        .expect("decrypt");
+    let jpegs: Vec<_> = fs::read_dir(plain.join("carved/jpeg"))
+        .expect("jpeg dir")
+        .flatten()
+        .collect();
+    assert_eq!(jpegs.len(), 1);
+    assert_eq!(fs::read(jpegs[0].path()).expect("jpeg"), sample_jpeg());
+
+    // A staging directory on a disk is refused before anything is carved.
+    if !encryption::is_memory_backed(temp_dir.path()).unwrap_or(true) {
+        cfg.encryption_staging_dir = Some(temp_dir.path().to_path_buf());
+        let refused = temp_dir.path().join("refused");
+        assert!(run(&cfg, &refused).is_err());
+        assert!(!refused.join("carved").exists());
+    }
+}
@@ -35,6 +35,7 @@ fn parquet_writes_expected_files() {
     let file = CarvedFile {
         run_id: "run_001".to_string(),
         file_type: "jpeg".to_string(),
+        signature_type: "jpeg".to_string(),
         path: "carved/jpeg_00000001.jpg".to_string(),
         extension: "jpg".to_string(),
         global_start: 10,
@@ -56,6 +57,8 @@ fn parquet_writes_expected_files() {
         encoding: "ascii".to_string(),
         global_start: 100,
         global_end: 123,
+        source_kind: "string_span".to_string(),
+        source_detail: "chunk=0 span=90-140".to_string(),
     };
     sink.record_string(&artefact).expect("record url");
 
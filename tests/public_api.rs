@@ -0,0 +1,183 @@
+//! Guards the stable API: every extension trait is implemented here using only
+//! the crate-root re-exports, and the implementations are driven through the
+//! pipeline. A change that breaks this file needs a major version bump.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use swiftbeaver::config;
+use swiftbeaver::pipeline;
+use swiftbeaver::{
+    CarveError, CarveHandler, CarveRegistry, CarvedFile, EvidenceError, EvidenceSource,
+    ExtractionContext, Hit, MetadataError, MetadataSink, NormalizedHit, Rejection, RunSummary,
+    ScanChunk, SignatureScanner, StringScanner, StringSpan, note_rejection,
+};
+
+const MAGIC: &[u8] = b"REC!";
+const RECORD_LEN: u64 = 16;
+
+struct MemorySource(Vec<u8>);
+
+impl EvidenceSource for MemorySource {
+    fn len(&self) -> u64 {
+        self.0.len() as u64
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize, EvidenceError> {
+        let data = self.0.get(offset as usize..).unwrap_or_default();
+        let n = data.len().min(buf.len());
+        buf[..n].copy_from_slice(&data[..n]);
+        Ok(n)
+    }
+}
+
+struct MagicScanner;
+
+impl SignatureScanner for MagicScanner {
+    fn scan_chunk(&self, chunk: &ScanChunk, data: &[u8]) -> Vec<Hit> {
+        data.windows(MAGIC.len())
+            .enumerate()
+            .filter(|(_, window)| *window == MAGIC)
+            .map(|(i, _)| Hit {
+                chunk_id: chunk.id,
+                local_offset: i as u64,
+                pattern_id: "rec_magic".to_string(),
+                file_type_id: "rec".to_string(),
+            })
+            .collect()
+    }
+}
+
+struct NoStrings;
+
+impl StringScanner for NoStrings {
+    fn scan_chunk(&self, _chunk: &ScanChunk, _data: &[u8]) -> Vec<StringSpan> {
+        Vec::new()
+    }
+}
+
+/// Carves 16-byte records whose last byte is not zero.
+struct RecordHandler;
+
+impl CarveHandler for RecordHandler {
+    fn file_type(&self) -> &str {
+        "rec"
+    }
+
+    fn extension(&self) -> &str {
+        "rec"
+    }
+
+    fn process_hit(
+        &self,
+        hit: &NormalizedHit,
+        ctx: &ExtractionContext,
+    ) -> Result<Option<CarvedFile>, CarveError> {
+        let mut record = [0u8; RECORD_LEN as usize];
+        let n = ctx
+            .evidence
+            .read_at(hit.global_offset, &mut record)
+            .map_err(|err| CarveError::Evidence(err.to_string()))?;
+        if n < record.len() || record[record.len() - 1] == 0 {
+            note_rejection(Rejection::Invalid("unterminated record"));
+            return Ok(None);
+        }
+        let path = format!("rec/rec_{:012X}.rec", hit.global_offset);
+        std::fs::create_dir_all(ctx.output_root.join("rec"))?;
+        std::fs::write(ctx.output_root.join(&path), record)?;
+        Ok(Some(CarvedFile {
+            run_id: ctx.run_id.to_string(),
+            file_type: self.file_type().to_string(),
+            signature_type: self.file_type().to_string(),
+            path,
+            extension: self.extension().to_string(),
+            global_start: hit.global_offset,
+            global_end: hit.global_offset + RECORD_LEN - 1,
+            size: RECORD_LEN,
+            md5: None,
+            sha256: None,
+            validated: true,
+            truncated: false,
+            errors: Vec::new(),
+            pattern_id: Some(hit.pattern_id.clone()),
+        }))
+    }
+}
+
+/// Implements only the required methods; artefact streams use the defaults.
+#[derive(Default)]
+struct CollectingSink {
+    files: Arc<Mutex<Vec<CarvedFile>>>,
+    summaries: Arc<Mutex<Vec<RunSummary>>>,
+}
+
+impl MetadataSink for CollectingSink {
+    fn record_file(&self, file: &CarvedFile) -> Result<(), MetadataError> {
+        self.files.lock().unwrap().push(file.clone());
+        Ok(())
+    }
+
+    fn record_run_summary(&self, summary: &RunSummary) -> Result<(), MetadataError> {
+        self.summaries.lock().unwrap().push(summary.clone());
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<(), MetadataError> {
+        Ok(())
+    }
+}
+
+#[test]
+fn custom_trait_implementations_drive_the_pipeline() {
+    let mut data = vec![0u8; 256 * 1024];
+    let mut record = MAGIC.to_vec();
+    record.resize(RECORD_LEN as usize, 0xAA);
+    for offset in [0x1000usize, 0x20010] {
+        data[offset..offset + record.len()].copy_from_slice(&record);
+    }
+    // Zero-terminated, so the handler rejects it.
+    data[0x30000..0x30000 + MAGIC.len()].copy_from_slice(MAGIC);
+
+    let temp_dir = tempfile::tempdir().expect("tempdir");
+    let loaded = config::load_config(None).expect("config");
+    let sink = CollectingSink::default();
+    let files = sink.files.clone();
+    let summaries = sink.summaries.clone();
+
+    let mut handlers: HashMap<String, Box<dyn CarveHandler>> = HashMap::new();
+    handlers.insert("rec".to_string(), Box::new(RecordHandler));
+
+    let stats = pipeline::run_pipeline(
+        &loaded.config,
+        Arc::new(MemorySource(data)),
+        Arc::new(MagicScanner),
+        Some(Arc::new(NoStrings)),
+        Box::new(sink),
+        temp_dir.path(),
+        2,
+        64 * 1024,
+        64,
+        None,
+        None,
+        Arc::new(CarveRegistry::new(handlers)),
+    )
+    .expect("pipeline");
+
+    assert_eq!(stats.hits_found, 3);
+    assert_eq!(stats.files_carved, 2);
+    let mut starts: Vec<u64> = files
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|file| file.global_start)
+        .collect();
+    starts.sort_unstable();
+    assert_eq!(starts, vec![0x1000, 0x20010]);
+    assert!(
+        temp_dir
+            .path()
+            .join("carved/rec/rec_000000001000.rec")
+            .exists()
+    );
+    assert_eq!(summaries.lock().unwrap()[0].files_carved, 2);
+}